@@ -18,6 +18,19 @@ struct ObjectPopularity {
   3: i64 threshold;
 } (rust.exhaustive)
 
+struct CdnConfig {
+  // The base URL of the CDN. Download URLs handed out to clients are built by
+  // appending the path of the download on this server to it.
+  1: string base_url;
+  // The key shared with the CDN that download URLs are signed with.
+  2: string signing_key;
+  // How long (in seconds) signed URLs remain valid.
+  3: i32 url_ttl_secs;
+  // A URL on the CDN that returns a successful status while the CDN is able
+  // to serve downloads. If it fails, downloads are served directly.
+  4: optional string health_check_url;
+} (rust.exhaustive)
+
 struct LfsServerConfig {
   // Whether or not to increment counters when sending bytes as opposed to when
   // accepting an upload.
@@ -62,4 +75,7 @@ struct LfsServerConfig {
 
   // Load shedding config
   16: list<ratelimits.LoadShedLimit> loadshedding_limits;
+
+  // Redirect downloads to a CDN using signed URLs.
+  17: optional CdnConfig cdn;
 } (rust.exhaustive)
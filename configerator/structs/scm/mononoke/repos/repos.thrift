@@ -48,6 +48,11 @@ struct RawRepoConfigs {
   4: map<string, RawStorageConfig> (rust.type = "HashMap") storage; # to be renamed to storage_configs
   6: map<string, RawAclRegionConfig> (rust.type = "HashMap") acl_region_configs;
   5: RawRepoDefinitions repo_definitions;
+  // The version of the config schema the configs are written against. Unset
+  // means the current version. Config files written against older versions
+  // are migrated to the current version as they are loaded, but configs from
+  // configerator must be written against the current version.
+  7: optional i64 config_schema_version;
 } (rust.exhaustive)
 
 struct RawRepoDefinitions {
@@ -94,6 +99,13 @@ struct RawRepoDefinition {
   // Default hashing scheme used for revisions given by clients
   // when they interact with the repo without specifying this explicitly.
   12: optional RawCommitIdentityScheme default_commit_identity_scheme;
+
+  // Details shown to users when the repo is read-only: why, who made it
+  // read-only, and when it is expected to accept writes again (seconds
+  // since the Unix epoch).
+  13: optional string readonly_reason;
+  14: optional string readonly_locked_by;
+  15: optional i64 readonly_expected_unlock;
 } (rust.exhaustive)
 
 // The schemes by which commits can be identified.
@@ -192,6 +204,55 @@ struct RawRepoConfig {
   // deep-sharded: In addition to requests, repo is also sharded, i.e. present
   // on select servers.
   54: optional RawShardingModeConfig deep_sharding_config;
+  // Retry and timeout policy for calls to the repo's blobstore
+  55: optional RawRetryConfig blobstore_retry_config;
+  // Keys trusted to sign commits in this repo
+  56: optional RawCommitSigningConfig commit_signing_config;
+  // Bookmarks whose ancestors are public.  If unset, all publishing
+  // bookmarks make their ancestors public.
+  57: optional list<string> publishing_bookmarks;
+  // Timeouts of wireproto commands, keyed by command name, as durations
+  // like "600s" or "30m".  The "default" key sets the timeout of commands
+  // that aren't listed, except those with their own built-in timeout
+  // (getbundle, getpackv1, getpackv2 and stream_out_shallow).
+  58: optional map<string, string> wireproto_timeouts;
+  // Feature flags, keyed by feature name.
+  59: optional map<string, RawFeatureFlag> features;
+  // Capabilities advertised to wireproto clients
+  60: optional RawWireprotoCapabilitiesConfig wireproto_capabilities;
+  // Minimum versions of clients allowed to connect
+  61: optional RawClientVersionConfig client_versions;
+  // If set, this repo is a read-only mirror of a repo on another Mononoke
+  62: optional RawMirrorConfig mirror_config;
+  // Path policies that the whole working copy is checked against by the
+  // path lint job
+  63: optional RawPathLintConfig path_lint_config;
+  // Pushes to review bookmarks create code reviews
+  64: optional RawPushToReviewConfig push_to_review_config;
+  // Webhooks that repo events are posted to
+  65: optional list<RawWebhookConfig> webhooks;
+  // Who may create tags
+  66: optional RawTagConfig tag_config;
+  // Service level objectives that requests to the repo are tracked against
+  67: optional RawSloConfig slo_config;
+  // Recurring windows during which heavy background jobs pause, or writes
+  // are rejected
+  68: optional list<RawMaintenanceWindow> maintenance_windows;
+  // How hg filenodes are generated and served
+  69: optional RawFilenodesConfig filenodes_config;
+  // Virtual repos that share this repo's storage, but only serve some of its
+  // bookmarks and the commits reachable from them
+  70: optional list<RawRepoView> views;
+  // Continuously export a copy of this repo that only contains some of its
+  // paths to another repo
+  71: optional RawSparseExportConfig sparse_export_config;
+} (rust.exhaustive)
+
+// A feature flag is either enabled or disabled for all requests, or rolled
+// out to a percentage of them.  Exactly one of the fields must be set.
+struct RawFeatureFlag {
+  1: optional bool enabled;
+  2: optional i32 rollout_percentage;
 } (rust.exhaustive)
 
 // Config determining if deep sharding mode is enabled for a service.
@@ -299,6 +360,7 @@ struct RawDerivedDataTypesConfig {
   4: optional bool hg_set_committer_extra;
   5: optional i16 blame_version;
 // 7. deleted
+  8: optional i64 unode_shard_threshold;
 } (rust.exhaustive)
 
 struct RawBlobstoreDisabled {} (rust.exhaustive)
@@ -401,6 +463,9 @@ struct RawBlobstoreIdConfig {
   1: i64 blobstore_id;
   2: RawBlobstoreConfig blobstore;
   3: optional RawMultiplexedStoreType store_type;
+  // The region the blobstore is in. Hosts read from the blobstores in their
+  // own region first.
+  4: optional string region;
 } (rust.exhaustive)
 
 struct RawDbLocal {
@@ -438,6 +503,17 @@ struct RawRemoteMetadataConfig {
   2: RawDbShardableRemote filenodes;
   3: RawDbRemote mutation;
   4: RawDbRemote sparse_profiles;
+  5: optional RawReadConsistencyParams read_consistency;
+} (rust.exhaustive)
+
+// Thresholds for routing reads that would normally go to replicas to the
+// primary database instead.
+struct RawReadConsistencyParams {
+  // Reads by a session within this time of its last write go to the
+  // primary, or for as long as the replicas are lagging, if longer.
+  1: optional i64 read_your_writes_window_ms;
+  // All reads go to the primary while the replicas lag by more than this.
+  2: optional i64 max_replica_lag_ms;
 } (rust.exhaustive)
 
 union RawMetadataConfig {
@@ -513,6 +589,9 @@ struct RawPushrebaseParams {
   // If assigning globalrevs on a large repo, only do it if the
   // small repo is pushredirected.
   14: optional i32 globalrevs_small_repo_id;
+  // Merge conflicting changes to the same text file during pushrebase, if
+  // they change different lines.
+  15: optional bool merge_non_overlapping_changes;
 } (rust.exhaustive)
 
 struct RawBookmarkConfig {
@@ -574,8 +653,65 @@ struct RawBookmarkConfig {
   // because commit is already public, meaning that hooks already
   // should have been run when the commit was first made public.
   11: optional bool allow_move_to_public_commits_without_hooks;
+
+  // Only allow this bookmark to be moved to commits that have a valid
+  // signature from one of the repo's trusted keys.  This also means it is
+  // not possible to pushrebase onto this bookmark, as pushrebase creates
+  // new, unsigned, commits.
+  12: optional bool require_signed_commits;
+
+  // Only allow this bookmark to be moved by pushrebase.  Plain pushes and
+  // other direct moves of the bookmark, including creating it, are
+  // rejected unless the caller is allowed to override this restriction.
+  13: optional bool pushrebase_only;
+
+  // If specified, members of any of these groups will also be allowed to
+  // move this bookmark.  Combined with allowed_users and
+  // allowed_hipster_group: a user that satisfies any of them is allowed.
+  14: optional list<string> allowed_groups;
+
+  // If specified, callers with any of these service identities will also be
+  // allowed to move this bookmark.
+  15: optional list<RawAllowlistIdentity> allowed_service_identities;
+
+  // Only allow this bookmark to be moved to commits for which these derived
+  // data types have been derived, so that clients can always fetch the
+  // commit the bookmark points to.  Pushrebases derive them for the rebased
+  // commits within the budget below.  Supported types are changeset_info,
+  // fsnodes, hgchangesets, skeleton_manifests and unodes.
+  16: optional list<string> required_derived_data;
+
+  // If the required derived data isn't derived yet, derive it as part of
+  // the bookmark move, provided this takes less than this many
+  // milliseconds.  If unset, the derived data must already be derived.
+  17: optional i64 required_derived_data_budget_ms;
+
+  // Conditions that direct moves of this bookmark, including creating it,
+  // must satisfy.  Bookmarks with a status check precondition can't be
+  // pushrebased onto, as pushrebase creates new commits that haven't been
+  // checked.
+  18: optional list<RawBookmarkPrecondition> preconditions;
+} (rust.exhaustive)
+
+struct RawDescendsFromOldPrecondition {} (rust.exhaustive)
+
+struct RawStatusCheckPrecondition {
+  // URL that the repo, bookmark and new target are posted to as JSON.  The
+  // endpoint responds with a JSON object whose "status" field is "green" if
+  // the bookmark may be moved to the target.
+  1: string url;
+  // How long to wait for the endpoint to respond, in milliseconds.  Defaults
+  // to 10 seconds.
+  2: optional i64 timeout_ms;
 } (rust.exhaustive)
 
+union RawBookmarkPrecondition {
+  // The new target of the bookmark must descend from its old target
+  1: RawDescendsFromOldPrecondition descends_from_old;
+  // An external status check must report the new target as green
+  2: RawStatusCheckPrecondition status_check;
+}
+
 struct RawAllowlistIdentity {
   1: string identity_type;
   2: string identity_data;
@@ -626,6 +762,62 @@ struct RawCommonConfig {
   // Identity for internal Mononoke services. Requests from these services
   // can be trusted to not have been done directly by users.
   10: RawAllowlistIdentity internal_identity;
+
+  // List of identities that are permitted to perform administrative
+  // mutations (moving bookmarks, locking repos, triggering derivation)
+  // through the control API.  Every such mutation is recorded in the
+  // admin audit log.
+  11: optional list<RawAllowlistIdentity> admin_allowlist;
+
+  // Additional ways of authenticating clients, beyond the subject name of
+  // their TLS client certificate.
+  12: optional RawAuthenticationConfig authentication_config;
+
+  // Permissions granted on all repos to identities or tiers, in addition
+  // to those granted by each repo's ACL.
+  13: optional list<RawAuthorizationRule> authorization_rules;
+} (rust.exhaustive)
+
+// Grants permissions to a single identity, or to the identities that a
+// tier's ACL allows to perform the action named after each permission.
+// Exactly one of `identity` and `tier` must be set.
+struct RawAuthorizationRule {
+  1: optional RawAllowlistIdentity identity;
+  2: optional string tier;
+  // Any of "read", "draft_push", "public_push" and "admin".  Each
+  // permission implies the ones before it.
+  3: list<string> permissions;
+} (rust.exhaustive)
+
+// Maps subject alternative names of TLS client certificates to identities.
+struct RawCertIdentityMapping {
+  // The kind of subject alternative name to map: "DNS", "URI" or "EMAIL".
+  1: string san_type;
+  // Only names that start with this prefix are mapped.  The prefix is
+  // removed to form the identity data.
+  2: optional string prefix;
+  // The type of the resulting identity.
+  3: string identity_type;
+} (rust.exhaustive)
+
+// An issuer of JSON web tokens that clients can authenticate with.
+struct RawJwtIssuer {
+  // Required value of the `iss` claim.
+  1: string issuer;
+  // PEM-encoded public key that signs the tokens.  RSA (RS256), EC (ES256)
+  // and Ed25519 (EdDSA) keys are supported.
+  2: string public_key;
+  // Required value of the `aud` claim, if any.
+  3: optional string audience;
+  // Maps claim names to the type of identity they provide, e.g.
+  // `sub` => `USER`.  String claims provide one identity, and list claims
+  // provide one identity per element.
+  4: map<string, string> claim_identities;
+} (rust.exhaustive)
+
+struct RawAuthenticationConfig {
+  1: optional list<RawCertIdentityMapping> cert_identities;
+  2: optional list<RawJwtIssuer> jwt_issuers;
 } (rust.exhaustive)
 
 struct RawCacheWarmupConfig {
@@ -643,6 +835,11 @@ struct RawHookManagerParams {
   1: bool disable_acl_checker;
   2: bool all_hooks_bypassed;
   3: optional string bypassed_commits_scuba_table;
+  // Maximum number of hooks to run at the same time on a push. All hooks
+  // run at the same time if unset.
+  4: optional i64 max_concurrent_hooks;
+  // Maximum time that running the hooks on a push can take
+  5: optional i64 push_time_budget_ms;
 } (rust.exhaustive)
 
 struct RawHookConfig {
@@ -659,6 +856,8 @@ struct RawHookConfig {
   11: optional map<string, list<i64>> (
     rust.type = "HashMap",
   ) config_int_64_lists;
+  // Maximum number of bytes of file content the hook may read per push
+  12: optional i64 content_byte_budget;
 } (rust.exhaustive)
 
 struct RawLfsParams {
@@ -668,6 +867,8 @@ struct RawLfsParams {
   // Whether to generate lfs blobs in hg sync job
   3: optional bool generate_lfs_blob_in_hg_sync_job;
 // 4: deleted
+  // Whether LFS content is stored in storage shared with other repos
+  5: optional bool shared_storage;
 } (rust.exhaustive)
 
 struct RawBundle2ReplayParams {
@@ -687,6 +888,8 @@ struct RawInfinitepushParams {
 struct RawFilestoreParams {
   1: i64 chunk_size;
   2: i32 concurrency;
+  // Files larger than this are chunked. Defaults to chunk_size.
+  3: optional i64 chunking_threshold;
 } (rust.exhaustive)
 
 struct RawCommitSyncSmallRepoConfig {
@@ -920,4 +1123,174 @@ struct RawUpdateLoggingConfig {
 struct RawCommitGraphConfig {
   // Scuba table to log commit graph operations to
   1: optional string scuba_table;
+  // Whether to keep a bloom filter of changeset ids in memory, so that
+  // lookups of changesets that don't exist don't reach the database
+  2: optional bool use_prefix_filter;
+} (rust.exhaustive)
+
+struct RawRetryConfig {
+  // Maximum number of attempts, including the first
+  1: i64 max_attempts;
+  // Delay before the first retry, doubled for each subsequent retry
+  2: optional i64 base_delay_ms;
+  // Maximum random jitter added to each delay
+  3: optional i64 jitter_ms;
+  // Timeout for each attempt
+  4: optional i64 attempt_timeout_ms;
+  // Timeout for the whole operation, including all retries
+  5: optional i64 operation_timeout_ms;
+  // Percentage of calls that may be retried.  If not set, retries are not
+  // limited.
+  6: optional i64 retry_budget_percent;
+} (rust.exhaustive)
+
+struct RawCommitSigningConfig {
+  // Public keys trusted to sign commits, indexed by key id.  Keys are in
+  // PEM or OpenSSH format, or are armored OpenPGP key blocks.
+  1: map<string, string> trusted_keys;
+} (rust.exhaustive)
+
+struct RawWireprotoCapabilitiesConfig {
+  // Capabilities that are not advertised, even though the server would
+  // advertise them by default.  Applies to both wireproto and bundle2
+  // capabilities, matched by name (e.g. "clonebundles" or "b2x:rebase").
+  1: optional list<string> disabled;
+  // Additional wireproto capabilities to advertise, verbatim
+  // (e.g. "name" or "name=value").
+  2: optional list<string> extra;
+  // Minimum client version for a capability, keyed by capability name
+  // (e.g. {"segmentedchangelog": "0.2.20230601"}).  The capability is not
+  // advertised to older clients, or to clients whose version is unknown.
+  3: optional map<string, string> min_client_versions;
+} (rust.exhaustive)
+
+struct RawClientVersionConfig {
+  // Clients with an older version are rejected (e.g. "0.2.20230101")
+  1: optional string reject_below;
+  // Clients with an older version are warned that it is deprecated
+  2: optional string warn_below;
+  // Whether clients that don't report their version are warned that it is
+  // deprecated.  They are never rejected, as clients connecting over SSH
+  // can't report their version.
+  3: optional bool treat_unknown_as_outdated;
+  // Message shown to outdated clients, e.g. how to upgrade
+  4: optional string message;
+} (rust.exhaustive)
+
+struct RawMirrorConfig {
+  // Base URL of the upstream Mononoke's EdenAPI server, e.g.
+  // "https://mononoke.example.com/edenapi"
+  1: string upstream_url;
+  // Name of the repo on the upstream, if it differs from this repo's name
+  2: optional string upstream_repo_name;
+} (rust.exhaustive)
+
+struct RawPathLintConfig {
+  // Paths longer than this many bytes are violations
+  1: optional i64 max_path_length;
+  // Characters that must not appear in any path component
+  2: optional string forbidden_chars;
+  // File extensions that are not allowed, without the leading dot
+  // (e.g. "exe"), matched case-insensitively
+  3: optional list<string> disallowed_extensions;
+  // Whether paths that only differ in case are violations
+  4: optional bool check_case_conflicts;
+  // Scribe category that violations are logged to when the job files
+  // tasks for them
+  5: optional string task_scribe_category;
+} (rust.exhaustive)
+
+struct RawPushToReviewConfig {
+  // Prefix of the scratch bookmarks that create reviews.  A push to
+  // "<prefix><bookmark>" (e.g. "for/master") creates a review of the pushed
+  // commit against "<bookmark>".  The bookmarks must match the repo's
+  // infinitepush namespace.
+  1: string bookmark_prefix;
+  // URL that review creation requests are posted to
+  2: string webhook_url;
+} (rust.exhaustive)
+
+struct RawWebhookConfig {
+  // URL that events are posted to as JSON
+  1: string url;
+  // Events that are posted, out of "bookmark_moved", "tag_created" and
+  // "hook_rejected"
+  2: list<string> events;
+  // If set, payloads are signed with HMAC-SHA256 using this secret, and the
+  // signature is sent in the X-Mononoke-Signature header
+  3: optional string secret;
+} (rust.exhaustive)
+
+struct RawTagConfig {
+  // Namespaces that only some users may create tags in.  Tags outside of
+  // them may be created by anyone who can write to the repo.
+  1: list<RawProtectedTagNamespace> protected_namespaces;
+} (rust.exhaustive)
+
+struct RawProtectedTagNamespace {
+  // Tags whose names start with this prefix (e.g. "release/") are in the
+  // namespace
+  1: string prefix;
+  // Regex that the unixnames of users allowed to create tags in the
+  // namespace match.  Services never may, so if unset, tags in the namespace
+  // can only be created with full access.
+  2: optional string allowed_users;
+} (rust.exhaustive)
+
+struct RawMaintenanceWindow {
+  // Cron-like schedule of when the window starts, in UTC, as "minute hour
+  // day-of-month month day-of-week" (e.g. "0 14 * * 1-5" for 14:00 on
+  // weekdays).  Fields are "*", numbers, ranges, "*/step" and lists of
+  // these, and days of the week are numbered from Sunday as 0.
+  1: string schedule;
+  // How long the window lasts, in minutes.  At most a week.
+  2: i64 duration_mins;
+  // Operations that are restricted during the window, out of
+  // "background_jobs" (heavy jobs such as derived data backfills and
+  // repacking pause until the window ends) and "writes" (bookmark moves are
+  // rejected as if the repo were locked)
+  3: list<string> operations;
+  // Shown to clients whose writes are rejected during the window
+  4: optional string reason;
+} (rust.exhaustive)
+
+struct RawFilenodesConfig {
+  // Advertise the `filenode-free` EdenAPI capability, and serve file history
+  // from unodes and fastlog rather than filenodes to clients that use it.
+  1: optional bool filenode_free_serving;
+  // Don't generate filenodes at all.  Only for repos whose clients all support
+  // filenode-free serving, which this implies.
+  2: optional bool disable_generation;
+} (rust.exhaustive)
+
+struct RawRepoView {
+  // Name the view is served under, e.g. "myrepo-release".  Must not be the
+  // name of another repo.
+  1: string name;
+  // Regexes matching the whole names of the bookmarks the view serves
+  2: list<string> bookmarks;
+} (rust.exhaustive)
+
+struct RawSparseExportConfig {
+  // Id of the repo that the sparse copy of this repo is exported to
+  1: i32 export_repo_id;
+  // Paths that are exported, with everything under them.  Changes to any
+  // other path are dropped, and commits that only change other paths are
+  // not exported.
+  2: list<string> allowed_paths;
+  // Bookmarks that are exported.  The export of each bookmark is moved to
+  // the export of its tip.
+  3: list<string> bookmarks;
+} (rust.exhaustive)
+
+struct RawSloConfig {
+  // Fraction of requests that must not fail with an internal error, in parts
+  // per million (e.g. 999000 for 99.9%)
+  1: optional i64 availability_target_ppm;
+  // Target p99 latency of requests to each method, in milliseconds, keyed by
+  // method name (e.g. "repo_list_bookmarks")
+  2: map<string, i64> method_p99_latency_ms;
+  // Length of the rolling window that attainment is measured over.  Defaults
+  // to an hour.
+  3: optional i64 window_secs;
 } (rust.exhaustive)
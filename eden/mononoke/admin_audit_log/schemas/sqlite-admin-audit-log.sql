@@ -0,0 +1,19 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+CREATE TABLE IF NOT EXISTS `admin_audit_log` (
+  `id` INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  `repo_id` INTEGER NOT NULL,
+  `timestamp` BIGINT NOT NULL,
+  `actor` VARCHAR(512) NOT NULL,
+  `operation` VARCHAR(64) NOT NULL,
+  `arguments` TEXT NOT NULL,
+  `outcome` TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS `admin_audit_log_repo_timestamp`
+  ON `admin_audit_log` (`repo_id`, `timestamp`);
@@ -0,0 +1,277 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Admin audit log records every administrative mutation performed against a
+//! Mononoke repository (bookmark moves, repo locks, triggered derivations,
+//! etc.), along with who performed it, what arguments were supplied and
+//! whether it succeeded.
+//!
+//! The log is append-only: entries can be recorded and listed, but never
+//! modified or deleted through this interface.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use context::CoreContext;
+use context::PerfCounterType;
+use mononoke_types::RepositoryId;
+use mononoke_types::Timestamp;
+use permission_checker::MononokeIdentitySet;
+use sql_construct::SqlConstruct;
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::mononoke_queries;
+use sql_ext::SqlConnections;
+
+/// The result of an administrative operation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AdminAuditOutcome {
+    /// The operation was permitted and is about to be performed.  It is
+    /// followed by another entry with its outcome, unless performing it was
+    /// interrupted.
+    Attempted,
+    /// The operation was permitted and completed successfully.
+    Success,
+    /// The operation was rejected because the actor is not authorized.
+    Denied,
+    /// The operation was permitted but failed with the given error.
+    Failure(String),
+}
+
+impl AdminAuditOutcome {
+    fn to_sql(&self) -> String {
+        match self {
+            Self::Attempted => "attempted".to_string(),
+            Self::Success => "success".to_string(),
+            Self::Denied => "denied".to_string(),
+            Self::Failure(err) => format!("failure: {}", err),
+        }
+    }
+
+    fn from_sql(outcome: String) -> Self {
+        match outcome.as_str() {
+            "attempted" => Self::Attempted,
+            "success" => Self::Success,
+            "denied" => Self::Denied,
+            _ => Self::Failure(
+                outcome
+                    .strip_prefix("failure: ")
+                    .map(ToString::to_string)
+                    .unwrap_or(outcome),
+            ),
+        }
+    }
+}
+
+/// A single entry in the admin audit log.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AdminAuditEntry {
+    /// When the operation was performed.
+    pub timestamp: Timestamp,
+    /// The identities of the caller that performed the operation.
+    pub actor: String,
+    /// The name of the operation, e.g. `move_bookmark`.
+    pub operation: String,
+    /// The arguments supplied to the operation, serialized as JSON.
+    pub arguments: String,
+    /// Whether the operation succeeded.
+    pub outcome: AdminAuditOutcome,
+}
+
+/// Format the identities of the caller as the actor of an audit log entry.
+pub fn format_actor(identities: &MononokeIdentitySet) -> String {
+    identities
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[facet::facet]
+#[async_trait]
+pub trait AdminAuditLog: Send + Sync {
+    /// Append an entry to the audit log.
+    async fn record(&self, ctx: &CoreContext, entry: AdminAuditEntry) -> Result<()>;
+
+    /// List the most recent entries in the audit log, newest first.
+    async fn list_recent(&self, ctx: &CoreContext, limit: u64) -> Result<Vec<AdminAuditEntry>>;
+}
+
+mononoke_queries! {
+    write AddAuditEntry(
+        repo_id: RepositoryId,
+        timestamp: Timestamp,
+        actor: &str,
+        operation: &str,
+        arguments: &str,
+        outcome: &str,
+    ) {
+        none,
+        "INSERT INTO admin_audit_log (repo_id, timestamp, actor, operation, arguments, outcome)
+         VALUES ({repo_id}, {timestamp}, {actor}, {operation}, {arguments}, {outcome})"
+    }
+
+    read ListRecentAuditEntries(
+        repo_id: RepositoryId,
+        limit: u64,
+    ) -> (Timestamp, String, String, String, String) {
+        "SELECT timestamp, actor, operation, arguments, outcome
+         FROM admin_audit_log
+         WHERE repo_id = {repo_id}
+         ORDER BY id DESC
+         LIMIT {limit}"
+    }
+}
+
+pub struct SqlAdminAuditLog {
+    repo_id: RepositoryId,
+    connections: SqlConnections,
+}
+
+pub struct SqlAdminAuditLogBuilder {
+    connections: SqlConnections,
+}
+
+impl SqlConstruct for SqlAdminAuditLogBuilder {
+    const LABEL: &'static str = "admin_audit_log";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-admin-audit-log.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self { connections }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SqlAdminAuditLogBuilder {}
+
+impl SqlAdminAuditLogBuilder {
+    pub fn build(self, repo_id: RepositoryId) -> SqlAdminAuditLog {
+        SqlAdminAuditLog {
+            repo_id,
+            connections: self.connections,
+        }
+    }
+}
+
+#[async_trait]
+impl AdminAuditLog for SqlAdminAuditLog {
+    async fn record(&self, ctx: &CoreContext, entry: AdminAuditEntry) -> Result<()> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        AddAuditEntry::query(
+            &self.connections.write_connection,
+            &self.repo_id,
+            &entry.timestamp,
+            &entry.actor.as_str(),
+            &entry.operation.as_str(),
+            &entry.arguments.as_str(),
+            &entry.outcome.to_sql().as_str(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn list_recent(&self, ctx: &CoreContext, limit: u64) -> Result<Vec<AdminAuditEntry>> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsMaster);
+        let rows = ListRecentAuditEntries::query(
+            &self.connections.read_master_connection,
+            &self.repo_id,
+            &limit,
+        )
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(
+                |(timestamp, actor, operation, arguments, outcome)| AdminAuditEntry {
+                    timestamp,
+                    actor,
+                    operation,
+                    arguments,
+                    outcome: AdminAuditOutcome::from_sql(outcome),
+                },
+            )
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use mononoke_types_mocks::repo::REPO_ONE;
+    use mononoke_types_mocks::repo::REPO_ZERO;
+
+    use super::*;
+
+    fn entry(operation: &str, outcome: AdminAuditOutcome) -> AdminAuditEntry {
+        AdminAuditEntry {
+            timestamp: Timestamp::from_timestamp_secs(1000),
+            actor: "USER:alice".to_string(),
+            operation: operation.to_string(),
+            arguments: r#"{"bookmark":"main"}"#.to_string(),
+            outcome,
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_record_and_list(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let audit_log = SqlAdminAuditLogBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+
+        audit_log
+            .record(&ctx, entry("lock_repo", AdminAuditOutcome::Attempted))
+            .await?;
+        audit_log
+            .record(&ctx, entry("lock_repo", AdminAuditOutcome::Success))
+            .await?;
+        audit_log
+            .record(&ctx, entry("move_bookmark", AdminAuditOutcome::Denied))
+            .await?;
+        audit_log
+            .record(
+                &ctx,
+                entry(
+                    "derive",
+                    AdminAuditOutcome::Failure("unknown type".to_string()),
+                ),
+            )
+            .await?;
+
+        assert_eq!(
+            audit_log.list_recent(&ctx, 10).await?,
+            vec![
+                entry(
+                    "derive",
+                    AdminAuditOutcome::Failure("unknown type".to_string())
+                ),
+                entry("move_bookmark", AdminAuditOutcome::Denied),
+                entry("lock_repo", AdminAuditOutcome::Success),
+                entry("lock_repo", AdminAuditOutcome::Attempted),
+            ]
+        );
+        assert_eq!(audit_log.list_recent(&ctx, 1).await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_entries_are_per_repo(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let builder = SqlAdminAuditLogBuilder::with_sqlite_in_memory()?;
+        let connections = builder.connections.clone();
+        let audit_log = builder.build(REPO_ZERO);
+        let other_audit_log =
+            SqlAdminAuditLogBuilder::from_sql_connections(connections).build(REPO_ONE);
+
+        audit_log
+            .record(&ctx, entry("lock_repo", AdminAuditOutcome::Success))
+            .await?;
+
+        assert_eq!(audit_log.list_recent(&ctx, 10).await?.len(), 1);
+        assert!(other_audit_log.list_recent(&ctx, 10).await?.is_empty());
+
+        Ok(())
+    }
+}
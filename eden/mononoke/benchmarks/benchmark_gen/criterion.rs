@@ -0,0 +1,176 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Criterion benchmarks for derivation and commit graph queries against
+//! in-memory repos generated by `benchmark_gen`.
+
+use benchmark_gen::generate;
+use benchmark_gen::RepoShape;
+use benchmark_gen::ShapeSettings;
+use changesets::Changesets;
+use commit_graph::CommitGraph;
+use commit_graph::CommitGraphRef;
+use context::CoreContext;
+use criterion::measurement::WallTime;
+use criterion::BatchSize;
+use criterion::BenchmarkGroup;
+use criterion::Criterion;
+use derived_data_manager::BonsaiDerivable;
+use fbinit::FacebookInit;
+use fsnodes::RootFsnodeId;
+use mononoke_types::ChangesetId;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use repo_blobstore::RepoBlobstore;
+use repo_derived_data::RepoDerivedData;
+use repo_derived_data::RepoDerivedDataRef;
+use repo_identity::RepoIdentity;
+use skeleton_manifest::RootSkeletonManifestId;
+use tokio::runtime::Runtime;
+use unodes::RootUnodeManifestId;
+
+const SHAPES: &[RepoShape] = &[
+    RepoShape::Linear,
+    RepoShape::WideMerges,
+    RepoShape::ManyFiles,
+    RepoShape::DeepDirectories,
+];
+
+#[facet::container]
+struct Repo {
+    #[facet]
+    repo_identity: RepoIdentity,
+
+    #[facet]
+    repo_blobstore: RepoBlobstore,
+
+    #[facet]
+    changesets: dyn Changesets,
+
+    #[facet]
+    commit_graph: CommitGraph,
+
+    #[facet]
+    repo_derived_data: RepoDerivedData,
+}
+
+/// Settings for each shape, scaled down so that a fresh repo can be
+/// generated for every derivation sample.
+fn bench_settings(shape: RepoShape) -> ShapeSettings {
+    let settings = shape.default_settings();
+    ShapeSettings {
+        commits: (settings.commits / 20).max(1),
+        files_per_commit: (settings.files_per_commit / 10).max(1),
+        ..settings
+    }
+}
+
+fn generate_repo(
+    fb: FacebookInit,
+    runtime: &Runtime,
+    ctx: &CoreContext,
+    shape: RepoShape,
+) -> (Repo, Vec<ChangesetId>) {
+    let repo: Repo = test_repo_factory::build_empty(fb).expect("failed to build repo");
+    let mut rng = XorShiftRng::seed_from_u64(0);
+    let generated = runtime
+        .block_on(generate(
+            ctx,
+            &repo,
+            shape,
+            bench_settings(shape),
+            &mut rng,
+            None,
+        ))
+        .expect("failed to generate repo");
+    (repo, generated)
+}
+
+fn bench_derive<Derivable: BonsaiDerivable>(
+    group: &mut BenchmarkGroup<WallTime>,
+    fb: FacebookInit,
+    runtime: &Runtime,
+    ctx: &CoreContext,
+    shape: RepoShape,
+) {
+    group.bench_function(Derivable::NAME, |b| {
+        b.iter_batched(
+            || generate_repo(fb, runtime, ctx, shape),
+            |(repo, generated)| {
+                let head = *generated.last().unwrap();
+                runtime
+                    .block_on(repo.repo_derived_data().derive::<Derivable>(ctx, head))
+                    .expect("failed to derive")
+            },
+            BatchSize::PerIteration,
+        )
+    });
+}
+
+fn derivation_benchmark(c: &mut Criterion, fb: FacebookInit, runtime: &Runtime, ctx: &CoreContext) {
+    for shape in SHAPES {
+        let mut group = c.benchmark_group(format!("derive/{:?}", shape));
+        bench_derive::<RootFsnodeId>(&mut group, fb, runtime, ctx, *shape);
+        bench_derive::<RootUnodeManifestId>(&mut group, fb, runtime, ctx, *shape);
+        bench_derive::<RootSkeletonManifestId>(&mut group, fb, runtime, ctx, *shape);
+        group.finish();
+    }
+}
+
+fn graph_benchmark(c: &mut Criterion, fb: FacebookInit, runtime: &Runtime, ctx: &CoreContext) {
+    for shape in SHAPES {
+        let (repo, generated) = generate_repo(fb, runtime, ctx, *shape);
+        let root = *generated.first().unwrap();
+        let head = *generated.last().unwrap();
+        let middle = generated[generated.len() / 2];
+
+        let mut group = c.benchmark_group(format!("graph/{:?}", shape));
+        group.bench_function("is_ancestor", |b| {
+            b.iter(|| {
+                runtime
+                    .block_on(repo.commit_graph().is_ancestor(ctx, root, head))
+                    .expect("is_ancestor failed")
+            })
+        });
+        group.bench_function("ancestors_difference", |b| {
+            b.iter(|| {
+                runtime
+                    .block_on(repo.commit_graph().ancestors_difference(
+                        ctx,
+                        vec![head],
+                        vec![middle],
+                    ))
+                    .expect("ancestors_difference failed")
+            })
+        });
+        group.bench_function("lowest_common_frontier", |b| {
+            b.iter(|| {
+                runtime
+                    .block_on(repo.commit_graph().lowest_common_frontier(
+                        ctx,
+                        vec![head],
+                        vec![middle],
+                    ))
+                    .expect("lowest_common_frontier failed")
+            })
+        });
+        group.finish();
+    }
+}
+
+#[fbinit::main]
+fn main(fb: FacebookInit) {
+    let runtime = Runtime::new().expect("failed to initialize runtime");
+    let ctx = CoreContext::test_mock(fb);
+
+    let mut criterion = Criterion::default().sample_size(10);
+
+    derivation_benchmark(&mut criterion, fb, &runtime, &ctx);
+    graph_benchmark(&mut criterion, fb, &runtime, &ctx);
+
+    criterion.final_summary();
+}
@@ -0,0 +1,127 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Generate a synthetic repository with a configurable shape directly into
+//! the blobstore and commit graph of a configured repo.
+
+use anyhow::Result;
+use benchmark_gen::generate;
+use benchmark_gen::RepoShape;
+use bookmarks::BookmarkKey;
+use bookmarks::BookmarkUpdateReason;
+use bookmarks::Bookmarks;
+use bookmarks::BookmarksRef;
+use changesets::Changesets;
+use clap::Parser;
+use commit_graph::CommitGraph;
+use fbinit::FacebookInit;
+use futures_stats::TimedFutureExt;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::MononokeApp;
+use mononoke_app::MononokeAppBuilder;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use repo_blobstore::RepoBlobstore;
+use repo_identity::RepoIdentity;
+
+/// Generate a synthetic repository for benchmarking.
+#[derive(Parser)]
+struct BenchmarkGenArgs {
+    #[clap(flatten)]
+    repo: RepoArgs,
+
+    /// Shape of the repository to generate.
+    #[clap(long, arg_enum)]
+    shape: RepoShape,
+
+    /// Number of commits to generate (defaults depend on the shape).
+    #[clap(long)]
+    commits: Option<usize>,
+
+    /// Number of files changed by each commit (defaults depend on the shape).
+    #[clap(long)]
+    files_per_commit: Option<usize>,
+
+    /// Number of branches joined by each merge (defaults depend on the shape).
+    #[clap(long)]
+    merge_width: Option<usize>,
+
+    /// Number of directory levels above each file (defaults depend on the
+    /// shape).
+    #[clap(long)]
+    directory_depth: Option<usize>,
+
+    /// Seed for the random number generator.
+    #[clap(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Bookmark to point at the head of the generated history.
+    #[clap(long)]
+    bookmark: Option<String>,
+}
+
+#[facet::container]
+struct Repo {
+    #[facet]
+    repo_identity: RepoIdentity,
+
+    #[facet]
+    repo_blobstore: RepoBlobstore,
+
+    #[facet]
+    changesets: dyn Changesets,
+
+    #[facet]
+    commit_graph: CommitGraph,
+
+    #[facet]
+    bookmarks: dyn Bookmarks,
+}
+
+#[fbinit::main]
+fn main(fb: FacebookInit) -> Result<()> {
+    MononokeAppBuilder::new(fb)
+        .build::<BenchmarkGenArgs>()?
+        .run_basic(async_main)
+}
+
+async fn async_main(app: MononokeApp) -> Result<()> {
+    let args: BenchmarkGenArgs = app.args()?;
+    let ctx = app.new_basic_context();
+    let repo: Repo = app.open_repo(&args.repo).await?;
+
+    let mut settings = args.shape.default_settings();
+    settings.commits = args.commits.unwrap_or(settings.commits);
+    settings.files_per_commit = args.files_per_commit.unwrap_or(settings.files_per_commit);
+    settings.merge_width = args.merge_width.unwrap_or(settings.merge_width);
+    settings.directory_depth = args.directory_depth.unwrap_or(settings.directory_depth);
+    println!("Generating {:?} repo with {:?}", args.shape, settings);
+
+    let mut rng = XorShiftRng::seed_from_u64(args.seed);
+    let (stats, generated) = generate(&ctx, &repo, args.shape, settings, &mut rng, None)
+        .timed()
+        .await;
+    let generated = generated?;
+    println!(
+        "Generated {} commits in {:?}",
+        generated.len(),
+        stats.completion_time
+    );
+
+    if let Some(head) = generated.last() {
+        println!("Head: {}", head);
+        if let Some(bookmark) = &args.bookmark {
+            let bookmark = BookmarkKey::new(bookmark)?;
+            let mut transaction = repo.bookmarks().create_transaction(ctx.clone());
+            transaction.force_set(&bookmark, *head, BookmarkUpdateReason::ManualMove)?;
+            transaction.commit().await?;
+            println!("Moved {} to {}", bookmark, head);
+        }
+    }
+
+    Ok(())
+}
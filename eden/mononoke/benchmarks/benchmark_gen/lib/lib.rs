@@ -0,0 +1,265 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Generation of synthetic repositories with a configurable shape.
+//!
+//! Commits are written directly into the repo's blobstore, changesets and
+//! commit graph, so a repo of any shape and size can be produced quickly for
+//! benchmarking derivation and graph queries against a chosen backend.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use blobrepo::save_bonsai_changesets;
+use blobstore::Storable;
+use changesets::ChangesetsRef;
+use clap::ArgEnum;
+use commit_graph::CommitGraphRef;
+use commit_graph_types::ChangesetParents;
+use context::CoreContext;
+use futures::stream;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use mononoke_types::BlobstoreValue;
+use mononoke_types::BonsaiChangeset;
+use mononoke_types::BonsaiChangesetMut;
+use mononoke_types::ChangesetId;
+use mononoke_types::ContentBlob;
+use mononoke_types::DateTime;
+use mononoke_types::FileChange;
+use mononoke_types::FileContents;
+use mononoke_types::FileType;
+use mononoke_types::MPath;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use repo_blobstore::RepoBlobstoreRef;
+
+/// Number of generated commits to buffer before writing them to the repo.
+const FLUSH_SIZE: usize = 1000;
+
+/// Fan-out of each directory level for shapes with balanced directories.
+const DIRECTORY_FANOUT: usize = 16;
+
+/// Probability that a file change modifies an existing file rather than
+/// adding a new one, for shapes that allow modifications.
+const P_MODIFY: f64 = 0.3;
+
+/// The shape of a generated repository.
+#[derive(ArgEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RepoShape {
+    /// A linear history of small commits.
+    Linear,
+    /// Repeated fan-outs of short branches, each joined by an octopus merge.
+    WideMerges,
+    /// A linear history of commits that each change many files.
+    ManyFiles,
+    /// A linear history where all files live at the bottom of a very deep
+    /// directory.
+    DeepDirectories,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ShapeSettings {
+    /// Total number of commits to generate.
+    pub commits: usize,
+    /// Number of files added or modified by each commit.
+    pub files_per_commit: usize,
+    /// Number of branches joined by each merge (`WideMerges` only).
+    pub merge_width: usize,
+    /// Number of directory levels above each file.
+    pub directory_depth: usize,
+}
+
+impl RepoShape {
+    /// Settings that give each shape its character at a moderate size.
+    pub fn default_settings(&self) -> ShapeSettings {
+        match self {
+            RepoShape::Linear => ShapeSettings {
+                commits: 10000,
+                files_per_commit: 2,
+                merge_width: 0,
+                directory_depth: 3,
+            },
+            RepoShape::WideMerges => ShapeSettings {
+                commits: 10000,
+                files_per_commit: 2,
+                merge_width: 16,
+                directory_depth: 3,
+            },
+            RepoShape::ManyFiles => ShapeSettings {
+                commits: 100,
+                files_per_commit: 10000,
+                merge_width: 0,
+                directory_depth: 3,
+            },
+            RepoShape::DeepDirectories => ShapeSettings {
+                commits: 1000,
+                files_per_commit: 2,
+                merge_width: 0,
+                directory_depth: 200,
+            },
+        }
+    }
+}
+
+struct Generator<'a, R> {
+    ctx: &'a CoreContext,
+    repo: &'a R,
+    shape: RepoShape,
+    settings: ShapeSettings,
+    /// All files created so far.
+    files: Vec<MPath>,
+    /// Number of commits generated so far.
+    generated: usize,
+    pending_contents: Vec<ContentBlob>,
+    pending_changesets: Vec<BonsaiChangeset>,
+}
+
+impl<'a, R> Generator<'a, R>
+where
+    R: RepoBlobstoreRef + ChangesetsRef + CommitGraphRef + Send + Sync,
+{
+    fn new(ctx: &'a CoreContext, repo: &'a R, shape: RepoShape, settings: ShapeSettings) -> Self {
+        Self {
+            ctx,
+            repo,
+            shape,
+            settings,
+            files: Vec::new(),
+            generated: 0,
+            pending_contents: Vec::new(),
+            pending_changesets: Vec::new(),
+        }
+    }
+
+    /// Path of the file with the given index.
+    fn path(&self, index: usize) -> Result<MPath> {
+        let mut elements: Vec<String> = (0..self.settings.directory_depth)
+            .map(|level| match self.shape {
+                RepoShape::DeepDirectories => format!("d{}", level),
+                _ => format!(
+                    "d{}",
+                    (index / DIRECTORY_FANOUT.saturating_pow(level as u32 + 1)) % DIRECTORY_FANOUT
+                ),
+            })
+            .collect();
+        elements.push(format!("f{}", index));
+        MPath::new(elements.join("/"))
+    }
+
+    fn commit(&mut self, rng: &mut impl Rng, parents: Vec<ChangesetId>) -> Result<ChangesetId> {
+        // Merged branches only ever add files, so that merges never need to
+        // resolve conflicting changes.
+        let allow_modify = self.shape != RepoShape::WideMerges;
+
+        let mut file_changes = BTreeMap::new();
+        while file_changes.len() < self.settings.files_per_commit {
+            let path = match self.files.choose(rng) {
+                Some(path) if allow_modify && rng.gen_bool(P_MODIFY) => path.clone(),
+                _ => {
+                    let path = self.path(self.files.len())?;
+                    self.files.push(path.clone());
+                    path
+                }
+            };
+            let content =
+                FileContents::new_bytes(format!("{} in commit {}\n", path, self.generated));
+            let size = content.size();
+            let blob = content.into_blob();
+            file_changes.insert(
+                path,
+                FileChange::tracked(*blob.id(), FileType::Regular, size, None),
+            );
+            self.pending_contents.push(blob);
+        }
+
+        let bcs = BonsaiChangesetMut {
+            parents,
+            author: "benchmark_gen".to_string(),
+            author_date: DateTime::from_timestamp(self.generated as i64, 0)?,
+            message: format!("{:?} commit {}", self.shape, self.generated),
+            file_changes: file_changes.into(),
+            ..Default::default()
+        }
+        .freeze()?;
+        let cs_id = bcs.get_changeset_id();
+        self.pending_changesets.push(bcs);
+        self.generated += 1;
+        Ok(cs_id)
+    }
+
+    /// Write all pending contents and changesets to the repo.
+    async fn flush(&mut self) -> Result<()> {
+        let ctx = self.ctx;
+        let blobstore = self.repo.repo_blobstore();
+        stream::iter(std::mem::take(&mut self.pending_contents))
+            .map(|blob| blob.store(ctx, blobstore))
+            .buffer_unordered(100)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let changesets = std::mem::take(&mut self.pending_changesets);
+        let parents: Vec<(ChangesetId, ChangesetParents)> = changesets
+            .iter()
+            .map(|bcs| (bcs.get_changeset_id(), bcs.parents().collect()))
+            .collect();
+        save_bonsai_changesets(changesets, ctx.clone(), self.repo).await?;
+
+        // The changesets may already have been written to the commit graph
+        // when they were saved, in which case this is a no-op.
+        for (cs_id, parents) in parents {
+            self.repo.commit_graph().add(ctx, cs_id, parents).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Generate a repository of the given shape on top of `parent` (or as a new
+/// root), returning the generated changesets in topological order.  The
+/// last changeset is the single head of the generated history.
+pub async fn generate(
+    ctx: &CoreContext,
+    repo: &(impl RepoBlobstoreRef + ChangesetsRef + CommitGraphRef + Send + Sync),
+    shape: RepoShape,
+    settings: ShapeSettings,
+    rng: &mut impl Rng,
+    parent: Option<ChangesetId>,
+) -> Result<Vec<ChangesetId>> {
+    let mut generator = Generator::new(ctx, repo, shape, settings);
+    let mut head = parent;
+    let mut generated = Vec::with_capacity(settings.commits);
+
+    while generated.len() < settings.commits {
+        let remaining = settings.commits - generated.len();
+        let branches = settings.merge_width.min(remaining - 1);
+        match head {
+            Some(base) if shape == RepoShape::WideMerges && branches >= 2 => {
+                let mut merge_parents = Vec::with_capacity(branches);
+                for _ in 0..branches {
+                    let cs_id = generator.commit(rng, vec![base])?;
+                    merge_parents.push(cs_id);
+                    generated.push(cs_id);
+                }
+                let merge = generator.commit(rng, merge_parents)?;
+                generated.push(merge);
+                head = Some(merge);
+            }
+            _ => {
+                let cs_id = generator.commit(rng, head.into_iter().collect())?;
+                generated.push(cs_id);
+                head = Some(cs_id);
+            }
+        }
+
+        if generator.pending_changesets.len() >= FLUSH_SIZE {
+            generator.flush().await?;
+        }
+    }
+    generator.flush().await?;
+
+    Ok(generated)
+}
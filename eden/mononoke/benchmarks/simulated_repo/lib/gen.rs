@@ -142,6 +142,7 @@ impl GenManifest {
                     file_changes: file_changes.into(),
                     is_snapshot: false,
                     git_annotated_tag: None,
+                    extras: None,
                 }
                 .freeze()
                 .expect("generated bonsai failed to freeze");
@@ -85,6 +85,7 @@ pub async fn create_bonsai_changeset_object(
         file_changes,
         is_snapshot: false,
         git_annotated_tag: None,
+        extras: None,
     }
     .freeze()
 }
@@ -242,7 +242,7 @@ pub async fn get_file_history(
                 .map(|filenode| (filenode.filenode, filenode))
                 .collect();
             let history = get_file_history_using_prefetched(
-                ctx, repo, filenode, path, max_length, prefetched,
+                ctx, repo, filenode, path, max_length, prefetched, true,
             )
             .try_collect()
             .await?;
@@ -259,6 +259,7 @@ pub async fn get_file_history(
                 path,
                 max_length,
                 HashMap::new(),
+                true,
             )
             .try_collect()
             .await?;
@@ -268,8 +269,31 @@ pub async fn get_file_history(
     }
 }
 
+/// Get the history of the file corresponding to the given filenode and path
+/// from file envelopes alone, without reading filenodes.  Envelopes don't
+/// record linknodes, so all entries have null linknodes.
+pub fn get_file_history_from_envelopes(
+    ctx: CoreContext,
+    repo: BlobRepo,
+    filenode: HgFileNodeId,
+    path: MPath,
+    max_length: Option<u64>,
+) -> impl Stream<Item = Result<HgFileHistoryEntry, Error>> {
+    get_file_history_using_prefetched(
+        ctx,
+        repo,
+        filenode,
+        path,
+        max_length,
+        HashMap::new(),
+        false,
+    )
+}
+
 /// Get the history of the file at the specified path, using the given
-/// prefetched history map as a cache to speed up the operation.
+/// prefetched history map as a cache to speed up the operation.  Filenodes
+/// that aren't prefetched are read from the filenodes of the repo if
+/// `read_filenodes` is set, and from their envelopes otherwise.
 ///
 /// FIXME: max_legth parameter is not necessary. We can use .take() method on the stream
 /// i.e. get_file_history_using_prefetched().take(max_length)
@@ -280,6 +304,7 @@ fn get_file_history_using_prefetched(
     path: MPath,
     max_length: Option<u64>,
     prefetched_history: HashMap<HgFileNodeId, FilenodeInfo>,
+    read_filenodes: bool,
 ) -> impl Stream<Item = Result<HgFileHistoryEntry, Error>> {
     if startnode == HgFileNodeId::new(NULL_HASH) {
         return stream::empty().left_stream();
@@ -295,6 +320,7 @@ fn get_file_history_using_prefetched(
         repo: BlobRepo,
         path: RepoPath,
         prefetched_history: HashMap<HgFileNodeId, FilenodeInfo>,
+        read_filenodes: bool,
     }
 
     let bfs_context = BfsContext {
@@ -302,6 +328,7 @@ fn get_file_history_using_prefetched(
         repo,
         path,
         prefetched_history,
+        read_filenodes,
     };
 
     // TODO: There is probably another thundering herd problem here. If we change a file twice,
@@ -324,7 +351,7 @@ fn get_file_history_using_prefetched(
 
             let filenode = if let Some(filenode) = bfs_context.prefetched_history.get(&node) {
                 filenode.clone()
-            } else {
+            } else if bfs_context.read_filenodes {
                 get_maybe_missing_filenode(
                     &bfs_context.ctx,
                     &bfs_context.repo,
@@ -332,6 +359,15 @@ fn get_file_history_using_prefetched(
                     node,
                 )
                 .await?
+            } else {
+                get_filenode_from_envelope(
+                    bfs_context.repo.repo_blobstore().clone(),
+                    &bfs_context.ctx,
+                    &bfs_context.path,
+                    node,
+                    NULL_CSID,
+                )
+                .await?
             };
 
             let p1 = filenode.p1.map(|p| p.into_nodehash());
@@ -395,7 +431,9 @@ async fn get_maybe_missing_filenode(
     }
 }
 
-async fn get_filenode_from_envelope(
+/// Reconstruct the filenode info of a filenode from its envelope, with the
+/// given linknode.
+pub async fn get_filenode_from_envelope(
     blobstore: impl Blobstore + 'static,
     ctx: &CoreContext,
     path: &RepoPath,
@@ -76,6 +76,14 @@ impl RepoBlobstore {
         Self::build(new_inner_blobstore, prefix, redacted_blobstore_config)
     }
 
+    /// A blobstore backed by the same storage and redaction config, but using
+    /// a different prefix. This lets data that isn't specific to a single
+    /// repo be stored next to repo data.
+    pub fn with_prefix(&self, prefix: String) -> Self {
+        let (blobstore, redacted_blobstore_config, _) = self.0.as_parts();
+        Self::build(blobstore, prefix, redacted_blobstore_config)
+    }
+
     #[allow(clippy::let_and_return)]
     fn build(
         blobstore: Arc<dyn Blobstore>,
@@ -107,7 +115,10 @@ impl Blobstore for RepoBlobstore {
         ctx: &'a CoreContext,
         key: &'a str,
     ) -> Result<Option<BlobstoreGetData>> {
-        self.0.0.get(ctx, key).await
+        // Reads are abandoned once the client has given up or gone away.
+        // Writes are not, as their effects may still be seen, e.g. by a retry
+        // of the request.
+        ctx.run_until_abandoned(self.0.0.get(ctx, key)).await?
     }
     async fn put<'a>(
         &'a self,
@@ -122,7 +133,7 @@ impl Blobstore for RepoBlobstore {
         ctx: &'a CoreContext,
         key: &'a str,
     ) -> Result<BlobstoreIsPresent> {
-        self.0.0.is_present(ctx, key).await
+        ctx.run_until_abandoned(self.0.0.is_present(ctx, key)).await?
     }
     async fn copy<'a>(
         &'a self,
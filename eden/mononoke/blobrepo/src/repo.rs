@@ -18,6 +18,7 @@ use changeset_fetcher::ChangesetFetcher;
 use changeset_fetcher::SimpleChangesetFetcher;
 use changesets::Changesets;
 use changesets::ChangesetsRef;
+use commit_signatures::CommitSignatures;
 use context::CoreContext;
 use ephemeral_blobstore::Bubble;
 use filenodes::Filenodes;
@@ -102,6 +103,9 @@ pub struct BlobRepoInner {
 
     #[facet]
     pub repo_bookmark_attrs: RepoBookmarkAttrs,
+
+    #[facet]
+    pub commit_signatures: dyn CommitSignatures,
 }
 
 #[facet::container]
@@ -128,6 +132,7 @@ pub struct BlobRepo {
         dyn RepoPermissionChecker,
         dyn RepoLock,
         RepoBookmarkAttrs,
+        dyn CommitSignatures,
     )]
     inner: Arc<BlobRepoInner>,
 }
@@ -6,6 +6,7 @@
  */
 
 use std::str::FromStr;
+use std::sync::Arc;
 
 use anyhow::anyhow;
 use anyhow::Error;
@@ -16,12 +17,16 @@ use context::CoreContext;
 use derived_data::BonsaiDerived;
 use derived_data_filenodes::FilenodesOnlyPublic;
 use fbinit::FacebookInit;
+use filenodes::DisabledFilenodes;
 use fixtures::Linear;
 use fixtures::TestRepoFixture;
 use manifest::ManifestOps;
+use mercurial_derived_data::DeriveHgChangeset;
 use mercurial_types::HgChangesetId;
+use mercurial_types::NULL_CSID;
 use mononoke_types::MPath;
 use repo_blobstore::RepoBlobstoreRef;
+use test_repo_factory::TestRepoFactory;
 use tests_utils::resolve_cs_id;
 
 #[fbinit::test]
@@ -86,6 +91,31 @@ async fn test_linear_get_file_history(fb: FacebookInit) -> Result<(), Error> {
     Ok(())
 }
 
+#[fbinit::test]
+async fn test_get_file_history_without_filenodes(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo: BlobRepo = TestRepoFactory::new(fb)?
+        .with_filenodes_override(|_| Arc::new(DisabledFilenodes))
+        .build()?;
+    Linear::initrepo(fb, &repo).await;
+
+    let master_cs_id = resolve_cs_id(&ctx, &repo, "master").await?;
+    let master_hg_cs_id = repo.derive_hg_changeset(&ctx, master_cs_id).await?;
+
+    // The repo has no filenodes, so the whole history is read from the
+    // envelopes, with null linknodes.
+    assert_linknodes(
+        &ctx,
+        &repo,
+        vec![NULL_CSID; 10],
+        master_hg_cs_id,
+        MPath::new("files")?,
+        None,
+    )
+    .await?;
+    Ok(())
+}
+
 async fn assert_linknodes(
     ctx: &CoreContext,
     repo: &BlobRepo,
@@ -607,6 +607,7 @@ fn make_bonsai_changeset(
             .collect(),
         is_snapshot: false,
         git_annotated_tag: None,
+        extras: None,
     }
     .freeze()
     .unwrap()
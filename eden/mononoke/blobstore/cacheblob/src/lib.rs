@@ -26,4 +26,5 @@ pub use crate::memcache_cache_lease::new_memcache_blobstore;
 pub use crate::memcache_cache_lease::MemcacheOps;
 
 mod mem_writes;
+pub use crate::mem_writes::CacheUsage;
 pub use crate::mem_writes::MemWritesBlobstore;
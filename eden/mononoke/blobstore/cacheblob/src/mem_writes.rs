@@ -28,20 +28,59 @@ use tokio::sync::Mutex as AsyncMutex;
 #[derive(Default, Debug)]
 pub struct Cache {
     live: HashMap<String, BlobstoreBytes>,
+    live_bytes: usize,
     flushing: Option<Arc<HashMap<String, BlobstoreBytes>>>,
+    flushing_bytes: usize,
+    usage: CacheUsage,
 }
 
 impl Cache {
     pub fn len(&self) -> usize {
         self.live.len() + self.flushing.as_ref().map_or(0, |flushing| flushing.len())
     }
+
+    /// Bytes of memory holding the writes in the cache, including their
+    /// keys.
+    pub fn memory_bytes(&self) -> usize {
+        self.live_bytes + self.flushing_bytes
+    }
+
+    /// What the cache has held since it was created.
+    pub fn usage(&self) -> CacheUsage {
+        self.usage
+    }
+
+    fn insert(&mut self, key: String, value: BlobstoreBytes) {
+        let key_len = key.len();
+        let value_len = value.len();
+        if let Some(old) = self.live.insert(key, value) {
+            self.live_bytes -= key_len + old.len();
+        }
+        self.live_bytes += key_len + value_len;
+        self.usage.blob_bytes += value_len;
+        self.usage.peak_memory_bytes = self.usage.peak_memory_bytes.max(self.memory_bytes());
+    }
+}
+
+/// What a `MemWritesBlobstore` cache has held.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CacheUsage {
+    /// The most bytes of memory used at once to hold writes, including
+    /// their keys.
+    pub peak_memory_bytes: usize,
+    /// Total size of the blobs written.
+    pub blob_bytes: usize,
 }
 
 /// A blobstore wrapper that reads from the underlying blobstore but writes to memory.
+///
+/// If it has a memory limit, the writes are persisted to the underlying
+/// blobstore whenever the memory holding them exceeds it.
 #[derive(Clone, Debug)]
 pub struct MemWritesBlobstore<T> {
     inner: T,
     cache: Arc<Mutex<Cache>>,
+    memory_limit: Option<usize>,
     // Mutex to ensure only one task is flushing the cache at a time.
     // Note: this doesn't wrap the cache as read access is permitted while
     // the mutex is held.
@@ -60,6 +99,7 @@ impl<T: Blobstore + Clone> MemWritesBlobstore<T> {
         Self {
             inner: blobstore,
             cache: Default::default(),
+            memory_limit: None,
             flush_mutex: Default::default(),
             no_access_to_inner: Default::default(),
         }
@@ -88,6 +128,7 @@ impl<T: Blobstore + Clone> MemWritesBlobstore<T> {
             }
             let flushing = Arc::new(mem::take(&mut cache.live));
             cache.flushing = Some(flushing.clone());
+            cache.flushing_bytes = mem::take(&mut cache.live_bytes);
             Ok(flushing)
         })?;
 
@@ -106,11 +147,21 @@ impl<T: Blobstore + Clone> MemWritesBlobstore<T> {
         // flushing the cache.
         self.cache.with(|cache| {
             cache.flushing = None;
+            cache.flushing_bytes = 0;
         });
 
         result
     }
 
+    /// Persist the writes whenever the memory holding them exceeds
+    /// `memory_limit` bytes, rather than only when `persist` is called.
+    pub fn with_memory_limit(self, memory_limit: Option<usize>) -> Self {
+        Self {
+            memory_limit,
+            ..self
+        }
+    }
+
     pub fn get_inner(&self) -> T {
         self.inner.clone()
     }
@@ -129,11 +180,19 @@ impl<T: Blobstore + Clone> MemWritesBlobstore<T> {
 impl<T: Blobstore + Clone> Blobstore for MemWritesBlobstore<T> {
     async fn put<'a>(
         &'a self,
-        _ctx: &'a CoreContext,
+        ctx: &'a CoreContext,
         key: String,
         value: BlobstoreBytes,
     ) -> Result<()> {
-        self.cache.with(|cache| cache.live.insert(key, value));
+        let live_bytes = self.cache.with(|cache| {
+            cache.insert(key, value);
+            cache.live_bytes
+        });
+        if let Some(memory_limit) = self.memory_limit {
+            if live_bytes > memory_limit && !self.no_access_to_inner.load(Ordering::Relaxed) {
+                self.persist(ctx).await?;
+            }
+        }
         Ok(())
     }
 
@@ -282,6 +341,43 @@ mod test {
         Ok(())
     }
 
+    #[fbinit::test]
+    async fn test_memory_limit(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+
+        let inner = Memblob::default();
+        let outer = MemWritesBlobstore::new(inner.clone()).with_memory_limit(Some(20));
+
+        // Each write takes 3 bytes of key and 5 of value.
+        let value = BlobstoreBytes::from_bytes("value");
+        outer.put(ctx, "ke1".to_owned(), value.clone()).await?;
+        outer.put(ctx, "ke2".to_owned(), value.clone()).await?;
+        assert!(inner.get(ctx, "ke1").await?.is_none());
+        assert_eq!(outer.get_cache().lock().unwrap().memory_bytes(), 16);
+
+        // Overwrites replace the memory used by the old value.
+        outer.put(ctx, "ke2".to_owned(), value.clone()).await?;
+        assert_eq!(outer.get_cache().lock().unwrap().memory_bytes(), 16);
+
+        // Exceeding the limit persists the writes.
+        outer.put(ctx, "ke3".to_owned(), value.clone()).await?;
+        for key in ["ke1", "ke2", "ke3"] {
+            assert_eq!(inner.get(ctx, key).await?, Some(value.clone().into()));
+        }
+        let cache = outer.get_cache().lock().unwrap();
+        assert_eq!(cache.memory_bytes(), 0);
+        assert_eq!(
+            cache.usage(),
+            CacheUsage {
+                peak_memory_bytes: 24,
+                blob_bytes: 20,
+            }
+        );
+
+        Ok(())
+    }
+
     /// A blobstore wrapper that prevents writes until a flag is set.
     #[derive(Clone, Debug)]
     pub struct GatedBlobstore<T> {
@@ -153,6 +153,14 @@ impl MemcacheOps {
         })
     }
 
+    /// Remove a blob from memcache, so that it stops being served from the
+    /// cache once it has been removed from the backing store.
+    pub async fn invalidate(&self, key: &str) -> Result<()> {
+        self.memcache.del(self.keygen.key(key)).await?;
+        self.memcache.del(self.presence_keygen.key(key)).await?;
+        Ok(())
+    }
+
     async fn get_lock_state(&self, key: String) -> Option<LockState> {
         let mc_key = self.presence_keygen.key(key.clone());
         STATS::presence_get.add_value(1);
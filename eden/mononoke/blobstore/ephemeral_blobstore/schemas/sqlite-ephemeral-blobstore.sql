@@ -29,3 +29,6 @@ CREATE TABLE IF NOT EXISTS 'ephemeral_bubble_labels' (
     `label` VARCHAR(255),
     PRIMARY KEY (`bubble_id`, `label`)
 );
+
+CREATE INDEX IF NOT EXISTS `ephemeral_bubble_labels_label`
+  ON `ephemeral_bubble_labels` (`label`, `bubble_id`);
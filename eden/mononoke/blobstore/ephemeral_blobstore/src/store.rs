@@ -139,6 +139,21 @@ mononoke_queries! {
         WHERE bubble_id = {id}"
     }
 
+    read SelectChangesetsWithLabel(
+        repo_id: RepositoryId,
+        label: &str,
+        expiry_status: ExpiryStatus,
+        limit: u32,
+    ) -> (BubbleId, ChangesetId) {
+        "SELECT M.bubble_id, M.cs_id
+        FROM ephemeral_bubble_labels L
+        JOIN ephemeral_bubbles B ON B.id = L.bubble_id
+        JOIN ephemeral_bubble_changeset_mapping M ON M.bubble_id = B.id
+        WHERE L.label = {label} AND M.repo_id = {repo_id} AND B.expired = {expiry_status}
+        ORDER BY B.created_at DESC, B.id DESC
+        LIMIT {limit}"
+    }
+
     read SelectBubblesWithExpiry(
         expires_at: Timestamp,
         limit: u32,
@@ -326,6 +341,25 @@ impl RepoEphemeralStoreInner {
         Ok(rows.into_iter().map(|b| b.0).collect::<Vec<_>>())
     }
 
+    /// Fetch the changesets in active bubbles that have the given label,
+    /// most recently created first.
+    async fn changesets_with_label(
+        &self,
+        repo_id: &RepositoryId,
+        label: &str,
+        limit: u32,
+    ) -> Result<Vec<(BubbleId, ChangesetId)>> {
+        let rows = SelectChangesetsWithLabel::query(
+            &self.connections.read_connection,
+            repo_id,
+            &label,
+            &ExpiryStatus::Active,
+            &limit,
+        )
+        .await?;
+        Ok(rows)
+    }
+
     /// Gets the vector of bubbles that are past their expiry period
     /// by atleast a duration of expiry_offset + bubble_expiration_grace
     async fn get_expired_bubbles(
@@ -579,6 +613,19 @@ impl RepoEphemeralStore {
         self.inner()?.changesets_from_bubble(bubble_id).await
     }
 
+    /// Fetch the changesets within this repository that are in active
+    /// bubbles with the given label, along with their bubble IDs, most
+    /// recently created first.
+    pub async fn changesets_with_label(
+        &self,
+        label: &str,
+        limit: u32,
+    ) -> Result<Vec<(BubbleId, ChangesetId)>> {
+        self.inner()?
+            .changesets_with_label(&self.repo_id, label, limit)
+            .await
+    }
+
     /// Associate the given labels with the bubble corresponding to the input
     /// bubble ID.
     pub async fn add_bubble_labels(&self, bubble_id: BubbleId, labels: Vec<String>) -> Result<()> {
@@ -614,6 +661,9 @@ mod test {
     use memblob::Memblob;
     use metaconfig_types::BubbleDeletionMode;
     use metaconfig_types::PackFormat;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::THREES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
     use mononoke_types_mocks::repo::REPO_ZERO;
     use packblob::PackBlob;
     use repo_blobstore::RepoBlobstore;
@@ -1085,6 +1135,52 @@ mod test {
         Ok(())
     }
 
+    mononoke_queries! {
+        write InsertBubbleChangeset(
+            values: (repo_id: RepositoryId, cs_id: ChangesetId, bubble_id: BubbleId, gen: u64)
+        ) {
+            none,
+            "INSERT INTO ephemeral_bubble_changeset_mapping
+            (repo_id, cs_id, bubble_id, gen)
+            VALUES {values}"
+        }
+    }
+
+    #[fbinit::test]
+    async fn changesets_with_label_test(fb: FacebookInit) -> Result<()> {
+        let initial = Duration::from_secs(30 * 24 * 60 * 60);
+        let grace = Duration::from_secs(6 * 60 * 60);
+        let (_, _, _, eph) = bootstrap(fb, initial, grace, BubbleDeletionMode::MarkAndDelete)?;
+        let workspace = vec!["workspace".to_string()];
+        let bubble1 = eph.create_bubble(None, workspace.clone()).await?;
+        let bubble2 = eph.create_bubble(None, workspace).await?;
+        let bubble3 = eph.create_bubble(None, vec!["other".to_string()]).await?;
+        InsertBubbleChangeset::query(
+            &eph.inner()?.connections.write_connection,
+            &[
+                (&REPO_ZERO, &ONES_CSID, &bubble1.bubble_id(), &1),
+                (&REPO_ZERO, &TWOS_CSID, &bubble2.bubble_id(), &1),
+                (&REPO_ZERO, &THREES_CSID, &bubble3.bubble_id(), &1),
+            ],
+        )
+        .await?;
+        // Only changesets in bubbles with the label are listed, most
+        // recently created bubble first.
+        assert_eq!(
+            eph.changesets_with_label("workspace", 10).await?,
+            vec![
+                (bubble2.bubble_id(), TWOS_CSID),
+                (bubble1.bubble_id(), ONES_CSID),
+            ]
+        );
+        assert_eq!(
+            eph.changesets_with_label("workspace", 1).await?,
+            vec![(bubble2.bubble_id(), TWOS_CSID)]
+        );
+        assert!(eph.changesets_with_label("missing", 10).await?.is_empty());
+        Ok(())
+    }
+
     #[fbinit::test]
     async fn create_and_fetch_active_test(fb: FacebookInit) -> Result<()> {
         let initial = Duration::from_secs(30 * 24 * 60 * 60);
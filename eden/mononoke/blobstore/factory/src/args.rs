@@ -6,15 +6,21 @@
  */
 
 use std::num::NonZeroU32;
+use std::num::NonZeroU64;
 use std::num::NonZeroUsize;
+use std::time::Duration;
 
 use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Result;
 use arg_extensions::ArgDefaults;
 use clap::Args;
+use hotkeyblob::HotKeyOptions;
 use metaconfig_types::PackFormat;
+use mononoke_types::RepositoryId;
+use multiplexedblob_wal::ConsistencySamplerOptions;
 use rand_distr::Normal;
+use singleflightblob::SingleFlightScope;
 
 use crate::PutBehaviour;
 
@@ -104,6 +110,51 @@ pub struct BlobstoreArgs {
     /// Desired blobstore behaviour when a put is made to an existing key.
     #[clap(long)]
     pub blobstore_put_behaviour: Option<PutBehaviour>,
+
+    /// Log blobstore keys that are fetched more than this many times a
+    /// minute.
+    #[clap(long)]
+    pub blobstore_hot_key_threshold: Option<NonZeroU32>,
+
+    /// Log blobstore gets that take longer than this many milliseconds.
+    #[clap(long)]
+    pub blobstore_slow_get_threshold_ms: Option<u64>,
+
+    /// Cache up to this many hot keys in memory.  Requires
+    /// --blobstore-hot-key-threshold.
+    #[clap(long, requires = "blobstore-hot-key-threshold")]
+    pub blobstore_hot_key_cache_size: Option<NonZeroUsize>,
+
+    /// Make concurrent gets for the same blobstore key share a single get.
+    #[clap(long)]
+    pub blobstore_single_flight: bool,
+
+    /// Only share gets for the keys of these repos.  Requires
+    /// --blobstore-single-flight.
+    #[clap(long, requires = "blobstore-single-flight")]
+    pub blobstore_single_flight_repo_id: Vec<i32>,
+
+    /// Check one in this many keys written to WAL multiplexed blobstores
+    /// for presence in all of the multiplex's blobstores, and queue the
+    /// ones that are missing for healing.
+    #[clap(long)]
+    pub blobstore_consistency_sample_rate: Option<NonZeroU64>,
+
+    /// How long after a sampled key is written it should be present in
+    /// all blobstores.  Only used with --blobstore-consistency-sample-rate.
+    #[clap(long, default_value_t = 60)]
+    pub blobstore_consistency_sla_secs: u64,
+
+    /// The region this host is in.  Reads from WAL multiplexed blobstores
+    /// go to the blobstores configured in this region first.
+    #[clap(long)]
+    pub blobstore_local_region: Option<String>,
+
+    /// How long reads from the blobstores in the local region can take
+    /// before they are also sent to the other regions.  Only used with
+    /// --blobstore-local-region.
+    #[clap(long, requires = "blobstore-local-region")]
+    pub blobstore_read_hedge_delay_ms: Option<u64>,
 }
 
 impl BlobstoreArgs {
@@ -122,6 +173,44 @@ impl BlobstoreArgs {
         }
     }
 
+    pub fn hot_key_options(&self) -> HotKeyOptions {
+        HotKeyOptions {
+            hot_key_threshold: self.blobstore_hot_key_threshold,
+            slow_get_threshold: self
+                .blobstore_slow_get_threshold_ms
+                .map(Duration::from_millis),
+            hot_key_cache_size: self.blobstore_hot_key_cache_size,
+        }
+    }
+
+    pub fn single_flight_scope(&self) -> SingleFlightScope {
+        if !self.blobstore_single_flight {
+            SingleFlightScope::Disabled
+        } else if self.blobstore_single_flight_repo_id.is_empty() {
+            SingleFlightScope::All
+        } else {
+            SingleFlightScope::Repos(
+                self.blobstore_single_flight_repo_id
+                    .iter()
+                    .copied()
+                    .map(RepositoryId::new)
+                    .collect(),
+            )
+        }
+    }
+
+    pub fn consistency_sampler_options(&self) -> Option<ConsistencySamplerOptions> {
+        self.blobstore_consistency_sample_rate
+            .map(|sample_rate| ConsistencySamplerOptions {
+                sample_rate,
+                sla: Duration::from_secs(self.blobstore_consistency_sla_secs),
+            })
+    }
+
+    pub fn read_hedge_delay(&self) -> Option<Duration> {
+        self.blobstore_read_hedge_delay_ms.map(Duration::from_millis)
+    }
+
     pub fn get_delay_distribution(&self) -> Result<Option<Normal<f64>>> {
         delay_distribution(
             self.blobstore_get_mean_delay_secs,
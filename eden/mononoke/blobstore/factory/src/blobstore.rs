@@ -5,6 +5,7 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashSet;
 use std::num::NonZeroU64;
 use std::num::NonZeroUsize;
 use std::sync::Arc;
@@ -26,6 +27,7 @@ use cacheblob::CachelibBlobstoreOptions;
 use cached_config::ConfigStore;
 use chaosblob::ChaosBlobstore;
 use chaosblob::ChaosOptions;
+use context::CoreContext;
 use delayblob::DelayOptions;
 use delayblob::DelayedBlobstore;
 use fbinit::FacebookInit;
@@ -34,6 +36,8 @@ use futures::future;
 use futures::future::BoxFuture;
 use futures::future::FutureExt;
 use futures_watchdog::WatchdogExt;
+use hotkeyblob::HotKeyBlobstore;
+use hotkeyblob::HotKeyOptions;
 use logblob::LogBlob;
 #[cfg(fbcode_build)]
 use manifoldblob::ManifoldOptions;
@@ -49,6 +53,7 @@ use multiplexedblob::ScrubHandler;
 use multiplexedblob::ScrubOptions;
 use multiplexedblob::SrubWriteOnly;
 use multiplexedblob_wal::scrub::WalScrubBlobstore;
+use multiplexedblob_wal::ConsistencySamplerOptions;
 use multiplexedblob_wal::Scuba as WalScuba;
 use multiplexedblob_wal::WalMultiplexedBlobstore;
 use packblob::PackBlob;
@@ -57,6 +62,8 @@ use readonlyblob::ReadOnlyBlobstore;
 use samplingblob::ComponentSamplingHandler;
 use samplingblob::SamplingBlobstorePutOps;
 use scuba_ext::MononokeScubaSampleBuilder;
+use singleflightblob::SingleFlightBlobstore;
+use singleflightblob::SingleFlightScope;
 use slog::Logger;
 use sql_construct::SqlConstructFromShardedDatabaseConfig;
 use sql_ext::facebook::MysqlOptions;
@@ -72,6 +79,8 @@ pub struct BlobstoreOptions {
     pub chaos_options: ChaosOptions,
     pub delay_options: DelayOptions,
     pub throttle_options: ThrottleOptions,
+    pub hot_key_options: HotKeyOptions,
+    pub single_flight_scope: SingleFlightScope,
     #[cfg(fbcode_build)]
     pub manifold_options: ManifoldOptions,
     pub pack_options: PackOptions,
@@ -79,6 +88,9 @@ pub struct BlobstoreOptions {
     pub put_behaviour: PutBehaviour,
     pub scrub_options: Option<ScrubOptions>,
     pub sqlblob_mysql_options: MysqlOptions,
+    pub consistency_sampler_options: Option<ConsistencySamplerOptions>,
+    pub local_region: Option<String>,
+    pub read_hedge_delay: Option<Duration>,
 }
 
 impl BlobstoreOptions {
@@ -96,6 +108,9 @@ impl BlobstoreOptions {
             chaos_options,
             delay_options,
             throttle_options,
+            // These are added via the builder methods
+            hot_key_options: HotKeyOptions::default(),
+            single_flight_scope: SingleFlightScope::default(),
             #[cfg(fbcode_build)]
             manifold_options,
             pack_options,
@@ -105,6 +120,48 @@ impl BlobstoreOptions {
             // These are added via the builder methods
             scrub_options: None,
             sqlblob_mysql_options,
+            consistency_sampler_options: None,
+            local_region: None,
+            read_hedge_delay: None,
+        }
+    }
+
+    pub fn with_hot_key_options(self, hot_key_options: HotKeyOptions) -> Self {
+        Self {
+            hot_key_options,
+            ..self
+        }
+    }
+
+    pub fn with_single_flight_scope(self, single_flight_scope: SingleFlightScope) -> Self {
+        Self {
+            single_flight_scope,
+            ..self
+        }
+    }
+
+    pub fn with_consistency_sampler_options(
+        self,
+        consistency_sampler_options: Option<ConsistencySamplerOptions>,
+    ) -> Self {
+        Self {
+            consistency_sampler_options,
+            ..self
+        }
+    }
+
+    /// Set the region this host is in, so that multiplexed reads go to the
+    /// blobstores in this region first, and are hedged to the others after
+    /// `read_hedge_delay`.
+    pub fn with_local_region(
+        self,
+        local_region: Option<String>,
+        read_hedge_delay: Option<Duration>,
+    ) -> Self {
+        Self {
+            local_region,
+            read_hedge_delay,
+            ..self
         }
     }
 
@@ -193,7 +250,20 @@ pub fn make_blobstore<'a>(
         .await?;
         // Workaround for trait A {} trait B:A {} but Arc<dyn B> is not a Arc<dyn A>
         // See https://github.com/rust-lang/rfcs/issues/2765 if interested
-        Ok(Arc::new(store) as Arc<dyn Blobstore>)
+        let mut store = Arc::new(store) as Arc<dyn Blobstore>;
+        // Gets are coalesced and hot keys detected here rather than in
+        // make_blobstore_put_ops, so that gets to a multiplex are only handled
+        // once.  Hot keys are detected on the gets before they are coalesced.
+        if blobstore_options.single_flight_scope.is_enabled() {
+            store = Arc::new(SingleFlightBlobstore::new(
+                store,
+                blobstore_options.single_flight_scope.clone(),
+            ));
+        }
+        if blobstore_options.hot_key_options.has_detection() {
+            store = Arc::new(HotKeyBlobstore::new(store, blobstore_options.hot_key_options));
+        }
+        Ok(store)
     }
     .boxed()
 }
@@ -360,6 +430,51 @@ async fn make_manifold_blobstore(
     unimplemented!("This is implemented only for fbcode_build")
 }
 
+#[cfg(fbcode_build)]
+async fn make_s3_blobstore(
+    fb: FacebookInit,
+    blobconfig: BlobConfig,
+    blobstore_options: &BlobstoreOptions,
+    logger: &Logger,
+) -> Result<Arc<dyn BlobstoreUnlinkOps>, Error> {
+    if let BlobConfig::S3 {
+        bucket,
+        keychain_group,
+        region_name,
+        endpoint,
+        num_concurrent_operations,
+        secret_name,
+    } = blobconfig
+    {
+        ::s3blob::S3Blob::new(
+            fb,
+            bucket,
+            keychain_group,
+            secret_name,
+            region_name,
+            endpoint,
+            blobstore_options.put_behaviour,
+            logger,
+            num_concurrent_operations,
+        )
+        .await
+        .context(ErrorKind::StateOpen)
+        .map(|store| Arc::new(store) as Arc<dyn BlobstoreUnlinkOps>)
+    } else {
+        bail!("Not an S3 blobstore")
+    }
+}
+
+#[cfg(not(fbcode_build))]
+async fn make_s3_blobstore(
+    _fb: FacebookInit,
+    _blobconfig: BlobConfig,
+    _blobstore_options: &BlobstoreOptions,
+    _logger: &Logger,
+) -> Result<Arc<dyn BlobstoreUnlinkOps>, Error> {
+    unimplemented!("This is implemented only for fbcode_build")
+}
+
 async fn make_files_blobstore(
     blobconfig: BlobConfig,
     blobstore_options: &BlobstoreOptions,
@@ -372,7 +487,7 @@ async fn make_files_blobstore(
     }
 }
 
-async fn make_blobstore_with_link<'a>(
+pub(crate) async fn make_blobstore_with_link<'a>(
     fb: FacebookInit,
     blobconfig: BlobConfig,
     readonly_storage: ReadOnlyStorage,
@@ -401,6 +516,11 @@ async fn make_blobstore_with_link<'a>(
         Files { .. } => make_files_blobstore(blobconfig, blobstore_options)
             .await
             .map(|store| Arc::new(store) as Arc<dyn BlobstoreUnlinkOps>),
+        S3 { .. } => {
+            make_s3_blobstore(fb, blobconfig, blobstore_options, logger)
+                .watched(logger)
+                .await
+        }
         _ => bail!("Not a physical blobstore"),
     }
 }
@@ -493,45 +613,10 @@ fn make_blobstore_put_ops<'a>(
             Files { .. } => make_files_blobstore(blobconfig, blobstore_options)
                 .await
                 .map(|store| Arc::new(store) as Arc<dyn BlobstorePutOps>)?,
-            S3 {
-                bucket,
-                keychain_group,
-                region_name,
-                endpoint,
-                num_concurrent_operations,
-                secret_name,
-            } => {
-                #[cfg(fbcode_build)]
-                {
-                    ::s3blob::S3Blob::new(
-                        fb,
-                        bucket,
-                        keychain_group,
-                        secret_name,
-                        region_name,
-                        endpoint,
-                        blobstore_options.put_behaviour,
-                        logger,
-                        num_concurrent_operations,
-                    )
-                    .watched(logger)
-                    .await
-                    .context(ErrorKind::StateOpen)
-                    .map(|store| Arc::new(store) as Arc<dyn BlobstorePutOps>)?
-                }
-                #[cfg(not(fbcode_build))]
-                {
-                    let _ = (
-                        bucket,
-                        keychain_group,
-                        secret_name,
-                        region_name,
-                        endpoint,
-                        num_concurrent_operations,
-                    );
-                    unimplemented!("This is implemented only for fbcode_build")
-                }
-            }
+            S3 { .. } => make_s3_blobstore(fb, blobconfig, blobstore_options, logger)
+                .watched(logger)
+                .await
+                .map(|store| Arc::new(store) as Arc<dyn BlobstorePutOps>)?,
 
             // Special case
             Disabled => {
@@ -547,8 +632,16 @@ fn make_blobstore_put_ops<'a>(
                 inner_blobstores_scuba_table,
                 multiplex_scuba_table,
                 scuba_sample_rate,
+                blobstore_regions,
             } => {
                 needs_wrappers = false;
+                let local_blobstores = match &blobstore_options.local_region {
+                    Some(local_region) => blobstore_regions
+                        .into_iter()
+                        .filter_map(|(id, region)| (&region == local_region).then_some(id))
+                        .collect(),
+                    None => HashSet::new(),
+                };
                 make_multiplexed_wal(
                     fb,
                     multiplex_id,
@@ -558,6 +651,7 @@ fn make_blobstore_put_ops<'a>(
                     scuba_sample_rate,
                     blobstores,
                     write_quorum,
+                    local_blobstores,
                     mysql_options,
                     readonly_storage,
                     blobstore_options,
@@ -677,6 +771,7 @@ async fn make_multiplexed_wal<'a>(
     scuba_sample_rate: NonZeroU64,
     inner_config: Vec<(BlobstoreId, MultiplexedStoreType, BlobConfig)>,
     write_quorum: usize,
+    local_blobstores: HashSet<BlobstoreId>,
     mysql_options: &'a MysqlOptions,
     readonly_storage: ReadOnlyStorage,
     blobstore_options: &'a BlobstoreOptions,
@@ -724,15 +819,28 @@ async fn make_multiplexed_wal<'a>(
                 scrub_handler.clone(),
             )?) as Arc<dyn BlobstorePutOps>
         }
-        None => Arc::new(WalMultiplexedBlobstore::new(
-            multiplex_id,
-            wal_queue,
-            normal_components,
-            write_only_components,
-            write_quorum,
-            None, // use default timeouts
-            scuba,
-        )?) as Arc<dyn BlobstorePutOps>,
+        // Scrubbing reads from all the blobstores, so only plain reads prefer
+        // the local ones.
+        None => {
+            let blobstore = WalMultiplexedBlobstore::new(
+                multiplex_id,
+                wal_queue,
+                normal_components,
+                write_only_components,
+                write_quorum,
+                None, // use default timeouts
+                scuba,
+            )?
+            .with_local_blobstores(&local_blobstores, blobstore_options.read_hedge_delay);
+            let blobstore = match &blobstore_options.consistency_sampler_options {
+                Some(options) => blobstore.with_consistency_sampler(
+                    CoreContext::new_for_bulk_processing(fb, logger.clone()),
+                    options.clone(),
+                ),
+                None => blobstore,
+            };
+            Arc::new(blobstore) as Arc<dyn BlobstorePutOps>
+        }
     };
 
     Ok(blobstore)
@@ -14,6 +14,7 @@ mod args;
 mod blobstore;
 #[cfg(fbcode_build)]
 mod facebook;
+mod probe;
 mod sql;
 
 pub use ::blobstore::PutBehaviour;
@@ -22,6 +23,7 @@ pub use blobstore_stats::OperationType;
 pub use cacheblob::CachelibBlobstoreOptions;
 pub use chaosblob::ChaosOptions;
 pub use delayblob::DelayOptions;
+pub use hotkeyblob::HotKeyOptions;
 #[cfg(fbcode_build)]
 pub use facebook::ManifoldArgs;
 #[cfg(fbcode_build)]
@@ -33,6 +35,7 @@ pub use multiplexedblob::ScrubAction;
 pub use multiplexedblob::ScrubHandler;
 pub use packblob::PackOptions;
 pub use samplingblob::ComponentSamplingHandler;
+pub use singleflightblob::SingleFlightScope;
 pub use throttledblob::ThrottleOptions;
 
 pub use crate::args::BlobstoreArgDefaults;
@@ -43,6 +46,9 @@ pub use crate::blobstore::make_packblob;
 pub use crate::blobstore::make_sql_blobstore;
 pub use crate::blobstore::make_sql_blobstore_xdb;
 pub use crate::blobstore::BlobstoreOptions;
+pub use crate::probe::probe_blobstores;
+pub use crate::probe::ProbeResult;
+pub use crate::probe::PROBE_KEY_PREFIX;
 pub use crate::sql::make_metadata_sql_factory;
 pub use crate::sql::MetadataSqlFactory;
 pub use crate::sql::SqlTierInfo;
@@ -0,0 +1,342 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Probing of the connectivity of configured blobstores.
+//!
+//! Parsing a blobstore config can't tell whether the credentials it refers to
+//! are valid or whether the stores it names are reachable.  Probing writes,
+//! reads back and deletes a small blob in each physical store, and reads from
+//! the write-ahead log of each multiplex, so that those problems are found
+//! before the config is deployed.
+
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use blobstore::BlobstoreBytes;
+use blobstore::BlobstoreUnlinkOps;
+use blobstore_sync_queue::BlobstoreWal;
+use blobstore_sync_queue::SqlBlobstoreWal;
+use cached_config::ConfigStore;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use metaconfig_types::BlobConfig;
+use metaconfig_types::MultiplexId;
+use metaconfig_types::ShardedDatabaseConfig;
+use mononoke_types::Timestamp;
+use slog::Logger;
+use sql_construct::SqlConstructFromShardedDatabaseConfig;
+use sql_ext::facebook::MysqlOptions;
+
+use crate::blobstore::make_blobstore_with_link;
+use crate::BlobstoreOptions;
+use crate::ReadOnlyStorage;
+
+/// The keyspace probe blobs are written to, which nothing else uses.
+pub const PROBE_KEY_PREFIX: &str = "mononoke_storage_probe.";
+
+/// The outcome of probing one physical blobstore or write-ahead log.
+pub struct ProbeResult {
+    /// Where the store is in the config, e.g. `name/3` for the component
+    /// with id 3 of the multiplex of the storage config `name`, or
+    /// `name/wal` for the write-ahead log of that multiplex.
+    pub location: String,
+    /// A description of the store.
+    pub store: String,
+    pub result: Result<()>,
+}
+
+/// Something in a config that can be probed.
+#[derive(Debug)]
+enum ProbeTarget {
+    Blobstore(BlobConfig),
+    Wal(MultiplexId, ShardedDatabaseConfig),
+}
+
+/// The physical blobstores and write-ahead logs in a config, with where
+/// they are in it.
+fn probe_targets(location: String, blobconfig: BlobConfig) -> Vec<(String, ProbeTarget)> {
+    use BlobConfig::*;
+    match blobconfig {
+        Disabled => vec![],
+        Logging { blobconfig, .. } | Pack { blobconfig, .. } => {
+            probe_targets(location, *blobconfig)
+        }
+        MultiplexedWal {
+            multiplex_id,
+            blobstores,
+            queue_db,
+            ..
+        } => {
+            let wal = ProbeTarget::Wal(multiplex_id, queue_db);
+            let mut targets = vec![(format!("{}/wal", location), wal)];
+            for (id, _, blobconfig) in blobstores {
+                targets.extend(probe_targets(format!("{}/{}", location, id), blobconfig));
+            }
+            targets
+        }
+        _ => vec![(location, ProbeTarget::Blobstore(blobconfig))],
+    }
+}
+
+fn describe(target: &ProbeTarget) -> String {
+    use BlobConfig::*;
+    let blobconfig = match target {
+        ProbeTarget::Blobstore(blobconfig) => blobconfig,
+        ProbeTarget::Wal(multiplex_id, _) => {
+            return format!("write-ahead log of multiplex {}", multiplex_id);
+        }
+    };
+    match blobconfig {
+        Files { path } => format!("files at {}", path.display()),
+        Sqlite { path } => format!("sqlite at {}", path.display()),
+        Manifold { bucket, prefix } | ManifoldWithTtl { bucket, prefix, .. } => {
+            format!("manifold bucket {} with prefix '{}'", bucket, prefix)
+        }
+        Mysql { .. } => "mysql".to_string(),
+        S3 {
+            bucket, endpoint, ..
+        } => format!("s3 bucket {} at {}", bucket, endpoint),
+        _ => format!("{:?}", blobconfig),
+    }
+}
+
+async fn probe(ctx: &CoreContext, blobstore: &dyn BlobstoreUnlinkOps, key: &str) -> Result<()> {
+    let value = BlobstoreBytes::from_bytes(key.as_bytes().to_vec());
+    blobstore
+        .put(ctx, key.to_string(), value.clone())
+        .await
+        .context("Write failed")?;
+    let read = async {
+        let read = blobstore
+            .get(ctx, key)
+            .await
+            .context("Read failed")?
+            .context("Read found nothing where the probe blob was written")?;
+        if read.as_bytes() != &value {
+            bail!("Read returned different contents from those written");
+        }
+        anyhow::Ok(())
+    }
+    .await;
+    // Delete the probe blob even if reading it back failed.
+    let delete = blobstore.unlink(ctx, key).await.context("Delete failed");
+    read.and(delete)
+}
+
+/// Write, read back and delete a blob in the probe keyspace of each of the
+/// physical blobstores in `blobconfig`, and read from the write-ahead log of
+/// each of its multiplexes.  A failure to probe one store doesn't stop the
+/// others from being probed.
+pub async fn probe_blobstores(
+    fb: FacebookInit,
+    ctx: &CoreContext,
+    name: &str,
+    blobconfig: BlobConfig,
+    mysql_options: &MysqlOptions,
+    blobstore_options: &BlobstoreOptions,
+    logger: &Logger,
+    config_store: &ConfigStore,
+) -> Vec<ProbeResult> {
+    let nonce = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos());
+    let key = format!("{}{}.{}", PROBE_KEY_PREFIX, std::process::id(), nonce);
+    let mut results = Vec::new();
+    for (location, target) in probe_targets(name.to_string(), blobconfig) {
+        let store = describe(&target);
+        let result = async {
+            match target {
+                ProbeTarget::Blobstore(blobconfig) => {
+                    let blobstore = make_blobstore_with_link(
+                        fb,
+                        blobconfig,
+                        ReadOnlyStorage(false),
+                        blobstore_options,
+                        logger,
+                        config_store,
+                    )
+                    .await
+                    .context("Failed to open blobstore")?;
+                    probe(ctx, blobstore.as_ref(), &key).await
+                }
+                ProbeTarget::Wal(multiplex_id, queue_db) => {
+                    let wal = SqlBlobstoreWal::with_sharded_database_config(
+                        fb,
+                        &queue_db,
+                        mysql_options,
+                        true,
+                    )
+                    .context("Failed to open write-ahead log")?;
+                    wal.read(ctx, &multiplex_id, &Timestamp::now(), 1)
+                        .await
+                        .context("Read failed")?;
+                    Ok(())
+                }
+            }
+        }
+        .await;
+        results.push(ProbeResult {
+            location,
+            store,
+            result,
+        });
+    }
+    results
+}
+
+#[cfg(test)]
+mod test {
+    use std::fmt;
+    use std::num::NonZeroU64;
+
+    use async_trait::async_trait;
+    use blobstore::Blobstore;
+    use blobstore::BlobstoreGetData;
+    use blobstore::BlobstorePutOps;
+    use blobstore::OverwriteStatus;
+    use blobstore::PutBehaviour;
+    use fileblob::Fileblob;
+    use metaconfig_types::BlobstoreId;
+    use metaconfig_types::LocalDatabaseConfig;
+    use metaconfig_types::MultiplexedStoreType;
+    use sqlblob::get_test_config_store;
+    use sqlblob::Sqlblob;
+
+    use super::*;
+
+    /// A blobstore whose reads always fail.
+    #[derive(Debug)]
+    struct UnreadableBlob(Fileblob);
+
+    impl fmt::Display for UnreadableBlob {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "UnreadableBlob")
+        }
+    }
+
+    #[async_trait]
+    impl Blobstore for UnreadableBlob {
+        async fn get<'a>(
+            &'a self,
+            _ctx: &'a CoreContext,
+            _key: &'a str,
+        ) -> Result<Option<BlobstoreGetData>> {
+            bail!("Unreadable")
+        }
+
+        async fn put<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: String,
+            value: BlobstoreBytes,
+        ) -> Result<()> {
+            self.0.put(ctx, key, value).await
+        }
+    }
+
+    #[async_trait]
+    impl BlobstorePutOps for UnreadableBlob {
+        async fn put_explicit<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: String,
+            value: BlobstoreBytes,
+            put_behaviour: PutBehaviour,
+        ) -> Result<OverwriteStatus> {
+            self.0.put_explicit(ctx, key, value, put_behaviour).await
+        }
+
+        async fn put_with_status<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: String,
+            value: BlobstoreBytes,
+        ) -> Result<OverwriteStatus> {
+            self.0.put_with_status(ctx, key, value).await
+        }
+    }
+
+    #[async_trait]
+    impl BlobstoreUnlinkOps for UnreadableBlob {
+        async fn unlink<'a>(&'a self, ctx: &'a CoreContext, key: &'a str) -> Result<()> {
+            self.0.unlink(ctx, key).await
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_probe(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let dir = tempfile::tempdir()?;
+        let key = format!("{}test", PROBE_KEY_PREFIX);
+
+        let files = Fileblob::create(dir.path().join("files"), PutBehaviour::Overwrite)?;
+        probe(&ctx, &files, &key).await?;
+        assert!(files.get(&ctx, &key).await?.is_none());
+
+        let (_test_source, config_store) = get_test_config_store();
+        let sqlite =
+            Sqlblob::with_sqlite_in_memory(PutBehaviour::Overwrite, &config_store, false, 0)?;
+        probe(&ctx, &sqlite, &key).await?;
+        assert!(sqlite.get(&ctx, &key).await?.is_none());
+
+        // The probe blob is deleted even if reading it back fails.
+        let unreadable = UnreadableBlob(Fileblob::create(
+            dir.path().join("unreadable"),
+            PutBehaviour::Overwrite,
+        )?);
+        let err = probe(&ctx, &unreadable, &key).await.unwrap_err();
+        assert_eq!(format!("{:#}", err), "Read failed: Unreadable");
+        assert!(unreadable.0.get(&ctx, &key).await?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_probe_targets() {
+        let files = BlobConfig::Files {
+            path: "/tmp/files".into(),
+        };
+        let sqlite = BlobConfig::Sqlite {
+            path: "/tmp/sqlite".into(),
+        };
+        let multiplex = BlobConfig::MultiplexedWal {
+            multiplex_id: MultiplexId::new(1),
+            blobstores: vec![
+                (BlobstoreId::new(1), MultiplexedStoreType::Normal, files),
+                (BlobstoreId::new(2), MultiplexedStoreType::Normal, sqlite),
+            ],
+            write_quorum: 1,
+            queue_db: ShardedDatabaseConfig::Local(LocalDatabaseConfig {
+                path: "/tmp/queue".into(),
+            }),
+            inner_blobstores_scuba_table: None,
+            multiplex_scuba_table: None,
+            scuba_sample_rate: NonZeroU64::new(1).unwrap(),
+            blobstore_regions: Default::default(),
+        };
+
+        let targets = probe_targets("storage".to_string(), multiplex)
+            .into_iter()
+            .map(|(location, target)| (location, describe(&target)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            targets,
+            vec![
+                (
+                    "storage/wal".to_string(),
+                    "write-ahead log of multiplex 1".to_string()
+                ),
+                ("storage/1".to_string(), "files at /tmp/files".to_string()),
+                ("storage/2".to_string(), "sqlite at /tmp/sqlite".to_string()),
+            ]
+        );
+        assert!(probe_targets("storage".to_string(), BlobConfig::Disabled).is_empty());
+    }
+}
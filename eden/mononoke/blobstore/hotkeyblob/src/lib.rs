@@ -0,0 +1,312 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::fmt;
+use std::num::NonZeroU32;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use blobstore::Blobstore;
+use blobstore::BlobstoreGetData;
+use blobstore::BlobstoreIsPresent;
+use context::CoreContext;
+use futures_stats::TimedFutureExt;
+use mononoke_types::BlobstoreBytes;
+use slog::warn;
+use stats::prelude::*;
+
+define_stats! {
+    prefix = "mononoke.blobstore.hotkey";
+    hot_keys: timeseries(Sum),
+    slow_gets: timeseries(Sum),
+    cache_hits: timeseries(Sum),
+}
+
+/// Hot keys are counted over windows of this length.
+const WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HotKeyOptions {
+    /// Report keys that are fetched more than this many times a minute.
+    pub hot_key_threshold: Option<NonZeroU32>,
+    /// Report gets that take longer than this.
+    pub slow_get_threshold: Option<Duration>,
+    /// Keep up to this many hot keys in memory, and serve them from memory
+    /// for the rest of the minute they became hot in.
+    pub hot_key_cache_size: Option<NonZeroUsize>,
+}
+
+impl HotKeyOptions {
+    pub fn has_detection(&self) -> bool {
+        self.hot_key_threshold.is_some() || self.slow_get_threshold.is_some()
+    }
+}
+
+struct Window {
+    start: Instant,
+    counts: HashMap<String, u32>,
+    cache: HashMap<String, BlobstoreGetData>,
+}
+
+impl Window {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            counts: HashMap::new(),
+            cache: HashMap::new(),
+        }
+    }
+}
+
+enum GetAccess {
+    Cached(BlobstoreGetData),
+    Fetch { count: u32 },
+}
+
+/// A blobstore that detects hot keys and slow gets, and logs them with the
+/// context of the request that caused them.  Hot keys can optionally be
+/// cached in memory.
+///
+/// Every key fetched in the current minute is counted, so this is intended
+/// to be enabled while investigating blobstore load rather than all the time.
+pub struct HotKeyBlobstore<T> {
+    inner: T,
+    options: HotKeyOptions,
+    window: Mutex<Window>,
+}
+
+impl<T> HotKeyBlobstore<T> {
+    pub fn new(inner: T, options: HotKeyOptions) -> Self {
+        Self {
+            inner,
+            options,
+            window: Mutex::new(Window::new()),
+        }
+    }
+
+    fn access(&self, key: &str) -> GetAccess {
+        if self.options.hot_key_threshold.is_none() {
+            return GetAccess::Fetch { count: 0 };
+        }
+        let mut window = self.window.lock().expect("lock poisoned");
+        if window.start.elapsed() >= WINDOW {
+            *window = Window::new();
+        }
+        if let Some(data) = window.cache.get(key) {
+            return GetAccess::Cached(data.clone());
+        }
+        let count = window.counts.entry(key.to_string()).or_insert(0);
+        *count += 1;
+        GetAccess::Fetch { count: *count }
+    }
+
+    fn cache(&self, key: &str, data: &BlobstoreGetData) {
+        if let Some(cache_size) = self.options.hot_key_cache_size {
+            let mut window = self.window.lock().expect("lock poisoned");
+            if window.cache.len() < cache_size.get() {
+                window.cache.insert(key.to_string(), data.clone());
+            }
+        }
+    }
+
+    fn invalidate(&self, key: &str) {
+        let mut window = self.window.lock().expect("lock poisoned");
+        window.cache.remove(key);
+    }
+}
+
+fn log_offender(ctx: &CoreContext, key: &str, problem: String) {
+    let metadata = ctx.metadata();
+    warn!(
+        ctx.logger(),
+        "Blobstore key {} {}", key, problem;
+        "session_id" => metadata.session_id().as_str(),
+        "client_hostname" => metadata.client_hostname(),
+        "unix_name" => metadata.unix_name(),
+    );
+}
+
+impl<T: fmt::Display> fmt::Display for HotKeyBlobstore<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HotKeyBlobstore<{}>", &self.inner)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for HotKeyBlobstore<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HotKeyBlobstore")
+            .field("inner", &self.inner)
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<T: Blobstore> Blobstore for HotKeyBlobstore<T> {
+    async fn get<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<Option<BlobstoreGetData>> {
+        let count = match self.access(key) {
+            GetAccess::Cached(data) => {
+                STATS::cache_hits.add_value(1);
+                return Ok(Some(data));
+            }
+            GetAccess::Fetch { count } => count,
+        };
+        let threshold = self.options.hot_key_threshold.map_or(u32::MAX, NonZeroU32::get);
+        let is_hot = count > threshold;
+        // Only report each hot key once per window.
+        if count == threshold.saturating_add(1) {
+            STATS::hot_keys.add_value(1);
+            log_offender(ctx, key, format!("fetched {} times this minute", count));
+        }
+
+        let (stats, result) = self.inner.get(ctx, key).timed().await;
+
+        if let Some(slow_get_threshold) = self.options.slow_get_threshold {
+            if stats.completion_time > slow_get_threshold {
+                STATS::slow_gets.add_value(1);
+                log_offender(
+                    ctx,
+                    key,
+                    format!("took {}ms to fetch", stats.completion_time.as_millis()),
+                );
+            }
+        }
+        if is_hot {
+            if let Ok(Some(data)) = &result {
+                self.cache(key, data);
+            }
+        }
+
+        result
+    }
+
+    async fn put<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<()> {
+        self.invalidate(&key);
+        let result = self.inner.put(ctx, key.clone(), value).await;
+        // A get that started before the put may have cached the old value.
+        self.invalidate(&key);
+        result
+    }
+
+    async fn is_present<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<BlobstoreIsPresent> {
+        self.inner.is_present(ctx, key).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    use borrowed::borrowed;
+    use fbinit::FacebookInit;
+    use memblob::Memblob;
+
+    use super::*;
+
+    /// Blobstore that counts the gets that reach it.
+    #[derive(Debug, Default)]
+    struct CountingBlobstore {
+        inner: Memblob,
+        gets: AtomicUsize,
+    }
+
+    impl fmt::Display for CountingBlobstore {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "CountingBlobstore")
+        }
+    }
+
+    #[async_trait]
+    impl Blobstore for CountingBlobstore {
+        async fn get<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: &'a str,
+        ) -> Result<Option<BlobstoreGetData>> {
+            self.gets.fetch_add(1, Ordering::Relaxed);
+            self.inner.get(ctx, key).await
+        }
+
+        async fn put<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: String,
+            value: BlobstoreBytes,
+        ) -> Result<()> {
+            self.inner.put(ctx, key, value).await
+        }
+
+        async fn is_present<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: &'a str,
+        ) -> Result<BlobstoreIsPresent> {
+            self.inner.is_present(ctx, key).await
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_hot_key_cache(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let key = "foobar";
+
+        let blobstore = HotKeyBlobstore::new(
+            CountingBlobstore::default(),
+            HotKeyOptions {
+                hot_key_threshold: NonZeroU32::new(2),
+                slow_get_threshold: None,
+                hot_key_cache_size: NonZeroUsize::new(10),
+            },
+        );
+        blobstore
+            .put(ctx, key.to_owned(), BlobstoreBytes::from_bytes("before"))
+            .await?;
+
+        // The third get makes the key hot, so it is cached and later gets
+        // don't reach the inner blobstore.
+        for _ in 0..5 {
+            let value = blobstore.get(ctx, key).await?;
+            assert_eq!(
+                value.map(|v| v.into_bytes()),
+                Some(BlobstoreBytes::from_bytes("before"))
+            );
+        }
+        assert_eq!(blobstore.inner.gets.load(Ordering::Relaxed), 3);
+
+        // Writing the key invalidates the cache.
+        blobstore
+            .put(ctx, key.to_owned(), BlobstoreBytes::from_bytes("after"))
+            .await?;
+        let value = blobstore.get(ctx, key).await?;
+        assert_eq!(
+            value.map(|v| v.into_bytes()),
+            Some(BlobstoreBytes::from_bytes("after"))
+        );
+        assert_eq!(blobstore.inner.gets.load(Ordering::Relaxed), 4);
+        Ok(())
+    }
+}
@@ -0,0 +1,256 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::num::NonZeroU64;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Result;
+use blobstore::BlobstoreIsPresent;
+use blobstore_sync_queue::BlobstoreWal;
+use blobstore_sync_queue::BlobstoreWalEntry;
+use context::CoreContext;
+use futures::future;
+use futures::stream;
+use futures::StreamExt;
+use metaconfig_types::BlobstoreId;
+use metaconfig_types::MultiplexId;
+use mononoke_types::Timestamp;
+use rand::Rng;
+use scuba_ext::MononokeScubaSampleBuilder;
+use slog::warn;
+use stats::prelude::*;
+use tokio::task::JoinHandle;
+
+use crate::timed::TimedStore;
+
+define_stats! {
+    prefix = "mononoke.blobstore.multiplex_consistency";
+    checked: dynamic_timeseries(
+        "{}.{}.checked",
+        (multiplex_id: String, blobstore_id: String);
+        Rate, Sum
+    ),
+    missing: dynamic_timeseries(
+        "{}.{}.missing",
+        (multiplex_id: String, blobstore_id: String);
+        Rate, Sum
+    ),
+    score_ppm: dynamic_singleton_counter(
+        "{}.{}.score_ppm",
+        (multiplex_id: String, blobstore_id: String)
+    ),
+}
+
+/// Keys waiting for their SLA to pass are dropped beyond this many, so that
+/// a burst of writes can't make the sampler use unbounded memory.
+const MAX_PENDING_KEYS: usize = 10_000;
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const CHECK_CONCURRENCY: usize = 100;
+
+#[derive(Clone, Debug)]
+pub struct ConsistencySamplerOptions {
+    /// Check one in this many written keys.
+    pub sample_rate: NonZeroU64,
+    /// How long after a key is written it should be present in every
+    /// blobstore of the multiplex.
+    pub sla: Duration,
+}
+
+struct PendingKey {
+    key: String,
+    blob_size: u64,
+    written_at: Instant,
+}
+
+#[derive(Default)]
+struct StoreCounts {
+    checked: u64,
+    missing: u64,
+}
+
+impl StoreCounts {
+    fn score(&self) -> f64 {
+        if self.checked == 0 {
+            1.0
+        } else {
+            1.0 - self.missing as f64 / self.checked as f64
+        }
+    }
+}
+
+/// Checks a random sample of the keys written through a multiplex for
+/// presence in each of its blobstores once the SLA for them to be written
+/// everywhere has passed.
+///
+/// Each blobstore gets a score of the fraction of sampled keys it had, and
+/// keys that are missing from any blobstore are logged to the WAL again so
+/// that the healer repairs them.
+pub(crate) struct ConsistencySampler {
+    multiplex_id: MultiplexId,
+    wal_queue: Arc<dyn BlobstoreWal>,
+    /// All the blobstores of the multiplex, including the write-only ones.
+    blobstores: Vec<TimedStore>,
+    options: ConsistencySamplerOptions,
+    pending: Mutex<VecDeque<PendingKey>>,
+    counts: Mutex<HashMap<BlobstoreId, StoreCounts>>,
+}
+
+impl ConsistencySampler {
+    pub(crate) fn new(
+        multiplex_id: MultiplexId,
+        wal_queue: Arc<dyn BlobstoreWal>,
+        blobstores: Vec<TimedStore>,
+        options: ConsistencySamplerOptions,
+    ) -> Self {
+        Self {
+            multiplex_id,
+            wal_queue,
+            blobstores,
+            options,
+            pending: Mutex::new(VecDeque::new()),
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check the sampled keys whose SLA has passed every `CHECK_INTERVAL`,
+    /// until the sampler is dropped.
+    pub(crate) fn spawn(self: Arc<Self>, ctx: CoreContext) -> JoinHandle<()> {
+        let sampler = Arc::downgrade(&self);
+        drop(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                let sampler = match sampler.upgrade() {
+                    Some(sampler) => sampler,
+                    None => break,
+                };
+                if let Err(e) = sampler.check_due(&ctx).await {
+                    warn!(
+                        ctx.logger(),
+                        "Failed to queue inconsistent keys for healing: {:#}", e
+                    );
+                }
+            }
+        })
+    }
+
+    /// Consider a key that was just written for sampling.
+    pub(crate) fn record_put(&self, key: &str, blob_size: u64) {
+        if rand::thread_rng().gen_range(0..self.options.sample_rate.get()) != 0 {
+            return;
+        }
+        let mut pending = self.pending.lock().expect("lock poisoned");
+        if pending.len() < MAX_PENDING_KEYS {
+            pending.push_back(PendingKey {
+                key: key.to_string(),
+                blob_size,
+                written_at: Instant::now(),
+            });
+        }
+    }
+
+    fn take_due(&self) -> Vec<PendingKey> {
+        let mut pending = self.pending.lock().expect("lock poisoned");
+        let mut due = Vec::new();
+        while let Some(key) = pending.front() {
+            if key.written_at.elapsed() < self.options.sla {
+                break;
+            }
+            due.extend(pending.pop_front());
+        }
+        due
+    }
+
+    /// Check the sampled keys whose SLA has passed, and queue the ones that
+    /// are missing from any blobstore for healing.
+    pub(crate) async fn check_due(&self, ctx: &CoreContext) -> Result<()> {
+        let due = self.take_due();
+        if due.is_empty() {
+            return Ok(());
+        }
+        let missing = stream::iter(due)
+            .map(|pending| self.check_key(ctx, pending))
+            .buffer_unordered(CHECK_CONCURRENCY)
+            .filter_map(future::ready)
+            .collect::<Vec<_>>()
+            .await;
+
+        let multiplex_id = self.multiplex_id.to_string();
+        for (blobstore_id, counts) in self.counts.lock().expect("lock poisoned").iter() {
+            STATS::score_ppm.set_value(
+                ctx.fb,
+                (counts.score() * 1_000_000.0) as i64,
+                (multiplex_id.clone(), blobstore_id.to_string()),
+            );
+        }
+
+        if !missing.is_empty() {
+            self.wal_queue.log_many(ctx, missing).await?;
+        }
+        Ok(())
+    }
+
+    /// Check a key for presence in every blobstore, and return the WAL entry
+    /// to heal it with if it is missing from any of them.
+    async fn check_key(
+        &self,
+        ctx: &CoreContext,
+        pending: PendingKey,
+    ) -> Option<BlobstoreWalEntry> {
+        let results = future::join_all(self.blobstores.iter().map(|bs| {
+            bs.is_present(ctx, &pending.key, MononokeScubaSampleBuilder::with_discard())
+        }))
+        .await;
+
+        let multiplex_id = self.multiplex_id.to_string();
+        let mut counts = self.counts.lock().expect("lock poisoned");
+        let mut any_missing = false;
+        for (blobstore_id, result) in results {
+            // Failed checks say nothing about whether the blobstore has the key.
+            let missing = match result {
+                Ok(BlobstoreIsPresent::Present) => false,
+                Ok(BlobstoreIsPresent::Absent) => true,
+                Ok(BlobstoreIsPresent::ProbablyNotPresent(_)) | Err(_) => continue,
+            };
+            let key = (multiplex_id.clone(), blobstore_id.to_string());
+            let store_counts = counts.entry(blobstore_id).or_default();
+            store_counts.checked += 1;
+            STATS::checked.add_value(1, key.clone());
+            if missing {
+                store_counts.missing += 1;
+                STATS::missing.add_value(1, key);
+                any_missing = true;
+            }
+        }
+
+        any_missing.then(|| {
+            BlobstoreWalEntry::new(
+                pending.key,
+                self.multiplex_id,
+                Timestamp::now(),
+                pending.blob_size,
+            )
+        })
+    }
+
+    /// The fraction of sampled keys that each blobstore had once their SLA
+    /// had passed.
+    pub(crate) fn scores(&self) -> HashMap<BlobstoreId, f64> {
+        self.counts
+            .lock()
+            .expect("lock poisoned")
+            .iter()
+            .map(|(blobstore_id, counts)| (*blobstore_id, counts.score()))
+            .collect()
+    }
+}
@@ -5,12 +5,14 @@
  * GNU General Public License version 2.
  */
 
+mod consistency;
 pub(crate) mod multiplex;
 pub mod scrub;
 #[cfg(test)]
 mod test;
 mod timed;
 
+pub use consistency::ConsistencySamplerOptions;
 pub use multiplex::MultiplexQuorum;
 pub use multiplex::Scuba;
 pub use multiplex::WalMultiplexedBlobstore;
@@ -6,12 +6,14 @@
  */
 
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt;
 use std::num::NonZeroU64;
 use std::num::NonZeroUsize;
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::anyhow;
 use anyhow::Context as _;
@@ -32,7 +34,9 @@ use context::CoreContext;
 use context::PerfCounterType;
 use fbinit::FacebookInit;
 use futures::future;
+use futures::stream;
 use futures::stream::FuturesUnordered;
+use futures::stream::SelectAll;
 use futures::Future;
 use futures::Stream;
 use futures::StreamExt;
@@ -48,11 +52,17 @@ use thiserror::Error;
 use time_ext::DurationExt;
 use tokio::task::JoinHandle;
 
+use crate::consistency::ConsistencySampler;
+use crate::consistency::ConsistencySamplerOptions;
 use crate::timed::with_timed_stores;
 use crate::timed::MultiplexTimeout;
 use crate::timed::TimedStore;
 type BlobstoresReturnedError = HashMap<BlobstoreId, Error>;
 
+/// How long reads from the local blobstores can take before they are also sent to the blobstores
+/// in other regions.
+const READ_HEDGE_DELAY: Duration = Duration::from_millis(200);
+
 #[derive(Error, Debug, Clone)]
 pub enum ErrorKind {
     #[error("All blobstores failed: {0:?}")]
@@ -148,12 +158,20 @@ pub struct WalMultiplexedBlobstore {
     /// Write-mostly blobstores are not normally read from on `get`, but take part in writes
     /// like a normal blobstore.
     pub(crate) write_only_blobstores: Arc<[TimedStore]>,
+    /// The normal blobstores grouped in the order they are read from.  Each group is only read
+    /// from if the ones before it did not find the blob or reach a read quorum, or if they take
+    /// longer than `hedge_delay` to answer.
+    pub(crate) read_tiers: Vec<Arc<[TimedStore]>>,
+    pub(crate) hedge_delay: Duration,
 
     /// Scuba table to log status of the underlying single blobstore queries.
     pub(crate) scuba: Scuba,
 
     /// Counter keeping track of the yet-to-complete blobstore operations in flight.
     pub(crate) inflight_ops_counter: Arc<AtomicU64>,
+
+    /// Checks that a sample of written keys reach all the blobstores.
+    pub(crate) consistency_sampler: Option<Arc<ConsistencySampler>>,
 }
 
 impl Drop for WalMultiplexedBlobstore {
@@ -203,20 +221,78 @@ impl WalMultiplexedBlobstore {
         let quorum = MultiplexQuorum::new(blobstores.len(), write_quorum)?;
 
         let to = timeout.unwrap_or_default();
-        let blobstores = with_timed_stores(blobstores, to.clone()).into();
+        let blobstores: Arc<[TimedStore]> = with_timed_stores(blobstores, to.clone()).into();
         let write_only_blobstores = with_timed_stores(write_only_blobstores, to).into();
+        let read_tiers = vec![blobstores.clone()];
         let inflight_ops_counter = Arc::new(AtomicU64::new(0));
         Ok(Self {
             multiplex_id,
             wal_queue,
             blobstores,
             write_only_blobstores,
+            read_tiers,
+            hedge_delay: READ_HEDGE_DELAY,
             quorum,
             scuba,
             inflight_ops_counter,
+            consistency_sampler: None,
         })
     }
 
+    /// Read from the given blobstores, which are in the same region as this process, before
+    /// the others.  The other blobstores are only read from if the local ones fail, don't reach
+    /// a read quorum, or don't answer within `hedge_delay`, in which case the read is hedged by
+    /// sending it to the other blobstores as well.
+    pub fn with_local_blobstores(
+        mut self,
+        local_blobstores: &HashSet<BlobstoreId>,
+        hedge_delay: Option<Duration>,
+    ) -> Self {
+        self.hedge_delay = hedge_delay.unwrap_or(READ_HEDGE_DELAY);
+        let (local, remote): (Vec<_>, Vec<_>) = self
+            .blobstores
+            .iter()
+            .cloned()
+            .partition(|bs| local_blobstores.contains(bs.id()));
+        if !local.is_empty() && !remote.is_empty() {
+            self.read_tiers = vec![local.into(), remote.into()];
+        }
+        self
+    }
+
+    /// Check a random sample of the keys written through this multiplex for presence in all of
+    /// its blobstores once the SLA for writing them has passed.  Keys that are missing from any
+    /// blobstore are logged to the WAL again, so that the healer repairs them.
+    pub fn with_consistency_sampler(
+        mut self,
+        ctx: CoreContext,
+        options: ConsistencySamplerOptions,
+    ) -> Self {
+        let blobstores = self
+            .blobstores
+            .iter()
+            .chain(self.write_only_blobstores.iter())
+            .cloned()
+            .collect();
+        let sampler = Arc::new(ConsistencySampler::new(
+            self.multiplex_id,
+            self.wal_queue.clone(),
+            blobstores,
+            options,
+        ));
+        sampler.clone().spawn(ctx);
+        self.consistency_sampler = Some(sampler);
+        self
+    }
+
+    /// The fraction of sampled keys that each blobstore had once the SLA for writing them had
+    /// passed, if the consistency sampler is enabled.
+    pub fn consistency_scores(&self) -> Option<HashMap<BlobstoreId, f64>> {
+        self.consistency_sampler
+            .as_ref()
+            .map(|sampler| sampler.scores())
+    }
+
     async fn put_impl<'a>(
         &'a self,
         ctx: &'a CoreContext,
@@ -304,6 +380,10 @@ impl WalMultiplexedBlobstore {
                                 });
                             }
 
+                            if let Some(sampler) = &self.consistency_sampler {
+                                sampler.record_put(&key, blob_size);
+                            }
+
                             return Ok(OverwriteStatus::NotChecked);
                         }
                     }
@@ -346,20 +426,22 @@ impl WalMultiplexedBlobstore {
         ctx.perf_counters()
             .increment_counter(PerfCounterType::BlobGets);
 
-        let mut get_futs = inner_multi_get(
-            ctx,
-            self.blobstores.clone(),
-            key,
-            OperationType::Get,
-            scuba,
-            self.inflight_ops_counter.clone(),
-        );
-
         // Wait for the quorum successful "Not Found" reads before
         // returning Ok(None).
         let mut quorum: usize = self.quorum.read.get();
-        let mut get_errors = HashMap::with_capacity(get_futs.len());
+        let mut get_errors = HashMap::with_capacity(self.blobstores.len());
         let (stats, result) = async move {
+            let get_futs = hedged_reads(&self.read_tiers, self.hedge_delay, |tier| {
+                inner_multi_get(
+                    ctx,
+                    tier,
+                    key,
+                    OperationType::Get,
+                    scuba,
+                    self.inflight_ops_counter.clone(),
+                )
+            });
+            futures::pin_mut!(get_futs);
             while let Some((bs_id, result)) = get_futs.next().await {
                 match result {
                     Ok(Some(get_data)) => {
@@ -423,19 +505,15 @@ impl WalMultiplexedBlobstore {
         ctx.perf_counters()
             .increment_counter(PerfCounterType::BlobPresenceChecks);
 
-        let mut futs = inner_multi_is_present(
-            ctx,
-            self.blobstores.clone(),
-            key,
-            scuba,
-            self.inflight_ops_counter.clone(),
-        );
-
         // Wait for the quorum successful "Not Found" reads before
         // returning Ok(None).
         let mut quorum: usize = self.quorum.read.get();
-        let mut errors = HashMap::with_capacity(futs.len());
+        let mut errors = HashMap::with_capacity(self.blobstores.len());
         let (stats, result) = async move {
+            let futs = hedged_reads(&self.read_tiers, self.hedge_delay, |tier| {
+                inner_multi_is_present(ctx, tier, key, scuba, self.inflight_ops_counter.clone())
+            });
+            futures::pin_mut!(futs);
             while let Some(result) = futs.next().await {
                 match result {
                     (_, Ok(BlobstoreIsPresent::Present)) => {
@@ -662,6 +740,43 @@ pub(crate) fn inner_multi_get<'a>(
     get_futs
 }
 
+/// Run a read on each tier of blobstores in turn, returning the results of all of them as they
+/// complete.  The read on the next tier is started once the reads on the ones before it have all
+/// completed, or as a hedge if none of them complete within `hedge_delay`.
+fn hedged_reads<'a, S: Stream + Unpin + 'a>(
+    read_tiers: &'a [Arc<[TimedStore]>],
+    hedge_delay: Duration,
+    read: impl Fn(Arc<[TimedStore]>) -> S + 'a,
+) -> impl Stream<Item = S::Item> + 'a {
+    let state = (read_tiers.iter(), SelectAll::new(), 0, read);
+    stream::unfold(state, move |(mut tiers, mut reads, mut pending, read)| async move {
+        loop {
+            let result = if pending == 0 {
+                None
+            } else if tiers.len() == 0 {
+                reads.next().await
+            } else {
+                tokio::time::timeout(hedge_delay, reads.next())
+                    .await
+                    .unwrap_or(None)
+            };
+            match result {
+                Some(result) => {
+                    pending -= 1;
+                    return Some((result, (tiers, reads, pending, read)));
+                }
+                None => {
+                    // Either every read so far has completed, or they are slow, so start
+                    // reading from the next tier.
+                    let tier = tiers.next()?;
+                    pending += tier.len();
+                    reads.push(read(tier.clone()));
+                }
+            }
+        }
+    })
+}
+
 fn inner_multi_is_present<'a>(
     ctx: &'a CoreContext,
     blobstores: Arc<[TimedStore]>,
@@ -5,6 +5,7 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashSet;
 use std::fmt::Debug;
 use std::future::Future;
 use std::panic;
@@ -28,6 +29,7 @@ use fbinit::FacebookInit;
 use futures::future::FutureExt;
 use futures::task::Poll;
 use lock_ext::LockExt;
+use memblob::Memblob;
 use metaconfig_types::BlobstoreId;
 use metaconfig_types::MultiplexId;
 use mononoke_types::BlobstoreBytes;
@@ -41,7 +43,10 @@ use nonzero_ext::nonzero;
 use scuba_ext::MononokeScubaSampleBuilder;
 use sql_construct::SqlConstruct;
 
+use crate::consistency::ConsistencySampler;
+use crate::consistency::ConsistencySamplerOptions;
 use crate::scrub::WalScrubBlobstore;
+use crate::timed::with_timed_stores;
 use crate::MultiplexTimeout;
 use crate::Scuba;
 use crate::WalMultiplexedBlobstore;
@@ -531,6 +536,94 @@ async fn test_get_on_existing(fb: FacebookInit) -> Result<()> {
     Ok(())
 }
 
+#[fbinit::test]
+async fn test_get_prefers_local_blobstores(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (tickable_queue, tickable_blobstores, multiplex) = setup_multiplex(3, 2, None)?;
+    // The hedge delay is long enough that reads are never hedged in this test.
+    let multiplex = multiplex.with_local_blobstores(
+        &HashSet::from([BlobstoreId::new(0)]),
+        Some(Duration::from_secs(3600)),
+    );
+
+    let v = make_value("v1");
+    let k = "k1";
+
+    let mut put_fut = multiplex.put(&ctx, k.to_owned(), v.clone()).boxed();
+    assert_pending(&mut put_fut).await;
+    tickable_queue.tick(None);
+    assert_pending(&mut put_fut).await;
+    for (_id, store) in &tickable_blobstores {
+        store.tick(None);
+    }
+    assert!(put_fut.await.is_ok());
+
+    // the local blobstore returns the blob, the others are not read from
+    {
+        let mut get_fut = multiplex.get(&ctx, k).boxed();
+        assert_pending(&mut get_fut).await;
+
+        // ticking the remote blobstores has no effect, as they have no requests
+        tickable_blobstores[1].1.tick(None);
+        tickable_blobstores[2].1.tick(None);
+        assert_pending(&mut get_fut).await;
+
+        tickable_blobstores[0].1.tick(None);
+        validate_blob(get_fut.await, Ok(Some(&v)));
+    }
+
+    // the local blobstore fails, so the get falls back to the remote ones
+    {
+        let mut get_fut = multiplex.get(&ctx, k).boxed();
+        assert_pending(&mut get_fut).await;
+
+        tickable_blobstores[0].1.tick(Some("bs0 failed!"));
+        assert_pending(&mut get_fut).await;
+
+        tickable_blobstores[1].1.tick(None);
+        validate_blob(get_fut.await, Ok(Some(&v)));
+        tickable_blobstores[2].1.drain(1);
+    }
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_get_hedges_slow_local_blobstores(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (tickable_queue, tickable_blobstores, multiplex) = setup_multiplex(3, 2, None)?;
+    let multiplex = multiplex.with_local_blobstores(
+        &HashSet::from([BlobstoreId::new(0)]),
+        Some(Duration::from_millis(10)),
+    );
+
+    let v = make_value("v1");
+    let k = "k1";
+
+    let mut put_fut = multiplex.put(&ctx, k.to_owned(), v.clone()).boxed();
+    assert_pending(&mut put_fut).await;
+    tickable_queue.tick(None);
+    assert_pending(&mut put_fut).await;
+    for (_id, store) in &tickable_blobstores {
+        store.tick(None);
+    }
+    assert!(put_fut.await.is_ok());
+
+    // the local blobstore doesn't answer within the hedge delay, so the read is also sent to
+    // the remote blobstores, and the first to answer wins
+    let mut get_fut = multiplex.get(&ctx, k).boxed();
+    assert_pending(&mut get_fut).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_pending(&mut get_fut).await;
+
+    tickable_blobstores[2].1.tick(None);
+    validate_blob(get_fut.await, Ok(Some(&v)));
+    tickable_blobstores[0].1.drain(1);
+    tickable_blobstores[1].1.drain(1);
+
+    Ok(())
+}
+
 #[fbinit::test]
 async fn test_is_present_missing(fb: FacebookInit) -> Result<()> {
     let ctx = CoreContext::test_mock(fb);
@@ -797,6 +890,63 @@ async fn test_timeout_on_request(fb: FacebookInit) -> Result<()> {
     Ok(())
 }
 
+#[fbinit::test]
+async fn test_consistency_sampler(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let multiplex_id = MultiplexId::new(1);
+    let wal = Arc::new(SqlBlobstoreWal::with_sqlite_in_memory()?);
+    let blobstores = (0..2)
+        .map(|id| (BlobstoreId::new(id), Arc::new(Memblob::default())))
+        .collect::<Vec<_>>();
+    let sampler = ConsistencySampler::new(
+        multiplex_id,
+        wal.clone(),
+        with_timed_stores(
+            blobstores
+                .iter()
+                .map(|(id, bs)| (*id, bs.clone() as Arc<dyn BlobstorePutOps>))
+                .collect(),
+            MultiplexTimeout::default(),
+        ),
+        ConsistencySamplerOptions {
+            sample_rate: nonzero!(1u64),
+            sla: Duration::ZERO,
+        },
+    );
+
+    // One key reached both blobstores, the other only reached the first.
+    for (_, bs) in &blobstores {
+        bs.put(ctx, "everywhere".to_string(), make_value("v")).await?;
+    }
+    blobstores[0]
+        .1
+        .put(ctx, "partial".to_string(), make_value("v"))
+        .await?;
+    sampler.record_put("everywhere", 1);
+    sampler.record_put("partial", 1);
+    sampler.check_due(ctx).await?;
+
+    let scores = sampler.scores();
+    assert_eq!(scores[&BlobstoreId::new(0)], 1.0);
+    assert_eq!(scores[&BlobstoreId::new(1)], 0.5);
+
+    // The partially written key is queued for the healer.
+    let queued = wal
+        .read(ctx, &multiplex_id, &Timestamp::now(), 100)
+        .await?
+        .into_iter()
+        .map(|entry| entry.blobstore_key)
+        .collect::<Vec<_>>();
+    assert_eq!(queued, vec!["partial".to_string()]);
+
+    // Checked keys are not checked again.
+    sampler.check_due(ctx).await?;
+    assert_eq!(sampler.scores()[&BlobstoreId::new(1)], 0.5);
+
+    Ok(())
+}
+
 async fn assert_pending<T: Debug>(fut: &mut (impl Future<Output = T> + Unpin)) {
     match futures::poll!(fut) {
         Poll::Pending => {}
@@ -0,0 +1,205 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::fmt;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use blobstore::Blobstore;
+use blobstore::BlobstoreGetData;
+use blobstore::BlobstoreIsPresent;
+use context::CoreContext;
+use mononoke_types::BlobstoreBytes;
+use retry::RetryPolicy;
+
+/// A blobstore that retries failed and timed out operations on the inner
+/// blobstore according to a `RetryPolicy`.  Blobstore puts are idempotent,
+/// so all operations are retried on any error.  Operations are never retried
+/// past the deadline of the context they are made in.
+#[derive(Debug)]
+pub struct RetryBlobstore<T> {
+    inner: T,
+    policy: RetryPolicy,
+}
+
+impl<T> RetryBlobstore<T> {
+    pub fn new(inner: T, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for RetryBlobstore<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RetryBlobstore<{}>", &self.inner)
+    }
+}
+
+#[async_trait]
+impl<T: Blobstore> Blobstore for RetryBlobstore<T> {
+    async fn get<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<Option<BlobstoreGetData>> {
+        let (value, _attempts) = self
+            .policy
+            .run(|_| self.inner.get(ctx, key), |_| true, ctx.deadline())
+            .await?;
+        Ok(value)
+    }
+
+    async fn put<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<()> {
+        self.policy
+            .run(
+                |_| self.inner.put(ctx, key.clone(), value.clone()),
+                |_| true,
+                ctx.deadline(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn is_present<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<BlobstoreIsPresent> {
+        let (present, _attempts) = self
+            .policy
+            .run(|_| self.inner.is_present(ctx, key), |_| true, ctx.deadline())
+            .await?;
+        Ok(present)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use anyhow::anyhow;
+    use borrowed::borrowed;
+    use fbinit::FacebookInit;
+    use memblob::Memblob;
+    use retry::RetryLogic;
+
+    use super::*;
+
+    /// Blobstore whose first `failures` gets fail.
+    #[derive(Debug)]
+    struct FlakyBlobstore {
+        inner: Memblob,
+        failures: AtomicUsize,
+    }
+
+    impl fmt::Display for FlakyBlobstore {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "FlakyBlobstore")
+        }
+    }
+
+    #[async_trait]
+    impl Blobstore for FlakyBlobstore {
+        async fn get<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: &'a str,
+        ) -> Result<Option<BlobstoreGetData>> {
+            let remaining = self.failures.load(Ordering::Relaxed);
+            if remaining > 0 {
+                self.failures.store(remaining - 1, Ordering::Relaxed);
+                return Err(anyhow!("flaky get"));
+            }
+            self.inner.get(ctx, key).await
+        }
+
+        async fn put<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: String,
+            value: BlobstoreBytes,
+        ) -> Result<()> {
+            self.inner.put(ctx, key, value).await
+        }
+
+        async fn is_present<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: &'a str,
+        ) -> Result<BlobstoreIsPresent> {
+            self.inner.is_present(ctx, key).await
+        }
+    }
+
+    fn policy(max_attempts: usize) -> RetryPolicy {
+        RetryPolicy {
+            logic: RetryLogic::Exponential {
+                base: Duration::from_millis(1),
+                factor: 1.0,
+            },
+            max_attempts,
+            ..RetryPolicy::no_retries()
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_retry_get(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let key = "foobar";
+
+        let flaky = FlakyBlobstore {
+            inner: Memblob::default(),
+            failures: AtomicUsize::new(2),
+        };
+        let blobstore = RetryBlobstore::new(flaky, policy(3));
+        blobstore
+            .put(
+                ctx,
+                key.to_owned(),
+                BlobstoreBytes::from_bytes("test foobar"),
+            )
+            .await?;
+        let value = blobstore.get(ctx, key).await?;
+        assert_eq!(
+            value.map(|v| v.into_bytes()),
+            Some(BlobstoreBytes::from_bytes("test foobar"))
+        );
+
+        let flaky = FlakyBlobstore {
+            inner: Memblob::default(),
+            failures: AtomicUsize::new(3),
+        };
+        let blobstore = RetryBlobstore::new(flaky, policy(3));
+        assert!(blobstore.get(ctx, key).await.is_err());
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_retry_stops_at_ctx_deadline(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb).clone_with_deadline(Instant::now());
+        borrowed!(ctx);
+        let key = "foobar";
+
+        // The get would succeed on its second attempt, but the deadline has
+        // already passed, so it isn't retried.
+        let flaky = FlakyBlobstore {
+            inner: Memblob::default(),
+            failures: AtomicUsize::new(1),
+        };
+        let blobstore = RetryBlobstore::new(flaky, policy(3));
+        assert!(blobstore.get(ctx, key).await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,369 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use blobstore::Blobstore;
+use blobstore::BlobstoreGetData;
+use blobstore::BlobstoreIsPresent;
+use context::CoreContext;
+use futures::future::BoxFuture;
+use futures::future::FutureExt;
+use futures::future::Shared;
+use mononoke_types::BlobstoreBytes;
+use mononoke_types::RepositoryId;
+use mononoke_types::REPO_PREFIX_REGEX;
+use shared_error::anyhow::IntoSharedError;
+use shared_error::anyhow::SharedError;
+use stats::prelude::*;
+
+define_stats! {
+    prefix = "mononoke.blobstore.single_flight";
+    fetches: dynamic_timeseries("{}.fetches", (scope: String); Rate, Sum),
+    coalesced: dynamic_timeseries("{}.coalesced", (scope: String); Rate, Sum),
+}
+
+/// Which keys have their concurrent gets coalesced.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SingleFlightScope {
+    /// Gets are never coalesced.
+    Disabled,
+    /// Gets are coalesced for all keys.
+    All,
+    /// Gets are only coalesced for the keys of these repos.
+    Repos(HashSet<RepositoryId>),
+}
+
+impl Default for SingleFlightScope {
+    fn default() -> Self {
+        SingleFlightScope::Disabled
+    }
+}
+
+impl SingleFlightScope {
+    pub fn is_enabled(&self) -> bool {
+        *self != SingleFlightScope::Disabled
+    }
+
+    fn covers(&self, repo_id: Option<RepositoryId>) -> bool {
+        match self {
+            SingleFlightScope::Disabled => false,
+            SingleFlightScope::All => true,
+            SingleFlightScope::Repos(repo_ids) => {
+                repo_id.map_or(false, |repo_id| repo_ids.contains(&repo_id))
+            }
+        }
+    }
+}
+
+/// The repo that a key belongs to, if it has a repo prefix.
+fn key_repo_id(key: &str) -> Option<RepositoryId> {
+    let captures = REPO_PREFIX_REGEX.captures(key)?;
+    let id = captures.get(1)?.as_str().parse().ok()?;
+    Some(RepositoryId::new(id))
+}
+
+/// Name that stats are reported under for keys of the given repo.
+fn stats_scope(repo_id: Option<RepositoryId>) -> String {
+    match repo_id {
+        Some(repo_id) => format!("repo{}", repo_id.id()),
+        None => "other".to_string(),
+    }
+}
+
+type SharedGet = Shared<BoxFuture<'static, Result<Option<BlobstoreGetData>, SharedError>>>;
+
+struct InFlight {
+    id: u64,
+    get: SharedGet,
+    /// How many gets are waiting for this one.
+    waiters: usize,
+}
+
+/// Stops waiting for an in-flight get when dropped, removing the get once
+/// nothing waits for it, so that gets that are all cancelled before they
+/// finish are not left behind.
+struct Waiter<'a> {
+    in_flight: &'a Mutex<HashMap<String, InFlight>>,
+    key: &'a str,
+    id: u64,
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock().expect("lock poisoned");
+        if let Some(entry) = in_flight.get_mut(self.key) {
+            if entry.id == self.id {
+                entry.waiters -= 1;
+                if entry.waiters == 0 {
+                    in_flight.remove(self.key);
+                }
+            }
+        }
+    }
+}
+
+/// A blobstore that makes concurrent gets for the same key share a single
+/// get from the inner blobstore, so that many requests for a newly written
+/// blob don't all reach the underlying storage at once.
+///
+/// Gets are only shared while they are in flight: once a get finishes, the
+/// next get for its key fetches it again.
+pub struct SingleFlightBlobstore<T> {
+    inner: Arc<T>,
+    scope: SingleFlightScope,
+    in_flight: Arc<Mutex<HashMap<String, InFlight>>>,
+    next_id: AtomicU64,
+}
+
+impl<T: Blobstore + 'static> SingleFlightBlobstore<T> {
+    pub fn new(inner: T, scope: SingleFlightScope) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            scope,
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    fn fetch(&self, ctx: &CoreContext, key: &str, id: u64) -> SharedGet {
+        let inner = self.inner.clone();
+        let in_flight = self.in_flight.clone();
+        let ctx = ctx.clone();
+        let key = key.to_string();
+        async move {
+            let result = inner.get(&ctx, &key).await;
+            // A put may have already replaced this get with a newer one.
+            let mut in_flight = in_flight.lock().expect("lock poisoned");
+            if in_flight.get(&key).map_or(false, |entry| entry.id == id) {
+                in_flight.remove(&key);
+            }
+            result.shared_error()
+        }
+        .boxed()
+        .shared()
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for SingleFlightBlobstore<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SingleFlightBlobstore<{}>", &self.inner)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SingleFlightBlobstore<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SingleFlightBlobstore")
+            .field("inner", &self.inner)
+            .field("scope", &self.scope)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<T: Blobstore + 'static> Blobstore for SingleFlightBlobstore<T> {
+    async fn get<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<Option<BlobstoreGetData>> {
+        let repo_id = key_repo_id(key);
+        if !self.scope.covers(repo_id) {
+            return self.inner.get(ctx, key).await;
+        }
+        let (id, get) = {
+            let mut in_flight = self.in_flight.lock().expect("lock poisoned");
+            match in_flight.get_mut(key) {
+                Some(entry) => {
+                    STATS::coalesced.add_value(1, (stats_scope(repo_id),));
+                    entry.waiters += 1;
+                    (entry.id, entry.get.clone())
+                }
+                None => {
+                    STATS::fetches.add_value(1, (stats_scope(repo_id),));
+                    let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                    let get = self.fetch(ctx, key, id);
+                    in_flight.insert(
+                        key.to_string(),
+                        InFlight {
+                            id,
+                            get: get.clone(),
+                            waiters: 1,
+                        },
+                    );
+                    (id, get)
+                }
+            }
+        };
+        let _waiter = Waiter {
+            in_flight: &self.in_flight,
+            key,
+            id,
+        };
+        Ok(get.await?)
+    }
+
+    async fn put<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<()> {
+        let result = self.inner.put(ctx, key.clone(), value).await;
+        // Gets that start after the put must not share a get that may have
+        // fetched the old value.
+        self.in_flight.lock().expect("lock poisoned").remove(&key);
+        result
+    }
+
+    async fn is_present<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<BlobstoreIsPresent> {
+        self.inner.is_present(ctx, key).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use borrowed::borrowed;
+    use fbinit::FacebookInit;
+    use futures::future::try_join_all;
+    use memblob::Memblob;
+
+    use super::*;
+
+    /// Blobstore with slow gets, that counts the gets that reach it.
+    #[derive(Debug, Default)]
+    struct SlowBlobstore {
+        inner: Memblob,
+        gets: AtomicU64,
+    }
+
+    impl fmt::Display for SlowBlobstore {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "SlowBlobstore")
+        }
+    }
+
+    #[async_trait]
+    impl Blobstore for SlowBlobstore {
+        async fn get<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: &'a str,
+        ) -> Result<Option<BlobstoreGetData>> {
+            self.gets.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            self.inner.get(ctx, key).await
+        }
+
+        async fn put<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: String,
+            value: BlobstoreBytes,
+        ) -> Result<()> {
+            self.inner.put(ctx, key, value).await
+        }
+
+        async fn is_present<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: &'a str,
+        ) -> Result<BlobstoreIsPresent> {
+            self.inner.is_present(ctx, key).await
+        }
+    }
+
+    async fn concurrent_gets(
+        ctx: &CoreContext,
+        blobstore: &SingleFlightBlobstore<SlowBlobstore>,
+        key: &str,
+    ) -> Result<u64> {
+        let before = blobstore.inner.gets.load(Ordering::Relaxed);
+        let values = try_join_all((0..5).map(|_| blobstore.get(ctx, key))).await?;
+        for value in values {
+            assert_eq!(
+                value.map(|v| v.into_bytes()),
+                Some(BlobstoreBytes::from_bytes("value"))
+            );
+        }
+        Ok(blobstore.inner.gets.load(Ordering::Relaxed) - before)
+    }
+
+    #[fbinit::test]
+    async fn test_concurrent_gets_coalesced(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let key = "repo0001.foobar";
+
+        let blobstore =
+            SingleFlightBlobstore::new(SlowBlobstore::default(), SingleFlightScope::All);
+        blobstore
+            .put(ctx, key.to_owned(), BlobstoreBytes::from_bytes("value"))
+            .await?;
+
+        assert_eq!(concurrent_gets(ctx, &blobstore, key).await?, 1);
+        // The finished get is not reused.
+        assert_eq!(concurrent_gets(ctx, &blobstore, key).await?, 1);
+        assert!(blobstore.in_flight.lock().unwrap().is_empty());
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_repo_scope(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let in_scope = "repo0001.foobar";
+        let out_of_scope = "repo0002.foobar";
+
+        let scope = SingleFlightScope::Repos(HashSet::from([RepositoryId::new(1)]));
+        let blobstore = SingleFlightBlobstore::new(SlowBlobstore::default(), scope);
+        for key in [in_scope, out_of_scope] {
+            blobstore
+                .put(ctx, key.to_owned(), BlobstoreBytes::from_bytes("value"))
+                .await?;
+        }
+
+        assert_eq!(concurrent_gets(ctx, &blobstore, in_scope).await?, 1);
+        assert_eq!(concurrent_gets(ctx, &blobstore, out_of_scope).await?, 5);
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_cancelled_gets_removed(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let key = "repo0001.foobar";
+
+        let blobstore =
+            SingleFlightBlobstore::new(SlowBlobstore::default(), SingleFlightScope::All);
+        blobstore
+            .put(ctx, key.to_owned(), BlobstoreBytes::from_bytes("value"))
+            .await?;
+
+        let gets = try_join_all((0..5).map(|_| blobstore.get(ctx, key)));
+        let cancelled = tokio::time::timeout(Duration::from_millis(10), gets).await;
+        assert!(cancelled.is_err());
+        assert!(blobstore.in_flight.lock().unwrap().is_empty());
+
+        assert_eq!(concurrent_gets(ctx, &blobstore, key).await?, 1);
+        Ok(())
+    }
+}
@@ -505,7 +505,7 @@ impl Sqlblob {
             let blob = match chunked.chunking_method {
                 ChunkingMethod::InlineBase64 => {
                     let decoded = base64::decode_config(&chunked.id, base64::STANDARD_NO_PAD)?;
-                    Bytes::copy_from_slice(decoded.as_ref())
+                    Bytes::from(decoded)
                 }
                 ChunkingMethod::ByContentHashBlake2 => {
                     let chunks = (0..chunked.count)
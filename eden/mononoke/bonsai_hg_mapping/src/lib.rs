@@ -377,8 +377,15 @@ impl BonsaiHgMapping for SqlBonsaiHgMapping {
         STATS::gets.add_value(1);
         ctx.perf_counters()
             .increment_counter(PerfCounterType::SqlReadsReplica);
-        let (mut mappings, left_to_fetch) =
-            select_mapping(ctx.fb, &self.read_connection, self.repo_id, ids).await?;
+        // Reads are abandoned once nobody is waiting for them.
+        let (mut mappings, left_to_fetch) = ctx
+            .run_until_abandoned(select_mapping(
+                ctx.fb,
+                &self.read_connection,
+                self.repo_id,
+                ids,
+            ))
+            .await??;
 
         if left_to_fetch.is_empty() {
             return Ok(mappings);
@@ -387,13 +394,14 @@ impl BonsaiHgMapping for SqlBonsaiHgMapping {
         STATS::gets_master.add_value(1);
         ctx.perf_counters()
             .increment_counter(PerfCounterType::SqlReadsMaster);
-        let (mut master_mappings, _) = select_mapping(
-            ctx.fb,
-            &self.read_master_connection,
-            self.repo_id,
-            left_to_fetch,
-        )
-        .await?;
+        let (mut master_mappings, _) = ctx
+            .run_until_abandoned(select_mapping(
+                ctx.fb,
+                &self.read_master_connection,
+                self.repo_id,
+                left_to_fetch,
+            ))
+            .await??;
 
         mappings.append(&mut master_mappings);
         Ok(mappings)
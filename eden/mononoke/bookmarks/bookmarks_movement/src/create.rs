@@ -137,6 +137,13 @@ impl<'op> CreateBookmarkOp<'op> {
 
         check_bookmark_sync_config(repo, self.bookmark, kind)?;
 
+        // Check this before running hooks, so that direct moves of
+        // pushrebase-only bookmarks are rejected early.
+        if kind.is_public() {
+            crate::restrictions::check_restriction_pushrebase_only(ctx, authz, repo, self.bookmark)
+                .await?;
+        }
+
         self.affected_changesets
             .check_restrictions(
                 ctx,
@@ -179,6 +186,29 @@ impl<'op> CreateBookmarkOp<'op> {
                     self.target,
                 )
                 .await?;
+                crate::restrictions::check_restriction_require_signed_commits(
+                    ctx,
+                    repo,
+                    self.bookmark,
+                    self.target,
+                )
+                .await?;
+                crate::required_derived_data::check_required_derived_data(
+                    ctx,
+                    repo,
+                    self.bookmark,
+                    self.target,
+                )
+                .await?;
+                crate::preconditions::check_preconditions(
+                    ctx,
+                    repo,
+                    lca_hint,
+                    self.bookmark,
+                    None,
+                    self.target,
+                )
+                .await?;
 
                 let txn_hook_fut = crate::git_mapping::populate_git_mapping_txn_hook(
                     ctx,
@@ -100,6 +100,11 @@ impl<'op> DeleteBookmarkOp<'op> {
 
         check_bookmark_sync_config(repo, self.bookmark, kind)?;
 
+        if kind.is_public() {
+            crate::restrictions::check_restriction_pushrebase_only(ctx, authz, repo, self.bookmark)
+                .await?;
+        }
+
         if repo
             .repo_bookmark_attrs()
             .is_fast_forward_only(self.bookmark)
@@ -20,6 +20,8 @@ use hooks::HookOutcome;
 use hooks::PushAuthoredBy;
 use mononoke_types::BonsaiChangeset;
 use tunables::tunables;
+use webhooks::HookRejectionPayload;
+use webhooks::WebhookEvent;
 
 use crate::BookmarkMovementError;
 
@@ -138,6 +140,24 @@ pub async fn run_hooks(
     if rejections.is_empty() {
         Ok(())
     } else {
+        let event = WebhookEvent::HookRejected {
+            bookmark: bookmark.to_string(),
+            rejections: rejections
+                .iter()
+                .map(|rejection| HookRejectionPayload {
+                    hook_name: rejection.hook_name.clone(),
+                    changeset_id: rejection.cs_id,
+                    description: rejection.reason.description.to_string(),
+                    long_description: rejection.reason.long_description.clone(),
+                })
+                .collect(),
+        };
+        webhooks::send_event(
+            ctx,
+            hook_manager.repo_name(),
+            hook_manager.webhooks(),
+            event,
+        );
         Err(BookmarkMovementError::HookFailure(rejections))
     }
 }
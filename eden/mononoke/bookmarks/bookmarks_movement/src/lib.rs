@@ -16,8 +16,11 @@ use bookmarks::BookmarksRef;
 use bookmarks_types::BookmarkKey;
 use changeset_fetcher::ChangesetFetcherArc;
 use changesets::ChangesetsRef;
+use commit_signatures::SignatureVerification;
 use itertools::Itertools;
+use metaconfig_types::ReadOnlyInfo;
 use metaconfig_types::RepoConfigRef;
+use metaconfig_types::RequiredDerivedDataType;
 use mononoke_types::ChangesetId;
 use mononoke_types::MPath;
 use phases::PhasesRef;
@@ -37,8 +40,10 @@ mod create;
 mod delete;
 mod git_mapping;
 mod hook_running;
+mod preconditions;
 mod pushrebase_onto;
 mod repo_lock;
+mod required_derived_data;
 mod restrictions;
 mod update;
 
@@ -50,6 +55,11 @@ pub use pushrebase::PushrebaseOutcome;
 pub use crate::create::CreateBookmarkOp;
 pub use crate::delete::DeleteBookmarkOp;
 pub use crate::hook_running::run_hooks;
+pub use crate::preconditions::precondition;
+pub use crate::preconditions::BookmarkMove;
+pub use crate::preconditions::BookmarkMovePrecondition;
+pub use crate::preconditions::DescendsFromOld;
+pub use crate::preconditions::StatusCheck;
 pub use crate::pushrebase_onto::get_pushrebase_hooks;
 pub use crate::pushrebase_onto::PushrebaseOntoBookmarkOp;
 pub use crate::restrictions::check_bookmark_sync_config;
@@ -129,7 +139,7 @@ pub enum BookmarkMovementError {
     PushrebaseError(#[source] PushrebaseError),
 
     #[error("Repo is locked: {0}")]
-    RepoLocked(String),
+    RepoLocked(ReadOnlyInfo),
 
     #[error("Case conflict found in {changeset_id}: {path1} conflicts with {path2}")]
     CaseConflict {
@@ -164,6 +174,44 @@ pub enum BookmarkMovementError {
         descendant_bookmark: BookmarkKey,
     },
 
+    #[error(
+        "Bookmark '{bookmark}' can only be moved to commits signed by a trusted key, but {changeset_id} is {verification:?}"
+    )]
+    RequiresSignedCommit {
+        bookmark: BookmarkKey,
+        changeset_id: ChangesetId,
+        verification: SignatureVerification,
+    },
+
+    #[error(
+        "Pushrebase is not allowed onto the bookmark '{bookmark}', because this bookmark requires signed commits"
+    )]
+    PushrebaseNotAllowedRequiresSignedCommits { bookmark: BookmarkKey },
+
+    #[error(
+        "Bookmark '{bookmark}' can only be moved to commits with {derived_data_type} derived, but it is not derived for {changeset_id}"
+    )]
+    RequiresDerivedData {
+        bookmark: BookmarkKey,
+        changeset_id: ChangesetId,
+        derived_data_type: RequiredDerivedDataType,
+    },
+
+    #[error("Bookmark '{bookmark}' can only be moved by pushrebase")]
+    PushrebaseOnly { bookmark: BookmarkKey },
+
+    #[error("Bookmark '{bookmark}' cannot be moved: precondition {precondition} failed: {reason}")]
+    PreconditionFailed {
+        bookmark: BookmarkKey,
+        precondition: String,
+        reason: String,
+    },
+
+    #[error(
+        "Pushrebase is not allowed onto the bookmark '{bookmark}', because moves of this bookmark must pass a status check"
+    )]
+    PushrebaseNotAllowedRequiresStatusCheck { bookmark: BookmarkKey },
+
     #[error(
         "Bookmark '{bookmark}' cannot be moved because publishing bookmarks are being redirected"
     )]
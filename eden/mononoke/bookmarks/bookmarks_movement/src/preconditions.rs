@@ -0,0 +1,247 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Preconditions that direct moves of bookmarks must satisfy.
+//!
+//! Preconditions are configured per bookmark, and each kind of precondition
+//! is an implementation of `BookmarkMovePrecondition`.
+
+use std::time::Duration;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use bookmarks_types::BookmarkKey;
+use changeset_fetcher::ArcChangesetFetcher;
+use context::CoreContext;
+use hyper::client::HttpConnector;
+use hyper::Body;
+use hyper::Client;
+use hyper::Request;
+use hyper::Uri;
+use hyper_openssl::HttpsConnector;
+use metaconfig_types::BookmarkPrecondition;
+use mononoke_types::ChangesetId;
+use once_cell::sync::OnceCell;
+use reachabilityindex::LeastCommonAncestorsHint;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::BookmarkMovementError;
+use crate::Repo;
+
+type HttpsClient = Client<HttpsConnector<HttpConnector>>;
+
+static CLIENT: OnceCell<HttpsClient> = OnceCell::new();
+
+/// The client that all status checks share, so that it is only built once.
+fn client() -> Result<&'static HttpsClient> {
+    CLIENT.get_or_try_init(|| Ok(Client::builder().build(HttpsConnector::new()?)))
+}
+
+/// A move of a bookmark whose preconditions are being checked.
+pub struct BookmarkMove<'a> {
+    pub ctx: &'a CoreContext,
+    pub repo_name: &'a str,
+    pub bookmark: &'a BookmarkKey,
+    /// Unset if the bookmark is being created.
+    pub old: Option<ChangesetId>,
+    pub new: ChangesetId,
+    pub changeset_fetcher: ArcChangesetFetcher,
+    pub lca_hint: &'a dyn LeastCommonAncestorsHint,
+}
+
+/// A condition that a direct move of a bookmark must satisfy.
+#[async_trait]
+pub trait BookmarkMovePrecondition: Send + Sync {
+    /// The name of the precondition, for error messages.
+    fn name(&self) -> &'static str;
+
+    /// Check a bookmark move against the precondition, and return why it
+    /// isn't satisfied, if it isn't.  Errors mean the precondition couldn't
+    /// be checked, which also prevents the move.
+    async fn check(&self, bookmark_move: &BookmarkMove<'_>) -> Result<Option<String>>;
+}
+
+/// The new target of the bookmark must descend from its old target.
+pub struct DescendsFromOld;
+
+#[async_trait]
+impl BookmarkMovePrecondition for DescendsFromOld {
+    fn name(&self) -> &'static str {
+        "descends_from_old"
+    }
+
+    async fn check(&self, bookmark_move: &BookmarkMove<'_>) -> Result<Option<String>> {
+        let old = match bookmark_move.old {
+            Some(old) if old != bookmark_move.new => old,
+            _ => return Ok(None),
+        };
+        let is_ancestor = bookmark_move
+            .lca_hint
+            .is_ancestor(
+                bookmark_move.ctx,
+                &bookmark_move.changeset_fetcher,
+                old,
+                bookmark_move.new,
+            )
+            .await?;
+        if is_ancestor {
+            return Ok(None);
+        }
+        Ok(Some(format!("{} does not descend from {}", bookmark_move.new, old)))
+    }
+}
+
+#[derive(Serialize)]
+struct StatusCheckRequest<'a> {
+    repo_name: &'a str,
+    bookmark: &'a str,
+    changeset_id: ChangesetId,
+}
+
+#[derive(Deserialize)]
+struct StatusCheckResponse {
+    status: String,
+    description: Option<String>,
+}
+
+/// An external status check, e.g. of CI results, must report the new target
+/// of the bookmark as green.
+///
+/// The repo, bookmark and new target are posted to the status check as
+/// JSON, and it responds with a JSON object whose `status` field is `green`
+/// if the bookmark may be moved, and whose optional `description` field
+/// explains why not otherwise.
+pub struct StatusCheck {
+    client: &'static HttpsClient,
+    uri: Uri,
+    timeout: Duration,
+}
+
+impl StatusCheck {
+    pub fn new(url: &str, timeout: Duration) -> Result<Self> {
+        let client = client()?;
+        let uri = url
+            .parse()
+            .with_context(|| format!("Invalid status check URL {}", url))?;
+        Ok(Self {
+            client,
+            uri,
+            timeout,
+        })
+    }
+
+    async fn status(&self, bookmark_move: &BookmarkMove<'_>) -> Result<StatusCheckResponse> {
+        let body = serde_json::to_vec(&StatusCheckRequest {
+            repo_name: bookmark_move.repo_name,
+            bookmark: bookmark_move.bookmark.as_str(),
+            changeset_id: bookmark_move.new,
+        })?;
+        let request = Request::post(self.uri.clone())
+            .header("Content-Type", "application/json")
+            .body(Body::from(body))?;
+        let response = self
+            .client
+            .request(request)
+            .await
+            .with_context(|| format!("Request to {} failed", self.uri))?;
+        let (head, body) = response.into_parts();
+        let body = hyper::body::to_bytes(body)
+            .await
+            .with_context(|| format!("Failed to read response from {}", self.uri))?;
+        if !head.status.is_success() {
+            bail!(
+                "Request to {} failed with status {}: {}",
+                self.uri,
+                head.status,
+                String::from_utf8_lossy(&body)
+            );
+        }
+        serde_json::from_slice(&body)
+            .with_context(|| format!("Invalid response from {}", self.uri))
+    }
+}
+
+#[async_trait]
+impl BookmarkMovePrecondition for StatusCheck {
+    fn name(&self) -> &'static str {
+        "status_check"
+    }
+
+    async fn check(&self, bookmark_move: &BookmarkMove<'_>) -> Result<Option<String>> {
+        let response = tokio::time::timeout(self.timeout, self.status(bookmark_move))
+            .await
+            .with_context(|| format!("Request to {} timed out", self.uri))??;
+        if response.status == "green" {
+            return Ok(None);
+        }
+        let mut reason = format!(
+            "{} reported {} as {}",
+            self.uri, bookmark_move.new, response.status
+        );
+        if let Some(description) = response.description {
+            reason.push_str(": ");
+            reason.push_str(&description);
+        }
+        Ok(Some(reason))
+    }
+}
+
+/// The implementation of a configured precondition.
+pub fn precondition(config: &BookmarkPrecondition) -> Result<Box<dyn BookmarkMovePrecondition>> {
+    Ok(match config {
+        BookmarkPrecondition::DescendsFromOld => Box::new(DescendsFromOld),
+        BookmarkPrecondition::StatusCheck { url, timeout } => {
+            Box::new(StatusCheck::new(url, *timeout)?)
+        }
+    })
+}
+
+/// Whether a precondition can't be satisfied by moves to commits created by
+/// pushrebase, as nothing can have checked them yet.
+pub(crate) fn blocks_pushrebase(config: &BookmarkPrecondition) -> bool {
+    match config {
+        BookmarkPrecondition::DescendsFromOld => false,
+        BookmarkPrecondition::StatusCheck { .. } => true,
+    }
+}
+
+/// Check a direct move of a bookmark against all of the preconditions
+/// configured for it.
+pub(crate) async fn check_preconditions(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    lca_hint: &dyn LeastCommonAncestorsHint,
+    bookmark: &BookmarkKey,
+    old: Option<ChangesetId>,
+    new: ChangesetId,
+) -> Result<(), BookmarkMovementError> {
+    let bookmark_move = BookmarkMove {
+        ctx,
+        repo_name: repo.repo_identity().name(),
+        bookmark,
+        old,
+        new,
+        changeset_fetcher: repo.changeset_fetcher_arc(),
+        lca_hint,
+    };
+    for attr in repo.repo_bookmark_attrs().select(bookmark) {
+        for config in attr.params().preconditions.iter() {
+            let precondition = precondition(config)?;
+            if let Some(reason) = precondition.check(&bookmark_move).await? {
+                return Err(BookmarkMovementError::PreconditionFailed {
+                    bookmark: bookmark.clone(),
+                    precondition: precondition.name().to_string(),
+                    reason,
+                });
+            }
+        }
+    }
+    Ok(())
+}
@@ -30,6 +30,7 @@ use repo_authorization::AuthorizationContext;
 use repo_authorization::RepoWriteOperation;
 use repo_bookmark_attrs::RepoBookmarkAttrsRef;
 use repo_cross_repo::RepoCrossRepoRef;
+use repo_derived_data::RepoDerivedDataArc;
 use repo_identity::RepoIdentityRef;
 use repo_update_logger::log_bookmark_operation;
 use repo_update_logger::log_new_commits;
@@ -39,8 +40,10 @@ use repo_update_logger::CommitInfo;
 
 use crate::affected_changesets::AdditionalChangesets;
 use crate::affected_changesets::AffectedChangesets;
+use crate::preconditions::blocks_pushrebase;
 use crate::repo_lock::check_repo_lock;
 use crate::repo_lock::RepoLockPushrebaseHook;
+use crate::required_derived_data::RequiredDerivedDataPushrebaseHook;
 use crate::restrictions::check_bookmark_sync_config;
 use crate::restrictions::BookmarkKindRestrictions;
 use crate::BookmarkMovementError;
@@ -197,6 +200,14 @@ impl<'op> PushrebaseOntoBookmarkOp<'op> {
             pushrebase_hooks.push(hook);
         }
 
+        if let Some(hook) = RequiredDerivedDataPushrebaseHook::new(
+            repo,
+            repo.as_blob_repo().repo_derived_data_arc(),
+            self.bookmark,
+        ) {
+            pushrebase_hooks.push(hook);
+        }
+
         let mut flags = repo.repo_config().pushrebase.flags.clone();
         if let Some(rewritedates) = repo
             .repo_bookmark_attrs()
@@ -331,6 +342,20 @@ pub fn get_pushrebase_hooks(
                 },
             );
         }
+        if attr.params().require_signed_commits {
+            return Err(
+                BookmarkMovementError::PushrebaseNotAllowedRequiresSignedCommits {
+                    bookmark: bookmark.clone(),
+                },
+            );
+        }
+        if attr.params().preconditions.iter().any(blocks_pushrebase) {
+            return Err(
+                BookmarkMovementError::PushrebaseNotAllowedRequiresStatusCheck {
+                    bookmark: bookmark.clone(),
+                },
+            );
+        }
     }
 
     if pushrebase_params.populate_git_mapping {
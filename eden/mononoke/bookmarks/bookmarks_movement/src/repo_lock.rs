@@ -14,7 +14,10 @@ use async_trait::async_trait;
 use bookmarks::BookmarkTransactionError;
 use bookmarks_types::BookmarkKind;
 use bytes::Bytes;
+use chrono::Utc;
 use context::CoreContext;
+use maintenance_windows::write_restriction;
+use metaconfig_types::RepoConfigRef;
 use mononoke_types::BonsaiChangesetMut;
 use mononoke_types::ChangesetId;
 use mononoke_types::RepositoryId;
@@ -66,11 +69,18 @@ async fn should_check_repo_lock(
 }
 
 pub(crate) async fn check_repo_lock(
-    repo: &(impl RepoLockRef + RepoPermissionCheckerRef),
+    repo: &(impl RepoConfigRef + RepoLockRef + RepoPermissionCheckerRef),
     kind: BookmarkKind,
     pushvars: Option<&HashMap<String, Bytes>>,
     idents: &MononokeIdentitySet,
 ) -> Result<(), BookmarkMovementError> {
+    // Mirrors are only written to by replication, which doesn't move
+    // bookmarks through here, so even scratch bookmarks and bypasses are
+    // rejected.
+    if let Some(mirror_config) = &repo.repo_config().mirror_config {
+        return Err(BookmarkMovementError::RepoLocked(mirror_config.read_only_info()));
+    }
+
     if should_check_repo_lock(kind, pushvars, repo.repo_permission_checker(), idents).await {
         let state = repo
             .repo_lock()
@@ -78,8 +88,13 @@ pub(crate) async fn check_repo_lock(
             .await
             .context("Failed to fetch repo lock state")?;
 
-        if let RepoLockState::Locked(reason) = state {
-            return Err(BookmarkMovementError::RepoLocked(reason));
+        if let RepoLockState::Locked(info) = state {
+            return Err(BookmarkMovementError::RepoLocked(info));
+        }
+
+        let maintenance_windows = &repo.repo_config().maintenance_windows;
+        if let Some(info) = write_restriction(maintenance_windows, Utc::now()) {
+            return Err(BookmarkMovementError::RepoLocked(info));
         }
     }
 
@@ -154,10 +169,10 @@ impl PushrebaseTransactionHook for RepoLockCommitTransactionHook {
             .check_repo_lock_with_transaction(txn)
             .await
             .context("Failed to fetch repo lock state")?;
-        if let RepoLockState::Locked(reason) = state {
+        if let RepoLockState::Locked(info) = state {
             return Err(BookmarkTransactionError::Other(anyhow!(
                 "Repo is locked: {}",
-                reason
+                info
             )));
         }
 
@@ -0,0 +1,222 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use async_trait::async_trait;
+use bookmarks::BookmarkTransactionError;
+use bookmarks_types::BookmarkKey;
+use changeset_info::ChangesetInfo;
+use context::CoreContext;
+use derived_data_manager::BonsaiDerivable;
+use fsnodes::RootFsnodeId;
+use mercurial_derived_data::MappedHgChangesetId;
+use metaconfig_types::RequiredDerivedDataType;
+use mononoke_types::BonsaiChangesetMut;
+use mononoke_types::ChangesetId;
+use pushrebase_hook::PushrebaseCommitHook;
+use pushrebase_hook::PushrebaseHook;
+use pushrebase_hook::PushrebaseTransactionHook;
+use pushrebase_hook::RebasedChangesets;
+use repo_bookmark_attrs::RepoBookmarkAttrsRef;
+use repo_derived_data::RepoDerivedData;
+use repo_derived_data::RepoDerivedDataRef;
+use skeleton_manifest::RootSkeletonManifestId;
+use sql::Transaction;
+use tokio::time::Instant;
+use unodes::RootUnodeManifestId;
+
+use crate::BookmarkMovementError;
+
+/// The derived data types that must be derived for commits that the
+/// bookmark is moved to, each with how long to spend deriving it.
+fn required_derived_data(
+    repo: &impl RepoBookmarkAttrsRef,
+    bookmark: &BookmarkKey,
+) -> Vec<(RequiredDerivedDataType, Duration)> {
+    repo.repo_bookmark_attrs()
+        .select(bookmark)
+        .flat_map(|attr| {
+            let budget = attr.params().required_derived_data_budget;
+            attr.params()
+                .required_derived_data
+                .iter()
+                .map(move |derived_data_type| (*derived_data_type, budget))
+        })
+        .collect()
+}
+
+/// Returns whether the derived data is derived for the changeset, deriving
+/// it if that finishes before the deadline.
+async fn derive_before<Derivable: BonsaiDerivable>(
+    ctx: &CoreContext,
+    derived_data: &RepoDerivedData,
+    cs_id: ChangesetId,
+    deadline: Instant,
+) -> Result<bool> {
+    if derived_data
+        .fetch_derived::<Derivable>(ctx, cs_id)
+        .await?
+        .is_some()
+    {
+        return Ok(true);
+    }
+    if Instant::now() >= deadline {
+        return Ok(false);
+    }
+    match tokio::time::timeout_at(deadline, derived_data.derive::<Derivable>(ctx, cs_id)).await {
+        Ok(res) => {
+            res?;
+            Ok(true)
+        }
+        Err(_) => Ok(false),
+    }
+}
+
+/// Returns the first of the required derived data types that couldn't be
+/// derived for the changeset within its budget, counted from `started`.
+async fn find_underived(
+    ctx: &CoreContext,
+    derived_data: &RepoDerivedData,
+    required: &[(RequiredDerivedDataType, Duration)],
+    started: Instant,
+    cs_id: ChangesetId,
+) -> Result<Option<RequiredDerivedDataType>> {
+    for (derived_data_type, budget) in required {
+        let deadline = started + *budget;
+        let derived = match derived_data_type {
+            RequiredDerivedDataType::ChangesetInfo => {
+                derive_before::<ChangesetInfo>(ctx, derived_data, cs_id, deadline).await?
+            }
+            RequiredDerivedDataType::Fsnodes => {
+                derive_before::<RootFsnodeId>(ctx, derived_data, cs_id, deadline).await?
+            }
+            RequiredDerivedDataType::HgChangesets => {
+                derive_before::<MappedHgChangesetId>(ctx, derived_data, cs_id, deadline).await?
+            }
+            RequiredDerivedDataType::SkeletonManifests => {
+                derive_before::<RootSkeletonManifestId>(ctx, derived_data, cs_id, deadline).await?
+            }
+            RequiredDerivedDataType::Unodes => {
+                derive_before::<RootUnodeManifestId>(ctx, derived_data, cs_id, deadline).await?
+            }
+        };
+        if !derived {
+            return Ok(Some(*derived_data_type));
+        }
+    }
+    Ok(None)
+}
+
+pub(crate) async fn check_required_derived_data(
+    ctx: &CoreContext,
+    repo: &(impl RepoBookmarkAttrsRef + RepoDerivedDataRef),
+    bookmark_to_move: &BookmarkKey,
+    target: ChangesetId,
+) -> Result<(), BookmarkMovementError> {
+    let required = required_derived_data(repo, bookmark_to_move);
+    let underived = find_underived(
+        ctx,
+        repo.repo_derived_data(),
+        &required,
+        Instant::now(),
+        target,
+    )
+    .await?;
+    if let Some(derived_data_type) = underived {
+        return Err(BookmarkMovementError::RequiresDerivedData {
+            bookmark: bookmark_to_move.clone(),
+            changeset_id: target,
+            derived_data_type,
+        });
+    }
+    Ok(())
+}
+
+/// Requires the derived data to be derived for the rebased commits before
+/// the bookmark is moved to them.
+#[derive(Clone)]
+pub(crate) struct RequiredDerivedDataPushrebaseHook {
+    bookmark: BookmarkKey,
+    derived_data: Arc<RepoDerivedData>,
+    required: Vec<(RequiredDerivedDataType, Duration)>,
+}
+
+impl RequiredDerivedDataPushrebaseHook {
+    pub(crate) fn new(
+        repo: &impl RepoBookmarkAttrsRef,
+        derived_data: Arc<RepoDerivedData>,
+        bookmark: &BookmarkKey,
+    ) -> Option<Box<dyn PushrebaseHook>> {
+        let required = required_derived_data(repo, bookmark);
+        if required.is_empty() {
+            return None;
+        }
+        let hook = Box::new(RequiredDerivedDataPushrebaseHook {
+            bookmark: bookmark.clone(),
+            derived_data,
+            required,
+        });
+        Some(hook as Box<dyn PushrebaseHook>)
+    }
+}
+
+#[async_trait]
+impl PushrebaseHook for RequiredDerivedDataPushrebaseHook {
+    async fn in_critical_section(&self) -> Result<Box<dyn PushrebaseCommitHook>> {
+        Ok(Box::new(self.clone()) as Box<dyn PushrebaseCommitHook>)
+    }
+}
+
+#[async_trait]
+impl PushrebaseCommitHook for RequiredDerivedDataPushrebaseHook {
+    fn post_rebase_changeset(
+        &mut self,
+        _bcs_old: ChangesetId,
+        _bcs_new: &mut BonsaiChangesetMut,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn into_transaction_hook(
+        self: Box<Self>,
+        ctx: &CoreContext,
+        rebased: &RebasedChangesets,
+    ) -> Result<Box<dyn PushrebaseTransactionHook>> {
+        // The rebased commits are new, so they always need deriving.
+        // Deriving a commit derives its ancestors too, so the stack is only
+        // derived once.
+        let started = Instant::now();
+        for (cs_id, _) in rebased.values() {
+            let underived =
+                find_underived(ctx, &self.derived_data, &self.required, started, *cs_id).await?;
+            if let Some(derived_data_type) = underived {
+                let err = BookmarkMovementError::RequiresDerivedData {
+                    bookmark: self.bookmark.clone(),
+                    changeset_id: *cs_id,
+                    derived_data_type,
+                };
+                return Err(anyhow!("{}", err));
+            }
+        }
+        Ok(self as Box<dyn PushrebaseTransactionHook>)
+    }
+}
+
+#[async_trait]
+impl PushrebaseTransactionHook for RequiredDerivedDataPushrebaseHook {
+    async fn populate_transaction(
+        &self,
+        _ctx: &CoreContext,
+        txn: Transaction,
+    ) -> Result<Transaction, BookmarkTransactionError> {
+        Ok(txn)
+    }
+}
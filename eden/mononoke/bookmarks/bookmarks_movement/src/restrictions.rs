@@ -8,6 +8,8 @@
 use bookmarks::BookmarkKey;
 use bookmarks::BookmarkKind;
 use bookmarks::BookmarkUpdateReason;
+use commit_signatures::verify_signatures;
+use commit_signatures::CommitSignaturesRef;
 use context::CoreContext;
 use futures::stream;
 use futures::StreamExt;
@@ -120,6 +122,62 @@ pub(crate) async fn check_restriction_ensure_ancestor_of(
     Ok(())
 }
 
+pub(crate) async fn check_restriction_require_signed_commits(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    bookmark_to_move: &BookmarkKey,
+    target: ChangesetId,
+) -> Result<(), BookmarkMovementError> {
+    let required = repo
+        .repo_bookmark_attrs()
+        .select(bookmark_to_move)
+        .any(|attr| attr.params().require_signed_commits);
+    if !required {
+        return Ok(());
+    }
+
+    let signatures = repo
+        .as_blob_repo()
+        .commit_signatures()
+        .get_signatures(ctx, target)
+        .await?;
+    let verification = verify_signatures(
+        &repo.repo_config().commit_signing_config,
+        target,
+        &signatures,
+    )?;
+    if !verification.is_verified() {
+        return Err(BookmarkMovementError::RequiresSignedCommit {
+            bookmark: bookmark_to_move.clone(),
+            changeset_id: target,
+            verification,
+        });
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn check_restriction_pushrebase_only(
+    ctx: &CoreContext,
+    authz: &AuthorizationContext,
+    repo: &impl Repo,
+    bookmark_to_move: &BookmarkKey,
+) -> Result<(), BookmarkMovementError> {
+    if repo
+        .repo_bookmark_attrs()
+        .is_pushrebase_only(bookmark_to_move)
+        && authz
+            .check_override_pushrebase_only(ctx, repo)
+            .await
+            .is_denied()
+    {
+        return Err(BookmarkMovementError::PushrebaseOnly {
+            bookmark: bookmark_to_move.clone(),
+        });
+    }
+    Ok(())
+}
+
 pub(crate) async fn ensure_ancestor_of(
     ctx: &CoreContext,
     repo: &impl Repo,
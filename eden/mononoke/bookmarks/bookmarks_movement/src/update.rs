@@ -195,6 +195,11 @@ impl<'op> UpdateBookmarkOp<'op> {
 
         check_bookmark_sync_config(repo, self.bookmark, kind)?;
 
+        if kind.is_public() {
+            crate::restrictions::check_restriction_pushrebase_only(ctx, authz, repo, self.bookmark)
+                .await?;
+        }
+
         self.update_policy
             .check_update_permitted(ctx, repo, lca_hint.as_ref(), self.bookmark, &self.targets)
             .await?;
@@ -244,6 +249,29 @@ impl<'op> UpdateBookmarkOp<'op> {
                     self.targets.new,
                 )
                 .await?;
+                crate::restrictions::check_restriction_require_signed_commits(
+                    ctx,
+                    repo,
+                    self.bookmark,
+                    self.targets.new,
+                )
+                .await?;
+                crate::required_derived_data::check_required_derived_data(
+                    ctx,
+                    repo,
+                    self.bookmark,
+                    self.targets.new,
+                )
+                .await?;
+                crate::preconditions::check_preconditions(
+                    ctx,
+                    repo,
+                    lca_hint,
+                    self.bookmark,
+                    Some(self.targets.old),
+                    self.targets.new,
+                )
+                .await?;
 
                 let txn_hook_fut = crate::git_mapping::populate_git_mapping_txn_hook(
                     ctx,
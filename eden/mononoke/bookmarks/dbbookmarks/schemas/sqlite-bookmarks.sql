@@ -27,7 +27,8 @@ CREATE TABLE IF NOT EXISTS bookmarks_update_log (
   name VARCHAR(512) NOT NULL,
   from_changeset_id VARBINARY(32),
   to_changeset_id VARBINARY(32),
-  reason VARCHAR(32) NOT NULL, -- enum is used in mysql
+  -- enum('pushrebase','push','blobimport','manualmove','testmove','backsyncer','xreposync','apirequest','mirror','sparseexport') NOT NULL in mysql
+  reason VARCHAR(32) NOT NULL,
   timestamp BIGINT NOT NULL,
   category VARCHAR(32) NOT NULL DEFAULT (CAST('branch' AS BLOB)),
   PRIMARY KEY (repo_id, id)
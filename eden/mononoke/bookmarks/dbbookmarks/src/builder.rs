@@ -5,9 +5,12 @@
  * GNU General Public License version 2.
  */
 
+use std::sync::Arc;
+
 use mononoke_types::RepositoryId;
 use sql_construct::SqlConstruct;
 use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::consistency::ReadConsistency;
 use sql_ext::SqlConnections;
 
 use crate::store::SqlBookmarks;
@@ -15,6 +18,7 @@ use crate::store::SqlBookmarks;
 #[derive(Clone)]
 pub struct SqlBookmarksBuilder {
     pub(crate) connections: SqlConnections,
+    pub(crate) read_consistency: Option<Arc<ReadConsistency>>,
 }
 
 impl SqlConstruct for SqlBookmarksBuilder {
@@ -23,14 +27,24 @@ impl SqlConstruct for SqlBookmarksBuilder {
     const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-bookmarks.sql");
 
     fn from_sql_connections(connections: SqlConnections) -> Self {
-        Self { connections }
+        Self {
+            connections,
+            read_consistency: None,
+        }
     }
 }
 
 impl SqlConstructFromMetadataDatabaseConfig for SqlBookmarksBuilder {}
 
 impl SqlBookmarksBuilder {
+    /// Route reads by sessions that have recently moved bookmarks to the
+    /// primary database.
+    pub fn with_read_consistency(mut self, read_consistency: Arc<ReadConsistency>) -> Self {
+        self.read_consistency = Some(read_consistency);
+        self
+    }
+
     pub fn with_repo_id(self, repo_id: RepositoryId) -> SqlBookmarks {
-        SqlBookmarks::new(repo_id, self.connections)
+        SqlBookmarks::new(repo_id, self.connections, self.read_consistency)
     }
 }
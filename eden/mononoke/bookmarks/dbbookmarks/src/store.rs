@@ -5,6 +5,8 @@
  * GNU General Public License version 2.
  */
 
+use std::sync::Arc;
+
 use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Result;
@@ -41,6 +43,7 @@ use mononoke_types::RepositoryId;
 use mononoke_types::Timestamp;
 use rand::Rng;
 use sql::Connection;
+use sql_ext::consistency::ReadConsistency;
 use sql_ext::mononoke_queries;
 use sql_ext::SqlConnections;
 use stats::prelude::*;
@@ -294,18 +297,45 @@ mononoke_queries! {
 pub struct SqlBookmarks {
     pub(crate) repo_id: RepositoryId,
     pub(crate) connections: SqlConnections,
+    pub(crate) read_consistency: Option<Arc<ReadConsistency>>,
 }
 
 impl SqlBookmarks {
-    pub(crate) fn new(repo_id: RepositoryId, connections: SqlConnections) -> Self {
+    pub(crate) fn new(
+        repo_id: RepositoryId,
+        connections: SqlConnections,
+        read_consistency: Option<Arc<ReadConsistency>>,
+    ) -> Self {
         Self {
             repo_id,
             connections,
+            read_consistency,
         }
     }
 
-    pub fn connection(&self, ctx: &CoreContext, freshness: Freshness) -> &Connection {
-        match freshness {
+    /// The freshness a read requested with `freshness` should actually use.
+    /// Reads by a session that has recently moved bookmarks go to the
+    /// primary, so that the session sees its own changes.
+    pub(crate) async fn read_freshness(
+        &self,
+        ctx: &CoreContext,
+        freshness: Freshness,
+    ) -> Freshness {
+        match (freshness, &self.read_consistency) {
+            (Freshness::MaybeStale, Some(read_consistency)) => {
+                let session = ctx.metadata().session_id();
+                if read_consistency.should_read_primary(session.as_str()).await {
+                    Freshness::MostRecent
+                } else {
+                    Freshness::MaybeStale
+                }
+            }
+            _ => freshness,
+        }
+    }
+
+    pub async fn connection(&self, ctx: &CoreContext, freshness: Freshness) -> &Connection {
+        match self.read_freshness(ctx, freshness).await {
             Freshness::MaybeStale => {
                 ctx.perf_counters()
                     .increment_counter(PerfCounterType::SqlReadsReplica);
@@ -335,32 +365,34 @@ impl SqlBookmarks {
             SessionClass::WarmBookmarksCache
         );
 
-        let conn = match freshness {
-            Freshness::MaybeStale => {
-                STATS::list_maybe_stale.add_value(1);
-                if is_wbc {
-                    STATS::list_maybe_stale_wbc.add_value(1);
-                }
-                ctx.perf_counters()
-                    .increment_counter(PerfCounterType::SqlReadsReplica);
-                self.connections.read_connection.clone()
-            }
-            Freshness::MostRecent => {
-                STATS::list.add_value(1);
-                if is_wbc {
-                    STATS::list_wbc.add_value(2);
-                }
-                ctx.perf_counters()
-                    .increment_counter(PerfCounterType::SqlReadsMaster);
-                self.connections.read_master_connection.clone()
-            }
-        };
-
-        cloned!(pagination, prefix, self.repo_id);
+        cloned!(ctx, pagination, prefix, self.repo_id);
+        let this = self.clone();
         let kinds: Vec<BookmarkKind> = kinds.to_vec();
         let categories: Vec<_> = categories.to_vec();
 
         async move {
+            let conn = match this.read_freshness(&ctx, freshness).await {
+                Freshness::MaybeStale => {
+                    STATS::list_maybe_stale.add_value(1);
+                    if is_wbc {
+                        STATS::list_maybe_stale_wbc.add_value(1);
+                    }
+                    ctx.perf_counters()
+                        .increment_counter(PerfCounterType::SqlReadsReplica);
+                    this.connections.read_connection.clone()
+                }
+                Freshness::MostRecent => {
+                    STATS::list.add_value(1);
+                    if is_wbc {
+                        STATS::list_wbc.add_value(2);
+                    }
+                    ctx.perf_counters()
+                        .increment_counter(PerfCounterType::SqlReadsMaster);
+                    this.connections.read_master_connection.clone()
+                }
+            };
+
+            let deadline = ctx.deadline();
             let rows = if prefix.is_empty() {
                 match pagination {
                     BookmarkPagination::FromStart => {
@@ -368,8 +400,9 @@ impl SqlBookmarks {
                         // names, then skip the sorting.
                         if limit == std::u64::MAX {
                             let tok: i32 = rand::thread_rng().gen();
-                            SelectAllUnordered::query(
+                            SelectAllUnordered::query_with_deadline(
                                 &conn,
+                                deadline,
                                 &repo_id,
                                 &limit,
                                 &tok,
@@ -388,22 +421,14 @@ impl SqlBookmarks {
                             })
                             .collect()
                         } else {
-                            SelectAll::query(&conn, &repo_id, &limit, &kinds, &categories)
-                                .await?
-                                .into_iter()
-                                .map(|(name, category, kind, cs_id, log_id)| {
-                                    (
-                                        BookmarkKey::with_name_and_category(name, category),
-                                        kind,
-                                        cs_id,
-                                        log_id,
-                                    )
-                                })
-                                .collect()
-                        }
-                    }
-                    BookmarkPagination::After(after) => {
-                        SelectAllAfter::query(&conn, &repo_id, &after, &limit, &kinds, &categories)
+                            SelectAll::query_with_deadline(
+                                &conn,
+                                deadline,
+                                &repo_id,
+                                &limit,
+                                &kinds,
+                                &categories,
+                            )
                             .await?
                             .into_iter()
                             .map(|(name, category, kind, cs_id, log_id)| {
@@ -415,15 +440,37 @@ impl SqlBookmarks {
                                 )
                             })
                             .collect()
+                        }
                     }
+                    BookmarkPagination::After(after) => SelectAllAfter::query_with_deadline(
+                        &conn,
+                        deadline,
+                        &repo_id,
+                        &after,
+                        &limit,
+                        &kinds,
+                        &categories,
+                    )
+                    .await?
+                    .into_iter()
+                    .map(|(name, category, kind, cs_id, log_id)| {
+                        (
+                            BookmarkKey::with_name_and_category(name, category),
+                            kind,
+                            cs_id,
+                            log_id,
+                        )
+                    })
+                    .collect(),
                 }
             } else {
                 let prefix_like_pattern = prefix.to_escaped_sql_like_pattern();
                 match pagination {
                     BookmarkPagination::FromStart => {
                         if limit == std::u64::MAX {
-                            SelectByPrefixUnordered::query(
+                            SelectByPrefixUnordered::query_with_deadline(
                                 &conn,
+                                deadline,
                                 &repo_id,
                                 &prefix_like_pattern,
                                 &"\\",
@@ -443,8 +490,9 @@ impl SqlBookmarks {
                             })
                             .collect()
                         } else {
-                            SelectByPrefix::query(
+                            SelectByPrefix::query_with_deadline(
                                 &conn,
+                                deadline,
                                 &repo_id,
                                 &prefix_like_pattern,
                                 &"\\",
@@ -465,8 +513,9 @@ impl SqlBookmarks {
                             .collect()
                         }
                     }
-                    BookmarkPagination::After(after) => SelectByPrefixAfter::query(
+                    BookmarkPagination::After(after) => SelectByPrefixAfter::query_with_deadline(
                         &conn,
+                        deadline,
                         &repo_id,
                         &prefix_like_pattern,
                         &"\\",
@@ -504,7 +553,14 @@ impl SqlBookmarks {
         let conn = self.connections.read_master_connection.clone();
         cloned!(self.repo_id, key);
         async move {
-            let rows = SelectBookmark::query(&conn, &repo_id, key.name(), key.category()).await?;
+            let rows = SelectBookmark::query_with_deadline(
+                &conn,
+                ctx.deadline(),
+                &repo_id,
+                key.name(),
+                key.category(),
+            )
+            .await?;
             Ok(rows.into_iter().next())
         }
     }
@@ -565,6 +621,7 @@ impl Bookmarks for SqlBookmarks {
             ctx,
             self.connections.write_connection.clone(),
             self.repo_id.clone(),
+            self.read_consistency.clone(),
         ))
     }
 }
@@ -579,18 +636,11 @@ impl BookmarkUpdateLog for SqlBookmarks {
         freshness: Freshness,
     ) -> BoxStream<'static, Result<(u64, Option<ChangesetId>, BookmarkUpdateReason, Timestamp)>>
     {
-        let conn = if freshness == Freshness::MostRecent {
-            ctx.perf_counters()
-                .increment_counter(PerfCounterType::SqlReadsMaster);
-            self.connections.read_master_connection.clone()
-        } else {
-            ctx.perf_counters()
-                .increment_counter(PerfCounterType::SqlReadsReplica);
-            self.connections.read_connection.clone()
-        };
+        let this = self.clone();
         let repo_id = self.repo_id;
 
         async move {
+            let conn = this.connection(&ctx, freshness).await.clone();
             let tok: i32 = rand::thread_rng().gen();
 
             let rows = match offset {
@@ -798,19 +848,12 @@ impl BookmarkUpdateLog for SqlBookmarks {
         limit: u64,
         freshness: Freshness,
     ) -> BoxStream<'static, Result<BookmarkUpdateLogEntry>> {
-        let connection = if freshness == Freshness::MostRecent {
-            ctx.perf_counters()
-                .increment_counter(PerfCounterType::SqlReadsMaster);
-            self.connections.read_master_connection.clone()
-        } else {
-            ctx.perf_counters()
-                .increment_counter(PerfCounterType::SqlReadsReplica);
-            self.connections.read_connection.clone()
-        };
+        let this = self.clone();
 
         let repo_id = self.repo_id;
 
         async move {
+            let connection = this.connection(&ctx, freshness).await.clone();
             let entries =
                 ReadNextBookmarkLogEntries::query(&connection, &id, &repo_id, &limit).await?;
 
@@ -839,18 +882,11 @@ impl BookmarkUpdateLog for SqlBookmarks {
         ctx: CoreContext,
         freshness: Freshness,
     ) -> BoxFuture<'static, Result<Option<u64>>> {
-        let connection = if freshness == Freshness::MostRecent {
-            ctx.perf_counters()
-                .increment_counter(PerfCounterType::SqlReadsMaster);
-            self.connections.read_master_connection.clone()
-        } else {
-            ctx.perf_counters()
-                .increment_counter(PerfCounterType::SqlReadsReplica);
-            self.connections.read_connection.clone()
-        };
+        let this = self.clone();
         let repo_id = self.repo_id;
 
         async move {
+            let connection = this.connection(&ctx, freshness).await.clone();
             let entries = GetLargestLogId::query(&connection, &repo_id).await?;
             let entry = entries.into_iter().next();
             match entry {
@@ -59,7 +59,7 @@ impl SqlBookmarksSubscription {
         // read). This means that the first select here will establish a snapshot, and we'll see
         // bookmarks updated prior to this log id in the second query.
 
-        let conn = sql_bookmarks.connection(ctx, freshness);
+        let conn = sql_bookmarks.connection(ctx, freshness).await;
         let txn = conn
             .start_transaction()
             .await
@@ -146,7 +146,7 @@ impl BookmarksSubscription for SqlBookmarksSubscription {
             return Ok(());
         }
 
-        let conn = self.sql_bookmarks.connection(ctx, self.freshness);
+        let conn = self.sql_bookmarks.connection(ctx, self.freshness).await;
 
         let changes =
             SelectUpdatedBookmarks::query(conn, &self.sql_bookmarks.repo_id, &self.log_id)
@@ -28,6 +28,7 @@ use mononoke_types::RepositoryId;
 use mononoke_types::Timestamp;
 use sql::Connection;
 use sql::Transaction as SqlTransaction;
+use sql_ext::consistency::ReadConsistency;
 use sql_ext::mononoke_queries;
 use stats::prelude::*;
 
@@ -429,6 +430,7 @@ impl SqlBookmarksTransactionPayload {
 
 pub struct SqlBookmarksTransaction {
     write_connection: Connection,
+    read_consistency: Option<Arc<ReadConsistency>>,
     ctx: CoreContext,
 
     /// Bookmarks that have been seen already in this transaction.
@@ -445,9 +447,11 @@ impl SqlBookmarksTransaction {
         ctx: CoreContext,
         write_connection: Connection,
         repo_id: RepositoryId,
+        read_consistency: Option<Arc<ReadConsistency>>,
     ) -> Self {
         Self {
             write_connection,
+            read_consistency,
             ctx,
             seen: HashSet::new(),
             payload: SqlBookmarksTransactionPayload::new(repo_id),
@@ -603,6 +607,7 @@ impl BookmarkTransaction for SqlBookmarksTransaction {
             ctx,
             payload,
             write_connection,
+            read_consistency,
             ..
         } = *self;
 
@@ -644,6 +649,11 @@ impl BookmarkTransaction for SqlBookmarksTransaction {
                     STATS::bookmarks_update_log_insert_success_attempt_count
                         .add_value(attempt as i64);
                     txn.commit().await?;
+                    if let Some(read_consistency) = read_consistency {
+                        read_consistency
+                            .record_write(ctx.metadata().session_id().as_str())
+                            .await;
+                    }
                     Ok(true)
                 }
                 Err(BookmarkTransactionError::LogicError) => {
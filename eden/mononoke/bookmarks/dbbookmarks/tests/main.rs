@@ -909,10 +909,13 @@ fn test_update_reason_conversion() -> Result<(), Error> {
         TestMove => {}
         XRepoSync => {}
         ApiRequest => {}
+        Mirror => {}
+        SparseExport => {}
     };
 
     let reasons = vec![
         Backsyncer, Blobimport, ManualMove, Push, Pushrebase, TestMove, XRepoSync, ApiRequest,
+        Mirror, SparseExport,
     ];
 
     for reason in reasons {
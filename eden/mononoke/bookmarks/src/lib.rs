@@ -7,11 +7,13 @@
 
 #![feature(never_type)]
 
+use std::collections::HashSet;
 use std::sync::Arc;
 
 use anyhow::Result;
 use async_trait::async_trait;
 use context::CoreContext;
+use futures::future;
 use futures::future::BoxFuture;
 use futures::future::FutureExt;
 use futures::stream::BoxStream;
@@ -23,6 +25,7 @@ mod cache;
 mod log;
 mod subscription;
 mod transaction;
+mod view;
 
 pub use bookmarks_types::Bookmark;
 pub use bookmarks_types::BookmarkCategory;
@@ -44,6 +47,8 @@ pub use subscription::BookmarksSubscription;
 pub use transaction::BookmarkTransaction;
 pub use transaction::BookmarkTransactionError;
 pub use transaction::BookmarkTransactionHook;
+pub use view::ViewBookmarkUpdateLog;
+pub use view::ViewBookmarks;
 
 #[facet::facet]
 #[async_trait]
@@ -166,3 +171,38 @@ pub fn bookmark_heads_fetcher(
         }
     })
 }
+
+/// Construct a heads fetcher that uses the given publishing bookmarks as all
+/// heads.  Publishing bookmarks that are not in the list are ignored.  If the
+/// list is empty, all publishing bookmarks are used.
+pub fn configured_bookmark_heads_fetcher(
+    bookmarks: ArcBookmarks,
+    publishing_bookmarks: Vec<BookmarkKey>,
+) -> Arc<dyn Fn(&CoreContext) -> BoxFuture<'static, Result<Vec<ChangesetId>>> + Send + Sync> {
+    if publishing_bookmarks.is_empty() {
+        return bookmark_heads_fetcher(bookmarks);
+    }
+    let publishing_bookmarks: Arc<HashSet<BookmarkKey>> =
+        Arc::new(publishing_bookmarks.into_iter().collect());
+    Arc::new({
+        move |ctx: &CoreContext| {
+            let publishing_bookmarks = publishing_bookmarks.clone();
+            bookmarks
+                .list(
+                    ctx.clone(),
+                    Freshness::MaybeStale,
+                    &BookmarkPrefix::empty(),
+                    BookmarkCategory::ALL,
+                    BookmarkKind::ALL_PUBLISHING,
+                    &BookmarkPagination::FromStart,
+                    std::u64::MAX,
+                )
+                .try_filter_map(move |(bookmark, cs_id)| {
+                    let is_publishing = publishing_bookmarks.contains(bookmark.key());
+                    future::ok(is_publishing.then_some(cs_id))
+                })
+                .try_collect()
+                .boxed()
+        }
+    })
+}
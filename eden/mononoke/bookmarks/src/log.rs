@@ -145,6 +145,12 @@ pub enum BookmarkUpdateReason {
 
     /// Bookmark was moved by an API request.
     ApiRequest,
+
+    /// Bookmark was moved to match the upstream of a mirror repo.
+    Mirror,
+
+    /// Bookmark was moved by the sparse export of another repo.
+    SparseExport,
 }
 
 impl std::fmt::Display for BookmarkUpdateReason {
@@ -160,6 +166,8 @@ impl std::fmt::Display for BookmarkUpdateReason {
             Backsyncer => "backsyncer",
             XRepoSync => "xreposync",
             ApiRequest => "apirequest",
+            Mirror => "mirror",
+            SparseExport => "sparseexport",
         };
         write!(f, "{}", s)
     }
@@ -178,6 +186,8 @@ impl ConvIr<BookmarkUpdateReason> for BookmarkUpdateReason {
             Value::Bytes(ref b) if b == b"backsyncer" => Ok(Backsyncer),
             Value::Bytes(ref b) if b == b"xreposync" => Ok(XRepoSync),
             Value::Bytes(ref b) if b == b"apirequest" => Ok(ApiRequest),
+            Value::Bytes(ref b) if b == b"mirror" => Ok(Mirror),
+            Value::Bytes(ref b) if b == b"sparseexport" => Ok(SparseExport),
             v => Err(FromValueError(v)),
         }
     }
@@ -208,6 +218,8 @@ impl From<BookmarkUpdateReason> for Value {
             Backsyncer => Value::Bytes(b"backsyncer".to_vec()),
             XRepoSync => Value::Bytes(b"xreposync".to_vec()),
             ApiRequest => Value::Bytes(b"apirequest".to_vec()),
+            Mirror => Value::Bytes(b"mirror".to_vec()),
+            SparseExport => Value::Bytes(b"sparseexport".to_vec()),
         }
     }
 }
@@ -0,0 +1,843 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Result;
+use async_trait::async_trait;
+use bookmarks_types::Bookmark;
+use bookmarks_types::BookmarkCategory;
+use bookmarks_types::BookmarkKey;
+use bookmarks_types::BookmarkKind;
+use bookmarks_types::BookmarkPagination;
+use bookmarks_types::BookmarkPrefix;
+use bookmarks_types::Freshness;
+use context::CoreContext;
+use futures::future;
+use futures::future::BoxFuture;
+use futures::future::FutureExt;
+use futures::stream;
+use futures::stream::BoxStream;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
+use mononoke_types::ChangesetId;
+use mononoke_types::Timestamp;
+use regex::Regex;
+
+use crate::log::BookmarkUpdateLog;
+use crate::log::BookmarkUpdateLogEntry;
+use crate::log::BookmarkUpdateReason;
+use crate::subscription::BookmarksSubscription;
+use crate::transaction::BookmarkTransaction;
+use crate::transaction::BookmarkTransactionHook;
+use crate::Bookmarks;
+
+/// How many bookmarks or log entries are listed from the underlying
+/// bookmarks or log at a time while listing those a view serves.
+const LIST_PAGE_SIZE: u64 = 1000;
+
+/// The bookmarks of a view of a repo, which are the bookmarks of the repo
+/// whose whole names match any of the view's patterns.
+///
+/// Views are read-only, so their transactions reject all writes.
+#[derive(Clone)]
+pub struct ViewBookmarks {
+    bookmarks: Arc<dyn Bookmarks>,
+    patterns: Arc<Vec<Regex>>,
+}
+
+impl ViewBookmarks {
+    pub fn new(bookmarks: Arc<dyn Bookmarks>, patterns: Vec<Regex>) -> Self {
+        Self {
+            bookmarks,
+            patterns: Arc::new(patterns),
+        }
+    }
+
+    fn serves(patterns: &[Regex], bookmark: &BookmarkKey) -> bool {
+        patterns
+            .iter()
+            .any(|pattern| pattern.is_match(bookmark.as_str()))
+    }
+}
+
+#[async_trait]
+impl Bookmarks for ViewBookmarks {
+    fn get(
+        &self,
+        ctx: CoreContext,
+        bookmark: &BookmarkKey,
+    ) -> BoxFuture<'static, Result<Option<ChangesetId>>> {
+        if Self::serves(&self.patterns, bookmark) {
+            self.bookmarks.get(ctx, bookmark)
+        } else {
+            future::ok(None).boxed()
+        }
+    }
+
+    fn list(
+        &self,
+        ctx: CoreContext,
+        freshness: Freshness,
+        prefix: &BookmarkPrefix,
+        categories: &[BookmarkCategory],
+        kinds: &[BookmarkKind],
+        pagination: &BookmarkPagination,
+        limit: u64,
+    ) -> BoxStream<'static, Result<(Bookmark, ChangesetId)>> {
+        let bookmarks = self.bookmarks.clone();
+        let patterns = self.patterns.clone();
+        let prefix = prefix.clone();
+        let categories = categories.to_vec();
+        let kinds = kinds.to_vec();
+        // The bookmarks the view serves may be sparse among the underlying
+        // bookmarks, so list them a page at a time until there are enough.
+        stream::try_unfold(
+            (Some(pagination.clone()), limit),
+            move |(pagination, remaining)| {
+                let bookmarks = bookmarks.clone();
+                let patterns = patterns.clone();
+                let ctx = ctx.clone();
+                let prefix = prefix.clone();
+                let categories = categories.clone();
+                let kinds = kinds.clone();
+                async move {
+                    let pagination = match pagination {
+                        Some(pagination) if remaining > 0 => pagination,
+                        _ => return Ok(None),
+                    };
+                    let page = bookmarks
+                        .list(
+                            ctx,
+                            freshness,
+                            &prefix,
+                            &categories,
+                            &kinds,
+                            &pagination,
+                            LIST_PAGE_SIZE,
+                        )
+                        .try_collect::<Vec<_>>()
+                        .await?;
+                    let next = match page.last() {
+                        Some((bookmark, _)) if page.len() as u64 == LIST_PAGE_SIZE => {
+                            Some(BookmarkPagination::After(bookmark.name().clone()))
+                        }
+                        _ => None,
+                    };
+                    let served = page
+                        .into_iter()
+                        .filter(|(bookmark, _)| Self::serves(&patterns, bookmark.key()))
+                        .take(remaining as usize)
+                        .collect::<Vec<_>>();
+                    let remaining = remaining - served.len() as u64;
+                    anyhow::Ok(Some((served, (next, remaining))))
+                }
+            },
+        )
+        .map_ok(|served| stream::iter(served.into_iter().map(Ok)))
+        .try_flatten()
+        .boxed()
+    }
+
+    fn create_transaction(&self, _ctx: CoreContext) -> Box<dyn BookmarkTransaction> {
+        Box::new(ViewBookmarksTransaction)
+    }
+
+    async fn create_subscription(
+        &self,
+        ctx: &CoreContext,
+        freshness: Freshness,
+    ) -> Result<Box<dyn BookmarksSubscription>> {
+        let subscription = self.bookmarks.create_subscription(ctx, freshness).await?;
+        Ok(Box::new(ViewBookmarksSubscription::new(
+            subscription,
+            self.patterns.clone(),
+        )))
+    }
+
+    fn drop_caches(&self) {
+        self.bookmarks.drop_caches()
+    }
+}
+
+/// A transaction on the bookmarks of a view, which rejects all writes.
+struct ViewBookmarksTransaction;
+
+impl ViewBookmarksTransaction {
+    fn read_only(bookmark: &BookmarkKey) -> Result<()> {
+        bail!(
+            "Bookmark {} can't be modified in a read-only view",
+            bookmark
+        )
+    }
+}
+
+impl BookmarkTransaction for ViewBookmarksTransaction {
+    fn update(
+        &mut self,
+        bookmark: &BookmarkKey,
+        _new_cs: ChangesetId,
+        _old_cs: ChangesetId,
+        _reason: BookmarkUpdateReason,
+    ) -> Result<()> {
+        Self::read_only(bookmark)
+    }
+
+    fn create(
+        &mut self,
+        bookmark: &BookmarkKey,
+        _new_cs: ChangesetId,
+        _reason: BookmarkUpdateReason,
+    ) -> Result<()> {
+        Self::read_only(bookmark)
+    }
+
+    fn force_set(
+        &mut self,
+        bookmark: &BookmarkKey,
+        _new_cs: ChangesetId,
+        _reason: BookmarkUpdateReason,
+    ) -> Result<()> {
+        Self::read_only(bookmark)
+    }
+
+    fn delete(
+        &mut self,
+        bookmark: &BookmarkKey,
+        _old_cs: ChangesetId,
+        _reason: BookmarkUpdateReason,
+    ) -> Result<()> {
+        Self::read_only(bookmark)
+    }
+
+    fn force_delete(
+        &mut self,
+        bookmark: &BookmarkKey,
+        _reason: BookmarkUpdateReason,
+    ) -> Result<()> {
+        Self::read_only(bookmark)
+    }
+
+    fn update_scratch(
+        &mut self,
+        bookmark: &BookmarkKey,
+        _new_cs: ChangesetId,
+        _old_cs: ChangesetId,
+    ) -> Result<()> {
+        Self::read_only(bookmark)
+    }
+
+    fn create_scratch(&mut self, bookmark: &BookmarkKey, _new_cs: ChangesetId) -> Result<()> {
+        Self::read_only(bookmark)
+    }
+
+    fn delete_scratch(&mut self, bookmark: &BookmarkKey, _old_cs: ChangesetId) -> Result<()> {
+        Self::read_only(bookmark)
+    }
+
+    fn create_publishing(
+        &mut self,
+        bookmark: &BookmarkKey,
+        _new_cs: ChangesetId,
+        _reason: BookmarkUpdateReason,
+    ) -> Result<()> {
+        Self::read_only(bookmark)
+    }
+
+    fn commit(self: Box<Self>) -> BoxFuture<'static, Result<bool>> {
+        future::err(anyhow!(
+            "Transactions can't be committed in a read-only view"
+        ))
+        .boxed()
+    }
+
+    fn commit_with_hook(
+        self: Box<Self>,
+        _txn_hook: BookmarkTransactionHook,
+    ) -> BoxFuture<'static, Result<bool>> {
+        self.commit()
+    }
+}
+
+/// A subscription to the bookmarks a view serves.
+struct ViewBookmarksSubscription {
+    subscription: Box<dyn BookmarksSubscription>,
+    patterns: Arc<Vec<Regex>>,
+    bookmarks: HashMap<BookmarkKey, (ChangesetId, BookmarkKind)>,
+}
+
+impl ViewBookmarksSubscription {
+    fn new(subscription: Box<dyn BookmarksSubscription>, patterns: Arc<Vec<Regex>>) -> Self {
+        let mut view_subscription = Self {
+            subscription,
+            patterns,
+            bookmarks: HashMap::new(),
+        };
+        view_subscription.update_bookmarks();
+        view_subscription
+    }
+
+    fn update_bookmarks(&mut self) {
+        self.bookmarks = self
+            .subscription
+            .bookmarks()
+            .iter()
+            .filter(|(bookmark, _)| ViewBookmarks::serves(&self.patterns, bookmark))
+            .map(|(bookmark, value)| (bookmark.clone(), *value))
+            .collect();
+    }
+}
+
+#[async_trait]
+impl BookmarksSubscription for ViewBookmarksSubscription {
+    async fn refresh(&mut self, ctx: &CoreContext) -> Result<()> {
+        self.subscription.refresh(ctx).await?;
+        self.update_bookmarks();
+        Ok(())
+    }
+
+    fn bookmarks(&self) -> &HashMap<BookmarkKey, (ChangesetId, BookmarkKind)> {
+        &self.bookmarks
+    }
+}
+
+/// The bookmark update log of a view of a repo, which only has the entries
+/// for the bookmarks the view serves.
+#[derive(Clone)]
+pub struct ViewBookmarkUpdateLog {
+    log: Arc<dyn BookmarkUpdateLog>,
+    patterns: Arc<Vec<Regex>>,
+}
+
+impl ViewBookmarkUpdateLog {
+    pub fn new(log: Arc<dyn BookmarkUpdateLog>, patterns: Vec<Regex>) -> Self {
+        Self {
+            log,
+            patterns: Arc::new(patterns),
+        }
+    }
+
+    /// All the entries after `id` for the bookmarks the view serves, which
+    /// are read from the underlying log a page at a time as they are needed.
+    fn served_entries(
+        &self,
+        ctx: CoreContext,
+        id: u64,
+        freshness: Freshness,
+    ) -> BoxStream<'static, Result<BookmarkUpdateLogEntry>> {
+        let log = self.log.clone();
+        let patterns = self.patterns.clone();
+        stream::try_unfold(Some(id), move |id| {
+            let log = log.clone();
+            let patterns = patterns.clone();
+            let ctx = ctx.clone();
+            async move {
+                let id = match id {
+                    Some(id) => id,
+                    None => return Ok(None),
+                };
+                let page = log
+                    .read_next_bookmark_log_entries(ctx, id, LIST_PAGE_SIZE, freshness)
+                    .try_collect::<Vec<_>>()
+                    .await?;
+                let next = match page.last() {
+                    Some(entry) if page.len() as u64 == LIST_PAGE_SIZE => Some(entry.id as u64),
+                    _ => None,
+                };
+                let served = page
+                    .into_iter()
+                    .filter(|entry| ViewBookmarks::serves(&patterns, &entry.bookmark_name))
+                    .collect::<Vec<_>>();
+                anyhow::Ok(Some((served, next)))
+            }
+        })
+        .map_ok(|served| stream::iter(served.into_iter().map(Ok)))
+        .try_flatten()
+        .boxed()
+    }
+}
+
+impl BookmarkUpdateLog for ViewBookmarkUpdateLog {
+    fn read_next_bookmark_log_entries(
+        &self,
+        ctx: CoreContext,
+        id: u64,
+        limit: u64,
+        freshness: Freshness,
+    ) -> BoxStream<'static, Result<BookmarkUpdateLogEntry>> {
+        self.served_entries(ctx, id, freshness)
+            .take(limit as usize)
+            .boxed()
+    }
+
+    fn read_next_bookmark_log_entries_same_bookmark_and_reason(
+        &self,
+        ctx: CoreContext,
+        id: u64,
+        limit: u64,
+    ) -> BoxStream<'static, Result<BookmarkUpdateLogEntry>> {
+        let mut first = None;
+        self.served_entries(ctx, id, Freshness::MaybeStale)
+            .try_take_while(move |entry| {
+                let (bookmark, reason) =
+                    first.get_or_insert_with(|| (entry.bookmark_name.clone(), entry.reason));
+                future::ok(*bookmark == entry.bookmark_name && *reason == entry.reason)
+            })
+            .take(limit as usize)
+            .boxed()
+    }
+
+    fn list_bookmark_log_entries(
+        &self,
+        ctx: CoreContext,
+        name: BookmarkKey,
+        max_rec: u32,
+        offset: Option<u32>,
+        freshness: Freshness,
+    ) -> BoxStream<'static, Result<(u64, Option<ChangesetId>, BookmarkUpdateReason, Timestamp)>>
+    {
+        if ViewBookmarks::serves(&self.patterns, &name) {
+            self.log
+                .list_bookmark_log_entries(ctx, name, max_rec, offset, freshness)
+        } else {
+            stream::empty().boxed()
+        }
+    }
+
+    fn list_bookmark_log_entries_ts_in_range(
+        &self,
+        ctx: CoreContext,
+        name: BookmarkKey,
+        max_rec: u32,
+        min_ts: Timestamp,
+        max_ts: Timestamp,
+    ) -> BoxStream<'static, Result<(u64, Option<ChangesetId>, BookmarkUpdateReason, Timestamp)>>
+    {
+        if ViewBookmarks::serves(&self.patterns, &name) {
+            self.log
+                .list_bookmark_log_entries_ts_in_range(ctx, name, max_rec, min_ts, max_ts)
+        } else {
+            stream::empty().boxed()
+        }
+    }
+
+    fn count_further_bookmark_log_entries(
+        &self,
+        ctx: CoreContext,
+        id: u64,
+        exclude_reason: Option<BookmarkUpdateReason>,
+    ) -> BoxFuture<'static, Result<u64>> {
+        self.served_entries(ctx, id, Freshness::MaybeStale)
+            .try_fold(0, move |count, entry| {
+                if Some(entry.reason) == exclude_reason {
+                    future::ok(count)
+                } else {
+                    future::ok(count + 1)
+                }
+            })
+            .boxed()
+    }
+
+    fn count_further_bookmark_log_entries_by_reason(
+        &self,
+        ctx: CoreContext,
+        id: u64,
+    ) -> BoxFuture<'static, Result<Vec<(BookmarkUpdateReason, u64)>>> {
+        let mut entries = self.served_entries(ctx, id, Freshness::MaybeStale);
+        async move {
+            let mut counts: Vec<(BookmarkUpdateReason, u64)> = Vec::new();
+            while let Some(entry) = entries.try_next().await? {
+                match counts
+                    .iter_mut()
+                    .find(|(reason, _)| *reason == entry.reason)
+                {
+                    Some((_, count)) => *count += 1,
+                    None => counts.push((entry.reason, 1)),
+                }
+            }
+            Ok(counts)
+        }
+        .boxed()
+    }
+
+    fn skip_over_bookmark_log_entries_with_reason(
+        &self,
+        ctx: CoreContext,
+        id: u64,
+        reason: BookmarkUpdateReason,
+    ) -> BoxFuture<'static, Result<Option<u64>>> {
+        let mut entries = self.served_entries(ctx, id, Freshness::MaybeStale);
+        async move {
+            // Like the underlying log, only skip over the entries with the
+            // reason if there is an entry without it after them.
+            let mut last_skipped = None;
+            while let Some(entry) = entries.try_next().await? {
+                if entry.reason != reason {
+                    return Ok(last_skipped);
+                }
+                last_skipped = Some(entry.id as u64);
+            }
+            Ok(None)
+        }
+        .boxed()
+    }
+
+    fn get_largest_log_id(
+        &self,
+        ctx: CoreContext,
+        freshness: Freshness,
+    ) -> BoxFuture<'static, Result<Option<u64>>> {
+        self.log.get_largest_log_id(ctx, freshness)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use bookmarks_types::BookmarkName;
+    use fbinit::FacebookInit;
+    use mononoke_types::RepositoryId;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+
+    use super::*;
+
+    /// Bookmarks that are only listed, which are more than fit in a page.
+    struct MockBookmarks {
+        bookmarks: BTreeMap<BookmarkKey, ChangesetId>,
+    }
+
+    #[async_trait]
+    impl Bookmarks for MockBookmarks {
+        fn get(
+            &self,
+            _ctx: CoreContext,
+            bookmark: &BookmarkKey,
+        ) -> BoxFuture<'static, Result<Option<ChangesetId>>> {
+            future::ok(self.bookmarks.get(bookmark).copied()).boxed()
+        }
+
+        fn list(
+            &self,
+            _ctx: CoreContext,
+            _freshness: Freshness,
+            _prefix: &BookmarkPrefix,
+            _categories: &[BookmarkCategory],
+            _kinds: &[BookmarkKind],
+            pagination: &BookmarkPagination,
+            limit: u64,
+        ) -> BoxStream<'static, Result<(Bookmark, ChangesetId)>> {
+            let bookmarks = self
+                .bookmarks
+                .iter()
+                .filter(|(key, _)| match pagination {
+                    BookmarkPagination::FromStart => true,
+                    BookmarkPagination::After(name) => key.name() > name,
+                })
+                .take(limit as usize)
+                .map(|(key, cs_id)| {
+                    let bookmark = Bookmark::new(key.clone(), BookmarkKind::PullDefaultPublishing);
+                    Ok((bookmark, *cs_id))
+                })
+                .collect::<Vec<_>>();
+            stream::iter(bookmarks).boxed()
+        }
+
+        fn create_transaction(&self, _ctx: CoreContext) -> Box<dyn BookmarkTransaction> {
+            unimplemented!()
+        }
+
+        async fn create_subscription(
+            &self,
+            _ctx: &CoreContext,
+            _freshness: Freshness,
+        ) -> Result<Box<dyn BookmarksSubscription>> {
+            unimplemented!()
+        }
+    }
+
+    /// A bookmark update log that is only read, which has more entries than
+    /// fit in a page.
+    struct MockBookmarkUpdateLog {
+        entries: Vec<BookmarkUpdateLogEntry>,
+    }
+
+    impl BookmarkUpdateLog for MockBookmarkUpdateLog {
+        fn read_next_bookmark_log_entries(
+            &self,
+            _ctx: CoreContext,
+            id: u64,
+            limit: u64,
+            _freshness: Freshness,
+        ) -> BoxStream<'static, Result<BookmarkUpdateLogEntry>> {
+            let entries = self
+                .entries
+                .iter()
+                .filter(|entry| entry.id as u64 > id)
+                .take(limit as usize)
+                .cloned()
+                .map(Ok)
+                .collect::<Vec<_>>();
+            stream::iter(entries).boxed()
+        }
+
+        fn read_next_bookmark_log_entries_same_bookmark_and_reason(
+            &self,
+            _ctx: CoreContext,
+            _id: u64,
+            _limit: u64,
+        ) -> BoxStream<'static, Result<BookmarkUpdateLogEntry>> {
+            unimplemented!()
+        }
+
+        fn list_bookmark_log_entries(
+            &self,
+            _ctx: CoreContext,
+            name: BookmarkKey,
+            max_rec: u32,
+            _offset: Option<u32>,
+            _freshness: Freshness,
+        ) -> BoxStream<'static, Result<(u64, Option<ChangesetId>, BookmarkUpdateReason, Timestamp)>>
+        {
+            let entries = self
+                .entries
+                .iter()
+                .rev()
+                .filter(|entry| entry.bookmark_name == name)
+                .take(max_rec as usize)
+                .map(|entry| {
+                    Ok((
+                        entry.id as u64,
+                        entry.to_changeset_id,
+                        entry.reason,
+                        entry.timestamp,
+                    ))
+                })
+                .collect::<Vec<_>>();
+            stream::iter(entries).boxed()
+        }
+
+        fn list_bookmark_log_entries_ts_in_range(
+            &self,
+            _ctx: CoreContext,
+            _name: BookmarkKey,
+            _max_rec: u32,
+            _min_ts: Timestamp,
+            _max_ts: Timestamp,
+        ) -> BoxStream<'static, Result<(u64, Option<ChangesetId>, BookmarkUpdateReason, Timestamp)>>
+        {
+            unimplemented!()
+        }
+
+        fn count_further_bookmark_log_entries(
+            &self,
+            _ctx: CoreContext,
+            _id: u64,
+            _exclude_reason: Option<BookmarkUpdateReason>,
+        ) -> BoxFuture<'static, Result<u64>> {
+            unimplemented!()
+        }
+
+        fn count_further_bookmark_log_entries_by_reason(
+            &self,
+            _ctx: CoreContext,
+            _id: u64,
+        ) -> BoxFuture<'static, Result<Vec<(BookmarkUpdateReason, u64)>>> {
+            unimplemented!()
+        }
+
+        fn skip_over_bookmark_log_entries_with_reason(
+            &self,
+            _ctx: CoreContext,
+            _id: u64,
+            _reason: BookmarkUpdateReason,
+        ) -> BoxFuture<'static, Result<Option<u64>>> {
+            unimplemented!()
+        }
+
+        fn get_largest_log_id(
+            &self,
+            _ctx: CoreContext,
+            _freshness: Freshness,
+        ) -> BoxFuture<'static, Result<Option<u64>>> {
+            future::ok(self.entries.last().map(|entry| entry.id as u64)).boxed()
+        }
+    }
+
+    fn key(name: &str) -> BookmarkKey {
+        BookmarkKey::new(name).unwrap()
+    }
+
+    #[fbinit::test]
+    async fn test_view_bookmarks(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        // Only one in ten bookmarks is served, so listing them takes more
+        // than one page of the underlying bookmarks.
+        let bookmarks = (0..2500)
+            .map(|i| (key(&format!("book{:04}", i)), ONES_CSID))
+            .collect();
+        let view = ViewBookmarks::new(
+            Arc::new(MockBookmarks { bookmarks }),
+            vec![Regex::new(r"^book\d{3}0$")?],
+        );
+
+        assert_eq!(
+            view.get(ctx.clone(), &key("book0010")).await?,
+            Some(ONES_CSID)
+        );
+        assert_eq!(view.get(ctx.clone(), &key("book0011")).await?, None);
+
+        let list = |pagination: BookmarkPagination, limit: u64| {
+            view.list(
+                ctx.clone(),
+                Freshness::MostRecent,
+                &BookmarkPrefix::empty(),
+                BookmarkCategory::ALL,
+                BookmarkKind::ALL,
+                &pagination,
+                limit,
+            )
+            .map_ok(|(bookmark, _)| bookmark.key().to_string())
+            .try_collect::<Vec<_>>()
+        };
+        let served = list(BookmarkPagination::FromStart, u64::MAX).await?;
+        assert_eq!(served.len(), 250);
+        assert_eq!(served[..2], ["book0000", "book0010"]);
+        assert_eq!(served[249], "book2490");
+
+        let limited = list(BookmarkPagination::FromStart, 120).await?;
+        assert_eq!(limited, served[..120]);
+
+        let after = list(
+            BookmarkPagination::After(BookmarkName::new("book1005")?),
+            u64::MAX,
+        )
+        .await?;
+        assert_eq!(after, served[101..]);
+
+        let mut transaction = view.create_transaction(ctx.clone());
+        assert!(
+            transaction
+                .create(&key("book0010"), TWOS_CSID, BookmarkUpdateReason::TestMove)
+                .is_err()
+        );
+        assert!(transaction.commit().await.is_err());
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_view_bookmark_update_log(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        // One in ten entries is for main, which is served, and main is moved
+        // by tests until id 2000, and by pushrebases after that.
+        let entries = (1..=2500)
+            .map(|id| BookmarkUpdateLogEntry {
+                id,
+                repo_id: RepositoryId::new(0),
+                bookmark_name: key(if id % 10 == 1 { "main" } else { "other" }),
+                from_changeset_id: None,
+                to_changeset_id: Some(TWOS_CSID),
+                reason: if id <= 2000 {
+                    BookmarkUpdateReason::TestMove
+                } else {
+                    BookmarkUpdateReason::Pushrebase
+                },
+                timestamp: Timestamp::from_timestamp_secs(id),
+            })
+            .collect();
+        let view = ViewBookmarkUpdateLog::new(
+            Arc::new(MockBookmarkUpdateLog { entries }),
+            vec![Regex::new("^main$")?],
+        );
+
+        let ids = |entries: Vec<BookmarkUpdateLogEntry>| {
+            entries
+                .into_iter()
+                .map(|entry| entry.id)
+                .collect::<Vec<_>>()
+        };
+        let next = view
+            .read_next_bookmark_log_entries(ctx.clone(), 0, 3, Freshness::MostRecent)
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(ids(next), [1, 11, 21]);
+        let next = view
+            .read_next_bookmark_log_entries(ctx.clone(), 1995, 3, Freshness::MostRecent)
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(ids(next), [2001, 2011, 2021]);
+
+        let same = view
+            .read_next_bookmark_log_entries_same_bookmark_and_reason(ctx.clone(), 0, u64::MAX)
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(same.len(), 200);
+        assert_eq!(same.last().map(|entry| entry.id), Some(1991));
+
+        assert_eq!(
+            view.count_further_bookmark_log_entries(ctx.clone(), 0, None)
+                .await?,
+            250
+        );
+        assert_eq!(
+            view.count_further_bookmark_log_entries(
+                ctx.clone(),
+                0,
+                Some(BookmarkUpdateReason::TestMove)
+            )
+            .await?,
+            50
+        );
+        assert_eq!(
+            view.count_further_bookmark_log_entries_by_reason(ctx.clone(), 0)
+                .await?,
+            [
+                (BookmarkUpdateReason::TestMove, 200),
+                (BookmarkUpdateReason::Pushrebase, 50)
+            ]
+        );
+
+        assert_eq!(
+            view.skip_over_bookmark_log_entries_with_reason(
+                ctx.clone(),
+                0,
+                BookmarkUpdateReason::TestMove
+            )
+            .await?,
+            Some(1991)
+        );
+        // There is no entry with another reason after the pushrebases, so
+        // they aren't skipped over.
+        assert_eq!(
+            view.skip_over_bookmark_log_entries_with_reason(
+                ctx.clone(),
+                1991,
+                BookmarkUpdateReason::Pushrebase
+            )
+            .await?,
+            None
+        );
+
+        let main = view
+            .list_bookmark_log_entries(ctx.clone(), key("main"), 2, None, Freshness::MostRecent)
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(main.len(), 2);
+        let other = view
+            .list_bookmark_log_entries(ctx.clone(), key("other"), 2, None, Freshness::MostRecent)
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert!(other.is_empty());
+
+        Ok(())
+    }
+}
@@ -62,6 +62,7 @@ use metaconfig_types::BlameVersion;
 use mononoke_types::ChangesetId;
 use mononoke_types::Timestamp;
 use phases::ArcPhases;
+use repo_blobstore::ArcRepoBlobstore;
 use repo_derived_data::ArcRepoDerivedData;
 use repo_identity::ArcRepoIdentity;
 use repo_identity::RepoIdentity;
@@ -74,6 +75,7 @@ use stats::prelude::*;
 use tunables::tunables;
 use unodes::RootUnodeManifestId;
 
+mod snapshot;
 mod warmers;
 pub use warmers::create_derived_data_warmer;
 pub use warmers::create_public_phase_warmer;
@@ -85,6 +87,12 @@ define_stats! {
     max_staleness_secs: dynamic_singleton_counter("{}.max_staleness_secs", (reponame: String)),
 }
 
+/// How often snapshots of the cache are exported by default.
+pub const DEFAULT_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How old a snapshot may be by default to bootstrap from it.
+pub const DEFAULT_SNAPSHOT_MAX_AGE: Duration = Duration::from_secs(600);
+
 pub struct WarmBookmarksCache {
     bookmarks: Arc<RwLock<HashMap<BookmarkKey, (ChangesetId, BookmarkKind)>>>,
     terminate: Option<oneshot::Sender<()>>,
@@ -101,6 +109,16 @@ pub struct Warmer {
     warmer: Box<WarmerFn>,
     is_warm: Box<IsWarmFn>,
     name: String,
+    /// Bookmarks this warmer applies to, or `None` if it applies to all of them.
+    bookmarks: Option<HashSet<BookmarkKey>>,
+}
+
+impl Warmer {
+    fn applies_to(&self, book: &BookmarkKey) -> bool {
+        self.bookmarks
+            .as_ref()
+            .map_or(true, |bookmarks| bookmarks.contains(book))
+    }
 }
 
 /// Initialization mode for the warm bookmarks cache.
@@ -120,6 +138,8 @@ pub struct WarmBookmarksCacheBuilder {
     repo_identity: ArcRepoIdentity,
     warmers: Vec<Warmer>,
     init_mode: InitMode,
+    snapshot_export: Option<(ArcRepoBlobstore, Duration)>,
+    snapshot_bootstrap: Option<(ArcRepoBlobstore, Duration)>,
 }
 
 impl WarmBookmarksCacheBuilder {
@@ -144,29 +164,38 @@ impl WarmBookmarksCacheBuilder {
             repo_identity,
             warmers: vec![],
             init_mode: InitMode::Rewind,
+            snapshot_export: None,
+            snapshot_bootstrap: None,
         }
     }
 
+    /// Add warmers for all active derived data types and public phases.
+    /// Public phases are only warmed for `publishing_bookmarks`, or for all
+    /// bookmarks if the list is empty.
     pub fn add_all_warmers(
         &mut self,
         repo_derived_data: &ArcRepoDerivedData,
         phases: &ArcPhases,
+        publishing_bookmarks: &[BookmarkKey],
     ) -> Result<(), Error> {
         self.add_derived_data_warmers(&repo_derived_data.active_config().types, repo_derived_data)?;
-        self.add_public_phase_warmer(phases);
+        self.add_public_phase_warmer(phases, publishing_bookmarks);
         Ok(())
     }
 
+    /// Add warmers for the data Mercurial clients need. Public phases are
+    /// warmed as in `add_all_warmers`.
     pub fn add_hg_warmers(
         &mut self,
         repo_derived_data: &ArcRepoDerivedData,
         phases: &ArcPhases,
+        publishing_bookmarks: &[BookmarkKey],
     ) -> Result<(), Error> {
         self.add_derived_data_warmers(
             vec![MappedHgChangesetId::NAME, FilenodesOnlyPublic::NAME],
             repo_derived_data,
         )?;
-        self.add_public_phase_warmer(phases);
+        self.add_public_phase_warmer(phases, publishing_bookmarks);
         Ok(())
     }
 
@@ -266,8 +295,12 @@ impl WarmBookmarksCacheBuilder {
         Ok(())
     }
 
-    fn add_public_phase_warmer(&mut self, phases: &ArcPhases) {
-        let warmer = create_public_phase_warmer(&self.ctx, phases.clone());
+    fn add_public_phase_warmer(
+        &mut self,
+        phases: &ArcPhases,
+        publishing_bookmarks: &[BookmarkKey],
+    ) {
+        let warmer = create_public_phase_warmer(&self.ctx, phases.clone(), publishing_bookmarks);
         self.warmers.push(warmer);
     }
 
@@ -279,6 +312,19 @@ impl WarmBookmarksCacheBuilder {
         self.init_mode = InitMode::Warm;
     }
 
+    /// Store a snapshot of the cache in the blobstore every `interval`, so
+    /// that other instances can bootstrap from it.
+    pub fn export_snapshots(&mut self, blobstore: ArcRepoBlobstore, interval: Duration) {
+        self.snapshot_export = Some((blobstore, interval));
+    }
+
+    /// Initialize the cache from the latest snapshot exported by another
+    /// instance rather than warming up all bookmarks, provided the snapshot
+    /// is at most `max_age` old and was warmed for at least the same data.
+    pub fn bootstrap_from_snapshot(&mut self, blobstore: ArcRepoBlobstore, max_age: Duration) {
+        self.snapshot_bootstrap = Some((blobstore, max_age));
+    }
+
     pub async fn build(self) -> Result<WarmBookmarksCache, Error> {
         WarmBookmarksCache::new(
             &self.ctx,
@@ -287,6 +333,8 @@ impl WarmBookmarksCacheBuilder {
             &self.repo_identity,
             self.warmers,
             self.init_mode,
+            self.snapshot_export,
+            self.snapshot_bootstrap,
         )
         .await
     }
@@ -366,6 +414,8 @@ impl WarmBookmarksCache {
         repo_identity: &ArcRepoIdentity,
         warmers: Vec<Warmer>,
         init_mode: InitMode,
+        snapshot_export: Option<(ArcRepoBlobstore, Duration)>,
+        snapshot_bootstrap: Option<(ArcRepoBlobstore, Duration)>,
     ) -> Result<Self, Error> {
         let warmers = Arc::new(warmers);
         let (sender, receiver) = oneshot::channel();
@@ -376,18 +426,53 @@ impl WarmBookmarksCache {
             .await
             .context("Error creating bookmarks subscription")?;
 
-        let bookmarks_to_watch = init_bookmarks(
-            ctx,
-            &*sub,
-            bookmarks.as_ref(),
-            bookmark_update_log.as_ref(),
-            &warmers,
-            init_mode,
-        )
-        .await?;
+        let from_snapshot = match snapshot_bootstrap {
+            Some((blobstore, max_age)) => {
+                // A missing or broken snapshot only makes startup slower.
+                snapshot::load_snapshot(ctx, &blobstore, &warmers, max_age)
+                    .await
+                    .unwrap_or_else(|err| {
+                        warn!(ctx.logger(), "failed to load snapshot: {:?}", err);
+                        None
+                    })
+            }
+            None => None,
+        };
+
+        let bookmarks_to_watch = match from_snapshot {
+            Some(bookmarks_to_watch) => {
+                info!(
+                    ctx.logger(),
+                    "bootstrapped {} bookmarks from snapshot",
+                    bookmarks_to_watch.len()
+                );
+                bookmarks_to_watch
+            }
+            None => {
+                init_bookmarks(
+                    ctx,
+                    &*sub,
+                    bookmarks.as_ref(),
+                    bookmark_update_log.as_ref(),
+                    &warmers,
+                    init_mode,
+                )
+                .await?
+            }
+        };
 
         let bookmarks_to_watch = Arc::new(RwLock::new(bookmarks_to_watch));
 
+        if let Some((blobstore, interval)) = snapshot_export {
+            snapshot::spawn_exporter(
+                ctx.clone(),
+                blobstore,
+                interval,
+                Arc::downgrade(&bookmarks_to_watch),
+                warmers.clone(),
+            );
+        }
+
         BookmarksCoordinator::new(
             bookmarks_to_watch.clone(),
             sub,
@@ -489,7 +574,10 @@ async fn init_bookmarks(
 
             let remaining = total - i - 1;
 
-            if !is_warm(ctx, cs_id, warmers).watched(ctx.logger()).await {
+            if !is_warm(ctx, &book, cs_id, warmers)
+                .watched(ctx.logger())
+                .await
+            {
                 match mode {
                     InitMode::Rewind => {
                         let maybe_cs_id = move_bookmark_back_in_history_until_derived(
@@ -510,7 +598,9 @@ async fn init_bookmarks(
                     }
                     InitMode::Warm => {
                         info!(ctx.logger(), "warmed bookmark {} at {}", book, cs_id);
-                        warm_all(ctx, cs_id, warmers).watched(ctx.logger()).await?;
+                        warm_all(ctx, &book, cs_id, warmers)
+                            .watched(ctx.logger())
+                            .await?;
                         Ok((remaining, Some((book, (cs_id, kind)))))
                     }
                 }
@@ -538,9 +628,15 @@ async fn init_bookmarks(
     Ok(res)
 }
 
-async fn is_warm(ctx: &CoreContext, cs_id: ChangesetId, warmers: &[Warmer]) -> bool {
+async fn is_warm(
+    ctx: &CoreContext,
+    book: &BookmarkKey,
+    cs_id: ChangesetId,
+    warmers: &[Warmer],
+) -> bool {
     let is_warm = warmers
         .iter()
+        .filter(|warmer| warmer.applies_to(book))
         .map(|warmer| (*warmer.is_warm)(ctx, cs_id).map(|res| res.unwrap_or(false)))
         .collect::<FuturesUnordered<_>>();
 
@@ -549,8 +645,14 @@ async fn is_warm(ctx: &CoreContext, cs_id: ChangesetId, warmers: &[Warmer]) -> b
         .await
 }
 
-async fn warm_all(ctx: &CoreContext, cs_id: ChangesetId, warmers: &[Warmer]) -> Result<(), Error> {
-    stream::iter(warmers.iter().map(Ok))
+async fn warm_all(
+    ctx: &CoreContext,
+    book: &BookmarkKey,
+    cs_id: ChangesetId,
+    warmers: &[Warmer],
+) -> Result<(), Error> {
+    let warmers = warmers.iter().filter(|warmer| warmer.applies_to(book));
+    stream::iter(warmers.map(Ok))
         .try_for_each_concurrent(100, |warmer| async {
             let (stats, res) = (*warmer.warmer)(ctx, cs_id).timed().await;
             let mut scuba = ctx.scuba().clone();
@@ -662,7 +764,7 @@ pub async fn find_all_underived_and_latest_derived(
             |(maybe_cs_id, id_and_ts)| async move {
                 match maybe_cs_id {
                     Some(cs_id) => {
-                        let derived = is_warm(ctx, cs_id, warmers).await;
+                        let derived = is_warm(ctx, book, cs_id, warmers).await;
                         (Some((cs_id, id_and_ts)), derived)
                     }
                     None => (None, true),
@@ -1063,7 +1165,9 @@ async fn single_bookmark_updater(
             .clone()
             .add("delay_ms", maybe_ts.map(|ts| ts.since_millis()))
             .log_with_msg("Before warming bookmark", None);
-        let (stats, res) = warm_all(&ctx, underived_cs_id, warmers).timed().await;
+        let (stats, res) = warm_all(&ctx, bookmark.key(), underived_cs_id, warmers)
+            .timed()
+            .await;
         ctx.scuba()
             .clone()
             .add_future_stats(&stats)
@@ -1105,6 +1209,8 @@ mod tests {
     use memblob::Memblob;
     use mononoke_api_types::InnerRepo;
     use mononoke_types::RepositoryId;
+    use phases::PhasesArc;
+    use phases::PhasesRef;
     use repo_derived_data::RepoDerivedDataArc;
     use repo_identity::RepoIdentityArc;
     use sql_ext::mononoke_queries;
@@ -1571,6 +1677,7 @@ mod tests {
                 }
             }),
             name: "test".to_string(),
+            bookmarks: None,
         };
         let mut warmers: Vec<Warmer> = Vec::new();
         warmers.push(warmer);
@@ -1684,6 +1791,7 @@ mod tests {
                 }
             }),
             name: "test".to_string(),
+            bookmarks: None,
         };
         let mut warmers: Vec<Warmer> = Vec::new();
         warmers.push(warmer);
@@ -1727,7 +1835,9 @@ mod tests {
             move || {
                 cloned!(ctx, master, warmers);
                 async move {
-                    let res: Result<_, Error> = Ok(is_warm(&ctx, master, &warmers).await);
+                    let master_book = BookmarkKey::new("master")?;
+                    let res: Result<_, Error> =
+                        Ok(is_warm(&ctx, &master_book, master, &warmers).await);
                     res
                 }
             }
@@ -1816,6 +1926,64 @@ mod tests {
         Ok(())
     }
 
+    #[fbinit::test]
+    async fn test_public_phase_warmer_publishing_bookmarks(fb: FacebookInit) -> Result<(), Error> {
+        let repo: InnerRepo = TestRepoFactory::new(fb)?
+            .with_config_override(|config| {
+                config.publishing_bookmarks = vec![BookmarkKey::new("master").unwrap()];
+            })
+            .build()?;
+        Linear::initrepo(fb, &repo.blob_repo).await;
+        let ctx = CoreContext::test_mock(fb);
+
+        let master = resolve_cs_id(&ctx, &repo.blob_repo, "master").await?;
+        let other = CreateCommitContext::new(&ctx, &repo.blob_repo, vec![master])
+            .add_file("other", "other")
+            .commit()
+            .await?;
+        bookmark(&ctx, &repo.blob_repo, "other")
+            .set_to(other)
+            .await?;
+
+        let mut warmers: Vec<Warmer> = Vec::new();
+        warmers.push(create_public_phase_warmer(
+            &ctx,
+            repo.phases_arc(),
+            &[BookmarkKey::new("master")?],
+        ));
+        let warmers = Arc::new(warmers);
+
+        let sub = repo
+            .blob_repo
+            .bookmarks()
+            .create_subscription(&ctx, Freshness::MostRecent)
+            .await?;
+        let bookmarks = init_bookmarks(
+            &ctx,
+            &*sub,
+            repo.bookmarks(),
+            repo.bookmark_update_log(),
+            &warmers,
+            InitMode::Warm,
+        )
+        .await?;
+
+        // Both bookmarks are served, but only the configured publishing
+        // bookmark makes its ancestors public.
+        assert_eq!(
+            bookmarks.get(&BookmarkKey::new("other")?),
+            Some(&(other, BookmarkKind::PullDefaultPublishing))
+        );
+        let public = repo
+            .phases()
+            .get_cached_public(&ctx, vec![master, other])
+            .await?;
+        assert!(public.contains(&master));
+        assert!(!public.contains(&other));
+
+        Ok(())
+    }
+
     mononoke_queries! {
         write ClearBookmarkUpdateLog(repo_id: RepositoryId) {
             none,
@@ -1840,9 +2008,9 @@ mod tests {
         let warmers = Arc::new(warmers);
 
         let master_cs_id = resolve_cs_id(&ctx, &repo.blob_repo, "master").await?;
-        warm_all(&ctx, master_cs_id, &warmers).await?;
-
         let master_book_name = BookmarkKey::new("master")?;
+        warm_all(&ctx, &master_book_name, master_cs_id, &warmers).await?;
+
         let master_book = Bookmark::new(
             master_book_name.clone(),
             BookmarkKind::PullDefaultPublishing,
@@ -0,0 +1,265 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Snapshots of the warm bookmarks cache.
+//!
+//! Warming up the cache from scratch requires checking (and possibly
+//! rewinding) every bookmark, which makes cold starts slow. A leader instance
+//! can periodically store the state of its cache in the blobstore, so that
+//! other instances can start from it and only catch up with bookmark moves
+//! since it was taken.
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::RwLock;
+use std::sync::Weak;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Context as _;
+use anyhow::Error;
+use blobstore::Blobstore;
+use blobstore::BlobstoreBytes;
+use bookmarks::BookmarkCategory;
+use bookmarks::BookmarkKey;
+use bookmarks_types::BookmarkKind;
+use bookmarks_types::BookmarkName;
+use context::CoreContext;
+use lock_ext::RwLockExt;
+use mononoke_types::ChangesetId;
+use mononoke_types::Timestamp;
+use repo_blobstore::ArcRepoBlobstore;
+use serde::Deserialize;
+use serde::Serialize;
+use slog::info;
+use slog::warn;
+
+use crate::Warmer;
+
+const SNAPSHOT_KEY: &str = "warm_bookmarks_cache.snapshot";
+const SNAPSHOT_VERSION: u32 = 1;
+
+type BookmarksMap = HashMap<BookmarkKey, (ChangesetId, BookmarkKind)>;
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SnapshotEntry {
+    name: String,
+    category: String,
+    kind: String,
+    changeset_id: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Snapshot {
+    version: u32,
+    created_at_secs: i64,
+    /// The warmers every bookmark in the snapshot has been warmed by.
+    warmers: BTreeSet<String>,
+    bookmarks: Vec<SnapshotEntry>,
+}
+
+fn parse_enum<T: Copy + ToString>(all: &[T], value: &str) -> Result<T, Error> {
+    all.iter()
+        .find(|v| v.to_string() == value)
+        .copied()
+        .ok_or_else(|| anyhow!("Unknown value: {}", value))
+}
+
+impl Snapshot {
+    fn new(bookmarks: &BookmarksMap, warmers: &[Warmer]) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            created_at_secs: Timestamp::now().timestamp_seconds(),
+            warmers: warmers.iter().map(|w| w.name.clone()).collect(),
+            bookmarks: bookmarks
+                .iter()
+                .map(|(key, (cs_id, kind))| SnapshotEntry {
+                    name: key.name().to_string(),
+                    category: key.category().to_string(),
+                    kind: kind.to_string(),
+                    changeset_id: cs_id.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Why the snapshot can't be used by an instance with these warmers, if
+    /// it can't.
+    fn unusable_reason(&self, warmers: &[Warmer], max_age: Duration) -> Option<String> {
+        if self.version != SNAPSHOT_VERSION {
+            return Some(format!("unsupported version {}", self.version));
+        }
+        let age = Timestamp::from_timestamp_secs(self.created_at_secs).since_seconds();
+        if age > max_age.as_secs() as i64 {
+            return Some(format!("snapshot is {}s old", age));
+        }
+        if let Some(warmer) = warmers.iter().find(|w| !self.warmers.contains(&w.name)) {
+            return Some(format!("snapshot is not warm for {}", warmer.name));
+        }
+        None
+    }
+
+    fn into_bookmarks(self) -> Result<BookmarksMap, Error> {
+        self.bookmarks
+            .into_iter()
+            .map(|entry| {
+                let name = BookmarkName::new(&entry.name)?;
+                let category = parse_enum(BookmarkCategory::ALL, &entry.category)?;
+                let kind = parse_enum(BookmarkKind::ALL, &entry.kind)?;
+                let cs_id = ChangesetId::from_str(&entry.changeset_id)?;
+                let key = BookmarkKey::with_name_and_category(name, category);
+                Ok((key, (cs_id, kind)))
+            })
+            .collect()
+    }
+}
+
+/// Store a snapshot of the cache in the blobstore.
+pub(crate) async fn export_snapshot(
+    ctx: &CoreContext,
+    blobstore: &ArcRepoBlobstore,
+    bookmarks: &BookmarksMap,
+    warmers: &[Warmer],
+) -> Result<(), Error> {
+    let snapshot = Snapshot::new(bookmarks, warmers);
+    let bytes = serde_json::to_vec(&snapshot)?;
+    blobstore
+        .put(
+            ctx,
+            SNAPSHOT_KEY.to_string(),
+            BlobstoreBytes::from_bytes(bytes),
+        )
+        .await
+}
+
+/// Load the bookmarks of the latest snapshot, if there is one that is recent
+/// enough and was taken by an instance warming at least the same things.
+pub(crate) async fn load_snapshot(
+    ctx: &CoreContext,
+    blobstore: &ArcRepoBlobstore,
+    warmers: &[Warmer],
+    max_age: Duration,
+) -> Result<Option<BookmarksMap>, Error> {
+    let data = match blobstore.get(ctx, SNAPSHOT_KEY).await? {
+        Some(data) => data,
+        None => {
+            info!(ctx.logger(), "No warm bookmarks cache snapshot found");
+            return Ok(None);
+        }
+    };
+    let snapshot: Snapshot = serde_json::from_slice(data.as_raw_bytes())
+        .context("Invalid warm bookmarks cache snapshot")?;
+
+    if let Some(reason) = snapshot.unusable_reason(warmers, max_age) {
+        info!(
+            ctx.logger(),
+            "Not using warm bookmarks cache snapshot: {}", reason
+        );
+        return Ok(None);
+    }
+    Ok(Some(snapshot.into_bookmarks()?))
+}
+
+/// Periodically export snapshots of the cache, until the cache is dropped.
+pub(crate) fn spawn_exporter(
+    ctx: CoreContext,
+    blobstore: ArcRepoBlobstore,
+    interval: Duration,
+    bookmarks: Weak<RwLock<BookmarksMap>>,
+    warmers: Arc<Vec<Warmer>>,
+) {
+    let fut = async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            // The coordinator holds the only other reference to the
+            // bookmarks, and stops when the cache is dropped.
+            let bookmarks = match bookmarks.upgrade() {
+                Some(bookmarks) => bookmarks.with_read(|bookmarks| bookmarks.clone()),
+                None => break,
+            };
+            if let Err(err) = export_snapshot(&ctx, &blobstore, &bookmarks, &warmers).await {
+                warn!(
+                    ctx.logger(),
+                    "failed to export warm bookmarks cache snapshot: {:?}", err
+                );
+            }
+        }
+    };
+
+    // Fire and forget. This terminates once the cache is dropped.
+    std::mem::drop(tokio::task::spawn(fut));
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use futures::future::FutureExt;
+    use maplit::hashmap;
+    use memblob::Memblob;
+    use mononoke_types::RepositoryId;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+    use repo_blobstore::RepoBlobstore;
+    use scuba_ext::MononokeScubaSampleBuilder;
+
+    use super::*;
+
+    fn warmer(name: &str) -> Warmer {
+        Warmer {
+            warmer: Box::new(|_ctx, _cs_id| async { Ok(()) }.boxed()),
+            is_warm: Box::new(|_ctx, _cs_id| async { Ok(true) }.boxed()),
+            name: name.to_string(),
+            bookmarks: None,
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_snapshot_roundtrip(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let blobstore = Arc::new(RepoBlobstore::new(
+            Arc::new(Memblob::default()),
+            None,
+            RepositoryId::new(0),
+            MononokeScubaSampleBuilder::with_discard(),
+        ));
+        let max_age = Duration::from_secs(3600);
+        let warmers = vec![warmer("hgchangesets"), warmer("unodes")];
+
+        assert_eq!(
+            load_snapshot(&ctx, &blobstore, &warmers, max_age).await?,
+            None
+        );
+
+        let bookmarks = hashmap! {
+            BookmarkKey::new("main")? => (ONES_CSID, BookmarkKind::PullDefaultPublishing),
+            BookmarkKey::with_name_and_category(
+                BookmarkName::new("v1.0")?,
+                BookmarkCategory::Tag,
+            ) => (TWOS_CSID, BookmarkKind::Publishing),
+        };
+        export_snapshot(&ctx, &blobstore, &bookmarks, &warmers).await?;
+
+        assert_eq!(
+            load_snapshot(&ctx, &blobstore, &warmers, max_age).await?,
+            Some(bookmarks.clone())
+        );
+        // An instance warming a subset of the data can use the snapshot.
+        assert_eq!(
+            load_snapshot(&ctx, &blobstore, &warmers[..1], max_age).await?,
+            Some(bookmarks)
+        );
+        // An instance warming more data can't.
+        let more_warmers = vec![warmer("hgchangesets"), warmer("fsnodes")];
+        assert_eq!(
+            load_snapshot(&ctx, &blobstore, &more_warmers, max_age).await?,
+            None
+        );
+        Ok(())
+    }
+}
@@ -5,6 +5,7 @@
  * GNU General Public License version 2.
  */
 
+use bookmarks::BookmarkKey;
 use cloned::cloned;
 use context::CoreContext;
 use derived_data_manager::BonsaiDerivable;
@@ -59,10 +60,17 @@ where
         warmer,
         is_warm,
         name: Derivable::NAME.to_string(),
+        bookmarks: None,
     }
 }
 
-pub fn create_public_phase_warmer(ctx: &CoreContext, phases: ArcPhases) -> Warmer {
+/// Create a warmer that marks bookmark ancestors as public. Only
+/// `publishing_bookmarks` are warmed, or all bookmarks if the list is empty.
+pub fn create_public_phase_warmer(
+    ctx: &CoreContext,
+    phases: ArcPhases,
+    publishing_bookmarks: &[BookmarkKey],
+) -> Warmer {
     info!(ctx.logger(), "Warming public phases");
     let warmer: Box<WarmerFn> = Box::new({
         cloned!(phases);
@@ -91,5 +99,10 @@ pub fn create_public_phase_warmer(ctx: &CoreContext, phases: ArcPhases) -> Warme
         warmer,
         is_warm,
         name: "public phases".to_string(),
+        bookmarks: if publishing_bookmarks.is_empty() {
+            None
+        } else {
+            Some(publishing_bookmarks.iter().cloned().collect())
+        },
     }
 }
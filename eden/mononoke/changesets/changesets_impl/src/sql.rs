@@ -400,8 +400,15 @@ impl Changesets for SqlChangesets {
         ctx.perf_counters()
             .increment_counter(PerfCounterType::SqlReadsReplica);
 
-        let fetched_cs =
-            select_many_changesets(ctx.fb, &self.read_connection, self.repo_id, &cs_ids).await?;
+        // Reads are abandoned once nobody is waiting for them.
+        let fetched_cs = ctx
+            .run_until_abandoned(select_many_changesets(
+                ctx.fb,
+                &self.read_connection,
+                self.repo_id,
+                &cs_ids,
+            ))
+            .await??;
         let fetched_set: HashSet<_> = fetched_cs
             .clone()
             .into_iter()
@@ -418,13 +425,14 @@ impl Changesets for SqlChangesets {
             STATS::gets_master.add_value(1);
             ctx.perf_counters()
                 .increment_counter(PerfCounterType::SqlReadsMaster);
-            let mut master_fetched_cs = select_many_changesets(
-                ctx.fb,
-                &self.read_master_connection,
-                self.repo_id,
-                &notfetched_cs_ids,
-            )
-            .await?;
+            let mut master_fetched_cs = ctx
+                .run_until_abandoned(select_many_changesets(
+                    ctx.fb,
+                    &self.read_master_connection,
+                    self.repo_id,
+                    &notfetched_cs_ids,
+                ))
+                .await??;
             master_fetched_cs.extend(fetched_cs);
             Ok(master_fetched_cs)
         }
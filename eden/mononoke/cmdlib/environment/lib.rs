@@ -54,6 +54,16 @@ pub enum WarmBookmarksCacheDerivedData {
     NoDerivation,
 }
 
+/// How the warm bookmarks cache shares its state with other instances
+/// through snapshots stored in the blobstore.
+#[derive(Copy, Clone, Debug, ValueEnum, EnumString, strum_macros::Display)]
+pub enum WarmBookmarksCacheSnapshotMode {
+    /// Periodically export snapshots for other instances to bootstrap from.
+    Export,
+    /// Bootstrap from the latest snapshot, if it is recent enough.
+    Bootstrap,
+}
+
 /// Struct representing the configuration associated with a MononokeApp instance which
 /// is immutable post the point of app construction.
 pub struct MononokeEnvironment {
@@ -75,6 +85,7 @@ pub struct MononokeEnvironment {
     pub acl_provider: Arc<dyn AclProvider>,
     pub skiplist_enabled: bool,
     pub warm_bookmarks_cache_derived_data: Option<WarmBookmarksCacheDerivedData>,
+    pub warm_bookmarks_cache_snapshot: Option<WarmBookmarksCacheSnapshotMode>,
     /// Function determining whether given repo (identified by name) should be loaded
     pub filter_repos: Option<Arc<dyn Fn(&str) -> bool + Send + Sync + 'static>>,
 }
@@ -18,13 +18,40 @@ pub struct ScribeLoggingArgs {
     /// Filesystem directory where to log all scribe writes
     #[clap(long)]
     pub scribe_logging_directory: Option<String>,
+
+    /// Comma-separated list of Kafka brokers to publish scribe writes to,
+    /// instead of Scribe (requires the kafka feature)
+    #[clap(long, conflicts_with = "scribe-logging-directory")]
+    pub scribe_kafka_brokers: Option<String>,
+
+    /// Prefix for the Kafka topic each scribe category is published to
+    #[clap(long, default_value = "")]
+    pub scribe_kafka_topic_prefix: String,
 }
 
 impl ScribeLoggingArgs {
     pub fn get_scribe(&self, fb: FacebookInit) -> Result<Scribe> {
-        match &self.scribe_logging_directory {
-            Some(dir) => Ok(Scribe::new_to_file(PathBuf::from(dir))),
-            None => Ok(Scribe::new(fb)),
+        if let Some(dir) = &self.scribe_logging_directory {
+            return Ok(Scribe::new_to_file(PathBuf::from(dir)));
+        }
+        if let Some(brokers) = &self.scribe_kafka_brokers {
+            return kafka_scribe(brokers, &self.scribe_kafka_topic_prefix);
         }
+        Ok(Scribe::new(fb))
     }
 }
+
+#[cfg(feature = "kafka")]
+fn kafka_scribe(brokers: &str, topic_prefix: &str) -> Result<Scribe> {
+    use std::sync::Arc;
+
+    use scribe_ext::KafkaQueue;
+
+    let queue = KafkaQueue::new(brokers, topic_prefix)?;
+    Ok(Scribe::new_with_queue(Arc::new(queue)))
+}
+
+#[cfg(not(feature = "kafka"))]
+fn kafka_scribe(_brokers: &str, _topic_prefix: &str) -> Result<Scribe> {
+    anyhow::bail!("--scribe-kafka-brokers requires building with the kafka feature")
+}
@@ -28,6 +28,7 @@ use clap::ArgMatches;
 use clap::Error as ClapError;
 use clap::FromArgMatches;
 use cmdlib_running::run_until_terminated;
+use context::log_keys;
 use context::CoreContext;
 use environment::MononokeEnvironment;
 use facet::AsyncBuildable;
@@ -349,7 +350,7 @@ impl MononokeApp {
 
     /// Construct a logger for a specific repo.
     pub fn repo_logger(&self, repo_name: &str) -> Logger {
-        self.env.logger.new(o!("repo" => repo_name.to_string()))
+        self.env.logger.new(o!(log_keys::REPO => repo_name.to_string()))
     }
 
     /// The mysql options for this app.
@@ -497,7 +498,21 @@ impl MononokeApp {
     where
         Repo: for<'builder> AsyncBuildable<'builder, RepoFactoryBuilder<'builder>>,
     {
-        let (repo_name, mut repo_config) = self.repo_config(repo_arg.as_repo_arg())?;
+        let (repo_name, repo_config) = self.repo_config(repo_arg.as_repo_arg())?;
+        self.create_repo_from_config(repo_name, repo_config).await
+    }
+
+    /// Create a new repo object from a config that need not be one of the
+    /// app's configs, e.g. for a repo that has just been added.
+    /// Makes sure that the opened repo has redaction DISABLED
+    pub async fn create_repo_from_config<Repo>(
+        &self,
+        repo_name: String,
+        mut repo_config: RepoConfig,
+    ) -> Result<Repo>
+    where
+        Repo: for<'builder> AsyncBuildable<'builder, RepoFactoryBuilder<'builder>>,
+    {
         let common_config = self.repo_configs().common.clone();
 
         match &repo_config.storage_config.blobstore {
@@ -552,30 +567,29 @@ impl MononokeApp {
     {
         let repo_filter = self.environment().filter_repos.clone();
         let service_name = service.clone();
-        let repo_names =
-            self.repo_configs()
-                .repos
-                .clone()
-                .into_iter()
-                .filter_map(|(name, config)| {
-                    let is_matching_filter =
-                        repo_filter.as_ref().map_or(true, |filter| filter(&name));
-                    let is_deep_sharded = service
-                        .as_ref()
-                        .and_then(|service| {
-                            config
-                                .deep_sharding_config
-                                .and_then(|c| c.status.get(service).copied())
-                        })
-                        .unwrap_or(false);
-                    // Initialize repos that are enabled and not deep-sharded (i.e. need to exist
-                    // at service startup)
-                    if config.enabled && !is_deep_sharded && is_matching_filter {
-                        Some(name)
-                    } else {
-                        None
-                    }
-                });
+        let repo_configs = self.repo_configs();
+        let repo_names = repo_configs
+            .served_repos()
+            .filter_map(|(name, config)| {
+                let is_matching_filter = repo_filter.as_ref().map_or(true, |filter| filter(name));
+                let is_deep_sharded = service
+                    .as_ref()
+                    .and_then(|service| {
+                        config
+                            .deep_sharding_config
+                            .as_ref()
+                            .and_then(|c| c.status.get(service).copied())
+                    })
+                    .unwrap_or(false);
+                // Initialize repos that are enabled and not deep-sharded (i.e. need to exist
+                // at service startup)
+                if config.enabled && !is_deep_sharded && is_matching_filter {
+                    Some(name.clone())
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
         self.open_named_managed_repos(repo_names, service_name)
             .await
     }
@@ -9,6 +9,7 @@ use anyhow::Result;
 use clap::Parser;
 use environment::MononokeEnvironment;
 use environment::WarmBookmarksCacheDerivedData;
+use environment::WarmBookmarksCacheSnapshotMode;
 
 use crate::AppExtension;
 
@@ -18,6 +19,11 @@ pub struct WarmBookmarksCacheArgs {
     /// What needs to be warmed
     #[clap(long, value_enum, value_name = "warm derived data")]
     pub enable_wbc_with: Option<WarmBookmarksCacheDerivedData>,
+
+    /// Share the warm bookmarks cache with other instances through snapshots
+    /// in the blobstore, to speed up their startup
+    #[clap(long, value_enum, value_name = "snapshot mode")]
+    pub wbc_snapshot: Option<WarmBookmarksCacheSnapshotMode>,
 }
 
 pub struct WarmBookmarksCacheExtension;
@@ -29,6 +35,7 @@ impl AppExtension for WarmBookmarksCacheExtension {
         if let Some(wbc_config) = args.enable_wbc_with {
             env.warm_bookmarks_cache_derived_data = Some(wbc_config)
         }
+        env.warm_bookmarks_cache_snapshot = args.wbc_snapshot;
         Ok(())
     }
 }
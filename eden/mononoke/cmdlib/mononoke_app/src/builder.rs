@@ -381,6 +381,7 @@ impl MononokeAppBuilder {
             disabled_hooks: HashMap::new(),
             skiplist_enabled: self.skiplist_enabled,
             warm_bookmarks_cache_derived_data: self.warm_bookmarks_cache_derived_data,
+            warm_bookmarks_cache_snapshot: None,
             filter_repos: None,
         })
     }
@@ -519,6 +520,13 @@ fn create_blobstore_options(
         cachelib_blobstore_options,
         blobstore_put_behaviour,
         mysql_sqlblob_options,
+    )
+    .with_hot_key_options(blobstore_args.hot_key_options())
+    .with_single_flight_scope(blobstore_args.single_flight_scope())
+    .with_consistency_sampler_options(blobstore_args.consistency_sampler_options())
+    .with_local_region(
+        blobstore_args.blobstore_local_region.clone(),
+        blobstore_args.read_hedge_delay(),
     );
 
     Ok(blobstore_options)
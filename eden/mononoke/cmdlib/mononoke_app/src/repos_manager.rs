@@ -12,6 +12,7 @@ use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Result;
 use async_trait::async_trait;
+use context::log_keys;
 use facet::AsyncBuildable;
 use futures::stream;
 use futures::stream::StreamExt;
@@ -41,6 +42,15 @@ define_stats! {
     completion_duration_secs: timeseries(Average, Sum, Count),
 }
 
+/// The id a repo can be found by in a MononokeRepos collection.  Views of
+/// other repos share the id of the repo they are a view of, so have none.
+fn repo_id(repo_config: &RepoConfig) -> Option<i32> {
+    match repo_config.view {
+        Some(_) => None,
+        None => Some(repo_config.repoid.id()),
+    }
+}
+
 /// A manager of a MononokeRepos collection.
 ///
 /// This allows repos to be added or removed from the MononokeRepos
@@ -98,16 +108,16 @@ impl<Repo> MononokeReposManager<Repo> {
 
     /// Construct a logger for a specific repo.
     pub fn repo_logger(&self, repo_name: &str) -> Logger {
-        self.logger.new(o!("repo" => repo_name.to_string()))
+        self.logger.new(o!(log_keys::REPO => repo_name.to_string()))
     }
 
-    /// Return a repo config for a named repo.  This reads from the main
-    /// configuration, so doesn't need to be a currently managed repo.
+    /// Return a repo config for a named repo, or a view of a repo.  This
+    /// reads from the main configuration, so doesn't need to be a currently
+    /// managed repo.
     pub fn repo_config(&self, repo_name: &str) -> Result<RepoConfig> {
         self.configs
             .repo_configs()
-            .repos
-            .get(repo_name)
+            .get_served_repo_config(repo_name)
             .cloned()
             .ok_or_else(|| anyhow!("unknown reponame: {:?}", repo_name))
     }
@@ -118,7 +128,7 @@ impl<Repo> MononokeReposManager<Repo> {
         Repo: for<'builder> AsyncBuildable<'builder, RepoFactoryBuilder<'builder>>,
     {
         let repo_config = self.repo_config(repo_name)?;
-        let repo_id = repo_config.repoid.id();
+        let repo_id = repo_id(&repo_config);
         let common_config = self.configs.repo_configs().common.clone();
         let repo = self
             .repo_factory
@@ -149,13 +159,13 @@ impl<Repo> MononokeReposManager<Repo> {
                     let logger = self.logger();
                     let repo_config = self.repo_config(&repo_name)?;
                     let common_config = self.configs.repo_configs().common.clone();
-                    let repo_id = repo_config.repoid.id();
-                    info!(logger, "Initializing repo: {}", &repo_name);
+                    let repo_id = repo_id(&repo_config);
+                    info!(logger, "Initializing repo"; log_keys::REPO => &repo_name);
                     let repo = repo_factory
                         .build(name, repo_config, common_config)
                         .await
                         .with_context(|| format!("Failed to initialize repo '{}'", &repo_name))?;
-                    info!(logger, "Initialized repo: {}", &repo_name);
+                    info!(logger, "Initialized repo"; log_keys::REPO => &repo_name);
                     STATS::initialization_time_millisecs.add_value(
                         start.elapsed().as_millis().try_into().unwrap_or(i64::MAX),
                         (repo_name.to_string(),),
@@ -182,7 +192,7 @@ impl<Repo> MononokeReposManager<Repo> {
 impl MononokeReposManager<mononoke_api::Repo> {
     pub fn make_mononoke_api(&self) -> Result<Mononoke> {
         let repo_names_in_tier =
-            Vec::from_iter(self.configs.repo_configs().repos.iter().filter_map(
+            Vec::from_iter(self.configs.repo_configs().served_repos().filter_map(
                 |(name, config)| {
                     if config.enabled {
                         Some(name.to_string())
@@ -231,7 +241,8 @@ where
         _: Arc<StorageConfigs>,
     ) -> Result<()> {
         let mut repos_to_load = Vec::new();
-        for (repo_name, repo_config) in repo_configs.repos.clone().into_iter() {
+        for (repo_name, repo_config) in repo_configs.served_repos() {
+            let (repo_name, repo_config) = (repo_name.clone(), repo_config.clone());
             if self.repos.get_by_name(repo_name.as_str()).is_some() {
                 // Repo was already present on the server. Need to reload it.
                 repos_to_load.push((repo_name, repo_config))
@@ -265,13 +276,13 @@ where
                 let logger = self.logger.clone();
                 let common_config = repo_configs.common.clone();
                 async move {
-                    let repo_id = repo_config.repoid.id();
-                    info!(logger, "Reloading repo: {}", &repo_name);
+                    let repo_id = repo_id(&repo_config);
+                    info!(logger, "Reloading repo"; log_keys::REPO => &repo_name);
                     let repo = repo_factory
                         .build(name, repo_config, common_config)
                         .await
                         .with_context(|| format!("Failed to reload repo '{}'", &repo_name))?;
-                    info!(logger, "Reloaded repo: {}", &repo_name);
+                    info!(logger, "Reloaded repo"; log_keys::REPO => &repo_name);
 
                     anyhow::Ok((repo_id, repo_name, repo))
                 }
@@ -238,6 +238,7 @@ impl<'a> MononokeMatches<'a> {
                     disabled_hooks: HashMap::new(),
                     skiplist_enabled: true,
                     warm_bookmarks_cache_derived_data: None,
+                    warm_bookmarks_cache_snapshot: None,
                     filter_repos: None,
                 }),
                 app_data,
@@ -764,6 +764,7 @@ async fn create_commit_for_mapping_change(
         file_changes: file_changes.into(),
         is_snapshot: false,
         git_annotated_tag: None,
+        extras: None,
     }
     .freeze()?;
 
@@ -31,6 +31,8 @@ use futures::stream;
 use futures::stream::StreamExt;
 use futures::stream::TryStreamExt;
 use mercurial_types::FileBytes;
+use metaconfig_types::MaintenanceWindow;
+use mononoke_app::args::AsRepoArg;
 use mononoke_app::args::RepoArgs;
 use mononoke_app::fb303::AliveService;
 use mononoke_app::fb303::Fb303AppExtension;
@@ -89,17 +91,25 @@ struct AliasVerification {
     #[allow(dead_code)]
     repoid: RepositoryId,
     mode: Mode,
+    maintenance_windows: Vec<MaintenanceWindow>,
     err_cnt: Arc<AtomicUsize>,
     cs_processed: Arc<AtomicUsize>,
 }
 
 impl AliasVerification {
-    pub fn new(logger: Logger, blobrepo: BlobRepo, repoid: RepositoryId, mode: Mode) -> Self {
+    pub fn new(
+        logger: Logger,
+        blobrepo: BlobRepo,
+        repoid: RepositoryId,
+        mode: Mode,
+        maintenance_windows: Vec<MaintenanceWindow>,
+    ) -> Self {
         Self {
             logger,
             blobrepo,
             repoid,
             mode,
+            maintenance_windows,
             err_cnt: Arc::new(AtomicUsize::new(0)),
             cs_processed: Arc::new(AtomicUsize::new(0)),
         }
@@ -292,7 +302,15 @@ impl AliasVerification {
 
         stream::iter(bounds)
             .map(Ok)
-            .try_for_each(move |(min_val, max_val)| self.get_bounded(ctx, min_val, max_val))
+            .try_for_each(move |(min_val, max_val)| async move {
+                maintenance_windows::wait_until_background_jobs_allowed(
+                    ctx,
+                    self.blobrepo.repo_identity().name(),
+                    &self.maintenance_windows,
+                )
+                .await;
+                self.get_bounded(ctx, min_val, max_val).await
+            })
             .await?;
 
         self.print_report(false);
@@ -310,12 +328,14 @@ async fn async_main(app: MononokeApp) -> Result<(), Error> {
     let step = args.step;
     let min_cs_db_id = args.min_cs_db_id;
 
+    let (_repo_name, repo_config) = app.repo_config(args.repo.as_repo_arg())?;
     let repo: BlobRepo = app
         .open_repo(&args.repo)
         .await
         .context("Failed to open repo")?;
     let repo_id = repo.repo_identity().id();
-    AliasVerification::new(logger.clone(), repo, repo_id, mode)
+    let windows = repo_config.maintenance_windows;
+    AliasVerification::new(logger.clone(), repo, repo_id, mode, windows)
         .verify_all(&ctx, step, min_cs_db_id)
         .await
 }
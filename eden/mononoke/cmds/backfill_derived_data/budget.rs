@@ -0,0 +1,168 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Budgets for the resources that deriving a chunk of changesets may use.
+//!
+//! Derived blobs are held in memory until a whole chunk has been derived, so
+//! a chunk of changesets with massive manifests can use a lot of memory.  The
+//! write cache is flushed early once it holds more than the
+//! `derived_data_write_cache_max_bytes` tunable, which bounds a single chunk;
+//! the budgets here keep chunks small enough that this is rarely needed.
+//! When a chunk exceeds its budget, the chunks after it are shrunk in
+//! proportion, and they grow back towards the requested size while there is
+//! room.
+
+use clap_old::App;
+use clap_old::Arg;
+use clap_old::ArgMatches;
+use cmdlib::args;
+
+const ARG_MAX_CHUNK_MEMORY: &str = "max-chunk-memory";
+const ARG_MAX_CHUNK_BLOB_BYTES: &str = "max-chunk-blob-bytes";
+
+/// What a chunk of changesets used when it was derived.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ChunkUsage {
+    /// Memory used to hold the chunk's derived blobs until they were
+    /// written.
+    pub memory_bytes: usize,
+    /// Total size of the chunk's derived blobs.
+    pub blob_bytes: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ChunkBudget {
+    max_memory_bytes: Option<usize>,
+    max_blob_bytes: Option<usize>,
+}
+
+impl ChunkBudget {
+    pub fn add_opts<'a, 'b>(subcommand: App<'a, 'b>) -> App<'a, 'b> {
+        subcommand
+            .arg(
+                Arg::with_name(ARG_MAX_CHUNK_MEMORY)
+                    .long(ARG_MAX_CHUNK_MEMORY)
+                    .takes_value(true)
+                    .help(
+                        "shrink chunks that use more than this many bytes of memory to hold \
+                        derived blobs",
+                    ),
+            )
+            .arg(
+                Arg::with_name(ARG_MAX_CHUNK_BLOB_BYTES)
+                    .long(ARG_MAX_CHUNK_BLOB_BYTES)
+                    .takes_value(true)
+                    .help("shrink chunks that derive more than this many bytes of blobs"),
+            )
+    }
+
+    pub fn from_matches(matches: &ArgMatches<'_>) -> Self {
+        Self {
+            max_memory_bytes: args::get_usize_opt(matches, ARG_MAX_CHUNK_MEMORY),
+            max_blob_bytes: args::get_usize_opt(matches, ARG_MAX_CHUNK_BLOB_BYTES),
+        }
+    }
+
+    /// The factor by which a chunk that used `usage` would have to be scaled
+    /// to just fit within the budget, if the budget limits it.
+    fn scale(&self, usage: ChunkUsage) -> Option<f64> {
+        let scale = |max: Option<usize>, used: usize| match max {
+            Some(max) if used > 0 => Some(max as f64 / used as f64),
+            _ => None,
+        };
+        [
+            scale(self.max_memory_bytes, usage.memory_bytes),
+            scale(self.max_blob_bytes, usage.blob_bytes),
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(f64::min)
+    }
+}
+
+/// Sizes chunks of changesets to keep them within a budget.
+pub struct ChunkSizer {
+    budget: ChunkBudget,
+    max_chunk_size: usize,
+    chunk_size: usize,
+}
+
+impl ChunkSizer {
+    pub fn new(budget: ChunkBudget, max_chunk_size: usize) -> Self {
+        Self {
+            budget,
+            max_chunk_size,
+            chunk_size: max_chunk_size,
+        }
+    }
+
+    /// The size of the next chunk.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Record what a chunk of `len` changesets used, and size the next chunk
+    /// so that it would fit within the budget if its changesets use as much
+    /// as these did.  Chunks at most double in size from one to the next.
+    /// Returns whether the chunk exceeded the budget.
+    pub fn record(&mut self, len: usize, usage: ChunkUsage) -> bool {
+        let scale = match self.budget.scale(usage) {
+            Some(scale) if len > 0 => scale,
+            _ => return false,
+        };
+        let fitting = (len as f64 * scale) as usize;
+        self.chunk_size = fitting.clamp(1, self.max_chunk_size.min(len * 2).max(1));
+        scale < 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(memory_bytes: usize, blob_bytes: usize) -> ChunkUsage {
+        ChunkUsage {
+            memory_bytes,
+            blob_bytes,
+        }
+    }
+
+    #[test]
+    fn test_chunk_sizer() {
+        let budget = ChunkBudget {
+            max_memory_bytes: Some(1000),
+            max_blob_bytes: Some(600),
+        };
+        let mut sizer = ChunkSizer::new(budget, 100);
+        assert_eq!(sizer.chunk_size(), 100);
+
+        // Over the memory budget.
+        assert!(sizer.record(100, usage(4000, 400)));
+        assert_eq!(sizer.chunk_size(), 25);
+
+        // The tightest budget wins.
+        assert!(sizer.record(25, usage(1000, 1200)));
+        assert_eq!(sizer.chunk_size(), 12);
+
+        // Even single changesets over the budget are derived.
+        assert!(sizer.record(12, usage(100_000, 0)));
+        assert_eq!(sizer.chunk_size(), 1);
+
+        // Chunks with room grow back, at most doubling each time.
+        assert!(!sizer.record(1, usage(10, 10)));
+        assert_eq!(sizer.chunk_size(), 2);
+        assert!(!sizer.record(64, usage(100, 100)));
+        assert_eq!(sizer.chunk_size(), 100);
+    }
+
+    #[test]
+    fn test_chunk_sizer_without_budget() {
+        let mut sizer = ChunkSizer::new(ChunkBudget::default(), 100);
+        assert!(!sizer.record(100, usage(usize::MAX, usize::MAX)));
+        assert_eq!(sizer.chunk_size(), 100);
+    }
+}
@@ -96,16 +96,28 @@ use topo_sort::sort_topological;
 use tunables::tunables;
 use wait_for_replication::WaitForReplication;
 
+mod budget;
 mod commit_discovery;
 mod regenerate;
 mod slice;
 mod validation;
 
+use budget::ChunkBudget;
+use budget::ChunkSizer;
+use budget::ChunkUsage;
 use commit_discovery::CommitDiscoveryOptions;
 
 define_stats! {
     prefix = "mononoke.derived_data";
     oldest_underived_secs: dynamic_singleton_counter("{}.oldest_underived_secs", (reponame: String)),
+    oldest_underived_secs_by_type: dynamic_singleton_counter(
+        "{}.{}.oldest_underived_secs",
+        (reponame: String, derived_data_type: String)
+    ),
+    pending_heads_by_type: dynamic_singleton_counter(
+        "{}.{}.pending_heads",
+        (reponame: String, derived_data_type: String)
+    ),
     derivation_time_ms: dynamic_timeseries("{}.derivation_time_ms", (reponame: String); Average, Sum),
     derivation_idle_time_ms: dynamic_timeseries("{}.idle_time_ms", (reponame: String); Sum),
 }
@@ -115,6 +127,7 @@ const ARG_DERIVED_DATA_TYPE: &str = "derived-data-type";
 const ARG_SKIP: &str = "skip-changesets";
 const ARG_LIMIT: &str = "limit";
 const ARG_REGENERATE: &str = "regenerate";
+const ARG_UPGRADE_FORMAT: &str = "upgrade-format";
 const ARG_PREFETCHED_COMMITS_PATH: &str = "prefetched-commits-path";
 const ARG_CHANGESET: &str = "changeset";
 const ARG_USE_SHARED_LEASES: &str = "use-shared-leases";
@@ -128,7 +141,11 @@ const ARG_BACKFILL: &str = "backfill";
 const ARG_GAP_SIZE: &str = "gap-size";
 const ARG_JSON: &str = "json";
 const ARG_VALIDATE_CHUNK_SIZE: &str = "validate-chunk-size";
+const ARG_VALIDATE_PATH: &str = "validate-path";
+const ARG_VALIDATE_EXCLUDE_PATH: &str = "validate-exclude-path";
+const ARG_VALIDATE_MAX_DEPTH: &str = "validate-max-depth";
 const ARG_BACKFILL_CONFIG_NAME: &str = "backfill-config-name";
+const ARG_TRACE: &str = "trace";
 
 const SUBCOMMAND_BACKFILL: &str = "backfill";
 const SUBCOMMAND_BACKFILL_ALL: &str = "backfill-all";
@@ -203,7 +220,7 @@ impl DerivedDataProcess {
             .build()
             .about("Utility to work with bonsai derived data")
             .subcommand(
-                SubCommand::with_name(SUBCOMMAND_BACKFILL)
+                budget::ChunkBudget::add_opts(SubCommand::with_name(SUBCOMMAND_BACKFILL))
                     .about("backfill derived data for public commits")
                     .arg(
                         Arg::with_name(ARG_DERIVED_DATA_TYPE)
@@ -229,6 +246,15 @@ impl DerivedDataProcess {
                             .long(ARG_REGENERATE)
                             .help("regenerate derivations even if mapping contains changeset"),
                     )
+                    .arg(
+                        Arg::with_name(ARG_UPGRADE_FORMAT)
+                            .long(ARG_UPGRADE_FORMAT)
+                            .conflicts_with(ARG_REGENERATE)
+                            .help(
+                                "regenerate derivations that were derived at an older format \
+                                version than the current one",
+                            ),
+                    )
                     .arg(
                         Arg::with_name(ARG_PREFETCHED_COMMITS_PATH)
                             .long(ARG_PREFETCHED_COMMITS_PATH)
@@ -351,6 +377,16 @@ impl DerivedDataProcess {
                             .takes_value(false)
                             .help("derive all derived data types enabled for this repo"),
                     )
+                    .arg(
+                        Arg::with_name(ARG_TRACE)
+                            .long(ARG_TRACE)
+                            .required(false)
+                            .takes_value(false)
+                            .help(
+                                "print a JSON trace of the derivations that deriving the \
+                                changeset required",
+                            ),
+                    )
                     .arg(
                         Arg::with_name(ARG_CHANGESET)
                             .required(true)
@@ -465,7 +501,7 @@ impl DerivedDataProcess {
             .subcommand(
                 regenerate::DeriveOptions::add_opts(
                     commit_discovery::CommitDiscoveryOptions::add_opts(
-                        SubCommand::with_name(SUBCOMMAND_VALIDATE)
+                        budget::ChunkBudget::add_opts(SubCommand::with_name(SUBCOMMAND_VALIDATE))
                             .about(
                                 "rederive the commits and make sure they are saved to the storage",
                             )
@@ -487,6 +523,28 @@ impl DerivedDataProcess {
                         .default_value(DEFAULT_VALIDATE_CHUNK_SIZE)
                         .help("how many commits to validate at once."),
                 )
+                .arg(
+                    Arg::with_name(ARG_VALIDATE_PATH)
+                        .long(ARG_VALIDATE_PATH)
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("only validate the generated blobs under this path"),
+                )
+                .arg(
+                    Arg::with_name(ARG_VALIDATE_EXCLUDE_PATH)
+                        .long(ARG_VALIDATE_EXCLUDE_PATH)
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("don't validate the generated blobs under this path"),
+                )
+                .arg(
+                    Arg::with_name(ARG_VALIDATE_MAX_DEPTH)
+                        .long(ARG_VALIDATE_MAX_DEPTH)
+                        .takes_value(true)
+                        .help("only validate the blobs at most this many directories deep"),
+                )
                 .arg(
                     Arg::with_name(ARG_JSON)
                         .long(ARG_JSON)
@@ -766,6 +824,7 @@ async fn run_subcmd<'a>(
                 .to_string();
 
             let regenerate = sub_m.is_present(ARG_REGENERATE);
+            let upgrade_format = sub_m.is_present(ARG_UPGRADE_FORMAT);
 
             let skip = sub_m
                 .value_of(ARG_SKIP)
@@ -815,12 +874,14 @@ async fn run_subcmd<'a>(
                 &repo,
                 derived_data_type.as_str(),
                 regenerate,
+                upgrade_format,
                 parallel,
                 batch_size,
                 gap_size,
                 changesets,
                 backfill_config_name,
                 wait_for_replication,
+                ChunkBudget::from_matches(sub_m),
             )
             .await
         }
@@ -900,7 +961,8 @@ async fn run_subcmd<'a>(
             let (repo, types) =
                 parse_repo_and_derived_data_types(fb, logger, matches, sub_m, repo_name).await?;
             let csid = helpers::csid_resolve(ctx, repo.clone(), hash_or_bookmark).await?;
-            subcommand_single(ctx, &repo, csid, types).await
+            let trace = sub_m.is_present(ARG_TRACE);
+            subcommand_single(ctx, &repo, csid, types, trace).await
         }
         (SUBCOMMAND_BENCHMARK, Some(sub_m)) => {
             let (repo, types) =
@@ -1105,12 +1167,14 @@ async fn subcommand_backfill(
     repo: &InnerRepo,
     derived_data_type: &str,
     regenerate: bool,
+    upgrade_format: bool,
     parallel: bool,
     batch_size: usize,
     gap_size: Option<usize>,
     changesets: Vec<ChangesetId>,
     config_name: &str,
     wait_for_replication: WaitForReplication,
+    budget: ChunkBudget,
 ) -> Result<()> {
     let derived_utils =
         &derived_data_utils_for_config(ctx.fb, &repo.blob_repo, derived_data_type, config_name)?;
@@ -1130,17 +1194,36 @@ async fn subcommand_backfill(
         derived_utils.regenerate(&changesets);
     }
 
-    for chunk in changesets.chunks(batch_size) {
+    let mut sizer = ChunkSizer::new(budget, batch_size);
+    let mut remaining = changesets.as_slice();
+    while !remaining.is_empty() {
+        let (chunk, rest) = remaining.split_at(sizer.chunk_size().min(remaining.len()));
+        remaining = rest;
+        maintenance_windows::wait_until_background_jobs_allowed(
+            ctx,
+            repo.repo_identity().name(),
+            &repo.repo_config.maintenance_windows,
+        )
+        .await;
         info!(
             ctx.logger(),
             "starting batch of {} from {}",
             chunk.len(),
             chunk.first().unwrap()
         );
-        let (stats, chunk_size) = async {
+        let (stats, result) = async {
             wait_for_replication
                 .wait_for_replication(ctx.logger())
                 .await?;
+            if upgrade_format {
+                let outdated = derived_utils.outdated(ctx, chunk.to_vec()).await?;
+                info!(
+                    ctx.logger(),
+                    "{} changesets were derived at an older format version",
+                    outdated.len()
+                );
+                derived_utils.regenerate(&outdated);
+            }
             let chunk = derived_utils
                 .pending(
                     ctx.clone(),
@@ -1153,7 +1236,7 @@ async fn subcommand_backfill(
             warmup::warmup(ctx, &repo.blob_repo, derived_data_type, &chunk).await?;
             info!(ctx.logger(), "warmup of {} changesets complete", chunk_size);
 
-            derived_utils
+            let outcome = derived_utils
                 .derive_exactly_batch(
                     get_batch_ctx(ctx, parallel || gap_size.is_some()).await,
                     repo.repo_derived_data_arc(),
@@ -1162,12 +1245,31 @@ async fn subcommand_backfill(
                     gap_size,
                 )
                 .await?;
-            Result::<_>::Ok(chunk_size)
+            // Without a write cache there is nothing held in memory to
+            // budget for.
+            let usage = outcome.usage.map(|usage| ChunkUsage {
+                memory_bytes: usage.peak_memory_bytes,
+                blob_bytes: usage.blob_bytes,
+            });
+            Result::<_>::Ok((chunk_size, usage))
         }
         .timed()
         .await;
 
-        let chunk_size = chunk_size?;
+        let (chunk_size, usage) = result?;
+        if let Some(usage) = usage {
+            if sizer.record(chunk_size, usage) {
+                info!(
+                    ctx.logger(),
+                    "batch of {} exceeded its budget with {} bytes of memory for {} bytes of \
+                    blobs, shrinking batches to {}",
+                    chunk_size,
+                    usage.memory_bytes,
+                    usage.blob_bytes,
+                    sizer.chunk_size(),
+                );
+            }
+        }
         generated_count += chunk_size;
         let elapsed = stats.completion_time;
         total_duration += elapsed;
@@ -1620,10 +1722,20 @@ async fn tail_one_iteration(
 
     let pending = future::try_join_all(find_pending_futs).await?;
 
-    // Log oldest underived ancestor to ods
+    // Log oldest underived ancestor to ods, both overall and for each type
     let mut oldest_underived_age = 0;
-    for (_, _, cur_oldest_underived_age) in &pending {
+    for (derive, cur_pending, cur_oldest_underived_age) in &pending {
         oldest_underived_age = ::std::cmp::max(oldest_underived_age, *cur_oldest_underived_age);
+        let key = (
+            repo.repo_identity().name().to_string(),
+            derive.name().to_string(),
+        );
+        STATS::oldest_underived_secs_by_type.set_value(
+            ctx.fb,
+            *cur_oldest_underived_age,
+            key.clone(),
+        );
+        STATS::pending_heads_by_type.set_value(ctx.fb, cur_pending.len() as i64, key);
     }
     STATS::oldest_underived_secs.set_value(
         ctx.fb,
@@ -1675,6 +1787,7 @@ async fn subcommand_single(
     repo: &BlobRepo,
     csid: ChangesetId,
     derived_data_types: Vec<String>,
+    trace: bool,
 ) -> Result<()> {
     let repo = repo.dangerous_override(|_| Arc::new(DummyLease {}) as Arc<dyn LeaseOps>);
     let mut derived_utils = vec![];
@@ -1683,6 +1796,15 @@ async fn subcommand_single(
         utils.regenerate(&[csid]);
         derived_utils.push(utils);
     }
+    if trace {
+        // Traces are printed one after another, so derive the types in turn.
+        // Like any other backfill, this derives as much history as it must.
+        for derived_utils in derived_utils {
+            let trace = derived_utils.derive_traced(ctx, csid, u64::MAX).await?;
+            println!("{}", serde_json::to_string_pretty(&trace)?);
+        }
+        return Ok(());
+    }
     stream::iter(derived_utils)
         .map(Ok)
         .try_for_each_concurrent(100, |derived_utils| {
@@ -1764,6 +1886,7 @@ mod tests {
             &repo,
             master,
             vec![RootUnodeManifestId::NAME.to_string()],
+            false,
         )
         .await?;
 
@@ -1773,6 +1896,7 @@ mod tests {
             &repo,
             master,
             vec![RootUnodeManifestId::NAME.to_string()],
+            false,
         )
         .await?;
         assert!(counting_blobstore.writes_count() > writes_count);
@@ -35,13 +35,15 @@ use futures::future::try_join_all;
 use futures::stream;
 use futures::StreamExt;
 use futures::TryStreamExt;
-use manifest::find_intersection_of_diffs;
-use manifest::find_intersection_of_diffs_and_parents;
+use manifest::find_intersection_of_diffs_and_parents_with_filter;
+use manifest::find_intersection_of_diffs_with_filter;
+use manifest::AsyncManifest;
+use manifest::DiffFilter;
 use manifest::Entry;
-use manifest::Manifest;
 use mercurial_derived_data::MappedHgChangesetId;
 use mononoke_types::BlobstoreKey;
 use mononoke_types::ChangesetId;
+use mononoke_types::MPath;
 use readonlyblob::ReadOnlyBlobstore;
 use repo_blobstore::RepoBlobstoreArc;
 use repo_blobstore::RepoBlobstoreRef;
@@ -52,10 +54,16 @@ use slog::info;
 use slog::warn;
 use unodes::RootUnodeManifestId;
 
+use crate::budget::ChunkBudget;
+use crate::budget::ChunkSizer;
+use crate::budget::ChunkUsage;
 use crate::commit_discovery::CommitDiscoveryOptions;
 use crate::regenerate;
 use crate::ARG_DERIVED_DATA_TYPE;
 use crate::ARG_VALIDATE_CHUNK_SIZE;
+use crate::ARG_VALIDATE_EXCLUDE_PATH;
+use crate::ARG_VALIDATE_MAX_DEPTH;
+use crate::ARG_VALIDATE_PATH;
 
 pub async fn validate(
     ctx: &CoreContext,
@@ -84,12 +92,17 @@ pub async fn validate(
         repo.repo_identity().name()
     );
     let opts = regenerate::DeriveOptions::from_matches(sub_m)?;
+    let filter = diff_filter_from_matches(sub_m)?;
 
     let validate_chunk_size = args::get_usize(&sub_m, ARG_VALIDATE_CHUNK_SIZE, 10000);
+    let mut sizer = ChunkSizer::new(ChunkBudget::from_matches(sub_m), validate_chunk_size);
     let warn_once = Once::new();
 
     info!(ctx.logger(), "Started validation");
-    for chunk in csids.chunks(validate_chunk_size) {
+    let mut remaining = csids.as_slice();
+    while !remaining.is_empty() {
+        let (chunk, rest) = remaining.split_at(sizer.chunk_size().min(remaining.len()));
+        remaining = rest;
         let chunk = chunk.to_vec();
         info!(
             ctx.logger(),
@@ -128,9 +141,25 @@ pub async fn validate(
 
         regenerate::regenerate_derived_data(ctx, &repo, chunk.clone(), types, &opts).await?;
 
-        {
+        let cache_usage = {
             let cache = memblobstore.get_cache().lock().unwrap();
             info!(ctx.logger(), "created {} blobs", cache.len());
+            cache.usage()
+        };
+        let usage = ChunkUsage {
+            memory_bytes: cache_usage.peak_memory_bytes,
+            blob_bytes: cache_usage.blob_bytes,
+        };
+        if sizer.record(chunk.len(), usage) {
+            info!(
+                ctx.logger(),
+                "chunk of {} exceeded its budget with {} bytes of memory for {} bytes of blobs, \
+                shrinking chunks to {}",
+                chunk.len(),
+                usage.memory_bytes,
+                usage.blob_bytes,
+                sizer.chunk_size(),
+            );
         }
         let real_derived_utils = &derived_data_utils(ctx.fb, &orig_repo, derived_data_type)?;
 
@@ -143,7 +172,7 @@ pub async fn validate(
         });
         let rederived_utils = &derived_data_utils(ctx.fb, &repo, derived_data_type)?;
 
-        borrowed!(ctx, orig_repo, repo, warn_once);
+        borrowed!(ctx, orig_repo, repo, warn_once, filter);
         stream::iter(chunk)
             .map(Ok)
             .try_for_each_concurrent(100, |csid| async move {
@@ -159,9 +188,17 @@ pub async fn validate(
                     return Err(anyhow!("mismatch in {}: {} vs {}", csid, real, rederived));
                 };
 
-                validate_generated_data(ctx, orig_repo, warn_once, real_derived_utils, csid, repo)
-                    .await
-                    .with_context(|| format!("failed validating generated data for {}", csid))
+                validate_generated_data(
+                    ctx,
+                    orig_repo,
+                    warn_once,
+                    real_derived_utils,
+                    csid,
+                    repo,
+                    filter,
+                )
+                .await
+                .with_context(|| format!("failed validating generated data for {}", csid))
             })
             .await?;
         info!(ctx.logger(), "Validation successful!");
@@ -170,6 +207,25 @@ pub async fn validate(
     Ok(())
 }
 
+/// The part of each manifest whose generated blobs are validated.
+fn diff_filter_from_matches(sub_m: &ArgMatches<'_>) -> Result<DiffFilter, Error> {
+    let mut filter = DiffFilter::new();
+    for path in sub_m.values_of(ARG_VALIDATE_PATH).into_iter().flatten() {
+        filter = filter.include(MPath::new(path)?);
+    }
+    for path in sub_m
+        .values_of(ARG_VALIDATE_EXCLUDE_PATH)
+        .into_iter()
+        .flatten()
+    {
+        filter = filter.exclude(MPath::new(path)?);
+    }
+    if let Some(max_depth) = args::get_usize_opt(sub_m, ARG_VALIDATE_MAX_DEPTH) {
+        filter = filter.max_depth(max_depth);
+    }
+    Ok(filter)
+}
+
 async fn validate_generated_data<'a>(
     ctx: &'a CoreContext,
     real_repo: &'a BlobRepo,
@@ -177,16 +233,17 @@ async fn validate_generated_data<'a>(
     real_derived_utils: &'a Arc<dyn DerivedUtils>,
     cs_id: ChangesetId,
     mem_blob_repo: &'a BlobRepo,
+    filter: &DiffFilter,
 ) -> Result<(), Error> {
     let mem_blob = mem_blob_repo.repo_blobstore_arc() as Arc<dyn Blobstore>;
     if real_derived_utils.name() == RootFsnodeId::NAME {
-        validate_fsnodes(ctx, real_repo, cs_id, &mem_blob).await?;
+        validate_fsnodes(ctx, real_repo, cs_id, &mem_blob, filter).await?;
     } else if real_derived_utils.name() == RootSkeletonManifestId::NAME {
-        validate_skeleton_manifests(ctx, real_repo, cs_id, &mem_blob).await?;
+        validate_skeleton_manifests(ctx, real_repo, cs_id, &mem_blob, filter).await?;
     } else if real_derived_utils.name() == RootUnodeManifestId::NAME {
-        validate_unodes(ctx, real_repo, cs_id, &mem_blob).await?;
+        validate_unodes(ctx, real_repo, cs_id, &mem_blob, filter).await?;
     } else if real_derived_utils.name() == MappedHgChangesetId::NAME {
-        validate_hgchangesets(ctx, real_repo, cs_id, &mem_blob).await?;
+        validate_hgchangesets(ctx, real_repo, cs_id, &mem_blob, filter).await?;
     } else {
         warn_once.call_once(||
             warn!(
@@ -205,6 +262,7 @@ async fn validate_fsnodes<'a>(
     real_repo: &'a BlobRepo,
     cs_id: ChangesetId,
     mem_blob: &'a Arc<dyn Blobstore>,
+    filter: &DiffFilter,
 ) -> Result<(), Error> {
     let real_blobstore = real_repo.repo_blobstore_arc();
     let (fsnode, parents) =
@@ -221,6 +279,7 @@ async fn validate_fsnodes<'a>(
         fsnode,
         parents,
         mem_blob,
+        filter.clone(),
         |tree_id| Some(tree_id.blobstore_key()),
         |_| None,
     )
@@ -234,6 +293,7 @@ async fn validate_skeleton_manifests<'a>(
     real_repo: &'a BlobRepo,
     cs_id: ChangesetId,
     mem_blob: &'a Arc<dyn Blobstore>,
+    filter: &DiffFilter,
 ) -> Result<(), Error> {
     let real_blobstore = real_repo.repo_blobstore_arc();
 
@@ -251,6 +311,7 @@ async fn validate_skeleton_manifests<'a>(
         skeleton_manifest,
         parents,
         mem_blob,
+        filter.clone(),
         |tree_id| Some(tree_id.blobstore_key()),
         |_| None,
     )
@@ -264,6 +325,7 @@ async fn validate_unodes<'a>(
     real_repo: &'a BlobRepo,
     cs_id: ChangesetId,
     mem_blob: &'a Arc<dyn Blobstore>,
+    filter: &DiffFilter,
 ) -> Result<(), Error> {
     let real_blobstore = real_repo.repo_blobstore_arc();
     let (unode, parents) =
@@ -279,6 +341,7 @@ async fn validate_unodes<'a>(
         unode,
         parents,
         mem_blob,
+        filter.clone(),
         |tree_id| Some(tree_id.blobstore_key()),
         |leaf_id| Some(leaf_id.blobstore_key()),
     )
@@ -292,6 +355,7 @@ async fn validate_hgchangesets<'a>(
     real_repo: &'a BlobRepo,
     cs_id: ChangesetId,
     mem_blob: &'a Arc<dyn Blobstore>,
+    filter: &DiffFilter,
 ) -> Result<(), Error> {
     let real_blobstore = real_repo.repo_blobstore_arc();
 
@@ -321,26 +385,31 @@ async fn validate_hgchangesets<'a>(
 
     let (manifest, parents) = try_join(manifest, parents).await?;
 
-    let mf_entries =
-        find_intersection_of_diffs_and_parents(ctx.clone(), real_blobstore, manifest, parents)
-            .try_filter_map(|(_, entry, parent_entries)| async move {
-                match entry {
-                    Entry::Leaf((ty, filenode_id)) => {
-                        for p in parent_entries {
-                            if let Entry::Leaf((_ty, parent_filenode_id)) = p {
-                                // This is mode-only change, no new blobstore writes were made
-                                if parent_filenode_id == filenode_id {
-                                    return Ok(None);
-                                }
-                            }
+    let mf_entries = find_intersection_of_diffs_and_parents_with_filter(
+        ctx.clone(),
+        real_blobstore,
+        manifest,
+        parents,
+        filter.clone(),
+    )
+    .try_filter_map(|(_, entry, parent_entries)| async move {
+        match entry {
+            Entry::Leaf((ty, filenode_id)) => {
+                for p in parent_entries {
+                    if let Entry::Leaf((_ty, parent_filenode_id)) = p {
+                        // This is mode-only change, no new blobstore writes were made
+                        if parent_filenode_id == filenode_id {
+                            return Ok(None);
                         }
-                        Ok(Some(Entry::Leaf((ty, filenode_id))))
                     }
-                    Entry::Tree(manifest_id) => Ok(Some(Entry::Tree(manifest_id))),
                 }
-            })
-            .try_collect::<Vec<_>>()
-            .await?;
+                Ok(Some(Entry::Leaf((ty, filenode_id))))
+            }
+            Entry::Tree(manifest_id) => Ok(Some(Entry::Tree(manifest_id))),
+        }
+    })
+    .try_collect::<Vec<_>>()
+    .await?;
 
     for entry in mf_entries {
         let key = match entry {
@@ -377,19 +446,26 @@ async fn validate_new_manifest_entries<TreeId, LeafId>(
     mfid: TreeId,
     parent_mfids: Vec<TreeId>,
     mem_blob: &Arc<dyn Blobstore>,
+    filter: DiffFilter,
     tree_blob_key: impl Fn(TreeId) -> Option<String>,
     leaf_blob_key: impl Fn(LeafId) -> Option<String>,
 ) -> Result<(), Error>
 where
     TreeId: StoreLoadable<Arc<dyn Blobstore>> + Clone + Send + Sync + Eq + Unpin + 'static,
     <TreeId as StoreLoadable<Arc<dyn Blobstore>>>::Value:
-        Manifest<TreeId = TreeId, LeafId = LeafId> + Send,
+        AsyncManifest<Arc<dyn Blobstore>, TreeId = TreeId, LeafId = LeafId> + Send + Sync,
     LeafId: Clone + Send + Eq + Unpin + 'static,
 {
-    let mf_entries = find_intersection_of_diffs(ctx.clone(), real_blobstore, mfid, parent_mfids)
-        .map_ok(|(_, entry)| entry)
-        .try_collect::<Vec<_>>()
-        .await?;
+    let mf_entries = find_intersection_of_diffs_with_filter(
+        ctx.clone(),
+        real_blobstore,
+        mfid,
+        parent_mfids,
+        filter,
+    )
+    .map_ok(|(_, entry)| entry)
+    .try_collect::<Vec<_>>()
+    .await?;
 
     for entry in mf_entries {
         let maybe_key = match entry {
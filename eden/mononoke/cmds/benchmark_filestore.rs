@@ -131,6 +131,7 @@ async fn run_benchmark_filestore<'a>(
 
     let config = FilestoreConfig {
         chunk_size: Some(chunk_size),
+        chunking_threshold: None,
         concurrency,
     };
 
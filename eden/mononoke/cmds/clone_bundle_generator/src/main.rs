@@ -0,0 +1,219 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use blobrepo::AsBlobRepo;
+use blobrepo::BlobRepo;
+use bookmarks::BookmarkKey;
+use bookmarks::Bookmarks;
+use bookmarks::BookmarksRef;
+use clap::Parser;
+use clone_bundles::upload_clonebundles_manifest;
+use clone_bundles::write_clone_bundle;
+use clone_bundles::CloneBundleEntry;
+use clone_bundles::BUNDLESPEC;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use getbundle_response::SessionLfsParams;
+use mercurial_derived_data::DeriveHgChangeset;
+use mercurial_types::HgChangesetId;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::fb303::AliveService;
+use mononoke_app::fb303::Fb303AppExtension;
+use mononoke_app::MononokeApp;
+use mononoke_app::MononokeAppBuilder;
+use reachabilityindex::LeastCommonAncestorsHint;
+use repo_blobstore::RepoBlobstore;
+use repo_blobstore::RepoBlobstoreRef;
+use repo_derived_data::RepoDerivedData;
+use repo_identity::RepoIdentity;
+use repo_identity::RepoIdentityRef;
+use skiplist::SkiplistIndex;
+use skiplist::SkiplistIndexArc;
+use slog::info;
+
+/// Generate a full clone bundle of a repo and advertise it to hg clients.
+///
+/// The bundle is written to a directory that is expected to be served by a
+/// CDN or other static file server at the given URL prefix.  The most
+/// recent bundles for previous bookmark positions are left in place, so that
+/// clients that are still downloading them aren't interrupted.
+///
+/// Clients only fetch the manifest from repos that advertise the
+/// `clonebundles` wireproto capability, which is enabled by adding it to the
+/// extra capabilities in the repo config.
+#[derive(Parser)]
+struct CloneBundleGeneratorArgs {
+    #[clap(flatten)]
+    repo_args: RepoArgs,
+
+    /// Bookmark to generate the bundle at.
+    #[clap(long, default_value = "master")]
+    bookmark: String,
+
+    /// Directory to write the bundle to.
+    #[clap(long)]
+    output_dir: PathBuf,
+
+    /// URL prefix that clients can download the files in the output
+    /// directory from.
+    #[clap(long)]
+    url_prefix: String,
+
+    /// Number of bundles of the repo to keep in the output directory,
+    /// including the advertised one.  Older bundles are removed once the
+    /// new bundle has been advertised.
+    #[clap(long, default_value_t = 2)]
+    keep_bundles: usize,
+}
+
+#[facet::container]
+struct Repo {
+    #[delegate(RepoBlobstore, RepoDerivedData, RepoIdentity, dyn Bookmarks)]
+    blob_repo: BlobRepo,
+
+    #[facet]
+    skiplist_index: SkiplistIndex,
+}
+
+impl AsBlobRepo for Repo {
+    fn as_blob_repo(&self) -> &BlobRepo {
+        &self.blob_repo
+    }
+}
+
+#[fbinit::main]
+fn main(fb: FacebookInit) -> Result<()> {
+    let app = MononokeAppBuilder::new(fb)
+        .with_app_extension(Fb303AppExtension {})
+        .build::<CloneBundleGeneratorArgs>()?;
+
+    app.run_with_monitoring_and_logging(async_main, "clone_bundle_generator", AliveService)
+}
+
+async fn async_main(app: MononokeApp) -> Result<()> {
+    let args: CloneBundleGeneratorArgs = app.args()?;
+    let ctx = CoreContext::new_with_logger(app.fb, app.logger().clone());
+    let repo: Repo = app.open_repo(&args.repo_args).await?;
+    let repo_name = repo.repo_identity().name();
+
+    let bookmark = BookmarkKey::new(&args.bookmark)?;
+    let cs_id = repo
+        .bookmarks()
+        .get(ctx.clone(), &bookmark)
+        .await?
+        .ok_or_else(|| anyhow!("Bookmark {} does not exist in {}", bookmark, repo_name))?;
+    let hg_cs_id = repo.derive_hg_changeset(&ctx, cs_id).await?;
+
+    let file_name = format!("{}-{}.hg", repo_name, hg_cs_id);
+    let path = args.output_dir.join(&file_name);
+    if tokio::fs::metadata(&path).await.is_ok() {
+        info!(ctx.logger(), "Clone bundle for {} at {} already exists", bookmark, hg_cs_id);
+    } else {
+        info!(ctx.logger(), "Generating clone bundle for {} at {}", bookmark, hg_cs_id);
+        // Write to a temporary file first, so the CDN never serves a
+        // partially written bundle.
+        let tmp_path = args.output_dir.join(format!("{}.tmp", file_name));
+        let size = match write_bundle(&ctx, &repo, hg_cs_id, &tmp_path).await {
+            Ok(size) => size,
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&tmp_path).await;
+                return Err(e);
+            }
+        };
+        tokio::fs::rename(&tmp_path, &path)
+            .await
+            .with_context(|| format!("Failed to rename bundle to {}", path.display()))?;
+        info!(ctx.logger(), "Wrote {} bytes to {}", size, path.display());
+    }
+
+    let entry = CloneBundleEntry {
+        url: format!("{}/{}", args.url_prefix.trim_end_matches('/'), file_name),
+        bundlespec: BUNDLESPEC.to_string(),
+    };
+    upload_clonebundles_manifest(&ctx, repo.repo_blobstore(), &[entry]).await?;
+    info!(ctx.logger(), "Advertised clone bundle {}", file_name);
+
+    remove_old_bundles(
+        &ctx,
+        &args.output_dir,
+        repo_name,
+        &file_name,
+        args.keep_bundles,
+    )
+    .await
+}
+
+async fn write_bundle(
+    ctx: &CoreContext,
+    repo: &Repo,
+    hg_cs_id: HgChangesetId,
+    path: &Path,
+) -> Result<u64> {
+    let mut file = tokio::fs::File::create(path)
+        .await
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    let lca_hint: Arc<dyn LeastCommonAncestorsHint> = repo.skiplist_index_arc();
+    let size = write_clone_bundle(
+        ctx,
+        repo.as_blob_repo(),
+        &lca_hint,
+        &[hg_cs_id],
+        &SessionLfsParams { threshold: None },
+        &mut file,
+    )
+    .await
+    .with_context(|| format!("Failed to write {}", path.display()))?;
+    file.sync_all().await?;
+    Ok(size)
+}
+
+/// Whether the file is a bundle generated for the repo.
+fn is_repo_bundle(file_name: &str, repo_name: &str) -> bool {
+    file_name
+        .strip_prefix(repo_name)
+        .and_then(|rest| rest.strip_prefix('-'))
+        .and_then(|rest| rest.strip_suffix(".hg"))
+        .map_or(false, |id| id.parse::<HgChangesetId>().is_ok())
+}
+
+/// Remove the bundles of the repo from the output directory, other than
+/// the current one and the most recently written ones before it.
+async fn remove_old_bundles(
+    ctx: &CoreContext,
+    output_dir: &Path,
+    repo_name: &str,
+    current: &str,
+    keep: usize,
+) -> Result<()> {
+    let mut bundles = Vec::new();
+    let mut entries = tokio::fs::read_dir(output_dir)
+        .await
+        .with_context(|| format!("Failed to list {}", output_dir.display()))?;
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if file_name != current && is_repo_bundle(&file_name, repo_name) {
+            let modified = entry.metadata().await?.modified()?;
+            bundles.push((modified, entry.path()));
+        }
+    }
+
+    bundles.sort_by(|a, b| b.0.cmp(&a.0));
+    for (_, path) in bundles.into_iter().skip(keep.saturating_sub(1)) {
+        info!(ctx.logger(), "Removing old clone bundle {}", path.display());
+        tokio::fs::remove_file(&path)
+            .await
+            .with_context(|| format!("Failed to remove {}", path.display()))?;
+    }
+    Ok(())
+}
@@ -8,14 +8,17 @@
 use std::collections::BTreeMap;
 
 use anyhow::bail;
+use anyhow::Context;
 use anyhow::Result;
+use blobstore_factory::probe_blobstores;
 use cmdlib::args;
+use context::CoreContext;
 use fbinit::FacebookInit;
 use itertools::Itertools;
 
 #[fbinit::main]
 fn main(fb: FacebookInit) -> Result<()> {
-    let (matches, _runtime) = args::MononokeAppBuilder::new("Lint Mononoke config files")
+    let (matches, runtime) = args::MononokeAppBuilder::new("Lint Mononoke config files")
         .with_advanced_args_hidden()
         .build()
         .about("Check Mononoke server configs for syntax and sanity.")
@@ -23,12 +26,14 @@ fn main(fb: FacebookInit) -> Result<()> {
             r#"
             -q --quiet 'Only print errors'
             -v --verbose 'Dump content of configs'
+            --probe-storage 'Write, read back and delete a probe blob in each configured blobstore'
             "#,
         )
         .get_matches(fb)?;
 
     let quiet = matches.is_present("quiet");
     let verbose = matches.is_present("verbose");
+    let probe_storage = matches.is_present("probe-storage");
     let config_store = matches.config_store();
 
     // Most of the work is done here - this validates that the files are present,
@@ -41,6 +46,12 @@ fn main(fb: FacebookInit) -> Result<()> {
         Ok(configs) => configs,
     };
 
+    if !quiet {
+        for warning in &configs.schema_warnings {
+            eprintln!("WARNING: {}", warning);
+        }
+    }
+
     if verbose {
         println!("Configs:\n{:#?}", configs)
     }
@@ -88,6 +99,43 @@ fn main(fb: FacebookInit) -> Result<()> {
         }
     }
 
+    if probe_storage {
+        let storage_configs = args::load_storage_configs(config_store, &matches)
+            .context("Could not read storage configs")?;
+        let logger = matches.logger();
+        let blobstore_options = matches.blobstore_options();
+        let ctx = CoreContext::new_for_bulk_processing(fb, logger.clone());
+        let storage = storage_configs.storage.into_iter().collect::<BTreeMap<_, _>>();
+        for (name, config) in storage {
+            let results = runtime.block_on(probe_blobstores(
+                fb,
+                &ctx,
+                &name,
+                config.blobstore,
+                matches.mysql_options(),
+                blobstore_options,
+                logger,
+                config_store,
+            ));
+            for probe in results {
+                match probe.result {
+                    Ok(()) => {
+                        if !quiet {
+                            println!("Storage {}: {} - probe OK", probe.location, probe.store);
+                        }
+                    }
+                    Err(err) => {
+                        eprintln!(
+                            "ERROR: Storage {}: {} - probe failed: {:#}",
+                            probe.location, probe.store, err
+                        );
+                        bad = true;
+                    }
+                }
+            }
+        }
+    }
+
     if bad {
         bail!("Anomaly detected")
     } else {
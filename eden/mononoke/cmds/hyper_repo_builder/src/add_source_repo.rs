@@ -187,6 +187,7 @@ async fn create_new_bonsai_changeset_for_source_repo(
             git_extra_headers: None,
             git_tree_hash: None,
             git_annotated_tag: None,
+            extras: None,
         };
 
         if idx + 1 == len {
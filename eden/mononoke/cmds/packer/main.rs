@@ -112,9 +112,10 @@ fn main(fb: FacebookInit) -> Result<()> {
     let blobstore_options = &env.blobstore_options;
 
     let repo_arg = args.repo_args.as_repo_arg();
-    let (_repo_name, repo_config) = app.repo_config(repo_arg)?;
+    let (repo_name, repo_config) = app.repo_config(repo_arg)?;
     let blobconfig = repo_config.storage_config.blobstore;
     let repo_prefix = repo_config.repoid.prefix();
+    let windows = repo_config.maintenance_windows;
 
     let input_lines: Vec<String> = io::stdin()
         .lock()
@@ -136,8 +137,10 @@ fn main(fb: FacebookInit) -> Result<()> {
         .await?;
         stream::iter(input_lines.split(String::is_empty).map(Result::Ok))
             .try_for_each_concurrent(max_parallelism, |pack_keys| {
-                borrowed!(ctx, repo_prefix, blobstore, scuba);
+                borrowed!(ctx, repo_name, repo_prefix, windows, blobstore, scuba);
                 async move {
+                    maintenance_windows::wait_until_background_jobs_allowed(ctx, repo_name, windows)
+                        .await;
                     let pack_keys: Vec<&str> = pack_keys.iter().map(|i| i.as_ref()).collect();
                     pack_utils::repack_keys(
                         ctx,
@@ -0,0 +1,229 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use anyhow::Error;
+use anyhow::Result;
+use blobstore::Blobstore;
+use blobstore::Loadable;
+use bounded_traversal::bounded_traversal_stream;
+use context::CoreContext;
+use futures::future::FutureExt;
+use futures::stream;
+use futures::stream::Stream;
+use futures::stream::TryStreamExt;
+use metaconfig_types::PathLintConfig;
+use mononoke_types::skeleton_manifest::SkeletonManifestEntry;
+use mononoke_types::MPath;
+use mononoke_types::MPathElement;
+use mononoke_types::SkeletonManifestId;
+use serde::Serialize;
+
+/// A path that violates one of a repo's path policies.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Violation {
+    /// The path is longer than the maximum length.
+    PathTooLong { path: String, length: usize },
+    /// The last component of the path contains a forbidden character.
+    ForbiddenCharacter { path: String, character: char },
+    /// The file has a disallowed extension.
+    DisallowedFileType { path: String, extension: String },
+    /// The path only differs in case from another path.
+    CaseConflict {
+        path: String,
+        conflicts_with: String,
+    },
+}
+
+impl Violation {
+    pub fn path(&self) -> &str {
+        match self {
+            Violation::PathTooLong { path, .. }
+            | Violation::ForbiddenCharacter { path, .. }
+            | Violation::DisallowedFileType { path, .. }
+            | Violation::CaseConflict { path, .. } => path,
+        }
+    }
+}
+
+/// Check a file or directory against the policies that apply to single
+/// paths.  Only the last component of the path is checked for forbidden
+/// characters, as the others are checked along with their directories.
+fn check_path(config: &PathLintConfig, path: &MPath, is_file: bool) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let name = String::from_utf8_lossy(path.basename().as_ref()).into_owned();
+    if let Some(character) = name.chars().find(|c| config.forbidden_chars.contains(*c)) {
+        violations.push(Violation::ForbiddenCharacter {
+            path: path.to_string(),
+            character,
+        });
+    }
+    if is_file {
+        // Directories are always shorter than the files in them.
+        if let Some(max_path_length) = config.max_path_length {
+            if path.len() > max_path_length {
+                violations.push(Violation::PathTooLong {
+                    path: path.to_string(),
+                    length: path.len(),
+                });
+            }
+        }
+        if let Some((stem, extension)) = name.rsplit_once('.') {
+            let extension = extension.to_lowercase();
+            if !stem.is_empty() && config.disallowed_extensions.contains(&extension) {
+                violations.push(Violation::DisallowedFileType {
+                    path: path.to_string(),
+                    extension,
+                });
+            }
+        }
+    }
+    violations
+}
+
+/// Pairs of names that only differ in case.  Each name is paired with the
+/// first name it conflicts with.
+fn case_conflicts<'a>(
+    names: impl Iterator<Item = &'a MPathElement>,
+) -> Vec<(&'a MPathElement, &'a MPathElement)> {
+    let mut lower_map = HashMap::new();
+    let mut conflicts = Vec::new();
+    for name in names {
+        if let Some(lower_name) = name.to_lowercase_utf8() {
+            match lower_map.entry(lower_name) {
+                Entry::Occupied(entry) => conflicts.push((name, *entry.get())),
+                Entry::Vacant(entry) => {
+                    entry.insert(name);
+                }
+            }
+        }
+    }
+    conflicts
+}
+
+/// Check every path in the tree of a skeleton manifest against the path
+/// policies of `config`.
+pub fn lint_tree<'a>(
+    ctx: &'a CoreContext,
+    blobstore: &'a impl Blobstore,
+    config: &'a PathLintConfig,
+    root: SkeletonManifestId,
+) -> impl Stream<Item = Result<Violation>> + 'a {
+    bounded_traversal_stream(
+        256,
+        Some((None, root)),
+        move |(path, sk_mf_id): (Option<MPath>, SkeletonManifestId)| {
+            async move {
+                let sk_mf = sk_mf_id.load(ctx, blobstore).await?;
+                let mut violations = Vec::new();
+                if config.check_case_conflicts && sk_mf.summary().child_case_conflicts {
+                    let names = sk_mf.list().map(|(name, _)| name);
+                    for (name, other_name) in case_conflicts(names) {
+                        let conflicting_path = MPath::join_opt_element(path.as_ref(), name);
+                        let other_path = MPath::join_opt_element(path.as_ref(), other_name);
+                        violations.push(Violation::CaseConflict {
+                            path: conflicting_path.to_string(),
+                            conflicts_with: other_path.to_string(),
+                        });
+                    }
+                }
+                let mut subdirs = Vec::new();
+                for (name, entry) in sk_mf.list() {
+                    let child_path = MPath::join_opt_element(path.as_ref(), name);
+                    match entry {
+                        SkeletonManifestEntry::File => {
+                            violations.extend(check_path(config, &child_path, true));
+                        }
+                        SkeletonManifestEntry::Directory(subdir) => {
+                            violations.extend(check_path(config, &child_path, false));
+                            subdirs.push((Some(child_path), *subdir.id()));
+                        }
+                    }
+                }
+                Ok::<_, Error>((violations, subdirs))
+            }
+            .boxed()
+        },
+    )
+    .map_ok(|violations| stream::iter(violations.into_iter().map(Ok)))
+    .try_flatten()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config() -> PathLintConfig {
+        PathLintConfig {
+            max_path_length: Some(13),
+            forbidden_chars: ":".to_string(),
+            disallowed_extensions: vec!["exe".to_string()],
+            check_case_conflicts: true,
+            task_scribe_category: None,
+        }
+    }
+
+    fn path(path: &str) -> MPath {
+        MPath::new(path).unwrap()
+    }
+
+    #[test]
+    fn test_check_path() {
+        let config = config();
+        assert_eq!(check_path(&config, &path("dir/ok.txt"), true), vec![]);
+        assert_eq!(
+            check_path(&config, &path("dir/a:b/c.txt"), true),
+            vec![],
+            "only the last component is checked"
+        );
+        assert_eq!(
+            check_path(&config, &path("dir/a:b"), false),
+            vec![Violation::ForbiddenCharacter {
+                path: "dir/a:b".to_string(),
+                character: ':',
+            }]
+        );
+        assert_eq!(
+            check_path(&config, &path("dir/TOOL.EXE"), true),
+            vec![Violation::DisallowedFileType {
+                path: "dir/TOOL.EXE".to_string(),
+                extension: "exe".to_string(),
+            }]
+        );
+        assert_eq!(check_path(&config, &path("dir/.exe"), true), vec![]);
+        assert_eq!(
+            check_path(&config, &path("dir/longer_name"), true),
+            vec![Violation::PathTooLong {
+                path: "dir/longer_name".to_string(),
+                length: 15,
+            }]
+        );
+        assert_eq!(check_path(&config, &path("dir/longer_name"), false), vec![]);
+    }
+
+    #[test]
+    fn test_case_conflicts() {
+        let names = ["README", "a", "b", "readme", "ReadMe"]
+            .into_iter()
+            .map(|name| MPathElement::new(name.into()).unwrap())
+            .collect::<Vec<_>>();
+        let conflicts = case_conflicts(names.iter())
+            .into_iter()
+            .map(|(name, other)| (name.to_string(), other.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            conflicts,
+            vec![
+                ("readme".to_string(), "README".to_string()),
+                ("ReadMe".to_string(), "README".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,175 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use blobrepo::BlobRepo;
+use bookmarks::BookmarkKey;
+use bookmarks::Bookmarks;
+use bookmarks::BookmarksRef;
+use clap::Parser;
+use cmdlib_logging::ScribeLoggingArgs;
+use context::log_keys;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use futures::stream::TryStreamExt;
+use metaconfig_types::RepoConfig;
+use metaconfig_types::RepoConfigRef;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::fb303::AliveService;
+use mononoke_app::fb303::Fb303AppExtension;
+use mononoke_app::MononokeApp;
+use mononoke_app::MononokeAppBuilder;
+use repo_blobstore::RepoBlobstore;
+use repo_blobstore::RepoBlobstoreRef;
+use repo_derived_data::RepoDerivedData;
+use repo_derived_data::RepoDerivedDataRef;
+use repo_identity::RepoIdentity;
+use repo_identity::RepoIdentityRef;
+use serde::Serialize;
+use skeleton_manifest::RootSkeletonManifestId;
+use slog::info;
+
+use crate::lint::lint_tree;
+use crate::lint::Violation;
+
+mod lint;
+
+/// Check every path in the working copy of a repo against the repo's path
+/// policies, and report the paths that violate them.
+///
+/// Hooks stop new violations from being pushed, but paths that were added
+/// before a policy was configured can only be found by checking the whole
+/// tree, which this job is meant to do on a schedule.
+#[derive(Parser)]
+struct PathLintArgs {
+    #[clap(flatten)]
+    repo_args: RepoArgs,
+
+    #[clap(flatten)]
+    scribe_logging_args: ScribeLoggingArgs,
+
+    /// Bookmark whose working copy is checked.
+    #[clap(long, default_value = "master")]
+    bookmark: String,
+
+    /// File to write the report to, as JSON.  If not given, the report is
+    /// printed.
+    #[clap(long)]
+    report: Option<PathBuf>,
+
+    /// Log each violation to the repo's task scribe category, so that
+    /// tasks are filed for them.
+    #[clap(long)]
+    file_tasks: bool,
+}
+
+#[facet::container]
+struct Repo {
+    #[delegate(RepoBlobstore, RepoDerivedData, RepoIdentity, dyn Bookmarks)]
+    blob_repo: BlobRepo,
+
+    #[facet]
+    repo_config: RepoConfig,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    repo: &'a str,
+    bookmark: &'a str,
+    changeset_id: String,
+    violations: &'a [Violation],
+}
+
+#[derive(Serialize)]
+struct TaskMessage<'a> {
+    repo: &'a str,
+    bookmark: &'a str,
+    changeset_id: String,
+    violation: &'a Violation,
+}
+
+#[fbinit::main]
+fn main(fb: FacebookInit) -> Result<()> {
+    let app = MononokeAppBuilder::new(fb)
+        .with_app_extension(Fb303AppExtension {})
+        .build::<PathLintArgs>()?;
+
+    app.run_with_monitoring_and_logging(async_main, "path_lint", AliveService)
+}
+
+async fn async_main(app: MononokeApp) -> Result<()> {
+    let args: PathLintArgs = app.args()?;
+    let repo: Repo = app.open_repo(&args.repo_args).await?;
+    let repo_name = repo.repo_identity().name();
+    let ctx = CoreContext::new_with_logger(app.fb, app.repo_logger(repo_name));
+    let config = repo
+        .repo_config()
+        .path_lint_config
+        .as_ref()
+        .ok_or_else(|| anyhow!("Path lint is not configured for {}", repo_name))?;
+
+    let bookmark = BookmarkKey::new(&args.bookmark)?;
+    let cs_id = repo
+        .bookmarks()
+        .get(ctx.clone(), &bookmark)
+        .await?
+        .ok_or_else(|| anyhow!("Bookmark {} does not exist in {}", bookmark, repo_name))?;
+    let root = repo
+        .repo_derived_data()
+        .derive::<RootSkeletonManifestId>(&ctx, cs_id)
+        .await?;
+
+    info!(ctx.logger(), "Checking paths of {}", bookmark; log_keys::CS_ID => %cs_id);
+    let mut violations = lint_tree(
+        &ctx,
+        repo.repo_blobstore(),
+        config,
+        root.into_skeleton_manifest_id(),
+    )
+    .try_collect::<Vec<_>>()
+    .await?;
+    // The tree is traversed concurrently, so sort for a stable report.
+    violations.sort_by(|a, b| a.path().cmp(b.path()));
+    info!(ctx.logger(), "Found {} violations", violations.len());
+
+    if args.file_tasks {
+        let category = config
+            .task_scribe_category
+            .as_ref()
+            .ok_or_else(|| anyhow!("No task scribe category is configured for {}", repo_name))?;
+        let scribe = args.scribe_logging_args.get_scribe(app.fb)?;
+        for violation in &violations {
+            let message = TaskMessage {
+                repo: repo_name,
+                bookmark: &args.bookmark,
+                changeset_id: cs_id.to_string(),
+                violation,
+            };
+            scribe.offer(category, &serde_json::to_string(&message)?)?;
+        }
+        info!(ctx.logger(), "Filed tasks for {} violations", violations.len());
+    }
+
+    let report = serde_json::to_string_pretty(&Report {
+        repo: repo_name,
+        bookmark: &args.bookmark,
+        changeset_id: cs_id.to_string(),
+        violations: &violations,
+    })?;
+    match &args.report {
+        Some(path) => tokio::fs::write(path, report)
+            .await
+            .with_context(|| format!("Failed to write report to {}", path.display()))?,
+        None => println!("{}", report),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,237 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashSet;
+use std::io::Write;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use blobstore::Blobstore;
+use blobstore::Loadable;
+use bonsai_hg_mapping::BonsaiHgMappingRef;
+use commit_graph::CommitGraphRef;
+use context::CoreContext;
+use filestore::FetchKey;
+use futures::future::try_join_all;
+use futures::stream;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use manifest::find_intersection_of_diffs;
+use manifest::Entry;
+use mercurial_types::HgChangesetId;
+use mononoke_types::BlobstoreKey;
+use mononoke_types::BonsaiChangeset;
+use mononoke_types::ChangesetId;
+use mononoke_types::ContentId;
+use mononoke_types::FileChange;
+use repo_blobstore::RepoBlobstoreRef;
+
+use crate::format::BundleRecord;
+use crate::format::BundleWriter;
+use crate::Repo;
+
+/// What was written to a bundle.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ExportStats {
+    pub commits: usize,
+    pub blobs: usize,
+    pub files: usize,
+}
+
+/// Writes the records for commits to a bundle, skipping blobs and file
+/// contents that have already been written.
+struct Exporter<'a, R: Repo, W: Write> {
+    ctx: &'a CoreContext,
+    repo: &'a R,
+    writer: BundleWriter<W>,
+    written_keys: HashSet<String>,
+    written_contents: HashSet<ContentId>,
+}
+
+impl<'a, R: Repo, W: Write> Exporter<'a, R, W> {
+    async fn write_blob(&mut self, key: String) -> Result<()> {
+        if self.written_keys.contains(&key) {
+            return Ok(());
+        }
+        let data = self
+            .repo
+            .repo_blobstore()
+            .get(self.ctx, &key)
+            .await?
+            .ok_or_else(|| anyhow!("Blob {} not found", key))?
+            .into_raw_bytes();
+        self.writer.write(&BundleRecord::Blob {
+            key: key.clone(),
+            data,
+        })?;
+        self.written_keys.insert(key);
+        Ok(())
+    }
+
+    async fn write_file(&mut self, content_id: ContentId) -> Result<()> {
+        if self.written_contents.contains(&content_id) {
+            return Ok(());
+        }
+        // The content is written as it is fetched, so that large files are
+        // never held in memory.
+        let (ctx, repo) = (self.ctx, self.repo);
+        let (mut content, size) = filestore::fetch_with_size(
+            repo.repo_blobstore(),
+            ctx,
+            &FetchKey::Canonical(content_id),
+        )
+        .await?
+        .ok_or_else(|| anyhow!("Content {} not found", content_id))?;
+        self.writer.start_file(content_id, size)?;
+        while let Some(chunk) = content
+            .try_next()
+            .await
+            .with_context(|| format!("Failed to fetch content {}", content_id))?
+        {
+            self.writer.write_file_content(&chunk)?;
+        }
+        self.written_contents.insert(content_id);
+        Ok(())
+    }
+
+    /// Write the hg changeset of a commit, together with the manifests and
+    /// filenodes that it introduces.
+    async fn write_hg_changeset(&mut self, hg_cs_id: HgChangesetId) -> Result<()> {
+        let (ctx, repo) = (self.ctx, self.repo);
+        let blobstore = repo.repo_blobstore();
+        let hg_cs = hg_cs_id.load(ctx, blobstore).await?;
+        let parent_mf_ids = try_join_all(
+            hg_cs
+                .p1()
+                .into_iter()
+                .chain(hg_cs.p2())
+                .map(|parent| async move {
+                    let parent = HgChangesetId::new(parent).load(ctx, blobstore).await?;
+                    anyhow::Ok(parent.manifestid())
+                }),
+        )
+        .await?;
+        let entries: Vec<_> = find_intersection_of_diffs(
+            ctx.clone(),
+            blobstore.clone(),
+            hg_cs.manifestid(),
+            parent_mf_ids,
+        )
+        .try_collect()
+        .await?;
+        for (_path, entry) in entries {
+            match entry {
+                Entry::Tree(mf_id) => self.write_blob(mf_id.blobstore_key()).await?,
+                Entry::Leaf((_file_type, filenode_id)) => {
+                    let envelope = filenode_id.load(ctx, blobstore).await?;
+                    self.write_file(envelope.content_id()).await?;
+                    self.write_blob(filenode_id.blobstore_key()).await?;
+                }
+            }
+        }
+        self.write_blob(hg_cs_id.blobstore_key()).await
+    }
+
+    async fn write_changeset(&mut self, bcs: &BonsaiChangeset) -> Result<()> {
+        let cs_id = bcs.get_changeset_id();
+        for (_path, file_change) in bcs.file_changes() {
+            if let FileChange::Change(change) = file_change {
+                self.write_file(change.content_id()).await?;
+            }
+        }
+        self.write_blob(cs_id.blobstore_key()).await?;
+
+        // Export the hg changeset only if it has already been derived.
+        let hg_cs_id = self
+            .repo
+            .bonsai_hg_mapping()
+            .get_hg_from_bonsai(self.ctx, cs_id)
+            .await?;
+        if let Some(hg_cs_id) = hg_cs_id {
+            self.write_hg_changeset(hg_cs_id).await?;
+        }
+
+        self.writer
+            .write(&BundleRecord::Changeset { cs_id, hg_cs_id })
+    }
+}
+
+/// Number of bonsai changesets to load at once.
+const LOAD_CONCURRENCY: usize = 100;
+
+/// Load the commits that are ancestors of `heads` but not of `common`,
+/// sorted so that parents come before their children.
+///
+/// If `limit` is given, only that many commits are returned.  As parents
+/// come first, these are the complete ancestry of the returned commits, so
+/// the rest can be found by adding them to `common`.
+pub async fn commits_to_export(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    heads: Vec<ChangesetId>,
+    common: Vec<ChangesetId>,
+    limit: Option<usize>,
+) -> Result<Vec<BonsaiChangeset>> {
+    let mut cs_ids = repo
+        .commit_graph()
+        .ancestors_difference(ctx, heads, common)
+        .await?;
+    // These are in descending order of generation, so reversing them puts
+    // parents first, and the limit can be applied before any changesets
+    // are loaded.
+    cs_ids.reverse();
+    cs_ids.truncate(limit.unwrap_or(usize::MAX));
+    stream::iter(cs_ids)
+        .map(|cs_id| async move {
+            cs_id
+                .load(ctx, repo.repo_blobstore())
+                .await
+                .with_context(|| format!("Failed to load changeset {}", cs_id))
+        })
+        .buffered(LOAD_CONCURRENCY)
+        .try_collect()
+        .await
+}
+
+/// Write a bundle of commits, which must be sorted so that parents come
+/// before their children, and return the underlying writer.
+///
+/// If `max_size` is given, no more commits are written once the bundle has
+/// reached that many bytes.  At least one commit is always written, so the
+/// bundle can exceed it by the size of that commit.  The number of commits
+/// written is returned in the stats, and as parents come first, the rest
+/// can be exported by adding those to the common commits.
+pub async fn export_bundle<W: Write>(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    bonsais: &[BonsaiChangeset],
+    writer: W,
+    max_size: Option<u64>,
+) -> Result<(W, ExportStats)> {
+    let mut exporter = Exporter {
+        ctx,
+        repo,
+        writer: BundleWriter::new(writer)?,
+        written_keys: HashSet::new(),
+        written_contents: HashSet::new(),
+    };
+    let mut commits = 0;
+    for bcs in bonsais {
+        if commits > 0 && exporter.writer.written() >= max_size.unwrap_or(u64::MAX) {
+            break;
+        }
+        exporter.write_changeset(bcs).await?;
+        commits += 1;
+    }
+    let stats = ExportStats {
+        commits,
+        blobs: exporter.written_keys.len(),
+        files: exporter.written_contents.len(),
+    };
+    Ok((exporter.writer.finish()?, stats))
+}
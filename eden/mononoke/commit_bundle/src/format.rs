@@ -0,0 +1,341 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! The bundle file format.
+//!
+//! A bundle starts with a magic string and a version, followed by a
+//! sequence of records, and ends with an end marker.  Each record is a tag
+//! byte followed by its fields.  Integers are big-endian, and variable
+//! length fields are prefixed by their length.
+//!
+//! Records are written so that everything a commit refers to comes before
+//! the commit itself, and commits come after their parents, so a bundle can
+//! be imported in a single pass.
+
+use std::io::Read;
+use std::io::Write;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use bytes::Bytes;
+use mercurial_types::HgChangesetId;
+use mononoke_types::ChangesetId;
+use mononoke_types::ContentId;
+
+const MAGIC: &[u8; 8] = b"MNBUNDLE";
+const VERSION: u32 = 1;
+
+const TAG_END: u8 = 0;
+const TAG_BLOB: u8 = 1;
+const TAG_FILE: u8 = 2;
+const TAG_CHANGESET: u8 = 3;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BundleRecord {
+    /// A blob to store as-is, e.g. a bonsai changeset or an hg manifest,
+    /// with its blobstore key.
+    Blob { key: String, data: Bytes },
+    /// The content of a file, which is stored through the filestore so that
+    /// it is chunked and aliased according to the target repo's config.
+    File { content_id: ContentId, content: Bytes },
+    /// A commit whose blobs have all been written, along with its hg
+    /// changeset if that was derived in the source repo.
+    Changeset {
+        cs_id: ChangesetId,
+        hg_cs_id: Option<HgChangesetId>,
+    },
+}
+
+pub struct BundleWriter<W: Write> {
+    writer: W,
+    written: u64,
+    /// The number of bytes of a file record's content still to be written.
+    file_remaining: u64,
+}
+
+impl<W: Write> BundleWriter<W> {
+    pub fn new(writer: W) -> Result<Self> {
+        let mut bundle_writer = Self {
+            writer,
+            written: 0,
+            file_remaining: 0,
+        };
+        bundle_writer.write_all(MAGIC)?;
+        bundle_writer.write_all(&VERSION.to_be_bytes())?;
+        Ok(bundle_writer)
+    }
+
+    /// The number of bytes written to the bundle so far.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer.write_all(bytes)?;
+        self.written += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.write_all(&(bytes.len() as u64).to_be_bytes())?;
+        self.write_all(bytes)
+    }
+
+    fn check_file_finished(&self) -> Result<()> {
+        if self.file_remaining != 0 {
+            bail!(
+                "File record is missing {} bytes of content",
+                self.file_remaining
+            );
+        }
+        Ok(())
+    }
+
+    pub fn write(&mut self, record: &BundleRecord) -> Result<()> {
+        self.check_file_finished()?;
+        match record {
+            BundleRecord::Blob { key, data } => {
+                self.write_all(&[TAG_BLOB])?;
+                self.write_bytes(key.as_bytes())?;
+                self.write_bytes(data)?;
+            }
+            BundleRecord::File {
+                content_id,
+                content,
+            } => {
+                self.start_file(*content_id, content.len() as u64)?;
+                self.write_file_content(content)?;
+            }
+            BundleRecord::Changeset { cs_id, hg_cs_id } => {
+                self.write_all(&[TAG_CHANGESET])?;
+                self.write_all(cs_id.blake2().as_ref())?;
+                match hg_cs_id {
+                    Some(hg_cs_id) => {
+                        self.write_all(&[1])?;
+                        self.write_all(hg_cs_id.as_bytes())?;
+                    }
+                    None => self.write_all(&[0])?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Start a file record for content of `size` bytes, which must then be
+    /// written with `write_file_content` before anything else.  This allows
+    /// large files to be written without holding them in memory.
+    pub fn start_file(&mut self, content_id: ContentId, size: u64) -> Result<()> {
+        self.check_file_finished()?;
+        self.write_all(&[TAG_FILE])?;
+        self.write_all(content_id.blake2().as_ref())?;
+        self.write_all(&size.to_be_bytes())?;
+        self.file_remaining = size;
+        Ok(())
+    }
+
+    /// Write part of the content of the file record that was started last.
+    pub fn write_file_content(&mut self, content: &[u8]) -> Result<()> {
+        if content.len() as u64 > self.file_remaining {
+            bail!("File content is larger than its record");
+        }
+        self.write_all(content)?;
+        self.file_remaining -= content.len() as u64;
+        Ok(())
+    }
+
+    /// Write the end marker and return the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.check_file_finished()?;
+        self.write_all(&[TAG_END])?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+pub struct BundleReader<R: Read> {
+    reader: R,
+    finished: bool,
+}
+
+impl<R: Read> BundleReader<R> {
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut magic = [0; 8];
+        reader
+            .read_exact(&mut magic)
+            .context("Failed to read bundle header")?;
+        if &magic != MAGIC {
+            bail!("Not a bundle file");
+        }
+        let version = u32::from_be_bytes(read_array(&mut reader)?);
+        if version != VERSION {
+            bail!("Unsupported bundle version {}", version);
+        }
+        Ok(Self {
+            reader,
+            finished: false,
+        })
+    }
+
+    fn read_bytes(&mut self) -> Result<Bytes> {
+        let len = u64::from_be_bytes(read_array(&mut self.reader)?);
+        // The length can't be trusted, so the buffer only grows as the data
+        // is actually read.
+        let mut bytes = Vec::new();
+        (&mut self.reader).take(len).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != len {
+            bail!("Bundle is truncated");
+        }
+        Ok(Bytes::from(bytes))
+    }
+
+    /// Read the next record, or `None` at the end marker.  Bundles that are
+    /// truncated before the end marker are an error.
+    pub fn read(&mut self) -> Result<Option<BundleRecord>> {
+        if self.finished {
+            return Ok(None);
+        }
+        let [tag] = read_array(&mut self.reader).context("Bundle is truncated")?;
+        let record = match tag {
+            TAG_END => {
+                self.finished = true;
+                return Ok(None);
+            }
+            TAG_BLOB => {
+                let key = String::from_utf8(self.read_bytes()?.to_vec())
+                    .context("Invalid blob key")?;
+                let data = self.read_bytes()?;
+                BundleRecord::Blob { key, data }
+            }
+            TAG_FILE => {
+                let content_id = ContentId::from_bytes(read_array::<32>(&mut self.reader)?)?;
+                let content = self.read_bytes()?;
+                BundleRecord::File {
+                    content_id,
+                    content,
+                }
+            }
+            TAG_CHANGESET => {
+                let cs_id = ChangesetId::from_bytes(read_array::<32>(&mut self.reader)?)?;
+                let [has_hg] = read_array(&mut self.reader)?;
+                let hg_cs_id = match has_hg {
+                    0 => None,
+                    _ => Some(HgChangesetId::from_bytes(&read_array::<20>(
+                        &mut self.reader,
+                    )?)?),
+                };
+                BundleRecord::Changeset { cs_id, hg_cs_id }
+            }
+            tag => bail!("Unknown bundle record tag {}", tag),
+        };
+        Ok(Some(record))
+    }
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> Result<[u8; N]> {
+    let mut array = [0; N];
+    reader.read_exact(&mut array)?;
+    Ok(array)
+}
+
+#[cfg(test)]
+mod test {
+    use mercurial_types_mocks::nodehash::ONES_CSID as HG_ONES_CSID;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+    use mononoke_types_mocks::contentid::ONES_CTID;
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let records = vec![
+            BundleRecord::Blob {
+                key: "changeset.blake2.1111".to_string(),
+                data: Bytes::from_static(b"bonsai"),
+            },
+            BundleRecord::File {
+                content_id: ONES_CTID,
+                content: Bytes::from_static(b"content\n"),
+            },
+            BundleRecord::Changeset {
+                cs_id: ONES_CSID,
+                hg_cs_id: Some(HG_ONES_CSID),
+            },
+            BundleRecord::Changeset {
+                cs_id: TWOS_CSID,
+                hg_cs_id: None,
+            },
+        ];
+
+        let mut writer = BundleWriter::new(Vec::new())?;
+        for record in records.iter() {
+            writer.write(record)?;
+        }
+        let bundle = writer.finish()?;
+
+        let mut reader = BundleReader::new(bundle.as_slice())?;
+        let mut read = Vec::new();
+        while let Some(record) = reader.read()? {
+            read.push(record);
+        }
+        assert_eq!(read, records);
+
+        // A bundle without the end marker is truncated.
+        let mut reader = BundleReader::new(&bundle[..bundle.len() - 1])?;
+        while let Ok(Some(_)) = reader.read() {}
+        assert!(reader.read().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_streamed_file() -> Result<()> {
+        let changeset = BundleRecord::Changeset {
+            cs_id: ONES_CSID,
+            hg_cs_id: None,
+        };
+        let mut writer = BundleWriter::new(Vec::new())?;
+        writer.start_file(ONES_CTID, 8)?;
+        writer.write_file_content(b"cont")?;
+        // The record must be finished before anything else is written.
+        assert!(writer.write(&changeset).is_err());
+        writer.write_file_content(b"ent\n")?;
+        assert!(writer.write_file_content(b"more").is_err());
+        let written = writer.written();
+        let bundle = writer.finish()?;
+        assert_eq!(written + 1, bundle.len() as u64);
+
+        let mut reader = BundleReader::new(bundle.as_slice())?;
+        assert_eq!(
+            reader.read()?,
+            Some(BundleRecord::File {
+                content_id: ONES_CTID,
+                content: Bytes::from_static(b"content\n"),
+            })
+        );
+        assert_eq!(reader.read()?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_huge_length() -> Result<()> {
+        let mut bundle = BundleWriter::new(Vec::new())?.finish()?;
+        bundle.pop();
+        bundle.push(TAG_BLOB);
+        bundle.extend_from_slice(&u64::MAX.to_be_bytes());
+        bundle.extend_from_slice(b"key");
+
+        // A length that is larger than the rest of the bundle is rejected,
+        // without allocating it.
+        let mut reader = BundleReader::new(bundle.as_slice())?;
+        assert!(reader.read().is_err());
+
+        Ok(())
+    }
+}
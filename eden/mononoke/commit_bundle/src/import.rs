@@ -0,0 +1,120 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::io::Read;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use blobstore::Blobstore;
+use blobstore::BlobstoreBytes;
+use blobstore::Loadable;
+use bonsai_hg_mapping::BonsaiHgMappingEntry;
+use bonsai_hg_mapping::BonsaiHgMappingRef;
+use changesets_creation::save_changesets;
+use context::CoreContext;
+use filestore::FilestoreConfigRef;
+use filestore::StoreRequest;
+use futures::future::try_join_all;
+use futures::stream;
+use mercurial_types::HgChangesetId;
+use mononoke_types::BonsaiChangeset;
+use mononoke_types::ChangesetId;
+use repo_blobstore::RepoBlobstoreRef;
+
+use crate::format::BundleReader;
+use crate::format::BundleRecord;
+use crate::Repo;
+
+/// Save a batch of commits whose blobs have been written, and add the
+/// mapping to their hg changesets.
+async fn save_batch(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    batch: Vec<(BonsaiChangeset, Option<HgChangesetId>)>,
+) -> Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+    let (bonsais, hg_cs_ids): (Vec<_>, Vec<_>) = batch
+        .into_iter()
+        .map(|(bcs, hg_cs_id)| {
+            let bcs_id = bcs.get_changeset_id();
+            let entry = hg_cs_id.map(|hg_cs_id| BonsaiHgMappingEntry { hg_cs_id, bcs_id });
+            (bcs, entry)
+        })
+        .unzip();
+    save_changesets(ctx, repo, bonsais).await?;
+    // The hg changesets must only be mapped once their bonsai changesets are
+    // saved.
+    try_join_all(
+        hg_cs_ids
+            .into_iter()
+            .flatten()
+            .map(|entry| repo.bonsai_hg_mapping().add(ctx, entry)),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Import the commits in a bundle, saving them `chunk_size` at a time.
+///
+/// The parents of the oldest commits in the bundle must already be in the
+/// repo.  Returns the imported commits, parents first.
+pub async fn import_bundle(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    reader: impl Read,
+    chunk_size: usize,
+) -> Result<Vec<ChangesetId>> {
+    let mut reader = BundleReader::new(reader)?;
+    let chunk_size = chunk_size.max(1);
+    let mut batch = Vec::with_capacity(chunk_size);
+    let mut imported = Vec::new();
+    while let Some(record) = reader.read()? {
+        match record {
+            BundleRecord::Blob { key, data } => {
+                repo.repo_blobstore()
+                    .put(ctx, key, BlobstoreBytes::from_bytes(data))
+                    .await?;
+            }
+            BundleRecord::File {
+                content_id,
+                content,
+            } => {
+                // Storing with the canonical id checks that the content
+                // matches it.
+                let req = StoreRequest::with_canonical(content.len() as u64, content_id);
+                filestore::store(
+                    repo.repo_blobstore(),
+                    *repo.filestore_config(),
+                    ctx,
+                    &req,
+                    stream::once(async { Ok(content) }),
+                )
+                .await
+                .with_context(|| format!("Failed to store content {}", content_id))?;
+            }
+            BundleRecord::Changeset { cs_id, hg_cs_id } => {
+                let bcs = cs_id
+                    .load(ctx, repo.repo_blobstore())
+                    .await
+                    .with_context(|| format!("Failed to load changeset {}", cs_id))?;
+                if bcs.get_changeset_id() != cs_id {
+                    bail!("Changeset {} in bundle has a different hash", cs_id);
+                }
+                imported.push(cs_id);
+                batch.push((bcs, hg_cs_id));
+                if batch.len() >= chunk_size {
+                    save_batch(ctx, repo, std::mem::take(&mut batch)).await?;
+                }
+            }
+        }
+    }
+    save_batch(ctx, repo, batch).await?;
+    Ok(imported)
+}
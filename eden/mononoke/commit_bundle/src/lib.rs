@@ -0,0 +1,44 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![feature(trait_alias)]
+
+//! Commit bundles transfer commits between Mononoke instances.
+//!
+//! A bundle is a self-contained file with everything needed to add a set of
+//! commits to a repo that already has their parents: the bonsai
+//! changesets, the contents of the files they change, and their hg
+//! changesets, manifests and filenodes if those were derived.
+
+mod export;
+mod format;
+mod import;
+#[cfg(test)]
+mod test;
+
+use bonsai_hg_mapping::BonsaiHgMappingRef;
+use changesets::ChangesetsRef;
+use commit_graph::CommitGraphRef;
+use filestore::FilestoreConfigRef;
+use repo_blobstore::RepoBlobstoreRef;
+
+pub use crate::export::commits_to_export;
+pub use crate::export::export_bundle;
+pub use crate::export::ExportStats;
+pub use crate::format::BundleReader;
+pub use crate::format::BundleRecord;
+pub use crate::format::BundleWriter;
+pub use crate::import::import_bundle;
+
+/// Trait alias for the repo attributes needed to export and import bundles.
+pub trait Repo = BonsaiHgMappingRef
+    + ChangesetsRef
+    + CommitGraphRef
+    + FilestoreConfigRef
+    + RepoBlobstoreRef
+    + Send
+    + Sync;
@@ -0,0 +1,112 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Result;
+use blobstore::Loadable;
+use bonsai_hg_mapping::BonsaiHgMapping;
+use bookmarks::Bookmarks;
+use changeset_fetcher::ChangesetFetcher;
+use changesets::Changesets;
+use changesets::ChangesetsRef;
+use commit_graph::CommitGraph;
+use commit_graph::CommitGraphRef;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use filestore::FilestoreConfig;
+use mononoke_types::ChangesetId;
+use mononoke_types::FileChange;
+use repo_blobstore::RepoBlobstore;
+use repo_blobstore::RepoBlobstoreRef;
+use repo_derived_data::RepoDerivedData;
+use tests_utils::CreateCommitContext;
+
+use crate::commits_to_export;
+use crate::export_bundle;
+use crate::import_bundle;
+
+#[facet::container]
+struct TestRepo {
+    #[facet]
+    bonsai_hg_mapping: dyn BonsaiHgMapping,
+    #[facet]
+    bookmarks: dyn Bookmarks,
+    #[facet]
+    changesets: dyn Changesets,
+    #[facet]
+    changeset_fetcher: dyn ChangesetFetcher,
+    #[facet]
+    commit_graph: CommitGraph,
+    #[facet]
+    filestore_config: FilestoreConfig,
+    #[facet]
+    repo_blobstore: RepoBlobstore,
+    #[facet]
+    repo_derived_data: RepoDerivedData,
+}
+
+async fn commit(
+    ctx: &CoreContext,
+    repo: &TestRepo,
+    parents: Vec<ChangesetId>,
+    path: &str,
+    content: &str,
+) -> Result<ChangesetId> {
+    let cs_id = CreateCommitContext::new(ctx, repo, parents.clone())
+        .add_file(path, content)
+        .commit()
+        .await?;
+    repo.commit_graph()
+        .add(ctx, cs_id, parents.into_iter().collect())
+        .await?;
+    Ok(cs_id)
+}
+
+#[fbinit::test]
+async fn test_export_import(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let source: TestRepo = test_repo_factory::build_empty(fb)?;
+    let target: TestRepo = test_repo_factory::build_empty(fb)?;
+
+    let a = commit(&ctx, &source, vec![], "a", "a\n").await?;
+    let b = commit(&ctx, &source, vec![a], "b", "b\n").await?;
+    let c = commit(&ctx, &source, vec![b], "c", "c\n").await?;
+    let d = commit(&ctx, &source, vec![c], "d", "d\n").await?;
+
+    // The limit keeps the oldest commits, parents first.
+    let bonsais = commits_to_export(&ctx, &source, vec![d], vec![a], Some(2)).await?;
+    let cs_ids: Vec<_> = bonsais.iter().map(|bcs| bcs.get_changeset_id()).collect();
+    assert_eq!(cs_ids, vec![b, c]);
+
+    let bonsais = commits_to_export(&ctx, &source, vec![d], vec![], None).await?;
+    let cs_ids: Vec<_> = bonsais.iter().map(|bcs| bcs.get_changeset_id()).collect();
+    assert_eq!(cs_ids, vec![a, b, c, d]);
+
+    // Commits stop being added once the bundle reaches its maximum size.
+    let (_bundle, stats) = export_bundle(&ctx, &source, &bonsais, Vec::new(), Some(1)).await?;
+    assert_eq!(stats.commits, 1);
+
+    let (bundle, stats) = export_bundle(&ctx, &source, &bonsais, Vec::new(), None).await?;
+    assert_eq!(stats.commits, 4);
+    assert_eq!(stats.files, 4);
+
+    let imported = import_bundle(&ctx, &target, bundle.as_slice(), 3).await?;
+    assert_eq!(imported, vec![a, b, c, d]);
+    for cs_id in imported {
+        assert!(target.changesets().exists(&ctx, cs_id).await?);
+    }
+    let bcs = d.load(&ctx, target.repo_blobstore()).await?;
+    for (_path, file_change) in bcs.file_changes() {
+        if let FileChange::Change(change) = file_change {
+            let content =
+                filestore::fetch_concat(target.repo_blobstore(), &ctx, change.content_id())
+                    .await?;
+            assert_eq!(content.as_ref(), b"d\n");
+        }
+    }
+
+    Ok(())
+}
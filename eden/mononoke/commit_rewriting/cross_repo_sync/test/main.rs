@@ -144,6 +144,7 @@ async fn create_empty_commit(ctx: CoreContext, repo: &TestRepo) -> ChangesetId {
         file_changes: sorted_vector_map! {},
         is_snapshot: false,
         git_annotated_tag: None,
+        extras: None,
     }
     .freeze()
     .unwrap();
@@ -1853,6 +1854,7 @@ async fn create_merge(
         file_changes: Default::default(),
         is_snapshot: false,
         git_annotated_tag: None,
+        extras: None,
     }
     .freeze()
     .unwrap();
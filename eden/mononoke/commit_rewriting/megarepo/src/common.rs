@@ -158,6 +158,7 @@ fn create_bonsai_changeset_only(
         file_changes,
         is_snapshot: false,
         git_annotated_tag: None,
+        extras: None,
     }
     .freeze()
 }
@@ -346,6 +346,16 @@ pub fn get_movers(
     }
 }
 
+/// Create a `Mover` that keeps the paths under any of `allowed_paths` where
+/// they are, and drops all other paths
+pub fn get_allowlist_mover(allowed_paths: &[MPath]) -> Result<Mover> {
+    let prefix_map = allowed_paths
+        .iter()
+        .map(|path| (path.clone(), PrefixAction::Change(path.clone())))
+        .collect();
+    mover_factory(prefix_map, DefaultAction::DoNotSync)
+}
+
 #[cfg(test)]
 mod test {
     use maplit::hashmap;
@@ -420,6 +430,20 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_allowlist_mover() {
+        let mover = get_allowlist_mover(&[mp("public"), mp("docs/api")]).unwrap();
+        assert_eq!(mover(&mp("public/1.txt")).unwrap(), Some(mp("public/1.txt")));
+        assert_eq!(mover(&mp("public")).unwrap(), Some(mp("public")));
+        assert_eq!(
+            mover(&mp("docs/api/index.md")).unwrap(),
+            Some(mp("docs/api/index.md"))
+        );
+        assert_eq!(mover(&mp("docs/internal.md")).unwrap(), None);
+        assert_eq!(mover(&mp("publicity/1.txt")).unwrap(), None);
+        assert_eq!(mover(&mp("secret/1.txt")).unwrap(), None);
+    }
+
     #[test]
     fn test_mover() {
         let hm = hashmap! {
@@ -0,0 +1,25 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+-- The outcome of exporting each commit of a repo to its sparse export.
+-- Commits that were exported map to their exports.  Commits that change no
+-- exported paths weren't exported, and map to the export of their nearest
+-- exported ancestor, or to NULL if they have none.  Each commit is exported
+-- with a version of the exported paths, and the exports of commits can only
+-- be built on those of parents that were exported with the same version.
+CREATE TABLE IF NOT EXISTS `sparse_export_mapping` (
+  `source_repo_id` INTEGER NOT NULL,
+  `source_bcs_id` BINARY(32) NOT NULL,
+  `export_repo_id` INTEGER NOT NULL,
+  `export_bcs_id` BINARY(32) NULL,
+  `exported` BOOLEAN NOT NULL,
+  `allowed_paths_version` VARCHAR(64) NOT NULL,
+  PRIMARY KEY (`source_repo_id`, `export_repo_id`, `source_bcs_id`)
+);
+
+CREATE INDEX IF NOT EXISTS `sparse_export_mapping_export`
+  ON `sparse_export_mapping` (`export_repo_id`, `export_bcs_id`);
@@ -0,0 +1,87 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use clap::Parser;
+use context::CoreContext;
+use cross_repo_sync::ConcreteRepo;
+use fbinit::FacebookInit;
+use mononoke_app::args::AsRepoArg;
+use mononoke_app::args::RepoArg;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::fb303::AliveService;
+use mononoke_app::fb303::Fb303AppExtension;
+use mononoke_app::MononokeApp;
+use mononoke_app::MononokeAppBuilder;
+use slog::info;
+use slog::warn;
+use sparse_export::SparseExporter;
+use sparse_export::SqlSparseExportMappingBuilder;
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+
+/// Export the commits of a repo's exported bookmarks to its sparse export,
+/// which only contains the repo's allow-listed paths.
+#[derive(Parser)]
+struct SparseExportArgs {
+    /// Repo to export
+    #[clap(flatten)]
+    repo_args: RepoArgs,
+
+    /// Keep exporting at this interval, in seconds, instead of exporting
+    /// once
+    #[clap(long)]
+    interval_secs: Option<u64>,
+}
+
+#[fbinit::main]
+fn main(fb: FacebookInit) -> Result<()> {
+    let app = MononokeAppBuilder::new(fb)
+        .with_app_extension(Fb303AppExtension {})
+        .build::<SparseExportArgs>()?;
+
+    app.run_with_monitoring_and_logging(async_main, "sparse_export", AliveService)
+}
+
+async fn async_main(app: MononokeApp) -> Result<()> {
+    let args: SparseExportArgs = app.args()?;
+    let (repo_name, repo_config) = app.repo_config(args.repo_args.as_repo_arg())?;
+    let ctx = CoreContext::new_with_logger(app.fb, app.repo_logger(&repo_name));
+    let config = repo_config
+        .sparse_export_config
+        .as_ref()
+        .ok_or_else(|| anyhow!("Sparse export is not configured for {}", repo_name))?;
+    let source_repo: ConcreteRepo = app.open_repo(&args.repo_args).await?;
+    let export_repo: ConcreteRepo = app
+        .open_repo(&RepoArg::Id(config.export_repo_id))
+        .await?;
+    let mapping = SqlSparseExportMappingBuilder::with_metadata_database_config(
+        app.fb,
+        &repo_config.storage_config.metadata,
+        app.mysql_options(),
+        app.readonly_storage().0,
+    )?
+    .build(repo_config.repoid, config.export_repo_id);
+    let exporter = SparseExporter::new(source_repo, export_repo, mapping, config)?;
+
+    let interval = match args.interval_secs {
+        Some(interval_secs) => Duration::from_secs(interval_secs),
+        None => return exporter.export_bookmarks(&ctx).await,
+    };
+
+    info!(ctx.logger(), "Exporting every {:?}", interval);
+    loop {
+        // When exporting continuously, failures are logged and retried at
+        // the next interval.
+        if let Err(err) = exporter.export_bookmarks(&ctx).await {
+            warn!(ctx.logger(), "Sparse export failed: {:?}", err);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
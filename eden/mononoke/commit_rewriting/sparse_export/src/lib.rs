@@ -0,0 +1,366 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Sparse export materializes a copy of a repo that only contains the paths
+//! on an allow-list in another repo, e.g. to mirror part of a repo as open
+//! source.
+//!
+//! Each commit of the exported bookmarks is rewritten with a mover that
+//! drops all changes to paths that aren't allowed, and the rewritten commit
+//! is uploaded to the export repo on top of the exports of its parents.
+//! Commits that change no allowed paths aren't exported.  The outcome of
+//! exporting each commit is recorded in a mapping, so that exporting can be
+//! resumed from where it stopped.
+//!
+//! The mapping also records the version of the allowed paths each commit was
+//! exported with.  Exporting a commit on top of exports made with other
+//! allowed paths would leave paths in the export that are no longer allowed,
+//! or miss paths that are newly allowed, so changing the allowed paths needs
+//! a new export repo.
+
+#![feature(trait_alias)]
+
+mod mapping;
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Result;
+use blobstore::Loadable;
+use bookmarks::BookmarkKey;
+use bookmarks::BookmarkUpdateReason;
+use bookmarks::BookmarksRef;
+use changeset_fetcher::ChangesetFetcherRef;
+use commit_transformation::upload_commits;
+use commit_transformation::CommitRewrittenToEmpty;
+use context::log_keys;
+use context::CoreContext;
+use cross_repo_sync::rewrite_commit;
+use cross_repo_sync::Repo;
+use futures::future::try_join_all;
+use metaconfig_types::SparseExportConfig;
+use mononoke_types::hash;
+use mononoke_types::ChangesetId;
+use mononoke_types::FileChange;
+use mononoke_types::MPath;
+use movers::get_allowlist_mover;
+use movers::Mover;
+use repo_blobstore::RepoBlobstoreRef;
+use slog::info;
+use topo_sort::sort_topological;
+
+pub use crate::mapping::SparseExportEntry;
+pub use crate::mapping::SparseExportOutcome;
+pub use crate::mapping::SqlSparseExportMapping;
+pub use crate::mapping::SqlSparseExportMappingBuilder;
+
+/// Exports the bookmarks of a repo, and the commits reachable from them, to
+/// the sparse export of the repo.
+pub struct SparseExporter<R> {
+    source_repo: R,
+    export_repo: R,
+    mapping: SqlSparseExportMapping,
+    mover: Mover,
+    allowed_paths_version: String,
+    bookmarks: Vec<BookmarkKey>,
+}
+
+/// The version of a set of allowed paths, which changes whenever they do.
+fn allowed_paths_version(allowed_paths: &[MPath]) -> String {
+    let mut allowed_paths = allowed_paths.to_vec();
+    allowed_paths.sort();
+    allowed_paths.dedup();
+    let mut context = hash::Context::new(b"sparse_export.allowed_paths");
+    for path in allowed_paths {
+        context.update(path.to_vec());
+        context.update(b"\0");
+    }
+    context.finish().to_hex().to_string()
+}
+
+impl<R: Repo> SparseExporter<R> {
+    pub fn new(
+        source_repo: R,
+        export_repo: R,
+        mapping: SqlSparseExportMapping,
+        config: &SparseExportConfig,
+    ) -> Result<Self> {
+        Ok(Self {
+            source_repo,
+            export_repo,
+            mapping,
+            mover: get_allowlist_mover(&config.allowed_paths)?,
+            allowed_paths_version: allowed_paths_version(&config.allowed_paths),
+            bookmarks: config.bookmarks.clone(),
+        })
+    }
+
+    /// Export each of the exported bookmarks.
+    pub async fn export_bookmarks(&self, ctx: &CoreContext) -> Result<()> {
+        for bookmark in &self.bookmarks {
+            self.export_bookmark(ctx, bookmark).await?;
+        }
+        Ok(())
+    }
+
+    /// Export the commits reachable from a bookmark that aren't exported yet,
+    /// and move the bookmark in the export to the export of its tip.
+    /// Returns the export of the tip, if it has one.
+    pub async fn export_bookmark(
+        &self,
+        ctx: &CoreContext,
+        bookmark: &BookmarkKey,
+    ) -> Result<Option<ChangesetId>> {
+        let bookmarks = self.source_repo.bookmarks();
+        let tip = match bookmarks.get(ctx.clone(), bookmark).await? {
+            Some(tip) => tip,
+            None => {
+                info!(
+                    ctx.logger(),
+                    "{} does not exist, not exporting it", bookmark
+                );
+                return Ok(None);
+            }
+        };
+        let export_cs_id = self
+            .export_commit_and_ancestors(ctx, tip)
+            .await?
+            .export_cs_id();
+        match export_cs_id {
+            Some(export_cs_id) => {
+                self.move_export_bookmark(ctx, bookmark, export_cs_id)
+                    .await?
+            }
+            None => {
+                info!(
+                    ctx.logger(),
+                    "{} has no exported paths, not exporting it", bookmark
+                );
+            }
+        }
+        Ok(export_cs_id)
+    }
+
+    /// Export a commit and those of its ancestors that aren't exported yet.
+    pub async fn export_commit_and_ancestors(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<SparseExportOutcome> {
+        let (unexported, mut entries) = self
+            .find_toposorted_unexported_ancestors(ctx, cs_id)
+            .await?;
+        if !unexported.is_empty() {
+            info!(
+                ctx.logger(),
+                "Exporting {} commits", unexported.len();
+                log_keys::CS_ID => %cs_id
+            );
+        }
+        for (index, unexported_cs_id) in unexported.iter().enumerate() {
+            let outcome = self
+                .export_commit(ctx, *unexported_cs_id, &mut entries)
+                .await?;
+            if (index + 1) % 100 == 0 {
+                info!(
+                    ctx.logger(),
+                    "Exported {} of {} commits",
+                    index + 1,
+                    unexported.len()
+                );
+            }
+            if *unexported_cs_id == cs_id {
+                return Ok(outcome);
+            }
+        }
+        entries
+            .remove(&cs_id)
+            .map(|entry| entry.outcome)
+            .ok_or_else(|| anyhow!("{} was not exported", cs_id))
+    }
+
+    /// The ancestors of a commit, including the commit itself, that aren't
+    /// exported yet, ancestors first, and the entries of the exported commits
+    /// that were found while looking for them.
+    async fn find_toposorted_unexported_ancestors(
+        &self,
+        ctx: &CoreContext,
+        start_cs_id: ChangesetId,
+    ) -> Result<(Vec<ChangesetId>, HashMap<ChangesetId, SparseExportEntry>)> {
+        let mut visited = HashSet::from([start_cs_id]);
+        let mut queue = vec![start_cs_id];
+        let mut unexported = HashMap::new();
+        let mut entries = HashMap::new();
+        while !queue.is_empty() {
+            // Most of the commits were exported long ago, so only those that
+            // look unexported are checked with the master, once at the end.
+            let exported = self.mapping.get_maybe_stale(ctx, &queue).await?;
+            let parents = try_join_all(
+                queue
+                    .drain(..)
+                    .filter(|cs_id| !exported.contains_key(cs_id))
+                    .map(|cs_id| async move {
+                        let parents = self
+                            .source_repo
+                            .changeset_fetcher()
+                            .get_parents(ctx, cs_id)
+                            .await?;
+                        anyhow::Ok((cs_id, parents))
+                    }),
+            )
+            .await?;
+            for (cs_id, parents) in parents {
+                queue.extend(parents.iter().filter(|parent| visited.insert(**parent)));
+                unexported.insert(cs_id, parents);
+            }
+            entries.extend(exported);
+        }
+        let exported = self
+            .mapping
+            .get(ctx, &unexported.keys().copied().collect::<Vec<_>>())
+            .await?;
+        for cs_id in exported.keys() {
+            unexported.remove(cs_id);
+        }
+        entries.extend(exported);
+
+        // The sorted commits include the parents of the unexported commits,
+        // which are already exported.
+        let sorted =
+            sort_topological(&unexported).ok_or_else(|| anyhow!("Commit graph has a cycle"))?;
+        let sorted = sorted
+            .into_iter()
+            .filter(|cs_id| unexported.contains_key(cs_id))
+            .collect();
+        Ok((sorted, entries))
+    }
+
+    /// Export a commit whose parents are all exported, given the entries of
+    /// its parents, and add its own entry to them.
+    async fn export_commit(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+        entries: &mut HashMap<ChangesetId, SparseExportEntry>,
+    ) -> Result<SparseExportOutcome> {
+        let cs = cs_id.load(ctx, self.source_repo.repo_blobstore()).await?;
+        let mut cs = cs.into_mut();
+
+        // Parents with no exported paths have no exports, and several parents
+        // may have the same export, so the export may have fewer parents.
+        let mut remapped_parents = HashMap::new();
+        let mut export_parents = Vec::new();
+        let mut parents = Vec::new();
+        for parent in cs.parents {
+            let entry = entries
+                .get(&parent)
+                .ok_or_else(|| anyhow!("Parent {} of {} is not exported", parent, cs_id))?;
+            if entry.allowed_paths_version != self.allowed_paths_version {
+                bail!(
+                    "Parent {} of {} was exported with other allowed paths, which would leave \
+                     the export inconsistent: export to a new repo to change the allowed paths",
+                    parent,
+                    cs_id,
+                );
+            }
+            if let Some(export_parent) = entry.outcome.export_cs_id() {
+                if !export_parents.contains(&export_parent) {
+                    remapped_parents.insert(parent, export_parent);
+                    export_parents.push(export_parent);
+                    parents.push(parent);
+                }
+            }
+        }
+        cs.parents = parents;
+        // Extras can refer to the commits, trees and paths of the source repo,
+        // which aren't all exported, so none of them are.
+        cs.hg_extra = Default::default();
+        cs.git_extra_headers = None;
+        cs.git_tree_hash = None;
+        cs.git_annotated_tag = None;
+        cs.extras = None;
+        // Copies from parents that were dropped can't be remapped.
+        for change in cs.file_changes.values_mut() {
+            if let FileChange::Change(tc) = change {
+                let copied_from_dropped_parent = tc.copy_from().map_or(false, |(_, from_cs_id)| {
+                    !remapped_parents.contains_key(from_cs_id)
+                });
+                if copied_from_dropped_parent {
+                    *tc = tc.with_new_copy_from(None);
+                }
+            }
+        }
+
+        let rewritten = rewrite_commit(
+            ctx,
+            cs,
+            &remapped_parents,
+            self.mover.clone(),
+            &self.source_repo,
+            CommitRewrittenToEmpty::Discard,
+        )
+        .await?;
+        let outcome = match rewritten {
+            // Merges are exported even if they don't change any exported
+            // paths, as they change the working copy of their first parent.
+            Some(rewritten)
+                if rewritten.parents.len() > 1 || !rewritten.file_changes.is_empty() =>
+            {
+                let rewritten = rewritten.freeze()?;
+                let export_cs_id = rewritten.get_changeset_id();
+                upload_commits(ctx, vec![rewritten], &self.source_repo, &self.export_repo).await?;
+                SparseExportOutcome::Exported(export_cs_id)
+            }
+            _ => SparseExportOutcome::Skipped(export_parents.first().copied()),
+        };
+        let entry = SparseExportEntry {
+            outcome,
+            allowed_paths_version: self.allowed_paths_version.clone(),
+        };
+        self.mapping.add(ctx, cs_id, &entry).await?;
+        entries.insert(cs_id, entry);
+        Ok(outcome)
+    }
+
+    async fn move_export_bookmark(
+        &self,
+        ctx: &CoreContext,
+        bookmark: &BookmarkKey,
+        export_cs_id: ChangesetId,
+    ) -> Result<()> {
+        let bookmarks = self.export_repo.bookmarks();
+        let old_cs_id = bookmarks.get(ctx.clone(), bookmark).await?;
+        if old_cs_id == Some(export_cs_id) {
+            return Ok(());
+        }
+        let mut txn = bookmarks.create_transaction(ctx.clone());
+        match old_cs_id {
+            Some(old_cs_id) => txn.update(
+                bookmark,
+                export_cs_id,
+                old_cs_id,
+                BookmarkUpdateReason::SparseExport,
+            )?,
+            None => txn.create(bookmark, export_cs_id, BookmarkUpdateReason::SparseExport)?,
+        }
+        if !txn.commit().await? {
+            bail!(
+                "Failed to move {} in the export to {}",
+                bookmark,
+                export_cs_id
+            );
+        }
+        info!(
+            ctx.logger(),
+            "Moved {} in the export", bookmark;
+            log_keys::CS_ID => %export_cs_id
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,284 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use context::CoreContext;
+use context::PerfCounterType;
+use mononoke_types::ChangesetId;
+use mononoke_types::RepositoryId;
+use sql_construct::SqlConstruct;
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::mononoke_queries;
+use sql_ext::SqlConnections;
+
+/// The outcome of exporting a commit.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SparseExportOutcome {
+    /// The commit was exported as this commit.
+    Exported(ChangesetId),
+    /// The commit changed no exported paths, so it wasn't exported.  Its
+    /// working copy in the export is that of the export of its nearest
+    /// exported ancestor, if it has one.
+    Skipped(Option<ChangesetId>),
+}
+
+/// The outcome of exporting a commit, along with the version of the allowed
+/// paths it was exported with.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SparseExportEntry {
+    pub outcome: SparseExportOutcome,
+    pub allowed_paths_version: String,
+}
+
+impl SparseExportOutcome {
+    /// The commit in the export whose working copy is the exported part of
+    /// the working copy of the commit.
+    pub fn export_cs_id(&self) -> Option<ChangesetId> {
+        match self {
+            Self::Exported(cs_id) => Some(*cs_id),
+            Self::Skipped(cs_id) => *cs_id,
+        }
+    }
+}
+
+mononoke_queries! {
+    write InsertMapping(
+        values: (
+            source_repo_id: RepositoryId,
+            source_bcs_id: ChangesetId,
+            export_repo_id: RepositoryId,
+            export_bcs_id: Option<ChangesetId>,
+            exported: bool,
+            allowed_paths_version: String
+        )
+    ) {
+        insert_or_ignore,
+        "{insert_or_ignore} INTO sparse_export_mapping
+            (source_repo_id, source_bcs_id, export_repo_id, export_bcs_id, exported,
+             allowed_paths_version)
+         VALUES {values}"
+    }
+
+    read SelectMapping(
+        source_repo_id: RepositoryId,
+        export_repo_id: RepositoryId,
+        >list source_bcs_ids: ChangesetId
+    ) -> (ChangesetId, Option<ChangesetId>, bool, String) {
+        "SELECT source_bcs_id, export_bcs_id, exported, allowed_paths_version
+         FROM sparse_export_mapping
+         WHERE source_repo_id = {source_repo_id}
+           AND export_repo_id = {export_repo_id}
+           AND source_bcs_id IN {source_bcs_ids}"
+    }
+}
+
+/// Mapping from the commits of a repo to their outcomes of being exported
+/// to the sparse export of the repo.
+#[derive(Clone)]
+pub struct SqlSparseExportMapping {
+    source_repo_id: RepositoryId,
+    export_repo_id: RepositoryId,
+    connections: SqlConnections,
+}
+
+pub struct SqlSparseExportMappingBuilder {
+    connections: SqlConnections,
+}
+
+impl SqlConstruct for SqlSparseExportMappingBuilder {
+    const LABEL: &'static str = "sparse_export_mapping";
+
+    const CREATION_QUERY: &'static str =
+        include_str!("../schemas/sqlite-sparse-export-mapping.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self { connections }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SqlSparseExportMappingBuilder {}
+
+impl SqlSparseExportMappingBuilder {
+    pub fn build(
+        self,
+        source_repo_id: RepositoryId,
+        export_repo_id: RepositoryId,
+    ) -> SqlSparseExportMapping {
+        SqlSparseExportMapping {
+            source_repo_id,
+            export_repo_id,
+            connections: self.connections,
+        }
+    }
+}
+
+impl SqlSparseExportMapping {
+    /// The outcomes of exporting those of the commits that were exported.
+    pub async fn get(
+        &self,
+        ctx: &CoreContext,
+        source_cs_ids: &[ChangesetId],
+    ) -> Result<HashMap<ChangesetId, SparseExportEntry>> {
+        let mut entries = self.get_maybe_stale(ctx, source_cs_ids).await?;
+        // Commits may have only just been exported, in which case the
+        // replica doesn't know about them yet.
+        let missing = source_cs_ids
+            .iter()
+            .filter(|cs_id| !entries.contains_key(cs_id))
+            .copied()
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            ctx.perf_counters()
+                .increment_counter(PerfCounterType::SqlReadsMaster);
+            let rows = SelectMapping::query(
+                &self.connections.read_master_connection,
+                &self.source_repo_id,
+                &self.export_repo_id,
+                &missing,
+            )
+            .await?;
+            entries.extend(rows.into_iter().map(Self::entry_from_row));
+        }
+        Ok(entries)
+    }
+
+    /// The outcomes of exporting those of the commits that the replica knows
+    /// were exported.  Outcomes never change once they are recorded, so those
+    /// that are found are up to date.
+    pub async fn get_maybe_stale(
+        &self,
+        ctx: &CoreContext,
+        source_cs_ids: &[ChangesetId],
+    ) -> Result<HashMap<ChangesetId, SparseExportEntry>> {
+        if source_cs_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+        let rows = SelectMapping::query(
+            &self.connections.read_connection,
+            &self.source_repo_id,
+            &self.export_repo_id,
+            source_cs_ids,
+        )
+        .await?;
+        Ok(rows.into_iter().map(Self::entry_from_row).collect())
+    }
+
+    fn entry_from_row(
+        (source_cs_id, export_cs_id, exported, allowed_paths_version): (
+            ChangesetId,
+            Option<ChangesetId>,
+            bool,
+            String,
+        ),
+    ) -> (ChangesetId, SparseExportEntry) {
+        let outcome = match export_cs_id {
+            Some(export_cs_id) if exported => SparseExportOutcome::Exported(export_cs_id),
+            export_cs_id => SparseExportOutcome::Skipped(export_cs_id),
+        };
+        let entry = SparseExportEntry {
+            outcome,
+            allowed_paths_version,
+        };
+        (source_cs_id, entry)
+    }
+
+    /// Record the outcome of exporting a commit.  A commit is only ever
+    /// exported once, so if an outcome is already recorded, it is kept.
+    pub async fn add(
+        &self,
+        ctx: &CoreContext,
+        source_cs_id: ChangesetId,
+        entry: &SparseExportEntry,
+    ) -> Result<()> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        let exported = matches!(entry.outcome, SparseExportOutcome::Exported(_));
+        InsertMapping::query(
+            &self.connections.write_connection,
+            &[(
+                &self.source_repo_id,
+                &source_cs_id,
+                &self.export_repo_id,
+                &entry.outcome.export_cs_id(),
+                &exported,
+                &entry.allowed_paths_version,
+            )],
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use mononoke_types_mocks::changesetid::FOURS_CSID;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::THREES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+    use mononoke_types_mocks::repo::REPO_ONE;
+    use mononoke_types_mocks::repo::REPO_TWO;
+    use mononoke_types_mocks::repo::REPO_ZERO;
+
+    use super::*;
+
+    fn entry(outcome: SparseExportOutcome, allowed_paths_version: &str) -> SparseExportEntry {
+        SparseExportEntry {
+            outcome,
+            allowed_paths_version: allowed_paths_version.to_string(),
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_add_and_get(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let mapping =
+            SqlSparseExportMappingBuilder::with_sqlite_in_memory()?.build(REPO_ZERO, REPO_ONE);
+
+        let skipped = entry(SparseExportOutcome::Skipped(None), "v1");
+        let exported = entry(SparseExportOutcome::Exported(FOURS_CSID), "v1");
+        let skipped_after_exported = entry(SparseExportOutcome::Skipped(Some(FOURS_CSID)), "v2");
+        mapping.add(&ctx, ONES_CSID, &skipped).await?;
+        mapping.add(&ctx, TWOS_CSID, &exported).await?;
+        mapping
+            .add(&ctx, THREES_CSID, &skipped_after_exported)
+            .await?;
+        // Outcomes that are already recorded are kept.
+        mapping.add(&ctx, ONES_CSID, &exported).await?;
+
+        let expected = HashMap::from([
+            (ONES_CSID, skipped),
+            (TWOS_CSID, exported),
+            (THREES_CSID, skipped_after_exported),
+        ]);
+        let cs_ids = [ONES_CSID, TWOS_CSID, THREES_CSID, FOURS_CSID];
+        assert_eq!(mapping.get(&ctx, &cs_ids).await?, expected);
+        assert_eq!(mapping.get_maybe_stale(&ctx, &cs_ids).await?, expected);
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_mappings_are_per_export(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let builder = SqlSparseExportMappingBuilder::with_sqlite_in_memory()?;
+        let connections = builder.connections.clone();
+        let mapping = builder.build(REPO_ZERO, REPO_ONE);
+        let other_mapping = SqlSparseExportMappingBuilder::from_sql_connections(connections)
+            .build(REPO_ZERO, REPO_TWO);
+
+        let exported = entry(SparseExportOutcome::Exported(TWOS_CSID), "v1");
+        mapping.add(&ctx, ONES_CSID, &exported).await?;
+
+        assert_eq!(mapping.get(&ctx, &[ONES_CSID]).await?.len(), 1);
+        assert!(other_mapping.get(&ctx, &[ONES_CSID]).await?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,331 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use blobstore::Loadable;
+use bookmarks::BookmarkKey;
+use bookmarks::BookmarksRef;
+use context::CoreContext;
+use cross_repo_sync::ConcreteRepo;
+use fbinit::FacebookInit;
+use metaconfig_types::SparseExportConfig;
+use mononoke_types::ChangesetId;
+use mononoke_types::MPath;
+use mononoke_types_mocks::repo::REPO_ONE;
+use mononoke_types_mocks::repo::REPO_ZERO;
+use repo_blobstore::RepoBlobstoreRef;
+use sparse_export::SparseExportOutcome;
+use sparse_export::SparseExporter;
+use sparse_export::SqlSparseExportMapping;
+use sparse_export::SqlSparseExportMappingBuilder;
+use sql_construct::SqlConstruct;
+use test_repo_factory::TestRepoFactory;
+use tests_utils::bookmark;
+use tests_utils::list_working_copy_utf8;
+use tests_utils::CreateCommitContext;
+
+struct TestExport {
+    ctx: CoreContext,
+    source_repo: ConcreteRepo,
+    export_repo: ConcreteRepo,
+    mapping: SqlSparseExportMapping,
+    exporter: SparseExporter<ConcreteRepo>,
+}
+
+impl TestExport {
+    fn new(fb: FacebookInit) -> Result<Self> {
+        let ctx = CoreContext::test_mock(fb);
+        let mut factory = TestRepoFactory::new(fb)?;
+        let source_repo: ConcreteRepo = factory.with_id(REPO_ZERO).build()?;
+        let export_repo: ConcreteRepo = factory.with_id(REPO_ONE).build()?;
+        let mapping =
+            SqlSparseExportMappingBuilder::with_sqlite_in_memory()?.build(REPO_ZERO, REPO_ONE);
+        let exporter = Self::exporter(
+            &source_repo,
+            &export_repo,
+            &mapping,
+            vec![MPath::new("public")?],
+        )?;
+        Ok(Self {
+            ctx,
+            source_repo,
+            export_repo,
+            mapping,
+            exporter,
+        })
+    }
+
+    fn exporter(
+        source_repo: &ConcreteRepo,
+        export_repo: &ConcreteRepo,
+        mapping: &SqlSparseExportMapping,
+        allowed_paths: Vec<MPath>,
+    ) -> Result<SparseExporter<ConcreteRepo>> {
+        let config = SparseExportConfig {
+            export_repo_id: REPO_ONE,
+            allowed_paths,
+            bookmarks: vec![BookmarkKey::new("master")?],
+        };
+        SparseExporter::new(
+            source_repo.clone(),
+            export_repo.clone(),
+            mapping.clone(),
+            &config,
+        )
+    }
+
+    async fn outcome(&self, cs_id: ChangesetId) -> Result<SparseExportOutcome> {
+        self.mapping
+            .get(&self.ctx, &[cs_id])
+            .await?
+            .remove(&cs_id)
+            .map(|entry| entry.outcome)
+            .ok_or_else(|| anyhow!("{} was not exported", cs_id))
+    }
+
+    async fn export_master(&self) -> Result<Option<ChangesetId>> {
+        self.export_repo
+            .bookmarks()
+            .get(self.ctx.clone(), &BookmarkKey::new("master")?)
+            .await
+    }
+
+    async fn export_parents(&self, cs_id: ChangesetId) -> Result<Vec<ChangesetId>> {
+        let cs = cs_id
+            .load(&self.ctx, self.export_repo.repo_blobstore())
+            .await?;
+        Ok(cs.parents().collect())
+    }
+
+    async fn export_working_copy(&self, cs_id: ChangesetId) -> Result<HashMap<String, String>> {
+        Ok(list_working_copy_utf8(&self.ctx, &self.export_repo, cs_id)
+            .await?
+            .into_iter()
+            .map(|(path, content)| (path.to_string(), content))
+            .collect())
+    }
+}
+
+#[fbinit::test]
+async fn test_export_drops_paths_not_allowed(fb: FacebookInit) -> Result<()> {
+    let test = TestExport::new(fb)?;
+    let ctx = &test.ctx;
+    let repo = &test.source_repo;
+
+    let root = CreateCommitContext::new_root(ctx, repo)
+        .add_file("public/a", "a")
+        .add_file("secret/b", "b")
+        .commit()
+        .await?;
+    let secret_only = CreateCommitContext::new(ctx, repo, vec![root])
+        .add_file("secret/b", "b2")
+        .commit()
+        .await?;
+    let tip = CreateCommitContext::new(ctx, repo, vec![secret_only])
+        .add_file("public/c", "c")
+        .delete_file("secret/b")
+        .commit()
+        .await?;
+    bookmark(ctx, repo, "master").set_to(tip).await?;
+
+    test.exporter.export_bookmarks(ctx).await?;
+
+    let export_root = match test.outcome(root).await? {
+        SparseExportOutcome::Exported(export_root) => export_root,
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    };
+    assert_eq!(
+        test.outcome(secret_only).await?,
+        SparseExportOutcome::Skipped(Some(export_root))
+    );
+    let export_tip = match test.outcome(tip).await? {
+        SparseExportOutcome::Exported(export_tip) => export_tip,
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    };
+    assert_eq!(test.export_parents(export_tip).await?, vec![export_root]);
+    assert_eq!(test.export_master().await?, Some(export_tip));
+    assert_eq!(
+        test.export_working_copy(export_root).await?,
+        HashMap::from([("public/a".to_string(), "a".to_string())])
+    );
+    assert_eq!(
+        test.export_working_copy(export_tip).await?,
+        HashMap::from([
+            ("public/a".to_string(), "a".to_string()),
+            ("public/c".to_string(), "c".to_string()),
+        ])
+    );
+
+    // Commits that change no allowed paths don't move the export.
+    let secret_tip = CreateCommitContext::new(ctx, repo, vec![tip])
+        .add_file("secret/d", "d")
+        .commit()
+        .await?;
+    bookmark(ctx, repo, "master").set_to(secret_tip).await?;
+    test.exporter.export_bookmarks(ctx).await?;
+    assert_eq!(
+        test.outcome(secret_tip).await?,
+        SparseExportOutcome::Skipped(Some(export_tip))
+    );
+    assert_eq!(test.export_master().await?, Some(export_tip));
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_export_merges(fb: FacebookInit) -> Result<()> {
+    let test = TestExport::new(fb)?;
+    let ctx = &test.ctx;
+    let repo = &test.source_repo;
+
+    // The history of secret paths is merged in, which doesn't change the
+    // export.
+    let secret_root = CreateCommitContext::new_root(ctx, repo)
+        .add_file("secret/a", "a")
+        .commit()
+        .await?;
+    let root = CreateCommitContext::new_root(ctx, repo)
+        .add_file("public/a", "a")
+        .commit()
+        .await?;
+    let secret_merge = CreateCommitContext::new(ctx, repo, vec![root, secret_root])
+        .commit()
+        .await?;
+    // Both sides of this merge change public paths, so it is exported as a
+    // merge.
+    let left = CreateCommitContext::new(ctx, repo, vec![secret_merge])
+        .add_file("public/b", "b")
+        .commit()
+        .await?;
+    let right = CreateCommitContext::new(ctx, repo, vec![secret_merge])
+        .add_file("public/c", "c")
+        .commit()
+        .await?;
+    let merge = CreateCommitContext::new(ctx, repo, vec![left, right])
+        .add_file("public/c", "c")
+        .commit()
+        .await?;
+    bookmark(ctx, repo, "master").set_to(merge).await?;
+
+    test.exporter.export_bookmarks(ctx).await?;
+
+    assert_eq!(
+        test.outcome(secret_root).await?,
+        SparseExportOutcome::Skipped(None)
+    );
+    let export_root = test
+        .outcome(root)
+        .await?
+        .export_cs_id()
+        .ok_or_else(|| anyhow!("root was not exported"))?;
+    assert_eq!(
+        test.outcome(secret_merge).await?,
+        SparseExportOutcome::Skipped(Some(export_root))
+    );
+    let export_left = test.outcome(left).await?.export_cs_id();
+    let export_right = test.outcome(right).await?.export_cs_id();
+    let export_merge = match test.outcome(merge).await? {
+        SparseExportOutcome::Exported(export_merge) => export_merge,
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    };
+    assert_eq!(
+        test.export_parents(export_merge)
+            .await?
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>(),
+        vec![export_left, export_right]
+    );
+    assert_eq!(test.export_master().await?, Some(export_merge));
+    assert_eq!(
+        test.export_working_copy(export_merge).await?,
+        HashMap::from([
+            ("public/a".to_string(), "a".to_string()),
+            ("public/b".to_string(), "b".to_string()),
+            ("public/c".to_string(), "c".to_string()),
+        ])
+    );
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_export_drops_extras(fb: FacebookInit) -> Result<()> {
+    let test = TestExport::new(fb)?;
+    let ctx = &test.ctx;
+    let repo = &test.source_repo;
+
+    let root = CreateCommitContext::new_root(ctx, repo)
+        .add_file("public/a", "a")
+        .add_extra("source", "secret/b")
+        .commit()
+        .await?;
+    bookmark(ctx, repo, "master").set_to(root).await?;
+
+    test.exporter.export_bookmarks(ctx).await?;
+
+    let export_root = test
+        .outcome(root)
+        .await?
+        .export_cs_id()
+        .ok_or_else(|| anyhow!("root was not exported"))?;
+    let export_cs = export_root
+        .load(ctx, test.export_repo.repo_blobstore())
+        .await?;
+    assert_eq!(export_cs.hg_extra().count(), 0);
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_export_refuses_changed_allowed_paths(fb: FacebookInit) -> Result<()> {
+    let test = TestExport::new(fb)?;
+    let ctx = &test.ctx;
+    let repo = &test.source_repo;
+
+    let root = CreateCommitContext::new_root(ctx, repo)
+        .add_file("public/a", "a")
+        .add_file("secret/b", "b")
+        .commit()
+        .await?;
+    bookmark(ctx, repo, "master").set_to(root).await?;
+    test.exporter.export_bookmarks(ctx).await?;
+
+    // Allowing more paths on top of the existing export would leave their
+    // history incomplete, so it is refused.
+    let exporter = TestExport::exporter(
+        &test.source_repo,
+        &test.export_repo,
+        &test.mapping,
+        vec![MPath::new("public")?, MPath::new("secret")?],
+    )?;
+    let tip = CreateCommitContext::new(ctx, repo, vec![root])
+        .add_file("secret/b", "b2")
+        .commit()
+        .await?;
+    bookmark(ctx, repo, "master").set_to(tip).await?;
+    assert!(exporter.export_bookmarks(ctx).await.is_err());
+    assert!(test.mapping.get(ctx, &[tip]).await?.is_empty());
+
+    // Repeating an allowed path doesn't change the version.
+    let exporter = TestExport::exporter(
+        &test.source_repo,
+        &test.export_repo,
+        &test.mapping,
+        vec![MPath::new("public")?, MPath::new("public")?],
+    )?;
+    exporter.export_bookmarks(ctx).await?;
+    assert_eq!(
+        test.outcome(tip).await?,
+        SparseExportOutcome::Skipped(test.outcome(root).await?.export_cs_id())
+    );
+
+    Ok(())
+}
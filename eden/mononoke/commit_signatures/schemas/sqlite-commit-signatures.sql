@@ -0,0 +1,14 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+CREATE TABLE IF NOT EXISTS commit_signatures (
+  repo_id INT UNSIGNED NOT NULL,
+  cs_id BINARY(32) NOT NULL,
+  key_id VARCHAR(255) NOT NULL,
+  signature BLOB NOT NULL,
+  PRIMARY KEY (repo_id, cs_id, key_id)
+);
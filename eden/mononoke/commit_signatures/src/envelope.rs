@@ -0,0 +1,461 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Keys and signatures in the formats produced by OpenSSH and OpenPGP, so
+//! that commits can be signed with `ssh-keygen -Y sign` or
+//! `gpg --detach-sign --armor` directly.
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use openssl::bn::BigNum;
+use openssl::hash::hash;
+use openssl::hash::MessageDigest;
+use openssl::pkey::Id;
+use openssl::pkey::PKey;
+use openssl::pkey::Public;
+use openssl::rsa::Rsa;
+use openssl::sign::Verifier;
+
+/// The namespace SSH signatures over commits must be made in, e.g. with
+/// `ssh-keygen -Y sign -n mononoke-commit`.
+pub const SSH_SIGNATURE_NAMESPACE: &str = "mononoke-commit";
+
+const SSH_SIGNATURE_LABEL: &str = "SSH SIGNATURE";
+const PGP_SIGNATURE_LABEL: &str = "PGP SIGNATURE";
+const PGP_PUBLIC_KEY_LABEL: &str = "PGP PUBLIC KEY BLOCK";
+
+/// Parse a trusted key, which is either a PEM public key, an OpenSSH public
+/// key, or an armored OpenPGP key block.  All the keys in an OpenPGP key
+/// block that are of a supported type are returned, so that signatures
+/// made with a subkey can be verified.
+pub(crate) fn parse_public_keys(text: &str) -> Result<Vec<PKey<Public>>> {
+    let text = text.trim();
+    if let Some(data) = dearmor(text.as_bytes(), PGP_PUBLIC_KEY_LABEL)? {
+        pgp::parse_public_keys(&data)
+    } else if text.starts_with("ssh-") {
+        Ok(vec![ssh::parse_public_key(text)?])
+    } else {
+        Ok(vec![PKey::public_key_from_pem(text.as_bytes())?])
+    }
+}
+
+/// Check whether the signature over the payload was made by any of the
+/// keys.  The signature is either an armored SSH or OpenPGP signature, or
+/// a raw signature.
+pub(crate) fn verify(keys: &[PKey<Public>], payload: &[u8], signature: &[u8]) -> bool {
+    // Malformed signatures are just signatures that don't verify.
+    verify_envelope(keys, payload, signature).unwrap_or(false)
+}
+
+fn verify_envelope(keys: &[PKey<Public>], payload: &[u8], signature: &[u8]) -> Result<bool> {
+    if let Some(data) = dearmor(signature, SSH_SIGNATURE_LABEL)? {
+        ssh::verify(keys, payload, &data)
+    } else if let Some(data) = dearmor(signature, PGP_SIGNATURE_LABEL)? {
+        pgp::verify(keys, payload, &data)
+    } else {
+        Ok(keys.iter().any(|key| {
+            let digest = match key.id() {
+                Id::ED25519 | Id::ED448 => None,
+                _ => Some(MessageDigest::sha256()),
+            };
+            verify_with_key(key, digest, payload, signature)
+        }))
+    }
+}
+
+/// Verify a signature over the message, which is hashed with the digest
+/// first.  EdDSA keys take no digest, as they hash the message as part of
+/// the signature scheme.
+fn verify_with_key(
+    key: &PKey<Public>,
+    digest: Option<MessageDigest>,
+    message: &[u8],
+    signature: &[u8],
+) -> bool {
+    let verifier = match digest {
+        Some(digest) => Verifier::new(digest, key),
+        None => Verifier::new_without_digest(key),
+    };
+    // Malformed signatures are reported as errors by some key types.
+    verifier
+        .and_then(|mut verifier| verifier.verify_oneshot(signature, message))
+        .unwrap_or(false)
+}
+
+/// Decode ASCII armored data, or return `None` if the data is not armored
+/// with this label.  Armor headers and the OpenPGP checksum are skipped.
+fn dearmor(data: &[u8], label: &str) -> Result<Option<Vec<u8>>> {
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text.trim(),
+        Err(_) => return Ok(None),
+    };
+    let body = match text
+        .strip_prefix(&format!("-----BEGIN {}-----", label))
+        .and_then(|text| text.strip_suffix(&format!("-----END {}-----", label)))
+    {
+        Some(body) => body,
+        None => return Ok(None),
+    };
+    let encoded = body
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.contains(':') && !line.starts_with('='))
+        .collect::<String>();
+    let data = base64::decode(&encoded).with_context(|| format!("Invalid {}", label))?;
+    Ok(Some(data))
+}
+
+fn left_pad(bytes: &[u8], len: usize) -> Result<Vec<u8>> {
+    if bytes.len() > len {
+        bail!("Value is longer than {} bytes", len);
+    }
+    let mut padded = vec![0; len - bytes.len()];
+    padded.extend_from_slice(bytes);
+    Ok(padded)
+}
+
+/// Reads the big-endian encodings used by both OpenSSH and OpenPGP.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            bail!("Unexpected end of data");
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into()?))
+    }
+
+    /// An OpenSSH string (RFC 4251).
+    fn string(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+
+    /// An OpenPGP multiprecision integer (RFC 4880).
+    fn mpi(&mut self) -> Result<&'a [u8]> {
+        let bits = self.u16()? as usize;
+        self.bytes((bits + 7) / 8)
+    }
+}
+
+mod ssh {
+    use super::*;
+
+    /// Parse an OpenSSH public key line: `<type> <base64 key> [comment]`.
+    pub(super) fn parse_public_key(line: &str) -> Result<PKey<Public>> {
+        let encoded = line
+            .split_whitespace()
+            .nth(1)
+            .context("Missing SSH public key data")?;
+        let data = base64::decode(encoded).context("Invalid SSH public key")?;
+        let mut reader = Reader(&data);
+        match reader.string()? {
+            b"ssh-ed25519" => Ok(PKey::public_key_from_raw_bytes(reader.string()?, Id::ED25519)?),
+            b"ssh-rsa" => {
+                let e = BigNum::from_slice(reader.string()?)?;
+                let n = BigNum::from_slice(reader.string()?)?;
+                Ok(PKey::from_rsa(Rsa::from_public_components(n, e)?)?)
+            }
+            key_type => bail!(
+                "Unsupported SSH key type '{}'",
+                String::from_utf8_lossy(key_type)
+            ),
+        }
+    }
+
+    fn put_string(buf: &mut Vec<u8>, string: &[u8]) {
+        buf.extend_from_slice(&(string.len() as u32).to_be_bytes());
+        buf.extend_from_slice(string);
+    }
+
+    /// Verify an SSHSIG signature, as made by `ssh-keygen -Y sign`.
+    pub(super) fn verify(keys: &[PKey<Public>], payload: &[u8], data: &[u8]) -> Result<bool> {
+        let mut reader = Reader(data);
+        if reader.bytes(6)? != b"SSHSIG" || reader.u32()? != 1 {
+            bail!("Unsupported SSH signature");
+        }
+        let _public_key = reader.string()?;
+        let namespace = reader.string()?;
+        let reserved = reader.string()?;
+        let hash_algorithm = reader.string()?;
+        let mut signature = Reader(reader.string()?);
+
+        // A signature made for another purpose must not be usable to sign
+        // commits.
+        if namespace != SSH_SIGNATURE_NAMESPACE.as_bytes() {
+            return Ok(false);
+        }
+        let digest = match hash_algorithm {
+            b"sha256" => MessageDigest::sha256(),
+            b"sha512" => MessageDigest::sha512(),
+            _ => bail!("Unsupported SSH signature hash algorithm"),
+        };
+        let mut message = b"SSHSIG".to_vec();
+        put_string(&mut message, namespace);
+        put_string(&mut message, reserved);
+        put_string(&mut message, hash_algorithm);
+        put_string(&mut message, &hash(digest, payload)?);
+
+        let (key_type, digest) = match signature.string()? {
+            b"ssh-ed25519" => (Id::ED25519, None),
+            b"rsa-sha2-256" => (Id::RSA, Some(MessageDigest::sha256())),
+            b"rsa-sha2-512" => (Id::RSA, Some(MessageDigest::sha512())),
+            _ => bail!("Unsupported SSH signature algorithm"),
+        };
+        let signature = signature.string()?;
+        Ok(keys
+            .iter()
+            .filter(|key| key.id() == key_type)
+            .any(|key| verify_with_key(key, digest, &message, signature)))
+    }
+}
+
+mod pgp {
+    use super::*;
+
+    const SIGNATURE_TAG: u8 = 2;
+    const PUBLIC_KEY_TAG: u8 = 6;
+    const PUBLIC_SUBKEY_TAG: u8 = 14;
+
+    const RSA: u8 = 1;
+    const EDDSA: u8 = 22;
+    const ED25519_OID: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xda, 0x47, 0x0f, 0x01];
+
+    /// Split OpenPGP data into its packets, as (tag, body) pairs.
+    fn packets(mut data: &[u8]) -> Result<Vec<(u8, &[u8])>> {
+        let mut packets = Vec::new();
+        while let Some((&header, rest)) = data.split_first() {
+            if header & 0x80 == 0 {
+                bail!("Invalid OpenPGP packet header");
+            }
+            let mut reader = Reader(rest);
+            let (tag, len) = if header & 0x40 != 0 {
+                let len = match reader.u8()? {
+                    len @ 0..=191 => len as usize,
+                    len @ 192..=223 => ((len as usize - 192) << 8) + reader.u8()? as usize + 192,
+                    255 => reader.u32()? as usize,
+                    _ => bail!("Partial OpenPGP packet lengths are not supported"),
+                };
+                (header & 0x3f, len)
+            } else {
+                let len = match header & 0x03 {
+                    0 => reader.u8()? as usize,
+                    1 => reader.u16()? as usize,
+                    2 => reader.u32()? as usize,
+                    _ => reader.0.len(),
+                };
+                ((header >> 2) & 0x0f, len)
+            };
+            packets.push((tag, reader.bytes(len)?));
+            data = reader.0;
+        }
+        Ok(packets)
+    }
+
+    /// Parse the primary key and subkeys of an OpenPGP key block.  Keys of
+    /// unsupported types are skipped, as they don't make the other keys in
+    /// the block unusable.
+    pub(super) fn parse_public_keys(data: &[u8]) -> Result<Vec<PKey<Public>>> {
+        let keys = packets(data)?
+            .into_iter()
+            .filter(|(tag, _)| *tag == PUBLIC_KEY_TAG || *tag == PUBLIC_SUBKEY_TAG)
+            .filter_map(|(_, body)| parse_public_key(body).ok())
+            .collect::<Vec<_>>();
+        if keys.is_empty() {
+            bail!("OpenPGP key block has no keys of a supported type");
+        }
+        Ok(keys)
+    }
+
+    fn parse_public_key(body: &[u8]) -> Result<PKey<Public>> {
+        let mut reader = Reader(body);
+        if reader.u8()? != 4 {
+            bail!("Unsupported OpenPGP key version");
+        }
+        let _creation_time = reader.u32()?;
+        match reader.u8()? {
+            RSA => {
+                let n = BigNum::from_slice(reader.mpi()?)?;
+                let e = BigNum::from_slice(reader.mpi()?)?;
+                Ok(PKey::from_rsa(Rsa::from_public_components(n, e)?)?)
+            }
+            EDDSA => {
+                let oid_len = reader.u8()? as usize;
+                if reader.bytes(oid_len)? != ED25519_OID {
+                    bail!("Unsupported OpenPGP EdDSA curve");
+                }
+                // The point is prefixed with 0x40 to mark it as native.
+                match reader.mpi()?.split_first() {
+                    Some((0x40, point)) => Ok(PKey::public_key_from_raw_bytes(point, Id::ED25519)?),
+                    _ => bail!("Invalid OpenPGP Ed25519 key"),
+                }
+            }
+            algorithm => bail!("Unsupported OpenPGP key algorithm {}", algorithm),
+        }
+    }
+
+    /// Verify a version 4 OpenPGP signature, as made by
+    /// `gpg --detach-sign`.
+    pub(super) fn verify(keys: &[PKey<Public>], payload: &[u8], data: &[u8]) -> Result<bool> {
+        let body = match packets(data)?.as_slice() {
+            [(SIGNATURE_TAG, body)] => *body,
+            _ => bail!("Expected a single OpenPGP signature"),
+        };
+        let mut reader = Reader(body);
+        if reader.u8()? != 4 {
+            bail!("Unsupported OpenPGP signature version");
+        }
+        // Binary and text signatures are the same, as the payload has no
+        // line endings.
+        if !matches!(reader.u8()?, 0x00 | 0x01) {
+            bail!("Not an OpenPGP signature of a document");
+        }
+        let algorithm = reader.u8()?;
+        let digest = match reader.u8()? {
+            8 => MessageDigest::sha256(),
+            9 => MessageDigest::sha384(),
+            10 => MessageDigest::sha512(),
+            hash_algorithm => bail!("Unsupported OpenPGP hash algorithm {}", hash_algorithm),
+        };
+        let hashed_len = reader.u16()? as usize;
+        reader.bytes(hashed_len)?;
+
+        // The signature is over the payload, followed by the signature
+        // packet up to the end of the hashed subpackets and a trailer.
+        let hashed = &body[..body.len() - reader.0.len()];
+        let mut message = payload.to_vec();
+        message.extend_from_slice(hashed);
+        message.extend_from_slice(&[0x04, 0xff]);
+        message.extend_from_slice(&(hashed.len() as u32).to_be_bytes());
+
+        let unhashed_len = reader.u16()? as usize;
+        reader.bytes(unhashed_len)?;
+        let _digest_prefix = reader.bytes(2)?;
+
+        match algorithm {
+            RSA => {
+                let signature = reader.mpi()?;
+                Ok(keys.iter().filter(|key| key.id() == Id::RSA).any(|key| {
+                    // Leading zeros are stripped from the integer, but the
+                    // signature must be as long as the modulus.
+                    left_pad(signature, key.size()).map_or(false, |signature| {
+                        verify_with_key(key, Some(digest), &message, &signature)
+                    })
+                }))
+            }
+            EDDSA => {
+                let mut signature = left_pad(reader.mpi()?, 32)?;
+                signature.extend(left_pad(reader.mpi()?, 32)?);
+                // EdDSA signs the digest of the message, not the message.
+                let message = hash(digest, &message)?;
+                Ok(keys
+                    .iter()
+                    .filter(|key| key.id() == Id::ED25519)
+                    .any(|key| verify_with_key(key, None, &message, &signature)))
+            }
+            _ => bail!("Unsupported OpenPGP signature algorithm {}", algorithm),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+
+    use super::*;
+    use crate::signed_payload;
+
+    // Made with `ssh-keygen -t ed25519` and `ssh-keygen -Y sign -n
+    // mononoke-commit` over `signed_payload(ONES_CSID)`.
+    const SSH_PUBLIC_KEY: &str = "ssh-ed25519 \
+        AAAAC3NzaC1lZDI1NTE5AAAAIMp26qlvOGg9U7RZrySYKbrwvIC9g+3SUQecyl1S7+2P test";
+    const SSH_SIGNATURE: &str = "-----BEGIN SSH SIGNATURE-----
+U1NIU0lHAAAAAQAAADMAAAALc3NoLWVkMjU1MTkAAAAgynbqqW84aD1TtFmvJJgpuvC8gL
+2D7dJRB5zKXVLv7Y8AAAAPbW9ub25va2UtY29tbWl0AAAAAAAAAAZzaGE1MTIAAABTAAAA
+C3NzaC1lZDI1NTE5AAAAQJCUe7fMsSDt5BymiiK1uMULe5uvHlyS3MC084v7494znZGI3q
+2w38Mjc1STSqCQissyKnx5wWd9BWAchXLaoQ8=
+-----END SSH SIGNATURE-----
+";
+
+    // Made with `gpg --quick-gen-key ... ed25519 sign` and
+    // `gpg --detach-sign --armor` over `signed_payload(ONES_CSID)`.
+    const PGP_PUBLIC_KEY: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEatJPrxYJKwYBBAHaRw8BAQdAm8UMqQOH4G03Q122bG8gfr1QpnhulOD4nClJ
+bLCgUJ60F1Rlc3QgPHRlc3RAZXhhbXBsZS5jb20+iJAEExYIADgWIQRrJtH2705U
+8HV8kBF6s8QP0us+0gUCatJPrwIbAwULCQgHAgYVCgkICwIEFgIDAQIeAQIXgAAK
+CRB6s8QP0us+0kluAQCIoQE5dlY47876qjXagMeq10FlaTH2/v+uSZjObOa+JQD/
+TjUME66OuD3FmuhhMH2oYnghw67A8EIKk9yRMZzXgg4=
+=P1qp
+-----END PGP PUBLIC KEY BLOCK-----
+";
+    const PGP_SIGNATURE: &str = "-----BEGIN PGP SIGNATURE-----
+
+iHUEABYIAB0WIQRrJtH2705U8HV8kBF6s8QP0us+0gUCatJPrwAKCRB6s8QP0us+
+0gxzAPwM4FwyTBtkK3K2QI0xPyHDLC8/PASmZ0k2TOk5nNr3jAD9GUK4gzobFOk5
+/yYhviUUgxQOOKq8eYgdmNVS/RbATQM=
+=KoK+
+-----END PGP SIGNATURE-----
+";
+
+    #[test]
+    fn test_ssh_signature() -> Result<()> {
+        let payload = signed_payload(ONES_CSID);
+        let other_payload = signed_payload(TWOS_CSID);
+        let keys = parse_public_keys(SSH_PUBLIC_KEY)?;
+        assert!(verify(&keys, &payload, SSH_SIGNATURE.as_bytes()));
+        assert!(!verify(&keys, &other_payload, SSH_SIGNATURE.as_bytes()));
+
+        let pgp_keys = parse_public_keys(PGP_PUBLIC_KEY)?;
+        assert!(!verify(&pgp_keys, &payload, SSH_SIGNATURE.as_bytes()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_pgp_signature() -> Result<()> {
+        let payload = signed_payload(ONES_CSID);
+        let other_payload = signed_payload(TWOS_CSID);
+        let keys = parse_public_keys(PGP_PUBLIC_KEY)?;
+        assert!(verify(&keys, &payload, PGP_SIGNATURE.as_bytes()));
+        assert!(!verify(&keys, &other_payload, PGP_SIGNATURE.as_bytes()));
+
+        let ssh_keys = parse_public_keys(SSH_PUBLIC_KEY)?;
+        assert!(!verify(&ssh_keys, &payload, PGP_SIGNATURE.as_bytes()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed_envelopes() -> Result<()> {
+        let payload = signed_payload(ONES_CSID);
+        let keys = parse_public_keys(SSH_PUBLIC_KEY)?;
+        let truncated = SSH_SIGNATURE.replace("2w38Mjc1STSqCQissyKnx5wWd9BWAchXLaoQ8=\n", "");
+        assert!(!verify(&keys, &payload, truncated.as_bytes()));
+        assert!(!verify(
+            &keys,
+            &payload,
+            b"-----BEGIN PGP SIGNATURE-----\n!!!\n-----END PGP SIGNATURE-----"
+        ));
+        assert!(parse_public_keys("ssh-dss AAAAB3NzaC1kc3M=").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Commit signatures stores cryptographic signatures over bonsai changesets
+//! and verifies them against the keys a repository trusts.
+//!
+//! A signature is a detached signature, made with the private half of a
+//! key, over the payload returned by `signed_payload` for the changeset.
+//! It is either a raw signature, or an armored SSH signature (made in the
+//! `SSH_SIGNATURE_NAMESPACE` namespace) or OpenPGP signature.
+//! Signatures are stored separately from the changeset itself, so signing
+//! a commit does not change its id.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use context::CoreContext;
+use context::PerfCounterType;
+use mononoke_types::ChangesetId;
+use mononoke_types::RepositoryId;
+use sql_construct::SqlConstruct;
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::mononoke_queries;
+use sql_ext::SqlConnections;
+
+mod envelope;
+mod verify;
+
+pub use crate::envelope::SSH_SIGNATURE_NAMESPACE;
+pub use crate::verify::signed_payload;
+pub use crate::verify::verify_signatures;
+pub use crate::verify::SignatureVerification;
+
+/// A signature over a changeset.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CommitSignature {
+    /// The id of the key that made the signature, as configured in the
+    /// repo's trusted keys.
+    pub key_id: String,
+    /// The raw signature bytes.
+    pub signature: Vec<u8>,
+}
+
+#[facet::facet]
+#[async_trait]
+pub trait CommitSignatures: Send + Sync {
+    /// Store a signature for a changeset.  A changeset may be signed by
+    /// several keys; storing a second signature from the same key replaces
+    /// the first.
+    async fn add_signature(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+        signature: CommitSignature,
+    ) -> Result<()>;
+
+    /// Get all signatures stored for a changeset, ordered by key id.
+    async fn get_signatures(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<Vec<CommitSignature>>;
+}
+
+mononoke_queries! {
+    write AddSignature(
+        repo_id: RepositoryId,
+        cs_id: ChangesetId,
+        key_id: &str,
+        signature: &[u8],
+    ) {
+        none,
+        mysql(
+            "REPLACE INTO commit_signatures (repo_id, cs_id, key_id, signature)
+             VALUES ({repo_id}, {cs_id}, {key_id}, {signature})"
+        )
+        sqlite(
+            "REPLACE INTO commit_signatures (repo_id, cs_id, key_id, signature)
+             VALUES ({repo_id}, {cs_id}, CAST({key_id} AS TEXT), {signature})"
+        )
+    }
+
+    read GetSignatures(
+        repo_id: RepositoryId,
+        cs_id: ChangesetId,
+    ) -> (String, Vec<u8>) {
+        "SELECT key_id, signature
+         FROM commit_signatures
+         WHERE repo_id = {repo_id} AND cs_id = {cs_id}
+         ORDER BY key_id"
+    }
+}
+
+pub struct SqlCommitSignatures {
+    repo_id: RepositoryId,
+    connections: SqlConnections,
+}
+
+pub struct SqlCommitSignaturesBuilder {
+    connections: SqlConnections,
+}
+
+impl SqlConstruct for SqlCommitSignaturesBuilder {
+    const LABEL: &'static str = "commit_signatures";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-commit-signatures.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self { connections }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SqlCommitSignaturesBuilder {}
+
+impl SqlCommitSignaturesBuilder {
+    pub fn build(self, repo_id: RepositoryId) -> SqlCommitSignatures {
+        SqlCommitSignatures {
+            repo_id,
+            connections: self.connections,
+        }
+    }
+}
+
+#[async_trait]
+impl CommitSignatures for SqlCommitSignatures {
+    async fn add_signature(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+        signature: CommitSignature,
+    ) -> Result<()> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        AddSignature::query(
+            &self.connections.write_connection,
+            &self.repo_id,
+            &cs_id,
+            &signature.key_id.as_str(),
+            &signature.signature.as_slice(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn get_signatures(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<Vec<CommitSignature>> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsMaster);
+        let rows = GetSignatures::query(
+            &self.connections.read_master_connection,
+            &self.repo_id,
+            &cs_id,
+        )
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(key_id, signature)| CommitSignature { key_id, signature })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+    use mononoke_types_mocks::repo::REPO_ONE;
+    use mononoke_types_mocks::repo::REPO_ZERO;
+
+    use super::*;
+
+    fn signature(key_id: &str, signature: &[u8]) -> CommitSignature {
+        CommitSignature {
+            key_id: key_id.to_string(),
+            signature: signature.to_vec(),
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_add_and_get(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let builder = SqlCommitSignaturesBuilder::with_sqlite_in_memory()?;
+        let connections = builder.connections.clone();
+        let store = builder.build(REPO_ZERO);
+        let other_store =
+            SqlCommitSignaturesBuilder::from_sql_connections(connections).build(REPO_ONE);
+
+        store
+            .add_signature(&ctx, ONES_CSID, signature("bob", b"sig1"))
+            .await?;
+        store
+            .add_signature(&ctx, ONES_CSID, signature("alice", b"sig2"))
+            .await?;
+        assert_eq!(
+            store.get_signatures(&ctx, ONES_CSID).await?,
+            vec![signature("alice", b"sig2"), signature("bob", b"sig1")]
+        );
+
+        // Signing again with the same key replaces the signature.
+        store
+            .add_signature(&ctx, ONES_CSID, signature("bob", b"sig3"))
+            .await?;
+        assert_eq!(
+            store.get_signatures(&ctx, ONES_CSID).await?,
+            vec![signature("alice", b"sig2"), signature("bob", b"sig3")]
+        );
+
+        assert!(store.get_signatures(&ctx, TWOS_CSID).await?.is_empty());
+        assert!(
+            other_store
+                .get_signatures(&ctx, ONES_CSID)
+                .await?
+                .is_empty()
+        );
+        Ok(())
+    }
+}
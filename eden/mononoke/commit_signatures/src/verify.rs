@@ -0,0 +1,162 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Context;
+use anyhow::Result;
+use metaconfig_types::CommitSigningConfig;
+use mononoke_types::ChangesetId;
+
+use crate::envelope;
+use crate::CommitSignature;
+
+/// The result of verifying the signatures of a changeset.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SignatureVerification {
+    /// The changeset has no signatures.
+    Unsigned,
+    /// The changeset has a valid signature from the trusted key with this id.
+    Verified { key_id: String },
+    /// The changeset is signed, but only by keys the repo doesn't trust.
+    Untrusted,
+    /// The changeset has a signature from the trusted key with this id, but
+    /// the signature doesn't match.
+    Invalid { key_id: String },
+}
+
+impl SignatureVerification {
+    pub fn is_verified(&self) -> bool {
+        matches!(self, Self::Verified { .. })
+    }
+}
+
+/// The payload that is signed to sign a changeset.
+pub fn signed_payload(cs_id: ChangesetId) -> Vec<u8> {
+    format!("mononoke-commit-signature:{}", cs_id).into_bytes()
+}
+
+fn verify_signature(trusted_key: &str, payload: &[u8], signature: &[u8]) -> Result<bool> {
+    let keys = envelope::parse_public_keys(trusted_key).context("Invalid trusted key")?;
+    Ok(envelope::verify(&keys, payload, signature))
+}
+
+/// Verify the signatures of a changeset against the keys trusted by the
+/// repo.  A single valid signature from a trusted key is enough for the
+/// changeset to be verified.  Errors are only returned if a trusted key is
+/// invalid.
+pub fn verify_signatures(
+    config: &CommitSigningConfig,
+    cs_id: ChangesetId,
+    signatures: &[CommitSignature],
+) -> Result<SignatureVerification> {
+    if signatures.is_empty() {
+        return Ok(SignatureVerification::Unsigned);
+    }
+    let payload = signed_payload(cs_id);
+    let mut invalid = None;
+    for signature in signatures {
+        if let Some(trusted_key) = config.trusted_keys.get(&signature.key_id) {
+            let valid = verify_signature(trusted_key, &payload, &signature.signature)
+                .with_context(|| format!("Failed to verify with key '{}'", signature.key_id))?;
+            if valid {
+                return Ok(SignatureVerification::Verified {
+                    key_id: signature.key_id.clone(),
+                });
+            }
+            invalid.get_or_insert_with(|| signature.key_id.clone());
+        }
+    }
+    Ok(match invalid {
+        Some(key_id) => SignatureVerification::Invalid { key_id },
+        None => SignatureVerification::Untrusted,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use maplit::hashmap;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+    use openssl::pkey::PKey;
+    use openssl::pkey::Private;
+    use openssl::sign::Signer;
+
+    use super::*;
+
+    fn sign(key: &PKey<Private>, cs_id: ChangesetId) -> Vec<u8> {
+        let mut signer = Signer::new_without_digest(key).unwrap();
+        signer.sign_oneshot_to_vec(&signed_payload(cs_id)).unwrap()
+    }
+
+    fn signature(key_id: &str, signature: Vec<u8>) -> CommitSignature {
+        CommitSignature {
+            key_id: key_id.to_string(),
+            signature,
+        }
+    }
+
+    #[test]
+    fn test_verify_signatures() -> Result<()> {
+        let trusted = PKey::generate_ed25519()?;
+        let untrusted = PKey::generate_ed25519()?;
+        let config = CommitSigningConfig {
+            trusted_keys: hashmap! {
+                "trusted".to_string() =>
+                    String::from_utf8(trusted.public_key_to_pem()?)?,
+            },
+        };
+
+        assert_eq!(
+            verify_signatures(&config, ONES_CSID, &[])?,
+            SignatureVerification::Unsigned
+        );
+        assert_eq!(
+            verify_signatures(
+                &config,
+                ONES_CSID,
+                &[signature("trusted", sign(&trusted, ONES_CSID))]
+            )?,
+            SignatureVerification::Verified {
+                key_id: "trusted".to_string()
+            }
+        );
+        assert_eq!(
+            verify_signatures(
+                &config,
+                ONES_CSID,
+                &[signature("other", sign(&untrusted, ONES_CSID))]
+            )?,
+            SignatureVerification::Untrusted
+        );
+
+        // A signature for a different changeset, or by a different key
+        // claiming to be trusted, is invalid.
+        assert_eq!(
+            verify_signatures(
+                &config,
+                ONES_CSID,
+                &[signature("trusted", sign(&trusted, TWOS_CSID))]
+            )?,
+            SignatureVerification::Invalid {
+                key_id: "trusted".to_string()
+            }
+        );
+        assert_eq!(
+            verify_signatures(
+                &config,
+                ONES_CSID,
+                &[
+                    signature("trusted", sign(&untrusted, ONES_CSID)),
+                    signature("other", sign(&untrusted, ONES_CSID)),
+                ]
+            )?,
+            SignatureVerification::Invalid {
+                key_id: "trusted".to_string()
+            }
+        );
+        Ok(())
+    }
+}
@@ -85,6 +85,10 @@ pub struct AllocationStats {
 }
 
 impl AllocationStats {
+    pub fn allocated(&self) -> u64 {
+        self.allocated
+    }
+
     pub fn delta(&self) -> Result<i64, Error> {
         let allocated: i64 = self.allocated.try_into()?;
         let freed: i64 = self.freed.try_into()?;
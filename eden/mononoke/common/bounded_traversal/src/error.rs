@@ -15,6 +15,8 @@ pub enum BoundedTraversalError {
         file: &'static str,
         line: u32,
     },
+    #[error("Traversal was cancelled")]
+    Cancelled,
 }
 
 macro_rules! programming_error {
@@ -6,6 +6,7 @@
  */
 
 //! Read the documentation of [bounded_traversal](crate::bounded_traversal),
+//! [bounded_traversal_limited](crate::bounded_traversal_limited),
 //! [bounded_traversal_dag](crate::bounded_traversal_dag) and
 //! [bounded_traversal_stream](crate::bounded_traversal_stream)
 
@@ -15,6 +16,11 @@ pub use error::BoundedTraversalError;
 
 mod tree;
 pub use tree::bounded_traversal;
+pub use tree::bounded_traversal_limited;
+
+mod limits;
+pub use limits::TraversalLimits;
+pub use limits::TraversalStats;
 
 mod dag;
 pub use dag::bounded_traversal_dag;
@@ -0,0 +1,105 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+/// Limits, cancellation and instrumentation for
+/// [bounded_traversal_limited](crate::bounded_traversal_limited).
+///
+/// By default only the number of concurrently scheduled jobs is limited.
+pub struct TraversalLimits<'a, In> {
+    pub(crate) scheduled_max: usize,
+    pub(crate) weight_max: Option<u64>,
+    pub(crate) weight: Option<Box<dyn Fn(&In) -> u64 + Send + 'a>>,
+    pub(crate) is_cancelled: Option<Box<dyn Fn() -> bool + Send + 'a>>,
+    pub(crate) stats: Option<Arc<TraversalStats>>,
+}
+
+impl<'a, In> TraversalLimits<'a, In> {
+    /// Allow at most `scheduled_max` unfold and fold jobs to run at once.
+    pub fn new(scheduled_max: usize) -> Self {
+        Self {
+            scheduled_max,
+            weight_max: None,
+            weight: None,
+            is_cancelled: None,
+            stats: None,
+        }
+    }
+
+    /// Additionally limit the total weight of concurrently running unfolds.
+    /// `weight` estimates the cost of unfolding a node, e.g. the number of
+    /// bytes it will fetch.  A single unfold heavier than `weight_max` is
+    /// still run, but only on its own.
+    pub fn with_weight_limit(
+        mut self,
+        weight_max: u64,
+        weight: impl Fn(&In) -> u64 + Send + 'a,
+    ) -> Self {
+        self.weight_max = Some(weight_max);
+        self.weight = Some(Box::new(weight));
+        self
+    }
+
+    /// Stop the traversal with `BoundedTraversalError::Cancelled` once
+    /// `is_cancelled` returns true.  It is checked before scheduling new
+    /// jobs, so jobs that are already running are allowed to finish.
+    pub fn with_cancellation(mut self, is_cancelled: impl Fn() -> bool + Send + 'a) -> Self {
+        self.is_cancelled = Some(Box::new(is_cancelled));
+        self
+    }
+
+    /// Record statistics about the traversal in `stats`.
+    pub fn with_stats(mut self, stats: Arc<TraversalStats>) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+}
+
+/// Statistics collected by a traversal with
+/// [TraversalLimits::with_stats].
+#[derive(Debug, Default)]
+pub struct TraversalStats {
+    unfolds: AtomicU64,
+    folds: AtomicU64,
+    max_scheduled: AtomicU64,
+    max_weight: AtomicU64,
+}
+
+impl TraversalStats {
+    /// Number of unfold jobs run.
+    pub fn unfolds(&self) -> u64 {
+        self.unfolds.load(Ordering::Relaxed)
+    }
+
+    /// Number of fold jobs run.
+    pub fn folds(&self) -> u64 {
+        self.folds.load(Ordering::Relaxed)
+    }
+
+    /// Highest number of jobs that were running at once.
+    pub fn max_scheduled(&self) -> u64 {
+        self.max_scheduled.load(Ordering::Relaxed)
+    }
+
+    /// Highest total weight of unfolds that were running at once.
+    pub fn max_weight(&self) -> u64 {
+        self.max_weight.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_scheduled(&self, is_unfold: bool, scheduled: usize, weight: u64) {
+        if is_unfold {
+            self.unfolds.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.folds.fetch_add(1, Ordering::Relaxed);
+        }
+        self.max_scheduled.fetch_max(scheduled as u64, Ordering::Relaxed);
+        self.max_weight.fetch_max(weight, Ordering::Relaxed);
+    }
+}
@@ -6,6 +6,9 @@
  */
 
 use std::collections::BTreeSet;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
 use anyhow::Error;
 use cloned::cloned;
@@ -20,8 +23,12 @@ use super::utils::StateLog;
 use super::utils::Tick;
 use crate::bounded_traversal;
 use crate::bounded_traversal_dag;
+use crate::bounded_traversal_limited;
 use crate::bounded_traversal_stream;
 use crate::limited_by_key_shardable;
+use crate::BoundedTraversalError;
+use crate::TraversalLimits;
+use crate::TraversalStats;
 
 // Tree for test purposes
 #[derive(Debug)]
@@ -128,6 +135,71 @@ async fn test_bounded_traversal() -> Result<(), Error> {
     Ok(())
 }
 
+fn traverse_limited(
+    limits: TraversalLimits<'static, Tree>,
+) -> impl std::future::Future<Output = Result<String, Error>> {
+    bounded_traversal_limited(
+        limits,
+        build_tree(),
+        |Tree { id, children }| {
+            async move {
+                yield_now().await;
+                Ok::<_, Error>((id, children))
+            }
+            .boxed()
+        },
+        |id, children| {
+            async move { Ok::<_, Error>(id.to_string() + &children.collect::<String>()) }.boxed()
+        },
+    )
+}
+
+#[tokio::test]
+async fn test_bounded_traversal_limited() -> Result<(), Error> {
+    // Each unfold weighs as much as its id, so with a limit of 5 the
+    // unfolds of 4 and 5 can't run together.
+    let stats = Arc::new(TraversalStats::default());
+    let limits = TraversalLimits::new(10)
+        .with_weight_limit(5, |tree: &Tree| tree.id as u64)
+        .with_stats(stats.clone());
+    assert_eq!(traverse_limited(limits).await?, "015234");
+    assert_eq!(stats.unfolds(), 6);
+    assert_eq!(stats.folds(), 6);
+    assert!(stats.max_weight() <= 5);
+
+    // A node heavier than the limit still runs on its own.
+    let stats = Arc::new(TraversalStats::default());
+    let limits = TraversalLimits::new(10)
+        .with_weight_limit(1, |tree: &Tree| tree.id as u64)
+        .with_stats(stats.clone());
+    assert_eq!(traverse_limited(limits).await?, "015234");
+    assert_eq!(stats.max_weight(), 5);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bounded_traversal_cancelled() -> Result<(), Error> {
+    let cancelled = Arc::new(AtomicBool::new(true));
+    let limits = TraversalLimits::new(2).with_cancellation({
+        let cancelled = cancelled.clone();
+        move || cancelled.load(Ordering::Relaxed)
+    });
+    let error = traverse_limited(limits)
+        .await
+        .expect_err("traversal should be cancelled");
+    assert!(matches!(
+        error.downcast_ref::<BoundedTraversalError>(),
+        Some(BoundedTraversalError::Cancelled)
+    ));
+
+    cancelled.store(false, Ordering::Relaxed);
+    let limits =
+        TraversalLimits::new(2).with_cancellation(move || cancelled.load(Ordering::Relaxed));
+    assert_eq!(traverse_limited(limits).await?, "015234");
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_bounded_traversal_dag() -> Result<(), Error> {
     // dag
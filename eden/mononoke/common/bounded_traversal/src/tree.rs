@@ -9,6 +9,7 @@ use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::Context;
 use std::task::Poll;
 
@@ -23,6 +24,9 @@ use futures::stream::FuturesUnordered;
 use futures::stream::StreamExt;
 
 use super::common::Either2;
+use super::limits::TraversalLimits;
+use super::limits::TraversalStats;
+use super::BoundedTraversalError;
 use super::Iter;
 
 /// `bounded_traversal` traverses implicit asynchronous tree specified by `init`
@@ -65,7 +69,59 @@ where
     Ins: IntoIterator<Item = In> + 'caller,
     Fold: FnMut(OutCtx, Iter<Out>) -> BoxFuture<'caller, Result<Out, Err>> + 'caller,
 {
-    BoundedTraversal::new(scheduled_max, init, unfold, fold)
+    BoundedTraversal::new(
+        scheduled_max,
+        None,
+        |_: &In| 0,
+        || Ok(()),
+        None,
+        init,
+        unfold,
+        fold,
+    )
+}
+
+/// Like [bounded_traversal], but with the additional limits, cancellation and
+/// instrumentation described by `limits`.
+///
+/// If the traversal is cancelled, it fails with
+/// `BoundedTraversalError::Cancelled`.
+pub fn bounded_traversal_limited<'caller, Err, In, Ins, Out, OutCtx, Unfold, Fold>(
+    limits: TraversalLimits<'caller, In>,
+    init: In,
+    unfold: Unfold,
+    fold: Fold,
+) -> impl Future<Output = Result<Out, Err>> + 'caller
+where
+    Err: From<BoundedTraversalError> + 'caller,
+    In: 'caller,
+    Ins: 'caller,
+    Out: 'caller,
+    OutCtx: 'caller,
+    Unfold: FnMut(In) -> BoxFuture<'caller, Result<(OutCtx, Ins), Err>> + 'caller,
+    Ins: IntoIterator<Item = In> + 'caller,
+    Fold: FnMut(OutCtx, Iter<Out>) -> BoxFuture<'caller, Result<Out, Err>> + 'caller,
+{
+    let TraversalLimits {
+        scheduled_max,
+        weight_max,
+        weight,
+        is_cancelled,
+        stats,
+    } = limits;
+    BoundedTraversal::new(
+        scheduled_max,
+        weight_max,
+        move |value: &In| weight.as_ref().map_or(0, |weight| weight(value)),
+        move || match &is_cancelled {
+            Some(is_cancelled) if is_cancelled() => Err(BoundedTraversalError::Cancelled.into()),
+            _ => Ok(()),
+        },
+        stats,
+        init,
+        unfold,
+        fold,
+    )
 }
 
 // execution tree node
@@ -80,35 +136,60 @@ struct Node<Out, OutCtx> {
 struct NodeIndex(usize);
 type NodeLocation = super::common::NodeLocation<NodeIndex>;
 
+// scheduled job, along with the location of its node and its weight
+type Job<UFut, FFut> = Join<Ready<(NodeLocation, u64)>, Either2<UFut, FFut>>;
+
 #[must_use = "futures do nothing unless polled"]
-struct BoundedTraversal<Out, OutCtx, Unfold, UFut, Fold, FFut>
+struct BoundedTraversal<Out, OutCtx, Unfold, UFut, Fold, FFut, Weight, Cancel>
 where
     UFut: Future,
     FFut: Future,
 {
     unfold: Unfold,
     fold: Fold,
+    weight: Weight,
+    check_cancelled: Cancel,
+    stats: Option<Arc<TraversalStats>>,
     scheduled_max: usize,
-    scheduled: FuturesUnordered<Join<Ready<NodeLocation>, Either2<UFut, FFut>>>, // jobs being executed
-    unscheduled: VecDeque<Join<Ready<NodeLocation>, Either2<UFut, FFut>>>, // as of yet unscheduled jobs
+    scheduled_weight: u64,                        // total weight of scheduled jobs
+    weight_max: Option<u64>,                      // limit on scheduled_weight
+    scheduled: FuturesUnordered<Job<UFut, FFut>>, // jobs being executed
+    // as of yet unscheduled jobs, with whether they are unfolds and their weight
+    unscheduled: VecDeque<(bool, u64, Job<UFut, FFut>)>,
     execution_tree: HashMap<NodeIndex, Node<Out, OutCtx>>, // tree tracking execution process
     execution_tree_index: NodeIndex,                       // last allocated node index
 }
 
-impl<Err, In, Ins, Out, OutCtx, Unfold, UFut, Fold, FFut>
-    BoundedTraversal<Out, OutCtx, Unfold, UFut, Fold, FFut>
+impl<Err, In, Ins, Out, OutCtx, Unfold, UFut, Fold, FFut, Weight, Cancel>
+    BoundedTraversal<Out, OutCtx, Unfold, UFut, Fold, FFut, Weight, Cancel>
 where
     Unfold: FnMut(In) -> UFut,
     UFut: Future<Output = Result<(OutCtx, Ins), Err>>,
     Ins: IntoIterator<Item = In>,
     Fold: FnMut(OutCtx, Iter<Out>) -> FFut,
     FFut: Future<Output = Result<Out, Err>>,
+    Weight: Fn(&In) -> u64,
+    Cancel: FnMut() -> Result<(), Err>,
 {
-    fn new(scheduled_max: usize, init: In, unfold: Unfold, fold: Fold) -> Self {
+    fn new(
+        scheduled_max: usize,
+        weight_max: Option<u64>,
+        weight: Weight,
+        check_cancelled: Cancel,
+        stats: Option<Arc<TraversalStats>>,
+        init: In,
+        unfold: Unfold,
+        fold: Fold,
+    ) -> Self {
         let mut this = Self {
             unfold,
             fold,
+            weight,
+            check_cancelled,
+            stats,
             scheduled_max,
+            scheduled_weight: 0,
+            weight_max,
             scheduled: FuturesUnordered::new(),
             unscheduled: VecDeque::new(),
             execution_tree: HashMap::new(),
@@ -125,16 +206,42 @@ where
     }
 
     fn enqueue_unfold(&mut self, parent: NodeLocation, value: In) {
-        let fut = join(ready(parent), Either2::Left((self.unfold)(value)));
-        self.unscheduled.push_front(fut);
+        let weight = (self.weight)(&value);
+        let fut = join(ready((parent, weight)), Either2::Left((self.unfold)(value)));
+        self.unscheduled.push_front((true, weight, fut));
     }
 
     fn enqueue_fold(&mut self, parent: NodeLocation, context: OutCtx, children: Iter<Out>) {
         let fut = join(
-            ready(parent),
+            ready((parent, 0)),
             Either2::Right((self.fold)(context, children)),
         );
-        self.unscheduled.push_front(fut);
+        self.unscheduled.push_front((false, 0, fut));
+    }
+
+    /// Move jobs from unscheduled to scheduled while both the job count and
+    /// the weight limits allow it.  A job is always scheduled if nothing
+    /// else is running, so a single overweight job can't stall the
+    /// traversal.
+    fn schedule(&mut self) {
+        while self.scheduled.len() < self.scheduled_max {
+            let weight = match self.unscheduled.front() {
+                Some((_, weight, _)) => *weight,
+                None => break,
+            };
+            if let Some(weight_max) = self.weight_max {
+                if !self.scheduled.is_empty() && self.scheduled_weight + weight > weight_max {
+                    break;
+                }
+            }
+            if let Some((is_unfold, weight, job)) = self.unscheduled.pop_front() {
+                self.scheduled.push(job);
+                self.scheduled_weight += weight;
+                if let Some(stats) = &self.stats {
+                    stats.record_scheduled(is_unfold, self.scheduled.len(), self.scheduled_weight);
+                }
+            }
+        }
     }
 
     fn process_unfold(&mut self, parent: NodeLocation, (context, children): (OutCtx, Ins)) {
@@ -199,35 +306,37 @@ where
     }
 }
 
-impl<Err, In, Ins, Out, OutCtx, Unfold, UFut, Fold, FFut> Future
-    for BoundedTraversal<Out, OutCtx, Unfold, UFut, Fold, FFut>
+impl<Err, In, Ins, Out, OutCtx, Unfold, UFut, Fold, FFut, Weight, Cancel> Future
+    for BoundedTraversal<Out, OutCtx, Unfold, UFut, Fold, FFut, Weight, Cancel>
 where
     Unfold: FnMut(In) -> UFut,
     UFut: Future<Output = Result<(OutCtx, Ins), Err>>,
     Ins: IntoIterator<Item = In>,
     Fold: FnMut(OutCtx, Iter<Out>) -> FFut,
     FFut: Future<Output = Result<Out, Err>>,
+    Weight: Fn(&In) -> u64,
+    Cancel: FnMut() -> Result<(), Err>,
 {
     type Output = Result<Out, Err>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
         let this = unsafe { self.get_unchecked_mut() };
         loop {
-            // schedule as many jobs as possible
-            for job in this.unscheduled.drain(
-                ..std::cmp::min(
-                    this.unscheduled.len(),
-                    this.scheduled_max - this.scheduled.len(),
-                ),
-            ) {
-                this.scheduled.push(job);
+            // stop before starting any new work if we have been cancelled
+            if !this.unscheduled.is_empty() {
+                (this.check_cancelled)()?;
             }
 
+            // schedule as many jobs as possible
+            this.schedule();
+
             // execute scheduled until it is blocked or done
             if let Some(job_result) = ready!(this.scheduled.poll_next_unpin(cx)) {
-                match job_result {
-                    (value, Either::Left(result)) => this.process_unfold(value, result?),
-                    (value, Either::Right(result)) => {
+                let ((value, weight), result) = job_result;
+                this.scheduled_weight -= weight;
+                match result {
+                    Either::Left(result) => this.process_unfold(value, result?),
+                    Either::Right(result) => {
                         // `0` is special index which means whole tree have been executed
                         if value.node_index == NodeIndex(0) {
                             // all jobs have to be completed and execution_tree empty
@@ -0,0 +1,411 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Authentication of clients by the subject alternative names of their TLS
+//! client certificate, or by JSON web tokens signed by a configured issuer.
+
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use metaconfig_types::AuthenticationConfig;
+use metaconfig_types::CertIdentityMapping;
+use metaconfig_types::JwtIssuer;
+use metaconfig_types::SubjectAltNameType;
+use openssl::bn::BigNum;
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::MessageDigest;
+use openssl::pkey::Id;
+use openssl::pkey::PKey;
+use openssl::pkey::Public;
+use openssl::sign::Verifier;
+use openssl::x509::X509;
+use permission_checker::MononokeIdentity;
+use permission_checker::MononokeIdentitySet;
+use serde_json::Value;
+
+/// Allowed clock skew when checking the validity period of tokens.
+const CLOCK_SKEW_SECS: u64 = 60;
+
+struct TrustedIssuer {
+    config: JwtIssuer,
+    key: PKey<Public>,
+}
+
+/// Maps client credentials to the identities used in ACLs and hooks.
+pub struct ClientAuthenticator {
+    cert_identities: Vec<CertIdentityMapping>,
+    issuers: Vec<TrustedIssuer>,
+}
+
+impl ClientAuthenticator {
+    pub fn new(config: &AuthenticationConfig) -> Result<Self> {
+        let issuers = config
+            .jwt_issuers
+            .iter()
+            .map(|issuer| {
+                let key = PKey::public_key_from_pem(issuer.public_key.as_bytes())
+                    .with_context(|| format!("Invalid public key for issuer '{}'", issuer.issuer))?;
+                Ok(TrustedIssuer {
+                    config: issuer.clone(),
+                    key,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            cert_identities: config.cert_identities.clone(),
+            issuers,
+        })
+    }
+
+    /// Identities of a client authenticated with a TLS client certificate:
+    /// the subject name of the certificate, plus an identity for each
+    /// subject alternative name matched by the configured mappings.
+    pub fn identities_from_certificate(&self, cert: &X509) -> Result<MononokeIdentitySet> {
+        let mut identities = MononokeIdentity::try_from_x509(cert)?;
+        if self.cert_identities.is_empty() {
+            return Ok(identities);
+        }
+        for name in cert.subject_alt_names().into_iter().flatten() {
+            for mapping in &self.cert_identities {
+                let value = match mapping.san_type {
+                    SubjectAltNameType::Dns => name.dnsname(),
+                    SubjectAltNameType::Uri => name.uri(),
+                    SubjectAltNameType::Email => name.email(),
+                };
+                if let Some(data) = value.and_then(|value| value.strip_prefix(&mapping.prefix)) {
+                    if !data.is_empty() {
+                        identities.insert(MononokeIdentity::new(&mapping.identity_type, data));
+                    }
+                }
+            }
+        }
+        Ok(identities)
+    }
+
+    /// Identities of a client authenticated with a signed JSON web token.
+    /// The token must be signed by a configured issuer, be within its
+    /// validity period, and provide at least one identity.
+    pub fn identities_from_jwt(&self, token: &str) -> Result<MononokeIdentitySet> {
+        let mut parts = token.split('.');
+        let (header, claims, signature) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(header), Some(claims), Some(signature)) if parts.next().is_none() => {
+                (header, claims, signature)
+            }
+            _ => bail!("Malformed token"),
+        };
+        let header_json = decode_json(header).context("Malformed token header")?;
+        let claims_json = decode_json(claims).context("Malformed token claims")?;
+
+        let issuer_name = claims_json
+            .get("iss")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("Token has no issuer"))?;
+        let issuer = self
+            .issuers
+            .iter()
+            .find(|issuer| issuer.config.issuer == issuer_name)
+            .ok_or_else(|| anyhow!("Token issuer '{}' is not trusted", issuer_name))?;
+
+        let alg = header_json
+            .get("alg")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("Token has no algorithm"))?;
+        let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD)
+            .context("Malformed token signature")?;
+        let signed = &token[..header.len() + 1 + claims.len()];
+        if !verify_signature(&issuer.key, alg, signed.as_bytes(), &signature)? {
+            bail!("Token signature is invalid");
+        }
+
+        check_claims(&issuer.config, &claims_json)?;
+
+        let mut identities = MononokeIdentitySet::new();
+        for (claim, identity_type) in &issuer.config.claim_identities {
+            match claims_json.get(claim) {
+                Some(Value::String(data)) => {
+                    identities.insert(MononokeIdentity::new(identity_type, data));
+                }
+                Some(Value::Array(values)) => {
+                    for data in values.iter().filter_map(Value::as_str) {
+                        identities.insert(MononokeIdentity::new(identity_type, data));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if identities.is_empty() {
+            bail!("Token provides no identities");
+        }
+        Ok(identities)
+    }
+}
+
+fn decode_json(part: &str) -> Result<Value> {
+    let bytes = base64::decode_config(part, base64::URL_SAFE_NO_PAD)?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+fn verify_signature(
+    key: &PKey<Public>,
+    alg: &str,
+    signed: &[u8],
+    signature: &[u8],
+) -> Result<bool> {
+    match (alg, key.id()) {
+        ("RS256", Id::RSA) => {
+            let mut verifier = Verifier::new(MessageDigest::sha256(), key)?;
+            Ok(verifier.verify_oneshot(signature, signed).unwrap_or(false))
+        }
+        ("ES256", Id::EC) => {
+            // JWS encodes ECDSA signatures as the concatenation of r and s,
+            // rather than the DER encoding openssl expects.
+            if signature.len() != 64 {
+                return Ok(false);
+            }
+            let r = BigNum::from_slice(&signature[..32])?;
+            let s = BigNum::from_slice(&signature[32..])?;
+            let der = EcdsaSig::from_private_components(r, s)?.to_der()?;
+            let mut verifier = Verifier::new(MessageDigest::sha256(), key)?;
+            Ok(verifier.verify_oneshot(&der, signed).unwrap_or(false))
+        }
+        ("EdDSA", Id::ED25519) => {
+            let mut verifier = Verifier::new_without_digest(key)?;
+            Ok(verifier.verify_oneshot(signature, signed).unwrap_or(false))
+        }
+        _ => bail!("Token algorithm '{}' does not match the issuer's key", alg),
+    }
+}
+
+fn check_claims(issuer: &JwtIssuer, claims: &Value) -> Result<()> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let expiry = claims
+        .get("exp")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| anyhow!("Token has no expiry"))?;
+    if expiry + CLOCK_SKEW_SECS < now {
+        bail!("Token has expired");
+    }
+    if let Some(not_before) = claims.get("nbf").and_then(Value::as_u64) {
+        if not_before > now + CLOCK_SKEW_SECS {
+            bail!("Token is not yet valid");
+        }
+    }
+    if let Some(audience) = &issuer.audience {
+        let matches = match claims.get("aud") {
+            Some(Value::String(aud)) => aud == audience,
+            Some(Value::Array(auds)) => auds.iter().any(|aud| aud.as_str() == Some(audience)),
+            _ => false,
+        };
+        if !matches {
+            bail!("Token is not intended for this audience");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use maplit::hashmap;
+    use openssl::asn1::Asn1Time;
+    use openssl::ec::EcGroup;
+    use openssl::ec::EcKey;
+    use openssl::nid::Nid;
+    use openssl::pkey::Private;
+    use openssl::sign::Signer;
+    use openssl::x509::extension::SubjectAlternativeName;
+    use openssl::x509::X509Builder;
+    use openssl::x509::X509NameBuilder;
+    use serde_json::json;
+
+    use super::*;
+
+    fn encode(value: &Value) -> String {
+        base64::encode_config(value.to_string(), base64::URL_SAFE_NO_PAD)
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn ed25519_token(key: &PKey<Private>, claims: Value) -> String {
+        let signed = format!("{}.{}", encode(&json!({"alg": "EdDSA"})), encode(&claims));
+        let mut signer = Signer::new_without_digest(key).unwrap();
+        let signature = signer.sign_oneshot_to_vec(signed.as_bytes()).unwrap();
+        format!(
+            "{}.{}",
+            signed,
+            base64::encode_config(signature, base64::URL_SAFE_NO_PAD)
+        )
+    }
+
+    fn es256_token(key: &PKey<Private>, claims: Value) -> String {
+        let signed = format!("{}.{}", encode(&json!({"alg": "ES256"})), encode(&claims));
+        let mut signer = Signer::new(MessageDigest::sha256(), key).unwrap();
+        let der = signer.sign_oneshot_to_vec(signed.as_bytes()).unwrap();
+        let sig = EcdsaSig::from_der(&der).unwrap();
+        let mut signature = sig.r().to_vec_padded(32).unwrap();
+        signature.extend(sig.s().to_vec_padded(32).unwrap());
+        format!(
+            "{}.{}",
+            signed,
+            base64::encode_config(signature, base64::URL_SAFE_NO_PAD)
+        )
+    }
+
+    fn issuer(name: &str, key: &PKey<Private>, audience: Option<&str>) -> JwtIssuer {
+        JwtIssuer {
+            issuer: name.to_string(),
+            public_key: String::from_utf8(key.public_key_to_pem().unwrap()).unwrap(),
+            audience: audience.map(String::from),
+            claim_identities: hashmap! {
+                "sub".to_string() => "USER".to_string(),
+                "groups".to_string() => "GROUP".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_jwt_identities() -> Result<()> {
+        let key = PKey::generate_ed25519()?;
+        let other_key = PKey::generate_ed25519()?;
+        let authenticator = ClientAuthenticator::new(&AuthenticationConfig {
+            cert_identities: vec![],
+            jwt_issuers: vec![issuer("issuer", &key, Some("mononoke"))],
+        })?;
+
+        let token = ed25519_token(
+            &key,
+            json!({
+                "iss": "issuer",
+                "aud": ["other", "mononoke"],
+                "exp": now() + 3600,
+                "sub": "alice",
+                "groups": ["eng", "oncall"],
+            }),
+        );
+        let identities = authenticator.identities_from_jwt(&token)?;
+        let expected: MononokeIdentitySet = [
+            MononokeIdentity::new("USER", "alice"),
+            MononokeIdentity::new("GROUP", "eng"),
+            MononokeIdentity::new("GROUP", "oncall"),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(identities, expected);
+
+        let claims = json!({
+            "iss": "issuer",
+            "aud": "mononoke",
+            "exp": now() + 3600,
+            "sub": "alice",
+        });
+        // Signed by the wrong key.
+        assert!(
+            authenticator
+                .identities_from_jwt(&ed25519_token(&other_key, claims.clone()))
+                .is_err()
+        );
+        // Tampered with after signing.
+        let token = ed25519_token(&key, claims);
+        let mut parts: Vec<&str> = token.split('.').collect();
+        let tampered = encode(&json!({
+            "iss": "issuer",
+            "aud": "mononoke",
+            "exp": now() + 3600,
+            "sub": "root",
+        }));
+        parts[1] = &tampered;
+        assert!(authenticator.identities_from_jwt(&parts.join(".")).is_err());
+        // Expired, wrong audience, untrusted issuer, or no identities.
+        for claims in [
+            json!({"iss": "issuer", "aud": "mononoke", "exp": now() - 3600, "sub": "alice"}),
+            json!({"iss": "issuer", "aud": "other", "exp": now() + 3600, "sub": "alice"}),
+            json!({"iss": "other", "aud": "mononoke", "exp": now() + 3600, "sub": "alice"}),
+            json!({"iss": "issuer", "aud": "mononoke", "exp": now() + 3600}),
+        ] {
+            assert!(
+                authenticator
+                    .identities_from_jwt(&ed25519_token(&key, claims))
+                    .is_err()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_es256() -> Result<()> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        let key = PKey::from_ec_key(EcKey::generate(&group)?)?;
+        let authenticator = ClientAuthenticator::new(&AuthenticationConfig {
+            cert_identities: vec![],
+            jwt_issuers: vec![issuer("issuer", &key, None)],
+        })?;
+        let token = es256_token(
+            &key,
+            json!({"iss": "issuer", "exp": now() + 3600, "sub": "alice"}),
+        );
+        let identities = authenticator.identities_from_jwt(&token)?;
+        assert!(identities.contains(&MononokeIdentity::new("USER", "alice")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_certificate_identities() -> Result<()> {
+        let key = PKey::generate_ed25519()?;
+        let mut name = X509NameBuilder::new()?;
+        name.append_entry_by_text("CN", "client")?;
+        let name = name.build();
+        let mut builder = X509Builder::new()?;
+        builder.set_version(2)?;
+        builder.set_subject_name(&name)?;
+        builder.set_issuer_name(&name)?;
+        builder.set_pubkey(&key)?;
+        builder.set_not_before(&*Asn1Time::days_from_now(0)?)?;
+        builder.set_not_after(&*Asn1Time::days_from_now(1)?)?;
+        let san = SubjectAlternativeName::new()
+            .uri("spiffe://example.com/user/alice")
+            .uri("spiffe://example.com/service/web")
+            .dns("client.example.com")
+            .build(&builder.x509v3_context(None, None))?;
+        builder.append_extension(san)?;
+        builder.sign(&key, MessageDigest::null())?;
+        let cert = builder.build();
+
+        let authenticator = ClientAuthenticator::new(&AuthenticationConfig {
+            cert_identities: vec![
+                CertIdentityMapping {
+                    san_type: SubjectAltNameType::Uri,
+                    prefix: "spiffe://example.com/user/".to_string(),
+                    identity_type: "USER".to_string(),
+                },
+                CertIdentityMapping {
+                    san_type: SubjectAltNameType::Dns,
+                    prefix: String::new(),
+                    identity_type: "MACHINE".to_string(),
+                },
+            ],
+            jwt_issuers: vec![],
+        })?;
+        let identities = authenticator.identities_from_certificate(&cert)?;
+        let expected: MononokeIdentitySet = [
+            MononokeIdentity::new("X509_SUBJECT_NAME", "CN=client"),
+            MononokeIdentity::new("USER", "alice"),
+            MononokeIdentity::new("MACHINE", "client.example.com"),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(identities, expected);
+        Ok(())
+    }
+}
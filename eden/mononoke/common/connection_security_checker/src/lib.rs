@@ -5,17 +5,26 @@
  * GNU General Public License version 2.
  */
 
+mod authentication;
+
 use anyhow::Result;
+use metaconfig_types::AuthorizationPrincipal;
 use metaconfig_types::CommonConfig;
 use metaconfig_types::Identity;
+use metaconfig_types::RepoPermission;
+use openssl::x509::X509;
 use permission_checker::AclProvider;
 use permission_checker::BoxPermissionChecker;
 use permission_checker::MononokeIdentity;
 use permission_checker::MononokeIdentitySet;
 use permission_checker::PermissionCheckerBuilder;
 
+pub use crate::authentication::ClientAuthenticator;
+
 pub struct ConnectionSecurityChecker {
     checker: BoxPermissionChecker,
+    admin_checker: BoxPermissionChecker,
+    authenticator: ClientAuthenticator,
 }
 
 impl ConnectionSecurityChecker {
@@ -34,8 +43,34 @@ impl ConnectionSecurityChecker {
             builder = builder.allow_allowlist(allowlisted_identities);
         }
 
+        let mut admin_builder = PermissionCheckerBuilder::new();
+        let mut admin_identities = MononokeIdentitySet::new();
+        for Identity { id_type, id_data } in &common_config.admin_allowlist {
+            admin_identities.insert(MononokeIdentity::new(id_type, id_data));
+        }
+        // Authorization rules that grant the admin permission on all repos
+        // also permit administrative mutations.
+        for rule in &common_config.authorization_rules {
+            if !rule.permissions.contains(&RepoPermission::Admin) {
+                continue;
+            }
+            match &rule.principal {
+                AuthorizationPrincipal::Identity(Identity { id_type, id_data }) => {
+                    admin_identities.insert(MononokeIdentity::new(id_type, id_data));
+                }
+                AuthorizationPrincipal::Tier(tier) => {
+                    admin_builder = admin_builder.allow(acl_provider.tier_acl(tier).await?);
+                }
+            }
+        }
+        let admin_checker = admin_builder.allow_allowlist(admin_identities).build();
+
+        let authenticator = ClientAuthenticator::new(&common_config.authentication_config)?;
+
         Ok(Self {
             checker: builder.build(),
+            admin_checker,
+            authenticator,
         })
     }
 
@@ -46,4 +81,21 @@ impl ConnectionSecurityChecker {
             .check_set(identities, &["trusted_parties"])
             .await
     }
+
+    /// Check if the given identities are permitted to perform administrative
+    /// mutations through the control API.
+    pub async fn check_if_admin(&self, identities: &MononokeIdentitySet) -> bool {
+        self.admin_checker.check_set(identities, &["admin"]).await
+    }
+
+    /// Identities of a client that presented the given TLS client
+    /// certificate.
+    pub fn identities_from_certificate(&self, cert: &X509) -> Result<MononokeIdentitySet> {
+        self.authenticator.identities_from_certificate(cert)
+    }
+
+    /// Identities of a client that presented the given bearer token.
+    pub fn identities_from_bearer_token(&self, token: &str) -> Result<MononokeIdentitySet> {
+        self.authenticator.identities_from_jwt(token)
+    }
 }
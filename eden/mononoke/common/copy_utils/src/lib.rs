@@ -333,6 +333,7 @@ fn create_bonsai_changeset(
         file_changes,
         is_snapshot: false,
         git_annotated_tag: None,
+        extras: None,
     }
     .freeze()
 }
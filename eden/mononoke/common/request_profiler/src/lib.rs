@@ -0,0 +1,209 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! On-demand CPU profiling of requests.
+//!
+//! A profile is requested for a class of requests with `arm`.  The next
+//! request of that class to start then profiles the whole process until it
+//! finishes (or the maximum duration passes), and the result can be fetched
+//! in pprof format with `take_profile`.  Only one profile runs at a time, as
+//! the profiler samples every thread in the process.
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+use lazy_static::lazy_static;
+use pprof::protos::Message;
+use pprof::ProfilerGuardBuilder;
+use strum_macros::AsRefStr;
+use strum_macros::Display;
+use strum_macros::EnumString;
+
+/// Sampling frequency of the profiler, in Hz.
+const FREQUENCY: i32 = 99;
+
+/// Classes of requests that can be profiled.
+#[derive(AsRefStr, EnumString, Display, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[strum(serialize_all = "snake_case")]
+pub enum RequestClass {
+    Getbundle,
+    Unbundle,
+    Derivation,
+}
+
+/// Status of the profile of a request class.
+#[derive(Debug)]
+pub enum ProfileStatus {
+    /// No profile has been requested.
+    Idle,
+    /// Waiting for a request of this class to start.
+    Armed,
+    /// Profiling a request of this class.
+    Running,
+    /// Profiling finished, with the profile encoded in pprof format.
+    Finished(Result<Vec<u8>, String>),
+}
+
+enum State {
+    Armed { max_duration: Duration },
+    Running,
+    Finished(Result<Vec<u8>, String>),
+}
+
+#[derive(Default)]
+struct Profiles {
+    states: HashMap<RequestClass, State>,
+    running: bool,
+}
+
+lazy_static! {
+    static ref PROFILES: Mutex<Profiles> = Mutex::new(Profiles::default());
+}
+
+/// Whether any request class is armed, so that starting requests doesn't
+/// need to take the lock when profiling isn't in use.
+static ANY_ARMED: AtomicBool = AtomicBool::new(false);
+
+impl Profiles {
+    fn update_armed(&self) {
+        let any_armed = self
+            .states
+            .values()
+            .any(|state| matches!(state, State::Armed { .. }));
+        ANY_ARMED.store(any_armed, Ordering::Relaxed);
+    }
+}
+
+/// Profile the next request of this class, for at most `max_duration`.
+/// Replaces any profile of this class that hasn't been taken yet.
+pub fn arm(class: RequestClass, max_duration: Duration) {
+    let mut profiles = PROFILES.lock().expect("lock poisoned");
+    if matches!(profiles.states.get(&class), Some(State::Running)) {
+        return;
+    }
+    profiles.states.insert(class, State::Armed { max_duration });
+    profiles.update_armed();
+}
+
+/// Returns the status of the profile of this class.  Finished profiles are
+/// removed, so that they are only returned once.
+pub fn take_profile(class: RequestClass) -> ProfileStatus {
+    let mut profiles = PROFILES.lock().expect("lock poisoned");
+    match profiles.states.remove(&class) {
+        None => ProfileStatus::Idle,
+        Some(State::Finished(profile)) => ProfileStatus::Finished(profile),
+        Some(state) => {
+            let status = match state {
+                State::Armed { .. } => ProfileStatus::Armed,
+                _ => ProfileStatus::Running,
+            };
+            profiles.states.insert(class, state);
+            status
+        }
+    }
+}
+
+/// A running profile.  Profiling stops when this is dropped.
+pub struct ProfilingSession {
+    stop: mpsc::Sender<()>,
+}
+
+impl Drop for ProfilingSession {
+    fn drop(&mut self) {
+        // The profiler thread may have already stopped at the maximum
+        // duration.
+        let _ = self.stop.send(());
+    }
+}
+
+/// Called when a request of this class starts.  If a profile of this class
+/// was requested, profiling starts and lasts until the returned session is
+/// dropped, which should be when the request finishes.
+pub fn start_profiling(class: RequestClass) -> Option<ProfilingSession> {
+    if !ANY_ARMED.load(Ordering::Relaxed) {
+        return None;
+    }
+    let mut profiles = PROFILES.lock().expect("lock poisoned");
+    if profiles.running {
+        return None;
+    }
+    let max_duration = match profiles.states.get(&class) {
+        Some(State::Armed { max_duration }) => *max_duration,
+        _ => return None,
+    };
+    profiles.states.insert(class, State::Running);
+    profiles.running = true;
+    profiles.update_armed();
+
+    let (stop, stopped) = mpsc::channel();
+    thread::spawn(move || {
+        let profile = profile_until(stopped, max_duration).map_err(|err| format!("{:#}", err));
+        let mut profiles = PROFILES.lock().expect("lock poisoned");
+        profiles.states.insert(class, State::Finished(profile));
+        profiles.running = false;
+    });
+    Some(ProfilingSession { stop })
+}
+
+fn profile_until(stopped: mpsc::Receiver<()>, max_duration: Duration) -> Result<Vec<u8>> {
+    let guard = ProfilerGuardBuilder::default()
+        .frequency(FREQUENCY)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()?;
+    // Either the session was dropped, or we reached the maximum duration.
+    let _ = stopped.recv_timeout(max_duration);
+    let profile = guard.report().build()?.pprof()?;
+    let mut encoded = Vec::new();
+    profile.encode(&mut encoded)?;
+    Ok(encoded)
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn test_profile() -> Result<()> {
+        assert_eq!(RequestClass::from_str("getbundle")?, RequestClass::Getbundle);
+
+        // Nothing is profiled unless it was requested.
+        assert!(start_profiling(RequestClass::Unbundle).is_none());
+        assert!(matches!(take_profile(RequestClass::Unbundle), ProfileStatus::Idle));
+
+        arm(RequestClass::Unbundle, Duration::from_secs(60));
+        assert!(matches!(take_profile(RequestClass::Unbundle), ProfileStatus::Armed));
+        assert!(start_profiling(RequestClass::Getbundle).is_none());
+        let session = start_profiling(RequestClass::Unbundle).expect("profile was requested");
+        // Only the first request is profiled.
+        assert!(start_profiling(RequestClass::Unbundle).is_none());
+        drop(session);
+
+        let start = Instant::now();
+        loop {
+            match take_profile(RequestClass::Unbundle) {
+                ProfileStatus::Finished(profile) => {
+                    assert!(profile.is_ok());
+                    break;
+                }
+                ProfileStatus::Running => {}
+                status => panic!("unexpected status {:?}", status),
+            }
+            assert!(start.elapsed() < Duration::from_secs(60));
+            thread::sleep(Duration::from_millis(10));
+        }
+        Ok(())
+    }
+}
@@ -5,15 +5,21 @@
  * GNU General Public License version 2.
  */
 
+use std::sync::Arc;
+use std::sync::Mutex;
 use std::time::Duration;
+use std::time::Instant;
 
+use anyhow::Error;
 use futures::Future;
 use slog::info;
 use slog::Logger;
+use thiserror::Error;
 
 #[derive(Copy, Clone)]
 pub struct RetryAttemptsCount(pub usize);
 
+#[derive(Clone, Copy, Debug)]
 pub enum RetryLogic {
     /// Multiply by a factor every time
     Exponential { base: Duration, factor: f64 },
@@ -41,7 +47,7 @@ impl RetryLogic {
 
 /// Retry a function whenever it fails.
 /// See `retry` for more information.
-pub async fn retry_always<V, Fut, Func, Error>(
+pub async fn retry_always<V, Fut, Func>(
     logger: &Logger,
     func: Func,
     base_delay_ms: u64,
@@ -70,12 +76,14 @@ where
 /// `should_retry` tells whether an error should be retried.
 /// `retry_num` is the maximum amount of times it will be retried
 /// `base_retry_delay_ms` is how much to wait between retries. It does exponential backoffs, doubling this value every time.
-pub async fn retry<V, Fut, Func, RetryFunc, Error>(
+///
+/// This is a `RetryPolicy` without timeouts or a budget.
+pub async fn retry<V, Fut, Func, RetryFunc>(
     logger: Option<&Logger>,
     // Function to be retried.
     mut func: Func,
     // Function that tells whether an error should be retried.
-    mut should_retry: RetryFunc,
+    should_retry: RetryFunc,
     retry_logic: RetryLogic,
     retry_num: usize,
 ) -> Result<(V, RetryAttemptsCount), Error>
@@ -85,27 +93,163 @@ where
     Func: FnMut(usize) -> Fut + Send,
     RetryFunc: FnMut(&Error) -> bool + Send,
 {
-    let mut attempt = 1;
-    loop {
-        let res = func(attempt).await;
-        match res {
-            Ok(res) => {
-                return Ok((res, RetryAttemptsCount(attempt)));
-            }
-            Err(err) if attempt < retry_num && should_retry(&err) => {
-                if let Some(logger) = logger {
-                    info!(
-                        logger,
-                        "retrying attempt {} of {}...",
-                        attempt + 1,
-                        retry_num
-                    );
+    let policy = RetryPolicy {
+        logic: retry_logic,
+        max_attempts: retry_num,
+        ..RetryPolicy::no_retries()
+    };
+    policy
+        .run(
+            |attempt| {
+                if attempt > 1 {
+                    if let Some(logger) = logger {
+                        info!(logger, "retrying attempt {} of {}...", attempt, retry_num);
+                    }
                 }
+                func(attempt)
+            },
+            should_retry,
+            None,
+        )
+        .await
+}
+
+/// Error returned when an attempt made under a `RetryPolicy` times out.
+#[derive(Debug, Error)]
+#[error("attempt {attempt} timed out after {timeout:?}")]
+pub struct AttemptTimedOut {
+    pub attempt: usize,
+    pub timeout: Duration,
+}
+
+/// Budget that limits retries to a fraction of all calls, so that retries
+/// can't multiply the load on a backend that is already failing.
+///
+/// Each call deposits `ratio` tokens, up to `max_tokens`, and each retry
+/// withdraws one.  Retries are not made when the budget is exhausted.
+#[derive(Debug)]
+pub struct RetryBudget {
+    ratio: f64,
+    max_tokens: f64,
+    tokens: Mutex<f64>,
+}
+
+impl RetryBudget {
+    pub fn new(ratio: f64, max_tokens: f64) -> Self {
+        Self {
+            ratio,
+            max_tokens,
+            tokens: Mutex::new(max_tokens),
+        }
+    }
+
+    fn deposit(&self) {
+        let mut tokens = self.tokens.lock().expect("lock poisoned");
+        *tokens = (*tokens + self.ratio).min(self.max_tokens);
+    }
+
+    fn try_withdraw(&self) -> bool {
+        let mut tokens = self.tokens.lock().expect("lock poisoned");
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Policy for retrying an operation: how often it is attempted, how long to
+/// wait between attempts, and how long each attempt and the whole operation
+/// may take.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub logic: RetryLogic,
+    /// Maximum number of attempts, including the first.
+    pub max_attempts: usize,
+    /// Timeout for each attempt.
+    pub attempt_timeout: Option<Duration>,
+    /// Timeout for the whole operation, including waiting between attempts.
+    pub operation_timeout: Option<Duration>,
+    /// Budget that retries are withdrawn from.  May be shared between
+    /// policies.
+    pub budget: Option<Arc<RetryBudget>>,
+}
+
+impl RetryPolicy {
+    /// A policy that makes a single attempt with no timeout.
+    pub fn no_retries() -> Self {
+        Self {
+            logic: RetryLogic::Exponential {
+                base: Duration::ZERO,
+                factor: 1.0,
+            },
+            max_attempts: 1,
+            attempt_timeout: None,
+            operation_timeout: None,
+            budget: None,
+        }
+    }
 
-                tokio::time::sleep(retry_logic.delay(attempt)).await;
-                attempt += 1;
+    /// Run `func` under this policy.  Failed attempts are retried if they
+    /// timed out, or if `should_retry` returns true for their error.
+    ///
+    /// `deadline`, if provided, further limits the operation, e.g. to the
+    /// deadline of the request it is part of.
+    pub async fn run<V, Fut, Func, RetryFunc>(
+        &self,
+        mut func: Func,
+        mut should_retry: RetryFunc,
+        deadline: Option<Instant>,
+    ) -> Result<(V, RetryAttemptsCount), Error>
+    where
+        Fut: Future<Output = Result<V, Error>>,
+        Func: FnMut(usize) -> Fut,
+        RetryFunc: FnMut(&Error) -> bool,
+    {
+        let deadline = match (deadline, self.operation_timeout) {
+            (Some(deadline), Some(timeout)) => Some(deadline.min(Instant::now() + timeout)),
+            (deadline, timeout) => deadline.or_else(|| timeout.map(|t| Instant::now() + t)),
+        };
+        if let Some(budget) = &self.budget {
+            budget.deposit();
+        }
+
+        let mut attempt = 1;
+        loop {
+            let timeout = match (self.attempt_timeout, deadline) {
+                (Some(timeout), Some(deadline)) => {
+                    Some(timeout.min(deadline.saturating_duration_since(Instant::now())))
+                }
+                (Some(timeout), None) => Some(timeout),
+                (None, Some(deadline)) => Some(deadline.saturating_duration_since(Instant::now())),
+                (None, None) => None,
+            };
+            let res = match timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, func(attempt)).await {
+                    Ok(res) => res,
+                    Err(_) => Err(AttemptTimedOut { attempt, timeout }.into()),
+                },
+                None => func(attempt).await,
+            };
+
+            match res {
+                Ok(res) => return Ok((res, RetryAttemptsCount(attempt))),
+                Err(err) => {
+                    let delay = self.logic.delay(attempt);
+                    let out_of_time =
+                        deadline.map_or(false, |deadline| Instant::now() + delay >= deadline);
+                    if attempt >= self.max_attempts
+                        || out_of_time
+                        || !(err.is::<AttemptTimedOut>() || should_retry(&err))
+                        || !self.budget.as_ref().map_or(true, |b| b.try_withdraw())
+                    {
+                        return Err(err);
+                    }
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
             }
-            Err(err) => return Err(err),
         }
     }
 }
@@ -142,3 +286,97 @@ fn test_exponential_jitter() {
     let d = logic.delay(1);
     assert!(d >= two_sec && d <= two_sec + half_sec);
 }
+
+#[tokio::test]
+async fn test_policy_retries() {
+    let policy = RetryPolicy {
+        logic: RetryLogic::Exponential {
+            base: Duration::from_millis(1),
+            factor: 1.0,
+        },
+        max_attempts: 3,
+        ..RetryPolicy::no_retries()
+    };
+    let (res, attempts) = policy
+        .run(
+            |attempt| async move {
+                if attempt < 3 {
+                    Err(anyhow::anyhow!("failed"))
+                } else {
+                    Ok(attempt)
+                }
+            },
+            |_| true,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res, 3);
+    assert_eq!(attempts.0, 3);
+
+    let res = policy
+        .run(
+            |_| async { Err::<(), _>(anyhow::anyhow!("failed")) },
+            |_| false,
+            None,
+        )
+        .await;
+    assert!(res.is_err());
+}
+
+#[tokio::test]
+async fn test_policy_timeouts() {
+    let policy = RetryPolicy {
+        logic: RetryLogic::Exponential {
+            base: Duration::from_millis(1),
+            factor: 1.0,
+        },
+        max_attempts: 2,
+        attempt_timeout: Some(Duration::from_millis(10)),
+        ..RetryPolicy::no_retries()
+    };
+    // The first attempt hangs and is retried even though other errors are
+    // not.
+    let (res, attempts) = policy
+        .run(
+            |attempt| async move {
+                if attempt == 1 {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                }
+                Ok(attempt)
+            },
+            |_| false,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res, 2);
+    assert_eq!(attempts.0, 2);
+}
+
+#[tokio::test]
+async fn test_policy_budget() {
+    let policy = RetryPolicy {
+        logic: RetryLogic::Exponential {
+            base: Duration::from_millis(1),
+            factor: 1.0,
+        },
+        max_attempts: 10,
+        budget: Some(Arc::new(RetryBudget::new(0.0, 2.0))),
+        ..RetryPolicy::no_retries()
+    };
+    let attempts = std::sync::atomic::AtomicUsize::new(0);
+    let res = policy
+        .run(
+            |_| {
+                attempts.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                async { Err::<(), _>(anyhow::anyhow!("failed")) }
+            },
+            |_| true,
+            None,
+        )
+        .await;
+    assert!(res.is_err());
+    // The first attempt plus the two retries in the budget.
+    assert_eq!(attempts.into_inner(), 3);
+}
@@ -0,0 +1,305 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Read-your-writes consistency for replicated databases.
+//!
+//! Reads that may be stale are normally served by replicas, which can lag
+//! behind the primary. A client that has just written something (e.g. moved
+//! a bookmark by pushing) and reads it back straight away may therefore not
+//! see its own write. `ReadConsistency` remembers which sessions have
+//! recently written to a shard, and routes their reads to the primary until
+//! replicas can be expected to have caught up.
+//!
+//! Writes are also recorded in memcache, so that a session's reads are
+//! routed to the primary by every server, and not just by the one that
+//! served its write.
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::Weak;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use bytes::Bytes;
+use caching_ext::MemcacheHandler;
+use fbinit::FacebookInit;
+use slog::warn;
+use slog::Logger;
+use stats::prelude::*;
+
+use crate::replication::ReplicaLagMonitor;
+
+define_stats! {
+    prefix = "mononoke.sql.consistency";
+    replica_lag_ms: dynamic_singleton_counter("{}.replica_lag_ms", (shard: String)),
+    primary_reads_after_write: timeseries(Sum),
+    primary_reads_lagging: timeseries(Sum),
+    shared_write_record_errors: timeseries(Sum),
+}
+
+/// How often the replica lag of a shard is polled.
+const REPLICA_LAG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Above this number of tracked sessions, sessions that no longer need to
+/// read from the primary are forgotten on the next write.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Thresholds for routing reads to the primary.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct ReadConsistencyConfig {
+    /// How long reads by a session that has written to the shard go to the
+    /// primary. If the replicas are lagging by more than this, reads go to
+    /// the primary until the lag has elapsed instead.
+    pub read_your_writes_window: Duration,
+    /// If set, all reads go to the primary while the replicas are lagging
+    /// by more than this.
+    pub max_replica_lag: Option<Duration>,
+}
+
+/// Tracks recent writes to a single shard and its replica lag.
+pub struct ReadConsistency {
+    shard: String,
+    config: ReadConsistencyConfig,
+    replica_lag_ms: AtomicU64,
+    recent_writes: Mutex<HashMap<String, Instant>>,
+    /// Where writes are recorded for other servers to see.
+    shared_writes: MemcacheHandler,
+}
+
+impl ReadConsistency {
+    pub fn new(
+        shard: String,
+        config: ReadConsistencyConfig,
+        shared_writes: MemcacheHandler,
+    ) -> Self {
+        Self {
+            shard,
+            config,
+            replica_lag_ms: AtomicU64::new(0),
+            recent_writes: Mutex::new(HashMap::new()),
+            shared_writes,
+        }
+    }
+
+    /// Create a `ReadConsistency` whose replica lag is periodically updated
+    /// from `monitor`, until it is dropped.
+    pub fn with_monitor(
+        fb: FacebookInit,
+        logger: Logger,
+        shard: String,
+        config: ReadConsistencyConfig,
+        shared_writes: MemcacheHandler,
+        monitor: Arc<dyn ReplicaLagMonitor>,
+    ) -> Arc<Self> {
+        let this = Arc::new(Self::new(shard, config, shared_writes));
+        spawn_lag_poller(fb, logger, Arc::downgrade(&this), monitor);
+        this
+    }
+
+    pub fn shard(&self) -> &str {
+        &self.shard
+    }
+
+    /// The last known replica lag of the shard.
+    pub fn replica_lag(&self) -> Duration {
+        Duration::from_millis(self.replica_lag_ms.load(Ordering::Relaxed))
+    }
+
+    pub fn set_replica_lag(&self, lag: Duration) {
+        let lag_ms = lag.as_millis().try_into().unwrap_or(u64::MAX);
+        self.replica_lag_ms.store(lag_ms, Ordering::Relaxed);
+    }
+
+    /// How long after a write reads should go to the primary.
+    fn primary_window(&self) -> Duration {
+        std::cmp::max(self.config.read_your_writes_window, self.replica_lag())
+    }
+
+    fn shared_key(&self, session: &str) -> String {
+        format!("sql.read_consistency.{}.{}", self.shard, session)
+    }
+
+    /// Record that `session` has written to the shard.
+    pub async fn record_write(&self, session: &str) {
+        let now = Instant::now();
+        let window = self.primary_window();
+        {
+            let mut recent_writes = self.recent_writes.lock().expect("lock poisoned");
+            if recent_writes.len() >= PRUNE_THRESHOLD {
+                recent_writes.retain(|_, written| now.duration_since(*written) < window);
+            }
+            recent_writes.insert(session.to_string(), now);
+        }
+
+        if self.shared_writes.is_noop() || window.is_zero() {
+            return;
+        }
+        let written_ms = unix_time_ms().to_be_bytes();
+        let res = self
+            .shared_writes
+            .set_with_ttl(
+                self.shared_key(session),
+                Bytes::copy_from_slice(&written_ms),
+                window,
+            )
+            .await;
+        if res.is_err() {
+            // Other servers may serve stale reads to the session, but the
+            // write itself has succeeded.
+            STATS::shared_write_record_errors.add_value(1);
+        }
+    }
+
+    /// How long ago `session` last wrote to the shard, as recorded by any
+    /// server.
+    async fn since_last_write(&self, session: &str) -> Option<Duration> {
+        let local = self
+            .recent_writes
+            .lock()
+            .expect("lock poisoned")
+            .get(session)
+            .map(|written| written.elapsed());
+        if local.is_some() || self.shared_writes.is_noop() {
+            return local;
+        }
+        let data = self.shared_writes.get(self.shared_key(session)).await.ok()??;
+        let written_ms = u64::from_be_bytes(data.as_ref().try_into().ok()?);
+        Some(Duration::from_millis(unix_time_ms().saturating_sub(written_ms)))
+    }
+
+    /// Whether a read by `session` that would normally go to a replica
+    /// should go to the primary instead.
+    pub async fn should_read_primary(&self, session: &str) -> bool {
+        if let Some(max_replica_lag) = self.config.max_replica_lag {
+            if self.replica_lag() > max_replica_lag {
+                STATS::primary_reads_lagging.add_value(1);
+                return true;
+            }
+        }
+        if self.config.read_your_writes_window.is_zero() {
+            return false;
+        }
+        let wrote_recently = self
+            .since_last_write(session)
+            .await
+            .map_or(false, |elapsed| elapsed < self.primary_window());
+        if wrote_recently {
+            STATS::primary_reads_after_write.add_value(1);
+        }
+        wrote_recently
+    }
+}
+
+fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_millis() as u64)
+}
+
+fn spawn_lag_poller(
+    fb: FacebookInit,
+    logger: Logger,
+    consistency: Weak<ReadConsistency>,
+    monitor: Arc<dyn ReplicaLagMonitor>,
+) {
+    let fut = async move {
+        loop {
+            let lag = monitor.get_max_replica_lag().await;
+            let consistency = match consistency.upgrade() {
+                Some(consistency) => consistency,
+                None => break,
+            };
+            match lag {
+                Ok(lag) => {
+                    consistency.set_replica_lag(lag.delay);
+                    STATS::replica_lag_ms.set_value(
+                        fb,
+                        lag.delay.as_millis() as i64,
+                        (consistency.shard().to_string(),),
+                    );
+                }
+                Err(err) => warn!(
+                    logger,
+                    "Failed to get replica lag for {}: {:?}",
+                    consistency.shard(),
+                    err
+                ),
+            }
+            drop(consistency);
+            tokio::time::sleep(REPLICA_LAG_POLL_INTERVAL).await;
+        }
+    };
+
+    // Fire and forget. This terminates once the ReadConsistency is dropped.
+    std::mem::drop(tokio::task::spawn(fut));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn consistency(
+        window_secs: u64,
+        max_lag_secs: Option<u64>,
+        shared_writes: MemcacheHandler,
+    ) -> ReadConsistency {
+        ReadConsistency::new(
+            "shard".to_string(),
+            ReadConsistencyConfig {
+                read_your_writes_window: Duration::from_secs(window_secs),
+                max_replica_lag: max_lag_secs.map(Duration::from_secs),
+            },
+            shared_writes,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_read_your_writes() {
+        let consistency = consistency(60, None, MemcacheHandler::create_noop());
+        assert!(!consistency.should_read_primary("a").await);
+        consistency.record_write("a").await;
+        assert!(consistency.should_read_primary("a").await);
+        assert!(!consistency.should_read_primary("b").await);
+    }
+
+    #[tokio::test]
+    async fn test_read_your_writes_across_servers() {
+        let shared_writes = MemcacheHandler::create_mock();
+        let writer = consistency(60, None, shared_writes.clone());
+        let reader = consistency(60, None, shared_writes);
+        assert!(!reader.should_read_primary("a").await);
+        writer.record_write("a").await;
+        assert!(reader.should_read_primary("a").await);
+        assert!(!reader.should_read_primary("b").await);
+
+        // Servers that don't share the record only see their own writes.
+        let other = consistency(60, None, MemcacheHandler::create_mock());
+        assert!(!other.should_read_primary("a").await);
+    }
+
+    #[tokio::test]
+    async fn test_disabled() {
+        let consistency = consistency(0, None, MemcacheHandler::create_mock());
+        consistency.record_write("a").await;
+        consistency.set_replica_lag(Duration::from_secs(3600));
+        assert!(!consistency.should_read_primary("a").await);
+    }
+
+    #[tokio::test]
+    async fn test_max_replica_lag() {
+        let consistency = consistency(0, Some(5), MemcacheHandler::create_noop());
+        consistency.set_replica_lag(Duration::from_secs(1));
+        assert!(!consistency.should_read_primary("a").await);
+        consistency.set_replica_lag(Duration::from_secs(10));
+        assert!(consistency.should_read_primary("a").await);
+    }
+}
@@ -5,6 +5,7 @@
  * GNU General Public License version 2.
  */
 
+pub mod consistency;
 mod mononoke_queries;
 #[cfg(not(fbcode_build))]
 mod oss;
@@ -33,11 +34,13 @@ pub mod _macro_internal {
     pub use paste;
     pub use sql::queries;
     pub use sql::Connection;
+    pub use sql::Transaction;
     pub use sql::WriteResult;
     pub use sql_query_config::SqlQueryConfig;
     pub use twox_hash::xxh3::Hash128;
     pub use twox_hash::xxh3::HasherExt;
 
+    pub use crate::mononoke_queries::instrument_query;
     pub use crate::mononoke_queries::query_with_retry;
     pub use crate::mononoke_queries::query_with_retry_no_cache;
     pub use crate::mononoke_queries::CacheData;
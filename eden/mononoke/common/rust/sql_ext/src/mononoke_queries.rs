@@ -8,7 +8,9 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::future::Future;
+use std::sync::Arc;
 use std::time::Duration;
+use std::time::Instant;
 
 use abomonation::Abomonation;
 use abomonation_derive::Abomonation;
@@ -21,16 +23,58 @@ use itertools::Itertools;
 use maplit::hashmap;
 use maplit::hashset;
 use memcache::KeyGen;
-use retry::retry;
+use once_cell::sync::Lazy;
+use retry::RetryBudget;
 use retry::RetryLogic;
+use retry::RetryPolicy;
 use sql_query_config::CachingConfig;
+use stats::prelude::*;
 use tunables::tunables;
 
+define_stats! {
+    prefix = "mononoke.sql";
+    calls: dynamic_timeseries("{}.calls", (query: &'static str); Rate, Sum),
+    errors: dynamic_timeseries("{}.errors", (query: &'static str); Rate, Sum),
+    retries: dynamic_timeseries("{}.retries", (query: &'static str); Rate, Sum),
+    completion_time_ms: dynamic_histogram(
+        "{}.completion_time_ms",
+        (query: &'static str);
+        10, 0, 1_000, Average, Sum, Count; P 50; P 90; P 99
+    ),
+}
+
 const RETRY_ATTEMPTS: usize = 2;
 
+/// Retries allowed per query, across all queries made by this process.
+const RETRY_BUDGET_RATIO: f64 = 0.1;
+/// Maximum number of retries that can be saved up in the budget.
+const RETRY_BUDGET_MAX: f64 = 100.0;
+
+static SQL_RETRY_POLICY: Lazy<RetryPolicy> = Lazy::new(|| RetryPolicy {
+    // See https://fburl.com/7dmedu1u for backoff reasoning
+    logic: RetryLogic::ExponentialWithJitter {
+        base: Duration::from_secs(10),
+        factor: 1.2,
+        jitter: Duration::from_secs(5),
+    },
+    max_attempts: RETRY_ATTEMPTS,
+    attempt_timeout: None,
+    operation_timeout: None,
+    budget: Some(Arc::new(RetryBudget::new(
+        RETRY_BUDGET_RATIO,
+        RETRY_BUDGET_MAX,
+    ))),
+});
+
 // This wraps around rust/shed/sql::queries, check that macro: https://fburl.com/code/semq9xm3
 /// Define SQL queries that automatically retry on certain errors.
 ///
+/// Each query records the number of calls, errors and retries, and its
+/// completion time, under the path of the module generated for it. Queries
+/// made within a transaction record the same stats but are never retried.
+/// Callers with a `CoreContext` should use `query_with_deadline` with
+/// `ctx.deadline()` so that queries are not retried past it.
+///
 /// Caching can be enabled on a read query by:
 /// - Adding "cacheable" keyword to your query.
 /// - Make sure all parameters (input) to the query implement the Hash trait.
@@ -97,16 +141,38 @@ macro_rules! mononoke_queries {
                 use $crate::_macro_internal::*;
 
                 // Not possible to retry query with transaction
-                #[allow(unused_imports)]
-                pub use [<$name Impl>]::query_with_transaction;
+                #[allow(dead_code)]
+                pub async fn query_with_transaction(
+                    transaction: $crate::_macro_internal::Transaction,
+                    $( $pname: & $ptype, )*
+                    $( $lname: & [ $ltype ], )*
+                ) -> Result<($crate::_macro_internal::Transaction, Vec<($( $rtype, )*)>)> {
+                    instrument_query(
+                        module_path!(),
+                        [<$name Impl>]::query_with_transaction(transaction, $( $pname, )* $( $lname, )*),
+                    ).await
+                }
 
                 #[allow(dead_code)]
                 pub async fn query(
                     connection: &Connection,
                     $( $pname: & $ptype, )*
                     $( $lname: & [ $ltype ], )*
+                ) -> Result<Vec<($( $rtype, )*)>> {
+                    query_with_deadline(connection, None, $( $pname, )* $( $lname, )*).await
+                }
+
+                /// Like `query`, but the query is not retried past `deadline`.
+                #[allow(dead_code)]
+                pub async fn query_with_deadline(
+                    connection: &Connection,
+                    deadline: Option<std::time::Instant>,
+                    $( $pname: & $ptype, )*
+                    $( $lname: & [ $ltype ], )*
                 ) -> Result<Vec<($( $rtype, )*)>> {
                     query_with_retry_no_cache(
+                        module_path!(),
+                        deadline,
                         || [<$name Impl>]::query(connection, $( $pname, )* $( $lname, )*),
                     ).await
                 }
@@ -141,8 +207,17 @@ macro_rules! mononoke_queries {
                 use $crate::_macro_internal::*;
 
                 // Not possible to retry query with transaction
-                #[allow(unused_imports)]
-                pub use [<$name Impl>]::query_with_transaction;
+                #[allow(dead_code)]
+                pub async fn query_with_transaction(
+                    transaction: $crate::_macro_internal::Transaction,
+                    $( $pname: & $ptype, )*
+                    $( $lname: & [ $ltype ], )*
+                ) -> Result<($crate::_macro_internal::Transaction, Vec<($( $rtype, )*)>)> {
+                    instrument_query(
+                        module_path!(),
+                        [<$name Impl>]::query_with_transaction(transaction, $( $pname, )* $( $lname, )*),
+                    ).await
+                }
 
                 #[allow(dead_code)]
                 pub async fn query(
@@ -150,6 +225,18 @@ macro_rules! mononoke_queries {
                     connection: &Connection,
                     $( $pname: & $ptype, )*
                     $( $lname: & [ $ltype ], )*
+                ) -> Result<Vec<($( $rtype, )*)>> {
+                    query_with_deadline(config, connection, None, $( $pname, )* $( $lname, )*).await
+                }
+
+                /// Like `query`, but the query is not retried past `deadline`.
+                #[allow(dead_code)]
+                pub async fn query_with_deadline(
+                    config: &SqlQueryConfig,
+                    connection: &Connection,
+                    deadline: Option<std::time::Instant>,
+                    $( $pname: & $ptype, )*
+                    $( $lname: & [ $ltype ], )*
                 ) -> Result<Vec<($( $rtype, )*)>> {
                     let mut hasher = Hash128::with_seed(0);
 
@@ -167,6 +254,8 @@ macro_rules! mononoke_queries {
 
 
                     Ok(query_with_retry(
+                        module_path!(),
+                        deadline,
                         data,
                         || async move { Ok(MemcacheWrapper([<$name Impl>]::query(connection, $( $pname, )* $( $lname, )*).await?)) },
                     ).await?.0)
@@ -219,16 +308,38 @@ macro_rules! mononoke_queries {
                 use $crate::_macro_internal::*;
 
                 // Not possible to retry query with transaction
-                #[allow(unused_imports)]
-                pub use [<$name Impl>]::query_with_transaction;
+                #[allow(dead_code)]
+                pub async fn query_with_transaction(
+                    transaction: $crate::_macro_internal::Transaction,
+                    values: &[($( & $vtype, )*)],
+                    $( $pname: & $ptype ),*
+                ) -> Result<($crate::_macro_internal::Transaction, WriteResult)> {
+                    instrument_query(
+                        module_path!(),
+                        [<$name Impl>]::query_with_transaction(transaction, values $( , $pname )*),
+                    ).await
+                }
 
                 #[allow(dead_code)]
                 pub async fn query(
                     connection: &Connection,
                     values: &[($( & $vtype, )*)],
                     $( $pname: & $ptype ),*
+                ) -> Result<WriteResult> {
+                    query_with_deadline(connection, None, values $( , $pname )*).await
+                }
+
+                /// Like `query`, but the query is not retried past `deadline`.
+                #[allow(dead_code)]
+                pub async fn query_with_deadline(
+                    connection: &Connection,
+                    deadline: Option<std::time::Instant>,
+                    values: &[($( & $vtype, )*)],
+                    $( $pname: & $ptype ),*
                 ) -> Result<WriteResult> {
                     query_with_retry_no_cache(
+                        module_path!(),
+                        deadline,
                         || [<$name Impl>]::query(connection, values $( , $pname )* ),
                     ).await
                 }
@@ -280,16 +391,38 @@ macro_rules! mononoke_queries {
                 use $crate::_macro_internal::*;
 
                 // Not possible to retry query with transaction
-                #[allow(unused_imports)]
-                pub use [<$name Impl>]::query_with_transaction;
+                #[allow(dead_code)]
+                pub async fn query_with_transaction(
+                    transaction: $crate::_macro_internal::Transaction,
+                    $( $pname: & $ptype, )*
+                    $( $lname: & [ $ltype ], )*
+                ) -> Result<($crate::_macro_internal::Transaction, WriteResult)> {
+                    instrument_query(
+                        module_path!(),
+                        [<$name Impl>]::query_with_transaction(transaction, $( $pname, )* $( $lname, )*),
+                    ).await
+                }
 
                 #[allow(dead_code)]
                 pub async fn query(
                     connection: &Connection,
                     $( $pname: & $ptype, )*
                     $( $lname: & [ $ltype ], )*
+                ) -> Result<WriteResult> {
+                    query_with_deadline(connection, None, $( $pname, )* $( $lname, )*).await
+                }
+
+                /// Like `query`, but the query is not retried past `deadline`.
+                #[allow(dead_code)]
+                pub async fn query_with_deadline(
+                    connection: &Connection,
+                    deadline: Option<std::time::Instant>,
+                    $( $pname: & $ptype, )*
+                    $( $lname: & [ $ltype ], )*
                 ) -> Result<WriteResult> {
                     query_with_retry_no_cache(
+                        module_path!(),
+                        deadline,
                         || [<$name Impl>]::query(connection, $( $pname, )* $( $lname, )*),
                     ).await
                 }
@@ -407,33 +540,59 @@ where
     }
 }
 
+/// Record the number of calls and errors, and the completion time, of a
+/// query under `query_name`.
+pub async fn instrument_query<T>(
+    query_name: &'static str,
+    query: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    STATS::calls.add_value(1, (query_name,));
+    let start = Instant::now();
+    let result = query.await;
+    STATS::completion_time_ms.add_value(start.elapsed().as_millis() as i64, (query_name,));
+    if result.is_err() {
+        STATS::errors.add_value(1, (query_name,));
+    }
+    result
+}
+
+/// Run a query, retrying it on errors that are safe to retry, but not past
+/// `deadline`.
 pub async fn query_with_retry_no_cache<T, Fut>(
+    query_name: &'static str,
+    deadline: Option<Instant>,
     do_query: impl Fn() -> Fut + Send + Sync,
 ) -> Result<T>
 where
     T: Send + 'static,
     Fut: Future<Output = Result<T>>,
 {
-    if tunables().disable_sql_auto_retries().unwrap_or_default() {
-        return do_query().await;
-    }
-    Ok(retry(
-        None,
-        |_| do_query(),
-        should_retry_mysql_query,
-        // See https://fburl.com/7dmedu1u for backoff reasoning
-        RetryLogic::ExponentialWithJitter {
-            base: Duration::from_secs(10),
-            factor: 1.2,
-            jitter: Duration::from_secs(5),
-        },
-        RETRY_ATTEMPTS,
-    )
-    .await?
-    .0)
+    instrument_query(query_name, async {
+        if tunables().disable_sql_auto_retries().unwrap_or_default() {
+            return do_query().await;
+        }
+        let mut attempts = 1;
+        let result = SQL_RETRY_POLICY
+            .run(
+                |attempt| {
+                    attempts = attempt;
+                    do_query()
+                },
+                should_retry_mysql_query,
+                deadline,
+            )
+            .await
+            .map(|(value, _)| value);
+        // Failed queries may have been retried too, so record retries either way.
+        STATS::retries.add_value(attempts as i64 - 1, (query_name,));
+        result
+    })
+    .await
 }
 
 pub async fn query_with_retry<T, Fut>(
+    query_name: &'static str,
+    deadline: Option<Instant>,
     cache_data: CacheData<'_>,
     do_query: impl Fn() -> Fut + Send + Sync,
 ) -> Result<T>
@@ -442,9 +601,9 @@ where
     Fut: Future<Output = Result<T>> + Send,
 {
     if tunables().disable_sql_auto_cache().unwrap_or_default() {
-        return query_with_retry_no_cache(&do_query).await;
+        return query_with_retry_no_cache(query_name, deadline, &do_query).await;
     }
-    let fetch = || query_with_retry_no_cache(&do_query);
+    let fetch = || query_with_retry_no_cache(query_name, deadline, &do_query);
     let key = cache_data.key;
     if let Some(config) = cache_data.config.as_ref() {
         let store = QueryCacheStore {
@@ -467,6 +626,12 @@ where
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::open_sqlite_in_memory;
+
     mononoke_queries! {
         read TestQuery(param_str: String, param_uint: u64) -> (u64, Option<i32>, String, i64) {
             "SELECT 44, NULL, {param_str}, {param_uint}"
@@ -499,12 +664,51 @@ mod tests {
         let config: &SqlQueryConfig = todo!();
         let connection: &sql::Connection = todo!();
         TestQuery::query(connection, todo!(), todo!()).await?;
+        TestQuery::query_with_deadline(connection, None, todo!(), todo!()).await?;
         TestQuery::query_with_transaction(todo!(), todo!(), todo!()).await?;
         TestQuery2::query(config, connection).await?;
+        TestQuery2::query_with_deadline(config, connection, None).await?;
         TestQuery2::query_with_transaction(todo!()).await?;
         TestQuery3::query(connection, &[(&12,)]).await?;
+        TestQuery3::query_with_deadline(connection, None, &[(&12,)]).await?;
         TestQuery3::query_with_transaction(todo!(), &[(&12,)]).await?;
         TestQuery4::query(connection, &"hello").await?;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_instrumented_queries() -> Result<()> {
+        let connection = sql::Connection::with_sqlite(open_sqlite_in_memory()?);
+
+        let rows = TestQuery::query(&connection, &"hello".to_string(), &7).await?;
+        assert_eq!(rows, vec![(44, None, "hello".to_string(), 7)]);
+
+        let transaction = connection.start_transaction().await?;
+        let (transaction, rows) =
+            TestQuery::query_with_transaction(transaction, &"hello".to_string(), &7).await?;
+        assert_eq!(rows, vec![(44, None, "hello".to_string(), 7)]);
+        transaction.rollback().await?;
+
+        // Errors are still returned once they have been recorded.
+        let result = TestQuery4::query(&connection, &"hello").await;
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_with_retry_no_cache() {
+        let attempts = AtomicUsize::new(0);
+        let result = query_with_retry_no_cache("test_query", None, || async {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            Err::<(), _>(anyhow!("not retryable"))
+        })
+        .await;
+        assert!(result.is_err());
+        // Errors that can't be retried are only attempted once.
+        assert_eq!(attempts.load(Ordering::Relaxed), 1);
+
+        let result = query_with_retry_no_cache("test_query", None, || async { Ok(42) }).await;
+        assert_eq!(result.unwrap(), 42);
+    }
 }
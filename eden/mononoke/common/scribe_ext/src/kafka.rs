@@ -0,0 +1,100 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Error;
+use rdkafka::config::ClientConfig;
+use rdkafka::producer::BaseRecord;
+use rdkafka::producer::DeliveryResult;
+use rdkafka::producer::Producer;
+use rdkafka::producer::ProducerContext;
+use rdkafka::producer::ThreadedProducer;
+use rdkafka::ClientContext;
+use stats::prelude::*;
+
+use crate::MessageQueue;
+
+define_stats! {
+    prefix = "mononoke.scribe.kafka";
+    delivery_failures: timeseries(Rate, Sum),
+}
+
+/// Counts the messages that Kafka failed to deliver.  Delivery is reported
+/// asynchronously, after `offer` has returned, so failures are reported by
+/// `flush`.
+#[derive(Default)]
+struct DeliveryContext {
+    failed_deliveries: AtomicU64,
+}
+
+impl ClientContext for DeliveryContext {}
+
+impl ProducerContext for DeliveryContext {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, result: &DeliveryResult<'_>, _opaque: Self::DeliveryOpaque) {
+        if result.is_err() {
+            STATS::delivery_failures.add_value(1);
+            self.failed_deliveries.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A message queue that publishes each category to a Kafka topic.
+pub struct KafkaQueue {
+    producer: ThreadedProducer<DeliveryContext>,
+    topic_prefix: String,
+}
+
+impl KafkaQueue {
+    /// Connect to the given comma-separated list of Kafka brokers.  Messages
+    /// for a category are published to the topic named by `topic_prefix`
+    /// followed by the category.
+    pub fn new(brokers: &str, topic_prefix: impl Into<String>) -> Result<Self, Error> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .create_with_context(DeliveryContext::default())
+            .with_context(|| format!("Failed to create Kafka producer for {}", brokers))?;
+        Ok(Self {
+            producer,
+            topic_prefix: topic_prefix.into(),
+        })
+    }
+}
+
+impl MessageQueue for KafkaQueue {
+    fn offer(&self, category: &str, message: &str) -> Result<(), Error> {
+        let topic = format!("{}{}", self.topic_prefix, category);
+        self.producer
+            .send(BaseRecord::<(), str>::to(&topic).payload(message))
+            .map_err(|(e, _record)| e)
+            .with_context(|| format!("Failed to queue message for Kafka topic {}", topic))
+    }
+
+    fn flush(&self, timeout: Duration) -> Result<(), Error> {
+        self.producer
+            .flush(timeout)
+            .context("Failed to flush Kafka producer")?;
+        let failed_deliveries = self
+            .producer
+            .context()
+            .failed_deliveries
+            .swap(0, Ordering::Relaxed);
+        if failed_deliveries > 0 {
+            bail!(
+                "{} messages could not be delivered to Kafka",
+                failed_deliveries
+            );
+        }
+        Ok(())
+    }
+}
@@ -12,6 +12,7 @@ use std::io::Write;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
 
 use anyhow as _;
 use anyhow::anyhow;
@@ -20,18 +21,38 @@ use fbinit::FacebookInit;
 #[cfg(fbcode_build)]
 use scribe::ScribeClient; // oss uses anyhow
 
+#[cfg(feature = "kafka")]
+mod kafka;
 #[cfg(not(fbcode_build))]
 mod oss;
 
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaQueue;
+
 #[cfg(not(fbcode_build))]
 pub use oss::ScribeClientImplementation;
 #[cfg(fbcode_build)]
 pub use scuba::ScribeClientImplementation;
 
+/// A message queue that logging streams can be published to, as an
+/// alternative to Scribe.
+pub trait MessageQueue: Send + Sync {
+    /// Publish a message to the given category.  Messages may be published
+    /// asynchronously, so success doesn't mean the message was delivered.
+    fn offer(&self, category: &str, message: &str) -> Result<(), Error>;
+
+    /// Wait for the messages that have been published so far to be
+    /// delivered, and report any that could not be.
+    fn flush(&self, _timeout: Duration) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
 #[derive(Clone)]
 pub enum Scribe {
     Client(Arc<ScribeClientImplementation>),
     LogToFile(Arc<Mutex<PathBuf>>),
+    Queue(Arc<dyn MessageQueue>),
 }
 
 impl ::std::fmt::Debug for Scribe {
@@ -39,6 +60,7 @@ impl ::std::fmt::Debug for Scribe {
         match self {
             Self::Client(_) => f.debug_struct("Scribe::Client").finish(),
             Self::LogToFile(_) => f.debug_struct("Scribe::LogToFile").finish(),
+            Self::Queue(_) => f.debug_struct("Scribe::Queue").finish(),
         }
     }
 }
@@ -52,6 +74,10 @@ impl Scribe {
         Self::LogToFile(Arc::new(Mutex::new(dir_path)))
     }
 
+    pub fn new_with_queue(queue: Arc<dyn MessageQueue>) -> Self {
+        Self::Queue(queue)
+    }
+
     pub fn offer(&self, category: &str, sample: &str) -> Result<(), Error> {
         use Scribe::*;
 
@@ -84,6 +110,66 @@ impl Scribe {
                 ::std::writeln!(file, "{}", sample)?;
                 Ok(())
             }
+            Queue(queue) => queue.offer(category, sample),
+        }
+    }
+
+    /// Wait for messages that are published asynchronously to be delivered.
+    /// This should be called before shutting down, so that they are not
+    /// lost.
+    pub fn flush(&self, timeout: Duration) -> Result<(), Error> {
+        match self {
+            Self::Client(_) | Self::LogToFile(_) => Ok(()),
+            Self::Queue(queue) => queue.flush(timeout),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::fs::read_to_string;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct TestQueue {
+        messages: Mutex<Vec<(String, String)>>,
+    }
+
+    impl MessageQueue for TestQueue {
+        fn offer(&self, category: &str, message: &str) -> Result<(), Error> {
+            self.messages
+                .lock()
+                .unwrap()
+                .push((category.to_string(), message.to_string()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_log_to_file() -> Result<(), Error> {
+        let dir = tempfile::tempdir()?;
+        let scribe = Scribe::new_to_file(dir.path().to_path_buf());
+        scribe.offer("commits", "one")?;
+        scribe.offer("commits", "two")?;
+        assert_eq!(read_to_string(dir.path().join("commits"))?, "one\ntwo\n");
+        assert!(scribe.offer("../commits", "three").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue() -> Result<(), Error> {
+        let queue = Arc::new(TestQueue::default());
+        let scribe = Scribe::new_with_queue(queue.clone());
+        scribe.offer("commits", "one")?;
+        scribe.offer("wireproto", "two")?;
+        assert_eq!(
+            *queue.messages.lock().unwrap(),
+            vec![
+                ("commits".to_string(), "one".to_string()),
+                ("wireproto".to_string(), "two".to_string()),
+            ]
+        );
+        Ok(())
+    }
+}
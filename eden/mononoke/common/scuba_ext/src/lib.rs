@@ -9,6 +9,7 @@ use std::collections::hash_map::Entry;
 use std::io::Error as IoError;
 use std::num::NonZeroU64;
 use std::path::Path;
+use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::Result;
@@ -30,12 +31,20 @@ pub use scuba::ScubaValue;
 use time_ext::DurationExt;
 use tunables::tunables;
 
+mod sink;
+
+pub use crate::sink::ClickHouseSink;
+pub use crate::sink::JsonLinesSink;
+pub use crate::sink::ScubaSink;
+
 const FILE_PREFIX: &str = "file://";
 
 /// An extensible wrapper struct around `ScubaSampleBuilder`
 #[derive(Clone)]
 pub struct MononokeScubaSampleBuilder {
     inner: ScubaSampleBuilder,
+    // Where samples are logged to, if not a scuba table or a file
+    maybe_sink: Option<Arc<dyn ScubaSink>>,
     maybe_observability_context: Option<ObservabilityContext>,
     // This field decides if sampled out requests should
     // still be logged when verbose logging is enabled
@@ -63,8 +72,12 @@ fn get_scuba_logging_type(arg: &str) -> ScubaLoggingType {
 
 impl MononokeScubaSampleBuilder {
     pub fn new(fb: FacebookInit, scuba_table: &str) -> Result<Self> {
+        if let Some(sink) = sink::sink_for_table(scuba_table)? {
+            return Ok(Self::with_sink(sink));
+        }
         Ok(Self {
             inner: Self::get_scuba_sample_builder(fb, get_scuba_logging_type(scuba_table))?,
+            maybe_sink: None,
             maybe_observability_context: None,
             fallback_sampled_out_to_verbose: false,
         })
@@ -73,11 +86,20 @@ impl MononokeScubaSampleBuilder {
     pub fn with_discard() -> Self {
         Self {
             inner: ScubaSampleBuilder::with_discard(),
+            maybe_sink: None,
             maybe_observability_context: None,
             fallback_sampled_out_to_verbose: false,
         }
     }
 
+    /// Log samples that pass sampling to a sink instead of a scuba table.
+    pub fn with_sink(sink: Arc<dyn ScubaSink>) -> Self {
+        Self {
+            maybe_sink: Some(sink),
+            ..Self::with_discard()
+        }
+    }
+
     pub fn with_opt_table(fb: FacebookInit, scuba_table: Option<String>) -> Result<Self> {
         match scuba_table {
             None => Ok(Self::with_discard()),
@@ -164,6 +186,8 @@ impl MononokeScubaSampleBuilder {
             .add_opt("client_tw_job", metadata.clientinfo_tw_job());
         self.inner
             .add_opt("client_tw_task", metadata.clientinfo_tw_task());
+        self.inner
+            .add_opt("client_version", metadata.client_version());
 
         self
     }
@@ -203,7 +227,7 @@ impl MononokeScubaSampleBuilder {
 
             self.inner.add("msg", msg);
         }
-        self.inner.log();
+        self.log();
     }
 
     /// Same as `log_with_msg`, but sample is assumed to be verbose and is only logged
@@ -281,7 +305,7 @@ impl MononokeScubaSampleBuilder {
     }
 
     pub fn is_discard(&self) -> bool {
-        self.inner.is_discard()
+        self.inner.is_discard() && self.maybe_sink.is_none()
     }
 
     pub fn sampled(&mut self, sample_rate: NonZeroU64) -> &mut Self {
@@ -302,7 +326,18 @@ impl MononokeScubaSampleBuilder {
     }
 
     pub fn log(&mut self) -> bool {
-        self.inner.log()
+        let logged = self.inner.log();
+        if logged {
+            self.log_to_sink(None);
+        }
+        logged
+    }
+
+    fn log_to_sink(&self, time: Option<u64>) {
+        if let Some(sink) = &self.maybe_sink {
+            // Like scuba logging, logging to a sink is best effort.
+            let _ = sink.log(self.inner.get_sample(), time);
+        }
     }
 
     /// Same as `log`, but sample is assumed to be verbose and is only logged
@@ -346,7 +381,11 @@ impl MononokeScubaSampleBuilder {
     }
 
     pub fn log_with_time(&mut self, time: u64) -> bool {
-        self.inner.log_with_time(time)
+        let logged = self.inner.log_with_time(time);
+        if logged {
+            self.log_to_sink(Some(time));
+        }
+        logged
     }
 
     pub fn entry<K: Into<String>>(&mut self, key: K) -> Entry<String, ScubaValue> {
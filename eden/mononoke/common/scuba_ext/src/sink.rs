@@ -0,0 +1,351 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Sinks that scuba samples can be routed to instead of a scuba table.
+//!
+//! A scuba table configured as `jsonl://<path>` is logged to a rotating
+//! JSON-lines file, and one configured as `clickhouse://<host>:<port>/<table>`
+//! (or `clickhouses://` for HTTPS) is inserted into a ClickHouse table over
+//! HTTP.
+
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::ErrorKind;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::mpsc::SyncSender;
+use std::sync::mpsc::TrySendError;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use scuba::ScubaSample;
+use serde_json::Map;
+use serde_json::Value;
+
+const JSONL_PREFIX: &str = "jsonl://";
+const CLICKHOUSE_PREFIX: &str = "clickhouse://";
+const CLICKHOUSE_HTTPS_PREFIX: &str = "clickhouses://";
+
+const DEFAULT_MAX_BYTES: u64 = 100 * 1024 * 1024;
+const DEFAULT_MAX_FILES: usize = 5;
+
+/// Maximum number of samples waiting to be sent to ClickHouse.  Samples
+/// logged while the queue is full are dropped.
+const CLICKHOUSE_QUEUE_SIZE: usize = 10000;
+/// Maximum size of a single insert sent to ClickHouse.
+const CLICKHOUSE_MAX_BATCH_BYTES: usize = 1024 * 1024;
+
+/// A destination for scuba samples.
+pub trait ScubaSink: Send + Sync {
+    /// Log a sample that has passed sampling.
+    fn log(&self, sample: &ScubaSample, time: Option<u64>) -> Result<()>;
+}
+
+/// Sinks are shared by all sample builders that log to the same destination.
+static SINKS: Lazy<Mutex<HashMap<String, Arc<dyn ScubaSink>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Returns the sink for a scuba table, or `None` if the scuba table is not
+/// routed to a sink.
+pub(crate) fn sink_for_table(scuba_table: &str) -> Result<Option<Arc<dyn ScubaSink>>> {
+    if !scuba_table.starts_with(JSONL_PREFIX)
+        && !scuba_table.starts_with(CLICKHOUSE_PREFIX)
+        && !scuba_table.starts_with(CLICKHOUSE_HTTPS_PREFIX)
+    {
+        return Ok(None);
+    }
+    let mut sinks = SINKS.lock().unwrap();
+    if let Some(sink) = sinks.get(scuba_table) {
+        return Ok(Some(sink.clone()));
+    }
+    let sink: Arc<dyn ScubaSink> = if let Some(spec) = scuba_table.strip_prefix(JSONL_PREFIX) {
+        Arc::new(JsonLinesSink::from_spec(spec)?)
+    } else if let Some(spec) = scuba_table.strip_prefix(CLICKHOUSE_PREFIX) {
+        Arc::new(ClickHouseSink::from_spec("http", spec)?)
+    } else if let Some(spec) = scuba_table.strip_prefix(CLICKHOUSE_HTTPS_PREFIX) {
+        Arc::new(ClickHouseSink::from_spec("https", spec)?)
+    } else {
+        unreachable!()
+    };
+    sinks.insert(scuba_table.to_string(), sink.clone());
+    Ok(Some(sink))
+}
+
+/// Convert a sample to scuba's JSON format, where columns are grouped by
+/// type (`int`, `double`, `normal`, `denorm`, `normvector` and `tags`).
+fn sample_json(sample: &ScubaSample, time: Option<u64>) -> Result<Map<String, Value>> {
+    let mut json = match sample.to_json()? {
+        Value::Object(json) => json,
+        _ => return Err(anyhow!("Scuba sample is not a JSON object")),
+    };
+    let ints = json
+        .entry("int")
+        .or_insert_with(|| Value::Object(Map::new()))
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("Scuba sample int columns are not a JSON object"))?;
+    let time = match time {
+        Some(time) => time,
+        None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+    };
+    ints.entry("time").or_insert_with(|| Value::from(time));
+    Ok(json)
+}
+
+/// Sink that appends samples in scuba's JSON format to a file, one sample
+/// per line.  When the file exceeds its maximum size it is rotated to
+/// `<path>.1`, and older files are moved to `<path>.2` and so on, keeping at
+/// most `max_files` old files.
+pub struct JsonLinesSink {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: Mutex<Option<(File, u64)>>,
+}
+
+impl JsonLinesSink {
+    pub fn new(path: impl Into<PathBuf>, max_bytes: u64, max_files: usize) -> Self {
+        Self {
+            path: path.into(),
+            max_bytes,
+            max_files,
+            file: Mutex::new(None),
+        }
+    }
+
+    /// Parse `<path>[?max_bytes=<n>][&max_files=<n>]`.
+    fn from_spec(spec: &str) -> Result<Self> {
+        let (path, options) = spec.split_once('?').unwrap_or((spec, ""));
+        let mut max_bytes = DEFAULT_MAX_BYTES;
+        let mut max_files = DEFAULT_MAX_FILES;
+        for option in options.split('&').filter(|option| !option.is_empty()) {
+            match option.split_once('=') {
+                Some(("max_bytes", value)) => {
+                    max_bytes = value.parse().context("Invalid max_bytes")?;
+                }
+                Some(("max_files", value)) => {
+                    max_files = value.parse().context("Invalid max_files")?;
+                }
+                _ => return Err(anyhow!("Unknown JSON-lines sink option '{}'", option)),
+            }
+        }
+        Ok(Self::new(path, max_bytes, max_files))
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    fn rotate(&self) -> Result<()> {
+        let rename = |from: &Path, to: &Path| match fs::rename(from, to) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+        if self.max_files == 0 {
+            return match fs::remove_file(&self.path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+        for index in (1..self.max_files).rev() {
+            rename(&self.rotated_path(index), &self.rotated_path(index + 1))?;
+        }
+        rename(&self.path, &self.rotated_path(1))?;
+        Ok(())
+    }
+}
+
+impl ScubaSink for JsonLinesSink {
+    fn log(&self, sample: &ScubaSample, time: Option<u64>) -> Result<()> {
+        let mut line = serde_json::to_string(&sample_json(sample, time)?)?;
+        line.push('\n');
+
+        let mut file = self.file.lock().unwrap();
+        if let Some((_, size)) = file.as_ref() {
+            if *size > 0 && *size + line.len() as u64 > self.max_bytes {
+                *file = None;
+                self.rotate()?;
+            }
+        }
+        if file.is_none() {
+            let opened = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .with_context(|| format!("Failed to open {}", self.path.display()))?;
+            let size = opened.metadata()?.len();
+            *file = Some((opened, size));
+        }
+        let (opened, size) = file.as_mut().unwrap();
+        opened.write_all(line.as_bytes())?;
+        *size += line.len() as u64;
+        Ok(())
+    }
+}
+
+/// Sink that inserts samples into a ClickHouse table using the HTTP
+/// interface.  Columns of all types are inserted as top-level fields, so
+/// `int` and `double` columns map to numeric columns, `normal` and `denorm`
+/// columns map to `String` columns, and `normvector` and `tags` columns map
+/// to `Array(String)` columns.  Sample columns that the table doesn't have
+/// are ignored.
+///
+/// Samples are sent in batches from a background thread.
+pub struct ClickHouseSink {
+    sender: SyncSender<String>,
+}
+
+impl ClickHouseSink {
+    pub fn new(url: String, table: String) -> Result<Self> {
+        let (sender, receiver) = mpsc::sync_channel::<String>(CLICKHOUSE_QUEUE_SIZE);
+        let (started_sender, started_receiver) = mpsc::sync_channel(1);
+        let query = format!("INSERT INTO {} FORMAT JSONEachRow", table);
+        thread::Builder::new()
+            .name("scuba-clickhouse".to_string())
+            .spawn(move || {
+                // The blocking client must be created outside of any async
+                // runtime.
+                let client = match reqwest::blocking::Client::builder()
+                    .timeout(Duration::from_secs(30))
+                    .build()
+                {
+                    Ok(client) => {
+                        let _ = started_sender.send(Ok(()));
+                        client
+                    }
+                    Err(e) => {
+                        let _ = started_sender.send(Err(e));
+                        return;
+                    }
+                };
+                while let Ok(mut body) = receiver.recv() {
+                    while body.len() < CLICKHOUSE_MAX_BATCH_BYTES {
+                        match receiver.try_recv() {
+                            Ok(row) => body.push_str(&row),
+                            Err(_) => break,
+                        }
+                    }
+                    // Like scuba, logging is best effort, so failed inserts
+                    // are dropped.
+                    let _ = client
+                        .post(&url)
+                        .query(&[
+                            ("query", query.as_str()),
+                            ("input_format_skip_unknown_fields", "1"),
+                        ])
+                        .body(body)
+                        .send()
+                        .and_then(|response| response.error_for_status());
+                }
+            })
+            .context("Failed to start ClickHouse logging thread")?;
+        started_receiver
+            .recv()
+            .context("ClickHouse logging thread exited before starting")?
+            .context("Failed to create ClickHouse client")?;
+        Ok(Self { sender })
+    }
+
+    /// Parse `<host>:<port>/<table>`.
+    fn from_spec(scheme: &str, spec: &str) -> Result<Self> {
+        let (host, table) = spec
+            .split_once('/')
+            .filter(|(host, table)| !host.is_empty() && !table.is_empty())
+            .ok_or_else(|| anyhow!("Invalid ClickHouse table '{}'", spec))?;
+        Self::new(format!("{}://{}/", scheme, host), table.to_string())
+    }
+}
+
+impl ScubaSink for ClickHouseSink {
+    fn log(&self, sample: &ScubaSample, time: Option<u64>) -> Result<()> {
+        let mut row = Map::new();
+        for (_column_type, columns) in sample_json(sample, time)? {
+            if let Value::Object(columns) = columns {
+                row.extend(columns);
+            }
+        }
+        let mut line = serde_json::to_string(&row)?;
+        line.push('\n');
+        self.sender.try_send(line).map_err(|e| match e {
+            TrySendError::Full(_) => anyhow!("ClickHouse logging queue is full"),
+            TrySendError::Disconnected(_) => anyhow!("ClickHouse logging thread has exited"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::read_to_string;
+
+    use super::*;
+
+    fn sample(value: i64) -> ScubaSample {
+        let mut sample = ScubaSample::new();
+        sample.add("value", value);
+        sample.add("name", "test");
+        sample
+    }
+
+    #[test]
+    fn test_json_lines_sink() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("scuba.jsonl");
+        let sink = JsonLinesSink::from_spec(&format!(
+            "{}?max_bytes=100&max_files=2",
+            path.display()
+        ))?;
+
+        sink.log(&sample(1), Some(1000))?;
+        let line: Value = serde_json::from_str(read_to_string(&path)?.trim_end())?;
+        assert_eq!(line["int"]["value"], 1);
+        assert_eq!(line["int"]["time"], 1000);
+        assert_eq!(line["normal"]["name"], "test");
+
+        // Each sample is more than half of the maximum size, so every
+        // sample after the first rotates the file.
+        for value in 2..=4 {
+            sink.log(&sample(value), Some(1000))?;
+        }
+        let value_in = |path: &Path| -> Result<Value> {
+            let line: Value = serde_json::from_str(read_to_string(path)?.trim_end())?;
+            Ok(line["int"]["value"].clone())
+        };
+        assert_eq!(value_in(&path)?, 4);
+        assert_eq!(value_in(&sink.rotated_path(1))?, 3);
+        assert_eq!(value_in(&sink.rotated_path(2))?, 2);
+        assert!(!sink.rotated_path(3).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sink_for_table() -> Result<()> {
+        assert!(sink_for_table("some_table")?.is_none());
+        assert!(sink_for_table("file:///tmp/scuba")?.is_none());
+        assert!(ClickHouseSink::from_spec("http", "localhost:8123").is_err());
+        assert!(JsonLinesSink::from_spec("/tmp/scuba.jsonl?max_size=1").is_err());
+
+        let dir = tempfile::tempdir()?;
+        let table = format!("jsonl://{}", dir.path().join("scuba.jsonl").display());
+        let first = sink_for_table(&table)?.expect("should be a sink");
+        let second = sink_for_table(&table)?.expect("should be a sink");
+        assert!(Arc::ptr_eq(&first, &second));
+        Ok(())
+    }
+}
@@ -339,6 +339,7 @@ mod test {
             file_changes: sorted_vector_map! { MPath::new("file").unwrap() => FileChange::Deletion },
             is_snapshot: false,
             git_annotated_tag: None,
+            extras: None,
         }
         .freeze()
         .unwrap()
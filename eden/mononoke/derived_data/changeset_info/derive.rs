@@ -24,7 +24,8 @@ use mononoke_types::ChangesetId;
 
 use crate::ChangesetInfo;
 
-fn format_key(derivation_ctx: &DerivationContext, changeset_id: ChangesetId) -> String {
+/// The key of the blob that stores the changeset info for a changeset.
+pub fn format_key(derivation_ctx: &DerivationContext, changeset_id: ChangesetId) -> String {
     let root_prefix = "changeset_info.blake2.";
     let key_prefix = derivation_ctx.mapping_key_prefix::<ChangesetInfo>();
     format!("{}{}{}", root_prefix, key_prefix, changeset_id)
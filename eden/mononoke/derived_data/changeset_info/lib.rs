@@ -7,6 +7,9 @@
 
 mod changeset_info;
 mod derive;
+mod prefetch;
 
 pub use crate::changeset_info::ChangesetInfo;
 pub use crate::changeset_info::ChangesetMessage;
+pub use crate::derive::format_key;
+pub use crate::prefetch::fetch_changeset_info_batch;
@@ -0,0 +1,79 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Result;
+use context::CoreContext;
+use derived_data_manager::DerivedDataManager;
+use futures::future::try_join_all;
+use mononoke_types::ChangesetId;
+
+use crate::ChangesetInfo;
+
+/// Fetch the changeset infos for a batch of changesets, in the same order.
+///
+/// Infos that have already been derived are fetched together, and the rest
+/// are derived.
+pub async fn fetch_changeset_info_batch(
+    ctx: &CoreContext,
+    manager: &DerivedDataManager,
+    cs_ids: Vec<ChangesetId>,
+) -> Result<Vec<ChangesetInfo>> {
+    let mut fetched = manager
+        .fetch_derived_batch::<ChangesetInfo>(ctx, cs_ids.clone(), None)
+        .await?;
+    Ok(try_join_all(cs_ids.into_iter().map(|cs_id| {
+        let info = fetched.remove(&cs_id);
+        async move {
+            match info {
+                Some(info) => Ok(info),
+                None => manager.derive::<ChangesetInfo>(ctx, cs_id, None).await,
+            }
+        }
+    }))
+    .await?)
+}
+
+#[cfg(test)]
+mod test {
+    use changeset_fetcher::ChangesetFetcherArc;
+    use fbinit::FacebookInit;
+    use fixtures::Linear;
+    use fixtures::TestRepoFixture;
+    use futures::compat::Stream01CompatExt;
+    use futures::stream::TryStreamExt;
+    use repo_derived_data::RepoDerivedDataRef;
+    use revset::AncestorsNodeStream;
+    use tests_utils::resolve_cs_id;
+
+    use super::*;
+
+    #[fbinit::test]
+    async fn test_fetch_batch_in_order(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = Linear::getrepo(fb).await;
+        let master_cs_id = resolve_cs_id(&ctx, &repo, "master").await?;
+        let manager = repo.repo_derived_data().manager();
+
+        let cs_ids =
+            AncestorsNodeStream::new(ctx.clone(), &repo.changeset_fetcher_arc(), master_cs_id)
+                .compat()
+                .try_collect::<Vec<_>>()
+                .await?;
+        // Derive some of the infos, so that some are fetched and some derived.
+        manager
+            .derive::<ChangesetInfo>(&ctx, cs_ids[cs_ids.len() / 2], None)
+            .await?;
+
+        let infos = fetch_changeset_info_batch(&ctx, manager, cs_ids.clone()).await?;
+        let info_cs_ids = infos
+            .iter()
+            .map(|info| *info.changeset_id())
+            .collect::<Vec<_>>();
+        assert_eq!(info_cs_ids, cs_ids);
+        Ok(())
+    }
+}
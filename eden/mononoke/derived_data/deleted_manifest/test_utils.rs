@@ -855,6 +855,7 @@ async fn create_bonsai_changeset(
         file_changes,
         is_snapshot: false,
         git_annotated_tag: None,
+        extras: None,
     }
     .freeze()
     .unwrap();
@@ -594,6 +594,25 @@ mod tests {
 
     #[fbinit::test]
     async fn verify_batch_and_sequential_derive(fb: FacebookInit) -> Result<()> {
+        test_verify_batch_and_sequential_derive(fb).await
+    }
+
+    #[fbinit::test]
+    fn verify_batch_and_sequential_derive_small_inserts(fb: FacebookInit) -> Result<()> {
+        // Write the filenodes for each commit separately.
+        let tunables = MononokeTunables::default();
+        tunables.update_ints(&hashmap! {
+            "filenodes_derive_batch_insert_size".to_string() => 1,
+            "filenodes_insert_chunk_size".to_string() => 2,
+        });
+
+        with_tunables(tunables, || {
+            let runtime = tokio::runtime::Runtime::new()?;
+            runtime.block_on(test_verify_batch_and_sequential_derive(fb))
+        })
+    }
+
+    async fn test_verify_batch_and_sequential_derive(fb: FacebookInit) -> Result<()> {
         let ctx = CoreContext::test_mock(fb);
         let repo1: TestRepo = test_repo_factory::build_empty(fb)?;
         Linear::initrepo(fb, &repo1).await;
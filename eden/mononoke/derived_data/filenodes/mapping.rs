@@ -94,6 +94,58 @@ impl TryFrom<PreparedFilenode> for PreparedRootFilenode {
     }
 }
 
+/// Default number of filenodes to collect before writing them during batch
+/// derivation.
+const DEFAULT_BATCH_INSERT_SIZE: usize = 10000;
+
+/// Filenodes for commits in a batch that have not been written yet.
+#[derive(Default)]
+struct PendingFilenodes {
+    commits: Vec<(ChangesetId, PreparedRootFilenode)>,
+    filenodes: Vec<PreparedFilenode>,
+}
+
+impl PendingFilenodes {
+    fn add(
+        &mut self,
+        cs_id: ChangesetId,
+        root_filenode: PreparedRootFilenode,
+        non_roots: Vec<PreparedFilenode>,
+    ) {
+        self.commits.push((cs_id, root_filenode));
+        self.filenodes.extend(non_roots);
+    }
+
+    /// Write the pending filenodes, and record the result for the commits
+    /// they belong to.
+    async fn write(
+        &mut self,
+        ctx: &CoreContext,
+        derivation_ctx: &DerivationContext,
+        res: &mut HashMap<ChangesetId, FilenodesOnlyPublic>,
+    ) -> Result<()> {
+        let commits = std::mem::take(&mut self.commits);
+        let filenodes = std::mem::take(&mut self.filenodes);
+        if filenodes.is_empty() {
+            return Ok(());
+        }
+        let result = derivation_ctx
+            .filenodes()?
+            .add_filenodes(ctx, filenodes)
+            .await?;
+        for (cs_id, root_filenode) in commits {
+            let filenode = match result {
+                FilenodeResult::Disabled => FilenodesOnlyPublic::Disabled,
+                FilenodeResult::Present(()) => FilenodesOnlyPublic::Present {
+                    root_filenode: Some(root_filenode),
+                },
+            };
+            res.insert(cs_id, filenode);
+        }
+        Ok(())
+    }
+}
+
 /// Derives filenodes that are stores in Filenodes object (usually in a database).
 /// Note: that should be derived only for public commits!
 ///
@@ -128,26 +180,36 @@ impl BonsaiDerivable for FilenodesOnlyPublic {
         bonsais: Vec<BonsaiChangeset>,
         _gap_size: Option<usize>,
     ) -> Result<HashMap<ChangesetId, Self>> {
-        let filenodes = derivation_ctx.filenodes()?;
         let prepared = derive_filenodes_in_batch(ctx, derivation_ctx, bonsais).await?;
+        let batch_insert_size = match tunables::tunables()
+            .filenodes_derive_batch_insert_size()
+            .unwrap_or_default()
+        {
+            size if size > 0 => size as usize,
+            _ => DEFAULT_BATCH_INSERT_SIZE,
+        };
+
+        // Rather than writing the filenodes for each commit separately,
+        // collect them from several commits so that they can be written in
+        // larger batches.
         let mut res = HashMap::with_capacity(prepared.len());
+        let mut pending = PendingFilenodes::default();
         for (cs_id, public_filenode, non_roots) in prepared.into_iter() {
-            let filenode = match public_filenode {
-                FilenodesOnlyPublic::Present { root_filenode } => match root_filenode {
-                    Some(filenode) if !non_roots.is_empty() => {
-                        match filenodes.add_filenodes(ctx, non_roots).await? {
-                            FilenodeResult::Disabled => FilenodesOnlyPublic::Disabled,
-                            FilenodeResult::Present(()) => FilenodesOnlyPublic::Present {
-                                root_filenode: Some(filenode),
-                            },
-                        }
+            match public_filenode {
+                FilenodesOnlyPublic::Present {
+                    root_filenode: Some(filenode),
+                } if !non_roots.is_empty() => {
+                    pending.add(cs_id, filenode, non_roots);
+                    if pending.filenodes.len() >= batch_insert_size {
+                        pending.write(ctx, derivation_ctx, &mut res).await?;
                     }
-                    _ => FilenodesOnlyPublic::Present { root_filenode },
-                },
-                FilenodesOnlyPublic::Disabled => FilenodesOnlyPublic::Disabled,
-            };
-            res.insert(cs_id, filenode.clone());
+                }
+                filenode => {
+                    res.insert(cs_id, filenode);
+                }
+            }
         }
+        pending.write(ctx, derivation_ctx, &mut res).await?;
         Ok(res)
     }
 
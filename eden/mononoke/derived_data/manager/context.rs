@@ -13,6 +13,7 @@ use anyhow::Context;
 use anyhow::Result;
 use blobstore::Blobstore;
 use bonsai_hg_mapping::BonsaiHgMapping;
+use cacheblob::CacheUsage;
 use cacheblob::MemWritesBlobstore;
 use context::CoreContext;
 use filenodes::Filenodes;
@@ -25,6 +26,9 @@ use mononoke_types::RepositoryId;
 use crate::derivable::BonsaiDerivable;
 use crate::manager::derive::Rederivation;
 use crate::manager::DerivedDataManager;
+use crate::trace::ChangesetTracer;
+use crate::trace::TracingBlobstore;
+use crate::version;
 
 /// Context for performing derivation.
 ///
@@ -44,6 +48,13 @@ pub struct DerivationContext {
         Arc<dyn Blobstore>,
         Arc<MemWritesBlobstore<Arc<dyn Blobstore>>>,
     )>,
+
+    /// Tracer for the derivation of a changeset, if derivations are being
+    /// traced.
+    tracer: Option<Arc<ChangesetTracer>>,
+
+    /// Maximum number of changesets that a traced derivation may derive.
+    max_traced_underived: u64,
 }
 
 impl DerivationContext {
@@ -57,10 +68,15 @@ impl DerivationContext {
             rederivation,
             blobstore,
             blobstore_write_cache: None,
+            tracer: None,
+            max_traced_underived: 0,
         }
     }
 
     /// Fetch previously derived data.
+    ///
+    /// Data derived at a format version that is no longer compatible is
+    /// treated as not derived.
     pub async fn fetch_derived<Derivable>(
         &self,
         ctx: &CoreContext,
@@ -75,10 +91,20 @@ impl DerivationContext {
             }
         }
         let derived = Derivable::fetch(ctx, self, csid).await?;
+        if derived.is_some() && version::may_be_incompatible::<Derivable>() {
+            let format_version = version::fetch_format_version::<Derivable>(ctx, self, csid)
+                .await?;
+            if format_version < Derivable::MIN_COMPATIBLE_FORMAT_VERSION {
+                return Ok(None);
+            }
+        }
         Ok(derived)
     }
 
     /// Fetch a batch of previously derived data.
+    ///
+    /// Data derived at a format version that is no longer compatible is
+    /// omitted.
     pub async fn fetch_derived_batch<Derivable>(
         &self,
         ctx: &CoreContext,
@@ -90,7 +116,16 @@ impl DerivationContext {
         if let Some(rederivation) = self.rederivation.as_ref() {
             csids.retain(|csid| rederivation.needs_rederive(Derivable::NAME, *csid) != Some(true));
         }
-        let derived = Derivable::fetch_batch(ctx, self, &csids).await?;
+        let mut derived = Derivable::fetch_batch(ctx, self, &csids).await?;
+        if version::may_be_incompatible::<Derivable>() {
+            let format_versions =
+                version::fetch_format_versions::<Derivable>(ctx, self, derived.keys().copied())
+                    .await?;
+            derived.retain(|csid, _| {
+                format_versions.get(csid).copied().unwrap_or_default()
+                    >= Derivable::MIN_COMPATIBLE_FORMAT_VERSION
+            });
+        }
         Ok(derived)
     }
 
@@ -179,10 +214,25 @@ impl DerivationContext {
     where
         Derivable: BonsaiDerivable,
     {
-        Ok(self
-            .manager
-            .derive::<Derivable>(ctx, csid, self.rederivation.clone())
-            .await?)
+        match &self.tracer {
+            Some(tracer) => {
+                let (derived, trace) = self
+                    .manager
+                    .derive_traced::<Derivable>(
+                        ctx,
+                        csid,
+                        self.rederivation.clone(),
+                        self.max_traced_underived,
+                    )
+                    .await?;
+                tracer.record_dependency(trace);
+                Ok(derived)
+            }
+            None => Ok(self
+                .manager
+                .derive::<Derivable>(ctx, csid, self.rederivation.clone())
+                .await?),
+        }
     }
 
     /// The repo id of the repo being derived.
@@ -217,6 +267,10 @@ impl DerivationContext {
     }
 
     /// Mapping key prefix for a particular derived data type.
+    pub(crate) fn manager(&self) -> &DerivedDataManager {
+        &self.manager
+    }
+
     pub fn mapping_key_prefix<Derivable>(&self) -> &str
     where
         Derivable: BonsaiDerivable,
@@ -254,11 +308,67 @@ impl DerivationContext {
     /// a call to `flush` to make them persistent.
     pub(crate) fn enable_write_batching(&mut self) {
         if self.blobstore_write_cache.is_none() {
-            let blobstore = Arc::new(MemWritesBlobstore::new(self.blobstore.clone()));
+            let blobstore = Arc::new(
+                MemWritesBlobstore::new(self.blobstore.clone())
+                    .with_memory_limit(self.manager.write_cache_memory_limit()),
+            );
             self.blobstore_write_cache = Some((blobstore.clone(), blobstore));
         }
     }
 
+    /// Enable tracing of derivations for this derivation context.
+    ///
+    /// With tracing enabled, each changeset is derived with its own context
+    /// from `for_traced_changeset`, and dependencies are derived with
+    /// tracing enabled too, so that their traces are recorded.  Each of
+    /// these derivations may derive at most `max_underived` changesets.
+    pub(crate) fn enable_tracing(&mut self, max_underived: u64) {
+        if self.tracer.is_none() {
+            self.tracer = Some(Arc::new(ChangesetTracer::default()));
+        }
+        self.max_traced_underived = max_underived;
+    }
+
+    pub(crate) fn is_tracing(&self) -> bool {
+        self.tracer.is_some()
+    }
+
+    /// Maximum number of changesets that a derivation with this context may
+    /// derive, if it is traced.
+    pub(crate) fn max_traced_underived(&self) -> Option<u64> {
+        self.is_tracing().then_some(self.max_traced_underived)
+    }
+
+    /// If tracing is enabled, a derivation context for deriving a single
+    /// changeset, and the tracer that records its derivation.
+    ///
+    /// If write batching is enabled, the writes still go through the write
+    /// cache, so they are flushed along with the rest.
+    pub(crate) fn for_traced_changeset(&self) -> Option<(Self, Arc<ChangesetTracer>)> {
+        self.tracer.as_ref()?;
+        let tracer = Arc::new(ChangesetTracer::default());
+        let mut derivation_ctx = DerivationContext {
+            tracer: Some(tracer.clone()),
+            ..self.clone()
+        };
+        match &mut derivation_ctx.blobstore_write_cache {
+            Some((blobstore, _)) => {
+                *blobstore = Arc::new(TracingBlobstore::new(blobstore.clone(), tracer.clone()));
+            }
+            None => {
+                derivation_ctx.blobstore = Arc::new(TracingBlobstore::new(
+                    derivation_ctx.blobstore.clone(),
+                    tracer.clone(),
+                ));
+            }
+        }
+        Some((derivation_ctx, tracer))
+    }
+
+    pub(crate) fn tracer(&self) -> Option<&ChangesetTracer> {
+        self.tracer.as_deref()
+    }
+
     /// Flush any pending writes for this derivation context.
     pub(crate) async fn flush(&self, ctx: &CoreContext) -> Result<()> {
         if let Some((_, blobstore)) = &self.blobstore_write_cache {
@@ -266,4 +376,11 @@ impl DerivationContext {
         }
         Ok(())
     }
+
+    /// What the write cache of this derivation context has held, if write
+    /// batching is enabled.
+    pub(crate) fn write_cache_usage(&self) -> Option<CacheUsage> {
+        let (_, blobstore) = self.blobstore_write_cache.as_ref()?;
+        Some(blobstore.get_cache().lock().unwrap().usage())
+    }
 }
@@ -25,6 +25,7 @@ use strum_macros::Display;
 use strum_macros::EnumString;
 
 use crate::context::DerivationContext;
+use crate::version::INITIAL_FORMAT_VERSION;
 
 /// Enum which consolidates all available derived data types
 /// It provides access to `const &'static str` representation to
@@ -86,6 +87,20 @@ pub trait BonsaiDerivable: Sized + Send + Sync + Clone + Debug + 'static {
     const VARIANT: DerivableType;
     const NAME: &'static str = Self::VARIANT.name();
 
+    /// Version of the format of this derived data type, which is recorded
+    /// whenever it is derived.
+    ///
+    /// Bump this when changing what derivation produces, rather than
+    /// moving the mapping to new keys.  Data derived before the first bump
+    /// has no record, and is at `INITIAL_FORMAT_VERSION`.
+    const FORMAT_VERSION: u32 = INITIAL_FORMAT_VERSION;
+
+    /// Oldest format version that this code can still serve.
+    ///
+    /// Data derived at an older version is treated as underived, so that
+    /// it is re-derived when it is next requested, or by a backfill.
+    const MIN_COMPATIBLE_FORMAT_VERSION: u32 = INITIAL_FORMAT_VERSION;
+
     /// Types of derived data types on which this derived data type
     /// depends.
     ///
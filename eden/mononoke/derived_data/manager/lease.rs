@@ -5,28 +5,44 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashMap;
 use std::future::Future;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::time::Duration;
 use std::time::Instant;
 
 use anyhow::Result;
 use cacheblob::LeaseOps;
 use context::CoreContext;
+use context::RequestAbandoned;
 use futures::channel::oneshot;
 use futures::future::FutureExt;
 use slog::warn;
+use tokio_util::sync::CancellationToken;
 
 const LEASE_WARNING_THRESHOLD: Duration = Duration::from_secs(60);
 
 #[derive(Clone)]
 pub struct DerivedDataLease {
     lease_ops: Arc<dyn LeaseOps>,
+    /// Requests in this process waiting for each derivation, by lease key.
+    waiting: Arc<Mutex<HashMap<String, Waiting>>>,
+}
+
+struct Waiting {
+    count: usize,
+    /// Cancelled once every request waiting for the derivation has been
+    /// abandoned.
+    all_abandoned: CancellationToken,
 }
 
 impl DerivedDataLease {
     pub fn new(lease_ops: Arc<dyn LeaseOps>) -> Self {
-        DerivedDataLease { lease_ops }
+        DerivedDataLease {
+            lease_ops,
+            waiting: Arc::new(Mutex::new(HashMap::new())),
+        }
     }
 
     pub fn lease_ops(&self) -> &Arc<dyn LeaseOps> {
@@ -70,6 +86,77 @@ impl DerivedDataLease {
             sender: Some(sender),
         }))
     }
+
+    /// Run `fut`, the derivation protected by the lease `key`, on behalf of
+    /// `ctx`.
+    ///
+    /// Other requests in this process may be waiting for the same
+    /// derivation, so it is only abandoned once all of them have been
+    /// abandoned.
+    pub async fn run_until_all_abandoned<F: Future>(
+        &self,
+        ctx: &CoreContext,
+        key: &str,
+        fut: F,
+    ) -> Result<F::Output, RequestAbandoned> {
+        let mut waiter = self.wait_for(key);
+        tokio::select! {
+            biased;
+            output = fut => Ok(output),
+            abandoned = async {
+                let abandoned = ctx.abandoned().await;
+                waiter.leave().cancelled().await;
+                abandoned
+            } => Err(abandoned),
+        }
+    }
+
+    fn wait_for(&self, key: &str) -> Waiter<'_> {
+        let mut waiting = self.waiting.lock().expect("lock poisoned");
+        let entry = waiting.entry(key.to_string()).or_insert_with(|| Waiting {
+            count: 0,
+            all_abandoned: CancellationToken::new(),
+        });
+        entry.count += 1;
+        Waiter {
+            lease: self,
+            key: key.to_string(),
+            all_abandoned: entry.all_abandoned.clone(),
+            waiting: true,
+        }
+    }
+}
+
+/// A request waiting for a derivation.  It stops waiting when dropped.
+struct Waiter<'a> {
+    lease: &'a DerivedDataLease,
+    key: String,
+    all_abandoned: CancellationToken,
+    waiting: bool,
+}
+
+impl Waiter<'_> {
+    /// Stop waiting for the derivation.  Returns a token that is cancelled
+    /// once no other request is waiting for it either.
+    fn leave(&mut self) -> CancellationToken {
+        if std::mem::take(&mut self.waiting) {
+            let mut waiting = self.lease.waiting.lock().expect("lock poisoned");
+            if let Some(entry) = waiting.get_mut(&self.key) {
+                entry.count -= 1;
+                if entry.count == 0 {
+                    entry.all_abandoned.cancel();
+                    waiting.remove(&self.key);
+                }
+            }
+        }
+        self.all_abandoned.clone()
+    }
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        self.leave();
+    }
 }
 
 /// Guard representing an active lease.  We stop renewing the lease when
@@ -10,6 +10,8 @@ pub mod derivable;
 pub mod error;
 pub mod lease;
 pub mod manager;
+pub mod trace;
+pub mod version;
 
 pub use self::context::DerivationContext;
 pub use self::derivable::BonsaiDerivable;
@@ -18,5 +20,8 @@ pub use self::error::DerivationError;
 pub use self::lease::DerivedDataLease;
 pub use self::manager::derive::BatchDeriveOptions;
 pub use self::manager::derive::BatchDeriveStats;
+pub use self::manager::derive::BatchDeriveOutcome;
 pub use self::manager::derive::Rederivation;
 pub use self::manager::DerivedDataManager;
+pub use self::trace::DerivationTrace;
+pub use self::version::INITIAL_FORMAT_VERSION;
@@ -22,6 +22,7 @@ use repo_blobstore::RepoBlobstore;
 use scuba_ext::MononokeScubaSampleBuilder;
 
 use crate::lease::DerivedDataLease;
+use crate::version::FormatVersionCache;
 
 pub mod bubble;
 pub mod derive;
@@ -57,6 +58,7 @@ pub struct DerivedDataManagerInner {
     secondary: Option<SecondaryManagerData>,
     /// If this client is set, then derivation will be done remotely on derived data service
     derivation_service_client: Option<Arc<dyn DerivationClient>>,
+    format_versions: Arc<FormatVersionCache>,
 }
 
 pub struct DerivationAssignment {
@@ -110,6 +112,7 @@ impl DerivedDataManager {
                 scuba,
                 secondary: None,
                 derivation_service_client,
+                format_versions: Arc::new(FormatVersionCache::new()),
             }),
         }
     }
@@ -129,6 +132,7 @@ impl DerivedDataManager {
         Self {
             inner: Arc::new(DerivedDataManagerInner {
                 repo_blobstore,
+                format_versions: Arc::new(FormatVersionCache::new()),
                 ..self.inner.as_ref().clone()
             }),
         }
@@ -205,6 +209,10 @@ impl DerivedDataManager {
         &self.inner.lease
     }
 
+    pub(crate) fn format_versions(&self) -> &FormatVersionCache {
+        &self.inner.format_versions
+    }
+
     pub fn scuba(&self) -> &MononokeScubaSampleBuilder {
         &self.inner.scuba
     }
@@ -8,6 +8,8 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::future;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
 use std::time::Instant;
@@ -19,8 +21,11 @@ use anyhow::Result;
 use async_recursion::async_recursion;
 use blobstore::Loadable;
 use borrowed::borrowed;
+use cacheblob::CacheUsage;
 use cloned::cloned;
 use context::CoreContext;
+use context::SessionClass;
+use derived_data_service_if::DerivationPriority;
 use derived_data_service_if::DerivationType;
 use derived_data_service_if::DeriveRequest;
 use derived_data_service_if::DeriveResponse;
@@ -39,7 +44,10 @@ use futures::stream::TryStreamExt;
 use futures_stats::TimedFutureExt;
 use futures_stats::TimedTryFutureExt;
 use mononoke_types::ChangesetId;
+use request_profiler::start_profiling;
+use request_profiler::RequestClass;
 use slog::debug;
+use stats::prelude::*;
 use topo_sort::TopoSortedDagTraversal;
 use tunables::get_duration_from_tunable_or;
 use tunables::MononokeTunables;
@@ -51,6 +59,15 @@ use crate::derivable::BonsaiDerivable;
 use crate::derivable::DerivationDependencies;
 use crate::error::DerivationError;
 use crate::manager::util::DiscoveryStats;
+use crate::trace::millis;
+use crate::trace::DerivationTrace;
+use crate::version;
+use crate::version::INITIAL_FORMAT_VERSION;
+
+define_stats! {
+    prefix = "mononoke.derived_data";
+    abandoned_changesets: dynamic_timeseries("{}.abandoned_changesets.{}", (derived_data_type: &'static str, reason: &'static str); Rate, Sum),
+}
 
 #[derive(Clone, Copy)]
 pub enum BatchDeriveOptions {
@@ -78,6 +95,31 @@ impl BatchDeriveStats {
     }
 }
 
+/// The outcome of deriving a batch of changesets.
+#[derive(Debug)]
+pub struct BatchDeriveOutcome {
+    pub stats: BatchDeriveStats,
+    /// What was used to hold the derived blobs in memory until they were
+    /// written, if they were held in memory.
+    pub usage: Option<CacheUsage>,
+}
+
+impl BatchDeriveOutcome {
+    fn append(self, other: Self) -> anyhow::Result<Self> {
+        let usage = match (self.usage, other.usage) {
+            (Some(u1), Some(u2)) => Some(CacheUsage {
+                peak_memory_bytes: u1.peak_memory_bytes.max(u2.peak_memory_bytes),
+                blob_bytes: u1.blob_bytes + u2.blob_bytes,
+            }),
+            (usage, None) | (None, usage) => usage,
+        };
+        Ok(Self {
+            stats: self.stats.append(other.stats)?,
+            usage,
+        })
+    }
+}
+
 /// Trait to allow determination of rederivation.
 pub trait Rederivation: Send + Sync + 'static {
     /// Determine whether a changeset needs rederivation of
@@ -146,6 +188,13 @@ impl DerivedDataManager {
         Ok(())
     }
 
+    fn lease_key<Derivable>(&self, csid: ChangesetId) -> String
+    where
+        Derivable: BonsaiDerivable,
+    {
+        format!("repo{}.{}.{}", self.repo_id(), Derivable::NAME, csid)
+    }
+
     /// Perform derivation for a single changeset.
     /// Will fail in case data for parents changeset wasn't derived
     async fn perform_single_derivation<Derivable>(
@@ -167,9 +216,10 @@ impl DerivedDataManager {
             .log_with_msg("Waiting for derived data to be generated", None);
 
         debug!(ctx.logger(), "derive {} for {}", Derivable::NAME, csid);
-        let lease_key = format!("repo{}.{}.{}", self.repo_id(), Derivable::NAME, csid);
+        let lease_key = self.lease_key::<Derivable>(csid);
 
         let ctx = ctx.clone_and_reset();
+        let _profiling = start_profiling(RequestClass::Derivation);
 
         let (stats, result) = async {
             let bonsai = csid.load(&ctx, self.repo_blobstore()).map_err(Error::from);
@@ -183,7 +233,8 @@ impl DerivedDataManager {
                     Some(
                         self.lease()
                             .try_acquire_in_loop(&ctx, &lease_key, || async {
-                                Ok(Derivable::fetch(&ctx, derivation_ctx, csid)
+                                Ok(derivation_ctx
+                                    .fetch_derived::<Derivable>(&ctx, csid)
                                     .await?
                                     .is_some())
                             })
@@ -194,7 +245,8 @@ impl DerivedDataManager {
             let (bonsai, guard) = join!(bonsai, guard);
             if matches!(guard, Some(Ok(None))) {
                 // Something else completed derivation
-                let derived = Derivable::fetch(&ctx, derivation_ctx, csid)
+                let derived = derivation_ctx
+                    .fetch_derived::<Derivable>(&ctx, csid)
                     .await?
                     .ok_or_else(|| {
                         anyhow!("derivation completed elsewhere but data could not be fetched")
@@ -231,11 +283,18 @@ impl DerivedDataManager {
 
                 // We may now store the mapping, and flush the blobstore to
                 // ensure the mapping is persisted.
-                let (persist_stats, persisted) = derived
-                    .clone()
-                    .store_mapping(&ctx, derivation_ctx, csid)
-                    .timed()
-                    .await;
+                let (persist_stats, persisted) = async {
+                    derived
+                        .clone()
+                        .store_mapping(&ctx, derivation_ctx, csid)
+                        .await?;
+                    // The format version is only recorded once the mapping
+                    // it describes has been persisted.
+                    version::store_format_version::<Derivable>(&ctx, derivation_ctx, csid)
+                        .await
+                }
+                .timed()
+                .await;
 
                 self.log_mapping_insertion(
                     &ctx,
@@ -247,6 +306,13 @@ impl DerivedDataManager {
 
                 persisted?;
 
+                if let Some(tracer) = derivation_ctx.tracer() {
+                    tracer.record_times(
+                        derive_stats.completion_time,
+                        persist_stats.completion_time,
+                    );
+                }
+
                 Ok((csid, derived))
             }
         }
@@ -262,16 +328,21 @@ impl DerivedDataManager {
     }
 
     /// Find ancestors of the target changeset that are underived.
+    ///
+    /// If `max_underived` is set, fails as soon as more underived changesets
+    /// are found than it allows.
     async fn find_underived_inner<Derivable>(
         &self,
         ctx: &CoreContext,
         csid: ChangesetId,
         limit: Option<u64>,
+        max_underived: Option<MaxUnderived>,
         derivation_ctx: &DerivationContext,
     ) -> Result<HashMap<ChangesetId, Vec<ChangesetId>>>
     where
         Derivable: BonsaiDerivable,
     {
+        let underived_count = &AtomicU64::new(0);
         let underived_commits_parents: HashMap<ChangesetId, Vec<ChangesetId>> =
             bounded_traversal::bounded_traversal_dag_limited(
                     100,
@@ -285,6 +356,12 @@ impl DerivedDataManager {
                             {
                                 Ok((None, Vec::new()))
                             } else {
+                                let count = underived_count.fetch_add(1, Ordering::Relaxed) + 1;
+                                if let Some(max_underived) = max_underived {
+                                    if count > max_underived.count() {
+                                        return Err(max_underived.exceeded::<Derivable>());
+                                    }
+                                }
                                 let parents = self
                                     .changesets()
                                     .get(ctx, csid)
@@ -341,10 +418,27 @@ impl DerivedDataManager {
     where
         Derivable: BonsaiDerivable,
     {
+        // Data at an incompatible format version is re-derived lazily, but
+        // only as long as that doesn't mean re-deriving much of the history.
+        // Traced derivations are bounded too.
+        let max_underived = [
+            version::may_be_incompatible::<Derivable>()
+                .then(|| MaxUnderived::Rederivation(self.max_lazy_rederivations())),
+            derivation_ctx.max_traced_underived().map(MaxUnderived::Traced),
+        ]
+        .into_iter()
+        .flatten()
+        .min_by_key(MaxUnderived::count);
         let (find_underived_stats, dag_traversal) = async {
-            self.find_underived_inner::<Derivable>(ctx, target_csid, None, derivation_ctx.as_ref())
-                .await
-                .context("Finding underived commits")
+            self.find_underived_inner::<Derivable>(
+                ctx,
+                target_csid,
+                None,
+                max_underived,
+                derivation_ctx.as_ref(),
+            )
+            .await
+            .context("Finding underived commits")
         }
         .try_timed()
         .await?;
@@ -353,27 +447,54 @@ impl DerivedDataManager {
             find_underived_completion_time: find_underived_stats.completion_time,
             commits_discovered: dag_traversal.len() as u32,
         });
+        // Traces record which parents of each changeset had to be derived
+        // before it.
+        let underived_parents = derivation_ctx.is_tracing().then(|| dag_traversal.clone());
         let mut dag_traversal = TopoSortedDagTraversal::new(dag_traversal);
 
         let buffer_size = self.max_parallel_derivations();
         let mut derivations = FuturesUnordered::new();
         let mut completed_count = 0;
         let mut target_derived = None;
+        let mut traces = Vec::new();
         while !dag_traversal.is_empty() || !derivations.is_empty() {
             let free = buffer_size.saturating_sub(derivations.len());
             derivations.extend(dag_traversal.drain(free).map(|csid| {
-                cloned!(ctx, derivation_ctx);
+                cloned!(ctx);
+                let (derivation_ctx, tracer) = match derivation_ctx.for_traced_changeset() {
+                    Some((derivation_ctx, tracer)) => (Arc::new(derivation_ctx), Some(tracer)),
+                    None => (derivation_ctx.clone(), None),
+                };
                 let manager = self.clone();
                 let stats = stats.clone();
+                // Spawned derivations outlive the request that wanted them
+                // unless every request waiting for them is abandoned.
                 let derivation = async move {
-                    manager
-                        .perform_single_derivation(&ctx, &derivation_ctx, csid, &stats)
+                    let lease_key = manager.lease_key::<Derivable>(csid);
+                    let derivation =
+                        manager.perform_single_derivation(&ctx, &derivation_ctx, csid, &stats);
+                    let (csid, derived) = manager
+                        .lease()
+                        .run_until_all_abandoned(&ctx, &lease_key, derivation)
                         .await
+                        .map_err(|abandoned| {
+                            STATS::abandoned_changesets
+                                .add_value(1, (Derivable::NAME, abandoned.reason()));
+                            abandoned
+                        })??;
+                    Ok::<_, Error>((csid, derived, tracer))
                 };
                 tokio::spawn(derivation).map_err(Error::from)
             }));
             if let Some(derivation_result) = derivations.try_next().await? {
-                let (derived_csid, derived) = derivation_result?;
+                let (derived_csid, derived, tracer) = derivation_result?;
+                if let (Some(tracer), Some(underived_parents)) = (tracer, &underived_parents) {
+                    let parents = underived_parents
+                        .get(&derived_csid)
+                        .cloned()
+                        .unwrap_or_default();
+                    traces.push(tracer.finish(derived_csid, parents));
+                }
                 if derived_csid == target_csid {
                     target_derived = Some(derived);
                 }
@@ -395,10 +516,18 @@ impl DerivedDataManager {
             }
         };
 
+        let trace = derivation_ctx.is_tracing().then(|| DerivationTrace {
+            derived_data_type: Derivable::NAME.to_string(),
+            changeset_id: target_csid,
+            find_underived_ms: millis(find_underived_stats.completion_time),
+            derived: traces,
+        });
+
         Ok(DerivationOutcome {
             derived,
             count: completed_count,
             find_underived_time: find_underived_stats.completion_time,
+            trace,
         })
     }
 
@@ -432,7 +561,7 @@ impl DerivedDataManager {
         self.check_enabled::<Derivable>()?;
         let derivation_ctx = self.derivation_context(rederivation);
         let underived = self
-            .find_underived_inner::<Derivable>(ctx, csid, limit, &derivation_ctx)
+            .find_underived_inner::<Derivable>(ctx, csid, limit, None, &derivation_ctx)
             .await?;
         Ok(underived.len() as u64)
     }
@@ -478,17 +607,34 @@ impl DerivedDataManager {
     {
         self.check_enabled::<Derivable>()?;
         let derivation_ctx = self.derivation_context(rederivation);
-        self.find_underived_inner::<Derivable>(ctx, csid, limit, &derivation_ctx)
+        self.find_underived_inner::<Derivable>(ctx, csid, limit, None, &derivation_ctx)
             .await
     }
 
     /// Derive or retrieve derived data for a changeset.
+    ///
+    /// Derivation is abandoned if the deadline of the context passes or its
+    /// client disconnects first.
     pub async fn derive<Derivable>(
         &self,
         ctx: &CoreContext,
         csid: ChangesetId,
         rederivation: Option<Arc<dyn Rederivation>>,
     ) -> Result<Derivable, DerivationError>
+    where
+        Derivable: BonsaiDerivable,
+    {
+        ctx.run_until_abandoned(self.derive_inner(ctx, csid, rederivation))
+            .await
+            .map_err(|e| DerivationError::Error(e.into()))?
+    }
+
+    async fn derive_inner<Derivable>(
+        &self,
+        ctx: &CoreContext,
+        csid: ChangesetId,
+        rederivation: Option<Arc<dyn Rederivation>>,
+    ) -> Result<Derivable, DerivationError>
     where
         Derivable: BonsaiDerivable,
     {
@@ -516,6 +662,7 @@ impl DerivedDataManager {
                 changeset_id: csid.as_ref().to_vec(),
                 config_name: self.config_name(),
                 derivation_type: DerivationType::derive_underived(DeriveUnderived {}),
+                priority: Some(derivation_priority(ctx)),
             };
             let mut request_state = DerivationState::NotRequested;
             while let Some(true) =
@@ -654,6 +801,55 @@ impl DerivedDataManager {
         }
     }
 
+    /// Derive data for a changeset, and trace the derivations that this
+    /// required.
+    ///
+    /// The trace records which ancestors had to be derived first, which
+    /// other derived data types their derivation depended on, and how long
+    /// each derivation took and how much it wrote.  Traced derivations are
+    /// always performed locally, as the derivation service can't trace them.
+    ///
+    /// Fails without deriving anything if more than `max_underived`
+    /// changesets would have to be derived, either for this changeset or for
+    /// any of its dependencies.
+    pub async fn derive_traced<Derivable>(
+        &self,
+        ctx: &CoreContext,
+        csid: ChangesetId,
+        rederivation: Option<Arc<dyn Rederivation>>,
+        max_underived: u64,
+    ) -> Result<(Derivable, DerivationTrace), DerivationError>
+    where
+        Derivable: BonsaiDerivable,
+    {
+        self.get_manager(ctx, csid)
+            .await?
+            .derive_traced_impl::<Derivable>(ctx, csid, rederivation, max_underived)
+            .await
+    }
+
+    async fn derive_traced_impl<Derivable>(
+        &self,
+        ctx: &CoreContext,
+        csid: ChangesetId,
+        rederivation: Option<Arc<dyn Rederivation>>,
+        max_underived: u64,
+    ) -> Result<(Derivable, DerivationTrace), DerivationError>
+    where
+        Derivable: BonsaiDerivable,
+    {
+        self.check_enabled::<Derivable>()?;
+        let mut derivation_ctx = self.derivation_context(rederivation);
+        derivation_ctx.enable_tracing(max_underived);
+        let outcome = self
+            .derive_underived::<Derivable>(ctx, Arc::new(derivation_ctx), csid)
+            .await?;
+        let trace = outcome
+            .trace
+            .ok_or_else(|| anyhow!("derivation was not traced"))?;
+        Ok((outcome.derived, trace))
+    }
+
     #[async_recursion]
     /// Derive data for exactly a batch of changesets.
     ///
@@ -671,7 +867,7 @@ impl DerivedDataManager {
         csids: Vec<ChangesetId>,
         batch_options: BatchDeriveOptions,
         rederivation: Option<Arc<dyn Rederivation>>,
-    ) -> Result<BatchDeriveStats, DerivationError>
+    ) -> Result<BatchDeriveOutcome, DerivationError>
     where
         Derivable: BonsaiDerivable,
     {
@@ -696,11 +892,14 @@ impl DerivedDataManager {
         } else {
             (
                 csids,
-                future::ready(Ok(match batch_options {
-                    BatchDeriveOptions::Serial => BatchDeriveStats::Serial(vec![]),
-                    BatchDeriveOptions::Parallel { .. } => {
-                        BatchDeriveStats::Parallel(Duration::ZERO)
-                    }
+                future::ready(Ok(BatchDeriveOutcome {
+                    stats: match batch_options {
+                        BatchDeriveOptions::Serial => BatchDeriveStats::Serial(vec![]),
+                        BatchDeriveOptions::Parallel { .. } => {
+                            BatchDeriveStats::Parallel(Duration::ZERO)
+                        }
+                    },
+                    usage: None,
                 }))
                 .right_future(),
             )
@@ -877,8 +1076,10 @@ impl DerivedDataManager {
             // Flush the blobstore.  If it has been set up to cache writes, these
             // must be flushed before we write the mapping.
             let (stats, _) = derivation_ctx.flush(ctx).try_timed().await?;
+            let usage = derivation_ctx.write_cache_usage();
             scuba
                 .add_future_stats(&stats)
+                .add_opt("blob_bytes", usage.map(|usage| usage.blob_bytes))
                 .log_with_msg("Flushed derived blobs", None);
 
             let mut derivation_ctx = self.derivation_context(rederivation.clone());
@@ -897,6 +1098,18 @@ impl DerivedDataManager {
                     .await?;
 
                 derivation_ctx.flush(ctx).await?;
+
+                // Format versions are only recorded once the mappings they
+                // describe have been persisted.
+                stream::iter(csids.iter().copied())
+                    .map(|csid| {
+                        version::store_format_version::<Derivable>(ctx, derivation_ctx_ref, csid)
+                    })
+                    .buffer_unordered(100)
+                    .try_for_each(|_| async { Ok(()) })
+                    .await?;
+                derivation_ctx.flush(ctx).await?;
+
                 if let Some(rederivation) = rederivation {
                     for csid in csids {
                         rederivation.mark_derived(Derivable::NAME, csid);
@@ -921,7 +1134,10 @@ impl DerivedDataManager {
                 .add_future_stats(&persist_stats)
                 .log_with_msg("Flushed mapping", None);
 
-            Ok(batch_stats)
+            Ok(BatchDeriveOutcome {
+                stats: batch_stats,
+                usage,
+            })
         }
         .timed()
         .await;
@@ -934,9 +1150,9 @@ impl DerivedDataManager {
             result.as_ref().err(),
         );
 
-        let batch_stats = result?;
+        let outcome = result?;
 
-        Ok(batch_stats.append(secondary_derivation.await?)?)
+        Ok(outcome.append(secondary_derivation.await?)?)
     }
 
     /// Fetch derived data for a changeset if it has previously been derived.
@@ -1009,6 +1225,38 @@ impl DerivedDataManager {
         derived.extend(secondary_derivation.await?);
         Ok(derived)
     }
+
+    /// Find which of a batch of changesets have data derived at an older
+    /// format version than the current one, whether or not it is still
+    /// compatible, so that they can be upgraded by a backfill.
+    ///
+    /// Changesets that have not been derived are omitted.
+    pub async fn find_outdated<Derivable>(
+        &self,
+        ctx: &CoreContext,
+        csids: Vec<ChangesetId>,
+    ) -> Result<Vec<ChangesetId>, DerivationError>
+    where
+        Derivable: BonsaiDerivable,
+    {
+        self.check_enabled::<Derivable>()?;
+        if Derivable::FORMAT_VERSION == INITIAL_FORMAT_VERSION {
+            return Ok(Vec::new());
+        }
+        let derivation_ctx = self.derivation_context(None);
+        let derived = Derivable::fetch_batch(ctx, &derivation_ctx, &csids).await?;
+        let format_versions =
+            version::fetch_format_versions::<Derivable>(ctx, &derivation_ctx, derived.into_keys())
+                .await?;
+        Ok(csids
+            .into_iter()
+            .filter(|csid| {
+                format_versions
+                    .get(csid)
+                    .map_or(false, |version| *version < Derivable::FORMAT_VERSION)
+            })
+            .collect())
+    }
 }
 
 pub(super) struct DerivationOutcome<Derivable> {
@@ -1020,6 +1268,9 @@ pub(super) struct DerivationOutcome<Derivable> {
 
     /// Time take to find the underived changesets.
     pub(super) find_underived_time: Duration,
+
+    /// The trace of the derivation, if it was traced.
+    pub(super) trace: Option<DerivationTrace>,
 }
 
 enum DerivationState {
@@ -1027,6 +1278,59 @@ enum DerivationState {
     InProgress,
 }
 
+/// Bound on the number of underived changesets a derivation may derive.
+#[derive(Clone, Copy, Debug)]
+enum MaxUnderived {
+    /// The data derived at old format versions is incompatible, and only
+    /// this many changesets may be re-derived lazily.
+    Rederivation(u64),
+    /// The derivation is traced, and may derive only this many changesets.
+    Traced(u64),
+}
+
+impl MaxUnderived {
+    fn count(&self) -> u64 {
+        match self {
+            MaxUnderived::Rederivation(count) | MaxUnderived::Traced(count) => *count,
+        }
+    }
+
+    /// Error for a derivation that would derive more than this allows.
+    fn exceeded<Derivable>(&self) -> Error
+    where
+        Derivable: BonsaiDerivable,
+    {
+        match self {
+            MaxUnderived::Rederivation(count) => anyhow!(
+                concat!(
+                    "more than {} changesets must be derived, as {} data derived at old ",
+                    "format versions is incompatible: backfill them with --upgrade-format ",
+                    "instead",
+                ),
+                count,
+                Derivable::NAME,
+            ),
+            MaxUnderived::Traced(count) => anyhow!(
+                "more than {} changesets must be derived to trace {} derivation: backfill \
+                them first",
+                count,
+                Derivable::NAME,
+            ),
+        }
+    }
+}
+
+/// Priority for remote derivation requests made by this session.
+fn derivation_priority(ctx: &CoreContext) -> DerivationPriority {
+    match ctx.session().session_class() {
+        SessionClass::UserWaiting => DerivationPriority::HIGH,
+        SessionClass::BackgroundUnlessTooSlow | SessionClass::WarmBookmarksCache => {
+            DerivationPriority::NORMAL
+        }
+        SessionClass::Background => DerivationPriority::LOW,
+    }
+}
+
 fn emergency_disabled(repo_name: &str, derivable_name: &str) -> bool {
     let disabled_for_repo = tunables::tunables()
         .by_repo_all_derived_data_disabled(repo_name)
@@ -90,4 +90,26 @@ impl DerivedDataManager {
             10
         }
     }
+
+    /// Maximum number of changesets that a single derivation of a type
+    /// with incompatible old data may derive.
+    pub(super) fn max_lazy_rederivations(&self) -> u64 {
+        let max_lazy_rederivations = tunables::tunables()
+            .derived_data_max_lazy_rederivations()
+            .unwrap_or_default();
+        if max_lazy_rederivations > 0 {
+            max_lazy_rederivations as u64
+        } else {
+            1000
+        }
+    }
+
+    /// How many bytes of derived blobs may be held in memory while a batch
+    /// is being derived before they are written out, if this is limited.
+    pub(crate) fn write_cache_memory_limit(&self) -> Option<usize> {
+        let max_bytes = tunables::tunables()
+            .derived_data_write_cache_max_bytes()
+            .unwrap_or_default();
+        (max_bytes > 0).then_some(max_bytes as usize)
+    }
 }
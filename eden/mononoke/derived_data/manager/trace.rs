@@ -0,0 +1,157 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Traces of derivations.
+//!
+//! Deriving data for a changeset may first require deriving data for many of
+//! its ancestors, and for other derived data types that it depends on.  A
+//! trace records that tree of derivations, with how long each one took and
+//! what it wrote, to explain why a derivation took as long as it did.
+
+use std::fmt;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use blobstore::Blobstore;
+use blobstore::BlobstoreBytes;
+use blobstore::BlobstoreGetData;
+use blobstore::BlobstoreIsPresent;
+use context::CoreContext;
+use mononoke_types::ChangesetId;
+use serde::Serialize;
+
+/// The trace of deriving a derived data type for a changeset.
+#[derive(Clone, Debug, Serialize)]
+pub struct DerivationTrace {
+    pub derived_data_type: String,
+    pub changeset_id: ChangesetId,
+    /// Time taken to find the underived ancestors of the changeset.
+    pub find_underived_ms: u64,
+    /// The changeset and its ancestors that had to be derived, in the order
+    /// their derivations completed.  Empty if it was already derived.
+    pub derived: Vec<ChangesetTrace>,
+}
+
+/// The trace of deriving a derived data type for a single changeset, once
+/// its parents were derived.
+#[derive(Clone, Debug, Serialize)]
+pub struct ChangesetTrace {
+    pub changeset_id: ChangesetId,
+    /// The parents of the changeset that had to be derived before it.
+    pub underived_parents: Vec<ChangesetId>,
+    /// Time taken to derive the data, unset if it was derived elsewhere
+    /// while waiting for the lease.
+    pub derive_ms: Option<u64>,
+    /// Time taken to store the mapping, unset if the data was derived
+    /// elsewhere.
+    pub store_mapping_ms: Option<u64>,
+    pub blobs_written: u64,
+    pub blob_bytes_written: u64,
+    /// The derivations of other derived data types that this derivation
+    /// depended on.
+    pub dependencies: Vec<DerivationTrace>,
+}
+
+/// Collects the trace of deriving a single changeset.
+#[derive(Debug, Default)]
+pub(crate) struct ChangesetTracer {
+    times: Mutex<Option<(Duration, Duration)>>,
+    blobs_written: AtomicU64,
+    blob_bytes_written: AtomicU64,
+    dependencies: Mutex<Vec<DerivationTrace>>,
+}
+
+impl ChangesetTracer {
+    pub(crate) fn record_times(&self, derive: Duration, store_mapping: Duration) {
+        *self.times.lock().unwrap() = Some((derive, store_mapping));
+    }
+
+    pub(crate) fn record_dependency(&self, trace: DerivationTrace) {
+        self.dependencies.lock().unwrap().push(trace);
+    }
+
+    fn record_write(&self, bytes: usize) {
+        self.blobs_written.fetch_add(1, Ordering::Relaxed);
+        self.blob_bytes_written
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn finish(
+        &self,
+        changeset_id: ChangesetId,
+        underived_parents: Vec<ChangesetId>,
+    ) -> ChangesetTrace {
+        let times = *self.times.lock().unwrap();
+        ChangesetTrace {
+            changeset_id,
+            underived_parents,
+            derive_ms: times.map(|(derive, _)| millis(derive)),
+            store_mapping_ms: times.map(|(_, store_mapping)| millis(store_mapping)),
+            blobs_written: self.blobs_written.load(Ordering::Relaxed),
+            blob_bytes_written: self.blob_bytes_written.load(Ordering::Relaxed),
+            dependencies: std::mem::take(&mut *self.dependencies.lock().unwrap()),
+        }
+    }
+}
+
+pub(crate) fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+/// Blobstore that records the writes made while deriving a changeset in its
+/// trace.
+#[derive(Debug)]
+pub(crate) struct TracingBlobstore {
+    inner: Arc<dyn Blobstore>,
+    tracer: Arc<ChangesetTracer>,
+}
+
+impl TracingBlobstore {
+    pub(crate) fn new(inner: Arc<dyn Blobstore>, tracer: Arc<ChangesetTracer>) -> Self {
+        Self { inner, tracer }
+    }
+}
+
+impl fmt::Display for TracingBlobstore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TracingBlobstore<{}>", self.inner)
+    }
+}
+
+#[async_trait]
+impl Blobstore for TracingBlobstore {
+    async fn get<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<Option<BlobstoreGetData>> {
+        self.inner.get(ctx, key).await
+    }
+
+    async fn put<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<()> {
+        self.tracer.record_write(value.len());
+        self.inner.put(ctx, key, value).await
+    }
+
+    async fn is_present<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<BlobstoreIsPresent> {
+        self.inner.is_present(ctx, key).await
+    }
+}
@@ -0,0 +1,173 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Format versions of derived data.
+//!
+//! The format version that each changeset's data was derived at is recorded
+//! next to its mapping.  When a derived data type's format version is
+//! bumped, data derived at older versions continues to be served as long as
+//! it is compatible with the new code.  Incompatible data is re-derived,
+//! either lazily when it is requested, or in ranges by a backfill.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::Context;
+use anyhow::Result;
+use blobstore::BlobstoreBytes;
+use context::CoreContext;
+use futures::stream;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
+use mononoke_types::ChangesetId;
+
+use crate::context::DerivationContext;
+use crate::derivable::BonsaiDerivable;
+
+/// Format version of data that was derived before any version was recorded.
+pub const INITIAL_FORMAT_VERSION: u32 = 1;
+
+/// The most format versions that are cached before the cache is emptied.
+const MAX_CACHED_FORMAT_VERSIONS: usize = 1_000_000;
+
+/// Format versions of data that can still be served, by the key of their
+/// record.
+///
+/// Checking the version of data is part of every fetch of a type that may
+/// be incompatible, so versions are only fetched once.  Compatible data is
+/// only ever replaced by data at a newer version, so a cached version can be
+/// out of date, but never wrongly compatible.
+pub(crate) struct FormatVersionCache {
+    versions: Mutex<HashMap<String, u32>>,
+}
+
+impl FormatVersionCache {
+    pub(crate) fn new() -> Self {
+        Self {
+            versions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<u32> {
+        self.versions
+            .lock()
+            .expect("lock poisoned")
+            .get(key)
+            .copied()
+    }
+
+    fn insert<Derivable>(&self, key: String, version: u32)
+    where
+        Derivable: BonsaiDerivable,
+    {
+        if version < Derivable::MIN_COMPATIBLE_FORMAT_VERSION {
+            return;
+        }
+        let mut versions = self.versions.lock().expect("lock poisoned");
+        if versions.len() >= MAX_CACHED_FORMAT_VERSIONS {
+            versions.clear();
+        }
+        versions.insert(key, version);
+    }
+}
+
+fn format_version_key<Derivable>(derivation_ctx: &DerivationContext, csid: ChangesetId) -> String
+where
+    Derivable: BonsaiDerivable,
+{
+    format!(
+        "{}derived_format_version.{}.{}",
+        derivation_ctx.mapping_key_prefix::<Derivable>(),
+        Derivable::NAME,
+        csid,
+    )
+}
+
+/// Whether data of this type may have been derived at a format version that
+/// can no longer be served.
+pub(crate) fn may_be_incompatible<Derivable>() -> bool
+where
+    Derivable: BonsaiDerivable,
+{
+    Derivable::MIN_COMPATIBLE_FORMAT_VERSION > INITIAL_FORMAT_VERSION
+}
+
+/// Fetch the format version that a changeset's data was derived at.  The
+/// data must already have been derived.
+pub(crate) async fn fetch_format_version<Derivable>(
+    ctx: &CoreContext,
+    derivation_ctx: &DerivationContext,
+    csid: ChangesetId,
+) -> Result<u32>
+where
+    Derivable: BonsaiDerivable,
+{
+    let key = format_version_key::<Derivable>(derivation_ctx, csid);
+    let cache = derivation_ctx.manager().format_versions();
+    if let Some(version) = cache.get(&key) {
+        return Ok(version);
+    }
+    let version = match derivation_ctx.blobstore().get(ctx, &key).await? {
+        Some(data) => std::str::from_utf8(data.as_raw_bytes())
+            .ok()
+            .and_then(|version| version.parse().ok())
+            .with_context(|| format!("invalid format version in {}", key))?,
+        None => INITIAL_FORMAT_VERSION,
+    };
+    cache.insert::<Derivable>(key, version);
+    Ok(version)
+}
+
+/// Fetch the format versions that a batch of changesets' data was derived
+/// at.  The data must already have been derived.
+pub(crate) async fn fetch_format_versions<Derivable>(
+    ctx: &CoreContext,
+    derivation_ctx: &DerivationContext,
+    csids: impl IntoIterator<Item = ChangesetId>,
+) -> Result<HashMap<ChangesetId, u32>>
+where
+    Derivable: BonsaiDerivable,
+{
+    stream::iter(csids)
+        .map(|csid| async move {
+            let version = fetch_format_version::<Derivable>(ctx, derivation_ctx, csid)
+                .await?;
+            Ok::<_, anyhow::Error>((csid, version))
+        })
+        .buffer_unordered(100)
+        .try_collect()
+        .await
+}
+
+/// Record that a changeset's data has been derived at the current format
+/// version.  This must only be called once the mapping has been persisted,
+/// so that the record never describes data that hasn't been written.
+pub(crate) async fn store_format_version<Derivable>(
+    ctx: &CoreContext,
+    derivation_ctx: &DerivationContext,
+    csid: ChangesetId,
+) -> Result<()>
+where
+    Derivable: BonsaiDerivable,
+{
+    // Data without a record is at the initial version, so types that have
+    // never been bumped don't need a record for every changeset.
+    if Derivable::FORMAT_VERSION == INITIAL_FORMAT_VERSION {
+        return Ok(());
+    }
+    let key = format_version_key::<Derivable>(derivation_ctx, csid);
+    let version = Derivable::FORMAT_VERSION.to_string();
+    derivation_ctx
+        .blobstore()
+        .put(ctx, key.clone(), BlobstoreBytes::from_bytes(version))
+        .await?;
+    derivation_ctx
+        .manager()
+        .format_versions()
+        .insert::<Derivable>(key, Derivable::FORMAT_VERSION);
+    Ok(())
+}
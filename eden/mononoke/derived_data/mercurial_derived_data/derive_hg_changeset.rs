@@ -465,6 +465,9 @@ async fn generate_hg_changeset(
         message: bcs.message().to_string(),
     };
     metadata.record_step_parents(step_parents.map(|blob| blob.get_changeset_id()));
+    if let Some(extras) = bcs.extras() {
+        metadata.record_extras(extras);
+    }
     if options.set_committer_field {
         match (bcs.committer(), bcs.committer_date()) {
             (Some(committer), Some(date)) => {
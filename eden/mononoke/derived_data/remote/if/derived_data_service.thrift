@@ -38,12 +38,28 @@ enum RequestStatus {
   DOES_NOT_EXIST = 2,
 }
 
+/// Priority of a derivation request, based on the session class of the
+/// client.  It is recorded with the request so that the service can tell
+/// waiting users from background work, but requests are not yet ordered
+/// by it.
+enum DerivationPriority {
+  /// Background work such as backfilling
+  LOW = 0,
+  /// Background work that should not be delayed for long
+  NORMAL = 1,
+  /// Someone is waiting for the derivation to complete
+  HIGH = 2,
+}
+
 struct DeriveRequest {
   1: string repo_name;
   2: DerivedDataType derived_data_type;
   3: binary changeset_id;
   4: string config_name;
   5: DerivationType derivation_type;
+  /// Missing for requests from older clients, which should be treated as
+  /// HIGH priority.
+  6: optional DerivationPriority priority;
 } (rust.exhaustive)
 
 struct DeriveResponse {
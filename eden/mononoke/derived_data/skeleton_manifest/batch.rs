@@ -7,6 +7,7 @@
 
 use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::collections::HashSet;
 
 use anyhow::Context;
 use anyhow::Error;
@@ -15,16 +16,20 @@ use borrowed::borrowed;
 use context::CoreContext;
 use derived_data::batch::split_batch_in_linear_stacks;
 use derived_data::batch::FileConflicts;
+use derived_data::batch::LinearStack;
 use derived_data::batch::StackItem;
 use derived_data_manager::BonsaiDerivable;
 use derived_data_manager::DerivationContext;
+use futures::stream;
 use futures::stream::FuturesOrdered;
+use futures::stream::StreamExt;
 use futures::stream::TryStreamExt;
 use itertools::Itertools;
 use mononoke_types::ChangesetId;
 use stats::prelude::*;
 
 use crate::derive::derive_skeleton_manifest_stack;
+use crate::derive::SkeletonManifestCache;
 use crate::RootSkeletonManifestId;
 use crate::SkeletonManifestId;
 
@@ -33,12 +38,18 @@ define_stats! {
     new_parallel: timeseries(Rate, Sum),
 }
 
+/// Maximum number of independent linear stacks that are derived concurrently.
+const STACK_CONCURRENCY: usize = 10;
+
 /// Derive a batch of skeleton manifests, potentially doing it faster than
 /// deriving skeleton manifests sequentially.  The primary purpose of this is
 /// to be used while backfilling skeleton manifests for a large repository.
 ///
 /// This is the same mechanism as fsnodes, see `derive_fsnode_in_batch` for
-/// more details.
+/// more details.  Additionally, linear stacks that don't depend on each
+/// other (e.g. sibling branches from the same commit) are derived
+/// concurrently, and skeleton manifests loaded for one stack are shared with
+/// the others in the batch.
 pub async fn derive_skeleton_manifests_in_batch(
     ctx: &CoreContext,
     derivation_ctx: &DerivationContext,
@@ -52,51 +63,94 @@ pub async fn derive_skeleton_manifests_in_batch(
         FileConflicts::ChangeDelete.into(),
     )
     .await?;
+    let cache = SkeletonManifestCache::new();
     let mut res: HashMap<ChangesetId, RootSkeletonManifestId> = HashMap::new();
-    for linear_stack in linear_stacks {
-        // Fetch the parent skeleton manifests, either from a previous
-        // iteration of this loop (which will have stored the mapping in
-        // `res`, or from the main mapping, where they should already be
-        // derived.
-        let parent_skeleton_manifests = linear_stack
-            .parents
-            .into_iter()
-            .map(|p| {
-                borrowed!(res);
+    for wave in independent_stacks(linear_stacks) {
+        let derived = stream::iter(wave)
+            .map(|linear_stack| {
+                borrowed!(cache, res);
                 async move {
-                    anyhow::Result::<_>::Ok(
-                        match res.get(&p) {
-                            Some(sk_mf_id) => sk_mf_id.clone(),
-                            None => derivation_ctx.fetch_dependency(ctx, p).await?,
-                        }
-                        .into_skeleton_manifest_id(),
+                    // Fetch the parent skeleton manifests, either from a
+                    // previous wave (which will have stored the mapping in
+                    // `res`), or from the main mapping, where they should
+                    // already be derived.
+                    let parent_skeleton_manifests = linear_stack
+                        .parents
+                        .iter()
+                        .map(|p| async move {
+                            anyhow::Result::<_>::Ok(
+                                match res.get(p) {
+                                    Some(sk_mf_id) => sk_mf_id.clone(),
+                                    None => derivation_ctx.fetch_dependency(ctx, *p).await?,
+                                }
+                                .into_skeleton_manifest_id(),
+                            )
+                        })
+                        .collect::<FuturesOrdered<_>>()
+                        .try_collect::<Vec<_>>()
+                        .await?;
+                    STATS::new_parallel.add_value(1);
+                    // Commits in the stack only depend on each other and on
+                    // the parents of the stack.
+                    let mut derived = linear_stack
+                        .parents
+                        .iter()
+                        .filter_map(|p| Some((*p, res.get(p)?.clone())))
+                        .collect::<HashMap<_, _>>();
+                    new_batch_derivation(
+                        ctx,
+                        derivation_ctx,
+                        parent_skeleton_manifests,
+                        gap_size,
+                        linear_stack.stack_items,
+                        cache,
+                        &mut derived,
                     )
+                    .await?;
+                    anyhow::Ok(derived)
                 }
             })
-            .collect::<FuturesOrdered<_>>()
+            .buffered(STACK_CONCURRENCY)
             .try_collect::<Vec<_>>()
             .await?;
-        STATS::new_parallel.add_value(1);
-        new_batch_derivation(
-            ctx,
-            derivation_ctx,
-            parent_skeleton_manifests,
-            gap_size,
-            linear_stack.stack_items,
-            &mut res,
-        )
-        .await?;
+        for derived in derived {
+            res.extend(derived);
+        }
     }
 
     Ok(res)
 }
 
+/// Group linear stacks into waves of stacks that can be derived
+/// concurrently, as none of them depends on another stack in the same wave.
+fn independent_stacks(linear_stacks: Vec<LinearStack>) -> Vec<Vec<LinearStack>> {
+    let mut waves: Vec<Vec<LinearStack>> = Vec::new();
+    let mut wave_cs_ids = HashSet::new();
+    for linear_stack in linear_stacks {
+        let depends_on_wave = linear_stack
+            .parents
+            .iter()
+            .any(|p| wave_cs_ids.contains(p));
+        if waves.is_empty() || depends_on_wave {
+            waves.push(Vec::new());
+            wave_cs_ids.clear();
+        }
+        wave_cs_ids.extend(linear_stack.stack_items.iter().map(|item| item.cs_id));
+        waves
+            .last_mut()
+            .expect("a wave was just added")
+            .push(linear_stack);
+    }
+    waves
+}
+
 pub async fn new_batch_derivation(
     ctx: &CoreContext,
     derivation_ctx: &DerivationContext,
     parent_skeleton_manifests: Vec<SkeletonManifestId>,
     gap_size: Option<usize>,
     file_changes: Vec<StackItem>,
+    cache: &SkeletonManifestCache,
     already_derived: &mut HashMap<ChangesetId, RootSkeletonManifestId>,
 ) -> Result<(), Error> {
     if parent_skeleton_manifests.len() > 1 {
@@ -145,6 +199,7 @@ pub async fn new_batch_derivation(
             derivation_ctx,
             file_changes,
             parent_skeleton_manifests.get(0).copied(),
+            cache.clone(),
         )
         .await
         .with_context(|| format!("failed deriving stack of {:?} to {:?}", first, last,))?;
@@ -313,6 +368,67 @@ mod test {
         Ok(repo)
     }
 
+    #[fbinit::test]
+    async fn batch_derive_with_siblings(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let dag = r##"
+              B-C
+             /
+            A-D-E
+             \
+              F
+            "##;
+        let heads = ["C", "E", "F"];
+
+        let new_batch = {
+            let repo: TestRepo = TestRepoFactory::new(fb)?.build()?;
+            let commit_map = create_from_dag(&ctx, &repo, dag).await?;
+            let cs_ids = ["A", "B", "C", "D", "E", "F"]
+                .iter()
+                .map(|name| commit_map[*name])
+                .collect();
+            let manager = repo.repo_derived_data().manager();
+            manager
+                .derive_exactly_batch::<RootSkeletonManifestId>(
+                    &ctx,
+                    cs_ids,
+                    BatchDeriveOptions::Parallel { gap_size: None },
+                    None,
+                )
+                .await?;
+            let mut derived = Vec::new();
+            for head in heads {
+                derived.push(
+                    manager
+                        .fetch_derived::<RootSkeletonManifestId>(&ctx, commit_map[head], None)
+                        .await?
+                        .unwrap()
+                        .into_skeleton_manifest_id(),
+                );
+            }
+            derived
+        };
+
+        let sequential = {
+            let repo: TestRepo = TestRepoFactory::new(fb)?.build()?;
+            let commit_map = create_from_dag(&ctx, &repo, dag).await?;
+            let manager = repo.repo_derived_data().manager();
+            let mut derived = Vec::new();
+            for head in heads {
+                derived.push(
+                    manager
+                        .derive::<RootSkeletonManifestId>(&ctx, commit_map[head], None)
+                        .await?
+                        .into_skeleton_manifest_id(),
+                );
+            }
+            derived
+        };
+
+        assert_eq!(new_batch, sequential);
+        Ok(())
+    }
+
     #[fbinit::test]
     async fn batch_derive_with_gaps(fb: FacebookInit) -> Result<(), Error> {
         let ctx = CoreContext::test_mock(fb);
@@ -9,6 +9,7 @@ use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::sync::Arc;
+use std::sync::Mutex;
 
 use anyhow::format_err;
 use anyhow::Context;
@@ -47,11 +48,68 @@ use sorted_vector_map::SortedVectorMap;
 
 use crate::SkeletonManifestDerivationError;
 
+/// Maximum number of skeleton manifests kept by a `SkeletonManifestCache`.
+const MAX_CACHED_SKELETON_MANIFESTS: usize = 100_000;
+
+/// Skeleton manifests loaded while deriving a batch of commits.  Sibling
+/// commits in the same batch share most of their unchanged subtrees, so
+/// this saves loading those subtrees again for each of them.
+#[derive(Clone, Default)]
+pub(crate) struct SkeletonManifestCache {
+    manifests: Arc<Mutex<HashMap<SkeletonManifestId, SkeletonManifest>>>,
+}
+
+impl SkeletonManifestCache {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    async fn load(
+        &self,
+        ctx: &CoreContext,
+        blobstore: &Arc<dyn Blobstore>,
+        skeleton_id: SkeletonManifestId,
+    ) -> Result<SkeletonManifest> {
+        if let Some(skeleton) = self.get(&skeleton_id) {
+            return Ok(skeleton);
+        }
+        let skeleton = skeleton_id.load(ctx, blobstore).await?;
+        self.insert(skeleton_id, &skeleton);
+        Ok(skeleton)
+    }
+
+    fn get(&self, skeleton_id: &SkeletonManifestId) -> Option<SkeletonManifest> {
+        let manifests = self.manifests.lock().expect("lock poisoned");
+        manifests.get(skeleton_id).cloned()
+    }
+
+    fn insert(&self, skeleton_id: SkeletonManifestId, skeleton: &SkeletonManifest) {
+        let mut manifests = self.manifests.lock().expect("lock poisoned");
+        if manifests.len() < MAX_CACHED_SKELETON_MANIFESTS {
+            manifests.insert(skeleton_id, skeleton.clone());
+        }
+    }
+}
+
+/// Load a skeleton manifest, using the cache if there is one.
+async fn load_skeleton_manifest(
+    ctx: &CoreContext,
+    blobstore: &Arc<dyn Blobstore>,
+    cache: Option<&SkeletonManifestCache>,
+    skeleton_id: SkeletonManifestId,
+) -> Result<SkeletonManifest> {
+    match cache {
+        Some(cache) => cache.load(ctx, blobstore, skeleton_id).await,
+        None => Ok(skeleton_id.load(ctx, blobstore).await?),
+    }
+}
+
 pub(crate) async fn derive_skeleton_manifest_stack(
     ctx: &CoreContext,
     derivation_ctx: &DerivationContext,
     file_changes: Vec<(ChangesetId, BTreeMap<MPath, Option<(ContentId, FileType)>>)>,
     parent: Option<SkeletonManifestId>,
+    cache: SkeletonManifestCache,
 ) -> Result<HashMap<ChangesetId, SkeletonManifestId>, Error> {
     let blobstore = derivation_ctx.blobstore();
 
@@ -71,8 +129,10 @@ pub(crate) async fn derive_skeleton_manifest_stack(
         {
             cloned!(blobstore, ctx);
             move |tree_info, _cs_id| {
-                cloned!(blobstore, ctx);
-                async move { create_skeleton_manifest(&ctx, &blobstore, None, tree_info).await }
+                cloned!(blobstore, cache, ctx);
+                async move {
+                    create_skeleton_manifest(&ctx, &blobstore, Some(&cache), None, tree_info).await
+                }
             }
         },
         |_leaf_info, _cs_id| async { Ok((None, ())) },
@@ -107,7 +167,8 @@ pub(crate) async fn derive_skeleton_manifest(
                 move |tree_info, sender| {
                     cloned!(blobstore, ctx);
                     async move {
-                        create_skeleton_manifest(&ctx, &blobstore, Some(sender), tree_info).await
+                        create_skeleton_manifest(&ctx, &blobstore, None, Some(sender), tree_info)
+                            .await
                     }
                 }
             },
@@ -125,7 +186,8 @@ pub(crate) async fn derive_skeleton_manifest(
                 parents,
                 subentries: Default::default(),
             };
-            let (_, tree_id) = create_skeleton_manifest(ctx, blobstore, None, tree_info).await?;
+            let (_, tree_id) =
+                create_skeleton_manifest(ctx, blobstore, None, None, tree_info).await?;
             Ok(tree_id)
         }
     }
@@ -136,6 +198,7 @@ pub(crate) async fn derive_skeleton_manifest(
 async fn collect_skeleton_subentries(
     ctx: &CoreContext,
     blobstore: &Arc<dyn Blobstore>,
+    cache: Option<&SkeletonManifestCache>,
     parents: &[SkeletonManifestId],
     subentries: BTreeMap<
         MPathElement,
@@ -151,8 +214,7 @@ async fn collect_skeleton_subentries(
         .iter()
         .map({
             move |skeleton_id| async move {
-                skeleton_id
-                    .load(ctx, blobstore)
+                load_skeleton_manifest(ctx, blobstore, cache, *skeleton_id)
                     .await
                     .context(SkeletonManifestDerivationError::MissingParent(*skeleton_id))
             }
@@ -196,14 +258,16 @@ async fn collect_skeleton_subentries(
                             // Some other directory is being used. Fetch its
                             // summary from the blobstore.
                             let skeleton_manifest =
-                                skeleton_id.load(ctx, blobstore).await.with_context({
-                                    || {
-                                        SkeletonManifestDerivationError::MissingSubentry(
-                                            String::from_utf8_lossy(elem.as_ref()).to_string(),
-                                            skeleton_id,
-                                        )
-                                    }
-                                })?;
+                                load_skeleton_manifest(ctx, blobstore, cache, skeleton_id)
+                                    .await
+                                    .with_context({
+                                        || {
+                                            SkeletonManifestDerivationError::MissingSubentry(
+                                                String::from_utf8_lossy(elem.as_ref()).to_string(),
+                                                skeleton_id,
+                                            )
+                                        }
+                                    })?;
 
                             let entry =
                                 SkeletonManifestEntry::Directory(SkeletonManifestDirectory::new(
@@ -226,12 +290,18 @@ async fn collect_skeleton_subentries(
 async fn create_skeleton_manifest(
     ctx: &CoreContext,
     blobstore: &Arc<dyn Blobstore>,
+    cache: Option<&SkeletonManifestCache>,
     sender: Option<mpsc::UnboundedSender<BoxFuture<'static, Result<(), Error>>>>,
     tree_info: TreeInfo<SkeletonManifestId, (), Option<SkeletonManifestSummary>>,
 ) -> Result<(Option<SkeletonManifestSummary>, SkeletonManifestId)> {
-    let entries =
-        collect_skeleton_subentries(ctx, blobstore, &tree_info.parents, tree_info.subentries)
-            .await?;
+    let entries = collect_skeleton_subentries(
+        ctx,
+        blobstore,
+        cache,
+        &tree_info.parents,
+        tree_info.subentries,
+    )
+    .await?;
 
     // Build a summary of the entries and store it as the new skeleton
     // manifest.
@@ -140,6 +140,62 @@ impl BonsaiDerivable for DerivedGeneration {
     }
 }
 
+/// The same data as `DerivedGeneration`, at a newer format version that
+/// can't serve data derived at the old one.
+#[derive(Clone, Debug)]
+pub struct DerivedGenerationV2(pub DerivedGeneration);
+
+#[async_trait]
+impl BonsaiDerivable for DerivedGenerationV2 {
+    const NAME: &'static str = DerivedGeneration::NAME;
+    const VARIANT: DerivableType = DerivedGeneration::VARIANT;
+    const FORMAT_VERSION: u32 = 2;
+    const MIN_COMPATIBLE_FORMAT_VERSION: u32 = 2;
+
+    type Dependencies = dependencies![];
+
+    async fn derive_single(
+        ctx: &CoreContext,
+        derivation_ctx: &DerivationContext,
+        bonsai: BonsaiChangeset,
+        parents: Vec<Self>,
+    ) -> Result<Self> {
+        let parents = parents.into_iter().map(|parent| parent.0).collect();
+        let derived = DerivedGeneration::derive_single(ctx, derivation_ctx, bonsai, parents)
+            .await?;
+        Ok(DerivedGenerationV2(derived))
+    }
+
+    async fn store_mapping(
+        self,
+        ctx: &CoreContext,
+        derivation_ctx: &DerivationContext,
+        changeset_id: ChangesetId,
+    ) -> Result<()> {
+        self.0
+            .store_mapping(ctx, derivation_ctx, changeset_id)
+            .await
+    }
+
+    async fn fetch(
+        ctx: &CoreContext,
+        derivation_ctx: &DerivationContext,
+        changeset_id: ChangesetId,
+    ) -> Result<Option<Self>> {
+        let derived = DerivedGeneration::fetch(ctx, derivation_ctx, changeset_id)
+            .await?;
+        Ok(derived.map(DerivedGenerationV2))
+    }
+
+    fn from_thrift(_: thrift::DerivedData) -> Result<Self> {
+        bail!("Not implemented for {}", Self::NAME);
+    }
+
+    fn into_thrift(_: Self) -> Result<thrift::DerivedData> {
+        bail!("Not implemented for {}", Self::NAME);
+    }
+}
+
 pub fn make_test_repo_factory(fb: FacebookInit) -> TestRepoFactory {
     let mut factory = TestRepoFactory::new(fb).unwrap();
     factory.with_config_override(|repo_config| {
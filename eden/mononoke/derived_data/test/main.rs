@@ -26,10 +26,13 @@ use changesets::Changesets;
 use changesets::ChangesetsRef;
 use cloned::cloned;
 use context::CoreContext;
+use context::RequestAbandoned;
+use context::SessionContainer;
 use derived_data_manager::BonsaiDerivable;
 use derived_data_manager::DerivationError;
 use derived_data_test_derived_generation::make_test_repo_factory;
 use derived_data_test_derived_generation::DerivedGeneration;
+use derived_data_test_derived_generation::DerivedGenerationV2;
 use fbinit::FacebookInit;
 use filestore::FilestoreConfig;
 use fixtures::BranchEven;
@@ -43,6 +46,7 @@ use fixtures::MergeUneven;
 use fixtures::TestRepoFixture;
 use fixtures::UnsharedMergeEven;
 use fixtures::UnsharedMergeUneven;
+use futures::channel::oneshot;
 use futures::future::BoxFuture;
 use futures_stats::TimedFutureExt;
 use futures_stats::TimedTryFutureExt;
@@ -216,6 +220,153 @@ async fn test_gapped_derivation(fb: FacebookInit) -> Result<()> {
     Ok(())
 }
 
+#[fbinit::test]
+/// Test that data derived at an incompatible format version is re-derived,
+/// and that the version it is re-derived at is recorded.
+async fn test_format_version_bump(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo: TestRepo = make_test_repo_factory(fb).build()?;
+    Linear::initrepo(fb, &repo).await;
+    let manager = repo.repo_derived_data().manager();
+
+    let master = repo
+        .bookmarks()
+        .get(ctx.clone(), &BookmarkKey::new("master")?)
+        .await?
+        .expect("master should be set");
+    let generation = repo
+        .changesets()
+        .get(&ctx, master)
+        .await?
+        .expect("changeset should exist")
+        .gen;
+
+    repo.repo_derived_data()
+        .derive::<DerivedGeneration>(&ctx, master)
+        .await?;
+    assert_eq!(
+        manager
+            .find_outdated::<DerivedGenerationV2>(&ctx, vec![master])
+            .await?,
+        vec![master]
+    );
+
+    // None of the data at the old version can be served.
+    assert!(
+        manager
+            .fetch_derived::<DerivedGenerationV2>(&ctx, master, None)
+            .await?
+            .is_none()
+    );
+    assert_eq!(
+        manager
+            .count_underived::<DerivedGenerationV2>(&ctx, master, None, None)
+            .await?,
+        generation
+    );
+
+    let derived = repo
+        .repo_derived_data()
+        .derive::<DerivedGenerationV2>(&ctx, master)
+        .await?;
+    assert_eq!(derived.0.generation, generation);
+    assert!(
+        manager
+            .find_outdated::<DerivedGenerationV2>(&ctx, vec![master])
+            .await?
+            .is_empty()
+    );
+    assert!(
+        manager
+            .fetch_derived::<DerivedGenerationV2>(&ctx, master, None)
+            .await?
+            .is_some()
+    );
+
+    // Format versions are cached, so changing the record doesn't affect
+    // data that is already known to be compatible.
+    repo.repo_blobstore()
+        .put(
+            &ctx,
+            format!("derived_format_version.{}.{}", DerivedGenerationV2::NAME, master),
+            BlobstoreBytes::from_bytes(Bytes::from_static(b"1")),
+        )
+        .await?;
+    assert!(
+        manager
+            .fetch_derived::<DerivedGenerationV2>(&ctx, master, None)
+            .await?
+            .is_some()
+    );
+
+    Ok(())
+}
+
+#[fbinit::test]
+/// Test that traces record the ancestors that had to be derived.
+async fn test_derivation_trace(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo: TestRepo = make_test_repo_factory(fb).build()?;
+    Linear::initrepo(fb, &repo).await;
+    let manager = repo.repo_derived_data().manager();
+
+    let master = repo
+        .bookmarks()
+        .get(ctx.clone(), &BookmarkKey::new("master")?)
+        .await?
+        .expect("master should be set");
+    let generation = repo
+        .changesets()
+        .get(&ctx, master)
+        .await?
+        .expect("changeset should exist")
+        .gen;
+
+    // Traced derivations that would derive too much history fail without
+    // deriving anything.
+    assert!(
+        manager
+            .derive_traced::<DerivedGeneration>(&ctx, master, None, generation - 1)
+            .await
+            .is_err()
+    );
+    assert!(
+        manager
+            .fetch_derived::<DerivedGeneration>(&ctx, master, None)
+            .await?
+            .is_none()
+    );
+
+    let (derived, trace) = manager
+        .derive_traced::<DerivedGeneration>(&ctx, master, None, generation)
+        .await?;
+    assert_eq!(derived.generation, generation);
+    assert_eq!(trace.changeset_id, master);
+    assert_eq!(trace.derived.len() as u64, generation);
+    // Each changeset in the linear history waited for its parent, except
+    // the root.
+    let last = trace.derived.last().expect("master should be derived");
+    assert_eq!(last.changeset_id, master);
+    assert_eq!(last.underived_parents.len(), 1);
+    assert_eq!(
+        trace
+            .derived
+            .iter()
+            .filter(|cs| cs.underived_parents.is_empty())
+            .count(),
+        1
+    );
+    assert!(trace.derived.iter().all(|cs| cs.blobs_written > 0));
+
+    // Once derived, there is nothing more to derive.
+    let (_, trace) = manager
+        .derive_traced::<DerivedGeneration>(&ctx, master, None, generation)
+        .await?;
+    assert!(trace.derived.is_empty());
+
+    Ok(())
+}
+
 #[fbinit::test]
 async fn test_leases(fb: FacebookInit) -> Result<(), Error> {
     let ctx = CoreContext::test_mock(fb);
@@ -356,6 +507,39 @@ async fn test_always_failing_lease(fb: FacebookInit) -> Result<(), Error> {
     Ok(())
 }
 
+#[fbinit::test]
+async fn test_shared_derivation_abandoned(fb: FacebookInit) -> Result<(), Error> {
+    let repo: TestRepo = make_test_repo_factory(fb).build()?;
+    let lease = repo.repo_derived_data().manager().lease();
+    let wait = |session: &SessionContainer, derivation: oneshot::Receiver<()>| {
+        let lease = lease.clone();
+        let ctx = CoreContext::test_mock_session(session.clone());
+        tokio::spawn(async move { lease.run_until_all_abandoned(&ctx, "key", derivation).await })
+    };
+
+    let first_session = SessionContainer::new_with_defaults(fb);
+    let second_session = SessionContainer::new_with_defaults(fb);
+    let (first_done, first_derivation) = oneshot::channel();
+    let (_second_done, second_derivation) = oneshot::channel();
+    let first = wait(&first_session, first_derivation);
+    let second = wait(&second_session, second_derivation);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // The second request is still waiting for the derivation, so it carries
+    // on after the first is abandoned.
+    first_session.cancel();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!first.is_finished());
+    first_done.send(()).unwrap();
+    assert!(first.await?.is_ok());
+
+    // Once every request has been abandoned, so is the derivation.
+    second_session.cancel();
+    assert!(matches!(second.await?, Err(RequestAbandoned::ClientDisconnected)));
+
+    Ok(())
+}
+
 #[fbinit::test]
 async fn test_parallel_derivation(fb: FacebookInit) -> Result<(), Error> {
     let ctx = CoreContext::test_mock(fb);
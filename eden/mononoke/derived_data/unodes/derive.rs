@@ -23,6 +23,7 @@ use futures::future::BoxFuture;
 use futures::future::FutureExt as NewFutureExt;
 use futures::future::TryFutureExt;
 use futures::future::{self as new_future};
+use futures::stream::TryStreamExt;
 use manifest::derive_manifest_with_io_sender;
 use manifest::derive_manifests_for_simple_stack_of_commits;
 use manifest::Entry;
@@ -55,6 +56,7 @@ pub(crate) async fn derive_unode_manifest_stack(
     unode_version: UnodeVersion,
 ) -> Result<HashMap<ChangesetId, ManifestUnodeId>, Error> {
     let blobstore = derivation_ctx.blobstore();
+    let shard_threshold = derivation_ctx.config().unode_shard_threshold;
 
     let manifest_changes = file_changes
         .into_iter()
@@ -79,6 +81,7 @@ pub(crate) async fn derive_unode_manifest_stack(
                     None,
                     tree_info,
                     unode_version,
+                    shard_threshold,
                 )
             }
         },
@@ -115,6 +118,7 @@ pub(crate) async fn derive_unode_manifest(
 ) -> Result<ManifestUnodeId, Error> {
     let parents: Vec<_> = parents.into_iter().collect();
     let blobstore = derivation_ctx.blobstore();
+    let shard_threshold = derivation_ctx.config().unode_shard_threshold;
 
     let maybe_tree_id = derive_manifest_with_io_sender(
         ctx.clone(),
@@ -131,6 +135,7 @@ pub(crate) async fn derive_unode_manifest(
                     Some(sender),
                     tree_info,
                     unode_version,
+                    shard_threshold,
                 )
             }
         },
@@ -166,6 +171,7 @@ pub(crate) async fn derive_unode_manifest(
                 None,
                 tree_info,
                 unode_version,
+                shard_threshold,
             )
             .await?;
             Ok(tree_id)
@@ -196,6 +202,7 @@ async fn create_unode_manifest(
     sender: Option<mpsc::UnboundedSender<BoxFuture<'static, Result<(), Error>>>>,
     tree_info: TreeInfo<ManifestUnodeId, FileUnodeId, ()>,
     unode_version: UnodeVersion,
+    shard_threshold: Option<usize>,
 ) -> Result<((), ManifestUnodeId), Error> {
     let mut subentries = SortedVectorMap::new();
     for (basename, (_context, entry)) in tree_info.subentries {
@@ -216,7 +223,13 @@ async fn create_unode_manifest(
         }
     }
 
-    let mf_unode = ManifestUnode::new(tree_info.parents, subentries, linknode);
+    let mf_unode = match shard_threshold {
+        Some(threshold) if subentries.len() > threshold => {
+            ManifestUnode::new_sharded(&ctx, &blobstore, tree_info.parents, subentries, linknode)
+                .await?
+        }
+        _ => ManifestUnode::new(tree_info.parents, subentries, linknode),
+    };
     let mf_unode_id = mf_unode.get_unode_id();
 
     let key = mf_unode_id.blobstore_key();
@@ -418,11 +431,25 @@ async fn reuse_manifest_parent(
     )
     .await?;
 
-    if let Some(mf_unode) = parents.iter().find(|p| p.subentries() == subentries) {
-        Ok(Some(mf_unode.get_unode_id()))
-    } else {
-        Ok(None)
+    for parent in parents {
+        let same_subentries = if parent.is_sharded() {
+            let parent_subentries = parent
+                .clone()
+                .into_subentries(ctx, blobstore)
+                .try_collect::<Vec<_>>()
+                .await?;
+            parent_subentries
+                .iter()
+                .map(|(basename, entry)| (basename, entry))
+                .eq(subentries.iter())
+        } else {
+            parent.subentries() == subentries
+        };
+        if same_subentries {
+            return Ok(Some(parent.get_unode_id()));
+        }
     }
+    Ok(None)
 }
 
 async fn reuse_file_parent(
@@ -785,6 +812,67 @@ mod tests {
         diamond_merge_unodes_v2(fb).await
     }
 
+    #[fbinit::test]
+    async fn test_sharded_unodes(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: TestRepo = TestRepoFactory::new(fb)?
+            .with_config_override(|config| {
+                config
+                    .derived_data_config
+                    .get_active_config()
+                    .expect("No enabled derived data types config")
+                    .unode_shard_threshold = Some(2);
+            })
+            .build()?;
+
+        let root_unode_id = create_changeset_and_derive_unode(
+            ctx.clone(),
+            repo.clone(),
+            btreemap! {
+                "dir/a" => Some(("a", FileType::Regular)),
+                "dir/b" => Some(("b", FileType::Regular)),
+                "dir/c" => Some(("c", FileType::Regular)),
+                "file" => Some(("file", FileType::Regular)),
+            },
+        )
+        .await?;
+
+        // The root only has two entries, so is stored inline.
+        let root_unode = root_unode_id.load(&ctx, &repo.repo_blobstore).await?;
+        assert!(!root_unode.is_sharded());
+        let dir_unode_id = match root_unode.lookup(&MPathElement::new(b"dir".to_vec())?) {
+            Some(UnodeEntry::Directory(id)) => *id,
+            entry => return Err(format_err!("unexpected entry for dir: {:?}", entry)),
+        };
+
+        let dir_unode = dir_unode_id.load(&ctx, &repo.repo_blobstore).await?;
+        assert!(dir_unode.is_sharded());
+        assert!(dir_unode.list().next().is_none());
+        let entry = dir_unode
+            .lookup_entry(&ctx, &repo.repo_blobstore, &MPathElement::new(b"b".to_vec())?)
+            .await?;
+        assert!(matches!(entry, Some(UnodeEntry::File(_))));
+
+        let all_unodes: Vec<_> =
+            iterate_all_manifest_entries(&ctx, &repo, Entry::Tree(root_unode_id))
+                .try_collect()
+                .await?;
+        let mut paths: Vec<_> = all_unodes.into_iter().map(|(path, _)| path).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                None,
+                Some(MPath::new("dir").unwrap()),
+                Some(MPath::new("dir/a").unwrap()),
+                Some(MPath::new("dir/b").unwrap()),
+                Some(MPath::new("dir/c").unwrap()),
+                Some(MPath::new("file").unwrap()),
+            ]
+        );
+        Ok(())
+    }
+
     #[fbinit::test]
     async fn test_parent_order(fb: FacebookInit) -> Result<(), Error> {
         let repo: TestRepo = test_repo_factory::build_empty(fb).unwrap();
@@ -994,6 +1082,7 @@ mod tests {
             file_changes: file_changes.into(),
             is_snapshot: false,
             git_annotated_tag: None,
+            extras: None,
         }
         .freeze()
         .unwrap();
@@ -34,10 +34,12 @@ use deleted_manifest::RootDeletedManifestV2Id;
 use derived_data::DerivedDataTypesConfig;
 use derived_data_filenodes::FilenodesOnlyPublic;
 use derived_data_manager::BatchDeriveOptions;
+pub use derived_data_manager::BatchDeriveOutcome;
 pub use derived_data_manager::BatchDeriveStats;
 use derived_data_manager::BonsaiDerivable as NewBonsaiDerivable;
 use derived_data_manager::DerivableType;
 use derived_data_manager::DerivationError;
+use derived_data_manager::DerivationTrace;
 use derived_data_manager::DerivedDataManager;
 use derived_data_manager::Rederivation;
 use fastlog::RootFastlog;
@@ -179,6 +181,15 @@ pub trait DerivedUtils: Send + Sync + 'static {
         csid: ChangesetId,
     ) -> BoxFuture<'static, Result<String, Error>>;
 
+    /// Derive data for changeset, and trace the derivations this required,
+    /// failing if more than `max_underived` changesets must be derived
+    async fn derive_traced(
+        &self,
+        ctx: &CoreContext,
+        csid: ChangesetId,
+        max_underived: u64,
+    ) -> Result<DerivationTrace, Error>;
+
     /// Derive data for exactly a batch of changeset
     ///
     /// "exactly" means that all ancestors must already have had their data derive,
@@ -190,7 +201,7 @@ pub trait DerivedUtils: Send + Sync + 'static {
         csids: Vec<ChangesetId>,
         parallel: bool,
         gap_size: Option<usize>,
-    ) -> BoxFuture<'static, Result<BatchDeriveStats, Error>>;
+    ) -> BoxFuture<'static, Result<BatchDeriveOutcome, Error>>;
 
     /// Find pending changeset (changesets for which data have not been derived)
     async fn pending(
@@ -208,6 +219,14 @@ pub trait DerivedUtils: Send + Sync + 'static {
         csid: ChangesetId,
     ) -> Result<u64, Error>;
 
+    /// Find changesets whose data was derived at an older format version
+    /// than the current one
+    async fn outdated(
+        &self,
+        ctx: &CoreContext,
+        csids: Vec<ChangesetId>,
+    ) -> Result<Vec<ChangesetId>, Error>;
+
     /// Regenerate derived data for specified set of commits
     fn regenerate(&self, csids: &[ChangesetId]);
 
@@ -305,6 +324,20 @@ where
         .boxed()
     }
 
+    async fn derive_traced(
+        &self,
+        ctx: &CoreContext,
+        csid: ChangesetId,
+        max_underived: u64,
+    ) -> Result<DerivationTrace, Error> {
+        let utils = Arc::new(self.clone());
+        let (_, trace) = self
+            .manager
+            .derive_traced::<Derivable>(ctx, csid, Some(utils), max_underived)
+            .await?;
+        Ok(trace)
+    }
+
     fn derive_exactly_batch(
         &self,
         ctx: CoreContext,
@@ -312,7 +345,7 @@ where
         csids: Vec<ChangesetId>,
         parallel: bool,
         gap_size: Option<usize>,
-    ) -> BoxFuture<'static, Result<BatchDeriveStats, Error>> {
+    ) -> BoxFuture<'static, Result<BatchDeriveOutcome, Error>> {
         let options = if parallel || gap_size.is_some() {
             BatchDeriveOptions::Parallel { gap_size }
         } else {
@@ -357,6 +390,14 @@ where
             .await?)
     }
 
+    async fn outdated(
+        &self,
+        ctx: &CoreContext,
+        csids: Vec<ChangesetId>,
+    ) -> Result<Vec<ChangesetId>, Error> {
+        Ok(self.manager.find_outdated::<Derivable>(ctx, csids).await?)
+    }
+
     fn regenerate(&self, csids: &[ChangesetId]) {
         self.rederive
             .with(|rederive| rederive.extend(csids.iter().copied()));
@@ -1244,6 +1285,15 @@ mod tests {
             self.deriver.derive(ctx, repo, csid)
         }
 
+        async fn derive_traced(
+            &self,
+            ctx: &CoreContext,
+            csid: ChangesetId,
+            max_underived: u64,
+        ) -> Result<DerivationTrace, Error> {
+            self.deriver.derive_traced(ctx, csid, max_underived).await
+        }
+
         fn derive_exactly_batch(
             &self,
             ctx: CoreContext,
@@ -1251,7 +1301,7 @@ mod tests {
             csids: Vec<ChangesetId>,
             parallel: bool,
             gap_size: Option<usize>,
-        ) -> BoxFuture<'static, Result<BatchDeriveStats, Error>> {
+        ) -> BoxFuture<'static, Result<BatchDeriveOutcome, Error>> {
             self.deriver
                 .derive_exactly_batch(ctx, repo, csids, parallel, gap_size)
         }
@@ -1275,6 +1325,14 @@ mod tests {
             unimplemented!()
         }
 
+        async fn outdated(
+            &self,
+            _ctx: &CoreContext,
+            _csids: Vec<ChangesetId>,
+        ) -> Result<Vec<ChangesetId>, Error> {
+            unimplemented!()
+        }
+
         fn regenerate(&self, _csids: &[ChangesetId]) {
             unimplemented!()
         }
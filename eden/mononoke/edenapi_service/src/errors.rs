@@ -11,6 +11,7 @@ use anyhow::Error;
 use gotham_ext::error::HttpError;
 use mononoke_api::ChangesetId;
 use mononoke_api::MononokeError;
+use mononoke_api::MononokeErrorCode;
 use thiserror::Error;
 use types::HgId;
 use types::Key;
@@ -65,6 +66,10 @@ pub enum ErrorKind {
     CommitRevlogDataRequestFailed,
     #[error("HgId not found: {0}")]
     HgIdNotFound(HgId),
+    #[error("Invalid HgId: {0}")]
+    InvalidHgId(String),
+    #[error("Too many ids requested: {0}, the maximum is {1}")]
+    TooManyIds(usize, usize),
     #[error("Failed to fetch HgId for Bonsai Changeset ID {0}")]
     BonsaiChangesetToHgIdError(ChangesetId),
     #[error(
@@ -90,16 +95,16 @@ impl MononokeErrorExt for MononokeError {
     where
         C: Display + Send + Sync + 'static,
     {
-        use MononokeError::*;
-        (match self {
-            InvalidRequest(_) => HttpError::e400,
-            ServicePermissionDenied { .. } => HttpError::e403,
-            NotAvailable { .. } => HttpError::e503,
-            HookFailure(_) => HttpError::e400,
-            PushrebaseConflicts(_) => HttpError::e400,
-            AuthorizationError(_) => HttpError::e403,
-            InternalError(_) => HttpError::e500,
-            MergeConflicts { .. } => HttpError::e400,
+        let code = self.code();
+        code.report();
+        (match code {
+            MononokeErrorCode::InvalidRequest
+            | MononokeErrorCode::HookFailure
+            | MononokeErrorCode::PushrebaseConflicts
+            | MononokeErrorCode::MergeConflicts => HttpError::e400,
+            MononokeErrorCode::PermissionDenied => HttpError::e403,
+            MononokeErrorCode::NotAvailable | MononokeErrorCode::RepoReadOnly => HttpError::e503,
+            MononokeErrorCode::InternalError => HttpError::e500,
         })(Error::from(self).context(context))
     }
 }
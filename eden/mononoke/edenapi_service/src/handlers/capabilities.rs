@@ -29,6 +29,9 @@ pub struct CapabilitiesParams {
 }
 
 static CAP_SEGMENTED_CHANGELOG: &str = "segmented-changelog";
+/// File history is served without filenodes to history requests that give
+/// a commit containing the files.
+static CAP_FILENODE_FREE: &str = "filenode-free";
 
 /// Get capabilities as a vector of static strings.
 ///
@@ -45,6 +48,10 @@ async fn get_capabilities_vec(
         capabilities.push(CAP_SEGMENTED_CHANGELOG);
     }
 
+    if hg_repo_ctx.serves_filenode_free() {
+        capabilities.push(CAP_FILENODE_FREE);
+    }
+
     Ok(capabilities)
 }
 
@@ -41,6 +41,8 @@ use edenapi_types::EphemeralPrepareRequest;
 use edenapi_types::EphemeralPrepareResponse;
 use edenapi_types::FetchSnapshotRequest;
 use edenapi_types::FetchSnapshotResponse;
+use edenapi_types::ListSnapshotsRequest;
+use edenapi_types::ListSnapshotsResponse;
 use edenapi_types::UploadBonsaiChangesetRequest;
 use edenapi_types::UploadHgChangesetsRequest;
 use edenapi_types::UploadToken;
@@ -394,6 +396,7 @@ impl EdenApiHandler for UploadBonsaiChangesetHandler {
                     extra: cs.extra.into_iter().map(|e| (e.key, e.value)).collect(),
                     // TODO(rajshar): Need to allow passing git_extra_headers through Eden API as well.
                     git_extra_headers: None,
+                    extras: None,
                 },
                 cs.file_changes
                     .into_iter()
@@ -565,6 +568,59 @@ impl EdenApiHandler for AlterSnapshotHandler {
     }
 }
 
+/// Default maximum number of snapshots returned by a list snapshots request.
+const DEFAULT_LIST_SNAPSHOTS_LIMIT: u32 = 100;
+/// Most snapshots a list snapshots request may ask for.
+const MAX_LIST_SNAPSHOTS_LIMIT: u32 = 1000;
+
+/// List the snapshots that have a given label
+pub struct ListSnapshotsHandler;
+
+#[async_trait]
+impl EdenApiHandler for ListSnapshotsHandler {
+    type Request = ListSnapshotsRequest;
+    type Response = ListSnapshotsResponse;
+
+    const HTTP_METHOD: hyper::Method = hyper::Method::POST;
+    const API_METHOD: EdenApiMethod = EdenApiMethod::ListSnapshots;
+    const ENDPOINT: &'static str = "/snapshot/list";
+
+    async fn handler(
+        repo: HgRepoContext,
+        _path: Self::PathExtractor,
+        _query: Self::QueryStringExtractor,
+        request: Self::Request,
+    ) -> HandlerResult<'async_trait, Self::Response> {
+        let limit = request
+            .limit
+            .unwrap_or(DEFAULT_LIST_SNAPSHOTS_LIMIT)
+            .min(MAX_LIST_SNAPSHOTS_LIMIT);
+        let snapshots = repo
+            .ephemeral_store()
+            .changesets_with_label(&request.label, limit)
+            .await
+            .context("Failed to list snapshots")?;
+        Ok(stream::iter(snapshots)
+            .map(move |(bubble_id, cs_id)| {
+                let repo = repo.clone();
+                async move {
+                    let labels = repo
+                        .ephemeral_store()
+                        .labels_from_bubble(&bubble_id)
+                        .await
+                        .context("Failed to fetch labels associated with the snapshot")?;
+                    Ok::<_, Error>(ListSnapshotsResponse {
+                        cs_id: cs_id.into(),
+                        bubble_id: Some(bubble_id.into()),
+                        labels,
+                    })
+                }
+            })
+            .buffered(10)
+            .boxed())
+    }
+}
+
 /// Creates an ephemeral bubble and return its id
 pub struct EphemeralPrepareHandler;
 
@@ -41,6 +41,9 @@ use gotham::state::State;
 use gotham_derive::StateData;
 use gotham_derive::StaticResponseExtender;
 use gotham_ext::error::HttpError;
+use gotham_ext::response::client_has_etag;
+use gotham_ext::response::BytesBody;
+use gotham_ext::response::ContentAddressedBody;
 use gotham_ext::response::TryIntoResponse;
 use hyper::Body;
 use mercurial_types::HgFileNodeId;
@@ -51,6 +54,7 @@ use mononoke_api_hg::HgRepoContext;
 use rate_limiting::Metric;
 use serde::Deserialize;
 use types::Key;
+use types::RepoPathBuf;
 
 use super::EdenApiHandler;
 use super::EdenApiMethod;
@@ -59,8 +63,14 @@ use super::HandlerResult;
 use crate::context::ServerContext;
 use crate::errors::ErrorKind;
 use crate::middleware::RequestContext;
+use crate::utils::cbor_mime;
 use crate::utils::cbor_stream_filtered_errors;
+use crate::utils::ensure_key_served;
+use crate::utils::fetch_error_to_http;
 use crate::utils::get_repo;
+use crate::utils::hgids_etag;
+use crate::utils::parse_hgids;
+use crate::utils::to_cbor_seq_bytes;
 
 /// XXX: This number was chosen arbitrarily.
 const MAX_CONCURRENT_FILE_FETCHES_PER_REQUEST: usize = 10;
@@ -207,6 +217,8 @@ async fn fetch_file(
     key: Key,
     attrs: FileAttributes,
 ) -> Result<FileEntry, Error> {
+    ensure_key_served(&repo, &key, false).await?;
+
     let id = HgFileNodeId::from_node_hash(HgNodeHash::from(key.hgid));
 
     let ctx = id
@@ -274,6 +286,52 @@ async fn store_file(
     Ok(())
 }
 
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct FilesByIdParams {
+    repo: String,
+    /// Comma-separated list of filenode ids.
+    hgids: String,
+}
+
+/// Fetch files by their filenode ids. Unlike the `files` endpoint, the
+/// response can be cached by the client, as filenodes are content-addressed.
+/// The files are serialized in the order of the ids, and are all fetched
+/// before the response is sent, so that a failed fetch can't leave a
+/// truncated response in the cache.
+pub async fn files_by_id(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let params = FilesByIdParams::take_from(state);
+
+    state.put(HandlerInfo::new(&params.repo, EdenApiMethod::FilesById));
+
+    let rctx = RequestContext::borrow_from(state).clone();
+    let sctx = ServerContext::borrow_from(state);
+
+    let repo = get_repo(sctx, &rctx, &params.repo, None).await?;
+    let hgids = parse_hgids(&params.hgids)?;
+    let etag = hgids_etag(&hgids);
+    if client_has_etag(state, &etag) {
+        return Ok(ContentAddressedBody::not_modified(etag));
+    }
+
+    let attrs = FileAttributes {
+        content: true,
+        aux_data: false,
+    };
+    let entries: Vec<_> = stream::iter(hgids)
+        .map(|hgid| {
+            let key = Key::new(RepoPathBuf::new(), hgid);
+            fetch_file(repo.clone(), key, attrs.clone())
+        })
+        .buffered(MAX_CONCURRENT_FILE_FETCHES_PER_REQUEST)
+        .map_ok(|entry| entry.to_wire())
+        .try_collect()
+        .await
+        .map_err(fetch_error_to_http)?;
+    let bytes = to_cbor_seq_bytes(entries).map_err(HttpError::e500)?;
+
+    Ok(ContentAddressedBody::new(etag, BytesBody::new(bytes, cbor_mime())))
+}
+
 /// Upload content of a file requested by the client.
 pub async fn upload_file(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
     let params = UploadFileParams::take_from(state);
@@ -11,20 +11,44 @@ use async_trait::async_trait;
 use cloned::cloned;
 use edenapi_types::HistoryRequest;
 use edenapi_types::HistoryResponseChunk;
+use edenapi_types::ToWire;
 use edenapi_types::WireHistoryEntry;
 use futures::stream;
 use futures::stream::BoxStream;
 use futures::StreamExt;
 use futures::TryStreamExt;
+use gotham::state::FromState;
+use gotham::state::State;
+use gotham_derive::StateData;
+use gotham_derive::StaticResponseExtender;
+use gotham_ext::error::HttpError;
+use gotham_ext::response::client_has_etag;
+use gotham_ext::response::BytesBody;
+use gotham_ext::response::ContentAddressedBody;
+use gotham_ext::response::TryIntoResponse;
+use mercurial_types::HgChangesetId;
 use mercurial_types::HgFileNodeId;
 use mercurial_types::HgNodeHash;
 use mononoke_api_hg::HgRepoContext;
+use rate_limiting::Metric;
+use serde::Deserialize;
+use types::HgId;
 use types::Key;
+use types::RepoPathBuf;
 
 use super::EdenApiHandler;
 use super::EdenApiMethod;
+use super::HandlerInfo;
 use super::HandlerResult;
+use crate::context::ServerContext;
 use crate::errors::ErrorKind;
+use crate::middleware::RequestContext;
+use crate::utils::cbor_mime;
+use crate::utils::fetch_error_to_http;
+use crate::utils::get_repo;
+use crate::utils::hgids_etag;
+use crate::utils::parse_hgids;
+use crate::utils::to_cbor_seq_bytes;
 use crate::utils::to_mpath;
 
 type HistoryStream = BoxStream<'static, Result<WireHistoryEntry, Error>>;
@@ -49,7 +73,11 @@ impl EdenApiHandler for HistoryHandler {
         _query: Self::QueryStringExtractor,
         request: Self::Request,
     ) -> HandlerResult<'async_trait, Self::Response> {
-        let HistoryRequest { keys, length } = request;
+        let HistoryRequest {
+            keys,
+            length,
+            commit,
+        } = request;
 
         let fetches = keys.into_iter().map(move |key| {
             // Construct a Future that buffers the full history for this key.
@@ -59,7 +87,7 @@ impl EdenApiHandler for HistoryHandler {
             cloned!(repo);
             async move {
                 let path = key.path.clone();
-                let stream = fetch_history_for_key(repo, key, length).await?;
+                let stream = fetch_history_for_key(repo, key, length, commit).await?;
                 let entries = stream.try_collect().await?;
                 Ok(HistoryResponseChunk { path, entries })
             }
@@ -71,10 +99,73 @@ impl EdenApiHandler for HistoryHandler {
     }
 }
 
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct HistoryByIdParams {
+    repo: String,
+    /// Comma-separated list of filenode ids.
+    hgids: String,
+}
+
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct HistoryByIdQueryString {
+    path: String,
+    length: Option<u32>,
+}
+
+/// Fetch the history of filenodes of the file at `path` by their ids. Unlike
+/// the `history` endpoint, the response can be cached by the client, as the
+/// history of a filenode never changes. The histories are serialized in the
+/// order of the ids, and are all fetched before the response is sent, so that
+/// a failed fetch can't leave a truncated response in the cache.
+pub async fn history_by_id(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let params = HistoryByIdParams::take_from(state);
+    let query_string = HistoryByIdQueryString::take_from(state);
+
+    state.put(HandlerInfo::new(&params.repo, EdenApiMethod::HistoryById));
+
+    let rctx = RequestContext::borrow_from(state).clone();
+    let sctx = ServerContext::borrow_from(state);
+
+    let repo = get_repo(sctx, &rctx, &params.repo, None).await?;
+    let hgids = parse_hgids(&params.hgids)?;
+    let path = RepoPathBuf::from_string(query_string.path.clone())
+        .with_context(|| ErrorKind::InvalidPath(query_string.path.into_bytes()))
+        .map_err(HttpError::e400)?;
+    // The path and length are part of the URL, so the filenode ids alone
+    // identify the response.
+    let etag = hgids_etag(&hgids);
+    if client_has_etag(state, &etag) {
+        return Ok(ContentAddressedBody::not_modified(etag));
+    }
+
+    let length = query_string.length;
+    let chunks: Vec<_> = stream::iter(hgids)
+        .map(|hgid| {
+            cloned!(repo, path);
+            async move {
+                let key = Key::new(path.clone(), hgid);
+                let stream = fetch_history_for_key(repo, key, length, None).await?;
+                let entries = stream.try_collect().await?;
+                Ok::<_, Error>(HistoryResponseChunk { path, entries }.to_wire())
+            }
+        })
+        .buffered(MAX_CONCURRENT_FETCHES_PER_REQUEST)
+        .try_collect()
+        .await
+        .map_err(fetch_error_to_http)?;
+    let bytes = to_cbor_seq_bytes(chunks).map_err(HttpError::e500)?;
+
+    Ok(ContentAddressedBody::new(etag, BytesBody::new(bytes, cbor_mime())))
+}
+
+/// Fetch the history of a file.  If the client gave a commit that contains
+/// the file, and the repo serves history without filenodes, the history is
+/// served from that commit instead of from filenodes.
 async fn fetch_history_for_key(
     repo: HgRepoContext,
     key: Key,
     length: Option<u32>,
+    commit: Option<HgId>,
 ) -> Result<HistoryStream, Error> {
     let filenode_id = HgFileNodeId::new(HgNodeHash::from(key.hgid));
     let mpath = to_mpath(&key.path)?.context(ErrorKind::UnexpectedEmptyPath)?;
@@ -87,8 +178,19 @@ async fn fetch_history_for_key(
 
     // Fetch the file's history and convert the entries into
     // the expected on-the-wire format.
-    let history = file
-        .history(mpath, length)
+    let history = match commit {
+        Some(commit) if repo.serves_filenode_free() => {
+            let commit = HgChangesetId::new(HgNodeHash::from(commit));
+            let entries = file
+                .history_without_filenodes(mpath, commit, length)
+                .await
+                .with_context(|| ErrorKind::HistoryFetchFailed(key.clone()))?;
+            stream::iter(entries.into_iter().map(Ok)).left_stream()
+        }
+        _ => file.history(mpath, length).into_stream().right_stream(),
+    };
+
+    let history = history
         .err_into::<Error>()
         .map_err(move |e| e.context(ErrorKind::HistoryFetchFailed(key.clone())))
         .and_then(|entry| async { WireHistoryEntry::try_from(entry) })
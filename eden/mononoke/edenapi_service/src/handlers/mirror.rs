@@ -0,0 +1,116 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Endpoints used by mirror repos to replicate from this server.
+
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use bytes::Bytes;
+use futures::TryStreamExt;
+use gotham::state::FromState;
+use gotham::state::State;
+use gotham_derive::StateData;
+use gotham_derive::StaticResponseExtender;
+use gotham_ext::error::HttpError;
+use gotham_ext::response::BytesBody;
+use mononoke_types::ChangesetId;
+use serde::Deserialize;
+
+use crate::context::ServerContext;
+use crate::errors::ErrorKind;
+use crate::errors::MononokeErrorExt;
+use crate::handlers::EdenApiMethod;
+use crate::handlers::HandlerInfo;
+use crate::middleware::RequestContext;
+use crate::utils::get_repo;
+use crate::utils::get_request_body;
+
+/// Number of commits in a bundle if the request doesn't specify a limit.
+const DEFAULT_BUNDLE_LIMIT: usize = 1000;
+
+/// Size in bytes after which no more commits are added to a bundle.  The
+/// bundle is held in memory while it is sent, so this bounds how much each
+/// request can use.
+const MAX_BUNDLE_SIZE: u64 = 256 * 1024 * 1024;
+
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct MirrorBookmarksParams {
+    repo: String,
+}
+
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct MirrorBundleParams {
+    repo: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MirrorBundleRequest {
+    heads: Vec<ChangesetId>,
+    #[serde(default)]
+    common: Vec<ChangesetId>,
+    limit: Option<usize>,
+}
+
+/// Return the public bookmarks of the repo, as a JSON object mapping
+/// bookmark names to bonsai changeset ids.
+pub async fn mirror_bookmarks(state: &mut State) -> Result<BytesBody<Bytes>, HttpError> {
+    let params = MirrorBookmarksParams::take_from(state);
+
+    state.put(HandlerInfo::new(&params.repo, EdenApiMethod::MirrorBookmarks));
+
+    let sctx = ServerContext::borrow_from(state);
+    let rctx = RequestContext::borrow_from(state).clone();
+    let hg_repo_ctx = get_repo(sctx, &rctx, &params.repo, None).await?;
+    let bookmarks: BTreeMap<String, ChangesetId> = hg_repo_ctx
+        .repo()
+        .list_bookmarks(false, None, None, None)
+        .await
+        .map_err(|e| e.into_http_error("error listing bookmarks"))?
+        .try_collect()
+        .await
+        .map_err(|e| e.into_http_error("error listing bookmarks"))?;
+    let bytes: Bytes = serde_json::to_vec(&bookmarks)
+        .context(ErrorKind::SerializationFailed)
+        .map_err(HttpError::e500)?
+        .into();
+
+    Ok(BytesBody::new(bytes, mime::APPLICATION_JSON))
+}
+
+/// Export the commits that are ancestors of the requested heads but not of
+/// the common commits as a commit bundle.  The request is a JSON object with
+/// `heads`, `common` and an optional `limit` on the number of commits.
+///
+/// Bundles stop growing at `MAX_BUNDLE_SIZE`, so the bundle may contain
+/// fewer commits than requested even if there are more to export.
+pub async fn mirror_bundle(state: &mut State) -> Result<BytesBody<Bytes>, HttpError> {
+    let params = MirrorBundleParams::take_from(state);
+
+    state.put(HandlerInfo::new(&params.repo, EdenApiMethod::MirrorBundle));
+
+    let body = get_request_body(state).await?;
+    let request: MirrorBundleRequest = serde_json::from_slice(&body)
+        .context(ErrorKind::DeserializationFailed)
+        .map_err(HttpError::e400)?;
+
+    let sctx = ServerContext::borrow_from(state);
+    let rctx = RequestContext::borrow_from(state).clone();
+    let hg_repo_ctx = get_repo(sctx, &rctx, &params.repo, None).await?;
+    let bundle = hg_repo_ctx
+        .repo()
+        .export_commit_bundle(
+            request.heads,
+            request.common,
+            request.limit.unwrap_or(DEFAULT_BUNDLE_LIMIT),
+            MAX_BUNDLE_SIZE,
+        )
+        .await
+        .map_err(|e| e.into_http_error("error exporting commit bundle"))?;
+
+    Ok(BytesBody::new(bundle.data, mime::APPLICATION_OCTET_STREAM))
+}
@@ -58,6 +58,8 @@ use crate::middleware::RequestContext;
 use crate::scuba::EdenApiScubaKey;
 use crate::utils::cbor_mime;
 use crate::utils::get_repo;
+use crate::utils::monitor::CancelOnDrop;
+use crate::utils::monitor::CancelUnlessFinished;
 use crate::utils::monitor::Monitor;
 use crate::utils::parse_wire_request;
 use crate::utils::to_cbor_bytes;
@@ -71,6 +73,7 @@ mod handler;
 mod history;
 mod land;
 mod lookup;
+mod mirror;
 mod pull;
 mod repos;
 mod trees;
@@ -108,11 +111,17 @@ pub enum EdenApiMethod {
     EphemeralPrepare,
     FetchSnapshot,
     AlterSnapshot,
+    ListSnapshots,
     CommitGraph,
     CommitGraphV2,
     DownloadFile,
     CommitMutations,
     CommitTranslateId,
+    TreesById,
+    FilesById,
+    HistoryById,
+    MirrorBookmarks,
+    MirrorBundle,
 }
 
 impl fmt::Display for EdenApiMethod {
@@ -144,9 +153,15 @@ impl fmt::Display for EdenApiMethod {
             Self::EphemeralPrepare => "ephemeral_prepare",
             Self::FetchSnapshot => "fetch_snapshot",
             Self::AlterSnapshot => "alter_snapshot",
+            Self::ListSnapshots => "list_snapshots",
             Self::DownloadFile => "download_file",
             Self::CommitMutations => "commit_mutations",
             Self::CommitTranslateId => "commit_translate_id",
+            Self::TreesById => "trees_by_id",
+            Self::FilesById => "files_by_id",
+            Self::HistoryById => "history_by_id",
+            Self::MirrorBookmarks => "mirror_bookmarks",
+            Self::MirrorBundle => "mirror_bundle",
         };
         write!(f, "{}", name)
     }
@@ -231,6 +246,11 @@ define_handler!(clone_handler, clone::clone_data);
 define_handler!(upload_file_handler, files::upload_file);
 define_handler!(pull_fast_forward_master, pull::pull_fast_forward_master);
 define_handler!(pull_lazy, pull::pull_lazy);
+define_handler!(trees_by_id_handler, trees::trees_by_id);
+define_handler!(files_by_id_handler, files::files_by_id);
+define_handler!(history_by_id_handler, history::history_by_id);
+define_handler!(mirror_bookmarks_handler, mirror::mirror_bookmarks);
+define_handler!(mirror_bundle_handler, mirror::mirror_bundle);
 
 static HIGH_LOAD_SIGNAL: &str = "I_AM_OVERLOADED";
 static ALIVE: &str = "I_AM_ALIVE";
@@ -268,6 +288,11 @@ async fn handler_wrapper<Handler: EdenApiHandler>(
 where
     <Handler as EdenApiHandler>::Request: std::fmt::Debug,
 {
+    // If the client disconnects before the response has been sent, this
+    // future or the response stream is dropped, and the guard cancels the
+    // rest of the work for the request.
+    let mut disconnect_guard =
+        CancelOnDrop::new(RequestContext::borrow_from(&state).ctx.session().clone());
     let (future_stats, res) = async {
         let path = Handler::PathExtractor::take_from(&mut state);
         let query_string = Handler::QueryStringExtractor::take_from(&mut state);
@@ -291,15 +316,20 @@ where
         };
 
         match Handler::handler(repo, path, query_string, request).await {
-            Ok(responses) => Ok(encode_response_stream(
-                monitor_request(&state, responses),
-                content_encoding,
-            )),
+            Ok(responses) => {
+                let responses = monitor_request(&state, responses);
+                Ok(encode_response_stream(
+                    CancelUnlessFinished::new(responses, disconnect_guard.transfer()),
+                    content_encoding,
+                ))
+            }
             Err(HandlerError::E500(err)) => Err(HttpError::e500(err)),
         }
     }
     .timed()
     .await;
+    // The handler has finished, so the client was still there.
+    disconnect_guard.disarm();
     ScubaMiddlewareState::try_set_future_stats(&mut state, &future_stats);
 
     build_response(res, state, &JsonErrorFomatter)
@@ -434,6 +464,7 @@ pub fn build_router(ctx: ServerContext) -> Router {
         Handlers::setup::<trees::UploadTreesHandler>(route);
         Handlers::setup::<commit::FetchSnapshotHandler>(route);
         Handlers::setup::<commit::AlterSnapshotHandler>(route);
+        Handlers::setup::<commit::ListSnapshotsHandler>(route);
         Handlers::setup::<commit::GraphHandler>(route);
         Handlers::setup::<commit::GraphHandlerV2>(route);
         Handlers::setup::<files::DownloadFileHandler>(route);
@@ -473,5 +504,26 @@ pub fn build_router(ctx: ServerContext) -> Router {
             .with_path_extractor::<files::UploadFileParams>()
             .with_query_string_extractor::<files::UploadFileQueryString>()
             .to(upload_file_handler);
+        route
+            .get("/:repo/trees/:hgids")
+            .with_path_extractor::<trees::TreesByIdParams>()
+            .to(trees_by_id_handler);
+        route
+            .get("/:repo/files/:hgids")
+            .with_path_extractor::<files::FilesByIdParams>()
+            .to(files_by_id_handler);
+        route
+            .get("/:repo/history/:hgids")
+            .with_path_extractor::<history::HistoryByIdParams>()
+            .with_query_string_extractor::<history::HistoryByIdQueryString>()
+            .to(history_by_id_handler);
+        route
+            .get("/:repo/mirror/bookmarks")
+            .with_path_extractor::<mirror::MirrorBookmarksParams>()
+            .to(mirror_bookmarks_handler);
+        route
+            .post("/:repo/mirror/bundle")
+            .with_path_extractor::<mirror::MirrorBundleParams>()
+            .to(mirror_bundle_handler);
     })
 }
@@ -10,6 +10,7 @@ use anyhow::Error;
 use async_trait::async_trait;
 use bytes::Bytes;
 use context::PerfCounterType;
+use edenapi_types::wire::ToWire;
 use edenapi_types::wire::WireTreeRequest;
 use edenapi_types::AnyId;
 use edenapi_types::Batch;
@@ -33,6 +34,9 @@ use gotham_derive::StateData;
 use gotham_derive::StaticResponseExtender;
 use gotham_ext::error::HttpError;
 use gotham_ext::middleware::scuba::ScubaMiddlewareState;
+use gotham_ext::response::client_has_etag;
+use gotham_ext::response::BytesBody;
+use gotham_ext::response::ContentAddressedBody;
 use gotham_ext::response::TryIntoResponse;
 use manifest::Entry;
 use manifest::Manifest;
@@ -58,9 +62,15 @@ use crate::context::ServerContext;
 use crate::errors::ErrorKind;
 use crate::middleware::request_dumper::RequestDumper;
 use crate::middleware::RequestContext;
+use crate::utils::cbor_mime;
 use crate::utils::custom_cbor_stream;
+use crate::utils::ensure_key_served;
+use crate::utils::fetch_error_to_http;
 use crate::utils::get_repo;
+use crate::utils::hgids_etag;
+use crate::utils::parse_hgids;
 use crate::utils::parse_wire_request;
+use crate::utils::to_cbor_seq_bytes;
 
 /// XXX: This number was chosen arbitrarily.
 const MAX_CONCURRENT_TREE_FETCHES_PER_REQUEST: usize = 10;
@@ -98,6 +108,48 @@ pub async fn trees(state: &mut State) -> Result<impl TryIntoResponse, HttpError>
     ))
 }
 
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct TreesByIdParams {
+    repo: String,
+    /// Comma-separated list of tree ids.
+    hgids: String,
+}
+
+/// Fetch trees by their ids. Unlike the `trees` endpoint, the response can
+/// be cached by the client, as trees are content-addressed. The trees are
+/// serialized in the order of the ids, and are all fetched before the
+/// response is sent, so that a failed fetch can't leave a truncated response
+/// in the cache.
+pub async fn trees_by_id(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let params = TreesByIdParams::take_from(state);
+
+    state.put(HandlerInfo::new(&params.repo, EdenApiMethod::TreesById));
+
+    let rctx = RequestContext::borrow_from(state).clone();
+    let sctx = ServerContext::borrow_from(state);
+
+    let repo = get_repo(sctx, &rctx, &params.repo, Metric::TotalManifests).await?;
+    let hgids = parse_hgids(&params.hgids)?;
+    let etag = hgids_etag(&hgids);
+    if client_has_etag(state, &etag) {
+        return Ok(ContentAddressedBody::not_modified(etag));
+    }
+
+    let entries: Vec<_> = stream::iter(hgids)
+        .map(|hgid| fetch_tree(repo.clone(), Key::new(RepoPathBuf::new(), hgid), false))
+        .buffered(MAX_CONCURRENT_TREE_FETCHES_PER_REQUEST)
+        .map_ok(|entry| entry.to_wire())
+        .try_collect()
+        .await
+        .map_err(fetch_error_to_http)?;
+    rctx.ctx
+        .session()
+        .bump_load(Metric::TotalManifests, entries.len() as f64);
+    let bytes = to_cbor_seq_bytes(entries).map_err(HttpError::e500)?;
+
+    Ok(ContentAddressedBody::new(etag, BytesBody::new(bytes, cbor_mime())))
+}
+
 /// Fetch trees for all of the requested keys concurrently.
 fn fetch_all_trees(
     repo: HgRepoContext,
@@ -126,6 +178,8 @@ async fn fetch_tree(
     key: Key,
     fetch_metadata: bool,
 ) -> Result<TreeEntry, Error> {
+    ensure_key_served(&repo, &key, true).await?;
+
     let id = HgManifestId::from_node_hash(HgNodeHash::from(key.hgid));
 
     let ctx = id
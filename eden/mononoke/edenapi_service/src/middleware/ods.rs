@@ -49,6 +49,7 @@ define_stats! {
     ephemeral_prepare_duration_ms: histogram(100, 0, 5000, Average, Sum, Count; P 50; P 75; P 95; P 99),
     fetch_snapshot_duration_ms: histogram(100, 0, 5000, Average, Sum, Count; P 50; P 75; P 95; P 99),
     alter_snapshot_duration_ms: histogram(100, 0, 5000, Average, Sum, Count; P 50; P 75; P 95; P 99),
+    list_snapshots_duration_ms: histogram(100, 0, 5000, Average, Sum, Count; P 50; P 75; P 95; P 99),
     commit_graph_duration_ms: histogram(100, 0, 5000, Average, Sum, Count; P 50; P 75; P 95; P 99),
     commit_graph_v2_duration_ms: histogram(100, 0, 5000, Average, Sum, Count; P 50; P 75; P 95; P 99),
     download_file_duration_ms: histogram(100, 0, 5000, Average, Sum, Count; P 50; P 75; P 95; P 99),
@@ -109,6 +110,7 @@ fn log_stats(state: &mut State, status: StatusCode) -> Option<()> {
                 EphemeralPrepare => STATS::ephemeral_prepare_duration_ms.add_value(dur_ms),
                 FetchSnapshot => STATS::fetch_snapshot_duration_ms.add_value(dur_ms),
                 AlterSnapshot => STATS::alter_snapshot_duration_ms.add_value(dur_ms),
+                ListSnapshots => STATS::list_snapshots_duration_ms.add_value(dur_ms),
                 CommitGraph => STATS::commit_graph_duration_ms.add_value(dur_ms),
                 CommitGraphV2 => STATS::commit_graph_v2_duration_ms.add_value(dur_ms),
                 DownloadFile => STATS::download_file_duration_ms.add_value(dur_ms),
@@ -13,6 +13,7 @@ use fbinit::FacebookInit;
 use gotham::state::FromState;
 use gotham::state::State;
 use gotham_derive::StateData;
+use gotham_ext::middleware::ClientDeadline;
 use gotham_ext::middleware::MetadataState;
 use gotham_ext::middleware::Middleware;
 use gotham_ext::state_ext::StateExt;
@@ -80,7 +81,11 @@ impl Middleware for RequestContextMiddleware {
 
         let request_id = state.short_request_id();
         let logger = self.logger.new(o!("request_id" => request_id.to_string()));
-        let ctx = session.new_context(logger.clone(), (*self.scuba).clone());
+        let mut ctx = session.new_context(logger.clone(), (*self.scuba).clone());
+        // Work for the request should not outlive the client's interest in it.
+        if let Some(ClientDeadline(deadline)) = ClientDeadline::try_borrow_from(state) {
+            ctx = ctx.clone_with_deadline(*deadline);
+        }
 
         state.put(RequestContext::new(ctx, logger).await);
 
@@ -48,6 +48,18 @@ pub fn to_cbor_bytes<S: Serialize>(s: &S) -> Result<Bytes, Error> {
         .context(ErrorKind::SerializationFailed)
 }
 
+/// Serialize each of the items as CBOR, one after the other, in the same
+/// format as a streaming response.
+pub fn to_cbor_seq_bytes<S: Serialize>(
+    items: impl IntoIterator<Item = S>,
+) -> Result<Bytes, Error> {
+    let mut buf = Vec::new();
+    for item in items {
+        serde_cbor::to_writer(&mut buf, &item).context(ErrorKind::SerializationFailed)?;
+    }
+    Ok(Bytes::from(buf))
+}
+
 /// Serialize each item of the input stream as CBOR and return a streaming
 /// response. Any errors yielded by the stream will be filtered out.
 pub fn cbor_stream_filtered_errors<S, T>(stream: S) -> impl TryIntoResponse
@@ -259,4 +271,15 @@ mod tests {
         assert_eq!(errors.extra_error_count, 1);
         assert_eq!(format!("{:?}", errors.errors[0]).as_str(), "-1");
     }
+
+    #[test]
+    fn test_to_cbor_seq_bytes() {
+        let items = vec![CustomWireResult::ok(10), CustomWireResult::err(-1)];
+        let bytes = to_cbor_seq_bytes(items.clone()).unwrap();
+        let expected: Vec<u8> = items
+            .iter()
+            .flat_map(|item| to_cbor_bytes(item).unwrap())
+            .collect();
+        assert_eq!(bytes, expected);
+    }
 }
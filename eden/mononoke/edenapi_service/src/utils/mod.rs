@@ -5,7 +5,10 @@
  * GNU General Public License version 2.
  */
 
+use std::str::FromStr;
+
 use anyhow::Context;
+use anyhow::Error;
 use bytes::Bytes;
 use gotham::state::FromState;
 use gotham::state::State;
@@ -13,9 +16,15 @@ use gotham_ext::body_ext::BodyExt;
 use gotham_ext::error::HttpError;
 use http::HeaderMap;
 use hyper::Body;
+use mercurial_types::HgFileNodeId;
+use mercurial_types::HgNodeHash;
 use mononoke_api_hg::HgRepoContext;
 use mononoke_api_hg::RepoContextHgExt;
+use mononoke_types::hash;
+use mononoke_types::RepoPath;
 use rate_limiting::Metric;
+use types::HgId;
+use types::Key;
 
 use crate::context::ServerContext;
 use crate::errors::ErrorKind;
@@ -33,6 +42,7 @@ pub use cbor::custom_cbor_stream;
 pub use cbor::parse_cbor_request;
 pub use cbor::parse_wire_request;
 pub use cbor::to_cbor_bytes;
+pub use cbor::to_cbor_seq_bytes;
 pub use convert::to_create_change;
 pub use convert::to_hg_path;
 pub use convert::to_mononoke_path;
@@ -64,6 +74,79 @@ pub async fn get_repo(
         .map_err(|e| e.into_http_error(ErrorKind::RepoLoadFailed(name.to_string())))
 }
 
+/// Maximum number of ids that can be given in the path of a request.
+const MAX_HGIDS_PER_REQUEST: usize = 100;
+
+/// Parse an HgId given in the path of a request.
+pub fn parse_hgid(hgid: &str) -> Result<HgId, HttpError> {
+    HgId::from_str(hgid)
+        .with_context(|| ErrorKind::InvalidHgId(hgid.to_string()))
+        .map_err(HttpError::e400)
+}
+
+/// Parse a comma-separated list of HgIds given in the path of a request.
+pub fn parse_hgids(hgids: &str) -> Result<Vec<HgId>, HttpError> {
+    let hgids = hgids
+        .split(',')
+        .map(parse_hgid)
+        .collect::<Result<Vec<_>, _>>()?;
+    if hgids.len() > MAX_HGIDS_PER_REQUEST {
+        return Err(HttpError::e400(ErrorKind::TooManyIds(
+            hgids.len(),
+            MAX_HGIDS_PER_REQUEST,
+        )));
+    }
+    Ok(hgids)
+}
+
+/// The ETag of a response with the content-addressed items identified by
+/// `hgids`, in that order.
+pub fn hgids_etag(hgids: &[HgId]) -> String {
+    match hgids {
+        [hgid] => hgid.to_hex(),
+        hgids => {
+            let mut context = hash::Context::new(b"edenapi.hgids");
+            for hgid in hgids {
+                context.update(hgid.into_byte_array());
+            }
+            context.finish().to_hex().to_string()
+        }
+    }
+}
+
+/// Convert the error of fetching a single item into an `HttpError`, so that
+/// requests for items that don't exist fail with a 404.
+pub fn fetch_error_to_http(err: Error) -> HttpError {
+    match err.downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::KeyDoesNotExist(_)) => HttpError::e404(err),
+        _ => HttpError::e500(err),
+    }
+}
+
+/// Fail as if the file or tree with `key` didn't exist if the repo is a view
+/// of another repo that doesn't serve it.
+pub async fn ensure_key_served(
+    repo: &HgRepoContext,
+    key: &Key,
+    is_tree: bool,
+) -> Result<(), Error> {
+    // Files and trees are looked up by their paths, so views don't serve
+    // those whose keys don't have valid paths.
+    let path = match to_mpath(&key.path).ok().flatten() {
+        Some(path) if is_tree => RepoPath::DirectoryPath(path),
+        Some(path) => RepoPath::FilePath(path),
+        None => RepoPath::RootPath,
+    };
+    let filenode_id = HgFileNodeId::new(HgNodeHash::from(key.hgid));
+    let outside_view = repo
+        .hg_entries_outside_view(vec![(path, filenode_id)])
+        .await?;
+    if !outside_view.is_empty() {
+        return Err(ErrorKind::KeyDoesNotExist(key.clone()).into());
+    }
+    Ok(())
+}
+
 pub async fn get_request_body(state: &mut State) -> Result<Bytes, HttpError> {
     let body = Body::take_from(state);
     let headers = HeaderMap::try_borrow_from(state);
@@ -81,3 +164,33 @@ pub async fn get_request_body(state: &mut State) -> Result<Bytes, HttpError> {
 
     Ok(body)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONES: &str = "1111111111111111111111111111111111111111";
+    const TWOS: &str = "2222222222222222222222222222222222222222";
+
+    #[test]
+    fn test_parse_hgids() {
+        let ids = parse_hgids(&format!("{},{}", ONES, TWOS)).unwrap();
+        let expected = vec![parse_hgid(ONES).unwrap(), parse_hgid(TWOS).unwrap()];
+        assert_eq!(ids, expected);
+
+        assert!(parse_hgids("").is_err());
+        assert!(parse_hgids(&format!("{},", ONES)).is_err());
+        assert!(parse_hgids(&vec![ONES; MAX_HGIDS_PER_REQUEST].join(",")).is_ok());
+        assert!(parse_hgids(&vec![ONES; MAX_HGIDS_PER_REQUEST + 1].join(",")).is_err());
+    }
+
+    #[test]
+    fn test_hgids_etag() {
+        let ones = parse_hgid(ONES).unwrap();
+        let twos = parse_hgid(TWOS).unwrap();
+        assert_eq!(hgids_etag(&[ones]), ONES);
+        assert_ne!(hgids_etag(&[ones, twos]), hgids_etag(&[twos, ones]));
+        assert_ne!(hgids_etag(&[ones, twos]), hgids_etag(&[ones]));
+        assert_eq!(hgids_etag(&[ones, twos]), hgids_etag(&[ones, twos]));
+    }
+}
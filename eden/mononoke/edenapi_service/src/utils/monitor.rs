@@ -10,8 +10,15 @@ use std::pin::Pin;
 use std::task::Context;
 use std::task::Poll;
 
+use context::SessionContainer;
 use futures::Stream;
 use pin_project::pin_project;
+use stats::prelude::*;
+
+define_stats! {
+    prefix = "mononoke.edenapi.request";
+    cancelled_on_disconnect: timeseries(Rate, Sum),
+}
 
 #[pin_project]
 pub struct Monitor<T, P> {
@@ -53,3 +60,73 @@ impl<S: Stream, P> Stream for Monitor<S, P> {
         self.inner.size_hint()
     }
 }
+
+/// Cancels the session of a request if it is dropped before being disarmed,
+/// which happens when the client disconnects before the whole response has
+/// been sent.  Work for the request that is still running, such as spawned
+/// derivations, is then abandoned rather than finished for nobody.
+pub struct CancelOnDrop {
+    session: Option<SessionContainer>,
+}
+
+impl CancelOnDrop {
+    pub fn new(session: SessionContainer) -> Self {
+        Self {
+            session: Some(session),
+        }
+    }
+
+    pub fn disarm(&mut self) {
+        self.session = None;
+    }
+
+    /// Move the duty to cancel into a new guard, e.g. one that lives as long
+    /// as the response stream, disarming this one.
+    pub fn transfer(&mut self) -> Self {
+        Self {
+            session: self.session.take(),
+        }
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(session) = self.session.take() {
+            STATS::cancelled_on_disconnect.add_value(1);
+            session.cancel();
+        }
+    }
+}
+
+/// A stream that cancels the session of its request if it is dropped before
+/// it finishes.
+#[pin_project]
+pub struct CancelUnlessFinished<S> {
+    #[pin]
+    inner: S,
+    guard: CancelOnDrop,
+}
+
+impl<S> CancelUnlessFinished<S> {
+    pub fn new(inner: S, guard: CancelOnDrop) -> Self {
+        Self { inner, guard }
+    }
+}
+
+impl<S: Stream> Stream for CancelUnlessFinished<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let poll = this.inner.poll_next(cx);
+        if let Poll::Ready(None) = poll {
+            this.guard.disarm();
+        }
+        poll
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
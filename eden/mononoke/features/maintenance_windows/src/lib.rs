@@ -0,0 +1,193 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Enforcement of repos' maintenance windows.
+//!
+//! A maintenance window starts at each minute of its schedule and lasts for
+//! its duration.  Heavy background jobs wait for the windows that restrict
+//! them to end before doing more work, and writes are rejected during the
+//! windows that restrict them.
+
+use std::time::Duration;
+
+use chrono::DateTime;
+use chrono::Datelike;
+use chrono::Timelike;
+use chrono::Utc;
+use context::CoreContext;
+use metaconfig_types::MaintenanceOperation;
+use metaconfig_types::MaintenanceWindow;
+use metaconfig_types::ReadOnlyInfo;
+use slog::info;
+
+/// A maintenance window that is in progress.
+pub struct ActiveWindow<'a> {
+    pub window: &'a MaintenanceWindow,
+    pub ends_at: DateTime<Utc>,
+}
+
+/// When the occurrence of a window that is in progress at `now` ends, if
+/// one is in progress.
+fn active_until(window: &MaintenanceWindow, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let duration = chrono::Duration::from_std(window.duration).ok()?;
+    let one_minute = chrono::Duration::minutes(1);
+    let mut start = now.with_second(0)?.with_nanosecond(0)?;
+    // The most recent start ends last, so look for it from now backwards,
+    // skipping whole days and hours that aren't in the schedule.
+    while start + duration > now {
+        let schedule = &window.schedule;
+        let day_of_week = start.weekday().num_days_from_sunday();
+        if !schedule.matches_day(start.day(), start.month(), day_of_week) {
+            start = start.with_hour(0)?.with_minute(0)? - one_minute;
+        } else if !schedule.hours.contains(&start.hour()) {
+            start = start.with_minute(0)? - one_minute;
+        } else if !schedule.minutes.contains(&start.minute()) {
+            start = start - one_minute;
+        } else {
+            return Some(start + duration);
+        }
+    }
+    None
+}
+
+/// The window restricting `operation` that is in progress at `now`, if any.
+/// If several are in progress, this is the one that ends last.
+pub fn active_window(
+    windows: &[MaintenanceWindow],
+    operation: MaintenanceOperation,
+    now: DateTime<Utc>,
+) -> Option<ActiveWindow<'_>> {
+    windows
+        .iter()
+        .filter(|window| window.operations.contains(&operation))
+        .filter_map(|window| {
+            let ends_at = active_until(window, now)?;
+            Some(ActiveWindow { window, ends_at })
+        })
+        .max_by_key(|active| active.ends_at)
+}
+
+/// Why writes are rejected at `now`, if a window restricting them is in
+/// progress.
+pub fn write_restriction(
+    windows: &[MaintenanceWindow],
+    now: DateTime<Utc>,
+) -> Option<ReadOnlyInfo> {
+    let active = active_window(windows, MaintenanceOperation::Writes, now)?;
+    let reason = active
+        .window
+        .reason
+        .clone()
+        .unwrap_or_else(|| "Repo is in a maintenance window".to_string());
+    Some(ReadOnlyInfo {
+        expected_unlock: Some(active.ends_at.timestamp()),
+        ..ReadOnlyInfo::new(reason)
+    })
+}
+
+/// Wait until no window restricting background jobs is in progress.  Heavy
+/// background jobs call this before each batch of work, so that they pause
+/// during the windows.
+pub async fn wait_until_background_jobs_allowed(
+    ctx: &CoreContext,
+    repo_name: &str,
+    windows: &[MaintenanceWindow],
+) {
+    let operation = MaintenanceOperation::BackgroundJobs;
+    while let Some(active) = active_window(windows, operation, Utc::now()) {
+        info!(
+            ctx.logger(),
+            "Pausing work on {} for maintenance window until {}", repo_name, active.ends_at
+        );
+        let wait = (active.ends_at - Utc::now())
+            .to_std()
+            .unwrap_or(Duration::ZERO);
+        tokio::time::sleep(wait).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn window(schedule: &str, duration_mins: u64) -> MaintenanceWindow {
+        MaintenanceWindow {
+            schedule: schedule.parse().unwrap(),
+            duration: Duration::from_secs(duration_mins * 60),
+            operations: vec![MaintenanceOperation::BackgroundJobs],
+            reason: None,
+        }
+    }
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        // May 2023 started on a Monday.
+        Utc.with_ymd_and_hms(2023, 5, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_active_until() {
+        let weekday_afternoons = window("30 14 * * 1-5", 90);
+        assert_eq!(active_until(&weekday_afternoons, at(1, 14, 29)), None);
+        assert_eq!(
+            active_until(&weekday_afternoons, at(1, 14, 30)),
+            Some(at(1, 16, 0))
+        );
+        assert_eq!(
+            active_until(&weekday_afternoons, at(1, 15, 59)),
+            Some(at(1, 16, 0))
+        );
+        assert_eq!(active_until(&weekday_afternoons, at(1, 16, 0)), None);
+        // Saturday.
+        assert_eq!(active_until(&weekday_afternoons, at(6, 15, 0)), None);
+
+        // Windows can span midnight.
+        let nights = window("0 23 * * *", 120);
+        assert_eq!(active_until(&nights, at(2, 0, 30)), Some(at(2, 1, 0)));
+
+        // Days are skipped over without missing the end of a long window.
+        let weekly = window("45 23 * * 0", 7 * 24 * 60);
+        assert_eq!(active_until(&weekly, at(13, 23, 0)), Some(at(14, 23, 45)));
+        let quarterly = window("0 2 1 */3 *", 60);
+        assert_eq!(active_until(&quarterly, at(1, 2, 30)), None);
+        assert_eq!(
+            active_until(&quarterly, at(1, 2, 30).with_month(4).unwrap()),
+            Some(at(1, 3, 0).with_month(4).unwrap())
+        );
+
+        // Overlapping occurrences end with the last one.
+        let every_ten_minutes = window("*/10 * * * *", 15);
+        assert_eq!(
+            active_until(&every_ten_minutes, at(1, 12, 12)),
+            Some(at(1, 12, 25))
+        );
+    }
+
+    #[test]
+    fn test_write_restriction() {
+        let background = window("0 * * * *", 30);
+        let writes = MaintenanceWindow {
+            operations: vec![MaintenanceOperation::Writes],
+            reason: Some("Database upgrade".to_string()),
+            ..window("0 2 * * *", 60)
+        };
+        let windows = vec![background, writes];
+
+        assert_eq!(write_restriction(&windows, at(1, 12, 10)), None);
+        let info = write_restriction(&windows, at(1, 2, 10)).unwrap();
+        assert_eq!(info.reason, "Database upgrade");
+        assert_eq!(info.expected_unlock, Some(at(1, 3, 0).timestamp()));
+
+        let active = active_window(
+            &windows,
+            MaintenanceOperation::BackgroundJobs,
+            at(1, 12, 10),
+        );
+        assert_eq!(active.map(|active| active.ends_at), Some(at(1, 12, 30)));
+    }
+}
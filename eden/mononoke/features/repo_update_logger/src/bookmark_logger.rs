@@ -11,6 +11,7 @@ use std::fmt;
 use anyhow::Result;
 use async_trait::async_trait;
 use bookmarks::BookmarkUpdateReason;
+use bookmarks_types::BookmarkCategory;
 use bookmarks_types::BookmarkKey;
 use bookmarks_types::BookmarkKind;
 use context::CoreContext;
@@ -21,6 +22,7 @@ use mononoke_bookmark_rust_logger::MononokeBookmarkLogger;
 use mononoke_types::ChangesetId;
 use repo_identity::RepoIdentityRef;
 use serde_derive::Serialize;
+use webhooks::WebhookEvent;
 #[cfg(fbcode_build)]
 use whence_logged::WhenceScribeLogged;
 
@@ -141,11 +143,45 @@ impl Loggable for PlainBookmarkInfo {
     }
 }
 
+/// The event that webhooks are sent for a bookmark operation, if any.
+///
+/// Scratch bookmarks aren't reported.  Tags are only reported as tag
+/// events, so creating one is a tag creation, and moving or deleting one
+/// isn't reported.
+fn webhook_event(info: &BookmarkInfo) -> Option<WebhookEvent> {
+    if info.bookmark_kind == BookmarkKind::Scratch {
+        return None;
+    }
+    if *info.bookmark_name.category() == BookmarkCategory::Tag {
+        return match info.operation {
+            BookmarkOperation::Create(changeset_id) => Some(WebhookEvent::TagCreated {
+                tag: info.bookmark_name.to_string(),
+                changeset_id,
+            }),
+            _ => None,
+        };
+    }
+    Some(WebhookEvent::BookmarkMoved {
+        bookmark: info.bookmark_name.to_string(),
+        old_value: info.operation.old_bookmark_value(),
+        new_value: info.operation.new_bookmark_value(),
+        reason: info.reason.to_string(),
+    })
+}
+
 pub async fn log_bookmark_operation(
     ctx: &CoreContext,
     repo: &(impl RepoIdentityRef + RepoConfigRef),
     info: &BookmarkInfo,
 ) {
+    if let Some(event) = webhook_event(info) {
+        webhooks::send_event(
+            ctx,
+            repo.repo_identity().name(),
+            &repo.repo_config().webhooks,
+            event,
+        );
+    }
     if let Some(bookmark_logging_destination) = &repo
         .repo_config()
         .update_logging_config
@@ -6,7 +6,8 @@
  */
 
 //! Log changes to the repository (new commits and bookmark updates) to
-//! external telemetry.
+//! external telemetry, and post bookmark updates to the repository's
+//! webhooks.
 
 mod bookmark_logger;
 mod commit_logger;
@@ -0,0 +1,280 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Creation of code reviews for commits pushed to review bookmarks.
+//!
+//! Pushing a commit to the scratch bookmark `<prefix><bookmark>`, e.g.
+//! `for/master`, creates a review of the commit against `<bookmark>`.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use bookmarks::BookmarkKey;
+use context::CoreContext;
+use hyper::client::HttpConnector;
+use hyper::Body;
+use hyper::Client;
+use hyper::Request;
+use hyper::Uri;
+use hyper_openssl::HttpsConnector;
+use metaconfig_types::PushToReviewConfig;
+use mononoke_types::ChangesetId;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// A review to create for a pushed commit.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ReviewRequest {
+    pub repo_name: String,
+    /// The review bookmark that the commit was pushed to.
+    pub review_bookmark: String,
+    /// The bookmark that the commit is reviewed against.
+    pub target_bookmark: String,
+    pub changeset_id: ChangesetId,
+    /// Who pushed the commit, if known.
+    pub pusher: Option<String>,
+}
+
+/// How long creating a review may take before it is given up on.  Reviews
+/// are created while the client waits for its push to finish.
+const REVIEW_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Something that creates code reviews.
+#[facet::facet]
+#[async_trait]
+pub trait ReviewCreator: Send + Sync {
+    /// Create a review, and return its URL.
+    async fn create_review(&self, ctx: &CoreContext, request: &ReviewRequest) -> Result<String>;
+}
+
+#[derive(Deserialize)]
+struct WebhookResponse {
+    url: String,
+}
+
+/// Creates reviews by posting requests to a webhook as JSON.  The webhook
+/// responds with a JSON object whose `url` field is the URL of the review.
+pub struct WebhookReviewCreator {
+    client: Client<HttpsConnector<HttpConnector>>,
+    uri: Uri,
+    timeout: Duration,
+}
+
+impl WebhookReviewCreator {
+    pub fn new(webhook_url: &str) -> Result<Self> {
+        Self::with_timeout(webhook_url, REVIEW_TIMEOUT)
+    }
+
+    pub fn with_timeout(webhook_url: &str, timeout: Duration) -> Result<Self> {
+        let connector = HttpsConnector::new()?;
+        let client = Client::builder().build(connector);
+        let uri = webhook_url
+            .parse()
+            .with_context(|| format!("Invalid review webhook URL {}", webhook_url))?;
+        Ok(Self {
+            client,
+            uri,
+            timeout,
+        })
+    }
+
+    async fn post(&self, request: &ReviewRequest) -> Result<String> {
+        let body = serde_json::to_vec(request)?;
+        let request = Request::post(self.uri.clone())
+            .header("Content-Type", "application/json")
+            .body(Body::from(body))?;
+        let response = self
+            .client
+            .request(request)
+            .await
+            .with_context(|| format!("Request to {} failed", self.uri))?;
+        let (head, body) = response.into_parts();
+        let body = hyper::body::to_bytes(body)
+            .await
+            .with_context(|| format!("Failed to read response from {}", self.uri))?;
+        if !head.status.is_success() {
+            bail!(
+                "Request to {} failed with status {}: {}",
+                self.uri,
+                head.status,
+                String::from_utf8_lossy(&body)
+            );
+        }
+        let response: WebhookResponse = serde_json::from_slice(&body)
+            .with_context(|| format!("Invalid response from {}", self.uri))?;
+        Ok(response.url)
+    }
+}
+
+#[async_trait]
+impl ReviewCreator for WebhookReviewCreator {
+    async fn create_review(&self, _ctx: &CoreContext, request: &ReviewRequest) -> Result<String> {
+        tokio::time::timeout(self.timeout, self.post(request))
+            .await
+            .map_err(|_| anyhow!("Request to {} timed out after {:?}", self.uri, self.timeout))?
+    }
+}
+
+/// Review creator for repos that don't create reviews.
+pub struct DisabledReviewCreator;
+
+#[async_trait]
+impl ReviewCreator for DisabledReviewCreator {
+    async fn create_review(&self, _ctx: &CoreContext, _request: &ReviewRequest) -> Result<String> {
+        bail!("Review creation is not configured for this repo")
+    }
+}
+
+/// The review creator for a repo, given its push to review config.
+pub fn review_creator(config: Option<&PushToReviewConfig>) -> Result<ArcReviewCreator> {
+    Ok(match config {
+        Some(config) => Arc::new(WebhookReviewCreator::new(&config.webhook_url)?),
+        None => Arc::new(DisabledReviewCreator),
+    })
+}
+
+/// The bookmark that a push to `bookmark` should be reviewed against, if
+/// `bookmark` is a review bookmark.
+pub fn review_target(config: &PushToReviewConfig, bookmark: &BookmarkKey) -> Option<BookmarkKey> {
+    let target = bookmark.as_str().strip_prefix(&config.bookmark_prefix)?;
+    if target.is_empty() {
+        return None;
+    }
+    BookmarkKey::new(target).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+    use tokio::net::TcpStream;
+
+    use super::*;
+
+    async fn read_request(stream: &mut TcpStream) {
+        let mut request = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            if n == 0 {
+                return;
+            }
+            request.extend_from_slice(&buf[..n]);
+            if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                let headers = String::from_utf8_lossy(&request[..end]).to_lowercase();
+                let length = headers
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length:"))
+                    .map_or(0, |length| length.trim().parse().unwrap());
+                if request.len() >= end + 4 + length {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Answer a single HTTP request with `status` and `body` after `delay`,
+    /// returning the URL to send the request to.
+    async fn serve_once(status: &'static str, body: &'static str, delay: Duration) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/create", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            read_request(&mut stream).await;
+            tokio::time::sleep(delay).await;
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+        url
+    }
+
+    fn review_request() -> ReviewRequest {
+        ReviewRequest {
+            repo_name: "repo".to_string(),
+            review_bookmark: "for/master".to_string(),
+            target_bookmark: "master".to_string(),
+            changeset_id: ChangesetId::from_bytes([1; 32]).unwrap(),
+            pusher: Some("user".to_string()),
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_webhook_creates_review(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let body = r#"{"url": "https://review.example.com/D1"}"#;
+        let url = serve_once("200 OK", body, Duration::ZERO).await;
+        let creator = WebhookReviewCreator::new(&url)?;
+        let review = creator.create_review(&ctx, &review_request()).await?;
+        assert_eq!(review, "https://review.example.com/D1");
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_webhook_failure(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let url = serve_once("500 Internal Server Error", "broken", Duration::ZERO).await;
+        let creator = WebhookReviewCreator::new(&url)?;
+        let err = creator
+            .create_review(&ctx, &review_request())
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("500 Internal Server Error: broken"));
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_webhook_timeout(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let url = serve_once("200 OK", "{}", Duration::from_secs(60)).await;
+        let creator = WebhookReviewCreator::with_timeout(&url, Duration::from_millis(100))?;
+        let err = creator
+            .create_review(&ctx, &review_request())
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("timed out"));
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_disabled_review_creator(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let creator = review_creator(None)?;
+        assert!(creator.create_review(&ctx, &review_request()).await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_review_target() -> Result<()> {
+        let config = PushToReviewConfig {
+            bookmark_prefix: "for/".to_string(),
+            webhook_url: "https://review.example.com/create".to_string(),
+        };
+        assert_eq!(
+            review_target(&config, &BookmarkKey::new("for/master")?),
+            Some(BookmarkKey::new("master")?)
+        );
+        assert_eq!(
+            review_target(&config, &BookmarkKey::new("for/release/1.0")?),
+            Some(BookmarkKey::new("release/1.0")?)
+        );
+        assert_eq!(review_target(&config, &BookmarkKey::new("for/")?), None);
+        assert_eq!(review_target(&config, &BookmarkKey::new("master")?), None);
+        Ok(())
+    }
+}
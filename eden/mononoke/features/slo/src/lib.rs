@@ -0,0 +1,393 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Tracking of the attainment of repos' service level objectives.
+//!
+//! Requests are counted per repo and method over a rolling window.  Each
+//! objective allows a fraction of requests to miss it, which is its error
+//! budget.  The burn rate is how fast the budget is being spent: at a burn
+//! rate of 1 the budget is used up exactly over the window, so sustained
+//! burn rates above 1 are worth alerting on.
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use context::CoreContext;
+use metaconfig_types::SloConfig;
+use slog::warn;
+use stats::prelude::*;
+
+define_stats! {
+    prefix = "mononoke.slo";
+    requests: dynamic_timeseries("{}.{}.requests", (repo: String, method: String); Rate, Sum),
+    internal_failures: dynamic_timeseries(
+        "{}.{}.internal_failures",
+        (repo: String, method: String);
+        Rate, Sum
+    ),
+    slow_requests: dynamic_timeseries(
+        "{}.{}.slow_requests",
+        (repo: String, method: String);
+        Rate, Sum
+    ),
+    attainment_ppm: dynamic_singleton_counter(
+        "{}.{}.attainment_ppm",
+        (repo: String, objective: String)
+    ),
+    burn_rate_permille: dynamic_singleton_counter(
+        "{}.{}.burn_rate_permille",
+        (repo: String, objective: String)
+    ),
+}
+
+/// Fraction of requests, in parts per million, that must be faster than
+/// their method's p99 latency target.
+const LATENCY_TARGET_PPM: u64 = 990_000;
+/// Windows are split into this many buckets, which expire one at a time.
+const BUCKETS_PER_WINDOW: u32 = 60;
+const REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct Counts {
+    requests: u64,
+    internal_failures: u64,
+    slow_requests: u64,
+}
+
+impl Counts {
+    fn add(&mut self, other: Counts) {
+        self.requests += other.requests;
+        self.internal_failures += other.internal_failures;
+        self.slow_requests += other.slow_requests;
+    }
+}
+
+/// Request counts over a rolling window.
+struct RollingWindow {
+    window: Duration,
+    bucket_width: Duration,
+    /// The start of each bucket and the counts of the requests in it,
+    /// oldest first.
+    buckets: VecDeque<(Instant, Counts)>,
+}
+
+impl RollingWindow {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            bucket_width: window / BUCKETS_PER_WINDOW,
+            buckets: VecDeque::new(),
+        }
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some((start, _)) = self.buckets.front() {
+            if now.saturating_duration_since(*start) < self.window {
+                break;
+            }
+            self.buckets.pop_front();
+        }
+    }
+
+    fn record(&mut self, now: Instant, counts: Counts) {
+        self.expire(now);
+        match self.buckets.back_mut() {
+            Some((start, bucket)) if now.saturating_duration_since(*start) < self.bucket_width => {
+                bucket.add(counts)
+            }
+            _ => self.buckets.push_back((now, counts)),
+        }
+    }
+
+    fn totals(&mut self, now: Instant) -> Counts {
+        self.expire(now);
+        let mut totals = Counts::default();
+        for (_, counts) in self.buckets.iter() {
+            totals.add(*counts);
+        }
+        totals
+    }
+}
+
+/// The attainment of one objective of a repo over its window.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Attainment {
+    pub repo: String,
+    /// `availability`, or `latency.<method>`.
+    pub objective: String,
+    /// Fraction of requests that must meet the objective, in parts per
+    /// million.
+    pub target_ppm: u64,
+    pub requests: u64,
+    /// Requests that missed the objective.
+    pub bad_requests: u64,
+}
+
+impl Attainment {
+    /// Fraction of requests that met the objective, in parts per million.
+    pub fn attainment_ppm(&self) -> u64 {
+        if self.requests == 0 {
+            1_000_000
+        } else {
+            1_000_000 - self.bad_requests * 1_000_000 / self.requests
+        }
+    }
+
+    /// How fast the error budget is being spent, relative to the rate that
+    /// would spend exactly all of it over the window.
+    pub fn burn_rate(&self) -> f64 {
+        if self.bad_requests == 0 {
+            return 0.0;
+        }
+        let budget_ppm = 1_000_000 - self.target_ppm.min(1_000_000);
+        if budget_ppm == 0 {
+            return f64::INFINITY;
+        }
+        self.bad_requests as f64 * 1_000_000.0 / (self.requests as f64 * budget_ppm as f64)
+    }
+
+    /// Fraction of the error budget that is left, in parts per million.
+    pub fn budget_remaining_ppm(&self) -> u64 {
+        ((1.0 - self.burn_rate()).max(0.0) * 1_000_000.0) as u64
+    }
+}
+
+/// Tracks the attainment of each repo's service level objectives.
+pub struct SloTracker {
+    configs: HashMap<String, SloConfig>,
+    /// Windows of the requests to each method of each repo.
+    windows: Mutex<HashMap<(String, String), RollingWindow>>,
+}
+
+impl SloTracker {
+    /// Create a tracker for repos with these configs.  Repos without
+    /// objectives aren't tracked.
+    pub fn new(configs: impl IntoIterator<Item = (String, SloConfig)>) -> Self {
+        Self {
+            configs: configs
+                .into_iter()
+                .filter(|(_, config)| !config.is_empty())
+                .collect(),
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a completed request.  Only internal failures count against
+    /// availability, as request errors are the fault of the client.
+    pub fn record(&self, repo: &str, method: &str, latency: Duration, internal_failure: bool) {
+        self.record_at(Instant::now(), repo, method, latency, internal_failure)
+    }
+
+    fn record_at(
+        &self,
+        now: Instant,
+        repo: &str,
+        method: &str,
+        latency: Duration,
+        internal_failure: bool,
+    ) {
+        let config = match self.configs.get(repo) {
+            Some(config) => config,
+            None => return,
+        };
+        let slow = config
+            .method_p99_latency
+            .get(method)
+            .map_or(false, |target| latency > *target);
+        let counts = Counts {
+            requests: 1,
+            internal_failures: internal_failure as u64,
+            slow_requests: slow as u64,
+        };
+
+        let key = (repo.to_string(), method.to_string());
+        STATS::requests.add_value(1, key.clone());
+        if internal_failure {
+            STATS::internal_failures.add_value(1, key.clone());
+        }
+        if slow {
+            STATS::slow_requests.add_value(1, key.clone());
+        }
+        self.windows
+            .lock()
+            .expect("lock poisoned")
+            .entry(key)
+            .or_insert_with(|| RollingWindow::new(config.window))
+            .record(now, counts);
+    }
+
+    /// The attainment of each objective of each repo that has had requests
+    /// within its window.
+    pub fn attainment(&self) -> Vec<Attainment> {
+        self.attainment_at(Instant::now())
+    }
+
+    fn attainment_at(&self, now: Instant) -> Vec<Attainment> {
+        let mut windows = self.windows.lock().expect("lock poisoned");
+        let mut repo_totals: HashMap<&str, Counts> = HashMap::new();
+        let mut attainment = Vec::new();
+        for ((repo, method), window) in windows.iter_mut() {
+            let totals = window.totals(now);
+            repo_totals.entry(repo.as_str()).or_default().add(totals);
+            if self.configs[repo].method_p99_latency.contains_key(method) {
+                attainment.push(Attainment {
+                    repo: repo.clone(),
+                    objective: format!("latency.{}", method),
+                    target_ppm: LATENCY_TARGET_PPM,
+                    requests: totals.requests,
+                    bad_requests: totals.slow_requests,
+                });
+            }
+        }
+        for (repo, totals) in repo_totals {
+            if let Some(target_ppm) = self.configs[repo].availability_target_ppm {
+                attainment.push(Attainment {
+                    repo: repo.to_string(),
+                    objective: "availability".to_string(),
+                    target_ppm,
+                    requests: totals.requests,
+                    bad_requests: totals.internal_failures,
+                });
+            }
+        }
+        attainment.retain(|attainment| attainment.requests > 0);
+        attainment.sort_by(|a, b| (&a.repo, &a.objective).cmp(&(&b.repo, &b.objective)));
+        attainment
+    }
+
+    /// Publish the attainment of each objective as counters, and log it to
+    /// scuba.  Objectives whose budget is being burned faster than it lasts
+    /// are also logged as warnings.
+    pub fn report(&self, ctx: &CoreContext) {
+        for attainment in self.attainment() {
+            let burn_rate = attainment.burn_rate();
+            let key = (attainment.repo.clone(), attainment.objective.clone());
+            STATS::attainment_ppm.set_value(
+                ctx.fb,
+                attainment.attainment_ppm() as i64,
+                key.clone(),
+            );
+            STATS::burn_rate_permille.set_value(ctx.fb, (burn_rate * 1000.0) as i64, key);
+            if burn_rate > 1.0 {
+                warn!(
+                    ctx.logger(),
+                    "{} is burning its {} error budget {:.1}x too fast",
+                    attainment.repo,
+                    attainment.objective,
+                    burn_rate
+                );
+            }
+            ctx.scuba()
+                .clone()
+                .add("reponame", attainment.repo.clone())
+                .add("slo_objective", attainment.objective.clone())
+                .add("slo_target_ppm", attainment.target_ppm)
+                .add("slo_attainment_ppm", attainment.attainment_ppm())
+                .add("slo_requests", attainment.requests)
+                .add("slo_bad_requests", attainment.bad_requests)
+                .add("slo_burn_rate", burn_rate)
+                .add("slo_budget_remaining_ppm", attainment.budget_remaining_ppm())
+                .log_with_msg("SLO attainment", None);
+        }
+    }
+
+    /// Report attainment every `REPORT_INTERVAL`, forever.
+    pub async fn report_forever(self: Arc<Self>, ctx: CoreContext) {
+        let mut interval = tokio::time::interval(REPORT_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.report(&ctx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use maplit::hashmap;
+
+    use super::*;
+
+    fn tracker() -> SloTracker {
+        let config = SloConfig {
+            availability_target_ppm: Some(999_000),
+            method_p99_latency: hashmap! {
+                "repo_list_bookmarks".to_string() => Duration::from_millis(100),
+            },
+            window: Duration::from_secs(600),
+        };
+        SloTracker::new(vec![
+            ("repo".to_string(), config),
+            ("untracked".to_string(), SloConfig::default()),
+        ])
+    }
+
+    #[test]
+    fn test_attainment() {
+        let tracker = tracker();
+        let start = Instant::now();
+        let fast = Duration::from_millis(10);
+        let slow = Duration::from_millis(200);
+        for i in 0..1000 {
+            tracker.record_at(start, "repo", "repo_list_bookmarks", fast, i < 3);
+            tracker.record_at(start, "repo", "commit_info", slow, false);
+            tracker.record_at(start, "untracked", "commit_info", fast, true);
+        }
+        for _ in 0..5 {
+            tracker.record_at(start, "repo", "repo_list_bookmarks", slow, false);
+        }
+
+        let attainment = tracker.attainment_at(start);
+        assert_eq!(
+            attainment,
+            vec![
+                Attainment {
+                    repo: "repo".to_string(),
+                    objective: "availability".to_string(),
+                    target_ppm: 999_000,
+                    requests: 2005,
+                    bad_requests: 3,
+                },
+                Attainment {
+                    repo: "repo".to_string(),
+                    objective: "latency.repo_list_bookmarks".to_string(),
+                    target_ppm: LATENCY_TARGET_PPM,
+                    requests: 1005,
+                    bad_requests: 5,
+                },
+            ]
+        );
+        // 3 failures in 2005 requests spends the 0.1% budget 1.5x too fast.
+        assert!((attainment[0].burn_rate() - 1.496).abs() < 0.001);
+        assert_eq!(attainment[0].budget_remaining_ppm(), 0);
+        // 5 slow requests in 1005 spends about half of the 1% budget.
+        assert!((attainment[1].burn_rate() - 0.4975).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_window_expiry() {
+        let tracker = tracker();
+        let start = Instant::now();
+        let fast = Duration::from_millis(10);
+        tracker.record_at(start, "repo", "commit_info", fast, true);
+        tracker.record_at(start + Duration::from_secs(300), "repo", "commit_info", fast, false);
+
+        let availability = |now| {
+            let attainment = tracker.attainment_at(now);
+            attainment
+                .into_iter()
+                .map(|attainment| (attainment.requests, attainment.bad_requests))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(availability(start + Duration::from_secs(599)), vec![(2, 1)]);
+        assert_eq!(availability(start + Duration::from_secs(600)), vec![(1, 0)]);
+        assert_eq!(availability(start + Duration::from_secs(900)), vec![]);
+    }
+}
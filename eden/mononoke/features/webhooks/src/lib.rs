@@ -0,0 +1,476 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Posting of repo events to the webhooks configured for a repo.
+//!
+//! Each event is posted as JSON to every webhook that subscribes to its
+//! kind.  Deliveries happen in the background and are retried with
+//! exponential backoff.  Events that can't be delivered are logged as dead
+//! letters, with their payload, so that they can be redelivered by hand.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use context::CoreContext;
+use hmac::Hmac;
+use hmac::Mac;
+use hyper::client::HttpConnector;
+use hyper::Body;
+use hyper::Client;
+use hyper::Request;
+use hyper::StatusCode;
+use hyper_openssl::HttpsConnector;
+use metaconfig_types::WebhookConfig;
+use metaconfig_types::WebhookEventKind;
+use mononoke_types::ChangesetId;
+use mononoke_types::Timestamp;
+use once_cell::sync::Lazy;
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+use sha2::Sha256;
+use slog::warn;
+use stats::prelude::*;
+use tokio::sync::Semaphore;
+
+define_stats! {
+    prefix = "mononoke.webhooks";
+    delivered: dynamic_timeseries("{}.delivered", (repo: String); Rate, Sum),
+    retried: dynamic_timeseries("{}.retried", (repo: String); Rate, Sum),
+    dead_lettered: dynamic_timeseries("{}.dead_lettered", (repo: String); Rate, Sum),
+}
+
+const RETRY_POLICY: RetryPolicy = RetryPolicy {
+    max_attempts: 5,
+    base_delay: Duration::from_secs(1),
+};
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Maximum number of deliveries in flight at once, across all repos.
+/// Events sent while this many are in flight are dead-lettered straight
+/// away.
+const MAX_CONCURRENT_DELIVERIES: usize = 1000;
+
+type HttpsClient = Client<HttpsConnector<HttpConnector>>;
+
+static DELIVERIES: Lazy<Arc<Semaphore>> =
+    Lazy::new(|| Arc::new(Semaphore::new(MAX_CONCURRENT_DELIVERIES)));
+static CLIENT: OnceCell<HttpsClient> = OnceCell::new();
+
+/// The client that all deliveries share, so that connections to webhooks
+/// are reused.
+fn client() -> Result<&'static HttpsClient> {
+    CLIENT.get_or_try_init(|| Ok(Client::builder().build(HttpsConnector::new()?)))
+}
+
+#[derive(Clone, Copy, Debug)]
+struct RetryPolicy {
+    /// Maximum number of attempts to deliver an event, including the first.
+    max_attempts: u32,
+    /// Delay before the first retry, doubled for each subsequent retry.
+    base_delay: Duration,
+}
+
+/// A hook's rejection of a changeset.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct HookRejectionPayload {
+    pub hook_name: String,
+    pub changeset_id: ChangesetId,
+    pub description: String,
+    pub long_description: String,
+}
+
+/// An event in a repo that webhooks can subscribe to.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    BookmarkMoved {
+        bookmark: String,
+        /// Unset if the bookmark was created.
+        old_value: Option<ChangesetId>,
+        /// Unset if the bookmark was deleted.
+        new_value: Option<ChangesetId>,
+        reason: String,
+    },
+    TagCreated {
+        tag: String,
+        changeset_id: ChangesetId,
+    },
+    HookRejected {
+        bookmark: String,
+        rejections: Vec<HookRejectionPayload>,
+    },
+}
+
+impl WebhookEvent {
+    pub fn kind(&self) -> WebhookEventKind {
+        match self {
+            WebhookEvent::BookmarkMoved { .. } => WebhookEventKind::BookmarkMoved,
+            WebhookEvent::TagCreated { .. } => WebhookEventKind::TagCreated,
+            WebhookEvent::HookRejected { .. } => WebhookEventKind::HookRejected,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    repo_name: &'a str,
+    timestamp: i64,
+    #[serde(flatten)]
+    event: &'a WebhookEvent,
+}
+
+/// The signature header of a payload, which receivers can check to verify
+/// that the payload came from us.
+fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn subscribed_webhooks(
+    webhooks: &[WebhookConfig],
+    kind: WebhookEventKind,
+) -> impl Iterator<Item = &WebhookConfig> {
+    webhooks
+        .iter()
+        .filter(move |webhook| webhook.events.contains(&kind))
+}
+
+/// Whether a request that failed with this status may succeed if retried.
+fn is_retryable(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+async fn post(
+    client: &HttpsClient,
+    webhook: &WebhookConfig,
+    kind: WebhookEventKind,
+    body: &[u8],
+) -> Result<StatusCode> {
+    let mut request = Request::post(webhook.url.as_str())
+        .header("Content-Type", "application/json")
+        .header("X-Mononoke-Event", kind.as_str());
+    if let Some(secret) = &webhook.secret {
+        request = request.header("X-Mononoke-Signature", signature(secret, body));
+    }
+    let request = request.body(Body::from(body.to_vec()))?;
+    let response = tokio::time::timeout(ATTEMPT_TIMEOUT, client.request(request))
+        .await
+        .with_context(|| format!("Request to {} timed out", webhook.url))?
+        .with_context(|| format!("Request to {} failed", webhook.url))?;
+    Ok(response.status())
+}
+
+async fn deliver_with_retries(
+    client: &HttpsClient,
+    repo_name: &str,
+    webhook: &WebhookConfig,
+    kind: WebhookEventKind,
+    body: &[u8],
+    retry: RetryPolicy,
+) -> Result<()> {
+    let mut delay = retry.base_delay;
+    let mut attempt = 1;
+    loop {
+        let error = match post(client, webhook, kind, body).await {
+            Ok(status) if status.is_success() => return Ok(()),
+            Ok(status) if !is_retryable(status) => {
+                return Err(anyhow!("{} responded with status {}", webhook.url, status));
+            }
+            Ok(status) => anyhow!("{} responded with status {}", webhook.url, status),
+            Err(e) => e,
+        };
+        if attempt == retry.max_attempts {
+            return Err(error.context(format!("Gave up after {} attempts", attempt)));
+        }
+        STATS::retried.add_value(1, (repo_name.to_string(),));
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+/// Log an event that couldn't be delivered, with its payload.
+fn dead_letter(
+    ctx: &CoreContext,
+    repo_name: &str,
+    webhook: &WebhookConfig,
+    kind: WebhookEventKind,
+    body: &[u8],
+    error: &anyhow::Error,
+) {
+    STATS::dead_lettered.add_value(1, (repo_name.to_string(),));
+    warn!(
+        ctx.logger(),
+        "Failed to deliver {} event to webhook {}: {:#}",
+        kind.as_str(),
+        webhook.url,
+        error
+    );
+    ctx.scuba()
+        .clone()
+        .add("webhook_url", webhook.url.clone())
+        .add("webhook_event", kind.as_str())
+        .add("webhook_payload", String::from_utf8_lossy(body).into_owned())
+        .add("error", format!("{:#}", error))
+        .log_with_msg("Webhook delivery failed", None);
+}
+
+/// Deliver an event to a webhook, dead-lettering it if it can't be
+/// delivered.  Returns whether the event was delivered.
+async fn deliver(
+    ctx: &CoreContext,
+    client: &HttpsClient,
+    repo_name: &str,
+    webhook: &WebhookConfig,
+    kind: WebhookEventKind,
+    body: &[u8],
+    retry: RetryPolicy,
+) -> bool {
+    match deliver_with_retries(client, repo_name, webhook, kind, body, retry).await {
+        Ok(()) => {
+            STATS::delivered.add_value(1, (repo_name.to_string(),));
+            true
+        }
+        Err(e) => {
+            dead_letter(ctx, repo_name, webhook, kind, body, &e);
+            false
+        }
+    }
+}
+
+/// Start delivering an event to a webhook in the background.
+fn spawn_delivery(
+    ctx: &CoreContext,
+    repo_name: &str,
+    webhook: WebhookConfig,
+    kind: WebhookEventKind,
+    body: Vec<u8>,
+) -> Result<()> {
+    let permit = DELIVERIES
+        .clone()
+        .try_acquire_owned()
+        .map_err(|_| anyhow!("Too many webhook deliveries in flight"))?;
+    let client = client()?;
+    let ctx = ctx.clone();
+    let repo_name = repo_name.to_string();
+    tokio::spawn(async move {
+        deliver(
+            &ctx,
+            client,
+            &repo_name,
+            &webhook,
+            kind,
+            &body,
+            RETRY_POLICY,
+        )
+        .await;
+        drop(permit);
+    });
+    Ok(())
+}
+
+/// Post an event to each of `webhooks` that subscribes to it.  This doesn't
+/// wait for the deliveries, which happen in the background.
+pub fn send_event(
+    ctx: &CoreContext,
+    repo_name: &str,
+    webhooks: &[WebhookConfig],
+    event: WebhookEvent,
+) {
+    let kind = event.kind();
+    let subscribed = subscribed_webhooks(webhooks, kind)
+        .cloned()
+        .collect::<Vec<_>>();
+    if subscribed.is_empty() {
+        return;
+    }
+    let payload = Payload {
+        repo_name,
+        timestamp: Timestamp::now().timestamp_seconds(),
+        event: &event,
+    };
+    let body = match serde_json::to_vec(&payload) {
+        Ok(body) => body,
+        Err(e) => {
+            warn!(ctx.logger(), "Failed to serialize webhook payload: {:#}", e);
+            return;
+        }
+    };
+    for webhook in subscribed {
+        if let Err(e) = spawn_delivery(ctx, repo_name, webhook.clone(), kind, body.clone()) {
+            dead_letter(ctx, repo_name, &webhook, kind, &body, &e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    use fbinit::FacebookInit;
+    use mononoke_types::hash::Blake2;
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+    use tokio::net::TcpStream;
+
+    use super::*;
+
+    const TEST_RETRY_POLICY: RetryPolicy = RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(1),
+    };
+
+    async fn read_request(stream: &mut TcpStream) {
+        let mut request = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            if n == 0 {
+                return;
+            }
+            request.extend_from_slice(&buf[..n]);
+            if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                let headers = String::from_utf8_lossy(&request[..end]).to_lowercase();
+                let length = headers
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length:"))
+                    .map_or(0, |length| length.trim().parse().unwrap());
+                if request.len() >= end + 4 + length {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Serve a webhook that responds to each request with the next of
+    /// `statuses`, repeating the last one once they run out.  Returns the
+    /// webhook, and the number of requests it has received.
+    async fn serve(statuses: Vec<&'static str>) -> (WebhookConfig, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let webhook = WebhookConfig {
+            url: format!("http://{}/hook", listener.local_addr().unwrap()),
+            events: vec![WebhookEventKind::BookmarkMoved],
+            secret: Some("secret".to_string()),
+        };
+        let requests = Arc::new(AtomicUsize::new(0));
+        let served = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                read_request(&mut stream).await;
+                let index = served.fetch_add(1, Ordering::SeqCst);
+                let status = statuses[index.min(statuses.len() - 1)];
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        (webhook, requests)
+    }
+
+    async fn deliver_to(fb: FacebookInit, statuses: Vec<&'static str>) -> Result<(bool, usize)> {
+        let ctx = CoreContext::test_mock(fb);
+        let (webhook, requests) = serve(statuses).await;
+        let delivered = deliver(
+            &ctx,
+            client()?,
+            "repo",
+            &webhook,
+            WebhookEventKind::BookmarkMoved,
+            b"{}",
+            TEST_RETRY_POLICY,
+        )
+        .await;
+        Ok((delivered, requests.load(Ordering::SeqCst)))
+    }
+
+    #[fbinit::test]
+    async fn test_deliver(fb: FacebookInit) -> Result<()> {
+        assert_eq!(deliver_to(fb, vec!["200 OK"]).await?, (true, 1));
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_deliver_retries(fb: FacebookInit) -> Result<()> {
+        let statuses = vec!["502 Bad Gateway", "429 Too Many Requests", "200 OK"];
+        assert_eq!(deliver_to(fb, statuses).await?, (true, 3));
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_deliver_dead_letters(fb: FacebookInit) -> Result<()> {
+        // Server errors are retried until the attempts run out.
+        let statuses = vec!["500 Internal Server Error"];
+        assert_eq!(deliver_to(fb, statuses).await?, (false, 3));
+        // Other errors aren't retried.
+        let statuses = vec!["400 Bad Request", "200 OK"];
+        assert_eq!(deliver_to(fb, statuses).await?, (false, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_signature() {
+        // Test case 2 of RFC 4231.
+        assert_eq!(
+            signature("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_subscribed_webhooks() {
+        let webhook = |url: &str, events: Vec<WebhookEventKind>| WebhookConfig {
+            url: url.to_string(),
+            events,
+            secret: None,
+        };
+        let webhooks = vec![
+            webhook("https://a", vec![WebhookEventKind::BookmarkMoved]),
+            webhook(
+                "https://b",
+                vec![
+                    WebhookEventKind::TagCreated,
+                    WebhookEventKind::HookRejected,
+                ],
+            ),
+        ];
+        let urls = |kind| {
+            subscribed_webhooks(&webhooks, kind)
+                .map(|webhook| webhook.url.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(urls(WebhookEventKind::BookmarkMoved), vec!["https://a"]);
+        assert_eq!(urls(WebhookEventKind::HookRejected), vec!["https://b"]);
+    }
+
+    #[test]
+    fn test_payload() -> Result<()> {
+        let changeset_id = ChangesetId::new(Blake2::from_byte_array([1; 32]));
+        let event = WebhookEvent::TagCreated {
+            tag: "v1.0".to_string(),
+            changeset_id,
+        };
+        let payload = serde_json::to_value(Payload {
+            repo_name: "repo",
+            timestamp: 1000,
+            event: &event,
+        })?;
+        assert_eq!(payload["event"], "tag_created");
+        assert_eq!(payload["repo_name"], "repo");
+        assert_eq!(payload["tag"], "v1.0");
+        assert_eq!(payload["changeset_id"], serde_json::to_value(changeset_id)?);
+        Ok(())
+    }
+}
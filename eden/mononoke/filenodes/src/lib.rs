@@ -151,6 +151,55 @@ pub trait Filenodes: Send + Sync {
     fn prime_cache(&self, ctx: &CoreContext, filenodes: &[PreparedFilenode]);
 }
 
+/// Filenodes for a repo that doesn't generate them.  Writes and single
+/// filenode reads report that filenodes are disabled, so nothing is written
+/// and filenodes are never considered derived.
+///
+/// Ranges of filenodes are reported as empty rather than disabled: file
+/// history is then walked in full through the file envelopes, with null
+/// linknodes, instead of being cut down to a single entry as it is while
+/// filenodes are disabled temporarily.
+pub struct DisabledFilenodes;
+
+#[async_trait]
+impl Filenodes for DisabledFilenodes {
+    async fn add_filenodes(
+        &self,
+        _ctx: &CoreContext,
+        _info: Vec<PreparedFilenode>,
+    ) -> Result<FilenodeResult<()>> {
+        Ok(FilenodeResult::Disabled)
+    }
+
+    async fn add_or_replace_filenodes(
+        &self,
+        _ctx: &CoreContext,
+        _info: Vec<PreparedFilenode>,
+    ) -> Result<FilenodeResult<()>> {
+        Ok(FilenodeResult::Disabled)
+    }
+
+    async fn get_filenode(
+        &self,
+        _ctx: &CoreContext,
+        _path: &RepoPath,
+        _filenode: HgFileNodeId,
+    ) -> Result<FilenodeResult<Option<FilenodeInfo>>> {
+        Ok(FilenodeResult::Disabled)
+    }
+
+    async fn get_all_filenodes_maybe_stale(
+        &self,
+        _ctx: &CoreContext,
+        _path: &RepoPath,
+        _limit: Option<u64>,
+    ) -> Result<FilenodeResult<FilenodeRange>> {
+        Ok(FilenodeResult::Present(FilenodeRange::Filenodes(vec![])))
+    }
+
+    fn prime_cache(&self, _ctx: &CoreContext, _filenodes: &[PreparedFilenode]) {}
+}
+
 #[cfg(test)]
 mod test {
     use quickcheck::quickcheck;
@@ -79,6 +79,9 @@ mod test;
 #[derive(Debug, Copy, Clone)]
 pub struct FilestoreConfig {
     pub chunk_size: Option<u64>,
+    /// Files larger than this are stored in chunks of `chunk_size`.
+    /// Defaults to `chunk_size` if unset.
+    pub chunking_threshold: Option<u64>,
     pub concurrency: usize,
 }
 
@@ -86,9 +89,18 @@ impl FilestoreConfig {
     pub fn no_chunking_filestore() -> Self {
         Self {
             chunk_size: None,
+            chunking_threshold: None,
             concurrency: 1,
         }
     }
+
+    /// The chunk size to store a file of `expected_size` with, or `None` if
+    /// it should be stored inline.
+    fn chunk_size_for(&self, expected_size: expected_size::ExpectedSize) -> Option<u64> {
+        let chunk_size = self.chunk_size?;
+        let threshold = self.chunking_threshold.unwrap_or(chunk_size);
+        expected_size.should_chunk(threshold).then_some(chunk_size)
+    }
 }
 
 /// Key for storing. We'll compute any missing keys, but we must have the total size.
@@ -175,22 +187,53 @@ impl StoreRequest {
     }
 }
 
-/// Fetch the metadata for the underlying content. This will return None if the content does
-/// not exist. It might recompute metadata on the fly if the content exists but the metadata does
-/// not.
-pub async fn get_metadata<B: Blobstore>(
+/// Resolve a key to the canonical id of the content it refers to. Aliases are recorded when
+/// content is stored, so this lets callers that only have a SHA1, SHA256 or Git SHA1 of some
+/// content find it without hashing the content again. This will return None if the content does
+/// not exist, including when only its alias was stored (see `FetchKey::load`).
+pub async fn get_canonical_id<B: Blobstore>(
     blobstore: &B,
     ctx: &CoreContext,
     key: &FetchKey,
-) -> Result<Option<ContentMetadata>, Error> {
-    let maybe_id = key
-        .load(ctx, blobstore)
+) -> Result<Option<ContentId>, Error> {
+    let maybe_id = resolve_id(blobstore, ctx, key).await?;
+
+    match maybe_id {
+        Some(id) => {
+            let present = blobstore
+                .is_present(ctx, &id.blobstore_key())
+                .await?
+                .fail_if_unsure()?;
+            Ok(present.then_some(id))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Resolve a key to a content id without checking that the content exists.
+async fn resolve_id<B: Blobstore>(
+    blobstore: &B,
+    ctx: &CoreContext,
+    key: &FetchKey,
+) -> Result<Option<ContentId>, Error> {
+    key.load(ctx, blobstore)
         .await
         .map(Some)
         .or_else(|err| match err {
             LoadableError::Error(err) => Err(err),
             LoadableError::Missing(_) => Ok(None),
-        })?;
+        })
+}
+
+/// Fetch the metadata for the underlying content. This will return None if the content does
+/// not exist. It might recompute metadata on the fly if the content exists but the metadata does
+/// not.
+pub async fn get_metadata<B: Blobstore>(
+    blobstore: &B,
+    ctx: &CoreContext,
+    key: &FetchKey,
+) -> Result<Option<ContentMetadata>, Error> {
+    let maybe_id = resolve_id(blobstore, ctx, key).await?;
 
     match maybe_id {
         Some(id) => metadata::get_metadata(blobstore, ctx, id).await,
@@ -206,14 +249,7 @@ pub async fn get_metadata_readonly<B: Blobstore>(
     ctx: &CoreContext,
     key: &FetchKey,
 ) -> Result<Option<Option<ContentMetadata>>, Error> {
-    let maybe_id = key
-        .load(ctx, blobstore)
-        .await
-        .map(Some)
-        .or_else(|err| match err {
-            LoadableError::Error(err) => Err(err),
-            LoadableError::Missing(_) => Ok(None),
-        })?;
+    let maybe_id = resolve_id(blobstore, ctx, key).await?;
 
     match maybe_id {
         Some(id) => metadata::get_metadata_readonly(blobstore, ctx, id)
@@ -223,6 +259,17 @@ pub async fn get_metadata_readonly<B: Blobstore>(
     }
 }
 
+/// Recompute the metadata for the underlying content from its bytes, including its ContentId,
+/// so that it can be compared with the stored metadata to verify the content. This will return
+/// None if the content does not exist. Nothing is stored.
+pub async fn recompute_metadata<B: Blobstore>(
+    blobstore: &B,
+    ctx: &CoreContext,
+    content_id: ContentId,
+) -> Result<Option<ContentMetadata>, Error> {
+    metadata::recompute_metadata(blobstore, ctx, content_id).await
+}
+
 /// Return true if the given key exists. A successful return means the key definitely
 /// either exists or doesn't; an error means the existence could not be determined.
 pub async fn exists<B: Blobstore>(
@@ -230,14 +277,7 @@ pub async fn exists<B: Blobstore>(
     ctx: &CoreContext,
     key: &FetchKey,
 ) -> Result<bool, Error> {
-    let maybe_id = key
-        .load(ctx, blobstore)
-        .await
-        .map(Some)
-        .or_else(|err| match err {
-            LoadableError::Error(err) => Err(err),
-            LoadableError::Missing(_) => Ok(None),
-        })?;
+    let maybe_id = resolve_id(blobstore, ctx, key).await?;
 
     match maybe_id {
         Some(id) => blobstore
@@ -415,7 +455,8 @@ pub async fn store<B: Blobstore + Clone + 'static>(
 ) -> Result<ContentMetadata, Error> {
     use chunk::Chunks;
 
-    let prepared = match chunk::make_chunks(data, req.expected_size, config.chunk_size) {
+    let chunk_size = config.chunk_size_for(req.expected_size);
+    let prepared = match chunk::make_chunks(data, req.expected_size, chunk_size) {
         Chunks::Inline(fut) => prepare::prepare_bytes(fut.await?),
         Chunks::Chunked(expected_size, chunks) => {
             prepare::prepare_chunked(
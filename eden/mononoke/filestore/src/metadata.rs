@@ -10,16 +10,23 @@ use blobstore::Blobstore;
 use blobstore::Loadable;
 use blobstore::LoadableError;
 use blobstore::Storable;
+use bytes::Bytes;
 use context::CoreContext;
+use futures::future;
+use futures::stream::StreamExt;
 use mononoke_types::BlobstoreValue;
 use mononoke_types::ContentId;
 use mononoke_types::ContentMetadata;
 use mononoke_types::ContentMetadataId;
 use thiserror::Error;
 
+use crate::alias::add_aliases_to_multiplexer;
 use crate::alias::alias_stream;
 use crate::expected_size::ExpectedSize;
 use crate::fetch;
+use crate::incremental_hash::ContentIdIncrementalHasher;
+use crate::multiplexer::Multiplexer;
+use crate::streamhash::hash_stream;
 
 #[derive(Debug, Error)]
 pub enum RebuildBackmappingError {
@@ -132,3 +139,43 @@ async fn rebuild_metadata<B: Blobstore>(
 
     Ok(metadata)
 }
+
+/// Recompute the metadata for a ContentId from the bytes of the content, including the
+/// ContentId and size themselves, so that it can be compared with the stored metadata to
+/// find corrupt content. Unlike `rebuild_metadata`, nothing is stored. Returns None if the
+/// content does not exist.
+pub async fn recompute_metadata<B: Blobstore>(
+    blobstore: &B,
+    ctx: &CoreContext,
+    content_id: ContentId,
+) -> Result<Option<ContentMetadata>, Error> {
+    let file_contents = match content_id.load(ctx, blobstore).await {
+        Ok(file_contents) => file_contents,
+        Err(LoadableError::Missing(_)) => return Ok(None),
+        Err(LoadableError::Error(err)) => return Err(err),
+    };
+
+    // The git sha1 depends on the size, so use the stored size for it.  If the stored size is
+    // wrong, the recomputed size will not match it.
+    let stored_size = file_contents.size();
+    let content_stream =
+        fetch::stream_file_bytes(blobstore, ctx, file_contents, fetch::Range::all())?;
+
+    let mut multiplexer = Multiplexer::<Bytes>::new();
+    let recomputed_id =
+        multiplexer.add(|stream| hash_stream(ContentIdIncrementalHasher::new(), stream));
+    let total_size = multiplexer.add(|stream| {
+        stream.fold(0, |size, bytes| future::ready(size + bytes.len() as u64))
+    });
+    let aliases = add_aliases_to_multiplexer(&mut multiplexer, ExpectedSize::new(stored_size));
+    multiplexer.drain(content_stream).await?;
+
+    let (sha1, sha256, git_sha1) = aliases.await?.redeem(stored_size)?;
+    Ok(Some(ContentMetadata {
+        total_size: total_size.await?,
+        content_id: recomputed_id.await?,
+        sha1,
+        sha256,
+        git_sha1,
+    }))
+}
@@ -20,6 +20,7 @@ use mononoke_types::FileContents;
 use slog::debug;
 use thiserror::Error;
 
+use crate::expected_size::ExpectedSize;
 use crate::fetch;
 use crate::get_metadata;
 use crate::store;
@@ -53,7 +54,8 @@ pub async fn force_rechunk<B: Blobstore + Clone + 'static>(
 }
 
 /// Fetch a file from the blobstore and reupload it in a chunked form
-/// only if it is chunked using a larger chunk size (or unchunked)
+/// only if it is larger than the chunking threshold and is chunked
+/// using a larger chunk size (or unchunked)
 /// Note that this fn is not suitable for unchunking a file,
 /// as if existing file uses smaller-than-requested chunk size,
 /// this fn won't do anything.
@@ -66,15 +68,15 @@ pub async fn rechunk<B: Blobstore + Clone + 'static>(
     content_id: ContentId,
 ) -> Result<(ContentMetadata, bool), Error> {
     let fetch_key = FetchKey::Canonical(content_id.clone());
-    let chunk_size = filestore_config.chunk_size;
     let metadata = get_metadata(blobstore, ctx, &fetch_key).await?;
     let content_metadata: ContentMetadata = match metadata {
         Some(content_metadata) => content_metadata,
         None => return Err(ErrorKind::ContentNotFound(content_id).into()),
     };
 
-    match chunk_size {
-        Some(chunk_size) if content_metadata.total_size > chunk_size => {
+    let expected_size = ExpectedSize::new(content_metadata.total_size);
+    match filestore_config.chunk_size_for(expected_size) {
+        Some(chunk_size) => {
             let r: Result<(ContentMetadata, bool), Error> = rechunk_if_uses_larger_chunk_size(
                 blobstore,
                 chunk_size,
@@ -166,6 +168,7 @@ async fn rechunk_if_uses_larger_chunk_size<B: Blobstore + Clone + 'static>(
     if should_rechunk {
         let filestore_config = FilestoreConfig {
             chunk_size: Some(expected_chunk_size),
+            chunking_threshold: None,
             concurrency,
         };
 
@@ -10,6 +10,7 @@ use anyhow::Result;
 use assert_matches::assert_matches;
 use blobstore::Blobstore;
 use blobstore::PutBehaviour;
+use blobstore::Storable;
 use borrowed::borrowed;
 use bytes::Bytes;
 use bytes::BytesMut;
@@ -22,9 +23,12 @@ use futures::stream::TryStreamExt;
 use lazy_static::lazy_static;
 use mononoke_types::hash;
 use mononoke_types::typed_hash::BlobstoreKey;
+use mononoke_types::BlobstoreValue;
+use mononoke_types::ContentAlias;
 use mononoke_types::ContentId;
 use mononoke_types::ContentMetadata;
 use mononoke_types::ContentMetadataId;
+use mononoke_types::FileContents;
 use mononoke_types_mocks::contentid::ONES_CTID;
 
 use super::canonical;
@@ -35,6 +39,7 @@ use super::request;
 use crate as filestore;
 use crate::errors;
 use crate::Alias;
+use crate::AliasBlob;
 use crate::FetchKey;
 use crate::FilestoreConfig;
 use crate::StoreRequest;
@@ -43,6 +48,7 @@ const HELLO_WORLD: &[u8] = b"hello, world";
 const HELLO_WORLD_LENGTH: u64 = 12;
 const DEFAULT_CONFIG: FilestoreConfig = FilestoreConfig {
     chunk_size: None,
+    chunking_threshold: None,
     concurrency: 1,
 };
 
@@ -104,6 +110,55 @@ async fn filestore_put_alias(fb: FacebookInit) -> Result<()> {
     Ok(())
 }
 
+#[fbinit::test]
+async fn filestore_get_canonical_id(fb: FacebookInit) -> Result<()> {
+    let req = request(HELLO_WORLD);
+    let content_id = canonical(HELLO_WORLD);
+
+    let blob = memblob::Memblob::default();
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx, blob, req);
+
+    let sha1 = FetchKey::Aliased(Alias::Sha1(*HELLO_WORLD_SHA1));
+    assert_eq!(filestore::get_canonical_id(blob, ctx, &sha1).await?, None);
+
+    // Neither a canonical key nor an alias resolves until the content itself is stored.
+    AliasBlob(
+        Alias::Sha1(*HELLO_WORLD_SHA1),
+        ContentAlias::from_content_id(content_id),
+    )
+    .store(ctx, blob)
+    .await?;
+    assert_eq!(filestore::get_canonical_id(blob, ctx, &sha1).await?, None);
+    assert_eq!(
+        filestore::get_canonical_id(blob, ctx, &FetchKey::Canonical(content_id)).await?,
+        None
+    );
+
+    filestore::store(
+        blob,
+        DEFAULT_CONFIG,
+        ctx,
+        req,
+        stream::once(future::ready(Ok(Bytes::from(HELLO_WORLD)))),
+    )
+    .await?;
+
+    for key in [
+        FetchKey::Canonical(content_id),
+        sha1,
+        FetchKey::Aliased(Alias::GitSha1(HELLO_WORLD_GIT_SHA1.sha1())),
+        FetchKey::Aliased(Alias::Sha256(*HELLO_WORLD_SHA256)),
+    ] {
+        assert_eq!(
+            filestore::get_canonical_id(blob, ctx, &key).await?,
+            Some(content_id)
+        );
+    }
+
+    Ok(())
+}
+
 #[fbinit::test]
 async fn filestore_put_get_canon(fb: FacebookInit) -> Result<()> {
     let req = request(HELLO_WORLD);
@@ -229,6 +284,7 @@ async fn filestore_chunked_put_get(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::default();
     let config = FilestoreConfig {
         chunk_size: Some(1),
+        chunking_threshold: None,
         concurrency: 5,
     };
 
@@ -252,16 +308,58 @@ async fn filestore_chunked_put_get(fb: FacebookInit) -> Result<()> {
     Ok(())
 }
 
+#[fbinit::test]
+async fn filestore_chunking_threshold(fb: FacebookInit) -> Result<()> {
+    let blob = memblob::Memblob::default();
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx, blob);
+
+    let config = FilestoreConfig {
+        chunk_size: Some(3),
+        chunking_threshold: Some(6),
+        concurrency: 5,
+    };
+
+    // Files up to the threshold are stored inline.
+    let small_data = &b"foobar"[..];
+    let small_id = canonical(small_data);
+    filestore::store(
+        blob,
+        config,
+        ctx,
+        &request(small_data),
+        stream::once(future::ready(Ok(Bytes::from(small_data)))),
+    )
+    .await?;
+    assert_fetches_as(ctx, blob, small_id, vec!["foobar"]).await?;
+
+    // Larger files are chunked.
+    let large_data = &b"foobarbaz"[..];
+    let large_id = canonical(large_data);
+    filestore::store(
+        blob,
+        config,
+        ctx,
+        &request(large_data),
+        stream::once(future::ready(Ok(Bytes::from(large_data)))),
+    )
+    .await?;
+    assert_fetches_as(ctx, blob, large_id, vec!["foo", "bar", "baz"]).await?;
+    Ok(())
+}
+
 #[fbinit::test]
 async fn filestore_chunked_put_get_nested(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::default();
 
     let small = FilestoreConfig {
         chunk_size: Some(1),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let large = FilestoreConfig {
         chunk_size: Some(3),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -322,6 +420,7 @@ async fn filestore_chunk_not_found(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::default();
     let config = FilestoreConfig {
         chunk_size: Some(3),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -363,6 +462,7 @@ async fn filestore_put_invalid_size(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::default();
     let config = FilestoreConfig {
         chunk_size: Some(3),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -392,6 +492,7 @@ async fn filestore_put_content_id(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::default();
     let config = FilestoreConfig {
         chunk_size: Some(3),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -435,6 +536,7 @@ async fn filestore_put_sha1(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::default();
     let config = FilestoreConfig {
         chunk_size: Some(3),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -477,6 +579,7 @@ async fn filestore_put_git_sha1(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::default();
     let config = FilestoreConfig {
         chunk_size: Some(3),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -523,6 +626,7 @@ async fn filestore_put_sha256(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::default();
     let config = FilestoreConfig {
         chunk_size: Some(3),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -667,6 +771,7 @@ async fn filestore_get_invalid_range(fb: FacebookInit) -> Result<()> {
 async fn filestore_get_chunked_range(fb: FacebookInit) -> Result<()> {
     let small = FilestoreConfig {
         chunk_size: Some(3),
+        chunking_threshold: None,
         concurrency: 5,
     };
 
@@ -834,6 +939,53 @@ async fn filestore_rebuild_metadata(fb: FacebookInit) -> Result<()> {
     Ok(())
 }
 
+#[fbinit::test]
+async fn filestore_recompute_metadata(fb: FacebookInit) -> Result<()> {
+    let req = request(HELLO_WORLD);
+    let content_id = canonical(HELLO_WORLD);
+
+    let blob = memblob::Memblob::default();
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx, blob, req);
+
+    filestore::store(
+        blob,
+        DEFAULT_CONFIG,
+        ctx,
+        req,
+        stream::once(future::ready(Ok(Bytes::from(HELLO_WORLD)))),
+    )
+    .await?;
+
+    let res = filestore::recompute_metadata(blob, ctx, content_id).await?;
+    assert_eq!(
+        res,
+        Some(ContentMetadata {
+            total_size: HELLO_WORLD_LENGTH,
+            content_id,
+            sha1: *HELLO_WORLD_SHA1,
+            git_sha1: *HELLO_WORLD_GIT_SHA1,
+            sha256: *HELLO_WORLD_SHA256,
+        })
+    );
+
+    // Corrupt the content, and check that the recomputed id no longer matches.
+    let corrupt = FileContents::new_bytes(Bytes::from_static(b"hello, wOrld"));
+    blob.put(ctx, content_id.blobstore_key(), corrupt.into_blob().into())
+        .await?;
+    let res = filestore::recompute_metadata(blob, ctx, content_id).await?;
+    assert_eq!(
+        res.map(|metadata| metadata.content_id),
+        Some(canonical(b"hello, wOrld"))
+    );
+
+    // Missing content has no metadata.
+    let res = filestore::recompute_metadata(blob, ctx, canonical(b"missing")).await?;
+    assert_eq!(res, None);
+
+    Ok(())
+}
+
 #[fbinit::test]
 async fn filestore_test_missing_metadata(fb: FacebookInit) -> Result<()> {
     let content_id = canonical(HELLO_WORLD);
@@ -912,6 +1064,7 @@ async fn filestore_test_chunked_peek(fb: FacebookInit) -> Result<()> {
 
     let small = FilestoreConfig {
         chunk_size: Some(1),
+        chunking_threshold: None,
         concurrency: 5,
     };
 
@@ -1021,6 +1174,7 @@ async fn filestore_store_error(fb: FacebookInit) -> Result<()> {
 
     let config = FilestoreConfig {
         chunk_size: Some(1),
+        chunking_threshold: None,
         concurrency: 5,
     };
 
@@ -1047,10 +1201,12 @@ async fn filestore_test_rechunk(fb: FacebookInit) -> Result<()> {
 
     let small = FilestoreConfig {
         chunk_size: Some(1),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let large = FilestoreConfig {
         chunk_size: Some(3),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -1084,10 +1240,12 @@ async fn filestore_test_rechunk_larger(fb: FacebookInit) -> Result<()> {
 
     let small = FilestoreConfig {
         chunk_size: Some(1),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let large = FilestoreConfig {
         chunk_size: Some(3),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -1121,11 +1279,13 @@ async fn filestore_test_rechunk_unchunked(fb: FacebookInit) -> Result<()> {
 
     let small = FilestoreConfig {
         chunk_size: Some(1),
+        chunking_threshold: None,
         concurrency: 5,
     };
     // This is large enough that the data we upload won't be chunked.
     let large = FilestoreConfig {
         chunk_size: Some(100),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -1157,6 +1317,7 @@ async fn filestore_test_rechunk_missing_content(fb: FacebookInit) -> Result<()>
 
     let conf = FilestoreConfig {
         chunk_size: Some(1),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -1185,6 +1346,7 @@ async fn filestore_chunked_put_get_with_size(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::default();
     let config = FilestoreConfig {
         chunk_size: Some(1),
+        chunking_threshold: None,
         concurrency: 5,
     };
 
@@ -1228,10 +1390,12 @@ async fn filestore_test_rechunk_if_needed_tiny_unchunked_file(fb: FacebookInit)
 
     let large1 = FilestoreConfig {
         chunk_size: Some(100),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let large2 = FilestoreConfig {
         chunk_size: Some(200),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -1265,16 +1429,56 @@ async fn filestore_test_rechunk_if_needed_tiny_unchunked_file(fb: FacebookInit)
     assert_fetches_as(ctx, blob, full_id, vec!["foobar"]).await
 }
 
+#[fbinit::test]
+async fn filestore_test_rechunk_below_threshold(fb: FacebookInit) -> Result<()> {
+    let blob = memblob::Memblob::new(PutBehaviour::Overwrite);
+
+    let unchunked = FilestoreConfig {
+        chunk_size: None,
+        chunking_threshold: None,
+        concurrency: 5,
+    };
+    let thresholded = FilestoreConfig {
+        chunk_size: Some(1),
+        chunking_threshold: Some(6),
+        concurrency: 5,
+    };
+    let ctx = CoreContext::test_mock(fb);
+
+    let full_data = &b"foobar"[..];
+    let full_key = request(full_data);
+    let full_id = canonical(full_data);
+    borrowed!(ctx, blob, full_key);
+
+    // Don't chunk
+    filestore::store(
+        blob,
+        unchunked,
+        ctx,
+        full_key,
+        stream::once(future::ready(Ok(Bytes::from(full_data)))),
+    )
+    .await?;
+
+    // The file is no larger than the threshold, so it stays unchunked
+    let (_, rechunked) = filestore::rechunk::rechunk(blob, thresholded, ctx, full_id).await?;
+    assert!(!rechunked);
+
+    assert_fetches_as(ctx, blob, full_id, vec!["foobar"]).await
+}
+
 #[fbinit::test]
 async fn filestore_test_rechunk_if_needed_large_unchunked_file(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::new(PutBehaviour::Overwrite);
 
     let large = FilestoreConfig {
         chunk_size: Some(100),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let small = FilestoreConfig {
         chunk_size: Some(1),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -1309,10 +1513,12 @@ async fn filestore_test_rechunk_if_needed_large_chunks(fb: FacebookInit) -> Resu
 
     let large = FilestoreConfig {
         chunk_size: Some(5),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let small = FilestoreConfig {
         chunk_size: Some(1),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -1347,6 +1553,7 @@ async fn filestore_test_rechunk_if_needed_tiny_chunks(fb: FacebookInit) -> Resul
 
     let large = FilestoreConfig {
         chunk_size: Some(4),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -98,6 +98,7 @@ fn test_invariants(fb: FacebookInit) -> Result<()> {
     let blob = FailingBlobstore::new(memblob.clone(), 0.75, 0.75);
     let config = FilestoreConfig {
         chunk_size: Some(16),
+        chunking_threshold: None,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -143,16 +144,19 @@ async fn test_store_bytes_consistency(fb: FacebookInit) -> Result<(), Error> {
 
         let no_chunking = FilestoreConfig {
             chunk_size: None,
+            chunking_threshold: None,
             concurrency: 1,
         };
 
         let chunked = FilestoreConfig {
             chunk_size: Some(std::cmp::max(1, (bytes.len() as u64) / 2)),
+            chunking_threshold: None,
             concurrency: 1,
         };
 
         let too_small_to_chunk = FilestoreConfig {
             chunk_size: Some(std::cmp::max(1, (bytes.len() as u64) * 2)),
+            chunking_threshold: None,
             concurrency: 1,
         };
 
@@ -255,6 +255,7 @@ fn generate_bonsai_changeset(
         file_changes,
         is_snapshot: false,
         git_annotated_tag: None,
+        extras: None,
     }
     .freeze()
 }
@@ -7,6 +7,7 @@
 
 use std::net::IpAddr;
 use std::net::SocketAddr;
+use std::time::Instant;
 
 use cats::try_get_cats_idents;
 use fbinit::FacebookInit;
@@ -19,7 +20,9 @@ use hyper::Body;
 use hyper::Response;
 use hyper::StatusCode;
 use metaconfig_types::Identity;
+use metadata::parse_client_deadline;
 use metadata::Metadata;
+use metadata::CLIENT_DEADLINE_HEADER;
 use percent_encoding::percent_decode;
 use permission_checker::MononokeIdentity;
 use permission_checker::MononokeIdentitySet;
@@ -42,6 +45,11 @@ impl MetadataState {
     }
 }
 
+/// The time after which the client will no longer wait for the response to
+/// this request, if it sent one.
+#[derive(StateData, Clone, Copy)]
+pub struct ClientDeadline(pub Instant);
+
 pub struct MetadataMiddleware {
     fb: FacebookInit,
     logger: Logger,
@@ -81,6 +89,17 @@ fn request_ip_from_headers(headers: &HeaderMap) -> Option<IpAddr> {
     Some(ip)
 }
 
+/// The deadline the client sent, or an error if it sent an invalid one.
+fn request_deadline_from_headers(headers: &HeaderMap) -> Result<Option<Instant>, ()> {
+    match headers.get(CLIENT_DEADLINE_HEADER) {
+        Some(header) => {
+            let deadline = header.to_str().ok().and_then(parse_client_deadline);
+            deadline.map(Some).ok_or(())
+        }
+        None => Ok(None),
+    }
+}
+
 fn request_identities_from_headers(headers: &HeaderMap) -> Option<MononokeIdentitySet> {
     let encoded_identities = headers.get(ENCODED_CLIENT_IDENTITY)?;
     let json_identities = percent_decode(encoded_identities.as_bytes())
@@ -95,6 +114,28 @@ impl Middleware for MetadataMiddleware {
         let cert_idents = TlsCertificateIdentities::try_take_from(state);
         let mut metadata = Metadata::default();
 
+        match HeaderMap::try_borrow_from(state).map(request_deadline_from_headers) {
+            Some(Ok(Some(deadline))) => state.put(ClientDeadline(deadline)),
+            Some(Err(())) => {
+                let msg = "Invalid client deadline";
+                error!(self.logger, "{}", msg);
+                let response = Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(
+                        format!(
+                            "{{\"message:\"{}\", \"request_id\":\"{}\"}}",
+                            msg,
+                            state.short_request_id()
+                        )
+                        .into(),
+                    )
+                    .expect("Couldn't build http response");
+
+                return Some(response);
+            }
+            Some(Ok(None)) | None => {}
+        }
+
         if let Some(headers) = HeaderMap::try_borrow_from(state) {
             metadata = metadata.set_client_ip(request_ip_from_headers(headers));
 
@@ -23,6 +23,7 @@ pub mod tls_session_data;
 pub use self::load::LoadMiddleware;
 pub use self::load::RequestLoad;
 pub use self::log::LogMiddleware;
+pub use self::metadata::ClientDeadline;
 pub use self::metadata::MetadataMiddleware;
 pub use self::metadata::MetadataState;
 pub use self::post_request::PostResponseCallbacks;
@@ -0,0 +1,154 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Error;
+use gotham::state::FromState;
+use gotham::state::State;
+use hyper::header::HeaderMap;
+use hyper::header::HeaderValue;
+use hyper::header::CACHE_CONTROL;
+use hyper::header::CONTENT_LENGTH;
+use hyper::header::ETAG;
+use hyper::header::IF_NONE_MATCH;
+use hyper::Body;
+use hyper::Response;
+use hyper::StatusCode;
+
+use super::response::TryIntoResponse;
+use super::response_meta::PendingResponseMeta;
+use super::stream_stats::PendingStreamStats;
+
+/// Content-addressed data never changes, so it may be cached for as long as
+/// the client likes.  It is only served to authenticated clients, so it must
+/// not be stored by shared caches, which would serve it to anyone.
+const IMMUTABLE_CACHE_CONTROL: &str = "private, max-age=31536000, immutable";
+
+/// A response for content-addressed data, identified by `etag`.  The response
+/// carries caching headers allowing the client to cache it, and is empty with
+/// status 304 Not Modified if the client already has the data.
+pub struct ContentAddressedBody<B> {
+    etag: String,
+    body: Option<B>,
+}
+
+impl<B> ContentAddressedBody<B> {
+    pub fn new(etag: impl ToString, body: B) -> Self {
+        Self {
+            etag: etag.to_string(),
+            body: Some(body),
+        }
+    }
+
+    /// Response to a client that already has the data identified by `etag`.
+    pub fn not_modified(etag: impl ToString) -> Self {
+        Self {
+            etag: etag.to_string(),
+            body: None,
+        }
+    }
+}
+
+/// Whether the client making the request already has the data identified by
+/// `etag`, in which case it does not need to be fetched.
+pub fn client_has_etag(state: &State, etag: &str) -> bool {
+    HeaderMap::try_borrow_from(state)
+        .and_then(|headers| headers.get(IF_NONE_MATCH))
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| etag_matches(value, etag))
+}
+
+impl<B> TryIntoResponse for ContentAddressedBody<B>
+where
+    B: TryIntoResponse,
+{
+    fn try_into_response(self, state: &mut State) -> Result<Response<Body>, Error> {
+        let etag: HeaderValue = format!("\"{}\"", self.etag).parse()?;
+        let cache_control = HeaderValue::from_static(IMMUTABLE_CACHE_CONTROL);
+
+        let mut res = match self.body {
+            Some(body) => body.try_into_response(state)?,
+            None => {
+                state.put(PendingStreamStats::none());
+                state.put(PendingResponseMeta::immediate(0));
+
+                Response::builder()
+                    .status(StatusCode::NOT_MODIFIED)
+                    .header(CONTENT_LENGTH, 0)
+                    .body(Body::empty())?
+            }
+        };
+
+        let headers = res.headers_mut();
+        headers.insert(ETAG, etag);
+        headers.insert(CACHE_CONTROL, cache_control);
+        Ok(res)
+    }
+}
+
+/// Whether the value of an If-None-Match header matches `etag`.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',').any(|candidate| {
+        let candidate = candidate.trim();
+        let candidate = candidate.strip_prefix("W/").unwrap_or(candidate);
+        candidate == "*" || candidate.trim_matches('"') == etag
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use gotham::test::TestServer;
+
+    use super::*;
+    use crate::response::BytesBody;
+
+    fn respond(mut state: State) -> (State, Response<Body>) {
+        let body = if client_has_etag(&state, "abc") {
+            ContentAddressedBody::not_modified("abc")
+        } else {
+            ContentAddressedBody::new("abc", BytesBody::new("data", mime::TEXT_PLAIN))
+        };
+        let res = body
+            .try_into_response(&mut state)
+            .expect("Failed to build response");
+        (state, res)
+    }
+
+    #[test]
+    fn test_content_addressed_body() -> Result<(), Error> {
+        let server = TestServer::new(|| Ok(respond))?;
+
+        let res = server.client().get("http://host/").perform()?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[ETAG], "\"abc\"");
+        assert_eq!(
+            res.headers()[CACHE_CONTROL],
+            "private, max-age=31536000, immutable"
+        );
+        assert_eq!(res.read_body()?, b"data");
+
+        let res = server
+            .client()
+            .get("http://host/")
+            .with_header(IF_NONE_MATCH, HeaderValue::from_static("W/\"abc\""))
+            .perform()?;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers()[ETAG], "\"abc\"");
+        assert!(res.read_body()?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_etag_matches() {
+        assert!(etag_matches("\"abc\"", "abc"));
+        assert!(etag_matches("W/\"abc\"", "abc"));
+        assert!(etag_matches("\"def\", \"abc\"", "abc"));
+        assert!(etag_matches("*", "abc"));
+        assert!(!etag_matches("\"abcd\"", "abc"));
+        assert!(!etag_matches("", "abc"));
+    }
+}
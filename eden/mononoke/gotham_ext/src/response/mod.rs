@@ -5,6 +5,7 @@
  * GNU General Public License version 2.
  */
 
+mod content_addressed;
 mod content_meta;
 mod error_meta;
 mod response;
@@ -14,6 +15,8 @@ mod stream;
 mod stream_ext;
 mod stream_stats;
 
+pub use content_addressed::client_has_etag;
+pub use content_addressed::ContentAddressedBody;
 pub use content_meta::ContentMetaProvider;
 pub use error_meta::ErrorMeta;
 pub use error_meta::ErrorMetaProvider;
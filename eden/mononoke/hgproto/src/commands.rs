@@ -135,6 +135,14 @@ impl<H: HgCommands + Send + 'static> HgCommandHandler<H> {
                     .boxify(),
                 ok(instream).boxify(),
             ),
+            SingleRequest::Clonebundles => (
+                hgcmds
+                    .clonebundles()
+                    .map(SingleResponse::Clonebundles)
+                    .into_stream()
+                    .boxify(),
+                ok(instream).boxify(),
+            ),
             SingleRequest::Debugwireargs { one, two, all_args } => (
                 self.debugwireargs(one, two, all_args)
                     .map(SingleResponse::Debugwireargs)
@@ -630,6 +638,11 @@ pub trait HgCommands {
         unimplemented("clienttelemetry")
     }
 
+    // @wireprotocommand('clonebundles')
+    fn clonebundles(&self) -> HgCommandRes<Bytes> {
+        unimplemented("clonebundles")
+    }
+
     // @wireprotocommand('getbundle', '*')
     // TODO: make this streaming
     fn getbundle(&self, _args: GetbundleArgs) -> BoxStream<Bytes, Error> {
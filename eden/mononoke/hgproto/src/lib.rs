@@ -59,6 +59,7 @@ pub enum SingleRequest {
     ClientTelemetry {
         args: HashMap<Vec<u8>, Vec<u8>>,
     },
+    Clonebundles,
     Debugwireargs {
         one: Vec<u8>,
         two: Vec<u8>,
@@ -109,6 +110,7 @@ impl SingleRequest {
             SingleRequest::Branchmap => "branchmap",
             SingleRequest::Capabilities => "capabilities",
             SingleRequest::ClientTelemetry { .. } => "clienttelemetry",
+            SingleRequest::Clonebundles => "clonebundles",
             SingleRequest::Debugwireargs { .. } => "debugwireargs",
             SingleRequest::Getbundle(_) => "getbundle",
             SingleRequest::Heads => "heads",
@@ -143,6 +145,8 @@ pub struct GetbundleArgs {
     pub listkeys: Vec<Vec<u8>>,
     /// phases: Boolean indicating whether phases data is requested
     pub phases: bool,
+    /// How many milliseconds the client will wait for the bundle, if it said.
+    pub deadline_ms: Option<u64>,
 }
 
 impl Debug for GetbundleArgs {
@@ -167,6 +171,7 @@ impl Debug for GetbundleArgs {
             .field("bundlecaps", &bcaps)
             .field("listkeys", &listkeys)
             .field("phases", &self.phases)
+            .field("deadline_ms", &self.deadline_ms)
             .finish()
     }
 }
@@ -187,6 +192,8 @@ pub struct GettreepackArgs {
     pub directories: Vec<Bytes>,
     /// The depth from the root that should be sent.
     pub depth: Option<usize>,
+    /// How many milliseconds the client will wait for the trees, if it said.
+    pub deadline_ms: Option<u64>,
 }
 
 #[derive(Debug)]
@@ -201,6 +208,7 @@ pub enum SingleResponse {
     Branchmap(HashMap<String, HashSet<HgChangesetId>>),
     Capabilities(Vec<String>),
     ClientTelemetry(String),
+    Clonebundles(Bytes),
     Debugwireargs(Bytes),
     Getbundle(Bytes),
     Heads(HashSet<HgChangesetId>),
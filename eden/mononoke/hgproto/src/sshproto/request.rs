@@ -263,6 +263,15 @@ named!(
     separated_list_complete!(tag!(" "), pair)
 );
 
+// How many milliseconds the client will wait for a response, in decimal.
+named!(
+    deadline_ms<u64>,
+    map_res!(
+        map_res!(take_while1!(is_digit), str::from_utf8),
+        u64::from_str
+    )
+);
+
 // A space-separated list of changeset IDs
 named!(
     hashlist<Vec<HgChangesetId>>,
@@ -619,6 +628,7 @@ fn parse_with_params(
             |kv| Ok(ClientTelemetry{
                 args: kv,
             }))
+        | command!("clonebundles", Clonebundles, parse_params, {})
         | call!(parse_command, "getbundle", parse_params, 1,
             |kv| Ok(Getbundle(GetbundleArgs {
                 // Some params are currently ignored, like:
@@ -631,6 +641,7 @@ fn parse_with_params(
                 bundlecaps: parseval_default(&kv, "bundlecaps", commavalues)?.into_iter().collect(),
                 listkeys: parseval_default(&kv, "listkeys", commavalues)?,
                 phases: parseval_default(&kv, "phases", boolean)?,
+                deadline_ms: parseval_option(&kv, "deadlinems", deadline_ms)?,
             })))
         | command!("heads", Heads, parse_params, {})
         | command!("hello", Hello, parse_params, {})
@@ -670,6 +681,7 @@ fn parse_with_params(
                         usize::from_str
                     )
                 ))?,
+                deadline_ms: parseval_option(&kv, "deadlinems", deadline_ms)?,
             })))
         | call!(parse_command, "stream_out_shallow", parse_params, 1, |kv| {
             Ok(StreamOutShallow {
@@ -1386,12 +1398,13 @@ mod test_parse {
                 bundlecaps: hashset![],
                 listkeys: vec![],
                 phases: false,
+                deadline_ms: None,
             })),
         );
 
         // with arguments
         let inp = "getbundle\n\
-             * 6\n\
+             * 7\n\
              heads 40\n\
              1111111111111111111111111111111111111111\
              common 81\n\
@@ -1402,6 +1415,8 @@ mod test_parse {
              key1,key2\
              phases 1\n\
              1\
+             deadlinems 4\n\
+             1500\
              extra 5\n\
              extra";
         test_parse(
@@ -1412,6 +1427,7 @@ mod test_parse {
                 bundlecaps: hashset![b"cap1".to_vec(), b"CAP2".to_vec(), b"cap3".to_vec()],
                 listkeys: vec![b"key1".to_vec(), b"key2".to_vec()],
                 phases: true,
+                deadline_ms: Some(1500),
             })),
         );
     }
@@ -1423,6 +1439,13 @@ mod test_parse {
         test_parse(inp, Request::Single(SingleRequest::Heads {}));
     }
 
+    #[test]
+    fn test_parse_clonebundles() {
+        let inp = "clonebundles\n";
+
+        test_parse(inp, Request::Single(SingleRequest::Clonebundles {}));
+    }
+
     #[test]
     fn test_parse_hello() {
         let inp = "hello\n";
@@ -1491,6 +1514,7 @@ mod test_parse {
                 basemfnodes: btreeset![hash_ones_manifest()],
                 directories: vec![],
                 depth: None,
+                deadline_ms: None,
             })),
         );
 
@@ -1515,6 +1539,7 @@ mod test_parse {
                 basemfnodes: btreeset![hash_twos_manifest(), hash_ones_manifest()],
                 directories: vec![Bytes::from("".as_bytes())],
                 depth: Some(1),
+                deadline_ms: None,
             })),
         );
 
@@ -1539,6 +1564,7 @@ mod test_parse {
                 basemfnodes: btreeset![hash_twos_manifest(), hash_ones_manifest()],
                 directories: vec![Bytes::from(",".as_bytes()), Bytes::from(";".as_bytes())],
                 depth: Some(1),
+                deadline_ms: None,
             })),
         );
 
@@ -1560,6 +1586,7 @@ mod test_parse {
                 basemfnodes: btreeset![hash_ones_manifest()],
                 directories: vec![Bytes::from(b"".as_ref()), Bytes::from(b"foo".as_ref())],
                 depth: None,
+                deadline_ms: None,
             })),
         );
     }
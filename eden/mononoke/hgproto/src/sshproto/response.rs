@@ -108,6 +108,8 @@ fn encode_cmd(response: SingleResponse) -> Bytes {
 
         ClientTelemetry(hostname) => Bytes::from(hostname),
 
+        Clonebundles(manifest) => manifest,
+
         Debugwireargs(res) => res,
 
         Heads(set) => {
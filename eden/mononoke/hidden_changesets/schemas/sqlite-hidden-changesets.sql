@@ -0,0 +1,14 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+CREATE TABLE IF NOT EXISTS `hidden_changesets` (
+  `repo_id` INTEGER NOT NULL,
+  `cs_id` VARBINARY(32) NOT NULL,
+  `timestamp` BIGINT NOT NULL,
+  `reason` TEXT NOT NULL,
+  PRIMARY KEY (`repo_id`, `cs_id`)
+);
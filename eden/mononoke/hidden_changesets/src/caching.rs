@@ -0,0 +1,166 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Result;
+use arc_swap::ArcSwapOption;
+use async_trait::async_trait;
+use context::CoreContext;
+use mononoke_types::ChangesetId;
+use tokio::sync::Mutex;
+
+use crate::HiddenChangeset;
+use crate::HiddenChangesets;
+use crate::SqlHiddenChangesets;
+
+/// How long the set of hidden changesets is used for before it is loaded
+/// again.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+struct HiddenSet {
+    loaded: Instant,
+    cs_ids: HashSet<ChangesetId>,
+}
+
+/// Keeps the set of hidden changesets in memory, so that checking whether
+/// changesets are hidden doesn't query the database.  There are only ever a
+/// handful of hidden changesets, so the whole set is loaded at once.
+///
+/// Changesets hidden or unhidden through another instance take up to
+/// `REFRESH_INTERVAL` to take effect.
+pub struct CachingHiddenChangesets {
+    inner: SqlHiddenChangesets,
+    refresh_interval: Duration,
+    hidden: ArcSwapOption<HiddenSet>,
+    refresh_lock: Mutex<()>,
+}
+
+impl CachingHiddenChangesets {
+    pub fn new(inner: SqlHiddenChangesets) -> Self {
+        Self::with_refresh_interval(inner, REFRESH_INTERVAL)
+    }
+
+    fn with_refresh_interval(inner: SqlHiddenChangesets, refresh_interval: Duration) -> Self {
+        Self {
+            inner,
+            refresh_interval,
+            hidden: ArcSwapOption::empty(),
+            refresh_lock: Mutex::new(()),
+        }
+    }
+
+    fn fresh(&self) -> Option<Arc<HiddenSet>> {
+        self.hidden
+            .load_full()
+            .filter(|hidden| hidden.loaded.elapsed() < self.refresh_interval)
+    }
+
+    async fn hidden_set(&self, ctx: &CoreContext) -> Result<Arc<HiddenSet>> {
+        if let Some(hidden) = self.fresh() {
+            return Ok(hidden);
+        }
+        let _guard = self.refresh_lock.lock().await;
+        // Another caller may have loaded it while we were waiting.
+        if let Some(hidden) = self.fresh() {
+            return Ok(hidden);
+        }
+        let hidden = Arc::new(HiddenSet {
+            loaded: Instant::now(),
+            cs_ids: self.inner.load_all(ctx).await?,
+        });
+        self.hidden.store(Some(hidden.clone()));
+        Ok(hidden)
+    }
+}
+
+#[async_trait]
+impl HiddenChangesets for CachingHiddenChangesets {
+    async fn hide(&self, ctx: &CoreContext, cs_ids: &[ChangesetId], reason: &str) -> Result<()> {
+        self.inner.hide(ctx, cs_ids, reason).await?;
+        self.hidden.store(None);
+        Ok(())
+    }
+
+    async fn unhide(&self, ctx: &CoreContext, cs_ids: &[ChangesetId]) -> Result<()> {
+        self.inner.unhide(ctx, cs_ids).await?;
+        self.hidden.store(None);
+        Ok(())
+    }
+
+    async fn get_hidden(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+    ) -> Result<HashSet<ChangesetId>> {
+        if cs_ids.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let hidden = self.hidden_set(ctx).await?;
+        Ok(cs_ids
+            .iter()
+            .filter(|cs_id| hidden.cs_ids.contains(cs_id))
+            .copied()
+            .collect())
+    }
+
+    async fn list(&self, ctx: &CoreContext) -> Result<Vec<HiddenChangeset>> {
+        self.inner.list(ctx).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use context::PerfCounterType;
+    use fbinit::FacebookInit;
+    use maplit::hashset;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+    use mononoke_types_mocks::repo::REPO_ZERO;
+    use sql_construct::SqlConstruct;
+
+    use super::*;
+    use crate::SqlHiddenChangesetsBuilder;
+
+    #[fbinit::test]
+    async fn test_caching_hidden_changesets(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let builder = SqlHiddenChangesetsBuilder::with_sqlite_in_memory()?;
+        let connections = builder.connections.clone();
+        let caching = CachingHiddenChangesets::with_refresh_interval(
+            builder.build(REPO_ZERO),
+            Duration::from_secs(3600),
+        );
+        let other = SqlHiddenChangesetsBuilder::from_sql_connections(connections).build(REPO_ZERO);
+        let all = [ONES_CSID, TWOS_CSID];
+
+        caching.hide(&ctx, &[ONES_CSID], "leaked secret").await?;
+        assert_eq!(caching.get_hidden(&ctx, &all).await?, hashset! { ONES_CSID });
+
+        // Once loaded, the set is served from memory.
+        let reads = ctx
+            .perf_counters()
+            .get_counter(PerfCounterType::SqlReadsReplica);
+        assert_eq!(caching.get_hidden(&ctx, &all).await?, hashset! { ONES_CSID });
+        assert_eq!(
+            ctx.perf_counters()
+                .get_counter(PerfCounterType::SqlReadsReplica),
+            reads
+        );
+
+        // Changes made elsewhere are only seen once the set is reloaded.
+        other.hide(&ctx, &[TWOS_CSID], "leaked secret").await?;
+        assert_eq!(caching.get_hidden(&ctx, &all).await?, hashset! { ONES_CSID });
+        caching.unhide(&ctx, &[ONES_CSID]).await?;
+        assert_eq!(caching.get_hidden(&ctx, &all).await?, hashset! { TWOS_CSID });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Error;
+use anyhow::Result;
+use async_trait::async_trait;
+use changesets::ArcChangesets;
+use changesets::ChangesetEntry;
+use changesets::ChangesetInsert;
+use changesets::Changesets;
+use changesets::SortOrder;
+use context::CoreContext;
+use futures::stream::BoxStream;
+use mononoke_types::ChangesetId;
+use mononoke_types::ChangesetIdPrefix;
+use mononoke_types::ChangesetIdsResolvedFromPrefix;
+use mononoke_types::Generation;
+use mononoke_types::RepositoryId;
+use vec1::Vec1;
+
+use crate::ArcHiddenChangesets;
+
+/// Changesets that leaves out hidden changesets when they are fetched or
+/// resolved from a prefix, so that they appear not to exist.
+///
+/// Enumeration is left unchanged, so that maintenance jobs still see every
+/// changeset.
+pub struct HidingChangesets {
+    inner: ArcChangesets,
+    hidden: ArcHiddenChangesets,
+}
+
+impl HidingChangesets {
+    pub fn new(inner: ArcChangesets, hidden: ArcHiddenChangesets) -> Self {
+        Self { inner, hidden }
+    }
+
+    async fn without_hidden(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<Vec<ChangesetId>> {
+        let hidden = self.hidden.get_hidden(ctx, &cs_ids).await?;
+        Ok(cs_ids
+            .into_iter()
+            .filter(|cs_id| !hidden.contains(cs_id))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl Changesets for HidingChangesets {
+    fn repo_id(&self) -> RepositoryId {
+        self.inner.repo_id()
+    }
+
+    async fn add(&self, ctx: &CoreContext, cs: ChangesetInsert) -> Result<bool, Error> {
+        self.inner.add(ctx, cs).await
+    }
+
+    async fn add_many(
+        &self,
+        ctx: &CoreContext,
+        css: Vec1<(ChangesetInsert, Generation)>,
+    ) -> Result<(), Error> {
+        self.inner.add_many(ctx, css).await
+    }
+
+    async fn get(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<Option<ChangesetEntry>, Error> {
+        if !self.hidden.get_hidden(ctx, &[cs_id]).await?.is_empty() {
+            return Ok(None);
+        }
+        self.inner.get(ctx, cs_id).await
+    }
+
+    async fn get_many(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<Vec<ChangesetEntry>, Error> {
+        let cs_ids = self.without_hidden(ctx, cs_ids).await?;
+        self.inner.get_many(ctx, cs_ids).await
+    }
+
+    async fn get_many_by_prefix(
+        &self,
+        ctx: &CoreContext,
+        cs_prefix: ChangesetIdPrefix,
+        limit: usize,
+    ) -> Result<ChangesetIdsResolvedFromPrefix, Error> {
+        let resolved = self
+            .inner
+            .get_many_by_prefix(ctx, cs_prefix, limit)
+            .await?;
+        Ok(match resolved {
+            // There are still more matches than the limit, even if some of
+            // the ones returned are hidden.
+            ChangesetIdsResolvedFromPrefix::TooMany(cs_ids) => {
+                ChangesetIdsResolvedFromPrefix::TooMany(self.without_hidden(ctx, cs_ids).await?)
+            }
+            resolved => {
+                let cs_ids = self.without_hidden(ctx, resolved.to_vec()).await?;
+                ChangesetIdsResolvedFromPrefix::from_vec_and_limit(cs_ids, limit)
+            }
+        })
+    }
+
+    fn prime_cache(&self, ctx: &CoreContext, changesets: &[ChangesetEntry]) {
+        self.inner.prime_cache(ctx, changesets)
+    }
+
+    async fn enumeration_bounds(
+        &self,
+        ctx: &CoreContext,
+        read_from_master: bool,
+        known_heads: Vec<ChangesetId>,
+    ) -> Result<Option<(u64, u64)>, Error> {
+        self.inner
+            .enumeration_bounds(ctx, read_from_master, known_heads)
+            .await
+    }
+
+    fn list_enumeration_range(
+        &self,
+        ctx: &CoreContext,
+        min_id: u64,
+        max_id: u64,
+        sort_and_limit: Option<(SortOrder, u64)>,
+        read_from_master: bool,
+    ) -> BoxStream<'_, Result<(ChangesetId, u64), Error>> {
+        self.inner
+            .list_enumeration_range(ctx, min_id, max_id, sort_and_limit, read_from_master)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    use changesets_impl::SqlChangesetsBuilder;
+    use fbinit::FacebookInit;
+    use mononoke_types_mocks::changesetid::FS_CSID;
+    use mononoke_types_mocks::changesetid::FS_ES_CSID;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::repo::REPO_ZERO;
+    use rendezvous::RendezVousOptions;
+    use sql_construct::SqlConstruct;
+
+    use super::*;
+    use crate::HiddenChangesets;
+    use crate::SqlHiddenChangesetsBuilder;
+
+    #[fbinit::test]
+    async fn test_hiding_changesets(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let inner = SqlChangesetsBuilder::with_sqlite_in_memory()?
+            .build(RendezVousOptions::for_test(), REPO_ZERO);
+        for cs_id in [ONES_CSID, FS_CSID, FS_ES_CSID] {
+            let row = ChangesetInsert {
+                cs_id,
+                parents: vec![],
+            };
+            inner.add(&ctx, row).await?;
+        }
+        let hidden = Arc::new(
+            SqlHiddenChangesetsBuilder::with_sqlite_in_memory()?.build(REPO_ZERO),
+        );
+        let changesets = HidingChangesets::new(Arc::new(inner), hidden.clone());
+
+        hidden.hide(&ctx, &[FS_CSID], "leaked secret").await?;
+
+        assert!(changesets.get(&ctx, FS_CSID).await?.is_none());
+        assert!(!changesets.exists(&ctx, FS_CSID).await?);
+        assert!(changesets.exists(&ctx, FS_ES_CSID).await?);
+        let entries = changesets
+            .get_many(&ctx, vec![ONES_CSID, FS_CSID, FS_ES_CSID])
+            .await?;
+        let mut cs_ids = entries
+            .into_iter()
+            .map(|entry| entry.cs_id)
+            .collect::<Vec<_>>();
+        cs_ids.sort();
+        assert_eq!(cs_ids, vec![ONES_CSID, FS_ES_CSID]);
+
+        let resolved = changesets
+            .get_many_by_prefix(&ctx, ChangesetIdPrefix::from_str("fff")?, 10)
+            .await?;
+        assert_eq!(resolved, ChangesetIdsResolvedFromPrefix::Single(FS_ES_CSID));
+        let resolved = changesets
+            .get_many_by_prefix(&ctx, ChangesetIdPrefix::from_bytes(FS_CSID.as_ref())?, 10)
+            .await?;
+        assert_eq!(resolved, ChangesetIdsResolvedFromPrefix::NoMatch);
+
+        hidden.unhide(&ctx, &[FS_CSID]).await?;
+        assert!(changesets.exists(&ctx, FS_CSID).await?);
+
+        Ok(())
+    }
+}
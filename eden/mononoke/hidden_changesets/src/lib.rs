@@ -0,0 +1,258 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Hidden changesets are draft changesets that have been hidden by an
+//! administrator, typically because they contain something that should
+//! never have been pushed, such as a leaked secret.
+//!
+//! Hidden changesets still exist in the repository, but are not served to
+//! clients: they can't be resolved by their id or a prefix of it, pulled,
+//! or listed.  This is enforced by `HidingChangesets`, which makes them
+//! appear to be missing from the changesets table, and so from everything
+//! that fetches changesets through it.
+
+mod caching;
+mod hiding;
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use context::CoreContext;
+use context::PerfCounterType;
+use mononoke_types::ChangesetId;
+use mononoke_types::RepositoryId;
+use mononoke_types::Timestamp;
+use sql_construct::SqlConstruct;
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::mononoke_queries;
+use sql_ext::SqlConnections;
+
+pub use crate::caching::CachingHiddenChangesets;
+pub use crate::hiding::HidingChangesets;
+
+/// A changeset that has been hidden.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HiddenChangeset {
+    pub cs_id: ChangesetId,
+    /// When the changeset was hidden.
+    pub timestamp: Timestamp,
+    /// Why the changeset was hidden.
+    pub reason: String,
+}
+
+#[facet::facet]
+#[async_trait]
+pub trait HiddenChangesets: Send + Sync {
+    /// Hide these changesets.  Changesets that are already hidden keep their
+    /// original reason.
+    async fn hide(&self, ctx: &CoreContext, cs_ids: &[ChangesetId], reason: &str) -> Result<()>;
+
+    /// Stop hiding these changesets.
+    async fn unhide(&self, ctx: &CoreContext, cs_ids: &[ChangesetId]) -> Result<()>;
+
+    /// Returns which of these changesets are hidden.
+    async fn get_hidden(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+    ) -> Result<HashSet<ChangesetId>>;
+
+    /// List all hidden changesets.
+    async fn list(&self, ctx: &CoreContext) -> Result<Vec<HiddenChangeset>>;
+}
+
+mononoke_queries! {
+    write HideChangesets(
+        values: (repo_id: RepositoryId, cs_id: ChangesetId, timestamp: Timestamp, reason: String)
+    ) {
+        insert_or_ignore,
+        "{insert_or_ignore} INTO hidden_changesets (repo_id, cs_id, timestamp, reason)
+         VALUES {values}"
+    }
+
+    write UnhideChangesets(repo_id: RepositoryId, >list cs_ids: ChangesetId) {
+        none,
+        "DELETE FROM hidden_changesets
+         WHERE repo_id = {repo_id} AND cs_id IN {cs_ids}"
+    }
+
+    read SelectHiddenChangesets(
+        repo_id: RepositoryId,
+        >list cs_ids: ChangesetId
+    ) -> (ChangesetId) {
+        "SELECT cs_id
+         FROM hidden_changesets
+         WHERE repo_id = {repo_id} AND cs_id IN {cs_ids}"
+    }
+
+    read SelectAllHiddenChangesets(repo_id: RepositoryId) -> (ChangesetId) {
+        "SELECT cs_id
+         FROM hidden_changesets
+         WHERE repo_id = {repo_id}"
+    }
+
+    read ListHiddenChangesets(repo_id: RepositoryId) -> (ChangesetId, Timestamp, String) {
+        "SELECT cs_id, timestamp, reason
+         FROM hidden_changesets
+         WHERE repo_id = {repo_id}
+         ORDER BY timestamp"
+    }
+}
+
+pub struct SqlHiddenChangesets {
+    repo_id: RepositoryId,
+    connections: SqlConnections,
+}
+
+pub struct SqlHiddenChangesetsBuilder {
+    connections: SqlConnections,
+}
+
+impl SqlConstruct for SqlHiddenChangesetsBuilder {
+    const LABEL: &'static str = "hidden_changesets";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-hidden-changesets.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self { connections }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SqlHiddenChangesetsBuilder {}
+
+impl SqlHiddenChangesetsBuilder {
+    pub fn build(self, repo_id: RepositoryId) -> SqlHiddenChangesets {
+        SqlHiddenChangesets {
+            repo_id,
+            connections: self.connections,
+        }
+    }
+}
+
+impl SqlHiddenChangesets {
+    /// Load the ids of all hidden changesets.
+    pub(crate) async fn load_all(&self, ctx: &CoreContext) -> Result<HashSet<ChangesetId>> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+        let rows =
+            SelectAllHiddenChangesets::query(&self.connections.read_connection, &self.repo_id)
+                .await?;
+        Ok(rows.into_iter().map(|(cs_id,)| cs_id).collect())
+    }
+}
+
+#[async_trait]
+impl HiddenChangesets for SqlHiddenChangesets {
+    async fn hide(&self, ctx: &CoreContext, cs_ids: &[ChangesetId], reason: &str) -> Result<()> {
+        if cs_ids.is_empty() {
+            return Ok(());
+        }
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        let timestamp = Timestamp::now();
+        let reason = reason.to_string();
+        let values = cs_ids
+            .iter()
+            .map(|cs_id| (&self.repo_id, cs_id, &timestamp, &reason))
+            .collect::<Vec<_>>();
+        HideChangesets::query(&self.connections.write_connection, &values).await?;
+        Ok(())
+    }
+
+    async fn unhide(&self, ctx: &CoreContext, cs_ids: &[ChangesetId]) -> Result<()> {
+        if cs_ids.is_empty() {
+            return Ok(());
+        }
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        UnhideChangesets::query(&self.connections.write_connection, &self.repo_id, cs_ids).await?;
+        Ok(())
+    }
+
+    async fn get_hidden(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+    ) -> Result<HashSet<ChangesetId>> {
+        if cs_ids.is_empty() {
+            return Ok(HashSet::new());
+        }
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+        let rows = SelectHiddenChangesets::query(
+            &self.connections.read_connection,
+            &self.repo_id,
+            cs_ids,
+        )
+        .await?;
+        Ok(rows.into_iter().map(|(cs_id,)| cs_id).collect())
+    }
+
+    async fn list(&self, ctx: &CoreContext) -> Result<Vec<HiddenChangeset>> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsMaster);
+        let rows =
+            ListHiddenChangesets::query(&self.connections.read_master_connection, &self.repo_id)
+                .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(cs_id, timestamp, reason)| HiddenChangeset {
+                cs_id,
+                timestamp,
+                reason,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use maplit::hashset;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::THREES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+    use mononoke_types_mocks::repo::REPO_ONE;
+    use mononoke_types_mocks::repo::REPO_ZERO;
+
+    use super::*;
+
+    #[fbinit::test]
+    async fn test_hide_and_unhide(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let builder = SqlHiddenChangesetsBuilder::with_sqlite_in_memory()?;
+        let connections = builder.connections.clone();
+        let hidden = builder.build(REPO_ZERO);
+        let other_hidden =
+            SqlHiddenChangesetsBuilder::from_sql_connections(connections).build(REPO_ONE);
+        let all = [ONES_CSID, TWOS_CSID, THREES_CSID];
+
+        assert!(hidden.get_hidden(&ctx, &all).await?.is_empty());
+
+        hidden
+            .hide(&ctx, &[ONES_CSID, TWOS_CSID], "leaked secret")
+            .await?;
+        // Hiding again keeps the original reason.
+        hidden.hide(&ctx, &[TWOS_CSID], "other reason").await?;
+        assert_eq!(
+            hidden.get_hidden(&ctx, &all).await?,
+            hashset! { ONES_CSID, TWOS_CSID }
+        );
+        let listed = hidden.list(&ctx).await?;
+        assert_eq!(listed.len(), 2);
+        assert!(listed.iter().all(|entry| entry.reason == "leaked secret"));
+
+        // Hidden changesets are per repo.
+        assert!(other_hidden.get_hidden(&ctx, &all).await?.is_empty());
+
+        hidden.unhide(&ctx, &[ONES_CSID]).await?;
+        assert_eq!(hidden.get_hidden(&ctx, &all).await?, hashset! { TWOS_CSID });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use async_trait::async_trait;
+use bookmarks::BookmarkKey;
+use bytes::Bytes;
+use changeset_info::ChangesetInfo;
+use context::CoreContext;
+use futures::future;
+use futures::stream::BoxStream;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
+use mononoke_types::ChangesetId;
+use mononoke_types::ContentId;
+use mononoke_types::MPath;
+
+use crate::ErrorKind;
+use crate::FileChange;
+use crate::FileContentManager;
+use crate::PathContent;
+
+/// Limits the number of bytes of file content that a hook can read.  Once
+/// the budget is used up, reading more content fails.
+///
+/// File sizes can still be checked, so hooks can skip files that would not
+/// fit in what is left of their budget.
+pub struct BudgetedFileContentManager<'a> {
+    inner: &'a dyn FileContentManager,
+    budget: u64,
+    used: AtomicU64,
+}
+
+impl<'a> BudgetedFileContentManager<'a> {
+    pub fn new(inner: &'a dyn FileContentManager, budget: u64) -> Self {
+        Self {
+            inner,
+            budget,
+            used: AtomicU64::new(0),
+        }
+    }
+
+    /// The number of bytes of the budget that have not been used.
+    pub fn remaining(&self) -> u64 {
+        self.budget
+            .saturating_sub(self.used.load(Ordering::Relaxed))
+    }
+
+    fn charge(&self, bytes: u64) -> Result<(), ErrorKind> {
+        let used = self.used.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        if used > self.budget {
+            return Err(ErrorKind::ContentBudgetExceeded(self.budget));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<'b> FileContentManager for BudgetedFileContentManager<'b> {
+    async fn get_file_size<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<u64, ErrorKind> {
+        self.inner.get_file_size(ctx, id).await
+    }
+
+    async fn get_file_text<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<Option<Bytes>, ErrorKind> {
+        let text = self.inner.get_file_text(ctx, id).await?;
+        if let Some(bytes) = &text {
+            self.charge(bytes.len() as u64)?;
+        }
+        Ok(text)
+    }
+
+    /// Only the chunks that are read from the stream are charged to the
+    /// budget.
+    async fn get_file_stream<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<Option<BoxStream<'a, Result<Bytes, ErrorKind>>>, ErrorKind> {
+        let file_stream = self.inner.get_file_stream(ctx, id).await?;
+        Ok(file_stream.map(|file_stream| {
+            file_stream
+                .and_then(move |bytes| {
+                    future::ready(self.charge(bytes.len() as u64).map(|()| bytes))
+                })
+                .boxed()
+        }))
+    }
+
+    async fn find_content<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        bookmark: BookmarkKey,
+        paths: Vec<MPath>,
+    ) -> Result<HashMap<MPath, PathContent>, ErrorKind> {
+        self.inner.find_content(ctx, bookmark, paths).await
+    }
+
+    async fn file_changes<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        new_cs_id: ChangesetId,
+        old_cs_id: ChangesetId,
+    ) -> Result<Vec<(MPath, FileChange)>, ErrorKind> {
+        self.inner.file_changes(ctx, new_cs_id, old_cs_id).await
+    }
+
+    async fn latest_changes<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        bookmark: BookmarkKey,
+        paths: Vec<MPath>,
+    ) -> Result<HashMap<MPath, ChangesetInfo>, ErrorKind> {
+        self.inner.latest_changes(ctx, bookmark, paths).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use mononoke_types_mocks::contentid::ONES_CTID;
+    use mononoke_types_mocks::contentid::TWOS_CTID;
+
+    use super::*;
+    use crate::InMemoryFileContentManager;
+
+    #[fbinit::test]
+    async fn test_budget_exceeded(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+
+        let mut inner = InMemoryFileContentManager::new();
+        inner.insert(ONES_CTID, "foobar");
+        inner.insert(TWOS_CTID, "baz");
+
+        let store = BudgetedFileContentManager::new(&inner, 8);
+        let ret = store.get_file_text(&ctx, ONES_CTID).await.unwrap();
+        assert_eq!(ret, Some("foobar".into()));
+        assert_eq!(store.remaining(), 2);
+
+        let ret = store.get_file_size(&ctx, TWOS_CTID).await.unwrap();
+        assert_eq!(ret, 3);
+        let ret = store.get_file_text(&ctx, TWOS_CTID).await;
+        assert!(matches!(ret, Err(ErrorKind::ContentBudgetExceeded(8))));
+    }
+
+    #[fbinit::test]
+    async fn test_stream_charges_chunks_read(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+
+        let mut inner = InMemoryFileContentManager::new();
+        inner.insert(ONES_CTID, "foobar");
+
+        let store = BudgetedFileContentManager::new(&inner, 10);
+        let file_stream = store.get_file_stream(&ctx, ONES_CTID).await.unwrap();
+        assert_eq!(store.remaining(), 10);
+        let chunks: Vec<_> = file_stream.unwrap().try_collect().await.unwrap();
+        assert_eq!(chunks, vec![Bytes::from("foobar")]);
+        assert_eq!(store.remaining(), 4);
+    }
+}
@@ -0,0 +1,318 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use bookmarks::BookmarkKey;
+use bytes::Bytes;
+use changeset_info::ChangesetInfo;
+use context::CoreContext;
+use futures::future;
+use futures::future::BoxFuture;
+use futures::future::FutureExt;
+use futures::future::Shared;
+use futures::stream;
+use futures::stream::BoxStream;
+use futures::stream::StreamExt;
+use mononoke_types::ChangesetId;
+use mononoke_types::ContentId;
+use mononoke_types::MPath;
+use shared_error::anyhow::IntoSharedError;
+use shared_error::anyhow::SharedError;
+
+use crate::ErrorKind;
+use crate::FileChange;
+use crate::FileContentManager;
+use crate::PathContent;
+
+type SharedFetch<T> = Shared<BoxFuture<'static, Result<T, SharedError>>>;
+
+/// Caches the sizes and texts of the files that hooks fetch, so that each
+/// file is only fetched once however many hooks check it.  Hooks checking
+/// the same file at the same time share a single fetch.
+///
+/// Nothing is evicted from the cache, so it should only be kept for the
+/// hooks run on a single push.  Once the cached texts reach
+/// `max_cached_bytes`, further texts are fetched without being cached.
+pub struct CachingFileContentManager {
+    inner: Arc<dyn FileContentManager>,
+    max_cached_bytes: u64,
+    cached_bytes: AtomicU64,
+    sizes: Mutex<HashMap<ContentId, SharedFetch<u64>>>,
+    texts: Mutex<HashMap<ContentId, SharedFetch<Option<Bytes>>>>,
+}
+
+impl CachingFileContentManager {
+    pub fn new(inner: Arc<dyn FileContentManager>, max_cached_bytes: u64) -> Self {
+        Self {
+            inner,
+            max_cached_bytes,
+            cached_bytes: AtomicU64::new(0),
+            sizes: Mutex::new(HashMap::new()),
+            texts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reserve space in the cache for a text of `size` bytes, returning
+    /// whether it fits.
+    fn reserve(&self, size: u64) -> bool {
+        self.cached_bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |cached_bytes| {
+                let cached_bytes = cached_bytes.checked_add(size)?;
+                (cached_bytes <= self.max_cached_bytes).then_some(cached_bytes)
+            })
+            .is_ok()
+    }
+}
+
+fn shared_fetch<T: Clone>(fut: BoxFuture<'static, Result<T, ErrorKind>>) -> SharedFetch<T> {
+    fut.map(|res| res.map_err(anyhow::Error::from).shared_error())
+        .boxed()
+        .shared()
+}
+
+async fn await_shared<T: Clone>(fut: SharedFetch<T>) -> Result<T, ErrorKind> {
+    fut.await
+        .map_err(|e| ErrorKind::BackingStore(anyhow::Error::from(e)))
+}
+
+#[async_trait]
+impl FileContentManager for CachingFileContentManager {
+    async fn get_file_size<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<u64, ErrorKind> {
+        let fut = self
+            .sizes
+            .lock()
+            .expect("lock poisoned")
+            .entry(id)
+            .or_insert_with(|| {
+                let (inner, ctx) = (self.inner.clone(), ctx.clone());
+                shared_fetch(async move { inner.get_file_size(&ctx, id).await }.boxed())
+            })
+            .clone();
+        await_shared(fut).await
+    }
+
+    async fn get_file_text<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<Option<Bytes>, ErrorKind> {
+        let size = self.get_file_size(ctx, id).await?;
+        let fut = {
+            let mut texts = self.texts.lock().expect("lock poisoned");
+            match texts.get(&id) {
+                Some(fut) => Some(fut.clone()),
+                None if self.reserve(size) => {
+                    let (inner, ctx) = (self.inner.clone(), ctx.clone());
+                    let fut =
+                        shared_fetch(async move { inner.get_file_text(&ctx, id).await }.boxed());
+                    texts.insert(id, fut.clone());
+                    Some(fut)
+                }
+                None => None,
+            }
+        };
+        match fut {
+            Some(fut) => await_shared(fut).await,
+            None => self.inner.get_file_text(ctx, id).await,
+        }
+    }
+
+    /// Files that have already been fetched in full are streamed from the
+    /// cache.  Others are streamed from the inner store without being cached,
+    /// as the point of streaming them is to avoid fetching all of them.
+    async fn get_file_stream<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<Option<BoxStream<'a, Result<Bytes, ErrorKind>>>, ErrorKind> {
+        let cached = self
+            .texts
+            .lock()
+            .expect("lock poisoned")
+            .get(&id)
+            .and_then(|fut| fut.peek().cloned());
+        match cached {
+            Some(Ok(text)) => Ok(text.map(|bytes| stream::once(future::ok(bytes)).boxed())),
+            _ => self.inner.get_file_stream(ctx, id).await,
+        }
+    }
+
+    async fn find_content<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        bookmark: BookmarkKey,
+        paths: Vec<MPath>,
+    ) -> Result<HashMap<MPath, PathContent>, ErrorKind> {
+        self.inner.find_content(ctx, bookmark, paths).await
+    }
+
+    async fn file_changes<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        new_cs_id: ChangesetId,
+        old_cs_id: ChangesetId,
+    ) -> Result<Vec<(MPath, FileChange)>, ErrorKind> {
+        self.inner.file_changes(ctx, new_cs_id, old_cs_id).await
+    }
+
+    async fn latest_changes<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        bookmark: BookmarkKey,
+        paths: Vec<MPath>,
+    ) -> Result<HashMap<MPath, ChangesetInfo>, ErrorKind> {
+        self.inner.latest_changes(ctx, bookmark, paths).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+
+    use fbinit::FacebookInit;
+    use futures::stream::TryStreamExt;
+    use mononoke_types_mocks::contentid::ONES_CTID;
+    use mononoke_types_mocks::contentid::THREES_CTID;
+    use mononoke_types_mocks::contentid::TWOS_CTID;
+
+    use super::*;
+    use crate::InMemoryFileContentManager;
+
+    /// Counts the texts fetched from an in-memory store.
+    struct CountingFileContentManager {
+        inner: InMemoryFileContentManager,
+        text_fetches: AtomicUsize,
+    }
+
+    impl CountingFileContentManager {
+        fn text_fetches(&self) -> usize {
+            self.text_fetches.load(Ordering::Relaxed)
+        }
+    }
+
+    #[async_trait]
+    impl FileContentManager for CountingFileContentManager {
+        async fn get_file_size<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            id: ContentId,
+        ) -> Result<u64, ErrorKind> {
+            self.inner.get_file_size(ctx, id).await
+        }
+
+        async fn get_file_text<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            id: ContentId,
+        ) -> Result<Option<Bytes>, ErrorKind> {
+            self.text_fetches.fetch_add(1, Ordering::Relaxed);
+            self.inner.get_file_text(ctx, id).await
+        }
+
+        async fn get_file_stream<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            id: ContentId,
+        ) -> Result<Option<BoxStream<'a, Result<Bytes, ErrorKind>>>, ErrorKind> {
+            self.text_fetches.fetch_add(1, Ordering::Relaxed);
+            self.inner.get_file_stream(ctx, id).await
+        }
+
+        async fn find_content<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            bookmark: BookmarkKey,
+            paths: Vec<MPath>,
+        ) -> Result<HashMap<MPath, PathContent>, ErrorKind> {
+            self.inner.find_content(ctx, bookmark, paths).await
+        }
+
+        async fn file_changes<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            new_cs_id: ChangesetId,
+            old_cs_id: ChangesetId,
+        ) -> Result<Vec<(MPath, FileChange)>, ErrorKind> {
+            self.inner.file_changes(ctx, new_cs_id, old_cs_id).await
+        }
+
+        async fn latest_changes<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            bookmark: BookmarkKey,
+            paths: Vec<MPath>,
+        ) -> Result<HashMap<MPath, ChangesetInfo>, ErrorKind> {
+            self.inner.latest_changes(ctx, bookmark, paths).await
+        }
+    }
+
+    fn counting_store() -> Arc<CountingFileContentManager> {
+        let mut inner = InMemoryFileContentManager::new();
+        inner.insert(ONES_CTID, "foobar");
+        inner.insert(TWOS_CTID, "baz");
+        inner.insert(THREES_CTID, "quux");
+        Arc::new(CountingFileContentManager {
+            inner,
+            text_fetches: AtomicUsize::new(0),
+        })
+    }
+
+    #[fbinit::test]
+    async fn test_texts_fetched_once(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let inner = counting_store();
+        let store = CachingFileContentManager::new(inner.clone(), 100);
+
+        let (first, second) = future::join(
+            store.get_file_text(&ctx, ONES_CTID),
+            store.get_file_text(&ctx, ONES_CTID),
+        )
+        .await;
+        assert_eq!(first.unwrap(), Some("foobar".into()));
+        assert_eq!(second.unwrap(), Some("foobar".into()));
+        assert_eq!(inner.text_fetches(), 1);
+
+        // Texts already fetched are streamed from the cache.
+        let file_stream = store.get_file_stream(&ctx, ONES_CTID).await.unwrap();
+        let chunks: Vec<_> = file_stream.unwrap().try_collect().await.unwrap();
+        assert_eq!(chunks, vec![Bytes::from("foobar")]);
+        assert_eq!(inner.text_fetches(), 1);
+    }
+
+    #[fbinit::test]
+    async fn test_cache_is_bounded(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let inner = counting_store();
+        let store = CachingFileContentManager::new(inner.clone(), 10);
+
+        for _ in 0..2 {
+            let ret = store.get_file_text(&ctx, ONES_CTID).await.unwrap();
+            assert_eq!(ret, Some("foobar".into()));
+            let ret = store.get_file_text(&ctx, TWOS_CTID).await.unwrap();
+            assert_eq!(ret, Some("baz".into()));
+        }
+        assert_eq!(inner.text_fetches(), 2);
+
+        // This would take the cache over its bound, so it is fetched each
+        // time it is needed.
+        for _ in 0..2 {
+            let ret = store.get_file_text(&ctx, THREES_CTID).await.unwrap();
+            assert_eq!(ret, Some("quux".into()));
+        }
+        assert_eq!(inner.text_fetches(), 4);
+    }
+}
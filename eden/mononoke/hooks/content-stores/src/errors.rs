@@ -16,6 +16,8 @@ pub enum ErrorKind {
     BackingStore(#[from] anyhow::Error),
     #[error("Content too large to fit in memory")]
     ContentTooLarge,
+    #[error("Hook read more than its budget of {0} bytes of file content")]
+    ContentBudgetExceeded(u64),
 }
 
 impl From<std::num::TryFromIntError> for ErrorKind {
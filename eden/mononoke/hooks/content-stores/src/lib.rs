@@ -5,6 +5,8 @@
  * GNU General Public License version 2.
  */
 
+mod budget;
+mod caching;
 mod errors;
 mod memory;
 mod repo;
@@ -19,6 +21,8 @@ pub use store::FileChange;
 pub use store::FileContentManager;
 pub use store::PathContent;
 
+pub use crate::budget::BudgetedFileContentManager;
+pub use crate::caching::CachingFileContentManager;
 pub use crate::memory::InMemoryFileContentManager;
 pub use crate::memory::InMemoryFileText;
 pub use crate::repo::RepoFileContentManager;
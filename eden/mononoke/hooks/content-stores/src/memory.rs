@@ -13,6 +13,10 @@ use bookmarks::BookmarkKey;
 use bytes::Bytes;
 use changeset_info::ChangesetInfo;
 use context::CoreContext;
+use futures::future;
+use futures::stream;
+use futures::stream::BoxStream;
+use futures::stream::StreamExt;
 use mononoke_types::ChangesetId;
 use mononoke_types::ContentId;
 use mononoke_types::MPath;
@@ -82,6 +86,15 @@ impl FileContentManager for InMemoryFileContentManager {
             })
     }
 
+    async fn get_file_stream<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<Option<BoxStream<'a, Result<Bytes, ErrorKind>>>, ErrorKind> {
+        let text = self.get_file_text(ctx, id).await?;
+        Ok(text.map(|bytes| stream::once(future::ok(bytes)).boxed()))
+    }
+
     async fn find_content<'a>(
         &'a self,
         _ctx: &'a CoreContext,
@@ -18,6 +18,8 @@ use bytes::Bytes;
 use changeset_info::ChangesetInfo;
 use context::CoreContext;
 use futures::future;
+use futures::stream::BoxStream;
+use futures::stream::StreamExt;
 use futures::stream::TryStreamExt;
 use futures_util::future::TryFutureExt;
 use manifest::Diff;
@@ -76,6 +78,17 @@ impl FileContentManager for RepoFileContentManager {
             .map(Option::Some)
     }
 
+    async fn get_file_stream<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<Option<BoxStream<'a, Result<Bytes, ErrorKind>>>, ErrorKind> {
+        let stream = filestore::fetch(&self.repo_blobstore, ctx, &id.into())
+            .await?
+            .ok_or(ErrorKind::ContentIdNotFound(id))?;
+        Ok(Some(stream.map_err(ErrorKind::from).boxed()))
+    }
+
     async fn find_content<'a>(
         &'a self,
         ctx: &'a CoreContext,
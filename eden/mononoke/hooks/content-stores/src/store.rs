@@ -12,6 +12,7 @@ use bookmarks::BookmarkKey;
 use bytes::Bytes;
 use changeset_info::ChangesetInfo;
 use context::CoreContext;
+use futures::stream::BoxStream;
 use mononoke_types::ChangesetId;
 use mononoke_types::ContentId;
 use mononoke_types::MPath;
@@ -32,6 +33,15 @@ pub trait FileContentManager: Send + Sync {
         id: ContentId,
     ) -> Result<Option<Bytes>, ErrorKind>;
 
+    /// Stream the contents of a file, fetching its chunks only as the stream
+    /// is polled, so that hooks that stop reading early don't fetch the rest
+    /// of the file.  Returns `None` in the same cases as `get_file_text`.
+    async fn get_file_stream<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<Option<BoxStream<'a, Result<Bytes, ErrorKind>>>, ErrorKind>;
+
     async fn find_content<'a>(
         &'a self,
         ctx: &'a CoreContext,
@@ -13,6 +13,11 @@ use bookmarks::BookmarkKey;
 use bytes::Bytes;
 use changeset_info::ChangesetInfo;
 use context::CoreContext;
+use futures::future;
+use futures::stream;
+use futures::stream::BoxStream;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
 use mononoke_types::ChangesetId;
 use mononoke_types::ContentId;
 use mononoke_types::MPath;
@@ -72,6 +77,29 @@ impl<T: FileContentManager + 'static> FileContentManager for TextOnlyFileContent
         }))
     }
 
+    /// Like `get_file_text`, but only the first chunk of the file is checked
+    /// for null bytes, so that the rest of it can still be fetched lazily.
+    async fn get_file_stream<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<Option<BoxStream<'a, Result<Bytes, ErrorKind>>>, ErrorKind> {
+        let size = self.get_file_size(ctx, id).await?;
+        if size > self.max_size {
+            return Ok(None);
+        }
+
+        let mut file_stream = match self.inner.get_file_stream(ctx, id).await? {
+            Some(file_stream) => file_stream,
+            None => return Ok(None),
+        };
+        match file_stream.try_next().await? {
+            Some(bytes) if looks_like_binary(&bytes) => Ok(None),
+            Some(bytes) => Ok(Some(stream::once(future::ok(bytes)).chain(file_stream).boxed())),
+            None => Ok(Some(stream::empty().boxed())),
+        }
+    }
+
     async fn find_content<'a>(
         &'a self,
         ctx: &'a CoreContext,
@@ -8,6 +8,7 @@
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::time::Duration;
 
 use anyhow::Error;
 use async_trait::async_trait;
@@ -1418,6 +1419,7 @@ fn default_changeset() -> BonsaiChangeset {
         },
         is_snapshot: false,
         git_annotated_tag: None,
+        extras: None,
     }.freeze().expect("Created changeset")
 }
 
@@ -1480,10 +1482,17 @@ async fn test_load_hooks_bad_rust_hook(fb: FacebookInit) {
         only_fast_forward: false,
         allowed_users: None,
         allowed_hipster_group: None,
+        allowed_groups: vec![],
+        allowed_service_identities: vec![],
         rewrite_dates: None,
         hooks_skip_ancestors_of: vec![],
         ensure_ancestor_of: None,
         allow_move_to_public_commits_without_hooks: false,
+        require_signed_commits: false,
+        pushrebase_only: false,
+        required_derived_data: vec![],
+        required_derived_data_budget: Duration::from_secs(0),
+        preconditions: vec![],
     }];
 
     config.hooks = vec![HookParams {
@@ -1543,10 +1552,17 @@ async fn test_load_disabled_hooks_referenced_by_bookmark(fb: FacebookInit) {
         only_fast_forward: false,
         allowed_users: None,
         allowed_hipster_group: None,
+        allowed_groups: vec![],
+        allowed_service_identities: vec![],
         rewrite_dates: None,
         hooks_skip_ancestors_of: vec![],
         ensure_ancestor_of: None,
         allow_move_to_public_commits_without_hooks: false,
+        require_signed_commits: false,
+        pushrebase_only: false,
+        required_derived_data: vec![],
+        required_derived_data_budget: Duration::from_secs(0),
+        preconditions: vec![],
     }];
 
     config.hooks = vec![HookParams {
@@ -6,6 +6,7 @@
  */
 
 use std::collections::HashSet;
+use std::time::Duration;
 
 pub use mercurial_types::HgChangesetId;
 use metaconfig_types::BookmarkOrRegex;
@@ -38,4 +39,7 @@ pub enum ErrorKind {
 
     #[error("Disabled hook(s) do(es) not exist: {0:?}")]
     NoSuchHookToDisable(HashSet<String>),
+
+    #[error("Hooks took longer than the push time budget of {0:?}")]
+    PushTimeBudgetExceeded(Duration),
 }
@@ -25,6 +25,8 @@ use std::collections::HashMap;
 use std::fmt;
 use std::hash::Hash;
 use std::str;
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Error;
 use anyhow::Result;
@@ -34,18 +36,22 @@ use bytes::Bytes;
 use context::CoreContext;
 pub use errors::*;
 use fbinit::FacebookInit;
-use futures::stream::futures_unordered::FuturesUnordered;
+use futures::stream;
+use futures::stream::StreamExt;
 use futures::stream::TryStreamExt;
 use futures::try_join;
 use futures::Future;
 use futures::TryFutureExt;
 use futures_stats::TimedFutureExt;
+use hooks_content_stores::BudgetedFileContentManager;
+use hooks_content_stores::CachingFileContentManager;
 pub use hooks_content_stores::FileContentManager;
 pub use hooks_content_stores::PathContent;
 use metaconfig_types::BookmarkOrRegex;
 use metaconfig_types::HookBypass;
 use metaconfig_types::HookConfig;
 use metaconfig_types::HookManagerParams;
+use metaconfig_types::WebhookConfig;
 use mononoke_types::BasicFileChange;
 use mononoke_types::BonsaiChangeset;
 use mononoke_types::ChangesetId;
@@ -57,6 +63,34 @@ use regex::Regex;
 use scuba::builder::ServerData;
 use scuba_ext::MononokeScubaSampleBuilder;
 use slog::debug;
+use stats::prelude::*;
+
+/// The most file content that is kept in memory for the hooks run on a
+/// single push.
+const MAX_CACHED_CONTENT_BYTES: u64 = 100 * 1024 * 1024;
+
+define_stats! {
+    prefix = "mononoke.hooks";
+    accepted: dynamic_timeseries("{}.{}.accepted", (repo: String, hook: String); Rate, Sum),
+    rejected: dynamic_timeseries("{}.{}.rejected", (repo: String, hook: String); Rate, Sum),
+    failed: dynamic_timeseries("{}.{}.failed", (repo: String, hook: String); Rate, Sum),
+    bypassed: dynamic_timeseries("{}.{}.bypassed", (repo: String, hook: String); Rate, Sum),
+    completion_time_ms: dynamic_histogram(
+        "{}.{}.completion_time_ms",
+        (repo: String, hook: String);
+        10, 0, 1_000, Average, Sum, Count; P 50; P 90; P 99
+    ),
+    push_completion_time_ms: dynamic_histogram(
+        "{}.push_completion_time_ms",
+        (repo: String);
+        100, 0, 10_000, Average, Sum, Count; P 50; P 90; P 99
+    ),
+    push_time_budget_exceeded: dynamic_timeseries(
+        "{}.push_time_budget_exceeded",
+        (repo: String);
+        Rate, Sum
+    ),
+}
 
 /// Manages hooks and allows them to be installed and uninstalled given a name
 /// Knows how to run hooks
@@ -67,12 +101,15 @@ pub struct HookManager {
     hooks: HashMap<String, Hook>,
     bookmark_hooks: HashMap<BookmarkKey, Vec<String>>,
     regex_hooks: Vec<(Regex, Vec<String>)>,
-    content_manager: Box<dyn FileContentManager>,
+    content_manager: Arc<dyn FileContentManager>,
     reviewers_membership: ArcMembershipChecker,
     admin_membership: ArcMembershipChecker,
     scuba: MononokeScubaSampleBuilder,
     all_hooks_bypassed: bool,
     scuba_bypassed_commits: MononokeScubaSampleBuilder,
+    max_concurrent_hooks: Option<usize>,
+    push_time_budget: Option<Duration>,
+    webhooks: Vec<WebhookConfig>,
 }
 
 impl HookManager {
@@ -111,12 +148,15 @@ impl HookManager {
             hooks,
             bookmark_hooks: HashMap::new(),
             regex_hooks: Vec::new(),
-            content_manager,
+            content_manager: content_manager.into(),
             reviewers_membership: reviewers_membership.into(),
             admin_membership: admin_membership.into(),
             scuba,
             all_hooks_bypassed: hook_manager_params.all_hooks_bypassed,
             scuba_bypassed_commits,
+            max_concurrent_hooks: hook_manager_params.max_concurrent_hooks,
+            push_time_budget: hook_manager_params.push_time_budget,
+            webhooks: Vec::new(),
         })
     }
 
@@ -127,12 +167,15 @@ impl HookManager {
             hooks: HashMap::new(),
             bookmark_hooks: HashMap::new(),
             regex_hooks: Vec::new(),
-            content_manager,
+            content_manager: content_manager.into(),
             reviewers_membership: NeverMember::new().into(),
             admin_membership: NeverMember::new().into(),
             scuba: MononokeScubaSampleBuilder::with_discard(),
             all_hooks_bypassed: false,
             scuba_bypassed_commits: MononokeScubaSampleBuilder::with_discard(),
+            max_concurrent_hooks: None,
+            push_time_budget: None,
+            webhooks: Vec::new(),
         }
     }
 
@@ -217,6 +260,15 @@ impl HookManager {
         &self.scuba_bypassed_commits
     }
 
+    /// Set the webhooks that hook rejections are posted to.
+    pub fn set_webhooks(&mut self, webhooks: Vec<WebhookConfig>) {
+        self.webhooks = webhooks;
+    }
+
+    pub fn webhooks(&self) -> &[WebhookConfig] {
+        &self.webhooks
+    }
+
     pub async fn run_hooks_for_bookmark(
         &self,
         ctx: &CoreContext,
@@ -230,7 +282,22 @@ impl HookManager {
 
         let hooks = self.hooks_for_bookmark(bookmark);
 
-        let futs = FuturesUnordered::new();
+        // Hooks on the same push often check the same files, so share the
+        // file contents fetched by any of them.
+        let content_manager = CachingFileContentManager::new(
+            self.content_manager.clone(),
+            MAX_CACHED_CONTENT_BYTES,
+        );
+        let budgeted_content_managers: HashMap<&str, BudgetedFileContentManager> = hooks
+            .clone()
+            .filter_map(|hook_name| {
+                let budget = self.hooks.get(hook_name)?.get_config().content_byte_budget?;
+                let budgeted = BudgetedFileContentManager::new(&content_manager, budget);
+                Some((hook_name, budgeted))
+            })
+            .collect();
+
+        let mut futs = Vec::new();
 
         let mut scuba = self.scuba.clone();
         let username = ctx.metadata().unix_name();
@@ -255,15 +322,25 @@ impl HookManager {
                 cs.message(),
                 maybe_pushvars,
             ) {
+                STATS::bypassed.add_value(1, (self.repo_name.clone(), hook_name.to_string()));
+                // Record who bypassed the hook, so that hook owners can
+                // follow up on bypasses.
+                scuba.add_metadata(ctx.metadata());
                 scuba.add("bypass_reason", bypass_reason);
                 scuba.log();
                 continue;
             }
 
+            let hook_content_manager: &dyn FileContentManager =
+                match budgeted_content_managers.get(hook_name) {
+                    Some(budgeted) => budgeted,
+                    None => &content_manager,
+                };
             for future in hook.get_futures(
                 ctx,
                 bookmark,
-                &*self.content_manager,
+                hook_content_manager,
+                &self.repo_name,
                 hook_name,
                 cs,
                 scuba,
@@ -273,7 +350,32 @@ impl HookManager {
                 futs.push(future);
             }
         }
-        futs.try_collect().await
+
+        // Outcomes are returned in the order the hooks were started in, rather
+        // than the order they finish in, so that rejections are reported in
+        // the same order every time a push is attempted.
+        let outcomes = stream::iter(futs)
+            .buffered(self.max_concurrent_hooks.unwrap_or(usize::MAX))
+            .try_collect::<Vec<_>>();
+        let (stats, result) = async {
+            match self.push_time_budget {
+                Some(budget) => match tokio::time::timeout(budget, outcomes).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        STATS::push_time_budget_exceeded.add_value(1, (self.repo_name.clone(),));
+                        Err(ErrorKind::PushTimeBudgetExceeded(budget).into())
+                    }
+                },
+                None => outcomes.await,
+            }
+        }
+        .timed()
+        .await;
+        STATS::push_completion_time_ms.add_value(
+            stats.completion_time.as_millis() as i64,
+            (self.repo_name.clone(),),
+        );
+        result
     }
 }
 
@@ -358,6 +460,7 @@ impl<'a> HookInstance<'a> {
         ctx: &CoreContext,
         bookmark: &BookmarkKey,
         content_manager: &dyn FileContentManager,
+        repo_name: &str,
         hook_name: &str,
         mut scuba: MononokeScubaSampleBuilder,
         cs: &BonsaiChangeset,
@@ -415,15 +518,18 @@ impl<'a> HookInstance<'a> {
         let mut failed_hooks = 0;
         let mut stderr = None;
 
+        let stats_key = (repo_name.to_string(), hook_name.to_string());
         match result.as_ref().map(HookOutcome::get_execution) {
             Ok(HookExecution::Accepted) => {
-                // Nothing to do
+                STATS::accepted.add_value(1, stats_key.clone());
             }
             Ok(HookExecution::Rejected(info)) => {
+                STATS::rejected.add_value(1, stats_key.clone());
                 failed_hooks = 1;
                 stderr = Some(info.long_description.clone());
             }
             Err(e) => {
+                STATS::failed.add_value(1, stats_key.clone());
                 errorcode = 1;
                 stderr = Some(format!("{:?}", e));
             }
@@ -434,6 +540,7 @@ impl<'a> HookInstance<'a> {
         }
 
         let elapsed = stats.completion_time.as_millis() as i64;
+        STATS::completion_time_ms.add_value(elapsed, stats_key);
         scuba
             .add("elapsed", elapsed)
             .add("total_time", elapsed)
@@ -466,6 +573,7 @@ impl Hook {
         ctx: &'a CoreContext,
         bookmark: &'a BookmarkKey,
         content_manager: &'a dyn FileContentManager,
+        repo_name: &'a str,
         hook_name: &'cs str,
         cs: &'cs BonsaiChangeset,
         scuba: MononokeScubaSampleBuilder,
@@ -481,6 +589,7 @@ impl Hook {
                 ctx,
                 bookmark,
                 content_manager,
+                repo_name,
                 hook_name,
                 scuba,
                 cs,
@@ -494,6 +603,7 @@ impl Hook {
                         ctx,
                         bookmark,
                         content_manager,
+                        repo_name,
                         hook_name,
                         scuba.clone(),
                         cs,
@@ -9,7 +9,10 @@ use std::collections::HashSet;
 
 use anyhow::Error;
 use async_trait::async_trait;
+use bytes::Bytes;
 use context::CoreContext;
+use futures::stream::Stream;
+use futures::stream::TryStreamExt;
 use maplit::hashset;
 use mononoke_types::BasicFileChange;
 use mononoke_types::MPath;
@@ -21,6 +24,46 @@ use crate::HookExecution;
 use crate::HookRejectionInfo;
 use crate::PushAuthoredBy;
 
+/// Conflict markers are the first this many bytes of a line.
+const MARKER_LEN: usize = 8;
+
+fn is_conflict_marker(line: &[u8]) -> bool {
+    line.starts_with(b">>>>>>> ") || line.starts_with(b"<<<<<<< ")
+}
+
+/// Add `piece` to the start of the line being read, keeping no more of the
+/// line than is needed to tell whether it is a conflict marker.
+fn push_line_start(line_start: &mut Vec<u8>, piece: &[u8]) {
+    let wanted = MARKER_LEN.saturating_sub(line_start.len());
+    line_start.extend_from_slice(&piece[..wanted.min(piece.len())]);
+}
+
+/// Read the file a chunk at a time, so that only the chunks up to the first
+/// conflict marker are fetched.
+async fn has_conflict_markers<E>(
+    mut file_stream: impl Stream<Item = Result<Bytes, E>> + Unpin,
+) -> Result<bool, Error>
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let mut line_start = Vec::with_capacity(MARKER_LEN);
+    while let Some(chunk) = file_stream.try_next().await? {
+        let mut pieces = chunk.as_ref().split(|c| *c == b'\r' || *c == b'\n');
+        // The first piece continues the line the previous chunk ended in.
+        if let Some(piece) = pieces.next() {
+            push_line_start(&mut line_start, piece);
+        }
+        for piece in pieces {
+            if is_conflict_marker(&line_start) {
+                return Ok(true);
+            }
+            line_start.clear();
+            push_line_start(&mut line_start, piece);
+        }
+    }
+    Ok(is_conflict_marker(&line_start))
+}
+
 pub struct ConflictMarkers {
     allowed_suffixes: HashSet<&'static [u8]>,
 }
@@ -58,19 +101,47 @@ impl FileHook for ConflictMarkers {
             return Ok(HookExecution::Accepted);
         }
 
-        let text = content_manager
-            .get_file_text(ctx, change.content_id())
+        let file_stream = content_manager
+            .get_file_stream(ctx, change.content_id())
             .await?;
-        if let Some(text) = text {
-            for line in text.as_ref().split(|c| *c == b'\r' || *c == b'\n') {
-                if line.starts_with(b">>>>>>> ") || line.starts_with(b"<<<<<<< ") {
-                    return Ok(HookExecution::Rejected(HookRejectionInfo::new_long(
-                        "Conflict markers found",
-                        format!("Conflict markers were found in file '{}'", path),
-                    )));
-                }
+        if let Some(file_stream) = file_stream {
+            if has_conflict_markers(file_stream).await? {
+                return Ok(HookExecution::Rejected(HookRejectionInfo::new_long(
+                    "Conflict markers found",
+                    format!("Conflict markers were found in file '{}'", path),
+                )));
             }
         }
         Ok(HookExecution::Accepted)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use futures::stream;
+
+    use super::*;
+
+    async fn check(chunks: &[&'static str]) -> bool {
+        let chunks = chunks
+            .iter()
+            .map(|chunk| Ok::<_, std::io::Error>(Bytes::from_static(chunk.as_bytes())));
+        has_conflict_markers(stream::iter(chunks)).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_conflict_markers() {
+        assert!(check(&["foo\n<<<<<<< dest\nbar\n"]).await);
+        assert!(check(&["foo\r\n>>>>>>> source"]).await);
+        assert!(!check(&["foo\n <<<<<<< dest\n>>>>>>>\n"]).await);
+        assert!(!check(&[]).await);
+    }
+
+    #[tokio::test]
+    async fn test_markers_split_across_chunks() {
+        assert!(check(&["foo\n<<<", "<", "<<<", " dest\n"]).await);
+        assert!(check(&["foo\n", ">>>>>>> source"]).await);
+        assert!(!check(&["foo <<<", "<<<< dest\n"]).await);
+        assert!(!check(&["foo\n<<<<<<<", "\n dest"]).await);
+    }
+}
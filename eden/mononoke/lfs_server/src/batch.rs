@@ -11,10 +11,8 @@ use std::time::Instant;
 
 use anyhow::Context;
 use anyhow::Error;
-use blobstore::Blobstore;
-use blobstore::Loadable;
-use blobstore::LoadableError;
 use filestore::Alias;
+use filestore::FetchKey;
 use futures::future;
 use futures::future::FutureExt;
 use futures::pin_mut;
@@ -45,10 +43,8 @@ use lfs_protocol::Transfer;
 use maplit::hashmap;
 use mononoke_types::hash::Sha256;
 use mononoke_types::typed_hash::ContentId;
-use mononoke_types::BlobstoreKey;
 use rand::Rng;
 use redactedblobstore::has_redaction_root_cause;
-use repo_blobstore::RepoBlobstoreRef;
 use serde::Deserialize;
 use slog::debug;
 use stats::prelude::*;
@@ -236,60 +232,45 @@ async fn resolve_internal_object(
     ctx: &RepositoryRequestContext,
     oid: Sha256,
 ) -> Result<Option<InternalObject>, Error> {
-    let blobstore = ctx.repo.repo_blobstore();
-
-    let content_id = Alias::Sha256(oid).load(&ctx.ctx, blobstore).await;
+    let blobstore = ctx
+        .blobstore_for(&FetchKey::Aliased(Alias::Sha256(oid)))
+        .await
+        .context(ErrorKind::LocalAliasLoadError)?;
 
-    let content_id = match content_id {
-        Ok(content_id) => content_id,
-        Err(LoadableError::Missing(_)) => return Ok(None),
-        Err(e) => return Err(Error::from(e).context(ErrorKind::LocalAliasLoadError)),
-    };
+    let key = FetchKey::Aliased(Alias::Sha256(oid));
 
     // The filestore may allow aliases to be created before the contents are created (the creation
-    // of the content is what makes it logically exists), so we should check for the content's
-    // existence before we proceed here. Querying metadata does this implicitly, since metadata is
-    // written after content. This wouldn't matter if we didn't have an upstream, but it does
+    // of the content is what makes it logically exists), so resolving the alias also checks for
+    // the content's existence. This wouldn't matter if we didn't have an upstream, but it does
     // matter for now to handle the (very much edge-y) case of the content existing in the
     // upstream, its alias existing locally, but not its content (T57777060).
 
+    let content_id = filestore::get_canonical_id(&blobstore, &ctx.ctx, &key)
+        .await
+        .context(ErrorKind::LocalAliasLoadError)?;
+
+    let content_id = match content_id {
+        Some(content_id) => content_id,
+        None => return Ok(None),
+    };
+
     let meta = filestore::get_metadata(&blobstore, &ctx.ctx, &(content_id.into()))
         .await
         .with_context(|| format!("Failed fetching content metadata for {:?}", content_id));
 
     match meta {
-        Ok(Some(meta)) => {
-            return Ok(Some(InternalObject::new(
-                meta.content_id,
-                meta.sha256,
-                Some(meta.total_size),
-            )));
-        }
-        Ok(None) => {
-            return Ok(None);
-        }
+        Ok(Some(meta)) => Ok(Some(InternalObject::new(
+            meta.content_id,
+            meta.sha256,
+            Some(meta.total_size),
+        ))),
+        Ok(None) => Ok(None),
+        // Redaction only applies to the content, which we already know exists.
         Err(e) if has_redaction_root_cause(&e) => {
-            // Fallthrough to an existence check.
-        }
-        Err(e) => {
-            return Err(e);
+            Ok(Some(InternalObject::new(content_id, oid, None)))
         }
-    };
-
-    // If a load error was caused by redaction, then check for existence instead, which isn't
-    // subject to redaction (only the content is).
-
-    let exists = blobstore
-        .is_present(&ctx.ctx, &content_id.blobstore_key())
-        .await
-        .with_context(|| format!("Failed to check for existence of: {:?}", content_id))?
-        .assume_not_found_if_unsure();
-
-    if exists {
-        return Ok(Some(InternalObject::new(content_id, oid, None)));
+        Err(e) => Err(e),
     }
-
-    Ok(None)
 }
 
 fn generate_routing_key(tasks_per_content: NonZeroU16, oid: Sha256) -> String {
@@ -325,8 +306,13 @@ async fn internal_objects(
 
     objs.into_iter()
         .filter_map(|(maybe_obj, allow_consistent_routing)| match maybe_obj {
-            // Map the objects we have locally into an action routing to a Mononoke LFS server.
+            // Map the objects we have locally into an action routing to a Mononoke LFS server,
+            // or to the CDN in front of it.
             Some(obj) => {
+                if let Some(action) = ctx.cdn_download_action(&obj.id).transpose() {
+                    return Some(action.map(|action| (obj, action)));
+                }
+
                 let uri = if allow_consistent_routing && ctx.config.enable_consistent_routing() {
                     let routing_key = generate_routing_key(ctx.config.tasks_per_content(), obj.oid);
                     ctx.uri_builder
@@ -680,8 +666,10 @@ mod test {
     use std::sync::atomic::AtomicU64;
     use std::sync::atomic::Ordering;
     use std::sync::Arc;
+    use std::time::Duration;
 
     use async_trait::async_trait;
+    use blobstore::Blobstore;
     use blobstore::BlobstoreBytes;
     use blobstore::BlobstoreGetData;
     use bytes::Bytes;
@@ -692,6 +680,7 @@ mod test {
     use futures::stream;
     use hyper::Uri;
     use memblob::Memblob;
+    use mononoke_types::BlobstoreKey;
     use mononoke_types::ContentMetadataId;
     use mononoke_types_mocks::hash::FOURS_SHA256;
     use mononoke_types_mocks::hash::ONES_SHA256;
@@ -700,9 +689,12 @@ mod test {
     use pretty_assertions::assert_eq;
     use redactedblobstore::RedactedBlobs;
     use redactedblobstore::RedactedMetadata;
+    use repo_blobstore::RepoBlobstoreRef;
     use test_repo_factory::TestRepoFactory;
 
     use super::*;
+    use crate::config::CdnConfig;
+    use crate::config::ServerConfig;
     use crate::lfs_server_context::ServerUris;
     use crate::Repo;
 
@@ -1147,6 +1139,46 @@ mod test {
         assert_eq!(obj2, obj);
         assert_eq!(action1, action2);
 
+        Ok(())
+    }
+    #[fbinit::test]
+    async fn test_internal_objects_cdn(fb: FacebookInit) -> Result<(), Error> {
+        let repo: Repo = test_repo_factory::build_empty(fb)?;
+
+        let meta = filestore::store(
+            repo.repo_blobstore(),
+            *repo.filestore_config(),
+            &CoreContext::test_mock(fb),
+            &StoreRequest::new(6),
+            stream::once(async move { Ok(Bytes::from("foobar")) }),
+        )
+        .await?;
+
+        let mut config = ServerConfig::default();
+        *config.cdn_mut() = Some(CdnConfig {
+            base_url: "https://cdn.example.com".parse()?,
+            signing_key: b"secret".to_vec(),
+            url_ttl: Duration::from_secs(60),
+            health_check_url: None,
+        });
+        let ctx = RepositoryRequestContext::test_builder(fb)?
+            .repo(repo)
+            .config(config)
+            .build()?;
+
+        let obj = RequestObject {
+            oid: meta.sha256.into(),
+            size: 6,
+        };
+
+        let (_, action) = internal_objects(&ctx, &[obj])
+            .await?
+            .get(&meta.sha256.into())
+            .context("Missing object")?;
+
+        assert_eq!(action.href.host(), Some("cdn.example.com"));
+        assert_eq!(action.expires_in, Some(60));
+
         Ok(())
     }
 }
@@ -0,0 +1,127 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Signed URLs for serving downloads through a CDN.
+//!
+//! A signed URL is the path of the download on this server, appended to the
+//! CDN's base URL, with an expiry time and a signature of both added to the
+//! query string. The CDN verifies the signature using the same key before
+//! serving the content, fetching it from this server on a cache miss.
+
+use std::fmt::Write;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::Error;
+use http::uri::Uri;
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::config::CdnConfig;
+
+const HMAC_BLOCK_SIZE: usize = 64;
+
+/// HMAC-SHA256, as defined in RFC 2104.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; HMAC_BLOCK_SIZE];
+    if key.len() > HMAC_BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let pad = |byte: u8| block.iter().map(|b| b ^ byte).collect::<Vec<_>>();
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{:02x}", b);
+        s
+    })
+}
+
+/// The signature of `path`, relative to the CDN's base URL, for URLs expiring
+/// at `expires` (in seconds since the epoch).
+fn signature(signing_key: &[u8], path: &str, expires: u64) -> String {
+    let message = format!("{}:{}", path, expires);
+    to_hex(&hmac_sha256(signing_key, message.as_bytes()))
+}
+
+/// Build a signed CDN URL for `path`, valid from `now` until the configured
+/// TTL elapses.
+pub fn signed_uri(config: &CdnConfig, path: &str, now: SystemTime) -> Result<Uri, Error> {
+    let path = path.trim_start_matches('/');
+    let expires = (now + config.url_ttl).duration_since(UNIX_EPOCH)?.as_secs();
+    let signature = signature(&config.signing_key, path, expires);
+
+    let base_url = config.base_url.to_string();
+    let uri = format!(
+        "{}/{}?expires={}&signature={}",
+        base_url.trim_end_matches('/'),
+        path,
+        expires,
+        signature
+    );
+    Ok(uri.parse()?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_hmac_sha256() {
+        // Test cases 2 and 6 from RFC 4231.
+        assert_eq!(
+            to_hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            to_hex(&hmac_sha256(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn test_signed_uri() -> Result<(), Error> {
+        let config = CdnConfig {
+            base_url: "https://cdn.example.com/lfs/".parse()?,
+            signing_key: b"secret".to_vec(),
+            url_ttl: Duration::from_secs(60),
+            health_check_url: None,
+        };
+        let now = UNIX_EPOCH + Duration::from_secs(1000);
+
+        let uri = signed_uri(&config, "/repo/download/abc", now)?;
+        assert_eq!(
+            uri.to_string(),
+            format!(
+                "https://cdn.example.com/lfs/repo/download/abc?expires=1060&signature={}",
+                signature(b"secret", "repo/download/abc", 1060)
+            )
+        );
+        assert_ne!(
+            signature(b"secret", "repo/download/abc", 1060),
+            signature(b"secret", "repo/download/abd", 1060)
+        );
+        Ok(())
+    }
+}
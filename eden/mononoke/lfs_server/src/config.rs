@@ -8,10 +8,12 @@
 use std::collections::BTreeSet;
 use std::num::NonZeroU16;
 use std::str::FromStr;
+use std::time::Duration;
 
 use anyhow::Context;
 use anyhow::Error;
 use gotham_ext::middleware::PostResponseConfig;
+use http::uri::Uri;
 use permission_checker::MononokeIdentitySet;
 use rate_limiting::LoadShedLimit;
 use serde::de::Deserializer;
@@ -54,6 +56,68 @@ impl TryFrom<lfs_server_config::ObjectPopularity> for ObjectPopularity {
     }
 }
 
+#[derive(Clone)]
+pub struct CdnConfig {
+    /// Base URL of the CDN, which download paths are appended to.
+    pub base_url: Uri,
+    /// Key shared with the CDN to sign URLs with.
+    pub signing_key: Vec<u8>,
+    /// How long signed URLs remain valid.
+    pub url_ttl: Duration,
+    /// URL that succeeds while the CDN is able to serve downloads.
+    pub health_check_url: Option<Uri>,
+}
+
+impl std::fmt::Debug for CdnConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Don't leak the signing key into logs.
+        f.debug_struct("CdnConfig")
+            .field("base_url", &self.base_url)
+            .field("url_ttl", &self.url_ttl)
+            .field("health_check_url", &self.health_check_url)
+            .finish()
+    }
+}
+
+impl TryFrom<lfs_server_config::CdnConfig> for CdnConfig {
+    type Error = Error;
+
+    fn try_from(value: lfs_server_config::CdnConfig) -> Result<Self, Self::Error> {
+        let base_url = value
+            .base_url
+            .parse()
+            .with_context(|| format!("Invalid base_url: {:?}", value.base_url))?;
+
+        if value.signing_key.is_empty() {
+            return Err(Error::msg("signing_key is empty"));
+        }
+
+        let url_ttl_secs: u64 = value
+            .url_ttl_secs
+            .try_into()
+            .with_context(|| format!("Invalid url_ttl_secs: {:?}", value.url_ttl_secs))?;
+        if url_ttl_secs == 0 {
+            return Err(Error::msg("url_ttl_secs is 0"));
+        }
+
+        let health_check_url = value
+            .health_check_url
+            .as_ref()
+            .map(|url| {
+                url.parse()
+                    .with_context(|| format!("Invalid health_check_url: {:?}", url))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            base_url,
+            signing_key: value.signing_key.into_bytes(),
+            url_ttl: Duration::from_secs(url_ttl_secs),
+            health_check_url,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
     pub raw_server_config: lfs_server_config::LfsServerConfig,
@@ -61,6 +125,7 @@ pub struct ServerConfig {
     object_popularity: Option<ObjectPopularity>,
     tasks_per_content: NonZeroU16,
     disable_compression_identities: Vec<MononokeIdentitySet>,
+    cdn: Option<CdnConfig>,
 }
 
 impl TryFrom<lfs_server_config::LfsServerConfig> for ServerConfig {
@@ -99,12 +164,20 @@ impl TryFrom<lfs_server_config::LfsServerConfig> for ServerConfig {
             disable_compression_identities.push(idents);
         }
 
+        let cdn = value
+            .cdn
+            .as_ref()
+            .map(|c| c.clone().try_into())
+            .transpose()
+            .with_context(|| "Invalid cdn config")?;
+
         Ok(Self {
             raw_server_config: value,
             loadshedding_limits,
             object_popularity,
             tasks_per_content,
             disable_compression_identities,
+            cdn,
         })
     }
 }
@@ -142,6 +215,7 @@ impl Default for ServerConfig {
             disable_compression: false,
             disable_compression_identities: vec![],
             enforce_authentication: false,
+            cdn: None,
         };
 
         Self {
@@ -150,6 +224,7 @@ impl Default for ServerConfig {
             object_popularity: None,
             tasks_per_content: NonZeroU16::new(1).unwrap(),
             disable_compression_identities: vec![],
+            cdn: None,
         }
     }
 }
@@ -193,6 +268,13 @@ impl ServerConfig {
     pub fn disable_compression_identities_mut(&mut self) -> &mut Vec<MononokeIdentitySet> {
         &mut self.disable_compression_identities
     }
+    pub fn cdn(&self) -> Option<&CdnConfig> {
+        self.cdn.as_ref()
+    }
+    #[cfg(test)]
+    pub fn cdn_mut(&mut self) -> &mut Option<CdnConfig> {
+        &mut self.cdn
+    }
 }
 
 impl PostResponseConfig for ServerConfig {
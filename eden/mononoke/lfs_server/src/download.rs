@@ -32,7 +32,6 @@ use mononoke_types::hash::Sha256;
 use mononoke_types::ContentId;
 use permission_checker::MononokeIdentitySet;
 use redactedblobstore::has_redaction_root_cause;
-use repo_blobstore::RepoBlobstoreRef;
 use serde::Deserialize;
 use stats::prelude::*;
 
@@ -113,9 +112,14 @@ async fn fetch_by_key(
     range: Option<Range>,
     scuba: &mut Option<&mut ScubaMiddlewareState>,
 ) -> Result<impl TryIntoResponse, HttpError> {
+    let blobstore = ctx
+        .blobstore_for(&key)
+        .await
+        .map_err(|e| HttpError::e500(e.context(ErrorKind::FilestoreReadFailure)))?;
+
     // Query a stream out of the Filestore
     let fetched = filestore::fetch_range_with_size(
-        ctx.repo.repo_blobstore().clone(),
+        blobstore,
         ctx.ctx.clone(),
         &key,
         range.unwrap_or_else(Range::all),
@@ -11,12 +11,16 @@ use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
 
 use anyhow::Context;
 use anyhow::Error;
 use bytes::Bytes;
 use cached_config::ConfigHandle;
 use context::CoreContext;
+use filestore::FetchKey;
+use filestore::FilestoreConfigRef;
 use futures::future;
 use futures::stream::Stream;
 use futures::stream::StreamExt;
@@ -37,16 +41,21 @@ use hyper::Body;
 use hyper::Client;
 use hyper::Request;
 use hyper_openssl::HttpsConnector;
+use lfs_protocol::ObjectAction;
 use lfs_protocol::RequestBatch;
 use lfs_protocol::RequestObject;
 use lfs_protocol::ResponseBatch;
+use lfs_shared_store::SharedLfsStore;
 use metaconfig_types::RepoConfigRef;
 use mononoke_types::ContentId;
 use repo_authorization::AuthorizationContext;
+use repo_blobstore::RepoBlobstore;
+use repo_blobstore::RepoBlobstoreRef;
 use repo_permission_checker::RepoPermissionCheckerRef;
 use slog::Logger;
 use tokio::runtime::Handle;
 
+use crate::cdn;
 use crate::config::ServerConfig;
 use crate::errors::ErrorKind;
 use crate::errors::LfsServerContextErrorKind;
@@ -60,6 +69,9 @@ pub type HttpsHyperClient = Client<HttpsConnector<HttpConnector>>;
 // The user agent string presented to upstream
 const CLIENT_USER_AGENT: &str = "mononoke-lfs-server/0.1.0 git/2.15.1";
 
+const CDN_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const CDN_HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
 struct LfsServerContextInner {
     repositories: LfsRepos,
     client: Arc<HttpsHyperClient>,
@@ -73,6 +85,7 @@ struct LfsServerContextInner {
 pub struct LfsServerContext {
     inner: Arc<Mutex<LfsServerContextInner>>,
     will_exit: Arc<AtomicBool>,
+    cdn_available: Arc<AtomicBool>,
 }
 
 impl LfsServerContext {
@@ -101,6 +114,7 @@ impl LfsServerContext {
         Ok(LfsServerContext {
             inner: Arc::new(Mutex::new(inner)),
             will_exit,
+            cdn_available: Arc::new(AtomicBool::new(true)),
         })
     }
 
@@ -148,6 +162,7 @@ impl LfsServerContext {
             config,
             always_wait_for_upstream,
             max_upload_size,
+            cdn_available: self.cdn_available.load(Ordering::Relaxed),
         })
     }
 
@@ -167,6 +182,33 @@ impl LfsServerContext {
     pub fn will_exit(&self) -> bool {
         self.will_exit.load(Ordering::Relaxed)
     }
+
+    /// Periodically check whether the CDN is able to serve downloads, so that
+    /// downloads are served directly while it isn't.
+    pub fn spawn_cdn_health_check(&self) {
+        let this = self.clone();
+        tokio::spawn(async move {
+            while !this.will_exit() {
+                let available = this.check_cdn_health().await;
+                this.cdn_available.store(available, Ordering::Relaxed);
+                tokio::time::sleep(CDN_HEALTH_CHECK_INTERVAL).await;
+            }
+        });
+    }
+
+    async fn check_cdn_health(&self) -> bool {
+        let config = self.get_config();
+        let url = match config.cdn().and_then(|cdn| cdn.health_check_url.clone()) {
+            Some(url) => url,
+            None => return true,
+        };
+        let client = self.inner.lock().expect("poisoned lock").client.clone();
+
+        match tokio::time::timeout(CDN_HEALTH_CHECK_TIMEOUT, client.get(url)).await {
+            Ok(Ok(res)) => res.status().is_success(),
+            _ => false,
+        }
+    }
 }
 
 async fn acl_check(
@@ -205,6 +247,7 @@ pub struct RepositoryRequestContext {
     always_wait_for_upstream: bool,
     max_upload_size: Option<u64>,
     client: HttpClient,
+    cdn_available: bool,
 }
 
 pub struct HttpClientResponse<S: Stream<Item = Result<Bytes, Error>> + Send + 'static> {
@@ -294,6 +337,55 @@ impl RepositoryRequestContext {
         self.max_upload_size
     }
 
+    /// An action to download `content_id` from the CDN with a signed URL, if
+    /// downloads are served through a CDN and it is currently available.
+    pub fn cdn_download_action(
+        &self,
+        content_id: &ContentId,
+    ) -> Result<Option<ObjectAction>, ErrorKind> {
+        let cdn = match self.config.cdn() {
+            Some(cdn) if self.cdn_available => cdn,
+            _ => return Ok(None),
+        };
+
+        let path = format!("{}/download/{}", self.uri_builder.repository, content_id);
+        let uri = cdn::signed_uri(cdn, &path, SystemTime::now())
+            .map_err(|e| ErrorKind::UriBuilderFailed("cdn_download_uri", e))?;
+
+        let mut action = ObjectAction::new(uri);
+        action.expires_in = cdn.url_ttl.as_secs().try_into().ok();
+        Ok(Some(action))
+    }
+
+    /// The storage shared with other repos, if this repo stores its LFS
+    /// content there.
+    pub fn shared_store(&self) -> Option<SharedLfsStore> {
+        if self.repo.repo_config().lfs.shared_storage {
+            Some(SharedLfsStore::new(
+                self.repo.repo_blobstore(),
+                *self.repo.filestore_config(),
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// The blobstore to read the content `key` refers to from: the shared
+    /// storage if the repo has been granted the content there, or the repo's
+    /// own blobstore otherwise.
+    pub async fn blobstore_for(&self, key: &FetchKey) -> Result<RepoBlobstore, Error> {
+        if let Some(shared_store) = self.shared_store() {
+            if shared_store
+                .granted_content_id(&self.ctx, key)
+                .await?
+                .is_some()
+            {
+                return Ok(shared_store.blobstore().clone());
+            }
+        }
+        Ok(self.repo.repo_blobstore().clone())
+    }
+
     pub async fn dispatch(
         &self,
         mut request: Request<Body>,
@@ -579,6 +671,7 @@ mod test {
                 always_wait_for_upstream: false,
                 max_upload_size: None,
                 client: HttpClient::Disabled,
+                cdn_available: true,
             })
         }
     }
@@ -68,6 +68,7 @@ use crate::scuba::LfsScubaHandler;
 use crate::service::build_router;
 
 mod batch;
+mod cdn;
 mod config;
 mod download;
 mod errors;
@@ -303,6 +304,7 @@ fn main(fb: FacebookInit) -> Result<(), Error> {
                 will_exit,
                 config_handle.clone(),
             )?;
+            ctx.spawn_cdn_health_check();
             let enforce_authentication = ctx.get_config().enforce_authentication();
 
             let router = build_router(fb, ctx, git_blob_upload_allowed);
@@ -15,6 +15,7 @@ use fbinit::FacebookInit;
 use gotham::state::FromState;
 use gotham::state::State;
 use gotham_derive::StateData;
+use gotham_ext::middleware::ClientDeadline;
 use gotham_ext::middleware::MetadataState;
 use gotham_ext::middleware::Middleware;
 use gotham_ext::state_ext::StateExt;
@@ -147,7 +148,11 @@ impl Middleware for RequestContextMiddleware {
             .metadata(Arc::new(metadata))
             .readonly(self.readonly)
             .build();
-        let ctx = session.new_context(logger, MononokeScubaSampleBuilder::with_discard());
+        let mut ctx = session.new_context(logger, MononokeScubaSampleBuilder::with_discard());
+        // Work for the request should not outlive the client's interest in it.
+        if let Some(ClientDeadline(deadline)) = ClientDeadline::try_borrow_from(state) {
+            ctx = ctx.clone_with_deadline(*deadline);
+        }
 
         state.put(RequestContext::new(ctx, should_log));
 
@@ -167,14 +167,20 @@ where
 {
     STATS::internal_uploads.add_value(1);
 
-    filestore::store(
-        ctx.repo.repo_blobstore(),
-        *ctx.repo.filestore_config(),
-        &ctx.ctx,
-        &StoreRequest::with_sha256(size, oid),
-        data,
-    )
-    .await
+    let req = StoreRequest::with_sha256(size, oid);
+    match ctx.shared_store() {
+        Some(shared_store) => shared_store.store(&ctx.ctx, &req, data).await,
+        None => {
+            filestore::store(
+                ctx.repo.repo_blobstore(),
+                *ctx.repo.filestore_config(),
+                &ctx.ctx,
+                &req,
+                data,
+            )
+            .await
+        }
+    }
     .context(ErrorKind::FilestoreWriteFailure)?;
 
     STATS::internal_success.add_value(1);
@@ -317,7 +323,8 @@ async fn sync_internal_and_upstream(
 ) -> Result<(), Error> {
     let key = FetchKey::Aliased(Alias::Sha256(oid));
 
-    let res = filestore::fetch(ctx.repo.repo_blobstore().clone(), ctx.ctx.clone(), &key).await?;
+    let blobstore = ctx.blobstore_for(&key).await?;
+    let res = filestore::fetch(blobstore, ctx.ctx.clone(), &key).await?;
 
     match res {
         Some(stream) => {
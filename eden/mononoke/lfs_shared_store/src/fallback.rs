@@ -0,0 +1,128 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::fmt;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use blobstore::Blobstore;
+use blobstore::BlobstoreGetData;
+use blobstore::BlobstoreIsPresent;
+use context::CoreContext;
+use mononoke_types::BlobstoreBytes;
+use mononoke_types::BlobstoreKey;
+use mononoke_types::ContentAlias;
+use mononoke_types::ContentChunkId;
+use mononoke_types::ContentId;
+use mononoke_types::ContentMetadataId;
+
+use crate::grant_key;
+use crate::SHARED_LFS_PREFIX;
+
+/// A layer over the storage of a repo that stores its LFS content in the
+/// shared store, so that content moved there is still found by every reader
+/// of the repo, and not just the LFS server.
+///
+/// Keys are expected to carry the repo's prefix.  When a filestore blob is
+/// missing from the repo, it is read from the shared store instead, as long
+/// as the repo has been granted the content.
+#[derive(Debug)]
+pub struct SharedLfsFallbackBlobstore<T> {
+    blobstore: T,
+    repo_prefix: String,
+}
+
+impl<T: fmt::Display> fmt::Display for SharedLfsFallbackBlobstore<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SharedLfsFallbackBlobstore<{}>", &self.blobstore)
+    }
+}
+
+impl<T: Blobstore> SharedLfsFallbackBlobstore<T> {
+    pub fn new(blobstore: T, repo_prefix: String) -> Self {
+        Self {
+            blobstore,
+            repo_prefix,
+        }
+    }
+
+    /// The key in the shared store to read `key` from, if it is a filestore
+    /// key for content the repo has been granted.
+    async fn shared_key(&self, ctx: &CoreContext, key: &str) -> Result<Option<String>> {
+        let key = match key.strip_prefix(&self.repo_prefix) {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        let shared_key = format!("{}{}", SHARED_LFS_PREFIX, key);
+
+        let content_id = if let Ok(content_id) = ContentId::parse_blobstore_key(key) {
+            content_id
+        } else if let Ok(metadata_id) = ContentMetadataId::parse_blobstore_key(key) {
+            ContentId::new(*metadata_id.blake2())
+        } else if key.starts_with("alias.") {
+            match self.blobstore.get(ctx, &shared_key).await? {
+                Some(data) => ContentAlias::from_bytes(data.into_raw_bytes())?.content_id(),
+                None => return Ok(None),
+            }
+        } else if ContentChunkId::parse_blobstore_key(key).is_ok() {
+            // Chunks can only be found through the content they belong to,
+            // which has already been checked.
+            return Ok(Some(shared_key));
+        } else {
+            return Ok(None);
+        };
+
+        let grant_key = format!("{}{}", self.repo_prefix, grant_key(content_id));
+        let granted = self
+            .blobstore
+            .is_present(ctx, &grant_key)
+            .await?
+            .fail_if_unsure()?;
+        Ok(granted.then_some(shared_key))
+    }
+}
+
+#[async_trait]
+impl<T: Blobstore> Blobstore for SharedLfsFallbackBlobstore<T> {
+    async fn get<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<Option<BlobstoreGetData>> {
+        if let Some(data) = self.blobstore.get(ctx, key).await? {
+            return Ok(Some(data));
+        }
+        match self.shared_key(ctx, key).await? {
+            Some(shared_key) => self.blobstore.get(ctx, &shared_key).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn put<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<()> {
+        self.blobstore.put(ctx, key, value).await
+    }
+
+    async fn is_present<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<BlobstoreIsPresent> {
+        let present = self.blobstore.is_present(ctx, key).await?;
+        if let BlobstoreIsPresent::Present = present {
+            return Ok(present);
+        }
+        match self.shared_key(ctx, key).await? {
+            Some(shared_key) => self.blobstore.is_present(ctx, &shared_key).await,
+            None => Ok(present),
+        }
+    }
+}
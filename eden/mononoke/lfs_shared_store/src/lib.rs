@@ -0,0 +1,316 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Content-addressed LFS storage shared between repos.
+//!
+//! Content stored in the shared store is kept once, no matter how many repos
+//! reference it. Access is still authorized per repo: a repo can only see
+//! shared content it has been granted, either by a client uploading the
+//! content to it, or by migrating content the repo already stores itself.
+//! Grants are recorded in the repo's own blobstore namespace, so a repo can
+//! never learn about content uploaded to other repos.
+//!
+//! Repos that store their LFS content in the shared store read their blobs
+//! through `SharedLfsFallbackBlobstore`, so that content is found wherever it
+//! is stored.
+
+mod fallback;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use blobstore::Blobstore;
+use blobstore::BlobstoreBytes;
+use blobstore::Loadable;
+use bytes::Bytes;
+use context::CoreContext;
+use filestore::Alias;
+use filestore::FetchKey;
+use filestore::FilestoreConfig;
+use filestore::StoreRequest;
+use futures::Stream;
+use mononoke_types::BlobstoreKey;
+use mononoke_types::ContentId;
+use mononoke_types::ContentMetadata;
+use mononoke_types::ContentMetadataId;
+use mononoke_types::FileContents;
+use repo_blobstore::RepoBlobstore;
+
+pub use crate::fallback::SharedLfsFallbackBlobstore;
+
+/// The prefix under which shared LFS content is stored.
+pub const SHARED_LFS_PREFIX: &str = "lfs_shared.";
+
+/// The prefix of the keys recording that a repo may access shared content.
+const GRANT_PREFIX: &str = "lfs_shared_grant.";
+
+/// The key, in the repo's namespace, recording that the repo may access the
+/// shared content `content_id`.
+fn grant_key(content_id: ContentId) -> String {
+    format!("{}{}", GRANT_PREFIX, content_id)
+}
+
+/// Content moved from a repo to the shared store.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MigratedContent {
+    pub metadata: ContentMetadata,
+    /// The keys, without the repo's prefix, of the repo's own copy of the
+    /// content. They can be deleted, as the content is now read from the
+    /// shared store.
+    pub local_keys: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SharedLfsStore {
+    repo_blobstore: RepoBlobstore,
+    shared_blobstore: RepoBlobstore,
+    filestore_config: FilestoreConfig,
+}
+
+impl SharedLfsStore {
+    /// The shared store, as seen by the repo using `repo_blobstore`.
+    pub fn new(repo_blobstore: &RepoBlobstore, filestore_config: FilestoreConfig) -> Self {
+        Self {
+            repo_blobstore: repo_blobstore.clone(),
+            shared_blobstore: repo_blobstore.with_prefix(SHARED_LFS_PREFIX.to_string()),
+            filestore_config,
+        }
+    }
+
+    /// The blobstore holding shared content. Callers must check that the
+    /// repo has been granted access to content before serving it.
+    pub fn blobstore(&self) -> &RepoBlobstore {
+        &self.shared_blobstore
+    }
+
+    /// Record that the repo may access the shared content `content_id`.
+    async fn grant(&self, ctx: &CoreContext, content_id: ContentId) -> Result<()> {
+        self.repo_blobstore
+            .put(
+                ctx,
+                grant_key(content_id),
+                BlobstoreBytes::from_bytes(Bytes::new()),
+            )
+            .await
+            .with_context(|| format!("Failed to grant access to {}", content_id))
+    }
+
+    /// Returns the id of the shared content `key` refers to, if the repo may
+    /// access it.
+    pub async fn granted_content_id(
+        &self,
+        ctx: &CoreContext,
+        key: &FetchKey,
+    ) -> Result<Option<ContentId>> {
+        let maybe_id = filestore::get_canonical_id(&self.shared_blobstore, ctx, key).await?;
+        let content_id = match maybe_id {
+            Some(content_id) => content_id,
+            None => return Ok(None),
+        };
+        let granted = self
+            .repo_blobstore
+            .is_present(ctx, &grant_key(content_id))
+            .await?
+            .fail_if_unsure()?;
+        Ok(granted.then_some(content_id))
+    }
+
+    /// Fetch the metadata of shared content, if the repo may access it.
+    pub async fn get_metadata(
+        &self,
+        ctx: &CoreContext,
+        key: &FetchKey,
+    ) -> Result<Option<ContentMetadata>> {
+        match self.granted_content_id(ctx, key).await? {
+            Some(content_id) => {
+                filestore::get_metadata(&self.shared_blobstore, ctx, &content_id.into()).await
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Store content in the shared store and grant the repo access to it.
+    pub async fn store(
+        &self,
+        ctx: &CoreContext,
+        req: &StoreRequest,
+        data: impl Stream<Item = Result<Bytes>> + Send,
+    ) -> Result<ContentMetadata> {
+        let meta = filestore::store(
+            &self.shared_blobstore,
+            self.filestore_config,
+            ctx,
+            req,
+            data,
+        )
+        .await?;
+        self.grant(ctx, meta.content_id).await?;
+        Ok(meta)
+    }
+
+    /// Copy content the repo stores itself to the shared store, and grant the
+    /// repo access to it. Content already present in the shared store is not
+    /// copied again. The copy is verified against the content's metadata
+    /// before access is granted, and the keys of the repo's own copy are
+    /// returned so that they can be deleted. Returns None if the repo doesn't
+    /// have the content.
+    pub async fn migrate(
+        &self,
+        ctx: &CoreContext,
+        key: &FetchKey,
+    ) -> Result<Option<MigratedContent>> {
+        let meta = match filestore::get_metadata(&self.repo_blobstore, ctx, key).await? {
+            Some(meta) => meta,
+            None => return Ok(None),
+        };
+        let content_id = meta.content_id;
+        blobsync::copy_content(
+            ctx,
+            &self.repo_blobstore,
+            &self.shared_blobstore,
+            self.filestore_config,
+            content_id,
+        )
+        .await?;
+
+        let copied = filestore::recompute_metadata(&self.shared_blobstore, ctx, content_id)
+            .await
+            .with_context(|| format!("Failed to verify shared copy of {}", content_id))?;
+        if copied.as_ref() != Some(&meta) {
+            bail!("Shared copy of {} doesn't match the original", content_id);
+        }
+        self.grant(ctx, content_id).await?;
+
+        let mut local_keys = vec![
+            content_id.blobstore_key(),
+            ContentMetadataId::from(content_id).blobstore_key(),
+            Alias::Sha1(meta.sha1).blobstore_key(),
+            Alias::Sha256(meta.sha256).blobstore_key(),
+            Alias::GitSha1(meta.git_sha1.sha1()).blobstore_key(),
+        ];
+        let contents = content_id.load(ctx, &self.repo_blobstore).await?;
+        if let FileContents::Chunked(chunked) = contents {
+            local_keys.extend(
+                chunked
+                    .iter_chunks()
+                    .map(|chunk| chunk.chunk_id().blobstore_key()),
+            );
+        }
+
+        Ok(Some(MigratedContent {
+            metadata: meta,
+            local_keys,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use blobstore::BlobstoreUnlinkOps;
+    use fbinit::FacebookInit;
+    use futures::stream;
+    use memblob::Memblob;
+    use mononoke_types::RepositoryId;
+    use scuba_ext::MononokeScubaSampleBuilder;
+
+    use super::*;
+
+    fn repo_blobstore(inner: Arc<Memblob>, id: i32) -> RepoBlobstore {
+        RepoBlobstore::new(
+            inner,
+            None,
+            RepositoryId::new(id),
+            MononokeScubaSampleBuilder::with_discard(),
+        )
+    }
+
+    fn fallback_repo_blobstore(inner: Arc<Memblob>, id: i32) -> RepoBlobstore {
+        let prefix = RepositoryId::new(id).prefix();
+        RepoBlobstore::new(
+            Arc::new(SharedLfsFallbackBlobstore::new(inner, prefix)),
+            None,
+            RepositoryId::new(id),
+            MononokeScubaSampleBuilder::with_discard(),
+        )
+    }
+
+    #[fbinit::test]
+    async fn test_shared_store_grants(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let inner = Arc::new(Memblob::default());
+        let config = FilestoreConfig::no_chunking_filestore();
+        let store1 = SharedLfsStore::new(&repo_blobstore(inner.clone(), 1), config);
+        let store2 = SharedLfsStore::new(&repo_blobstore(inner, 2), config);
+
+        let bytes = Bytes::from_static(b"shared content");
+        let req = StoreRequest::new(bytes.len() as u64);
+        let meta = store1
+            .store(&ctx, &req, stream::once(async { Ok(bytes) }))
+            .await?;
+        let key = FetchKey::Aliased(Alias::Sha256(meta.sha256));
+
+        assert_eq!(store1.get_metadata(&ctx, &key).await?, Some(meta.clone()));
+        // The content is shared, but the other repo hasn't been granted it.
+        assert_eq!(store2.get_metadata(&ctx, &key).await?, None);
+        assert_eq!(store2.migrate(&ctx, &key).await?, None);
+        assert_eq!(store2.get_metadata(&ctx, &key).await?, None);
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_shared_store_migrate(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let inner = Arc::new(Memblob::default());
+        let config = FilestoreConfig {
+            chunk_size: Some(4),
+            chunking_threshold: None,
+            concurrency: 1,
+        };
+        let repo = fallback_repo_blobstore(inner.clone(), 1);
+        let store = SharedLfsStore::new(&repo, config);
+
+        let bytes = Bytes::from_static(b"local content");
+        let req = StoreRequest::new(bytes.len() as u64);
+        let meta = filestore::store(
+            &repo,
+            config,
+            &ctx,
+            &req,
+            stream::once({
+                let bytes = bytes.clone();
+                async { Ok(bytes) }
+            }),
+        )
+        .await?;
+        let key = FetchKey::Canonical(meta.content_id);
+        let alias = FetchKey::Aliased(Alias::Sha256(meta.sha256));
+
+        assert_eq!(store.get_metadata(&ctx, &key).await?, None);
+        let migrated = store.migrate(&ctx, &key).await?.expect("content is missing");
+        assert_eq!(migrated.metadata, meta);
+        assert_eq!(store.get_metadata(&ctx, &key).await?, Some(meta.clone()));
+
+        // Once the repo's own copy is deleted, the content is read from the
+        // shared store.
+        let prefix = RepositoryId::new(1).prefix();
+        for key in migrated.local_keys.iter() {
+            inner.unlink(&ctx, &format!("{}{}", prefix, key)).await?;
+        }
+        let local_key = format!("{}{}", prefix, meta.content_id.blobstore_key());
+        assert!(inner.get(&ctx, &local_key).await?.is_none());
+        assert_eq!(filestore::get_metadata(&repo, &ctx, &alias).await?, Some(meta));
+        assert_eq!(filestore::fetch_concat(&repo, &ctx, key.clone()).await?, bytes);
+
+        // Repos that haven't been granted the content don't see it.
+        let other = fallback_repo_blobstore(inner, 2);
+        assert_eq!(filestore::get_metadata(&other, &ctx, &alias).await?, None);
+        assert!(filestore::fetch_concat_opt(&other, &ctx, &key).await?.is_none());
+        Ok(())
+    }
+}
@@ -15,6 +15,7 @@ use std::sync::Arc;
 use anyhow::format_err;
 use anyhow::Error;
 use borrowed::borrowed;
+use bounded_traversal::TraversalStats;
 use cloned::cloned;
 use context::CoreContext;
 use futures::channel::mpsc;
@@ -26,6 +27,9 @@ use futures::stream::TryStreamExt;
 use mononoke_types::MPath;
 use mononoke_types::MPathElement;
 
+use crate::traversal::manifest_traversal_limits;
+use crate::traversal::report_traversal_stats;
+use crate::traversal::FetchSizeEstimator;
 use crate::AsyncManifest as Manifest;
 use crate::Entry;
 use crate::PathTree;
@@ -173,8 +177,17 @@ where
     LFut: Future<Output = Result<(Ctx, IntermediateLeafId), Error>> + Send + 'static,
     Ctx: Send + 'static,
 {
-    bounded_traversal::bounded_traversal(
-        256,
+    let stats = Arc::new(TraversalStats::default());
+    let estimator = Arc::new(FetchSizeEstimator::default());
+    let limits = manifest_traversal_limits(
+        &ctx,
+        stats.clone(),
+        estimator.clone(),
+        |merge_node: &MergeNode<_, _, _>| merge_node.tree_fetches(),
+    );
+    let report_ctx = ctx.clone();
+    bounded_traversal::bounded_traversal_limited(
+        limits,
         MergeNode {
             name: None,
             path: None,
@@ -187,7 +200,11 @@ where
         },
         // unfold, all merge logic happens in this unfold function
         move |merge_node: MergeNode<_, IntermediateLeafId, _>| {
-            merge(ctx.clone(), store.clone(), merge_node).boxed()
+            let tree_fetches = merge_node.tree_fetches();
+            let store = store.clone();
+            estimator
+                .measure(&ctx, tree_fetches, move |ctx| merge(ctx, store, merge_node))
+                .boxed()
         },
         // fold, this function only creates entries from merge result and already merged subentries
         {
@@ -254,6 +271,10 @@ where
             }
         },
     )
+    .map(move |result| {
+        report_traversal_stats(&report_ctx, &stats);
+        result
+    })
     .map_ok(|result: Option<_>| result.and_then(|(_, _, entry)| entry.into_tree()))
 }
 
@@ -384,6 +405,16 @@ struct MergeNode<TreeId, LeafId, Leaf> {
     parents: Vec<Entry<TreeId, LeafId>>, // unmerged parents of current node
 }
 
+impl<TreeId, LeafId, Leaf> MergeNode<TreeId, LeafId, Leaf> {
+    /// Number of parent trees that merging this node fetches.
+    fn tree_fetches(&self) -> u64 {
+        self.parents
+            .iter()
+            .filter(|parent| matches!(parent, Entry::Tree(_)))
+            .count() as u64
+    }
+}
+
 async fn merge<TreeId, LeafId, IntermediateLeafId, Leaf, Store>(
     ctx: CoreContext,
     store: Store,
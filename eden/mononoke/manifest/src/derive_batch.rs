@@ -14,17 +14,22 @@ use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Error;
 use blobstore::StoreLoadable;
+use bounded_traversal::TraversalStats;
 use cloned::cloned;
 use context::CoreContext;
 use futures::Future;
 use futures::FutureExt;
+use futures::TryStreamExt;
 use mononoke_types::ChangesetId;
 use mononoke_types::MPath;
 use mononoke_types::MPathElement;
 
+use crate::traversal::manifest_traversal_limits;
+use crate::traversal::report_traversal_stats;
+use crate::traversal::FetchSizeEstimator;
+use crate::AsyncManifest as Manifest;
 use crate::Entry;
 use crate::LeafInfo;
-use crate::Manifest;
 use crate::PathTree;
 use crate::TreeInfo;
 
@@ -69,7 +74,7 @@ where
     IntermediateLeafId: Clone + Send + From<LeafId> + 'static + Sync,
     Leaf: Send + 'static,
     TreeId: StoreLoadable<Store> + Clone + Eq + Hash + fmt::Debug + Send + Sync + 'static,
-    TreeId::Value: Manifest<TreeId = TreeId, LeafId = LeafId> + Sync,
+    TreeId::Value: Manifest<Store, TreeId = TreeId, LeafId = LeafId> + Sync,
     <TreeId as StoreLoadable<Store>>::Value: Send,
     T: Fn(TreeInfo<TreeId, IntermediateLeafId, Ctx>, ChangesetId) -> TFut + Send + Sync + 'static,
     TFut: Future<Output = Result<(Ctx, TreeId), Error>> + Send + 'caller,
@@ -128,7 +133,7 @@ where
     IntermediateLeafId: Clone + Send + From<LeafId> + 'static + Sync,
     Leaf: Send + 'static,
     TreeId: StoreLoadable<Store> + Clone + Eq + Hash + fmt::Debug + Send + Sync + 'static,
-    TreeId::Value: Manifest<TreeId = TreeId, LeafId = LeafId> + Sync,
+    TreeId::Value: Manifest<Store, TreeId = TreeId, LeafId = LeafId> + Sync,
     <TreeId as StoreLoadable<Store>>::Value: Send,
     T: Fn(TreeInfo<TreeId, IntermediateLeafId, Ctx>, ChangesetId) -> TFut + Send + Sync + 'static,
     TFut: Future<Output = Result<(Ctx, TreeId), Error>> + Send + 'caller,
@@ -191,8 +196,16 @@ where
         }
 
         let stack_of_commits = Arc::new(stack_of_commits);
-        let (_, entry_stack) = bounded_traversal::bounded_traversal(
-            256,
+        let stats = Arc::new(TraversalStats::default());
+        let estimator = Arc::new(FetchSizeEstimator::default());
+        let limits = manifest_traversal_limits(
+            &ctx,
+            stats.clone(),
+            estimator.clone(),
+            |state: &UnfoldState<_, _, _>| u64::from(matches!(state.parent, Some(Entry::Tree(_)))),
+        );
+        let result = bounded_traversal::bounded_traversal_limited(
+            limits,
             UnfoldState {
                 path: None,
                 name: None,
@@ -210,8 +223,9 @@ where
                         path_tree,
                     },
                 | {
-                    cloned!(ctx, store);
-                    async move {
+                    let tree_fetches = u64::from(matches!(parent, Some(Entry::Tree(_))));
+                    cloned!(store);
+                    let unfold = move |ctx: CoreContext| async move {
                         let PathTree {
                             value: changes,
                             subentries,
@@ -256,7 +270,12 @@ where
                             let mut deps: BTreeMap<MPathElement, _> = Default::default();
                             if let Some(Entry::Tree(tree_id)) = &parent {
                                 let mf = tree_id.load(&ctx, &store).await?;
-                                for (name, entry) in mf.list() {
+                                let mf_entries = mf
+                                    .list(&ctx, &store)
+                                    .await?
+                                    .try_collect::<Vec<_>>()
+                                    .await?;
+                                for (name, entry) in mf_entries {
                                     let subentry =
                                         deps.entry(name.clone()).or_insert_with(|| UnfoldState {
                                             path: Some(MPath::join_opt_element(path.as_ref(), &name)),
@@ -292,8 +311,8 @@ where
                             // No changes, no subentries - just reuse the entry
                             Ok((FoldState::Reuse(path, name, parent.map(convert_to_intermediate_entry)), vec![]))
                         }
-                    }
-                    .boxed()
+                    };
+                    estimator.measure(&ctx, tree_fetches, unfold).boxed()
                 }
             },
             // Fold - actually create the entries
@@ -357,7 +376,9 @@ where
                 }
             },
         )
-        .await?;
+        .await;
+        report_traversal_stats(&ctx, &stats);
+        let (_, entry_stack) = result?;
 
         let derived: BTreeMap<_, _> = entry_stack
             .values
@@ -438,11 +459,13 @@ where
                         // derive_manifest()
                         let parent_mf = tree_id.load(ctx, &store).await?;
                         let subentries = parent_mf
-                            .list()
-                            .map(|(path, entry)| {
+                            .list(ctx, &store)
+                            .await?
+                            .map_ok(|(path, entry)| {
                                 (path, (None, convert_to_intermediate_entry(entry)))
                             })
-                            .collect();
+                            .try_collect()
+                            .await?;
                         let (upload_ctx, tree_id) = create_tree(
                             TreeInfo {
                                 path: Some(path.clone()),
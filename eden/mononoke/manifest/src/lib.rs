@@ -18,10 +18,13 @@ pub use crate::derive::TreeInfo;
 pub use crate::implicit_deletes::get_implicit_deletes;
 pub use crate::ops::find_intersection_of_diffs;
 pub use crate::ops::find_intersection_of_diffs_and_parents;
+pub use crate::ops::find_intersection_of_diffs_and_parents_with_filter;
+pub use crate::ops::find_intersection_of_diffs_with_filter;
 pub use crate::ops::Diff;
 pub use crate::ops::ManifestOps;
 pub use crate::ordered_ops::After;
 pub use crate::ordered_ops::ManifestOrderedOps;
+pub use crate::select::DiffFilter;
 pub use crate::select::PathOrPrefix;
 pub use crate::types::AsyncManifest;
 pub use crate::types::AsyncOrderedManifest;
@@ -38,6 +41,7 @@ mod implicit_deletes;
 mod ops;
 mod ordered_ops;
 mod select;
+mod traversal;
 mod types;
 
 #[cfg(test)]
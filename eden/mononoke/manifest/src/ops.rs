@@ -26,6 +26,7 @@ use mononoke_types::MPath;
 
 use crate::select::select_path_tree;
 use crate::AsyncManifest as Manifest;
+use crate::DiffFilter;
 use crate::Entry;
 use crate::PathOrPrefix;
 use crate::PathTree;
@@ -38,6 +39,16 @@ pub enum Diff<Entry> {
     Changed(Option<MPath>, Entry, Entry),
 }
 
+impl<Entry> Diff<Entry> {
+    pub fn path(&self) -> Option<&MPath> {
+        match self {
+            Diff::Added(path, ..) | Diff::Removed(path, ..) | Diff::Changed(path, ..) => {
+                path.as_ref()
+            }
+        }
+    }
+}
+
 pub trait ManifestOps<Store>
 where
     Store: Sync + Send + Clone + 'static,
@@ -274,6 +285,33 @@ where
         self.filtered_diff(ctx, store.clone(), other, store, Some, |_| true)
     }
 
+    /// Returns differences between two manifests for the paths that match
+    /// `filter`.  Subtrees that can't contain matching paths are not
+    /// loaded.
+    fn diff_with_filter(
+        &self,
+        ctx: CoreContext,
+        store: Store,
+        other: Self,
+        filter: DiffFilter,
+    ) -> BoxStream<
+        'static,
+        Result<
+            Diff<Entry<Self, <<Self as StoreLoadable<Store>>::Value as Manifest<Store>>::LeafId>>,
+            Error,
+        >,
+    > {
+        let output_filter = filter.clone();
+        self.filtered_diff(
+            ctx,
+            store.clone(),
+            other,
+            store,
+            move |diff| output_filter.matches(diff.path()).then_some(diff),
+            move |diff| filter.should_recurse(diff.path()),
+        )
+    }
+
     /// Do a diff, but with knobs to filter_map output and prune some subtrees.
     /// `output_filter` let's us configure what will be returned from filtered_diff. it accepts
     /// every diff entry and returns Option<Out>, so it acts similar to filter_map() function
@@ -482,7 +520,27 @@ where
         Manifest<Store, TreeId = TreeId, LeafId = LeafId> + Send + Sync,
     LeafId: Clone + Send + Eq + Unpin + 'static,
 {
-    find_intersection_of_diffs_and_parents(ctx, store, mf_id, diff_against)
+    find_intersection_of_diffs_with_filter(ctx, store, mf_id, diff_against, DiffFilter::new())
+}
+
+/// Like `find_intersection_of_diffs`, but only returns entries whose paths
+/// match `filter`, without loading the parts of the manifests that can't
+/// contain them.
+pub fn find_intersection_of_diffs_with_filter<TreeId, LeafId, Store>(
+    ctx: CoreContext,
+    store: Store,
+    mf_id: TreeId,
+    diff_against: Vec<TreeId>,
+    filter: DiffFilter,
+) -> impl Stream<Item = Result<(Option<MPath>, Entry<TreeId, LeafId>), Error>> + 'static
+where
+    Store: Sync + Send + Clone + 'static,
+    TreeId: StoreLoadable<Store> + Clone + Send + Sync + Eq + Unpin + 'static,
+    <TreeId as StoreLoadable<Store>>::Value:
+        Manifest<Store, TreeId = TreeId, LeafId = LeafId> + Send + Sync,
+    LeafId: Clone + Send + Eq + Unpin + 'static,
+{
+    find_intersection_of_diffs_and_parents_with_filter(ctx, store, mf_id, diff_against, filter)
         .map_ok(|(path, entry, _)| (path, entry))
 }
 
@@ -503,6 +561,40 @@ pub fn find_intersection_of_diffs_and_parents<TreeId, LeafId, Store>(
         Error,
     >,
 > + 'static
+where
+    Store: Sync + Send + Clone + 'static,
+    TreeId: StoreLoadable<Store> + Clone + Send + Sync + Eq + Unpin + 'static,
+    <TreeId as StoreLoadable<Store>>::Value:
+        Manifest<Store, TreeId = TreeId, LeafId = LeafId> + Send + Sync,
+    LeafId: Clone + Send + Eq + Unpin + 'static,
+{
+    find_intersection_of_diffs_and_parents_with_filter(
+        ctx,
+        store,
+        mf_id,
+        diff_against,
+        DiffFilter::new(),
+    )
+}
+
+/// Like `find_intersection_of_diffs_and_parents`, but only returns entries
+/// whose paths match `filter`.
+pub fn find_intersection_of_diffs_and_parents_with_filter<TreeId, LeafId, Store>(
+    ctx: CoreContext,
+    store: Store,
+    mf_id: TreeId,
+    diff_against: Vec<TreeId>,
+    filter: DiffFilter,
+) -> impl Stream<
+    Item = Result<
+        (
+            Option<MPath>,
+            Entry<TreeId, LeafId>,
+            Vec<Entry<TreeId, LeafId>>,
+        ),
+        Error,
+    >,
+> + 'static
 where
     Store: Sync + Send + Clone + 'static,
     TreeId: StoreLoadable<Store> + Clone + Send + Sync + Eq + Unpin + 'static,
@@ -513,7 +605,8 @@ where
     match diff_against.get(0).cloned() {
         Some(parent) => async move {
             let mut new_entries = Vec::new();
-            let mut parent_diff = parent.diff(ctx.clone(), store.clone(), mf_id);
+            let mut parent_diff =
+                parent.diff_with_filter(ctx.clone(), store.clone(), mf_id, filter);
             while let Some(diff_entry) = parent_diff.try_next().await? {
                 match diff_entry {
                     Diff::Added(path, entry) => new_entries.push((path, entry, vec![])),
@@ -559,10 +652,22 @@ where
         }
         .try_flatten_stream()
         .right_stream(),
-        None => mf_id
-            .list_all_entries(ctx, store)
-            .map_ok(|(path, entry)| (path, entry, vec![]))
-            .left_stream(),
+        None => {
+            let entries = if filter.includes().is_empty() {
+                mf_id.list_all_entries(ctx, store)
+            } else {
+                let prefixes = filter
+                    .includes()
+                    .iter()
+                    .map(|prefix| PathOrPrefix::Prefix(Some(prefix.clone())))
+                    .collect::<Vec<_>>();
+                mf_id.find_entries(ctx, store, prefixes)
+            };
+            entries
+                .try_filter(move |(path, _)| future::ready(filter.matches(path.as_ref())))
+                .map_ok(|(path, entry)| (path, entry, vec![]))
+                .left_stream()
+        }
     }
 }
 
@@ -69,3 +69,84 @@ where
         })
         .collect()
 }
+
+/// Restricts a manifest diff to part of the tree.
+///
+/// A path matches the filter if it is under one of the included prefixes (or
+/// there are none), is not under any of the excluded prefixes, and has at
+/// most `max_depth` components.  Subtrees that can't contain any matching
+/// paths are not traversed.
+#[derive(Clone, Debug, Default)]
+pub struct DiffFilter {
+    includes: Vec<MPath>,
+    excludes: Vec<MPath>,
+    max_depth: Option<usize>,
+}
+
+impl DiffFilter {
+    /// A filter that matches every path.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match paths under `prefix`.  If several prefixes are included,
+    /// paths under any of them match.
+    pub fn include(mut self, prefix: MPath) -> Self {
+        self.includes.push(prefix);
+        self
+    }
+
+    /// Don't match paths under `prefix`, even if they are under an included
+    /// prefix.
+    pub fn exclude(mut self, prefix: MPath) -> Self {
+        self.excludes.push(prefix);
+        self
+    }
+
+    /// Only match paths with at most `max_depth` components.  The root has
+    /// no components, so a depth of 1 matches the entries of the root tree.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    pub(crate) fn includes(&self) -> &[MPath] {
+        &self.includes
+    }
+
+    fn depth(path: Option<&MPath>) -> usize {
+        path.map_or(0, MPath::num_components)
+    }
+
+    fn is_excluded(&self, path: Option<&MPath>) -> bool {
+        self.excludes
+            .iter()
+            .any(|prefix| prefix.is_prefix_of(MPath::iter_opt(path)))
+    }
+
+    /// Whether an entry at `path` should be returned.
+    pub fn matches(&self, path: Option<&MPath>) -> bool {
+        self.max_depth
+            .map_or(true, |max_depth| Self::depth(path) <= max_depth)
+            && (self.includes.is_empty()
+                || self
+                    .includes
+                    .iter()
+                    .any(|prefix| prefix.is_prefix_of(MPath::iter_opt(path))))
+            && !self.is_excluded(path)
+    }
+
+    /// Whether the tree at `path` may contain entries that match.  Trees
+    /// whose children are too deep are still loaded if the tree itself
+    /// matches, as a diff only reports a tree while listing it.
+    pub fn should_recurse(&self, path: Option<&MPath>) -> bool {
+        self.max_depth
+            .map_or(true, |max_depth| Self::depth(path) <= max_depth)
+            && (self.includes.is_empty()
+                || self.includes.iter().any(|prefix| {
+                    prefix.is_prefix_of(MPath::iter_opt(path))
+                        || MPath::is_prefix_of_opt(path, prefix)
+                }))
+            && !self.is_excluded(path)
+    }
+}
@@ -48,7 +48,9 @@ pub(crate) use crate::derive_batch::derive_manifests_for_simple_stack_of_commits
 pub(crate) use crate::derive_batch::ManifestChanges;
 pub(crate) use crate::derive_manifest;
 pub(crate) use crate::find_intersection_of_diffs;
+pub(crate) use crate::find_intersection_of_diffs_with_filter;
 pub(crate) use crate::Diff;
+pub(crate) use crate::DiffFilter;
 pub(crate) use crate::Entry;
 pub(crate) use crate::Manifest;
 pub(crate) use crate::ManifestOps;
@@ -1522,6 +1524,61 @@ async fn test_find_intersection_of_diffs(fb: FacebookInit) -> Result<()> {
         ])?,
     );
 
+    // Only look at part of the tree
+    let filter = DiffFilter::new()
+        .include(MPath::new("dir")?)
+        .exclude(MPath::new("dir/added_dir")?);
+    let intersection: Vec<_> = find_intersection_of_diffs_with_filter(
+        ctx.clone(),
+        blobstore.clone(),
+        mf1,
+        vec![mf0],
+        filter,
+    )
+    .try_collect()
+    .await?;
+
+    let intersection: BTreeSet<_> = intersection.into_iter().map(|(path, _)| path).collect();
+
+    assert_eq!(intersection, make_paths(&["dir", "dir/changed_file"])?);
+
+    let filter = DiffFilter::new().max_depth(1);
+    let intersection: Vec<_> = find_intersection_of_diffs_with_filter(
+        ctx.clone(),
+        blobstore.clone(),
+        mf1,
+        vec![mf0],
+        filter,
+    )
+    .try_collect()
+    .await?;
+
+    let intersection: BTreeSet<_> = intersection.into_iter().map(|(path, _)| path).collect();
+
+    assert_eq!(
+        intersection,
+        make_paths(&["/", "added_file", "dir", "dir_file_conflict"])?,
+    );
+
+    // Without parents, only the entries under the included prefixes are listed
+    let filter = DiffFilter::new().include(MPath::new("dir")?).max_depth(2);
+    let intersection: Vec<_> = find_intersection_of_diffs_with_filter(
+        ctx.clone(),
+        blobstore.clone(),
+        mf1,
+        vec![],
+        filter,
+    )
+    .try_collect()
+    .await?;
+
+    let intersection: BTreeSet<_> = intersection.into_iter().map(|(path, _)| path).collect();
+
+    assert_eq!(
+        intersection,
+        make_paths(&["dir", "dir/added_dir", "dir/changed_file", "dir/same_dir"])?,
+    );
+
     Ok(())
 }
 
@@ -0,0 +1,112 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::future::Future;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use bounded_traversal::TraversalLimits;
+use bounded_traversal::TraversalStats;
+use context::CoreContext;
+use context::PerfCounterType;
+
+/// Maximum number of unfold and fold jobs a manifest traversal runs at once.
+const SCHEDULED_MAX: usize = 256;
+
+/// Maximum number of bytes of tree blobs a manifest traversal fetches at
+/// once.  Merge nodes fetch a tree per parent, and trees vary widely in size,
+/// so this stops wide merges of large directories from fetching far more
+/// than the traversal can hold in memory.
+const FETCH_BYTES_MAX: u64 = 64 * 1024 * 1024;
+
+/// Assumed size of a tree blob until the traversal has fetched some.
+const DEFAULT_TREE_BYTES: u64 = 16 * 1024;
+
+/// Estimates how many bytes an unfold will fetch from the sizes of the tree
+/// blobs fetched by the earlier unfolds of the same traversal.
+#[derive(Default)]
+pub(crate) struct FetchSizeEstimator {
+    trees: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl FetchSizeEstimator {
+    /// Estimated number of bytes fetched by an unfold that fetches
+    /// `tree_fetches` trees.
+    fn estimate(&self, tree_fetches: u64) -> u64 {
+        let trees = self.trees.load(Ordering::Relaxed);
+        let bytes_per_tree = if trees == 0 {
+            DEFAULT_TREE_BYTES
+        } else {
+            self.bytes.load(Ordering::Relaxed) / trees
+        };
+        tree_fetches.saturating_mul(bytes_per_tree)
+    }
+
+    /// Run an unfold that fetches `tree_fetches` trees, recording the number
+    /// of bytes it fetched.  The unfold is given a context whose perf
+    /// counters are forked from those of `ctx`, so its blob gets are still
+    /// counted against `ctx`.
+    pub(crate) fn measure<Fut>(
+        self: &Arc<Self>,
+        ctx: &CoreContext,
+        tree_fetches: u64,
+        unfold: impl FnOnce(CoreContext) -> Fut,
+    ) -> impl Future<Output = Fut::Output>
+    where
+        Fut: Future,
+    {
+        let estimator = self.clone();
+        let mut unfold_ctx = ctx.clone();
+        let counters = unfold_ctx.fork_perf_counters();
+        let fut = unfold(unfold_ctx);
+        async move {
+            let result = fut.await;
+            if tree_fetches > 0 {
+                let bytes = counters.get_counter(PerfCounterType::BlobGetsTotalSize);
+                estimator.trees.fetch_add(tree_fetches, Ordering::Relaxed);
+                estimator
+                    .bytes
+                    .fetch_add(bytes.max(0) as u64, Ordering::Relaxed);
+            }
+            result
+        }
+    }
+}
+
+/// Limits for a traversal that walks manifests on behalf of `ctx`.  Unfolds
+/// are weighted by the number of bytes `estimator` expects them to fetch for
+/// the number of trees given by `tree_fetches`, and the traversal is
+/// cancelled once the deadline of `ctx` passes or its client disconnects.
+pub(crate) fn manifest_traversal_limits<'a, In>(
+    ctx: &CoreContext,
+    stats: Arc<TraversalStats>,
+    estimator: Arc<FetchSizeEstimator>,
+    tree_fetches: impl Fn(&In) -> u64 + Send + 'a,
+) -> TraversalLimits<'a, In> {
+    let ctx = ctx.clone();
+    TraversalLimits::new(SCHEDULED_MAX)
+        .with_weight_limit(FETCH_BYTES_MAX, move |node: &In| {
+            estimator.estimate(tree_fetches(node))
+        })
+        .with_cancellation(move || ctx.is_abandoned())
+        .with_stats(stats)
+}
+
+/// Add the statistics of a finished manifest traversal to the perf counters
+/// of `ctx`.
+pub(crate) fn report_traversal_stats(ctx: &CoreContext, stats: &TraversalStats) {
+    ctx.perf_counters().add_to_counter(
+        PerfCounterType::BoundedTraversalUnfolds,
+        stats.unfolds() as i64,
+    );
+    ctx.perf_counters().set_max_counter(
+        PerfCounterType::BoundedTraversalMaxWeight,
+        stats.max_weight() as i64,
+    );
+}
@@ -168,27 +168,57 @@ impl<Store: Blobstore> AsyncManifest<Store> for BasenameSuffixSkeletonManifest {
     }
 }
 
-impl Manifest for ManifestUnode {
+#[async_trait]
+impl<Store: Blobstore> AsyncManifest<Store> for ManifestUnode {
     type TreeId = ManifestUnodeId;
     type LeafId = FileUnodeId;
 
-    fn lookup(&self, name: &MPathElement) -> Option<Entry<Self::TreeId, Self::LeafId>> {
-        self.lookup(name).map(convert_unode)
+    async fn list(
+        &self,
+        ctx: &CoreContext,
+        blobstore: &Store,
+    ) -> Result<BoxStream<'async_trait, Result<(MPathElement, Entry<Self::TreeId, Self::LeafId>)>>>
+    {
+        anyhow::Ok(
+            self.clone()
+                .into_subentries(ctx, blobstore)
+                .map_ok(|(path, entry)| (path, convert_unode(entry)))
+                .boxed(),
+        )
     }
 
-    fn list(&self) -> Box<dyn Iterator<Item = (MPathElement, Entry<Self::TreeId, Self::LeafId>)>> {
-        let v: Vec<_> = self
-            .list()
-            .map(|(basename, entry)| (basename.clone(), convert_unode(entry)))
-            .collect();
-        Box::new(v.into_iter())
+    async fn list_prefix(
+        &self,
+        ctx: &CoreContext,
+        blobstore: &Store,
+        prefix: &[u8],
+    ) -> Result<BoxStream<'async_trait, Result<(MPathElement, Entry<Self::TreeId, Self::LeafId>)>>>
+    {
+        anyhow::Ok(
+            self.clone()
+                .into_prefix_subentries(ctx, blobstore, prefix)
+                .map_ok(|(path, entry)| (path, convert_unode(entry)))
+                .boxed(),
+        )
+    }
+
+    async fn lookup(
+        &self,
+        ctx: &CoreContext,
+        blobstore: &Store,
+        name: &MPathElement,
+    ) -> Result<Option<Entry<Self::TreeId, Self::LeafId>>> {
+        Ok(self
+            .lookup_entry(ctx, blobstore, name)
+            .await?
+            .map(convert_unode))
     }
 }
 
-fn convert_unode(unode_entry: &UnodeEntry) -> Entry<ManifestUnodeId, FileUnodeId> {
+fn convert_unode(unode_entry: UnodeEntry) -> Entry<ManifestUnodeId, FileUnodeId> {
     match unode_entry {
-        UnodeEntry::File(file_unode_id) => Entry::Leaf(file_unode_id.clone()),
-        UnodeEntry::Directory(mf_unode_id) => Entry::Tree(mf_unode_id.clone()),
+        UnodeEntry::File(file_unode_id) => Entry::Leaf(file_unode_id),
+        UnodeEntry::Directory(mf_unode_id) => Entry::Tree(mf_unode_id),
     }
 }
 
@@ -274,6 +274,7 @@ pub trait MegarepoOp {
             file_changes: file_changes.into_iter().collect(),
             is_snapshot: false,
             git_annotated_tag: None,
+            extras: None,
         };
         let merge = bcs.freeze()?;
         save_bonsai_changesets(vec![merge.clone()], ctx.clone(), repo.blob_repo()).await?;
@@ -1242,6 +1243,7 @@ pub(crate) fn new_megarepo_automation_commit(
         file_changes,
         is_snapshot: false,
         git_annotated_tag: None,
+        extras: None,
     }
 }
 
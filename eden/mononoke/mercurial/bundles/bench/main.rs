@@ -0,0 +1,103 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Benchmarks packing large files into a wirepack, as getpack does, with
+//! the file contents shared from their blobs or copied out of them.  Either
+//! way, packing copies the contents once more into the wirepack's chunks.
+
+use allocation_tracing::trace_allocations;
+use anyhow::Result;
+use bytes::Bytes;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::Throughput;
+use futures_old::stream;
+use futures_old::Future;
+use futures_old::Stream;
+use mercurial_bundles::wirepack::packer::WirePackPacker;
+use mercurial_bundles::wirepack::DataEntry;
+use mercurial_bundles::wirepack::Kind;
+use mercurial_bundles::wirepack::Part;
+use mercurial_types::Delta;
+use mercurial_types::HgNodeHash;
+use mercurial_types::RepoPath;
+use mercurial_types::NULL_HASH;
+
+const FILE_COUNT: usize = 10;
+const FILE_SIZES: &[usize] = &[1024 * 1024, 16 * 1024 * 1024];
+
+fn parts(contents: &[Bytes], copy: bool) -> Result<Vec<Part>> {
+    let mut parts = Vec::new();
+    for (i, content) in contents.iter().enumerate() {
+        let path = RepoPath::file(format!("dir/file{}", i).as_str())?;
+        let delta = if copy {
+            Delta::new_fulltext(content.to_vec())
+        } else {
+            Delta::new_fulltext(content.clone())
+        };
+        parts.push(Part::HistoryMeta {
+            path: path.clone(),
+            entry_count: 0,
+        });
+        parts.push(Part::DataMeta {
+            path,
+            entry_count: 1,
+        });
+        parts.push(Part::Data(DataEntry {
+            node: HgNodeHash::from_bytes(&[i as u8 + 1; 20])?,
+            delta_base: NULL_HASH,
+            delta,
+            metadata: None,
+        }));
+    }
+    parts.push(Part::End);
+    Ok(parts)
+}
+
+fn pack(contents: &[Bytes], copy: bool) -> Result<usize> {
+    let parts = parts(contents, copy)?;
+    let chunks = WirePackPacker::new(stream::iter_ok(parts), Kind::File)
+        .collect()
+        .wait()?;
+    Ok(chunks.len())
+}
+
+fn main() -> Result<()> {
+    let mut criterion = Criterion::default().sample_size(10);
+
+    for &size in FILE_SIZES {
+        let contents: Vec<Bytes> = (0..FILE_COUNT)
+            .map(|i| Bytes::from(vec![i as u8; size]))
+            .collect();
+
+        // Allocations are counted separately from the timings, as they do
+        // not vary between runs.
+        for (name, copy) in [("shared", false), ("copied", true)] {
+            let (res, stats) = trace_allocations(|| pack(&contents, copy));
+            res?;
+            println!(
+                "{} files of {} bytes, {}: {} bytes allocated",
+                FILE_COUNT,
+                size,
+                name,
+                stats.allocated()
+            );
+        }
+
+        let mut group = criterion.benchmark_group(format!("pack_files_{}", size));
+        group.throughput(Throughput::Bytes((FILE_COUNT * size) as u64));
+        for (name, copy) in [("shared", false), ("copied", true)] {
+            group.bench_with_input(BenchmarkId::from_parameter(name), &copy, |b, &copy| {
+                b.iter(|| pack(&contents, copy).expect("packing failed"))
+            });
+        }
+        group.finish();
+    }
+
+    criterion.final_summary();
+    Ok(())
+}
@@ -51,8 +51,8 @@ pub fn decode_delta(buf: BytesMut) -> Result<Delta> {
         frags.push(Fragment {
             start: start as usize,
             end: end as usize,
-            // TODO: avoid copies here by switching this to Bytes
-            content: buf.split_to(new_len as usize).to_vec(),
+            // TODO: avoid this copy by decoding from a bytes 1.x buffer
+            content: buf.split_to(new_len as usize).to_vec().into(),
         });
 
         remaining -= delta_len;
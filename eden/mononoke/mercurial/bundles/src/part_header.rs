@@ -68,12 +68,13 @@ pub enum PartHeaderType {
     /// Used in communicating phases between Mononoke and clients
     /// Pushkey / Listkeys are not used to communicate phases
     PhaseHeads,
+    /// Contains text that the client shows to the user
+    Output,
     // RemoteChangegroup,       // We don't wish to support this functionality
     // CheckBookmarks,          // TODO Do we want to support this?
     // CheckHeads,              // TODO Do we want to support this?
     // CheckUpdatedHeads,       // TODO Do we want to support this?
     // CheckPhases,             // TODO Do we want to support this?
-    // Output,                  // TODO Do we want to support this?
     // ErrorAbort,              // TODO Do we want to support this?
     // ErrorPushkey,            // TODO Do we want to support this?
     // ErrorUnsupportedContent, // TODO Do we want to support this?
@@ -107,6 +108,7 @@ impl PartHeaderType {
             "pushvars" => Ok(Pushvars),
             "phase-heads" => Ok(PhaseHeads),
             "obsmarkers" => Ok(Obsmarkers),
+            "output" => Ok(Output),
             bad => bail!("unknown header type {}", bad),
         }
     }
@@ -131,6 +133,7 @@ impl PartHeaderType {
             ReplyPushkey => "reply:pushkey",
             PhaseHeads => "phase-heads",
             Obsmarkers => "obsmarkers",
+            Output => "output",
         }
     }
 }
@@ -176,7 +176,7 @@ where
         // Linknode is the same as node
         let linknode = node;
         let text = blobnode.as_blob().as_inner().clone();
-        let delta = Delta::new_fulltext(text.to_vec());
+        let delta = Delta::new_fulltext(text);
 
         let flags = if version == CgVersion::Cg3Version {
             Some(RevFlags::REVIDX_DEFAULT_FLAGS)
@@ -215,7 +215,7 @@ where
                 // Linknode is the same as node
                 let linknode = hg_cs_id.into_nodehash();
                 let text = blobnode.as_blob().as_inner().clone();
-                let delta = Delta::new_fulltext(text.to_vec());
+                let delta = Delta::new_fulltext(text);
 
                 let deltachunk = CgDeltaChunk {
                     node: node.into_nodehash(),
@@ -442,6 +442,13 @@ pub fn replypushkey_part(res: bool, in_reply_to: PartId) -> Result<PartEncodeBui
     Ok(builder)
 }
 
+/// Text for the client to show to the user, one line per line of `text`.
+pub fn output_part(text: String) -> Result<PartEncodeBuilder> {
+    let mut builder = PartEncodeBuilder::advisory(PartHeaderType::Output)?;
+    builder.set_data_bytes(text)?;
+    Ok(builder)
+}
+
 pub fn obsmarkers_part<S>(
     pairs: S,
     time: DateTime,
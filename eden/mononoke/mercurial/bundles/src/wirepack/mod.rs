@@ -295,6 +295,7 @@ impl DataEntry {
         let delta = buf.split_to(delta_len);
 
         let delta = if delta_base == NULL_HASH {
+            // TODO: avoid this copy by decoding from a bytes 1.x buffer
             Delta::new_fulltext(delta.to_vec())
         } else {
             delta::decode_delta(delta)?
@@ -325,6 +326,9 @@ impl DataEntry {
 
     // This would ideally be generic over any BufMut, but that won't be very useful until
     // https://github.com/carllerche/bytes/issues/170 is fixed.
+    //
+    // The delta's content is copied into `buf`: chunks are still bytes 0.4 buffers, so they
+    // can't share the bytes 1.x content of the delta.
     pub(crate) fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
         self.verify()?;
         buf.put_slice(self.node.as_ref());
@@ -479,10 +483,10 @@ mod test {
     fn test_data_verify_basic() {
         #[rustfmt::skip]
         let tests = vec![
-            (NULL_HASH, vec![Fragment { start: 0, end: 0, content: vec![b'a'] }], true),
-            (NULL_HASH, vec![Fragment { start: 0, end: 5, content: vec![b'b'] }], false),
-            (AS_HASH, vec![Fragment { start: 0, end: 0, content: vec![b'c'] }], true),
-            (AS_HASH, vec![Fragment { start: 0, end: 5, content: vec![b'd'] }], true),
+            (NULL_HASH, vec![Fragment { start: 0, end: 0, content: "a".into() }], true),
+            (NULL_HASH, vec![Fragment { start: 0, end: 5, content: "b".into() }], false),
+            (AS_HASH, vec![Fragment { start: 0, end: 0, content: "c".into() }], true),
+            (AS_HASH, vec![Fragment { start: 0, end: 5, content: "d".into() }], true),
         ];
 
         for (delta_base, frags, is_valid) in tests.into_iter() {
@@ -154,6 +154,7 @@ impl ChunkBuilder {
         Ok(self)
     }
 
+    /// Encode a data entry.  This copies the entry's content into the chunk.
     #[inline]
     fn encode_data(&mut self, data_entry: &DataEntry) -> Result<&mut Self> {
         data_entry.encode(&mut self.inner)?;
@@ -19,6 +19,7 @@ use blobstore::Loadable;
 use blobstore::LoadableError;
 use bytes::Bytes;
 use context::CoreContext;
+use mononoke_types::ChangesetExtras;
 use mononoke_types::DateTime;
 
 use super::revlog::serialize_extras;
@@ -33,6 +34,8 @@ use crate::HgParents;
 use crate::MPath;
 
 const STEP_PARENTS_METADATA_KEY: &str = "stepparents";
+const SIGNED_OFF_BY_METADATA_KEY: &str = "signed-off-by";
+const COMMITTER_IDENTITY_METADATA_KEY: &str = "committer_identity";
 
 pub struct ChangesetMetadata {
     pub user: String,
@@ -82,6 +85,25 @@ impl ChangesetMetadata {
 
         Ok(())
     }
+
+    /// Record the typed changeset extras as hg extras.  Extras that are
+    /// already set (e.g. because the changeset was pushed from hg with
+    /// them) take precedence.
+    ///
+    /// The branch hint is not recorded: hg would treat it as a named
+    /// branch, so it is only carried in the bonsai changeset.
+    pub fn record_extras(&mut self, extras: &ChangesetExtras) {
+        if !extras.signed_off_by.is_empty() {
+            self.extra
+                .entry(SIGNED_OFF_BY_METADATA_KEY.into())
+                .or_insert_with(|| extras.signed_off_by.join("\n").into_bytes());
+        }
+        if let Some(identity) = &extras.committer_identity {
+            self.extra
+                .entry(COMMITTER_IDENTITY_METADATA_KEY.into())
+                .or_insert_with(|| identity.as_bytes().to_vec());
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -8,11 +8,13 @@
 use std::collections::BTreeMap;
 
 use bytes::Bytes;
+use mononoke_types::ChangesetExtras;
 use mononoke_types::DateTime;
 use quickcheck::quickcheck;
 use quickcheck::QuickCheck;
 use quickcheck::TestResult;
 
+use super::blob::ChangesetMetadata;
 use super::revlog::escape;
 use super::revlog::serialize_extras;
 use super::revlog::unescape;
@@ -154,3 +156,28 @@ fn extras_roundtrip() {
         .tests(50) // more takes too much time
         .quickcheck(extras_roundtrip_prop as fn(BTreeMap<Vec<u8>, Vec<u8>>) -> TestResult);
 }
+
+#[test]
+fn test_record_extras() {
+    let mut metadata = ChangesetMetadata {
+        user: "author".to_string(),
+        time: DateTime::from_timestamp(0, 0).expect("valid timestamp"),
+        extra: BTreeMap::new(),
+        message: "message".to_string(),
+    };
+    metadata
+        .extra
+        .insert(b"signed-off-by".to_vec(), b"from-hg".to_vec());
+    metadata.record_extras(&ChangesetExtras {
+        branch: Some("release".to_string()),
+        signed_off_by: vec!["alice".to_string(), "bob".to_string()],
+        committer_identity: Some("USER:alice".to_string()),
+    });
+
+    assert!(!metadata.extra.contains_key(&b"branch"[..]));
+    assert_eq!(metadata.extra[&b"signed-off-by"[..]], b"from-hg".to_vec());
+    assert_eq!(
+        metadata.extra[&b"committer_identity"[..]],
+        b"USER:alice".to_vec()
+    );
+}
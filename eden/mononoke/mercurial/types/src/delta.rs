@@ -12,6 +12,7 @@ use anyhow::ensure;
 use anyhow::format_err;
 use anyhow::Context;
 use anyhow::Result;
+use bytes::Bytes;
 use quickcheck::Arbitrary;
 use quickcheck::Gen;
 use rand_distr::Distribution;
@@ -35,8 +36,9 @@ impl Delta {
         Ok(Delta { frags })
     }
 
-    /// Construct a new Delta object given a fulltext (no delta).
-    pub fn new_fulltext<T: Into<Vec<u8>>>(text: T) -> Self {
+    /// Construct a new Delta object given a fulltext (no delta).  Passing
+    /// the fulltext as `Bytes` avoids copying it.
+    pub fn new_fulltext<T: Into<Bytes>>(text: T) -> Self {
         Self {
             frags: vec![Fragment {
                 start: 0,
@@ -59,7 +61,7 @@ impl Delta {
     /// in the beginning appears identical to a fulltext at this layer.
     pub fn maybe_fulltext(&self) -> Option<&[u8]> {
         if self.frags.len() == 1 && self.frags[0].start == 0 && self.frags[0].end == 0 {
-            Some(self.frags[0].content.as_slice())
+            Some(self.frags[0].content.as_ref())
         } else {
             None
         }
@@ -103,7 +105,7 @@ impl Arbitrary for Delta {
                 Fragment {
                     start,
                     end,
-                    content: arbitrary_frag_content(g),
+                    content: arbitrary_frag_content(g).into(),
                 }
             })
             .collect();
@@ -128,7 +130,7 @@ impl Arbitrary for Delta {
 pub struct Fragment {
     pub start: usize,
     pub end: usize,
-    pub content: Vec<u8>,
+    pub content: Bytes,
 }
 
 impl Fragment {
@@ -171,13 +173,13 @@ impl Arbitrary for Fragment {
         Fragment {
             start,
             end,
-            content: arbitrary_frag_content(g),
+            content: arbitrary_frag_content(g).into(),
         }
     }
 
     fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
         Box::new(
-            (self.start, self.end, self.content.clone())
+            (self.start, self.end, self.content.to_vec())
                 .shrink()
                 .filter(|&(start, end, ref _content)| {
                     // shrink could produce bad values
@@ -186,7 +188,7 @@ impl Arbitrary for Fragment {
                 .map(|(start, end, content)| Fragment {
                     start,
                     end,
-                    content,
+                    content: content.into(),
                 }),
         )
     }
@@ -320,7 +322,7 @@ pub mod compat {
                 .map(|delta| Fragment {
                     start: delta.start,
                     end: delta.end,
-                    content: delta.content,
+                    content: delta.content.into(),
                 })
                 .collect(),
         }
@@ -345,16 +347,16 @@ mod tests {
     fn test_delta_new() {
         #[rustfmt::skip]
         let test_cases = vec![
-            (vec![Fragment { start: 0, end: 0, content: vec![] }], true),
-            (vec![Fragment { start: 0, end: 5, content: vec![] }], true),
-            (vec![Fragment { start: 0, end: 5, content: vec![] },
-                  Fragment { start: 5, end: 8, content: vec![] }], true),
-            (vec![Fragment { start: 0, end: 5, content: vec![] },
-                  Fragment { start: 6, end: 9, content: vec![] }], true),
-            (vec![Fragment { start: 0, end: 5, content: vec![] },
-                  Fragment { start: 6, end: 5, content: vec![] }], false),
-            (vec![Fragment { start: 0, end: 5, content: vec![] },
-                  Fragment { start: 4, end: 8, content: vec![] }], false),
+            (vec![Fragment { start: 0, end: 0, content: Bytes::new() }], true),
+            (vec![Fragment { start: 0, end: 5, content: Bytes::new() }], true),
+            (vec![Fragment { start: 0, end: 5, content: Bytes::new() },
+                  Fragment { start: 5, end: 8, content: Bytes::new() }], true),
+            (vec![Fragment { start: 0, end: 5, content: Bytes::new() },
+                  Fragment { start: 6, end: 9, content: Bytes::new() }], true),
+            (vec![Fragment { start: 0, end: 5, content: Bytes::new() },
+                  Fragment { start: 6, end: 5, content: Bytes::new() }], false),
+            (vec![Fragment { start: 0, end: 5, content: Bytes::new() },
+                  Fragment { start: 4, end: 8, content: Bytes::new() }], false),
         ];
 
         for (frags, success) in test_cases.into_iter() {
@@ -371,12 +373,12 @@ mod tests {
     fn test_maybe_fulltext() {
         #[rustfmt::skip]
         let test_cases = vec![
-            (vec![Fragment { start: 0, end: 0, content: vec![] }], true),
-            (vec![Fragment { start: 0, end: 0, content: vec![b'a'] }], true),
-            (vec![Fragment { start: 0, end: 1, content: vec![b'b'] }], false),
-            (vec![Fragment { start: 1, end: 2, content: vec![b'c'] }], false),
-            (vec![Fragment { start: 0, end: 0, content: vec![b'd'] },
-                  Fragment { start: 1, end: 2, content: vec![b'e'] }], false),
+            (vec![Fragment { start: 0, end: 0, content: Bytes::new() }], true),
+            (vec![Fragment { start: 0, end: 0, content: "a".into() }], true),
+            (vec![Fragment { start: 0, end: 1, content: "b".into() }], false),
+            (vec![Fragment { start: 1, end: 2, content: "c".into() }], false),
+            (vec![Fragment { start: 0, end: 0, content: "d".into() },
+                  Fragment { start: 1, end: 2, content: "e".into() }], false),
         ];
 
         for (frags, maybe_fulltext) in test_cases.into_iter() {
@@ -30,7 +30,7 @@ pub fn wrap_deltas<I: IntoIterator<Item = Delta>>(
     for delta in deltas {
         let wrapped_delta = FragmentWrapperIterator::new(&delta, content_offset as i64);
         for frag in delta.fragments() {
-            data.extend_from_slice(frag.content.as_slice());
+            data.extend_from_slice(&frag.content);
             content_offset += frag.content.len();
         }
 
@@ -125,7 +125,7 @@ impl FragmentWrapperIterator {
             let frag = Fragment {
                 start: frag_wrapper.start as usize,
                 end: frag_wrapper.end as usize,
-                content: data.slice(content_start..content_end).to_vec(),
+                content: data.slice(content_start..content_end),
             };
             frags.push(frag);
         }
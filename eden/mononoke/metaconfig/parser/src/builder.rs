@@ -0,0 +1,323 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Programmatic construction of Mononoke configuration.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use repo_name::encode_repo_name;
+use repos::RawAclRegionConfig;
+use repos::RawCommitSyncConfig;
+use repos::RawCommonConfig;
+use repos::RawRepoConfig;
+use repos::RawRepoConfigs;
+use repos::RawRepoDefinition;
+use repos::RawStorageConfig;
+use serde::Serialize;
+
+use crate::config::load_configs_from_raw;
+use crate::config::RepoConfigs;
+use crate::raw::read_raw_configs_toml;
+
+/// Builds a set of repo configs, which can be parsed directly or written out
+/// as a tree of TOML files that `load_repo_configs` can read.
+#[derive(Clone, Debug, Default)]
+pub struct RepoConfigsBuilder {
+    raw: RawRepoConfigs,
+}
+
+impl RepoConfigsBuilder {
+    /// Create a builder with no repos and the default common config.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the config common to all repos.
+    pub fn with_common(mut self, common: RawCommonConfig) -> Self {
+        self.raw.common = common;
+        self
+    }
+
+    /// Add a named storage config that repos can refer to.
+    pub fn with_storage(mut self, name: impl Into<String>, storage: RawStorageConfig) -> Self {
+        self.raw.storage.insert(name.into(), storage);
+        self
+    }
+
+    /// Add a named ACL region config that repos can refer to.
+    pub fn with_acl_region_config(
+        mut self,
+        name: impl Into<String>,
+        acl_region_config: RawAclRegionConfig,
+    ) -> Self {
+        self.raw
+            .acl_region_configs
+            .insert(name.into(), acl_region_config);
+        self
+    }
+
+    /// Add a named commit sync config.
+    pub fn with_commit_sync(
+        mut self,
+        name: impl Into<String>,
+        commit_sync: RawCommitSyncConfig,
+    ) -> Self {
+        self.raw.commit_sync.insert(name.into(), commit_sync);
+        self
+    }
+
+    /// Add a repo.  Its config is stored under the repo's name, and its
+    /// definition is completed with the names of the repo and its config.
+    pub fn with_repo(
+        mut self,
+        name: impl Into<String>,
+        mut definition: RawRepoDefinition,
+        config: RawRepoConfig,
+    ) -> Self {
+        let name = name.into();
+        definition.repo_name = Some(name.clone());
+        definition.repo_config = Some(name.clone());
+        self.raw.repos.insert(name.clone(), config);
+        self.raw
+            .repo_definitions
+            .repo_definitions
+            .insert(name, definition);
+        self
+    }
+
+    /// Parse the configs, checking that they are valid.
+    pub fn build(&self) -> Result<RepoConfigs> {
+        load_configs_from_raw(self.raw.clone()).map(|(repo_configs, _)| repo_configs)
+    }
+
+    /// Serialize the configs as TOML files, keyed by their paths relative
+    /// to the root of the config tree.
+    pub fn to_toml(&self) -> Result<BTreeMap<PathBuf, String>> {
+        let common = Path::new("common");
+        let mut files = BTreeMap::new();
+        files.insert(common.join("common.toml"), to_toml(&self.raw.common)?);
+        files.insert(common.join("storage.toml"), to_toml(&self.raw.storage)?);
+        files.insert(
+            common.join("acl_regions.toml"),
+            to_toml(&self.raw.acl_region_configs)?,
+        );
+        files.insert(
+            common.join("commitsyncmap.toml"),
+            to_toml(&self.raw.commit_sync)?,
+        );
+        for (name, config) in &self.raw.repos {
+            let path = Path::new("repos")
+                .join(encode_repo_name(name))
+                .join("server.toml");
+            files.insert(path, to_toml(config)?);
+        }
+        for (name, definition) in &self.raw.repo_definitions.repo_definitions {
+            let path = Path::new("repo_definitions")
+                .join(encode_repo_name(name))
+                .join("server.toml");
+            files.insert(path, to_toml(definition)?);
+        }
+        Ok(files)
+    }
+
+    /// Write the configs as a tree of TOML files under `config_path`.
+    pub fn write_to(&self, config_path: impl AsRef<Path>) -> Result<()> {
+        let config_path = config_path.as_ref();
+        // The repo directories must exist even if there are no repos.
+        fs::create_dir_all(config_path.join("repos"))?;
+        fs::create_dir_all(config_path.join("repo_definitions"))?;
+        for (path, content) in self.to_toml()? {
+            let path = config_path.join(path);
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(&path, content)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Add a repo to the config tree at `config_path`, with a copy of the named
+/// repo config `template` as its config, and return the resulting configs.
+///
+/// If the definition has no repo id, the repo is given one more than the
+/// largest id of any repo defined in the tree, including disabled repos.
+/// The configs are checked before anything is written.
+pub fn add_repo_from_template(
+    config_path: impl AsRef<Path>,
+    name: &str,
+    mut definition: RawRepoDefinition,
+    template: &str,
+) -> Result<RepoConfigs> {
+    let config_path = config_path.as_ref();
+    if !config_path.is_dir() {
+        bail!("{} is not a config directory", config_path.display());
+    }
+    let (raw, schema_warnings) = read_raw_configs_toml(config_path)?;
+    if raw.repos.contains_key(name) || raw.repo_definitions.repo_definitions.contains_key(name) {
+        bail!("Repo {} already exists", name);
+    }
+    let definitions = &raw.repo_definitions.repo_definitions;
+    match definition.repo_id {
+        Some(repo_id) => {
+            if let Some((other, _)) = definitions
+                .iter()
+                .find(|(_, other)| other.repo_id == Some(repo_id))
+            {
+                bail!("Repo id {} is already used by repo {}", repo_id, other);
+            }
+        }
+        None => {
+            let max_id = definitions.values().filter_map(|other| other.repo_id).max();
+            definition.repo_id = Some(max_id.map_or(0, |id| id + 1));
+        }
+    }
+    let config = raw
+        .repos
+        .get(template)
+        .cloned()
+        .ok_or_else(|| anyhow!("No repo config {} to use as a template", template))?;
+    let builder = RepoConfigsBuilder { raw }.with_repo(name, definition, config);
+    let mut repo_configs = builder.build()?;
+    repo_configs.schema_warnings = schema_warnings;
+
+    // The template's file is copied, rather than its parsed config written
+    // out, so that its comments are kept.
+    let repos_dir = config_path.join("repos");
+    let repo_dir = repos_dir.join(encode_repo_name(name));
+    fs::create_dir_all(&repo_dir)?;
+    fs::copy(
+        repos_dir
+            .join(encode_repo_name(template))
+            .join("server.toml"),
+        repo_dir.join("server.toml"),
+    )?;
+    let definition_dir = config_path
+        .join("repo_definitions")
+        .join(encode_repo_name(name));
+    fs::create_dir_all(&definition_dir)?;
+    fs::write(
+        definition_dir.join("server.toml"),
+        to_toml(&builder.raw.repo_definitions.repo_definitions[name])?,
+    )?;
+    Ok(repo_configs)
+}
+
+fn to_toml(value: &impl Serialize) -> Result<String> {
+    // Going through `toml::Value` puts plain values before tables, which
+    // serializing the structs directly does not.
+    Ok(toml::to_string(&toml::Value::try_from(value)?)?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use cached_config::ConfigStore;
+    use cached_config::TestSource;
+    use repos::RawBlobstoreConfig;
+    use repos::RawBlobstoreDisabled;
+    use repos::RawDbLocal;
+    use repos::RawMetadataConfig;
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::config::load_repo_configs;
+
+    fn test_storage() -> RawStorageConfig {
+        RawStorageConfig {
+            metadata: RawMetadataConfig::local(RawDbLocal {
+                local_db_path: "/tmp/db".to_string(),
+            }),
+            blobstore: RawBlobstoreConfig::disabled(RawBlobstoreDisabled {}),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_written_configs_load() -> Result<()> {
+        let storage = test_storage();
+        let builder = RepoConfigsBuilder::new()
+            .with_storage("store", storage)
+            .with_repo(
+                "some/repo",
+                RawRepoDefinition {
+                    repo_id: Some(1),
+                    enabled: Some(true),
+                    ..Default::default()
+                },
+                RawRepoConfig {
+                    storage_config: Some("store".to_string()),
+                    ..Default::default()
+                },
+            );
+        let built = builder.build()?;
+        assert!(built.repos.contains_key("some/repo"));
+
+        let tmp_dir = TempDir::new("mononoke_test_config_builder")?;
+        builder.write_to(tmp_dir.path())?;
+        let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
+        let loaded = load_repo_configs(tmp_dir.path(), &config_store)?;
+        assert_eq!(loaded, built);
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_repo_from_template_ids() -> Result<()> {
+        let config = RawRepoConfig {
+            storage_config: Some("store".to_string()),
+            ..Default::default()
+        };
+        let tmp_dir = TempDir::new("mononoke_test_add_repo")?;
+        RepoConfigsBuilder::new()
+            .with_storage("store", test_storage())
+            .with_repo(
+                "template",
+                RawRepoDefinition {
+                    repo_id: Some(1),
+                    enabled: Some(false),
+                    ..Default::default()
+                },
+                config.clone(),
+            )
+            .with_repo(
+                "other",
+                RawRepoDefinition {
+                    repo_id: Some(5),
+                    enabled: Some(false),
+                    ..Default::default()
+                },
+                config,
+            )
+            .write_to(tmp_dir.path())?;
+
+        // Ids of disabled repos are taken into account.
+        let added = add_repo_from_template(
+            tmp_dir.path(),
+            "new",
+            RawRepoDefinition::default(),
+            "template",
+        )?;
+        assert_eq!(added.repos["new"].repoid.id(), 6);
+
+        let definition = RawRepoDefinition {
+            repo_id: Some(5),
+            ..Default::default()
+        };
+        assert!(add_repo_from_template(tmp_dir.path(), "clash", definition, "template").is_err());
+        assert!(!tmp_dir.path().join("repos").join("clash").exists());
+        Ok(())
+    }
+}
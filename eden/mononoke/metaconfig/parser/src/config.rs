@@ -11,21 +11,27 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::path::Path;
 use std::str;
+use std::time::Duration;
 
 use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Result;
+use bookmarks_types::BookmarkKey;
 use cached_config::ConfigHandle;
 use cached_config::ConfigStore;
 use metaconfig_types::BackupRepoConfig;
 use metaconfig_types::BlobConfig;
 use metaconfig_types::CensoredScubaParams;
 use metaconfig_types::CommonConfig;
+use metaconfig_types::ReadOnlyInfo;
 use metaconfig_types::Redaction;
 use metaconfig_types::RedactionConfig;
 use metaconfig_types::RepoConfig;
 use metaconfig_types::RepoReadOnly;
+use metaconfig_types::RepoView;
 use metaconfig_types::StorageConfig;
+use metaconfig_types::WireprotoTimeouts;
+use metaconfig_types::WIREPROTO_COMMANDS;
 use mononoke_types::RepositoryId;
 use repos::RawAclRegionConfig;
 use repos::RawCommonConfig;
@@ -36,6 +42,7 @@ use repos::RawStorageConfig;
 
 use crate::convert::Convert;
 use crate::errors::ConfigurationError;
+use crate::migration::CURRENT_SCHEMA_VERSION;
 
 const LIST_KEYS_PATTERNS_MAX_DEFAULT: u64 = 500_000;
 const HOOK_MAX_FILE_SIZE_DEFAULT: u64 = 8 * 1024 * 1024; // 8MiB
@@ -45,9 +52,11 @@ pub fn load_common_config(
     config_path: impl AsRef<Path>,
     config_store: &ConfigStore,
 ) -> Result<CommonConfig> {
+    // Warnings about deprecated fields are reported with the repo configs.
+    let (raw_config, _) = crate::raw::read_raw_configs(config_path.as_ref(), config_store)?;
     let RawRepoConfigs {
         common, storage, ..
-    } = crate::raw::read_raw_configs(config_path.as_ref(), config_store)?;
+    } = raw_config;
     parse_common_config(common, &storage)
 }
 
@@ -56,8 +65,15 @@ pub fn load_common_config(
 pub struct RepoConfigs {
     /// Configs for all repositories
     pub repos: HashMap<String, RepoConfig>,
+    /// Configs for views of repositories, which are served under their own
+    /// names with the id and storage of the repo they are a view of.
+    pub views: HashMap<String, RepoConfig>,
     /// Common configs for all repos
     pub common: CommonConfig,
+    /// Warnings about deprecated fields in config files written against
+    /// older versions of the config schema, which were migrated as they
+    /// were loaded.
+    pub schema_warnings: Vec<String>,
 }
 
 /// Provides an instance of ConfigHandle to the underlying
@@ -83,8 +99,11 @@ pub fn load_repo_configs(
     config_path: impl AsRef<Path>,
     config_store: &ConfigStore,
 ) -> Result<RepoConfigs> {
-    let raw_config = crate::raw::read_raw_configs(config_path.as_ref(), config_store)?;
-    load_configs_from_raw(raw_config).map(|(repo_configs, _)| repo_configs)
+    let (raw_config, schema_warnings) =
+        crate::raw::read_raw_configs(config_path.as_ref(), config_store)?;
+    let (mut repo_configs, _) = load_configs_from_raw(raw_config)?;
+    repo_configs.schema_warnings = schema_warnings;
+    Ok(repo_configs)
 }
 
 /// Load configuration based on the provided raw configs.
@@ -98,7 +117,17 @@ pub fn load_configs_from_raw(
         storage,
         acl_region_configs,
         repo_definitions,
+        config_schema_version,
     } = raw_repo_configs;
+    if let Some(version) = config_schema_version {
+        if version != CURRENT_SCHEMA_VERSION {
+            return Err(ConfigurationError::InvalidConfig(format!(
+                "config schema version {} must be migrated to version {} before it is loaded",
+                version, CURRENT_SCHEMA_VERSION
+            ))
+            .into());
+        }
+    }
     let repo_definitions = repo_definitions.repo_definitions;
     let repo_configs = repos;
     let storage_configs = storage;
@@ -121,6 +150,26 @@ pub fn load_configs_from_raw(
         resolved_repo_configs.insert(reponame, repo_config);
     }
 
+    // Views are served under their own names, so they can't share them with
+    // repos or other views.  They share the id of the repo they are a view
+    // of, so are kept apart from repos.
+    let mut resolved_view_configs = HashMap::new();
+    for (reponame, repo_config) in resolved_repo_configs.iter() {
+        for view in repo_config.views.iter() {
+            if resolved_repo_configs.contains_key(&view.name)
+                || resolved_view_configs
+                    .insert(view.name.clone(), view_config(reponame, repo_config, view))
+                    .is_some()
+            {
+                return Err(ConfigurationError::InvalidConfig(format!(
+                    "repo view {} has the name of another repo or view",
+                    view.name
+                ))
+                .into());
+            }
+        }
+    }
+
     let common = parse_common_config(common, &storage_configs)?;
     let storage = storage_configs
         .into_iter()
@@ -129,12 +178,29 @@ pub fn load_configs_from_raw(
     Ok((
         RepoConfigs {
             repos: resolved_repo_configs,
+            views: resolved_view_configs,
             common,
+            schema_warnings: Vec::new(),
         },
         StorageConfigs { storage },
     ))
 }
 
+/// The config of a view of a repo, which is served read-only with the id and
+/// storage of the repo.
+fn view_config(repo_name: &str, repo_config: &RepoConfig, view: &RepoView) -> RepoConfig {
+    RepoConfig {
+        readonly: RepoReadOnly::ReadOnly(ReadOnlyInfo::new(format!(
+            "{} is a read-only view of {}",
+            view.name, repo_name
+        ))),
+        views: Vec::new(),
+        view: Some(view.clone()),
+        sparse_export_config: None,
+        ..repo_config.clone()
+    }
+}
+
 fn parse_with_repo_definition(
     repo_definition: RawRepoDefinition,
     named_repo_configs: &HashMap<String, RawRepoConfig>,
@@ -153,6 +219,9 @@ fn parse_with_repo_definition(
         external_repo_id: _,
         acl_region_config,
         default_commit_identity_scheme,
+        readonly_reason,
+        readonly_locked_by,
+        readonly_expected_unlock,
     } = repo_definition;
 
     let default_commit_identity_scheme = default_commit_identity_scheme
@@ -224,6 +293,23 @@ fn parse_with_repo_definition(
         update_logging_config,
         commit_graph_config,
         deep_sharding_config,
+        blobstore_retry_config,
+        commit_signing_config,
+        publishing_bookmarks,
+        wireproto_timeouts,
+        features,
+        wireproto_capabilities,
+        client_versions,
+        mirror_config,
+        path_lint_config,
+        push_to_review_config,
+        webhooks,
+        tag_config,
+        slo_config,
+        maintenance_windows,
+        filenodes_config,
+        views,
+        sparse_export_config,
         ..
     } = named_repo_config;
 
@@ -273,7 +359,14 @@ fn parse_with_repo_definition(
         .unwrap_or(0);
 
     let readonly = if readonly.unwrap_or_default() {
-        RepoReadOnly::ReadOnly("Set by config option".to_string())
+        RepoReadOnly::ReadOnly(ReadOnlyInfo {
+            reason: readonly_reason
+                .filter(|reason| !reason.is_empty())
+                .unwrap_or_else(|| "Set by config option".to_string()),
+            locked_by: readonly_locked_by.filter(|locked_by| !locked_by.is_empty()),
+            expected_unlock: readonly_expected_unlock,
+            redirect_url: None,
+        })
     } else {
         RepoReadOnly::ReadWrite
     };
@@ -339,6 +432,36 @@ fn parse_with_repo_definition(
 
     let commit_graph_config = commit_graph_config.convert()?.unwrap_or_default();
     let deep_sharding_config = deep_sharding_config.convert()?;
+    let blobstore_retry_config = blobstore_retry_config.convert()?;
+    let commit_signing_config = commit_signing_config.convert()?.unwrap_or_default();
+    let publishing_bookmarks = publishing_bookmarks
+        .unwrap_or_default()
+        .into_iter()
+        .map(BookmarkKey::new)
+        .collect::<Result<Vec<_>>>()?;
+    let wireproto_timeouts = parse_wireproto_timeouts(wireproto_timeouts.unwrap_or_default())?;
+    let features = features
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, flag)| {
+            let rollout = flag
+                .convert()
+                .with_context(|| format!("invalid config for feature {}", name))?;
+            Ok((name, rollout))
+        })
+        .collect::<Result<HashMap<_, _>>>()?;
+    let wireproto_capabilities = wireproto_capabilities.convert()?.unwrap_or_default();
+    let client_versions = client_versions.convert()?.unwrap_or_default();
+    let mirror_config = mirror_config.convert()?;
+    let path_lint_config = path_lint_config.convert()?;
+    let push_to_review_config = push_to_review_config.convert()?;
+    let webhooks = webhooks.convert()?.unwrap_or_default();
+    let tag_config = tag_config.convert()?.unwrap_or_default();
+    let slo_config = slo_config.convert()?.unwrap_or_default();
+    let maintenance_windows = maintenance_windows.convert()?.unwrap_or_default();
+    let filenodes_config = filenodes_config.convert()?.unwrap_or_default();
+    let views = views.convert()?.unwrap_or_default();
+    let sparse_export_config = sparse_export_config.convert()?;
 
     Ok(RepoConfig {
         enabled,
@@ -382,9 +505,77 @@ fn parse_with_repo_definition(
         commit_graph_config,
         default_commit_identity_scheme,
         deep_sharding_config,
+        blobstore_retry_config,
+        commit_signing_config,
+        publishing_bookmarks,
+        wireproto_timeouts,
+        features,
+        wireproto_capabilities,
+        client_versions,
+        mirror_config,
+        path_lint_config,
+        push_to_review_config,
+        webhooks,
+        tag_config,
+        slo_config,
+        maintenance_windows,
+        filenodes_config,
+        views,
+        view: None,
+        sparse_export_config,
     })
 }
 
+/// Parses a duration such as "600s", "30m" or "4h".
+fn parse_duration(duration: &str) -> Result<Duration> {
+    let split = duration
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(duration.len());
+    let (value, unit) = duration.split_at(split);
+    let value: u64 = value.parse().map_err(|_| {
+        ConfigurationError::InvalidConfig(format!("Invalid duration \"{}\"", duration))
+    })?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(value)),
+        "s" => Ok(Duration::from_secs(value)),
+        "m" => Ok(Duration::from_secs(value * 60)),
+        "h" => Ok(Duration::from_secs(value * 60 * 60)),
+        _ => Err(ConfigurationError::InvalidConfig(format!(
+            "Invalid duration \"{}\", expected a unit of ms, s, m or h",
+            duration
+        ))
+        .into()),
+    }
+}
+
+fn parse_wireproto_timeouts(
+    raw: impl IntoIterator<Item = (String, String)>,
+) -> Result<WireprotoTimeouts> {
+    let mut timeouts = WireprotoTimeouts::default();
+    for (command, timeout) in raw {
+        let timeout = parse_duration(&timeout)?;
+        if timeout.is_zero() {
+            return Err(ConfigurationError::InvalidConfig(format!(
+                "Timeout of wireproto command {} must not be zero",
+                command
+            ))
+            .into());
+        }
+        if command == "default" {
+            timeouts.default = Some(timeout);
+        } else if WIREPROTO_COMMANDS.contains(&command.as_str()) {
+            timeouts.commands.insert(command, timeout);
+        } else {
+            return Err(ConfigurationError::InvalidConfig(format!(
+                "Unknown wireproto command {} in wireproto_timeouts",
+                command
+            ))
+            .into());
+        }
+    }
+    Ok(timeouts)
+}
+
 /// Holds configuration for storage.
 #[derive(Debug, PartialEq)]
 pub struct StorageConfigs {
@@ -397,7 +588,8 @@ pub fn load_storage_configs(
     config_path: impl AsRef<Path>,
     config_store: &ConfigStore,
 ) -> Result<StorageConfigs> {
-    let raw_config = crate::raw::read_raw_configs(config_path.as_ref(), config_store)?;
+    // Warnings about deprecated fields are reported with the repo configs.
+    let (raw_config, _) = crate::raw::read_raw_configs(config_path.as_ref(), config_store)?;
     load_configs_from_raw(raw_config).map(|(_, storage_configs)| storage_configs)
 }
 
@@ -420,12 +612,28 @@ fn parse_common_config(
         .into_iter()
         .map(Convert::convert)
         .collect::<Result<Vec<_>>>()?;
+    let admin_allowlist = common
+        .admin_allowlist
+        .unwrap_or_default()
+        .into_iter()
+        .map(Convert::convert)
+        .collect::<Result<Vec<_>>>()?;
+    let authorization_rules = common
+        .authorization_rules
+        .unwrap_or_default()
+        .into_iter()
+        .map(Convert::convert)
+        .collect::<Result<Vec<_>>>()?;
     let loadlimiter_category = common
         .loadlimiter_category
         .filter(|category| !category.is_empty());
     let scuba_censored_table = common.scuba_censored_table;
     let scuba_censored_local_path = common.scuba_local_path_censored;
     let internal_identity = common.internal_identity.convert()?;
+    let authentication_config = common
+        .authentication_config
+        .convert()?
+        .unwrap_or_default();
 
     let censored_scuba_params = CensoredScubaParams {
         table: scuba_censored_table,
@@ -465,6 +673,9 @@ fn parse_common_config(
         censored_scuba_params,
         redaction_config,
         internal_identity,
+        admin_allowlist,
+        authentication_config,
+        authorization_rules,
     })
 }
 
@@ -475,6 +686,18 @@ impl RepoConfigs {
             .iter()
             .find(|(_, repo_config)| repo_config.repoid == repo_id)
     }
+
+    /// Get the `RepoConfig` of a repo or a view of a repo that is served
+    /// under the given name.
+    pub fn get_served_repo_config(&self, name: &str) -> Option<&RepoConfig> {
+        self.repos.get(name).or_else(|| self.views.get(name))
+    }
+
+    /// Iterate over the repos and the views of repos that are served, by
+    /// the names they are served under.
+    pub fn served_repos(&self) -> impl Iterator<Item = (&String, &RepoConfig)> {
+        self.repos.iter().chain(self.views.iter())
+    }
 }
 
 #[cfg(test)]
@@ -483,33 +706,42 @@ mod test {
     use std::fs::write;
     use std::num::NonZeroUsize;
     use std::sync::Arc;
-    use std::time::Duration;
 
-    use bookmarks_types::BookmarkKey;
     use cached_config::TestSource;
     use maplit::btreemap;
+    use maplit::btreeset;
     use maplit::hashmap;
     use maplit::hashset;
     use metaconfig_types::AclRegion;
     use metaconfig_types::AclRegionConfig;
     use metaconfig_types::AclRegionRule;
     use metaconfig_types::Address;
+    use metaconfig_types::AuthenticationConfig;
+    use metaconfig_types::AuthorizationPrincipal;
+    use metaconfig_types::AuthorizationRule;
     use metaconfig_types::BlameVersion;
     use metaconfig_types::BlobConfig;
     use metaconfig_types::BlobstoreId;
     use metaconfig_types::BookmarkParams;
+    use metaconfig_types::BookmarkPrecondition;
     use metaconfig_types::BubbleDeletionMode;
     use metaconfig_types::CacheWarmupParams;
+    use metaconfig_types::ClientVersionConfig;
+    use metaconfig_types::CertIdentityMapping;
     use metaconfig_types::CommitGraphConfig;
     use metaconfig_types::CommitIdentityScheme;
+    use metaconfig_types::CommitSigningConfig;
     use metaconfig_types::CommitSyncConfig;
     use metaconfig_types::CommitSyncConfigVersion;
+    use metaconfig_types::CronSchedule;
     use metaconfig_types::CrossRepoCommitValidation;
     use metaconfig_types::DatabaseConfig;
     use metaconfig_types::DefaultSmallToLargeCommitSyncPathAction;
     use metaconfig_types::DerivedDataConfig;
     use metaconfig_types::DerivedDataTypesConfig;
     use metaconfig_types::EphemeralBlobstoreConfig;
+    use metaconfig_types::FeatureRollout;
+    use metaconfig_types::FilenodesConfig;
     use metaconfig_types::FilestoreParams;
     use metaconfig_types::HgSyncConfig;
     use metaconfig_types::HookBypass;
@@ -519,32 +751,50 @@ mod test {
     use metaconfig_types::Identity;
     use metaconfig_types::InfinitepushNamespace;
     use metaconfig_types::InfinitepushParams;
+    use metaconfig_types::JwtIssuer;
     use metaconfig_types::LfsParams;
     use metaconfig_types::LocalDatabaseConfig;
     use metaconfig_types::LoggingDestination;
+    use metaconfig_types::MaintenanceOperation;
+    use metaconfig_types::MaintenanceWindow;
     use metaconfig_types::MetadataDatabaseConfig;
+    use metaconfig_types::MirrorConfig;
     use metaconfig_types::MultiplexId;
     use metaconfig_types::MultiplexedStoreType;
+    use metaconfig_types::PathLintConfig;
+    use metaconfig_types::ProtectedTagNamespace;
     use metaconfig_types::PushParams;
+    use metaconfig_types::PushToReviewConfig;
     use metaconfig_types::PushrebaseFlags;
     use metaconfig_types::PushrebaseParams;
     use metaconfig_types::PushrebaseRemoteMode;
+    use metaconfig_types::ReadConsistencyParams;
     use metaconfig_types::RemoteDatabaseConfig;
     use metaconfig_types::RemoteMetadataDatabaseConfig;
     use metaconfig_types::RepoClientKnobs;
+    use metaconfig_types::RepoPermission;
+    use metaconfig_types::RequiredDerivedDataType;
+    use metaconfig_types::RetryConfig;
     use metaconfig_types::SegmentedChangelogConfig;
     use metaconfig_types::SegmentedChangelogHeadConfig;
     use metaconfig_types::ShardableRemoteDatabaseConfig;
     use metaconfig_types::ShardedDatabaseConfig;
     use metaconfig_types::ShardedRemoteDatabaseConfig;
     use metaconfig_types::ShardingModeConfig;
+    use metaconfig_types::SloConfig;
     use metaconfig_types::SmallRepoCommitSyncConfig;
     use metaconfig_types::SourceControlServiceMonitoring;
     use metaconfig_types::SourceControlServiceParams;
+    use metaconfig_types::SparseExportConfig;
     use metaconfig_types::SparseProfilesConfig;
+    use metaconfig_types::SubjectAltNameType;
+    use metaconfig_types::TagConfig;
     use metaconfig_types::UnodeVersion;
     use metaconfig_types::UpdateLoggingConfig;
     use metaconfig_types::WalkerConfig;
+    use metaconfig_types::WebhookConfig;
+    use metaconfig_types::WebhookEventKind;
+    use metaconfig_types::WireprotoCapabilitiesConfig;
     use mononoke_types::MPath;
     use mononoke_types_mocks::changesetid::ONES_CSID;
     use nonzero_ext::nonzero;
@@ -625,7 +875,7 @@ mod test {
         };
         let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
         let tmp_dir = write_files(&paths);
-        let raw_config = crate::raw::read_raw_configs(tmp_dir.path(), &config_store)
+        let (raw_config, _) = crate::raw::read_raw_configs(tmp_dir.path(), &config_store)
             .expect("expect to read configs");
         let commit_sync = parse_commit_sync_config(raw_config.commit_sync)
             .expect("expected to get a commit sync config");
@@ -680,7 +930,7 @@ mod test {
         };
         let tmp_dir = write_files(&paths);
         let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
-        let RawRepoConfigs { commit_sync, .. } =
+        let (RawRepoConfigs { commit_sync, .. }, _) =
             crate::raw::read_raw_configs(tmp_dir.path(), &config_store).unwrap();
         for (_config_name, commit_sync_config) in commit_sync {
             let res = commit_sync_config.convert();
@@ -717,7 +967,7 @@ mod test {
         };
         let tmp_dir = write_files(&paths);
         let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
-        let RawRepoConfigs { commit_sync, .. } =
+        let (RawRepoConfigs { commit_sync, .. }, _) =
             crate::raw::read_raw_configs(tmp_dir.path(), &config_store).unwrap();
         for (_config_name, commit_sync_config) in commit_sync {
             let res = commit_sync_config.convert();
@@ -789,6 +1039,69 @@ mod test {
             hook_max_file_size=456
             repo_client_use_warm_bookmarks_cache=true
             phabricator_callsign="FBS"
+            publishing_bookmarks=["master"]
+
+            [features]
+            new_pushrebase_path = { rollout_percentage = 25 }
+            old_getbundle_path = { enabled = false }
+
+            [wireproto_capabilities]
+            disabled=["clonebundles", "b2x:rebase"]
+            extra=["segmentedchangelog"]
+            min_client_versions={ segmentedchangelog = "0.2.20230601" }
+
+            [client_versions]
+            reject_below="0.1"
+            warn_below="0.2.20230101"
+            message="Please upgrade"
+
+            [mirror_config]
+            upstream_url="https://mononoke.example.com/edenapi"
+
+            [path_lint_config]
+            max_path_length=255
+            forbidden_chars=":*"
+            disallowed_extensions=[".EXE", "dll"]
+            check_case_conflicts=true
+
+            [push_to_review_config]
+            bookmark_prefix="for/"
+            webhook_url="https://review.example.com/create"
+
+            [[webhooks]]
+            url="https://hooks.example.com/mononoke"
+            events=["bookmark_moved", "tag_created"]
+            secret="s3cret"
+
+            [[tag_config.protected_namespaces]]
+            prefix="release/"
+            allowed_users="^(alice|bob)$"
+
+            [slo_config]
+            availability_target_ppm=999000
+            window_secs=1800
+
+            [slo_config.method_p99_latency_ms]
+            repo_list_bookmarks=500
+
+            [[maintenance_windows]]
+            schedule="30 14 * * 1-5"
+            duration_mins=90
+            operations=["background_jobs"]
+
+            [[maintenance_windows]]
+            schedule="0 2 1 */3 *"
+            duration_mins=60
+            operations=["background_jobs", "writes"]
+            reason="Quarterly database upgrade"
+
+            [filenodes_config]
+            filenode_free_serving=true
+
+            [wireproto_timeouts]
+            default="10m"
+            getbundle="600s"
+            unbundle="1200s"
 
             [cache_warmup]
             bookmark="master"
@@ -797,6 +1110,8 @@ mod test {
             disable_acl_checker=false
             all_hooks_bypassed=false
             bypassed_commits_scuba_table="commits_bypassed_hooks"
+            max_concurrent_hooks=10
+            push_time_budget_ms=60000
 
             [derived_data_config]
             enabled_config_name = "default"
@@ -804,11 +1119,19 @@ mod test {
             [derived_data_config.available_configs.default]
             types = ["fsnodes", "unodes", "blame"]
             unode_version = 2
+            unode_shard_threshold = 10000
             blame_filesize_limit = 101
 
             [[bookmarks]]
             name="master"
             allowed_users="^(svcscm|twsvcscm)$"
+            allowed_groups=["release_managers"]
+            allowed_service_identities=[
+                { identity_type="SERVICE_IDENTITY", identity_data="landing_service" },
+            ]
+            pushrebase_only=true
+            required_derived_data=["hgchangesets", "unodes"]
+            required_derived_data_budget_ms=30000
 
             [[bookmarks.hooks]]
             hook_name="hook1"
@@ -820,6 +1143,11 @@ mod test {
             regex="[^/]*/stable"
             ensure_ancestor_of="master"
             allow_move_to_public_commits_without_hooks=true
+            require_signed_commits=true
+            preconditions=[
+                { descends_from_old={} },
+                { status_check={ url="https://ci.example.com/status", timeout_ms=5000 } },
+            ]
 
             [[hooks]]
             name="hook1"
@@ -829,6 +1157,7 @@ mod test {
             name="rust:rusthook"
             config_ints={ int1 = 44 }
             config_ints_64={ int2 = 42 }
+            content_byte_budget=1048576
             [hooks.config_string_lists]
                 list1 = ["val1", "val2"]
 
@@ -841,6 +1170,7 @@ mod test {
             forbid_p2_root_rebases = false
             casefolding_check = false
             emit_obsmarkers = false
+            merge_non_overlapping_changes = true
             allow_change_xrepo_mapping_extra = true
 
             [pushrebase.remote_mode]
@@ -850,6 +1180,7 @@ mod test {
             threshold = 1000
             rollout_percentage = 56
             generate_lfs_blob_in_hg_sync_job = true
+            shared_storage = true
 
             [infinitepush]
             allow_writes = true
@@ -857,6 +1188,7 @@ mod test {
 
             [filestore]
             chunk_size = 768
+            chunking_threshold = 4096
             concurrency = 48
 
             [source_control_service_monitoring]
@@ -906,6 +1238,15 @@ mod test {
 
             [commit_graph_config]
             scuba_table = "commit_graph"
+            use_prefix_filter = true
+
+            [blobstore_retry_config]
+            max_attempts = 3
+            attempt_timeout_ms = 1000
+            retry_budget_percent = 10
+
+            [commit_signing_config.trusted_keys]
+            release = "-----BEGIN PUBLIC KEY-----"
             
             [deep_sharding_config.status]
         "#;
@@ -949,6 +1290,28 @@ mod test {
             [[global_allowlist]]
             identity_type = "username"
             identity_data = "user"
+
+            [[admin_allowlist]]
+            identity_type = "username"
+            identity_data = "admin"
+
+            [[authorization_rules]]
+            identity = { identity_type = "USER", identity_data = "releng" }
+            permissions = ["public_push"]
+
+            [[authorization_rules]]
+            tier = "ci_tier"
+            permissions = ["read", "draft_push"]
+
+            [[authentication_config.cert_identities]]
+            san_type = "URI"
+            prefix = "spiffe://example.com/user/"
+            identity_type = "USER"
+
+            [[authentication_config.jwt_issuers]]
+            issuer = "https://auth.example.com"
+            public_key = "-----BEGIN PUBLIC KEY-----"
+            claim_identities = { sub = "USER" }
         "#;
 
         let storage = r#"
@@ -957,6 +1320,7 @@ mod test {
         filenodes = { sharded = { shard_map = "db_address_shards", shard_num = 123 } }
         mutation = { db_address = "mutation_db_address" }
         sparse_profiles = { db_address = "sparse_profiles_db_address" }
+        read_consistency = { read_your_writes_window_ms = 5000, max_replica_lag_ms = 30000 }
 
         [main.blobstore.multiplexed_wal]
         multiplex_id = 1
@@ -964,8 +1328,8 @@ mod test {
         multiplex_scuba_table = "multiplex_scuba_table"
         write_quorum = 1
         components = [
-            { blobstore_id = 0, blobstore = { manifold = { manifold_bucket = "bucket" } } },
-            { blobstore_id = 1, blobstore = { blob_files = { path = "/tmp/foo" } } },
+            { blobstore_id = 0, region = "east", blobstore = { manifold = { manifold_bucket = "bucket" } } },
+            { blobstore_id = 1, region = "west", blobstore = { blob_files = { path = "/tmp/foo" } } },
         ]
         queue_db = { remote = { shard_map = "queue_db_address", shard_num = 13 } }
 
@@ -1041,6 +1405,10 @@ mod test {
                 shard_map: "queue_db_address".into(),
                 shard_num: nonzero!(13usize),
             }),
+            blobstore_regions: btreemap! {
+                BlobstoreId::new(0) => "east".to_string(),
+                BlobstoreId::new(1) => "west".to_string(),
+            },
         };
         let main_storage_config = StorageConfig {
             blobstore: multiplex,
@@ -1058,6 +1426,10 @@ mod test {
                 sparse_profiles: RemoteDatabaseConfig {
                     db_address: "sparse_profiles_db_address".into(),
                 },
+                read_consistency: ReadConsistencyParams {
+                    read_your_writes_window: Duration::from_secs(5),
+                    max_replica_lag: Some(Duration::from_secs(30)),
+                },
             }),
             ephemeral_blobstore: None,
         };
@@ -1082,6 +1454,8 @@ mod test {
                     disable_acl_checker: false,
                     all_hooks_bypassed: false,
                     bypassed_commits_scuba_table: Some("commits_bypassed_hooks".to_string()),
+                    max_concurrent_hooks: Some(10),
+                    push_time_budget: Some(Duration::from_secs(60)),
                 }),
                 bookmarks: vec![
                     BookmarkParams {
@@ -1090,10 +1464,23 @@ mod test {
                         only_fast_forward: false,
                         allowed_users: Some(Regex::new("^(svcscm|twsvcscm)$").unwrap().into()),
                         allowed_hipster_group: None,
+                        allowed_groups: vec!["release_managers".to_string()],
+                        allowed_service_identities: vec![Identity {
+                            id_type: "SERVICE_IDENTITY".to_string(),
+                            id_data: "landing_service".to_string(),
+                        }],
                         rewrite_dates: None,
                         hooks_skip_ancestors_of: vec![],
                         ensure_ancestor_of: None,
                         allow_move_to_public_commits_without_hooks: false,
+                        require_signed_commits: false,
+                        pushrebase_only: true,
+                        required_derived_data: vec![
+                            RequiredDerivedDataType::HgChangesets,
+                            RequiredDerivedDataType::Unodes,
+                        ],
+                        required_derived_data_budget: Duration::from_secs(30),
+                        preconditions: vec![],
                     },
                     BookmarkParams {
                         bookmark: Regex::new("[^/]*/stable").unwrap().into(),
@@ -1101,10 +1488,23 @@ mod test {
                         only_fast_forward: false,
                         allowed_users: None,
                         allowed_hipster_group: None,
+                        allowed_groups: vec![],
+                        allowed_service_identities: vec![],
                         rewrite_dates: None,
                         hooks_skip_ancestors_of: vec![],
                         ensure_ancestor_of: Some(BookmarkKey::new("master").unwrap()),
                         allow_move_to_public_commits_without_hooks: true,
+                        require_signed_commits: true,
+                        pushrebase_only: false,
+                        required_derived_data: vec![],
+                        required_derived_data_budget: Duration::from_secs(0),
+                        preconditions: vec![
+                            BookmarkPrecondition::DescendsFromOld,
+                            BookmarkPrecondition::StatusCheck {
+                                url: "https://ci.example.com/status".to_string(),
+                                timeout: Duration::from_secs(5),
+                            },
+                        ],
                     },
                 ],
                 hooks: vec![
@@ -1118,6 +1518,7 @@ mod test {
                             string_lists: hashmap! {},
                             int_lists: hashmap! {},
                             int_64_lists: hashmap! {},
+                            content_byte_budget: None,
                         },
                     },
                     HookParams {
@@ -1136,6 +1537,7 @@ mod test {
                             },
                             int_lists: hashmap! {},
                             int_64_lists: hashmap! {},
+                            content_byte_budget: Some(1048576),
                         },
                     },
                 ],
@@ -1150,6 +1552,7 @@ mod test {
                         casefolding_check: false,
                         not_generated_filenodes_limit: 500,
                         monitoring_bookmark: None,
+                        merge_non_overlapping_changes: true,
                     },
                     block_merges: false,
                     emit_obsmarkers: false,
@@ -1164,6 +1567,7 @@ mod test {
                     threshold: Some(1000),
                     rollout_percentage: 56,
                     generate_lfs_blob_in_hg_sync_job: true,
+                    shared_storage: true,
                 },
                 hash_validation_percentage: 0,
                 readonly: RepoReadOnly::ReadWrite,
@@ -1178,6 +1582,7 @@ mod test {
                 hook_max_file_size: 456,
                 filestore: Some(FilestoreParams {
                     chunk_size: 768,
+                    chunking_threshold: Some(4096),
                     concurrency: 48,
                 }),
                 hipster_acl: Some("foo/test".to_string()),
@@ -1207,6 +1612,7 @@ mod test {
                         blame_filesize_limit: Some(101),
                         hg_set_committer_extra: false,
                         blame_version: BlameVersion::V1,
+                        unode_shard_threshold: Some(10000),
                     },],
                     scuba_table: None,
                 },
@@ -1276,8 +1682,113 @@ mod test {
                 },
                 commit_graph_config: CommitGraphConfig {
                     scuba_table: Some("commit_graph".to_string()),
+                    use_prefix_filter: true,
                 },
                 deep_sharding_config: Some(ShardingModeConfig { status: hashmap!() }),
+                blobstore_retry_config: Some(RetryConfig {
+                    max_attempts: 3,
+                    base_delay: Duration::from_millis(100),
+                    jitter: Duration::from_millis(50),
+                    attempt_timeout: Some(Duration::from_millis(1000)),
+                    operation_timeout: None,
+                    retry_budget_percent: Some(10),
+                }),
+                commit_signing_config: CommitSigningConfig {
+                    trusted_keys: hashmap! {
+                        "release".to_string() => "-----BEGIN PUBLIC KEY-----".to_string(),
+                    },
+                },
+                publishing_bookmarks: vec![BookmarkKey::new("master").unwrap()],
+                wireproto_timeouts: WireprotoTimeouts {
+                    default: Some(Duration::from_secs(600)),
+                    commands: hashmap! {
+                        "getbundle".to_string() => Duration::from_secs(600),
+                        "unbundle".to_string() => Duration::from_secs(1200),
+                    },
+                },
+                features: hashmap! {
+                    "new_pushrebase_path".to_string() => FeatureRollout::Percentage(25),
+                    "old_getbundle_path".to_string() => FeatureRollout::Enabled(false),
+                },
+                wireproto_capabilities: WireprotoCapabilitiesConfig {
+                    disabled: hashset! {
+                        "clonebundles".to_string(),
+                        "b2x:rebase".to_string(),
+                    },
+                    extra: vec!["segmentedchangelog".to_string()],
+                    min_client_versions: hashmap! {
+                        "segmentedchangelog".to_string() => "0.2.20230601".parse().unwrap(),
+                    },
+                },
+                client_versions: ClientVersionConfig {
+                    reject_below: Some("0.1".parse().unwrap()),
+                    warn_below: Some("0.2.20230101".parse().unwrap()),
+                    treat_unknown_as_outdated: false,
+                    message: Some("Please upgrade".to_string()),
+                },
+                mirror_config: Some(MirrorConfig {
+                    upstream_url: "https://mononoke.example.com/edenapi".to_string(),
+                    upstream_repo_name: None,
+                }),
+                path_lint_config: Some(PathLintConfig {
+                    max_path_length: Some(255),
+                    forbidden_chars: ":*".to_string(),
+                    disallowed_extensions: vec!["exe".to_string(), "dll".to_string()],
+                    check_case_conflicts: true,
+                    task_scribe_category: None,
+                }),
+                push_to_review_config: Some(PushToReviewConfig {
+                    bookmark_prefix: "for/".to_string(),
+                    webhook_url: "https://review.example.com/create".to_string(),
+                }),
+                webhooks: vec![WebhookConfig {
+                    url: "https://hooks.example.com/mononoke".to_string(),
+                    events: vec![WebhookEventKind::BookmarkMoved, WebhookEventKind::TagCreated],
+                    secret: Some("s3cret".to_string()),
+                }],
+                tag_config: TagConfig {
+                    protected_namespaces: vec![ProtectedTagNamespace {
+                        prefix: "release/".to_string(),
+                        allowed_users: Some(Regex::new("^(alice|bob)$").unwrap().into()),
+                    }],
+                },
+                slo_config: SloConfig {
+                    availability_target_ppm: Some(999_000),
+                    method_p99_latency: hashmap! {
+                        "repo_list_bookmarks".to_string() => Duration::from_millis(500),
+                    },
+                    window: Duration::from_secs(1800),
+                },
+                maintenance_windows: vec![
+                    MaintenanceWindow {
+                        schedule: "30 14 * * 1-5".parse().unwrap(),
+                        duration: Duration::from_secs(90 * 60),
+                        operations: vec![MaintenanceOperation::BackgroundJobs],
+                        reason: None,
+                    },
+                    MaintenanceWindow {
+                        schedule: CronSchedule {
+                            minutes: btreeset! {0},
+                            hours: btreeset! {2},
+                            days_of_month: btreeset! {1},
+                            months: btreeset! {1, 4, 7, 10},
+                            days_of_week: (0..=6).collect(),
+                        },
+                        duration: Duration::from_secs(60 * 60),
+                        operations: vec![
+                            MaintenanceOperation::BackgroundJobs,
+                            MaintenanceOperation::Writes,
+                        ],
+                        reason: Some("Quarterly database upgrade".to_string()),
+                    },
+                ],
+                filenodes_config: FilenodesConfig {
+                    filenode_free_serving: true,
+                    disable_generation: false,
+                },
+                views: vec![],
+                view: None,
+                sparse_export_config: None,
             },
         );
 
@@ -1353,6 +1864,24 @@ mod test {
                 update_logging_config: UpdateLoggingConfig::default(),
                 commit_graph_config: CommitGraphConfig::default(),
                 deep_sharding_config: None,
+                blobstore_retry_config: None,
+                commit_signing_config: CommitSigningConfig::default(),
+                publishing_bookmarks: vec![],
+                wireproto_timeouts: WireprotoTimeouts::default(),
+                features: HashMap::new(),
+                wireproto_capabilities: WireprotoCapabilitiesConfig::default(),
+                client_versions: ClientVersionConfig::default(),
+                mirror_config: None,
+                path_lint_config: None,
+                push_to_review_config: None,
+                webhooks: vec![],
+                tag_config: TagConfig::default(),
+                slo_config: SloConfig::default(),
+                maintenance_windows: vec![],
+                filenodes_config: FilenodesConfig::default(),
+                views: vec![],
+                view: None,
+                sparse_export_config: None,
             },
         );
         assert_eq!(
@@ -1378,7 +1907,39 @@ mod test {
                 internal_identity: Identity {
                     id_type: "SERVICE_IDENTITY".to_string(),
                     id_data: "internal".to_string(),
-                }
+                },
+                admin_allowlist: vec![Identity {
+                    id_type: "username".to_string(),
+                    id_data: "admin".to_string()
+                }],
+                authentication_config: AuthenticationConfig {
+                    cert_identities: vec![CertIdentityMapping {
+                        san_type: SubjectAltNameType::Uri,
+                        prefix: "spiffe://example.com/user/".to_string(),
+                        identity_type: "USER".to_string(),
+                    }],
+                    jwt_issuers: vec![JwtIssuer {
+                        issuer: "https://auth.example.com".to_string(),
+                        public_key: "-----BEGIN PUBLIC KEY-----".to_string(),
+                        audience: None,
+                        claim_identities: hashmap! {
+                            "sub".to_string() => "USER".to_string(),
+                        },
+                    }],
+                },
+                authorization_rules: vec![
+                    AuthorizationRule {
+                        principal: AuthorizationPrincipal::Identity(Identity {
+                            id_type: "USER".to_string(),
+                            id_data: "releng".to_string(),
+                        }),
+                        permissions: vec![RepoPermission::PublicPush],
+                    },
+                    AuthorizationRule {
+                        principal: AuthorizationPrincipal::Tier("ci_tier".to_string()),
+                        permissions: vec![RepoPermission::Read, RepoPermission::DraftPush],
+                    },
+                ],
             }
         );
         assert_eq!(
@@ -1449,6 +2010,79 @@ mod test {
         assert!(msg.contains("InvalidPushvar"));
     }
 
+    #[test]
+    fn test_filestore_chunking_threshold_below_chunk_size() {
+        let content = r#"
+            storage_config = "sqlite"
+
+            [storage.sqlite.metadata.local]
+            local_db_path = "/tmp/fbsource"
+
+            [storage.sqlite.blobstore.blob_files]
+            path = "/tmp/fbsource"
+
+            [filestore]
+            chunk_size = 768
+            chunking_threshold = 512
+            concurrency = 48
+        "#;
+
+        let content_def = r#"
+            repo_id = 0
+            repo_name = "fbsource"
+            repo_config = "fbsource"
+        "#;
+
+        let paths = btreemap! {
+            "common/commitsyncmap.toml" => "",
+            "repos/fbsource/server.toml" => content,
+            "repo_definitions/fbsource/server.toml" => content_def,
+        };
+
+        let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
+        let tmp_dir = write_files(&paths);
+        let res = load_repo_configs(tmp_dir.path(), &config_store);
+        let msg = format!("{:#?}", res);
+        assert!(res.is_err());
+        assert!(msg.contains("must not be smaller than chunk_size"));
+    }
+
+    #[test]
+    fn test_broken_required_derived_data() {
+        let content = r#"
+            storage_config = "sqlite"
+
+            [storage.sqlite.metadata.local]
+            local_db_path = "/tmp/fbsource"
+
+            [storage.sqlite.blobstore.blob_files]
+            path = "/tmp/fbsource"
+
+            [[bookmarks]]
+            name="master"
+            required_derived_data=["hgchangesets", "unode"]
+        "#;
+
+        let content_def = r#"
+            repo_id = 0
+            repo_name = "fbsource"
+            repo_config = "fbsource"
+        "#;
+
+        let paths = btreemap! {
+            "common/commitsyncmap.toml" => "",
+            "repos/fbsource/server.toml" => content,
+            "repo_definitions/fbsource/server.toml" => content_def,
+        };
+
+        let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
+        let tmp_dir = write_files(&paths);
+        let res = load_repo_configs(tmp_dir.path(), &config_store);
+        let msg = format!("{:#?}", res);
+        assert!(res.is_err());
+        assert!(msg.contains("Derived data type 'unode' can't be required"));
+    }
+
     #[test]
     fn test_broken_common_config() {
         fn check_fails(common: &str, expect: &str) {
@@ -1502,6 +2136,107 @@ mod test {
         check_fails(common, "identity type and data must be specified");
     }
 
+    #[test]
+    fn test_broken_wireproto_timeouts() {
+        fn check_fails(timeouts: &str, expect: &str) {
+            let content = format!(
+                r#"
+                storage_config = "storage"
+
+                [storage.storage.metadata.local]
+                local_db_path = "/tmp/fbsource"
+
+                [storage.storage.blobstore.blob_sqlite]
+                path = "/tmp/fbsource"
+
+                [wireproto_timeouts]
+                {}
+            "#,
+                timeouts
+            );
+
+            let content_def = r#"
+                repo_id = 0
+                repo_name = "fbsource"
+                repo_config = "fbsource"
+            "#;
+
+            let paths = btreemap! {
+                "common/commitsyncmap.toml" => "",
+                "repos/fbsource/server.toml" => content.as_str(),
+                "repo_definitions/fbsource/server.toml" => content_def,
+            };
+
+            let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
+            let tmp_dir = write_files(&paths);
+            let res = load_repo_configs(tmp_dir.path(), &config_store);
+            let msg = format!("{:?}", res);
+            assert!(res.is_err(), "unexpected success for {}", timeouts);
+            assert!(
+                msg.contains(expect),
+                "wrong failure, wanted \"{}\" in {}",
+                expect,
+                msg
+            );
+        }
+
+        check_fails(r#"getbundle="10""#, "expected a unit");
+        check_fails(r#"getbundle="ten minutes""#, "Invalid duration");
+        check_fails(r#"getbundle="0s""#, "must not be zero");
+        check_fails(r#"getbundel="10m""#, "Unknown wireproto command getbundel");
+    }
+
+    #[test]
+    fn test_broken_client_versions() {
+        fn check_fails(client_versions: &str, expect: &str) {
+            let content = format!(
+                r#"
+                storage_config = "storage"
+
+                [storage.storage.metadata.local]
+                local_db_path = "/tmp/fbsource"
+
+                [storage.storage.blobstore.blob_sqlite]
+                path = "/tmp/fbsource"
+
+                [client_versions]
+                {}
+            "#,
+                client_versions
+            );
+
+            let content_def = r#"
+                repo_id = 0
+                repo_name = "fbsource"
+                repo_config = "fbsource"
+            "#;
+
+            let paths = btreemap! {
+                "common/commitsyncmap.toml" => "",
+                "repos/fbsource/server.toml" => content.as_str(),
+                "repo_definitions/fbsource/server.toml" => content_def,
+            };
+
+            let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
+            let tmp_dir = write_files(&paths);
+            let res = load_repo_configs(tmp_dir.path(), &config_store);
+            let msg = format!("{:?}", res);
+            assert!(res.is_err(), "unexpected success for {}", client_versions);
+            assert!(
+                msg.contains(expect),
+                "wrong failure, wanted \"{}\" in {}",
+                expect,
+                msg
+            );
+        }
+
+        check_fails(r#"reject_below="latest""#, "invalid client version");
+        check_fails(
+            "reject_below=\"4.4.2\"\nwarn_below=\"4.4\"",
+            "must not be older than",
+        );
+    }
+
     #[test]
     fn test_common_storage() {
         const STORAGE: &str = r#"
@@ -1581,6 +2316,7 @@ mod test {
                                 shard_num: nonzero!(1usize),
                             }
                         ),
+                        blobstore_regions: btreemap! {},
                     },
                     metadata: MetadataDatabaseConfig::Remote(RemoteMetadataDatabaseConfig {
                         primary: RemoteDatabaseConfig {
@@ -1595,6 +2331,7 @@ mod test {
                         sparse_profiles: RemoteDatabaseConfig {
                             db_address: "some_db".into(),
                         },
+                        read_consistency: ReadConsistencyParams::default(),
                     }),
                     ephemeral_blobstore: None,
                 },
@@ -1688,7 +2425,8 @@ mod test {
                         primary: RemoteDatabaseConfig { db_address: "other_other_db".into(), },
                         filenodes: ShardableRemoteDatabaseConfig::Sharded(ShardedRemoteDatabaseConfig { shard_map: "other-other-shards".into(), shard_num: NonZeroUsize::new(789).unwrap() }),
                         mutation: RemoteDatabaseConfig { db_address: "other_other_mutation_db".into(), },
-                        sparse_profiles: RemoteDatabaseConfig { db_address: "test_db".into(), }
+                        sparse_profiles: RemoteDatabaseConfig { db_address: "test_db".into(), },
+                        read_consistency: ReadConsistencyParams::default(),
                     }),
 
                     ephemeral_blobstore: None,
@@ -1828,4 +2566,280 @@ mod test {
             panic!("Multiplexed config is not a multiplexed blobstore");
         }
     }
+
+    #[test]
+    fn test_readonly_details() {
+        const STORAGE: &str = r#"
+        [store.metadata.local]
+        local_db_path = "/tmp/db"
+
+        [store.blobstore.blob_files]
+        path = "/tmp/blobs"
+        "#;
+
+        const REPO: &str = r#"
+        storage_config = "store"
+        "#;
+
+        const REPO_DEF: &str = r#"
+        repo_id = 123
+        repo_name = "test"
+        repo_config = "test"
+        readonly = true
+        readonly_reason = "Migrating storage"
+        readonly_locked_by = "alice"
+        readonly_expected_unlock = 1700000000
+        "#;
+
+        const COMMON: &str = r#"
+        [redaction_config]
+        blobstore = "store"
+        redaction_sets_location = "loc"
+
+        [internal_identity]
+        identity_type = "SERVICE_IDENTITY"
+        identity_data = "internal"
+        "#;
+
+        let paths = btreemap! {
+            "common/storage.toml" => STORAGE,
+            "common/common.toml" => COMMON,
+            "common/commitsyncmap.toml" => "",
+            "repos/test/server.toml" => REPO,
+            "repo_definitions/test/server.toml" => REPO_DEF,
+        };
+
+        let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
+        let tmp_dir = write_files(&paths);
+        let res = load_repo_configs(tmp_dir.path(), &config_store).expect("Read configs failed");
+
+        let info = ReadOnlyInfo {
+            reason: "Migrating storage".to_string(),
+            locked_by: Some("alice".to_string()),
+            expected_unlock: Some(1700000000),
+            redirect_url: None,
+        };
+        assert_eq!(
+            res.repos["test"].readonly,
+            RepoReadOnly::ReadOnly(info.clone())
+        );
+        assert_eq!(
+            info.to_string(),
+            "Migrating storage (locked by alice) (expected to be unlocked at 2023-11-14T22:13:20+00:00)"
+        );
+    }
+
+    #[test]
+    fn test_repo_views() {
+        const STORAGE: &str = r#"
+        [store.metadata.local]
+        local_db_path = "/tmp/db"
+
+        [store.blobstore.blob_files]
+        path = "/tmp/blobs"
+        "#;
+
+        const REPO: &str = r#"
+        storage_config = "store"
+
+        [[views]]
+        name = "test-release"
+        bookmarks = ["releases/.*", "stable"]
+        "#;
+
+        const REPO_DEF: &str = r#"
+        repo_id = 123
+        repo_name = "test"
+        repo_config = "test"
+        "#;
+
+        let paths = btreemap! {
+            "common/storage.toml" => STORAGE,
+            "common/commitsyncmap.toml" => "",
+            "repos/test/server.toml" => REPO,
+            "repo_definitions/test/server.toml" => REPO_DEF,
+        };
+
+        let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
+        let tmp_dir = write_files(&paths);
+        let res = load_repo_configs(tmp_dir.path(), &config_store).expect("Read configs failed");
+
+        let repo = &res.repos["test"];
+        let view = &res.views["test-release"];
+        assert!(!res.repos.contains_key("test-release"));
+        assert_eq!(res.get_served_repo_config("test-release"), Some(view));
+        assert_eq!(res.served_repos().count(), 2);
+        assert_eq!(repo.views.len(), 1);
+        assert_eq!(view.view.as_ref(), repo.views.first());
+        assert_eq!(view.repoid, repo.repoid);
+        assert_eq!(view.storage_config, repo.storage_config);
+        assert!(view.views.is_empty());
+        assert_eq!(
+            view.readonly,
+            RepoReadOnly::ReadOnly(ReadOnlyInfo::new("test-release is a read-only view of test"))
+        );
+        assert_eq!(
+            res.get_repo_config(RepositoryId::new(123))
+                .map(|(name, _)| name.as_str()),
+            Some("test")
+        );
+
+        let view = view.view.as_ref().unwrap();
+        assert!(view.serves_bookmark("releases/v1"));
+        assert!(view.serves_bookmark("stable"));
+        assert!(!view.serves_bookmark("master"));
+        assert!(!view.serves_bookmark("unstable"));
+
+        // Views can't have the name of a repo.
+        const CLASHING_REPO: &str = r#"
+        storage_config = "store"
+
+        [[views]]
+        name = "test"
+        bookmarks = ["stable"]
+        "#;
+        let paths = btreemap! {
+            "common/storage.toml" => STORAGE,
+            "common/commitsyncmap.toml" => "",
+            "repos/test/server.toml" => CLASHING_REPO,
+            "repo_definitions/test/server.toml" => REPO_DEF,
+        };
+        let tmp_dir = write_files(&paths);
+        let res = load_repo_configs(tmp_dir.path(), &config_store);
+        assert!(format!("{:#}", res.unwrap_err()).contains("has the name of another repo"));
+    }
+
+    #[test]
+    fn test_sparse_export_config() {
+        const STORAGE: &str = r#"
+        [store.metadata.local]
+        local_db_path = "/tmp/db"
+
+        [store.blobstore.blob_files]
+        path = "/tmp/blobs"
+        "#;
+
+        const REPO: &str = r#"
+        storage_config = "store"
+
+        [sparse_export_config]
+        export_repo_id = 124
+        allowed_paths = ["public", "docs/api"]
+        bookmarks = ["master"]
+        "#;
+
+        const REPO_DEF: &str = r#"
+        repo_id = 123
+        repo_name = "test"
+        repo_config = "test"
+        "#;
+
+        let paths = btreemap! {
+            "common/storage.toml" => STORAGE,
+            "common/commitsyncmap.toml" => "",
+            "repos/test/server.toml" => REPO,
+            "repo_definitions/test/server.toml" => REPO_DEF,
+        };
+
+        let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
+        let tmp_dir = write_files(&paths);
+        let res = load_repo_configs(tmp_dir.path(), &config_store).expect("Read configs failed");
+
+        assert_eq!(
+            res.repos["test"].sparse_export_config,
+            Some(SparseExportConfig {
+                export_repo_id: RepositoryId::new(124),
+                allowed_paths: vec![
+                    MPath::new("public").unwrap(),
+                    MPath::new("docs/api").unwrap()
+                ],
+                bookmarks: vec![BookmarkKey::new("master").unwrap()],
+            })
+        );
+    }
+
+    #[test]
+    fn test_schema_migration() {
+        const STORAGE: &str = r#"
+        config_schema_version = 1
+
+        [multiplex_store.metadata.remote]
+        primary = { db_address = "some_db" }
+        filenodes = { sharded = { shard_map = "some-shards", shard_num = 123 } }
+
+        [multiplex_store.blobstore.multiplexed]
+        multiplex_id = 1
+        components = [
+            { blobstore_id = 1, blobstore = { blob_files = { path = "/tmp/foo1" } } },
+            { blobstore_id = 2, blobstore = { mysql = { mysql_shardmap = "blobs", mysql_shard_num = 12 } } },
+        ]
+        queue_db = { remote = { db_address = "queue_db_address" } }
+        minimum_successful_writes = 2
+        not_present_read_quorum = 1
+        scuba_table = "blobstores"
+        "#;
+
+        const REPO: &str = r#"
+        storage_config = "multiplex_store"
+        "#;
+
+        const REPO_DEF: &str = r#"
+        repo_id = 123
+        repo_name = "test"
+        repo_config = "test"
+        "#;
+
+        let paths = btreemap! {
+            "common/storage.toml" => STORAGE,
+            "common/commitsyncmap.toml" => "",
+            "repos/test/server.toml" => REPO,
+            "repo_definitions/test/server.toml" => REPO_DEF,
+        };
+
+        let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
+        let tmp_dir = write_files(&paths);
+        let res = load_repo_configs(tmp_dir.path(), &config_store).expect("Read configs failed");
+
+        match &res.repos["test"].storage_config.blobstore {
+            BlobConfig::MultiplexedWal {
+                blobstores,
+                write_quorum,
+                queue_db,
+                inner_blobstores_scuba_table,
+                ..
+            } => {
+                assert_eq!(*write_quorum, 2);
+                assert_eq!(
+                    queue_db,
+                    &ShardedDatabaseConfig::Unsharded(RemoteDatabaseConfig {
+                        db_address: "queue_db_address".into(),
+                    })
+                );
+                assert_eq!(inner_blobstores_scuba_table.as_deref(), Some("blobstores"));
+                assert_eq!(
+                    blobstores[1].2,
+                    BlobConfig::Mysql {
+                        remote: ShardableRemoteDatabaseConfig::Sharded(
+                            ShardedRemoteDatabaseConfig {
+                                shard_map: "blobs".into(),
+                                shard_num: nonzero!(12usize),
+                            }
+                        ),
+                    }
+                );
+            }
+            _ => panic!("Multiplexed config was not migrated to a WAL multiplex"),
+        }
+        // One warning for the version, and one for each deprecated field.
+        assert_eq!(res.schema_warnings.len(), 4, "{:#?}", res.schema_warnings);
+
+        let paths = btreemap! {
+            "common/storage.toml" => "config_schema_version = 3",
+            "common/commitsyncmap.toml" => "",
+        };
+        let tmp_dir = write_files(&paths);
+        let res = load_repo_configs(tmp_dir.path(), &config_store);
+        let msg = format!("{:#}", res.unwrap_err());
+        assert!(msg.contains("newer than the newest supported version"), "{}", msg);
+    }
 }
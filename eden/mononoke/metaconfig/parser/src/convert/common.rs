@@ -6,8 +6,19 @@
  */
 
 use anyhow::Result;
+use metaconfig_types::AuthenticationConfig;
+use metaconfig_types::AuthorizationPrincipal;
+use metaconfig_types::AuthorizationRule;
+use metaconfig_types::CertIdentityMapping;
 use metaconfig_types::Identity;
+use metaconfig_types::JwtIssuer;
+use metaconfig_types::RepoPermission;
+use metaconfig_types::SubjectAltNameType;
 use repos::RawAllowlistIdentity;
+use repos::RawAuthenticationConfig;
+use repos::RawAuthorizationRule;
+use repos::RawCertIdentityMapping;
+use repos::RawJwtIssuer;
 
 use crate::convert::Convert;
 use crate::errors::ConfigurationError;
@@ -28,3 +39,89 @@ impl Convert for RawAllowlistIdentity {
         })
     }
 }
+
+impl Convert for RawCertIdentityMapping {
+    type Output = CertIdentityMapping;
+
+    fn convert(self) -> Result<Self::Output> {
+        let san_type = match self.san_type.as_str() {
+            "DNS" => SubjectAltNameType::Dns,
+            "URI" => SubjectAltNameType::Uri,
+            "EMAIL" => SubjectAltNameType::Email,
+            other => {
+                return Err(ConfigurationError::InvalidFileStructure(format!(
+                    "unknown subject alternative name type: {}",
+                    other
+                ))
+                .into());
+            }
+        };
+        if self.identity_type.is_empty() {
+            return Err(ConfigurationError::InvalidFileStructure(
+                "identity type must be specified".into(),
+            )
+            .into());
+        }
+        Ok(CertIdentityMapping {
+            san_type,
+            prefix: self.prefix.unwrap_or_default(),
+            identity_type: self.identity_type,
+        })
+    }
+}
+
+impl Convert for RawJwtIssuer {
+    type Output = JwtIssuer;
+
+    fn convert(self) -> Result<Self::Output> {
+        if self.issuer.is_empty() || self.public_key.is_empty() {
+            return Err(ConfigurationError::InvalidFileStructure(
+                "JWT issuer and public key must be specified".into(),
+            )
+            .into());
+        }
+        Ok(JwtIssuer {
+            issuer: self.issuer,
+            public_key: self.public_key,
+            audience: self.audience,
+            claim_identities: self.claim_identities.into_iter().collect(),
+        })
+    }
+}
+
+impl Convert for RawAuthenticationConfig {
+    type Output = AuthenticationConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        Ok(AuthenticationConfig {
+            cert_identities: self.cert_identities.convert()?.unwrap_or_default(),
+            jwt_issuers: self.jwt_issuers.convert()?.unwrap_or_default(),
+        })
+    }
+}
+
+impl Convert for RawAuthorizationRule {
+    type Output = AuthorizationRule;
+
+    fn convert(self) -> Result<Self::Output> {
+        let principal = match (self.identity, self.tier) {
+            (Some(identity), None) => AuthorizationPrincipal::Identity(identity.convert()?),
+            (None, Some(tier)) if !tier.is_empty() => AuthorizationPrincipal::Tier(tier),
+            _ => {
+                return Err(ConfigurationError::InvalidFileStructure(
+                    "authorization rule must specify exactly one of identity and tier".into(),
+                )
+                .into());
+            }
+        };
+        let permissions = self
+            .permissions
+            .iter()
+            .map(|permission| permission.parse::<RepoPermission>())
+            .collect::<Result<Vec<_>>>()?;
+        Ok(AuthorizationRule {
+            principal,
+            permissions,
+        })
+    }
+}
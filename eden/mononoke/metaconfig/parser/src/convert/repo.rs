@@ -6,6 +6,7 @@
  */
 
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::str::FromStr;
 use std::time::Duration;
 
@@ -17,13 +18,18 @@ use metaconfig_types::Address;
 use metaconfig_types::BlameVersion;
 use metaconfig_types::BookmarkOrRegex;
 use metaconfig_types::BookmarkParams;
+use metaconfig_types::BookmarkPrecondition;
 use metaconfig_types::CacheWarmupParams;
+use metaconfig_types::ClientVersionConfig;
 use metaconfig_types::CommitGraphConfig;
 use metaconfig_types::CommitIdentityScheme;
+use metaconfig_types::CommitSigningConfig;
 use metaconfig_types::ComparableRegex;
 use metaconfig_types::CrossRepoCommitValidation;
 use metaconfig_types::DerivedDataConfig;
 use metaconfig_types::DerivedDataTypesConfig;
+use metaconfig_types::FeatureRollout;
+use metaconfig_types::FilenodesConfig;
 use metaconfig_types::GlobalrevConfig;
 use metaconfig_types::HgSyncConfig;
 use metaconfig_types::HookBypass;
@@ -34,36 +40,54 @@ use metaconfig_types::InfinitepushNamespace;
 use metaconfig_types::InfinitepushParams;
 use metaconfig_types::LfsParams;
 use metaconfig_types::LoggingDestination;
+use metaconfig_types::MaintenanceWindow;
+use metaconfig_types::MirrorConfig;
+use metaconfig_types::PathLintConfig;
+use metaconfig_types::ProtectedTagNamespace;
 use metaconfig_types::PushParams;
+use metaconfig_types::PushToReviewConfig;
 use metaconfig_types::PushrebaseFlags;
 use metaconfig_types::PushrebaseParams;
 use metaconfig_types::PushrebaseRemoteMode;
 use metaconfig_types::RepoClientKnobs;
+use metaconfig_types::RepoView;
+use metaconfig_types::RequiredDerivedDataType;
+use metaconfig_types::RetryConfig;
 use metaconfig_types::SegmentedChangelogConfig;
 use metaconfig_types::SegmentedChangelogHeadConfig;
 use metaconfig_types::ServiceWriteRestrictions;
 use metaconfig_types::ShardedService;
 use metaconfig_types::ShardingModeConfig;
+use metaconfig_types::SloConfig;
 use metaconfig_types::SourceControlServiceMonitoring;
 use metaconfig_types::SourceControlServiceParams;
+use metaconfig_types::SparseExportConfig;
 use metaconfig_types::SparseProfilesConfig;
+use metaconfig_types::TagConfig;
 use metaconfig_types::UnodeVersion;
 use metaconfig_types::UpdateLoggingConfig;
 use metaconfig_types::WalkerConfig;
 use metaconfig_types::WalkerJobParams;
 use metaconfig_types::WalkerJobType;
+use metaconfig_types::WebhookConfig;
+use metaconfig_types::WireprotoCapabilitiesConfig;
 use mononoke_types::ChangesetId;
 use mononoke_types::MPath;
 use mononoke_types::PrefixTrie;
 use mononoke_types::RepositoryId;
 use regex::Regex;
 use repos::RawBookmarkConfig;
+use repos::RawBookmarkPrecondition;
 use repos::RawCacheWarmupConfig;
+use repos::RawClientVersionConfig;
 use repos::RawCommitGraphConfig;
+use repos::RawCommitSigningConfig;
 use repos::RawCommitIdentityScheme;
 use repos::RawCrossRepoCommitValidationConfig;
 use repos::RawDerivedDataConfig;
 use repos::RawDerivedDataTypesConfig;
+use repos::RawFeatureFlag;
+use repos::RawFilenodesConfig;
 use repos::RawHgSyncConfig;
 use repos::RawHookConfig;
 use repos::RawHookManagerParams;
@@ -71,23 +95,36 @@ use repos::RawInfinitepushParams;
 use repos::RawLfsParams;
 use repos::RawLoggingDestination;
 use repos::RawLoggingDestinationScribe;
+use repos::RawMaintenanceWindow;
+use repos::RawMirrorConfig;
+use repos::RawPathLintConfig;
+use repos::RawProtectedTagNamespace;
 use repos::RawPushParams;
+use repos::RawPushToReviewConfig;
 use repos::RawPushrebaseParams;
 use repos::RawPushrebaseRemoteMode;
 use repos::RawPushrebaseRemoteModeRemote;
 use repos::RawRepoClientKnobs;
+use repos::RawRepoView;
+use repos::RawRetryConfig;
 use repos::RawSegmentedChangelogConfig;
 use repos::RawSegmentedChangelogHeadConfig;
 use repos::RawServiceWriteRestrictions;
 use repos::RawShardedService;
 use repos::RawShardingModeConfig;
+use repos::RawSloConfig;
 use repos::RawSourceControlServiceMonitoring;
 use repos::RawSourceControlServiceParams;
+use repos::RawSparseExportConfig;
 use repos::RawSparseProfilesConfig;
+use repos::RawStatusCheckPrecondition;
+use repos::RawTagConfig;
 use repos::RawUpdateLoggingConfig;
 use repos::RawWalkerConfig;
 use repos::RawWalkerJobParams;
 use repos::RawWalkerJobType;
+use repos::RawWebhookConfig;
+use repos::RawWireprotoCapabilitiesConfig;
 
 use crate::convert::Convert;
 use crate::errors::ConfigurationError;
@@ -112,10 +149,23 @@ impl Convert for RawHookManagerParams {
     type Output = HookManagerParams;
 
     fn convert(self) -> Result<Self::Output> {
+        let max_concurrent_hooks = match self.max_concurrent_hooks {
+            Some(max) if max > 0 => Some(max.try_into()?),
+            Some(_) => return Err(anyhow!("max_concurrent_hooks must be positive")),
+            None => None,
+        };
+        let push_time_budget = self
+            .push_time_budget_ms
+            .map(|ms| ms.try_into().map(Duration::from_millis))
+            .transpose()
+            .context("push_time_budget_ms must not be negative")?;
+
         Ok(HookManagerParams {
             disable_acl_checker: self.disable_acl_checker,
             all_hooks_bypassed: self.all_hooks_bypassed,
             bypassed_commits_scuba_table: self.bypassed_commits_scuba_table,
+            max_concurrent_hooks,
+            push_time_budget,
         })
     }
 }
@@ -154,6 +204,10 @@ impl Convert for RawHookConfig {
             string_lists: self.config_string_lists.unwrap_or_default(),
             int_lists: self.config_int_lists.unwrap_or_default(),
             int_64_lists: self.config_int_64_lists.unwrap_or_default(),
+            content_byte_budget: self
+                .content_byte_budget
+                .map(|v| v.try_into())
+                .transpose()?,
         };
 
         Ok(HookParams {
@@ -195,6 +249,11 @@ impl Convert for RawBookmarkConfig {
             .transpose()?
             .map(ComparableRegex::new);
         let allowed_hipster_group = self.allowed_hipster_group;
+        let allowed_groups = self.allowed_groups.unwrap_or_default();
+        let allowed_service_identities = self
+            .allowed_service_identities
+            .unwrap_or_default()
+            .convert()?;
         let rewrite_dates = self.rewrite_dates;
         let hooks_skip_ancestors_of = self
             .hooks_skip_ancestors_of
@@ -206,6 +265,21 @@ impl Convert for RawBookmarkConfig {
         let allow_move_to_public_commits_without_hooks = self
             .allow_move_to_public_commits_without_hooks
             .unwrap_or(false);
+        let require_signed_commits = self.require_signed_commits.unwrap_or(false);
+        let pushrebase_only = self.pushrebase_only.unwrap_or(false);
+        let required_derived_data = self
+            .required_derived_data
+            .unwrap_or_default()
+            .iter()
+            .map(|name| name.parse())
+            .collect::<Result<Vec<RequiredDerivedDataType>>>()?;
+        let required_derived_data_budget = Duration::from_millis(
+            self.required_derived_data_budget_ms
+                .unwrap_or(0)
+                .try_into()
+                .context("required_derived_data_budget_ms must not be negative")?,
+        );
+        let preconditions = self.preconditions.unwrap_or_default().convert()?;
 
         Ok(BookmarkParams {
             bookmark: bookmark_or_regex,
@@ -213,14 +287,48 @@ impl Convert for RawBookmarkConfig {
             only_fast_forward,
             allowed_users,
             allowed_hipster_group,
+            allowed_groups,
+            allowed_service_identities,
             rewrite_dates,
             hooks_skip_ancestors_of,
             ensure_ancestor_of,
             allow_move_to_public_commits_without_hooks,
+            require_signed_commits,
+            pushrebase_only,
+            required_derived_data,
+            required_derived_data_budget,
+            preconditions,
         })
     }
 }
 
+impl Convert for RawBookmarkPrecondition {
+    type Output = BookmarkPrecondition;
+
+    fn convert(self) -> Result<Self::Output> {
+        let precondition = match self {
+            Self::descends_from_old(_) => BookmarkPrecondition::DescendsFromOld,
+            Self::status_check(RawStatusCheckPrecondition { url, timeout_ms }) => {
+                if url.is_empty() {
+                    return Err(anyhow!("status check url must not be empty"));
+                }
+                let timeout_ms = timeout_ms
+                    .unwrap_or(10_000)
+                    .try_into()
+                    .context("status check timeout_ms must not be negative")?;
+                BookmarkPrecondition::StatusCheck {
+                    url,
+                    timeout: Duration::from_millis(timeout_ms),
+                }
+            }
+            Self::UnknownField(f) => {
+                return Err(anyhow!("Unknown variant {} of RawBookmarkPrecondition", f));
+            }
+        };
+        Ok(precondition)
+    }
+}
+
 impl Convert for RawPushParams {
     type Output = PushParams;
 
@@ -297,6 +405,9 @@ impl Convert for RawPushrebaseParams {
                     .unwrap_or(default.flags.casefolding_check),
                 not_generated_filenodes_limit: 500,
                 monitoring_bookmark: self.monitoring_bookmark,
+                merge_non_overlapping_changes: self
+                    .merge_non_overlapping_changes
+                    .unwrap_or(default.flags.merge_non_overlapping_changes),
             },
             block_merges: self.block_merges.unwrap_or(default.block_merges),
             emit_obsmarkers: self.emit_obsmarkers.unwrap_or(default.emit_obsmarkers),
@@ -332,6 +443,7 @@ impl Convert for RawLfsParams {
             generate_lfs_blob_in_hg_sync_job: self
                 .generate_lfs_blob_in_hg_sync_job
                 .unwrap_or(false),
+            shared_storage: self.shared_storage.unwrap_or(false),
         })
     }
 }
@@ -449,6 +561,10 @@ impl Convert for RawDerivedDataTypesConfig {
             Some(2) => BlameVersion::V2,
             Some(version) => return Err(anyhow!("unknown blame version {}", version)),
         };
+        let unode_shard_threshold = self
+            .unode_shard_threshold
+            .map(|threshold| threshold.try_into())
+            .transpose()?;
         Ok(DerivedDataTypesConfig {
             types,
             mapping_key_prefixes,
@@ -456,6 +572,7 @@ impl Convert for RawDerivedDataTypesConfig {
             blame_filesize_limit,
             hg_set_committer_extra: self.hg_set_committer_extra.unwrap_or(false),
             blame_version,
+            unode_shard_threshold,
         })
     }
 }
@@ -700,6 +817,365 @@ impl Convert for RawCommitGraphConfig {
     fn convert(self) -> Result<Self::Output> {
         Ok(CommitGraphConfig {
             scuba_table: self.scuba_table,
+            use_prefix_filter: self.use_prefix_filter.unwrap_or(false),
+        })
+    }
+}
+
+impl Convert for RawCommitSigningConfig {
+    type Output = CommitSigningConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        Ok(CommitSigningConfig {
+            trusted_keys: self.trusted_keys.into_iter().collect(),
+        })
+    }
+}
+
+impl Convert for RawFeatureFlag {
+    type Output = FeatureRollout;
+
+    fn convert(self) -> Result<Self::Output> {
+        match (self.enabled, self.rollout_percentage) {
+            (Some(enabled), None) => Ok(FeatureRollout::Enabled(enabled)),
+            (None, Some(percentage)) if (0..=100).contains(&percentage) => {
+                Ok(FeatureRollout::Percentage(percentage as u32))
+            }
+            (None, Some(_)) => Err(anyhow!("rollout_percentage must be between 0 and 100")),
+            _ => Err(anyhow!("exactly one of enabled and rollout_percentage must be specified")),
+        }
+    }
+}
+
+impl Convert for RawWireprotoCapabilitiesConfig {
+    type Output = WireprotoCapabilitiesConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        let disabled: HashSet<String> = self.disabled.unwrap_or_default().into_iter().collect();
+        let extra = self.extra.unwrap_or_default();
+        let min_client_versions = self
+            .min_client_versions
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(capability, version)| Ok((capability, version.parse()?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+        for capability in disabled
+            .iter()
+            .chain(extra.iter())
+            .chain(min_client_versions.keys())
+        {
+            if capability.is_empty() || capability.contains(char::is_whitespace) {
+                return Err(anyhow!("invalid wireproto capability \"{}\"", capability));
+            }
+        }
+        if let Some(capability) = extra.iter().find(|cap| disabled.contains(cap.as_str())) {
+            return Err(anyhow!(
+                "wireproto capability {} is both disabled and extra",
+                capability
+            ));
+        }
+        Ok(WireprotoCapabilitiesConfig {
+            disabled,
+            extra,
+            min_client_versions,
+        })
+    }
+}
+
+impl Convert for RawClientVersionConfig {
+    type Output = ClientVersionConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        let reject_below = self.reject_below.map(|v| v.parse()).transpose()?;
+        let warn_below = self.warn_below.map(|v| v.parse()).transpose()?;
+        if let (Some(reject_below), Some(warn_below)) = (&reject_below, &warn_below) {
+            if warn_below < reject_below {
+                return Err(anyhow!(
+                    "warn_below ({}) must not be older than reject_below ({})",
+                    warn_below,
+                    reject_below
+                ));
+            }
+        }
+        Ok(ClientVersionConfig {
+            reject_below,
+            warn_below,
+            treat_unknown_as_outdated: self.treat_unknown_as_outdated.unwrap_or(false),
+            message: self.message,
+        })
+    }
+}
+
+impl Convert for RawMirrorConfig {
+    type Output = MirrorConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        if self.upstream_url.is_empty() {
+            return Err(anyhow!("mirror upstream_url must not be empty"));
+        }
+        Ok(MirrorConfig {
+            upstream_url: self.upstream_url,
+            upstream_repo_name: self.upstream_repo_name,
+        })
+    }
+}
+
+impl Convert for RawPathLintConfig {
+    type Output = PathLintConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        let max_path_length = self
+            .max_path_length
+            .map(|len| len.try_into())
+            .transpose()
+            .context("path lint max_path_length must be non-negative")?;
+        let disallowed_extensions = self
+            .disallowed_extensions
+            .unwrap_or_default()
+            .into_iter()
+            .map(|ext| ext.trim_start_matches('.').to_lowercase())
+            .collect();
+        Ok(PathLintConfig {
+            max_path_length,
+            forbidden_chars: self.forbidden_chars.unwrap_or_default(),
+            disallowed_extensions,
+            check_case_conflicts: self.check_case_conflicts.unwrap_or(false),
+            task_scribe_category: self.task_scribe_category,
+        })
+    }
+}
+
+impl Convert for RawPushToReviewConfig {
+    type Output = PushToReviewConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        if self.bookmark_prefix.is_empty() {
+            return Err(anyhow!("push to review bookmark_prefix must not be empty"));
+        }
+        if self.webhook_url.is_empty() {
+            return Err(anyhow!("push to review webhook_url must not be empty"));
+        }
+        Ok(PushToReviewConfig {
+            bookmark_prefix: self.bookmark_prefix,
+            webhook_url: self.webhook_url,
+        })
+    }
+}
+
+impl Convert for RawWebhookConfig {
+    type Output = WebhookConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        if self.url.is_empty() {
+            return Err(anyhow!("webhook url must not be empty"));
+        }
+        let events = self
+            .events
+            .iter()
+            .map(|event| event.parse())
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("Invalid events for webhook {}", self.url))?;
+        if events.is_empty() {
+            return Err(anyhow!("webhook {} has no events", self.url));
+        }
+        Ok(WebhookConfig {
+            url: self.url,
+            events,
+            secret: self.secret.filter(|secret| !secret.is_empty()),
+        })
+    }
+}
+
+impl Convert for RawTagConfig {
+    type Output = TagConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        Ok(TagConfig {
+            protected_namespaces: self.protected_namespaces.convert()?,
+        })
+    }
+}
+
+impl Convert for RawProtectedTagNamespace {
+    type Output = ProtectedTagNamespace;
+
+    fn convert(self) -> Result<Self::Output> {
+        if self.prefix.is_empty() {
+            return Err(anyhow!("protected tag namespace prefix must not be empty"));
+        }
+        let allowed_users = self
+            .allowed_users
+            .map(|re| Regex::new(&re))
+            .transpose()?
+            .map(ComparableRegex::new);
+        Ok(ProtectedTagNamespace {
+            prefix: self.prefix,
+            allowed_users,
+        })
+    }
+}
+
+impl Convert for RawMaintenanceWindow {
+    type Output = MaintenanceWindow;
+
+    fn convert(self) -> Result<Self::Output> {
+        const MAX_DURATION_MINS: u64 = 7 * 24 * 60;
+
+        let schedule = self.schedule.parse()?;
+        let duration_mins: u64 = self
+            .duration_mins
+            .try_into()
+            .context("maintenance window duration_mins must not be negative")?;
+        if duration_mins == 0 || duration_mins > MAX_DURATION_MINS {
+            return Err(anyhow!(
+                "maintenance window duration_mins must be between 1 and {}",
+                MAX_DURATION_MINS
+            ));
+        }
+        let operations = self
+            .operations
+            .iter()
+            .map(|operation| operation.parse())
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| {
+                format!("Invalid operations for maintenance window {}", self.schedule)
+            })?;
+        if operations.is_empty() {
+            return Err(anyhow!("maintenance window {} restricts no operations", self.schedule));
+        }
+        Ok(MaintenanceWindow {
+            schedule,
+            duration: Duration::from_secs(duration_mins * 60),
+            operations,
+            reason: self.reason,
+        })
+    }
+}
+
+impl Convert for RawFilenodesConfig {
+    type Output = FilenodesConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        Ok(FilenodesConfig {
+            filenode_free_serving: self.filenode_free_serving.unwrap_or(false),
+            disable_generation: self.disable_generation.unwrap_or(false),
+        })
+    }
+}
+
+impl Convert for RawRepoView {
+    type Output = RepoView;
+
+    fn convert(self) -> Result<Self::Output> {
+        if self.name.is_empty() {
+            return Err(anyhow!("repo view name must not be empty"));
+        }
+        let bookmarks = self
+            .bookmarks
+            .iter()
+            .map(|pattern| Regex::new(&format!("^(?:{})$", pattern)).map(ComparableRegex::new))
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("Invalid bookmarks for repo view {}", self.name))?;
+        if bookmarks.is_empty() {
+            return Err(anyhow!("repo view {} serves no bookmarks", self.name));
+        }
+        Ok(RepoView {
+            name: self.name,
+            bookmarks,
+        })
+    }
+}
+
+impl Convert for RawSparseExportConfig {
+    type Output = SparseExportConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        let allowed_paths = self
+            .allowed_paths
+            .iter()
+            .map(|path| MPath::new(path.as_bytes()))
+            .collect::<Result<Vec<_>>>()
+            .context("Invalid allowed path for sparse export")?;
+        if allowed_paths.is_empty() {
+            return Err(anyhow!("sparse export must allow at least one path"));
+        }
+        let bookmarks = self
+            .bookmarks
+            .into_iter()
+            .map(BookmarkKey::new)
+            .collect::<Result<Vec<_>>>()?;
+        if bookmarks.is_empty() {
+            return Err(anyhow!("sparse export must export at least one bookmark"));
+        }
+        Ok(SparseExportConfig {
+            export_repo_id: RepositoryId::new(self.export_repo_id),
+            allowed_paths,
+            bookmarks,
+        })
+    }
+}
+
+impl Convert for RawSloConfig {
+    type Output = SloConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        let availability_target_ppm = match self.availability_target_ppm {
+            Some(ppm) if (0..=1_000_000).contains(&ppm) => Some(ppm as u64),
+            Some(_) => return Err(anyhow!("availability_target_ppm must be between 0 and 1000000")),
+            None => None,
+        };
+        let method_p99_latency = self
+            .method_p99_latency_ms
+            .into_iter()
+            .map(|(method, ms)| {
+                let ms = ms.try_into().with_context(|| {
+                    format!("p99 latency target of {} must be non-negative", method)
+                })?;
+                Ok((method, Duration::from_millis(ms)))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+        let window = match self.window_secs {
+            Some(secs) if secs > 0 => Duration::from_secs(secs as u64),
+            Some(_) => return Err(anyhow!("SLO window_secs must be positive")),
+            None => SloConfig::default().window,
+        };
+        Ok(SloConfig {
+            availability_target_ppm,
+            method_p99_latency,
+            window,
+        })
+    }
+}
+
+impl Convert for RawRetryConfig {
+    type Output = RetryConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        let millis = |ms: i64| -> Result<Duration> {
+            Ok(Duration::from_millis(
+                ms.try_into()
+                    .context("retry config durations must be non-negative")?,
+            ))
+        };
+        let max_attempts: usize = self
+            .max_attempts
+            .try_into()
+            .context("max_attempts must be non-negative")?;
+        if max_attempts == 0 {
+            return Err(anyhow!("max_attempts must be at least 1"));
+        }
+        let retry_budget_percent = match self.retry_budget_percent {
+            Some(percent) if (0..=100).contains(&percent) => Some(percent as u64),
+            Some(_) => return Err(anyhow!("retry_budget_percent must be between 0 and 100")),
+            None => None,
+        };
+        Ok(RetryConfig {
+            max_attempts,
+            base_delay: millis(self.base_delay_ms.unwrap_or(100))?,
+            jitter: millis(self.jitter_ms.unwrap_or(50))?,
+            attempt_timeout: self.attempt_timeout_ms.map(millis).transpose()?,
+            operation_timeout: self.operation_timeout_ms.map(millis).transpose()?,
+            retry_budget_percent,
         })
     }
 }
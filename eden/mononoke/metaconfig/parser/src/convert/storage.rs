@@ -5,6 +5,7 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::BTreeMap;
 use std::num::NonZeroU64;
 use std::num::NonZeroUsize;
 use std::path::PathBuf;
@@ -26,6 +27,7 @@ use metaconfig_types::MultiplexId;
 use metaconfig_types::MultiplexedStoreType;
 use metaconfig_types::PackConfig;
 use metaconfig_types::PackFormat;
+use metaconfig_types::ReadConsistencyParams;
 use metaconfig_types::RemoteDatabaseConfig;
 use metaconfig_types::RemoteMetadataDatabaseConfig;
 use metaconfig_types::ShardableRemoteDatabaseConfig;
@@ -49,6 +51,7 @@ use repos::RawMetadataConfig;
 use repos::RawMultiplexedStoreNormal;
 use repos::RawMultiplexedStoreType;
 use repos::RawMultiplexedStoreWriteOnly;
+use repos::RawReadConsistencyParams;
 use repos::RawShardedDbConfig;
 use repos::RawStorageConfig;
 
@@ -142,25 +145,33 @@ impl Convert for RawBlobstoreConfig {
                     ));
                 }
 
+                let mut blobstore_regions = BTreeMap::new();
+                let blobstores = components
+                    .into_iter()
+                    .map(|comp| {
+                        let blobstore_id = BlobstoreId::new(comp.blobstore_id.try_into()?);
+                        if let Some(region) = comp.region {
+                            blobstore_regions.insert(blobstore_id, region);
+                        }
+                        Ok((
+                            blobstore_id,
+                            comp.store_type
+                                .convert()?
+                                .unwrap_or(MultiplexedStoreType::Normal),
+                            comp.blobstore.convert()?,
+                        ))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
                 BlobConfig::MultiplexedWal {
                     multiplex_id: MultiplexId::new(multiplex_id),
-                    blobstores: components
-                        .into_iter()
-                        .map(|comp| {
-                            Ok((
-                                BlobstoreId::new(comp.blobstore_id.try_into()?),
-                                comp.store_type
-                                    .convert()?
-                                    .unwrap_or(MultiplexedStoreType::Normal),
-                                comp.blobstore.convert()?,
-                            ))
-                        })
-                        .collect::<Result<Vec<_>>>()?,
+                    blobstores,
                     write_quorum,
                     queue_db: queue_db.convert()?,
                     inner_blobstores_scuba_table,
                     multiplex_scuba_table,
                     scuba_sample_rate: parse_scuba_sample_rate(scuba_sample_rate)?,
+                    blobstore_regions,
                 }
             }
 
@@ -331,6 +342,7 @@ impl Convert for RawMetadataConfig {
                     filenodes: raw.filenodes.convert()?,
                     mutation: raw.mutation.convert()?,
                     sparse_profiles: raw.sparse_profiles.convert()?,
+                    read_consistency: raw.read_consistency.convert()?.unwrap_or_default(),
                 },
             )),
             RawMetadataConfig::UnknownField(f) => Err(anyhow!(
@@ -341,12 +353,43 @@ impl Convert for RawMetadataConfig {
     }
 }
 
+impl Convert for RawReadConsistencyParams {
+    type Output = ReadConsistencyParams;
+
+    fn convert(self) -> Result<Self::Output> {
+        let duration = |ms: i64| -> Result<Duration> { Ok(Duration::from_millis(ms.try_into()?)) };
+        Ok(ReadConsistencyParams {
+            read_your_writes_window: self
+                .read_your_writes_window_ms
+                .map(duration)
+                .transpose()?
+                .unwrap_or_default(),
+            max_replica_lag: self.max_replica_lag_ms.map(duration).transpose()?,
+        })
+    }
+}
+
 impl Convert for RawFilestoreParams {
     type Output = FilestoreParams;
 
     fn convert(self) -> Result<Self::Output> {
+        let chunk_size = self.chunk_size.try_into()?;
+        let chunking_threshold = self
+            .chunking_threshold
+            .map(|threshold| threshold.try_into())
+            .transpose()?;
+        if let Some(threshold) = chunking_threshold {
+            if threshold < chunk_size {
+                bail!(
+                    "filestore chunking_threshold ({}) must not be smaller than chunk_size ({})",
+                    threshold,
+                    chunk_size
+                );
+            }
+        }
         Ok(FilestoreParams {
-            chunk_size: self.chunk_size.try_into()?,
+            chunk_size,
+            chunking_threshold,
             concurrency: self.concurrency.try_into()?,
         })
     }
@@ -9,16 +9,20 @@
 
 #![deny(missing_docs)]
 
+pub mod builder;
 pub mod config;
 mod convert;
 pub mod errors;
+mod migration;
 mod raw;
 
 pub use convert::Convert;
 
+pub use crate::builder::RepoConfigsBuilder;
 pub use crate::config::load_common_config;
 pub use crate::config::load_repo_configs;
 pub use crate::config::load_storage_configs;
 pub use crate::config::RepoConfigs;
 pub use crate::config::StorageConfigs;
 pub use crate::errors::ConfigurationError;
+pub use crate::migration::CURRENT_SCHEMA_VERSION;
@@ -0,0 +1,266 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Migration of configs written against older versions of the config schema.
+//!
+//! Each config file may declare the version of the schema it was written
+//! against in a top-level `config_schema_version`, and files that don't are
+//! taken to be written against the current version.  Files written against
+//! older versions are translated to the current version as they are loaded,
+//! with a warning for each deprecated field that is translated, so that the
+//! files of a config repo can be migrated one at a time.
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Result;
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
+
+/// The version of the config schema that configs are parsed as.
+pub const CURRENT_SCHEMA_VERSION: i64 = 2;
+
+/// The oldest version of the config schema that configs can be migrated
+/// from.
+const OLDEST_SCHEMA_VERSION: i64 = 1;
+
+pub(crate) const SCHEMA_VERSION_KEY: &str = "config_schema_version";
+
+/// What a config file contains, which determines where in it migrations look
+/// for deprecated fields.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ConfigFile {
+    /// All of the configs, in a single JSON file.
+    RepoConfigs,
+    /// The config of a repo.
+    RepoConfig,
+    /// Storage configs, keyed by name.
+    StorageConfigs,
+    /// Any other config, which no migration changes.
+    Other,
+}
+
+/// A migration translates a config file from one version of the schema to
+/// the next, adding a warning for each deprecated field it translates.
+type Migration = fn(&str, ConfigFile, &mut Value, &mut Vec<String>) -> Result<()>;
+
+/// The migration from each version, starting with `OLDEST_SCHEMA_VERSION`.
+const MIGRATIONS: &[Migration] = &[migrate_v1_to_v2];
+
+/// Take the schema version out of the config file `name`, and translate the
+/// config to the current version of the schema.
+pub(crate) fn migrate(
+    name: &str,
+    file: ConfigFile,
+    config: &mut Value,
+    warnings: &mut Vec<String>,
+) -> Result<()> {
+    let version = match config
+        .as_object_mut()
+        .and_then(|config| config.remove(SCHEMA_VERSION_KEY))
+    {
+        Some(version) => version
+            .as_i64()
+            .ok_or_else(|| anyhow!("{}: {} must be an integer", name, SCHEMA_VERSION_KEY))?,
+        None => return Ok(()),
+    };
+    if version > CURRENT_SCHEMA_VERSION {
+        bail!(
+            "{}: config schema version {} is newer than the newest supported version {}",
+            name,
+            version,
+            CURRENT_SCHEMA_VERSION
+        );
+    }
+    if version < OLDEST_SCHEMA_VERSION {
+        bail!(
+            "{}: config schema version {} is older than the oldest supported version {}",
+            name,
+            version,
+            OLDEST_SCHEMA_VERSION
+        );
+    }
+    if version < CURRENT_SCHEMA_VERSION {
+        warnings.push(format!(
+            "{}: config schema version {} is deprecated, the current version is {}",
+            name, version, CURRENT_SCHEMA_VERSION
+        ));
+    }
+    for migration in &MIGRATIONS[(version - OLDEST_SCHEMA_VERSION) as usize..] {
+        migration(name, file, config, warnings)?;
+    }
+    Ok(())
+}
+
+/// The storage configs in a config file, with where they are in it.
+fn storage_configs(file: ConfigFile, config: &mut Value) -> Vec<(String, &mut Value)> {
+    let storage_maps = match file {
+        ConfigFile::StorageConfigs => vec![(String::new(), Some(config))],
+        ConfigFile::RepoConfig => vec![(String::from("storage."), config.get_mut("storage"))],
+        ConfigFile::RepoConfigs => {
+            let config = match config.as_object_mut() {
+                Some(config) => config,
+                None => return Vec::new(),
+            };
+            let mut storage_maps = Vec::new();
+            for (key, value) in config.iter_mut() {
+                match key.as_str() {
+                    "storage" => storage_maps.push((String::from("storage."), Some(value))),
+                    "repos" => {
+                        let repos = value.as_object_mut().into_iter().flatten();
+                        for (repo, repo_config) in repos {
+                            let location = format!("repos.{}.storage.", repo);
+                            storage_maps.push((location, repo_config.get_mut("storage")));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            storage_maps
+        }
+        ConfigFile::Other => Vec::new(),
+    };
+    storage_maps
+        .into_iter()
+        .filter_map(|(prefix, storage_map)| Some((prefix, storage_map?.as_object_mut()?)))
+        .flat_map(|(prefix, storage_map)| {
+            storage_map
+                .iter_mut()
+                .map(move |(name, storage)| (format!("{}{}", prefix, name), storage))
+        })
+        .collect()
+}
+
+/// Version 2 replaced the `multiplexed` blobstore with `multiplexed_wal`, and
+/// moved the shard map of `mysql` blobstores into `remote`.
+fn migrate_v1_to_v2(
+    name: &str,
+    file: ConfigFile,
+    config: &mut Value,
+    warnings: &mut Vec<String>,
+) -> Result<()> {
+    for (location, storage) in storage_configs(file, config) {
+        if let Some(blobstore) = storage.get_mut("blobstore") {
+            let location = format!("{}: {}.blobstore", name, location);
+            migrate_blobstore_v1(&location, blobstore, warnings)?;
+        }
+        if let Some(blobstore) = storage.pointer_mut("/ephemeral_blobstore/blobstore") {
+            let location = format!("{}: {}.ephemeral_blobstore.blobstore", name, location);
+            migrate_blobstore_v1(&location, blobstore, warnings)?;
+        }
+    }
+    Ok(())
+}
+
+fn migrate_blobstore_v1(
+    location: &str,
+    blobstore: &mut Value,
+    warnings: &mut Vec<String>,
+) -> Result<()> {
+    let blobstore = match blobstore.as_object_mut() {
+        Some(blobstore) => blobstore,
+        None => return Ok(()),
+    };
+    if let Some(multiplexed) = blobstore.remove("multiplexed") {
+        let multiplexed = migrate_multiplexed_v1(location, multiplexed, warnings)?;
+        blobstore.insert("multiplexed_wal".to_string(), multiplexed);
+    }
+    for (kind, inner) in blobstore.iter_mut() {
+        match kind.as_str() {
+            "mysql" => migrate_mysql_v1(location, inner, warnings)?,
+            "logging" | "pack" => {
+                if let Some(inner) = inner.get_mut("blobstore") {
+                    let location = format!("{}.{}.blobstore", location, kind);
+                    migrate_blobstore_v1(&location, inner, warnings)?;
+                }
+            }
+            "multiplexed_wal" => {
+                let components = inner
+                    .get_mut("components")
+                    .and_then(Value::as_array_mut)
+                    .into_iter()
+                    .flatten();
+                for (index, component) in components.enumerate() {
+                    if let Some(inner) = component.get_mut("blobstore") {
+                        let location =
+                            format!("{}.{}.components[{}].blobstore", location, kind, index);
+                        migrate_blobstore_v1(&location, inner, warnings)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Rename the field `from` of `config` to `to`, if it is set.
+fn rename(config: &mut Map<String, Value>, from: &str, to: &str) {
+    if let Some(value) = config.remove(from) {
+        config.insert(to.to_string(), value);
+    }
+}
+
+fn migrate_multiplexed_v1(
+    location: &str,
+    multiplexed: Value,
+    warnings: &mut Vec<String>,
+) -> Result<Value> {
+    let mut multiplexed = match multiplexed {
+        Value::Object(multiplexed) => multiplexed,
+        _ => bail!("{}.multiplexed must be a table", location),
+    };
+    warnings.push(format!(
+        "{}: `multiplexed` is deprecated, translated to `multiplexed_wal`",
+        location
+    ));
+    rename(&mut multiplexed, "minimum_successful_writes", "write_quorum");
+    rename(&mut multiplexed, "scuba_table", "inner_blobstores_scuba_table");
+    if multiplexed.remove("not_present_read_quorum").is_some() {
+        warnings.push(format!(
+            "{}: `not_present_read_quorum` is no longer supported, and was ignored",
+            location
+        ));
+    }
+    // The queue of the old multiplex was never sharded.
+    if let Some(queue_db) = multiplexed
+        .get_mut("queue_db")
+        .and_then(Value::as_object_mut)
+    {
+        rename(queue_db, "remote", "unsharded");
+    }
+    Ok(Value::Object(multiplexed))
+}
+
+fn migrate_mysql_v1(location: &str, mysql: &mut Value, warnings: &mut Vec<String>) -> Result<()> {
+    let mysql = match mysql.as_object_mut() {
+        Some(mysql) => mysql,
+        None => return Ok(()),
+    };
+    let shard_map = match mysql.remove("mysql_shardmap") {
+        Some(shard_map) => shard_map,
+        None => return Ok(()),
+    };
+    if mysql.contains_key("remote") {
+        bail!(
+            "{}: `mysql.mysql_shardmap` and `mysql.remote` can't both be set",
+            location
+        );
+    }
+    warnings.push(format!(
+        "{}: `mysql.mysql_shardmap` is deprecated, translated to `mysql.remote`",
+        location
+    ));
+    let remote = match mysql.remove("mysql_shard_num") {
+        Some(shard_num) => json!({
+            "sharded": { "shard_map": shard_map, "shard_num": shard_num },
+        }),
+        None => json!({ "unsharded": { "db_address": shard_map } }),
+    };
+    mysql.insert("remote".to_string(), remote);
+    Ok(())
+}
@@ -23,13 +23,22 @@ use repos::RawRepoDefinitions;
 use repos::RawStorageConfig;
 
 use crate::errors::ConfigurationError;
+use crate::migration::migrate;
+use crate::migration::ConfigFile;
+use crate::migration::SCHEMA_VERSION_KEY;
 
 pub(crate) const CONFIGERATOR_PREFIX: &str = "configerator://";
 
+/// Read the raw configs, migrating files written against older versions of
+/// the config schema, and return them with warnings about the deprecated
+/// fields that were migrated.
+///
+/// Configs from configerator are always written against the current schema,
+/// so are never migrated.
 pub(crate) fn read_raw_configs(
     config_path: &Path,
     config_store: &ConfigStore,
-) -> Result<RawRepoConfigs> {
+) -> Result<(RawRepoConfigs, Vec<String>)> {
     if config_path.starts_with(CONFIGERATOR_PREFIX) {
         let cfg_path = config_path
             .strip_prefix(CONFIGERATOR_PREFIX)?
@@ -38,12 +47,16 @@ pub(crate) fn read_raw_configs(
         let arc_conf = config_store
             .get_config_handle::<RawRepoConfigs>(cfg_path)?
             .get();
-        Ok((*arc_conf).clone())
+        Ok(((*arc_conf).clone(), Vec::new()))
     } else if config_path.is_dir() {
         read_raw_configs_toml(config_path)
     } else if config_path.is_file() {
         let repo_configs = std::fs::read(config_path)?;
-        Ok(serde_json::from_slice(&repo_configs)?)
+        let mut repo_configs: serde_json::Value = serde_json::from_slice(&repo_configs)?;
+        let mut warnings = Vec::new();
+        let name = config_path.display().to_string();
+        migrate(&name, ConfigFile::RepoConfigs, &mut repo_configs, &mut warnings)?;
+        Ok((serde_json::from_value(repo_configs)?, warnings))
     } else {
         Err(ConfigurationError::InvalidFileStructure(format!(
             "{} does not exist",
@@ -53,21 +66,28 @@ pub(crate) fn read_raw_configs(
     }
 }
 
-fn read_raw_configs_toml(config_path: &Path) -> Result<RawRepoConfigs> {
+pub(crate) fn read_raw_configs_toml(config_path: &Path) -> Result<(RawRepoConfigs, Vec<String>)> {
+    let mut warnings = Vec::new();
     let commit_sync = read_toml_path::<HashMap<String, RawCommitSyncConfig>>(
         config_path
             .join("common")
             .join("commitsyncmap.toml")
             .as_path(),
         false,
+        ConfigFile::Other,
+        &mut warnings,
     )?;
     let common = read_toml_path::<RawCommonConfig>(
         config_path.join("common").join("common.toml").as_path(),
         true,
+        ConfigFile::Other,
+        &mut warnings,
     )?;
     let storage = read_toml_path::<HashMap<String, RawStorageConfig>>(
         config_path.join("common").join("storage.toml").as_path(),
         true,
+        ConfigFile::StorageConfigs,
+        &mut warnings,
     )?;
     let acl_region_configs = read_toml_path::<HashMap<String, RawAclRegionConfig>>(
         config_path
@@ -75,6 +95,8 @@ fn read_raw_configs_toml(config_path: &Path) -> Result<RawRepoConfigs> {
             .join("acl_regions.toml")
             .as_path(),
         true,
+        ConfigFile::Other,
+        &mut warnings,
     )?;
 
     let mut repo_definitions_map = HashMap::new();
@@ -104,6 +126,8 @@ fn read_raw_configs_toml(config_path: &Path) -> Result<RawRepoConfigs> {
         let repo_definition = read_toml_path::<RawRepoDefinition>(
             repo_definition_path.join("server.toml").as_path(),
             false,
+            ConfigFile::Other,
+            &mut warnings,
         )?;
         repo_definitions_map.insert(reponame, repo_definition);
     }
@@ -135,22 +159,33 @@ fn read_raw_configs_toml(config_path: &Path) -> Result<RawRepoConfigs> {
 
         let reponame = decode_repo_name(reponame)?;
 
-        let repo_config =
-            read_toml_path::<RawRepoConfig>(repo_config_path.join("server.toml").as_path(), false)?;
+        let repo_config = read_toml_path::<RawRepoConfig>(
+            repo_config_path.join("server.toml").as_path(),
+            false,
+            ConfigFile::RepoConfig,
+            &mut warnings,
+        )?;
         repos.insert(reponame, repo_config);
     }
 
-    Ok(RawRepoConfigs {
+    let raw_repo_configs = RawRepoConfigs {
         commit_sync,
         common,
         repos,
         storage,
         acl_region_configs,
         repo_definitions,
-    })
+        config_schema_version: None,
+    };
+    Ok((raw_repo_configs, warnings))
 }
 
-fn read_toml_path<T>(path: &Path, defaults: bool) -> Result<T>
+fn read_toml_path<T>(
+    path: &Path,
+    defaults: bool,
+    file: ConfigFile,
+    warnings: &mut Vec<String>,
+) -> Result<T>
 where
     T: serde::de::DeserializeOwned + Default,
 {
@@ -166,29 +201,50 @@ where
         .into());
     }
     let content = std::fs::read(path)?;
-    read_toml::<T>(&content)
+    read_toml::<T>(&path.display().to_string(), file, &content, warnings)
 }
 
 /// Helper to read toml files which throws an error upon encountering
-/// unknown keys
-pub(crate) fn read_toml<T>(bytes: &[u8]) -> Result<T>
+/// unknown keys.  Files that declare a schema version are migrated to the
+/// current version first, and other files are read directly, so that errors
+/// in them keep their line numbers.
+pub(crate) fn read_toml<T>(
+    name: &str,
+    file: ConfigFile,
+    bytes: &[u8],
+    warnings: &mut Vec<String>,
+) -> Result<T>
 where
     T: serde::de::DeserializeOwned,
 {
     match std::str::from_utf8(bytes) {
         Ok(s) => {
-            let mut unused = BTreeSet::new();
-            let de = &mut toml::de::Deserializer::new(s);
-            let t: T = serde_ignored::deserialize(de, |path| {
-                unused.insert(path.to_string());
-            })?;
-
-            if !unused.is_empty() {
-                return Err(anyhow!("unknown keys in config parsing: `{:?}`", unused));
+            let value: toml::Value = toml::from_str(s)?;
+            if value.get(SCHEMA_VERSION_KEY).is_none() {
+                return deserialize_strict(&mut toml::de::Deserializer::new(s));
             }
-
-            Ok(t)
+            let mut value = serde_json::to_value(value)?;
+            migrate(name, file, &mut value, warnings)?;
+            deserialize_strict(value)
         }
         Err(e) => Err(anyhow!("error parsing toml: {}", e)),
     }
 }
+
+fn deserialize_strict<'de, T, D>(de: D) -> Result<T>
+where
+    T: serde::de::Deserialize<'de>,
+    D: serde::de::Deserializer<'de>,
+    D::Error: Send + Sync + 'static,
+{
+    let mut unused = BTreeSet::new();
+    let t: T = serde_ignored::deserialize(de, |path| {
+        unused.insert(path.to_string());
+    })?;
+
+    if !unused.is_empty() {
+        return Err(anyhow!("unknown keys in config parsing: `{:?}`", unused));
+    }
+
+    Ok(t)
+}
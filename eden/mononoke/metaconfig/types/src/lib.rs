@@ -10,6 +10,8 @@
 
 #![deny(missing_docs)]
 
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt;
@@ -22,14 +24,17 @@ use std::str::FromStr;
 use std::time::Duration;
 
 use anyhow::anyhow;
+use anyhow::Context;
 use anyhow::Error;
 use anyhow::Result;
 use ascii::AsciiString;
 use bookmarks_types::BookmarkKey;
 use derive_more::From;
 use derive_more::Into;
+use mononoke_types::hash;
 use mononoke_types::BonsaiChangeset;
 use mononoke_types::ChangesetId;
+use mononoke_types::DateTime;
 use mononoke_types::MPath;
 use mononoke_types::PrefixTrie;
 use mononoke_types::RepositoryId;
@@ -127,6 +132,133 @@ pub struct CommonConfig {
     pub redaction_config: RedactionConfig,
     /// Service identity for interal Mononoke services.
     pub internal_identity: Identity,
+    /// Identities that are permitted to perform administrative mutations
+    /// through the control API.
+    pub admin_allowlist: Vec<Identity>,
+    /// Additional ways of authenticating clients.
+    pub authentication_config: AuthenticationConfig,
+    /// Permissions granted on all repos, in addition to those granted by
+    /// each repo's ACL.
+    pub authorization_rules: Vec<AuthorizationRule>,
+}
+
+/// A permission to operate on a repo.  Each permission implies the ones
+/// that are less than it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub enum RepoPermission {
+    /// Read the repo.
+    Read,
+    /// Push draft commits and modify scratch bookmarks.
+    DraftPush,
+    /// Push public commits and modify public bookmarks.
+    PublicPush,
+    /// Administer the repo, including bypassing its read-only state.
+    Admin,
+}
+
+impl RepoPermission {
+    /// Name of the permission in config, and of the action it is checked
+    /// with in tier ACLs.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepoPermission::Read => "read",
+            RepoPermission::DraftPush => "draft_push",
+            RepoPermission::PublicPush => "public_push",
+            RepoPermission::Admin => "admin",
+        }
+    }
+
+    /// Returns true if holding this permission grants `other`.
+    pub fn implies(&self, other: RepoPermission) -> bool {
+        *self >= other
+    }
+}
+
+impl fmt::Display for RepoPermission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for RepoPermission {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(RepoPermission::Read),
+            "draft_push" => Ok(RepoPermission::DraftPush),
+            "public_push" => Ok(RepoPermission::PublicPush),
+            "admin" => Ok(RepoPermission::Admin),
+            _ => Err(anyhow!("unknown repo permission: {}", s)),
+        }
+    }
+}
+
+/// Who an authorization rule grants permissions to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AuthorizationPrincipal {
+    /// A single identity.
+    Identity(Identity),
+    /// The identities that the named tier's ACL allows to perform the
+    /// action named after each permission.
+    Tier(String),
+}
+
+/// Permissions granted on all repos to an identity or tier.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AuthorizationRule {
+    /// Who the permissions are granted to.
+    pub principal: AuthorizationPrincipal,
+    /// The permissions granted.
+    pub permissions: Vec<RepoPermission>,
+}
+
+/// Configuration for authenticating clients, beyond the subject name of
+/// their TLS client certificate.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct AuthenticationConfig {
+    /// Mappings from client certificate subject alternative names to
+    /// identities.
+    pub cert_identities: Vec<CertIdentityMapping>,
+    /// Issuers of JSON web tokens that clients can authenticate with.
+    pub jwt_issuers: Vec<JwtIssuer>,
+}
+
+/// Kind of subject alternative name in a certificate.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SubjectAltNameType {
+    /// A DNS name.
+    Dns,
+    /// A URI, e.g. a SPIFFE id.
+    Uri,
+    /// An email address.
+    Email,
+}
+
+/// Mapping from a client certificate subject alternative name to an
+/// identity.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CertIdentityMapping {
+    /// The kind of subject alternative name to map.
+    pub san_type: SubjectAltNameType,
+    /// Only names with this prefix are mapped.  The prefix is removed to
+    /// form the identity data.
+    pub prefix: String,
+    /// The type of the resulting identity.
+    pub identity_type: String,
+}
+
+/// An issuer of JSON web tokens.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct JwtIssuer {
+    /// Required value of the `iss` claim.
+    pub issuer: String,
+    /// PEM-encoded public key that signs the tokens.
+    pub public_key: String,
+    /// Required value of the `aud` claim, if any.
+    pub audience: Option<String>,
+    /// Map from claim name to the type of identity it provides.
+    pub claim_identities: HashMap<String, String>,
 }
 
 /// Configuration for logging of censored blobstore accesses
@@ -228,6 +360,120 @@ pub struct RepoConfig {
     /// deep-sharded: In addition to requests, repo is also sharded, i.e. present
     /// on select servers.
     pub deep_sharding_config: Option<ShardingModeConfig>,
+    /// Retry and timeout policy for calls to the repo's blobstore.
+    pub blobstore_retry_config: Option<RetryConfig>,
+    /// Keys trusted to sign commits in this repo.
+    pub commit_signing_config: CommitSigningConfig,
+    /// Bookmarks whose ancestors are public.  If empty, all bookmarks of a
+    /// publishing kind make their ancestors public.
+    pub publishing_bookmarks: Vec<BookmarkKey>,
+    /// Timeouts of wireproto commands.
+    pub wireproto_timeouts: WireprotoTimeouts,
+    /// Feature flags, keyed by feature name.
+    pub features: HashMap<String, FeatureRollout>,
+    /// Capabilities advertised to wireproto clients.
+    pub wireproto_capabilities: WireprotoCapabilitiesConfig,
+    /// Minimum versions of clients allowed to connect.
+    pub client_versions: ClientVersionConfig,
+    /// If set, this repo is a read-only mirror of a repo on another
+    /// Mononoke.
+    pub mirror_config: Option<MirrorConfig>,
+    /// Path policies that the whole working copy is checked against by the
+    /// path lint job.
+    pub path_lint_config: Option<PathLintConfig>,
+    /// If set, pushes to review bookmarks create code reviews.
+    pub push_to_review_config: Option<PushToReviewConfig>,
+    /// Webhooks that repo events are posted to.
+    pub webhooks: Vec<WebhookConfig>,
+    /// Who may create tags.
+    pub tag_config: TagConfig,
+    /// Service level objectives that requests to the repo are tracked
+    /// against.
+    pub slo_config: SloConfig,
+    /// Recurring windows during which some operations on the repo are
+    /// restricted.
+    pub maintenance_windows: Vec<MaintenanceWindow>,
+    /// How hg filenodes are generated and served.
+    pub filenodes_config: FilenodesConfig,
+    /// Views of this repo, which are served under their own names.
+    pub views: Vec<RepoView>,
+    /// If set, this is the config of a view of another repo, which has the
+    /// other repo's id and storage.
+    pub view: Option<RepoView>,
+    /// If set, a copy of this repo that only contains some of its paths is
+    /// exported to another repo.
+    pub sparse_export_config: Option<SparseExportConfig>,
+}
+
+/// How widely a feature is enabled.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FeatureRollout {
+    /// Enabled or disabled for all requests.
+    Enabled(bool),
+    /// Enabled for this percentage of requests.
+    Percentage(u32),
+}
+
+/// Wireproto commands whose timeouts can be configured.
+pub const WIREPROTO_COMMANDS: &[&str] = &[
+    "between",
+    "clienttelemetry",
+    "clonebundles",
+    "getbundle",
+    "getcommitdata",
+    "getpackv1",
+    "getpackv2",
+    "gettreepack",
+    "heads",
+    "hello",
+    "known",
+    "knownnodes",
+    "listkeys",
+    "listkeyspatterns",
+    "lookup",
+    "stream_out_shallow",
+    "unbundle",
+];
+
+/// Timeouts of wireproto commands configured for a repo.  Commands are
+/// cancelled once they have run for longer than their timeout.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WireprotoTimeouts {
+    /// Timeout of commands that have neither their own configured timeout
+    /// nor a built-in one.
+    pub default: Option<Duration>,
+    /// Timeouts of individual commands, keyed by command name.
+    pub commands: HashMap<String, Duration>,
+}
+
+/// Built-in timeout of commands that have neither a configured timeout
+/// nor their own built-in one.
+pub const DEFAULT_WIREPROTO_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+impl WireprotoTimeouts {
+    /// The configured timeout of a command, or None if it has the built-in
+    /// timeout.
+    pub fn configured_timeout(&self, command: &str) -> Option<Duration> {
+        match self.commands.get(command) {
+            Some(timeout) => Some(*timeout),
+            None if Self::builtin_command_timeout(command).is_some() => None,
+            None => self.default,
+        }
+    }
+
+    /// The built-in timeout of a command.
+    pub fn builtin_timeout(command: &str) -> Duration {
+        Self::builtin_command_timeout(command).unwrap_or(DEFAULT_WIREPROTO_TIMEOUT)
+    }
+
+    fn builtin_command_timeout(command: &str) -> Option<Duration> {
+        match command {
+            "getbundle" => Some(Duration::from_secs(30 * 60)),
+            "getpackv1" | "getpackv2" => Some(Duration::from_secs(5 * 60 * 60)),
+            "stream_out_shallow" => Some(Duration::from_secs(4 * 60 * 60)),
+            _ => None,
+        }
+    }
 }
 
 /// Config determining if the repo is deep sharded in the context of a service.
@@ -369,6 +615,13 @@ pub struct DerivedDataTypesConfig {
 
     /// What blame version should be used.
     pub blame_version: BlameVersion,
+
+    /// Manifest unodes for directories with more than this many entries
+    /// are stored sharded across several blobs.  Sharded and unsharded
+    /// unodes can both be read, so changing this only affects unodes
+    /// derived afterwards.  To reshard existing unodes, backfill them with
+    /// a new entry in `mapping_key_prefixes` and switch to it.
+    pub unode_shard_threshold: Option<usize>,
 }
 
 /// What type of unode derived data to generate
@@ -396,6 +649,27 @@ impl RepoConfig {
     pub fn primary_metadata_db_address(&self) -> Option<String> {
         self.storage_config.metadata.primary_address()
     }
+
+    /// Returns whether a feature is enabled for a request, identified by its
+    /// `correlator`.  Features that aren't configured are disabled.  For
+    /// features rolled out to a percentage of requests, the decision is a
+    /// stable hash of the feature and the correlator, so that all the
+    /// commands of a request get the same behaviour from every server.
+    pub fn feature_enabled(&self, feature: &str, correlator: &str) -> bool {
+        match self.features.get(feature) {
+            None => false,
+            Some(FeatureRollout::Enabled(enabled)) => *enabled,
+            Some(FeatureRollout::Percentage(percentage)) => {
+                let mut context = hash::Context::new(b"feature_rollout");
+                context.update(feature);
+                context.update([0]);
+                context.update(correlator);
+                let digest = context.finish().into_inner();
+                let bucket = u64::from_be_bytes(digest[..8].try_into().unwrap()) % 100;
+                bucket < *percentage as u64
+            }
+        }
+    }
 }
 
 #[derive(Eq, Copy, Clone, Debug, Default, PartialEq, Deserialize)]
@@ -412,12 +686,62 @@ pub enum Redaction {
 /// Is the repo read-only?
 pub enum RepoReadOnly {
     /// This repo is read-only and should not accept pushes or other writes
-    ReadOnly(String),
+    ReadOnly(ReadOnlyInfo),
     /// This repo should accept writes.
     #[default]
     ReadWrite,
 }
 
+/// Why a repo is read-only, as reported to users whose writes are rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadOnlyInfo {
+    /// Human-readable reason the repo is read-only.
+    pub reason: String,
+    /// Who made the repo read-only, if known.
+    pub locked_by: Option<String>,
+    /// When the repo is expected to accept writes again, as seconds since
+    /// the Unix epoch, if known.
+    pub expected_unlock: Option<i64>,
+    /// Where writes should be sent instead, if the repo is a mirror of
+    /// another Mononoke.
+    pub redirect_url: Option<String>,
+}
+
+impl ReadOnlyInfo {
+    /// Read-only info with only a reason.
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+            locked_by: None,
+            expected_unlock: None,
+            redirect_url: None,
+        }
+    }
+}
+
+impl fmt::Display for ReadOnlyInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.reason)?;
+        if let Some(locked_by) = &self.locked_by {
+            write!(f, " (locked by {})", locked_by)?;
+        }
+        if let Some(expected_unlock) = self.expected_unlock {
+            match DateTime::from_timestamp(expected_unlock, 0) {
+                Ok(time) => write!(
+                    f,
+                    " (expected to be unlocked at {})",
+                    time.as_chrono().to_rfc3339()
+                )?,
+                Err(_) => write!(f, " (expected to be unlocked at {})", expected_unlock)?,
+            }
+        }
+        if let Some(redirect_url) = &self.redirect_url {
+            write!(f, " (send writes to {})", redirect_url)?;
+        }
+        Ok(())
+    }
+}
+
 /// Configuration of warming up the Mononoke cache. This warmup happens on startup
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct CacheWarmupParams {
@@ -439,6 +763,11 @@ pub struct HookManagerParams {
     pub all_hooks_bypassed: bool,
     /// Scuba table for bypassed commits logging.
     pub bypassed_commits_scuba_table: Option<String>,
+    /// Maximum number of hooks to run at the same time on a push.  All
+    /// hooks run at the same time if this is not set.
+    pub max_concurrent_hooks: Option<usize>,
+    /// Maximum time that running the hooks on a push can take.
+    pub push_time_budget: Option<Duration>,
 }
 
 /// Configuration might be done for a single bookmark or for all bookmarks matching a regex
@@ -489,6 +818,11 @@ pub struct BookmarkParams {
     /// Only users matching this pattern or hipster group will be allowed to
     /// move this bookmark
     pub allowed_hipster_group: Option<String>,
+    /// Members of these groups are also allowed to move this bookmark
+    pub allowed_groups: Vec<String>,
+    /// Callers with these service identities are also allowed to move this
+    /// bookmark
+    pub allowed_service_identities: Vec<Identity>,
     /// Skip hooks for changesets that are already ancestors of these
     /// bookmarks
     pub hooks_skip_ancestors_of: Vec<BookmarkKey>,
@@ -501,6 +835,87 @@ pub struct BookmarkParams {
     /// because commit is already public, meaning that hooks already
     /// should have been run when the commit was first made public.
     pub allow_move_to_public_commits_without_hooks: bool,
+    /// Only allow this bookmark to be moved to commits that have a valid
+    /// signature from one of the repo's trusted keys.
+    pub require_signed_commits: bool,
+    /// Only allow this bookmark to be moved by pushrebase, unless the
+    /// caller is allowed to override this restriction.
+    pub pushrebase_only: bool,
+    /// Only allow this bookmark to be moved to commits that have these
+    /// derived data types derived
+    pub required_derived_data: Vec<RequiredDerivedDataType>,
+    /// How long to spend deriving required derived data that isn't yet
+    /// derived during a bookmark move
+    pub required_derived_data_budget: Duration,
+    /// Conditions that direct moves of this bookmark must satisfy
+    pub preconditions: Vec<BookmarkPrecondition>,
+}
+
+/// A condition that a direct move of a bookmark must satisfy
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum BookmarkPrecondition {
+    /// The new target of the bookmark must descend from its old target
+    DescendsFromOld,
+    /// An external status check must report the new target as green
+    StatusCheck {
+        /// URL that the repo, bookmark and new target are posted to
+        url: String,
+        /// How long to wait for the status check to respond
+        timeout: Duration,
+    },
+}
+
+/// Derived data types that a bookmark can require to be derived for the
+/// commits it is moved to
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum RequiredDerivedDataType {
+    /// Changeset info
+    ChangesetInfo,
+    /// Fsnodes
+    Fsnodes,
+    /// Mercurial changesets
+    HgChangesets,
+    /// Skeleton manifests
+    SkeletonManifests,
+    /// Unodes
+    Unodes,
+}
+
+impl RequiredDerivedDataType {
+    /// The name of the derived data type
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequiredDerivedDataType::ChangesetInfo => "changeset_info",
+            RequiredDerivedDataType::Fsnodes => "fsnodes",
+            RequiredDerivedDataType::HgChangesets => "hgchangesets",
+            RequiredDerivedDataType::SkeletonManifests => "skeleton_manifests",
+            RequiredDerivedDataType::Unodes => "unodes",
+        }
+    }
+}
+
+impl fmt::Display for RequiredDerivedDataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for RequiredDerivedDataType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "changeset_info" => Ok(RequiredDerivedDataType::ChangesetInfo),
+            "fsnodes" => Ok(RequiredDerivedDataType::Fsnodes),
+            "hgchangesets" => Ok(RequiredDerivedDataType::HgChangesets),
+            "skeleton_manifests" => Ok(RequiredDerivedDataType::SkeletonManifests),
+            "unodes" => Ok(RequiredDerivedDataType::Unodes),
+            _ => Err(anyhow!(
+                "Derived data type '{}' can't be required for bookmarks",
+                s
+            )),
+        }
+    }
 }
 
 /// The type of the hook
@@ -592,6 +1007,8 @@ pub struct HookConfig {
     pub int_lists: HashMap<String, Vec<i32>>,
     /// Map of config to it's value. Values here are lists of 64bit integers
     pub int_64_lists: HashMap<String, Vec<i64>>,
+    /// Maximum number of bytes of file content the hook may read per push
+    pub content_byte_budget: Option<u64>,
 }
 
 /// Configuration for a hook
@@ -633,6 +1050,9 @@ pub struct PushrebaseFlags {
     pub not_generated_filenodes_limit: u64,
     /// Which bookmark to track in ODS
     pub monitoring_bookmark: Option<String>,
+    /// Whether to merge conflicting changes to the same text file, if they
+    /// change different lines, rather than failing the pushrebase.
+    pub merge_non_overlapping_changes: bool,
 }
 
 impl Default for PushrebaseFlags {
@@ -644,6 +1064,7 @@ impl Default for PushrebaseFlags {
             casefolding_check: true,
             not_generated_filenodes_limit: 500,
             monitoring_bookmark: None,
+            merge_non_overlapping_changes: false,
         }
     }
 }
@@ -723,6 +1144,10 @@ pub struct LfsParams {
     pub rollout_percentage: u32,
     /// Whether hg sync job should generate lfs blobs
     pub generate_lfs_blob_in_hg_sync_job: bool,
+    /// Whether LFS content is stored once in storage shared with other repos,
+    /// rather than in the repo's own storage.  All readers of the repo then
+    /// fall back to the shared storage for content the repo doesn't have.
+    pub shared_storage: bool,
 }
 
 /// Id used to discriminate diffirent underlying blobstore instances
@@ -866,6 +1291,9 @@ pub enum BlobConfig {
         multiplex_scuba_table: Option<String>,
         /// Used for both scuba tables. Write queries and read failures are not sampled.
         scuba_sample_rate: NonZeroU64,
+        /// The regions of the blobstores that have one configured.  Reads
+        /// prefer the blobstores in the same region as the host.
+        blobstore_regions: BTreeMap<BlobstoreId, String>,
     },
     /// Store in a manifold bucket, but every object will have an expiration
     ManifoldWithTtl {
@@ -1020,6 +1448,22 @@ pub struct RemoteMetadataDatabaseConfig {
     pub mutation: RemoteDatabaseConfig,
     /// Database for sparse profiles sizes.
     pub sparse_profiles: RemoteDatabaseConfig,
+    /// When reads of the primary metadata should go to the primary
+    /// database instead of replicas.
+    pub read_consistency: ReadConsistencyParams,
+}
+
+/// Thresholds for routing reads that would normally go to replicas to the
+/// primary database instead.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct ReadConsistencyParams {
+    /// Reads by a session within this time of its last write go to the
+    /// primary, or for as long as the replicas are lagging, if longer.
+    /// Zero disables read-your-writes routing.
+    pub read_your_writes_window: Duration,
+    /// If set, all reads go to the primary while the replicas lag by more
+    /// than this.
+    pub max_replica_lag: Option<Duration>,
 }
 
 /// Configuration for the Metadata database
@@ -1137,6 +1581,9 @@ pub struct InfinitepushParams {
 pub struct FilestoreParams {
     /// Chunk size for the Filestore, in bytes.
     pub chunk_size: u64,
+    /// Files larger than this many bytes are chunked.  Defaults to the
+    /// chunk size, and can't be smaller than it.
+    pub chunking_threshold: Option<u64>,
     /// Max number of concurrent chunk uploads to perform in the Filestore.
     pub concurrency: usize,
 }
@@ -1711,4 +2158,545 @@ pub struct UpdateLoggingConfig {
 pub struct CommitGraphConfig {
     /// Scuba table to log commit graph operations to
     pub scuba_table: Option<String>,
+    /// Whether to keep a bloom filter of changeset ids in memory, so that
+    /// lookups of changesets that don't exist don't reach the database.
+    /// The filter is only used once it has been persisted by the
+    /// `commit-graph update-prefix-filter` admin command, which should be
+    /// run periodically.
+    pub use_prefix_filter: bool,
+}
+
+/// Retry and timeout policy for calls to a storage backend
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RetryConfig {
+    /// Maximum number of attempts, including the first
+    pub max_attempts: usize,
+    /// Delay before the first retry, doubled for each subsequent retry
+    pub base_delay: Duration,
+    /// Maximum random jitter added to each delay
+    pub jitter: Duration,
+    /// Timeout for each attempt
+    pub attempt_timeout: Option<Duration>,
+    /// Timeout for the whole operation, including all retries
+    pub operation_timeout: Option<Duration>,
+    /// Percentage of calls that may be retried, or None if retries are not
+    /// limited
+    pub retry_budget_percent: Option<u64>,
+}
+
+/// Keys trusted to sign commits
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CommitSigningConfig {
+    /// Public keys trusted to sign commits, indexed by key id.  Keys are in
+    /// PEM or OpenSSH format, or are armored OpenPGP key blocks.
+    pub trusted_keys: HashMap<String, String>,
+}
+
+/// Capabilities advertised to wireproto clients, on top of the ones the
+/// server advertises by default.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct WireprotoCapabilitiesConfig {
+    /// Wireproto and bundle2 capabilities that are not advertised
+    pub disabled: HashSet<String>,
+    /// Additional wireproto capabilities, advertised verbatim
+    pub extra: Vec<String>,
+    /// Minimum client version for a capability, keyed by capability name.
+    /// The capability is not advertised to older clients, or to clients
+    /// whose version is unknown.
+    pub min_client_versions: HashMap<String, ClientVersion>,
+}
+
+/// Minimum versions of clients allowed to connect to a repo.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ClientVersionConfig {
+    /// Clients older than this are rejected
+    pub reject_below: Option<ClientVersion>,
+    /// Clients older than this are warned that their version is deprecated
+    pub warn_below: Option<ClientVersion>,
+    /// Whether clients that don't report their version are warned that it
+    /// is deprecated.  They are never rejected, as clients connecting over
+    /// SSH can't report their version.
+    pub treat_unknown_as_outdated: bool,
+    /// Message shown to outdated clients, e.g. how to upgrade
+    pub message: Option<String>,
+}
+
+/// Configuration of a repo that mirrors a repo on another Mononoke.  The
+/// mirror serves reads locally, but rejects writes, which must be sent to
+/// the upstream.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MirrorConfig {
+    /// Base URL of the upstream Mononoke's EdenAPI server.
+    pub upstream_url: String,
+    /// Name of the repo on the upstream, if it differs from this repo's
+    /// name.
+    pub upstream_repo_name: Option<String>,
+}
+
+impl MirrorConfig {
+    /// Read-only info reported when writes to the mirror are rejected.
+    pub fn read_only_info(&self) -> ReadOnlyInfo {
+        ReadOnlyInfo {
+            redirect_url: Some(self.upstream_url.clone()),
+            ..ReadOnlyInfo::new("This repo is a read-only mirror")
+        }
+    }
+}
+
+/// Path policies for the files in a repo.  Pushes are checked by hooks, but
+/// the path lint job checks the whole working copy, so that violations that
+/// predate the hooks are found too.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct PathLintConfig {
+    /// Paths longer than this many bytes are violations.
+    pub max_path_length: Option<usize>,
+    /// Characters that must not appear in any path component.
+    pub forbidden_chars: String,
+    /// File extensions that are not allowed, lowercased and without the
+    /// leading dot.
+    pub disallowed_extensions: Vec<String>,
+    /// Whether paths that only differ in case are violations.
+    pub check_case_conflicts: bool,
+    /// Scribe category that violations are logged to when the job files
+    /// tasks for them.
+    pub task_scribe_category: Option<String>,
+}
+
+/// Configuration for creating code reviews from pushes.  Pushing a commit
+/// to the scratch bookmark `<bookmark_prefix><bookmark>` creates a review
+/// of it against `<bookmark>`, and the review's URL is shown to the client.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PushToReviewConfig {
+    /// Prefix of the scratch bookmarks that create reviews, e.g. `for/`.
+    pub bookmark_prefix: String,
+    /// URL that review creation requests are posted to.
+    pub webhook_url: String,
+}
+
+/// A webhook that repo events are posted to.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct WebhookConfig {
+    /// URL that events are posted to as JSON.
+    pub url: String,
+    /// Events that are posted to the webhook.
+    pub events: Vec<WebhookEventKind>,
+    /// If set, payloads are signed with HMAC-SHA256 using this secret.
+    pub secret: Option<String>,
+}
+
+/// Kinds of repo events that can be posted to webhooks.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum WebhookEventKind {
+    /// A bookmark was created, moved or deleted.
+    BookmarkMoved,
+    /// A tag was created.
+    TagCreated,
+    /// Hooks rejected a push.
+    HookRejected,
+}
+
+impl WebhookEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEventKind::BookmarkMoved => "bookmark_moved",
+            WebhookEventKind::TagCreated => "tag_created",
+            WebhookEventKind::HookRejected => "hook_rejected",
+        }
+    }
+}
+
+/// Configuration for who may create tags.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct TagConfig {
+    /// Namespaces that only some users may create tags in.  Tags outside of
+    /// them may be created by anyone who can write to the repo.
+    pub protected_namespaces: Vec<ProtectedTagNamespace>,
+}
+
+impl TagConfig {
+    /// Whether the user with this unixname may create a tag.  Tags in
+    /// protected namespaces may only be created by users allowed in all of
+    /// the namespaces the tag is in.
+    pub fn is_allowed_user(&self, unixname: &str, tag: &str) -> bool {
+        self.protected_namespaces
+            .iter()
+            .filter(|namespace| tag.starts_with(&namespace.prefix))
+            .all(|namespace| {
+                namespace
+                    .allowed_users
+                    .as_ref()
+                    .map_or(false, |allowed_users| allowed_users.is_match(unixname))
+            })
+    }
+
+    /// Whether a tag is in any protected namespace.
+    pub fn is_protected(&self, tag: &str) -> bool {
+        self.protected_namespaces
+            .iter()
+            .any(|namespace| tag.starts_with(&namespace.prefix))
+    }
+}
+
+/// A namespace of tags that only some users may create tags in.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ProtectedTagNamespace {
+    /// Tags whose names start with this prefix are in the namespace.
+    pub prefix: String,
+    /// Users whose unixnames match this pattern may create tags in the
+    /// namespace.  Services never may, so if unset, tags in the namespace
+    /// can only be created with full access.
+    pub allowed_users: Option<ComparableRegex>,
+}
+
+/// A recurring window during which some operations on a repo are
+/// restricted.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MaintenanceWindow {
+    /// When the window starts.
+    pub schedule: CronSchedule,
+    /// How long the window lasts.
+    pub duration: Duration,
+    /// The operations that are restricted during the window.
+    pub operations: Vec<MaintenanceOperation>,
+    /// Why the window is scheduled, shown to clients whose writes are
+    /// rejected.
+    pub reason: Option<String>,
+}
+
+/// Operations that maintenance windows can restrict.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum MaintenanceOperation {
+    /// Heavy background jobs, such as derived data backfills and repacking,
+    /// pause until the window ends.
+    BackgroundJobs,
+    /// Bookmark moves are rejected as if the repo were locked.
+    Writes,
+}
+
+impl FromStr for MaintenanceOperation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "background_jobs" => Ok(MaintenanceOperation::BackgroundJobs),
+            "writes" => Ok(MaintenanceOperation::Writes),
+            _ => Err(anyhow!("Unknown maintenance operation '{}'", s)),
+        }
+    }
+}
+
+/// A cron-like schedule of the minutes at which something happens, in UTC.
+/// A minute is in the schedule if each of its fields is among the values
+/// allowed for that field, except that, as in cron, a day is in the schedule
+/// if it matches either the days of the month or the days of the week when
+/// both of them are restricted.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CronSchedule {
+    /// Minutes of the hour, from 0 to 59.
+    pub minutes: BTreeSet<u32>,
+    /// Hours of the day, from 0 to 23.
+    pub hours: BTreeSet<u32>,
+    /// Days of the month, from 1 to 31.
+    pub days_of_month: BTreeSet<u32>,
+    /// Months of the year, from 1 to 12.
+    pub months: BTreeSet<u32>,
+    /// Days of the week, from 0 to 6, where Sunday is 0.
+    pub days_of_week: BTreeSet<u32>,
+}
+
+impl CronSchedule {
+    /// Whether a minute, given by its fields, is in the schedule.
+    pub fn matches(
+        &self,
+        minute: u32,
+        hour: u32,
+        day_of_month: u32,
+        month: u32,
+        day_of_week: u32,
+    ) -> bool {
+        self.minutes.contains(&minute)
+            && self.hours.contains(&hour)
+            && self.matches_day(day_of_month, month, day_of_week)
+    }
+
+    /// Whether any minute of a day, given by its fields, is in the schedule.
+    pub fn matches_day(&self, day_of_month: u32, month: u32, day_of_week: u32) -> bool {
+        // A field that allows every day doesn't restrict the days.
+        let any_day_of_month = self.days_of_month.len() == 31;
+        let any_day_of_week = self.days_of_week.len() == 7;
+        let day_matches = if any_day_of_month || any_day_of_week {
+            self.days_of_month.contains(&day_of_month) && self.days_of_week.contains(&day_of_week)
+        } else {
+            self.days_of_month.contains(&day_of_month) || self.days_of_week.contains(&day_of_week)
+        };
+        day_matches && self.months.contains(&month)
+    }
+}
+
+/// Parse one field of a cron schedule, whose values range from `min` to
+/// `max` inclusive.
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<BTreeSet<u32>> {
+    let mut values = BTreeSet::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>()?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(anyhow!("Step of '{}' must be positive", part));
+        }
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (start.parse()?, end.parse()?),
+                None => {
+                    let value = range.parse()?;
+                    (value, value)
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            return Err(anyhow!("'{}' is not within {}-{}", part, min, max));
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+    Ok(values)
+}
+
+impl FromStr for CronSchedule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 5 {
+            return Err(anyhow!("Cron schedule '{}' must have 5 fields", s));
+        }
+        let field = |index: usize, min, max| {
+            parse_cron_field(fields[index], min, max)
+                .with_context(|| format!("Invalid cron schedule '{}'", s))
+        };
+        Ok(CronSchedule {
+            minutes: field(0, 0, 59)?,
+            hours: field(1, 0, 23)?,
+            days_of_month: field(2, 1, 31)?,
+            months: field(3, 1, 12)?,
+            days_of_week: field(4, 0, 6)?,
+        })
+    }
+}
+
+/// A virtual repo that shares the storage of the repo it is a view of, but
+/// only serves the bookmarks that match its patterns and the commits that
+/// are reachable from them.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RepoView {
+    /// Name the view is served under.
+    pub name: String,
+    /// Patterns matching the whole names of the bookmarks the view serves.
+    pub bookmarks: Vec<ComparableRegex>,
+}
+
+impl RepoView {
+    /// Whether the view serves the bookmark with this name.
+    pub fn serves_bookmark(&self, bookmark: &str) -> bool {
+        self.bookmarks.iter().any(|regex| regex.is_match(bookmark))
+    }
+}
+
+/// Export of a copy of a repo that only contains the allow-listed paths to
+/// another repo, e.g. to mirror part of a repo as open source.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SparseExportConfig {
+    /// Id of the repo the copy is exported to.
+    pub export_repo_id: RepositoryId,
+    /// Paths that are exported, with everything under them.
+    pub allowed_paths: Vec<MPath>,
+    /// Bookmarks that are exported.
+    pub bookmarks: Vec<BookmarkKey>,
+}
+
+/// How hg filenodes are generated and served for a repo.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct FilenodesConfig {
+    /// Serve file history from unodes and fastlog rather than filenodes, to
+    /// clients that support it.
+    pub filenode_free_serving: bool,
+    /// Don't generate filenodes.
+    pub disable_generation: bool,
+}
+
+impl FilenodesConfig {
+    /// Whether file history can be served without reading filenodes.
+    /// Repos that don't generate filenodes have none to read.
+    pub fn serve_filenode_free(&self) -> bool {
+        self.filenode_free_serving || self.disable_generation
+    }
+}
+
+/// Service level objectives of a repo.  Requests that fail with an internal
+/// error count against availability, and requests that take longer than
+/// their method's p99 latency target count against its latency objective,
+/// which allows 1% of requests to be slower.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SloConfig {
+    /// Fraction of requests that must not fail with an internal error, in
+    /// parts per million.  If unset, availability isn't tracked.
+    pub availability_target_ppm: Option<u64>,
+    /// Target p99 latency of requests to each method.  Latency is only
+    /// tracked for these methods.
+    pub method_p99_latency: HashMap<String, Duration>,
+    /// Length of the rolling window that attainment is measured over.
+    pub window: Duration,
+}
+
+impl SloConfig {
+    /// Whether the config has no objectives to track.
+    pub fn is_empty(&self) -> bool {
+        self.availability_target_ppm.is_none() && self.method_p99_latency.is_empty()
+    }
+}
+
+impl Default for SloConfig {
+    fn default() -> Self {
+        Self {
+            availability_target_ppm: None,
+            method_p99_latency: HashMap::new(),
+            window: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+impl FromStr for WebhookEventKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bookmark_moved" => Ok(WebhookEventKind::BookmarkMoved),
+            "tag_created" => Ok(WebhookEventKind::TagCreated),
+            "hook_rejected" => Ok(WebhookEventKind::HookRejected),
+            _ => Err(anyhow!("Unknown webhook event '{}'", s)),
+        }
+    }
+}
+
+/// The version of a client, e.g. "4.4.2" or "0.2.20230523-092610-h1e3e1a3d".
+/// Versions are compared by their leading numeric components, so for the
+/// latter the components are 0, 2, 20230523 and 92610.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ClientVersion(Vec<u64>);
+
+impl fmt::Display for ClientVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let components: Vec<_> = self.0.iter().map(|c| c.to_string()).collect();
+        f.write_str(&components.join("."))
+    }
+}
+
+impl FromStr for ClientVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let components: Vec<u64> = s
+            .split(|c| matches!(c, '.' | '-' | '_' | '+'))
+            .map_while(|component| component.parse().ok())
+            .collect();
+        if components.is_empty() {
+            return Err(anyhow!("invalid client version: {}", s));
+        }
+        Ok(ClientVersion(components))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_feature_enabled() {
+        let config = RepoConfig {
+            features: HashMap::from([
+                ("on".to_string(), FeatureRollout::Enabled(true)),
+                ("off".to_string(), FeatureRollout::Enabled(false)),
+                ("none".to_string(), FeatureRollout::Percentage(0)),
+                ("all".to_string(), FeatureRollout::Percentage(100)),
+                ("half".to_string(), FeatureRollout::Percentage(50)),
+            ]),
+            ..Default::default()
+        };
+        assert!(config.feature_enabled("on", "abcdef"));
+        assert!(!config.feature_enabled("off", "abcdef"));
+        assert!(!config.feature_enabled("unknown", "abcdef"));
+        assert!(!config.feature_enabled("none", "abcdef"));
+        assert!(config.feature_enabled("all", "abcdef"));
+
+        let correlators = (0..1000).map(|i| format!("{:08x}", i)).collect::<Vec<_>>();
+        let enabled = correlators
+            .iter()
+            .filter(|correlator| config.feature_enabled("half", correlator))
+            .collect::<Vec<_>>();
+        assert!(enabled.len() > 400 && enabled.len() < 600);
+        // The decision for a request doesn't change between checks.
+        for correlator in enabled {
+            assert!(config.feature_enabled("half", correlator));
+        }
+    }
+
+    #[test]
+    fn test_tag_config() {
+        let config = TagConfig {
+            protected_namespaces: vec![
+                ProtectedTagNamespace {
+                    prefix: "release/".to_string(),
+                    allowed_users: Some(Regex::new("^(alice|bob)$").unwrap().into()),
+                },
+                ProtectedTagNamespace {
+                    prefix: "release/stable/".to_string(),
+                    allowed_users: Some(Regex::new("^alice$").unwrap().into()),
+                },
+                ProtectedTagNamespace {
+                    prefix: "locked/".to_string(),
+                    allowed_users: None,
+                },
+            ],
+        };
+        assert!(config.is_protected("release/1.0"));
+        assert!(config.is_protected("locked/1.0"));
+        assert!(!config.is_protected("v1.0"));
+
+        assert!(config.is_allowed_user("carol", "v1.0"));
+        assert!(config.is_allowed_user("bob", "release/1.0"));
+        assert!(!config.is_allowed_user("carol", "release/1.0"));
+        // Users must be allowed in every namespace the tag is in.
+        assert!(config.is_allowed_user("alice", "release/stable/1.0"));
+        assert!(!config.is_allowed_user("bob", "release/stable/1.0"));
+        // Nobody is allowed in namespaces without allowed users.
+        assert!(!config.is_allowed_user("alice", "locked/1.0"));
+    }
+
+    #[test]
+    fn test_cron_schedule() {
+        // The 13th of the month and every Friday, in May 2023, where the
+        // 5th is a Friday and the 13th is a Saturday.
+        let schedule: CronSchedule = "0 0 13 * 5".parse().unwrap();
+        assert!(schedule.matches(0, 0, 5, 5, 5));
+        assert!(schedule.matches(0, 0, 13, 5, 6));
+        assert!(!schedule.matches(0, 0, 14, 5, 0));
+        assert!(!schedule.matches(1, 0, 13, 5, 6));
+
+        // Only the restricted field counts when the other allows every day.
+        let schedule: CronSchedule = "0 0 13 * *".parse().unwrap();
+        assert!(schedule.matches(0, 0, 13, 5, 6));
+        assert!(!schedule.matches(0, 0, 5, 5, 5));
+        let schedule: CronSchedule = "0 0 * * 5".parse().unwrap();
+        assert!(schedule.matches(0, 0, 5, 5, 5));
+        assert!(!schedule.matches(0, 0, 13, 5, 6));
+
+        // Months are always required to match.
+        let schedule: CronSchedule = "0 0 13 1 5".parse().unwrap();
+        assert!(!schedule.matches(0, 0, 5, 5, 5));
+        assert!(schedule.matches(0, 0, 6, 1, 5));
+
+        assert!("0 0 32 * *".parse::<CronSchedule>().is_err());
+        assert!("0 0 * *".parse::<CronSchedule>().is_err());
+    }
 }
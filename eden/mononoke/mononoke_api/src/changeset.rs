@@ -23,18 +23,25 @@ use bookmarks::BookmarkKey;
 use bytes::Bytes;
 use changeset_fetcher::ChangesetFetcherArc;
 use changeset_fetcher::ChangesetFetcherRef;
+use changeset_info::fetch_changeset_info_batch;
 use changeset_info::ChangesetInfo;
 use changesets::ChangesetsRef;
 use chrono::DateTime;
 use chrono::FixedOffset;
 use cloned::cloned;
+use commit_graph::CommitGraphArc;
 use commit_graph::CommitGraphRef;
+use commit_signatures::verify_signatures;
+use commit_signatures::CommitSignature;
+use commit_signatures::CommitSignaturesRef;
+use commit_signatures::SignatureVerification;
 use context::CoreContext;
 use deleted_manifest::DeletedManifestOps;
 use deleted_manifest::RootDeletedManifestIdCommon;
 use deleted_manifest::RootDeletedManifestV2Id;
 use derived_data::BonsaiDerived;
 use derived_data_manager::BonsaiDerivable;
+use derived_data_manager::DerivationTrace;
 use fsnodes::RootFsnodeId;
 use futures::future;
 use futures::future::try_join;
@@ -42,6 +49,7 @@ use futures::future::try_join_all;
 use futures::stream;
 use futures::stream::Stream;
 use futures::stream::StreamExt;
+use futures::stream::TryChunksError;
 use futures::stream::TryStreamExt;
 use futures_lazy_shared::LazyShared;
 use futures_stats::TimedFutureExt;
@@ -57,16 +65,20 @@ use manifest::PathOrPrefix;
 use maplit::hashset;
 use mercurial_types::Globalrev;
 use mononoke_types::BonsaiChangeset;
+use mononoke_types::ChangesetExtras;
 use mononoke_types::FileChange;
 pub use mononoke_types::Generation;
 use mononoke_types::MPath;
 use mononoke_types::MPathElement;
 use mononoke_types::SkeletonManifestId;
 use mononoke_types::Svnrev;
+use phases::PhasesRef;
 use reachabilityindex::ReachabilityIndex;
+use repo_authorization::RepoWriteOperation;
 use repo_blobstore::RepoBlobstoreArc;
 use repo_blobstore::RepoBlobstoreRef;
 use repo_derived_data::RepoDerivedDataArc;
+use repo_derived_data::RepoDerivedDataRef;
 use skeleton_manifest::RootSkeletonManifestId;
 use smallvec::SmallVec;
 use sorted_vector_map::SortedVectorMap;
@@ -556,6 +568,32 @@ impl ChangesetContext {
         }
     }
 
+    /// Load the `ChangesetInfo` for a batch of changesets in the same repo
+    /// together, so that later accesses don't need to load them.
+    async fn prefetch_changeset_info_batch(
+        changesets: &[ChangesetContext],
+    ) -> Result<(), MononokeError> {
+        let repo = match changesets.first() {
+            Some(changeset) => changeset.repo(),
+            None => return Ok(()),
+        };
+        // Otherwise the infos are made from the bonsai changesets, which
+        // can't be loaded in batches.
+        if !repo.derive_changeset_info_enabled() {
+            return Ok(());
+        }
+        let cs_ids = changesets.iter().map(|changeset| changeset.id()).collect();
+        let manager = repo.blob_repo().repo_derived_data().manager();
+        let infos = fetch_changeset_info_batch(repo.ctx(), manager, cs_ids).await?;
+        for (changeset, info) in changesets.iter().zip(infos) {
+            changeset
+                .changeset_info
+                .get_or_init(|| future::ok(info))
+                .await?;
+        }
+        Ok(())
+    }
+
     /// The IDs of the parents of the changeset.
     pub async fn parents(&self) -> Result<Vec<ChangesetId>, MononokeError> {
         Ok(self.changeset_info().await?.parents().collect())
@@ -659,6 +697,49 @@ impl ChangesetContext {
             }))
     }
 
+    /// Typed metadata about the commit, if any was recorded.
+    pub async fn extras(&self) -> Result<Option<ChangesetExtras>, MononokeError> {
+        let bonsai = self.bonsai_changeset().await?;
+        Ok(bonsai.extras().cloned())
+    }
+
+    /// Store a signature over the commit.  Signatures are checked against
+    /// the repo's trusted keys when they are verified, not when they are
+    /// stored, so signatures from keys that will be trusted later can be
+    /// stored in advance.
+    pub async fn add_signature(&self, signature: CommitSignature) -> Result<(), MononokeError> {
+        self.repo().start_write()?;
+        self.repo()
+            .authorization_context()
+            .require_repo_write(
+                self.ctx(),
+                self.repo().inner_repo(),
+                RepoWriteOperation::AddChangesetSignature,
+            )
+            .await?;
+        self.repo()
+            .blob_repo()
+            .commit_signatures()
+            .add_signature(self.ctx(), self.id, signature)
+            .await?;
+        Ok(())
+    }
+
+    /// Verify the signatures of the commit against the repo's trusted keys.
+    pub async fn signature_verification(&self) -> Result<SignatureVerification, MononokeError> {
+        let signatures = self
+            .repo()
+            .blob_repo()
+            .commit_signatures()
+            .get_signatures(self.ctx(), self.id)
+            .await?;
+        Ok(verify_signatures(
+            &self.repo().config().commit_signing_config,
+            self.id,
+            &signatures,
+        )?)
+    }
+
     /// File changes associated with the commit.
     pub async fn file_changes(&self) -> Result<SortedVectorMap<MPath, FileChange>, MononokeError> {
         let bonsai = self.bonsai_changeset().await?;
@@ -666,6 +747,18 @@ impl ChangesetContext {
         Ok(bonsai.file_changes)
     }
 
+    /// Returns `true` if this commit is public, i.e. it is reachable from a
+    /// publishing bookmark.
+    pub async fn is_public(&self) -> Result<bool, MononokeError> {
+        let public = self
+            .repo()
+            .blob_repo()
+            .phases()
+            .get_public(self.ctx(), vec![self.id], false)
+            .await?;
+        Ok(public.contains(&self.id))
+    }
+
     /// Returns `true` if this commit is an ancestor of `other_commit`.  A commit is considered its
     /// own ancestor for the purpose of this call.
     pub async fn is_ancestor_of(&self, other_commit: ChangesetId) -> Result<bool, MononokeError> {
@@ -703,9 +796,63 @@ impl ChangesetContext {
                 self.id,
             )
             .await?;
+        if self.should_shadow_commit_graph() {
+            let ctx = self.ctx().clone();
+            let commit_graph = self.repo().repo().commit_graph_arc();
+            let id = self.id;
+            self.shadow_commit_graph(
+                "is_ancestor_of",
+                other_commit,
+                is_ancestor_of,
+                async move { commit_graph.is_ancestor(&ctx, id, other_commit).await },
+            );
+        }
         Ok(is_ancestor_of)
     }
 
+    /// Whether to also answer a query answered by the skiplist index with
+    /// the new commit graph, to compare their answers.
+    fn should_shadow_commit_graph(&self) -> bool {
+        let shadow_pct = tunables()
+            .by_repo_new_commit_graph_shadow_percentage(self.repo().name())
+            .unwrap_or(0);
+        ((rand::random::<usize>() % 100) as i64) < shadow_pct
+    }
+
+    /// Answer a query with the new commit graph in the background, so that
+    /// the request doesn't wait for it, and log the answers if the new
+    /// commit graph's answer differs from the skiplist index's, or if the new
+    /// commit graph failed.
+    fn shadow_commit_graph<T, F>(
+        &self,
+        query: &str,
+        other_commit: ChangesetId,
+        skiplist_answer: T,
+        commit_graph_answer: F,
+    ) where
+        T: fmt::Debug + PartialEq + Send + 'static,
+        F: Future<Output = anyhow::Result<T>> + Send + 'static,
+    {
+        let mut scuba = self.ctx().scuba().clone();
+        scuba
+            .add("commit_graph_shadow_query", query)
+            .add("commit", self.id.to_string())
+            .add("other_commit", other_commit.to_string())
+            .add("skiplist_answer", format!("{:?}", skiplist_answer));
+        tokio::spawn(async move {
+            match commit_graph_answer.await {
+                Ok(answer) if answer == skiplist_answer => {}
+                Ok(answer) => {
+                    scuba.add("commit_graph_answer", format!("{:?}", answer));
+                    scuba.log_with_msg("Commit graph shadow mismatch", None);
+                }
+                Err(err) => {
+                    scuba.log_with_msg("Commit graph shadow failed", format!("{:#}", err));
+                }
+            }
+        });
+    }
+
     /// Returns the lowest common ancestor of two commits.
     ///
     /// In case of ambiguity (can happen with multiple merges of the same branches) returns the
@@ -724,6 +871,20 @@ impl ChangesetContext {
                 other_commit,
             )
             .await?;
+        if self.should_shadow_commit_graph() {
+            let ctx = self.ctx().clone();
+            let commit_graph = self.repo().repo().commit_graph_arc();
+            let id = self.id;
+            self.shadow_commit_graph(
+                "common_base_with",
+                other_commit,
+                lca.iter().copied().collect::<BTreeSet<_>>(),
+                async move {
+                    let common_base = commit_graph.common_base(&ctx, id, other_commit).await?;
+                    Ok(common_base.into_iter().collect::<BTreeSet<_>>())
+                },
+            );
+        }
         Ok(lca.get(0).map(|id| Self::new(self.repo.clone(), *id)))
     }
 
@@ -1533,4 +1694,81 @@ impl ChangesetContext {
             )
             .await?)
     }
+
+    /// Derive a type of derived data for the changeset, and trace the
+    /// derivations that this required.
+    ///
+    /// Deriving writes to the repo, so this requires write access.  Fails
+    /// without deriving anything if more than `max_underived` changesets
+    /// would have to be derived.
+    pub async fn derivation_trace(
+        &self,
+        derived_data_type: &str,
+        max_underived: u64,
+    ) -> Result<DerivationTrace, MononokeError> {
+        self.repo().start_write()?;
+        self.repo()
+            .authorization_context()
+            .require_repo_write(
+                self.ctx(),
+                self.repo().inner_repo(),
+                RepoWriteOperation::DeriveData,
+            )
+            .await?;
+        match derived_data_type {
+            ChangesetInfo::NAME => self.derive_traced::<ChangesetInfo>(max_underived).await,
+            RootDeletedManifestV2Id::NAME => {
+                self.derive_traced::<RootDeletedManifestV2Id>(max_underived).await
+            }
+            RootFsnodeId::NAME => self.derive_traced::<RootFsnodeId>(max_underived).await,
+            RootSkeletonManifestId::NAME => {
+                self.derive_traced::<RootSkeletonManifestId>(max_underived).await
+            }
+            RootUnodeManifestId::NAME => {
+                self.derive_traced::<RootUnodeManifestId>(max_underived).await
+            }
+            _ => Err(MononokeError::InvalidRequest(format!(
+                "tracing derivation of {} is not supported",
+                derived_data_type
+            ))),
+        }
+    }
+
+    async fn derive_traced<Derivable: BonsaiDerivable>(
+        &self,
+        max_underived: u64,
+    ) -> Result<DerivationTrace, MononokeError> {
+        let (_, trace) = self
+            .repo
+            .blob_repo()
+            .repo_derived_data()
+            .manager()
+            .derive_traced::<Derivable>(self.ctx(), self.id, None, max_underived)
+            .await?;
+        Ok(trace)
+    }
+}
+
+/// Load the `ChangesetInfo` for a stream of changesets in the same repo,
+/// such as the history of a commit, ahead of them being consumed.
+///
+/// Up to `lookahead` changesets are loaded ahead of the ones that have been
+/// consumed, `batch_size` at a time, so that fetching their authors, dates
+/// and messages rarely waits for storage.
+pub fn prefetch_changeset_info<'a>(
+    changesets: impl Stream<Item = Result<ChangesetContext, MononokeError>> + 'a,
+    batch_size: usize,
+    lookahead: usize,
+) -> impl Stream<Item = Result<ChangesetContext, MononokeError>> + 'a {
+    let batch_size = batch_size.max(1);
+    changesets
+        .try_chunks(batch_size)
+        .map_err(|TryChunksError(_, e)| e)
+        .map_ok(|changesets| async move {
+            ChangesetContext::prefetch_changeset_info_batch(&changesets).await?;
+            Ok(changesets)
+        })
+        .try_buffered((lookahead / batch_size).max(1))
+        .map_ok(|changesets| stream::iter(changesets.into_iter().map(Ok)))
+        .try_flatten()
 }
@@ -21,12 +21,20 @@ use bookmarks_movement::HookRejection;
 use derived_data::DeriveError;
 use itertools::Itertools;
 use megarepo_error::MegarepoError;
+use metaconfig_types::ReadOnlyInfo;
 use pushrebase::PushrebaseError;
 use repo_authorization::AuthorizationError;
+use repo_authorization::PermissionDenied;
+use stats::prelude::*;
 use thiserror::Error;
 
 use crate::path::MononokePath;
 
+define_stats! {
+    prefix = "mononoke.api.errors";
+    errors: dynamic_timeseries("{}", (code: &'static str); Rate, Sum),
+}
+
 #[derive(Clone, Debug)]
 pub struct InternalError(Arc<Error>);
 
@@ -79,11 +87,90 @@ pub enum MononokeError {
     #[error("not available: {0}")]
     NotAvailable(String),
     #[error("permission denied: {0}")]
-    AuthorizationError(String),
+    PermissionDenied(PermissionDenied),
+    #[error("repo is read-only: {0}")]
+    RepoReadOnly(ReadOnlyInfo),
     #[error("internal error: {0}")]
     InternalError(#[source] InternalError),
 }
 
+/// Stable classification of a `MononokeError`.  The code is reported with
+/// the error at every serving boundary, so the same failure is described
+/// the same way over wireproto, SCS and HTTP.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum MononokeErrorCode {
+    InvalidRequest,
+    MergeConflicts,
+    PushrebaseConflicts,
+    PermissionDenied,
+    HookFailure,
+    NotAvailable,
+    RepoReadOnly,
+    InternalError,
+}
+
+impl MononokeErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MononokeErrorCode::InvalidRequest => "invalid_request",
+            MononokeErrorCode::MergeConflicts => "merge_conflicts",
+            MononokeErrorCode::PushrebaseConflicts => "pushrebase_conflicts",
+            MononokeErrorCode::PermissionDenied => "permission_denied",
+            MononokeErrorCode::HookFailure => "hook_failure",
+            MononokeErrorCode::NotAvailable => "not_available",
+            MononokeErrorCode::RepoReadOnly => "repo_read_only",
+            MononokeErrorCode::InternalError => "internal_error",
+        }
+    }
+
+    /// Whether errors with this code are caused by the request, and so
+    /// can be fixed by the user, rather than by a problem in the server.
+    pub fn is_request_error(&self) -> bool {
+        *self != MononokeErrorCode::InternalError
+    }
+
+    /// Classify an arbitrary error.  Errors that are not (and are not
+    /// caused by) a `MononokeError` are internal errors.
+    pub fn classify(error: &Error) -> Self {
+        error
+            .chain()
+            .find_map(|e| e.downcast_ref::<MononokeError>())
+            .map_or(MononokeErrorCode::InternalError, MononokeError::code)
+    }
+
+    /// Count an error with this code in the per-code error stats.  This
+    /// should be called once, where the error leaves the server.
+    pub fn report(&self) {
+        STATS::errors.add_value(1, (self.as_str(),));
+    }
+}
+
+impl fmt::Display for MononokeErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl MononokeError {
+    pub fn code(&self) -> MononokeErrorCode {
+        match self {
+            MononokeError::InvalidRequest(_) => MononokeErrorCode::InvalidRequest,
+            MononokeError::MergeConflicts { .. } => MononokeErrorCode::MergeConflicts,
+            MononokeError::PushrebaseConflicts(_) => MononokeErrorCode::PushrebaseConflicts,
+            MononokeError::ServicePermissionDenied { .. }
+            | MononokeError::PermissionDenied(_) => MononokeErrorCode::PermissionDenied,
+            MononokeError::HookFailure(_) => MononokeErrorCode::HookFailure,
+            MononokeError::NotAvailable(_) => MononokeErrorCode::NotAvailable,
+            MononokeError::RepoReadOnly(_) => MononokeErrorCode::RepoReadOnly,
+            MononokeError::InternalError(_) => MononokeErrorCode::InternalError,
+        }
+    }
+
+    pub fn is_request_error(&self) -> bool {
+        self.code().is_request_error()
+    }
+}
+
 impl From<Error> for MononokeError {
     fn from(e: Error) -> Self {
         MononokeError::InternalError(InternalError(Arc::new(e)))
@@ -114,27 +201,34 @@ impl From<DeriveError> for MononokeError {
 impl From<BookmarkMovementError> for MononokeError {
     fn from(e: BookmarkMovementError) -> Self {
         match e {
-            BookmarkMovementError::AuthorizationError(e) => {
-                MononokeError::AuthorizationError(e.to_string())
-            }
+            BookmarkMovementError::AuthorizationError(e) => MononokeError::from(e),
             BookmarkMovementError::HookFailure(rejections) => {
                 MononokeError::HookFailure(rejections)
             }
             BookmarkMovementError::PushrebaseError(PushrebaseError::Conflicts(conflicts)) => {
                 MononokeError::PushrebaseConflicts(conflicts)
             }
+            BookmarkMovementError::RepoLocked(info) => MononokeError::RepoReadOnly(info),
             BookmarkMovementError::Error(e) => MononokeError::InternalError(InternalError::from(e)),
             _ => MononokeError::InvalidRequest(e.to_string()),
         }
     }
 }
 
+impl From<PushrebaseError> for MononokeError {
+    fn from(e: PushrebaseError) -> Self {
+        match e {
+            PushrebaseError::Conflicts(conflicts) => MononokeError::PushrebaseConflicts(conflicts),
+            PushrebaseError::Error(e) => MononokeError::InternalError(InternalError::from(e)),
+            _ => MononokeError::InvalidRequest(e.to_string()),
+        }
+    }
+}
+
 impl From<AuthorizationError> for MononokeError {
     fn from(e: AuthorizationError) -> Self {
         match e {
-            AuthorizationError::PermissionDenied(e) => {
-                MononokeError::AuthorizationError(e.to_string())
-            }
+            AuthorizationError::PermissionDenied(e) => MononokeError::PermissionDenied(e),
             AuthorizationError::Error(e) => MononokeError::InternalError(InternalError::from(e)),
         }
     }
@@ -36,10 +36,18 @@ mod test;
 
 // Re-export types that are useful for clients.
 pub use blame::CompatBlame;
+pub use commit_signatures::CommitSignature;
+pub use commit_signatures::SignatureVerification;
 pub use context::CoreContext;
 pub use context::LoggingContainer;
 pub use context::SessionContainer;
+pub use metaconfig_types::RepoPermission;
+pub use repo_authorization::PermissionDenied;
+pub use repo_stats::DirectoryStats;
+pub use repo_stats::RepoStatsEntry;
+pub use repo_tags::Tag;
 
+pub use crate::changeset::prefetch_changeset_info;
 pub use crate::changeset::ChangesetContext;
 pub use crate::changeset::ChangesetDiffItem;
 pub use crate::changeset::ChangesetFileOrdering;
@@ -58,6 +66,7 @@ pub use crate::changeset_path_diff::MetadataDiffLinesCount;
 pub use crate::changeset_path_diff::UnifiedDiff;
 pub use crate::changeset_path_diff::UnifiedDiffMode;
 pub use crate::errors::MononokeError;
+pub use crate::errors::MononokeErrorCode;
 pub use crate::file::headerless_unified_diff;
 pub use crate::file::FileContext;
 pub use crate::file::FileId;
@@ -70,6 +79,8 @@ pub use crate::repo::create_changeset::CreateChangeFile;
 pub use crate::repo::create_changeset::CreateCopyInfo;
 pub use crate::repo::create_changeset::CreateInfo;
 pub use crate::repo::land_stack::PushrebaseOutcome;
+pub use crate::repo::land_stack::PushrebasePreview;
+pub use crate::repo::mirror::CommitBundle;
 pub use crate::repo::BookmarkFreshness;
 pub use crate::repo::BookmarkInfo;
 pub use crate::repo::Repo;
@@ -88,6 +99,7 @@ pub use crate::tree::TreeEntry;
 pub use crate::tree::TreeId;
 pub use crate::tree::TreeSummary;
 pub use crate::xrepo::CandidateSelectionHintArgs;
+pub use crate::xrepo::XRepoLookupMatch;
 
 /// An instance of Mononoke, which may manage multiple repositories.
 pub struct Mononoke {
@@ -216,7 +228,7 @@ pub mod test_impl {
                     cloned!(ctx);
                     async move {
                         Repo::new_test(ctx.clone(), repo).await.map(move |repo| {
-                            (repo.blob_repo().repo_identity().id().id(), name, repo)
+                            (Some(repo.blob_repo().repo_identity().id().id()), name, repo)
                         })
                     }
                 })
@@ -253,7 +265,7 @@ pub mod test_impl {
                             Repo::new_test_xrepo(ctx.clone(), repo, lv_cfg, mapping)
                                 .await
                                 .map(move |repo| {
-                                    (repo.blob_repo().repo_identity().id().id(), name, repo)
+                                    (Some(repo.blob_repo().repo_identity().id().id()), name, repo)
                                 })
                         }
                     }
@@ -16,6 +16,7 @@ use std::time::UNIX_EPOCH;
 
 use acl_regions::build_disabled_acl_regions;
 use acl_regions::AclRegions;
+use admin_audit_log::AdminAuditLog;
 use anyhow::anyhow;
 use anyhow::Error;
 use blobrepo::AsBlobRepo;
@@ -62,6 +63,7 @@ use cross_repo_sync::CandidateSelectionHint;
 use cross_repo_sync::CommitSyncContext;
 use cross_repo_sync::CommitSyncRepos;
 use cross_repo_sync::CommitSyncer;
+use cross_repo_sync::PluralCommitSyncOutcome;
 use derived_data_manager::BonsaiDerivable as NewBonsaiDerivable;
 use ephemeral_blobstore::ArcRepoEphemeralStore;
 use ephemeral_blobstore::Bubble;
@@ -71,6 +73,7 @@ use ephemeral_blobstore::RepoEphemeralStoreArc;
 use ephemeral_blobstore::RepoEphemeralStoreRef;
 use ephemeral_blobstore::StorageLocation;
 use fbinit::FacebookInit;
+use filenodes::FilenodeResult;
 use filestore::Alias;
 use filestore::FetchKey;
 use filestore::FilestoreConfig;
@@ -83,6 +86,8 @@ use futures::stream::StreamExt;
 use futures::stream::TryStreamExt;
 use futures::try_join;
 use futures::Future;
+use hidden_changesets::HiddenChangesets;
+use hidden_changesets::HiddenChangesetsRef;
 use hooks::HookManager;
 use hooks::HookManagerArc;
 use itertools::Itertools;
@@ -91,6 +96,7 @@ use live_commit_sync_config::TestLiveCommitSyncConfig;
 use mercurial_derived_data::MappedHgChangesetId;
 use mercurial_mutation::HgMutationStore;
 use mercurial_types::Globalrev;
+use mercurial_types::HgFileNodeId;
 use metaconfig_types::HookManagerParams;
 use metaconfig_types::InfinitepushNamespace;
 use metaconfig_types::InfinitepushParams;
@@ -104,6 +110,7 @@ use mononoke_types::hash::Sha1;
 use mononoke_types::hash::Sha256;
 use mononoke_types::ContentId;
 use mononoke_types::Generation;
+use mononoke_types::RepoPath;
 use mononoke_types::RepositoryId;
 use mononoke_types::Svnrev;
 use mononoke_types::Timestamp;
@@ -115,6 +122,7 @@ use mutable_renames::SqlMutableRenamesStore;
 use phases::Phases;
 use phases::PhasesArc;
 use phases::PhasesRef;
+use push_log::PushLog;
 use pushrebase_mutation_mapping::PushrebaseMutationMapping;
 use reachabilityindex::LeastCommonAncestorsHint;
 use regex::Regex;
@@ -136,6 +144,11 @@ use repo_permission_checker::RepoPermissionChecker;
 use repo_sparse_profiles::ArcRepoSparseProfiles;
 use repo_sparse_profiles::RepoSparseProfiles;
 use repo_sparse_profiles::RepoSparseProfilesArc;
+use repo_stats::RepoStats;
+use repo_stats::RepoStatsEntry;
+use repo_stats::RepoStatsRef;
+use repo_tags::RepoTags;
+use review_creation::ReviewCreator;
 use revset::AncestorsNodeStream;
 use revset::DifferenceOfUnionsOfAncestorsNodeStream;
 use segmented_changelog::CloneData;
@@ -165,6 +178,7 @@ use wireproto_handler::PushRedirectorBase;
 use wireproto_handler::RepoHandlerBase;
 use wireproto_handler::RepoHandlerBaseRef;
 
+use self::view::ViewVisibility;
 use crate::changeset::ChangesetContext;
 use crate::errors::MononokeError;
 use crate::file::FileContext;
@@ -177,13 +191,17 @@ use crate::specifiers::HgChangesetId;
 use crate::tree::TreeContext;
 use crate::tree::TreeId;
 use crate::xrepo::CandidateSelectionHintArgs;
+use crate::xrepo::XRepoLookupMatch;
 
 pub mod create_bookmark;
 pub mod create_changeset;
 pub mod delete_bookmark;
 pub mod git;
 pub mod land_stack;
+pub mod mirror;
 pub mod move_bookmark;
+pub mod tags;
+mod view;
 
 define_stats! {
     prefix = "mononoke.api";
@@ -237,6 +255,9 @@ pub struct Repo {
     #[init(inner.repo_identity().name().to_string())]
     pub name: String,
 
+    #[init(Arc::new(ViewVisibility::default()))]
+    view_visibility: Arc<ViewVisibility>,
+
     #[facet]
     pub warm_bookmarks_cache: dyn BookmarksCache,
 
@@ -251,6 +272,24 @@ pub struct Repo {
 
     #[facet]
     pub filestore_config: FilestoreConfig,
+
+    #[facet]
+    pub admin_audit_log: dyn AdminAuditLog,
+
+    #[facet]
+    pub hidden_changesets: dyn HiddenChangesets,
+
+    #[facet]
+    pub repo_stats: dyn RepoStats,
+
+    #[facet]
+    pub repo_tags: dyn RepoTags,
+
+    #[facet]
+    pub push_log: dyn PushLog,
+
+    #[facet]
+    pub review_creator: dyn ReviewCreator,
 }
 
 impl AsBlobRepo for Repo {
@@ -394,12 +433,19 @@ impl Repo {
         };
         Self {
             name: self.name.clone(),
+            view_visibility: self.view_visibility.clone(),
             inner,
             warm_bookmarks_cache: self.warm_bookmarks_cache.clone(),
             hook_manager: self.hook_manager.clone(),
             repo_handler_base: self.repo_handler_base.clone(),
             commit_graph: self.commit_graph.clone(),
             filestore_config: self.filestore_config.clone(),
+            admin_audit_log: self.admin_audit_log.clone(),
+            hidden_changesets: self.hidden_changesets.clone(),
+            repo_stats: self.repo_stats.clone(),
+            repo_tags: self.repo_tags.clone(),
+            push_log: self.push_log.clone(),
+            review_creator: self.review_creator.clone(),
         }
     }
 
@@ -505,6 +551,12 @@ impl Repo {
             &mutable_counters,
         )?;
         let commit_graph = repo_factory.commit_graph(&blob_repo.repo_identity_arc())?;
+        let admin_audit_log = repo_factory.admin_audit_log(&blob_repo.repo_identity_arc())?;
+        let hidden_changesets = repo_factory.hidden_changesets(&blob_repo.repo_identity_arc())?;
+        let repo_stats = repo_factory.repo_stats(&blob_repo.repo_identity_arc())?;
+        let repo_tags = repo_factory.repo_tags(&blob_repo.repo_identity_arc())?;
+        let push_log = repo_factory.push_log(&blob_repo.repo_identity_arc())?;
+        let review_creator = repo_factory.review_creator(&Arc::new(config.clone()))?;
 
         let inner = InnerRepo {
             blob_repo,
@@ -530,8 +582,11 @@ impl Repo {
             inner.bookmark_update_log_arc(),
             inner.repo_identity_arc(),
         );
-        warm_bookmarks_cache_builder
-            .add_all_warmers(&inner.repo_derived_data_arc(), &inner.phases_arc())?;
+        warm_bookmarks_cache_builder.add_all_warmers(
+            &inner.repo_derived_data_arc(),
+            &inner.phases_arc(),
+            &config.publishing_bookmarks,
+        )?;
         // We are constructing a test repo, so ensure the warm bookmark cache
         // is fully warmed, so that tests see up-to-date bookmarks.
         warm_bookmarks_cache_builder.wait_until_warmed();
@@ -540,12 +595,19 @@ impl Repo {
 
         Ok(Self {
             name: name.clone(),
+            view_visibility: Arc::new(ViewVisibility::default()),
             inner,
             warm_bookmarks_cache: Arc::new(warm_bookmarks_cache),
             hook_manager,
             repo_handler_base,
             commit_graph,
             filestore_config,
+            admin_audit_log,
+            hidden_changesets,
+            repo_stats,
+            repo_tags,
+            push_log,
+            review_creator,
         })
     }
 
@@ -594,6 +656,80 @@ impl Repo {
         &self.inner.repo_config
     }
 
+    /// The changesets among `cs_ids` that this repo doesn't serve.  Views of
+    /// other repos only serve the changesets that are ancestors of the
+    /// bookmarks they serve, and other repos serve every changeset.
+    pub async fn changesets_outside_view(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<HashSet<ChangesetId>, MononokeError> {
+        if self.config().view.is_none() || cs_ids.is_empty() {
+            return Ok(HashSet::new());
+        }
+        Ok(self
+            .view_visibility
+            .unserved(
+                ctx,
+                self.blob_repo().bookmarks(),
+                &self.commit_graph,
+                cs_ids,
+            )
+            .await?)
+    }
+
+    /// The hg files and trees among `entries` that this repo doesn't serve.
+    /// Views of other repos only serve those whose linknodes they serve, so
+    /// those without linknodes aren't served either.
+    pub async fn hg_entries_outside_view(
+        &self,
+        ctx: &CoreContext,
+        entries: Vec<(RepoPath, HgFileNodeId)>,
+    ) -> Result<HashSet<HgFileNodeId>, MononokeError> {
+        if self.config().view.is_none() || entries.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let linknodes = stream::iter(entries)
+            .map(|(path, filenode_id)| async move {
+                let linknode = match self
+                    .blob_repo()
+                    .get_filenode_opt(ctx.clone(), &path, filenode_id)
+                    .await?
+                {
+                    FilenodeResult::Present(Some(filenode)) => Some(filenode.linknode),
+                    FilenodeResult::Present(None) | FilenodeResult::Disabled => None,
+                };
+                anyhow::Ok((filenode_id, linknode))
+            })
+            .buffer_unordered(100)
+            .try_collect::<Vec<_>>()
+            .await?;
+        let mapping = self
+            .blob_repo()
+            .get_hg_bonsai_mapping(
+                ctx.clone(),
+                linknodes
+                    .iter()
+                    .filter_map(|(_, linknode)| *linknode)
+                    .collect::<Vec<_>>(),
+            )
+            .await?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let outside_view = self
+            .changesets_outside_view(ctx, mapping.values().copied().collect())
+            .await?;
+        Ok(linknodes
+            .into_iter()
+            .filter_map(|(filenode_id, linknode)| {
+                let served = linknode
+                    .and_then(|linknode| mapping.get(&linknode))
+                    .map_or(false, |cs_id| !outside_view.contains(cs_id));
+                (!served).then_some(filenode_id)
+            })
+            .collect())
+    }
+
     pub async fn report_monitoring_stats(&self, ctx: &CoreContext) -> Result<(), MononokeError> {
         match self.config().source_control_service_monitoring.as_ref() {
             None => {}
@@ -1011,6 +1147,9 @@ impl RepoContext {
                 .changeset_exists(cs_id, StorageLocation::Persistent)
                 .await?
                 .then_some(cs_id),
+            // Ephemeral changesets aren't reachable from any bookmark, so
+            // views never serve them.
+            ChangesetSpecifier::EphemeralBonsai(..) if self.config().view.is_some() => None,
             ChangesetSpecifier::EphemeralBonsai(cs_id, bubble_id) => self
                 .changeset_exists(cs_id, StorageLocation::ephemeral(bubble_id))
                 .await?
@@ -1040,7 +1179,115 @@ impl RepoContext {
                     .await?
             }
         };
-        Ok(id)
+        match id {
+            Some(cs_id) if self.is_hidden(cs_id).await? => Ok(None),
+            Some(cs_id) if !self.is_served(cs_id).await? => Ok(None),
+            id => Ok(id),
+        }
+    }
+
+    /// Resolve many changeset specifiers at once, batching the lookups for
+    /// each kind of specifier.  Specifiers that don't resolve to a changeset
+    /// are left out.
+    pub async fn many_changeset_ids_from_specifiers(
+        &self,
+        specifiers: Vec<ChangesetSpecifier>,
+    ) -> Result<HashMap<ChangesetSpecifier, ChangesetId>, MononokeError> {
+        let mut bonsais = Vec::new();
+        let mut hg_ids = Vec::new();
+        let mut git_sha1s = Vec::new();
+        let mut globalrevs = Vec::new();
+        let mut others = Vec::new();
+        for specifier in specifiers {
+            match specifier {
+                ChangesetSpecifier::Bonsai(cs_id) => bonsais.push(cs_id),
+                ChangesetSpecifier::Hg(hg_cs_id) => hg_ids.push(hg_cs_id),
+                ChangesetSpecifier::GitSha1(git_sha1) => git_sha1s.push(git_sha1),
+                ChangesetSpecifier::Globalrev(rev) => globalrevs.push(rev),
+                specifier => others.push(specifier),
+            }
+        }
+
+        let (bonsais, hg_ids, git_sha1s, globalrevs, others) = try_join!(
+            async {
+                Ok::<_, MononokeError>(
+                    self.changesets(None)
+                        .await?
+                        .get_many(&self.ctx, bonsais)
+                        .await?
+                        .into_iter()
+                        .map(|entry| (ChangesetSpecifier::Bonsai(entry.cs_id), entry.cs_id))
+                        .collect::<Vec<_>>(),
+                )
+            },
+            self.many_changeset_ids_from_hg(hg_ids),
+            self.many_changeset_ids_from_git_sha1(git_sha1s),
+            self.many_changeset_ids_from_globalrev(globalrevs),
+            // Other specifiers are rare, so they are resolved one at a time.
+            stream::iter(others)
+                .map(|specifier| async move {
+                    let cs_id = self.resolve_specifier(specifier).await?;
+                    Ok::<_, MononokeError>(cs_id.map(|cs_id| (specifier, cs_id)))
+                })
+                .buffer_unordered(100)
+                .try_filter_map(|resolved| async move { Ok(resolved) })
+                .try_collect::<Vec<_>>(),
+        )?;
+        let mut resolved = bonsais
+            .into_iter()
+            .chain(hg_ids.into_iter().map(|(id, cs_id)| (id.into(), cs_id)))
+            .chain(git_sha1s.into_iter().map(|(id, cs_id)| (id.into(), cs_id)))
+            .chain(globalrevs.into_iter().map(|(id, cs_id)| (id.into(), cs_id)))
+            .chain(others)
+            .collect::<HashMap<_, _>>();
+
+        // Hidden changesets, and changesets outside of a view, can't be
+        // resolved.
+        let cs_ids = resolved.values().copied().collect::<Vec<_>>();
+        let (hidden, outside_view) = try_join!(
+            async {
+                Ok::<_, MononokeError>(
+                    self.repo
+                        .hidden_changesets()
+                        .get_hidden(&self.ctx, &cs_ids)
+                        .await?,
+                )
+            },
+            self.repo.changesets_outside_view(&self.ctx, cs_ids.clone()),
+        )?;
+        resolved.retain(|_, cs_id| !hidden.contains(cs_id) && !outside_view.contains(cs_id));
+        Ok(resolved)
+    }
+
+    /// Test whether a changeset is served by this repo.  Views of other
+    /// repos only serve the changesets that are reachable from their
+    /// bookmarks, so other changesets can't be resolved.
+    pub async fn is_served(&self, changeset_id: ChangesetId) -> Result<bool, MononokeError> {
+        Ok(self
+            .repo
+            .changesets_outside_view(&self.ctx, vec![changeset_id])
+            .await?
+            .is_empty())
+    }
+
+    /// The hg files and trees among `entries` that aren't served by this
+    /// repo, because they aren't part of the changesets a view serves.
+    pub async fn hg_entries_outside_view(
+        &self,
+        entries: Vec<(RepoPath, HgFileNodeId)>,
+    ) -> Result<HashSet<HgFileNodeId>, MononokeError> {
+        self.repo.hg_entries_outside_view(&self.ctx, entries).await
+    }
+
+    /// Test whether a changeset has been hidden by an administrator.  Hidden
+    /// changesets can't be resolved or listed.
+    pub async fn is_hidden(&self, changeset_id: ChangesetId) -> Result<bool, MononokeError> {
+        Ok(self
+            .repo
+            .hidden_changesets()
+            .get_hidden(&self.ctx, &[changeset_id])
+            .await?
+            .contains(&changeset_id))
     }
 
     /// Resolve a bookmark to a changeset.
@@ -1103,6 +1350,30 @@ impl RepoContext {
                 )
             }
         };
+        // Hidden changesets, and changesets outside of a view, must not be
+        // discoverable by prefix, so drop any candidates that don't resolve.
+        let resolved = match resolved {
+            ChangesetSpecifierPrefixResolution::Single(specifier) => {
+                match self.resolve_specifier(specifier).await? {
+                    Some(_) => ChangesetSpecifierPrefixResolution::Single(specifier),
+                    None => ChangesetSpecifierPrefixResolution::NoMatch,
+                }
+            }
+            ChangesetSpecifierPrefixResolution::Multiple(specifiers) => {
+                let mut visible = Vec::with_capacity(specifiers.len());
+                for specifier in specifiers {
+                    if self.resolve_specifier(specifier).await?.is_some() {
+                        visible.push(specifier);
+                    }
+                }
+                match visible.len() {
+                    0 => ChangesetSpecifierPrefixResolution::NoMatch,
+                    1 => ChangesetSpecifierPrefixResolution::Single(visible[0]),
+                    _ => ChangesetSpecifierPrefixResolution::Multiple(visible),
+                }
+            }
+            resolved => resolved,
+        };
         Ok(resolved)
     }
 
@@ -1326,6 +1597,15 @@ impl RepoContext {
         }))
     }
 
+    /// The most recently collected repo stats at a bookmark, if any have
+    /// been collected.
+    pub async fn latest_repo_stats(
+        &self,
+        bookmark: &str,
+    ) -> Result<Option<RepoStatsEntry>, MononokeError> {
+        Ok(self.repo.repo_stats().latest(&self.ctx, bookmark).await?)
+    }
+
     /// Get a list of bookmarks.
     pub async fn list_bookmarks(
         &self,
@@ -1488,6 +1768,14 @@ impl RepoContext {
             draft.extend(new_draft.into_iter());
         }
 
+        // Hidden changesets are never listed.
+        let hidden = self
+            .repo
+            .hidden_changesets()
+            .get_hidden(&self.ctx, &draft)
+            .await?;
+        draft.retain(|cs_id| !hidden.contains(cs_id));
+
         Ok(Stack {
             draft,
             public,
@@ -1637,6 +1925,21 @@ impl RepoContext {
         specifier: impl Into<ChangesetSpecifier>,
         maybe_candidate_selection_hint_args: Option<CandidateSelectionHintArgs>,
     ) -> Result<Option<ChangesetContext>, MononokeError> {
+        let maybe_cs = self
+            .xrepo_commit_lookup_with_match(other, specifier, maybe_candidate_selection_hint_args)
+            .await?;
+        Ok(maybe_cs.map(|(cs, _lookup_match)| cs))
+    }
+
+    /// Look up the equivalent of a commit in another repo, also returning
+    /// whether the commit was rewritten exactly, or whether the result is
+    /// only a working-copy-equivalent ancestor.
+    pub async fn xrepo_commit_lookup_with_match(
+        &self,
+        other: &Self,
+        specifier: impl Into<ChangesetSpecifier>,
+        maybe_candidate_selection_hint_args: Option<CandidateSelectionHintArgs>,
+    ) -> Result<Option<(ChangesetContext, XRepoLookupMatch)>, MononokeError> {
         let common_config = self
             .live_commit_sync_config()
             .get_common_config(self.blob_repo().repo_identity().id())
@@ -1676,11 +1979,51 @@ impl RepoContext {
                 false,
             )
             .await?;
-        Ok(maybe_cs_id.map(|cs_id| ChangesetContext::new(other.clone(), cs_id)))
+        let cs_id = match maybe_cs_id {
+            Some(cs_id) => cs_id,
+            None => return Ok(None),
+        };
+
+        // The commit may have been rewritten several times (e.g. with
+        // different mapping versions), in which case the hint selected one of
+        // the rewrites.
+        let lookup_match = match commit_syncer
+            .get_plural_commit_sync_outcome(&self.ctx, changeset)
+            .await?
+        {
+            Some(PluralCommitSyncOutcome::RewrittenAs(rewrites))
+                if rewrites.iter().any(|(rewritten, _)| *rewritten == cs_id) =>
+            {
+                XRepoLookupMatch::Exact
+            }
+            Some(PluralCommitSyncOutcome::EquivalentWorkingCopyAncestor(ancestor, _))
+                if ancestor == cs_id =>
+            {
+                XRepoLookupMatch::WorkingCopyEquivalent
+            }
+            Some(PluralCommitSyncOutcome::RewrittenAs(_))
+            | Some(PluralCommitSyncOutcome::EquivalentWorkingCopyAncestor(..))
+            | Some(PluralCommitSyncOutcome::NotSyncCandidate(_))
+            | None => {
+                return Err(anyhow!(
+                    "{} was synced to {}, but the sync outcome doesn't match",
+                    changeset,
+                    cs_id
+                )
+                .into());
+            }
+        };
+        Ok(Some((
+            ChangesetContext::new(other.clone(), cs_id),
+            lookup_match,
+        )))
     }
 
     /// Start a write to the repo.
     pub fn start_write(&self) -> Result<(), MononokeError> {
+        if let Some(mirror_config) = &self.config().mirror_config {
+            return Err(MononokeError::RepoReadOnly(mirror_config.read_only_info()));
+        }
         if self.authz.is_service() {
             if !self.config().source_control_service.permit_service_writes {
                 return Err(MononokeError::InvalidRequest(String::from(
@@ -1759,6 +2102,13 @@ impl RepoContext {
     pub async fn segmented_changelog_clone_data(
         &self,
     ) -> Result<(CloneData<ChangesetId>, HashMap<ChangesetId, HgChangesetId>), MononokeError> {
+        // The segmented changelog of a view is that of the whole repo.
+        if self.config().view.is_some() {
+            return Err(MononokeError::NotAvailable(format!(
+                "{} is a view of another repo and can't be cloned with segmented changelog",
+                self.name()
+            )));
+        }
         let segmented_changelog = self.repo.segmented_changelog();
         let clone_data = segmented_changelog
             .clone_data(&self.ctx)
@@ -1781,6 +2131,17 @@ impl RepoContext {
         common: Vec<ChangesetId>,
         missing: Vec<ChangesetId>,
     ) -> Result<CloneData<ChangesetId>, MononokeError> {
+        let outside_view = self
+            .repo
+            .changesets_outside_view(&self.ctx, missing.clone())
+            .await?;
+        if let Some(cs_id) = outside_view.into_iter().next() {
+            return Err(MononokeError::InvalidRequest(format!(
+                "changeset {} is not part of {} and can't be pulled",
+                cs_id,
+                self.name()
+            )));
+        }
         let segmented_changelog = self.repo.segmented_changelog();
         let pull_data = segmented_changelog
             .pull_data(&self.ctx, common, missing)
@@ -34,6 +34,7 @@ use metaconfig_types::RepoConfigRef;
 use mononoke_types::fsnode::FsnodeEntry;
 use mononoke_types::BonsaiChangeset;
 use mononoke_types::BonsaiChangesetMut;
+use mononoke_types::ChangesetExtras;
 use mononoke_types::ChangesetId;
 use mononoke_types::DateTime as MononokeDateTime;
 use mononoke_types::FileChange;
@@ -313,6 +314,7 @@ pub struct CreateInfo {
     pub message: String,
     pub extra: BTreeMap<String, Vec<u8>>,
     pub git_extra_headers: Option<BTreeMap<SmallVec<[u8; 24]>, Bytes>>,
+    pub extras: Option<ChangesetExtras>,
 }
 
 /// Verify that all deleted files existed in at least one of the parents.
@@ -938,6 +940,7 @@ impl RepoContext {
                 file_changes,
                 is_snapshot: bubble.is_some(),
                 git_annotated_tag: None,
+                extras: info.extras.filter(|extras| !extras.is_empty()),
             }
             .freeze()
             .map_err(|e| {
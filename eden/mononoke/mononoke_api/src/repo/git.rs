@@ -248,6 +248,7 @@ impl RepoContext {
             file_changes: SortedVectorMap::new(),
             is_snapshot: false,
             git_annotated_tag: Some(annotated_tag_target),
+            extras: None,
         }
         .freeze()
         .map_err(|e| {
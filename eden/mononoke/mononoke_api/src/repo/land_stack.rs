@@ -28,7 +28,9 @@ use futures::stream::TryStreamExt;
 use hooks::CrossRepoPushSource;
 use hooks::HookManagerRef;
 use hooks::PushAuthoredBy;
+use mononoke_types::BonsaiChangeset;
 use mononoke_types::ChangesetId;
+pub use pushrebase::PushrebasePreview;
 use pushrebase_client::LocalPushrebaseClient;
 use pushrebase_client::PushrebaseClient;
 use reachabilityindex::LeastCommonAncestorsHint;
@@ -126,23 +128,13 @@ impl RepoContext {
         }))
     }
 
-    /// Land a stack of commits to a bookmark via pushrebase.
-    pub async fn land_stack(
+    /// Load the commits of the stack from `base` (exclusive) to `head`.
+    async fn stack_changesets(
         &self,
-        bookmark: impl AsRef<str>,
+        lca_hint: &Arc<dyn LeastCommonAncestorsHint>,
         head: ChangesetId,
         base: ChangesetId,
-        pushvars: Option<&HashMap<String, Bytes>>,
-        bookmark_restrictions: BookmarkKindRestrictions,
-        push_authored_by: PushAuthoredBy,
-    ) -> Result<PushrebaseOutcome, MononokeError> {
-        self.start_write()?;
-
-        let bookmark = bookmark.as_ref();
-        let bookmark = BookmarkKey::new(bookmark)?;
-
-        let lca_hint: Arc<dyn LeastCommonAncestorsHint> = self.skiplist_index_arc();
-
+    ) -> Result<HashSet<BonsaiChangeset>, MononokeError> {
         // Check that base is an ancestor of the head commit, and fail with an
         // appropriate error message if that's not the case.
         if !lca_hint
@@ -187,6 +179,61 @@ impl RepoContext {
         .try_collect()
         .await?;
 
+        Ok(changesets)
+    }
+
+    /// Check whether a stack of commits could be landed to a bookmark via
+    /// pushrebase, without landing it.
+    ///
+    /// Fails with `MononokeError::PushrebaseConflicts` if the stack conflicts
+    /// with the commits that have landed since it was based.
+    pub async fn preview_land_stack(
+        &self,
+        bookmark: impl AsRef<str>,
+        head: ChangesetId,
+        base: ChangesetId,
+    ) -> Result<PushrebasePreview, MononokeError> {
+        if self.push_redirector.is_some() {
+            return Err(MononokeError::InvalidRequest(String::from(
+                "Previewing a land is not supported for repos whose pushes are redirected",
+            )));
+        }
+
+        let bookmark = BookmarkKey::new(bookmark.as_ref())?;
+        let lca_hint: Arc<dyn LeastCommonAncestorsHint> = self.skiplist_index_arc();
+        let changesets = self.stack_changesets(&lca_hint, head, base).await?;
+
+        let preview = pushrebase::preview_pushrebase_bonsai(
+            self.ctx(),
+            self.blob_repo(),
+            &self.config().pushrebase.flags,
+            &bookmark,
+            &changesets,
+        )
+        .await?;
+
+        Ok(preview)
+    }
+
+    /// Land a stack of commits to a bookmark via pushrebase.
+    pub async fn land_stack(
+        &self,
+        bookmark: impl AsRef<str>,
+        head: ChangesetId,
+        base: ChangesetId,
+        pushvars: Option<&HashMap<String, Bytes>>,
+        bookmark_restrictions: BookmarkKindRestrictions,
+        push_authored_by: PushAuthoredBy,
+    ) -> Result<PushrebaseOutcome, MononokeError> {
+        self.start_write()?;
+
+        let bookmark = bookmark.as_ref();
+        let bookmark = BookmarkKey::new(bookmark)?;
+
+        let lca_hint: Arc<dyn LeastCommonAncestorsHint> = self.skiplist_index_arc();
+        let changesets = self.stack_changesets(&lca_hint, head, base).await?;
+        let ctx = self.ctx();
+
         // We CANNOT do remote pushrebase here otherwise it would result in an infinite
         // loop, as this code is used for remote pushrebase. Let's use local pushrebase.
 
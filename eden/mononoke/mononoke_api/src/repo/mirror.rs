@@ -0,0 +1,58 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use bytes::Bytes;
+use commit_bundle::commits_to_export;
+use commit_bundle::export_bundle;
+use mononoke_types::ChangesetId;
+
+use crate::errors::MononokeError;
+use crate::repo::RepoContext;
+
+/// A bundle of commits exported for a mirror to import.
+pub struct CommitBundle {
+    /// The bundle, as written by `commit_bundle::export_bundle`.
+    pub data: Bytes,
+    /// The commits in the bundle, parents first.
+    pub commits: Vec<ChangesetId>,
+}
+
+impl RepoContext {
+    /// Export the commits that are ancestors of `heads` but not of `common`
+    /// as a bundle, so that a mirror of this repo can import them.
+    ///
+    /// At most `limit` commits are exported, and no more commits are added
+    /// once the bundle reaches `max_size` bytes.  If there are more, the
+    /// bundle contains the oldest of them, and the rest can be exported by
+    /// adding the commits in the bundle to `common`.
+    pub async fn export_commit_bundle(
+        &self,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+        limit: usize,
+        max_size: u64,
+    ) -> Result<CommitBundle, MononokeError> {
+        // The bundle contains the full contents of the commits, so this
+        // requires access to the whole repo.
+        self.authorization_context()
+            .require_full_repo_read(self.ctx(), self.inner_repo())
+            .await?;
+
+        let repo = self.repo();
+        let bonsais = commits_to_export(self.ctx(), repo, heads, common, Some(limit)).await?;
+
+        let (data, stats) =
+            export_bundle(self.ctx(), repo, &bonsais, Vec::new(), Some(max_size)).await?;
+        Ok(CommitBundle {
+            data: Bytes::from(data),
+            commits: bonsais[..stats.commits]
+                .iter()
+                .map(|bcs| bcs.get_changeset_id())
+                .collect(),
+        })
+    }
+}
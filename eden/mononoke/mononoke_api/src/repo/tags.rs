@@ -0,0 +1,127 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use ephemeral_blobstore::StorageLocation;
+use mononoke_types::ChangesetId;
+use mononoke_types::Timestamp;
+use repo_authorization::RepoWriteOperation;
+use repo_tags::RepoTagsRef;
+use repo_tags::Tag;
+use webhooks::WebhookEvent;
+
+use crate::errors::MononokeError;
+use crate::repo::RepoContext;
+
+/// Tag names are stored in a column of this many bytes.
+const MAX_TAG_NAME_LEN: usize = 512;
+
+fn validate_tag_name(name: &str) -> Result<(), MononokeError> {
+    if name.is_empty() {
+        return Err(MononokeError::InvalidRequest("Tag name must not be empty".to_string()));
+    }
+    if name.len() > MAX_TAG_NAME_LEN {
+        return Err(MononokeError::InvalidRequest(format!(
+            "Tag name must be at most {} bytes",
+            MAX_TAG_NAME_LEN
+        )));
+    }
+    if name.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(MononokeError::InvalidRequest(format!(
+            "Tag name '{}' must not contain whitespace or control characters",
+            name
+        )));
+    }
+    Ok(())
+}
+
+impl RepoContext {
+    /// Create a tag.  Tags can't be moved or deleted once created, so this
+    /// fails if the tag already exists.
+    pub async fn create_tag(
+        &self,
+        name: &str,
+        target: ChangesetId,
+        message: Option<String>,
+        signer: Option<String>,
+    ) -> Result<Tag, MononokeError> {
+        self.start_write()?;
+        validate_tag_name(name)?;
+        self.authorization_context()
+            .require_repo_write(self.ctx(), self.inner_repo(), RepoWriteOperation::CreateTag)
+            .await?;
+        self.authorization_context()
+            .require_tag_create(self.ctx(), self.inner_repo(), name)
+            .await?;
+        if !self
+            .changeset_exists(target, StorageLocation::Persistent)
+            .await?
+        {
+            return Err(MononokeError::InvalidRequest(format!(
+                "Cannot tag unknown commit {}",
+                target
+            )));
+        }
+
+        let tag = Tag {
+            name: name.to_string(),
+            cs_id: target,
+            message,
+            signer,
+            creator: self.ctx().metadata().unix_name().map(ToString::to_string),
+            created_at: Timestamp::now(),
+        };
+        if !self.repo().repo_tags().create(self.ctx(), tag.clone()).await? {
+            return Err(MononokeError::InvalidRequest(format!("Tag '{}' already exists", name)));
+        }
+
+        webhooks::send_event(
+            self.ctx(),
+            self.name(),
+            &self.config().webhooks,
+            WebhookEvent::TagCreated {
+                tag: tag.name.clone(),
+                changeset_id: tag.cs_id,
+            },
+        );
+        Ok(tag)
+    }
+
+    /// Get a tag by name.
+    pub async fn tag(&self, name: &str) -> Result<Option<Tag>, MononokeError> {
+        Ok(self.repo().repo_tags().get(self.ctx(), name).await?)
+    }
+
+    /// List tags whose names start with `prefix`, ordered by name.  If
+    /// `after` is given, only tags whose names sort after it are listed.
+    pub async fn list_tags(
+        &self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: u64,
+    ) -> Result<Vec<Tag>, MononokeError> {
+        Ok(self
+            .repo()
+            .repo_tags()
+            .list(self.ctx(), prefix, after, limit)
+            .await?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_tag_name() {
+        assert!(validate_tag_name("v1.0").is_ok());
+        assert!(validate_tag_name("release/2023-01-01").is_ok());
+        assert!(validate_tag_name("").is_err());
+        assert!(validate_tag_name("v1 0").is_err());
+        assert!(validate_tag_name("v1\n").is_err());
+        assert!(validate_tag_name(&"v".repeat(MAX_TAG_NAME_LEN + 1)).is_err());
+    }
+}
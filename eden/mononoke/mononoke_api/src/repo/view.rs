@@ -0,0 +1,240 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Result;
+use bookmarks::BookmarkCategory;
+use bookmarks::BookmarkKind;
+use bookmarks::BookmarkPagination;
+use bookmarks::BookmarkPrefix;
+use bookmarks::Bookmarks;
+use bookmarks::Freshness;
+use commit_graph::CommitGraph;
+use context::CoreContext;
+use futures::stream::TryStreamExt;
+use mononoke_types::ChangesetId;
+
+/// How long the bookmarks a view serves are used for before they are listed
+/// again.
+const TIPS_TTL: Duration = Duration::from_secs(10);
+
+/// The most changesets whose visibility is remembered at once.
+const MAX_REMEMBERED: usize = 1_000_000;
+
+/// The changesets a view of a repo serves, which are the ancestors of the
+/// bookmarks it serves.
+///
+/// The bookmarks are listed at most once every `TIPS_TTL`, and whether
+/// changesets are served is remembered for as long as the bookmarks don't
+/// move, so that resolving changesets doesn't list the bookmarks and query
+/// the commit graph every time.
+#[derive(Default)]
+pub struct ViewVisibility {
+    state: Mutex<Option<VisibilityState>>,
+}
+
+struct VisibilityState {
+    listed_at: Instant,
+    /// The changesets the view's bookmarks point to, sorted.
+    tips: Vec<ChangesetId>,
+    served: HashSet<ChangesetId>,
+    unserved: HashSet<ChangesetId>,
+}
+
+impl VisibilityState {
+    fn new(tips: Vec<ChangesetId>) -> Self {
+        Self {
+            listed_at: Instant::now(),
+            served: tips.iter().copied().collect(),
+            tips,
+            unserved: HashSet::new(),
+        }
+    }
+}
+
+impl ViewVisibility {
+    /// The changesets among `cs_ids` that aren't ancestors of any of the
+    /// view's `bookmarks`.
+    pub async fn unserved(
+        &self,
+        ctx: &CoreContext,
+        bookmarks: &dyn Bookmarks,
+        commit_graph: &CommitGraph,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<HashSet<ChangesetId>> {
+        let tips = self.tips(ctx, bookmarks).await?;
+
+        let mut unserved = HashSet::new();
+        let mut unknown = Vec::new();
+        {
+            let state = self.state.lock().expect("lock poisoned");
+            for cs_id in cs_ids {
+                match state.as_ref() {
+                    Some(state) if state.tips == tips && state.served.contains(&cs_id) => {}
+                    Some(state) if state.tips == tips && state.unserved.contains(&cs_id) => {
+                        unserved.insert(cs_id);
+                    }
+                    _ => unknown.push(cs_id),
+                }
+            }
+        }
+        if unknown.is_empty() {
+            return Ok(unserved);
+        }
+
+        let served = commit_graph
+            .is_ancestor_many(ctx, unknown.clone(), tips.clone())
+            .await?
+            .into_values()
+            .flatten()
+            .collect::<HashSet<_>>();
+        let newly_unserved = unknown
+            .into_iter()
+            .filter(|cs_id| !served.contains(cs_id))
+            .collect::<HashSet<_>>();
+
+        let mut state = self.state.lock().expect("lock poisoned");
+        // The bookmarks may have moved while the commit graph was queried,
+        // in which case what was found out about them is already stale.
+        if let Some(state) = state.as_mut().filter(|state| state.tips == tips) {
+            if state.served.len() + state.unserved.len() > MAX_REMEMBERED {
+                state.served = state.tips.iter().copied().collect();
+                state.unserved.clear();
+            }
+            state.served.extend(served);
+            state.unserved.extend(newly_unserved.iter().copied());
+        }
+        unserved.extend(newly_unserved);
+        Ok(unserved)
+    }
+
+    /// The changesets the view's bookmarks point to, listing them again if
+    /// they were listed too long ago.
+    async fn tips(&self, ctx: &CoreContext, bookmarks: &dyn Bookmarks) -> Result<Vec<ChangesetId>> {
+        {
+            let state = self.state.lock().expect("lock poisoned");
+            if let Some(state) = state.as_ref() {
+                if state.listed_at.elapsed() < TIPS_TTL {
+                    return Ok(state.tips.clone());
+                }
+            }
+        }
+
+        // The bookmarks of a view are only those it serves.
+        let mut tips = bookmarks
+            .list(
+                ctx.clone(),
+                Freshness::MaybeStale,
+                &BookmarkPrefix::empty(),
+                BookmarkCategory::ALL,
+                BookmarkKind::ALL_PUBLISHING,
+                &BookmarkPagination::FromStart,
+                u64::MAX,
+            )
+            .map_ok(|(_bookmark, cs_id)| cs_id)
+            .try_collect::<Vec<_>>()
+            .await?;
+        tips.sort();
+        tips.dedup();
+
+        let mut state = self.state.lock().expect("lock poisoned");
+        match state.as_mut() {
+            Some(state) if state.tips == tips => state.listed_at = Instant::now(),
+            _ => *state = Some(VisibilityState::new(tips.clone())),
+        }
+        Ok(tips)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use blobrepo::BlobRepo;
+    use bookmarks::BookmarkKey;
+    use bookmarks::BookmarkUpdateReason;
+    use bookmarks::BookmarksArc;
+    use bookmarks::BookmarksRef;
+    use bookmarks::ViewBookmarks;
+    use fbinit::FacebookInit;
+    use in_memory_commit_graph_storage::InMemoryCommitGraphStorage;
+    use maplit::hashset;
+    use mononoke_types::RepositoryId;
+    use mononoke_types_mocks::changesetid::FIVES_CSID;
+    use mononoke_types_mocks::changesetid::FOURS_CSID;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::THREES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+    use regex::Regex;
+    use smallvec::smallvec;
+    use test_repo_factory::TestRepoFactory;
+
+    use super::*;
+
+    #[fbinit::test]
+    async fn test_view_visibility(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+
+        // 1-2-3 is main, which the view serves, and 2-4-5 is secret, which
+        // it doesn't.
+        let commit_graph = CommitGraph::new(Arc::new(InMemoryCommitGraphStorage::new(
+            RepositoryId::new(1),
+        )));
+        let parents = [
+            (ONES_CSID, smallvec![]),
+            (TWOS_CSID, smallvec![ONES_CSID]),
+            (THREES_CSID, smallvec![TWOS_CSID]),
+            (FOURS_CSID, smallvec![TWOS_CSID]),
+            (FIVES_CSID, smallvec![FOURS_CSID]),
+        ];
+        for (cs_id, parents) in parents {
+            commit_graph.add(&ctx, cs_id, parents).await?;
+        }
+
+        let blob_repo: BlobRepo = TestRepoFactory::new(fb)?.build()?;
+        let mut transaction = blob_repo.bookmarks().create_transaction(ctx.clone());
+        transaction.create(
+            &BookmarkKey::new("main")?,
+            THREES_CSID,
+            BookmarkUpdateReason::TestMove,
+        )?;
+        transaction.create(
+            &BookmarkKey::new("secret")?,
+            FIVES_CSID,
+            BookmarkUpdateReason::TestMove,
+        )?;
+        assert!(transaction.commit().await?);
+        let bookmarks = ViewBookmarks::new(blob_repo.bookmarks_arc(), vec![Regex::new("^main$")?]);
+
+        let visibility = ViewVisibility::default();
+        let cs_ids = vec![ONES_CSID, TWOS_CSID, THREES_CSID, FOURS_CSID, FIVES_CSID];
+        assert_eq!(
+            visibility
+                .unserved(&ctx, &bookmarks, &commit_graph, cs_ids.clone())
+                .await?,
+            hashset! {FOURS_CSID, FIVES_CSID},
+        );
+
+        // Asking again is answered from what is remembered, even for a
+        // commit graph that doesn't know about the changesets.
+        let empty_commit_graph = CommitGraph::new(Arc::new(InMemoryCommitGraphStorage::new(
+            RepositoryId::new(1),
+        )));
+        assert_eq!(
+            visibility
+                .unserved(&ctx, &bookmarks, &empty_commit_graph, cs_ids)
+                .await?,
+            hashset! {FOURS_CSID, FIVES_CSID},
+        );
+
+        Ok(())
+    }
+}
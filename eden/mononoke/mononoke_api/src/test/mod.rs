@@ -6,14 +6,22 @@
  */
 
 mod test_blame;
+mod test_bookmark_preconditions;
+mod test_changeset_signatures;
 mod test_changeset_diff;
+mod test_errors;
 mod test_file_diff;
 mod test_git;
+mod test_hidden_changesets;
 mod test_history;
+mod test_phases;
+mod test_pushrebase_only_bookmarks;
 mod test_repo;
 mod test_repo_bookmarks;
 mod test_repo_create_changeset;
 mod test_repo_create_changeset_stack;
 mod test_repo_land_stack;
 mod test_repo_modify_bookmarks;
+mod test_repo_tags;
+mod test_required_derived_data;
 mod test_sparse_profile;
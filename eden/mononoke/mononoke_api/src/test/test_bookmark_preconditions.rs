@@ -0,0 +1,222 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use blobrepo::BlobRepo;
+use bookmarks::BookmarkKey;
+use bookmarks_movement::BookmarkKindRestrictions::AnyKind;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use hooks::PushAuthoredBy::User;
+use metaconfig_types::BookmarkParams;
+use metaconfig_types::BookmarkPrecondition;
+use mononoke_types::ChangesetId;
+use regex::Regex;
+use test_repo_factory::TestRepoFactory;
+use tests_utils::drawdag::create_from_dag;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
+
+use crate::repo::Repo;
+use crate::repo::RepoContext;
+use crate::MononokeError;
+
+fn bookmark_params(regex: &str, preconditions: Vec<BookmarkPrecondition>) -> BookmarkParams {
+    BookmarkParams {
+        bookmark: Regex::new(regex).unwrap().into(),
+        hooks: vec![],
+        only_fast_forward: false,
+        allowed_users: None,
+        allowed_hipster_group: None,
+        allowed_groups: vec![],
+        allowed_service_identities: vec![],
+        rewrite_dates: None,
+        hooks_skip_ancestors_of: vec![],
+        ensure_ancestor_of: None,
+        allow_move_to_public_commits_without_hooks: false,
+        require_signed_commits: false,
+        pushrebase_only: false,
+        required_derived_data: vec![],
+        required_derived_data_budget: Duration::from_secs(0),
+        preconditions,
+    }
+}
+
+async fn read_request(stream: &mut TcpStream) {
+    let mut request = Vec::new();
+    let mut buf = [0; 4096];
+    loop {
+        let n = stream.read(&mut buf).await.unwrap();
+        if n == 0 {
+            return;
+        }
+        request.extend_from_slice(&buf[..n]);
+        if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+            let headers = String::from_utf8_lossy(&request[..end]).to_lowercase();
+            let length = headers
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .map_or(0, |length| length.trim().parse().unwrap());
+            if request.len() >= end + 4 + length {
+                return;
+            }
+        }
+    }
+}
+
+/// Serve a status check that answers each request with the next of
+/// `responses`, as a status line and a body.  Returns its URL.
+async fn serve_status_check(responses: Vec<(&'static str, &'static str)>) -> Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}/status", listener.local_addr()?);
+    tokio::spawn(async move {
+        for (status, body) in responses {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            read_request(&mut stream).await;
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        }
+    });
+    Ok(url)
+}
+
+async fn init_repo(
+    ctx: &CoreContext,
+    status_check_url: String,
+) -> Result<(RepoContext, BTreeMap<String, ChangesetId>)> {
+    let blob_repo: BlobRepo = TestRepoFactory::new(ctx.fb)?
+        .with_config_override(|config| {
+            config.bookmarks = vec![
+                bookmark_params("^master$", vec![BookmarkPrecondition::DescendsFromOld]),
+                bookmark_params(
+                    "^checked$",
+                    vec![BookmarkPrecondition::StatusCheck {
+                        url: status_check_url,
+                        timeout: Duration::from_secs(1),
+                    }],
+                ),
+            ];
+        })
+        .build()?;
+    let changesets = create_from_dag(
+        ctx,
+        &blob_repo,
+        r##"
+            A-B-C
+             \
+              D
+        "##,
+    )
+    .await?;
+    let repo = Arc::new(Repo::new_test(ctx.clone(), blob_repo).await?);
+    let repo_ctx = RepoContext::new_test(ctx.clone(), repo).await?;
+    Ok((repo_ctx, changesets))
+}
+
+#[fbinit::test]
+async fn test_descends_from_old(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let url = serve_status_check(vec![]).await?;
+    let (repo, changesets) = init_repo(&ctx, url).await?;
+    let master = BookmarkKey::new("master")?;
+
+    // Creating the bookmark has no old target to descend from.
+    repo.create_bookmark(&master, changesets["A"], None).await?;
+    repo.move_bookmark(&master, changesets["C"], None, false, None)
+        .await?;
+
+    // Even forced moves must descend from the old target.
+    let result = repo
+        .move_bookmark(&master, changesets["D"], None, true, None)
+        .await;
+    match result {
+        Err(MononokeError::InvalidRequest(msg)) => assert_eq!(
+            msg,
+            format!(
+                "Bookmark 'master' cannot be moved: precondition descends_from_old failed: \
+                 {} does not descend from {}",
+                changesets["D"], changesets["C"]
+            )
+        ),
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    // Other bookmarks are unaffected.
+    let other = BookmarkKey::new("other")?;
+    repo.create_bookmark(&other, changesets["C"], None).await?;
+    repo.move_bookmark(&other, changesets["D"], None, true, None)
+        .await?;
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_status_check(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let url = serve_status_check(vec![
+        ("200 OK", r#"{"status": "green"}"#),
+        ("200 OK", r#"{"status": "red", "description": "tests failed"}"#),
+        ("500 Internal Server Error", "unavailable"),
+    ])
+    .await?;
+    let (repo, changesets) = init_repo(&ctx, url.clone()).await?;
+    let checked = BookmarkKey::new("checked")?;
+
+    // Moves the status check reports as green are allowed.
+    repo.create_bookmark(&checked, changesets["A"], None).await?;
+
+    // Other moves are rejected with the reason the status check gave.
+    let result = repo
+        .move_bookmark(&checked, changesets["B"], None, false, None)
+        .await;
+    match result {
+        Err(MononokeError::InvalidRequest(msg)) => assert_eq!(
+            msg,
+            format!(
+                "Bookmark 'checked' cannot be moved: precondition status_check failed: \
+                 {} reported {} as red: tests failed",
+                url, changesets["B"]
+            )
+        ),
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    // Moves are also rejected if the status check fails.
+    let result = repo
+        .move_bookmark(&checked, changesets["B"], None, false, None)
+        .await;
+    match result {
+        Err(MononokeError::InternalError(e)) => {
+            assert!(format!("{:#}", e).contains("failed with status 500"))
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    // Commits created by pushrebase can't have passed the status check.
+    let result = repo
+        .land_stack("checked", changesets["D"], changesets["A"], None, AnyKind, User)
+        .await;
+    match result {
+        Err(MononokeError::InvalidRequest(msg)) => assert_eq!(
+            msg,
+            "Pushrebase is not allowed onto the bookmark 'checked', because moves of this \
+             bookmark must pass a status check"
+        ),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    Ok(())
+}
@@ -0,0 +1,147 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use blobrepo::BlobRepo;
+use bookmarks::BookmarkKey;
+use commit_signatures::signed_payload;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use maplit::hashmap;
+use metaconfig_types::BookmarkParams;
+use metaconfig_types::CommitSigningConfig;
+use mononoke_types::ChangesetId;
+use openssl::pkey::PKey;
+use openssl::pkey::Private;
+use openssl::sign::Signer;
+use test_repo_factory::TestRepoFactory;
+use tests_utils::drawdag::create_from_dag;
+
+use crate::repo::Repo;
+use crate::repo::RepoContext;
+use crate::ChangesetSpecifier;
+use crate::CommitSignature;
+use crate::MononokeError;
+use crate::SignatureVerification;
+
+async fn init_repo(
+    ctx: &CoreContext,
+    key: &PKey<Private>,
+) -> Result<(RepoContext, BTreeMap<String, ChangesetId>)> {
+    let public_key = String::from_utf8(key.public_key_to_pem()?)?;
+    let blob_repo: BlobRepo = TestRepoFactory::new(ctx.fb)?
+        .with_config_override(|config| {
+            config.commit_signing_config = CommitSigningConfig {
+                trusted_keys: hashmap! { "release".to_string() => public_key },
+            };
+            config.bookmarks = vec![BookmarkParams {
+                bookmark: BookmarkKey::new("release").unwrap().into(),
+                hooks: vec![],
+                only_fast_forward: false,
+                allowed_users: None,
+                allowed_hipster_group: None,
+                allowed_groups: vec![],
+                allowed_service_identities: vec![],
+                rewrite_dates: None,
+                hooks_skip_ancestors_of: vec![],
+                ensure_ancestor_of: None,
+                allow_move_to_public_commits_without_hooks: false,
+                require_signed_commits: true,
+                pushrebase_only: false,
+                required_derived_data: vec![],
+                required_derived_data_budget: Duration::from_secs(0),
+                preconditions: vec![],
+            }];
+        })
+        .build()?;
+    let changesets = create_from_dag(
+        ctx,
+        &blob_repo,
+        r##"
+            A-B-C
+        "##,
+    )
+    .await?;
+    let repo = Repo::new_test(ctx.clone(), blob_repo).await?;
+    let repo_ctx = RepoContext::new_test(ctx.clone(), Arc::new(repo)).await?;
+    Ok((repo_ctx, changesets))
+}
+
+fn sign(key: &PKey<Private>, cs_id: ChangesetId) -> Result<CommitSignature> {
+    let mut signer = Signer::new_without_digest(key)?;
+    Ok(CommitSignature {
+        key_id: "release".to_string(),
+        signature: signer.sign_oneshot_to_vec(&signed_payload(cs_id))?,
+    })
+}
+
+#[fbinit::test]
+async fn test_signature_verification(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let key = PKey::generate_ed25519()?;
+    let other_key = PKey::generate_ed25519()?;
+    let (repo, changesets) = init_repo(&ctx, &key).await?;
+
+    let a = repo
+        .changeset(ChangesetSpecifier::Bonsai(changesets["A"]))
+        .await?
+        .expect("changeset should exist");
+    assert_eq!(
+        a.signature_verification().await?,
+        SignatureVerification::Unsigned
+    );
+
+    a.add_signature(sign(&other_key, changesets["A"])?).await?;
+    assert_eq!(
+        a.signature_verification().await?,
+        SignatureVerification::Invalid {
+            key_id: "release".to_string()
+        }
+    );
+
+    a.add_signature(sign(&key, changesets["A"])?).await?;
+    assert_eq!(
+        a.signature_verification().await?,
+        SignatureVerification::Verified {
+            key_id: "release".to_string()
+        }
+    );
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_bookmark_requires_signed_commits(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let key = PKey::generate_ed25519()?;
+    let (repo, changesets) = init_repo(&ctx, &key).await?;
+    let release = BookmarkKey::new("release")?;
+
+    // Unsigned commits can't be the target of the bookmark.
+    let result = repo.create_bookmark(&release, changesets["B"], None).await;
+    assert!(matches!(result, Err(MononokeError::InvalidRequest(_))));
+
+    let b = repo
+        .changeset(ChangesetSpecifier::Bonsai(changesets["B"]))
+        .await?
+        .expect("changeset should exist");
+    b.add_signature(sign(&key, changesets["B"])?).await?;
+    repo.create_bookmark(&release, changesets["B"], None).await?;
+
+    // Other bookmarks are unaffected.
+    let other = BookmarkKey::new("other")?;
+    repo.create_bookmark(&other, changesets["C"], None).await?;
+
+    let result = repo
+        .move_bookmark(&release, changesets["C"], None, false, None)
+        .await;
+    assert!(matches!(result, Err(MononokeError::InvalidRequest(_))));
+    Ok(())
+}
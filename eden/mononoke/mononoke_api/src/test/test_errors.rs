@@ -0,0 +1,57 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::anyhow;
+use anyhow::Error;
+use anyhow::Result;
+use metaconfig_types::ReadOnlyInfo;
+
+use crate::MononokeError;
+use crate::MononokeErrorCode;
+
+#[test]
+fn test_error_codes() -> Result<()> {
+    let invalid = MononokeError::InvalidRequest("bad path".to_string());
+    assert_eq!(invalid.code(), MononokeErrorCode::InvalidRequest);
+    assert!(invalid.is_request_error());
+
+    let denied = MononokeError::ServicePermissionDenied {
+        identities: "USER:alice".to_string(),
+        reponame: "repo".to_string(),
+        service_identity: "service".to_string(),
+    };
+    assert_eq!(denied.code(), MononokeErrorCode::PermissionDenied);
+    assert!(denied.is_request_error());
+
+    let read_only = MononokeError::RepoReadOnly(ReadOnlyInfo::new("migrating"));
+    assert_eq!(read_only.code(), MononokeErrorCode::RepoReadOnly);
+    assert!(read_only.is_request_error());
+
+    let internal = MononokeError::from(anyhow!("blobstore unavailable"));
+    assert_eq!(internal.code(), MononokeErrorCode::InternalError);
+    assert!(!internal.is_request_error());
+    Ok(())
+}
+
+#[test]
+fn test_classify_errors() -> Result<()> {
+    let error = Error::from(MononokeError::NotAvailable(
+        "derivation disabled".to_string(),
+    ))
+    .context("Failed to list directory");
+    assert_eq!(
+        MononokeErrorCode::classify(&error),
+        MononokeErrorCode::NotAvailable
+    );
+
+    let error = anyhow!("connection reset");
+    assert_eq!(
+        MononokeErrorCode::classify(&error),
+        MononokeErrorCode::InternalError
+    );
+    Ok(())
+}
@@ -0,0 +1,128 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::Result;
+use blobrepo::BlobRepo;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use hidden_changesets::HiddenChangesetsRef;
+use maplit::hashmap;
+use test_repo_factory::TestRepoFactory;
+use tests_utils::drawdag::create_from_dag;
+
+use crate::repo::Repo;
+use crate::repo::RepoContext;
+use crate::ChangesetId;
+use crate::ChangesetIdPrefix;
+use crate::ChangesetSpecifier;
+use crate::ChangesetSpecifierPrefixResolution;
+
+#[fbinit::test]
+async fn test_hidden_changesets(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let blob_repo: BlobRepo = TestRepoFactory::new(fb)?.build()?;
+    let changesets = create_from_dag(
+        &ctx,
+        &blob_repo,
+        r##"
+            A-B-C
+        "##,
+    )
+    .await?;
+    let repo = RepoContext::new_test(
+        ctx.clone(),
+        Arc::new(Repo::new_test(ctx.clone(), blob_repo).await?),
+    )
+    .await?;
+    let hidden = changesets["C"];
+    let prefix = ChangesetIdPrefix::from_str(&hidden.to_string()[..16])?;
+
+    assert!(repo.changeset(ChangesetSpecifier::Bonsai(hidden)).await?.is_some());
+    assert_eq!(
+        repo.resolve_changeset_id_prefix(prefix.into()).await?,
+        ChangesetSpecifierPrefixResolution::Single(ChangesetSpecifier::Bonsai(hidden))
+    );
+
+    repo.repo()
+        .hidden_changesets()
+        .hide(&ctx, &[hidden], "leaked secret")
+        .await?;
+
+    // Hidden changesets can't be resolved, either directly or by prefix.
+    assert!(repo.changeset(ChangesetSpecifier::Bonsai(hidden)).await?.is_none());
+    assert_eq!(
+        repo.resolve_changeset_id_prefix(prefix.into()).await?,
+        ChangesetSpecifierPrefixResolution::NoMatch
+    );
+
+    // Nor are they listed as part of a stack.
+    let stack = repo.stack(vec![hidden], 10).await?;
+    assert_eq!(stack.draft, vec![changesets["B"], changesets["A"]]);
+
+    // Unhiding makes the changeset visible again.
+    repo.repo()
+        .hidden_changesets()
+        .unhide(&ctx, &[hidden])
+        .await?;
+    assert!(repo.changeset(ChangesetSpecifier::Bonsai(hidden)).await?.is_some());
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_many_changeset_ids_from_specifiers(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let blob_repo: BlobRepo = TestRepoFactory::new(fb)?.build()?;
+    let changesets = create_from_dag(
+        &ctx,
+        &blob_repo,
+        r##"
+            A-B-C
+        "##,
+    )
+    .await?;
+    let repo = RepoContext::new_test(
+        ctx.clone(),
+        Arc::new(Repo::new_test(ctx.clone(), blob_repo).await?),
+    )
+    .await?;
+    let hg_id = repo
+        .changeset(ChangesetSpecifier::Bonsai(changesets["B"]))
+        .await?
+        .expect("changeset should exist")
+        .hg_id()
+        .await?
+        .expect("hg changeset should exist");
+    let missing = ChangesetId::from_bytes([1; 32])?;
+
+    repo.repo()
+        .hidden_changesets()
+        .hide(&ctx, &[changesets["C"]], "leaked secret")
+        .await?;
+
+    // Missing and hidden changesets are left out.
+    let resolved = repo
+        .many_changeset_ids_from_specifiers(vec![
+            ChangesetSpecifier::Bonsai(changesets["A"]),
+            ChangesetSpecifier::Hg(hg_id),
+            ChangesetSpecifier::Bonsai(changesets["C"]),
+            ChangesetSpecifier::Bonsai(missing),
+        ])
+        .await?;
+    assert_eq!(
+        resolved,
+        hashmap! {
+            ChangesetSpecifier::Bonsai(changesets["A"]) => changesets["A"],
+            ChangesetSpecifier::Hg(hg_id) => changesets["B"],
+        }
+    );
+
+    Ok(())
+}
@@ -15,6 +15,7 @@ use futures::stream::TryStreamExt;
 use mononoke_types::DateTime;
 use tests_utils::CreateCommitContext;
 
+use crate::prefetch_changeset_info;
 use crate::ChangesetHistoryOptions;
 use crate::ChangesetId;
 use crate::ChangesetPathHistoryOptions;
@@ -365,6 +366,34 @@ async fn commit_path_history(fb: FacebookInit) -> Result<()> {
     Ok(())
 }
 
+#[fbinit::test]
+async fn commit_history_prefetch_changeset_info(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (repo, changesets) = init_repo(&ctx).await?;
+
+    let cs = repo
+        .changeset(changesets["c2"])
+        .await?
+        .expect("changeset exists");
+    let history: Vec<_> = cs
+        .history(Default::default())
+        .await
+        .and_then(|cs| async move { Ok(cs.id()) })
+        .try_collect()
+        .await?;
+
+    // Prefetching keeps the history in order, across several batches.
+    let prefetched: Vec<_> = prefetch_changeset_info(cs.history(Default::default()).await, 3, 6)
+        .and_then(|cs| async move { Ok((cs.id(), cs.author_date().await?.timestamp())) })
+        .try_collect()
+        .await?;
+    let prefetched_ids: Vec<_> = prefetched.iter().map(|(id, _)| *id).collect();
+    assert_eq!(prefetched_ids, history);
+    assert_eq!(prefetched.last(), Some(&(changesets["a1"], 1000)));
+
+    Ok(())
+}
+
 #[fbinit::test]
 async fn commit_history(fb: FacebookInit) -> Result<()> {
     let ctx = CoreContext::test_mock(fb);
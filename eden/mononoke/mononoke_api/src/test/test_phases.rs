@@ -0,0 +1,65 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use blobrepo::BlobRepo;
+use bookmarks::BookmarkKey;
+use bookmarks::BookmarkUpdateReason;
+use bookmarks::BookmarksRef;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use test_repo_factory::TestRepoFactory;
+use tests_utils::drawdag::create_from_dag;
+
+use crate::repo::Repo;
+use crate::repo::RepoContext;
+use crate::ChangesetSpecifier;
+
+#[fbinit::test]
+async fn test_publishing_bookmarks(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let blob_repo: BlobRepo = TestRepoFactory::new(fb)?
+        .with_config_override(|config| {
+            config.publishing_bookmarks = vec![BookmarkKey::new("release").unwrap()];
+        })
+        .build()?;
+    let changesets = create_from_dag(
+        &ctx,
+        &blob_repo,
+        r##"
+            A-B-C
+        "##,
+    )
+    .await?;
+    let mut txn = blob_repo.bookmarks().create_transaction(ctx.clone());
+    txn.force_set(
+        &BookmarkKey::new("release")?,
+        changesets["B"],
+        BookmarkUpdateReason::TestMove,
+    )?;
+    txn.force_set(
+        &BookmarkKey::new("other")?,
+        changesets["C"],
+        BookmarkUpdateReason::TestMove,
+    )?;
+    txn.commit().await?;
+
+    let repo = Repo::new_test(ctx.clone(), blob_repo).await?;
+    let repo = RepoContext::new_test(ctx.clone(), Arc::new(repo)).await?;
+
+    // Only ancestors of the configured publishing bookmarks are public.
+    for (name, expected_public) in [("A", true), ("B", true), ("C", false)] {
+        let cs = repo
+            .changeset(ChangesetSpecifier::Bonsai(changesets[name]))
+            .await?
+            .expect("changeset should exist");
+        assert_eq!(cs.is_public().await?, expected_public, "commit {}", name);
+    }
+    Ok(())
+}
@@ -0,0 +1,142 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use blobrepo::BlobRepo;
+use bookmarks::BookmarkKey;
+use bookmarks_movement::BookmarkKindRestrictions::AnyKind;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use hooks::PushAuthoredBy::User;
+use metaconfig_types::BookmarkParams;
+use mononoke_types::ChangesetId;
+use regex::Regex;
+use repo_authorization::AuthorizationContext;
+use test_repo_factory::TestRepoFactory;
+use tests_utils::drawdag::create_from_dag;
+
+use crate::repo::Repo;
+use crate::repo::RepoContext;
+use crate::MononokeError;
+
+/// Returns a repo context for a user, and one that bypasses access control
+/// as admin tools do.
+async fn init_repo(
+    ctx: &CoreContext,
+) -> Result<(RepoContext, RepoContext, BTreeMap<String, ChangesetId>)> {
+    let blob_repo: BlobRepo = TestRepoFactory::new(ctx.fb)?
+        .with_config_override(|config| {
+            config.bookmarks = vec![BookmarkParams {
+                bookmark: Regex::new("^(master|releases/.*)$").unwrap().into(),
+                hooks: vec![],
+                only_fast_forward: false,
+                allowed_users: None,
+                allowed_hipster_group: None,
+                allowed_groups: vec![],
+                allowed_service_identities: vec![],
+                rewrite_dates: None,
+                hooks_skip_ancestors_of: vec![],
+                ensure_ancestor_of: None,
+                allow_move_to_public_commits_without_hooks: false,
+                require_signed_commits: false,
+                pushrebase_only: true,
+                required_derived_data: vec![],
+                required_derived_data_budget: Duration::from_secs(0),
+                preconditions: vec![],
+            }];
+        })
+        .build()?;
+    let changesets = create_from_dag(
+        ctx,
+        &blob_repo,
+        r##"
+            A-B-C
+             \
+              D
+        "##,
+    )
+    .await?;
+    let repo = Arc::new(Repo::new_test(ctx.clone(), blob_repo).await?);
+    let user_repo = RepoContext::new(
+        ctx.clone(),
+        Arc::new(AuthorizationContext::new(ctx)),
+        repo.clone(),
+        None,
+        None,
+    )
+    .await?;
+    let admin_repo = RepoContext::new_test(ctx.clone(), repo).await?;
+    Ok((user_repo, admin_repo, changesets))
+}
+
+#[fbinit::test]
+async fn test_pushrebase_only_bookmarks(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (user_repo, admin_repo, changesets) = init_repo(&ctx).await?;
+    let master = BookmarkKey::new("master")?;
+    let release = BookmarkKey::new("releases/1.0")?;
+
+    // Users can't create or move protected bookmarks directly.
+    let result = user_repo
+        .create_bookmark(&master, changesets["A"], None)
+        .await;
+    assert!(matches!(result, Err(MononokeError::InvalidRequest(_))));
+    let result = user_repo
+        .create_bookmark(&release, changesets["A"], None)
+        .await;
+    assert!(matches!(result, Err(MononokeError::InvalidRequest(_))));
+
+    // Admins can.
+    admin_repo
+        .create_bookmark(&master, changesets["A"], None)
+        .await?;
+    admin_repo
+        .move_bookmark(&master, changesets["B"], None, false, None)
+        .await?;
+
+    // Users can't move or force-move them either.
+    let result = user_repo
+        .move_bookmark(&master, changesets["C"], None, false, None)
+        .await;
+    assert!(matches!(result, Err(MononokeError::InvalidRequest(_))));
+    let result = user_repo
+        .move_bookmark(&master, changesets["A"], None, true, None)
+        .await;
+    assert!(matches!(result, Err(MononokeError::InvalidRequest(_))));
+
+    // Or delete them.
+    let result = user_repo.delete_bookmark(&master, None, None).await;
+    assert!(matches!(result, Err(MononokeError::InvalidRequest(_))));
+    admin_repo
+        .create_bookmark(&release, changesets["A"], None)
+        .await?;
+    let result = user_repo.delete_bookmark(&release, None, None).await;
+    assert!(matches!(result, Err(MononokeError::InvalidRequest(_))));
+
+    // Users can pushrebase onto protected bookmarks.
+    user_repo
+        .land_stack("master", changesets["D"], changesets["A"], None, AnyKind, User)
+        .await?;
+
+    // Other bookmarks are unaffected.
+    let other = BookmarkKey::new("other")?;
+    user_repo
+        .create_bookmark(&other, changesets["A"], None)
+        .await?;
+    user_repo
+        .move_bookmark(&other, changesets["C"], None, false, None)
+        .await?;
+    user_repo.delete_bookmark(&other, None, None).await?;
+
+    // Admins can delete protected bookmarks.
+    admin_repo.delete_bookmark(&release, None, None).await?;
+    Ok(())
+}
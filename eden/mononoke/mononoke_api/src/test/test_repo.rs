@@ -43,6 +43,9 @@ use repo_blobstore::RepoBlobstoreRef;
 use repo_identity::RepoIdentityRef;
 use slog::info;
 use synced_commit_mapping::ArcSyncedCommitMapping;
+use synced_commit_mapping::SyncedCommitMapping;
+use synced_commit_mapping::SyncedCommitMappingEntry;
+use synced_commit_mapping::SyncedCommitSourceRepo;
 use tests_utils::bookmark;
 use tests_utils::resolve_cs_id;
 use tests_utils::CreateCommitContext;
@@ -51,6 +54,7 @@ use tunables::with_tunables_async;
 use tunables::MononokeTunables;
 
 use crate::BookmarkFreshness;
+use crate::CandidateSelectionHintArgs;
 use crate::ChangesetFileOrdering;
 use crate::ChangesetId;
 use crate::ChangesetIdPrefix;
@@ -67,6 +71,7 @@ use crate::Mononoke;
 use crate::MononokePath;
 use crate::TreeEntry;
 use crate::TreeId;
+use crate::XRepoLookupMatch;
 
 #[fbinit::test]
 async fn commit_info_by_hash(fb: FacebookInit) -> Result<(), Error> {
@@ -1177,6 +1182,108 @@ async fn xrepo_commit_lookup_draft(fb: FacebookInit) -> Result<(), Error> {
     Ok(())
 }
 
+#[fbinit::test]
+async fn xrepo_commit_lookup_match(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let (mononoke, _cfg_src) = init_x_repo(&ctx).await?;
+
+    let smallrepo = mononoke
+        .repo(ctx.clone(), "smallrepo")
+        .await?
+        .expect("repo exists")
+        .build()
+        .await?;
+    let largerepo = mononoke
+        .repo(ctx.clone(), "largerepo")
+        .await?
+        .expect("repo exists")
+        .build()
+        .await?;
+    let large_master_cs_id = resolve_cs_id(&ctx, largerepo.blob_repo(), "master").await?;
+
+    // A commit that touches synced files is rewritten exactly
+    let remapped_draft =
+        CreateCommitContext::new(&ctx, largerepo.blob_repo(), vec![large_master_cs_id])
+            .add_file("prefix/remapped", "content1")
+            .commit()
+            .await?;
+    let (remapped_cs, lookup_match) = largerepo
+        .xrepo_commit_lookup_with_match(&smallrepo, remapped_draft, None)
+        .await?
+        .expect("changeset should exist");
+    assert_eq!(lookup_match, XRepoLookupMatch::Exact);
+
+    // A commit that only touches files that are not synced maps to its
+    // synced ancestor, which has an equivalent working copy
+    let not_remapped_draft =
+        CreateCommitContext::new(&ctx, largerepo.blob_repo(), vec![remapped_draft])
+            .add_file("not_remapped", "content2")
+            .commit()
+            .await?;
+    let (cs, lookup_match) = largerepo
+        .xrepo_commit_lookup_with_match(&smallrepo, not_remapped_draft, None)
+        .await?
+        .expect("changeset should exist");
+    assert_eq!(lookup_match, XRepoLookupMatch::WorkingCopyEquivalent);
+    assert_eq!(cs.id(), remapped_cs.id());
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn xrepo_commit_lookup_match_plural(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let (mononoke, _cfg_src) = init_x_repo(&ctx).await?;
+
+    let smallrepo = mononoke
+        .repo(ctx.clone(), "smallrepo")
+        .await?
+        .expect("repo exists")
+        .build()
+        .await?;
+    let largerepo = mononoke
+        .repo(ctx.clone(), "largerepo")
+        .await?
+        .expect("repo exists")
+        .build()
+        .await?;
+    let small_master_cs_id = resolve_cs_id(&ctx, smallrepo.blob_repo(), "master").await?;
+    let large_master_cs_id = resolve_cs_id(&ctx, largerepo.blob_repo(), "master").await?;
+
+    // Rewrite the small repo's master a second time, so that it has several
+    // remappings in the large repo
+    let other_large =
+        CreateCommitContext::new(&ctx, largerepo.blob_repo(), vec![large_master_cs_id])
+            .commit()
+            .await?;
+    largerepo
+        .synced_commit_mapping()
+        .add(
+            &ctx,
+            SyncedCommitMappingEntry::new(
+                largerepo.blob_repo().repo_identity().id(),
+                other_large,
+                smallrepo.blob_repo().repo_identity().id(),
+                small_master_cs_id,
+                CommitSyncConfigVersion("TEST_VERSION_NAME".to_string()),
+                SyncedCommitSourceRepo::Small,
+            ),
+        )
+        .await?;
+
+    for large_cs_id in [large_master_cs_id, other_large] {
+        let hint = CandidateSelectionHintArgs::Exact(ChangesetSpecifier::Bonsai(large_cs_id));
+        let (cs, lookup_match) = smallrepo
+            .xrepo_commit_lookup_with_match(&largerepo, small_master_cs_id, Some(hint))
+            .await?
+            .expect("changeset should exist");
+        assert_eq!(cs.id(), large_cs_id);
+        assert_eq!(lookup_match, XRepoLookupMatch::Exact);
+    }
+
+    Ok(())
+}
+
 #[fbinit::test]
 async fn xrepo_commit_lookup_public(fb: FacebookInit) -> Result<(), Error> {
     let ctx = CoreContext::test_mock(fb);
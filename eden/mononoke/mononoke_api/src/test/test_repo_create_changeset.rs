@@ -20,6 +20,7 @@ use fixtures::ManyFilesDirs;
 use fixtures::TestRepoFixture;
 use futures::try_join;
 use mononoke_types::hash::Sha256;
+use mononoke_types::ChangesetExtras;
 use repo_derived_data::RepoDerivedDataArc;
 use smallvec::SmallVec;
 
@@ -109,6 +110,7 @@ async fn create_commit(fb: FacebookInit, derived_data_to_derive: &str) -> Result
                 message: message.clone(),
                 extra: extra.clone(),
                 git_extra_headers: None,
+                extras: None,
             },
             changes.clone(),
             bubble,
@@ -137,6 +139,7 @@ async fn create_commit(fb: FacebookInit, derived_data_to_derive: &str) -> Result
                 message,
                 extra,
                 git_extra_headers: None,
+                extras: None,
             },
             changes,
             bubble,
@@ -260,6 +263,7 @@ async fn create_commit_bad_changes(fb: FacebookInit) -> Result<(), Error> {
                 message,
                 extra,
                 git_extra_headers,
+                extras: None,
             },
             changes,
             bubble,
@@ -398,6 +402,7 @@ async fn test_create_merge_commit(fb: FacebookInit) -> Result<(), Error> {
                 message: message.clone(),
                 extra: extra.clone(),
                 git_extra_headers,
+                extras: None,
             },
             changes.clone(),
             bubble,
@@ -506,6 +511,7 @@ async fn test_merge_commit_parent_file_conflict(fb: FacebookInit) -> Result<(),
                 message: message.clone(),
                 extra: extra.clone(),
                 git_extra_headers,
+                extras: None,
             },
             changes.clone(),
             bubble,
@@ -623,6 +629,7 @@ async fn test_merge_commit_parent_tree_file_conflict(fb: FacebookInit) -> Result
                 message: message.clone(),
                 extra: extra.clone(),
                 git_extra_headers,
+                extras: None,
             },
             changes.clone(),
             bubble,
@@ -697,3 +704,60 @@ async fn test_merge_commit_parent_tree_file_conflict(fb: FacebookInit) -> Result
 
     Ok(())
 }
+
+#[fbinit::test]
+async fn test_create_commit_with_extras(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let mononoke = Mononoke::new_test(
+        ctx.clone(),
+        vec![("test".to_string(), Linear::getrepo(fb).await)],
+    )
+    .await?;
+    let repo = mononoke
+        .repo(ctx.clone(), "test")
+        .await?
+        .expect("repo exists")
+        .build()
+        .await?;
+
+    let parent_hash = "7785606eb1f26ff5722c831de402350cf97052dc44bc175da6ac0d715a3dbbf6";
+    let author_date = FixedOffset::east_opt(0)
+        .unwrap()
+        .with_ymd_and_hms(2000, 2, 1, 12, 0, 0)
+        .unwrap();
+    let extras = ChangesetExtras {
+        branch: Some("release".to_string()),
+        signed_off_by: vec!["Test Reviewer <reviewer@example.com>".to_string()],
+        committer_identity: Some("USER:test".to_string()),
+    };
+
+    let create = |extras| {
+        repo.create_changeset(
+            vec![ChangesetId::from_str(parent_hash).unwrap()],
+            CreateInfo {
+                author: String::from("Test Author <test@example.com>"),
+                author_date,
+                committer: None,
+                committer_date: None,
+                message: String::from("Test Created Commit"),
+                extra: BTreeMap::new(),
+                git_extra_headers: None,
+                extras,
+            },
+            BTreeMap::new(),
+            None,
+        )
+    };
+
+    let cs = create(Some(extras.clone())).await?;
+    assert_eq!(cs.extras().await?, Some(extras));
+
+    // Empty extras are not recorded, so they don't change the commit id.
+    let without_extras = create(None).await?;
+    let empty_extras = create(Some(ChangesetExtras::default())).await?;
+    assert_eq!(empty_extras.extras().await?, None);
+    assert_eq!(empty_extras.id(), without_extras.id());
+    assert_ne!(cs.id(), without_extras.id());
+
+    Ok(())
+}
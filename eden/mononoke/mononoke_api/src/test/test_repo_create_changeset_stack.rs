@@ -55,6 +55,7 @@ async fn create_changeset_stack(
             message: format!("Test Created Commit {n}"),
             extra: extra.clone(),
             git_extra_headers: git_extra_headers.clone(),
+            extras: None,
         })
         .collect::<Vec<_>>();
     repo.create_changeset_stack(stack_parents, info_stack, changes_stack, bubble)
@@ -88,6 +89,7 @@ async fn create_changesets_sequentially(
             message: format!("Test Created Commit {change_num}"),
             extra: extra.clone(),
             git_extra_headers: git_extra_headers.clone(),
+            extras: None,
         };
         let commit = repo
             .create_changeset(parents, info, changes, bubble)
@@ -170,3 +170,27 @@ async fn land_stack(fb: FacebookInit) -> Result<()> {
 
     Ok(())
 }
+
+#[fbinit::test]
+async fn preview_land_stack(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (repo, changesets) = init_repo(&ctx).await?;
+
+    // D and E would be rebased over B and C.
+    let preview = repo
+        .preview_land_stack("trunk", changesets["E"], changesets["A"])
+        .await?;
+    assert_eq!(preview.bookmark_value, Some(changesets["C"]));
+    assert_eq!(preview.root, changesets["A"]);
+    assert_eq!(preview.pushrebase_distance.0, 2);
+
+    // Nothing was landed.
+    let key = BookmarkKey::new("trunk")?;
+    let trunk = repo
+        .resolve_bookmark(&key, BookmarkFreshness::MostRecent)
+        .await?
+        .expect("trunk should be set");
+    assert_eq!(trunk.id(), changesets["C"]);
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use blobrepo::BlobRepo;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use mononoke_types::ChangesetId;
+use repo_tags::Tag;
+use tests_utils::drawdag::create_from_dag;
+
+use crate::repo::Repo;
+use crate::repo::RepoContext;
+use crate::MononokeError;
+
+#[fbinit::test]
+async fn test_create_and_list_tags(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let blob_repo: BlobRepo = test_repo_factory::build_empty(fb)?;
+    let changesets = create_from_dag(&ctx, &blob_repo, "A-B").await?;
+    let repo = Repo::new_test(ctx.clone(), blob_repo).await?;
+    let repo = RepoContext::new_test(ctx.clone(), Arc::new(repo)).await?;
+
+    let tag = repo
+        .create_tag("v1", changesets["A"], Some("first".to_string()), None)
+        .await?;
+    assert_eq!(tag.cs_id, changesets["A"]);
+    assert_eq!(tag.message.as_deref(), Some("first"));
+    // The session has no user, so the creator isn't known.
+    assert_eq!(tag.creator, None);
+    assert_eq!(repo.tag("v1").await?, Some(tag));
+    repo.create_tag("v2", changesets["B"], None, None).await?;
+    repo.create_tag("w1", changesets["B"], None, None).await?;
+
+    // Tags can't be moved, or point at commits that don't exist.
+    let result = repo.create_tag("v1", changesets["B"], None, None).await;
+    assert!(matches!(result, Err(MononokeError::InvalidRequest(_))));
+    let unknown = ChangesetId::from_bytes([1; 32])?;
+    let result = repo.create_tag("v3", unknown, None, None).await;
+    assert!(matches!(result, Err(MononokeError::InvalidRequest(_))));
+    let result = repo.create_tag("v 3", changesets["B"], None, None).await;
+    assert!(matches!(result, Err(MononokeError::InvalidRequest(_))));
+    assert_eq!(repo.tag("v3").await?, None);
+
+    let names = |tags: Vec<Tag>| tags.into_iter().map(|tag| tag.name).collect::<Vec<_>>();
+    let tags = repo.list_tags("", None, 10).await?;
+    assert_eq!(names(tags), vec!["v1", "v2", "w1"]);
+    let tags = repo.list_tags("v", None, 10).await?;
+    assert_eq!(names(tags), vec!["v1", "v2"]);
+    let tags = repo.list_tags("v", None, 1).await?;
+    assert_eq!(names(tags), vec!["v1"]);
+    let tags = repo.list_tags("v", Some("v1"), 10).await?;
+    assert_eq!(names(tags), vec!["v2"]);
+
+    Ok(())
+}
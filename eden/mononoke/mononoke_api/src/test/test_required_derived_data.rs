@@ -0,0 +1,144 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use blobrepo::BlobRepo;
+use bookmarks::BookmarkKey;
+use bookmarks_movement::BookmarkKindRestrictions::AnyKind;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use hooks::PushAuthoredBy::User;
+use mercurial_derived_data::MappedHgChangesetId;
+use metaconfig_types::BookmarkParams;
+use metaconfig_types::RequiredDerivedDataType;
+use mononoke_types::ChangesetId;
+use repo_derived_data::RepoDerivedDataRef;
+use test_repo_factory::TestRepoFactory;
+use tests_utils::drawdag::create_from_dag;
+
+use crate::repo::Repo;
+use crate::repo::RepoContext;
+use crate::MononokeError;
+
+async fn init_repo(
+    ctx: &CoreContext,
+    budget: Duration,
+) -> Result<(RepoContext, BTreeMap<String, ChangesetId>)> {
+    let blob_repo: BlobRepo = TestRepoFactory::new(ctx.fb)?
+        .with_config_override(|config| {
+            config.bookmarks = vec![BookmarkParams {
+                bookmark: BookmarkKey::new("master").unwrap().into(),
+                hooks: vec![],
+                only_fast_forward: false,
+                allowed_users: None,
+                allowed_hipster_group: None,
+                allowed_groups: vec![],
+                allowed_service_identities: vec![],
+                rewrite_dates: None,
+                hooks_skip_ancestors_of: vec![],
+                ensure_ancestor_of: None,
+                allow_move_to_public_commits_without_hooks: false,
+                require_signed_commits: false,
+                pushrebase_only: false,
+                required_derived_data: vec![RequiredDerivedDataType::HgChangesets],
+                required_derived_data_budget: budget,
+                preconditions: vec![],
+            }];
+        })
+        .build()?;
+    let changesets = create_from_dag(
+        ctx,
+        &blob_repo,
+        r##"
+            A-B-C
+             \
+              D
+        "##,
+    )
+    .await?;
+    let repo = Repo::new_test(ctx.clone(), blob_repo).await?;
+    let repo_ctx = RepoContext::new_test(ctx.clone(), Arc::new(repo)).await?;
+    Ok((repo_ctx, changesets))
+}
+
+#[fbinit::test]
+async fn test_required_derived_data_must_be_derived(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (repo, changesets) = init_repo(&ctx, Duration::from_secs(0)).await?;
+    let master = BookmarkKey::new("master")?;
+
+    // Without a budget, the bookmark can't be moved to underived commits.
+    let result = repo.create_bookmark(&master, changesets["B"], None).await;
+    assert!(matches!(result, Err(MononokeError::InvalidRequest(_))));
+
+    repo.blob_repo()
+        .repo_derived_data()
+        .derive::<MappedHgChangesetId>(&ctx, changesets["B"])
+        .await?;
+    repo.create_bookmark(&master, changesets["B"], None).await?;
+
+    // Other bookmarks are unaffected.
+    let other = BookmarkKey::new("other")?;
+    repo.create_bookmark(&other, changesets["C"], None).await?;
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_required_derived_data_derived_within_budget(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (repo, changesets) = init_repo(&ctx, Duration::from_secs(600)).await?;
+    let master = BookmarkKey::new("master")?;
+
+    // The derived data is derived as part of the move.
+    repo.create_bookmark(&master, changesets["C"], None).await?;
+    assert!(
+        repo.blob_repo()
+            .repo_derived_data()
+            .fetch_derived::<MappedHgChangesetId>(&ctx, changesets["C"])
+            .await?
+            .is_some()
+    );
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_required_derived_data_pushrebase(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let master = BookmarkKey::new("master")?;
+
+    // Without a budget, pushrebases are rejected, as the rebased commits
+    // are new and so can't have been derived yet.
+    let (repo, changesets) = init_repo(&ctx, Duration::from_secs(0)).await?;
+    repo.blob_repo()
+        .repo_derived_data()
+        .derive::<MappedHgChangesetId>(&ctx, changesets["C"])
+        .await?;
+    repo.create_bookmark(&master, changesets["C"], None).await?;
+    let result = repo
+        .land_stack("master", changesets["D"], changesets["A"], None, AnyKind, User)
+        .await;
+    assert!(result.is_err());
+
+    // Otherwise the rebased commits are derived as part of the pushrebase.
+    let (repo, changesets) = init_repo(&ctx, Duration::from_secs(600)).await?;
+    repo.create_bookmark(&master, changesets["C"], None).await?;
+    let outcome = repo
+        .land_stack("master", changesets["D"], changesets["A"], None, AnyKind, User)
+        .await?;
+    assert!(
+        repo.blob_repo()
+            .repo_derived_data()
+            .fetch_derived::<MappedHgChangesetId>(&ctx, outcome.head)
+            .await?
+            .is_some()
+    );
+    Ok(())
+}
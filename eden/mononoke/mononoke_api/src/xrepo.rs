@@ -17,3 +17,14 @@ pub enum CandidateSelectionHintArgs {
     OnlyOrDescendantOfCommit(ChangesetSpecifier),
     Exact(ChangesetSpecifier),
 }
+
+/// How the result of a cross-repo commit lookup relates to the commit that
+/// was looked up.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum XRepoLookupMatch {
+    /// The commit was rewritten into the other repo as this commit.
+    Exact,
+    /// The commit was not rewritten into the other repo, but this commit
+    /// (its nearest synced ancestor) has an equivalent working copy.
+    WorkingCopyEquivalent,
+}
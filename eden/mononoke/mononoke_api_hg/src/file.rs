@@ -5,21 +5,32 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashSet;
+
 use async_trait::async_trait;
+use blobrepo_hg::file_history::filenode_to_history_entry;
+use blobrepo_hg::file_history::get_file_history_from_envelopes;
 use blobrepo_hg::file_history::get_file_history_maybe_incomplete;
+use blobrepo_hg::file_history::get_filenode_from_envelope;
 use blobstore::Loadable;
 use blobstore::LoadableError;
 use bytes::Bytes;
+use futures::StreamExt;
 use futures::TryStream;
 use futures::TryStreamExt;
 use getbundle_response::SessionLfsParams;
+use manifest::ManifestOps;
+use mercurial_derived_data::DeriveHgChangeset;
 use mercurial_types::envelope::HgFileEnvelope;
 use mercurial_types::FileType;
+use mercurial_types::HgChangesetId;
 use mercurial_types::HgFileHistoryEntry;
 use mercurial_types::HgFileNodeId;
 use mercurial_types::HgNodeHash;
 use mercurial_types::HgParents;
+use mercurial_types::RepoPath;
 use mononoke_api::errors::MononokeError;
+use mononoke_api::ChangesetPathHistoryOptions;
 use mononoke_types::fsnode::FsnodeFile;
 use mononoke_types::ContentMetadata;
 use mononoke_types::MPath;
@@ -99,6 +110,95 @@ impl HgFileContext {
         .map_err(MononokeError::from)
     }
 
+    /// Get the history of this file at `path` without reading filenodes, for
+    /// clients that support filenode-free serving.
+    ///
+    /// The history of the path is walked back from `commit` using unodes and
+    /// fastlog.  Each changeset in it that changes the file to a filenode in
+    /// the history of this file is the linknode of that filenode.  Filenodes
+    /// that this doesn't reach, for example because the file isn't in
+    /// `commit` as this filenode, are read from their envelopes, with null
+    /// linknodes.
+    pub async fn history_without_filenodes(
+        &self,
+        path: MPath,
+        commit: HgChangesetId,
+        max_length: Option<u32>,
+    ) -> Result<Vec<HgFileHistoryEntry>, MononokeError> {
+        let ctx = self.repo.ctx();
+        let blob_repo = self.repo.blob_repo();
+        let blobstore = blob_repo.repo_blobstore();
+        let max_length = max_length.map_or(usize::MAX, |len| len as usize);
+        let repo_path = RepoPath::FilePath(path.clone());
+
+        let changeset = self.repo.repo().changeset(commit).await?.ok_or_else(|| {
+            MononokeError::InvalidRequest(format!("commit not found: {}", commit))
+        })?;
+        let path_context = changeset.path_with_history(path.clone()).await?;
+        let mut changesets = path_context
+            .history(ChangesetPathHistoryOptions::default())
+            .await?
+            .boxed();
+
+        let mut history = vec![];
+        let mut seen = HashSet::new();
+        let mut wanted = HashSet::from([self.node_id()]);
+        while let Some(changeset) = changesets.try_next().await? {
+            if wanted.is_empty() || history.len() >= max_length {
+                break;
+            }
+            let hg_cs_id = blob_repo.derive_hg_changeset(ctx, changeset.id()).await?;
+            let manifest_id = hg_cs_id.load(ctx, blobstore).await?.manifestid();
+            let filenode_id = match manifest_id
+                .find_entry(ctx.clone(), blobstore.clone(), Some(path.clone()))
+                .await?
+                .and_then(|entry| entry.into_leaf())
+            {
+                Some((_file_type, filenode_id)) => filenode_id,
+                None => continue,
+            };
+            if !wanted.remove(&filenode_id) || !seen.insert(filenode_id) {
+                continue;
+            }
+            let info = get_filenode_from_envelope(
+                blobstore.clone(),
+                ctx,
+                &repo_path,
+                filenode_id,
+                hg_cs_id,
+            )
+            .await?;
+            wanted.extend(
+                info.p1
+                    .into_iter()
+                    .chain(info.p2)
+                    .filter(|parent| !seen.contains(parent)),
+            );
+            history.push(filenode_to_history_entry(filenode_id, info, &repo_path)?);
+        }
+
+        for filenode_id in wanted {
+            if history.len() >= max_length {
+                break;
+            }
+            let entries = get_file_history_from_envelopes(
+                ctx.clone(),
+                blob_repo.clone(),
+                filenode_id,
+                path.clone(),
+                None,
+            )
+            .try_filter(|entry| futures::future::ready(!seen.contains(entry.filenode())))
+            .take(max_length - history.len())
+            .try_collect::<Vec<_>>()
+            .await?;
+            seen.extend(entries.iter().map(|entry| *entry.filenode()));
+            history.extend(entries);
+        }
+
+        Ok(history)
+    }
+
     pub async fn content_metadata(&self) -> Result<ContentMetadata, MononokeError> {
         let content_id = self.envelope.content_id();
         let fetch_key = filestore::FetchKey::Canonical(content_id);
@@ -205,6 +305,7 @@ mod tests {
 
     use context::CoreContext;
     use fbinit::FacebookInit;
+    use fixtures::Linear;
     use fixtures::ManyFilesDirs;
     use fixtures::TestRepoFixture;
     use futures::TryStreamExt;
@@ -287,4 +388,68 @@ mod tests {
 
         Ok(())
     }
+
+    #[fbinit::test]
+    async fn test_hg_file_history_without_filenodes(fb: FacebookInit) -> Result<(), MononokeError> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = Arc::new(Repo::new_test(ctx.clone(), Linear::getrepo(fb).await).await?);
+        let repo_ctx = RepoContext::new_test(ctx.clone(), repo).await?;
+        let hg = repo_ctx.hg();
+
+        // Every commit in the `Linear` test repo but the last changes `files`.
+        let first = HgChangesetId::from_str("2d7d4ba9ce0a6ffd222de7785b249ead9c51c536")?;
+        let last = HgChangesetId::from_str("79a13814c5ce7330173ec04d279bf95ab3f652fb")?;
+        let expected_linknodes = vec![
+            HgChangesetId::from_str("a5ffa77602a066db7d5cfb9fb5823a0895717c5a")?,
+            HgChangesetId::from_str("3c15267ebf11807f3d772eb891272b911ec68759")?,
+            HgChangesetId::from_str("a9473beb2eb03ddb1cccc3fbaeb8a4820f9cd157")?,
+            HgChangesetId::from_str("0ed509bf086fadcb8a8a5384dc3b550729b0fc17")?,
+            HgChangesetId::from_str("eed3a8c0ec67b6a6fe2eb3543334df3f0b4f202b")?,
+            HgChangesetId::from_str("cb15ca4a43a59acff5388cea9648c162afde8372")?,
+            HgChangesetId::from_str("d0a361e9022d226ae52f689667bd7d212a19cfe0")?,
+            HgChangesetId::from_str("607314ef579bd2407752361ba1b0c1729d08b281")?,
+            HgChangesetId::from_str("3e0e761030db6e479a7fb58b12881883f9f8c63f")?,
+            first,
+        ];
+
+        let path = MPath::new("files")?;
+        let blobstore = repo_ctx.blob_repo().repo_blobstore().clone();
+        let (_file_type, file_id) = last
+            .load(&ctx, &blobstore)
+            .await?
+            .manifestid()
+            .find_entry(ctx.clone(), blobstore, Some(path.clone()))
+            .await?
+            .and_then(|entry| entry.into_leaf())
+            .expect("files should be in the last commit");
+        let hg_file = HgFileContext::new(hg.clone(), file_id).await?;
+
+        let linknodes = |history: Vec<HgFileHistoryEntry>| {
+            history
+                .iter()
+                .map(|entry| *entry.linknode())
+                .collect::<Vec<_>>()
+        };
+
+        // The linknodes come from the history of the file in the commit.
+        let history = hg_file
+            .history_without_filenodes(path.clone(), last, None)
+            .await?;
+        assert_eq!(linknodes(history), expected_linknodes);
+
+        let history = hg_file
+            .history_without_filenodes(path.clone(), last, Some(2))
+            .await?;
+        assert_eq!(linknodes(history), expected_linknodes[..2]);
+
+        // The file isn't in the first commit as this filenode, so the
+        // history is read from the envelopes, without linknodes.
+        let history = hg_file.history_without_filenodes(path, first, None).await?;
+        assert_eq!(
+            linknodes(history),
+            vec![HgChangesetId::new(NULL_HASH); expected_linknodes.len()]
+        );
+
+        Ok(())
+    }
 }
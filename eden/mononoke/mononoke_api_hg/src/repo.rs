@@ -318,6 +318,15 @@ impl HgRepoContext {
         HgFileContext::new_check_exists(self.clone(), filenode_id).await
     }
 
+    /// The hg files and trees among `entries`, with the paths they are at,
+    /// that the repo doesn't serve because they aren't part of a view.
+    pub async fn hg_entries_outside_view(
+        &self,
+        entries: Vec<(RepoPath, HgFileNodeId)>,
+    ) -> Result<HashSet<HgFileNodeId>, MononokeError> {
+        self.repo().hg_entries_outside_view(entries).await
+    }
+
     /// Look up a tree in the repo by `HgManifestId`.
     pub async fn tree(
         &self,
@@ -490,6 +499,7 @@ impl HgRepoContext {
             basemfnodes: base_versions.into_iter().collect(),
             directories: vec![], // Not supported.
             depth,
+            deadline_ms: None,
         };
 
         gettreepack_entries(ctx, blob_repo, args)
@@ -646,6 +656,14 @@ impl HgRepoContext {
         &self,
         hg_cs_id: HgChangesetId,
     ) -> Result<Option<Bytes>, MononokeError> {
+        // Views don't serve the changesets outside of them.
+        if self.config().view.is_some() {
+            if let Some(cs_id) = self.get_bonsai_from_hg(hg_cs_id).await? {
+                if !self.repo().is_served(cs_id).await? {
+                    return Ok(None);
+                }
+            }
+        }
         let ctx = self.ctx();
         let blobstore = self.blob_repo().repo_blobstore();
         let revlog_cs = RevlogChangeset::load(ctx, blobstore, hg_cs_id)
@@ -674,6 +692,12 @@ impl HgRepoContext {
         self.repo().segmented_changelog_disabled().await
     }
 
+    /// Whether file history can be served without reading filenodes, to
+    /// clients that support it.
+    pub fn serves_filenode_free(&self) -> bool {
+        self.config().filenodes_config.serve_filenode_free()
+    }
+
     pub async fn segmented_changelog_pull_data(
         &self,
         common: Vec<HgChangesetId>,
@@ -25,6 +25,7 @@ use metaconfig_parser::StorageConfigs;
 use repos::RawRepoConfigs;
 use slog::error;
 use slog::info;
+use slog::warn;
 use slog::Logger;
 use stats::prelude::*;
 use tokio::runtime::Handle;
@@ -64,6 +65,9 @@ impl MononokeConfigs {
         let storage_configs = metaconfig_parser::load_storage_configs(&config_path, config_store)?;
         let storage_configs = Arc::new(ArcSwap::from_pointee(storage_configs));
         let repo_configs = metaconfig_parser::load_repo_configs(&config_path, config_store)?;
+        for warning in &repo_configs.schema_warnings {
+            warn!(logger, "{}", warning);
+        }
         let repo_configs = Arc::new(ArcSwap::from_pointee(repo_configs));
         let update_receivers = Arc::new(ArcSwap::from_pointee(vec![]));
         let maybe_config_handle = configerator_config_handle(config_path.as_ref(), config_store)?;
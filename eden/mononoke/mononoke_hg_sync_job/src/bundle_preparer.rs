@@ -98,8 +98,8 @@ impl BundlePreparer {
 
         for log_entry in &entries {
             match log_entry.reason {
-                Pushrebase | Backsyncer | ManualMove | ApiRequest | XRepoSync | Push | TestMove => {
-                }
+                Pushrebase | Backsyncer | ManualMove | ApiRequest | XRepoSync | Push | TestMove
+                | Mirror | SparseExport => {}
                 Blobimport => {
                     return Err(UnexpectedBookmarkMove(format!("{}", log_entry.reason)).into());
                 }
@@ -87,6 +87,7 @@ use repo_blobstore::RepoBlobstoreRef;
 use repo_derived_data::RepoDerivedData;
 use repo_identity::RepoIdentity;
 use repo_lock::MutableRepoLock;
+use repo_lock::ReadOnlyInfo;
 use repo_lock::RepoLock;
 use repo_lock::RepoLockState;
 use repo_lock::SqlRepoLock;
@@ -631,7 +632,7 @@ async fn unlock_repo_if_locked(ctx: &CoreContext, repo_lock: &dyn RepoLock) -> R
     let repo_state = repo_lock.check_repo_lock().await?;
 
     match repo_state {
-        RepoLockState::Locked(ref lock_msg) if lock_msg == LOCK_REASON => {
+        RepoLockState::Locked(ref info) if info.reason == LOCK_REASON => {
             let updated = repo_lock.set_repo_lock(RepoLockState::Unlocked).await?;
 
             if updated {
@@ -651,7 +652,7 @@ async fn lock_repo_if_unlocked(ctx: &CoreContext, repo_lock: &dyn RepoLock) -> R
     match repo_state {
         RepoLockState::Unlocked => {
             let updated = repo_lock
-                .set_repo_lock(RepoLockState::Locked(LOCK_REASON.to_string()))
+                .set_repo_lock(RepoLockState::Locked(ReadOnlyInfo::new(LOCK_REASON)))
                 .await?;
 
             if updated {
@@ -661,8 +662,8 @@ async fn lock_repo_if_unlocked(ctx: &CoreContext, repo_lock: &dyn RepoLock) -> R
             Ok(())
         }
 
-        RepoLockState::Locked(ref lock_msg) => {
-            info!(ctx.logger(), "repo is locked already: {}", lock_msg);
+        RepoLockState::Locked(ref info) => {
+            info!(ctx.logger(), "repo is locked already: {}", info);
             Ok(())
         }
     }
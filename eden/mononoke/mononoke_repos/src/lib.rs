@@ -65,10 +65,10 @@ impl<R> MononokeRepos<R> {
     /// to the repos currently loaded for the service / command.
     pub fn iter_names(&self) -> impl Iterator<Item = String> {
         let result: Vec<_> = self
-            .id_to_name_map
+            .name_to_repo_map
             .load()
-            .iter()
-            .map(|(_, name)| name.to_string())
+            .keys()
+            .map(|name| name.to_string())
             .collect();
         result.into_iter()
     }
@@ -88,17 +88,19 @@ impl<R> MononokeRepos<R> {
     /// Private method that performs the add operations without lock-related
     /// logic. The public accessors to this method ensure that the lock is
     /// acquired before this method is invoked.
-    fn add_inner(&self, repo_name: &str, repo_id: i32, repo: R) {
+    fn add_inner(&self, repo_name: &str, repo_id: Option<i32>, repo: R) {
         // First, add the repo-id to repo-name mapping since the actual
         // repo addition should be the last step.
-        let id_to_name_map = self.id_to_name_map.load();
-        let mut new_id_to_name_map = HashMap::from_iter(
-            id_to_name_map
-                .iter()
-                .map(|(id, name)| (*id, name.to_string())),
-        );
-        new_id_to_name_map.insert(repo_id, repo_name.to_string());
-        self.id_to_name_map.store(Arc::new(new_id_to_name_map));
+        if let Some(repo_id) = repo_id {
+            let id_to_name_map = self.id_to_name_map.load();
+            let mut new_id_to_name_map = HashMap::from_iter(
+                id_to_name_map
+                    .iter()
+                    .map(|(id, name)| (*id, name.to_string())),
+            );
+            new_id_to_name_map.insert(repo_id, repo_name.to_string());
+            self.id_to_name_map.store(Arc::new(new_id_to_name_map));
+        }
 
         // Add the repo-name to repo mapping.
         let name_to_repo_map = self.name_to_repo_map.load();
@@ -113,13 +115,14 @@ impl<R> MononokeRepos<R> {
 
     /// Adds a new repo corresponding to the provided repo-name
     /// and repo-id. If a repo already exists for that combination,
-    /// then it is replaced by the passed in new repo.
+    /// then it is replaced by the passed in new repo. Views of other
+    /// repos have no repo-id of their own, and can only be found by name.
     /// NOTE: This is a mutex guarded operation that can induce wait times for
     /// the caller thread. If this isn't desired, use try_add instead.
     /// Before calling this method ensure that the caller is not holding additional
     /// locks. If the caller does hold additional locks, ensure that the locks are
     /// acquired in proper sequence to avoid deadlock or starvation.
-    pub fn add(&self, repo_name: &str, repo_id: i32, repo: R) {
+    pub fn add(&self, repo_name: &str, repo_id: Option<i32>, repo: R) {
         // Acquire the lock to avoid race conditions during update.
         let lock = self.update_lock.lock();
         self.add_inner(repo_name, repo_id, repo);
@@ -133,7 +136,7 @@ impl<R> MononokeRepos<R> {
     /// NOTE: Repo changes are guarded by a mutex. This method attempts
     /// to acquire the lock if it is available, without getting blocked
     /// on the lock.
-    pub fn try_add(&self, repo_name: &str, repo_id: i32, repo: R) -> Result<()> {
+    pub fn try_add(&self, repo_name: &str, repo_id: Option<i32>, repo: R) -> Result<()> {
         // Attempt to acquire the lock before add, to avoid race condition.
         match self.update_lock.try_lock() {
             // Lock acquired, add repo.
@@ -211,7 +214,8 @@ impl<R> MononokeRepos<R> {
     /// Method responsible for bulk populating MononokeRepos from an
     /// input iterator of Repos. This method completely discards any previous
     /// repos that were part of MononokeRepos and uses the input to generate
-    /// a new collection. Do not use for partial updates.
+    /// a new collection. Do not use for partial updates. Repos without a
+    /// repo-id, i.e. views of other repos, can only be found by name.
     /// NOTE: This is a mutex guarded operation that can induce wait times for
     /// the caller thread.
     /// Before calling this method ensure that the caller is not holding additional
@@ -219,14 +223,16 @@ impl<R> MononokeRepos<R> {
     /// acquired in proper sequence to avoid deadlock or starvation.
     pub fn populate<I>(&self, repos: I)
     where
-        I: IntoIterator<Item = (i32, String, R)>,
+        I: IntoIterator<Item = (Option<i32>, String, R)>,
     {
         // Acquire the lock to avoid race conditions during update.
         let lock = self.update_lock.lock();
         let mut id_to_name_map: HashMap<i32, String> = HashMap::new();
         let mut name_to_repo_map: HashMap<String, Arc<R>> = HashMap::new();
         for (id, name, repo) in repos.into_iter() {
-            id_to_name_map.insert(id, name.to_string());
+            if let Some(id) = id {
+                id_to_name_map.insert(id, name.to_string());
+            }
             name_to_repo_map.insert(name, Arc::new(repo));
         }
         self.id_to_name_map.store(Arc::new(id_to_name_map));
@@ -143,6 +143,22 @@ struct BonsaiChangeset {
   // represents an annotated tag, then this field will have a value.
   // Otherwise, it would be absent.
   12: optional BonsaiAnnotatedTag git_annotated_tag;
+  // Typed metadata about the changeset, for data that would otherwise be
+  // stored in the commit message or in untyped extras.
+  13: optional ChangesetExtras extras;
+} (rust.exhaustive)
+
+// Typed metadata about a changeset. New fields must be optional (or have
+// an empty default) so that existing changesets keep their ids.
+struct ChangesetExtras {
+  // The branch the changeset was created on, as a hint for tools that
+  // group commits by branch.
+  1: optional string branch;
+  // The people who signed off on the changeset, in order.
+  2: list<string> signed_off_by;
+  // The authenticated identity of the committer, e.g. "USER:alice", as
+  // opposed to the free-form committer name.
+  3: optional string committer_identity;
 } (rust.exhaustive)
 
 // Bonsai counterpart of a git annotated tag. This struct includes subset of
@@ -323,10 +339,14 @@ union UnodeEntry {
 
 struct ManifestUnode {
   1: list<ManifestUnodeId> parents;
+  // Empty if the subentries are stored in sharded_subentries.
   2: map<MPathElement, UnodeEntry> (
     rust.type = "sorted_vector_map::SortedVectorMap",
   ) subentries;
   3: ChangesetId linknode;
+  // Map of MPathElement -> UnodeEntry, used instead of subentries for very
+  // wide directories so that a single unode blob doesn't get too large.
+  4: optional ShardedMapNode sharded_subentries;
 } (rust.exhaustive)
 
 struct DeletedManifest {
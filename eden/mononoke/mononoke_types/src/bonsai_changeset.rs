@@ -6,6 +6,7 @@
  */
 
 use std::collections::BTreeMap;
+use std::collections::HashSet;
 
 use anyhow::bail;
 use anyhow::Context;
@@ -53,6 +54,7 @@ pub struct BonsaiChangesetMut {
     pub is_snapshot: bool,
     pub git_tree_hash: Option<GitSha1>,
     pub git_annotated_tag: Option<BonsaiAnnotatedTag>,
+    pub extras: Option<ChangesetExtras>,
 }
 
 impl Default for BonsaiChangesetMut {
@@ -70,6 +72,7 @@ impl Default for BonsaiChangesetMut {
             is_snapshot: false,
             git_tree_hash: None,
             git_annotated_tag: None,
+            extras: None,
         }
     }
 }
@@ -115,6 +118,7 @@ impl BonsaiChangesetMut {
                 .map(BonsaiAnnotatedTag::from_thrift)
                 .transpose()
                 .context("Invalid annotated tag")?,
+            extras: tc.extras.map(ChangesetExtras::from_thrift),
         })
     }
 
@@ -146,6 +150,7 @@ impl BonsaiChangesetMut {
             snapshot_state: self.is_snapshot.then_some(thrift::SnapshotState {}),
             git_tree_hash: self.git_tree_hash.map(|hash| hash.into_thrift()),
             git_annotated_tag: self.git_annotated_tag.map(BonsaiAnnotatedTag::into_thrift),
+            extras: self.extras.map(ChangesetExtras::into_thrift),
         }
     }
 
@@ -161,6 +166,7 @@ impl BonsaiChangesetMut {
     /// Freeze this instance and turn it into a `BonsaiChangeset`.
     pub fn freeze(self) -> Result<BonsaiChangeset> {
         self.verify()?;
+        self.verify_distinct_parents()?;
         let id = self.changeset_id();
         Ok(BonsaiChangeset { inner: self, id })
     }
@@ -227,6 +233,25 @@ impl BonsaiChangesetMut {
 
         Ok(())
     }
+
+    /// Verify that each parent is only listed once.  Merges may have any
+    /// number of parents, but they must all be distinct.
+    ///
+    /// Changesets stored before this was checked may list a parent more than
+    /// once, so this is only checked when a new changeset is created, not
+    /// when one is loaded.
+    fn verify_distinct_parents(&self) -> Result<()> {
+        let mut seen_parents = HashSet::new();
+        for parent in &self.parents {
+            if !seen_parents.insert(parent) {
+                bail!(ErrorKind::InvalidBonsaiChangeset(format!(
+                    "parent {} is listed more than once",
+                    parent
+                )));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -350,6 +375,11 @@ impl BonsaiChangeset {
         self.inner.git_tree_hash.as_ref()
     }
 
+    /// Get the typed extras for this changeset.
+    pub fn extras(&self) -> Option<&ChangesetExtras> {
+        self.inner.extras.as_ref()
+    }
+
     /// Get the changeset ID of this changeset.
     pub fn get_changeset_id(&self) -> ChangesetId {
         self.id
@@ -429,6 +459,7 @@ impl Arbitrary for BonsaiChangeset {
                 is_snapshot: bool::arbitrary(g),
                 git_tree_hash: None,
                 git_annotated_tag: None,
+                extras: Option::<ChangesetExtras>::arbitrary(g),
             }
             .freeze()
             .expect("generated bonsai changeset must be valid")
@@ -470,6 +501,7 @@ impl Arbitrary for BonsaiChangeset {
                     is_snapshot: cs.is_snapshot,
                     git_tree_hash,
                     git_annotated_tag: cs.git_annotated_tag.clone(),
+                    extras: cs.extras.clone(),
                 }
                 .freeze()
                 .expect("shrunken bonsai changeset must be valid")
@@ -562,6 +594,53 @@ impl Arbitrary for BonsaiAnnotatedTag {
     }
 }
 
+/// Typed metadata about a changeset, for data that would otherwise be
+/// stored in the commit message or in untyped extras.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct ChangesetExtras {
+    /// The branch the changeset was created on.
+    pub branch: Option<String>,
+    /// The people who signed off on the changeset, in order.
+    pub signed_off_by: Vec<String>,
+    /// The authenticated identity of the committer, e.g. "USER:alice".
+    pub committer_identity: Option<String>,
+}
+
+impl ChangesetExtras {
+    /// Create from a thrift `ChangesetExtras`.
+    fn from_thrift(thrift_extras: thrift::ChangesetExtras) -> Self {
+        Self {
+            branch: thrift_extras.branch,
+            signed_off_by: thrift_extras.signed_off_by,
+            committer_identity: thrift_extras.committer_identity,
+        }
+    }
+
+    /// Convert into a thrift `ChangesetExtras`.
+    fn into_thrift(self) -> thrift::ChangesetExtras {
+        thrift::ChangesetExtras {
+            branch: self.branch,
+            signed_off_by: self.signed_off_by,
+            committer_identity: self.committer_identity,
+        }
+    }
+
+    /// Whether none of the extras are set.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+impl Arbitrary for ChangesetExtras {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            branch: Option::<String>::arbitrary(g),
+            signed_off_by: Vec::<String>::arbitrary(g),
+            committer_identity: Option::<String>::arbitrary(g),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::str::FromStr;
@@ -623,6 +702,7 @@ mod test {
             ],
             is_snapshot: false,
             git_annotated_tag: None,
+            extras: None,
         };
         let tc = tc.freeze().expect("fixed bonsai changeset must be valid");
 
@@ -675,6 +755,7 @@ mod test {
                 file_changes,
                 is_snapshot,
                 git_annotated_tag: None,
+                extras: None,
             }
             .freeze()
         }
@@ -705,6 +786,7 @@ mod test {
             ])
             .ok(),
             git_annotated_tag: None,
+            extras: None,
         };
         changeset
             .freeze()
@@ -735,12 +817,51 @@ mod test {
             ])
             .ok(),
             git_annotated_tag: None,
+            extras: None,
         };
         changeset
             .freeze()
             .expect_err("Changeset representing Git trees can't have file changes");
     }
 
+    #[test]
+    fn octopus_merge_bonsai_changeset() {
+        let create = |parents: Vec<ChangesetId>| {
+            BonsaiChangesetMut {
+                parents,
+                author: "author".to_string(),
+                author_date: DateTime::from_timestamp(1234567890, 36800).unwrap(),
+                committer: None,
+                committer_date: None,
+                message: "octopus".to_string(),
+                hg_extra: SortedVectorMap::new(),
+                git_extra_headers: None,
+                file_changes: SortedVectorMap::new(),
+                is_snapshot: false,
+                git_tree_hash: None,
+                git_annotated_tag: None,
+                extras: None,
+            }
+        };
+        let p1 = ChangesetId::from_byte_array([1; 32]);
+        let p2 = ChangesetId::from_byte_array([2; 32]);
+        let p3 = ChangesetId::from_byte_array([3; 32]);
+
+        let octopus = create(vec![p1, p2, p3])
+            .freeze()
+            .expect("octopus merges are valid");
+        assert_eq!(octopus.parents().collect::<Vec<_>>(), vec![p1, p2, p3]);
+        assert!(create(vec![p1, p2, p1]).freeze().is_err());
+
+        // Changesets that were stored with a duplicate parent can still be
+        // loaded.
+        let duplicate = create(vec![p1, p2, p1]);
+        let id = duplicate.changeset_id();
+        let loaded = BonsaiChangeset::from_thrift_with_id(duplicate.into_thrift(), id)
+            .expect("stored changesets with duplicate parents can be loaded");
+        assert_eq!(loaded.parents().collect::<Vec<_>>(), vec![p1, p2, p1]);
+    }
+
     #[test]
     fn valid_git_tree_bonsai_changeset() {
         let changeset = BonsaiChangesetMut {
@@ -760,6 +881,7 @@ mod test {
             ])
             .ok(),
             git_annotated_tag: None,
+            extras: None,
         };
         changeset.freeze().unwrap();
     }
@@ -789,6 +911,7 @@ mod test {
             is_snapshot: false,
             git_tree_hash: None,
             git_annotated_tag: None,
+            extras: None,
         };
         changeset.freeze().unwrap();
     }
@@ -813,6 +936,7 @@ mod test {
                 target: BonsaiAnnotatedTagTarget::Changeset(ChangesetId::from_byte_array([4; 32])),
                 pgp_signature: None,
             }),
+            extras: None,
         };
         changeset
             .freeze()
@@ -844,6 +968,7 @@ mod test {
                 target: BonsaiAnnotatedTagTarget::Changeset(ChangesetId::from_byte_array([3; 32])),
                 pgp_signature: None,
             }),
+            extras: None,
         };
         changeset.freeze().expect_err(
             "Bonsai changeset representing a git annotated tag cannot have file changes",
@@ -869,6 +994,7 @@ mod test {
                 target: BonsaiAnnotatedTagTarget::Changeset(ChangesetId::from_byte_array([3; 32])),
                 pgp_signature: None,
             }),
+            extras: None,
         };
         changeset.freeze().expect_err(
             "Bonsai changeset representing a git annotated tag cannot have a git tree hash (indicating that its a git tree) as part of it",
@@ -893,6 +1019,7 @@ mod test {
                 target: BonsaiAnnotatedTagTarget::Changeset(ChangesetId::from_byte_array([3; 32])),
                 pgp_signature: None,
             }),
+            extras: None,
         };
         changeset.freeze().unwrap();
     }
@@ -915,6 +1042,7 @@ mod test {
                 target: BonsaiAnnotatedTagTarget::Changeset(ChangesetId::from_byte_array([3; 32])),
                 pgp_signature: None,
             }),
+            extras: None,
         };
         changeset.freeze().unwrap();
     }
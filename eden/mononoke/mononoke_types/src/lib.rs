@@ -50,6 +50,7 @@ pub use blob::RawBundle2Blob;
 pub use blobstore::BlobstoreBytes;
 pub use bonsai_changeset::BonsaiChangeset;
 pub use bonsai_changeset::BonsaiChangesetMut;
+pub use bonsai_changeset::ChangesetExtras;
 pub use content_chunk::ContentChunk;
 pub use content_metadata::ContentAlias;
 pub use content_metadata::ContentMetadata;
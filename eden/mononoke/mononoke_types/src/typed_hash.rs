@@ -45,6 +45,7 @@ use crate::skeleton_manifest::SkeletonManifest;
 use crate::thrift;
 use crate::unode::FileUnode;
 use crate::unode::ManifestUnode;
+use crate::unode::UnodeEntry;
 use crate::ThriftConvert;
 
 // There is no NULL_HASH for typed hashes. Any places that need a null hash should use an
@@ -149,6 +150,10 @@ pub struct FileUnodeId(Blake2);
 #[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
 pub struct ManifestUnodeId(Blake2);
 
+/// An identifier for a sharded map node used in manifest unodes
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub struct ShardedMapNodeUnodeId(Blake2);
+
 /// An identifier for a deleted manifest v2
 #[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
 pub struct DeletedManifestV2Id(Blake2);
@@ -560,6 +565,14 @@ impl_typed_hash! {
     context_key => "manifestunode",
 }
 
+impl_typed_hash! {
+    hash_type => ShardedMapNodeUnodeId,
+    thrift_hash_type => thrift::ShardedMapNodeId,
+    value_type => ShardedMapNode<UnodeEntry>,
+    context_type => ShardedMapNodeUnodeContext,
+    context_key => "manifestunode.mapnode",
+}
+
 impl_typed_hash! {
     hash_type => DeletedManifestV2Id,
     thrift_hash_type => thrift::DeletedManifestV2Id,
@@ -779,6 +792,12 @@ mod test {
         let id = ShardedMapNodeBSSMId::from_byte_array([1; 32]);
         assert_eq!(id.blobstore_key(), format!("bssm.mapnode.blake2.{}", id));
 
+        let id = ShardedMapNodeUnodeId::from_byte_array([1; 32]);
+        assert_eq!(
+            id.blobstore_key(),
+            format!("manifestunode.mapnode.blake2.{}", id)
+        );
+
         let id = ContentChunkId::from_byte_array([1; 32]);
         assert_eq!(id.blobstore_key(), format!("chunk.blake2.{}", id));
 
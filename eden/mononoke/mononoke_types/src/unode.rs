@@ -8,7 +8,13 @@
 use anyhow::bail;
 use anyhow::Context;
 use anyhow::Result;
+use blobstore::Blobstore;
+use bytes::Bytes;
+use context::CoreContext;
 use fbthrift::compact_protocol;
+use futures::stream;
+use futures::stream::BoxStream;
+use futures::stream::StreamExt;
 use sorted_vector_map::SortedVectorMap;
 
 use crate::blob::Blob;
@@ -19,6 +25,8 @@ use crate::errors::ErrorKind;
 use crate::file_change::FileType;
 use crate::path::MPathElement;
 use crate::path::MPathHash;
+use crate::sharded_map::MapValue;
+use crate::sharded_map::ShardedMapNode;
 use crate::thrift;
 use crate::typed_hash::ChangesetId;
 use crate::typed_hash::ContentId;
@@ -26,6 +34,9 @@ use crate::typed_hash::FileUnodeId;
 use crate::typed_hash::FileUnodeIdContext;
 use crate::typed_hash::ManifestUnodeId;
 use crate::typed_hash::ManifestUnodeIdContext;
+use crate::typed_hash::ShardedMapNodeUnodeContext;
+use crate::typed_hash::ShardedMapNodeUnodeId;
+use crate::ThriftConvert;
 
 /// Unode is a filenode with fixed linknodes. They are designed to find file or directory history
 /// quickly which can be used to answer "log" or "blame" requests.
@@ -85,6 +96,13 @@ use crate::typed_hash::ManifestUnodeIdContext;
 /// This means that manifest unodes will also be unique by construction — no path hash required.
 /// If Mononoke ever decides to support empty trees — perhaps representing empty directories? —
 /// it would have to add a path hash.
+///
+/// How are very wide directories stored?
+///
+/// A tree unode with a very large number of subentries can be derived with its subentries in a
+/// sharded map, which splits them into several blobs by name. Tree unodes with inline subentries
+/// remain readable, so only newly derived unodes are affected. Use the async accessors
+/// (`lookup_entry` and `into_subentries`) to read unodes that might be sharded.
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct FileUnode {
@@ -191,10 +209,11 @@ impl BlobstoreValue for FileUnode {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct ManifestUnode {
     parents: Vec<ManifestUnodeId>,
     subentries: SortedVectorMap<MPathElement, UnodeEntry>,
+    sharded_subentries: Option<ShardedMapNode<UnodeEntry>>,
     linknode: ChangesetId,
 }
 
@@ -207,22 +226,108 @@ impl ManifestUnode {
         Self {
             parents,
             subentries,
+            sharded_subentries: None,
             linknode,
         }
     }
 
+    /// Create a manifest unode that stores its subentries in a sharded map.
+    /// Any shards that don't fit in the unode blob itself are written to the
+    /// blobstore.
+    pub async fn new_sharded(
+        ctx: &CoreContext,
+        blobstore: &impl Blobstore,
+        parents: Vec<ManifestUnodeId>,
+        subentries: SortedVectorMap<MPathElement, UnodeEntry>,
+        linknode: ChangesetId,
+    ) -> Result<Self> {
+        let sharded_subentries = ShardedMapNode::default()
+            .update(
+                ctx,
+                blobstore,
+                subentries
+                    .into_iter()
+                    .map(|(basename, entry)| {
+                        (Bytes::copy_from_slice(basename.as_ref()), Some(entry))
+                    })
+                    .collect(),
+                |_| {},
+            )
+            .await?;
+        Ok(Self {
+            parents,
+            subentries: SortedVectorMap::new(),
+            sharded_subentries: Some(sharded_subentries),
+            linknode,
+        })
+    }
+
+    /// Whether the subentries of this unode are stored in a sharded map.
+    pub fn is_sharded(&self) -> bool {
+        self.sharded_subentries.is_some()
+    }
+
+    /// Look up a subentry in the inline subentries.  Always returns `None`
+    /// for sharded unodes; use `lookup_entry` if the unode might be sharded.
     pub fn lookup(&self, basename: &MPathElement) -> Option<&UnodeEntry> {
         self.subentries.get(basename)
     }
 
+    /// List the inline subentries.  This is empty for sharded unodes; use
+    /// `into_subentries` if the unode might be sharded.
     pub fn list(&self) -> impl Iterator<Item = (&MPathElement, &UnodeEntry)> {
         self.subentries.iter()
     }
 
+    /// The inline subentries.  This is empty for sharded unodes; use
+    /// `into_subentries` if the unode might be sharded.
     pub fn subentries(&self) -> &SortedVectorMap<MPathElement, UnodeEntry> {
         &self.subentries
     }
 
+    pub async fn lookup_entry(
+        &self,
+        ctx: &CoreContext,
+        blobstore: &impl Blobstore,
+        basename: &MPathElement,
+    ) -> Result<Option<UnodeEntry>> {
+        match &self.sharded_subentries {
+            Some(sharded) => sharded.lookup(ctx, blobstore, basename.as_ref()).await,
+            None => Ok(self.subentries.get(basename).cloned()),
+        }
+    }
+
+    pub fn into_subentries<'a>(
+        self,
+        ctx: &'a CoreContext,
+        blobstore: &'a impl Blobstore,
+    ) -> BoxStream<'a, Result<(MPathElement, UnodeEntry)>> {
+        self.into_prefix_subentries(ctx, blobstore, &[])
+    }
+
+    pub fn into_prefix_subentries<'a>(
+        self,
+        ctx: &'a CoreContext,
+        blobstore: &'a impl Blobstore,
+        prefix: &'a [u8],
+    ) -> BoxStream<'a, Result<(MPathElement, UnodeEntry)>> {
+        match self.sharded_subentries {
+            Some(sharded) => sharded
+                .into_prefix_entries(ctx, blobstore, prefix)
+                .map(|res| {
+                    res.and_then(|(k, v)| anyhow::Ok((MPathElement::from_smallvec(k)?, v)))
+                })
+                .boxed(),
+            None => stream::iter(
+                self.subentries
+                    .into_iter()
+                    .filter(|(basename, _)| basename.starts_with(prefix))
+                    .map(anyhow::Ok),
+            )
+            .boxed(),
+        }
+    }
+
     pub fn parents(&self) -> &Vec<ManifestUnodeId> {
         &self.parents
     }
@@ -255,10 +360,16 @@ impl ManifestUnode {
             })
             .collect::<Result<_>>()?;
 
+        let sharded_subentries = t
+            .sharded_subentries
+            .map(ShardedMapNode::from_thrift)
+            .transpose()?;
+
         let linknode = ChangesetId::from_thrift(t.linknode)?;
         Ok(ManifestUnode {
             parents,
             subentries,
+            sharded_subentries,
             linknode,
         })
     }
@@ -276,6 +387,7 @@ impl ManifestUnode {
             parents,
             subentries,
             linknode: self.linknode.into_thrift(),
+            sharded_subentries: self.sharded_subentries.map(ShardedMapNode::into_thrift),
         }
     }
 
@@ -293,7 +405,19 @@ pub enum UnodeEntry {
 }
 
 impl UnodeEntry {
-    pub(crate) fn from_thrift(t: thrift::UnodeEntry) -> Result<UnodeEntry> {
+    pub fn is_directory(&self) -> bool {
+        match self {
+            UnodeEntry::File(_) => false,
+            UnodeEntry::Directory(_) => true,
+        }
+    }
+}
+
+impl ThriftConvert for UnodeEntry {
+    const NAME: &'static str = "UnodeEntry";
+    type Thrift = thrift::UnodeEntry;
+
+    fn from_thrift(t: Self::Thrift) -> Result<Self> {
         match t {
             thrift::UnodeEntry::File(file_unode_id) => {
                 let file_unode_id = FileUnodeId::from_thrift(file_unode_id)?;
@@ -310,7 +434,7 @@ impl UnodeEntry {
         }
     }
 
-    pub(crate) fn into_thrift(self) -> thrift::UnodeEntry {
+    fn into_thrift(self) -> Self::Thrift {
         match self {
             UnodeEntry::File(file_unode_id) => {
                 thrift::UnodeEntry::File(file_unode_id.into_thrift())
@@ -320,13 +444,11 @@ impl UnodeEntry {
             }
         }
     }
+}
 
-    pub fn is_directory(&self) -> bool {
-        match self {
-            UnodeEntry::File(_) => false,
-            UnodeEntry::Directory(_) => true,
-        }
-    }
+impl MapValue for UnodeEntry {
+    type Id = ShardedMapNodeUnodeId;
+    type Context = ShardedMapNodeUnodeContext;
 }
 
 impl BlobstoreValue for ManifestUnode {
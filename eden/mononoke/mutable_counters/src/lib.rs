@@ -10,8 +10,9 @@
 //! revisions have been blobimported, replayed, etc.
 //!
 //! The counter values themselves are stored in a table in the metadata
-//! database.
+//! database.  `MemMutableCounters` keeps them in memory instead, for tests.
 
+use anyhow::anyhow;
 use anyhow::Result;
 use async_trait::async_trait;
 use context::CoreContext;
@@ -25,6 +26,10 @@ use sql_ext::SqlConnections;
 use sql_ext::TransactionResult;
 use stats::prelude::*;
 
+mod memory;
+
+pub use crate::memory::MemMutableCounters;
+
 define_stats! {
     prefix = "mononoke.mutable_counters";
     cur_value: dynamic_singleton_counter("{}.cur_value", (name: String)),
@@ -48,6 +53,10 @@ pub trait MutableCounters {
         prev_value: Option<i64>,
     ) -> Result<bool>;
 
+    /// Atomically add `delta` to the counter, creating it with value 0 if it
+    /// doesn't exist.  Returns the new value.
+    async fn increment_counter(&self, ctx: &CoreContext, name: &str, delta: i64) -> Result<i64>;
+
     /// Get the names and values of all the counters for the repository.
     async fn get_all_counters(&self, ctx: &CoreContext) -> Result<Vec<(String, i64)>>;
 }
@@ -79,6 +88,20 @@ mononoke_queries! {
         )
     }
 
+    write IncrementCounter(
+        repo_id: RepositoryId, name: &str, delta: i64
+    ) {
+        none,
+        mysql(
+            "INSERT INTO mutable_counters (repo_id, name, value) VALUES ({repo_id}, {name}, {delta})
+            ON DUPLICATE KEY UPDATE value = value + {delta}"
+        )
+        sqlite(
+            "INSERT INTO mutable_counters (repo_id, name, value) VALUES ({repo_id}, CAST({name} AS TEXT), {delta})
+            ON CONFLICT (repo_id, name) DO UPDATE SET value = value + {delta}"
+        )
+    }
+
     read GetCounter(repo_id: RepositoryId, name: &str) -> (i64) {
         mysql(
             "SELECT value FROM mutable_counters WHERE repo_id = {repo_id} and name = {name}"
@@ -162,6 +185,23 @@ impl MutableCounters for SqlMutableCounters {
         }
     }
 
+    async fn increment_counter(&self, ctx: &CoreContext, name: &str, delta: i64) -> Result<i64> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        let conn = &self.connections.write_connection;
+        let txn = conn.start_transaction().await?;
+        let (txn, _) =
+            IncrementCounter::query_with_transaction(txn, &self.repo_id, &name, &delta).await?;
+        let (txn, counter) = GetCounter::query_with_transaction(txn, &self.repo_id, &name).await?;
+        txn.commit().await?;
+        let value = counter
+            .first()
+            .map(|entry| entry.0)
+            .ok_or_else(|| anyhow!("Counter '{}' missing after increment", name))?;
+        STATS::cur_value.set_value(ctx.fb, value, (name.to_owned(),));
+        Ok(value)
+    }
+
     async fn get_all_counters(&self, ctx: &CoreContext) -> Result<Vec<(String, i64)>> {
         ctx.perf_counters()
             .increment_counter(PerfCounterType::SqlReadsMaster);
@@ -0,0 +1,72 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use context::CoreContext;
+
+use crate::MutableCounters;
+
+/// Mutable counters for a single repository, stored in memory.
+#[derive(Default)]
+pub struct MemMutableCounters {
+    counters: Mutex<BTreeMap<String, i64>>,
+}
+
+impl MemMutableCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl MutableCounters for MemMutableCounters {
+    async fn get_counter(&self, _ctx: &CoreContext, name: &str) -> Result<Option<i64>> {
+        Ok(self.counters.lock().unwrap().get(name).copied())
+    }
+
+    async fn get_maybe_stale_counter(&self, ctx: &CoreContext, name: &str) -> Result<Option<i64>> {
+        self.get_counter(ctx, name).await
+    }
+
+    async fn set_counter(
+        &self,
+        _ctx: &CoreContext,
+        name: &str,
+        value: i64,
+        prev_value: Option<i64>,
+    ) -> Result<bool> {
+        let mut counters = self.counters.lock().unwrap();
+        if let Some(prev_value) = prev_value {
+            if counters.get(name) != Some(&prev_value) {
+                return Ok(false);
+            }
+        }
+        counters.insert(name.to_string(), value);
+        Ok(true)
+    }
+
+    async fn increment_counter(&self, _ctx: &CoreContext, name: &str, delta: i64) -> Result<i64> {
+        let mut counters = self.counters.lock().unwrap();
+        let value = counters.entry(name.to_string()).or_insert(0);
+        *value += delta;
+        Ok(*value)
+    }
+
+    async fn get_all_counters(&self, _ctx: &CoreContext) -> Result<Vec<(String, i64)>> {
+        Ok(self
+            .counters
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, value)| (name.clone(), *value))
+            .collect())
+    }
+}
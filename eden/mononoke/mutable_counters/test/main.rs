@@ -9,6 +9,7 @@ use anyhow::Result;
 use context::CoreContext;
 use fbinit::FacebookInit;
 use mononoke_types_mocks::repo::REPO_ZERO;
+use mutable_counters::MemMutableCounters;
 use mutable_counters::MutableCounters;
 use mutable_counters::SqlMutableCounters;
 use mutable_counters::SqlMutableCountersBuilder;
@@ -91,3 +92,71 @@ async fn test_counter_conditional_update(fb: FacebookInit) -> Result<()> {
 
     Ok(())
 }
+
+async fn check_counter_increment(ctx: &CoreContext, mutable_counters: &dyn MutableCounters) {
+    assert_eq!(
+        mutable_counters
+            .increment_counter(ctx, "counter", 5)
+            .await
+            .unwrap(),
+        5
+    );
+    assert_eq!(
+        mutable_counters
+            .increment_counter(ctx, "counter", -2)
+            .await
+            .unwrap(),
+        3
+    );
+    assert_eq!(
+        mutable_counters.get_counter(ctx, "counter").await.unwrap(),
+        Some(3)
+    );
+
+    mutable_counters
+        .set_counter(ctx, "counter2", 10, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        mutable_counters
+            .increment_counter(ctx, "counter2", 1)
+            .await
+            .unwrap(),
+        11
+    );
+    assert_eq!(
+        mutable_counters.get_all_counters(ctx).await.unwrap(),
+        vec![("counter".to_string(), 3), ("counter2".to_string(), 11)]
+    );
+}
+
+#[fbinit::test]
+async fn test_counter_increment(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    check_counter_increment(&ctx, &create_db()?).await;
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_mem_counters(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let mutable_counters = MemMutableCounters::new();
+
+    assert!(
+        mutable_counters
+            .set_counter(&ctx, "counter", 1, None)
+            .await?
+    );
+    assert!(
+        !mutable_counters
+            .set_counter(&ctx, "counter", 3, Some(2))
+            .await?
+    );
+    assert_eq!(
+        mutable_counters.get_counter(&ctx, "counter").await?,
+        Some(1)
+    );
+
+    check_counter_increment(&ctx, &MemMutableCounters::new()).await;
+    Ok(())
+}
@@ -113,7 +113,12 @@ impl FilenodesWriter {
             return Ok(FilenodeResult::Disabled);
         }
 
-        for chunk in filenodes.chunks(self.chunk_size) {
+        let chunk_size = match tunables().filenodes_insert_chunk_size().unwrap_or_default() {
+            chunk_size if chunk_size > 0 => chunk_size as usize,
+            _ => self.chunk_size,
+        };
+
+        for chunk in filenodes.chunks(chunk_size) {
             let read_conn = &self.read_connections[shard_number];
             let write_conn = &self.write_connections[shard_number];
             ensure_paths_exists(ctx, read_conn, write_conn, repo_id, chunk)
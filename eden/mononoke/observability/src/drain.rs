@@ -5,17 +5,22 @@
  * GNU General Public License version 2.
  */
 
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
 use slog::Drain;
 use slog::Level;
 use slog::Never;
 use slog::OwnedKVList;
 use slog::Record;
+use tunables::tunables;
 
 use crate::context::ObservabilityContext;
 
 pub struct DynamicLevelDrain<D> {
     inner: D,
     observability_context: ObservabilityContext,
+    module_levels: ArcSwap<ModuleLevels>,
 }
 
 impl<D> DynamicLevelDrain<D> {
@@ -23,11 +28,28 @@ impl<D> DynamicLevelDrain<D> {
         Self {
             inner,
             observability_context,
+            module_levels: ArcSwap::from_pointee(ModuleLevels::default()),
         }
     }
 
-    fn current_level(&self) -> Level {
-        self.observability_context.get_logging_level()
+    fn current_level(&self, module: &str) -> Level {
+        self.module_level(module)
+            .unwrap_or_else(|| self.observability_context.get_logging_level())
+    }
+
+    /// The level overridden by the `log_module_levels` tunable for the
+    /// module, if any.
+    fn module_level(&self, module: &str) -> Option<Level> {
+        let entries = tunables().log_module_levels()?;
+        let module_levels = self.module_levels.load();
+        if module_levels.is_parsed_from(&entries) {
+            return module_levels.get(module);
+        }
+        // Threads that race to parse the updated tunable parse the same
+        // entries, so it doesn't matter which of them is stored.
+        let module_levels = Arc::new(ModuleLevels::parse(entries));
+        self.module_levels.store(module_levels.clone());
+        module_levels.get(module)
     }
 }
 
@@ -36,10 +58,119 @@ impl<D: Drain<Ok = (), Err = Never>> Drain for DynamicLevelDrain<D> {
     type Err = Never;
 
     fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
-        if record.level().is_at_least(self.current_level()) {
+        if record
+            .level()
+            .is_at_least(self.current_level(record.module()))
+        {
             self.inner.log(record, values)
         } else {
             Ok(())
         }
     }
 }
+
+/// Per-module level overrides, parsed from the entries of the
+/// `log_module_levels` tunable.  They are only parsed again when the
+/// tunable is updated.
+#[derive(Default)]
+struct ModuleLevels {
+    entries: Option<Arc<Vec<String>>>,
+    levels: Vec<(String, Level)>,
+}
+
+impl ModuleLevels {
+    /// Entries are of the form `module::path=level`.  Invalid entries are
+    /// ignored, so that they don't affect logging from other modules.
+    fn parse(entries: Arc<Vec<String>>) -> Self {
+        let levels = entries
+            .iter()
+            .filter_map(|entry| {
+                let (module, level) = entry.split_once('=')?;
+                let level = level.trim().parse::<Level>().ok()?;
+                Some((module.trim().to_string(), level))
+            })
+            .collect();
+        Self {
+            entries: Some(entries),
+            levels,
+        }
+    }
+
+    fn is_parsed_from(&self, entries: &Arc<Vec<String>>) -> bool {
+        self.entries
+            .as_ref()
+            .map_or(false, |parsed| Arc::ptr_eq(parsed, entries))
+    }
+
+    /// The level of the most specific module that the module is in.
+    fn get(&self, module: &str) -> Option<Level> {
+        self.levels
+            .iter()
+            .filter(|(prefix, _)| {
+                module
+                    .strip_prefix(prefix.as_str())
+                    .map_or(false, |rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, level)| *level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module_levels(entries: &[&str]) -> ModuleLevels {
+        ModuleLevels::parse(Arc::new(
+            entries.iter().map(|entry| entry.to_string()).collect(),
+        ))
+    }
+
+    #[test]
+    fn test_module_levels_prefix() {
+        let levels = module_levels(&["mononoke::repo=debug"]);
+        assert_eq!(levels.get("mononoke::repo"), Some(Level::Debug));
+        assert_eq!(levels.get("mononoke::repo::client"), Some(Level::Debug));
+        // Only whole path segments match.
+        assert_eq!(levels.get("mononoke::repo_client"), None);
+        assert_eq!(levels.get("mononoke"), None);
+    }
+
+    #[test]
+    fn test_module_levels_most_specific() {
+        let levels = module_levels(&[
+            "mononoke::repo::client=trace",
+            "mononoke=warn",
+            "mononoke::repo=debug",
+        ]);
+        assert_eq!(
+            levels.get("mononoke::repo::client::mod"),
+            Some(Level::Trace)
+        );
+        assert_eq!(levels.get("mononoke::repo::blobstore"), Some(Level::Debug));
+        assert_eq!(levels.get("mononoke::api"), Some(Level::Warning));
+        assert_eq!(levels.get("other"), None);
+    }
+
+    #[test]
+    fn test_module_levels_invalid_entries() {
+        let levels = module_levels(&[
+            "mononoke::repo",
+            "mononoke::api=loud",
+            "=",
+            " mononoke::hooks = error ",
+        ]);
+        assert_eq!(levels.get("mononoke::repo"), None);
+        assert_eq!(levels.get("mononoke::api"), None);
+        assert_eq!(levels.get("mononoke::hooks"), Some(Level::Error));
+    }
+
+    #[test]
+    fn test_module_levels_parsed_from() {
+        let entries = Arc::new(vec!["mononoke=debug".to_string()]);
+        let levels = ModuleLevels::parse(entries.clone());
+        assert!(levels.is_parsed_from(&entries));
+        assert!(!levels.is_parsed_from(&Arc::new(vec!["mononoke=debug".to_string()])));
+        assert!(!ModuleLevels::default().is_parsed_from(&entries));
+    }
+}
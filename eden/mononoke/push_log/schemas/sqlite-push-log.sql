@@ -0,0 +1,50 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+CREATE TABLE IF NOT EXISTS `push_log` (
+  `id` INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  `repo_id` INTEGER NOT NULL,
+  `timestamp` BIGINT NOT NULL,
+  `pusher` VARCHAR(512) NOT NULL,
+  `session_id` VARCHAR(64) NOT NULL,
+  `client_correlator` VARCHAR(255) NULL,
+  `commit_count` BIGINT NOT NULL,
+  `outcome` TEXT NOT NULL,
+  `hook_results` VARCHAR(512) NULL,
+  `duration_ms` BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS `push_log_repo_timestamp`
+  ON `push_log` (`repo_id`, `timestamp`);
+
+CREATE INDEX IF NOT EXISTS `push_log_repo_pusher_timestamp`
+  ON `push_log` (`repo_id`, `pusher`, `timestamp`);
+
+CREATE TABLE IF NOT EXISTS `push_log_bookmarks` (
+  `push_id` BIGINT NOT NULL,
+  `bookmark` VARCHAR(512) NOT NULL,
+  `from_cs_id` VARBINARY(32) NULL,
+  `to_cs_id` VARBINARY(32) NULL,
+  PRIMARY KEY (`push_id`, `bookmark`)
+);
+
+CREATE INDEX IF NOT EXISTS `push_log_bookmarks_bookmark`
+  ON `push_log_bookmarks` (`bookmark`, `push_id`);
+
+CREATE TABLE IF NOT EXISTS `push_log_idempotency_keys` (
+  `repo_id` INTEGER NOT NULL,
+  `pusher` VARCHAR(512) NOT NULL,
+  `idempotency_key` VARCHAR(255) NOT NULL,
+  `push_hash` VARCHAR(64) NOT NULL,
+  `claimed_until` BIGINT NOT NULL,
+  `expires_at` BIGINT NOT NULL,
+  `response` LONGBLOB NULL,
+  PRIMARY KEY (`repo_id`, `pusher`, `idempotency_key`)
+);
+
+CREATE INDEX IF NOT EXISTS `push_log_idempotency_keys_expires_at`
+  ON `push_log_idempotency_keys` (`repo_id`, `expires_at`);
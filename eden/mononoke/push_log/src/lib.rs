@@ -0,0 +1,873 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Push log records every push to a repository: who pushed, which bookmarks
+//! it moved, how many commits it contained, how it ended and how long it
+//! took.
+//!
+//! Entries record the session the push was served in and the correlator of
+//! the client that made it, so that a push can be joined with the server's
+//! and the client's logs of it.
+//!
+//! The push log also keeps the responses to pushes that were made with an
+//! idempotency key for a while, so that a client that retries a push after
+//! losing the connection gets the response to the original push rather than
+//! pushing again.  A push that held a key but never finished may or may not
+//! have landed, so its key is never claimed again until it is forgotten.
+//!
+//! Entries are kept for a limited time: `prune` deletes the ones older than
+//! the retention period in batches, and is run after pushes are recorded.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use async_trait::async_trait;
+use context::CoreContext;
+use context::PerfCounterType;
+use mononoke_types::ChangesetId;
+use mononoke_types::RepositoryId;
+use mononoke_types::Timestamp;
+use sql_construct::SqlConstruct;
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::mononoke_queries;
+use sql_ext::SqlConnections;
+
+/// How a push ended.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PushOutcome {
+    Success,
+    /// Hooks rejected the push.
+    HookRejected,
+    /// The push couldn't be rebased because of conflicts.
+    Conflicts,
+    /// The pusher exceeded a rate limit.
+    RateLimited,
+    /// The repo was read-only.
+    ReadOnly,
+    /// The push was a retry of an earlier push with the same idempotency
+    /// key, and the response to the earlier push was returned.
+    Replayed,
+    /// The push failed with the given error.
+    Failure(String),
+}
+
+impl PushOutcome {
+    fn to_sql(&self) -> String {
+        match self {
+            Self::Success => "success".to_string(),
+            Self::HookRejected => "hook_rejected".to_string(),
+            Self::Conflicts => "conflicts".to_string(),
+            Self::RateLimited => "rate_limited".to_string(),
+            Self::ReadOnly => "read_only".to_string(),
+            Self::Replayed => "replayed".to_string(),
+            Self::Failure(err) => format!("failure: {}", err),
+        }
+    }
+
+    fn from_sql(outcome: String) -> Self {
+        match outcome.as_str() {
+            "success" => Self::Success,
+            "hook_rejected" => Self::HookRejected,
+            "conflicts" => Self::Conflicts,
+            "rate_limited" => Self::RateLimited,
+            "read_only" => Self::ReadOnly,
+            "replayed" => Self::Replayed,
+            _ => Self::Failure(
+                outcome
+                    .strip_prefix("failure: ")
+                    .map(ToString::to_string)
+                    .unwrap_or(outcome),
+            ),
+        }
+    }
+}
+
+/// A bookmark that a push moved, or tried to move.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PushLogBookmark {
+    pub bookmark: String,
+    /// Where the bookmark pointed before the push, if it existed.  For
+    /// pushrebases that didn't say where they expected it to be, this is
+    /// unknown.
+    pub from: Option<ChangesetId>,
+    /// Where the push moved the bookmark to, if it wasn't deleted.  For
+    /// pushrebases that failed, this is unknown.
+    pub to: Option<ChangesetId>,
+}
+
+/// A single entry in the push log.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PushLogEntry {
+    /// When the push started.
+    pub timestamp: Timestamp,
+    /// Who pushed: their unix name, or their identities if they have none.
+    pub pusher: String,
+    /// The session the push was served in.
+    pub session_id: String,
+    /// The correlator the client logs its own telemetry with, if it sent
+    /// one.
+    pub client_correlator: Option<String>,
+    /// The bookmarks the push moved, ordered by name.
+    pub bookmarks: Vec<PushLogBookmark>,
+    /// The number of commits pushed.
+    pub commit_count: u64,
+    pub outcome: PushOutcome,
+    /// Where the results of the hooks run on the push were logged, if they
+    /// were.
+    pub hook_results: Option<String>,
+    pub duration: Duration,
+}
+
+/// Which pushes to list.
+#[derive(Clone, Debug)]
+pub struct PushLogQuery {
+    /// Only pushes that started at or after this time.
+    pub since: Timestamp,
+    /// Only pushes that started before this time.
+    pub until: Timestamp,
+    /// Only pushes by this pusher.
+    pub pusher: Option<String>,
+    /// Only pushes that moved this bookmark.
+    pub bookmark: Option<String>,
+    pub limit: u64,
+}
+
+/// The state of an idempotency key when a push with it is about to start.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum IdempotencyKeyClaim {
+    /// No other push has the key, and it is now held by this one.
+    Claimed,
+    /// Another push with the key is still in progress.
+    InProgress,
+    /// Another push with the key neither finished nor released it in time,
+    /// so whether it landed is unknown.
+    Abandoned,
+    /// The key was used for a different push.
+    Mismatched,
+    /// Another push with the key succeeded, with this response.
+    Completed(Vec<u8>),
+}
+
+#[facet::facet]
+#[async_trait]
+pub trait PushLog: Send + Sync {
+    /// Append an entry to the push log.
+    async fn record(&self, ctx: &CoreContext, entry: PushLogEntry) -> Result<()>;
+
+    /// List the pushes that match the query, newest first.
+    async fn query(&self, ctx: &CoreContext, query: &PushLogQuery) -> Result<Vec<PushLogEntry>>;
+
+    /// Delete at most `limit` of the oldest pushes that started before
+    /// `before`, returning how many were deleted.
+    async fn prune(&self, ctx: &CoreContext, before: Timestamp, limit: u64) -> Result<u64>;
+
+    /// Claim the idempotency key `key` of `pusher` for a push with contents
+    /// hashed to `push_hash` that is about to start.  If the push neither
+    /// completes nor releases the key within `timeout`, the key is abandoned,
+    /// and is forgotten `ttl` later.
+    async fn claim_idempotency_key(
+        &self,
+        ctx: &CoreContext,
+        pusher: &str,
+        key: &str,
+        push_hash: &str,
+        timeout: Duration,
+        ttl: Duration,
+    ) -> Result<IdempotencyKeyClaim>;
+
+    /// Keep the response to the push that claimed an idempotency key for
+    /// `ttl`, returning it to retries of the push in the meantime.
+    async fn complete_idempotency_key(
+        &self,
+        ctx: &CoreContext,
+        pusher: &str,
+        key: &str,
+        response: &[u8],
+        ttl: Duration,
+    ) -> Result<()>;
+
+    /// Release an idempotency key claimed by a push that failed, so that the
+    /// push can be retried.
+    async fn release_idempotency_key(
+        &self,
+        ctx: &CoreContext,
+        pusher: &str,
+        key: &str,
+    ) -> Result<()>;
+}
+
+mononoke_queries! {
+    write AddPush(
+        repo_id: RepositoryId,
+        timestamp: Timestamp,
+        pusher: &str,
+        session_id: &str,
+        client_correlator: Option<&str>,
+        commit_count: u64,
+        outcome: &str,
+        hook_results: Option<&str>,
+        duration_ms: u64,
+    ) {
+        none,
+        "INSERT INTO push_log
+            (repo_id, timestamp, pusher, session_id, client_correlator,
+             commit_count, outcome, hook_results, duration_ms)
+         VALUES
+            ({repo_id}, {timestamp}, {pusher}, {session_id}, {client_correlator},
+             {commit_count}, {outcome}, {hook_results}, {duration_ms})"
+    }
+
+    write AddPushBookmarks(
+        values: (
+            push_id: u64,
+            bookmark: str,
+            from_cs_id: Option<ChangesetId>,
+            to_cs_id: Option<ChangesetId>
+        )
+    ) {
+        none,
+        "INSERT INTO push_log_bookmarks (push_id, bookmark, from_cs_id, to_cs_id)
+         VALUES {values}"
+    }
+
+    read ListPushes(
+        repo_id: RepositoryId,
+        since: Timestamp,
+        until: Timestamp,
+        pusher: Option<&str>,
+        bookmark: Option<&str>,
+        limit: u64,
+    ) -> (u64, Timestamp, String, String, Option<String>, u64, String, Option<String>, u64) {
+        "SELECT id, timestamp, pusher, session_id, client_correlator,
+                commit_count, outcome, hook_results, duration_ms
+         FROM push_log
+         WHERE repo_id = {repo_id}
+           AND timestamp >= {since}
+           AND timestamp < {until}
+           AND ({pusher} IS NULL OR pusher = {pusher})
+           AND ({bookmark} IS NULL OR id IN (
+               SELECT push_id FROM push_log_bookmarks WHERE bookmark = {bookmark}
+           ))
+         ORDER BY id DESC
+         LIMIT {limit}"
+    }
+
+    read ListExpiredPushes(repo_id: RepositoryId, before: Timestamp, limit: u64) -> (u64,) {
+        "SELECT id
+         FROM push_log
+         WHERE repo_id = {repo_id} AND timestamp < {before}
+         ORDER BY id
+         LIMIT {limit}"
+    }
+
+    write DeletePushes(>list ids: u64) {
+        none,
+        "DELETE FROM push_log WHERE id IN {ids}"
+    }
+
+    write DeletePushBookmarks(>list push_ids: u64) {
+        none,
+        "DELETE FROM push_log_bookmarks WHERE push_id IN {push_ids}"
+    }
+
+    write DeleteExpiredIdempotencyKeys(repo_id: RepositoryId, now: Timestamp) {
+        none,
+        "DELETE FROM push_log_idempotency_keys
+         WHERE repo_id = {repo_id} AND expires_at <= {now}"
+    }
+
+    write ClaimIdempotencyKey(
+        values: (
+            repo_id: RepositoryId,
+            pusher: str,
+            idempotency_key: str,
+            push_hash: str,
+            claimed_until: Timestamp,
+            expires_at: Timestamp
+        )
+    ) {
+        insert_or_ignore,
+        "{insert_or_ignore} INTO push_log_idempotency_keys
+            (repo_id, pusher, idempotency_key, push_hash, claimed_until, expires_at)
+         VALUES {values}"
+    }
+
+    write CompleteIdempotencyKey(
+        repo_id: RepositoryId,
+        pusher: &str,
+        idempotency_key: &str,
+        expires_at: Timestamp,
+        response: &[u8],
+    ) {
+        none,
+        "UPDATE push_log_idempotency_keys
+         SET expires_at = {expires_at}, response = {response}
+         WHERE repo_id = {repo_id}
+           AND pusher = {pusher}
+           AND idempotency_key = {idempotency_key}"
+    }
+
+    write ReleaseIdempotencyKey(
+        repo_id: RepositoryId,
+        pusher: &str,
+        idempotency_key: &str,
+    ) {
+        none,
+        "DELETE FROM push_log_idempotency_keys
+         WHERE repo_id = {repo_id}
+           AND pusher = {pusher}
+           AND idempotency_key = {idempotency_key}"
+    }
+
+    read GetIdempotencyKey(
+        repo_id: RepositoryId,
+        pusher: &str,
+        idempotency_key: &str,
+        now: Timestamp,
+    ) -> (String, Timestamp, Option<Vec<u8>>) {
+        "SELECT push_hash, claimed_until, response
+         FROM push_log_idempotency_keys
+         WHERE repo_id = {repo_id}
+           AND pusher = {pusher}
+           AND idempotency_key = {idempotency_key}
+           AND expires_at > {now}"
+    }
+
+    read ListPushBookmarks(
+        >list push_ids: u64
+    ) -> (u64, String, Option<ChangesetId>, Option<ChangesetId>) {
+        "SELECT push_id, bookmark, from_cs_id, to_cs_id
+         FROM push_log_bookmarks
+         WHERE push_id IN {push_ids}
+         ORDER BY bookmark"
+    }
+}
+
+pub struct SqlPushLog {
+    repo_id: RepositoryId,
+    connections: SqlConnections,
+}
+
+pub struct SqlPushLogBuilder {
+    connections: SqlConnections,
+}
+
+impl SqlConstruct for SqlPushLogBuilder {
+    const LABEL: &'static str = "push_log";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-push-log.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self { connections }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SqlPushLogBuilder {}
+
+impl SqlPushLogBuilder {
+    pub fn build(self, repo_id: RepositoryId) -> SqlPushLog {
+        SqlPushLog {
+            repo_id,
+            connections: self.connections,
+        }
+    }
+}
+
+/// The time `ttl` from now.
+fn expiry(ttl: Duration) -> Timestamp {
+    let ttl = i64::try_from(ttl.as_nanos()).unwrap_or(i64::MAX);
+    Timestamp::from_timestamp_nanos(Timestamp::now().timestamp_nanos().saturating_add(ttl))
+}
+
+#[async_trait]
+impl PushLog for SqlPushLog {
+    async fn record(&self, ctx: &CoreContext, entry: PushLogEntry) -> Result<()> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        let txn = self
+            .connections
+            .write_connection
+            .start_transaction()
+            .await?;
+        let (txn, result) = AddPush::query_with_transaction(
+            txn,
+            &self.repo_id,
+            &entry.timestamp,
+            &entry.pusher.as_str(),
+            &entry.session_id.as_str(),
+            &entry.client_correlator.as_deref(),
+            &entry.commit_count,
+            &entry.outcome.to_sql().as_str(),
+            &entry.hook_results.as_deref(),
+            &(entry.duration.as_millis() as u64),
+        )
+        .await?;
+        let push_id = result
+            .last_insert_id()
+            .ok_or_else(|| anyhow!("Failed to record push"))?;
+        let txn = if entry.bookmarks.is_empty() {
+            txn
+        } else {
+            let bookmarks = entry
+                .bookmarks
+                .iter()
+                .map(|bookmark| {
+                    (
+                        &push_id,
+                        bookmark.bookmark.as_str(),
+                        &bookmark.from,
+                        &bookmark.to,
+                    )
+                })
+                .collect::<Vec<_>>();
+            let (txn, _) = AddPushBookmarks::query_with_transaction(txn, &bookmarks).await?;
+            txn
+        };
+        txn.commit().await?;
+        Ok(())
+    }
+
+    async fn query(&self, ctx: &CoreContext, query: &PushLogQuery) -> Result<Vec<PushLogEntry>> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+        let rows = ListPushes::query(
+            &self.connections.read_connection,
+            &self.repo_id,
+            &query.since,
+            &query.until,
+            &query.pusher.as_deref(),
+            &query.bookmark.as_deref(),
+            &query.limit,
+        )
+        .await?;
+        if rows.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let push_ids = rows.iter().map(|row| row.0).collect::<Vec<_>>();
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+        let mut bookmarks: HashMap<u64, Vec<PushLogBookmark>> = HashMap::new();
+        for (push_id, bookmark, from, to) in
+            ListPushBookmarks::query(&self.connections.read_connection, &push_ids).await?
+        {
+            bookmarks
+                .entry(push_id)
+                .or_default()
+                .push(PushLogBookmark { bookmark, from, to });
+        }
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(
+                    id,
+                    timestamp,
+                    pusher,
+                    session_id,
+                    client_correlator,
+                    commit_count,
+                    outcome,
+                    hook_results,
+                    duration_ms,
+                )| PushLogEntry {
+                    timestamp,
+                    pusher,
+                    session_id,
+                    client_correlator,
+                    bookmarks: bookmarks.remove(&id).unwrap_or_default(),
+                    commit_count,
+                    outcome: PushOutcome::from_sql(outcome),
+                    hook_results,
+                    duration: Duration::from_millis(duration_ms),
+                },
+            )
+            .collect())
+    }
+
+    async fn prune(&self, ctx: &CoreContext, before: Timestamp, limit: u64) -> Result<u64> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsMaster);
+        let push_ids = ListExpiredPushes::query(
+            &self.connections.read_master_connection,
+            &self.repo_id,
+            &before,
+            &limit,
+        )
+        .await?
+        .into_iter()
+        .map(|(id,)| id)
+        .collect::<Vec<_>>();
+        if push_ids.is_empty() {
+            return Ok(0);
+        }
+
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        let txn = self
+            .connections
+            .write_connection
+            .start_transaction()
+            .await?;
+        let (txn, _) = DeletePushBookmarks::query_with_transaction(txn, &push_ids).await?;
+        let (txn, result) = DeletePushes::query_with_transaction(txn, &push_ids).await?;
+        txn.commit().await?;
+        Ok(result.affected_rows())
+    }
+
+    async fn claim_idempotency_key(
+        &self,
+        ctx: &CoreContext,
+        pusher: &str,
+        key: &str,
+        push_hash: &str,
+        timeout: Duration,
+        ttl: Duration,
+    ) -> Result<IdempotencyKeyClaim> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        DeleteExpiredIdempotencyKeys::query(
+            &self.connections.write_connection,
+            &self.repo_id,
+            &Timestamp::now(),
+        )
+        .await?;
+
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        let result = ClaimIdempotencyKey::query(
+            &self.connections.write_connection,
+            &[(
+                &self.repo_id,
+                pusher,
+                key,
+                push_hash,
+                &expiry(timeout),
+                &expiry(timeout.saturating_add(ttl)),
+            )],
+        )
+        .await?;
+        if result.affected_rows() == 1 {
+            return Ok(IdempotencyKeyClaim::Claimed);
+        }
+
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsMaster);
+        let now = Timestamp::now();
+        let rows = GetIdempotencyKey::query(
+            &self.connections.read_master_connection,
+            &self.repo_id,
+            &pusher,
+            &key,
+            &now,
+        )
+        .await?;
+        Ok(match rows.into_iter().next() {
+            Some((claimed_hash, _, _)) if claimed_hash != push_hash => {
+                IdempotencyKeyClaim::Mismatched
+            }
+            Some((_, _, Some(response))) => IdempotencyKeyClaim::Completed(response),
+            Some((_, claimed_until, None)) if claimed_until <= now => {
+                IdempotencyKeyClaim::Abandoned
+            }
+            // Either the push that claimed the key is still in progress, or
+            // its claim has just been released or forgotten.  Either way,
+            // the push can be retried later.
+            _ => IdempotencyKeyClaim::InProgress,
+        })
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        ctx: &CoreContext,
+        pusher: &str,
+        key: &str,
+        response: &[u8],
+        ttl: Duration,
+    ) -> Result<()> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        CompleteIdempotencyKey::query(
+            &self.connections.write_connection,
+            &self.repo_id,
+            &pusher,
+            &key,
+            &expiry(ttl),
+            &response,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn release_idempotency_key(
+        &self,
+        ctx: &CoreContext,
+        pusher: &str,
+        key: &str,
+    ) -> Result<()> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        ReleaseIdempotencyKey::query(
+            &self.connections.write_connection,
+            &self.repo_id,
+            &pusher,
+            &key,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+    use mononoke_types_mocks::repo::REPO_ONE;
+    use mononoke_types_mocks::repo::REPO_ZERO;
+
+    use super::*;
+
+    fn entry(time: i64, pusher: &str, bookmark: &str, outcome: PushOutcome) -> PushLogEntry {
+        PushLogEntry {
+            timestamp: Timestamp::from_timestamp_secs(time),
+            pusher: pusher.to_string(),
+            session_id: format!("session{}", time),
+            client_correlator: Some(format!("correlator{}", time)),
+            bookmarks: vec![PushLogBookmark {
+                bookmark: bookmark.to_string(),
+                from: Some(ONES_CSID),
+                to: Some(TWOS_CSID),
+            }],
+            commit_count: 1,
+            outcome,
+            hook_results: None,
+            duration: Duration::from_millis(1500),
+        }
+    }
+
+    fn query(since: i64, until: i64) -> PushLogQuery {
+        PushLogQuery {
+            since: Timestamp::from_timestamp_secs(since),
+            until: Timestamp::from_timestamp_secs(until),
+            pusher: None,
+            bookmark: None,
+            limit: 10,
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_record_and_query(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let push_log = SqlPushLogBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+
+        let first = entry(1000, "alice", "main", PushOutcome::Success);
+        let second = PushLogEntry {
+            bookmarks: vec![],
+            commit_count: 3,
+            hook_results: Some("hooks_table:session2000".to_string()),
+            ..entry(2000, "bob", "main", PushOutcome::HookRejected)
+        };
+        let third = entry(
+            3000,
+            "alice",
+            "release",
+            PushOutcome::Failure("timed out".to_string()),
+        );
+        for entry in [&first, &second, &third] {
+            push_log.record(&ctx, entry.clone()).await?;
+        }
+
+        assert_eq!(
+            push_log.query(&ctx, &query(0, 5000)).await?,
+            vec![third.clone(), second.clone(), first.clone()]
+        );
+        assert_eq!(
+            push_log.query(&ctx, &query(1000, 3000)).await?,
+            vec![second, first.clone()]
+        );
+        assert_eq!(
+            push_log
+                .query(
+                    &ctx,
+                    &PushLogQuery {
+                        pusher: Some("alice".to_string()),
+                        ..query(0, 5000)
+                    }
+                )
+                .await?,
+            vec![third.clone(), first.clone()]
+        );
+        assert_eq!(
+            push_log
+                .query(
+                    &ctx,
+                    &PushLogQuery {
+                        bookmark: Some("main".to_string()),
+                        ..query(0, 5000)
+                    }
+                )
+                .await?,
+            vec![first]
+        );
+        assert_eq!(
+            push_log
+                .query(
+                    &ctx,
+                    &PushLogQuery {
+                        limit: 1,
+                        ..query(0, 5000)
+                    }
+                )
+                .await?,
+            vec![third]
+        );
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_entries_are_per_repo(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let builder = SqlPushLogBuilder::with_sqlite_in_memory()?;
+        let connections = builder.connections.clone();
+        let push_log = builder.build(REPO_ZERO);
+        let other_push_log = SqlPushLogBuilder::from_sql_connections(connections).build(REPO_ONE);
+
+        push_log
+            .record(&ctx, entry(1000, "alice", "main", PushOutcome::Success))
+            .await?;
+
+        assert_eq!(push_log.query(&ctx, &query(0, 5000)).await?.len(), 1);
+        assert!(other_push_log
+            .query(&ctx, &query(0, 5000))
+            .await?
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_prune(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let push_log = SqlPushLogBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+
+        for time in [1000, 2000, 3000, 4000] {
+            push_log
+                .record(&ctx, entry(time, "alice", "main", PushOutcome::Success))
+                .await?;
+        }
+
+        let before = Timestamp::from_timestamp_secs(3500);
+        assert_eq!(push_log.prune(&ctx, before, 2).await?, 2);
+        assert_eq!(push_log.prune(&ctx, before, 2).await?, 1);
+        assert_eq!(push_log.prune(&ctx, before, 2).await?, 0);
+        assert_eq!(
+            push_log.query(&ctx, &query(0, 5000)).await?,
+            vec![entry(4000, "alice", "main", PushOutcome::Success)]
+        );
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_idempotency_keys(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let push_log = SqlPushLogBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+        let timeout = Duration::from_secs(60);
+        let ttl = Duration::from_secs(3600);
+        let claim = |pusher, key, push_hash, timeout| {
+            push_log.claim_idempotency_key(&ctx, pusher, key, push_hash, timeout, ttl)
+        };
+
+        assert_eq!(
+            claim("alice", "key1", "hash1", timeout).await?,
+            IdempotencyKeyClaim::Claimed
+        );
+        assert_eq!(
+            claim("alice", "key1", "hash1", timeout).await?,
+            IdempotencyKeyClaim::InProgress
+        );
+        // Keys are per pusher.
+        assert_eq!(
+            claim("bob", "key1", "hash1", timeout).await?,
+            IdempotencyKeyClaim::Claimed
+        );
+
+        push_log
+            .complete_idempotency_key(&ctx, "alice", "key1", b"response", ttl)
+            .await?;
+        assert_eq!(
+            claim("alice", "key1", "hash1", timeout).await?,
+            IdempotencyKeyClaim::Completed(b"response".to_vec())
+        );
+        // A different push can't reuse the key.
+        assert_eq!(
+            claim("alice", "key1", "hash2", timeout).await?,
+            IdempotencyKeyClaim::Mismatched
+        );
+
+        push_log
+            .release_idempotency_key(&ctx, "bob", "key1")
+            .await?;
+        assert_eq!(
+            claim("bob", "key1", "hash1", timeout).await?,
+            IdempotencyKeyClaim::Claimed
+        );
+
+        // Expired keys are forgotten, and can be claimed again.
+        push_log
+            .complete_idempotency_key(&ctx, "bob", "key1", b"response", Duration::ZERO)
+            .await?;
+        assert_eq!(
+            claim("bob", "key1", "hash1", timeout).await?,
+            IdempotencyKeyClaim::Claimed
+        );
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_abandoned_idempotency_keys(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let push_log = SqlPushLogBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+        let ttl = Duration::from_secs(3600);
+
+        // The push that claimed the key never finished, so a retry of it
+        // can't tell whether it landed.
+        assert_eq!(
+            push_log
+                .claim_idempotency_key(&ctx, "alice", "key1", "hash1", Duration::ZERO, ttl)
+                .await?,
+            IdempotencyKeyClaim::Claimed
+        );
+        assert_eq!(
+            push_log
+                .claim_idempotency_key(&ctx, "alice", "key1", "hash1", Duration::ZERO, ttl)
+                .await?,
+            IdempotencyKeyClaim::Abandoned
+        );
+
+        // Once abandoned keys are forgotten, they can be claimed again.
+        assert_eq!(
+            push_log
+                .claim_idempotency_key(&ctx, "bob", "key1", "hash1", Duration::ZERO, Duration::ZERO)
+                .await?,
+            IdempotencyKeyClaim::Claimed
+        );
+        assert_eq!(
+            push_log
+                .claim_idempotency_key(&ctx, "bob", "key1", "hash1", Duration::ZERO, ttl)
+                .await?,
+            IdempotencyKeyClaim::Claimed
+        );
+
+        Ok(())
+    }
+}
@@ -61,6 +61,7 @@ use anyhow::format_err;
 use anyhow::Error;
 use anyhow::Result;
 use blobrepo_utils::convert_diff_result_into_file_change_for_diamond_merge;
+use blobstore::Blobstore;
 use blobstore::Loadable;
 use bonsai_hg_mapping::BonsaiHgMappingRef;
 use bookmarks::BookmarkKey;
@@ -70,9 +71,11 @@ use changeset_fetcher::ChangesetFetcherArc;
 use changesets::ChangesetsRef;
 use context::CoreContext;
 use derived_data_filenodes::FilenodesOnlyPublic;
+use filestore::FilestoreConfigRef;
 use futures::compat::Stream01CompatExt;
 use futures::future;
 use futures::future::try_join;
+use futures::future::try_join3;
 use futures::future::try_join_all;
 use futures::stream;
 use futures::FutureExt;
@@ -84,6 +87,7 @@ use manifest::bonsai_diff;
 use manifest::BonsaiDiffFileChange;
 use manifest::ManifestOps;
 use maplit::hashmap;
+use memblob::Memblob;
 use mercurial_derived_data::DeriveHgChangeset;
 use mercurial_types::HgChangesetId;
 use mercurial_types::HgFileNodeId;
@@ -110,6 +114,10 @@ use stats::prelude::*;
 use thiserror::Error;
 use tunables::tunables;
 
+use crate::merge::MergedFileChanges;
+
+mod merge;
+
 define_stats! {
     prefix = "mononoke.pushrebase";
     // Clowntown: This is actually nanoseconds (ns), not microseconds (us)
@@ -117,6 +125,7 @@ define_stats! {
     critical_section_failure_duration_us: dynamic_timeseries("{}.critical_section_failure_duration_us", (reponame: String); Average, Sum, Count),
     critical_section_retries_failed: dynamic_timeseries("{}.critical_section_retries_failed", (reponame: String); Average, Sum),
     commits_rebased: dynamic_timeseries("{}.commits_rebased", (reponame: String); Average, Sum, Count),
+    conflicts_merged: timeseries(Sum),
 }
 
 const MAX_REBASE_ATTEMPTS: usize = 100;
@@ -145,6 +154,8 @@ pub enum PushrebaseInternalError {
     P2RootRebaseForbidden(HgChangesetId, BookmarkKey),
     #[error("Unexpected file conflicts when adding new file changes to {0}")]
     NewFileChangesConflict(ChangesetId),
+    #[error("Changeset {0} in range {1}::{2} has no parents in the range")]
+    NoParentsInRange(ChangesetId, ChangesetId, ChangesetId),
 }
 
 #[derive(Debug, Error)]
@@ -241,6 +252,7 @@ pub trait Repo = BonsaiHgMappingRef
     + BookmarksRef
     + ChangesetsRef
     + ChangesetFetcherArc
+    + FilestoreConfigRef
     + RepoBlobstoreArc
     + RepoDerivedDataRef
     + RepoIdentityRef
@@ -291,6 +303,69 @@ pub async fn do_pushrebase_bonsai(
     Ok(res)
 }
 
+/// The outcome of previewing a pushrebase.
+#[derive(Debug, Clone)]
+pub struct PushrebasePreview {
+    /// The value of the bookmark the commits would be rebased onto.
+    pub bookmark_value: Option<ChangesetId>,
+    /// The commit the pushed commits would be rebased from.
+    pub root: ChangesetId,
+    /// The number of commits the pushed commits would be rebased over.
+    pub pushrebase_distance: PushrebaseDistance,
+}
+
+/// Check whether a pushrebase of `pushed` onto `onto_bookmark` would
+/// succeed, without rebasing anything or moving the bookmark.
+///
+/// Pushrebase decides whether there are conflicts from the files changed on
+/// either side before it creates any commits, so this runs the same checks
+/// against the current value of the bookmark, and fails with the same errors
+/// as `do_pushrebase_bonsai` would if the bookmark didn't move in the
+/// meantime. If conflicting changes are merged, the merged file contents are
+/// only kept in memory. Pushrebase hooks are not run.
+pub async fn preview_pushrebase_bonsai(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    config: &PushrebaseFlags,
+    onto_bookmark: &BookmarkKey,
+    pushed: &HashSet<BonsaiChangeset>,
+) -> Result<PushrebasePreview, PushrebaseError> {
+    let head = find_only_head_or_fail(pushed)?;
+    let roots = find_roots(pushed);
+
+    let root = find_closest_root(ctx, repo, config, onto_bookmark, &roots).await?;
+
+    let (client_cf, client_bcs, bookmark_value) = try_join3(
+        find_changed_files(ctx, repo, root, head),
+        fetch_bonsai_range_ancestor_not_included(ctx, repo, root, head),
+        get_bookmark_value(ctx, repo, onto_bookmark),
+    )
+    .await?;
+
+    let onto = bookmark_value.unwrap_or(root);
+    let (server_commits, conflicts) =
+        check_server_changes(ctx, repo, config, root, onto, &client_cf, &client_bcs).await?;
+    // Nothing is rebased, so the merged contents are discarded.
+    let merged_blobstore = Memblob::default();
+    resolve_conflicts(
+        ctx,
+        repo,
+        &merged_blobstore,
+        config,
+        &conflicts,
+        root,
+        onto,
+        &client_bcs,
+    )
+    .await?;
+
+    Ok(PushrebasePreview {
+        bookmark_value,
+        root,
+        pushrebase_distance: PushrebaseDistance(server_commits),
+    })
+}
+
 async fn check_filenodes_backfilled<'a>(
     ctx: &CoreContext,
     repo: &impl RepoDerivedDataRef,
@@ -326,6 +401,7 @@ async fn rebase_in_loop(
     let should_log = config.monitoring_bookmark.as_deref() == Some(onto_bookmark.as_str());
     let mut latest_rebase_attempt = root;
     let mut pushrebase_distance = PushrebaseDistance(0);
+    let mut conflicts = Vec::new();
 
     let repo_args = (repo.repo_identity().name().to_string(),);
     for retry_num in 0..MAX_REBASE_ATTEMPTS {
@@ -341,40 +417,34 @@ async fn rebase_in_loop(
         )
         .await?;
 
-        let server_bcs = fetch_bonsai_range_ancestor_not_included(
+        let onto = old_bookmark_value.unwrap_or(root);
+        let (server_commits, new_conflicts) = check_server_changes(
             ctx,
             repo,
+            config,
             latest_rebase_attempt,
-            old_bookmark_value.unwrap_or(root),
+            onto,
+            &client_cf,
+            client_bcs,
         )
         .await?;
-        pushrebase_distance = pushrebase_distance.add(server_bcs.len());
-
-        for bcs in server_bcs.iter() {
-            if should_fail_pushrebase(bcs) {
-                return Err(PushrebaseError::ForceFailPushrebase(bcs.get_changeset_id()));
-            }
-        }
+        pushrebase_distance = pushrebase_distance.add(server_commits);
 
-        if config.casefolding_check {
-            let conflict =
-                check_case_conflicts(server_bcs.iter().rev().chain(client_bcs.iter().rev()));
-            if let Some(conflict) = conflict {
-                return Err(PushrebaseError::PotentialCaseConflict(conflict.1));
-            }
-        }
-
-        let server_cf = find_changed_files(
+        // Conflicts found in earlier attempts must be merged again, as the
+        // files may have changed since.
+        conflicts.extend(new_conflicts);
+        let merged_file_changes = resolve_conflicts(
             ctx,
             repo,
-            latest_rebase_attempt,
-            old_bookmark_value.unwrap_or(root),
+            repo.repo_blobstore(),
+            config,
+            &conflicts,
+            root,
+            onto,
+            client_bcs,
         )
         .await?;
 
-        // TODO: Avoid this clone
-        intersect_changed_files(server_cf, client_cf.clone())?;
-
         let rebase_outcome = do_rebase(
             ctx,
             repo,
@@ -385,6 +455,7 @@ async fn rebase_in_loop(
             onto_bookmark,
             hooks,
             retry_num,
+            &merged_file_changes,
         )
         .await?;
         // CRITICAL SECTION END: Right after writing new value of bookmark
@@ -425,6 +496,84 @@ async fn rebase_in_loop(
     Err(PushrebaseInternalError::TooManyRebaseAttempts.into())
 }
 
+/// Check that the commits between `ancestor` and `onto` on the server can be
+/// rebased over by the pushed commits: that none of them forbids it, and that
+/// there are no case conflicts.
+///
+/// Returns the number of commits between `ancestor` and `onto`, and the
+/// conflicts between the files they change and the files the pushed commits
+/// change.
+async fn check_server_changes(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    config: &PushrebaseFlags,
+    ancestor: ChangesetId,
+    onto: ChangesetId,
+    client_cf: &[MPath],
+    client_bcs: &[BonsaiChangeset],
+) -> Result<(usize, Vec<PushrebaseConflict>), PushrebaseError> {
+    let server_bcs = fetch_bonsai_range_ancestor_not_included(ctx, repo, ancestor, onto).await?;
+
+    for bcs in server_bcs.iter() {
+        if should_fail_pushrebase(bcs) {
+            return Err(PushrebaseError::ForceFailPushrebase(bcs.get_changeset_id()));
+        }
+    }
+
+    if config.casefolding_check {
+        let conflict =
+            check_case_conflicts(server_bcs.iter().rev().chain(client_bcs.iter().rev()));
+        if let Some(conflict) = conflict {
+            return Err(PushrebaseError::PotentialCaseConflict(conflict.1));
+        }
+    }
+
+    let server_cf = find_changed_files(ctx, repo, ancestor, onto).await?;
+
+    let conflicts = match intersect_changed_files(server_cf, client_cf.to_vec()) {
+        Ok(()) => Vec::new(),
+        Err(PushrebaseError::Conflicts(conflicts)) => conflicts,
+        Err(err) => return Err(err),
+    };
+
+    Ok((server_bcs.len(), conflicts))
+}
+
+/// Resolve the conflicts between the pushed commits and the commits between
+/// `root` and `onto` by merging the conflicting changes, if the config
+/// allows it. Fails if there are conflicts that can't be resolved.
+async fn resolve_conflicts<B: Blobstore + Clone + 'static>(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    merged_blobstore: &B,
+    config: &PushrebaseFlags,
+    conflicts: &[PushrebaseConflict],
+    root: ChangesetId,
+    onto: ChangesetId,
+    client_bcs: &[BonsaiChangeset],
+) -> Result<MergedFileChanges, PushrebaseError> {
+    if conflicts.is_empty() {
+        return Ok(MergedFileChanges::new());
+    }
+    if config.merge_non_overlapping_changes {
+        let merged = merge::merge_conflicts(
+            ctx,
+            repo,
+            merged_blobstore,
+            conflicts,
+            root,
+            onto,
+            client_bcs,
+        )
+        .await?;
+        if let Some(merged) = merged {
+            STATS::conflicts_merged.add_value(conflicts.len() as i64);
+            return Ok(merged);
+        }
+    }
+    Err(PushrebaseError::Conflicts(conflicts.to_vec()))
+}
+
 fn should_fail_pushrebase(bcs: &BonsaiChangeset) -> bool {
     bcs.hg_extra().any(|(key, _)| key == FAIL_PUSHREBASE_EXTRA)
 }
@@ -439,6 +588,7 @@ async fn do_rebase(
     onto_bookmark: &BookmarkKey,
     mut hooks: Vec<Box<dyn PushrebaseCommitHook>>,
     retry_num: PushrebaseRetryNum,
+    merged_file_changes: &MergedFileChanges,
 ) -> Result<Option<(ChangesetId, Vec<PushrebaseChangesetPair>)>, PushrebaseError> {
     let (new_head, rebased_changesets) = create_rebased_changesets(
         ctx,
@@ -448,6 +598,7 @@ async fn do_rebase(
         head,
         old_bookmark_value.unwrap_or(root),
         &mut hooks,
+        merged_file_changes,
     )
     .await?;
 
@@ -769,32 +920,37 @@ async fn find_changed_files(
 
             async move {
                 let parents: Vec<_> = bcs.parents().collect();
-                match *parents {
-                    [] | [_] => Ok(extract_conflict_files_from_bonsai_changeset(bcs)),
-                    [p0_id, p1_id] => {
-                        match (ids.get(&p0_id), ids.get(&p1_id)) {
-                            (Some(_), Some(_)) => {
-                                // both parents are in the rebase set, so we can just take
-                                // filechanges from bonsai changeset
-                                Ok(extract_conflict_files_from_bonsai_changeset(bcs))
-                            }
-                            (Some(p_id), None) | (None, Some(p_id)) => {
-                                // TODO(stash, T40460159) - include copy sources in the list of
-                                // conflict files
-
-                                // one of the parents is not in the rebase set, to calculate
-                                // changed files in this case we will compute manifest diff
-                                // between elements that are in rebase set.
-                                find_changed_files_between_manifests(ctx, repo, id, *p_id).await
-                            }
-                            (None, None) => panic!(
-                                "`RangeNodeStream` produced invalid result for: ({}, {})",
-                                descendant, ancestor,
-                            ),
-                        }
-                    }
-                    _ => panic!("pushrebase supports only two parents"),
+                let parents_in_range: Vec<_> = parents
+                    .iter()
+                    .filter(|p| ids.contains(*p))
+                    .copied()
+                    .collect();
+                if parents.len() <= 1 || parents_in_range.len() == parents.len() {
+                    // all parents are in the rebase set, so we can just take
+                    // filechanges from bonsai changeset
+                    return Ok(extract_conflict_files_from_bonsai_changeset(bcs));
+                }
+                if parents_in_range.is_empty() {
+                    return Err(PushrebaseError::from(
+                        PushrebaseInternalError::NoParentsInRange(id, ancestor, descendant),
+                    ));
                 }
+
+                // TODO(stash, T40460159) - include copy sources in the list of
+                // conflict files
+
+                // some of the parents are not in the rebase set, to calculate
+                // changed files in this case we will compute manifest diff
+                // between elements that are in rebase set.  Merges can have
+                // more than two parents, so several of them may be in the
+                // rebase set.
+                let mut changed_files = Vec::new();
+                for p_id in parents_in_range {
+                    changed_files.extend(
+                        find_changed_files_between_manifests(ctx, repo, id, p_id).await?,
+                    );
+                }
+                Ok(changed_files)
             }
         })
         .collect();
@@ -890,6 +1046,7 @@ async fn create_rebased_changesets(
     head: ChangesetId,
     onto: ChangesetId,
     hooks: &mut [Box<dyn PushrebaseCommitHook>],
+    merged_file_changes: &MergedFileChanges,
 ) -> Result<(ChangesetId, RebasedChangesets), PushrebaseError> {
     let rebased_set = find_rebased_set(ctx, repo, root, head).await?;
 
@@ -924,6 +1081,7 @@ async fn create_rebased_changesets(
             repo,
             &rebased_set_ids,
             hooks,
+            merged_file_changes,
         )
         .await?;
         let timestamp = Timestamp::from(*bcs_new.author_date());
@@ -956,6 +1114,7 @@ async fn rebase_changeset(
     repo: &impl Repo,
     rebased_set: &HashSet<ChangesetId>,
     hooks: &mut [Box<dyn PushrebaseCommitHook>],
+    merged_file_changes: &MergedFileChanges,
 ) -> Result<BonsaiChangeset> {
     let orig_cs_id = bcs.get_changeset_id();
     let new_file_changes =
@@ -1015,6 +1174,9 @@ async fn rebase_changeset(
     }
 
     file_changes.extend(new_file_changes);
+    if let Some(merged) = merged_file_changes.get(&orig_cs_id) {
+        file_changes.extend(merged.clone());
+    }
     bcs.file_changes = file_changes;
 
     for hook in hooks.iter_mut() {
@@ -3246,6 +3408,139 @@ mod tests {
         Ok(())
     }
 
+    #[fbinit::test]
+    async fn test_pushrebase_merge_non_overlapping_changes(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: BlobRepo = test_repo_factory::build_empty(fb)?;
+
+        let root = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file("file", "1\n2\n3\n4\n5\n")
+            .commit()
+            .await?;
+        let master = CreateCommitContext::new(&ctx, &repo, vec![root])
+            .add_file("file", "one\n2\n3\n4\n5\n")
+            .commit()
+            .await?;
+        bookmark(&ctx, &repo, "master").set_to(master).await?;
+
+        let merged = CreateCommitContext::new(&ctx, &repo, vec![root])
+            .add_file("file", "1\n2\n3\n4\nfive\n")
+            .commit()
+            .await?
+            .load(&ctx, repo.repo_blobstore())
+            .await?;
+        let overlapping = CreateCommitContext::new(&ctx, &repo, vec![root])
+            .add_file("file", "uno\n2\n3\n4\n5\n")
+            .commit()
+            .await?
+            .load(&ctx, repo.repo_blobstore())
+            .await?;
+
+        let book = master_bookmark();
+        let flags = PushrebaseFlags {
+            merge_non_overlapping_changes: true,
+            ..Default::default()
+        };
+
+        // Without merging enabled, changes to the same file conflict.
+        let res = do_pushrebase_bonsai(
+            &ctx,
+            &repo,
+            &Default::default(),
+            &book,
+            &hashset![merged.clone()],
+            &[],
+        )
+        .await;
+        should_have_conflicts(res);
+
+        let res =
+            do_pushrebase_bonsai(&ctx, &repo, &flags, &book, &hashset![overlapping], &[]).await;
+        should_have_conflicts(res);
+
+        let outcome =
+            do_pushrebase_bonsai(&ctx, &repo, &flags, &book, &hashset![merged], &[]).await?;
+        let hg_cs_id = repo.derive_hg_changeset(&ctx, outcome.head).await?;
+        ensure_content(
+            &ctx,
+            hg_cs_id,
+            &repo,
+            btreemap! {
+                "file".to_string() => "one\n2\n3\n4\nfive\n".to_string(),
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_preview_pushrebase(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: BlobRepo = test_repo_factory::build_empty(fb)?;
+
+        let root = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file("file", "base")
+            .commit()
+            .await?;
+        let master = CreateCommitContext::new(&ctx, &repo, vec![root])
+            .add_file("file", "master")
+            .commit()
+            .await?;
+        bookmark(&ctx, &repo, "master").set_to(master).await?;
+
+        let clean = CreateCommitContext::new(&ctx, &repo, vec![root])
+            .add_file("other", "other")
+            .commit()
+            .await?
+            .load(&ctx, repo.repo_blobstore())
+            .await?;
+        let conflicting = CreateCommitContext::new(&ctx, &repo, vec![root])
+            .add_file("file", "draft")
+            .commit()
+            .await?
+            .load(&ctx, repo.repo_blobstore())
+            .await?;
+
+        let book = master_bookmark();
+        let preview = preview_pushrebase_bonsai(
+            &ctx,
+            &repo,
+            &Default::default(),
+            &book,
+            &hashset![clean],
+        )
+        .await?;
+        assert_eq!(preview.bookmark_value, Some(master));
+        assert_eq!(preview.root, root);
+        assert_eq!(preview.pushrebase_distance.0, 1);
+
+        let res = preview_pushrebase_bonsai(
+            &ctx,
+            &repo,
+            &Default::default(),
+            &book,
+            &hashset![conflicting],
+        )
+        .await;
+        match res {
+            Err(PushrebaseError::Conflicts(conflicts)) => assert_eq!(
+                conflicts,
+                vec![PushrebaseConflict {
+                    left: MPath::new("file")?,
+                    right: MPath::new("file")?,
+                }],
+            ),
+            Err(err) => return Err(err.into()),
+            Ok(_) => return Err(format_err!("should have found a conflict")),
+        }
+
+        // Previewing doesn't move the bookmark.
+        assert_eq!(repo.bookmarks().get(ctx.clone(), &book).await?, Some(master));
+
+        Ok(())
+    }
+
     async fn ensure_content(
         ctx: &CoreContext,
         hg_cs_id: HgChangesetId,
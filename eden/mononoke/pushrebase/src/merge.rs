@@ -0,0 +1,316 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Merging of conflicting changes to the same file.
+//!
+//! Pushrebase refuses to rebase pushed commits over commits that changed the
+//! same files.  When both sides changed different lines of a text file,
+//! though, the changes can be merged: the rebased commit gets a file change
+//! whose content is the three-way merge of the file at the root, at the
+//! bookmark, and in the pushed commit.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use anyhow::Result;
+use blobstore::Blobstore;
+use blobstore::Loadable;
+use bytes::Bytes;
+use context::CoreContext;
+use filestore::FilestoreConfigRef;
+use filestore::StoreRequest;
+use futures::future::try_join;
+use futures::future::try_join_all;
+use futures::stream;
+use manifest::Entry;
+use manifest::ManifestOps;
+use mercurial_types::MPath;
+use mononoke_types::BonsaiChangeset;
+use mononoke_types::ChangesetId;
+use mononoke_types::FileChange;
+use mononoke_types::FileType;
+use xdiff::diff_hunks;
+
+use crate::id_to_manifestid;
+use crate::PushrebaseConflict;
+use crate::Repo;
+
+/// Files larger than this are never merged.
+const MAX_MERGE_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// File changes of pushed commits that replace their original file changes
+/// when the commits are rebased.
+pub(crate) type MergedFileChanges = HashMap<ChangesetId, BTreeMap<MPath, FileChange>>;
+
+/// A change to a range of lines of the base text.
+struct Change<'a> {
+    /// The lines of the base text that are replaced.
+    base: Range<usize>,
+    /// The lines that replace them.
+    lines: &'a [&'a [u8]],
+}
+
+fn split_lines(text: &[u8]) -> Vec<&[u8]> {
+    text.split_inclusive(|b| *b == b'\n').collect()
+}
+
+fn is_binary(text: &[u8]) -> bool {
+    text.contains(&0)
+}
+
+/// Merge the changes from `base` to `ours` and from `base` to `theirs` line
+/// by line.
+///
+/// Returns `None` if the changes conflict, i.e. if a change on one side
+/// overlaps or is adjacent to a change on the other side, or if any of the
+/// texts is binary.
+fn merge_text(base: &[u8], ours: &[u8], theirs: &[u8]) -> Option<Vec<u8>> {
+    if ours == theirs {
+        return Some(ours.to_vec());
+    }
+    if is_binary(base) || is_binary(ours) || is_binary(theirs) {
+        return None;
+    }
+
+    let base_lines = split_lines(base);
+    let our_lines = split_lines(ours);
+    let their_lines = split_lines(theirs);
+
+    let our_changes = diff_hunks(base, ours)
+        .into_iter()
+        .map(|hunk| Change {
+            base: hunk.remove,
+            lines: &our_lines[hunk.add],
+        })
+        .collect::<Vec<_>>();
+    let their_changes = diff_hunks(base, theirs)
+        .into_iter()
+        .map(|hunk| Change {
+            base: hunk.remove,
+            lines: &their_lines[hunk.add],
+        })
+        .collect::<Vec<_>>();
+
+    for ours in our_changes.iter() {
+        for theirs in their_changes.iter() {
+            if ours.base.start <= theirs.base.end && theirs.base.start <= ours.base.end {
+                return None;
+            }
+        }
+    }
+
+    let mut changes = our_changes
+        .into_iter()
+        .chain(their_changes)
+        .collect::<Vec<_>>();
+    changes.sort_by_key(|change| change.base.start);
+
+    let mut merged = Vec::with_capacity(ours.len().max(theirs.len()));
+    let mut pos = 0;
+    for change in changes {
+        merged.extend(base_lines[pos..change.base.start].concat());
+        merged.extend(change.lines.concat());
+        pos = change.base.end;
+    }
+    merged.extend(base_lines[pos..].concat());
+    Some(merged)
+}
+
+/// The type and content of the file at `path` in `cs_id`, if there is a
+/// file there that is small enough to merge.
+async fn fetch_file(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    cs_id: ChangesetId,
+    path: &MPath,
+) -> Result<Option<(FileType, Bytes)>> {
+    let mfid = id_to_manifestid(ctx, repo, cs_id).await?;
+    let entry = mfid
+        .find_entry(
+            ctx.clone(),
+            repo.repo_blobstore().clone(),
+            Some(path.clone()),
+        )
+        .await?;
+    let (file_type, filenode_id) = match entry {
+        Some(Entry::Leaf(leaf)) => leaf,
+        _ => return Ok(None),
+    };
+    let envelope = filenode_id.load(ctx, repo.repo_blobstore()).await?;
+    if envelope.content_size() > MAX_MERGE_FILE_SIZE {
+        return Ok(None);
+    }
+    let content = filestore::fetch_concat(repo.repo_blobstore(), ctx, envelope.content_id()).await?;
+    Ok(Some((file_type, content)))
+}
+
+/// Merge the changes made to a file by a pushed commit with the changes made
+/// to it between `root` and `onto`.  The merged content is stored in
+/// `merged_blobstore`.
+///
+/// Returns the pushed commit and its new file change, or `None` if the
+/// changes can't be merged.
+async fn merge_file<B: Blobstore + Clone + 'static>(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    merged_blobstore: &B,
+    path: &MPath,
+    root: ChangesetId,
+    onto: ChangesetId,
+    client_bcs: &[BonsaiChangeset],
+) -> Result<Option<(ChangesetId, FileChange)>> {
+    // Only merge files that were modified by a single pushed commit, without
+    // copy information.
+    let mut changes = client_bcs.iter().filter_map(|bcs| {
+        bcs.file_changes_map()
+            .get(path)
+            .map(|change| (bcs.get_changeset_id(), change))
+    });
+    let (cs_id, change) = match (changes.next(), changes.next()) {
+        (Some((cs_id, FileChange::Change(change))), None) if change.copy_from().is_none() => {
+            (cs_id, change)
+        }
+        _ => return Ok(None),
+    };
+    if change.size() > MAX_MERGE_FILE_SIZE {
+        return Ok(None);
+    }
+
+    let ((base, server), ours) = try_join(
+        try_join(
+            fetch_file(ctx, repo, root, path),
+            fetch_file(ctx, repo, onto, path),
+        ),
+        filestore::fetch_concat(repo.repo_blobstore(), ctx, change.content_id()),
+    )
+    .await?;
+    let (base, (server_file_type, server)) = match (base, server) {
+        (Some((_, base)), Some(server)) => (base, server),
+        _ => return Ok(None),
+    };
+    if server_file_type != change.file_type() {
+        return Ok(None);
+    }
+
+    let merged = match merge_text(&base, &server, &ours) {
+        Some(merged) => Bytes::from(merged),
+        None => return Ok(None),
+    };
+    let metadata = filestore::store(
+        merged_blobstore,
+        *repo.filestore_config(),
+        ctx,
+        &StoreRequest::new(merged.len() as u64),
+        stream::once(async { Ok(merged) }),
+    )
+    .await?;
+    let file_change = FileChange::tracked(
+        metadata.content_id,
+        change.file_type(),
+        metadata.total_size,
+        None,
+    );
+    Ok(Some((cs_id, file_change)))
+}
+
+/// Try to resolve conflicts between the pushed commits and the commits
+/// between `root` and `onto` by merging the changes to each conflicting
+/// file.  The merged contents are stored in `merged_blobstore`.
+///
+/// Returns `None` if any of the conflicts can't be merged.
+pub(crate) async fn merge_conflicts<B: Blobstore + Clone + 'static>(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    merged_blobstore: &B,
+    conflicts: &[PushrebaseConflict],
+    root: ChangesetId,
+    onto: ChangesetId,
+    client_bcs: &[BonsaiChangeset],
+) -> Result<Option<MergedFileChanges>> {
+    // Conflicts between a file and a directory can never be merged.
+    if conflicts.iter().any(|conflict| conflict.left != conflict.right) {
+        return Ok(None);
+    }
+
+    let merged = try_join_all(conflicts.iter().map(|conflict| async move {
+        let merged = merge_file(
+            ctx,
+            repo,
+            merged_blobstore,
+            &conflict.left,
+            root,
+            onto,
+            client_bcs,
+        )
+        .await?;
+        anyhow::Ok(merged.map(|(cs_id, change)| (cs_id, conflict.left.clone(), change)))
+    }))
+    .await?;
+
+    let mut merged_file_changes = MergedFileChanges::new();
+    for merged in merged {
+        match merged {
+            Some((cs_id, path, change)) => {
+                merged_file_changes
+                    .entry(cs_id)
+                    .or_default()
+                    .insert(path, change);
+            }
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(merged_file_changes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_merge_text_non_overlapping() {
+        let base = b"a\nb\nc\nd\ne\n";
+        let ours = b"A\nb\nc\nd\ne\n";
+        let theirs = b"a\nb\nc\nd\nE\nf\n";
+        assert_eq!(
+            merge_text(base, ours, theirs).as_deref(),
+            Some(&b"A\nb\nc\nd\nE\nf\n"[..])
+        );
+    }
+
+    #[test]
+    fn test_merge_text_overlapping() {
+        let base = b"a\nb\nc\n";
+        let ours = b"a\nB\nc\n";
+        let theirs = b"a\nb2\nc\n";
+        assert_eq!(merge_text(base, ours, theirs), None);
+    }
+
+    #[test]
+    fn test_merge_text_adjacent() {
+        // Changes to adjacent lines are conflicts, as in other merge tools.
+        let base = b"a\nb\nc\n";
+        let ours = b"A\nb\nc\n";
+        let theirs = b"a\nB\nc\n";
+        assert_eq!(merge_text(base, ours, theirs), None);
+    }
+
+    #[test]
+    fn test_merge_text_same_change() {
+        let base = b"a\nb\n";
+        let ours = b"a\nB\n";
+        assert_eq!(merge_text(base, ours, ours).as_deref(), Some(&ours[..]));
+    }
+
+    #[test]
+    fn test_merge_text_binary() {
+        let base = b"a\0\nb\nc\n";
+        let ours = b"A\0\nb\nc\n";
+        let theirs = b"a\0\nb\nC\n";
+        assert_eq!(merge_text(base, ours, theirs), None);
+    }
+}
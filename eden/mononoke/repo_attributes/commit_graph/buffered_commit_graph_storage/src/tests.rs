@@ -48,6 +48,21 @@ async fn test_buffered_sqlite_skip_tree(fb: FacebookInit) -> Result<()> {
     test_skip_tree(&ctx, storage).await
 }
 
+#[fbinit::test]
+async fn test_buffered_sqlite_octopus_merges(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let storage = Arc::new(BufferedCommitGraphStorage::new(
+        Arc::new(
+            SqlCommitGraphStorageBuilder::with_sqlite_in_memory()
+                .unwrap()
+                .build(RendezVousOptions::for_test(), RepositoryId::new(1)),
+        ),
+        5,
+    ));
+
+    test_octopus_merges(&ctx, storage).await
+}
+
 #[fbinit::test]
 async fn test_buffered_sqlite_p1_linear_tree(fb: FacebookInit) -> Result<()> {
     let ctx = CoreContext::test_mock(fb);
@@ -122,3 +137,48 @@ async fn test_buffered_sqlite_ancestors_frontier_with(fb: FacebookInit) -> Resul
 
     test_ancestors_frontier_with(&ctx, storage).await
 }
+
+#[fbinit::test]
+async fn test_buffered_sqlite_frontier_operations(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let storage = Arc::new(BufferedCommitGraphStorage::new(
+        Arc::new(
+            SqlCommitGraphStorageBuilder::with_sqlite_in_memory()
+                .unwrap()
+                .build(RendezVousOptions::for_test(), RepositoryId::new(1)),
+        ),
+        5,
+    ));
+
+    test_frontier_operations(&ctx, storage).await
+}
+
+#[fbinit::test]
+async fn test_buffered_sqlite_random_frontier_operations(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let storage = Arc::new(BufferedCommitGraphStorage::new(
+        Arc::new(
+            SqlCommitGraphStorageBuilder::with_sqlite_in_memory()
+                .unwrap()
+                .build(RendezVousOptions::for_test(), RepositoryId::new(1)),
+        ),
+        5,
+    ));
+
+    test_random_frontier_operations(&ctx, storage).await
+}
+
+#[fbinit::test]
+fn test_buffered_sqlite_against_model(fb: FacebookInit) {
+    let ctx = CoreContext::test_mock(fb);
+    test_storage_against_model(ctx, || {
+        Arc::new(BufferedCommitGraphStorage::new(
+            Arc::new(
+                SqlCommitGraphStorageBuilder::with_sqlite_in_memory()
+                    .unwrap()
+                    .build(RendezVousOptions::for_test(), RepositoryId::new(1)),
+            ),
+            5,
+        ))
+    });
+}
@@ -46,6 +46,20 @@ async fn test_cached_sqlite_skip_tree(fb: FacebookInit) -> Result<()> {
     Ok(())
 }
 
+#[fbinit::test]
+async fn test_cached_sqlite_octopus_merges(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let storage = Arc::new(CachingCommitGraphStorage::mocked(Arc::new(
+        SqlCommitGraphStorageBuilder::with_sqlite_in_memory()
+            .unwrap()
+            .build(RendezVousOptions::for_test(), RepositoryId::new(1)),
+    )));
+
+    test_octopus_merges(&ctx, storage.clone()).await?;
+    assert!(storage.cachelib.mock_store().unwrap().stats().hits > 0);
+    Ok(())
+}
+
 #[fbinit::test]
 async fn test_cached_sqlite_p1_linear_tree(fb: FacebookInit) -> Result<()> {
     let ctx = CoreContext::test_mock(fb);
@@ -115,3 +129,43 @@ async fn test_cached_sqlite_ancestors_frontier_with(fb: FacebookInit) -> Result<
     assert!(storage.cachelib.mock_store().unwrap().stats().hits > 0);
     Ok(())
 }
+
+#[fbinit::test]
+async fn test_cached_sqlite_frontier_operations(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let storage = Arc::new(CachingCommitGraphStorage::mocked(Arc::new(
+        SqlCommitGraphStorageBuilder::with_sqlite_in_memory()
+            .unwrap()
+            .build(RendezVousOptions::for_test(), RepositoryId::new(1)),
+    )));
+
+    test_frontier_operations(&ctx, storage.clone()).await?;
+    assert!(storage.cachelib.mock_store().unwrap().stats().hits > 0);
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_cached_sqlite_random_frontier_operations(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let storage = Arc::new(CachingCommitGraphStorage::mocked(Arc::new(
+        SqlCommitGraphStorageBuilder::with_sqlite_in_memory()
+            .unwrap()
+            .build(RendezVousOptions::for_test(), RepositoryId::new(1)),
+    )));
+
+    test_random_frontier_operations(&ctx, storage.clone()).await?;
+    assert!(storage.cachelib.mock_store().unwrap().stats().hits > 0);
+    Ok(())
+}
+
+#[fbinit::test]
+fn test_cached_sqlite_against_model(fb: FacebookInit) {
+    let ctx = CoreContext::test_mock(fb);
+    test_storage_against_model(ctx, || {
+        Arc::new(CachingCommitGraphStorage::mocked(Arc::new(
+            SqlCommitGraphStorageBuilder::with_sqlite_in_memory()
+                .unwrap()
+                .build(RendezVousOptions::for_test(), RepositoryId::new(1)),
+        )))
+    });
+}
@@ -23,6 +23,7 @@ use commit_graph_types::edges::ChangesetEdges;
 use commit_graph_types::edges::ChangesetFrontier;
 use commit_graph_types::edges::ChangesetNode;
 use commit_graph_types::edges::ChangesetNodeParents;
+use commit_graph_types::segments::ChangesetSegment;
 use commit_graph_types::storage::CommitGraphStorage;
 use commit_graph_types::storage::Prefetch;
 use commit_graph_types::storage::PrefetchEdge;
@@ -595,28 +596,61 @@ impl CommitGraph {
                     let edges = frontier_edges
                         .get(&cs_id)
                         .ok_or_else(|| anyhow!("Missing changeset in commit graph: {}", cs_id))?;
-                    match edges
-                        .skip_tree_parent
-                        .into_iter()
-                        .chain(edges.skip_tree_skew_ancestor)
-                        .filter(|ancestor| ancestor.generation >= target_generation)
-                        .min_by_key(|ancestor| ancestor.generation)
-                    {
-                        Some(ancestor) => {
-                            frontier
-                                .entry(ancestor.generation)
-                                .or_default()
-                                .insert(ancestor.cs_id);
-                        }
-                        None => {
-                            for parent in edges.parents.iter() {
-                                frontier
-                                    .entry(parent.generation)
-                                    .or_default()
-                                    .insert(parent.cs_id);
-                            }
-                        }
-                    }
+                    lower_frontier_step(&mut frontier, edges, target_generation);
+                }
+            }
+        }
+    }
+
+    /// Lower several frontiers so that each contains the highest ancestors
+    /// of that frontier that have a generation number less than or equal to
+    /// `generation`.
+    ///
+    /// The frontiers are lowered together one generation at a time, so the
+    /// edges needed for each generation are fetched in a single batch, and
+    /// only once for changesets shared between frontiers.
+    async fn lower_frontiers(
+        &self,
+        ctx: &CoreContext,
+        mut frontiers: Vec<ChangesetFrontier>,
+        target_generation: Generation,
+    ) -> Result<Vec<ChangesetFrontier>> {
+        loop {
+            let highest_generation = frontiers
+                .iter()
+                .filter_map(|frontier| frontier.last_key_value())
+                .map(|(generation, _)| *generation)
+                .filter(|generation| *generation > target_generation)
+                .max();
+            let highest_generation = match highest_generation {
+                Some(generation) => generation,
+                None => return Ok(frontiers),
+            };
+
+            let popped = frontiers
+                .iter_mut()
+                .map(|frontier| frontier.remove(&highest_generation).unwrap_or_default())
+                .collect::<Vec<_>>();
+            let cs_ids = popped
+                .iter()
+                .flatten()
+                .copied()
+                .unique()
+                .collect::<Vec<_>>();
+            let frontier_edges = self
+                .storage
+                .fetch_many_edges_required(
+                    ctx,
+                    &cs_ids,
+                    Prefetch::Hint(PrefetchEdge::SkipTreeSkewAncestor, target_generation),
+                )
+                .await?;
+            for (frontier, cs_ids) in frontiers.iter_mut().zip(popped) {
+                for cs_id in cs_ids {
+                    let edges = frontier_edges
+                        .get(&cs_id)
+                        .ok_or_else(|| anyhow!("Missing changeset in commit graph: {}", cs_id))?;
+                    lower_frontier_step(frontier, edges, target_generation);
                 }
             }
         }
@@ -686,6 +720,131 @@ impl CommitGraph {
         Ok(ancestors_frontier.into_iter().collect())
     }
 
+    /// Returns the highest ancestors of heads that have a generation number
+    /// less than or equal to `generation`.
+    ///
+    /// Every ancestor of heads with a generation number less than or equal to
+    /// `generation` is an ancestor of one of the returned changesets, and none
+    /// of the returned changesets is an ancestor of another.
+    pub async fn frontier_at_generation(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        generation: Generation,
+    ) -> Result<Vec<ChangesetId>> {
+        let frontier = self.frontier(ctx, heads).await?;
+        let mut frontier = self.lower_frontier(ctx, frontier, generation).await?;
+
+        // The lowered frontier may still contain changesets that are
+        // ancestors of other changesets in it. Walk it from the highest
+        // generation down, keeping only the changesets that are not
+        // ancestors of any changeset kept so far.
+        let mut result = vec![];
+        let mut kept = ChangesetFrontier::new();
+        while let Some((generation, cs_ids)) = frontier.pop_last() {
+            kept = self.lower_frontier(ctx, kept, generation).await?;
+            let cs_ids = cs_ids
+                .into_iter()
+                .filter(|cs_id| !kept.highest_generation_contains(*cs_id, generation))
+                .collect::<Vec<_>>();
+            result.extend(&cs_ids);
+            kept.entry(generation).or_default().extend(cs_ids);
+        }
+
+        Ok(result)
+    }
+
+    /// Returns the lowest common frontier of two sets of heads: the highest
+    /// changesets that are ancestors of both some changeset in `heads1` and
+    /// some changeset in `heads2`.
+    ///
+    /// None of the returned changesets is an ancestor of another, and every
+    /// common ancestor of the two sets of heads is an ancestor of one of the
+    /// returned changesets.
+    pub async fn lowest_common_frontier(
+        &self,
+        ctx: &CoreContext,
+        heads1: Vec<ChangesetId>,
+        heads2: Vec<ChangesetId>,
+    ) -> Result<Vec<ChangesetId>> {
+        let mut lowest_common_frontier = vec![];
+
+        let (mut frontier1, mut frontier2) =
+            futures::try_join!(self.frontier(ctx, heads1), self.frontier(ctx, heads2))?;
+        // Frontier of the common ancestors found so far, used to exclude
+        // their ancestors from the result.
+        let mut found = ChangesetFrontier::new();
+
+        while let Some((generation, cs_ids)) = frontier1.pop_last() {
+            (frontier2, found) = futures::try_join!(
+                self.lower_frontier(ctx, frontier2, generation),
+                self.lower_frontier(ctx, found, generation),
+            )?;
+            if frontier2.is_empty() {
+                break;
+            }
+
+            let mut cs_ids_to_expand = vec![];
+            for cs_id in cs_ids {
+                if found.highest_generation_contains(cs_id, generation) {
+                    continue;
+                }
+                if frontier2.highest_generation_contains(cs_id, generation) {
+                    lowest_common_frontier.push(cs_id);
+                    found.entry(generation).or_default().insert(cs_id);
+                } else {
+                    cs_ids_to_expand.push(cs_id);
+                }
+            }
+
+            let all_edges = self
+                .storage
+                .fetch_many_edges_required(ctx, &cs_ids_to_expand, Prefetch::None)
+                .await?;
+
+            for (_, edges) in all_edges.into_iter() {
+                for parent in edges.parents.into_iter() {
+                    frontier1
+                        .entry(parent.generation)
+                        .or_default()
+                        .insert(parent.cs_id);
+                }
+            }
+        }
+
+        Ok(lowest_common_frontier)
+    }
+
+    /// Returns the common ancestors of two changesets with the highest
+    /// generation number.  There is more than one if the histories of the
+    /// changesets were merged more than once.
+    ///
+    /// These are the same changesets as the skiplist index's `lca` returns.
+    pub async fn common_base(
+        &self,
+        ctx: &CoreContext,
+        cs_id1: ChangesetId,
+        cs_id2: ChangesetId,
+    ) -> Result<Vec<ChangesetId>> {
+        let frontier = self
+            .lowest_common_frontier(ctx, vec![cs_id1], vec![cs_id2])
+            .await?;
+        let all_edges = self
+            .storage
+            .fetch_many_edges_required(ctx, &frontier, Prefetch::None)
+            .await?;
+        let max_generation = all_edges.values().map(|edges| edges.node.generation).max();
+        Ok(frontier
+            .into_iter()
+            .filter(|cs_id| {
+                all_edges
+                    .get(cs_id)
+                    .map(|edges| edges.node.generation)
+                    == max_generation
+            })
+            .collect())
+    }
+
     /// Returns true if the ancestor changeset is an ancestor of the descendant
     /// changeset.
     ///
@@ -705,6 +864,72 @@ impl CommitGraph {
         Ok(frontier.highest_generation_contains(ancestor, target_gen))
     }
 
+    /// Answers every ancestry query between `ancestors` and `descendants` at
+    /// once, returning a map from each descendant to the changesets in
+    /// `ancestors` that are its ancestors.
+    ///
+    /// Ancestry is inclusive: a commit is its own ancestor.  This gives the
+    /// same answers as calling `is_ancestor` for every pair, but the
+    /// frontiers of all descendants are lowered together, so the number of
+    /// storage round trips doesn't grow with the number of queries.
+    pub async fn is_ancestor_many(
+        &self,
+        ctx: &CoreContext,
+        ancestors: Vec<ChangesetId>,
+        descendants: Vec<ChangesetId>,
+    ) -> Result<HashMap<ChangesetId, HashSet<ChangesetId>>> {
+        let descendants = descendants.into_iter().unique().collect::<Vec<_>>();
+        let cs_ids = ancestors
+            .iter()
+            .chain(descendants.iter())
+            .copied()
+            .unique()
+            .collect::<Vec<_>>();
+        let all_edges = self
+            .storage
+            .fetch_many_edges_required(ctx, &cs_ids, Prefetch::None)
+            .await?;
+        let generation = |cs_id: ChangesetId| -> Result<Generation> {
+            all_edges
+                .get(&cs_id)
+                .map(|edges| edges.node.generation)
+                .ok_or_else(|| anyhow!("Missing changeset in commit graph: {}", cs_id))
+        };
+
+        let mut ancestors_by_generation = ChangesetFrontier::new();
+        for cs_id in ancestors {
+            ancestors_by_generation
+                .entry(generation(cs_id)?)
+                .or_default()
+                .insert(cs_id);
+        }
+        let mut frontiers = descendants
+            .iter()
+            .map(|cs_id| {
+                let mut frontier = ChangesetFrontier::new();
+                frontier.insert(generation(*cs_id)?, hashset! { *cs_id });
+                Ok(frontier)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut result = descendants
+            .iter()
+            .map(|cs_id| (*cs_id, HashSet::new()))
+            .collect::<HashMap<_, _>>();
+        while let Some((target_gen, cs_ids)) = ancestors_by_generation.pop_last() {
+            frontiers = self.lower_frontiers(ctx, frontiers, target_gen).await?;
+            for (descendant, frontier) in descendants.iter().zip(frontiers.iter()) {
+                for cs_id in cs_ids.iter() {
+                    if frontier.highest_generation_contains(*cs_id, target_gen) {
+                        result.entry(*descendant).or_default().insert(*cs_id);
+                    }
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
     /// Returns all ancestors of any changeset in heads, excluding
     /// any ancestor of any changeset in common and any changeset
     /// that satisfies a given property.
@@ -758,6 +983,9 @@ impl CommitGraph {
 
     /// Returns all ancestors of any changeset in heads, excluding
     /// any ancestor of any changeset in common.
+    ///
+    /// The ancestors are returned in descending order of generation,
+    /// so every changeset comes before its own ancestors.
     pub async fn ancestors_difference(
         &self,
         ctx: &CoreContext,
@@ -767,6 +995,275 @@ impl CommitGraph {
         self.ancestors_difference_with(ctx, heads, common, |_| false)
             .await
     }
+
+    /// Returns all ancestors of any changeset in heads, excluding any
+    /// ancestor of any changeset in common, as linear segments.
+    ///
+    /// Segments are broken at merges and at changesets with more than one
+    /// child among the returned ancestors, so that each segment can be
+    /// processed as a batch once the segments containing the parents of its
+    /// base have been.  Segments are ordered by the generation of their base,
+    /// so those segments always come first.
+    pub async fn ancestors_difference_segments(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+    ) -> Result<Vec<ChangesetSegment>> {
+        // Heads of the linear runs of changesets found so far, grouped by
+        // the base of the run, and the edges of each base.
+        let mut runs: HashMap<ChangesetId, Vec<ChangesetNode>> = HashMap::new();
+        let mut bases: HashMap<ChangesetId, ChangesetEdges> = HashMap::new();
+        // Parents of the bases: these have a child outside of the run they
+        // are in, so always start a new segment.
+        let mut base_parents: HashSet<ChangesetId> = HashSet::new();
+
+        let (mut heads, mut common) =
+            futures::try_join!(self.frontier(ctx, heads), self.frontier(ctx, common))?;
+
+        while let Some((generation, cs_ids)) = heads.pop_last() {
+            common = self.lower_frontier(ctx, common, generation).await?;
+
+            let cs_ids_not_excluded = cs_ids
+                .into_iter()
+                .filter(|cs_id| !common.highest_generation_contains(*cs_id, generation))
+                .collect::<Vec<_>>();
+
+            let all_edges = self
+                .storage
+                .fetch_many_edges_required(ctx, &cs_ids_not_excluded, Prefetch::None)
+                .await?;
+            let run_bases = futures::future::try_join_all(
+                all_edges
+                    .values()
+                    .map(|edges| self.linear_run_base(ctx, &common, edges)),
+            )
+            .await?;
+
+            let mut new_bases = vec![];
+            for (edges, base) in all_edges.values().zip(run_bases) {
+                runs.entry(base.cs_id).or_default().push(edges.node);
+                if !bases.contains_key(&base.cs_id) && !new_bases.contains(&base.cs_id) {
+                    new_bases.push(base.cs_id);
+                }
+            }
+
+            let base_edges = self
+                .storage
+                .fetch_many_edges_required(ctx, &new_bases, Prefetch::None)
+                .await?;
+            for (cs_id, edges) in base_edges.into_iter() {
+                for parent in edges.parents.iter() {
+                    base_parents.insert(parent.cs_id);
+                    heads
+                        .entry(parent.generation)
+                        .or_default()
+                        .insert(parent.cs_id);
+                }
+                bases.insert(cs_id, edges);
+            }
+        }
+
+        let mut segments = vec![];
+        for (base, run_heads) in runs {
+            let base = bases
+                .get(&base)
+                .ok_or_else(|| anyhow!("Missing changeset in commit graph: {}", base))?;
+            segments.extend(
+                self.split_linear_runs(ctx, base, run_heads, &base_parents)
+                    .await?,
+            );
+        }
+        segments.sort_by_key(|(generation, segment)| (*generation, segment.base));
+
+        Ok(segments.into_iter().map(|(_, segment)| segment).collect())
+    }
+
+    /// Returns true if the changeset is an ancestor of any changeset in the
+    /// frontier.
+    async fn frontier_contains_ancestor(
+        &self,
+        ctx: &CoreContext,
+        frontier: &ChangesetFrontier,
+        node: ChangesetNode,
+    ) -> Result<bool> {
+        let frontier = self
+            .lower_frontier(ctx, frontier.clone(), node.generation)
+            .await?;
+        Ok(frontier.highest_generation_contains(node.cs_id, node.generation))
+    }
+
+    /// Returns true if the ancestor changeset is an ancestor of the
+    /// descendant changeset in the p1 linear tree.
+    async fn is_p1_linear_ancestor(
+        &self,
+        ctx: &CoreContext,
+        ancestor: ChangesetNode,
+        descendant: ChangesetNode,
+    ) -> Result<bool> {
+        let level_ancestor = self
+            .p1_linear_level_ancestor(ctx, descendant.cs_id, ancestor.p1_linear_depth)
+            .await?;
+        Ok(level_ancestor.map(|node| node.cs_id) == Some(ancestor.cs_id))
+    }
+
+    /// Returns the base of the linear run of changesets that starts at a
+    /// changeset which is not an ancestor of `common`.
+    ///
+    /// Every changeset between a single-parent changeset and its merge
+    /// ancestor has a single parent, so the run goes down the p1 linear
+    /// tree until the merge ancestor, or until the lowest changeset that is
+    /// not an ancestor of `common`, which is found by a binary search over
+    /// p1 linear depths.
+    async fn linear_run_base(
+        &self,
+        ctx: &CoreContext,
+        common: &ChangesetFrontier,
+        edges: &ChangesetEdges,
+    ) -> Result<ChangesetNode> {
+        let bottom = match edges.merge_ancestor {
+            Some(merge_ancestor) => merge_ancestor,
+            None => return Ok(edges.node),
+        };
+        if !self.frontier_contains_ancestor(ctx, common, bottom).await? {
+            return Ok(bottom);
+        }
+
+        let mut base = edges.node;
+        let mut excluded_depth = bottom.p1_linear_depth;
+        while base.p1_linear_depth - excluded_depth > 1 {
+            let depth = excluded_depth + (base.p1_linear_depth - excluded_depth) / 2;
+            let node = self
+                .p1_linear_level_ancestor(ctx, base.cs_id, depth)
+                .await?
+                .ok_or_else(|| anyhow!("Missing p1 linear ancestor of {}", base.cs_id))?;
+            if self.frontier_contains_ancestor(ctx, common, node).await? {
+                excluded_depth = depth;
+            } else {
+                base = node;
+            }
+        }
+
+        Ok(base)
+    }
+
+    /// Splits the linear runs of changesets that share a base into
+    /// segments, returning each segment with the generation of its base.
+    ///
+    /// Runs that share a base coincide below the changeset where they fork,
+    /// so segments start at the heads of runs that aren't inside another
+    /// run, at the changesets where these fork, and at the heads of runs
+    /// that are the parent of the base of another run.
+    async fn split_linear_runs(
+        &self,
+        ctx: &CoreContext,
+        base: &ChangesetEdges,
+        mut run_heads: Vec<ChangesetNode>,
+        base_parents: &HashSet<ChangesetId>,
+    ) -> Result<Vec<(Generation, ChangesetSegment)>> {
+        run_heads.sort_by_key(|node| std::cmp::Reverse(node.generation));
+        let mut outer_heads: Vec<ChangesetNode> = vec![];
+        let mut segment_heads: Vec<ChangesetNode> = vec![];
+        for head in run_heads {
+            let mut inside = false;
+            for outer_head in outer_heads.iter() {
+                if self.is_p1_linear_ancestor(ctx, head, *outer_head).await? {
+                    inside = true;
+                    break;
+                }
+            }
+            if !inside {
+                for outer_head in outer_heads.iter() {
+                    let fork = self
+                        .p1_linear_lowest_common_ancestor(ctx, outer_head.cs_id, head.cs_id)
+                        .await?
+                        .ok_or_else(|| anyhow!("Missing common ancestor of {}", head.cs_id))?;
+                    segment_heads.push(fork);
+                }
+                outer_heads.push(head);
+            }
+            if !inside || base_parents.contains(&head.cs_id) {
+                segment_heads.push(head);
+            }
+        }
+        segment_heads.sort_by_key(|node| (std::cmp::Reverse(node.generation), node.cs_id));
+        segment_heads.dedup_by_key(|node| node.cs_id);
+
+        let mut segments = vec![];
+        for (index, head) in segment_heads.iter().enumerate() {
+            // The next segment down the run is the one with the highest
+            // generation whose head is an ancestor of this one.
+            let mut next = None;
+            for candidate in segment_heads[index + 1..].iter() {
+                if candidate.generation < head.generation
+                    && self.is_p1_linear_ancestor(ctx, *candidate, *head).await?
+                {
+                    next = Some(*candidate);
+                    break;
+                }
+            }
+            segments.push(match next {
+                Some(next) => {
+                    let segment_base = self
+                        .p1_linear_level_ancestor(ctx, head.cs_id, next.p1_linear_depth + 1)
+                        .await?
+                        .ok_or_else(|| anyhow!("Missing p1 linear ancestor of {}", head.cs_id))?;
+                    (
+                        segment_base.generation,
+                        ChangesetSegment {
+                            head: head.cs_id,
+                            base: segment_base.cs_id,
+                            length: head.generation.value() - next.generation.value(),
+                            parents: std::iter::once(next.cs_id).collect(),
+                        },
+                    )
+                }
+                None => (
+                    base.node.generation,
+                    ChangesetSegment {
+                        head: head.cs_id,
+                        base: base.node.cs_id,
+                        length: head.generation.value() - base.node.generation.value() + 1,
+                        parents: base.parents.iter().map(|parent| parent.cs_id).collect(),
+                    },
+                ),
+            });
+        }
+
+        Ok(segments)
+    }
+}
+
+/// Replace a changeset popped from a frontier that is being lowered to
+/// `target_generation` with the furthest of its skip tree ancestors that
+/// does not go below the target, or its parents if there is none.
+fn lower_frontier_step(
+    frontier: &mut ChangesetFrontier,
+    edges: &ChangesetEdges,
+    target_generation: Generation,
+) {
+    match edges
+        .skip_tree_parent
+        .into_iter()
+        .chain(edges.skip_tree_skew_ancestor)
+        .filter(|ancestor| ancestor.generation >= target_generation)
+        .min_by_key(|ancestor| ancestor.generation)
+    {
+        Some(ancestor) => {
+            frontier
+                .entry(ancestor.generation)
+                .or_default()
+                .insert(ancestor.cs_id);
+        }
+        None => {
+            for parent in edges.parents.iter() {
+                frontier
+                    .entry(parent.generation)
+                    .or_default()
+                    .insert(parent.cs_id);
+            }
+        }
+    }
 }
 
 #[async_trait]
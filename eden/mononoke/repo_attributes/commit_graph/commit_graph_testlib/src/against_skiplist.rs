@@ -0,0 +1,162 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Randomized testing of the commit graph against the skiplist index.
+//!
+//! Until the commit graph replaces the skiplist index, both of them answer
+//! ancestry and merge base queries, and they must agree. A
+//! `SkiplistTestCase` contains a random dag, which is either uniformly
+//! random or shaped like the history of a production repo, the changesets
+//! the skiplist index is built from, and a random mix of queries. The
+//! answers given by the commit graph are compared to the answers of the
+//! skiplist index.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use anyhow::ensure;
+use anyhow::Result;
+use changeset_fetcher::ArcChangesetFetcher;
+use commit_graph_types::storage::CommitGraphStorage;
+use context::CoreContext;
+use quickcheck::Arbitrary;
+use quickcheck::Gen;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand::SeedableRng;
+use reachabilityindex::ReachabilityIndex;
+use skiplist::SkiplistIndex;
+
+use crate::utils::*;
+
+/// A query to run against both the commit graph and the skiplist index.
+#[derive(Clone, Debug)]
+pub enum SkiplistQuery {
+    IsAncestor(String, String),
+    CommonBase(String, String),
+}
+
+#[derive(Clone, Debug)]
+pub struct SkiplistTestCase {
+    /// Map of changeset names to the names of their parents.
+    pub parents: BTreeMap<String, BTreeSet<String>>,
+    pub skip_edges_per_node: u32,
+    /// Changesets the skiplist index is built from. Ancestors further than
+    /// `max_index_depth` from all of them are left unindexed, so that
+    /// queries are also answered by partial indexes.
+    pub indexed_heads: Vec<String>,
+    pub max_index_depth: u64,
+    pub queries: Vec<SkiplistQuery>,
+}
+
+impl Arbitrary for SkiplistTestCase {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let mut rng = StdRng::seed_from_u64(u64::arbitrary(g));
+        let size = rng.gen_range(1..=g.size().max(1));
+
+        let parents = if rng.gen_bool(0.5) {
+            random_parents_map(&mut rng, "N", size)
+        } else {
+            production_shaped_parents_map(&mut rng, "N", size)
+        };
+        let names = parents.keys().cloned().collect::<Vec<_>>();
+
+        let num_indexed_heads = rng.gen_range(0..=3);
+        let indexed_heads = names
+            .choose_multiple(&mut rng, num_indexed_heads)
+            .cloned()
+            .collect();
+
+        let num_queries = rng.gen_range(0..32);
+        let queries = (0..num_queries)
+            .map(|_| {
+                let cs1 = names.choose(&mut rng).unwrap().clone();
+                let cs2 = names.choose(&mut rng).unwrap().clone();
+                if rng.gen_bool(0.5) {
+                    SkiplistQuery::IsAncestor(cs1, cs2)
+                } else {
+                    SkiplistQuery::CommonBase(cs1, cs2)
+                }
+            })
+            .collect();
+
+        Self {
+            parents,
+            skip_edges_per_node: rng.gen_range(1..=8),
+            indexed_heads,
+            max_index_depth: rng.gen_range(1..=size as u64),
+            queries,
+        }
+    }
+}
+
+/// Build a commit graph and a skiplist index of the dag of a test case,
+/// then check that they give the same answer to every query.
+pub async fn check_against_skiplist(
+    ctx: &CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+    test_case: &SkiplistTestCase,
+) -> Result<()> {
+    let graph = Arc::new(from_parents_map(ctx, &test_case.parents, storage).await?);
+    let changeset_fetcher: ArcChangesetFetcher = graph.clone();
+
+    let skiplist = SkiplistIndex::with_skip_edge_count(test_case.skip_edges_per_node);
+    for head in &test_case.indexed_heads {
+        skiplist
+            .add_node(
+                ctx,
+                &changeset_fetcher,
+                name_cs_id(head),
+                test_case.max_index_depth,
+            )
+            .await?;
+    }
+
+    for query in &test_case.queries {
+        match query {
+            SkiplistQuery::IsAncestor(ancestor, descendant) => {
+                let (ancestor, descendant) = (name_cs_id(ancestor), name_cs_id(descendant));
+                let skiplist_answer = skiplist
+                    .query_reachability(ctx, &changeset_fetcher, descendant, ancestor)
+                    .await?;
+                let graph_answer = graph.is_ancestor(ctx, ancestor, descendant).await?;
+                ensure!(
+                    skiplist_answer == graph_answer,
+                    "{:?} differs: skiplist says {}, commit graph says {}",
+                    query,
+                    skiplist_answer,
+                    graph_answer
+                );
+            }
+            SkiplistQuery::CommonBase(cs1, cs2) => {
+                let (cs1, cs2) = (name_cs_id(cs1), name_cs_id(cs2));
+                let skiplist_answer = skiplist
+                    .lca(ctx.clone(), changeset_fetcher.clone(), cs1, cs2)
+                    .await?
+                    .into_iter()
+                    .collect::<HashSet<_>>();
+                let graph_answer = graph
+                    .common_base(ctx, cs1, cs2)
+                    .await?
+                    .into_iter()
+                    .collect::<HashSet<_>>();
+                ensure!(
+                    skiplist_answer == graph_answer,
+                    "{:?} differs: skiplist says {:?}, commit graph says {:?}",
+                    query,
+                    skiplist_answer,
+                    graph_answer
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
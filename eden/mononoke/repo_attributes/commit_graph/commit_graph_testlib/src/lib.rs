@@ -5,6 +5,8 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashSet;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -17,11 +19,25 @@ use in_memory_commit_graph_storage::InMemoryCommitGraphStorage;
 use mononoke_types::ChangesetIdPrefix;
 use mononoke_types::ChangesetIdsResolvedFromPrefix;
 use mononoke_types::RepositoryId;
+use quickcheck::Arbitrary;
+use quickcheck::Gen;
+use quickcheck::QuickCheck;
+use quickcheck::TestResult;
+use quickcheck::Testable;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand::SeedableRng;
 use smallvec::smallvec;
+use tokio::runtime::Runtime;
 use vec1::vec1;
 
+use crate::against_skiplist::*;
+use crate::model::*;
 use crate::utils::*;
 
+mod against_skiplist;
+mod model;
 mod utils;
 
 pub async fn test_storage_store_and_fetch(
@@ -116,6 +132,25 @@ pub async fn test_storage_store_and_fetch(
             .is_ancestor(ctx, name_cs_id("B"), name_cs_id("E"))
             .await?
     );
+    let is_ancestor_many = graph
+        .is_ancestor_many(
+            ctx,
+            vec![name_cs_id("A"), name_cs_id("C"), name_cs_id("F")],
+            vec![name_cs_id("D"), name_cs_id("E"), name_cs_id("I")],
+        )
+        .await?;
+    assert_eq!(
+        is_ancestor_many[&name_cs_id("D")],
+        HashSet::from([name_cs_id("A"), name_cs_id("C")])
+    );
+    assert_eq!(
+        is_ancestor_many[&name_cs_id("E")],
+        HashSet::from([name_cs_id("A")])
+    );
+    assert_eq!(
+        is_ancestor_many[&name_cs_id("I")],
+        HashSet::from([name_cs_id("A"), name_cs_id("C"), name_cs_id("F")])
+    );
 
     // Check some underlying storage details.
     assert_eq!(
@@ -146,6 +181,101 @@ pub async fn test_storage_store_and_fetch(
     Ok(())
 }
 
+pub async fn test_octopus_merges(
+    ctx: &CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    // G is an octopus merge of C, E and F.
+    //
+    //   A-B-C-G-H
+    //   |\   /|
+    //   | D-E |
+    //    \    |
+    //     F---+
+    let dag: BTreeMap<String, BTreeSet<String>> = [
+        ("A", vec![]),
+        ("B", vec!["A"]),
+        ("C", vec!["B"]),
+        ("D", vec!["A"]),
+        ("E", vec!["D"]),
+        ("F", vec!["A"]),
+        ("G", vec!["C", "E", "F"]),
+        ("H", vec!["G"]),
+    ]
+    .into_iter()
+    .map(|(name, parents)| {
+        (
+            name.to_string(),
+            parents.into_iter().map(String::from).collect(),
+        )
+    })
+    .collect();
+    let graph = from_parents_map(ctx, &dag, storage.clone()).await?;
+
+    assert_eq!(
+        graph
+            .changeset_parents(ctx, name_cs_id("G"))
+            .await?
+            .unwrap()
+            .as_slice(),
+        &[name_cs_id("C"), name_cs_id("E"), name_cs_id("F")]
+    );
+    assert_eq!(
+        graph
+            .changeset_generation(ctx, name_cs_id("G"))
+            .await?
+            .unwrap()
+            .value(),
+        4
+    );
+
+    let g_edges = storage.fetch_edges_required(ctx, name_cs_id("G")).await?;
+    assert_eq!(g_edges.merge_ancestor, None);
+    assert_eq!(g_edges.parents.len(), 3);
+    assert_eq!(
+        storage
+            .fetch_edges_required(ctx, name_cs_id("H"))
+            .await?
+            .merge_ancestor
+            .map(|node| node.cs_id),
+        Some(name_cs_id("G"))
+    );
+
+    // The skip tree parent is the common ancestor of all three parents.
+    assert_skip_tree_parent(&storage, ctx, "G", "A").await?;
+    assert_skip_tree_lowest_common_ancestor(&graph, ctx, "H", "F", Some("A")).await?;
+
+    assert!(
+        graph
+            .is_ancestor(ctx, name_cs_id("F"), name_cs_id("H"))
+            .await?
+    );
+    assert!(
+        graph
+            .is_ancestor(ctx, name_cs_id("E"), name_cs_id("G"))
+            .await?
+    );
+    assert!(
+        !graph
+            .is_ancestor(ctx, name_cs_id("G"), name_cs_id("F"))
+            .await?
+    );
+
+    assert_ancestors_difference(
+        &graph,
+        ctx,
+        vec!["H"],
+        vec!["E"],
+        vec!["H", "G", "C", "B", "F"],
+    )
+    .await?;
+    assert_ancestors_difference(&graph, ctx, vec!["G"], vec!["C", "E"], vec!["G", "F"]).await?;
+    assert_lowest_common_frontier(&graph, ctx, vec!["E"], vec!["F"], vec!["A"]).await?;
+    assert_lowest_common_frontier(&graph, ctx, vec!["H"], vec!["F"], vec!["F"]).await?;
+
+    Ok(())
+}
+
 pub async fn test_skip_tree(ctx: &CoreContext, storage: Arc<dyn CommitGraphStorage>) -> Result<()> {
     let graph = from_dag(
         ctx,
@@ -356,6 +486,94 @@ pub async fn test_ancestors_difference(
     Ok(())
 }
 
+pub async fn test_ancestors_difference_segments(
+    ctx: &CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_ancestors_difference_segments(
+        &graph,
+        ctx,
+        vec!["K"],
+        vec![],
+        vec![
+            ("B", "A", 2, vec![]),
+            ("D", "C", 2, vec!["B"]),
+            ("F", "E", 2, vec!["B"]),
+            ("H", "G", 2, vec!["D", "F"]),
+            ("I", "I", 1, vec!["H"]),
+            ("K", "J", 2, vec!["H", "I"]),
+        ],
+    )
+    .await?;
+
+    assert_ancestors_difference_segments(
+        &graph,
+        ctx,
+        vec!["K", "U"],
+        vec!["F", "Q"],
+        vec![
+            ("D", "C", 2, vec!["B"]),
+            ("H", "G", 2, vec!["D", "F"]),
+            ("I", "I", 1, vec!["H"]),
+            ("U", "R", 4, vec!["Q"]),
+            ("K", "J", 2, vec!["H", "I"]),
+        ],
+    )
+    .await?;
+
+    assert_ancestors_difference_segments(
+        &graph,
+        ctx,
+        vec!["H"],
+        vec!["B"],
+        vec![
+            ("D", "C", 2, vec!["B"]),
+            ("F", "E", 2, vec!["B"]),
+            ("H", "G", 2, vec!["D", "F"]),
+        ],
+    )
+    .await?;
+
+    assert_ancestors_difference_segments(
+        &graph,
+        ctx,
+        vec!["D", "F", "C"],
+        vec![],
+        vec![
+            ("B", "A", 2, vec![]),
+            ("D", "C", 2, vec!["B"]),
+            ("F", "E", 2, vec!["B"]),
+        ],
+    )
+    .await?;
+
+    assert_ancestors_difference_segments(
+        &graph,
+        ctx,
+        vec!["U", "S", "O"],
+        vec!["M"],
+        vec![("U", "N", 8, vec!["M"])],
+    )
+    .await?;
+
+    assert_ancestors_difference_segments(&graph, ctx, vec!["U"], vec!["U"], vec![]).await?;
+
+    Ok(())
+}
+
 pub async fn test_find_by_prefix(
     ctx: &CoreContext,
     storage: Arc<dyn CommitGraphStorage>,
@@ -574,3 +792,166 @@ pub async fn test_ancestors_frontier_with(
 
     Ok(())
 }
+
+pub async fn test_frontier_operations(
+    ctx: &CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_frontier_at_generation(&graph, ctx, vec!["K"], 4, vec!["D", "F"]).await?;
+    assert_frontier_at_generation(&graph, ctx, vec!["K"], 3, vec!["C", "E"]).await?;
+    assert_frontier_at_generation(&graph, ctx, vec!["K"], 7, vec!["I"]).await?;
+    assert_frontier_at_generation(&graph, ctx, vec!["K", "U"], 5, vec!["G", "O"]).await?;
+    assert_frontier_at_generation(&graph, ctx, vec!["D", "F"], 2, vec!["B"]).await?;
+    assert_frontier_at_generation(&graph, ctx, vec!["J", "H"], 6, vec!["H"]).await?;
+    assert_frontier_at_generation(&graph, ctx, vec!["G"], 10, vec!["G"]).await?;
+    assert_frontier_at_generation(&graph, ctx, vec!["G"], 0, vec![]).await?;
+
+    assert_lowest_common_frontier(&graph, ctx, vec!["D"], vec!["F"], vec!["B"]).await?;
+    assert_lowest_common_frontier(&graph, ctx, vec!["K"], vec!["U"], vec![]).await?;
+    assert_lowest_common_frontier(&graph, ctx, vec!["D", "F"], vec!["G"], vec!["D", "F"]).await?;
+    assert_lowest_common_frontier(&graph, ctx, vec!["C"], vec!["E", "D"], vec!["C"]).await?;
+    assert_lowest_common_frontier(&graph, ctx, vec!["J"], vec!["I"], vec!["I"]).await?;
+    assert_lowest_common_frontier(&graph, ctx, vec!["D"], vec!["A"], vec!["A"]).await?;
+    assert_lowest_common_frontier(&graph, ctx, vec!["K", "U"], vec!["F", "Q"], vec!["F", "Q"])
+        .await?;
+
+    Ok(())
+}
+
+/// Compare the frontier operations against naive reference implementations
+/// on randomly generated dags.
+pub async fn test_random_frontier_operations(
+    ctx: &CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    for seed in 0..10 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let dag = random_parents_map(&mut rng, &format!("R{}_", seed), 60);
+        let generations = naive_generations(&dag);
+        let max_generation = generations.values().copied().max().unwrap_or(0);
+        let names = dag.keys().map(String::as_str).collect::<Vec<_>>();
+        let graph = from_parents_map(ctx, &dag, storage.clone()).await?;
+
+        for _ in 0..20 {
+            let heads1_len = rng.gen_range(1..=3);
+            let heads1 = names
+                .choose_multiple(&mut rng, heads1_len)
+                .copied()
+                .collect::<Vec<_>>();
+            let heads2_len = rng.gen_range(1..=3);
+            let heads2 = names
+                .choose_multiple(&mut rng, heads2_len)
+                .copied()
+                .collect::<Vec<_>>();
+            let generation = rng.gen_range(0..=max_generation);
+
+            assert_frontier_at_generation(
+                &graph,
+                ctx,
+                heads1.clone(),
+                generation,
+                naive_frontier_at_generation(&dag, &generations, &heads1, generation)
+                    .iter()
+                    .map(String::as_str)
+                    .collect(),
+            )
+            .await?;
+
+            assert_lowest_common_frontier(
+                &graph,
+                ctx,
+                heads1.clone(),
+                heads2.clone(),
+                naive_lowest_common_frontier(&dag, &heads1, &heads2)
+                    .iter()
+                    .map(String::as_str)
+                    .collect(),
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Check a storage implementation against a naive model of the commit graph,
+/// using randomly generated dags, insertion orders and queries.
+///
+/// `new_storage` is called to create an empty storage for each test case.
+/// This is not an async function as it drives its own runtime.
+pub fn test_storage_against_model<S: CommitGraphStorage + 'static>(
+    ctx: CoreContext,
+    new_storage: impl Fn() -> Arc<S> + 'static,
+) {
+    struct AgainstModel<F>(Runtime, CoreContext, F);
+
+    impl<F> Testable for AgainstModel<F>
+    where
+        F: Fn() -> Arc<dyn CommitGraphStorage> + 'static,
+    {
+        fn result(&self, gen: &mut Gen) -> TestResult {
+            let test_case = RandomDagTestCase::arbitrary(gen);
+            let graph = CommitGraph::new((self.2)());
+            match self
+                .0
+                .block_on(check_against_model(&self.1, &graph, &test_case))
+            {
+                Ok(()) => TestResult::passed(),
+                Err(err) => TestResult::error(format!("{:#} in {:?}", err, test_case)),
+            }
+        }
+    }
+
+    QuickCheck::new().tests(50).quickcheck(AgainstModel(
+        Runtime::new().unwrap(),
+        ctx,
+        move || -> Arc<dyn CommitGraphStorage> { new_storage() },
+    ));
+}
+
+/// Check the commit graph against the skiplist index, which it replaces,
+/// using randomly generated dags, partial indexes and queries.
+///
+/// `new_storage` is called to create an empty storage for each test case.
+/// This is not an async function as it drives its own runtime.
+pub fn test_storage_against_skiplist<S: CommitGraphStorage + 'static>(
+    ctx: CoreContext,
+    new_storage: impl Fn() -> Arc<S> + 'static,
+) {
+    struct AgainstSkiplist<F>(Runtime, CoreContext, F);
+
+    impl<F> Testable for AgainstSkiplist<F>
+    where
+        F: Fn() -> Arc<dyn CommitGraphStorage> + 'static,
+    {
+        fn result(&self, gen: &mut Gen) -> TestResult {
+            let test_case = SkiplistTestCase::arbitrary(gen);
+            match self
+                .0
+                .block_on(check_against_skiplist(&self.1, (self.2)(), &test_case))
+            {
+                Ok(()) => TestResult::passed(),
+                Err(err) => TestResult::error(format!("{:#} in {:?}", err, test_case)),
+            }
+        }
+    }
+
+    QuickCheck::new().tests(50).quickcheck(AgainstSkiplist(
+        Runtime::new().unwrap(),
+        ctx,
+        move || -> Arc<dyn CommitGraphStorage> { new_storage() },
+    ));
+}
@@ -0,0 +1,294 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Randomized testing of commit graph storages against a naive model.
+//!
+//! A `RandomDagTestCase` contains a randomly generated dag, a random
+//! topological order in which to insert it into the storage, and a random
+//! mix of queries. The answers given by the commit graph are compared to
+//! the answers of a model that computes everything directly from the map of
+//! parents.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use anyhow::ensure;
+use anyhow::Result;
+use commit_graph::CommitGraph;
+use context::CoreContext;
+use mononoke_types::ChangesetId;
+use mononoke_types::Generation;
+use quickcheck::Arbitrary;
+use quickcheck::Gen;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand::SeedableRng;
+
+use crate::utils::*;
+
+/// Name of a changeset that is never part of a generated dag.
+const MISSING: &str = "missing";
+
+/// A query to run against both the commit graph and the model.
+#[derive(Clone, Debug)]
+pub enum Query {
+    Exists(String),
+    Parents(String),
+    Generation(String),
+    IsAncestor(String, String),
+    IsAncestorMany(Vec<String>, Vec<String>),
+    AncestorsDifference(Vec<String>, Vec<String>),
+    FrontierAtGeneration(Vec<String>, u64),
+    LowestCommonFrontier(Vec<String>, Vec<String>),
+}
+
+#[derive(Clone, Debug)]
+pub struct RandomDagTestCase {
+    /// Map of changeset names to the names of their parents.
+    pub parents: BTreeMap<String, BTreeSet<String>>,
+    /// Order in which changesets are inserted. Parents are always inserted
+    /// before their children.
+    pub insert_order: Vec<String>,
+    pub queries: Vec<Query>,
+}
+
+fn random_heads(rng: &mut impl Rng, names: &[String]) -> Vec<String> {
+    let len = rng.gen_range(1..=3);
+    names.choose_multiple(rng, len).cloned().collect()
+}
+
+/// Pick a random name, occasionally one that is not in the dag.
+fn random_name(rng: &mut impl Rng, names: &[String]) -> String {
+    if rng.gen_bool(0.05) {
+        MISSING.to_string()
+    } else {
+        names.choose(rng).unwrap().clone()
+    }
+}
+
+fn random_query(rng: &mut impl Rng, names: &[String], max_generation: u64) -> Query {
+    match rng.gen_range(0..8) {
+        0 => Query::Exists(random_name(rng, names)),
+        1 => Query::Parents(random_name(rng, names)),
+        2 => Query::Generation(random_name(rng, names)),
+        3 => Query::IsAncestor(
+            names.choose(rng).unwrap().clone(),
+            names.choose(rng).unwrap().clone(),
+        ),
+        4 => Query::IsAncestorMany(random_heads(rng, names), random_heads(rng, names)),
+        5 => Query::AncestorsDifference(random_heads(rng, names), random_heads(rng, names)),
+        6 => Query::FrontierAtGeneration(
+            random_heads(rng, names),
+            rng.gen_range(0..=max_generation + 1),
+        ),
+        _ => Query::LowestCommonFrontier(random_heads(rng, names), random_heads(rng, names)),
+    }
+}
+
+impl Arbitrary for RandomDagTestCase {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let mut rng = StdRng::seed_from_u64(u64::arbitrary(g));
+        let size = rng.gen_range(1..=g.size().max(1));
+
+        let parents = random_parents_map(&mut rng, "N", size);
+        let generations = naive_generations(&parents);
+        let max_generation = generations.values().copied().max().unwrap_or(0);
+        let names = parents.keys().cloned().collect::<Vec<_>>();
+
+        let mut insert_order = vec![];
+        let mut inserted = HashSet::new();
+        while insert_order.len() < parents.len() {
+            let ready = parents
+                .iter()
+                .filter(|(name, cs_parents)| {
+                    !inserted.contains(*name)
+                        && cs_parents.iter().all(|parent| inserted.contains(parent))
+                })
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<_>>();
+            let name = ready
+                .choose(&mut rng)
+                .expect("random dag should be acyclic")
+                .clone();
+            inserted.insert(name.clone());
+            insert_order.push(name);
+        }
+
+        let num_queries = rng.gen_range(0..32);
+        let queries = (0..num_queries)
+            .map(|_| random_query(&mut rng, &names, max_generation))
+            .collect();
+
+        Self {
+            parents,
+            insert_order,
+            queries,
+        }
+    }
+}
+
+fn cs_ids(names: &[String]) -> Vec<ChangesetId> {
+    names.iter().map(|name| name_cs_id(name)).collect()
+}
+
+fn cs_id_set<'a>(names: impl IntoIterator<Item = &'a String>) -> HashSet<ChangesetId> {
+    names.into_iter().map(|name| name_cs_id(name)).collect()
+}
+
+fn as_strs(names: &[String]) -> Vec<&str> {
+    names.iter().map(String::as_str).collect()
+}
+
+/// Naive model of a commit graph, answering queries directly from the map of
+/// parents.
+struct CommitGraphModel<'a> {
+    parents: &'a BTreeMap<String, BTreeSet<String>>,
+    generations: HashMap<String, u64>,
+}
+
+impl<'a> CommitGraphModel<'a> {
+    fn new(parents: &'a BTreeMap<String, BTreeSet<String>>) -> Self {
+        Self {
+            parents,
+            generations: naive_generations(parents),
+        }
+    }
+
+    fn ancestors(&self, heads: &[String]) -> HashSet<String> {
+        naive_ancestors(self.parents, &as_strs(heads))
+    }
+
+    fn ancestors_difference(&self, heads: &[String], common: &[String]) -> HashSet<String> {
+        let common = self.ancestors(common);
+        self.ancestors(heads)
+            .into_iter()
+            .filter(|name| !common.contains(name))
+            .collect()
+    }
+}
+
+/// Insert the dag of a test case into a commit graph, then check that every
+/// query gives the same answer as the model.
+pub async fn check_against_model(
+    ctx: &CoreContext,
+    graph: &CommitGraph,
+    test_case: &RandomDagTestCase,
+) -> Result<()> {
+    let model = CommitGraphModel::new(&test_case.parents);
+
+    for name in &test_case.insert_order {
+        let parents = test_case.parents[name]
+            .iter()
+            .map(|parent| name_cs_id(parent))
+            .collect();
+        graph.add(ctx, name_cs_id(name), parents).await?;
+    }
+
+    for query in &test_case.queries {
+        match query {
+            Query::Exists(name) => {
+                ensure!(
+                    graph.exists(ctx, name_cs_id(name)).await? == model.parents.contains_key(name),
+                    "{:?} differs from model",
+                    query
+                );
+            }
+            Query::Parents(name) => {
+                let parents = graph
+                    .changeset_parents(ctx, name_cs_id(name))
+                    .await?
+                    .map(|parents| parents.to_vec());
+                let expected = model.parents.get(name).map(|parents| {
+                    parents
+                        .iter()
+                        .map(|parent| name_cs_id(parent))
+                        .collect::<Vec<_>>()
+                });
+                ensure!(parents == expected, "{:?} differs from model", query);
+            }
+            Query::Generation(name) => {
+                let generation = graph.changeset_generation(ctx, name_cs_id(name)).await?;
+                let expected = model.generations.get(name).copied().map(Generation::new);
+                ensure!(generation == expected, "{:?} differs from model", query);
+            }
+            Query::IsAncestor(ancestor, descendant) => {
+                let is_ancestor = graph
+                    .is_ancestor(ctx, name_cs_id(ancestor), name_cs_id(descendant))
+                    .await?;
+                let expected = model.ancestors(&[descendant.clone()]).contains(ancestor);
+                ensure!(is_ancestor == expected, "{:?} differs from model", query);
+            }
+            Query::IsAncestorMany(ancestors, descendants) => {
+                let is_ancestor_many = graph
+                    .is_ancestor_many(ctx, cs_ids(ancestors), cs_ids(descendants))
+                    .await?;
+                for descendant in descendants {
+                    let descendant_ancestors = model.ancestors(&[descendant.clone()]);
+                    let expected = cs_id_set(
+                        ancestors
+                            .iter()
+                            .filter(|ancestor| descendant_ancestors.contains(*ancestor)),
+                    );
+                    ensure!(
+                        is_ancestor_many.get(&name_cs_id(descendant)) == Some(&expected),
+                        "{:?} differs from model",
+                        query
+                    );
+                }
+            }
+            Query::AncestorsDifference(heads, common) => {
+                let difference = graph
+                    .ancestors_difference(ctx, cs_ids(heads), cs_ids(common))
+                    .await?;
+                ensure!(
+                    difference.len() == difference.iter().collect::<HashSet<_>>().len(),
+                    "{:?} returned duplicate changesets",
+                    query
+                );
+                ensure!(
+                    difference.into_iter().collect::<HashSet<_>>()
+                        == cs_id_set(&model.ancestors_difference(heads, common)),
+                    "{:?} differs from model",
+                    query
+                );
+            }
+            Query::FrontierAtGeneration(heads, generation) => {
+                let frontier = graph
+                    .frontier_at_generation(ctx, cs_ids(heads), Generation::new(*generation))
+                    .await?;
+                let expected = naive_frontier_at_generation(
+                    model.parents,
+                    &model.generations,
+                    &as_strs(heads),
+                    *generation,
+                );
+                ensure!(
+                    frontier.into_iter().collect::<HashSet<_>>() == cs_id_set(&expected),
+                    "{:?} differs from model",
+                    query
+                );
+            }
+            Query::LowestCommonFrontier(heads1, heads2) => {
+                let frontier = graph
+                    .lowest_common_frontier(ctx, cs_ids(heads1), cs_ids(heads2))
+                    .await?;
+                let expected =
+                    naive_lowest_common_frontier(model.parents, &as_strs(heads1), &as_strs(heads2));
+                ensure!(
+                    frontier.into_iter().collect::<HashSet<_>>() == cs_id_set(&expected),
+                    "{:?} differs from model",
+                    query
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -6,16 +6,21 @@
  */
 
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::sync::Arc;
 
 use anyhow::Result;
 use commit_graph::CommitGraph;
 use commit_graph_types::edges::ChangesetNode;
+use commit_graph_types::segments::ChangesetSegment;
 use commit_graph_types::storage::CommitGraphStorage;
 use context::CoreContext;
 use mononoke_types::ChangesetId;
 use mononoke_types::Generation;
+use rand::seq::SliceRandom;
+use rand::Rng;
 
 /// Generate a fake changeset id for graph testing purposes by using the raw
 /// bytes of the changeset name, padded with zeroes.
@@ -48,9 +53,18 @@ pub async fn from_dag(
     ctx: &CoreContext,
     dag: &str,
     storage: Arc<dyn CommitGraphStorage>,
+) -> Result<CommitGraph> {
+    from_parents_map(ctx, &drawdag::parse(dag), storage).await
+}
+
+/// Build a commit graph from a map of changeset names to the names of their
+/// parents.
+pub async fn from_parents_map(
+    ctx: &CoreContext,
+    dag: &BTreeMap<String, BTreeSet<String>>,
+    storage: Arc<dyn CommitGraphStorage>,
 ) -> Result<CommitGraph> {
     let mut added: BTreeMap<String, ChangesetId> = BTreeMap::new();
-    let dag = drawdag::parse(dag);
     let graph = CommitGraph::new(storage.clone());
 
     while added.len() < dag.len() {
@@ -80,6 +94,211 @@ pub async fn from_dag(
     Ok(graph)
 }
 
+/// Generate a random dag of `size` changesets whose names start with
+/// `prefix`, as a map of changeset names to the names of their parents.
+///
+/// Parents are biased towards recent changesets so that the dag contains
+/// long chains as well as merges of diverged branches, some of which are
+/// octopus merges with more than two parents.
+pub fn random_parents_map(
+    rng: &mut impl Rng,
+    prefix: &str,
+    size: usize,
+) -> BTreeMap<String, BTreeSet<String>> {
+    let names = (0..size)
+        .map(|index| format!("{}{}", prefix, index))
+        .collect::<Vec<_>>();
+    let mut dag = BTreeMap::new();
+
+    for (index, name) in names.iter().enumerate() {
+        let mut parents = BTreeSet::new();
+        if index > 0 && !rng.gen_bool(0.05) {
+            let num_parents = match rng.gen_range(0..100) {
+                0..=4 => 3,
+                5..=19 => 2,
+                _ => 1,
+            };
+            for _ in 0..num_parents {
+                let parent_index = if rng.gen_bool(0.8) {
+                    index - 1 - rng.gen_range(0..index.min(4))
+                } else {
+                    rng.gen_range(0..index)
+                };
+                parents.insert(names[parent_index].clone());
+            }
+        }
+        dag.insert(name.clone(), parents);
+    }
+
+    dag
+}
+
+/// Generate a dag of `size` changesets whose names start with `prefix`,
+/// shaped like the history of a production repo, as a map of changeset names
+/// to the names of their parents.
+///
+/// Most changesets are on a long main line.  Short-lived feature branches
+/// fork off recent main line changesets and are merged back after a few
+/// changesets, and a few long-lived release branches fork off it and
+/// occasionally have the main line merged into them.
+pub fn production_shaped_parents_map(
+    rng: &mut impl Rng,
+    prefix: &str,
+    size: usize,
+) -> BTreeMap<String, BTreeSet<String>> {
+    struct Branch {
+        tip: usize,
+        /// Changesets left before a feature branch is merged back into the
+        /// main line, or `None` for a release branch, which never is.
+        remaining: Option<usize>,
+    }
+
+    let names = (0..size)
+        .map(|index| format!("{}{}", prefix, index))
+        .collect::<Vec<_>>();
+    let mut dag = BTreeMap::new();
+    let mut main_line: Vec<usize> = vec![];
+    let mut branches: Vec<Branch> = vec![];
+
+    for (index, name) in names.iter().enumerate() {
+        let mut parents = BTreeSet::new();
+        let choice = rng.gen_range(0..100);
+        if main_line.is_empty() {
+            main_line.push(index);
+        } else if choice < 12 {
+            let fork_point =
+                main_line[main_line.len() - 1 - rng.gen_range(0..main_line.len().min(8))];
+            parents.insert(fork_point);
+            let remaining = if choice < 2 {
+                None
+            } else {
+                Some(rng.gen_range(1..=5))
+            };
+            branches.push(Branch {
+                tip: index,
+                remaining,
+            });
+        } else if choice < 50 && !branches.is_empty() {
+            let main_tip = main_line[main_line.len() - 1];
+            let branch = branches.choose_mut(rng).expect("branches should not be empty");
+            parents.insert(branch.tip);
+            match &mut branch.remaining {
+                Some(remaining) => *remaining = remaining.saturating_sub(1),
+                None if rng.gen_bool(0.2) => {
+                    parents.insert(main_tip);
+                }
+                None => {}
+            }
+            branch.tip = index;
+        } else {
+            parents.insert(main_line[main_line.len() - 1]);
+            if let Some(position) = branches
+                .iter()
+                .position(|branch| branch.remaining == Some(0))
+            {
+                parents.insert(branches.swap_remove(position).tip);
+            }
+            main_line.push(index);
+        }
+        dag.insert(
+            name.clone(),
+            parents
+                .into_iter()
+                .map(|parent| names[parent].clone())
+                .collect(),
+        );
+    }
+
+    dag
+}
+
+/// Naive reference implementation of the ancestors of heads, including the
+/// heads themselves.
+pub fn naive_ancestors(
+    dag: &BTreeMap<String, BTreeSet<String>>,
+    heads: &[&str],
+) -> HashSet<String> {
+    let mut ancestors = HashSet::new();
+    let mut queue = heads
+        .iter()
+        .map(|head| head.to_string())
+        .collect::<Vec<_>>();
+    while let Some(name) = queue.pop() {
+        if ancestors.insert(name.clone()) {
+            queue.extend(dag[&name].iter().cloned());
+        }
+    }
+    ancestors
+}
+
+/// Naive reference implementation of generation numbers.
+pub fn naive_generations(dag: &BTreeMap<String, BTreeSet<String>>) -> HashMap<String, u64> {
+    let mut generations: HashMap<String, u64> = HashMap::new();
+    while generations.len() < dag.len() {
+        for (name, parents) in dag.iter() {
+            if generations.contains_key(name) {
+                continue;
+            }
+            if let Some(parent_generations) = parents
+                .iter()
+                .map(|parent| generations.get(parent).copied())
+                .collect::<Option<Vec<_>>>()
+            {
+                let generation = parent_generations.into_iter().max().unwrap_or(0) + 1;
+                generations.insert(name.clone(), generation);
+            }
+        }
+    }
+    generations
+}
+
+/// Returns the changesets in a set that are not ancestors of any other
+/// changeset in it.
+///
+/// The set must be closed under taking paths, i.e. every changeset on a
+/// path between two changesets in the set must be in the set, which is the
+/// case for all sets this is used for.
+fn naive_highest(
+    dag: &BTreeMap<String, BTreeSet<String>>,
+    set: HashSet<String>,
+) -> HashSet<String> {
+    let non_highest = set
+        .iter()
+        .flat_map(|name| dag[name].iter().cloned())
+        .collect::<HashSet<_>>();
+    set.into_iter()
+        .filter(|name| !non_highest.contains(name))
+        .collect()
+}
+
+/// Naive reference implementation of `CommitGraph::frontier_at_generation`.
+pub fn naive_frontier_at_generation(
+    dag: &BTreeMap<String, BTreeSet<String>>,
+    generations: &HashMap<String, u64>,
+    heads: &[&str],
+    generation: u64,
+) -> HashSet<String> {
+    let ancestors = naive_ancestors(dag, heads)
+        .into_iter()
+        .filter(|name| generations[name] <= generation)
+        .collect();
+    naive_highest(dag, ancestors)
+}
+
+/// Naive reference implementation of `CommitGraph::lowest_common_frontier`.
+pub fn naive_lowest_common_frontier(
+    dag: &BTreeMap<String, BTreeSet<String>>,
+    heads1: &[&str],
+    heads2: &[&str],
+) -> HashSet<String> {
+    let ancestors2 = naive_ancestors(dag, heads2);
+    let common = naive_ancestors(dag, heads1)
+        .into_iter()
+        .filter(|name| ancestors2.contains(name))
+        .collect();
+    naive_highest(dag, common)
+}
+
 pub async fn assert_skip_tree_parent(
     storage: &Arc<dyn CommitGraphStorage>,
     ctx: &CoreContext,
@@ -199,6 +418,33 @@ pub async fn assert_ancestors_difference(
     Ok(())
 }
 
+pub async fn assert_ancestors_difference_segments(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
+    common: Vec<&str>,
+    segments: Vec<(&str, &str, u64, Vec<&str>)>,
+) -> Result<()> {
+    let heads = heads.into_iter().map(name_cs_id).collect();
+    let common = common.into_iter().map(name_cs_id).collect();
+
+    assert_eq!(
+        graph
+            .ancestors_difference_segments(ctx, heads, common)
+            .await?,
+        segments
+            .into_iter()
+            .map(|(head, base, length, parents)| ChangesetSegment {
+                head: name_cs_id(head),
+                base: name_cs_id(base),
+                length,
+                parents: parents.into_iter().map(name_cs_id).collect(),
+            })
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
 pub async fn assert_ancestors_frontier_with(
     graph: &CommitGraph,
     ctx: &CoreContext,
@@ -273,3 +519,47 @@ pub async fn assert_p1_linear_lowest_common_ancestor(
     );
     Ok(())
 }
+
+pub async fn assert_frontier_at_generation(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
+    generation: u64,
+    frontier: Vec<&str>,
+) -> Result<()> {
+    let heads = heads.into_iter().map(name_cs_id).collect();
+
+    assert_eq!(
+        graph
+            .frontier_at_generation(ctx, heads, Generation::new(generation))
+            .await?
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        frontier.into_iter().map(name_cs_id).collect::<HashSet<_>>()
+    );
+    Ok(())
+}
+
+pub async fn assert_lowest_common_frontier(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads1: Vec<&str>,
+    heads2: Vec<&str>,
+    lowest_common_frontier: Vec<&str>,
+) -> Result<()> {
+    let heads1 = heads1.into_iter().map(name_cs_id).collect();
+    let heads2 = heads2.into_iter().map(name_cs_id).collect();
+
+    assert_eq!(
+        graph
+            .lowest_common_frontier(ctx, heads1, heads2)
+            .await?
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        lowest_common_frontier
+            .into_iter()
+            .map(name_cs_id)
+            .collect::<HashSet<_>>()
+    );
+    Ok(())
+}
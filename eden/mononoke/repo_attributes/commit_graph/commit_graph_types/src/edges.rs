@@ -75,6 +75,7 @@ pub struct ChangesetEdges {
 }
 
 /// A frontier of changesets ordered by generation number.
+#[derive(Clone)]
 pub struct ChangesetFrontier(BTreeMap<Generation, HashSet<ChangesetId>>);
 
 impl ChangesetFrontier {
@@ -9,6 +9,7 @@ use mononoke_types::ChangesetId;
 use smallvec::SmallVec;
 
 pub mod edges;
+pub mod segments;
 pub mod storage;
 
 /// The parents of a changeset.
@@ -0,0 +1,33 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Commit Graph Segments
+
+use mononoke_types::ChangesetId;
+
+use crate::ChangesetParents;
+
+/// A linear run of changesets in the commit graph, from `base` up to `head`.
+///
+/// Every changeset in the segment other than `base` has exactly one parent,
+/// the changeset below it in the segment, and every changeset other than
+/// `head` has exactly one child among the changesets being segmented, the
+/// changeset above it in the segment.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChangesetSegment {
+    /// The highest changeset in the segment.
+    pub head: ChangesetId,
+
+    /// The lowest changeset in the segment.
+    pub base: ChangesetId,
+
+    /// The number of changesets in the segment.
+    pub length: u64,
+
+    /// The parents of the base of the segment.
+    pub parents: ChangesetParents,
+}
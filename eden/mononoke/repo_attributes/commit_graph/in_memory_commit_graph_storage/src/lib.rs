@@ -185,6 +185,14 @@ mod tests {
         test_skip_tree(&ctx, storage).await
     }
 
+    #[fbinit::test]
+    async fn test_in_memory_octopus_merges(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let storage = Arc::new(InMemoryCommitGraphStorage::new(RepositoryId::new(1)));
+
+        test_octopus_merges(&ctx, storage).await
+    }
+
     #[fbinit::test]
     async fn test_in_memory_p1_linear_tree(fb: FacebookInit) -> Result<()> {
         let ctx = CoreContext::test_mock(fb);
@@ -201,6 +209,14 @@ mod tests {
         test_ancestors_difference(&ctx, storage).await
     }
 
+    #[fbinit::test]
+    async fn test_in_memory_ancestors_difference_segments(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let storage = Arc::new(InMemoryCommitGraphStorage::new(RepositoryId::new(1)));
+
+        test_ancestors_difference_segments(&ctx, storage).await
+    }
+
     #[fbinit::test]
     async fn test_in_memory_find_by_prefix(fb: FacebookInit) -> Result<()> {
         let ctx = CoreContext::test_mock(fb);
@@ -224,4 +240,36 @@ mod tests {
 
         test_ancestors_frontier_with(&ctx, storage).await
     }
+
+    #[fbinit::test]
+    async fn test_in_memory_frontier_operations(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let storage = Arc::new(InMemoryCommitGraphStorage::new(RepositoryId::new(1)));
+
+        test_frontier_operations(&ctx, storage).await
+    }
+
+    #[fbinit::test]
+    async fn test_in_memory_random_frontier_operations(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let storage = Arc::new(InMemoryCommitGraphStorage::new(RepositoryId::new(1)));
+
+        test_random_frontier_operations(&ctx, storage).await
+    }
+
+    #[fbinit::test]
+    fn test_in_memory_against_model(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        test_storage_against_model(ctx, || {
+            Arc::new(InMemoryCommitGraphStorage::new(RepositoryId::new(1)))
+        });
+    }
+
+    #[fbinit::test]
+    fn test_in_memory_against_skiplist(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        test_storage_against_skiplist(ctx, || {
+            Arc::new(InMemoryCommitGraphStorage::new(RepositoryId::new(1)))
+        });
+    }
 }
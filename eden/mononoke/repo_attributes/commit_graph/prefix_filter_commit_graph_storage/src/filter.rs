@@ -0,0 +1,220 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use mononoke_types::ChangesetId;
+use mononoke_types::ChangesetIdPrefix;
+
+/// Number of bytes of the changeset id prefix that is added to the filter
+/// alongside the full changeset id.  Prefixes shorter than this can't be
+/// ruled out by the filter.
+const PREFIX_LEN: usize = 4;
+
+/// Each changeset adds its full id and its prefix to the filter.
+const KEYS_PER_CHANGESET: usize = 2;
+
+/// With 10 bits per key and 7 hashes, the false positive rate is about 1%.
+const BITS_PER_KEY: usize = 10;
+const NUM_HASHES: u64 = 7;
+
+/// A bloom filter of changeset ids and their prefixes.
+///
+/// The filter can have false positives, but never false negatives: if
+/// `may_contain` returns false, the changeset was never inserted.
+pub struct ChangesetIdFilter {
+    bits: Vec<AtomicU64>,
+    capacity: usize,
+    len: AtomicUsize,
+}
+
+impl ChangesetIdFilter {
+    /// Create a filter sized to hold `capacity` changesets with a low false
+    /// positive rate.  More changesets can be inserted, but the false
+    /// positive rate increases.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            bits: (0..num_words(capacity))
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+            capacity,
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Recreate a filter from the words returned by `words`.  Returns None
+    /// if there are not the right number of words for the capacity.
+    pub(crate) fn from_words(capacity: usize, len: usize, words: Vec<u64>) -> Option<Self> {
+        if words.len() != num_words(capacity) {
+            return None;
+        }
+        Some(Self {
+            bits: words.into_iter().map(AtomicU64::new).collect(),
+            capacity,
+            len: AtomicUsize::new(len),
+        })
+    }
+
+    /// The bits of the filter, 64 at a time.
+    pub(crate) fn words(&self) -> impl Iterator<Item = u64> + '_ {
+        self.bits.iter().map(|word| word.load(Ordering::Relaxed))
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The approximate number of distinct changesets in the filter.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether more changesets than the capacity have been inserted, so
+    /// that the false positive rate is higher than intended.
+    pub fn is_full(&self) -> bool {
+        self.len() > self.capacity
+    }
+
+    pub fn insert(&self, cs_id: &ChangesetId) {
+        let bytes = cs_id.as_ref();
+        if self.insert_key(bytes) {
+            self.len.fetch_add(1, Ordering::Relaxed);
+        }
+        self.insert_key(&bytes[..PREFIX_LEN]);
+    }
+
+    pub fn may_contain(&self, cs_id: &ChangesetId) -> bool {
+        self.may_contain_key(cs_id.as_ref())
+    }
+
+    /// Returns false if no changeset in the filter has this prefix.
+    pub fn may_contain_prefix(&self, prefix: &ChangesetIdPrefix) -> bool {
+        let min = prefix.min_as_ref();
+        let known = min
+            .iter()
+            .zip(prefix.max_as_ref())
+            .take_while(|(min, max)| min == max)
+            .count();
+        if known == min.len() {
+            self.may_contain_key(min)
+        } else if known >= PREFIX_LEN {
+            self.may_contain_key(&min[..PREFIX_LEN])
+        } else {
+            true
+        }
+    }
+
+    fn positions(&self, key: &[u8]) -> impl Iterator<Item = usize> {
+        let hash = stable_hash(key);
+        // Double hashing: the i-th position is h1 + i * h2.
+        let h2 = hash.rotate_left(32) | 1;
+        let num_bits = self.bits.len() as u64 * 64;
+        (0..NUM_HASHES).map(move |i| (hash.wrapping_add(i.wrapping_mul(h2)) % num_bits) as usize)
+    }
+
+    /// Returns true if any of the key's bits were not already set.
+    fn insert_key(&self, key: &[u8]) -> bool {
+        let mut new = false;
+        for pos in self.positions(key) {
+            let mask = 1 << (pos % 64);
+            let prev = self.bits[pos / 64].fetch_or(mask, Ordering::Relaxed);
+            new |= prev & mask == 0;
+        }
+        new
+    }
+
+    fn may_contain_key(&self, key: &[u8]) -> bool {
+        self.positions(key).all(|pos| {
+            let mask = 1 << (pos % 64);
+            self.bits[pos / 64].load(Ordering::Relaxed) & mask != 0
+        })
+    }
+}
+
+/// A hash of the key that doesn't change between builds, as filters are
+/// persisted: FNV-1a, followed by the splitmix64 finalizer to spread the
+/// bits.
+fn stable_hash(key: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in key {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash ^= hash >> 30;
+    hash = hash.wrapping_mul(0xbf58476d1ce4e5b9);
+    hash ^= hash >> 27;
+    hash = hash.wrapping_mul(0x94d049bb133111eb);
+    hash ^ (hash >> 31)
+}
+
+/// The number of 64-bit words in a filter with this capacity.
+fn num_words(capacity: usize) -> usize {
+    let num_bits = capacity.max(1) * KEYS_PER_CHANGESET * BITS_PER_KEY;
+    (num_bits + 63) / 64
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use anyhow::Result;
+    use mononoke_types::hash::Context;
+
+    use super::*;
+
+    fn cs_id(n: u64) -> ChangesetId {
+        // Hash the number, as real changeset ids are random.
+        let mut ctx = Context::new(b"changeset");
+        ctx.update(n.to_be_bytes());
+        ChangesetId::new(ctx.finish())
+    }
+
+    #[test]
+    fn test_filter() -> Result<()> {
+        let filter = ChangesetIdFilter::with_capacity(1000);
+        for n in 0..1000 {
+            filter.insert(&cs_id(n));
+        }
+        assert!(filter.len() > 950 && filter.len() <= 1000);
+
+        // No false negatives.
+        for n in 0..1000 {
+            let cs_id = cs_id(n);
+            assert!(filter.may_contain(&cs_id));
+            let hex = cs_id.to_hex();
+            for len in [8, 19, 64] {
+                let prefix = ChangesetIdPrefix::from_str(&hex.as_str()[..len])?;
+                assert!(filter.may_contain_prefix(&prefix));
+            }
+        }
+
+        // Few false positives.
+        let false_positives = (1000..11000)
+            .filter(|n| filter.may_contain(&cs_id(*n)))
+            .count();
+        assert!(false_positives < 300, "{} false positives", false_positives);
+
+        // The filter can be recreated from its words.
+        let words = filter.words().collect::<Vec<_>>();
+        let copy = ChangesetIdFilter::from_words(filter.capacity(), filter.len(), words)
+            .expect("words should match the capacity");
+        assert_eq!(copy.len(), filter.len());
+        assert!((0..1000).all(|n| copy.may_contain(&cs_id(n))));
+        assert!(ChangesetIdFilter::from_words(2000, 0, vec![]).is_none());
+
+        // Prefixes that are too short can't be ruled out.
+        let prefix = ChangesetIdPrefix::from_str("abc")?;
+        assert!(filter.may_contain_prefix(&prefix));
+        Ok(())
+    }
+}
@@ -0,0 +1,472 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Prefix Filter Commit Graph Storage
+//!
+//! Commit graph storage that consults a bloom filter of changeset ids
+//! before looking up changesets or changeset id prefixes, so that lookups of
+//! changesets that don't exist don't have to reach the database.
+//!
+//! The filter is persisted in the repo blobstore by a maintenance task,
+//! which rebuilds it when it gets full.  Servers load the persisted filter
+//! and keep it up to date with the changesets added to the commit graph by
+//! other servers, by periodically reading the ones added since it was
+//! persisted or last updated from a replica.
+//!
+//! A changeset that was added by another server since the last update is
+//! not in the filter, so lookups that the filter rejects are only trusted
+//! if it was updated within the `commit_graph_prefix_filter_max_staleness_ms`
+//! tunable, and are otherwise passed on to the underlying storage.  As the
+//! updates read from a replica, a changeset can be rejected for up to the
+//! replication lag on top of that.  By default the filter is never trusted,
+//! so that it never rejects a changeset that exists.
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Weak;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::Result;
+use arc_swap::ArcSwapOption;
+use async_trait::async_trait;
+use blobstore::Blobstore;
+use commit_graph_types::edges::ChangesetEdges;
+use commit_graph_types::storage::CommitGraphStorage;
+use commit_graph_types::storage::Prefetch;
+use context::CoreContext;
+use mononoke_types::ChangesetId;
+use mononoke_types::ChangesetIdPrefix;
+use mononoke_types::ChangesetIdsResolvedFromPrefix;
+use mononoke_types::RepositoryId;
+use slog::warn;
+use sql_commit_graph_storage::SqlCommitGraphStorage;
+use stats::prelude::*;
+use tunables::tunables;
+use vec1::Vec1;
+
+mod filter;
+mod persist;
+#[cfg(test)]
+mod tests;
+
+pub use crate::filter::ChangesetIdFilter;
+
+define_stats! {
+    prefix = "mononoke.commit_graph.prefix_filter";
+
+    rejected: timeseries("rejected"; Rate, Sum),
+    passed: timeseries("passed"; Rate, Sum),
+    stale: timeseries("stale"; Rate, Sum),
+    updates: timeseries("updates"; Rate, Sum),
+    loads: timeseries("loads"; Rate, Sum),
+    rebuilds: timeseries("rebuilds"; Rate, Sum),
+}
+
+/// Number of changesets to read from the database at a time.
+const UPDATE_BATCH_SIZE: usize = 10000;
+
+/// Changesets can become visible in the database out of order of their
+/// ids, e.g. when the transaction that added them commits late or the
+/// replica is lagging, so each update re-reads the changesets with ids
+/// after the last one that was seen at least this long ago.  The ids are
+/// shared by all repos, so this can't be done by looking for gaps in them.
+const REREAD_WINDOW: Duration = Duration::from_secs(120);
+
+/// The smallest filter that is built.
+const MIN_CAPACITY: usize = 100000;
+
+/// Servers update their filters at least this often, and more often if
+/// the maximum staleness requires it.
+const MAX_UPDATE_INTERVAL: Duration = Duration::from_secs(10);
+const MIN_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+struct UpdateState {
+    /// Changesets up to this id have been added to the filter, and no more
+    /// changesets with smaller ids are expected to become visible.
+    safe_id: u64,
+    /// The last id seen by each update within the reread window, and when
+    /// the update started.
+    seen: VecDeque<(Instant, u64)>,
+}
+
+impl UpdateState {
+    fn new(safe_id: u64) -> Self {
+        Self {
+            safe_id,
+            seen: VecDeque::new(),
+        }
+    }
+
+    /// The state of a persisted filter, which saw `last_id` when it was
+    /// saved.
+    fn loaded(header: &persist::Header) -> Self {
+        let saved_at = UNIX_EPOCH + Duration::from_secs(header.saved_at);
+        let age = SystemTime::now()
+            .duration_since(saved_at)
+            .unwrap_or_default();
+        let now = Instant::now();
+        Self {
+            safe_id: header.safe_id,
+            seen: VecDeque::from([(now.checked_sub(age).unwrap_or(now), header.last_id)]),
+        }
+    }
+
+    fn last_id(&self) -> u64 {
+        self.seen.back().map_or(self.safe_id, |(_, id)| *id)
+    }
+
+    /// Forget the ids seen by updates that are older than the reread
+    /// window: changesets with smaller ids should all be visible by now.
+    fn advance(&mut self, now: Instant) {
+        while let Some((started, id)) = self.seen.front() {
+            if now.saturating_duration_since(*started) < REREAD_WINDOW {
+                break;
+            }
+            self.safe_id = self.safe_id.max(*id);
+            self.seen.pop_front();
+        }
+    }
+}
+
+fn max_staleness() -> Duration {
+    Duration::from_millis(
+        tunables()
+            .commit_graph_prefix_filter_max_staleness_ms()
+            .unwrap_or_default()
+            .try_into()
+            .unwrap_or_default(),
+    )
+}
+
+/// A filter of the changesets in a repo's commit graph, and the means to
+/// keep it up to date.
+pub struct PrefixFilter {
+    sql_storage: Arc<SqlCommitGraphStorage>,
+    blobstore: Arc<dyn Blobstore>,
+    filter: ArcSwapOption<ChangesetIdFilter>,
+    update_state: tokio::sync::Mutex<UpdateState>,
+    /// When the last update finished.
+    updated_at: ArcSwapOption<Instant>,
+}
+
+impl PrefixFilter {
+    /// Create a filter for the changesets in this storage, persisted in
+    /// this blobstore.  The filter isn't used until it is loaded by the
+    /// first call to `update` after it has been persisted.
+    pub fn new(sql_storage: Arc<SqlCommitGraphStorage>, blobstore: Arc<dyn Blobstore>) -> Self {
+        Self {
+            sql_storage,
+            blobstore,
+            filter: ArcSwapOption::empty(),
+            update_state: tokio::sync::Mutex::new(UpdateState::new(0)),
+            updated_at: ArcSwapOption::empty(),
+        }
+    }
+
+    /// Add the changesets that were added to the commit graph since the
+    /// last update to the filter.  If the filter hasn't been loaded yet or
+    /// is full, the persisted filter is loaded if it is bigger.
+    pub async fn update(&self, ctx: &CoreContext) -> Result<()> {
+        let mut state = self.update_state.lock().await;
+        let current = self.filter.load_full();
+        if current.as_ref().map_or(true, |filter| filter.is_full()) {
+            let min_capacity = current.as_ref().map_or(0, |filter| filter.capacity() + 1);
+            if self.load_locked(ctx, &mut state, min_capacity).await? {
+                return Ok(());
+            }
+        }
+        if let Some(filter) = current {
+            self.update_locked(ctx, &mut state, &filter).await?;
+            self.mark_updated();
+        }
+        Ok(())
+    }
+
+    /// Load the persisted filter if its capacity is at least
+    /// `min_capacity`, and bring it up to date.  Returns whether it was
+    /// loaded.
+    async fn load_locked(
+        &self,
+        ctx: &CoreContext,
+        state: &mut UpdateState,
+        min_capacity: usize,
+    ) -> Result<bool> {
+        // Only the small header is read until it is known that the filter
+        // is worth loading.
+        let header = match persist::load_header(ctx, self.blobstore.as_ref()).await? {
+            Some(header) if header.capacity >= min_capacity => header,
+            _ => return Ok(false),
+        };
+        let filter = match persist::load_filter(ctx, self.blobstore.as_ref(), &header).await? {
+            Some(filter) => Arc::new(filter),
+            None => return Ok(false),
+        };
+        // Changesets that are added by this server while the filter is being
+        // loaded are only added to the old filter, so catch up with them
+        // before replacing it.
+        let mut new_state = UpdateState::loaded(&header);
+        self.update_locked(ctx, &mut new_state, &filter).await?;
+        *state = new_state;
+        self.filter.store(Some(filter));
+        self.mark_updated();
+        STATS::loads.add_value(1);
+        Ok(true)
+    }
+
+    /// Bring the persisted filter up to date with the commit graph and
+    /// persist it again.  This is the maintenance task that keeps the
+    /// persisted filter usable by servers.  The filter is rebuilt from
+    /// scratch, reading every changeset, if none has been persisted yet,
+    /// if it is full, or if `rebuild` is set.
+    pub async fn update_persisted(&self, ctx: &CoreContext, rebuild: bool) -> Result<()> {
+        let mut state = self.update_state.lock().await;
+        let loaded = !rebuild && self.load_locked(ctx, &mut state, 0).await?;
+        let filter = match self.filter.load_full() {
+            Some(filter) if loaded && !filter.is_full() => filter,
+            filter => {
+                let len = filter.map_or(0, |filter| filter.len());
+                self.rebuild_locked(ctx, &mut state, len).await?
+            }
+        };
+        persist::save(
+            ctx,
+            self.blobstore.as_ref(),
+            &filter,
+            state.safe_id,
+            state.last_id(),
+        )
+        .await
+    }
+
+    fn mark_updated(&self) {
+        self.updated_at.store(Some(Arc::new(Instant::now())));
+    }
+
+    async fn update_locked(
+        &self,
+        ctx: &CoreContext,
+        state: &mut UpdateState,
+        filter: &ChangesetIdFilter,
+    ) -> Result<()> {
+        let started = Instant::now();
+        state.advance(started);
+        let mut after_id = state.safe_id;
+        let mut last_id = state.last_id();
+        loop {
+            let changesets = self
+                .sql_storage
+                .fetch_changesets_after(ctx, after_id, UPDATE_BATCH_SIZE)
+                .await?;
+            for (id, cs_id) in changesets.iter() {
+                filter.insert(cs_id);
+                last_id = last_id.max(*id);
+            }
+            match changesets.last() {
+                Some((id, _)) if changesets.len() == UPDATE_BATCH_SIZE => after_id = *id,
+                _ => break,
+            }
+        }
+        state.seen.push_back((started, last_id));
+        STATS::updates.add_value(1);
+        Ok(())
+    }
+
+    async fn rebuild_locked(
+        &self,
+        ctx: &CoreContext,
+        state: &mut UpdateState,
+        len: usize,
+    ) -> Result<Arc<ChangesetIdFilter>> {
+        let mut capacity = (len * 2).max(MIN_CAPACITY);
+        let filter = loop {
+            let filter = ChangesetIdFilter::with_capacity(capacity);
+            let mut new_state = UpdateState::new(0);
+            self.update_locked(ctx, &mut new_state, &filter).await?;
+            if !filter.is_full() {
+                *state = new_state;
+                break filter;
+            }
+            capacity = filter.len() * 2;
+        };
+        // As when loading, catch up with the changesets added while the
+        // filter was being rebuilt.
+        let filter = Arc::new(filter);
+        self.update_locked(ctx, state, &filter).await?;
+        self.filter.store(Some(filter.clone()));
+        self.mark_updated();
+        STATS::rebuilds.add_value(1);
+        Ok(filter)
+    }
+
+    /// Keep the filter up to date until it is dropped.
+    pub async fn update_periodically(this: Weak<Self>, ctx: CoreContext) {
+        while let Some(prefix_filter) = this.upgrade() {
+            if let Err(err) = prefix_filter.update(&ctx).await {
+                warn!(
+                    ctx.logger(),
+                    "Failed to update changeset id prefix filter: {:#}", err
+                );
+            }
+            drop(prefix_filter);
+            // Update often enough that the filter is usually fresh enough
+            // to be trusted.
+            let max_staleness = max_staleness();
+            let interval = if max_staleness.is_zero() {
+                MAX_UPDATE_INTERVAL
+            } else {
+                (max_staleness / 2).clamp(MIN_UPDATE_INTERVAL, MAX_UPDATE_INTERVAL)
+            };
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    fn insert(&self, cs_id: &ChangesetId) {
+        if let Some(filter) = self.filter.load_full() {
+            filter.insert(cs_id);
+        }
+    }
+
+    /// Returns true if the filter rules out the lookup.  Lookups are never
+    /// ruled out by a filter that is out of date.
+    fn rejects(&self, may_contain: impl Fn(&ChangesetIdFilter) -> bool) -> bool {
+        let filter = match self.filter.load_full() {
+            Some(filter) => filter,
+            None => return false,
+        };
+        if may_contain(&filter) {
+            STATS::passed.add_value(1);
+            return false;
+        }
+        let max_staleness = max_staleness();
+        let is_fresh = self
+            .updated_at
+            .load_full()
+            .map_or(false, |updated_at| updated_at.elapsed() < max_staleness);
+        if is_fresh {
+            STATS::rejected.add_value(1);
+            true
+        } else {
+            STATS::stale.add_value(1);
+            false
+        }
+    }
+}
+
+pub struct PrefixFilterCommitGraphStorage {
+    storage: Arc<dyn CommitGraphStorage>,
+    prefix_filter: Arc<PrefixFilter>,
+}
+
+impl PrefixFilterCommitGraphStorage {
+    pub fn new(storage: Arc<dyn CommitGraphStorage>, prefix_filter: Arc<PrefixFilter>) -> Self {
+        Self {
+            storage,
+            prefix_filter,
+        }
+    }
+}
+
+#[async_trait]
+impl CommitGraphStorage for PrefixFilterCommitGraphStorage {
+    fn repo_id(&self) -> RepositoryId {
+        self.storage.repo_id()
+    }
+
+    async fn add(&self, ctx: &CoreContext, edges: ChangesetEdges) -> Result<bool> {
+        let cs_id = edges.node.cs_id;
+        let added = self.storage.add(ctx, edges).await?;
+        self.prefix_filter.insert(&cs_id);
+        Ok(added)
+    }
+
+    async fn add_many(&self, ctx: &CoreContext, many_edges: Vec1<ChangesetEdges>) -> Result<usize> {
+        let cs_ids = many_edges
+            .iter()
+            .map(|edges| edges.node.cs_id)
+            .collect::<Vec<_>>();
+        let added = self.storage.add_many(ctx, many_edges).await?;
+        for cs_id in cs_ids.iter() {
+            self.prefix_filter.insert(cs_id);
+        }
+        Ok(added)
+    }
+
+    async fn fetch_edges(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<Option<ChangesetEdges>> {
+        if self
+            .prefix_filter
+            .rejects(|filter| filter.may_contain(&cs_id))
+        {
+            return Ok(None);
+        }
+        self.storage.fetch_edges(ctx, cs_id).await
+    }
+
+    async fn fetch_edges_required(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<ChangesetEdges> {
+        self.storage.fetch_edges_required(ctx, cs_id).await
+    }
+
+    async fn fetch_many_edges(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+        prefetch: Prefetch,
+    ) -> Result<HashMap<ChangesetId, ChangesetEdges>> {
+        let maybe_present = cs_ids
+            .iter()
+            .copied()
+            .filter(|cs_id| {
+                !self
+                    .prefix_filter
+                    .rejects(|filter| filter.may_contain(cs_id))
+            })
+            .collect::<Vec<_>>();
+        if maybe_present.is_empty() {
+            return Ok(HashMap::new());
+        }
+        self.storage
+            .fetch_many_edges(ctx, &maybe_present, prefetch)
+            .await
+    }
+
+    async fn fetch_many_edges_required(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+        prefetch: Prefetch,
+    ) -> Result<HashMap<ChangesetId, ChangesetEdges>> {
+        self.storage
+            .fetch_many_edges_required(ctx, cs_ids, prefetch)
+            .await
+    }
+
+    async fn find_by_prefix(
+        &self,
+        ctx: &CoreContext,
+        cs_prefix: ChangesetIdPrefix,
+        limit: usize,
+    ) -> Result<ChangesetIdsResolvedFromPrefix> {
+        if self
+            .prefix_filter
+            .rejects(|filter| filter.may_contain_prefix(&cs_prefix))
+        {
+            return Ok(ChangesetIdsResolvedFromPrefix::NoMatch);
+        }
+        self.storage.find_by_prefix(ctx, cs_prefix, limit).await
+    }
+}
@@ -0,0 +1,181 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Filters are persisted in the repo blobstore, so that servers don't have
+//! to read every changeset from the database to build them.  The filter is
+//! split into chunks, and a header records where it is up to.  Each chunk
+//! carries the id of the write it belongs to, so that a filter that is read
+//! while it is being rewritten is detected and ignored.
+
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use blobstore::Blobstore;
+use bytes::Buf;
+use bytes::BufMut;
+use bytes::Bytes;
+use bytes::BytesMut;
+use context::CoreContext;
+use mononoke_types::BlobstoreBytes;
+
+use crate::filter::ChangesetIdFilter;
+
+const HEADER_KEY: &str = "commit_graph_prefix_filter.v1";
+const MAGIC: &[u8] = b"prefixfilter.v1";
+const HEADER_LEN: usize = MAGIC.len() + 8 * 7;
+
+/// Number of 64-bit words of the filter in each chunk.
+const CHUNK_WORDS: usize = 512 * 1024;
+
+fn chunk_key(index: u64) -> String {
+    format!("{}.chunk{}", HEADER_KEY, index)
+}
+
+pub(crate) struct Header {
+    write_id: u64,
+    /// Changesets up to this id were added to the filter, and no more
+    /// changesets with smaller ids were expected to become visible.
+    pub(crate) safe_id: u64,
+    /// The last changeset id that was added to the filter.
+    pub(crate) last_id: u64,
+    /// When the filter was saved, in seconds since the epoch.
+    pub(crate) saved_at: u64,
+    pub(crate) capacity: usize,
+    len: usize,
+    num_chunks: u64,
+}
+
+impl Header {
+    fn encode(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(HEADER_LEN);
+        buf.put_slice(MAGIC);
+        buf.put_u64(self.write_id);
+        buf.put_u64(self.safe_id);
+        buf.put_u64(self.last_id);
+        buf.put_u64(self.saved_at);
+        buf.put_u64(self.capacity as u64);
+        buf.put_u64(self.len as u64);
+        buf.put_u64(self.num_chunks);
+        buf.freeze()
+    }
+
+    fn decode(mut data: Bytes) -> Result<Self> {
+        if data.len() != HEADER_LEN || !data.starts_with(MAGIC) {
+            return Err(anyhow!("Invalid changeset id prefix filter header"));
+        }
+        data.advance(MAGIC.len());
+        Ok(Self {
+            write_id: data.get_u64(),
+            safe_id: data.get_u64(),
+            last_id: data.get_u64(),
+            saved_at: data.get_u64(),
+            capacity: data.get_u64() as usize,
+            len: data.get_u64() as usize,
+            num_chunks: data.get_u64(),
+        })
+    }
+
+    /// Returns the words of the chunk, or None if it was written by a
+    /// different write.
+    fn decode_chunk(&self, mut data: Bytes) -> Option<Vec<u64>> {
+        if data.len() < 8 || data.len() % 8 != 0 || data.get_u64() != self.write_id {
+            return None;
+        }
+        let mut words = Vec::with_capacity(data.len() / 8);
+        while data.has_remaining() {
+            words.push(data.get_u64_le());
+        }
+        Some(words)
+    }
+}
+
+/// Load the persisted header, if there is one.
+pub(crate) async fn load_header(
+    ctx: &CoreContext,
+    blobstore: &dyn Blobstore,
+) -> Result<Option<Header>> {
+    match blobstore.get(ctx, HEADER_KEY).await? {
+        Some(data) => Ok(Some(Header::decode(data.into_raw_bytes())?)),
+        None => Ok(None),
+    }
+}
+
+/// Load the filter that the header belongs to.  Returns None if the filter
+/// was rewritten while it was being loaded.
+pub(crate) async fn load_filter(
+    ctx: &CoreContext,
+    blobstore: &dyn Blobstore,
+    header: &Header,
+) -> Result<Option<ChangesetIdFilter>> {
+    let mut words = Vec::new();
+    for index in 0..header.num_chunks {
+        let data = blobstore
+            .get(ctx, &chunk_key(index))
+            .await?
+            .ok_or_else(|| anyhow!("Changeset id prefix filter chunk {} is missing", index))?;
+        match header.decode_chunk(data.into_raw_bytes()) {
+            Some(chunk) => words.extend(chunk),
+            None => return Ok(None),
+        }
+    }
+    ChangesetIdFilter::from_words(header.capacity, header.len, words)
+        .map(Some)
+        .ok_or_else(|| anyhow!("Changeset id prefix filter has the wrong size"))
+}
+
+/// Persist the filter, which has all changesets up to `safe_id`, and those
+/// up to `last_id` that were visible when it was last updated.
+pub(crate) async fn save(
+    ctx: &CoreContext,
+    blobstore: &dyn Blobstore,
+    filter: &ChangesetIdFilter,
+    safe_id: u64,
+    last_id: u64,
+) -> Result<()> {
+    let write_id = rand::random();
+    let mut words = filter.words().peekable();
+    let mut num_chunks = 0;
+    while words.peek().is_some() {
+        let mut buf = BytesMut::with_capacity(8 + CHUNK_WORDS * 8);
+        buf.put_u64(write_id);
+        for word in words.by_ref().take(CHUNK_WORDS) {
+            buf.put_u64_le(word);
+        }
+        blobstore
+            .put(
+                ctx,
+                chunk_key(num_chunks),
+                BlobstoreBytes::from_bytes(buf.freeze()),
+            )
+            .await?;
+        num_chunks += 1;
+    }
+
+    // The header is written last, so that it only refers to complete
+    // filters.
+    let saved_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs());
+    let header = Header {
+        write_id,
+        safe_id,
+        last_id,
+        saved_at,
+        capacity: filter.capacity(),
+        len: filter.len(),
+        num_chunks,
+    };
+    blobstore
+        .put(
+            ctx,
+            HEADER_KEY.to_string(),
+            BlobstoreBytes::from_bytes(header.encode()),
+        )
+        .await
+}
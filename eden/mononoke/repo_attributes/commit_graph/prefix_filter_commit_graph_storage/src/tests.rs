@@ -0,0 +1,228 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Result;
+use blobstore::Blobstore;
+use commit_graph_testlib::*;
+use commit_graph_types::storage::CommitGraphStorage;
+use commit_graph_types::storage::Prefetch;
+use context::CoreContext;
+use context::PerfCounterType;
+use fbinit::FacebookInit;
+use futures::FutureExt;
+use memblob::Memblob;
+use mononoke_types::ChangesetIdPrefix;
+use mononoke_types::ChangesetIdsResolvedFromPrefix;
+use mononoke_types::RepositoryId;
+use rendezvous::RendezVousOptions;
+use sql_commit_graph_storage::SqlCommitGraphStorage;
+use sql_commit_graph_storage::SqlCommitGraphStorageBuilder;
+use sql_construct::SqlConstruct;
+use tunables::with_tunables_async;
+use tunables::MononokeTunables;
+
+use crate::PrefixFilter;
+use crate::PrefixFilterCommitGraphStorage;
+use crate::UpdateState;
+use crate::REREAD_WINDOW;
+
+fn new_sql_storage() -> Arc<SqlCommitGraphStorage> {
+    Arc::new(
+        SqlCommitGraphStorageBuilder::with_sqlite_in_memory()
+            .unwrap()
+            .build(RendezVousOptions::for_test(), RepositoryId::new(1)),
+    )
+}
+
+async fn new_storage(
+    ctx: &CoreContext,
+) -> Result<(Arc<SqlCommitGraphStorage>, Arc<PrefixFilterCommitGraphStorage>)> {
+    let sql_storage = new_sql_storage();
+    let prefix_filter = Arc::new(PrefixFilter::new(
+        sql_storage.clone(),
+        Arc::new(Memblob::default()),
+    ));
+    prefix_filter.update_persisted(ctx, false).await?;
+    let storage = Arc::new(PrefixFilterCommitGraphStorage::new(
+        sql_storage.clone(),
+        prefix_filter,
+    ));
+    Ok((sql_storage, storage))
+}
+
+fn trusted_filter_tunables() -> MononokeTunables {
+    let tunables = MononokeTunables::default();
+    tunables.update_ints(&HashMap::from([(
+        "commit_graph_prefix_filter_max_staleness_ms".to_string(),
+        60000,
+    )]));
+    tunables
+}
+
+#[fbinit::test]
+async fn test_prefix_filter_storage_store_and_fetch(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (_, storage) = new_storage(&ctx).await?;
+
+    test_storage_store_and_fetch(&ctx, storage).await
+}
+
+#[fbinit::test]
+async fn test_prefix_filter_skip_tree(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (_, storage) = new_storage(&ctx).await?;
+
+    test_skip_tree(&ctx, storage).await
+}
+
+#[fbinit::test]
+async fn test_prefix_filter_find_by_prefix(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (_, storage) = new_storage(&ctx).await?;
+
+    test_find_by_prefix(&ctx, storage).await
+}
+
+#[fbinit::test]
+async fn test_prefix_filter_ancestors_difference(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (_, storage) = new_storage(&ctx).await?;
+
+    test_ancestors_difference(&ctx, storage).await
+}
+
+#[fbinit::test]
+async fn test_prefix_filter_changesets_added_elsewhere(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (sql_storage, storage) = new_storage(&ctx).await?;
+
+    // Changesets that are added by another server, and so bypass the
+    // filter, are still found, as the filter isn't trusted by default.
+    from_dag(&ctx, "AAAA1-AAAA2-AAAA3", sql_storage).await?;
+    assert!(
+        storage
+            .fetch_edges(&ctx, name_cs_id("AAAA2"))
+            .await?
+            .is_some()
+    );
+    assert_eq!(
+        storage
+            .find_by_prefix(&ctx, ChangesetIdPrefix::from_bytes("AAAA3")?, 10)
+            .await?,
+        ChangesetIdsResolvedFromPrefix::Single(name_cs_id("AAAA3"))
+    );
+
+    // Changesets that don't exist are not.
+    assert!(
+        storage
+            .fetch_edges(&ctx, name_cs_id("BBBB1"))
+            .await?
+            .is_none()
+    );
+    assert_eq!(
+        storage
+            .find_by_prefix(&ctx, ChangesetIdPrefix::from_bytes("BBBB")?, 10)
+            .await?,
+        ChangesetIdsResolvedFromPrefix::NoMatch
+    );
+    let fetched = storage
+        .fetch_many_edges(
+            &ctx,
+            &[name_cs_id("AAAA1"), name_cs_id("BBBB1")],
+            Prefetch::None,
+        )
+        .await?;
+    assert_eq!(
+        fetched.keys().copied().collect::<Vec<_>>(),
+        vec![name_cs_id("AAAA1")]
+    );
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_prefix_filter_persisted(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let sql_storage = new_sql_storage();
+    let blobstore: Arc<dyn Blobstore> = Arc::new(Memblob::default());
+    from_dag(&ctx, "AAAA1-AAAA2", sql_storage.clone()).await?;
+
+    // Servers don't build the filter themselves, so nothing is filtered
+    // until it has been persisted.
+    let prefix_filter = Arc::new(PrefixFilter::new(sql_storage.clone(), blobstore.clone()));
+    prefix_filter.update(&ctx).await?;
+    assert!(prefix_filter.filter.load_full().is_none());
+    prefix_filter.update_persisted(&ctx, false).await?;
+
+    // Another server loads the persisted filter, and catches up with the
+    // changesets that were added since it was persisted.
+    from_dag(&ctx, "AAAA3", sql_storage.clone()).await?;
+    let prefix_filter = Arc::new(PrefixFilter::new(sql_storage.clone(), blobstore));
+    prefix_filter.update(&ctx).await?;
+    let storage = PrefixFilterCommitGraphStorage::new(sql_storage, prefix_filter);
+
+    with_tunables_async(
+        trusted_filter_tunables(),
+        async {
+            for name in ["AAAA1", "AAAA2", "AAAA3"] {
+                assert!(storage.fetch_edges(&ctx, name_cs_id(name)).await?.is_some());
+            }
+
+            // Lookups of changesets that don't exist don't reach the
+            // database once the filter is trusted.
+            let sql_reads = ctx
+                .perf_counters()
+                .get_counter(PerfCounterType::SqlReadsReplica);
+            assert!(
+                storage
+                    .fetch_edges(&ctx, name_cs_id("BBBB1"))
+                    .await?
+                    .is_none()
+            );
+            assert_eq!(
+                storage
+                    .find_by_prefix(&ctx, ChangesetIdPrefix::from_bytes("BBBB")?, 10)
+                    .await?,
+                ChangesetIdsResolvedFromPrefix::NoMatch
+            );
+            assert_eq!(
+                ctx.perf_counters()
+                    .get_counter(PerfCounterType::SqlReadsReplica),
+                sql_reads
+            );
+            Ok::<_, anyhow::Error>(())
+        }
+        .boxed(),
+    )
+    .await
+}
+
+#[test]
+fn test_update_state_advance() {
+    let start = Instant::now();
+    let mut state = UpdateState::new(10);
+    state.seen.push_back((start, 20));
+    state.seen.push_back((start + Duration::from_secs(10), 30));
+    assert_eq!(state.last_id(), 30);
+
+    // Ids seen by recent updates aren't safe yet, as changesets with
+    // smaller ids may still become visible.
+    state.advance(start + REREAD_WINDOW / 2);
+    assert_eq!(state.safe_id, 10);
+
+    state.advance(start + REREAD_WINDOW);
+    assert_eq!(state.safe_id, 20);
+    assert_eq!(state.last_id(), 30);
+
+    state.advance(start + REREAD_WINDOW + Duration::from_secs(10));
+    assert_eq!(state.safe_id, 30);
+    assert!(state.seen.is_empty());
+}
@@ -13,6 +13,7 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::sync::Arc;
+use std::sync::Mutex;
 
 use anyhow::anyhow;
 use anyhow::Context;
@@ -69,23 +70,61 @@ impl SqlConstructFromMetadataDatabaseConfig for SqlCommitGraphStorageBuilder {}
 
 impl SqlCommitGraphStorageBuilder {
     pub fn build(self, opts: RendezVousOptions, repo_id: RepositoryId) -> SqlCommitGraphStorage {
-        SqlCommitGraphStorage {
-            repo_id,
-            read_connection: RendezVousConnection::new(
-                self.connections.read_connection,
-                "read",
-                opts,
-            ),
-            read_master_connection: RendezVousConnection::new(
-                self.connections.read_master_connection,
-                "read_master",
-                opts,
-            ),
-            write_connection: self.connections.write_connection,
+        SqlCommitGraphStorage::new(&self.connections, opts, repo_id)
+    }
+
+    /// Build storage that can be shared by all the repos whose commit graphs
+    /// are stored in this database.
+    pub fn build_multi_repo(self, opts: RendezVousOptions) -> MultiRepoCommitGraphStorage {
+        MultiRepoCommitGraphStorage {
+            connections: self.connections,
+            opts,
+            repos: Mutex::new(HashMap::new()),
         }
     }
 }
 
+/// Commit graph storage for all the repos whose commit graphs are stored in
+/// the same database.
+///
+/// The storage of each repo uses the connections of this storage, rather
+/// than opening its own, so that a server with many repos doesn't open a
+/// connection pool for each of them.  The storage of each repo only queries
+/// its own repo's commits.
+pub struct MultiRepoCommitGraphStorage {
+    connections: SqlConnections,
+    opts: RendezVousOptions,
+    repos: Mutex<HashMap<RepositoryId, Arc<SqlCommitGraphStorage>>>,
+}
+
+impl MultiRepoCommitGraphStorage {
+    /// Returns the storage for a repo, creating it the first time it is
+    /// needed.
+    pub fn repo(&self, repo_id: RepositoryId) -> Arc<SqlCommitGraphStorage> {
+        self.repos
+            .lock()
+            .expect("lock poisoned")
+            .entry(repo_id)
+            .or_insert_with(|| {
+                Arc::new(SqlCommitGraphStorage::new(&self.connections, self.opts, repo_id))
+            })
+            .clone()
+    }
+
+    /// The ids of the repos whose storage has been created.
+    pub fn repo_ids(&self) -> Vec<RepositoryId> {
+        let mut repo_ids: Vec<_> = self
+            .repos
+            .lock()
+            .expect("lock poisoned")
+            .keys()
+            .copied()
+            .collect();
+        repo_ids.sort();
+        repo_ids
+    }
+}
+
 #[derive(Clone)]
 struct RendezVousConnection {
     fetch_single: RendezVous<ChangesetId, ChangesetEdges>,
@@ -568,9 +607,59 @@ mononoke_queries! {
         LIMIT {limit}
         "
     }
+
+    read SelectChangesetsAfterId(repo_id: RepositoryId, after_id: u64, limit: usize) -> (u64, ChangesetId) {
+        "
+        SELECT id, cs_id
+        FROM commit_graph_edges
+        WHERE repo_id = {repo_id} AND id > {after_id}
+        ORDER BY id ASC
+        LIMIT {limit}
+        "
+    }
 }
 
 impl SqlCommitGraphStorage {
+    fn new(connections: &SqlConnections, opts: RendezVousOptions, repo_id: RepositoryId) -> Self {
+        // Fetches are batched by the rendezvous of each repo's storage, as
+        // each batch is fetched with a single query for a single repo.
+        SqlCommitGraphStorage {
+            repo_id,
+            read_connection: RendezVousConnection::new(
+                connections.read_connection.clone(),
+                "read",
+                opts,
+            ),
+            read_master_connection: RendezVousConnection::new(
+                connections.read_master_connection.clone(),
+                "read_master",
+                opts,
+            ),
+            write_connection: connections.write_connection.clone(),
+        }
+    }
+
+    /// Returns up to `limit` changesets that were added to the commit graph
+    /// after the changeset with the given id, in the order they were added,
+    /// along with their ids.  Reads from a replica, so changesets that were
+    /// just added may be missing.
+    pub async fn fetch_changesets_after(
+        &self,
+        ctx: &CoreContext,
+        after_id: u64,
+        limit: usize,
+    ) -> Result<Vec<(u64, ChangesetId)>> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+        Ok(SelectChangesetsAfterId::query(
+            &self.read_connection.conn,
+            &self.repo_id,
+            &after_id,
+            &limit,
+        )
+        .await?)
+    }
+
     fn collect_changeset_edges(
         fetched_edges: &[(
             ChangesetId,         // cs_id
@@ -715,12 +804,25 @@ impl SqlCommitGraphStorage {
         cs_id_to_cs_edges
     }
 
+    /// Fetch the edges of many changesets, abandoning the fetch once nobody
+    /// is waiting for it.
     async fn fetch_many_edges_impl(
         &self,
         ctx: &CoreContext,
         cs_ids: &[ChangesetId],
         prefetch: Prefetch,
         rendezvous: &RendezVousConnection,
+    ) -> Result<HashMap<ChangesetId, ChangesetEdges>> {
+        ctx.run_until_abandoned(self.select_many_edges(ctx, cs_ids, prefetch, rendezvous))
+            .await?
+    }
+
+    async fn select_many_edges(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+        prefetch: Prefetch,
+        rendezvous: &RendezVousConnection,
     ) -> Result<HashMap<ChangesetId, ChangesetEdges>> {
         if cs_ids.is_empty() {
             // This is actually NECESSARY, because SQL doesn't deal well with
@@ -9,6 +9,7 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use commit_graph_testlib::*;
+use commit_graph_types::storage::CommitGraphStorage;
 use context::CoreContext;
 use fbinit::FacebookInit;
 use mononoke_types::RepositoryId;
@@ -41,6 +42,18 @@ async fn test_sqlite_skip_tree(fb: FacebookInit) -> Result<()> {
     test_skip_tree(&ctx, storage).await
 }
 
+#[fbinit::test]
+async fn test_sqlite_octopus_merges(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let storage = Arc::new(
+        SqlCommitGraphStorageBuilder::with_sqlite_in_memory()
+            .unwrap()
+            .build(RendezVousOptions::for_test(), RepositoryId::new(1)),
+    );
+
+    test_octopus_merges(&ctx, storage).await
+}
+
 #[fbinit::test]
 async fn test_sqlite_p1_linear_tree(fb: FacebookInit) -> Result<()> {
     let ctx = CoreContext::test_mock(fb);
@@ -65,6 +78,18 @@ async fn test_sqlite_ancestors_difference(fb: FacebookInit) -> Result<()> {
     test_ancestors_difference(&ctx, storage).await
 }
 
+#[fbinit::test]
+async fn test_sqlite_ancestors_difference_segments(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let storage = Arc::new(
+        SqlCommitGraphStorageBuilder::with_sqlite_in_memory()
+            .unwrap()
+            .build(RendezVousOptions::for_test(), RepositoryId::new(1)),
+    );
+
+    test_ancestors_difference_segments(&ctx, storage).await
+}
+
 #[fbinit::test]
 async fn test_sqlite_find_by_prefix(fb: FacebookInit) -> Result<()> {
     let ctx = CoreContext::test_mock(fb);
@@ -100,3 +125,86 @@ async fn test_sqlite_ancestors_frontier_with(fb: FacebookInit) -> Result<()> {
 
     test_ancestors_frontier_with(&ctx, storage).await
 }
+
+#[fbinit::test]
+async fn test_sqlite_frontier_operations(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let storage = Arc::new(
+        SqlCommitGraphStorageBuilder::with_sqlite_in_memory()
+            .unwrap()
+            .build(RendezVousOptions::for_test(), RepositoryId::new(1)),
+    );
+
+    test_frontier_operations(&ctx, storage).await
+}
+
+#[fbinit::test]
+async fn test_sqlite_random_frontier_operations(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let storage = Arc::new(
+        SqlCommitGraphStorageBuilder::with_sqlite_in_memory()
+            .unwrap()
+            .build(RendezVousOptions::for_test(), RepositoryId::new(1)),
+    );
+
+    test_random_frontier_operations(&ctx, storage).await
+}
+
+#[fbinit::test]
+fn test_sqlite_against_model(fb: FacebookInit) {
+    let ctx = CoreContext::test_mock(fb);
+    test_storage_against_model(ctx, || {
+        Arc::new(
+            SqlCommitGraphStorageBuilder::with_sqlite_in_memory()
+                .unwrap()
+                .build(RendezVousOptions::for_test(), RepositoryId::new(1)),
+        )
+    });
+}
+
+#[fbinit::test]
+async fn test_sqlite_fetch_changesets_after(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let storage = Arc::new(
+        SqlCommitGraphStorageBuilder::with_sqlite_in_memory()
+            .unwrap()
+            .build(RendezVousOptions::for_test(), RepositoryId::new(1)),
+    );
+    from_dag(&ctx, "A-B-C", storage.clone()).await?;
+
+    let first = storage.fetch_changesets_after(&ctx, 0, 2).await?;
+    assert_eq!(
+        first.iter().map(|(_, cs_id)| *cs_id).collect::<Vec<_>>(),
+        vec![name_cs_id("A"), name_cs_id("B")]
+    );
+    let rest = storage.fetch_changesets_after(&ctx, first[1].0, 2).await?;
+    assert_eq!(
+        rest.iter().map(|(_, cs_id)| *cs_id).collect::<Vec<_>>(),
+        vec![name_cs_id("C")]
+    );
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_sqlite_multi_repo_storage(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let multi_repo_storage = SqlCommitGraphStorageBuilder::with_sqlite_in_memory()
+        .unwrap()
+        .build_multi_repo(RendezVousOptions::for_test());
+    let storage1 = multi_repo_storage.repo(RepositoryId::new(1));
+    let storage2 = multi_repo_storage.repo(RepositoryId::new(2));
+    assert!(Arc::ptr_eq(&storage1, &multi_repo_storage.repo(RepositoryId::new(1))));
+    assert_eq!(
+        multi_repo_storage.repo_ids(),
+        vec![RepositoryId::new(1), RepositoryId::new(2)]
+    );
+
+    from_dag(&ctx, "A-B-C", storage1.clone()).await?;
+    from_dag(&ctx, "A-D", storage2.clone()).await?;
+
+    assert!(storage1.fetch_edges(&ctx, name_cs_id("C")).await?.is_some());
+    assert!(storage1.fetch_edges(&ctx, name_cs_id("D")).await?.is_none());
+    assert!(storage2.fetch_edges(&ctx, name_cs_id("D")).await?.is_some());
+    assert!(storage2.fetch_edges(&ctx, name_cs_id("B")).await?.is_none());
+    Ok(())
+}
@@ -9,13 +9,18 @@
 //!
 //! Stores configuration and permission checkers for bookmarks
 
+use std::fmt;
+
 use anyhow::bail;
 use anyhow::Result;
 use bookmarks_types::BookmarkKey;
 use context::CoreContext;
+use itertools::Itertools;
 use metaconfig_types::BookmarkParams;
 use permission_checker::AclProvider;
 use permission_checker::BoxMembershipChecker;
+use permission_checker::MemberAllowlist;
+use permission_checker::MononokeIdentity;
 
 /// Repository bookmark attributes.
 #[facet::facet]
@@ -65,6 +70,12 @@ impl RepoBookmarkAttrs {
             .any(|attr| attr.params().only_fast_forward)
     }
 
+    /// Check if provided bookmark can only be moved by pushrebase
+    pub fn is_pushrebase_only(&self, bookmark: &BookmarkKey) -> bool {
+        self.select(bookmark)
+            .any(|attr| attr.params().pushrebase_only)
+    }
+
     /// Check if a bookmark config overrides whether date should be rewritten during pushrebase.
     /// Return None if there are no bookmark config overriding rewrite_dates.
     pub fn should_rewrite_dates(&self, bookmark: &BookmarkKey) -> Option<bool> {
@@ -85,34 +96,58 @@ impl RepoBookmarkAttrs {
         unixname: &str,
         bookmark: &BookmarkKey,
     ) -> bool {
+        self.check_allowed_user(ctx, unixname, bookmark)
+            .await
+            .is_ok()
+    }
+
+    /// Check if the user is allowed to move the specified bookmark, and if
+    /// not, describe who is.
+    pub async fn check_allowed_user(
+        &self,
+        ctx: &CoreContext,
+        unixname: &str,
+        bookmark: &BookmarkKey,
+    ) -> Result<(), BookmarkMoveDenial> {
         for attr in self.select(bookmark) {
-            let maybe_allowed = attr
-                .params()
-                .allowed_users
-                .as_ref()
-                .map(|re| re.is_match(unixname));
-
-            let maybe_member = if let Some(membership) = &attr.membership {
-                Some(membership.is_member(ctx.metadata().identities()).await)
-            } else {
-                None
-            };
-
-            // Check if the user is either allowed to access it or that they
-            // are a member of the allowed hipster group.
-            //
-            // If there is no allowlist and no configured group, then
-            // everyone is permitted access.
-            let allowed = match (maybe_allowed, maybe_member) {
-                (Some(x), Some(y)) => x || y,
-                (Some(x), None) | (None, Some(x)) => x,
-                (None, None) => true,
-            };
-            if !allowed {
-                return false;
+            if !attr.is_allowed_user(ctx, unixname).await {
+                return Err(attr.denial());
             }
         }
-        true
+        Ok(())
+    }
+}
+
+/// Describes who is allowed to move a bookmark, for a user that is not.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BookmarkMoveDenial {
+    /// Pattern that allowed users' unixnames match
+    pub allowed_users: Option<String>,
+    /// Groups whose members are allowed
+    pub allowed_groups: Vec<String>,
+    /// Allowed service identities
+    pub allowed_service_identities: Vec<String>,
+}
+
+impl fmt::Display for BookmarkMoveDenial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut allowed = Vec::new();
+        if let Some(allowed_users) = &self.allowed_users {
+            allowed.push(format!("users matching '{}'", allowed_users));
+        }
+        if !self.allowed_groups.is_empty() {
+            allowed.push(format!(
+                "members of {}",
+                self.allowed_groups.iter().join(", ")
+            ));
+        }
+        if !self.allowed_service_identities.is_empty() {
+            allowed.push(format!(
+                "services {}",
+                self.allowed_service_identities.iter().join(", ")
+            ));
+        }
+        write!(f, "only allowed for {}", allowed.join(" or "))
     }
 }
 
@@ -120,6 +155,8 @@ impl RepoBookmarkAttrs {
 pub struct BookmarkAttr {
     params: BookmarkParams,
     membership: Option<BoxMembershipChecker>,
+    group_memberships: Vec<BoxMembershipChecker>,
+    service_membership: Option<BoxMembershipChecker>,
 }
 
 impl BookmarkAttr {
@@ -128,19 +165,94 @@ impl BookmarkAttr {
             Some(hipster_group) => Some(acl_provider.group(hipster_group).await?),
             None => None,
         };
-        Ok(BookmarkAttr { params, membership })
+        let mut group_memberships = Vec::new();
+        for group in params.allowed_groups.iter() {
+            group_memberships.push(acl_provider.group(group).await?);
+        }
+        let service_membership = Self::service_membership(&params);
+        Ok(BookmarkAttr {
+            params,
+            membership,
+            group_memberships,
+            service_membership,
+        })
     }
 
     fn new_test(params: BookmarkParams) -> Result<BookmarkAttr> {
-        if params.allowed_hipster_group.is_some() {
-            bail!("Bookmark hipster groups are not supported in tests");
+        if params.allowed_hipster_group.is_some() || !params.allowed_groups.is_empty() {
+            bail!("Bookmark groups are not supported in tests");
         }
+        let service_membership = Self::service_membership(&params);
         Ok(BookmarkAttr {
             params,
             membership: None,
+            group_memberships: Vec::new(),
+            service_membership,
         })
     }
 
+    fn service_membership(params: &BookmarkParams) -> Option<BoxMembershipChecker> {
+        if params.allowed_service_identities.is_empty() {
+            return None;
+        }
+        let allowlist = params
+            .allowed_service_identities
+            .iter()
+            .map(|identity| MononokeIdentity::new(&identity.id_type, &identity.id_data))
+            .collect();
+        Some(MemberAllowlist::new(allowlist))
+    }
+
+    /// Check if the user satisfies any of the restrictions on who may move
+    /// bookmarks with these attributes.  If there are no restrictions, then
+    /// everyone is allowed.
+    async fn is_allowed_user(&self, ctx: &CoreContext, unixname: &str) -> bool {
+        let identities = ctx.metadata().identities();
+        let mut restricted = false;
+
+        if let Some(allowed_users) = &self.params.allowed_users {
+            if allowed_users.is_match(unixname) {
+                return true;
+            }
+            restricted = true;
+        }
+        let memberships = self
+            .membership
+            .iter()
+            .chain(self.group_memberships.iter())
+            .chain(self.service_membership.iter());
+        for membership in memberships {
+            if membership.is_member(identities).await {
+                return true;
+            }
+            restricted = true;
+        }
+        !restricted
+    }
+
+    fn denial(&self) -> BookmarkMoveDenial {
+        BookmarkMoveDenial {
+            allowed_users: self
+                .params
+                .allowed_users
+                .as_ref()
+                .map(|re| re.as_str().to_string()),
+            allowed_groups: self
+                .params
+                .allowed_hipster_group
+                .iter()
+                .chain(self.params.allowed_groups.iter())
+                .cloned()
+                .collect(),
+            allowed_service_identities: self
+                .params
+                .allowed_service_identities
+                .iter()
+                .map(|identity| format!("{}:{}", identity.id_type, identity.id_data))
+                .collect(),
+        }
+    }
+
     /// Bookmark parameters from config
     pub fn params(&self) -> &BookmarkParams {
         &self.params
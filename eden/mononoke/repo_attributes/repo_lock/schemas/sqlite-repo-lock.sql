@@ -8,5 +8,7 @@
 CREATE TABLE IF NOT EXISTS `repo_lock` (
   `repo_id` INTEGER PRIMARY KEY,
   `state` INTEGER NOT NULL,
-  `reason` VARCHAR(255)
+  `reason` VARCHAR(255),
+  `locked_by` VARCHAR(255),
+  `expected_unlock_time` BIGINT
 );
@@ -12,6 +12,7 @@ use anyhow::Context;
 use anyhow::Error;
 use async_trait::async_trait;
 use maplit::hashmap;
+pub use metaconfig_types::ReadOnlyInfo;
 use mononoke_types::RepositoryId;
 use sql::Connection;
 use sql::Transaction;
@@ -24,7 +25,7 @@ const DEFAULT_DB_MSG: &str = "Repo is locked in DB";
 
 #[derive(Eq, PartialEq, Debug)]
 pub enum RepoLockState {
-    Locked(String),
+    Locked(ReadOnlyInfo),
     Unlocked,
 }
 
@@ -40,23 +41,33 @@ pub trait RepoLock: Send + Sync {
 }
 
 mononoke_queries! {
-    write SetRepoLockStatus(repo_id: RepositoryId, state: u8, reason: Option<&str>) {
+    write SetRepoLockStatus(
+        repo_id: RepositoryId,
+        state: u8,
+        reason: Option<&str>,
+        locked_by: Option<&str>,
+        expected_unlock_time: Option<i64>,
+    ) {
         none,
-        mysql("INSERT INTO repo_lock (repo_id, state, reason)
-               VALUES ({repo_id}, {state}, {reason})
-               ON DUPLICATE KEY UPDATE state = {state}, reason = {reason}")
-
-        sqlite("INSERT OR REPLACE INTO repo_lock (repo_id, state, reason)
-                VALUES ({repo_id}, {state}, {reason})")
+        mysql("INSERT INTO repo_lock (repo_id, state, reason, locked_by, expected_unlock_time)
+               VALUES ({repo_id}, {state}, {reason}, {locked_by}, {expected_unlock_time})
+               ON DUPLICATE KEY UPDATE state = {state}, reason = {reason},
+                 locked_by = {locked_by}, expected_unlock_time = {expected_unlock_time}")
+
+        sqlite("INSERT OR REPLACE INTO repo_lock
+                  (repo_id, state, reason, locked_by, expected_unlock_time)
+                VALUES ({repo_id}, {state}, {reason}, {locked_by}, {expected_unlock_time})")
     }
 
-    read GetRepoLockStatus(repo_id: RepositoryId) -> (u8, Option<String>) {
-        "SELECT state, reason FROM repo_lock
+    read GetRepoLockStatus(
+        repo_id: RepositoryId,
+    ) -> (u8, Option<String>, Option<String>, Option<i64>) {
+        "SELECT state, reason, locked_by, expected_unlock_time FROM repo_lock
         WHERE repo_id = {repo_id}"
     }
 
-    read AllReposLockStatus() -> (RepositoryId, u8, Option<String>) {
-        "SELECT repo_id, state, reason FROM repo_lock"
+    read AllReposLockStatus() -> (RepositoryId, u8, Option<String>, Option<String>, Option<i64>) {
+        "SELECT repo_id, state, reason, locked_by, expected_unlock_time FROM repo_lock"
     }
 }
 
@@ -81,12 +92,22 @@ impl SqlConstruct for SqlRepoLock {
 
 impl SqlConstructFromMetadataDatabaseConfig for SqlRepoLock {}
 
-fn convert_sql_state((state, reason): &(u8, Option<String>)) -> Result<RepoLockState, Error> {
+fn convert_sql_state(
+    (state, reason, locked_by, expected_unlock_time): &(
+        u8,
+        Option<String>,
+        Option<String>,
+        Option<i64>,
+    ),
+) -> Result<RepoLockState, Error> {
     match state {
         0 => Ok(RepoLockState::Unlocked),
-        1 => Ok(RepoLockState::Locked(
-            reason.clone().unwrap_or_else(|| DEFAULT_DB_MSG.to_string()),
-        )),
+        1 => Ok(RepoLockState::Locked(ReadOnlyInfo {
+            reason: reason.clone().unwrap_or_else(|| DEFAULT_DB_MSG.to_string()),
+            locked_by: locked_by.clone(),
+            expected_unlock: *expected_unlock_time,
+            redirect_url: None,
+        })),
         _ => Err(anyhow!("Invalid repo lock state: {}", state)),
     }
 }
@@ -149,21 +170,26 @@ impl RepoLock for MutableRepoLock {
             .context("Failed to query repo lock status")?;
 
         rows.into_iter()
-            .map(|(repo_id, state, reason)| Ok((repo_id, convert_sql_state(&(state, reason))?)))
+            .map(|(repo_id, state, reason, locked_by, expected_unlock_time)| {
+                let state = convert_sql_state(&(state, reason, locked_by, expected_unlock_time))?;
+                Ok((repo_id, state))
+            })
             .collect()
     }
 
     async fn set_repo_lock(&self, lock_state: RepoLockState) -> Result<bool, Error> {
-        let (state, reason) = match lock_state {
+        let (state, info) = match lock_state {
             RepoLockState::Unlocked => (0, None),
-            RepoLockState::Locked(reason) => (1, Some(reason)),
+            RepoLockState::Locked(info) => (1, Some(info)),
         };
 
         SetRepoLockStatus::query(
             &self.sql_repo_lock.write_connection,
             &self.repo_id,
             &state,
-            &reason.as_deref(),
+            &info.as_ref().map(|info| info.reason.as_str()),
+            &info.as_ref().and_then(|info| info.locked_by.as_deref()),
+            &info.as_ref().and_then(|info| info.expected_unlock),
         )
         .await
         .map(|res| res.affected_rows() > 0)
@@ -173,23 +199,23 @@ impl RepoLock for MutableRepoLock {
 #[derive(Debug, Clone)]
 pub struct AlwaysLockedRepoLock {
     repo_id: RepositoryId,
-    reason: String,
+    info: ReadOnlyInfo,
 }
 
 impl AlwaysLockedRepoLock {
-    pub fn new(repo_id: RepositoryId, reason: String) -> Self {
-        Self { repo_id, reason }
+    pub fn new(repo_id: RepositoryId, info: ReadOnlyInfo) -> Self {
+        Self { repo_id, info }
     }
 }
 
 #[async_trait]
 impl RepoLock for AlwaysLockedRepoLock {
     async fn check_repo_lock(&self) -> Result<RepoLockState, Error> {
-        Ok(RepoLockState::Locked(self.reason.clone()))
+        Ok(RepoLockState::Locked(self.info.clone()))
     }
 
     async fn all_repos_lock(&self) -> Result<HashMap<RepositoryId, RepoLockState>, Error> {
-        Ok(hashmap! { self.repo_id => RepoLockState::Locked(self.reason.clone()) })
+        Ok(hashmap! { self.repo_id => RepoLockState::Locked(self.info.clone()) })
     }
 
     async fn set_repo_lock(&self, _: RepoLockState) -> Result<bool, Error> {
@@ -252,7 +278,7 @@ mod test {
 
         assert_eq!(
             repo_lock.check_repo_lock().await?,
-            RepoLockState::Locked("reason".to_string())
+            RepoLockState::Locked(ReadOnlyInfo::new("reason"))
         );
 
         Ok(())
@@ -281,12 +307,12 @@ mod test {
 
         assert!(
             repo_lock
-                .set_repo_lock(RepoLockState::Locked("test".into()))
+                .set_repo_lock(RepoLockState::Locked(ReadOnlyInfo::new("test")))
                 .await?,
         );
         assert_eq!(
             repo_lock.check_repo_lock().await?,
-            RepoLockState::Locked("test".into())
+            RepoLockState::Locked(ReadOnlyInfo::new("test"))
         );
         assert_eq!(
             other_repo_lock.check_repo_lock().await?,
@@ -295,4 +321,34 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_lock_details() -> Result<(), Error> {
+        let sql_repo_lock = SqlRepoLock::with_sqlite_in_memory()?;
+        let repo_id = RepositoryId::new(0);
+        let repo_lock = MutableRepoLock::new(sql_repo_lock, repo_id);
+
+        let info = ReadOnlyInfo {
+            reason: "Migrating storage".to_string(),
+            locked_by: Some("alice".to_string()),
+            expected_unlock: Some(1700000000),
+            redirect_url: None,
+        };
+        repo_lock
+            .set_repo_lock(RepoLockState::Locked(info.clone()))
+            .await?;
+        assert_eq!(
+            repo_lock.check_repo_lock().await?,
+            RepoLockState::Locked(info.clone())
+        );
+        assert_eq!(
+            repo_lock.all_repos_lock().await?,
+            hashmap! { repo_id => RepoLockState::Locked(info) }
+        );
+
+        repo_lock.set_repo_lock(RepoLockState::Unlocked).await?;
+        assert_eq!(repo_lock.check_repo_lock().await?, RepoLockState::Unlocked);
+
+        Ok(())
+    }
 }
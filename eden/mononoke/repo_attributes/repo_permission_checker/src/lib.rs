@@ -11,7 +11,10 @@ use anyhow::Context;
 use anyhow::Result;
 use async_trait::async_trait;
 use context::CoreContext;
+use metaconfig_types::AuthorizationPrincipal;
+use metaconfig_types::AuthorizationRule;
 use metaconfig_types::Identity;
+use metaconfig_types::RepoPermission;
 use permission_checker::AclProvider;
 use permission_checker::BoxPermissionChecker;
 use permission_checker::MononokeIdentity;
@@ -108,10 +111,43 @@ pub trait RepoPermissionChecker: Send + Sync + 'static {
     }
 }
 
+enum RulePrincipal {
+    Identity(MononokeIdentity),
+    Tier(BoxPermissionChecker),
+}
+
+/// An authorization rule from the common config, with its tier ACL loaded.
+struct PermissionRule {
+    principal: RulePrincipal,
+    permissions: Vec<RepoPermission>,
+}
+
+impl PermissionRule {
+    /// Check whether this rule grants `permission` to the given identities.
+    async fn grants(&self, identities: &MononokeIdentitySet, permission: RepoPermission) -> bool {
+        for granted in &self.permissions {
+            if !granted.implies(permission) {
+                continue;
+            }
+            let matches = match &self.principal {
+                RulePrincipal::Identity(identity) => identities.contains(identity),
+                RulePrincipal::Tier(checker) => {
+                    checker.check_set(identities, &[granted.as_str()]).await
+                }
+            };
+            if matches {
+                return true;
+            }
+        }
+        false
+    }
+}
+
 pub struct ProdRepoPermissionChecker {
     repo_permchecker: BoxPermissionChecker,
     service_permchecker: BoxPermissionChecker,
     repo_region_permcheckers: HashMap<String, BoxPermissionChecker>,
+    rules: Vec<PermissionRule>,
 }
 
 impl ProdRepoPermissionChecker {
@@ -123,6 +159,7 @@ impl ProdRepoPermissionChecker {
         repo_region_hipster_acls: Vec<&str>,
         reponame: &str,
         global_allowlist: &[Identity],
+        authorization_rules: &[AuthorizationRule],
     ) -> Result<Self> {
         let mut repo_permchecker_builder = PermissionCheckerBuilder::new();
         if let Some(acl_name) = repo_hipster_acl {
@@ -175,18 +212,52 @@ impl ProdRepoPermissionChecker {
             }
         }
 
+        let mut rules = Vec::with_capacity(authorization_rules.len());
+        for rule in authorization_rules {
+            let principal = match &rule.principal {
+                AuthorizationPrincipal::Identity(Identity { id_type, id_data }) => {
+                    RulePrincipal::Identity(MononokeIdentity::new(id_type, id_data))
+                }
+                AuthorizationPrincipal::Tier(tier) => RulePrincipal::Tier(
+                    acl_provider.tier_acl(tier).await.with_context(|| {
+                        format!("Failed to create PermissionChecker for {}", tier)
+                    })?,
+                ),
+            };
+            rules.push(PermissionRule {
+                principal,
+                permissions: rule.permissions.clone(),
+            });
+        }
+
         Ok(Self {
             repo_permchecker,
             service_permchecker,
             repo_region_permcheckers,
+            rules,
         })
     }
+
+    /// Check whether any of the authorization rules grants `permission`.
+    async fn check_rules(
+        &self,
+        identities: &MononokeIdentitySet,
+        permission: RepoPermission,
+    ) -> bool {
+        for rule in &self.rules {
+            if rule.grants(identities, permission).await {
+                return true;
+            }
+        }
+        false
+    }
 }
 
 #[async_trait]
 impl RepoPermissionChecker for ProdRepoPermissionChecker {
     async fn check_if_read_access_allowed(&self, identities: &MononokeIdentitySet) -> bool {
         self.repo_permchecker.check_set(identities, &["read"]).await
+            || self.check_rules(identities, RepoPermission::Read).await
     }
 
     async fn check_if_any_region_read_access_allowed(
@@ -220,18 +291,21 @@ impl RepoPermissionChecker for ProdRepoPermissionChecker {
         self.repo_permchecker
             .check_set(identities, &["draft"])
             .await
+            || self.check_rules(identities, RepoPermission::DraftPush).await
     }
 
     async fn check_if_write_access_allowed(&self, identities: &MononokeIdentitySet) -> bool {
         self.repo_permchecker
             .check_set(identities, &["write"])
             .await
+            || self.check_rules(identities, RepoPermission::PublicPush).await
     }
 
     async fn check_if_read_only_bypass_allowed(&self, identities: &MononokeIdentitySet) -> bool {
         self.repo_permchecker
             .check_set(identities, &["bypass_readonly"])
             .await
+            || self.check_rules(identities, RepoPermission::Admin).await
     }
 
     async fn check_if_service_writes_allowed(
@@ -294,3 +368,67 @@ impl RepoPermissionChecker for AlwaysAllowRepoPermissionChecker {
         true
     }
 }
+
+#[cfg(test)]
+mod test {
+    use permission_checker::InternalAclProvider;
+    use serde_json::json;
+
+    use super::*;
+
+    fn ids(ids: &[&str]) -> MononokeIdentitySet {
+        ids.iter().map(|id| id.parse().unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_authorization_rules() -> Result<()> {
+        let acl_provider = InternalAclProvider::new(serde_json::from_value(json!({
+            "tiers": {
+                "ci": {
+                    "actions": {
+                        "draft_push": ["SERVICE_IDENTITY:ci"],
+                    }
+                }
+            }
+        }))?);
+        let logger = Logger::root(slog::Discard, slog::o!());
+        let checker = ProdRepoPermissionChecker::new(
+            &logger,
+            acl_provider.as_ref(),
+            None,
+            None,
+            vec![],
+            "repo",
+            &[],
+            &[
+                AuthorizationRule {
+                    principal: AuthorizationPrincipal::Identity(Identity {
+                        id_type: "USER".to_string(),
+                        id_data: "releng".to_string(),
+                    }),
+                    permissions: vec![RepoPermission::PublicPush],
+                },
+                AuthorizationRule {
+                    principal: AuthorizationPrincipal::Tier("ci".to_string()),
+                    permissions: vec![RepoPermission::DraftPush],
+                },
+            ],
+        )
+        .await?;
+
+        let releng = ids(&["USER:releng"]);
+        assert!(checker.check_if_read_access_allowed(&releng).await);
+        assert!(checker.check_if_draft_access_allowed(&releng).await);
+        assert!(checker.check_if_write_access_allowed(&releng).await);
+        assert!(!checker.check_if_read_only_bypass_allowed(&releng).await);
+
+        let ci = ids(&["SERVICE_IDENTITY:ci"]);
+        assert!(checker.check_if_read_access_allowed(&ci).await);
+        assert!(checker.check_if_draft_access_allowed(&ci).await);
+        assert!(!checker.check_if_write_access_allowed(&ci).await);
+
+        let other = ids(&["USER:other"]);
+        assert!(!checker.check_if_read_access_allowed(&other).await);
+        Ok(())
+    }
+}
@@ -15,6 +15,7 @@ use metaconfig_types::RepoConfigRef;
 use mononoke_types::BonsaiChangeset;
 use mononoke_types::ChangesetId;
 use mononoke_types::MPath;
+use repo_bookmark_attrs::BookmarkMoveDenial;
 use repo_bookmark_attrs::RepoBookmarkAttrsRef;
 use repo_permission_checker::RepoPermissionCheckerRef;
 
@@ -23,6 +24,7 @@ use crate::error::DeniedAction;
 use crate::error::PermissionDenied;
 
 const GIT_IMPORT_SVC_WRITE_METHOD: &str = "git_import_operations";
+const OVERRIDE_PUSHREBASE_ONLY_SVC_WRITE_METHOD: &str = "override_pushrebase_only";
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum AuthorizationContext {
@@ -362,25 +364,46 @@ impl AuthorizationContext {
         repo: &(impl RepoConfigRef + RepoBookmarkAttrsRef),
         bookmark: &BookmarkKey,
     ) -> AuthorizationCheckOutcome {
-        let permitted = match self {
-            AuthorizationContext::FullAccess => true,
+        let permitted = self
+            .bookmark_modify_denial(ctx, repo, bookmark)
+            .await
+            .is_ok();
+        AuthorizationCheckOutcome::from_permitted(permitted)
+    }
+
+    /// Check whether the user is allowed to modify a particular bookmark,
+    /// and if not, describe who is, when that is known.
+    async fn bookmark_modify_denial(
+        &self,
+        ctx: &CoreContext,
+        repo: &(impl RepoConfigRef + RepoBookmarkAttrsRef),
+        bookmark: &BookmarkKey,
+    ) -> Result<(), Option<BookmarkMoveDenial>> {
+        match self {
+            AuthorizationContext::FullAccess => Ok(()),
             AuthorizationContext::Identity => {
                 let user = ctx.metadata().unix_name().unwrap_or("svcscm");
                 repo.repo_bookmark_attrs()
-                    .is_allowed_user(ctx, user, bookmark)
+                    .check_allowed_user(ctx, user, bookmark)
                     .await
+                    .map_err(Some)
 
                 // TODO: Check using ctx.identities, and deny if neither are provided.
             }
             AuthorizationContext::Service(service_name) => {
                 // Check this service is permitted to modify this bookmark.
-                repo.repo_config()
+                if repo
+                    .repo_config()
                     .source_control_service
                     .service_write_bookmark_permitted(service_name, bookmark)
+                {
+                    Ok(())
+                } else {
+                    Err(None)
+                }
             }
-            AuthorizationContext::ReadOnlyIdentity => false,
-        };
-        AuthorizationCheckOutcome::from_permitted(permitted)
+            AuthorizationContext::ReadOnlyIdentity => Err(None),
+        }
     }
 
     /// Require that the user is allowed to modify (create, update or delete)
@@ -391,10 +414,53 @@ impl AuthorizationContext {
         repo: &(impl RepoConfigRef + RepoBookmarkAttrsRef),
         bookmark: &BookmarkKey,
     ) -> Result<(), AuthorizationError> {
-        self.check_bookmark_modify(ctx, repo, bookmark)
+        self.bookmark_modify_denial(ctx, repo, bookmark)
+            .await
+            .map_err(|denial| {
+                self.permission_denied(
+                    ctx,
+                    DeniedAction::BookmarkModification(bookmark.clone(), denial),
+                )
+            })
+    }
+
+    /// Check whether the user is allowed to create a particular tag.
+    pub async fn check_tag_create(
+        &self,
+        ctx: &CoreContext,
+        repo: &impl RepoConfigRef,
+        tag: &str,
+    ) -> AuthorizationCheckOutcome {
+        let tag_config = &repo.repo_config().tag_config;
+        let permitted = match self {
+            AuthorizationContext::FullAccess => true,
+            AuthorizationContext::Identity => match ctx.metadata().unix_name() {
+                Some(user) => tag_config.is_allowed_user(user, tag),
+                // Users that aren't known can't be allowed in any protected
+                // namespace.
+                None => !tag_config.is_protected(tag),
+            },
+            AuthorizationContext::Service(_) => {
+                // Services may only create tags outside of protected
+                // namespaces.
+                !tag_config.is_protected(tag)
+            }
+            AuthorizationContext::ReadOnlyIdentity => false,
+        };
+        AuthorizationCheckOutcome::from_permitted(permitted)
+    }
+
+    /// Require that the user is allowed to create a particular tag.
+    pub async fn require_tag_create(
+        &self,
+        ctx: &CoreContext,
+        repo: &impl RepoConfigRef,
+        tag: &str,
+    ) -> Result<(), AuthorizationError> {
+        self.check_tag_create(ctx, repo, tag)
             .await
             .permitted_or_else(|| {
-                self.permission_denied(ctx, DeniedAction::BookmarkModification(bookmark.clone()))
+                self.permission_denied(ctx, DeniedAction::TagCreation(tag.to_string()))
             })
     }
 
@@ -437,6 +503,34 @@ impl AuthorizationContext {
             .permitted_or_else(|| self.permission_denied(ctx, DeniedAction::OverrideGitMapping))
     }
 
+    /// Check whether the caller is allowed to move bookmarks that can
+    /// otherwise only be moved by pushrebase.
+    pub async fn check_override_pushrebase_only(
+        &self,
+        _ctx: &CoreContext,
+        repo: &impl RepoConfigRef,
+    ) -> AuthorizationCheckOutcome {
+        let permitted = match self {
+            AuthorizationContext::FullAccess => true,
+            AuthorizationContext::Identity => {
+                // Users must always pushrebase onto these bookmarks.
+                false
+            }
+            AuthorizationContext::Service(service_name) => {
+                // Services are allowed to do this if they are configured to
+                // allow the method.
+                repo.repo_config()
+                    .source_control_service
+                    .service_write_method_permitted(
+                        service_name,
+                        OVERRIDE_PUSHREBASE_ONLY_SVC_WRITE_METHOD,
+                    )
+            }
+            AuthorizationContext::ReadOnlyIdentity => false,
+        };
+        AuthorizationCheckOutcome::from_permitted(permitted)
+    }
+
     /// Check whether the caller is allowed to invoke git-import related
     /// operations for the given repo.
     pub async fn check_git_import_operations(
@@ -495,11 +589,20 @@ pub enum RepoWriteOperation {
 
     /// Perform a megarepo sync
     MegarepoSync,
+
+    /// Add a signature to a changeset.
+    AddChangesetSignature,
+
+    /// Create a tag.
+    CreateTag,
+
+    /// Derive data for a changeset.
+    DeriveData,
 }
 
 impl RepoWriteOperation {
     /// Returns true if this is an operation that only affects draft commits.
-    fn is_draft(&self) -> bool {
+    pub(crate) fn is_draft(&self) -> bool {
         match self {
             RepoWriteOperation::CreateChangeset => true,
             RepoWriteOperation::CreateBookmark(kind)
@@ -507,6 +610,9 @@ impl RepoWriteOperation {
             | RepoWriteOperation::DeleteBookmark(kind)
             | RepoWriteOperation::LandStack(kind) => *kind == BookmarkKind::Scratch,
             RepoWriteOperation::MegarepoSync => false,
+            RepoWriteOperation::AddChangesetSignature => true,
+            RepoWriteOperation::CreateTag => false,
+            RepoWriteOperation::DeriveData => false,
         }
     }
 
@@ -520,6 +626,9 @@ impl RepoWriteOperation {
             RepoWriteOperation::DeleteBookmark(_) => "delete_bookmark",
             RepoWriteOperation::LandStack(_) => "land_stack",
             RepoWriteOperation::MegarepoSync => "megarepo_sync",
+            RepoWriteOperation::AddChangesetSignature => "add_changeset_signature",
+            RepoWriteOperation::CreateTag => "create_tag",
+            RepoWriteOperation::DeriveData => "derive_data",
         }
     }
 }
@@ -9,9 +9,11 @@ use std::fmt;
 
 use anyhow::Error;
 use bookmarks::BookmarkKey;
+use metaconfig_types::RepoPermission;
 use mononoke_types::ChangesetId;
 use mononoke_types::MPath;
 use permission_checker::MononokeIdentitySet;
+use repo_bookmark_attrs::BookmarkMoveDenial;
 use thiserror::Error;
 
 use crate::context::AuthorizationContext;
@@ -25,11 +27,33 @@ pub enum DeniedAction {
     PathRead(ChangesetId, Option<MPath>),
     RepoWrite(RepoWriteOperation),
     PathWrite(MPath),
-    BookmarkModification(BookmarkKey),
+    BookmarkModification(BookmarkKey, Option<BookmarkMoveDenial>),
+    TagCreation(String),
     OverrideGitMapping,
     GitImportOperation,
 }
 
+impl DeniedAction {
+    /// The repo permission that grants this action, if it is governed by a
+    /// single permission.  Path, bookmark, tag and service-specific actions are
+    /// governed by other configuration.
+    pub fn required_permission(&self) -> Option<RepoPermission> {
+        match self {
+            DeniedAction::FullRepoRead
+            | DeniedAction::RepoMetadataRead
+            | DeniedAction::PathRead(..) => Some(RepoPermission::Read),
+            DeniedAction::FullRepoDraft => Some(RepoPermission::DraftPush),
+            DeniedAction::RepoWrite(op) if op.is_draft() => Some(RepoPermission::DraftPush),
+            DeniedAction::RepoWrite(_) => Some(RepoPermission::PublicPush),
+            DeniedAction::PathWrite(_)
+            | DeniedAction::BookmarkModification(..)
+            | DeniedAction::TagCreation(_)
+            | DeniedAction::OverrideGitMapping
+            | DeniedAction::GitImportOperation => None,
+        }
+    }
+}
+
 impl fmt::Display for DeniedAction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -46,9 +70,13 @@ impl fmt::Display for DeniedAction {
             ),
             DeniedAction::RepoWrite(op) => write!(f, "Repo write access for {:?}", op),
             DeniedAction::PathWrite(path) => write!(f, "Repo write access to path '{}'", path),
-            DeniedAction::BookmarkModification(bookmark) => {
+            DeniedAction::BookmarkModification(bookmark, None) => {
                 write!(f, "Modification of bookmark '{}'", bookmark)
             }
+            DeniedAction::BookmarkModification(bookmark, Some(denial)) => {
+                write!(f, "Modification of bookmark '{}' ({})", bookmark, denial)
+            }
+            DeniedAction::TagCreation(tag) => write!(f, "Creation of tag '{}'", tag),
             DeniedAction::OverrideGitMapping => f.write_str("Overriding of Git mapping"),
             DeniedAction::GitImportOperation => {
                 f.write_str("Access for Git-import related operations")
@@ -64,6 +92,24 @@ pub struct PermissionDenied {
     pub(crate) identities: MononokeIdentitySet,
 }
 
+impl PermissionDenied {
+    /// The action that was denied.
+    pub fn denied_action(&self) -> &DeniedAction {
+        &self.denied_action
+    }
+
+    /// The identities of the caller that was denied.
+    pub fn identities(&self) -> &MononokeIdentitySet {
+        &self.identities
+    }
+
+    /// The repo permission the caller needs to perform the action, if it
+    /// is governed by a single permission.
+    pub fn required_permission(&self) -> Option<RepoPermission> {
+        self.denied_action.required_permission()
+    }
+}
+
 impl fmt::Display for PermissionDenied {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -76,7 +122,11 @@ impl fmt::Display for PermissionDenied {
             write!(f, "{}{}", delim, id)?;
             delim = ", ";
         }
-        f.write_str("]")
+        f.write_str("]")?;
+        if let Some(permission) = self.required_permission() {
+            write!(f, " (requires '{}' permission)", permission)?;
+        }
+        Ok(())
     }
 }
 
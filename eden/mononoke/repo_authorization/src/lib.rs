@@ -13,3 +13,5 @@ mod tests;
 pub use crate::context::AuthorizationContext;
 pub use crate::context::RepoWriteOperation;
 pub use crate::error::AuthorizationError;
+pub use crate::error::DeniedAction;
+pub use crate::error::PermissionDenied;
@@ -7,6 +7,7 @@
 
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -16,18 +17,30 @@ use context::CoreContext;
 use context::SessionContainer;
 use fbinit::FacebookInit;
 use futures::FutureExt;
+use maplit::btreeset;
 use maplit::hashmap;
 use maplit::hashset;
+use metaconfig_types::BookmarkParams;
+use metaconfig_types::Identity;
+use metaconfig_types::ProtectedTagNamespace;
 use metaconfig_types::RepoConfig;
+use metaconfig_types::RepoPermission;
 use metaconfig_types::ServiceWriteRestrictions;
+use metaconfig_types::TagConfig;
+use metadata::Metadata;
 use mononoke_types::PrefixTrie;
+use permission_checker::MononokeIdentity;
 use permission_checker::MononokeIdentitySet;
+use regex::Regex;
+use repo_bookmark_attrs::BookmarkMoveDenial;
 use repo_bookmark_attrs::RepoBookmarkAttrs;
 use repo_permission_checker::RepoPermissionChecker;
 use tunables::with_tunables_async;
 use tunables::MononokeTunables;
 
 use crate::AuthorizationContext;
+use crate::AuthorizationError;
+use crate::DeniedAction;
 use crate::RepoWriteOperation;
 
 #[facet::container]
@@ -154,16 +167,29 @@ async fn test_user_no_write_access(fb: FacebookInit) -> Result<()> {
             RepoWriteOperation::CreateBookmark(BookmarkKind::Scratch),
         )
         .await?;
-    assert!(
-        authz
-            .require_repo_write(
-                &ctx,
-                &repo,
-                RepoWriteOperation::LandStack(BookmarkKind::Publishing),
-            )
-            .await
-            .is_err()
-    );
+    let err = authz
+        .require_repo_write(
+            &ctx,
+            &repo,
+            RepoWriteOperation::LandStack(BookmarkKind::Publishing),
+        )
+        .await
+        .unwrap_err();
+    match err {
+        AuthorizationError::PermissionDenied(denied) => {
+            assert_eq!(
+                denied.denied_action(),
+                &DeniedAction::RepoWrite(RepoWriteOperation::LandStack(
+                    BookmarkKind::Publishing
+                ))
+            );
+            assert_eq!(
+                denied.required_permission(),
+                Some(RepoPermission::PublicPush)
+            );
+        }
+        AuthorizationError::Error(e) => return Err(e),
+    }
     authz
         .require_bookmark_modify(&ctx, &repo, &BookmarkKey::new("main")?)
         .await?;
@@ -393,6 +419,128 @@ async fn test_service_access(fb: FacebookInit) -> Result<()> {
     Ok(())
 }
 
+#[fbinit::test]
+async fn test_bookmark_allowed_identities(fb: FacebookInit) -> Result<()> {
+    let repo: Repo = test_repo_factory::TestRepoFactory::new(fb)?
+        .with_config_override(|config| {
+            config.bookmarks = vec![BookmarkParams {
+                bookmark: BookmarkKey::new("main").unwrap().into(),
+                hooks: vec![],
+                only_fast_forward: false,
+                allowed_users: Some(Regex::new("^releng$").unwrap().into()),
+                allowed_hipster_group: None,
+                allowed_groups: vec![],
+                allowed_service_identities: vec![Identity {
+                    id_type: "SERVICE_IDENTITY".to_string(),
+                    id_data: "lander".to_string(),
+                }],
+                rewrite_dates: None,
+                hooks_skip_ancestors_of: vec![],
+                ensure_ancestor_of: None,
+                allow_move_to_public_commits_without_hooks: false,
+                require_signed_commits: false,
+                pushrebase_only: false,
+                required_derived_data: vec![],
+                required_derived_data_budget: Duration::from_secs(0),
+                preconditions: vec![],
+            }];
+        })
+        .build()?;
+    let main = BookmarkKey::new("main")?;
+
+    // Users that don't match are denied, with a description of who is
+    // allowed.
+    let ctx = CoreContext::test_mock(fb);
+    let authz = AuthorizationContext::new(&ctx);
+    match authz.require_bookmark_modify(&ctx, &repo, &main).await {
+        Err(AuthorizationError::PermissionDenied(denied)) => {
+            assert_eq!(
+                denied.denied_action(),
+                &DeniedAction::BookmarkModification(
+                    main.clone(),
+                    Some(BookmarkMoveDenial {
+                        allowed_users: Some("^releng$".to_string()),
+                        allowed_groups: vec![],
+                        allowed_service_identities: vec!["SERVICE_IDENTITY:lander".to_string()],
+                    })
+                )
+            );
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    // Allowed service identities are permitted even though their unixname
+    // doesn't match.
+    let identities = btreeset! { MononokeIdentity::new("SERVICE_IDENTITY", "lander") };
+    let session = SessionContainer::builder(fb)
+        .metadata(Arc::new(Metadata::default().set_identities(identities)))
+        .build();
+    let ctx = CoreContext::test_mock_session(session);
+    let authz = AuthorizationContext::new(&ctx);
+    authz.require_bookmark_modify(&ctx, &repo, &main).await?;
+
+    // Other bookmarks are unrestricted.
+    authz
+        .require_bookmark_modify(&ctx, &repo, &BookmarkKey::new("other")?)
+        .await?;
+
+    Ok(())
+}
+
+async fn allowed(ctx: &CoreContext, authz: &AuthorizationContext, repo: &Repo, tag: &str) -> bool {
+    authz.require_tag_create(ctx, repo, tag).await.is_ok()
+}
+
+#[fbinit::test]
+async fn test_tag_create(fb: FacebookInit) -> Result<()> {
+    let repo: Repo = test_repo_factory::TestRepoFactory::new(fb)?
+        .with_config_override(|config| {
+            config.tag_config = TagConfig {
+                protected_namespaces: vec![ProtectedTagNamespace {
+                    prefix: "release/".to_string(),
+                    allowed_users: Some(Regex::new("^releng$").unwrap().into()),
+                }],
+            };
+        })
+        .build()?;
+    // Allowed users may create tags in the namespace.
+    let identities = btreeset! { MononokeIdentity::new("USER", "releng") };
+    let session = SessionContainer::builder(fb)
+        .metadata(Arc::new(Metadata::default().set_identities(identities)))
+        .build();
+    let ctx = CoreContext::test_mock_session(session);
+    let authz = AuthorizationContext::new(&ctx);
+    assert!(allowed(&ctx, &authz, &repo, "release/1.0").await);
+    assert!(allowed(&ctx, &authz, &repo, "v1.0").await);
+
+    // Other users, including ones that aren't known, may only create tags
+    // outside of it.
+    let identities = btreeset! { MononokeIdentity::new("USER", "alice") };
+    let session = SessionContainer::builder(fb)
+        .metadata(Arc::new(Metadata::default().set_identities(identities)))
+        .build();
+    let ctx = CoreContext::test_mock_session(session);
+    let authz = AuthorizationContext::new(&ctx);
+    assert!(!allowed(&ctx, &authz, &repo, "release/1.0").await);
+    assert!(allowed(&ctx, &authz, &repo, "v1.0").await);
+    let ctx = CoreContext::test_mock(fb);
+    let authz = AuthorizationContext::new(&ctx);
+    assert!(!allowed(&ctx, &authz, &repo, "release/1.0").await);
+    assert!(allowed(&ctx, &authz, &repo, "v1.0").await);
+
+    // Services may never create tags in protected namespaces.
+    let ctx = CoreContext::test_mock(fb);
+    let authz = AuthorizationContext::new_for_service_writes("test");
+    assert!(!allowed(&ctx, &authz, &repo, "release/1.0").await);
+    assert!(allowed(&ctx, &authz, &repo, "v1.0").await);
+
+    let ctx = CoreContext::test_mock(fb);
+    let authz = AuthorizationContext::new_bypass_access_control();
+    assert!(allowed(&ctx, &authz, &repo, "release/1.0").await);
+
+    Ok(())
+}
+
 #[fbinit::test]
 async fn test_user_readonly_instance(fb: FacebookInit) -> () {
     let ctx_session = SessionContainer::builder(fb).readonly(true).build();
@@ -0,0 +1,171 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Clone bundles are full bundles of a repo that are generated in advance
+//! and served from static storage.  Clients that support them download the
+//! bundle advertised in the `clonebundles` manifest when cloning, and then
+//! only pull the commits that were added since the bundle was generated.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use blobrepo::BlobRepo;
+use blobstore::Blobstore;
+use bytes::Bytes;
+use context::CoreContext;
+use futures::compat::Stream01CompatExt;
+use futures::TryStreamExt;
+use getbundle_response::create_getbundle_response;
+use getbundle_response::PhasesPart;
+use getbundle_response::SessionLfsParams;
+use mercurial_bundles::create_bundle_stream;
+use mercurial_types::HgChangesetId;
+use mononoke_types::BlobstoreBytes;
+use reachabilityindex::LeastCommonAncestorsHint;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+
+/// Key of the clone bundles manifest in the repo blobstore.
+const MANIFEST_KEY: &str = "clonebundles.manifest";
+
+/// Bundle spec of the bundles created by `create_clone_bundle`.
+pub const BUNDLESPEC: &str = "none-v2";
+
+/// A clone bundle advertised to clients.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CloneBundleEntry {
+    /// Where clients can download the bundle from.
+    pub url: String,
+    pub bundlespec: String,
+}
+
+impl CloneBundleEntry {
+    fn manifest_line(&self) -> String {
+        format!("{} BUNDLESPEC={}\n", self.url, self.bundlespec)
+    }
+}
+
+/// Write a bundle that contains all the ancestors of the given heads to
+/// the writer, as it is generated.  Returns the size of the bundle.
+pub async fn write_clone_bundle(
+    ctx: &CoreContext,
+    blobrepo: &BlobRepo,
+    lca_hint: &Arc<dyn LeastCommonAncestorsHint>,
+    heads: &[HgChangesetId],
+    lfs_params: &SessionLfsParams,
+    writer: &mut (impl AsyncWrite + Unpin),
+) -> Result<u64> {
+    let parts = create_getbundle_response(
+        ctx,
+        blobrepo,
+        vec![],
+        heads,
+        lca_hint,
+        PhasesPart::Yes,
+        lfs_params,
+    )
+    .await?;
+    let mut chunks = create_bundle_stream(parts, None).compat();
+    let mut size = 0;
+    while let Some(chunk) = chunks.try_next().await? {
+        writer.write_all(&chunk).await?;
+        size += chunk.len() as u64;
+    }
+    writer.flush().await?;
+    Ok(size)
+}
+
+/// Fetch the clone bundles manifest in the format expected by hg clients.
+/// The manifest is empty if no clone bundles have been uploaded.
+pub async fn fetch_clonebundles_manifest(
+    ctx: &CoreContext,
+    blobstore: &impl Blobstore,
+) -> Result<Bytes> {
+    let manifest = blobstore.get(ctx, MANIFEST_KEY).await?;
+    Ok(manifest.map_or_else(Bytes::new, |manifest| manifest.into_raw_bytes()))
+}
+
+/// Replace the clone bundles manifest.  Clients prefer the entries that
+/// come first.
+pub async fn upload_clonebundles_manifest(
+    ctx: &CoreContext,
+    blobstore: &impl Blobstore,
+    entries: &[CloneBundleEntry],
+) -> Result<()> {
+    let manifest = entries
+        .iter()
+        .map(CloneBundleEntry::manifest_line)
+        .collect::<String>();
+    blobstore
+        .put(
+            ctx,
+            MANIFEST_KEY.to_string(),
+            BlobstoreBytes::from_bytes(manifest),
+        )
+        .await
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use fbinit::FacebookInit;
+    use fixtures::Linear;
+    use fixtures::TestRepoFixture;
+    use memblob::Memblob;
+    use skiplist::SkiplistIndex;
+
+    use super::*;
+
+    #[fbinit::test]
+    async fn test_write_clone_bundle(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = Linear::getrepo(fb).await;
+        let lca_hint: Arc<dyn LeastCommonAncestorsHint> = Arc::new(SkiplistIndex::new());
+        let head = HgChangesetId::from_str("79a13814c5ce7330173ec04d279bf95ab3f652fb")?;
+
+        let mut bundle = Vec::new();
+        let size = write_clone_bundle(
+            &ctx,
+            &repo,
+            &lca_hint,
+            &[head],
+            &SessionLfsParams { threshold: None },
+            &mut bundle,
+        )
+        .await?;
+        assert_eq!(size, bundle.len() as u64);
+        assert!(bundle.starts_with(b"HG20"));
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_manifest(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let blobstore = Memblob::default();
+
+        assert_eq!(fetch_clonebundles_manifest(&ctx, &blobstore).await?, "");
+
+        let entries = vec![
+            CloneBundleEntry {
+                url: "https://cdn.example.com/repo-1.hg".to_string(),
+                bundlespec: BUNDLESPEC.to_string(),
+            },
+            CloneBundleEntry {
+                url: "https://cdn.example.com/repo-2.hg".to_string(),
+                bundlespec: "zstd-v2".to_string(),
+            },
+        ];
+        upload_clonebundles_manifest(&ctx, &blobstore, &entries).await?;
+        assert_eq!(
+            fetch_clonebundles_manifest(&ctx, &blobstore).await?,
+            "https://cdn.example.com/repo-1.hg BUNDLESPEC=none-v2\n\
+             https://cdn.example.com/repo-2.hg BUNDLESPEC=zstd-v2\n"
+        );
+        Ok(())
+    }
+}
@@ -701,6 +701,40 @@ async fn find_phase_heads(
     Ok(phase_heads)
 }
 
+/// Find the roots of the draft commits that are ancestors of `heads`, i.e.
+/// the draft commits none of whose parents are draft.
+pub async fn find_draft_roots(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    heads: &[HgChangesetId],
+    phases: &dyn Phases,
+) -> Result<Vec<HgChangesetId>, Error> {
+    let mut draft = HashMap::new();
+    traverse_draft_commits(
+        ctx,
+        repo,
+        phases,
+        heads,
+        |_public_bcs_id, _public_hg_cs_id| {},
+        |_draft_head_bcs_id, _draft_head_hg_cs_id| {},
+        |draft_bcs_id, draft_hg_cs_id| {
+            draft.insert(draft_bcs_id, draft_hg_cs_id);
+            true
+        },
+    )
+    .await?;
+
+    let entries = repo
+        .changesets()
+        .get_many(ctx, draft.keys().copied().collect())
+        .await?;
+    Ok(entries
+        .into_iter()
+        .filter(|entry| !entry.parents.iter().any(|p| draft.contains_key(p)))
+        .filter_map(|entry| draft.get(&entry.cs_id).copied())
+        .collect())
+}
+
 /// Traverses all draft commits, calling `draft_head_callback` on each draft
 /// head encountered, `draft_callback` on each draft commit encountered
 /// (including heads) and `public_callback` on the first public commit
@@ -443,6 +443,7 @@ mod test {
             .with_config_override(|config| {
                 config.filestore = Some(FilestoreParams {
                     chunk_size: 1,
+                    chunking_threshold: None,
                     concurrency: 1,
                 })
             })
@@ -7,6 +7,7 @@
 
 use anyhow::Error;
 use metaconfig_types::HgsqlName;
+use metaconfig_types::ReadOnlyInfo;
 use metaconfig_types::RepoReadOnly;
 use sql::mysql;
 use sql::mysql_async::prelude::ConvIr;
@@ -151,19 +152,19 @@ impl RepoReadWriteFetcher {
                 .await
                 .map(|item| match item {
                     Some((HgMononokeReadWrite::MononokeWrite, _)) => RepoReadOnly::ReadWrite,
-                    Some((_, reason)) => {
-                        RepoReadOnly::ReadOnly(reason.unwrap_or_else(|| DB_MSG.to_string()))
-                    }
-                    None => RepoReadOnly::ReadOnly(DEFAULT_MSG.to_string()),
+                    Some((_, reason)) => RepoReadOnly::ReadOnly(ReadOnlyInfo::new(
+                        reason.unwrap_or_else(|| DB_MSG.to_string()),
+                    )),
+                    None => RepoReadOnly::ReadOnly(ReadOnlyInfo::new(DEFAULT_MSG)),
                 }),
-            None => Ok(RepoReadOnly::ReadOnly(NOT_CONNECTED_MSG.to_string())),
+            None => Ok(RepoReadOnly::ReadOnly(ReadOnlyInfo::new(NOT_CONNECTED_MSG))),
         }
     }
 
     pub async fn readonly(&self) -> Result<RepoReadOnly, Error> {
         if self.sql_repo_read_write_status.is_some() {
             match self.readonly_config {
-                RepoReadOnly::ReadOnly(ref reason) => Ok(RepoReadOnly::ReadOnly(reason.clone())),
+                RepoReadOnly::ReadOnly(ref info) => Ok(RepoReadOnly::ReadOnly(info.clone())),
                 RepoReadOnly::ReadWrite => self.query_read_write_state().await,
             }
         } else {
@@ -214,13 +215,13 @@ mod test {
     async fn test_readonly_config_no_sqlite() {
         let fetcher = RepoReadWriteFetcher::new(
             None,
-            ReadOnly(CONFIG_MSG.to_string()),
+            ReadOnly(ReadOnlyInfo::new(CONFIG_MSG)),
             HgsqlName("repo".to_string()),
         );
 
         assert_eq!(
             fetcher.readonly().await.unwrap(),
-            ReadOnly(CONFIG_MSG.to_string())
+            ReadOnly(ReadOnlyInfo::new(CONFIG_MSG))
         );
     }
 
@@ -235,12 +236,12 @@ mod test {
         let sql_repo_read_write_status = SqlRepoReadWriteStatus::with_sqlite_in_memory().unwrap();
         let fetcher = RepoReadWriteFetcher::new(
             Some(sql_repo_read_write_status),
-            ReadOnly(CONFIG_MSG.to_string()),
+            ReadOnly(ReadOnlyInfo::new(CONFIG_MSG)),
             HgsqlName("repo".to_string()),
         );
         assert_eq!(
             fetcher.readonly().await.unwrap(),
-            ReadOnly(CONFIG_MSG.to_string())
+            ReadOnly(ReadOnlyInfo::new(CONFIG_MSG))
         );
     }
 
@@ -255,7 +256,7 @@ mod test {
         // As the DB hasn't been populated for this row, ensure that we mark the repo as locked.
         assert_eq!(
             fetcher.readonly().await.unwrap(),
-            ReadOnly(DEFAULT_MSG.to_string())
+            ReadOnly(ReadOnlyInfo::new(DEFAULT_MSG))
         );
 
         InsertState::query(
@@ -284,7 +285,7 @@ mod test {
 
         assert_eq!(
             fetcher.readonly().await.unwrap(),
-            ReadOnly(DB_MSG.to_string())
+            ReadOnly(ReadOnlyInfo::new(DB_MSG))
         );
     }
 
@@ -310,7 +311,7 @@ mod test {
 
         assert_eq!(
             fetcher.readonly().await.unwrap(),
-            ReadOnly("reason123".to_string())
+            ReadOnly(ReadOnlyInfo::new("reason123"))
         );
     }
 
@@ -325,7 +326,7 @@ mod test {
         // As the DB hasn't been populated for this row, ensure that we mark the repo as locked.
         assert_eq!(
             fetcher.readonly().await.unwrap(),
-            ReadOnly(DEFAULT_MSG.to_string())
+            ReadOnly(ReadOnlyInfo::new(DEFAULT_MSG))
         );
 
         InsertState::query(
@@ -341,7 +342,7 @@ mod test {
 
         assert_eq!(
             fetcher.readonly().await.unwrap(),
-            ReadOnly(DEFAULT_MSG.to_string())
+            ReadOnly(ReadOnlyInfo::new(DEFAULT_MSG))
         );
 
         InsertState::query(
@@ -369,7 +370,7 @@ mod test {
         // As the DB hasn't been populated for this row, ensure that we mark the repo as locked.
         assert_eq!(
             fetcher.readonly().await.unwrap(),
-            ReadOnly(DEFAULT_MSG.to_string())
+            ReadOnly(ReadOnlyInfo::new(DEFAULT_MSG))
         );
 
         fetcher
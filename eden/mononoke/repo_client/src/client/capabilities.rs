@@ -0,0 +1,135 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Capabilities advertised to wireproto clients in response to `hello`.
+
+use mercurial_types::percent_encode;
+use metaconfig_types::ClientVersion;
+use metaconfig_types::RepoConfig;
+use tunables::tunables;
+
+// `clonebundles` is only advertised by repos that add it to their extra
+// capabilities, as clients would otherwise fetch an empty manifest from
+// repos that have no clone bundles.
+const WIREPROTO_CAPS: &[&str] = &[
+    "clienttelemetry",
+    "lookup",
+    "known",
+    "getbundle",
+    "unbundle=HG10GZ,HG10BZ,HG10UN",
+    "unbundlereplay",
+    "gettreepack",
+    "remotefilelog",
+    "pushkey",
+    "stream-preferred",
+    "stream_option",
+    "streamreqs=generaldelta,lz4revlog,revlogv1",
+    "treeonly",
+    "knownnodes",
+    "designatednodes",
+    "getcommitdata",
+];
+
+const BUNDLE2_CAPS: &[(&str, &[&str])] = &[
+    ("HG20", &[]),
+    ("changegroup", &["02", "03"]),
+    ("b2x:infinitepush", &[]),
+    ("b2x:infinitepushscratchbookmarks", &[]),
+    ("pushkey", &[]),
+    ("treemanifestserver", &["True"]),
+    ("b2x:rebase", &[]),
+    ("b2x:rebasepackpart", &[]),
+    ("phases", &["heads"]),
+    ("obsmarkers", &["V1"]),
+    ("listkeys", &[]),
+];
+
+/// The capabilities a repo advertises.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Capabilities {
+    wireproto: Vec<String>,
+    bundle2: Vec<(String, Vec<String>)>,
+}
+
+impl Capabilities {
+    /// Assemble the capabilities a repo advertises to a client from the
+    /// repo's config and the version the client reported, if any.
+    pub(crate) fn for_repo(config: &RepoConfig, client_version: Option<&str>) -> Self {
+        let mut wireproto: Vec<String> = WIREPROTO_CAPS
+            .iter()
+            .map(|cap| cap.to_string())
+            .collect();
+        if config.lfs.threshold.is_some() {
+            wireproto.push("lfs".to_string());
+        }
+
+        let mut bundle2: Vec<(String, Vec<String>)> = BUNDLE2_CAPS
+            .iter()
+            .map(|(key, values)| {
+                let values = values.iter().map(|value| value.to_string()).collect();
+                (key.to_string(), values)
+            })
+            .collect();
+        if tunables()
+            .mutation_advertise_for_infinitepush()
+            .unwrap_or_default()
+        {
+            bundle2.push(("b2x:infinitepushmutation".to_string(), vec![]));
+        }
+
+        let mut caps = Self { wireproto, bundle2 };
+        let caps_config = &config.wireproto_capabilities;
+        for name in &caps_config.disabled {
+            caps.disable(name);
+        }
+        caps.wireproto.extend(caps_config.extra.iter().cloned());
+
+        let client_version = client_version.and_then(|v| v.parse::<ClientVersion>().ok());
+        for (name, min_version) in &caps_config.min_client_versions {
+            if client_version.as_ref().map_or(true, |v| v < min_version) {
+                caps.disable(name);
+            }
+        }
+        caps
+    }
+
+    /// Stop advertising a wireproto or bundle2 capability.
+    fn disable(&mut self, name: &str) {
+        self.wireproto.retain(|cap| cap_name(cap) != name);
+        self.bundle2.retain(|(key, _)| key != name);
+    }
+
+    /// The percent-encoded bundle2 capabilities, as advertised in the
+    /// `bundle2` wireproto capability.
+    fn encoded_bundle2(&self) -> String {
+        let encoded = self
+            .bundle2
+            .iter()
+            .map(|(key, values)| {
+                if values.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}={}", key, values.join(","))
+                }
+            })
+            .collect::<Vec<_>>();
+        percent_encode(&encoded.join("\n"))
+    }
+
+    /// The capabilities as returned by `hello`.
+    pub(crate) fn into_hello_caps(self) -> Vec<String> {
+        let bundle2 = format!("bundle2={}", self.encoded_bundle2());
+        let mut caps = self.wireproto;
+        caps.push(bundle2);
+        caps
+    }
+}
+
+/// The name of a wireproto capability, without its value.
+fn cap_name(cap: &str) -> &str {
+    cap.split_once('=').map_or(cap, |(name, _)| name)
+}
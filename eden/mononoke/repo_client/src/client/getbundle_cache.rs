@@ -0,0 +1,817 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::bail;
+use anyhow::Error;
+use anyhow::Result;
+use blobstore::Blobstore;
+use bookmarks::Bookmark;
+use bytes::Buf;
+use bytes::BufMut;
+use bytes::Bytes;
+use bytes::BytesMut;
+use context::CoreContext;
+use futures::channel::oneshot;
+use futures::future;
+use futures::future::FutureExt;
+use futures::future::Shared;
+use futures::stream;
+use futures::stream::BoxStream;
+use futures::stream::Stream;
+use futures::stream::StreamExt;
+use getbundle_response::SessionLfsParams;
+use lazy_static::lazy_static;
+use mercurial_types::HgChangesetId;
+use mononoke_types::hash::Blake2;
+use mononoke_types::hash::Context;
+use mononoke_types::BlobstoreBytes;
+use mononoke_types::RepositoryId;
+use slog::warn;
+use tunables::tunables;
+
+const MAGIC: &[u8] = b"getbundle.v2";
+const HEADER_LEN: usize = MAGIC.len() + 32 + 8 + 8 + 8;
+const CHUNK_HEADER_LEN: usize = 32 + 8 + 8;
+
+/// Cached bundles are stored in chunks of this size, so that neither
+/// caching nor serving a bundle holds all of it in memory.
+const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Responses are not cached when they are bigger than this, unless the
+/// tunable overrides it.
+const DEFAULT_MAX_CACHED_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Number of responses each repo can cache at once, unless the tunable
+/// overrides it.
+const DEFAULT_SLOTS: u64 = 16;
+
+/// Number of times a pull that isn't a clone has to miss the cache on a
+/// server before its response is cached, unless the tunable overrides it.
+const DEFAULT_MIN_MISSES: u64 = 3;
+
+/// Most pulls whose misses are counted at once.
+const MAX_COUNTED_MISSES: usize = 10_000;
+
+lazy_static! {
+    /// Responses that are being computed and cached by this server, so
+    /// that concurrent requests for them wait instead of computing them
+    /// again.
+    static ref IN_FLIGHT: Mutex<HashMap<GetbundleCacheKey, Shared<oneshot::Receiver<()>>>> =
+        Mutex::new(HashMap::new());
+
+    /// How many times responses that aren't cached were requested from this
+    /// server.
+    static ref MISSES: Mutex<HashMap<GetbundleCacheKey, u64>> = Mutex::new(HashMap::new());
+}
+
+/// How long cached getbundle responses are valid for, or None if the cache
+/// is disabled.
+pub fn getbundle_cache_ttl() -> Option<Duration> {
+    let ttl = tunables()
+        .repo_client_getbundle_cache_ttl_secs()
+        .unwrap_or_default();
+    if ttl > 0 {
+        Some(Duration::from_secs(ttl as u64))
+    } else {
+        None
+    }
+}
+
+pub fn getbundle_cache_max_bytes() -> u64 {
+    let max_bytes = tunables()
+        .repo_client_getbundle_cache_max_bytes()
+        .unwrap_or_default();
+    if max_bytes > 0 {
+        max_bytes as u64
+    } else {
+        DEFAULT_MAX_CACHED_BYTES
+    }
+}
+
+fn getbundle_cache_slots() -> u64 {
+    let slots = tunables()
+        .repo_client_getbundle_cache_slots()
+        .unwrap_or_default();
+    if slots > 0 {
+        slots as u64
+    } else {
+        DEFAULT_SLOTS
+    }
+}
+
+fn getbundle_cache_min_misses() -> u64 {
+    let min_misses = tunables()
+        .repo_client_getbundle_cache_min_misses()
+        .unwrap_or_default();
+    if min_misses > 0 {
+        min_misses as u64
+    } else {
+        DEFAULT_MIN_MISSES
+    }
+}
+
+/// Key of a cached getbundle response.
+///
+/// The publishing bookmarks are part of the key, because both the phases
+/// and the bookmarks parts of the response depend on them.  This means a
+/// bookmark move invalidates everything that was cached before it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GetbundleCacheKey {
+    hash: Blake2,
+    is_clone: bool,
+}
+
+impl GetbundleCacheKey {
+    /// Returns the key for a request, or None if the request shouldn't be
+    /// cached.  Only requests for heads that publishing bookmarks point to
+    /// are cached, as those are the ones that many clients make at once
+    /// (e.g. clones at master).
+    pub fn new(
+        repo_id: RepositoryId,
+        heads: &[HgChangesetId],
+        common: &[HgChangesetId],
+        use_phases: bool,
+        listkeys: &[Vec<u8>],
+        lfs_params: &SessionLfsParams,
+        bookmarks: &HashMap<Bookmark, HgChangesetId>,
+    ) -> Option<Self> {
+        if heads.is_empty()
+            || !heads
+                .iter()
+                .all(|head| bookmarks.values().any(|cs_id| cs_id == head))
+        {
+            return None;
+        }
+
+        let mut ctx = Context::new(b"getbundle");
+        ctx.update(repo_id.id().to_be_bytes());
+        let mut heads = heads.to_vec();
+        heads.sort();
+        heads.dedup();
+        for head in heads {
+            ctx.update(b"h");
+            ctx.update(head.as_bytes());
+        }
+        let mut common = common.to_vec();
+        common.sort();
+        common.dedup();
+        for cs_id in common {
+            ctx.update(b"c");
+            ctx.update(cs_id.as_bytes());
+        }
+        ctx.update(if use_phases { b"p1" } else { b"p0" });
+        let mut listkeys = listkeys.to_vec();
+        listkeys.sort();
+        for namespace in listkeys {
+            ctx.update(b"l");
+            ctx.update((namespace.len() as u64).to_be_bytes());
+            ctx.update(namespace);
+        }
+        if let Some(threshold) = lfs_params.threshold {
+            ctx.update(b"f");
+            ctx.update(threshold.to_be_bytes());
+        }
+        let mut bookmarks = bookmarks
+            .iter()
+            .map(|(bookmark, cs_id)| (bookmark.key().as_str(), cs_id))
+            .collect::<Vec<_>>();
+        bookmarks.sort();
+        for (name, cs_id) in bookmarks {
+            ctx.update(b"b");
+            ctx.update((name.len() as u64).to_be_bytes());
+            ctx.update(name);
+            ctx.update(cs_id.as_bytes());
+        }
+        Some(Self {
+            hash: ctx.finish(),
+            is_clone: common.is_empty(),
+        })
+    }
+
+    /// Whether a response that isn't cached is worth caching.  Clones are,
+    /// as many clients clone at the same heads, but incremental pulls
+    /// almost all have different common heads, so their responses are only
+    /// cached once the same pull has missed the cache a few times on this
+    /// server.
+    pub fn is_popular(&self) -> bool {
+        if self.is_clone {
+            return true;
+        }
+        let mut misses = MISSES.lock().expect("lock poisoned");
+        if misses.len() >= MAX_COUNTED_MISSES && !misses.contains_key(self) {
+            misses.clear();
+        }
+        let count = misses.entry(self.clone()).or_default();
+        *count += 1;
+        *count >= getbundle_cache_min_misses()
+    }
+
+    /// The slot the response is cached in.  Caching a response replaces
+    /// whatever was cached in its slot before, which bounds the number of
+    /// responses the cache serves at once.
+    fn slot(&self, slots: u64) -> u64 {
+        let mut hash = [0; 8];
+        hash.copy_from_slice(&self.hash.as_ref()[..8]);
+        u64::from_be_bytes(hash) % slots
+    }
+}
+
+fn header_blobstore_key(slot: u64) -> String {
+    format!("getbundle_cache.slot{}", slot)
+}
+
+/// Chunks are keyed by the write that cached them, so that caching another
+/// response in the same slot never overwrites the chunks of a response that
+/// is still being read.
+fn chunk_blobstore_key(header: &Header, index: u64) -> String {
+    format!(
+        "getbundle_cache.{}.{}.chunk{}",
+        header.key.hash, header.write_id, index
+    )
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+/// The header of a cached response, which is written after all of its
+/// chunks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Header {
+    key: GetbundleCacheKey,
+    write_id: u64,
+    expires: u64,
+    num_chunks: u64,
+}
+
+impl Header {
+    fn encode(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(HEADER_LEN);
+        buf.put_slice(MAGIC);
+        buf.put_slice(self.key.hash.as_ref());
+        buf.put_u64(self.write_id);
+        buf.put_u64(self.expires);
+        buf.put_u64(self.num_chunks);
+        buf.freeze()
+    }
+
+    /// Returns the header, or None if the cached value doesn't belong to
+    /// `key` or has expired.
+    fn decode(key: &GetbundleCacheKey, mut data: Bytes) -> Option<Self> {
+        if data.len() != HEADER_LEN || !data.starts_with(MAGIC) {
+            return None;
+        }
+        data.advance(MAGIC.len());
+        if &data[..32] != key.hash.as_ref() {
+            return None;
+        }
+        data.advance(32);
+        let header = Self {
+            key: key.clone(),
+            write_id: data.get_u64(),
+            expires: data.get_u64(),
+            num_chunks: data.get_u64(),
+        };
+        (header.expires > now_secs()).then_some(header)
+    }
+
+    fn encode_chunk(&self, index: u64, chunk: &[u8]) -> Bytes {
+        let mut buf = BytesMut::with_capacity(CHUNK_HEADER_LEN + chunk.len());
+        buf.put_slice(self.key.hash.as_ref());
+        buf.put_u64(self.write_id);
+        buf.put_u64(index);
+        buf.put_slice(chunk);
+        buf.freeze()
+    }
+
+    /// Returns the chunk, or None if it was written for a different
+    /// response.
+    fn decode_chunk(&self, index: u64, mut data: Bytes) -> Option<Bytes> {
+        if data.len() < CHUNK_HEADER_LEN || &data[..32] != self.key.hash.as_ref() {
+            return None;
+        }
+        data.advance(32);
+        if data.get_u64() != self.write_id || data.get_u64() != index {
+            return None;
+        }
+        Some(data)
+    }
+}
+
+/// Getbundle responses cached in a repo's blobstore.
+pub struct GetbundleCache<B> {
+    ctx: CoreContext,
+    blobstore: B,
+    key: GetbundleCacheKey,
+    slot: u64,
+}
+
+/// Whether this request should compute and cache the response, or wait
+/// for another request that is already doing so.
+pub enum Fill {
+    Leader(FillGuard),
+    Follower(Shared<oneshot::Receiver<()>>),
+}
+
+/// Wakes the requests waiting for a response to be cached once it is
+/// dropped, whether or not it was cached successfully.
+pub struct FillGuard {
+    key: GetbundleCacheKey,
+    _done: oneshot::Sender<()>,
+}
+
+impl Drop for FillGuard {
+    fn drop(&mut self) {
+        IN_FLIGHT.lock().expect("lock poisoned").remove(&self.key);
+    }
+}
+
+impl<B: Blobstore + Clone + 'static> GetbundleCache<B> {
+    pub fn new(ctx: CoreContext, blobstore: B, key: GetbundleCacheKey) -> Self {
+        let slot = key.slot(getbundle_cache_slots());
+        Self {
+            ctx,
+            blobstore,
+            key,
+            slot,
+        }
+    }
+
+    pub fn key(&self) -> &GetbundleCacheKey {
+        &self.key
+    }
+
+    /// Returns the cached response as a stream of chunks, or None if it
+    /// isn't cached.  Errors are logged and treated as misses.
+    pub async fn get(&self) -> Option<BoxStream<'static, Result<Bytes>>> {
+        match self.try_get().await {
+            Ok(bundle) => bundle,
+            Err(err) => {
+                warn!(
+                    self.ctx.logger(),
+                    "Failed to fetch cached getbundle: {:?}", err
+                );
+                None
+            }
+        }
+    }
+
+    async fn try_get(&self) -> Result<Option<BoxStream<'static, Result<Bytes>>>> {
+        let header = match self
+            .blobstore
+            .get(&self.ctx, &header_blobstore_key(self.slot))
+            .await?
+        {
+            Some(data) => match Header::decode(&self.key, data.into_raw_bytes()) {
+                Some(header) => header,
+                None => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+        // Fetch the first chunk before committing to the cached response,
+        // so that failing to fetch it falls back to computing the response.
+        let first = get_chunk(&self.ctx, &self.blobstore, &header, 0).await?;
+
+        let ctx = self.ctx.clone();
+        let blobstore = self.blobstore.clone();
+        let rest = stream::iter(1..header.num_chunks).then(move |index| {
+            let ctx = ctx.clone();
+            let blobstore = blobstore.clone();
+            let header = header.clone();
+            async move { get_chunk(&ctx, &blobstore, &header, index).await }
+        });
+        Ok(Some(stream::once(future::ready(Ok(first))).chain(rest).boxed()))
+    }
+
+    /// Claim the right to compute and cache the response on this server.
+    pub fn start_fill(&self) -> Fill {
+        let mut in_flight = IN_FLIGHT.lock().expect("lock poisoned");
+        if let Some(filled) = in_flight.get(&self.key) {
+            return Fill::Follower(filled.clone());
+        }
+        let (done, filled) = oneshot::channel();
+        in_flight.insert(self.key.clone(), filled.shared());
+        Fill::Leader(FillGuard {
+            key: self.key.clone(),
+            _done: done,
+        })
+    }
+
+    /// Pass `bundle` through, caching it as it goes.  The response is only
+    /// cached if all of it is streamed successfully and it is no bigger
+    /// than `max_bytes`.
+    pub fn write_through<T>(
+        self,
+        guard: FillGuard,
+        ttl: Duration,
+        max_bytes: u64,
+        bundle: impl Stream<Item = Result<T>> + Send + 'static,
+    ) -> impl Stream<Item = Result<T>>
+    where
+        T: AsRef<[u8]> + Send + 'static,
+    {
+        let writer = CacheWriter {
+            header: Header {
+                key: self.key.clone(),
+                write_id: rand::random(),
+                expires: now_secs().saturating_add(ttl.as_secs()),
+                num_chunks: 0,
+            },
+            cache: self,
+            max_bytes,
+            buffer: BytesMut::new(),
+            total_bytes: 0,
+            failed: false,
+        };
+        stream::unfold(Some((bundle.boxed(), writer, guard)), |state| async move {
+            let (mut bundle, mut writer, guard) = state?;
+            match bundle.next().await {
+                Some(Ok(chunk)) => {
+                    writer.write(chunk.as_ref()).await;
+                    Some((Ok(chunk), Some((bundle, writer, guard))))
+                }
+                // The response is incomplete, so it isn't cached.
+                Some(Err(err)) => Some((Err(err), None)),
+                None => {
+                    tokio::spawn(async move {
+                        writer.finish().await;
+                        drop(guard);
+                    });
+                    None
+                }
+            }
+        })
+    }
+}
+
+async fn get_chunk(
+    ctx: &CoreContext,
+    blobstore: &impl Blobstore,
+    header: &Header,
+    index: u64,
+) -> Result<Bytes> {
+    let data = blobstore
+        .get(ctx, &chunk_blobstore_key(header, index))
+        .await?
+        .and_then(|data| header.decode_chunk(index, data.into_raw_bytes()));
+    match data {
+        Some(data) => Ok(data),
+        None => bail!("Cached getbundle chunk {} is missing", index),
+    }
+}
+
+/// Writes a response to the cache in chunks as it is streamed.
+struct CacheWriter<B> {
+    cache: GetbundleCache<B>,
+    header: Header,
+    max_bytes: u64,
+    buffer: BytesMut,
+    total_bytes: u64,
+    failed: bool,
+}
+
+impl<B: Blobstore + Clone + 'static> CacheWriter<B> {
+    async fn write(&mut self, data: &[u8]) {
+        if self.failed {
+            return;
+        }
+        self.total_bytes += data.len() as u64;
+        if self.total_bytes > self.max_bytes {
+            self.abandon();
+            return;
+        }
+        self.buffer.extend_from_slice(data);
+        while self.buffer.len() >= CHUNK_SIZE {
+            let chunk = self.buffer.split_to(CHUNK_SIZE).freeze();
+            if let Err(err) = self.put_chunk(&chunk).await {
+                warn!(
+                    self.cache.ctx.logger(),
+                    "Failed to cache getbundle: {:?}", err
+                );
+                self.abandon();
+                return;
+            }
+        }
+    }
+
+    fn abandon(&mut self) {
+        self.failed = true;
+        self.buffer = BytesMut::new();
+    }
+
+    async fn put_chunk(&mut self, chunk: &[u8]) -> Result<(), Error> {
+        let index = self.header.num_chunks;
+        let value = BlobstoreBytes::from_bytes(self.header.encode_chunk(index, chunk));
+        self.cache
+            .blobstore
+            .put(
+                &self.cache.ctx,
+                chunk_blobstore_key(&self.header, index),
+                value,
+            )
+            .await?;
+        self.header.num_chunks += 1;
+        Ok(())
+    }
+
+    async fn finish(mut self) {
+        if self.failed {
+            return;
+        }
+        let res = async {
+            let chunk = std::mem::take(&mut self.buffer);
+            if !chunk.is_empty() || self.header.num_chunks == 0 {
+                self.put_chunk(&chunk).await?;
+            }
+            let value = BlobstoreBytes::from_bytes(self.header.encode());
+            self.cache
+                .blobstore
+                .put(&self.cache.ctx, header_blobstore_key(self.cache.slot), value)
+                .await
+        }
+        .await;
+        if let Err(err) = res {
+            warn!(
+                self.cache.ctx.logger(),
+                "Failed to cache getbundle: {:?}", err
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::anyhow;
+    use bookmarks::BookmarkKey;
+    use bookmarks::BookmarkKind;
+    use fbinit::FacebookInit;
+    use futures::stream::TryStreamExt;
+    use memblob::Memblob;
+    use mercurial_types_mocks::nodehash::ONES_CSID;
+    use mercurial_types_mocks::nodehash::THREES_CSID;
+    use mercurial_types_mocks::nodehash::TWOS_CSID;
+
+    use super::*;
+
+    fn bookmarks(cs_id: HgChangesetId) -> HashMap<Bookmark, HgChangesetId> {
+        let bookmark = Bookmark::new(
+            BookmarkKey::new("master").unwrap(),
+            BookmarkKind::PullDefaultPublishing,
+        );
+        HashMap::from([(bookmark, cs_id)])
+    }
+
+    fn cache_key(
+        repo_id: RepositoryId,
+        heads: &[HgChangesetId],
+        bookmarks: &HashMap<Bookmark, HgChangesetId>,
+    ) -> Option<GetbundleCacheKey> {
+        GetbundleCacheKey::new(
+            repo_id,
+            heads,
+            &[],
+            true,
+            &[b"bookmarks".to_vec()],
+            &SessionLfsParams { threshold: None },
+            bookmarks,
+        )
+    }
+
+    #[test]
+    fn test_key() {
+        let repo_id = RepositoryId::new(0);
+        let at_ones = bookmarks(ONES_CSID);
+        assert!(cache_key(repo_id, &[ONES_CSID], &at_ones).is_some());
+        // Heads that aren't bookmarked aren't cached.
+        assert_eq!(cache_key(repo_id, &[TWOS_CSID], &at_ones), None);
+        assert_eq!(cache_key(repo_id, &[ONES_CSID, TWOS_CSID], &at_ones), None);
+        // Moving any bookmark changes the key.
+        let stable = Bookmark::new(
+            BookmarkKey::new("stable").unwrap(),
+            BookmarkKind::Publishing,
+        );
+        let mut before = bookmarks(ONES_CSID);
+        before.insert(stable.clone(), TWOS_CSID);
+        let mut after = bookmarks(ONES_CSID);
+        after.insert(stable, THREES_CSID);
+        assert!(cache_key(repo_id, &[ONES_CSID], &before).is_some());
+        assert_ne!(
+            cache_key(repo_id, &[ONES_CSID], &before),
+            cache_key(repo_id, &[ONES_CSID], &after)
+        );
+        // Responses for different repos don't share keys.
+        assert_ne!(
+            cache_key(repo_id, &[ONES_CSID], &at_ones),
+            cache_key(RepositoryId::new(1), &[ONES_CSID], &at_ones)
+        );
+    }
+
+    #[test]
+    fn test_is_popular() {
+        let repo_id = RepositoryId::new(2);
+        let at_twos = bookmarks(TWOS_CSID);
+        let clone = cache_key(repo_id, &[TWOS_CSID], &at_twos).unwrap();
+        assert!(clone.is_popular());
+
+        // Incremental pulls are only cached once they have missed the cache
+        // a few times.
+        let pull = GetbundleCacheKey::new(
+            repo_id,
+            &[TWOS_CSID],
+            &[ONES_CSID],
+            true,
+            &[b"bookmarks".to_vec()],
+            &SessionLfsParams { threshold: None },
+            &at_twos,
+        )
+        .unwrap();
+        for _ in 1..DEFAULT_MIN_MISSES {
+            assert!(!pull.is_popular());
+        }
+        assert!(pull.is_popular());
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let repo_id = RepositoryId::new(0);
+        let key = cache_key(repo_id, &[ONES_CSID], &bookmarks(ONES_CSID)).unwrap();
+        let other_key = cache_key(repo_id, &[TWOS_CSID], &bookmarks(TWOS_CSID)).unwrap();
+        let header = Header {
+            key: key.clone(),
+            write_id: 1,
+            expires: now_secs() + 60,
+            num_chunks: 2,
+        };
+
+        let encoded = header.encode();
+        assert_eq!(Header::decode(&key, encoded.clone()), Some(header.clone()));
+        // Headers that belong to a different key, are truncated or have
+        // expired are rejected.
+        assert_eq!(Header::decode(&other_key, encoded.clone()), None);
+        assert_eq!(Header::decode(&key, encoded.slice(..HEADER_LEN - 1)), None);
+        let expired = Header {
+            expires: now_secs(),
+            ..header.clone()
+        };
+        assert_eq!(Header::decode(&key, expired.encode()), None);
+
+        // Chunks written for a different response, or a different part of
+        // it, are rejected.
+        let chunk = header.encode_chunk(1, b"bundle");
+        assert_eq!(
+            header.decode_chunk(1, chunk.clone()).as_deref(),
+            Some(&b"bundle"[..])
+        );
+        assert_eq!(header.decode_chunk(0, chunk.clone()), None);
+        let rewritten = Header {
+            write_id: 2,
+            ..header.clone()
+        };
+        assert_eq!(rewritten.decode_chunk(1, chunk), None);
+    }
+
+    #[fbinit::test]
+    async fn test_write_through(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let blobstore = Memblob::default();
+        let key = cache_key(RepositoryId::new(0), &[ONES_CSID], &bookmarks(ONES_CSID)).unwrap();
+        let cache = GetbundleCache::new(ctx.clone(), blobstore.clone(), key.clone());
+        assert!(cache.get().await.is_none());
+
+        let guard = match cache.start_fill() {
+            Fill::Leader(guard) => guard,
+            Fill::Follower(_) => return Err(anyhow!("expected to fill the cache")),
+        };
+        // Concurrent misses wait for the first one to fill the cache.
+        let filled = match cache.start_fill() {
+            Fill::Leader(_) => return Err(anyhow!("expected to wait for the cache")),
+            Fill::Follower(filled) => filled,
+        };
+
+        let bundle = vec![
+            Bytes::from(vec![1; CHUNK_SIZE + 1]),
+            Bytes::from(vec![2; CHUNK_SIZE]),
+            Bytes::from(vec![3; 10]),
+        ];
+        let streamed = cache
+            .write_through(
+                guard,
+                Duration::from_secs(60),
+                u64::MAX,
+                stream::iter(bundle.clone()).map(Ok),
+            )
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(streamed, bundle);
+        let _ = filled.await;
+
+        let cache = GetbundleCache::new(ctx, blobstore, key);
+        let cached = cache
+            .get()
+            .await
+            .ok_or_else(|| anyhow!("response was not cached"))?
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(cached.len(), 3);
+        assert_eq!(cached.concat(), bundle.concat());
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_refill_while_reading(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let blobstore = Memblob::default();
+        let key = cache_key(RepositoryId::new(3), &[ONES_CSID], &bookmarks(ONES_CSID)).unwrap();
+        let fill = |bundle: Vec<Bytes>| {
+            let cache = GetbundleCache::new(ctx.clone(), blobstore.clone(), key.clone());
+            async move {
+                let guard = match cache.start_fill() {
+                    Fill::Leader(guard) => guard,
+                    Fill::Follower(_) => return Err(anyhow!("expected to fill the cache")),
+                };
+                let filled = match cache.start_fill() {
+                    Fill::Leader(_) => return Err(anyhow!("expected to wait for the cache")),
+                    Fill::Follower(filled) => filled,
+                };
+                cache
+                    .write_through(
+                        guard,
+                        Duration::from_secs(60),
+                        u64::MAX,
+                        stream::iter(bundle).map(Ok),
+                    )
+                    .try_collect::<Vec<_>>()
+                    .await?;
+                // The header is written once the response has been streamed.
+                let _ = filled.await;
+                Ok(())
+            }
+        };
+
+        let bundle = vec![
+            Bytes::from(vec![1; CHUNK_SIZE]),
+            Bytes::from(vec![2; CHUNK_SIZE]),
+        ];
+        fill(bundle.clone()).await?;
+        let mut reader = GetbundleCache::new(ctx.clone(), blobstore.clone(), key.clone())
+            .get()
+            .await
+            .ok_or_else(|| anyhow!("response was not cached"))?;
+        let first = reader
+            .try_next()
+            .await?
+            .ok_or_else(|| anyhow!("response is empty"))?;
+
+        // Caching the response again, as another server might, doesn't
+        // affect requests that are still reading it.
+        fill(vec![
+            Bytes::from(vec![3; CHUNK_SIZE]),
+            Bytes::from(vec![4; CHUNK_SIZE]),
+        ])
+        .await?;
+        let rest = reader.try_collect::<Vec<_>>().await?;
+        assert_eq!([vec![first], rest].concat().concat(), bundle.concat());
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_write_through_too_big(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let blobstore = Memblob::default();
+        let key = cache_key(RepositoryId::new(0), &[TWOS_CSID], &bookmarks(TWOS_CSID)).unwrap();
+        let cache = GetbundleCache::new(ctx.clone(), blobstore.clone(), key.clone());
+        let guard = match cache.start_fill() {
+            Fill::Leader(guard) => guard,
+            Fill::Follower(_) => return Err(anyhow!("expected to fill the cache")),
+        };
+
+        let bundle = vec![Bytes::from_static(b"HG20"), Bytes::from_static(b"bundle")];
+        let streamed = cache
+            .write_through(
+                guard,
+                Duration::from_secs(60),
+                8,
+                stream::iter(bundle.clone()).map(Ok),
+            )
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(streamed, bundle);
+
+        // Responses that are too big are still sent, but not cached.
+        let cache = GetbundleCache::new(ctx, blobstore, key);
+        assert!(cache.get().await.is_none());
+        Ok(())
+    }
+}
@@ -22,6 +22,7 @@ use std::sync::Mutex;
 use std::time::Duration;
 use std::time::Instant;
 
+use anyhow::bail;
 use anyhow::format_err;
 use anyhow::Context;
 use anyhow::Error;
@@ -40,7 +41,9 @@ use bytes::Bytes;
 use bytes_old::BufMut as BufMutOld;
 use bytes_old::Bytes as BytesOld;
 use bytes_old::BytesMut as BytesMutOld;
+use clone_bundles::fetch_clonebundles_manifest;
 use cloned::cloned;
+use context::log_keys;
 use context::CoreContext;
 use context::LoggingContainer;
 use context::PerfCounterType;
@@ -80,12 +83,14 @@ use futures_old::Stream;
 use futures_stats::TimedFutureExt;
 use futures_stats::TimedStreamExt;
 use getbundle_response::create_getbundle_response;
+use getbundle_response::find_draft_roots;
 use getbundle_response::PhasesPart;
 use getbundle_response::SessionLfsParams;
 use hgproto::GetbundleArgs;
 use hgproto::GettreepackArgs;
 use hgproto::HgCommandRes;
 use hgproto::HgCommands;
+use hidden_changesets::HiddenChangesetsRef;
 use hooks::HookManagerArc;
 use hostname::get_hostname;
 use itertools::Itertools;
@@ -104,7 +109,6 @@ use mercurial_types::blobs::HgBlobChangeset;
 use mercurial_types::calculate_hg_node_id;
 use mercurial_types::convert_parents_to_remotefilelog_format;
 use mercurial_types::fetch_manifest_envelope;
-use mercurial_types::percent_encode;
 use mercurial_types::Delta;
 use mercurial_types::HgChangesetId;
 use mercurial_types::HgChangesetIdPrefix;
@@ -119,9 +123,11 @@ use mercurial_types::NULL_CSID;
 use mercurial_types::NULL_HASH;
 use metaconfig_types::RepoClientKnobs;
 use metaconfig_types::RepoConfigRef;
+use metaconfig_types::WireprotoTimeouts;
 use mononoke_api::Repo;
 use mononoke_types::hash::GitSha1;
 use mononoke_types::ChangesetId;
+use mononoke_types::Timestamp;
 use nonzero_ext::nonzero;
 use phases::PhasesArc;
 use rand::Rng;
@@ -135,6 +141,11 @@ use remotefilelog::GetpackBlobInfo;
 use repo_authorization::AuthorizationContext;
 use repo_blobstore::RepoBlobstoreRef;
 use repo_identity::RepoIdentityRef;
+use repo_tags::RepoTags;
+use repo_tags::RepoTagsRef;
+use repo_tags::Tag;
+use request_profiler::start_profiling;
+use request_profiler::RequestClass;
 use revisionstore_types::Metadata;
 use serde::Deserialize;
 use serde_json::json;
@@ -158,17 +169,34 @@ use wireproto_handler::BackupSourceRepo;
 
 use crate::errors::ErrorKind;
 
+mod capabilities;
+mod getbundle_cache;
 mod logging;
 mod monitor;
+mod push_recording;
 mod session_bookmarks_cache;
 mod tests;
 
+use capabilities::Capabilities;
+use getbundle_cache::getbundle_cache_max_bytes;
+use getbundle_cache::getbundle_cache_ttl;
+use getbundle_cache::Fill;
+use getbundle_cache::GetbundleCache;
+use getbundle_cache::GetbundleCacheKey;
 use logging::debug_format_manifest;
 use logging::debug_format_path;
 use logging::log_getpack_params_verbose;
 use logging::log_gettreepack_params_verbose;
 use logging::CommandLogger;
+pub use monitor::in_flight_commands;
+use monitor::InFlightCommand;
 use monitor::Monitor;
+use push_recording::claim_idempotency_key;
+use push_recording::finish_idempotency_key;
+use push_recording::idempotency_key;
+use push_recording::push_hash;
+use push_recording::record_push;
+use push_recording::PushDetails;
 use session_bookmarks_cache::SessionBookmarkCache;
 
 define_stats! {
@@ -190,6 +218,8 @@ define_stats! {
     null_linknode_gettreepack: timeseries(Rate, Sum),
     null_linknode_getpack: timeseries(Rate, Sum),
     getcommitdata_commit_count: timeseries(Rate, Sum),
+    getbundle_cache_hits: timeseries(Rate, Sum),
+    getbundle_cache_misses: timeseries(Rate, Sum),
 
     push_success: dynamic_timeseries("push_success.{}", (reponame: String); Rate, Sum),
     push_hook_failure: dynamic_timeseries("push_hook_failure.{}.{}", (reponame: String, hook_failure: String); Rate, Sum),
@@ -204,6 +234,7 @@ define_stats! {
 
 mod ops {
     pub static CLIENTTELEMETRY: &str = "clienttelemetry";
+    pub static CLONEBUNDLES: &str = "clonebundles";
     pub static HELLO: &str = "hello";
     pub static UNBUNDLE: &str = "unbundle";
     pub static HEADS: &str = "heads";
@@ -268,109 +299,76 @@ lazy_static! {
     static ref SLOW_REQUEST_THRESHOLD: Duration = Duration::from_secs(1);
 }
 
-fn clone_timeout() -> Duration {
-    let timeout = tunables()
-        .repo_client_clone_timeout_secs()
-        .unwrap_or_default();
-    if timeout > 0 {
-        Duration::from_secs(timeout as u64)
-    } else {
-        Duration::from_secs(4 * 60 * 60)
+/// Tunable that overrides the built-in timeout of a command, so that
+/// timeouts of repos that don't configure them can be raised or lowered in
+/// an emergency without a config push.
+fn timeout_override(command: &str) -> Option<Duration> {
+    let tunables = tunables();
+    let timeout = match command {
+        "getbundle" => tunables.repo_client_getbundle_timeout_secs(),
+        "getpackv1" | "getpackv2" => tunables.repo_client_getpack_timeout_secs(),
+        "stream_out_shallow" => tunables.repo_client_clone_timeout_secs(),
+        _ => tunables.repo_client_default_timeout_secs(),
     }
-}
-
-fn default_timeout() -> Duration {
-    let timeout = tunables()
-        .repo_client_default_timeout_secs()
-        .unwrap_or_default();
+    .unwrap_or_default();
     if timeout > 0 {
-        Duration::from_secs(timeout as u64)
+        Some(Duration::from_secs(timeout as u64))
     } else {
-        Duration::from_secs(15 * 60)
-    }
-}
-fn getbundle_timeout() -> Duration {
-    let timeout = tunables()
-        .repo_client_getbundle_timeout_secs()
-        .unwrap_or_default();
-    if timeout > 0 {
-        Duration::from_secs(timeout as u64)
-    } else {
-        Duration::from_secs(30 * 60)
+        None
     }
 }
 
-fn getpack_timeout() -> Duration {
-    let timeout = tunables()
-        .repo_client_getpack_timeout_secs()
+/// Most bytes of file content that a getpack request holds in memory at
+/// once, unless the tunable overrides it.
+const DEFAULT_GETPACK_MAX_IN_FLIGHT_BYTES: u64 = 100_000_000;
+
+fn getpack_max_in_flight_bytes() -> u64 {
+    let max_bytes = tunables()
+        .repo_client_getpack_max_in_flight_bytes()
         .unwrap_or_default();
-    if timeout > 0 {
-        Duration::from_secs(timeout as u64)
+    if max_bytes > 0 {
+        max_bytes as u64
     } else {
-        Duration::from_secs(5 * 60 * 60)
+        DEFAULT_GETPACK_MAX_IN_FLIGHT_BYTES
     }
 }
 
-fn wireprotocaps() -> Vec<String> {
-    vec![
-        "clienttelemetry".to_string(),
-        "lookup".to_string(),
-        "known".to_string(),
-        "getbundle".to_string(),
-        "unbundle=HG10GZ,HG10BZ,HG10UN".to_string(),
-        "unbundlereplay".to_string(),
-        "gettreepack".to_string(),
-        "remotefilelog".to_string(),
-        "pushkey".to_string(),
-        "stream-preferred".to_string(),
-        "stream_option".to_string(),
-        "streamreqs=generaldelta,lz4revlog,revlogv1".to_string(),
-        "treeonly".to_string(),
-        "knownnodes".to_string(),
-        "designatednodes".to_string(),
-        "getcommitdata".to_string(),
-    ]
-}
-
-fn bundle2caps() -> String {
-    let caps = {
-        let mut caps = vec![
-            ("HG20", vec![]),
-            ("changegroup", vec!["02", "03"]),
-            ("b2x:infinitepush", vec![]),
-            ("b2x:infinitepushscratchbookmarks", vec![]),
-            ("pushkey", vec![]),
-            ("treemanifestserver", vec!["True"]),
-            ("b2x:rebase", vec![]),
-            ("b2x:rebasepackpart", vec![]),
-            ("phases", vec!["heads"]),
-            ("obsmarkers", vec!["V1"]),
-            ("listkeys", vec![]),
-        ];
-
-        if tunables()
-            .mutation_advertise_for_infinitepush()
-            .unwrap_or_default()
-        {
-            caps.push(("b2x:infinitepushmutation", vec![]));
+fn getbundle_use_phases(phases: bool, bundlecaps: &HashSet<Vec<u8>>) -> bool {
+    if phases {
+        for cap in bundlecaps {
+            if let Some((cap_name, caps)) = parse_utf8_getbundle_caps(cap) {
+                if cap_name != "bundle2" {
+                    continue;
+                }
+                if let Some(phases) = caps.get("phases") {
+                    return phases.contains("heads");
+                }
+            }
         }
+    }
+    phases
+}
 
-        caps
-    };
-
-    let mut encodedcaps = vec![];
+/// Number of tags fetched at a time when listing the "tags" namespace.
+const LIST_TAGS_PAGE_SIZE: u64 = 10_000;
 
-    for (key, value) in &caps {
-        let encodedkey = key.to_string();
-        if !value.is_empty() {
-            let encodedvalue = value.join(",");
-            encodedcaps.push([encodedkey, encodedvalue].join("="));
-        } else {
-            encodedcaps.push(encodedkey)
+/// List every tag in the repo, a page at a time.  Clients expect the "tags"
+/// namespace to contain all tags, so unlike bookmarks it isn't truncated.
+async fn list_all_tags(
+    ctx: &CoreContext,
+    repo_tags: &dyn RepoTags,
+    page_size: u64,
+) -> Result<Vec<Tag>, Error> {
+    let mut tags: Vec<Tag> = Vec::new();
+    loop {
+        let after = tags.last().map(|tag| tag.name.as_str());
+        let page = repo_tags.list(ctx, "", after, page_size).await?;
+        let done = (page.len() as u64) < page_size;
+        tags.extend(page);
+        if done {
+            return Ok(tags);
         }
     }
-
-    percent_encode(&encodedcaps.join("\n"))
 }
 
 struct UndesiredPathLogger {
@@ -500,6 +498,8 @@ pub struct RepoClient {
     // In case `repo` is a backup of another repository `maybe_backup_repo_source` points to
     // a source for this repository.
     maybe_backup_repo_source: Option<BackupSourceRepo>,
+    // The correlator the client logs its own telemetry with, if it has sent one.
+    client_correlator: Arc<Mutex<Option<String>>>,
 }
 
 impl RepoClient {
@@ -523,6 +523,7 @@ impl RepoClient {
             knobs,
             request_perf_counters: Arc::new(PerfCounters::default()),
             maybe_backup_repo_source,
+            client_correlator: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -541,7 +542,8 @@ impl RepoClient {
         H: FnOnce(CoreContext, CommandLogger) -> F,
     {
         let (ctx, command_logger) = self.start_command(command, sampling_rate);
-        with_command_monitor(ctx.clone(), handler(ctx, command_logger)).boxify()
+        let in_flight = InFlightCommand::new(self.repo.inner_repo().repo_identity().name());
+        with_command_monitor(ctx.clone(), in_flight, handler(ctx, command_logger)).boxify()
     }
 
     fn command_stream<S, I, E, H>(
@@ -555,7 +557,8 @@ impl RepoClient {
         H: FnOnce(CoreContext, CommandLogger) -> S,
     {
         let (ctx, command_logger) = self.start_command(command, sampling_rate);
-        with_command_monitor(ctx.clone(), handler(ctx, command_logger)).boxify()
+        let in_flight = InFlightCommand::new(self.repo.inner_repo().repo_identity().name());
+        with_command_monitor(ctx.clone(), in_flight, handler(ctx, command_logger)).boxify()
     }
 
     fn start_command(
@@ -563,12 +566,25 @@ impl RepoClient {
         command: &str,
         sampling_rate: SamplingRate,
     ) -> (CoreContext, CommandLogger) {
-        info!(self.logging.logger(), "{}", command);
-
-        let logger = self
-            .logging
-            .logger()
-            .new(o!("command" => command.to_owned()));
+        info!(self.logging.logger(), "Start processing"; log_keys::COMMAND => command);
+
+        // Commands sent after the client's telemetry also carry its
+        // correlator, so that they can be joined to the client's logs.
+        let client_correlator = self
+            .client_correlator
+            .lock()
+            .expect("lock poisoned")
+            .clone();
+        let logger = match client_correlator {
+            Some(client_correlator) => self.logging.logger().new(o!(
+                log_keys::COMMAND => command.to_owned(),
+                log_keys::CORRELATOR => client_correlator
+            )),
+            None => self
+                .logging
+                .logger()
+                .new(o!(log_keys::COMMAND => command.to_owned())),
+        };
 
         let mut scuba = self.logging.scuba().clone();
         scuba
@@ -576,15 +592,39 @@ impl RepoClient {
             .add("command", command);
         scuba.clone().log_with_msg("Start processing", None);
 
-        let ctx =
-            self.session
-                .new_context_with_scribe(logger, scuba, self.logging.scribe().clone());
+        let deadline = Instant::now() + self.command_timeout(command);
+        let ctx = self
+            .session
+            .new_context_with_scribe(logger, scuba, self.logging.scribe().clone())
+            .clone_with_deadline(deadline);
 
         let command_logger = CommandLogger::new(ctx.clone(), self.request_perf_counters.clone());
 
         (ctx, command_logger)
     }
 
+    /// Give up on a command once the client has stopped waiting for it, if
+    /// it said how long it would wait.
+    fn with_client_deadline(ctx: CoreContext, deadline_ms: Option<u64>) -> CoreContext {
+        match deadline_ms {
+            Some(deadline_ms) => {
+                ctx.clone_with_deadline(Instant::now() + Duration::from_millis(deadline_ms))
+            }
+            None => ctx,
+        }
+    }
+
+    /// How long a command may run for before it is cancelled.
+    fn command_timeout(&self, command: &str) -> Duration {
+        self.repo
+            .inner_repo()
+            .repo_config()
+            .wireproto_timeouts
+            .configured_timeout(command)
+            .or_else(|| timeout_override(command))
+            .unwrap_or_else(|| WireprotoTimeouts::builtin_timeout(command))
+    }
+
     fn get_publishing_bookmarks_maybe_stale(
         &self,
         ctx: CoreContext,
@@ -615,7 +655,177 @@ impl RepoClient {
             })
     }
 
+    /// Tags, as a map from tag name to the hex hg changeset id of the
+    /// tagged changeset.
+    fn get_tags(
+        &self,
+        ctx: CoreContext,
+    ) -> impl Future<Item = HashMap<Vec<u8>, Vec<u8>>, Error = Error> {
+        let repo = self.repo.clone();
+        (async move {
+            let tags = list_all_tags(&ctx, repo.repo_tags(), LIST_TAGS_PAGE_SIZE).await?;
+            let cs_ids = tags.iter().map(|tag| tag.cs_id).collect::<Vec<_>>();
+            let hg_cs_ids = repo
+                .blob_repo()
+                .get_hg_bonsai_mapping(ctx.clone(), cs_ids)
+                .await?
+                .into_iter()
+                .map(|(hg_cs_id, cs_id)| (cs_id, hg_cs_id))
+                .collect::<HashMap<_, _>>();
+            Ok(tags
+                .into_iter()
+                .filter_map(|tag| {
+                    let hg_cs_id = hg_cs_ids.get(&tag.cs_id)?;
+                    let hash: Vec<u8> = hg_cs_id.into_nodehash().to_hex().into();
+                    Some((tag.name.into_bytes(), hash))
+                })
+                .collect())
+        })
+        .boxed()
+        .compat()
+    }
+
+    /// The "phases" listkeys namespace: the roots of the draft commits below
+    /// publishing bookmarks. Commits are only public if they are in the
+    /// phases store, so publishing bookmarks that aren't configured to
+    /// publish don't make their ancestors public.
+    fn get_phase_roots(
+        &self,
+        ctx: CoreContext,
+    ) -> impl Future<Item = HashMap<Vec<u8>, Vec<u8>>, Error = Error> {
+        let publishing_bookmarks = self.get_publishing_bookmarks_maybe_stale(ctx.clone());
+        let blobrepo = self.repo.blob_repo().clone();
+        let phases = self.repo.inner_repo().phases_arc();
+        (async move {
+            let heads = publishing_bookmarks
+                .compat()
+                .await?
+                .into_values()
+                .collect::<Vec<_>>();
+            let roots = find_draft_roots(&ctx, &blobrepo, &heads, phases.as_ref()).await?;
+            let mut res = roots
+                .into_iter()
+                .map(|root| {
+                    let hash: Vec<u8> = root.into_nodehash().to_hex().into();
+                    (hash, b"1".to_vec())
+                })
+                .collect::<HashMap<_, _>>();
+            res.insert(b"publishing".to_vec(), b"False".to_vec());
+            Ok(res)
+        })
+        .boxed()
+        .compat()
+    }
+
+    /// Fail if any of the requested changesets have been hidden, or aren't
+    /// served because the repo is a view of another repo, so that those
+    /// changesets can't be pulled.
+    fn ensure_not_hidden(
+        &self,
+        ctx: CoreContext,
+        hg_cs_ids: Vec<HgChangesetId>,
+    ) -> impl future::Future<Output = Result<(), Error>> {
+        let repo = self.repo.clone();
+        async move {
+            let mapping = repo
+                .blob_repo()
+                .get_hg_bonsai_mapping(ctx.clone(), hg_cs_ids)
+                .await?;
+            let cs_ids = mapping.iter().map(|(_, cs_id)| *cs_id).collect::<Vec<_>>();
+            let hidden = repo.hidden_changesets().get_hidden(&ctx, &cs_ids).await?;
+            let hidden_head = mapping.iter().find(|(_, cs_id)| hidden.contains(cs_id));
+            if let Some((hg_cs_id, _)) = hidden_head {
+                bail!("Changeset {} is hidden and can't be pulled", hg_cs_id);
+            }
+            let outside_view = repo.changesets_outside_view(&ctx, cs_ids).await?;
+            let unserved_head = mapping
+                .iter()
+                .find(|(_, cs_id)| outside_view.contains(cs_id));
+            if let Some((hg_cs_id, _)) = unserved_head {
+                bail!(
+                    "Changeset {} is not part of {} and can't be pulled",
+                    hg_cs_id,
+                    repo.name()
+                );
+            }
+            Ok(())
+        }
+    }
+
     fn create_bundle(&self, ctx: CoreContext, args: GetbundleArgs) -> BoxStream<BytesOld, Error> {
+        let ttl = match getbundle_cache_ttl() {
+            Some(ttl) => ttl,
+            None => return self.create_bundle_uncached(ctx, args),
+        };
+
+        let lfs_params = self.lfs_params();
+        let repo_id = self.repo.blob_repo().repo_identity().id();
+        let blobstore = self.repo.blob_repo().repo_blobstore().clone();
+        let publishing_bookmarks = self.get_publishing_bookmarks_maybe_stale(ctx.clone());
+        let use_phases = getbundle_use_phases(args.phases, &args.bundlecaps);
+        let heads = args.heads.clone();
+        let common = args.common.clone();
+        let listkeys = args.listkeys.clone();
+        let uncached = self.create_bundle_uncached(ctx.clone(), args);
+
+        async move {
+            let bookmarks = publishing_bookmarks.compat().await?;
+            let key = match GetbundleCacheKey::new(
+                repo_id,
+                &heads,
+                &common,
+                use_phases,
+                &listkeys,
+                &lfs_params,
+                &bookmarks,
+            ) {
+                Some(key) => key,
+                None => return Ok(uncached.compat().left_stream()),
+            };
+
+            let cache = GetbundleCache::new(ctx, blobstore, key);
+            if let Some(bundle) = cache.get().await {
+                STATS::getbundle_cache_hits.add_value(1);
+                let s = bundle.map_ok(bytes_ext::copy_from_new);
+                return Ok(s.boxed().right_stream().right_stream());
+            }
+            if !cache.key().is_popular() {
+                STATS::getbundle_cache_misses.add_value(1);
+                return Ok(uncached.compat().left_stream());
+            }
+
+            // Only one request on this server computes a missing response,
+            // the others wait for it to be cached.  If it isn't, they
+            // compute it without caching it.
+            let guard = match cache.start_fill() {
+                Fill::Leader(guard) => guard,
+                Fill::Follower(filled) => {
+                    let _ = filled.await;
+                    if let Some(bundle) = cache.get().await {
+                        STATS::getbundle_cache_hits.add_value(1);
+                        let s = bundle.map_ok(bytes_ext::copy_from_new);
+                        return Ok(s.boxed().right_stream().right_stream());
+                    }
+                    return Ok(uncached.compat().left_stream());
+                }
+            };
+            STATS::getbundle_cache_misses.add_value(1);
+
+            // Send the response while also writing it to the cache.
+            let s = cache.write_through(guard, ttl, getbundle_cache_max_bytes(), uncached.compat());
+            Ok::<_, Error>(s.boxed().left_stream().right_stream())
+        }
+        .try_flatten_stream()
+        .boxed()
+        .compat()
+        .boxify()
+    }
+
+    fn create_bundle_uncached(
+        &self,
+        ctx: CoreContext,
+        args: GetbundleArgs,
+    ) -> BoxStream<BytesOld, Error> {
         let lfs_params = self.lfs_params();
         let blobrepo = self.repo.blob_repo().clone();
         let mut bundle2_parts = vec![];
@@ -628,20 +838,7 @@ impl RepoClient {
             listkeys,
         } = args;
 
-        let mut use_phases = phases;
-        if use_phases {
-            for cap in &bundlecaps {
-                if let Some((cap_name, caps)) = parse_utf8_getbundle_caps(cap) {
-                    if cap_name != "bundle2" {
-                        continue;
-                    }
-                    if let Some(phases) = caps.get("phases") {
-                        use_phases = phases.contains("heads");
-                        break;
-                    }
-                }
-            }
-        }
+        let use_phases = getbundle_use_phases(phases, &bundlecaps);
         let pull_default_bookmarks = self.get_pull_default_bookmarks_maybe_stale(ctx.clone());
         let lca_hint: Arc<dyn LeastCommonAncestorsHint> =
             self.repo.inner_repo().skiplist_index_arc();
@@ -698,6 +895,14 @@ impl RepoClient {
         let undesired_path_logger =
             try_boxstream!(UndesiredPathLogger::new(ctx.clone(), self.repo.blob_repo()));
 
+        // Views only serve the trees of the changesets they serve.
+        let requested_trees = try_boxstream!(gettreepack_requested_trees(&params));
+        let served_check = {
+            cloned!(ctx);
+            let repo = self.repo.clone();
+            async move { ensure_entries_served(&ctx, &repo, requested_trees).await }
+        };
+
         let changed_entries = gettreepack_entries(ctx.clone(), self.repo.blob_repo(), params)
             .filter({
                 let mut used_hashes = HashSet::new();
@@ -729,7 +934,10 @@ impl RepoClient {
         // https://bz.mercurial-scm.org/show_bug.cgi?id=5646
         // TODO: possibly enable compression support once this is fixed.
         let compression = None;
-        part.into_future()
+        served_check
+            .boxed()
+            .compat()
+            .and_then(move |()| part)
             .map(move |part| create_bundle_stream(vec![part], compression))
             .flatten_stream()
             .boxify()
@@ -758,6 +966,7 @@ impl RepoClient {
             // That shouldn't be a problem because requests are quite small
             let getpack_params = Arc::new(Mutex::new(vec![]));
             let repo = self.repo.blob_repo().clone();
+            let view_repo = self.repo.clone();
 
             let lfs_params = self.lfs_params();
 
@@ -766,14 +975,21 @@ impl RepoClient {
             let validate_hash =
                 rand::thread_rng().gen_ratio(hash_validation_percentage as u32, 100);
             let getpack_buffer_size = 500;
+            let timeout = self.command_timeout(name);
 
             let request_stream = move || {
                 let content_stream = {
-                    cloned!(ctx, getpack_params, lfs_params, undesired_path_logger);
+                    cloned!(
+                        ctx,
+                        getpack_params,
+                        lfs_params,
+                        undesired_path_logger,
+                        view_repo
+                    );
 
                     async move {
                         let buffered_params = BufferedParams {
-                            weight_limit: 100_000_000,
+                            weight_limit: getpack_max_in_flight_bytes(),
                             buffer_size: getpack_buffer_size,
                         };
 
@@ -787,6 +1003,17 @@ impl RepoClient {
                             .add("getpack_paths", params.len())
                             .log_with_msg("Getpack Params", None);
 
+                        // Views only serve the files of the changesets they serve.
+                        let requested_files = params
+                            .iter()
+                            .flat_map(|(path, filenodes)| {
+                                filenodes.iter().map(move |filenode| {
+                                    (RepoPath::FilePath(path.clone()), *filenode)
+                                })
+                            })
+                            .collect();
+                        ensure_entries_served(&ctx, &view_repo, requested_files).await?;
+
                         let res = stream::iter(params.into_iter())
                             .map({
                                 cloned!(ctx, getpack_params, repo, lfs_params);
@@ -865,7 +1092,7 @@ impl RepoClient {
                 .try_flatten_stream();
 
                 let serialized_stream = content_stream
-                    .whole_stream_timeout(getpack_timeout())
+                    .whole_stream_timeout(timeout)
                     .yield_periodically()
                     .flatten_err()
                     .boxed()
@@ -905,7 +1132,6 @@ impl RepoClient {
                                 entry_count: contents.len() as u32,
                             });
                             for (filenode, content, metadata) in contents {
-                                let content = content.to_vec();
                                 let length = content.len() as u64;
 
                                 ctx.perf_counters().set_max_counter(
@@ -998,6 +1224,23 @@ impl RepoClient {
         })
     }
 
+    /// Whether a feature is enabled for this request in the repo config.
+    /// Requests are identified by the client's correlator, or by the session
+    /// id if the client hasn't sent one.
+    #[allow(dead_code)]
+    fn feature_enabled(&self, ctx: &CoreContext, feature: &str) -> bool {
+        let correlator = self
+            .client_correlator
+            .lock()
+            .expect("lock poisoned")
+            .clone()
+            .unwrap_or_else(|| ctx.metadata().session_id().to_string());
+        self.repo
+            .inner_repo()
+            .repo_config()
+            .feature_enabled(feature, &correlator)
+    }
+
     fn lfs_params(&self) -> SessionLfsParams {
         if self.force_lfs.load(Ordering::Relaxed) {
             SessionLfsParams {
@@ -1123,7 +1366,7 @@ impl RepoClient {
                     filter(ctx, nodes, hg_bcs_mapping).await
                 }
             }
-            .timeout(default_timeout())
+            .timeout(self.command_timeout(command))
             .flatten_err()
             .timed()
             .map(move |(stats, known_nodes)| {
@@ -1258,7 +1501,7 @@ impl HgCommands for RepoClient {
                 })
                 .collect()
                 .compat()
-                .timeout(default_timeout())
+                .timeout(self.command_timeout(ops::BETWEEN))
                 .flatten_err()
                 .timed()
                 .map(move |(stats, res)| {
@@ -1282,10 +1525,10 @@ impl HgCommands for RepoClient {
                 };
 
                 if let Some(client_correlator) = args.get(b"correlator" as &[u8]) {
-                    command_logger.add_scuba_extra(
-                        "client_correlator",
-                        String::from_utf8_lossy(client_correlator).into_owned(),
-                    );
+                    let client_correlator = String::from_utf8_lossy(client_correlator).into_owned();
+                    *self.client_correlator.lock().expect("lock poisoned") =
+                        Some(client_correlator.clone());
+                    command_logger.add_scuba_extra("client_correlator", client_correlator);
                 }
 
                 if let Some(command) = args.get(b"command" as &[u8]) {
@@ -1312,6 +1555,23 @@ impl HgCommands for RepoClient {
         )
     }
 
+    // @wireprotocommand('clonebundles')
+    fn clonebundles(&self) -> HgCommandRes<BytesOld> {
+        self.command_future(ops::CLONEBUNDLES, UNSAMPLED, |ctx, command_logger| {
+            let blobstore = self.repo.blob_repo().repo_blobstore().clone();
+            async move { fetch_clonebundles_manifest(&ctx, &blobstore).await }
+                .timeout(self.command_timeout(ops::CLONEBUNDLES))
+                .flatten_err()
+                .timed()
+                .map(move |(stats, res)| {
+                    command_logger.without_wireproto().finalize_command(&stats);
+                    res.map(bytes_ext::copy_from_new)
+                })
+                .boxed()
+                .compat()
+        })
+    }
+
     // @wireprotocommand('heads')
     fn heads(&self) -> HgCommandRes<HashSet<HgChangesetId>> {
         // Get a stream of heads and collect them into a HashSet
@@ -1322,7 +1582,7 @@ impl HgCommands for RepoClient {
             self.get_publishing_bookmarks_maybe_stale(ctx)
                 .map(|map| map.into_values().collect())
                 .compat()
-                .timeout(default_timeout())
+                .timeout(self.command_timeout(ops::HEADS))
                 .flatten_err()
                 .timed()
                 .map(move |(stats, res)| {
@@ -1421,6 +1681,16 @@ impl HgCommands for RepoClient {
                 },
             };
 
+            // Views don't serve every changeset, and those they don't serve
+            // mustn't be found by lookup.
+            let node_fut = node_fut
+                .and_then({
+                    cloned!(ctx);
+                    let repo = self.repo.clone();
+                    move |resolved| filter_lookup_to_view(ctx, repo, resolved).boxed().compat()
+                })
+                .boxify();
+
             // The lookup order:
             // If there is a git_lookup match, return that.
             // If there is an exact commit match, return that even if the key is the prefix of the hash.
@@ -1499,7 +1769,7 @@ impl HgCommands for RepoClient {
                 }
                 lookup_fut.compat().await
             }
-            .timeout(default_timeout())
+            .timeout(self.command_timeout(ops::LOOKUP))
             .flatten_err()
             .timed()
             .map(move |(stats, res)| {
@@ -1565,14 +1835,19 @@ impl HgCommands for RepoClient {
     // @wireprotocommand('getbundle', '*')
     fn getbundle(&self, args: GetbundleArgs) -> BoxStream<BytesOld, Error> {
         self.command_stream(ops::GETBUNDLE, UNSAMPLED, |ctx, command_logger| {
-            let s = self
-                .create_bundle(ctx, args)
-                .compat()
-                .whole_stream_timeout(getbundle_timeout())
+            let ctx = Self::with_client_deadline(ctx, args.deadline_ms);
+            let profiling = start_profiling(RequestClass::Getbundle);
+            let hidden_check = self.ensure_not_hidden(ctx.clone(), args.heads.clone());
+            let bundle = self.create_bundle(ctx, args);
+            let s = hidden_check
+                .map_ok(move |()| bundle.compat())
+                .try_flatten_stream()
+                .whole_stream_timeout(self.command_timeout(ops::GETBUNDLE))
                 .yield_periodically()
                 .flatten_err()
                 .timed({
                     move |stats| {
+                        drop(profiling);
                         if stats.completed {
                             if let Some(completion_time) = stats.completion_time {
                                 STATS::getbundle_ms
@@ -1592,11 +1867,13 @@ impl HgCommands for RepoClient {
 
     // @wireprotocommand('hello')
     fn hello(&self) -> HgCommandRes<HashMap<String, Vec<String>>> {
-        self.command_future(ops::HELLO, UNSAMPLED, |_ctx, command_logger| {
+        self.command_future(ops::HELLO, UNSAMPLED, |ctx, command_logger| {
             let mut res = HashMap::new();
-            let mut caps = wireprotocaps();
-            caps.push(format!("bundle2={}", bundle2caps()));
-            res.insert("capabilities".to_string(), caps);
+            let caps = Capabilities::for_repo(
+                self.repo.inner_repo().repo_config(),
+                ctx.metadata().client_version(),
+            );
+            res.insert("capabilities".to_string(), caps.into_hello_caps());
 
             future::ok(res)
                 .timed()
@@ -1624,6 +1901,30 @@ impl HgCommands for RepoClient {
                     .compat()
                     .boxify()
             })
+        } else if namespace == "tags" {
+            self.command_future(ops::LISTKEYS, UNSAMPLED, |ctx, command_logger| {
+                self.get_tags(ctx)
+                    .compat()
+                    .timed()
+                    .map(move |(stats, res)| {
+                        command_logger.without_wireproto().finalize_command(&stats);
+                        res
+                    })
+                    .compat()
+                    .boxify()
+            })
+        } else if namespace == "phases" {
+            self.command_future(ops::LISTKEYS, UNSAMPLED, |ctx, command_logger| {
+                self.get_phase_roots(ctx)
+                    .compat()
+                    .timed()
+                    .map(move |(stats, res)| {
+                        command_logger.without_wireproto().finalize_command(&stats);
+                        res
+                    })
+                    .compat()
+                    .boxify()
+            })
         } else {
             info!(
                 self.logging.logger(),
@@ -1696,7 +1997,7 @@ impl HgCommands for RepoClient {
                     ret.extend(books);
                     future::ready(Ok(ret))
                 })
-                .timeout(default_timeout())
+                .timeout(self.command_timeout(ops::LISTKEYSPATTERNS))
                 .flatten_err()
                 .timed()
                 .map(move |(stats, res)| {
@@ -1726,11 +2027,37 @@ impl HgCommands for RepoClient {
         self.session_bookmarks_cache.drop_cache();
 
         let lfs_params = self.lfs_params();
+        let timeout = self.command_timeout(ops::UNBUNDLE);
 
         let client = repoclient.clone();
         repoclient
             .command_future(ops::UNBUNDLE, UNSAMPLED, move |ctx, command_logger| {
+                let started_at = Timestamp::now();
+                let started = Instant::now();
+                let push_details = Arc::new(Mutex::new(PushDetails::default()));
+                let log_push = {
+                    cloned!(ctx, client, push_details);
+                    move |result: Result<_, BundleResolverError>| {
+                        let details = mem::take(&mut *push_details.lock().expect("lock poisoned"));
+                        let client_correlator = client
+                            .client_correlator
+                            .lock()
+                            .expect("lock poisoned")
+                            .clone();
+                        record_push(
+                            &ctx,
+                            &client.repo,
+                            client_correlator,
+                            started_at,
+                            started.elapsed(),
+                            details,
+                            &result,
+                        );
+                        result
+                    }
+                };
                 async move {
+                    let _profiling = start_profiling(RequestClass::Unbundle);
                     let repo = client.repo.inner_repo();
 
                     // To use unbundle wireproto command the user needs at least all-repo `draft` permission.
@@ -1759,68 +2086,127 @@ impl HgCommands for RepoClient {
                         maybe_backup_repo_source,
                     )
                     .await?;
+                    *push_details.lock().expect("lock poisoned") =
+                        PushDetails::from_action(&action);
+
+                    // A push made with an idempotency key that already
+                    // succeeded is a retry, which gets the original response.
+                    let idempotency_key = idempotency_key(&action);
+                    let replayed_response = match &idempotency_key {
+                        Some(key) => {
+                            let push_hash = push_hash(&action);
+                            claim_idempotency_key(&ctx, &client.repo, key, &push_hash, timeout)
+                                .await?
+                        }
+                        None => None,
+                    };
 
-                    let unbundle_future = async {
-                        maybe_validate_pushed_bonsais(&ctx, repo.as_blob_repo(), &maybereplaydata)
+                    let unbundle_future = {
+                        cloned!(ctx, client, push_details);
+                        async move {
+                            let repo = client.repo.inner_repo();
+                            maybe_validate_pushed_bonsais(
+                                &ctx,
+                                repo.as_blob_repo(),
+                                &maybereplaydata,
+                            )
                             .await?;
 
-                        match client.maybe_get_pushredirector_for_action(&ctx, &action)? {
-                            Some(push_redirector) => {
-                                // Push-redirection will cause
-                                // hooks to be run in the large
-                                // repo, but we must also run them
-                                // in the small repo.
-                                run_hooks(
+                            let response =
+                                match client.maybe_get_pushredirector_for_action(&ctx, &action)? {
+                                    Some(push_redirector) => {
+                                        // Push-redirection will cause
+                                        // hooks to be run in the large
+                                        // repo, but we must also run them
+                                        // in the small repo.
+                                        run_hooks(
+                                            &ctx,
+                                            repo.as_blob_repo(),
+                                            hook_manager.as_ref(),
+                                            &action,
+                                            CrossRepoPushSource::NativeToThisRepo,
+                                        )
+                                        .await?;
+
+                                        let ctx = ctx.with_mutated_scuba(|mut sample| {
+                                            sample.add(
+                                                "target_repo_name",
+                                                push_redirector
+                                                    .repo
+                                                    .inner_repo()
+                                                    .repo_identity()
+                                                    .name(),
+                                            );
+                                            sample.add(
+                                                "target_repo_id",
+                                                push_redirector
+                                                    .repo
+                                                    .inner_repo()
+                                                    .repo_identity()
+                                                    .id()
+                                                    .id(),
+                                            );
+                                            sample
+                                        });
+                                        ctx.scuba()
+                                            .clone()
+                                            .log_with_msg("Push redirected to large repo", None);
+                                        push_redirector
+                                            .run_redirected_post_resolve_action(&ctx, action)
+                                            .await?
+                                    }
+                                    None => {
+                                        run_post_resolve_action(
+                                            &ctx,
+                                            repo,
+                                            &lca_hint,
+                                            hook_manager.as_ref(),
+                                            action,
+                                            CrossRepoPushSource::NativeToThisRepo,
+                                        )
+                                        .await?
+                                    }
+                                };
+                            push_details
+                                .lock()
+                                .expect("lock poisoned")
+                                .update_from_response(&response);
+                            response
+                                .generate_bytes(
                                     &ctx,
                                     repo.as_blob_repo(),
-                                    hook_manager.as_ref(),
-                                    &action,
-                                    CrossRepoPushSource::NativeToThisRepo,
-                                )
-                                .await?;
-
-                                let ctx = ctx.with_mutated_scuba(|mut sample| {
-                                    sample.add(
-                                        "target_repo_name",
-                                        push_redirector.repo.inner_repo().repo_identity().name(),
-                                    );
-                                    sample.add(
-                                        "target_repo_id",
-                                        push_redirector.repo.inner_repo().repo_identity().id().id(),
-                                    );
-                                    sample
-                                });
-                                ctx.scuba()
-                                    .clone()
-                                    .log_with_msg("Push redirected to large repo", None);
-                                push_redirector
-                                    .run_redirected_post_resolve_action(&ctx, action)
-                                    .await?
-                            }
-                            None => {
-                                run_post_resolve_action(
-                                    &ctx,
-                                    repo,
+                                    pushrebase_params,
                                     &lca_hint,
-                                    hook_manager.as_ref(),
-                                    action,
-                                    CrossRepoPushSource::NativeToThisRepo,
+                                    &lfs_params,
+                                    respondlightly,
                                 )
-                                .await?
-                            }
+                                .await
+                                // Keep the kind of the error, so that e.g. hook
+                                // failures are counted and logged as such.
+                                .map_err(BundleResolverError::from)
                         }
-                        .generate_bytes(
-                            &ctx,
-                            repo.as_blob_repo(),
-                            pushrebase_params,
-                            &lca_hint,
-                            &lfs_params,
-                            respondlightly,
-                        )
-                        .await
                     };
 
-                    let response = unbundle_future.await?;
+                    let response = match (replayed_response, idempotency_key) {
+                        (Some(response), _) => {
+                            push_details.lock().expect("lock poisoned").mark_replayed();
+                            response
+                        }
+                        // The push has to finish, and its idempotency key with
+                        // it, even if the client disconnects: otherwise its
+                        // retry can't tell whether it landed.
+                        (None, Some(key)) => {
+                            cloned!(ctx, client);
+                            tokio::spawn(async move {
+                                let response = unbundle_future.await;
+                                finish_idempotency_key(&ctx, &client.repo, &key, &response).await;
+                                response
+                            })
+                            .await
+                            .map_err(Error::from)??
+                        }
+                        (None, None) => unbundle_future.await?,
+                    };
 
                     // There's a bookmarks race condition where the client requests bookmarks after we return commits to it,
                     // and is then confused because the bookmarks refer to commits that it doesn't know about. Ultimately,
@@ -1840,6 +2226,7 @@ impl HgCommands for RepoClient {
                         .await?;
                     Ok(response)
                 }
+                .map(log_push)
                 .inspect_err({
                     cloned!(reponame);
                     move |err| {
@@ -1862,7 +2249,7 @@ impl HgCommands for RepoClient {
                             RateLimitExceeded { .. } => {
                                 STATS::rate_limits_exceeded.add_value(1, (reponame,));
                             }
-                            Error(..) => {
+                            RepoReadOnly(..) | Error(..) => {
                                 STATS::push_error.add_value(1, (reponame,));
                             }
                         };
@@ -1871,7 +2258,7 @@ impl HgCommands for RepoClient {
                 .inspect_ok(move |_| STATS::push_success.add_value(1, (reponame,)))
                 .map_ok(bytes_ext::copy_from_new)
                 .map_err(Error::from)
-                .timeout(default_timeout())
+                .timeout(timeout)
                 .flatten_err()
                 .timed()
                 .map(move |(stats, res)| {
@@ -1891,6 +2278,7 @@ impl HgCommands for RepoClient {
             ops::GETTREEPACK,
             sampling_rate,
             |ctx, mut command_logger| {
+                let ctx = Self::with_client_deadline(ctx, params.deadline_ms);
                 let mut args = serde_json::Map::new();
                 args.insert(
                     "rootdir".to_string(),
@@ -1925,7 +2313,7 @@ impl HgCommands for RepoClient {
                 let s = self
                     .gettreepack_untimed(ctx.clone(), params)
                     .compat()
-                    .whole_stream_timeout(default_timeout())
+                    .whole_stream_timeout(self.command_timeout(ops::GETTREEPACK))
                     .yield_periodically()
                     .flatten_err()
                     .inspect_ok({
@@ -2081,7 +2469,7 @@ impl HgCommands for RepoClient {
             .try_flatten_stream();
 
             stream
-                .whole_stream_timeout(clone_timeout())
+                .whole_stream_timeout(self.command_timeout(ops::STREAMOUTSHALLOW))
                 .yield_periodically()
                 .flatten_err()
                 .map_ok(bytes_ext::copy_from_new)
@@ -2152,7 +2540,8 @@ impl HgCommands for RepoClient {
                 .add("getcommitdata_nodes", nodes.len())
                 .log_with_msg("GetCommitData Params", None);
 
-            let s = stream::iter(nodes.into_iter())
+            let hidden_check = self.ensure_not_hidden(ctx.clone(), nodes.clone());
+            let commits = stream::iter(nodes.into_iter())
                 .map({
                     cloned!(ctx, blobrepo);
                     move |hg_cs_id| {
@@ -2166,7 +2555,10 @@ impl HgCommands for RepoClient {
                         }
                     }
                 })
-                .buffered(100)
+                .buffered(100);
+            let s = hidden_check
+                .map_ok(move |()| commits)
+                .try_flatten_stream()
                 .inspect_ok({
                     cloned!(ctx);
                     move |bytes| {
@@ -2179,7 +2571,7 @@ impl HgCommands for RepoClient {
                         STATS::getcommitdata_commit_count.add_value(1);
                     }
                 })
-                .whole_stream_timeout(default_timeout())
+                .whole_stream_timeout(self.command_timeout(ops::GETCOMMITDATA))
                 .yield_periodically()
                 .flatten_err()
                 .timed(move |stats| {
@@ -2544,7 +2936,13 @@ fn serialize_getcommitdata(
     Ok(buffer.freeze())
 }
 
-fn with_command_monitor<T>(ctx: CoreContext, t: T) -> Monitor<T, Sender<()>> {
+/// Monitor a command while it runs, counting it as in flight until it is
+/// dropped.
+fn with_command_monitor<T>(
+    ctx: CoreContext,
+    in_flight: InFlightCommand,
+    t: T,
+) -> Monitor<T, (Sender<()>, InFlightCommand)> {
     let (sender, receiver) = oneshot::channel();
 
     let reporting_loop = async move {
@@ -2593,7 +2991,87 @@ fn with_command_monitor<T>(ctx: CoreContext, t: T) -> Monitor<T, Sender<()>> {
         let _ = future::select(reporting_loop, receiver).await;
     });
 
-    Monitor::new(t, sender)
+    Monitor::new(t, (sender, in_flight))
+}
+
+/// The trees a gettreepack request asks for by name, as opposed to those it
+/// asks for because they are under them.
+fn gettreepack_requested_trees(
+    params: &GettreepackArgs,
+) -> Result<Vec<(RepoPath, HgFileNodeId)>, Error> {
+    let paths = if params.depth == Some(1) && !params.directories.is_empty() {
+        params
+            .directories
+            .iter()
+            .map(MPath::new_opt)
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        vec![params.rootdir.clone(); params.mfnodes.len()]
+    };
+    Ok(paths
+        .into_iter()
+        .zip(params.mfnodes.iter())
+        .map(|(path, hg_mf_id)| {
+            let path = match path {
+                Some(path) => RepoPath::DirectoryPath(path),
+                None => RepoPath::RootPath,
+            };
+            (path, HgFileNodeId::new(hg_mf_id.into_nodehash()))
+        })
+        .collect())
+}
+
+/// Fail if any of the requested files or trees aren't served because the
+/// repo is a view of another repo, so that they can't be fetched.
+async fn ensure_entries_served(
+    ctx: &CoreContext,
+    repo: &Repo,
+    entries: Vec<(RepoPath, HgFileNodeId)>,
+) -> Result<(), Error> {
+    let outside_view = repo.hg_entries_outside_view(ctx, entries).await?;
+    if let Some(filenode_id) = outside_view.into_iter().next() {
+        bail!(
+            "{} is not part of {} and can't be fetched",
+            filenode_id,
+            repo.name()
+        );
+    }
+    Ok(())
+}
+
+/// Drop the changesets that the repo doesn't serve, because it is a view of
+/// another repo, from the changesets a lookup resolved.
+async fn filter_lookup_to_view(
+    ctx: CoreContext,
+    repo: Arc<Repo>,
+    resolved: HgChangesetIdsResolvedFromPrefix,
+) -> Result<HgChangesetIdsResolvedFromPrefix, Error> {
+    use HgChangesetIdsResolvedFromPrefix::*;
+
+    if repo.config().view.is_none() {
+        return Ok(resolved);
+    }
+    let hg_cs_ids = match resolved {
+        Single(hg_cs_id) => vec![hg_cs_id],
+        Multiple(hg_cs_ids) => hg_cs_ids,
+        resolved => return Ok(resolved),
+    };
+    let mapping = repo
+        .blob_repo()
+        .get_hg_bonsai_mapping(ctx.clone(), hg_cs_ids)
+        .await?;
+    let cs_ids = mapping.iter().map(|(_, cs_id)| *cs_id).collect::<Vec<_>>();
+    let outside_view = repo.changesets_outside_view(&ctx, cs_ids).await?;
+    let served = mapping
+        .into_iter()
+        .filter(|(_, cs_id)| !outside_view.contains(cs_id))
+        .map(|(hg_cs_id, _)| hg_cs_id)
+        .collect::<Vec<_>>();
+    Ok(match served.len() {
+        0 => NoMatch,
+        1 => Single(served[0]),
+        _ => Multiple(served),
+    })
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
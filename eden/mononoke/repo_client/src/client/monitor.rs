@@ -5,9 +5,51 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashMap;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+
 use futures_old::Future;
 use futures_old::Poll;
 use futures_old::Stream;
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref IN_FLIGHT_COMMANDS: Mutex<HashMap<String, Arc<AtomicUsize>>> = Default::default();
+}
+
+/// Counts a wireproto command to a repo as in flight until it is dropped.
+pub struct InFlightCommand(Arc<AtomicUsize>);
+
+impl InFlightCommand {
+    pub fn new(repo_name: &str) -> Self {
+        let count = IN_FLIGHT_COMMANDS
+            .lock()
+            .expect("lock poisoned")
+            .entry(repo_name.to_string())
+            .or_default()
+            .clone();
+        count.fetch_add(1, Ordering::Relaxed);
+        Self(count)
+    }
+}
+
+impl Drop for InFlightCommand {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Number of wireproto commands to a repo being served.
+pub fn in_flight_commands(repo_name: &str) -> usize {
+    IN_FLIGHT_COMMANDS
+        .lock()
+        .expect("lock poisoned")
+        .get(repo_name)
+        .map_or(0, |count| count.load(Ordering::Relaxed))
+}
 
 pub struct Monitor<T, P> {
     inner: T,
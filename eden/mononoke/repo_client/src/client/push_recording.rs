@@ -0,0 +1,479 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::time::Duration;
+
+use anyhow::format_err;
+use bytes::Bytes;
+use context::CoreContext;
+use itertools::Itertools;
+use mononoke_api::Repo;
+use mononoke_types::hash::Context;
+use mononoke_types::Timestamp;
+use push_log::IdempotencyKeyClaim;
+use push_log::PushLogArc;
+use push_log::PushLogBookmark;
+use push_log::PushLogEntry;
+use push_log::PushLogRef;
+use push_log::PushOutcome;
+use slog::warn;
+use tunables::tunables;
+use unbundle::BundleResolverError;
+use unbundle::PostResolveAction;
+use unbundle::PushrebaseBookmarkSpec;
+use unbundle::UnbundleResponse;
+
+/// The pushvar that clients set to an idempotency key to make a push safe to
+/// retry.
+const IDEMPOTENCY_KEY_PUSHVAR: &str = "IDEMPOTENCY_KEY";
+
+/// How long the responses to pushes made with idempotency keys are kept,
+/// unless the tunable overrides it.
+const DEFAULT_IDEMPOTENCY_KEY_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How many days push log entries are kept for, unless the tunable overrides
+/// it.
+const DEFAULT_PUSH_LOG_RETENTION_DAYS: i64 = 90;
+
+/// The most expired push log entries that are deleted after each push.
+const PUSH_LOG_PRUNE_BATCH_SIZE: u64 = 100;
+
+/// What a push contained, as far as it is known from the bundle and the
+/// response to it.
+#[derive(Default)]
+pub(crate) struct PushDetails {
+    bookmarks: Vec<PushLogBookmark>,
+    commit_count: u64,
+    replayed: bool,
+}
+
+impl PushDetails {
+    pub(crate) fn from_action(action: &PostResolveAction) -> Self {
+        let (mut bookmarks, commit_count) = match action {
+            PostResolveAction::Push(action) => (
+                action
+                    .bookmark_pushes
+                    .iter()
+                    .map(|push| PushLogBookmark {
+                        bookmark: push.name.to_string(),
+                        from: push.old,
+                        to: push.new,
+                    })
+                    .collect(),
+                action.uploaded_bonsais.len(),
+            ),
+            PostResolveAction::InfinitePush(action) => (
+                action
+                    .maybe_bookmark_push
+                    .iter()
+                    .map(|push| PushLogBookmark {
+                        bookmark: push.name.to_string(),
+                        from: push.old,
+                        to: Some(push.new),
+                    })
+                    .collect(),
+                action.uploaded_bonsais.len(),
+            ),
+            PostResolveAction::PushRebase(action) => {
+                // Where a pushrebase moves the bookmark to is only known once
+                // it has succeeded.
+                let from = match &action.bookmark_spec {
+                    PushrebaseBookmarkSpec::NormalPushrebase(_) => None,
+                    PushrebaseBookmarkSpec::ForcePushrebase(push) => push.old,
+                };
+                let bookmark = PushLogBookmark {
+                    bookmark: action.bookmark_spec.get_bookmark_name().to_string(),
+                    from,
+                    to: None,
+                };
+                (vec![bookmark], action.uploaded_bonsais.len())
+            }
+            PostResolveAction::BookmarkOnlyPushRebase(action) => {
+                let bookmark = PushLogBookmark {
+                    bookmark: action.bookmark_push.name.to_string(),
+                    from: action.bookmark_push.old,
+                    to: action.bookmark_push.new,
+                };
+                (vec![bookmark], 0)
+            }
+        };
+        bookmarks.sort_by(|a, b| a.bookmark.cmp(&b.bookmark));
+        Self {
+            bookmarks,
+            commit_count: commit_count as u64,
+            replayed: false,
+        }
+    }
+
+    /// Mark the push as a retry that was answered with the response to the
+    /// original push.
+    pub(crate) fn mark_replayed(&mut self) {
+        self.replayed = true;
+    }
+
+    /// Fill in what is only known once the push has succeeded.
+    pub(crate) fn update_from_response(&mut self, response: &UnbundleResponse) {
+        if let UnbundleResponse::PushRebase(response) = response {
+            let onto = response.onto.to_string();
+            for bookmark in self.bookmarks.iter_mut() {
+                if bookmark.bookmark == onto {
+                    bookmark.to = Some(response.pushrebased_rev);
+                }
+            }
+        }
+    }
+}
+
+fn outcome<T>(details: &PushDetails, result: &Result<T, BundleResolverError>) -> PushOutcome {
+    use BundleResolverError::*;
+    match result {
+        Ok(_) if details.replayed => PushOutcome::Replayed,
+        Ok(_) => PushOutcome::Success,
+        Err(HookError(_)) => PushOutcome::HookRejected,
+        Err(PushrebaseConflicts(_)) => PushOutcome::Conflicts,
+        Err(RateLimitExceeded { .. }) => PushOutcome::RateLimited,
+        Err(RepoReadOnly(_)) => PushOutcome::ReadOnly,
+        Err(Error(err)) => PushOutcome::Failure(format!("{:#}", err)),
+    }
+}
+
+/// Who is pushing: their unix name, or their identities if they have none.
+fn pusher(ctx: &CoreContext) -> String {
+    let metadata = ctx.metadata();
+    match metadata.unix_name() {
+        Some(unix_name) => unix_name.to_string(),
+        None => metadata.identities().iter().join(","),
+    }
+}
+
+/// The time before which push log entries have expired.
+fn push_log_retention_cutoff() -> Timestamp {
+    let days = tunables()
+        .repo_client_push_log_retention_days()
+        .unwrap_or_default();
+    let days = if days > 0 {
+        days
+    } else {
+        DEFAULT_PUSH_LOG_RETENTION_DAYS
+    };
+    Timestamp::from_timestamp_secs(Timestamp::now().timestamp_seconds() - days * 24 * 60 * 60)
+}
+
+/// Record a push that started at `timestamp` and ended with `result` in the
+/// repo's push log, and prune the entries that have expired.  This happens in
+/// the background so that it doesn't delay the response to the push, and
+/// failing to do it doesn't fail the push.
+pub(crate) fn record_push<T>(
+    ctx: &CoreContext,
+    repo: &Repo,
+    client_correlator: Option<String>,
+    timestamp: Timestamp,
+    duration: Duration,
+    details: PushDetails,
+    result: &Result<T, BundleResolverError>,
+) {
+    let session_id = ctx.metadata().session_id().to_string();
+    let hook_results = repo
+        .config()
+        .scuba_table_hooks
+        .as_ref()
+        .map(|table| format!("{}:{}", table, session_id));
+    let entry = PushLogEntry {
+        timestamp,
+        pusher: pusher(ctx),
+        session_id,
+        client_correlator,
+        outcome: outcome(&details, result),
+        bookmarks: details.bookmarks,
+        commit_count: details.commit_count,
+        hook_results,
+        duration,
+    };
+    let ctx = ctx.clone();
+    let push_log = repo.push_log_arc();
+    tokio::spawn(async move {
+        if let Err(err) = push_log.record(&ctx, entry).await {
+            warn!(
+                ctx.logger(),
+                "Failed to record push in the push log: {:#}", err
+            );
+        }
+        let cutoff = push_log_retention_cutoff();
+        if let Err(err) = push_log
+            .prune(&ctx, cutoff, PUSH_LOG_PRUNE_BATCH_SIZE)
+            .await
+        {
+            warn!(ctx.logger(), "Failed to prune the push log: {:#}", err);
+        }
+    });
+}
+
+/// The idempotency key the client made the push with, if it made it with one.
+pub(crate) fn idempotency_key(action: &PostResolveAction) -> Option<String> {
+    let pushvars = match action {
+        PostResolveAction::Push(action) => action.maybe_pushvars.as_ref(),
+        PostResolveAction::PushRebase(action) => action.maybe_pushvars.as_ref(),
+        PostResolveAction::BookmarkOnlyPushRebase(action) => action.maybe_pushvars.as_ref(),
+        PostResolveAction::InfinitePush(_) => None,
+    }?;
+    let key = pushvars.get(IDEMPOTENCY_KEY_PUSHVAR)?;
+    Some(String::from_utf8_lossy(key).into_owned())
+}
+
+/// A hash of what a push does, so that a retry of a push made with an
+/// idempotency key can be told apart from a different push with the same key.
+pub(crate) fn push_hash(action: &PostResolveAction) -> String {
+    let uploaded_bonsais = match action {
+        PostResolveAction::Push(action) => Some(&action.uploaded_bonsais),
+        PostResolveAction::InfinitePush(action) => Some(&action.uploaded_bonsais),
+        PostResolveAction::PushRebase(action) => Some(&action.uploaded_bonsais),
+        PostResolveAction::BookmarkOnlyPushRebase(_) => None,
+    };
+    let mut cs_ids = uploaded_bonsais
+        .into_iter()
+        .flatten()
+        .map(|bcs| bcs.get_changeset_id())
+        .collect::<Vec<_>>();
+    cs_ids.sort();
+
+    let mut context = Context::new(b"idempotent_push");
+    for bookmark in PushDetails::from_action(action).bookmarks {
+        context.update(format!(
+            "{}:{:?}:{:?}\n",
+            bookmark.bookmark, bookmark.from, bookmark.to
+        ));
+    }
+    for cs_id in cs_ids {
+        context.update(cs_id.as_ref());
+    }
+    context.finish().to_hex().to_string()
+}
+
+fn idempotency_key_ttl() -> Duration {
+    let ttl = tunables()
+        .repo_client_idempotency_key_ttl_secs()
+        .unwrap_or_default();
+    if ttl > 0 {
+        Duration::from_secs(ttl as u64)
+    } else {
+        DEFAULT_IDEMPOTENCY_KEY_TTL
+    }
+}
+
+/// Claim the idempotency key of a push that is about to start, for at most
+/// `timeout`.  Returns the response to the earlier push with the key if it
+/// succeeded, in which case this push is a retry that mustn't be made again.
+pub(crate) async fn claim_idempotency_key(
+    ctx: &CoreContext,
+    repo: &Repo,
+    key: &str,
+    push_hash: &str,
+    timeout: Duration,
+) -> Result<Option<Bytes>, BundleResolverError> {
+    let claim = repo
+        .push_log()
+        .claim_idempotency_key(
+            ctx,
+            &pusher(ctx),
+            key,
+            push_hash,
+            timeout,
+            idempotency_key_ttl(),
+        )
+        .await?;
+    match claim {
+        IdempotencyKeyClaim::Claimed => Ok(None),
+        IdempotencyKeyClaim::InProgress => Err(format_err!(
+            "A push with idempotency key {} is already in progress, retry it later",
+            key
+        )
+        .into()),
+        IdempotencyKeyClaim::Abandoned => Err(format_err!(
+            "The push with idempotency key {} was interrupted and may have landed, \
+             check the repo before pushing again with a new key",
+            key
+        )
+        .into()),
+        IdempotencyKeyClaim::Mismatched => Err(format_err!(
+            "Idempotency key {} was already used for a different push",
+            key
+        )
+        .into()),
+        IdempotencyKeyClaim::Completed(response) => Ok(Some(Bytes::from(response))),
+    }
+}
+
+/// Keep the response to a push that claimed an idempotency key for retries
+/// of it, or release the key if the push failed so that it can be retried.
+/// The push already succeeded or failed, so failing to do so doesn't fail it.
+pub(crate) async fn finish_idempotency_key<E>(
+    ctx: &CoreContext,
+    repo: &Repo,
+    key: &str,
+    result: &Result<Bytes, E>,
+) {
+    let pusher = pusher(ctx);
+    let push_log = repo.push_log();
+    let finished = match result {
+        Ok(response) => {
+            push_log
+                .complete_idempotency_key(ctx, &pusher, key, response, idempotency_key_ttl())
+                .await
+        }
+        Err(_) => push_log.release_idempotency_key(ctx, &pusher, key).await,
+    };
+    if let Err(err) = finished {
+        warn!(
+            ctx.logger(),
+            "Failed to finish idempotency key {}: {:#}", key, err
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    use bookmarks::BookmarkKey;
+    use mononoke_types::ChangesetId;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::THREES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+    use unbundle::CommonHeads;
+    use unbundle::InfiniteBookmarkPush;
+    use unbundle::NonFastForwardPolicy;
+    use unbundle::PlainBookmarkPush;
+    use unbundle::PostResolveBookmarkOnlyPushRebase;
+    use unbundle::PostResolveInfinitePush;
+    use unbundle::PostResolvePush;
+    use unbundle::PostResolvePushRebase;
+    use unbundle::UnbundlePushRebaseResponse;
+
+    use super::*;
+
+    fn bookmark_push(
+        name: &str,
+        old: Option<ChangesetId>,
+        new: Option<ChangesetId>,
+    ) -> PlainBookmarkPush<ChangesetId> {
+        PlainBookmarkPush {
+            part_id: 0,
+            name: BookmarkKey::new(name).unwrap(),
+            old,
+            new,
+        }
+    }
+
+    fn bookmark(name: &str, from: Option<ChangesetId>, to: Option<ChangesetId>) -> PushLogBookmark {
+        PushLogBookmark {
+            bookmark: name.to_string(),
+            from,
+            to,
+        }
+    }
+
+    #[test]
+    fn test_push_details_from_push() {
+        let action = PostResolveAction::Push(PostResolvePush {
+            changegroup_id: None,
+            bookmark_pushes: vec![
+                bookmark_push("release", None, Some(TWOS_CSID)),
+                bookmark_push("main", Some(ONES_CSID), Some(TWOS_CSID)),
+            ],
+            mutations: vec![],
+            maybe_pushvars: None,
+            non_fast_forward_policy: NonFastForwardPolicy::Disallowed,
+            uploaded_bonsais: HashSet::new(),
+            uploaded_hg_changeset_ids: HashSet::new(),
+            hook_rejection_remapper: Arc::new(|_| unreachable!("hooks aren't run")),
+        });
+        let details = PushDetails::from_action(&action);
+        assert_eq!(
+            details.bookmarks,
+            vec![
+                bookmark("main", Some(ONES_CSID), Some(TWOS_CSID)),
+                bookmark("release", None, Some(TWOS_CSID)),
+            ]
+        );
+        assert_eq!(details.commit_count, 0);
+        assert!(!details.replayed);
+    }
+
+    #[test]
+    fn test_push_details_from_infinitepush() {
+        let action = PostResolveAction::InfinitePush(PostResolveInfinitePush {
+            changegroup_id: None,
+            maybe_bookmark_push: Some(InfiniteBookmarkPush {
+                name: BookmarkKey::new("scratch/feature").unwrap(),
+                create: true,
+                force: false,
+                old: None,
+                new: ONES_CSID,
+            }),
+            mutations: vec![],
+            uploaded_bonsais: HashSet::new(),
+            uploaded_hg_changeset_ids: HashSet::new(),
+        });
+        assert_eq!(
+            PushDetails::from_action(&action).bookmarks,
+            vec![bookmark("scratch/feature", None, Some(ONES_CSID))]
+        );
+    }
+
+    #[test]
+    fn test_push_details_from_pushrebase() {
+        let action = PostResolveAction::PushRebase(PostResolvePushRebase {
+            bookmark_push_part_id: None,
+            bookmark_spec: PushrebaseBookmarkSpec::NormalPushrebase(
+                BookmarkKey::new("main").unwrap(),
+            ),
+            maybe_pushvars: None,
+            commonheads: CommonHeads { heads: vec![] },
+            uploaded_bonsais: HashSet::new(),
+            hook_rejection_remapper: Arc::new(|_| unreachable!("hooks aren't run")),
+        });
+        let mut details = PushDetails::from_action(&action);
+        assert_eq!(details.bookmarks, vec![bookmark("main", None, None)]);
+
+        // The bookmark is only known to have moved once the pushrebase
+        // succeeded.
+        details.update_from_response(&UnbundleResponse::PushRebase(UnbundlePushRebaseResponse {
+            commonheads: CommonHeads { heads: vec![] },
+            pushrebased_rev: THREES_CSID,
+            pushrebased_changesets: vec![],
+            onto: BookmarkKey::new("main").unwrap(),
+            bookmark_push_part_id: None,
+        }));
+        assert_eq!(
+            details.bookmarks,
+            vec![bookmark("main", None, Some(THREES_CSID))]
+        );
+    }
+
+    #[test]
+    fn test_push_details_from_bookmark_only_pushrebase() {
+        let action = PostResolveAction::BookmarkOnlyPushRebase(PostResolveBookmarkOnlyPushRebase {
+            bookmark_push: bookmark_push("main", Some(ONES_CSID), None),
+            maybe_pushvars: None,
+            non_fast_forward_policy: NonFastForwardPolicy::Allowed,
+            hook_rejection_remapper: Arc::new(|_| unreachable!("hooks aren't run")),
+        });
+        let mut details = PushDetails::from_action(&action);
+        assert_eq!(
+            details.bookmarks,
+            vec![bookmark("main", Some(ONES_CSID), None)]
+        );
+        assert_eq!(details.commit_count, 0);
+
+        details.mark_replayed();
+        assert_eq!(
+            outcome(&details, &Ok::<_, BundleResolverError>(())),
+            PushOutcome::Replayed
+        );
+    }
+}
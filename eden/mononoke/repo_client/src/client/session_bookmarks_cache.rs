@@ -365,7 +365,7 @@ mod test {
             repo.bookmark_update_log_arc(),
             repo.repo_identity_arc(),
         );
-        builder.add_hg_warmers(&repo.repo_derived_data_arc(), &repo.phases_arc())?;
+        builder.add_hg_warmers(&repo.repo_derived_data_arc(), &repo.phases_arc(), &[])?;
         let wbc = builder.build().await?;
         let session_bookmark_cache = SessionBookmarkCache::new(BasicTestRepo {
             repo: repo.blob_repo.clone(),
@@ -15,13 +15,18 @@ use manifest::Entry;
 use manifest::ManifestOps;
 use maplit::hashset;
 use mercurial_derived_data::DeriveHgChangeset;
+use mercurial_types::percent_encode;
 use mercurial_types::HgFileNodeId;
 use metaconfig_types::LfsParams;
+use metaconfig_types::RepoConfig;
+use metaconfig_types::WireprotoCapabilitiesConfig;
 use mononoke_api::Repo;
+use mononoke_types::Timestamp;
 use mononoke_types_mocks::changesetid::ONES_CSID;
 use repo_blobstore::RepoBlobstoreRef;
 use scuba_ext::MononokeScubaSampleBuilder;
 use serde_json::json;
+use tests_utils::drawdag::create_from_dag;
 use tests_utils::CreateCommitContext;
 
 use super::*;
@@ -389,6 +394,57 @@ async fn test_maybe_validate_pushed_bonsais(fb: FacebookInit) -> Result<(), Erro
     Ok(())
 }
 
+#[fbinit::test]
+async fn test_listkeys_tags(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let blob_repo: BlobRepo = test_repo_factory::build_empty(fb)?;
+    let changesets = create_from_dag(&ctx, &blob_repo, "A-B").await?;
+    let repo = Arc::new(Repo::new_test(ctx.clone(), blob_repo.clone()).await?);
+    for (name, target) in [("v1", "A"), ("v2", "B"), ("v3", "B")] {
+        let tag = Tag {
+            name: name.to_string(),
+            cs_id: changesets[target],
+            message: None,
+            signer: None,
+            creator: None,
+            created_at: Timestamp::now(),
+        };
+        assert!(repo.repo_tags().create(&ctx, tag).await?);
+    }
+
+    // Tags are listed a page at a time until there are none left.
+    let tags = list_all_tags(&ctx, repo.repo_tags(), 2).await?;
+    let names = tags.iter().map(|tag| tag.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["v1", "v2", "v3"]);
+
+    let logging = LoggingContainer::new(
+        ctx.fb,
+        ctx.logger().clone(),
+        MononokeScubaSampleBuilder::with_discard(),
+    );
+    let repo_client = RepoClient::new(
+        repo,
+        ctx.session().clone(),
+        logging,
+        None, // No PushRedirectorArgs
+        Default::default(),
+        None, // No backup repo source
+    );
+    let hex = |hg_cs_id: HgChangesetId| -> Vec<u8> { hg_cs_id.into_nodehash().to_hex().into() };
+    let a = hex(blob_repo.derive_hg_changeset(&ctx, changesets["A"]).await?);
+    let b = hex(blob_repo.derive_hg_changeset(&ctx, changesets["B"]).await?);
+    let listed = repo_client.listkeys("tags".to_string()).compat().await?;
+    assert_eq!(
+        listed,
+        hashmap! {
+            b"v1".to_vec() => a,
+            b"v2".to_vec() => b.clone(),
+            b"v3".to_vec() => b,
+        }
+    );
+    Ok(())
+}
+
 async fn run_and_check_if_lfs(
     ctx: &CoreContext,
     repo: &BlobRepo,
@@ -481,3 +537,97 @@ fn test_parse_git_lookup() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn test_capabilities_default() {
+    let caps = Capabilities::for_repo(&RepoConfig::default(), None).into_hello_caps();
+    assert!(!caps.contains(&"clonebundles".to_string()));
+    assert!(caps.contains(&"unbundle=HG10GZ,HG10BZ,HG10UN".to_string()));
+    assert!(!caps.contains(&"lfs".to_string()));
+
+    let bundle2 = caps.last().unwrap();
+    assert!(bundle2.starts_with(&format!("bundle2={}", percent_encode("HG20\n"))));
+    assert!(bundle2.contains(&percent_encode("\nb2x:rebase\n")));
+}
+
+#[test]
+fn test_capabilities_from_config() {
+    let config = RepoConfig {
+        lfs: LfsParams {
+            threshold: Some(1000),
+            ..Default::default()
+        },
+        wireproto_capabilities: WireprotoCapabilitiesConfig {
+            disabled: hashset! {
+                "unbundle".to_string(),
+                "b2x:rebase".to_string(),
+            },
+            extra: vec!["clonebundles".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let caps = Capabilities::for_repo(&config, None).into_hello_caps();
+    assert!(caps.contains(&"lfs".to_string()));
+    assert!(caps.contains(&"clonebundles".to_string()));
+    assert!(caps.contains(&"unbundlereplay".to_string()));
+    assert!(!caps.iter().any(|cap| cap.starts_with("unbundle=")));
+
+    let bundle2 = caps.last().unwrap();
+    assert!(bundle2.starts_with("bundle2="));
+    assert!(!bundle2.contains(&percent_encode("\nb2x:rebase\n")));
+    assert!(bundle2.contains(&percent_encode("\nb2x:rebasepackpart\n")));
+}
+
+#[test]
+fn test_capabilities_for_client_version() {
+    let config = RepoConfig {
+        wireproto_capabilities: WireprotoCapabilitiesConfig {
+            extra: vec!["segmentedchangelog".to_string()],
+            min_client_versions: hashmap! {
+                "segmentedchangelog".to_string() => "0.2.20230601".parse().unwrap(),
+                "b2x:rebase".to_string() => "0.2".parse().unwrap(),
+            },
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let advertises = |client_version: Option<&str>, cap: &str| {
+        let caps = Capabilities::for_repo(&config, client_version).into_hello_caps();
+        let bundle2 = caps.last().unwrap().clone();
+        caps.contains(&cap.to_string()) || bundle2.contains(&percent_encode(cap))
+    };
+
+    let new_client = Some("0.2.20230615-101010-h1234abcd");
+    assert!(advertises(new_client, "segmentedchangelog"));
+    assert!(advertises(new_client, "\nb2x:rebase\n"));
+
+    let old_client = Some("0.2.20230101-101010-h1234abcd");
+    assert!(!advertises(old_client, "segmentedchangelog"));
+    assert!(advertises(old_client, "\nb2x:rebase\n"));
+
+    // Clients whose version is unknown only get ungated capabilities.
+    assert!(!advertises(None, "segmentedchangelog"));
+    assert!(!advertises(Some("unknown"), "\nb2x:rebase\n"));
+    assert!(advertises(None, "getbundle"));
+}
+
+#[fbinit::test]
+async fn test_in_flight_commands(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo_name = "test_in_flight_commands";
+
+    let first = InFlightCommand::new(repo_name);
+    let command = with_command_monitor(ctx, first, future_old::ok::<_, Error>(()));
+    let second = InFlightCommand::new(repo_name);
+    assert_eq!(in_flight_commands(repo_name), 2);
+    assert_eq!(in_flight_commands("other_repo"), 0);
+
+    // Commands are in flight until they are done.
+    command.compat().await?;
+    assert_eq!(in_flight_commands(repo_name), 1);
+    drop(second);
+    assert_eq!(in_flight_commands(repo_name), 0);
+
+    Ok(())
+}
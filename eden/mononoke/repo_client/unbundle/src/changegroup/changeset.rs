@@ -83,7 +83,7 @@ mod tests {
             p2.into_option(),
         );
 
-        let delta = delta::Delta::new_fulltext(blobnode.as_blob().as_slice());
+        let delta = delta::Delta::new_fulltext(blobnode.as_blob().as_inner().clone());
         let cs = RevlogChangeset::new(blobnode).unwrap();
 
         let chunk = CgDeltaChunk {
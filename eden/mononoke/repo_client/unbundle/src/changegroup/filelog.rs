@@ -400,7 +400,7 @@ mod tests {
                     p2: f.p2.clone().unwrap_or(NULL_HASH),
                     base: NULL_HASH,
                     linknode: f.linknode.clone(),
-                    delta: Delta::new_fulltext(bytes.as_ref()),
+                    delta: Delta::new_fulltext(bytes.clone()),
                     flags: None,
                 },
             },
@@ -428,7 +428,7 @@ mod tests {
                         frags.push(Fragment {
                             start,
                             end: start + frag.len(),
-                            content: std::mem::take(&mut frag),
+                            content: std::mem::take(&mut frag).into(),
                         });
                     } else if v1 != v2 {
                         if frag.is_empty() {
@@ -450,14 +450,14 @@ mod tests {
             frags.push(Fragment {
                 start,
                 end: min(start + frag.len(), b1.len()),
-                content: std::mem::take(&mut frag),
+                content: std::mem::take(&mut frag).into(),
             });
         }
         if b1.len() > b2.len() {
             frags.push(Fragment {
                 start: b2.len(),
                 end: b1.len(),
-                content: Vec::new(),
+                content: Bytes::new(),
             });
         }
 
@@ -26,6 +26,7 @@ use hooks::HookManager;
 use mercurial_mutation::HgMutationStoreRef;
 use metaconfig_types::Address;
 use metaconfig_types::PushrebaseRemoteMode;
+use metaconfig_types::RepoConfigRef;
 use mononoke_types::BonsaiChangeset;
 use mononoke_types::ChangesetId;
 use pushrebase::PushrebaseError;
@@ -38,8 +39,12 @@ use repo_authorization::AuthorizationContext;
 use repo_identity::RepoIdentityRef;
 use repo_update_logger::log_new_commits;
 use repo_update_logger::CommitInfo;
+use review_creation::review_target;
+use review_creation::ReviewCreatorRef;
+use review_creation::ReviewRequest;
 use scuba_ext::MononokeScubaSampleBuilder;
 use slog::debug;
+use slog::warn;
 use stats::prelude::*;
 use tunables::tunables;
 
@@ -72,7 +77,7 @@ define_stats! {
     infinitepush: dynamic_timeseries("{}.infinitepush", (reponame: String); Rate, Sum),
 }
 
-pub trait Repo = bookmarks_movement::Repo + HgMutationStoreRef;
+pub trait Repo = bookmarks_movement::Repo + HgMutationStoreRef + ReviewCreatorRef;
 
 pub async fn run_post_resolve_action(
     ctx: &CoreContext,
@@ -260,6 +265,7 @@ async fn run_infinitepush(
             .context("Failed to store mutation data")?;
     }
 
+    let mut output = None;
     let bookmark = match maybe_bookmark_push {
         Some(bookmark_push) => {
             infinitepush_scratch_bookmark(
@@ -271,6 +277,7 @@ async fn run_infinitepush(
                 cross_repo_push_source,
             )
             .await?;
+            output = maybe_create_review(ctx, repo, &bookmark_push).await;
 
             Some(bookmark_push.name)
         }
@@ -290,7 +297,41 @@ async fn run_infinitepush(
     )
     .await;
 
-    Ok(UnbundleInfinitePushResponse { changegroup_id })
+    Ok(UnbundleInfinitePushResponse {
+        changegroup_id,
+        output,
+    })
+}
+
+/// If the push was to a review bookmark, create a review of the pushed
+/// commit, and return a message with its URL for the client.
+///
+/// The commit has already been pushed by now, so failing to create the
+/// review doesn't fail the push, and the user is told about it instead.
+async fn maybe_create_review(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    bookmark_push: &InfiniteBookmarkPush<ChangesetId>,
+) -> Option<String> {
+    let config = repo.repo_config().push_to_review_config.as_ref()?;
+    let target = review_target(config, &bookmark_push.name)?;
+    let request = ReviewRequest {
+        repo_name: repo.repo_identity().name().to_string(),
+        review_bookmark: bookmark_push.name.to_string(),
+        target_bookmark: target.to_string(),
+        changeset_id: bookmark_push.new,
+        pusher: ctx.metadata().unix_name().map(ToString::to_string),
+    };
+    match repo.review_creator().create_review(ctx, &request).await {
+        Ok(url) => Some(format!("created review against {}: {}\n", target, url)),
+        Err(e) => {
+            warn!(
+                ctx.logger(),
+                "Failed to create review for {}: {:#}", bookmark_push.name, e
+            );
+            Some(format!("failed to create review against {}: {:#}\n", target, e))
+        }
+    }
 }
 
 async fn run_pushrebase(
@@ -384,10 +425,14 @@ async fn run_pushrebase(
         }
     };
 
-    repo.phases()
-        .add_reachable_as_public(ctx, vec![pushrebased_rev.clone()])
-        .await
-        .context("While marking pushrebased changeset as public")?;
+    // Only configured publishing bookmarks make their ancestors public.
+    let publishing_bookmarks = &repo.repo_config().publishing_bookmarks;
+    if publishing_bookmarks.is_empty() || publishing_bookmarks.contains(&bookmark) {
+        repo.phases()
+            .add_reachable_as_public(ctx, vec![pushrebased_rev.clone()])
+            .await
+            .context("While marking pushrebased changeset as public")?;
+    }
 
     Ok(UnbundlePushRebaseResponse {
         commonheads,
@@ -454,6 +499,7 @@ async fn convert_bookmark_movement_err(
             let rejections = map_hook_rejections(rejections, hook_rejection_remapper).await?;
             BundleResolverError::HookError(rejections)
         }
+        BookmarkMovementError::RepoLocked(info) => BundleResolverError::RepoReadOnly(info),
         _ => BundleResolverError::Error(err.into()),
     })
 }
@@ -46,6 +46,7 @@ use mercurial_mutation::HgMutationEntry;
 use mercurial_revlog::changeset::RevlogChangeset;
 use mercurial_types::HgChangesetId;
 use metaconfig_types::PushrebaseFlags;
+use metaconfig_types::ReadOnlyInfo;
 use mononoke_types::BonsaiChangeset;
 use mononoke_types::ChangesetId;
 use rate_limiting::RateLimitBody;
@@ -131,6 +132,7 @@ impl HgHookRejection {
 pub enum BundleResolverError {
     HookError(Vec<HgHookRejection>),
     PushrebaseConflicts(Vec<pushrebase::PushrebaseConflict>),
+    RepoReadOnly(ReadOnlyInfo),
     Error(Error),
     RateLimitExceeded {
         limit_name: String,
@@ -166,6 +168,7 @@ impl From<BundleResolverError> for Error {
             PushrebaseConflicts(conflicts) => {
                 format_err!("pushrebase failed Conflicts({:?})", conflicts)
             }
+            RepoReadOnly(info) => format_err!("Repo is read-only: {}", info),
             RateLimitExceeded {
                 limit_name,
                 limit,
@@ -44,6 +44,8 @@ pub struct UnbundlePushResponse {
 /// Data, needed to generate an `InfinitePush` response
 pub struct UnbundleInfinitePushResponse {
     pub changegroup_id: Option<PartId>,
+    /// Text to show to the user, e.g. the URL of a review created by the push
+    pub output: Option<String>,
 }
 
 /// Data, needed to generate a `PushRebase` response
@@ -81,8 +83,12 @@ impl UnbundleResponse {
     async fn generate_push_or_infinitepush_response(
         changegroup_id: Option<PartId>,
         bookmark_ids: Vec<PartId>,
+        output: Option<String>,
     ) -> Result<Bytes> {
         let mut bundle = Self::get_bundle_builder();
+        if let Some(output) = output {
+            bundle.add_part(parts::output_part(output)?);
+        }
         if let Some(changegroup_id) = changegroup_id {
             bundle.add_part(parts::replychangegroup_part(
                 parts::ChangegroupApplyResult::Success { heads_num_diff: 0 },
@@ -104,7 +110,7 @@ impl UnbundleResponse {
             changegroup_id,
             bookmark_ids,
         } = data;
-        Self::generate_push_or_infinitepush_response(changegroup_id, bookmark_ids)
+        Self::generate_push_or_infinitepush_response(changegroup_id, bookmark_ids, None)
             .await
             .context("While preparing push response")
     }
@@ -113,8 +119,11 @@ impl UnbundleResponse {
         _ctx: &CoreContext,
         data: UnbundleInfinitePushResponse,
     ) -> Result<Bytes> {
-        let UnbundleInfinitePushResponse { changegroup_id } = data;
-        Self::generate_push_or_infinitepush_response(changegroup_id, vec![])
+        let UnbundleInfinitePushResponse {
+            changegroup_id,
+            output,
+        } = data;
+        Self::generate_push_or_infinitepush_response(changegroup_id, vec![], output)
             .await
             .context("While preparing infinitepush response")
     }
@@ -16,6 +16,8 @@ use std::sync::Arc;
 
 use acl_regions::build_acl_regions;
 use acl_regions::ArcAclRegions;
+use admin_audit_log::ArcAdminAuditLog;
+use admin_audit_log::SqlAdminAuditLogBuilder;
 use anyhow::Context;
 use anyhow::Result;
 use async_once_cell::AsyncOnceCell;
@@ -41,10 +43,12 @@ use bonsai_hg_mapping::SqlBonsaiHgMappingBuilder;
 use bonsai_svnrev_mapping::ArcBonsaiSvnrevMapping;
 use bonsai_svnrev_mapping::CachingBonsaiSvnrevMapping;
 use bonsai_svnrev_mapping::SqlBonsaiSvnrevMappingBuilder;
-use bookmarks::bookmark_heads_fetcher;
+use bookmarks::configured_bookmark_heads_fetcher;
 use bookmarks::ArcBookmarkUpdateLog;
 use bookmarks::ArcBookmarks;
 use bookmarks::CachedBookmarks;
+use bookmarks::ViewBookmarkUpdateLog;
+use bookmarks::ViewBookmarks;
 use cacheblob::new_cachelib_blobstore_no_lease;
 use cacheblob::new_memcache_blobstore;
 use cacheblob::CachelibBlobstoreOptions;
@@ -53,6 +57,7 @@ use cacheblob::LeaseOps;
 use cacheblob::MemcacheOps;
 use caching_commit_graph_storage::CachingCommitGraphStorage;
 use caching_ext::CacheHandlerFactory;
+use caching_ext::MemcacheHandler;
 use changeset_fetcher::ArcChangesetFetcher;
 use changeset_fetcher::SimpleChangesetFetcher;
 use changesets::ArcChangesets;
@@ -63,6 +68,9 @@ use commit_graph::ArcCommitGraph;
 use commit_graph::CommitGraph;
 use commit_graph_compat::ChangesetsCommitGraphCompat;
 use commit_graph_types::storage::CommitGraphStorage;
+use commit_signatures::ArcCommitSignatures;
+use commit_signatures::SqlCommitSignaturesBuilder;
+use context::log_keys;
 use context::CoreContext;
 use context::SessionContainer;
 use cross_repo_sync::create_commit_syncer_lease;
@@ -76,19 +84,26 @@ use derived_data_remote::RemoteDerivationOptions;
 use environment::Caching;
 use environment::MononokeEnvironment;
 use environment::WarmBookmarksCacheDerivedData;
+use environment::WarmBookmarksCacheSnapshotMode;
 use ephemeral_blobstore::ArcRepoEphemeralStore;
 use ephemeral_blobstore::RepoEphemeralStore;
 use ephemeral_blobstore::RepoEphemeralStoreBuilder;
 use fbinit::FacebookInit;
 use filenodes::ArcFilenodes;
+use filenodes::DisabledFilenodes;
 use filestore::ArcFilestoreConfig;
 use filestore::FilestoreConfig;
 use futures_watchdog::WatchdogExt;
+use hidden_changesets::ArcHiddenChangesets;
+use hidden_changesets::CachingHiddenChangesets;
+use hidden_changesets::HidingChangesets;
+use hidden_changesets::SqlHiddenChangesetsBuilder;
 use hooks::hook_loader::load_hooks;
 use hooks::ArcHookManager;
 use hooks::HookManager;
 use hooks_content_stores::RepoFileContentManager;
 use hooks_content_stores::TextOnlyFileContentManager;
+use lfs_shared_store::SharedLfsFallbackBlobstore;
 use live_commit_sync_config::CfgrLiveCommitSyncConfig;
 use memcache::KeyGen;
 use memcache::MemcacheClient;
@@ -99,10 +114,12 @@ use metaconfig_types::ArcCommonConfig;
 use metaconfig_types::ArcRepoConfig;
 use metaconfig_types::BlobConfig;
 use metaconfig_types::CommonConfig;
+use metaconfig_types::ComparableRegex;
 use metaconfig_types::MetadataDatabaseConfig;
 use metaconfig_types::Redaction;
 use metaconfig_types::RepoConfig;
 use metaconfig_types::RepoReadOnly;
+use metaconfig_types::RetryConfig;
 use mutable_counters::ArcMutableCounters;
 use mutable_counters::SqlMutableCountersBuilder;
 use mutable_renames::ArcMutableRenames;
@@ -112,6 +129,10 @@ use newfilenodes::NewFilenodesBuilder;
 use parking_lot::Mutex;
 use permission_checker::AclProvider;
 use phases::ArcPhases;
+use prefix_filter_commit_graph_storage::PrefixFilter;
+use prefix_filter_commit_graph_storage::PrefixFilterCommitGraphStorage;
+use push_log::ArcPushLog;
+use push_log::SqlPushLogBuilder;
 use pushrebase_mutation_mapping::ArcPushrebaseMutationMapping;
 use pushrebase_mutation_mapping::SqlPushrebaseMutationMappingConnection;
 use readonlyblob::ReadOnlyBlobstore;
@@ -141,8 +162,17 @@ use repo_permission_checker::ProdRepoPermissionChecker;
 use repo_sparse_profiles::ArcRepoSparseProfiles;
 use repo_sparse_profiles::RepoSparseProfiles;
 use repo_sparse_profiles::SqlSparseProfilesSizes;
+use repo_stats::ArcRepoStats;
+use repo_stats::SqlRepoStatsBuilder;
+use repo_tags::ArcRepoTags;
+use repo_tags::SqlRepoTagsBuilder;
 use requests_table::ArcLongRunningRequestsQueue;
 use requests_table::SqlLongRunningRequestsQueue;
+use review_creation::ArcReviewCreator;
+use retry::RetryBudget;
+use retry::RetryLogic;
+use retry::RetryPolicy;
+use retryblob::RetryBlobstore;
 use scuba_ext::MononokeScubaSampleBuilder;
 use segmented_changelog::new_server_segmented_changelog;
 use segmented_changelog::new_server_segmented_changelog_manager;
@@ -154,10 +184,18 @@ use skiplist::SkiplistIndex;
 use slog::o;
 use sql::SqlConnections;
 use sql::SqlConnectionsWithSchema;
+use sql_commit_graph_storage::MultiRepoCommitGraphStorage;
 use sql_commit_graph_storage::SqlCommitGraphStorageBuilder;
 use sql_construct::SqlConstruct;
 use sql_construct::SqlConstructFromDatabaseConfig;
 use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::consistency::ReadConsistency;
+use sql_ext::consistency::ReadConsistencyConfig;
+#[cfg(fbcode_build)]
+use sql_ext::facebook::MyAdmin;
+#[cfg(not(fbcode_build))]
+use sql_ext::replication::NoReplicaLagMonitor;
+use sql_ext::replication::ReplicaLagMonitor;
 use sql_query_config::ArcSqlQueryConfig;
 use sql_query_config::SqlQueryConfig;
 use sqlphases::SqlPhasesBuilder;
@@ -229,6 +267,9 @@ pub struct RepoFactory {
     sql_connections: RepoFactoryCache<MetadataDatabaseConfig, SqlConnectionsWithSchema>,
     blobstores: RepoFactoryCache<BlobConfig, Arc<dyn Blobstore>>,
     redacted_blobs: RepoFactoryCache<MetadataDatabaseConfig, Arc<RedactedBlobs>>,
+    read_consistency: RepoFactoryCache<MetadataDatabaseConfig, Option<Arc<ReadConsistency>>>,
+    commit_graph_storages:
+        RepoFactoryCache<MetadataDatabaseConfig, Arc<MultiRepoCommitGraphStorage>>,
     blobstore_override: Option<Arc<dyn RepoFactoryOverride<Arc<dyn Blobstore>>>>,
     scrub_handler: Arc<dyn ScrubHandler>,
     blobstore_component_sampler: Option<Arc<dyn ComponentSamplingHandler>>,
@@ -242,6 +283,8 @@ impl RepoFactory {
             sql_connections: RepoFactoryCache::new(),
             blobstores: RepoFactoryCache::new(),
             redacted_blobs: RepoFactoryCache::new(),
+            read_consistency: RepoFactoryCache::new(),
+            commit_graph_storages: RepoFactoryCache::new(),
             blobstore_override: None,
             scrub_handler: default_scrub_handler(),
             blobstore_component_sampler: None,
@@ -295,6 +338,53 @@ impl RepoFactory {
             .await
     }
 
+    /// Read-your-writes tracking for the primary metadata database, shared
+    /// by all repos using it. None if it is disabled in the config.
+    async fn read_consistency(
+        &self,
+        config: &MetadataDatabaseConfig,
+    ) -> Result<Option<Arc<ReadConsistency>>> {
+        self.read_consistency
+            .get_or_try_init(config, || async move {
+                let remote = match config {
+                    MetadataDatabaseConfig::Remote(remote) => remote,
+                    MetadataDatabaseConfig::Local(_) => return Ok(None),
+                };
+                let params = &remote.read_consistency;
+                if params.read_your_writes_window.is_zero() && params.max_replica_lag.is_none() {
+                    return Ok(None);
+                }
+                let shard = remote.primary.db_address.clone();
+                #[cfg(fbcode_build)]
+                let monitor: Arc<dyn ReplicaLagMonitor> =
+                    Arc::new(MyAdmin::new(self.env.fb)?.single_shard_lag_monitor(shard.clone()));
+                #[cfg(not(fbcode_build))]
+                let monitor: Arc<dyn ReplicaLagMonitor> = Arc::new(NoReplicaLagMonitor());
+                // Writes are shared with other servers through memcache, so
+                // that a session's reads are routed to the primary wherever
+                // it writes from.
+                let shared_writes = match self.env.caching {
+                    Caching::Enabled(_) => MemcacheHandler::from(
+                        MemcacheClient::new(self.env.fb)
+                            .context("Failed to initialize memcache client")?,
+                    ),
+                    _ => MemcacheHandler::create_noop(),
+                };
+                Ok(Some(ReadConsistency::with_monitor(
+                    self.env.fb,
+                    self.env.logger.clone(),
+                    shard,
+                    ReadConsistencyConfig {
+                        read_your_writes_window: params.read_your_writes_window,
+                        max_replica_lag: params.max_replica_lag,
+                    },
+                    shared_writes,
+                    monitor,
+                )))
+            })
+            .await
+    }
+
     async fn sql_connections(
         &self,
         config: &MetadataDatabaseConfig,
@@ -332,6 +422,22 @@ impl RepoFactory {
         T::from_connections_with_schema(sql_connections)
     }
 
+    /// Commit graph storage shared by all the repos that use the same
+    /// metadata database.
+    async fn commit_graph_storage(
+        &self,
+        config: &MetadataDatabaseConfig,
+    ) -> Result<Arc<MultiRepoCommitGraphStorage>> {
+        self.commit_graph_storages
+            .get_or_try_init(config, || async move {
+                let builder = self.open::<SqlCommitGraphStorageBuilder>(config).await?;
+                Ok(Arc::new(builder.build_multi_repo(RendezVousOptions {
+                    free_connections: 5,
+                })))
+            })
+            .await
+    }
+
     async fn blobstore_no_cache(&self, config: &BlobConfig) -> Result<Arc<dyn Blobstore>> {
         make_blobstore(
             self.env.fb,
@@ -359,6 +465,12 @@ impl RepoFactory {
         if self.env.readonly_storage.0 {
             blobstore = Arc::new(ReadOnlyBlobstore::new(blobstore));
         }
+        if repo_config.lfs.shared_storage {
+            blobstore = Arc::new(SharedLfsFallbackBlobstore::new(
+                blobstore,
+                repo_identity.id().prefix(),
+            ));
+        }
 
         let redacted_blobs = match repo_config.redaction {
             Redaction::Enabled => {
@@ -483,7 +595,7 @@ impl RepoFactory {
             || self.env.logger.new(o!()),
             |id| {
                 let repo_name = String::from(id.name());
-                self.env.logger.new(o!("repo" => repo_name))
+                self.env.logger.new(o!(log_keys::REPO => repo_name))
             },
         );
         let session = SessionContainer::new_with_defaults(self.env.fb);
@@ -626,6 +738,24 @@ pub enum RepoFactoryError {
     #[error("Error opening mutable counters")]
     MutableCounters,
 
+    #[error("Error opening admin audit log")]
+    AdminAuditLog,
+
+    #[error("Error opening hidden changesets")]
+    HiddenChangesets,
+
+    #[error("Error opening commit signatures")]
+    CommitSignatures,
+
+    #[error("Error opening repo stats")]
+    RepoStats,
+
+    #[error("Error opening push log")]
+    PushLog,
+
+    #[error("Error opening repo tags")]
+    RepoTags,
+
     #[error("Error creating hook manager")]
     HookManager,
 
@@ -668,6 +798,7 @@ impl RepoFactory {
         repo_identity: &ArcRepoIdentity,
         repo_config: &ArcRepoConfig,
         commit_graph: &ArcCommitGraph,
+        hidden_changesets: &ArcHiddenChangesets,
     ) -> Result<ArcChangesets> {
         let builder = self
             .open::<SqlChangesetsBuilder>(&repo_config.storage_config.metadata)
@@ -685,13 +816,18 @@ impl RepoFactory {
                 Arc::new(changesets)
             };
 
-        Ok(Arc::new(ChangesetsCommitGraphCompat::new(
+        let changesets = Arc::new(ChangesetsCommitGraphCompat::new(
             self.env.fb,
             possibly_cached_changesets,
             commit_graph.clone(),
             repo_identity.name().to_string(),
             repo_config.commit_graph_config.scuba_table.as_deref(),
-        )?))
+        )?);
+
+        Ok(Arc::new(HidingChangesets::new(
+            changesets,
+            hidden_changesets.clone(),
+        )))
     }
 
     pub fn changeset_fetcher(
@@ -710,11 +846,19 @@ impl RepoFactory {
         repo_config: &ArcRepoConfig,
         repo_identity: &ArcRepoIdentity,
     ) -> Result<ArcSqlBookmarks> {
-        let sql_bookmarks = self
-            .open::<SqlBookmarksBuilder>(&repo_config.storage_config.metadata)
+        let metadata = &repo_config.storage_config.metadata;
+        let mut builder = self
+            .open::<SqlBookmarksBuilder>(metadata)
+            .await
+            .context(RepoFactoryError::Bookmarks)?;
+        if let Some(read_consistency) = self
+            .read_consistency(metadata)
             .await
             .context(RepoFactoryError::Bookmarks)?
-            .with_repo_id(repo_identity.id());
+        {
+            builder = builder.with_read_consistency(read_consistency);
+        }
+        let sql_bookmarks = builder.with_repo_id(repo_identity.id());
 
         Ok(Arc::new(sql_bookmarks))
     }
@@ -723,15 +867,43 @@ impl RepoFactory {
         &self,
         sql_bookmarks: &ArcSqlBookmarks,
         repo_identity: &ArcRepoIdentity,
+        repo_config: &ArcRepoConfig,
     ) -> ArcBookmarks {
-        Arc::new(CachedBookmarks::new(
+        let bookmarks: ArcBookmarks = Arc::new(CachedBookmarks::new(
             sql_bookmarks.clone(),
             repo_identity.id(),
-        ))
+        ));
+        match &repo_config.view {
+            Some(view) => {
+                let patterns = view
+                    .bookmarks
+                    .iter()
+                    .cloned()
+                    .map(ComparableRegex::into_inner)
+                    .collect();
+                Arc::new(ViewBookmarks::new(bookmarks, patterns))
+            }
+            None => bookmarks,
+        }
     }
 
-    pub fn bookmark_update_log(&self, sql_bookmarks: &ArcSqlBookmarks) -> ArcBookmarkUpdateLog {
-        sql_bookmarks.clone()
+    pub fn bookmark_update_log(
+        &self,
+        sql_bookmarks: &ArcSqlBookmarks,
+        repo_config: &ArcRepoConfig,
+    ) -> ArcBookmarkUpdateLog {
+        match &repo_config.view {
+            Some(view) => {
+                let patterns = view
+                    .bookmarks
+                    .iter()
+                    .cloned()
+                    .map(ComparableRegex::into_inner)
+                    .collect();
+                Arc::new(ViewBookmarkUpdateLog::new(sql_bookmarks.clone(), patterns))
+            }
+            None => sql_bookmarks.clone(),
+        }
     }
 
     pub async fn phases(
@@ -748,7 +920,10 @@ impl RepoFactory {
         if let Some(cache_handler_factory) = self.cache_handler_factory("phases")? {
             sql_phases_builder.enable_caching(cache_handler_factory);
         }
-        let heads_fetcher = bookmark_heads_fetcher(bookmarks.clone());
+        let heads_fetcher = configured_bookmark_heads_fetcher(
+            bookmarks.clone(),
+            repo_config.publishing_bookmarks.clone(),
+        );
         Ok(sql_phases_builder.build(repo_identity.id(), changeset_fetcher.clone(), heads_fetcher))
     }
 
@@ -883,6 +1058,7 @@ impl RepoFactory {
                 .unwrap_or_default(),
             repo_name,
             &common_config.global_allowlist,
+            &common_config.authorization_rules,
         )
         .await?;
         Ok(Arc::new(permission_checker))
@@ -893,6 +1069,9 @@ impl RepoFactory {
         repo_config: &ArcRepoConfig,
         repo_identity: &ArcRepoIdentity,
     ) -> Result<ArcFilenodes> {
+        if repo_config.filenodes_config.disable_generation {
+            return Ok(Arc::new(DisabledFilenodes));
+        }
         let sql_factory = self
             .sql_factory(&repo_config.storage_config.metadata)
             .await?;
@@ -1068,9 +1247,12 @@ impl RepoFactory {
         repo_config: &ArcRepoConfig,
         common_config: &ArcCommonConfig,
     ) -> Result<ArcRepoBlobstore> {
-        let blobstore = self
+        let mut blobstore = self
             .blobstore(&repo_config.storage_config.blobstore)
             .await?;
+        if let Some(retry_config) = &repo_config.blobstore_retry_config {
+            blobstore = Arc::new(RetryBlobstore::new(blobstore, retry_policy(retry_config)));
+        }
         Ok(Arc::new(
             self.repo_blobstore_from_blobstore(
                 repo_identity,
@@ -1087,6 +1269,7 @@ impl RepoFactory {
             FilestoreConfig::no_chunking_filestore,
             |p| FilestoreConfig {
                 chunk_size: Some(p.chunk_size),
+                chunking_threshold: p.chunking_threshold,
                 concurrency: p.concurrency,
             },
         );
@@ -1200,7 +1383,7 @@ impl RepoFactory {
         let logger = self
             .env
             .logger
-            .new(o!("repo" => repo_identity.name().to_string()));
+            .new(o!(log_keys::REPO => repo_identity.name().to_string()));
         let live_commit_sync_config = Arc::new(CfgrLiveCommitSyncConfig::new(
             &logger,
             &self.env.config_store,
@@ -1226,6 +1409,88 @@ impl RepoFactory {
         ))
     }
 
+    pub async fn admin_audit_log(
+        &self,
+        repo_identity: &ArcRepoIdentity,
+        repo_config: &ArcRepoConfig,
+    ) -> Result<ArcAdminAuditLog> {
+        Ok(Arc::new(
+            self.open::<SqlAdminAuditLogBuilder>(&repo_config.storage_config.metadata)
+                .await
+                .context(RepoFactoryError::AdminAuditLog)?
+                .build(repo_identity.id()),
+        ))
+    }
+
+    pub async fn hidden_changesets(
+        &self,
+        repo_identity: &ArcRepoIdentity,
+        repo_config: &ArcRepoConfig,
+    ) -> Result<ArcHiddenChangesets> {
+        let hidden_changesets = self
+            .open::<SqlHiddenChangesetsBuilder>(&repo_config.storage_config.metadata)
+            .await
+            .context(RepoFactoryError::HiddenChangesets)?
+            .build(repo_identity.id());
+        Ok(Arc::new(CachingHiddenChangesets::new(hidden_changesets)))
+    }
+
+    pub async fn commit_signatures(
+        &self,
+        repo_identity: &ArcRepoIdentity,
+        repo_config: &ArcRepoConfig,
+    ) -> Result<ArcCommitSignatures> {
+        Ok(Arc::new(
+            self.open::<SqlCommitSignaturesBuilder>(&repo_config.storage_config.metadata)
+                .await
+                .context(RepoFactoryError::CommitSignatures)?
+                .build(repo_identity.id()),
+        ))
+    }
+
+    pub async fn repo_stats(
+        &self,
+        repo_identity: &ArcRepoIdentity,
+        repo_config: &ArcRepoConfig,
+    ) -> Result<ArcRepoStats> {
+        Ok(Arc::new(
+            self.open::<SqlRepoStatsBuilder>(&repo_config.storage_config.metadata)
+                .await
+                .context(RepoFactoryError::RepoStats)?
+                .build(repo_identity.id()),
+        ))
+    }
+
+    pub async fn push_log(
+        &self,
+        repo_identity: &ArcRepoIdentity,
+        repo_config: &ArcRepoConfig,
+    ) -> Result<ArcPushLog> {
+        Ok(Arc::new(
+            self.open::<SqlPushLogBuilder>(&repo_config.storage_config.metadata)
+                .await
+                .context(RepoFactoryError::PushLog)?
+                .build(repo_identity.id()),
+        ))
+    }
+
+    pub async fn repo_tags(
+        &self,
+        repo_identity: &ArcRepoIdentity,
+        repo_config: &ArcRepoConfig,
+    ) -> Result<ArcRepoTags> {
+        Ok(Arc::new(
+            self.open::<SqlRepoTagsBuilder>(&repo_config.storage_config.metadata)
+                .await
+                .context(RepoFactoryError::RepoTags)?
+                .build(repo_identity.id()),
+        ))
+    }
+
+    pub fn review_creator(&self, repo_config: &ArcRepoConfig) -> Result<ArcReviewCreator> {
+        review_creation::review_creator(repo_config.push_to_review_config.as_ref())
+    }
+
     pub fn acl_regions(
         &self,
         repo_config: &ArcRepoConfig,
@@ -1299,6 +1564,7 @@ impl RepoFactory {
                 &disabled_hooks,
             )
             .await?;
+            hook_manager.set_webhooks(repo_config.webhooks.clone());
 
             <Result<_, anyhow::Error>>::Ok(hook_manager)
         }
@@ -1328,10 +1594,16 @@ impl RepoFactory {
         repo_config: &ArcRepoConfig,
         repo_identity: &ArcRepoIdentity,
     ) -> Result<ArcRepoLock> {
+        if let Some(mirror_config) = &repo_config.mirror_config {
+            return Ok(Arc::new(AlwaysLockedRepoLock::new(
+                repo_identity.id(),
+                mirror_config.read_only_info(),
+            )));
+        }
         match repo_config.readonly {
-            RepoReadOnly::ReadOnly(ref reason) => Ok(Arc::new(AlwaysLockedRepoLock::new(
+            RepoReadOnly::ReadOnly(ref info) => Ok(Arc::new(AlwaysLockedRepoLock::new(
                 repo_identity.id(),
-                reason.clone(),
+                info.clone(),
             ))),
             RepoReadOnly::ReadWrite => {
                 let sql = SqlRepoLock::with_metadata_database_config(
@@ -1375,11 +1647,13 @@ impl RepoFactory {
 
     pub async fn warm_bookmarks_cache(
         &self,
+        repo_config: &ArcRepoConfig,
         bookmarks: &ArcBookmarks,
         bookmark_update_log: &ArcBookmarkUpdateLog,
         repo_identity: &ArcRepoIdentity,
         repo_derived_data: &ArcRepoDerivedData,
         phases: &ArcPhases,
+        repo_blobstore: &ArcRepoBlobstore,
     ) -> Result<ArcBookmarksCache> {
         match self.env.warm_bookmarks_cache_derived_data {
             Some(derived_data) => {
@@ -1395,14 +1669,38 @@ impl RepoFactory {
 
                 match derived_data {
                     WarmBookmarksCacheDerivedData::HgOnly => {
-                        wbc_builder.add_hg_warmers(repo_derived_data, phases)?;
+                        wbc_builder.add_hg_warmers(
+                            repo_derived_data,
+                            phases,
+                            &repo_config.publishing_bookmarks,
+                        )?;
                     }
                     WarmBookmarksCacheDerivedData::AllKinds => {
-                        wbc_builder.add_all_warmers(repo_derived_data, phases)?;
+                        wbc_builder.add_all_warmers(
+                            repo_derived_data,
+                            phases,
+                            &repo_config.publishing_bookmarks,
+                        )?;
                     }
                     WarmBookmarksCacheDerivedData::NoDerivation => {}
                 }
 
+                match self.env.warm_bookmarks_cache_snapshot {
+                    Some(WarmBookmarksCacheSnapshotMode::Export) => {
+                        wbc_builder.export_snapshots(
+                            repo_blobstore.clone(),
+                            warm_bookmarks_cache::DEFAULT_SNAPSHOT_INTERVAL,
+                        );
+                    }
+                    Some(WarmBookmarksCacheSnapshotMode::Bootstrap) => {
+                        wbc_builder.bootstrap_from_snapshot(
+                            repo_blobstore.clone(),
+                            warm_bookmarks_cache::DEFAULT_SNAPSHOT_MAX_AGE,
+                        );
+                    }
+                    None => {}
+                }
+
                 Ok(Arc::new(
                     wbc_builder.build().watched(&self.env.logger).await?,
                 ))
@@ -1509,29 +1807,42 @@ impl RepoFactory {
 
     pub async fn commit_graph(
         &self,
-        repo_identity: &RepoIdentity,
+        repo_identity: &ArcRepoIdentity,
         repo_config: &RepoConfig,
+        repo_blobstore: &ArcRepoBlobstore,
     ) -> Result<ArcCommitGraph> {
         let sql_storage = self
-            .open::<SqlCommitGraphStorageBuilder>(&repo_config.storage_config.metadata)
+            .commit_graph_storage(&repo_config.storage_config.metadata)
             .await?
-            .build(
-                RendezVousOptions {
-                    free_connections: 5,
-                },
-                repo_identity.id(),
-            );
+            .repo(repo_identity.id());
         let maybe_cached_storage: Arc<dyn CommitGraphStorage> =
             if let Some(cache_handler_factory) = self.cache_handler_factory("commit_graph")? {
                 Arc::new(CachingCommitGraphStorage::new(
-                    Arc::new(sql_storage),
+                    sql_storage.clone(),
                     cache_handler_factory,
                 ))
             } else {
-                Arc::new(sql_storage)
+                sql_storage.clone()
+            };
+        let maybe_filtered_storage: Arc<dyn CommitGraphStorage> =
+            if repo_config.commit_graph_config.use_prefix_filter {
+                let prefix_filter = Arc::new(PrefixFilter::new(
+                    sql_storage,
+                    repo_blobstore.clone(),
+                ));
+                tokio::spawn(PrefixFilter::update_periodically(
+                    Arc::downgrade(&prefix_filter),
+                    self.ctx(Some(repo_identity)),
+                ));
+                Arc::new(PrefixFilterCommitGraphStorage::new(
+                    maybe_cached_storage,
+                    prefix_filter,
+                ))
+            } else {
+                maybe_cached_storage
             };
 
-        Ok(Arc::new(CommitGraph::new(maybe_cached_storage)))
+        Ok(Arc::new(CommitGraph::new(maybe_filtered_storage)))
     }
 }
 
@@ -1549,6 +1860,24 @@ fn lease_init(
     }
 }
 
+fn retry_policy(config: &RetryConfig) -> RetryPolicy {
+    RetryPolicy {
+        logic: RetryLogic::ExponentialWithJitter {
+            base: config.base_delay,
+            factor: 2.0,
+            jitter: config.jitter,
+        },
+        max_attempts: config.max_attempts,
+        attempt_timeout: config.attempt_timeout,
+        operation_timeout: config.operation_timeout,
+        budget: config.retry_budget_percent.map(|percent| {
+            // Bursts of up to `percent` retries are allowed, after which
+            // retries are limited to `percent`% of calls.
+            Arc::new(RetryBudget::new(percent as f64 / 100.0, percent as f64))
+        }),
+    }
+}
+
 fn build_scuba(
     fb: FacebookInit,
     scuba_table: Option<String>,
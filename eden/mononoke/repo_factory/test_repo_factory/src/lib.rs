@@ -12,6 +12,8 @@ use std::sync::Arc;
 
 use acl_regions::build_acl_regions;
 use acl_regions::ArcAclRegions;
+use admin_audit_log::ArcAdminAuditLog;
+use admin_audit_log::SqlAdminAuditLogBuilder;
 use anyhow::Result;
 use basename_suffix_skeleton_manifest::RootBasenameSuffixSkeletonManifest;
 use blame::BlameRoot;
@@ -24,7 +26,7 @@ use bonsai_hg_mapping::ArcBonsaiHgMapping;
 use bonsai_hg_mapping::SqlBonsaiHgMappingBuilder;
 use bonsai_svnrev_mapping::ArcBonsaiSvnrevMapping;
 use bonsai_svnrev_mapping::SqlBonsaiSvnrevMappingBuilder;
-use bookmarks::bookmark_heads_fetcher;
+use bookmarks::configured_bookmark_heads_fetcher;
 use bookmarks::ArcBookmarkUpdateLog;
 use bookmarks::ArcBookmarks;
 use bookmarks::BookmarkKey;
@@ -37,6 +39,8 @@ use changesets::ArcChangesets;
 use changesets_impl::SqlChangesetsBuilder;
 use commit_graph::ArcCommitGraph;
 use commit_graph::CommitGraph;
+use commit_signatures::ArcCommitSignatures;
+use commit_signatures::SqlCommitSignaturesBuilder;
 use context::CoreContext;
 use dbbookmarks::ArcSqlBookmarks;
 use dbbookmarks::SqlBookmarksBuilder;
@@ -52,9 +56,14 @@ use filestore::ArcFilestoreConfig;
 use filestore::FilestoreConfig;
 use fsnodes::RootFsnodeId;
 use git_types::TreeHandle;
+use hidden_changesets::ArcHiddenChangesets;
+use hidden_changesets::CachingHiddenChangesets;
+use hidden_changesets::HidingChangesets;
+use hidden_changesets::SqlHiddenChangesetsBuilder;
 use hooks::ArcHookManager;
 use hooks::HookManager;
 use hooks_content_stores::RepoFileContentManager;
+use lfs_shared_store::SharedLfsFallbackBlobstore;
 use live_commit_sync_config::TestLiveCommitSyncConfig;
 use maplit::hashmap;
 use maplit::hashset;
@@ -79,6 +88,8 @@ use mutable_renames::MutableRenames;
 use mutable_renames::SqlMutableRenamesStore;
 use newfilenodes::NewFilenodesBuilder;
 use phases::ArcPhases;
+use push_log::ArcPushLog;
+use push_log::SqlPushLogBuilder;
 use pushrebase_mutation_mapping::ArcPushrebaseMutationMapping;
 use pushrebase_mutation_mapping::SqlPushrebaseMutationMappingConnection;
 use redactedblobstore::RedactedBlobs;
@@ -103,7 +114,12 @@ use repo_permission_checker::ArcRepoPermissionChecker;
 use repo_sparse_profiles::ArcRepoSparseProfiles;
 use repo_sparse_profiles::RepoSparseProfiles;
 use repo_sparse_profiles::SqlSparseProfilesSizes;
+use repo_stats::ArcRepoStats;
+use repo_stats::SqlRepoStatsBuilder;
+use repo_tags::ArcRepoTags;
+use repo_tags::SqlRepoTagsBuilder;
 use requests_table::SqlLongRunningRequestsQueue;
+use review_creation::ArcReviewCreator;
 use scuba_ext::MononokeScubaSampleBuilder;
 use segmented_changelog::new_test_segmented_changelog;
 use segmented_changelog::SegmentedChangelogSqlConnections;
@@ -239,6 +255,12 @@ impl TestRepoFactory {
         metadata_con.execute_batch(SqlRepoLock::CREATION_QUERY)?;
         metadata_con.execute_batch(SqlSparseProfilesSizes::CREATION_QUERY)?;
         metadata_con.execute_batch(StreamingCloneBuilder::CREATION_QUERY)?;
+        metadata_con.execute_batch(SqlAdminAuditLogBuilder::CREATION_QUERY)?;
+        metadata_con.execute_batch(SqlHiddenChangesetsBuilder::CREATION_QUERY)?;
+        metadata_con.execute_batch(SqlCommitSignaturesBuilder::CREATION_QUERY)?;
+        metadata_con.execute_batch(SqlRepoStatsBuilder::CREATION_QUERY)?;
+        metadata_con.execute_batch(SqlPushLogBuilder::CREATION_QUERY)?;
+        metadata_con.execute_batch(SqlRepoTagsBuilder::CREATION_QUERY)?;
         let metadata_db =
             SqlConnectionsWithSchema::new_single(Connection::with_sqlite(metadata_con));
 
@@ -343,11 +365,18 @@ impl TestRepoFactory {
     }
 
     /// Construct Changesets using the in-memory metadata database.
-    pub fn changesets(&self, repo_identity: &ArcRepoIdentity) -> Result<ArcChangesets> {
-        Ok(Arc::new(
+    pub fn changesets(
+        &self,
+        repo_identity: &ArcRepoIdentity,
+        hidden_changesets: &ArcHiddenChangesets,
+    ) -> Result<ArcChangesets> {
+        let changesets =
             SqlChangesetsBuilder::from_sql_connections(self.metadata_db.clone().into())
-                .build(RendezVousOptions::for_test(), repo_identity.id()),
-        ))
+                .build(RendezVousOptions::for_test(), repo_identity.id());
+        Ok(Arc::new(HidingChangesets::new(
+            Arc::new(changesets),
+            hidden_changesets.clone(),
+        )))
     }
 
     /// Construct a Changeset Fetcher.
@@ -384,12 +413,16 @@ impl TestRepoFactory {
     pub fn phases(
         &self,
         repo_identity: &ArcRepoIdentity,
+        repo_config: &ArcRepoConfig,
         bookmarks: &ArcBookmarks,
         changeset_fetcher: &ArcChangesetFetcher,
     ) -> ArcPhases {
         let sql_phases_builder =
             SqlPhasesBuilder::from_sql_connections(self.metadata_db.clone().into());
-        let heads_fetcher = bookmark_heads_fetcher(bookmarks.clone());
+        let heads_fetcher = configured_bookmark_heads_fetcher(
+            bookmarks.clone(),
+            repo_config.publishing_bookmarks.clone(),
+        );
         sql_phases_builder.build(repo_identity.id(), changeset_fetcher.clone(), heads_fetcher)
     }
 
@@ -530,9 +563,20 @@ impl TestRepoFactory {
     }
 
     /// Construct the RepoBlobstore using the blobstore in the factory.
-    pub fn repo_blobstore(&self, repo_identity: &ArcRepoIdentity) -> ArcRepoBlobstore {
+    pub fn repo_blobstore(
+        &self,
+        repo_identity: &ArcRepoIdentity,
+        repo_config: &ArcRepoConfig,
+    ) -> ArcRepoBlobstore {
+        let mut blobstore = self.blobstore.clone();
+        if repo_config.lfs.shared_storage {
+            blobstore = Arc::new(SharedLfsFallbackBlobstore::new(
+                blobstore,
+                repo_identity.id().prefix(),
+            ));
+        }
         let repo_blobstore = RepoBlobstore::new(
-            self.blobstore.clone(),
+            blobstore,
             self.redacted.clone(),
             repo_identity.id(),
             MononokeScubaSampleBuilder::with_discard(),
@@ -546,6 +590,7 @@ impl TestRepoFactory {
             FilestoreConfig::no_chunking_filestore,
             |p| FilestoreConfig {
                 chunk_size: Some(p.chunk_size),
+                chunking_threshold: p.chunking_threshold,
                 concurrency: p.concurrency,
             },
         );
@@ -646,6 +691,65 @@ impl TestRepoFactory {
         ))
     }
 
+    /// Admin audit log
+    pub fn admin_audit_log(&self, repo_identity: &ArcRepoIdentity) -> Result<ArcAdminAuditLog> {
+        Ok(Arc::new(
+            SqlAdminAuditLogBuilder::from_sql_connections(self.metadata_db.clone().into())
+                .build(repo_identity.id()),
+        ))
+    }
+
+    /// Hidden changesets
+    pub fn hidden_changesets(
+        &self,
+        repo_identity: &ArcRepoIdentity,
+    ) -> Result<ArcHiddenChangesets> {
+        let hidden_changesets =
+            SqlHiddenChangesetsBuilder::from_sql_connections(self.metadata_db.clone().into())
+                .build(repo_identity.id());
+        Ok(Arc::new(CachingHiddenChangesets::new(hidden_changesets)))
+    }
+
+    /// Commit signatures
+    pub fn commit_signatures(
+        &self,
+        repo_identity: &ArcRepoIdentity,
+    ) -> Result<ArcCommitSignatures> {
+        Ok(Arc::new(
+            SqlCommitSignaturesBuilder::from_sql_connections(self.metadata_db.clone().into())
+                .build(repo_identity.id()),
+        ))
+    }
+
+    /// Repo stats
+    pub fn repo_stats(&self, repo_identity: &ArcRepoIdentity) -> Result<ArcRepoStats> {
+        Ok(Arc::new(
+            SqlRepoStatsBuilder::from_sql_connections(self.metadata_db.clone().into())
+                .build(repo_identity.id()),
+        ))
+    }
+
+    /// Push log
+    pub fn push_log(&self, repo_identity: &ArcRepoIdentity) -> Result<ArcPushLog> {
+        Ok(Arc::new(
+            SqlPushLogBuilder::from_sql_connections(self.metadata_db.clone().into())
+                .build(repo_identity.id()),
+        ))
+    }
+
+    /// Review creator
+    pub fn review_creator(&self, repo_config: &ArcRepoConfig) -> Result<ArcReviewCreator> {
+        review_creation::review_creator(repo_config.push_to_review_config.as_ref())
+    }
+
+    /// Repo tags
+    pub fn repo_tags(&self, repo_identity: &ArcRepoIdentity) -> Result<ArcRepoTags> {
+        Ok(Arc::new(
+            SqlRepoTagsBuilder::from_sql_connections(self.metadata_db.clone().into())
+                .build(repo_identity.id()),
+        ))
+    }
+
     /// Set of DerivedDataManagers for DDS
     pub fn derived_data_manager_set(
         &self,
@@ -617,6 +617,7 @@ async fn merge_imported_commit(
         file_changes: Default::default(),
         is_snapshot: false,
         git_annotated_tag: None,
+        extras: None,
     }
     .freeze()?;
 
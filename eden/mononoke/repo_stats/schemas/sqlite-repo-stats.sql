@@ -0,0 +1,20 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+CREATE TABLE IF NOT EXISTS `repo_stats` (
+  `id` INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  `repo_id` INTEGER NOT NULL,
+  `bookmark` VARCHAR(512) NOT NULL,
+  `timestamp` BIGINT NOT NULL,
+  `cs_id` VARBINARY(32) NOT NULL,
+  `file_count` BIGINT NOT NULL,
+  `total_size` BIGINT NOT NULL,
+  `directories` TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS `repo_stats_repo_bookmark`
+  ON `repo_stats` (`repo_id`, `bookmark`, `id`);
@@ -0,0 +1,269 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Repo stats record the size of a repository at the tips of its bookmarks
+//! over time: the total number of files, their total size, and the same for
+//! each top-level directory.
+//!
+//! Entries are collected periodically, so that the series for a bookmark
+//! shows how the repository has grown.
+
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use context::CoreContext;
+use context::PerfCounterType;
+use mononoke_types::ChangesetId;
+use mononoke_types::RepositoryId;
+use mononoke_types::Timestamp;
+use serde::Deserialize;
+use serde::Serialize;
+use sql_construct::SqlConstruct;
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::mononoke_queries;
+use sql_ext::SqlConnections;
+
+/// The number and total size of the files in a directory, recursively.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DirectoryStats {
+    pub file_count: u64,
+    pub total_size: u64,
+}
+
+/// The stats of a repository at the tip of a bookmark.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RepoStatsEntry {
+    /// When the stats were collected.
+    pub timestamp: Timestamp,
+    pub bookmark: String,
+    /// The changeset the bookmark pointed to.
+    pub cs_id: ChangesetId,
+    /// The number of files in the repository.
+    pub file_count: u64,
+    /// The total size of the files in the repository, in bytes.
+    pub total_size: u64,
+    /// Stats for each top-level directory. Files at the root of the
+    /// repository are counted under the empty name.
+    pub directories: BTreeMap<String, DirectoryStats>,
+}
+
+#[facet::facet]
+#[async_trait]
+pub trait RepoStats: Send + Sync {
+    /// Record the stats collected at a bookmark.
+    async fn add_entry(&self, ctx: &CoreContext, entry: RepoStatsEntry) -> Result<()>;
+
+    /// The most recently collected stats for a bookmark.
+    async fn latest(&self, ctx: &CoreContext, bookmark: &str) -> Result<Option<RepoStatsEntry>>;
+
+    /// List the most recently collected stats for a bookmark, newest first.
+    async fn list(
+        &self,
+        ctx: &CoreContext,
+        bookmark: &str,
+        limit: u64,
+    ) -> Result<Vec<RepoStatsEntry>>;
+}
+
+mononoke_queries! {
+    write AddRepoStatsEntry(
+        repo_id: RepositoryId,
+        bookmark: &str,
+        timestamp: Timestamp,
+        cs_id: ChangesetId,
+        file_count: u64,
+        total_size: u64,
+        directories: &str,
+    ) {
+        none,
+        "INSERT INTO repo_stats
+            (repo_id, bookmark, timestamp, cs_id, file_count, total_size, directories)
+         VALUES
+            ({repo_id}, {bookmark}, {timestamp}, {cs_id},
+             {file_count}, {total_size}, {directories})"
+    }
+
+    read ListRepoStatsEntries(
+        repo_id: RepositoryId,
+        bookmark: &str,
+        limit: u64,
+    ) -> (Timestamp, ChangesetId, u64, u64, String) {
+        "SELECT timestamp, cs_id, file_count, total_size, directories
+         FROM repo_stats
+         WHERE repo_id = {repo_id} AND bookmark = {bookmark}
+         ORDER BY id DESC
+         LIMIT {limit}"
+    }
+}
+
+pub struct SqlRepoStats {
+    repo_id: RepositoryId,
+    connections: SqlConnections,
+}
+
+pub struct SqlRepoStatsBuilder {
+    connections: SqlConnections,
+}
+
+impl SqlConstruct for SqlRepoStatsBuilder {
+    const LABEL: &'static str = "repo_stats";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-repo-stats.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self { connections }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SqlRepoStatsBuilder {}
+
+impl SqlRepoStatsBuilder {
+    pub fn build(self, repo_id: RepositoryId) -> SqlRepoStats {
+        SqlRepoStats {
+            repo_id,
+            connections: self.connections,
+        }
+    }
+}
+
+#[async_trait]
+impl RepoStats for SqlRepoStats {
+    async fn add_entry(&self, ctx: &CoreContext, entry: RepoStatsEntry) -> Result<()> {
+        let directories = serde_json::to_string(&entry.directories)?;
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        AddRepoStatsEntry::query(
+            &self.connections.write_connection,
+            &self.repo_id,
+            &entry.bookmark.as_str(),
+            &entry.timestamp,
+            &entry.cs_id,
+            &entry.file_count,
+            &entry.total_size,
+            &directories.as_str(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn latest(&self, ctx: &CoreContext, bookmark: &str) -> Result<Option<RepoStatsEntry>> {
+        Ok(self.list(ctx, bookmark, 1).await?.into_iter().next())
+    }
+
+    async fn list(
+        &self,
+        ctx: &CoreContext,
+        bookmark: &str,
+        limit: u64,
+    ) -> Result<Vec<RepoStatsEntry>> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+        let rows = ListRepoStatsEntries::query(
+            &self.connections.read_connection,
+            &self.repo_id,
+            &bookmark,
+            &limit,
+        )
+        .await?;
+        rows.into_iter()
+            .map(|(timestamp, cs_id, file_count, total_size, directories)| {
+                Ok(RepoStatsEntry {
+                    timestamp,
+                    bookmark: bookmark.to_string(),
+                    cs_id,
+                    file_count,
+                    total_size,
+                    directories: serde_json::from_str(&directories)
+                        .with_context(|| format!("Invalid directory stats for {}", cs_id))?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use maplit::btreemap;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+    use mononoke_types_mocks::repo::REPO_ONE;
+    use mononoke_types_mocks::repo::REPO_ZERO;
+
+    use super::*;
+
+    fn entry(bookmark: &str, cs_id: ChangesetId, file_count: u64) -> RepoStatsEntry {
+        RepoStatsEntry {
+            timestamp: Timestamp::from_timestamp_secs(1000),
+            bookmark: bookmark.to_string(),
+            cs_id,
+            file_count,
+            total_size: file_count * 10,
+            directories: btreemap! {
+                "".to_string() => DirectoryStats {
+                    file_count: 1,
+                    total_size: 10,
+                },
+                "src".to_string() => DirectoryStats {
+                    file_count: file_count - 1,
+                    total_size: (file_count - 1) * 10,
+                },
+            },
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_add_and_list(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo_stats = SqlRepoStatsBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+
+        assert_eq!(repo_stats.latest(&ctx, "main").await?, None);
+
+        repo_stats
+            .add_entry(&ctx, entry("main", ONES_CSID, 5))
+            .await?;
+        repo_stats
+            .add_entry(&ctx, entry("main", TWOS_CSID, 8))
+            .await?;
+        repo_stats
+            .add_entry(&ctx, entry("release", ONES_CSID, 5))
+            .await?;
+
+        assert_eq!(
+            repo_stats.latest(&ctx, "main").await?,
+            Some(entry("main", TWOS_CSID, 8))
+        );
+        assert_eq!(
+            repo_stats.list(&ctx, "main", 10).await?,
+            vec![entry("main", TWOS_CSID, 8), entry("main", ONES_CSID, 5)]
+        );
+        assert_eq!(repo_stats.list(&ctx, "main", 1).await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_entries_are_per_repo(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let builder = SqlRepoStatsBuilder::with_sqlite_in_memory()?;
+        let connections = builder.connections.clone();
+        let repo_stats = builder.build(REPO_ZERO);
+        let other_repo_stats =
+            SqlRepoStatsBuilder::from_sql_connections(connections).build(REPO_ONE);
+
+        repo_stats
+            .add_entry(&ctx, entry("main", ONES_CSID, 5))
+            .await?;
+
+        assert!(repo_stats.latest(&ctx, "main").await?.is_some());
+        assert_eq!(other_repo_stats.latest(&ctx, "main").await?, None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,17 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+CREATE TABLE IF NOT EXISTS `repo_tags` (
+  `repo_id` INTEGER NOT NULL,
+  `name` VARCHAR(512) NOT NULL,
+  `cs_id` VARBINARY(32) NOT NULL,
+  `message` TEXT NULL,
+  `signer` VARCHAR(255) NULL,
+  `creator` VARCHAR(255) NULL,
+  `created_at` BIGINT NOT NULL,
+  PRIMARY KEY (`repo_id`, `name`)
+);
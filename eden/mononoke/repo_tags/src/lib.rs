@@ -0,0 +1,300 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Repo tags are immutable named pointers to changesets.
+//!
+//! Unlike bookmarks, a tag is created once and can never be moved or
+//! deleted, so it reliably names the same changeset forever.  Tags are
+//! stored separately from bookmarks, and may carry a message and the
+//! identity of whoever signed them.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use context::CoreContext;
+use context::PerfCounterType;
+use mononoke_types::ChangesetId;
+use mononoke_types::RepositoryId;
+use mononoke_types::Timestamp;
+use sql_construct::SqlConstruct;
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::mononoke_queries;
+use sql_ext::SqlConnections;
+
+/// A tag, naming a changeset.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Tag {
+    pub name: String,
+    pub cs_id: ChangesetId,
+    /// The message the tag was created with, if any.
+    pub message: Option<String>,
+    /// Who signed the tag, if it was signed.
+    pub signer: Option<String>,
+    /// Who created the tag, if they are known.
+    pub creator: Option<String>,
+    pub created_at: Timestamp,
+}
+
+#[facet::facet]
+#[async_trait]
+pub trait RepoTags: Send + Sync {
+    /// Create a tag.  Tags can't be moved, so if a tag with the same name
+    /// already exists, nothing is changed and this returns `false`.
+    async fn create(&self, ctx: &CoreContext, tag: Tag) -> Result<bool>;
+
+    /// Get a tag by name.
+    async fn get(&self, ctx: &CoreContext, name: &str) -> Result<Option<Tag>>;
+
+    /// List tags whose names start with `prefix`, ordered by name.  If
+    /// `after` is given, only tags whose names sort after it are listed.
+    async fn list(
+        &self,
+        ctx: &CoreContext,
+        prefix: &str,
+        after: Option<&str>,
+        limit: u64,
+    ) -> Result<Vec<Tag>>;
+}
+
+mononoke_queries! {
+    write CreateTag(
+        repo_id: RepositoryId,
+        name: &str,
+        cs_id: ChangesetId,
+        message: Option<&str>,
+        signer: Option<&str>,
+        creator: Option<&str>,
+        created_at: Timestamp,
+    ) {
+        none,
+        mysql(
+            "INSERT IGNORE INTO repo_tags
+                (repo_id, name, cs_id, message, signer, creator, created_at)
+             VALUES
+                ({repo_id}, {name}, {cs_id}, {message}, {signer}, {creator}, {created_at})"
+        )
+        sqlite(
+            "INSERT OR IGNORE INTO repo_tags
+                (repo_id, name, cs_id, message, signer, creator, created_at)
+             VALUES
+                ({repo_id}, {name}, {cs_id}, {message}, {signer}, {creator}, {created_at})"
+        )
+    }
+
+    read GetTag(
+        repo_id: RepositoryId,
+        name: &str,
+    ) -> (String, ChangesetId, Option<String>, Option<String>, Option<String>, Timestamp) {
+        "SELECT name, cs_id, message, signer, creator, created_at
+         FROM repo_tags
+         WHERE repo_id = {repo_id} AND name = {name}"
+    }
+
+    read ListTags(
+        repo_id: RepositoryId,
+        prefix_like_pattern: String,
+        escape_character: &str,
+        after: &str,
+        limit: u64,
+    ) -> (String, ChangesetId, Option<String>, Option<String>, Option<String>, Timestamp) {
+        "SELECT name, cs_id, message, signer, creator, created_at
+         FROM repo_tags
+         WHERE repo_id = {repo_id}
+           AND name LIKE {prefix_like_pattern} ESCAPE {escape_character}
+           AND name > {after}
+         ORDER BY name ASC
+         LIMIT {limit}"
+    }
+}
+
+type TagRow = (
+    String,
+    ChangesetId,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Timestamp,
+);
+
+fn tag_from_row((name, cs_id, message, signer, creator, created_at): TagRow) -> Tag {
+    Tag {
+        name,
+        cs_id,
+        message,
+        signer,
+        creator,
+        created_at,
+    }
+}
+
+fn to_escaped_sql_like_pattern(prefix: &str) -> String {
+    let mut like_pattern = String::with_capacity(prefix.len() + 1);
+    for ch in prefix.chars() {
+        if ch == '\\' || ch == '%' || ch == '_' {
+            like_pattern.push('\\');
+        }
+        like_pattern.push(ch);
+    }
+    like_pattern.push('%');
+    like_pattern
+}
+
+pub struct SqlRepoTags {
+    repo_id: RepositoryId,
+    connections: SqlConnections,
+}
+
+pub struct SqlRepoTagsBuilder {
+    connections: SqlConnections,
+}
+
+impl SqlConstruct for SqlRepoTagsBuilder {
+    const LABEL: &'static str = "repo_tags";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-repo-tags.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self { connections }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SqlRepoTagsBuilder {}
+
+impl SqlRepoTagsBuilder {
+    pub fn build(self, repo_id: RepositoryId) -> SqlRepoTags {
+        SqlRepoTags {
+            repo_id,
+            connections: self.connections,
+        }
+    }
+}
+
+#[async_trait]
+impl RepoTags for SqlRepoTags {
+    async fn create(&self, ctx: &CoreContext, tag: Tag) -> Result<bool> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        let result = CreateTag::query(
+            &self.connections.write_connection,
+            &self.repo_id,
+            &tag.name.as_str(),
+            &tag.cs_id,
+            &tag.message.as_deref(),
+            &tag.signer.as_deref(),
+            &tag.creator.as_deref(),
+            &tag.created_at,
+        )
+        .await?;
+        Ok(result.affected_rows() == 1)
+    }
+
+    async fn get(&self, ctx: &CoreContext, name: &str) -> Result<Option<Tag>> {
+        // Tags never change once created, so a replica is only ever missing
+        // tags, which the master is then consulted for.
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+        let rows = GetTag::query(&self.connections.read_connection, &self.repo_id, &name).await?;
+        if let Some(row) = rows.into_iter().next() {
+            return Ok(Some(tag_from_row(row)));
+        }
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsMaster);
+        let rows = GetTag::query(
+            &self.connections.read_master_connection,
+            &self.repo_id,
+            &name,
+        )
+        .await?;
+        Ok(rows.into_iter().next().map(tag_from_row))
+    }
+
+    async fn list(
+        &self,
+        ctx: &CoreContext,
+        prefix: &str,
+        after: Option<&str>,
+        limit: u64,
+    ) -> Result<Vec<Tag>> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+        let rows = ListTags::query(
+            &self.connections.read_connection,
+            &self.repo_id,
+            &to_escaped_sql_like_pattern(prefix),
+            &"\\",
+            &after.unwrap_or(""),
+            &limit,
+        )
+        .await?;
+        Ok(rows.into_iter().map(tag_from_row).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+    use mononoke_types_mocks::repo::REPO_ONE;
+    use mononoke_types_mocks::repo::REPO_ZERO;
+
+    use super::*;
+
+    fn tag(name: &str, cs_id: ChangesetId) -> Tag {
+        Tag {
+            name: name.to_string(),
+            cs_id,
+            message: Some(format!("Release {}", name)),
+            signer: None,
+            creator: Some("alice".to_string()),
+            created_at: Timestamp::from_timestamp_secs(1000),
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_create_and_get(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let builder = SqlRepoTagsBuilder::with_sqlite_in_memory()?;
+        let connections = builder.connections.clone();
+        let tags = builder.build(REPO_ZERO);
+        let other_tags = SqlRepoTagsBuilder::from_sql_connections(connections).build(REPO_ONE);
+
+        assert!(tags.create(&ctx, tag("v1.0", ONES_CSID)).await?);
+        assert_eq!(tags.get(&ctx, "v1.0").await?, Some(tag("v1.0", ONES_CSID)));
+        assert_eq!(other_tags.get(&ctx, "v1.0").await?, None);
+
+        // Tags can't be moved.
+        assert!(!tags.create(&ctx, tag("v1.0", TWOS_CSID)).await?);
+        assert_eq!(tags.get(&ctx, "v1.0").await?, Some(tag("v1.0", ONES_CSID)));
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_list(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let tags = SqlRepoTagsBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+        for name in ["v1.1", "v1.0", "v2.0", "v1_x"] {
+            tags.create(&ctx, tag(name, ONES_CSID)).await?;
+        }
+
+        let names = |tags: Vec<Tag>| tags.into_iter().map(|tag| tag.name).collect::<Vec<_>>();
+        assert_eq!(
+            names(tags.list(&ctx, "", None, 10).await?),
+            vec!["v1.0", "v1.1", "v1_x", "v2.0"]
+        );
+        assert_eq!(
+            names(tags.list(&ctx, "v1.", None, 10).await?),
+            vec!["v1.0", "v1.1"]
+        );
+        assert_eq!(
+            names(tags.list(&ctx, "v1", Some("v1.0"), 2).await?),
+            vec!["v1.1", "v1_x"]
+        );
+
+        Ok(())
+    }
+}
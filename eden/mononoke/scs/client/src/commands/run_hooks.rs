@@ -38,11 +38,22 @@ pub(super) struct CommandArgs {
     to: String,
 }
 
+#[derive(Serialize)]
+struct HookRejection {
+    description: String,
+    long_description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+}
+
 #[derive(Serialize)]
 #[serde(tag = "status")]
 enum HookOutcome {
     Accepted,
-    Rejected { reason: String },
+    Rejected {
+        reason: String,
+        rejections: Vec<HookRejection>,
+    },
 }
 
 #[derive(Serialize)]
@@ -65,7 +76,17 @@ impl Render for RunHooksOutput {
             write!(w, "{} => ", hook_name)?;
             match outcome {
                 HookOutcome::Accepted => write!(w, "ACCEPTED\n")?,
-                HookOutcome::Rejected { reason } => write!(w, "REJECTED: {}\n", reason)?,
+                HookOutcome::Rejected { rejections, .. } => {
+                    write!(w, "REJECTED:\n")?;
+                    for rejection in rejections {
+                        match &rejection.path {
+                            Some(path) => {
+                                write!(w, "  {}: {}\n", path, rejection.long_description)?
+                            }
+                            None => write!(w, "  {}\n", rejection.long_description)?,
+                        }
+                    }
+                }
             };
         }
         Ok(())
@@ -105,10 +126,18 @@ pub(super) async fn run(app: ScscApp, args: CommandArgs) -> Result<()> {
                     thrift::HookOutcome::accepted(_) => HookOutcome::Accepted,
                     thrift::HookOutcome::rejections(rejs) => HookOutcome::Rejected {
                         reason: rejs
-                            .into_iter()
-                            .map(|rej| rej.long_description)
+                            .iter()
+                            .map(|rej| rej.long_description.as_str())
                             .collect::<Vec<_>>()
                             .join("\n"),
+                        rejections: rejs
+                            .into_iter()
+                            .map(|rej| HookRejection {
+                                description: rej.description,
+                                long_description: rej.long_description,
+                                path: rej.path,
+                            })
+                            .collect(),
                     },
                     thrift::HookOutcome::UnknownField(_) => anyhow::bail!("Unknown hook outcome"),
                 },
@@ -6,8 +6,10 @@
  */
 
 use std::collections::BTreeMap;
+use std::io::Write;
 
 use anyhow::Result;
+use serde::Serialize;
 use source_control::types as thrift;
 
 use crate::args::commit_id::map_commit_ids;
@@ -17,6 +19,7 @@ use crate::args::commit_id::CommitIdArgs;
 use crate::args::commit_id::SchemeArgs;
 use crate::commands::lookup::LookupOutput;
 use crate::connection::Connection;
+use crate::render::Render;
 use crate::ScscApp;
 
 #[derive(clap::Parser)]
@@ -54,6 +57,35 @@ pub(super) struct CommandArgs {
     hint_descendant_of_bookmark: Option<String>,
 }
 
+#[derive(Serialize)]
+struct XRepoLookupOutput {
+    #[serde(flatten)]
+    lookup: LookupOutput,
+    /// Either "exact" or "working_copy_equivalent", if the commit exists.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    xrepo_match: Option<&'static str>,
+}
+
+impl Render for XRepoLookupOutput {
+    type Args = SchemeArgs;
+
+    fn render(&self, args: &Self::Args, w: &mut dyn Write) -> Result<()> {
+        self.lookup.render(args, w)
+    }
+
+    fn render_json(&self, _args: &Self::Args, w: &mut dyn Write) -> Result<()> {
+        Ok(serde_json::to_writer(w, self)?)
+    }
+}
+
+fn xrepo_match_name(xrepo_match: &thrift::XRepoLookupMatch) -> Option<&'static str> {
+    match *xrepo_match {
+        thrift::XRepoLookupMatch::EXACT => Some("exact"),
+        thrift::XRepoLookupMatch::WORKING_COPY_EQUIVALENT => Some("working_copy_equivalent"),
+        _ => None,
+    }
+}
+
 async fn build_commit_hint(
     connection: &Connection,
     target_repo: &thrift::RepoSpecifier,
@@ -143,10 +175,13 @@ pub(super) async fn run(app: ScscApp, args: CommandArgs) -> Result<()> {
         None => BTreeMap::new(),
     };
 
-    let output = LookupOutput {
-        requested: commit_id.to_string(),
-        exists: response.exists,
-        ids,
+    let output = XRepoLookupOutput {
+        lookup: LookupOutput {
+            requested: commit_id.to_string(),
+            exists: response.exists,
+            ids,
+        },
+        xrepo_match: response.xrepo_match.as_ref().and_then(xrepo_match_name),
     };
 
     app.target.render_one(&args.scheme_args, output).await
@@ -207,6 +207,21 @@ struct CommitInfo {
   /// Extra git headers associated with the commit if the commit is a
   /// mirrored version from a git repo.
   10: optional map<small_binary, binary_bytes> git_extra_headers;
+
+  /// Typed metadata about the commit, if any was recorded.
+  11: optional CommitExtras extras;
+}
+
+/// Typed metadata about a commit.
+struct CommitExtras {
+  /// The branch the commit was created on.
+  1: optional string branch;
+
+  /// The people who signed off on the commit, in order.
+  2: list<string> signed_off_by;
+
+  /// The authenticated identity of the committer, e.g. "USER:alice".
+  3: optional string committer_identity;
 }
 
 struct BookmarkInfo {
@@ -222,6 +237,43 @@ struct BookmarkInfo {
   3: i64 last_update_timestamp_ns;
 }
 
+struct TagInfo {
+  /// The name of the tag.
+  1: string name;
+  /// The IDs of the tagged commit.
+  2: map<CommitIdentityScheme, CommitId> ids;
+  /// The message the tag was created with, if any.
+  3: optional string message;
+  /// Who signed the tag, if it was signed.
+  4: optional string signer;
+  /// Who created the tag, if they are known.
+  5: optional string creator;
+  /// When the tag was created, in seconds since the epoch.
+  6: i64 created_at;
+}
+
+struct DirectoryStats {
+  /// The number of files in the directory, recursively.
+  1: i64 file_count;
+  /// The total size of the files in the directory, recursively, in bytes.
+  2: i64 total_size;
+}
+
+struct RepoStats {
+  /// When the stats were collected, in seconds since the epoch.
+  1: i64 timestamp;
+  /// The IDs of the commit the bookmark pointed to when the stats were
+  /// collected.
+  2: map<CommitIdentityScheme, CommitId> ids;
+  /// The number of files in the repo.
+  3: i64 file_count;
+  /// The total size of the files in the repo, in bytes.
+  4: i64 total_size;
+  /// Stats for each top-level directory.  Files at the root of the repo are
+  /// counted under the empty name.
+  5: map<string, DirectoryStats> directories;
+}
+
 enum EntryType {
   /// Unknown type
   UNKNOWN = 0,
@@ -711,6 +763,19 @@ struct RepoResolveCommitPrefixParams {
   3: set<CommitIdentityScheme> identity_schemes;
 }
 
+struct RepoResolveCommitsParams {
+  /// The revisions to look up.  Each is a bookmark name, a full commit hash
+  /// or a hash prefix.  If a revision is both the name of a bookmark and a
+  /// hash prefix, it is resolved as the bookmark.
+  1: list<string> revisions;
+
+  /// Identity scheme of the hashes and prefixes in `revisions`.
+  2: CommitIdentityScheme hash_scheme;
+
+  /// Commit identity schemes to return.
+  3: set<CommitIdentityScheme> identity_schemes;
+}
+
 struct RepoBookmarkInfoParams {
   /// The bookmark name to look up.
   1: string bookmark_name;
@@ -722,6 +787,14 @@ struct RepoBookmarkInfoParams {
   2: set<CommitIdentityScheme> identity_schemes;
 }
 
+struct RepoStatsParams {
+  /// The bookmark to get the stats of.
+  1: string bookmark_name;
+
+  /// Commit identity schemes to return.
+  2: set<CommitIdentityScheme> identity_schemes;
+}
+
 const i64 REPO_LIST_BOOKMARKS_MAX_LIMIT = 10000;
 
 struct RepoListBookmarksParams {
@@ -742,6 +815,22 @@ struct RepoListBookmarksParams {
   5: set<CommitIdentityScheme> identity_schemes;
 }
 
+const i64 REPO_LIST_TAGS_MAX_LIMIT = 10000;
+
+struct RepoListTagsParams {
+  /// Prefix to match when listing tags.
+  1: string tag_prefix;
+
+  /// Limit to the number of tags that may match.
+  2: i64 limit;
+
+  /// Return tags after this name, to be used for paging.
+  3: optional string after;
+
+  /// Commit identity schemes to return.
+  4: set<CommitIdentityScheme> identity_schemes;
+}
+
 const i64 REPO_STACK_INFO_MAX_LIMIT = 10000;
 
 struct RepoStackInfoParams {
@@ -838,6 +927,9 @@ struct RepoCreateCommitParamsCommitInfo {
   /// Extra git headers associated with the commit if the commit is a
   /// mirrored version from a git repo.
   7: optional map<small_binary, binary_bytes> git_extra_headers;
+
+  /// Typed metadata about the commit.
+  8: optional CommitExtras extras;
 }
 
 struct RepoCreateCommitParams {
@@ -865,6 +957,15 @@ struct RepoCreateCommitParams {
 
   /// Service identity to use for this commit creation.
   5: optional string service_identity;
+
+  /// If set, the new commit is landed onto this bookmark via pushrebase
+  /// once it has been created.  The commit must have exactly one parent.
+  /// Hooks are run as part of landing, and hook rejections and pushrebase
+  /// conflicts are reported as exceptions.
+  6: optional string land_to_bookmark;
+
+  /// The pushvars to use when landing the commit.
+  7: optional map<string, binary> pushvars;
 }
 
 struct RepoCreateStackParamsCommit {
@@ -903,6 +1004,26 @@ struct RepoCreateBookmarkParams {
   3: optional string service_identity;
 }
 
+struct RepoCreateTagParams {
+  /// The name of the tag to create.
+  1: string tag;
+
+  /// The commit to tag.
+  2: CommitId target;
+
+  /// The message of the tag.
+  3: optional string message;
+
+  /// Who signed the tag, if it is signed.
+  4: optional string signer;
+
+  /// Service identity to use for this tag creation.
+  5: optional string service_identity;
+
+  /// Commit identity schemes to return.
+  6: set<CommitIdentityScheme> identity_schemes;
+}
+
 struct RepoMoveBookmarkParams {
   /// The name of the bookmark to move.
   1: string bookmark;
@@ -991,6 +1112,20 @@ struct RepoLandStackParams {
   9: BookmarkKindRestrictions bookmark_restrictions = BookmarkKindRestrictions.ANY_KIND;
 }
 
+struct RepoLandStackPreviewParams {
+  /// The name of the bookmark the stack would be landed to.
+  1: string bookmark;
+
+  /// The head commit of the stack that would be landed.
+  2: CommitId head;
+
+  /// The parent of the bottom of the stack that would be landed.
+  3: CommitId base;
+
+  /// The set of commit identity schemes to return in the response.
+  4: set<CommitIdentityScheme> identity_schemes;
+}
+
 /// Only support the types of derived data that we wish to expose to SCS clients.
 /// This can be extended later if other usecases arrise.
 /// See https://www.internalfb.com/code/fbsource/[f84d7f31d5e251d6b1a4dcacce880e4b29a73652]/fbcode/eden/mononoke/derived_data/remote/if/derived_data_service.thrift?lines=40
@@ -1052,6 +1187,10 @@ struct CommitIsAncestorOfParams {
   1: CommitId descendant_commit_id;
 }
 
+struct CommitSignatureVerificationParams {}
+
+struct CommitPhaseParams {}
+
 struct CommitCommonBaseWithParams {
   1: CommitId other_commit_id;
   2: set<CommitIdentityScheme> identity_schemes;
@@ -1122,6 +1261,33 @@ struct CommitFileDiffsParams {
   5: optional i64 diff_size_limit;
 }
 
+const i64 COMMIT_DIFF_FILE_SIZE_LIMIT = 0x1000000; /// 16MiB
+
+struct CommitDiffParams {
+  /// The commit to diff against.  If not set, the commit is diffed against
+  /// its first parent, or shown as adding all of its files if it has none.
+  1: optional CommitId other_commit_id;
+
+  /// Number of lines of unified context around differences (default: 3)
+  2: i64 context = 3;
+
+  /// Render copies and renames as such.  Copies and renames are only
+  /// known when diffing against a parent.
+  3: bool detect_renames = true;
+
+  /// Only diff files at or under these paths.
+  4: optional list<Path> paths;
+
+  /// Only diff files after this path, to resume a stream that was
+  /// interrupted.  Use the path of the last file that was received.
+  5: optional Path after;
+
+  /// Files of which either version is larger than this are not diffed: a
+  /// placeholder diff is returned instead.  Defaults to, and can't be more
+  /// than, COMMIT_DIFF_FILE_SIZE_LIMIT.
+  6: optional i64 file_size_limit;
+}
+
 const i64 COMMIT_FIND_FILES_MAX_LIMIT = 100000;
 
 struct CommitFindFilesParams {
@@ -1233,6 +1399,11 @@ struct CommitRunHooksParams {
   2: optional map<string, binary> pushvars;
 }
 
+struct CommitDerivationTraceParams {
+  /// The name of the type of derived data to derive, e.g. `unodes`.
+  1: string derived_data_type;
+}
+
 struct CommitPathExistsParams {}
 
 struct CommitPathInfoParams {}
@@ -1246,6 +1417,11 @@ struct CommitMultiplePathInfoParams {
 
 const i64 TREE_LIST_MAX_LIMIT = 10000;
 
+/// Sorting tree entries by mtime requires looking up the history of every
+/// entry, so it is only supported for directories with at most this many
+/// (matching) entries.
+const i64 TREE_LIST_MTIME_SORT_MAX_ENTRIES = 10000;
+
 struct CommitPathBlameParams {
   /// Which format to use in the response.
   1: BlameFormat format;
@@ -1327,12 +1503,41 @@ struct CommitSparseProfileSizeParams {
 
 struct TreeExistsParams {}
 
+enum TreeListSortOrder {
+  /// Sort entries by name.
+  NAME = 0,
+
+  /// Sort entries by size: the size of the file for files, or the total
+  /// size of all files in the directory for directories.  Ties are broken
+  /// by name.
+  SIZE = 1,
+
+  /// Sort entries by the author date of the commit that last modified them.
+  /// Ties are broken by name.  Only supported for trees specified by commit
+  /// and path, with at most TREE_LIST_MTIME_SORT_MAX_ENTRIES entries.
+  MTIME = 2,
+}
+
 struct TreeListParams {
-  /// Start listing at this offset in the tree.
+  /// Start listing at this offset in the tree, or after the entry
+  /// identified by `continuation_token` if it is set.
   1: i64 offset;
 
   /// Limit to the number of tree entries listed.
   2: i64 limit;
+
+  /// Continue listing from where a previous request with the same sort
+  /// order left off.  Use the `continuation_token` of its response.
+  3: optional string continuation_token;
+
+  /// The order to list entries in.
+  4: TreeListSortOrder sort_order;
+
+  /// List entries in descending order.
+  5: bool descending;
+
+  /// If set, only list entries of these types.
+  6: optional set<EntryType> entry_types;
 }
 
 struct FileExistsParams {}
@@ -1392,6 +1597,17 @@ struct CommitLookupXRepoParams {
   3: optional CandidateSelectionHint candidate_selection_hint;
 }
 
+/// How the commit returned by a cross-repo lookup relates to the commit
+/// that was looked up.
+enum XRepoLookupMatch {
+  /// The commit is the rewritten form of the requested commit.
+  EXACT = 0,
+  /// The requested commit was not rewritten into the other repo (e.g. it
+  /// only touched files that are not synced), and the returned commit is
+  /// its nearest synced ancestor, which has the same working copy.
+  WORKING_COPY_EQUIVALENT = 1,
+}
+
 /// Synchronization target
 struct MegarepoTarget {
   /// Mononoke repository id, where the target is located
@@ -1601,11 +1817,39 @@ struct RepoResolveCommitPrefixResponse {
   2: optional map<CommitIdentityScheme, CommitId> ids;
 }
 
+enum ResolvedCommitSource {
+  BOOKMARK = 0,
+  HASH = 1,
+}
+
+struct ResolvedCommit {
+  /// Whether the revision resolved to a single commit.
+  1: RepoResolveCommitPrefixResponseType resolved_type;
+
+  /// Whether the revision was resolved as a bookmark or as a hash
+  /// (if type == RESOLVED)
+  2: optional ResolvedCommitSource source;
+
+  /// The resolved commit IDs in the requested schemes (if type == RESOLVED)
+  3: optional map<CommitIdentityScheme, CommitId> ids;
+}
+
+struct RepoResolveCommitsResponse {
+  /// The result for each requested revision, in the same order.
+  1: list<ResolvedCommit> results;
+}
+
 struct RepoBookmarkInfoResponse {
   /// Bookmark info, null if doesn't exist.
   1: optional BookmarkInfo info;
 }
 
+struct RepoStatsResponse {
+  /// The most recently collected stats, null if none have been collected
+  /// for the bookmark.
+  1: optional RepoStats stats;
+}
+
 struct RepoListBookmarksResponse {
   /// A map from bookmark name to the bookmarked commit's IDs in the
   /// requested schemes (if available).
@@ -1617,6 +1861,15 @@ struct RepoListBookmarksResponse {
   2: optional string continue_after;
 }
 
+struct RepoListTagsResponse {
+  /// The tags, ordered by name.
+  1: list<TagInfo> tags;
+
+  /// If set, there are potentially more tags.  Provide this tag name as
+  /// the `after` parameter in a new request to continue finding them.
+  2: optional string continue_after;
+}
+
 struct RepoStackInfoResponse {
   /// Draft commits in topological order.
   1: list<CommitInfo> draft_commits;
@@ -1634,6 +1887,10 @@ struct RepoStackInfoResponse {
 struct RepoCreateCommitResponse {
   /// The IDs of the created commit.
   1: map<CommitIdentityScheme, CommitId> ids;
+
+  /// If the commit was landed, the outcome of the pushrebase.  The IDs of
+  /// the landed commit are in `pushrebase_outcome.head`.
+  2: optional PushrebaseOutcome pushrebase_outcome;
 }
 
 struct RepoCreateStackResponse {
@@ -1643,6 +1900,11 @@ struct RepoCreateStackResponse {
 
 struct RepoCreateBookmarkResponse {}
 
+struct RepoCreateTagResponse {
+  /// The created tag.
+  1: TagInfo tag;
+}
+
 struct RepoMoveBookmarkResponse {}
 
 struct RepoDeleteBookmarkResponse {}
@@ -1651,6 +1913,20 @@ struct RepoLandStackResponse {
   1: PushrebaseOutcome pushrebase_outcome;
 }
 
+struct RepoLandStackPreviewResponse {
+  /// The files changed by the stack that conflict with files changed by
+  /// commits that have landed on the bookmark since the stack's base.  If
+  /// empty, landing the stack would not fail due to conflicts.
+  1: list<PushrebaseConflict> conflicts;
+
+  /// The commit the bookmark currently points to, which the stack would be
+  /// rebased onto.
+  2: optional map<CommitIdentityScheme, CommitId> bookmark_value;
+
+  /// The number of commits the stack would be rebased over.
+  3: i64 pushrebase_distance;
+}
+
 struct RepoPrepareCommitsResponse {}
 
 struct RepoUploadFileContentResponse {
@@ -1692,12 +1968,36 @@ struct CommitFileDiffsResponse {
   2: optional CommitFileDiffsStoppedAtPair stopped_at_pair;
 }
 
+struct CommitDiffFile {
+  /// The path of the file in the commit, missing if it was removed.
+  1: optional Path base_path;
+  /// The path of the file in the other commit, missing if it was added.
+  2: optional Path other_path;
+  /// Whether the file was copied or moved from the other path.
+  3: CopyInfo copy_info;
+  /// The diff of the file, in unified diff format.  Binary files get a
+  /// placeholder diff.
+  4: RawDiff diff;
+  /// The file was larger than the file size limit, so the diff is just a
+  /// placeholder.
+  5: bool is_omitted;
+}
+
+struct CommitDiffResponse {
+  /// The number of files that differ, and will be streamed.
+  1: i64 file_count;
+}
+
 struct CommitLookupResponse {
   /// Whether the commit exists.
   1: bool exists;
 
   /// The commit's IDs in the requested schemes (if available).
   2: optional map<CommitIdentityScheme, CommitId> ids;
+
+  /// For cross-repo lookups, how the returned commit relates to the
+  /// requested commit (if it exists).
+  3: optional XRepoLookupMatch xrepo_match;
 }
 
 struct CommitLookupPushrebaseHistoryResponse {
@@ -1706,6 +2006,35 @@ struct CommitLookupPushrebaseHistoryResponse {
   2: CommitSpecifier origin;
 }
 
+enum CommitPhase {
+  /// The commit is not reachable from any publishing bookmark.
+  DRAFT = 0,
+  /// The commit is reachable from a publishing bookmark.
+  PUBLIC = 1,
+}
+
+struct CommitPhaseResponse {
+  1: CommitPhase phase;
+}
+
+enum CommitSignatureStatus {
+  /// The commit has no signatures.
+  UNSIGNED = 0,
+  /// The commit has a valid signature from a key trusted by the repo.
+  VERIFIED = 1,
+  /// The commit is signed, but only by keys the repo doesn't trust.
+  UNTRUSTED = 2,
+  /// The commit has a signature from a trusted key that doesn't match.
+  INVALID = 3,
+}
+
+struct CommitSignatureVerificationResponse {
+  1: CommitSignatureStatus status;
+  /// The id of the trusted key that signed the commit, for `VERIFIED` and
+  /// `INVALID` commits.
+  2: optional string key_id;
+}
+
 struct CommitFindFilesResponse {
   /// The files that match.
   1: list<string> files;
@@ -1733,6 +2062,8 @@ struct HookOutcomeRejected {
   1: string description;
   /// A full explanation of what went wrong, suitable for presenting to the user (should include guidance for fixing this failure, where possible)
   2: string long_description;
+  /// For hooks that check individual files, the file that was rejected
+  3: optional Path path;
 }
 
 union HookOutcome {
@@ -1744,6 +2075,12 @@ struct CommitRunHooksResponse {
   1: map<string, HookOutcome> outcomes;
 }
 
+struct CommitDerivationTraceResponse {
+  /// The trace as JSON: the ancestors and dependencies that had to be
+  /// derived, with how long each derivation took and the blobs it wrote.
+  1: string trace_json;
+}
+
 struct CommitPathExistsResponse {
   /// Whether anything exists at this path.
   1: bool exists;
@@ -1821,10 +2158,15 @@ struct TreeListResponse {
   /// limited by the limit requested.
   1: list<TreeEntry> entries;
 
-  /// The total number of entries in this directory. If this is greater
-  /// than the requested limit, then more requests to get the rest of the
-  /// list will be required.
+  /// The total number of entries in this directory (of the requested
+  /// types, if `entry_types` was set). If this is greater than the
+  /// requested limit, then more requests to get the rest of the list will
+  /// be required.
   2: i64 count;
+
+  /// If set, there are more entries.  Provide this as the
+  /// `continuation_token` in a new request to continue listing them.
+  3: optional string continuation_token;
 }
 
 struct FileDiffResponse {
@@ -1936,11 +2278,50 @@ enum RequestErrorKind {
   NOT_AVAILABLE = 9,
   NOT_IMPLEMENTED = 10,
   MERGE_CONFLICTS = 11,
+  REPO_READ_ONLY = 12,
+}
+
+/// Permissions to operate on a repo.  Each permission implies the ones
+/// before it.
+enum RepoPermission {
+  READ = 0,
+  DRAFT_PUSH = 1,
+  PUBLIC_PUSH = 2,
+  ADMIN = 3,
+}
+
+/// Details of a PERMISSION_DENIED request error.
+struct PermissionDeniedDetails {
+  /// Description of the action that was denied.
+  1: string denied_action;
+  /// The permission that would allow the action, if the action is governed
+  /// by a single permission.
+  2: optional RepoPermission required_permission;
+  /// The identities the request was made with.
+  3: list<string> identities;
+}
+
+/// Details of a REPO_READ_ONLY request error.
+struct RepoReadOnlyDetails {
+  /// Why the repo is read-only.
+  1: string reason;
+  /// Who made the repo read-only, if known.
+  2: optional string locked_by;
+  /// When the repo is expected to become writable again, as a unix
+  /// timestamp, if known.
+  3: optional i64 expected_unlock;
+  /// Where writes should be sent instead, if the repo is a mirror of
+  /// another server.
+  4: optional string redirect_url;
 }
 
 exception RequestError {
   1: RequestErrorKind kind;
   2: string reason;
+  /// Set for PERMISSION_DENIED errors caused by a failed authorization check.
+  3: optional PermissionDeniedDetails permission_denied;
+  /// Set for REPO_READ_ONLY errors.
+  4: optional RepoReadOnlyDetails repo_read_only;
 } (message = "reason")
 
 exception InternalError {
@@ -2026,6 +2407,14 @@ service SourceControlService extends fb303_core.BaseService {
     2: RepoResolveCommitPrefixParams params,
   ) throws (1: RequestError request_error, 2: InternalError internal_error);
 
+  /// Resolve many revisions in one call, each of which may be a bookmark, a
+  /// full commit hash or a prefix.  Revisions that can't be resolved are
+  /// reported as not found or ambiguous rather than failing the call.
+  RepoResolveCommitsResponse repo_resolve_commits(
+    1: RepoSpecifier repo,
+    2: RepoResolveCommitsParams params,
+  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+
   /// Comprehensive information about bookmark (use repo_resolve_bookmark for
   /// simply resolving bookmark value).
   RepoBookmarkInfoResponse repo_bookmark_info(
@@ -2033,12 +2422,25 @@ service SourceControlService extends fb303_core.BaseService {
     2: RepoBookmarkInfoParams params,
   ) throws (1: RequestError request_error, 2: InternalError internal_error);
 
+  /// Get the most recently collected size stats of the repo at a bookmark.
+  /// Stats are collected periodically by the repo stats collector.
+  RepoStatsResponse repo_stats(
+    1: RepoSpecifier repo,
+    2: RepoStatsParams params,
+  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+
   /// List all bookmarks in the repo.
   RepoListBookmarksResponse repo_list_bookmarks(
     1: RepoSpecifier repo,
     2: RepoListBookmarksParams params,
   ) throws (1: RequestError request_error, 2: InternalError internal_error);
 
+  /// List tags in the repo.
+  RepoListTagsResponse repo_list_tags(
+    1: RepoSpecifier repo,
+    2: RepoListTagsParams params,
+  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+
   /// Generate commit info for all the draft commits
   /// for the given set of heads.and public roots.
   RepoStackInfoResponse repo_stack_info(
@@ -2049,11 +2451,16 @@ service SourceControlService extends fb303_core.BaseService {
   /// Repository write methods
   /// ========================
 
-  /// Create a new commit.
+  /// Create a new commit, optionally landing it onto a bookmark.
   RepoCreateCommitResponse repo_create_commit(
     1: RepoSpecifier repo,
     2: RepoCreateCommitParams params,
-  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+  ) throws (
+    1: RequestError request_error,
+    2: InternalError internal_error,
+    3: PushrebaseConflictsException pushrebase_conflicts,
+    4: HookRejectionsException hook_rejections,
+  );
 
   /// Create a stack of new commits.  A stack is a linear chain of commits
   /// where each commit is the single immediate child of the previous commit.
@@ -2068,6 +2475,12 @@ service SourceControlService extends fb303_core.BaseService {
     2: RepoCreateBookmarkParams params,
   ) throws (1: RequestError request_error, 2: InternalError internal_error);
 
+  /// Create a tag.  Tags can't be moved or deleted once created.
+  RepoCreateTagResponse repo_create_tag(
+    1: RepoSpecifier repo,
+    2: RepoCreateTagParams params,
+  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+
   /// Move a bookmark.
   RepoMoveBookmarkResponse repo_move_bookmark(
     1: RepoSpecifier repo,
@@ -2091,6 +2504,17 @@ service SourceControlService extends fb303_core.BaseService {
     4: HookRejectionsException hook_rejections,
   );
 
+  /// Check whether a stack of commits would conflict if it were landed via
+  /// pushrebase, without landing it.
+  ///
+  /// Conflicts are checked against the current location of the bookmark, so
+  /// a land may still fail if the bookmark moves in the meantime.  Hooks are
+  /// not run.
+  RepoLandStackPreviewResponse repo_land_stack_preview(
+    1: RepoSpecifier repo,
+    2: RepoLandStackPreviewParams params,
+  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+
   /// Derive data for commits in a repo
   RepoPrepareCommitsResponse repo_prepare_commits(
     1: RepoSpecifier repo,
@@ -2117,6 +2541,19 @@ service SourceControlService extends fb303_core.BaseService {
     2: CommitFileDiffsParams params,
   ) throws (1: RequestError request_error, 2: InternalError internal_error);
 
+  /// Render the unified diffs of all files that differ between this commit
+  /// and another commit (by default, its first parent).  The diffs are
+  /// streamed in path order, as they are rendered.
+  CommitDiffResponse, stream<
+    CommitDiffFile throws (
+      1: RequestError request_error,
+      2: InternalError internal_error,
+    )
+  > commit_diff(
+    1: CommitSpecifier commit,
+    2: CommitDiffParams params,
+  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+
   /// Look-up a commit to see if it exists and find alternative IDs.
   CommitLookupResponse commit_lookup(
     1: CommitSpecifier commit,
@@ -2153,6 +2590,19 @@ service SourceControlService extends fb303_core.BaseService {
     2: CommitIsAncestorOfParams params,
   ) throws (1: RequestError request_error, 2: InternalError internal_error);
 
+  /// Get the phase of this commit.
+  CommitPhaseResponse commit_phase(
+    1: CommitSpecifier commit,
+    2: CommitPhaseParams params,
+  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+
+  /// Verify the signatures of this commit against the keys trusted by the
+  /// repo.
+  CommitSignatureVerificationResponse commit_signature_verification(
+    1: CommitSpecifier commit,
+    2: CommitSignatureVerificationParams params,
+  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+
   /// Find the lowest common ancestor of two commits.
   ///
   /// In case of ambiguity (can happen with multiple merges of the same
@@ -2194,6 +2644,17 @@ service SourceControlService extends fb303_core.BaseService {
     2: CommitRunHooksParams params,
   ) throws (1: RequestError request_error, 2: InternalError interal_error);
 
+  /// Derive data for a commit, and trace the derivations this required.
+  /// For diagnosing slow derivations.
+  ///
+  /// Deriving writes to the repo, so this requires write access to it.
+  /// Fails without deriving anything if more than 1000 commits would have
+  /// to be derived.
+  CommitDerivationTraceResponse commit_derivation_trace(
+    1: CommitSpecifier commit,
+    2: CommitDerivationTraceParams params,
+  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+
   /// CommitPath methods
   /// ==============
 
@@ -11,6 +11,8 @@ use std::error::Error as StdError;
 use megarepo_error::MegarepoError;
 use mononoke_api::repo::git::GitError;
 use mononoke_api::MononokeError;
+use mononoke_api::PermissionDenied;
+use mononoke_api::RepoPermission;
 use source_control as thrift;
 use source_control::services::source_control_service as service;
 
@@ -54,11 +56,19 @@ impl LoggableError for ServiceError {
 impl ServiceError {
     pub fn context(self, context: &str) -> Self {
         match self {
-            Self::Request(thrift::RequestError { kind, reason, .. }) => {
+            Self::Request(thrift::RequestError {
+                kind,
+                reason,
+                permission_denied,
+                repo_read_only,
+                ..
+            }) => {
                 let reason = format!("{}: {}", context, reason);
                 Self::Request(thrift::RequestError {
                     kind,
                     reason,
+                    permission_denied,
+                    repo_read_only,
                     ..Default::default()
                 })
             }
@@ -136,8 +146,29 @@ impl From<MegarepoError> for ServiceError {
     }
 }
 
+fn permission_denied_details(denied: &PermissionDenied) -> thrift::PermissionDeniedDetails {
+    thrift::PermissionDeniedDetails {
+        denied_action: denied.denied_action().to_string(),
+        required_permission: denied
+            .required_permission()
+            .map(|permission| match permission {
+                RepoPermission::Read => thrift::RepoPermission::READ,
+                RepoPermission::DraftPush => thrift::RepoPermission::DRAFT_PUSH,
+                RepoPermission::PublicPush => thrift::RepoPermission::PUBLIC_PUSH,
+                RepoPermission::Admin => thrift::RepoPermission::ADMIN,
+            }),
+        identities: denied
+            .identities()
+            .iter()
+            .map(|identity| identity.to_string())
+            .collect(),
+        ..Default::default()
+    }
+}
+
 impl From<MononokeError> for ServiceError {
     fn from(e: MononokeError) -> Self {
+        e.code().report();
         match e {
             MononokeError::InvalidRequest(reason) => Self::Request(thrift::RequestError {
                 kind: thrift::RequestErrorKind::INVALID_REQUEST,
@@ -156,9 +187,22 @@ impl From<MononokeError> for ServiceError {
                     ..Default::default()
                 })
             }
-            error @ MononokeError::AuthorizationError(_) => Self::Request(thrift::RequestError {
+            MononokeError::PermissionDenied(denied) => Self::Request(thrift::RequestError {
                 kind: thrift::RequestErrorKind::PERMISSION_DENIED,
-                reason: error.to_string(),
+                reason: format!("permission denied: {}", denied),
+                permission_denied: Some(permission_denied_details(&denied)),
+                ..Default::default()
+            }),
+            MononokeError::RepoReadOnly(info) => Self::Request(thrift::RequestError {
+                kind: thrift::RequestErrorKind::REPO_READ_ONLY,
+                reason: format!("repo is read-only: {}", info),
+                repo_read_only: Some(thrift::RepoReadOnlyDetails {
+                    reason: info.reason,
+                    locked_by: info.locked_by,
+                    expected_unlock: info.expected_unlock,
+                    redirect_url: info.redirect_url,
+                    ..Default::default()
+                }),
                 ..Default::default()
             }),
             error @ MononokeError::NotAvailable(_) => Self::Request(thrift::RequestError {
@@ -216,28 +260,38 @@ impl_into_thrift_error!(service::ListReposExn);
 impl_into_thrift_error!(service::RepoInfoExn);
 impl_into_thrift_error!(service::RepoResolveBookmarkExn);
 impl_into_thrift_error!(service::RepoResolveCommitPrefixExn);
+impl_into_thrift_error!(service::RepoResolveCommitsExn);
 impl_into_thrift_error!(service::RepoListBookmarksExn);
+impl_into_thrift_error!(service::RepoListTagsExn);
 impl_into_thrift_error!(service::RepoCreateCommitExn);
 impl_into_thrift_error!(service::RepoCreateStackExn);
 impl_into_thrift_error!(service::RepoCreateBookmarkExn);
+impl_into_thrift_error!(service::RepoCreateTagExn);
 impl_into_thrift_error!(service::RepoMoveBookmarkExn);
 impl_into_thrift_error!(service::RepoDeleteBookmarkExn);
 impl_into_thrift_error!(service::RepoLandStackExn);
+impl_into_thrift_error!(service::RepoLandStackPreviewExn);
 impl_into_thrift_error!(service::RepoBookmarkInfoExn);
+impl_into_thrift_error!(service::RepoStatsExn);
 impl_into_thrift_error!(service::RepoStackInfoExn);
 impl_into_thrift_error!(service::RepoPrepareCommitsExn);
 impl_into_thrift_error!(service::RepoUploadFileContentExn);
 impl_into_thrift_error!(service::CommitCommonBaseWithExn);
 impl_into_thrift_error!(service::CommitFileDiffsExn);
+impl_into_thrift_error!(service::CommitDiffExn);
+impl_into_thrift_error!(service::CommitDiffStreamExn);
 impl_into_thrift_error!(service::CommitLookupExn);
 impl_into_thrift_error!(service::CommitLookupPushrebaseHistoryExn);
 impl_into_thrift_error!(service::CommitInfoExn);
+impl_into_thrift_error!(service::CommitPhaseExn);
+impl_into_thrift_error!(service::CommitSignatureVerificationExn);
 impl_into_thrift_error!(service::CommitCompareExn);
 impl_into_thrift_error!(service::CommitIsAncestorOfExn);
 impl_into_thrift_error!(service::CommitFindFilesExn);
 impl_into_thrift_error!(service::CommitHistoryExn);
 impl_into_thrift_error!(service::CommitListDescendantBookmarksExn);
 impl_into_thrift_error!(service::CommitRunHooksExn);
+impl_into_thrift_error!(service::CommitDerivationTraceExn);
 impl_into_thrift_error!(service::CommitPathExistsExn);
 impl_into_thrift_error!(service::CommitPathInfoExn);
 impl_into_thrift_error!(service::CommitMultiplePathInfoExn);
@@ -42,6 +42,7 @@ use mononoke_api::MononokePath;
 use mononoke_api::TreeId;
 use mononoke_types::hash::Sha1;
 use mononoke_types::hash::Sha256;
+use mononoke_types::ChangesetExtras;
 use source_control as thrift;
 
 use crate::commit_id::CommitIdExt;
@@ -229,48 +230,56 @@ impl FromRequest<thrift::RepoResolveCommitPrefixParams> for ChangesetPrefixSpeci
     fn from_request(
         params: &thrift::RepoResolveCommitPrefixParams,
     ) -> Result<Self, thrift::RequestError> {
-        match params.prefix_scheme {
-            thrift::CommitIdentityScheme::HG => {
-                let prefix = HgChangesetIdPrefix::from_str(&params.prefix).map_err(|e| {
-                    errors::invalid_request(format!(
-                        "invalid commit id prefix (scheme={} {}): {}",
-                        params.prefix_scheme, params.prefix, e
-                    ))
-                })?;
-                Ok(ChangesetPrefixSpecifier::from(prefix))
-            }
-            thrift::CommitIdentityScheme::GIT => {
-                let prefix = GitSha1Prefix::from_str(&params.prefix).map_err(|e| {
-                    errors::invalid_request(format!(
-                        "invalid commit id prefix (scheme={} {}): {}",
-                        params.prefix_scheme, params.prefix, e
-                    ))
-                })?;
-                Ok(ChangesetPrefixSpecifier::from(prefix))
-            }
-            thrift::CommitIdentityScheme::BONSAI => {
-                let prefix = ChangesetIdPrefix::from_str(&params.prefix).map_err(|e| {
-                    errors::invalid_request(format!(
-                        "invalid commit id prefix (scheme={} {}): {}",
-                        params.prefix_scheme, params.prefix, e
-                    ))
-                })?;
-                Ok(ChangesetPrefixSpecifier::from(prefix))
-            }
-            thrift::CommitIdentityScheme::GLOBALREV => {
-                let rev = params.prefix.parse().map_err(|e| {
-                    errors::invalid_request(format!(
-                        "invalid commit id prefix (scheme={} {}): {}",
-                        params.prefix_scheme, params.prefix, e
-                    ))
-                })?;
-                Ok(ChangesetPrefixSpecifier::from(Globalrev::new(rev)))
-            }
-            _ => Err(errors::invalid_request(format!(
-                "unsupported prefix identity scheme ({})",
-                params.prefix_scheme
-            ))),
+        changeset_prefix_specifier(&params.prefix, &params.prefix_scheme)
+    }
+}
+
+/// Parse a commit hash prefix in the given identity scheme.
+pub(crate) fn changeset_prefix_specifier(
+    prefix: &str,
+    scheme: &thrift::CommitIdentityScheme,
+) -> Result<ChangesetPrefixSpecifier, thrift::RequestError> {
+    match *scheme {
+        thrift::CommitIdentityScheme::HG => {
+            let prefix = HgChangesetIdPrefix::from_str(prefix).map_err(|e| {
+                errors::invalid_request(format!(
+                    "invalid commit id prefix (scheme={} {}): {}",
+                    scheme, prefix, e
+                ))
+            })?;
+            Ok(ChangesetPrefixSpecifier::from(prefix))
         }
+        thrift::CommitIdentityScheme::GIT => {
+            let prefix = GitSha1Prefix::from_str(prefix).map_err(|e| {
+                errors::invalid_request(format!(
+                    "invalid commit id prefix (scheme={} {}): {}",
+                    scheme, prefix, e
+                ))
+            })?;
+            Ok(ChangesetPrefixSpecifier::from(prefix))
+        }
+        thrift::CommitIdentityScheme::BONSAI => {
+            let prefix = ChangesetIdPrefix::from_str(prefix).map_err(|e| {
+                errors::invalid_request(format!(
+                    "invalid commit id prefix (scheme={} {}): {}",
+                    scheme, prefix, e
+                ))
+            })?;
+            Ok(ChangesetPrefixSpecifier::from(prefix))
+        }
+        thrift::CommitIdentityScheme::GLOBALREV => {
+            let rev = prefix.parse().map_err(|e| {
+                errors::invalid_request(format!(
+                    "invalid commit id prefix (scheme={} {}): {}",
+                    scheme, prefix, e
+                ))
+            })?;
+            Ok(ChangesetPrefixSpecifier::from(Globalrev::new(rev)))
+        }
+        _ => Err(errors::invalid_request(format!(
+            "unsupported prefix identity scheme ({})",
+            scheme
+        ))),
     }
 }
 
@@ -358,6 +367,11 @@ impl FromRequest<thrift::RepoCreateCommitParamsCommitInfo> for CreateInfo {
                 .map(|(k, v)| (k.0.clone(), v.clone()))
                 .collect()
         });
+        let extras = info.extras.as_ref().map(|extras| ChangesetExtras {
+            branch: extras.branch.clone(),
+            signed_off_by: extras.signed_off_by.clone(),
+            committer_identity: extras.committer_identity.clone(),
+        });
 
         Ok(CreateInfo {
             author,
@@ -367,6 +381,7 @@ impl FromRequest<thrift::RepoCreateCommitParamsCommitInfo> for CreateInfo {
             message,
             extra,
             git_extra_headers,
+            extras,
         })
     }
 }
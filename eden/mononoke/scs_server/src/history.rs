@@ -12,6 +12,7 @@ use futures::stream;
 use futures::stream::Stream;
 use futures::stream::StreamExt;
 use futures::stream::TryStreamExt;
+use mononoke_api::prefetch_changeset_info;
 use mononoke_api::ChangesetContext;
 use mononoke_api::MononokeError;
 use source_control as thrift;
@@ -19,6 +20,13 @@ use source_control as thrift;
 use crate::errors;
 use crate::into_response::AsyncIntoResponseWith;
 
+/// Changeset infos are loaded this many at a time for history that needs
+/// them, or fewer if fewer changesets have been asked for.
+const PREFETCH_BATCH_SIZE: usize = 100;
+
+/// Changeset infos are loaded up to this many changesets ahead.
+const PREFETCH_LOOKAHEAD: usize = 500;
+
 pub(crate) async fn collect_history(
     history_stream: impl Stream<Item = Result<ChangesetContext, MononokeError>>,
     skip: usize,
@@ -28,9 +36,23 @@ pub(crate) async fn collect_history(
     format: thrift::HistoryFormat,
     identity_schemes: &BTreeSet<thrift::CommitIdentityScheme>,
 ) -> Result<thrift::History, errors::ServiceError> {
-    let history_stream = history_stream
-        .map_err(errors::ServiceError::from)
-        .skip(skip);
+    let history_stream = history_stream.skip(skip);
+    // Commit infos and time filters need the authors, dates and messages of
+    // the changesets, which are slow to load one at a time.
+    let needs_info = format == thrift::HistoryFormat::COMMIT_INFO
+        || before_timestamp.is_some()
+        || after_timestamp.is_some();
+    let history_stream = if needs_info {
+        prefetch_changeset_info(
+            history_stream,
+            PREFETCH_BATCH_SIZE.min(limit),
+            PREFETCH_LOOKAHEAD.min(limit),
+        )
+        .left_stream()
+    } else {
+        history_stream.right_stream()
+    };
+    let history_stream = history_stream.map_err(errors::ServiceError::from);
 
     let history = if before_timestamp.is_some() || after_timestamp.is_some() {
         history_stream
@@ -30,10 +30,12 @@ use mononoke_api::MetadataDiffLinesCount;
 use mononoke_api::MononokeError;
 use mononoke_api::PushrebaseOutcome;
 use mononoke_api::RepoContext;
+use mononoke_api::SignatureVerification;
 use mononoke_api::TreeEntry;
 use mononoke_api::TreeId;
 use mononoke_api::TreeSummary;
 use mononoke_api::UnifiedDiff;
+use mononoke_api::XRepoLookupMatch;
 use source_control as thrift;
 
 use crate::commit_id::map_commit_identities;
@@ -179,6 +181,37 @@ impl IntoResponse<thrift::CopyInfo> for CopyInfo {
     }
 }
 
+impl IntoResponse<thrift::XRepoLookupMatch> for XRepoLookupMatch {
+    fn into_response(self) -> thrift::XRepoLookupMatch {
+        match self {
+            XRepoLookupMatch::Exact => thrift::XRepoLookupMatch::EXACT,
+            XRepoLookupMatch::WorkingCopyEquivalent => {
+                thrift::XRepoLookupMatch::WORKING_COPY_EQUIVALENT
+            }
+        }
+    }
+}
+
+impl IntoResponse<thrift::CommitSignatureVerificationResponse> for SignatureVerification {
+    fn into_response(self) -> thrift::CommitSignatureVerificationResponse {
+        let (status, key_id) = match self {
+            SignatureVerification::Unsigned => (thrift::CommitSignatureStatus::UNSIGNED, None),
+            SignatureVerification::Verified { key_id } => {
+                (thrift::CommitSignatureStatus::VERIFIED, Some(key_id))
+            }
+            SignatureVerification::Untrusted => (thrift::CommitSignatureStatus::UNTRUSTED, None),
+            SignatureVerification::Invalid { key_id } => {
+                (thrift::CommitSignatureStatus::INVALID, Some(key_id))
+            }
+        };
+        thrift::CommitSignatureVerificationResponse {
+            status,
+            key_id,
+            ..Default::default()
+        }
+    }
+}
+
 impl IntoResponse<Option<thrift::MetadataDiffFileContentType>> for Option<FileContentType> {
     fn into_response(self) -> Option<thrift::MetadataDiffFileContentType> {
         match self {
@@ -340,16 +373,18 @@ impl AsyncIntoResponseWith<thrift::CommitInfo> for ChangesetContext {
                 .collect())
         }
 
-        let (ids, message, date, author, parents, hg_extra, git_extra_headers, generation) = try_join!(
-            map_commit_identity(&self, identity_schemes),
-            self.message(),
-            self.author_date(),
-            self.author(),
-            map_parent_identities(&self, identity_schemes),
-            self.hg_extras(),
-            self.git_extra_headers(),
-            self.generation(),
-        )?;
+        let (ids, message, date, author, parents, hg_extra, git_extra_headers, generation, extras) =
+            try_join!(
+                map_commit_identity(&self, identity_schemes),
+                self.message(),
+                self.author_date(),
+                self.author(),
+                map_parent_identities(&self, identity_schemes),
+                self.hg_extras(),
+                self.git_extra_headers(),
+                self.generation(),
+                self.extras(),
+            )?;
         Ok(thrift::CommitInfo {
             ids,
             message,
@@ -365,6 +400,12 @@ impl AsyncIntoResponseWith<thrift::CommitInfo> for ChangesetContext {
                     .collect()
             }),
             generation: generation.value() as i64,
+            extras: extras.map(|extras| thrift::CommitExtras {
+                branch: extras.branch,
+                signed_off_by: extras.signed_off_by,
+                committer_identity: extras.committer_identity,
+                ..Default::default()
+            }),
             ..Default::default()
         })
     }
@@ -30,6 +30,7 @@ use megarepo_api::MegarepoApi;
 use metaconfig_types::ShardedService;
 use mononoke_api::repo::Repo;
 use mononoke_api::CoreContext;
+use mononoke_api::SessionContainer;
 use mononoke_app::args::HooksAppExtension;
 use mononoke_app::args::RepoFilterAppExtension;
 use mononoke_app::args::ShutdownTimeoutArgs;
@@ -38,6 +39,7 @@ use mononoke_app::MononokeAppBuilder;
 use mononoke_app::MononokeReposManager;
 use panichandler::Fate;
 use permission_checker::DefaultAclProvider;
+use slo::SloTracker;
 use slog::info;
 use source_control::server::make_SourceControlService_server;
 use srserver::service_framework::BuildModule;
@@ -225,6 +227,12 @@ fn main(fb: FacebookInit) -> Result<(), Error> {
         acl_provider.as_ref(),
         &app.repo_configs().common,
     ))?;
+    let slo_tracker = Arc::new(SloTracker::new(
+        app.repo_configs()
+            .repos
+            .iter()
+            .map(|(name, config)| (name.clone(), config.slo_config.clone())),
+    ));
     let source_control_server = source_control_impl::SourceControlServiceImpl::new(
         fb,
         mononoke.clone(),
@@ -234,6 +242,7 @@ fn main(fb: FacebookInit) -> Result<(), Error> {
         args.scribe_logging_args.get_scribe(fb)?,
         security_checker,
         &app.repo_configs().common,
+        slo_tracker.clone(),
     );
     let service = {
         move |proto| {
@@ -249,6 +258,12 @@ fn main(fb: FacebookInit) -> Result<(), Error> {
         monitoring::monitoring_stats_submitter(monitoring_ctx, mononoke)
     };
     runtime.spawn(monitoring_forever);
+    let slo_reporting_forever = {
+        let slo_ctx = SessionContainer::new_with_defaults(fb)
+            .new_context(logger.clone(), env.scuba_sample_builder.clone());
+        slo_tracker.report_forever(slo_ctx)
+    };
+    runtime.spawn(slo_reporting_forever);
 
     let thrift: ThriftServer = ThriftServerBuilder::new(fb)
         .with_name(SERVICE_NAME)
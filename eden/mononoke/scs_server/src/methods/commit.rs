@@ -56,6 +56,9 @@ use crate::source_control_impl::SourceControlServiceImpl;
 // Magic number used when we want to limit concurrency with buffer_unordered.
 const CONCURRENCY_LIMIT: usize = 100;
 
+// Maximum number of commits that tracing a derivation may derive.
+const MAX_TRACED_UNDERIVED: u64 = 1000;
+
 enum CommitComparePath {
     File(thrift::CommitCompareFile),
     Tree(thrift::CommitCompareTree),
@@ -491,6 +494,36 @@ impl SourceControlServiceImpl {
         changeset.into_response_with(&params.identity_schemes).await
     }
 
+    /// Get the phase of the commit.
+    pub(crate) async fn commit_phase(
+        &self,
+        ctx: CoreContext,
+        commit: thrift::CommitSpecifier,
+        _params: thrift::CommitPhaseParams,
+    ) -> Result<thrift::CommitPhaseResponse, errors::ServiceError> {
+        let (_repo, changeset) = self.repo_changeset(ctx, &commit).await?;
+        let phase = if changeset.is_public().await? {
+            thrift::CommitPhase::PUBLIC
+        } else {
+            thrift::CommitPhase::DRAFT
+        };
+        Ok(thrift::CommitPhaseResponse {
+            phase,
+            ..Default::default()
+        })
+    }
+
+    /// Verify the signatures of the commit.
+    pub(crate) async fn commit_signature_verification(
+        &self,
+        ctx: CoreContext,
+        commit: thrift::CommitSpecifier,
+        _params: thrift::CommitSignatureVerificationParams,
+    ) -> Result<thrift::CommitSignatureVerificationResponse, errors::ServiceError> {
+        let (_repo, changeset) = self.repo_changeset(ctx, &commit).await?;
+        Ok(changeset.signature_verification().await?.into_response())
+    }
+
     /// Returns `true` if this commit is an ancestor of `other_commit`.
     pub(crate) async fn commit_is_ancestor_of(
         &self,
@@ -938,9 +971,9 @@ impl SourceControlServiceImpl {
         let mut outcomes_map = BTreeMap::new();
 
         for outcome in outcomes {
-            let (name, execution) = match outcome {
-                HookOutcome::FileHook(id, exec) => (id.hook_name, exec),
-                HookOutcome::ChangesetHook(id, exec) => (id.hook_name, exec),
+            let (name, path, execution) = match outcome {
+                HookOutcome::FileHook(id, exec) => (id.hook_name, Some(id.path), exec),
+                HookOutcome::ChangesetHook(id, exec) => (id.hook_name, None, exec),
             };
 
             match execution {
@@ -955,6 +988,7 @@ impl SourceControlServiceImpl {
                     let rejection = thrift::HookOutcomeRejected {
                         description: rej.description.to_string(),
                         long_description: rej.long_description,
+                        path: path.map(|path| path.to_string()),
                         ..Default::default()
                     };
 
@@ -975,6 +1009,25 @@ impl SourceControlServiceImpl {
         })
     }
 
+    /// Derive data for a commit, and trace the derivations this required
+    pub(crate) async fn commit_derivation_trace(
+        &self,
+        ctx: CoreContext,
+        commit: thrift::CommitSpecifier,
+        params: thrift::CommitDerivationTraceParams,
+    ) -> Result<thrift::CommitDerivationTraceResponse, errors::ServiceError> {
+        let (_repo, changeset) = self.repo_changeset(ctx, &commit).await?;
+        let trace = changeset
+            .derivation_trace(&params.derived_data_type, MAX_TRACED_UNDERIVED)
+            .await?;
+        let trace_json = serde_json::to_string(&trace)
+            .map_err(|e| errors::internal_error(format!("{e}")))?;
+        Ok(thrift::CommitDerivationTraceResponse {
+            trace_json,
+            ..Default::default()
+        })
+    }
+
     /// Do a cross-repo lookup to see if a commit exists under a different hash in another repo
     pub(crate) async fn commit_lookup_xrepo(
         &self,
@@ -990,18 +1043,19 @@ impl SourceControlServiceImpl {
         };
 
         match repo
-            .xrepo_commit_lookup(
+            .xrepo_commit_lookup_with_match(
                 &other_repo,
                 ChangesetSpecifier::from_request(&commit.id)?,
                 candidate_selection_hint,
             )
             .await?
         {
-            Some(cs) => {
+            Some((cs, lookup_match)) => {
                 let ids = map_commit_identity(&cs, &params.identity_schemes).await?;
                 Ok(thrift::CommitLookupResponse {
                     exists: true,
                     ids: Some(ids),
+                    xrepo_match: Some(lookup_match.into_response()),
                     ..Default::default()
                 })
             }
@@ -0,0 +1,166 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use context::CoreContext;
+use futures::stream;
+use futures::stream::BoxStream;
+use futures::stream::StreamExt;
+use futures::try_join;
+use maplit::btreeset;
+use mononoke_api::ChangesetDiffItem;
+use mononoke_api::ChangesetFileOrdering;
+use mononoke_api::ChangesetPathContentContext;
+use mononoke_api::ChangesetPathDiffContext;
+use mononoke_api::ChangesetSpecifier;
+use mononoke_api::MononokeError;
+use mononoke_api::MononokePath;
+use mononoke_api::UnifiedDiffMode;
+use source_control as thrift;
+
+use crate::errors;
+use crate::from_request::check_range_and_convert;
+use crate::into_response::IntoResponse;
+use crate::source_control_impl::SourceControlServiceImpl;
+
+// Number of file diffs rendered concurrently.
+const RENDER_CONCURRENCY: usize = 20;
+
+pub(crate) type CommitDiffFileStream =
+    BoxStream<'static, Result<thrift::CommitDiffFile, errors::ServiceError>>;
+
+/// The size of the file at a path, or zero if there is no file there.
+async fn file_size(
+    path: Option<&ChangesetPathContentContext>,
+) -> Result<u64, errors::ServiceError> {
+    if let Some(path) = path {
+        if let Some(file) = path.file().await? {
+            return Ok(file.metadata().await?.total_size);
+        }
+    }
+    Ok(0)
+}
+
+/// Render the unified diff of a file, or a placeholder if either version of
+/// the file is larger than `file_size_limit`.
+async fn render_file_diff(
+    path_diff: ChangesetPathDiffContext,
+    context_lines: usize,
+    file_size_limit: u64,
+) -> Result<thrift::CommitDiffFile, errors::ServiceError> {
+    let (base_size, other_size) =
+        try_join!(file_size(path_diff.base()), file_size(path_diff.other()))?;
+    let is_omitted = base_size > file_size_limit || other_size > file_size_limit;
+    let mode = if is_omitted {
+        UnifiedDiffMode::OmitContent
+    } else {
+        UnifiedDiffMode::Inline
+    };
+    let diff = path_diff.unified_diff(context_lines, mode).await?;
+    Ok(thrift::CommitDiffFile {
+        base_path: path_diff.base().map(|p| p.path().to_string()),
+        other_path: path_diff.other().map(|p| p.path().to_string()),
+        copy_info: path_diff.copy_info().into_response(),
+        diff: thrift::RawDiff {
+            raw_diff: Some(diff.raw_diff),
+            is_binary: diff.is_binary,
+            ..Default::default()
+        },
+        is_omitted,
+        ..Default::default()
+    })
+}
+
+impl SourceControlServiceImpl {
+    /// Render the diffs of the files that differ between two commits.
+    ///
+    /// The files that differ are found up front, and their diffs are then
+    /// streamed in path order as they are rendered.
+    pub(crate) async fn commit_diff(
+        &self,
+        ctx: CoreContext,
+        commit: thrift::CommitSpecifier,
+        params: thrift::CommitDiffParams,
+    ) -> Result<(thrift::CommitDiffResponse, CommitDiffFileStream), errors::ServiceError> {
+        let context_lines: usize = check_range_and_convert("context", params.context, 0..)?;
+        let file_size_limit: u64 = check_range_and_convert(
+            "file_size_limit",
+            params
+                .file_size_limit
+                .unwrap_or(thrift::consts::COMMIT_DIFF_FILE_SIZE_LIMIT),
+            0..=thrift::consts::COMMIT_DIFF_FILE_SIZE_LIMIT,
+        )?;
+        let after = params
+            .after
+            .map(|after| {
+                MononokePath::try_from(&after).map_err(|e| {
+                    errors::invalid_request(format!("invalid continuation path '{}': {}", after, e))
+                })
+            })
+            .transpose()?;
+        let paths: Option<Vec<MononokePath>> = params
+            .paths
+            .map(|paths| {
+                paths
+                    .iter()
+                    .map(MononokePath::try_from)
+                    .collect::<Result<Vec<_>, MononokeError>>()
+            })
+            .transpose()?;
+
+        let (base_changeset, other_changeset) = match &params.other_commit_id {
+            Some(id) => {
+                let (_repo, base_changeset, other_changeset) =
+                    self.repo_changeset_pair(ctx, &commit, id).await?;
+                (base_changeset, Some(other_changeset))
+            }
+            None => {
+                let (repo, base_changeset) = self.repo_changeset(ctx, &commit).await?;
+                let other_changeset = match base_changeset.parents().await?.first() {
+                    Some(parent) => Some(
+                        repo.changeset(ChangesetSpecifier::Bonsai(*parent))
+                            .await?
+                            .ok_or_else(|| errors::internal_error("parent changeset is missing"))?,
+                    ),
+                    None => None,
+                };
+                (base_changeset, other_changeset)
+            }
+        };
+
+        let ordering = ChangesetFileOrdering::Ordered { after };
+        let diff_items = btreeset! { ChangesetDiffItem::FILES };
+        let path_diffs = match other_changeset {
+            Some(ref other_changeset) => {
+                base_changeset
+                    .diff(
+                        other_changeset,
+                        params.detect_renames,
+                        paths,
+                        diff_items,
+                        ordering,
+                        None,
+                    )
+                    .await?
+            }
+            None => {
+                base_changeset
+                    .diff_root(paths, diff_items, ordering, None)
+                    .await?
+            }
+        };
+
+        let response = thrift::CommitDiffResponse {
+            file_count: path_diffs.len() as i64,
+            ..Default::default()
+        };
+        let files = stream::iter(path_diffs)
+            .map(move |path_diff| render_file_diff(path_diff, context_lines, file_size_limit))
+            .buffered(RENDER_CONCURRENCY)
+            .boxed();
+        Ok((response, files))
+    }
+}
@@ -12,6 +12,7 @@ use crate::errors;
 use crate::source_control_impl::SourceControlServiceImpl;
 
 pub(crate) mod commit;
+pub(crate) mod commit_diff;
 pub(crate) mod commit_lookup_pushrebase_history;
 pub(crate) mod commit_path;
 pub(crate) mod commit_sparse_profile_info;
@@ -6,6 +6,7 @@
  */
 
 use std::collections::BTreeMap;
+use std::str::FromStr;
 
 use bookmarks::BookmarkKey;
 use bytes::Bytes;
@@ -33,10 +34,12 @@ use mononoke_api::CreateCopyInfo;
 use mononoke_api::CreateInfo;
 use mononoke_api::FileId;
 use mononoke_api::FileType;
+use mononoke_api::HgChangesetId;
 use mononoke_api::MononokeError;
 use mononoke_api::MononokePath;
 use mononoke_api::RepoContext;
 use mononoke_api::StoreRequest;
+use mononoke_api::Tag;
 use mononoke_types::hash::GitSha1;
 use mononoke_types::hash::Sha1;
 use mononoke_types::hash::Sha256;
@@ -49,14 +52,105 @@ use crate::commit_id::map_commit_identity;
 use crate::commit_id::CommitIdExt;
 use crate::errors;
 use crate::errors::ServiceErrorResultExt;
+use crate::from_request::changeset_prefix_specifier;
 use crate::from_request::check_range_and_convert;
 use crate::from_request::convert_pushvars;
 use crate::from_request::FromRequest;
 use crate::into_response::AsyncIntoResponseWith;
 use crate::source_control_impl::SourceControlServiceImpl;
 
+mod create_commit;
 mod land_stack;
 
+/// Maximum number of revisions that can be resolved in one call.
+const MAX_RESOLVE_COMMITS: usize = 10_000;
+
+/// Number of revisions of a batch that are resolved concurrently.
+const RESOLVE_COMMITS_CONCURRENCY: usize = 100;
+
+enum RevisionResolution {
+    Resolved(thrift::ResolvedCommitSource, ChangesetId),
+    Ambiguous,
+    NotFound,
+}
+
+/// Resolve a revision as a bookmark name, if there is such a bookmark.
+async fn resolve_revision_bookmark(
+    repo: &RepoContext,
+    revision: &str,
+) -> Result<Option<RevisionResolution>, errors::ServiceError> {
+    let bookmark = match BookmarkKey::new(revision) {
+        Ok(bookmark) if !revision.is_empty() => bookmark,
+        _ => return Ok(None),
+    };
+    let cs = repo
+        .resolve_bookmark(&bookmark, BookmarkFreshness::MaybeStale)
+        .await?;
+    Ok(cs.map(|cs| RevisionResolution::Resolved(thrift::ResolvedCommitSource::BOOKMARK, cs.id())))
+}
+
+/// Parse a revision as a full commit hash in the given scheme.  Full hashes
+/// can be resolved in batches, rather than one prefix query at a time.
+fn full_hash_specifier(
+    revision: &str,
+    hash_scheme: &thrift::CommitIdentityScheme,
+) -> Option<ChangesetSpecifier> {
+    match *hash_scheme {
+        thrift::CommitIdentityScheme::HG => HgChangesetId::from_str(revision).ok().map(Into::into),
+        thrift::CommitIdentityScheme::GIT => GitSha1::from_str(revision).ok().map(Into::into),
+        thrift::CommitIdentityScheme::BONSAI => {
+            ChangesetId::from_str(revision).ok().map(Into::into)
+        }
+        _ => None,
+    }
+}
+
+/// Resolve a revision as a hash prefix.
+async fn resolve_revision_prefix(
+    repo: &RepoContext,
+    revision: &str,
+    hash_scheme: &thrift::CommitIdentityScheme,
+) -> Result<RevisionResolution, errors::ServiceError> {
+    use ChangesetSpecifierPrefixResolution::*;
+
+    // A revision that is neither a bookmark nor a valid hash in the scheme
+    // is not found, rather than failing the whole batch.
+    let prefix = match changeset_prefix_specifier(revision, hash_scheme) {
+        Ok(prefix) => prefix,
+        Err(_) => return Ok(RevisionResolution::NotFound),
+    };
+    match repo.resolve_changeset_id_prefix(prefix).await? {
+        Single(ChangesetSpecifier::Bonsai(cs_id)) => Ok(RevisionResolution::Resolved(
+            thrift::ResolvedCommitSource::HASH,
+            cs_id,
+        )),
+        Single(specifier) => match repo.resolve_specifier(specifier).await? {
+            Some(cs_id) => Ok(RevisionResolution::Resolved(
+                thrift::ResolvedCommitSource::HASH,
+                cs_id,
+            )),
+            None => Ok(RevisionResolution::NotFound),
+        },
+        NoMatch => Ok(RevisionResolution::NotFound),
+        _ => Ok(RevisionResolution::Ambiguous),
+    }
+}
+
+fn tag_info(
+    tag: Tag,
+    ids: BTreeMap<thrift::CommitIdentityScheme, thrift::CommitId>,
+) -> thrift::TagInfo {
+    thrift::TagInfo {
+        name: tag.name,
+        ids,
+        message: tag.message,
+        signer: tag.signer,
+        creator: tag.creator,
+        created_at: tag.created_at.timestamp_seconds(),
+        ..Default::default()
+    }
+}
+
 impl SourceControlServiceImpl {
     /// Detailed repo info.
     ///
@@ -197,6 +291,119 @@ impl SourceControlServiceImpl {
         }
     }
 
+    /// Resolve many revisions, each of which may be a bookmark name, a full
+    /// commit hash or a hash prefix.
+    ///
+    /// Returns a result for each revision, in the same order, so that
+    /// clients resolving many revisions only need a single round trip.
+    pub(crate) async fn repo_resolve_commits(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoResolveCommitsParams,
+    ) -> Result<thrift::RepoResolveCommitsResponse, errors::ServiceError> {
+        type ResponseType = thrift::RepoResolveCommitPrefixResponseType;
+
+        if params.revisions.len() > MAX_RESOLVE_COMMITS {
+            return Err(errors::invalid_request(format!(
+                "too many revisions to resolve ({}, the limit is {})",
+                params.revisions.len(),
+                MAX_RESOLVE_COMMITS
+            ))
+            .into());
+        }
+        match params.hash_scheme {
+            thrift::CommitIdentityScheme::HG
+            | thrift::CommitIdentityScheme::GIT
+            | thrift::CommitIdentityScheme::BONSAI
+            | thrift::CommitIdentityScheme::GLOBALREV => {}
+            _ => {
+                return Err(errors::invalid_request(format!(
+                    "unsupported hash identity scheme ({})",
+                    params.hash_scheme
+                ))
+                .into());
+            }
+        }
+
+        let repo = self.repo(ctx, &repo).await?;
+
+        // Bookmarks take precedence over hashes.
+        let mut resolutions = stream::iter(params.revisions.iter())
+            .map(|revision| resolve_revision_bookmark(&repo, revision))
+            .buffered(RESOLVE_COMMITS_CONCURRENCY)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        // Full hashes are resolved together.
+        let full_hashes = params
+            .revisions
+            .iter()
+            .zip(resolutions.iter())
+            .filter(|(_, resolution)| resolution.is_none())
+            .filter_map(|(revision, _)| full_hash_specifier(revision, &params.hash_scheme))
+            .collect::<Vec<_>>();
+        let resolved = repo.many_changeset_ids_from_specifiers(full_hashes).await?;
+        for (revision, resolution) in params.revisions.iter().zip(resolutions.iter_mut()) {
+            if resolution.is_none() {
+                if let Some(specifier) = full_hash_specifier(revision, &params.hash_scheme) {
+                    *resolution = Some(match resolved.get(&specifier) {
+                        Some(cs_id) => RevisionResolution::Resolved(
+                            thrift::ResolvedCommitSource::HASH,
+                            *cs_id,
+                        ),
+                        None => RevisionResolution::NotFound,
+                    });
+                }
+            }
+        }
+
+        // Only the remaining revisions need a prefix query each.
+        let resolutions = stream::iter(params.revisions.iter().zip(resolutions))
+            .map(|(revision, resolution)| async {
+                match resolution {
+                    Some(resolution) => Ok(resolution),
+                    None if revision.is_empty() => Ok(RevisionResolution::NotFound),
+                    None => resolve_revision_prefix(&repo, revision, &params.hash_scheme).await,
+                }
+            })
+            .buffered(RESOLVE_COMMITS_CONCURRENCY)
+            .try_collect::<Vec<_>>()
+            .await?;
+        let cs_ids = resolutions
+            .iter()
+            .filter_map(|resolution| match resolution {
+                RevisionResolution::Resolved(_, cs_id) => Some(*cs_id),
+                _ => None,
+            })
+            .collect();
+        let ids = map_commit_identities(&repo, cs_ids, &params.identity_schemes).await?;
+
+        let results = resolutions
+            .into_iter()
+            .map(|resolution| match resolution {
+                RevisionResolution::Resolved(source, cs_id) => thrift::ResolvedCommit {
+                    resolved_type: ResponseType::RESOLVED,
+                    source: Some(source),
+                    ids: ids.get(&cs_id).cloned(),
+                    ..Default::default()
+                },
+                RevisionResolution::Ambiguous => thrift::ResolvedCommit {
+                    resolved_type: ResponseType::AMBIGUOUS,
+                    ..Default::default()
+                },
+                RevisionResolution::NotFound => thrift::ResolvedCommit {
+                    resolved_type: ResponseType::NOT_FOUND,
+                    ..Default::default()
+                },
+            })
+            .collect();
+        Ok(thrift::RepoResolveCommitsResponse {
+            results,
+            ..Default::default()
+        })
+    }
+
     /// Comprehensive bookmark info.
     ///
     /// Returns value of the bookmark (both fresh and warm) and the timestamp of
@@ -218,6 +425,51 @@ impl SourceControlServiceImpl {
         })
     }
 
+    /// Repo size stats.
+    ///
+    /// Returns the most recently collected stats of the repo at the
+    /// bookmark, if there are any.
+    pub(crate) async fn repo_stats(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoStatsParams,
+    ) -> Result<thrift::RepoStatsResponse, errors::ServiceError> {
+        let repo = self.repo(ctx, &repo).await?;
+        let entry = match repo.latest_repo_stats(&params.bookmark_name).await? {
+            Some(entry) => entry,
+            None => return Ok(thrift::RepoStatsResponse::default()),
+        };
+        let cs = repo
+            .changeset(ChangesetSpecifier::Bonsai(entry.cs_id))
+            .await?
+            .ok_or_else(|| errors::internal_error("repo stats refer to an unknown commit"))?;
+        let ids = map_commit_identity(&cs, &params.identity_schemes).await?;
+        let directories = entry
+            .directories
+            .into_iter()
+            .map(|(name, stats)| {
+                let stats = thrift::DirectoryStats {
+                    file_count: stats.file_count as i64,
+                    total_size: stats.total_size as i64,
+                    ..Default::default()
+                };
+                (name, stats)
+            })
+            .collect();
+        Ok(thrift::RepoStatsResponse {
+            stats: Some(thrift::RepoStats {
+                timestamp: entry.timestamp.timestamp_seconds(),
+                ids,
+                file_count: entry.file_count as i64,
+                total_size: entry.total_size as i64,
+                directories,
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
     /// List bookmarks.
     pub(crate) async fn repo_list_bookmarks(
         &self,
@@ -271,6 +523,46 @@ impl SourceControlServiceImpl {
         })
     }
 
+    /// List tags.
+    pub(crate) async fn repo_list_tags(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoListTagsParams,
+    ) -> Result<thrift::RepoListTagsResponse, errors::ServiceError> {
+        let limit = match check_range_and_convert(
+            "limit",
+            params.limit,
+            0..=source_control::REPO_LIST_TAGS_MAX_LIMIT,
+        )? {
+            0 => source_control::REPO_LIST_TAGS_MAX_LIMIT as u64,
+            limit => limit,
+        };
+        let repo = self.repo(ctx, &repo).await?;
+        let tags = repo
+            .list_tags(&params.tag_prefix, params.after.as_deref(), limit)
+            .await?;
+        let continue_after = if tags.len() as u64 >= limit {
+            tags.last().map(|tag| tag.name.clone())
+        } else {
+            None
+        };
+        let ids = tags.iter().map(|tag| tag.cs_id).collect();
+        let id_mapping = map_commit_identities(&repo, ids, &params.identity_schemes).await?;
+        let tags = tags
+            .into_iter()
+            .map(|tag| {
+                let ids = id_mapping.get(&tag.cs_id).cloned().unwrap_or_default();
+                tag_info(tag, ids)
+            })
+            .collect();
+        Ok(thrift::RepoListTagsResponse {
+            tags,
+            continue_after,
+            ..Default::default()
+        })
+    }
+
     async fn convert_create_commit_parents(
         repo: &RepoContext,
         parents: &[thrift::CommitId],
@@ -421,43 +713,6 @@ impl SourceControlServiceImpl {
         Ok(changes)
     }
 
-    /// Create a new commit.
-    pub(crate) async fn repo_create_commit(
-        &self,
-        ctx: CoreContext,
-        repo: thrift::RepoSpecifier,
-        params: thrift::RepoCreateCommitParams,
-    ) -> Result<thrift::RepoCreateCommitResponse, errors::ServiceError> {
-        let repo = self
-            .repo_for_service(ctx, &repo, params.service_identity.clone())
-            .await?;
-
-        let parents = Self::convert_create_commit_parents(&repo, &params.parents).await?;
-        let info = CreateInfo::from_request(&params.info)?;
-        let changes = Self::convert_create_commit_changes(&repo, params.changes).await?;
-        let bubble = None;
-
-        let changeset = repo
-            .create_changeset(parents, info, changes, bubble)
-            .await?;
-
-        // If you ask for a git identity back, then we'll assume that you supplied one to us
-        // and set it. Later, when we can derive a git commit hash, this'll become more
-        // open, because we'll only do the check if you ask for a hash different to the
-        // one we would derive
-        if params
-            .identity_schemes
-            .contains(&thrift::CommitIdentityScheme::GIT)
-        {
-            repo.set_git_mapping_from_changeset(&changeset).await?;
-        }
-        let ids = map_commit_identity(&changeset, &params.identity_schemes).await?;
-        Ok(thrift::RepoCreateCommitResponse {
-            ids,
-            ..Default::default()
-        })
-    }
-
     /// Create a new stack of commits.
     pub(crate) async fn repo_create_stack(
         &self,
@@ -656,6 +911,30 @@ impl SourceControlServiceImpl {
         })
     }
 
+    pub(crate) async fn repo_create_tag(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoCreateTagParams,
+    ) -> Result<thrift::RepoCreateTagResponse, errors::ServiceError> {
+        let repo = self
+            .repo_for_service(ctx, &repo, params.service_identity)
+            .await?;
+        let target = &params.target;
+        let changeset = repo
+            .changeset(ChangesetSpecifier::from_request(target)?)
+            .await?
+            .ok_or_else(|| errors::commit_not_found(target.to_string()))?;
+        let tag = repo
+            .create_tag(&params.tag, changeset.id(), params.message, params.signer)
+            .await?;
+        let ids = map_commit_identity(&changeset, &params.identity_schemes).await?;
+        Ok(thrift::RepoCreateTagResponse {
+            tag: tag_info(tag, ids),
+            ..Default::default()
+        })
+    }
+
     pub(crate) async fn repo_move_bookmark(
         &self,
         ctx: CoreContext,
@@ -0,0 +1,111 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use bookmarks_movement::BookmarkKindRestrictions;
+use context::CoreContext;
+use hooks::PushAuthoredBy;
+use mononoke_api::CreateInfo;
+use source_control as thrift;
+use source_control::services::source_control_service as service;
+
+use super::land_stack::LandStackError;
+use crate::commit_id::map_commit_identity;
+use crate::errors;
+use crate::errors::LoggableError;
+use crate::from_request::convert_pushvars;
+use crate::from_request::FromRequest;
+use crate::into_response::AsyncIntoResponseWith;
+use crate::source_control_impl::SourceControlServiceImpl;
+
+impl SourceControlServiceImpl {
+    async fn impl_repo_create_commit(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoCreateCommitParams,
+    ) -> Result<thrift::RepoCreateCommitResponse, LandStackError> {
+        let push_authored_by = if params.service_identity.is_some() {
+            PushAuthoredBy::Service
+        } else {
+            PushAuthoredBy::User
+        };
+        let repo = self
+            .repo_for_service(ctx, &repo, params.service_identity.clone())
+            .await?;
+
+        let parents = Self::convert_create_commit_parents(&repo, &params.parents).await?;
+        let land_base = match (&params.land_to_bookmark, parents.as_slice()) {
+            (None, _) => None,
+            (Some(_), [parent]) => Some(*parent),
+            (Some(bookmark), _) => {
+                return Err(errors::invalid_request(format!(
+                    "cannot land commit to '{}': commits must have exactly one parent to be landed",
+                    bookmark
+                ))
+                .into());
+            }
+        };
+        let info = CreateInfo::from_request(&params.info)?;
+        let changes = Self::convert_create_commit_changes(&repo, params.changes).await?;
+        let bubble = None;
+
+        let changeset = repo
+            .create_changeset(parents, info, changes, bubble)
+            .await?;
+
+        // If you ask for a git identity back, then we'll assume that you supplied one to us
+        // and set it. Later, when we can derive a git commit hash, this'll become more
+        // open, because we'll only do the check if you ask for a hash different to the
+        // one we would derive
+        if params
+            .identity_schemes
+            .contains(&thrift::CommitIdentityScheme::GIT)
+        {
+            repo.set_git_mapping_from_changeset(&changeset).await?;
+        }
+        let ids = map_commit_identity(&changeset, &params.identity_schemes).await?;
+
+        let pushrebase_outcome = match (params.land_to_bookmark, land_base) {
+            (Some(bookmark), Some(base)) => {
+                let pushvars = convert_pushvars(params.pushvars);
+                let outcome = repo
+                    .land_stack(
+                        &bookmark,
+                        changeset.id(),
+                        base,
+                        pushvars.as_ref(),
+                        BookmarkKindRestrictions::AnyKind,
+                        push_authored_by,
+                    )
+                    .await?
+                    .into_response_with(&(repo.clone(), params.identity_schemes, None))
+                    .await?;
+                Some(outcome)
+            }
+            _ => None,
+        };
+
+        Ok(thrift::RepoCreateCommitResponse {
+            ids,
+            pushrebase_outcome,
+            ..Default::default()
+        })
+    }
+
+    /// Create a new commit, and optionally land it via pushrebase.
+    pub(crate) async fn repo_create_commit(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoCreateCommitParams,
+    ) -> Result<
+        thrift::RepoCreateCommitResponse,
+        impl Into<service::RepoCreateCommitExn> + LoggableError,
+    > {
+        self.impl_repo_create_commit(ctx, repo, params).await
+    }
+}
@@ -14,10 +14,12 @@ use hooks::PushAuthoredBy;
 use mononoke_api::ChangesetSpecifier;
 use mononoke_api::MononokeError;
 use pushrebase::PushrebaseConflict;
+use service::RepoCreateCommitExn;
 use service::RepoLandStackExn;
 use source_control as thrift;
 use source_control::services::source_control_service as service;
 
+use crate::commit_id::map_commit_identities;
 use crate::commit_id::CommitIdExt;
 use crate::errors;
 use crate::errors::LoggableError;
@@ -28,7 +30,7 @@ use crate::from_request::FromRequest;
 use crate::into_response::AsyncIntoResponseWith;
 use crate::source_control_impl::SourceControlServiceImpl;
 
-enum LandStackError {
+pub(super) enum LandStackError {
     Service(errors::ServiceError),
     PushrebaseConflicts(Vec<PushrebaseConflict>),
     HookRejections(Vec<HookRejection>),
@@ -73,6 +75,14 @@ fn reason_conflicts(conflicts: &Vec<PushrebaseConflict>) -> String {
     format!("Conflicts while pushrebasing: {:?}", conflicts)
 }
 
+fn convert_conflict(conflict: PushrebaseConflict) -> thrift::PushrebaseConflict {
+    thrift::PushrebaseConflict {
+        left: conflict.left.to_string(),
+        right: conflict.right.to_string(),
+        ..Default::default()
+    }
+}
+
 fn convert_rejection(rejection: HookRejection) -> thrift::HookRejection {
     thrift::HookRejection {
         hook_name: rejection.hook_name,
@@ -86,28 +96,27 @@ fn convert_rejection(rejection: HookRejection) -> thrift::HookRejection {
     }
 }
 
-impl From<LandStackError> for RepoLandStackExn {
-    fn from(e: LandStackError) -> RepoLandStackExn {
-        match e {
+impl LandStackError {
+    /// Convert into a thrift exception, using the given constructors for the
+    /// hook rejection and pushrebase conflict variants.
+    fn into_exn<E: From<errors::ServiceError>>(
+        self,
+        hook_rejections: impl FnOnce(thrift::HookRejectionsException) -> E,
+        pushrebase_conflicts: impl FnOnce(thrift::PushrebaseConflictsException) -> E,
+    ) -> E {
+        match self {
             LandStackError::Service(e) => e.into(),
             LandStackError::HookRejections(rejections) => {
-                RepoLandStackExn::hook_rejections(thrift::HookRejectionsException {
+                hook_rejections(thrift::HookRejectionsException {
                     reason: reason_rejections(&rejections),
                     rejections: rejections.into_iter().map(convert_rejection).collect(),
                     ..Default::default()
                 })
             }
             LandStackError::PushrebaseConflicts(conflicts) => {
-                RepoLandStackExn::pushrebase_conflicts(thrift::PushrebaseConflictsException {
+                pushrebase_conflicts(thrift::PushrebaseConflictsException {
                     reason: reason_conflicts(&conflicts),
-                    conflicts: conflicts
-                        .into_iter()
-                        .map(|c| thrift::PushrebaseConflict {
-                            left: c.left.to_string(),
-                            right: c.right.to_string(),
-                            ..Default::default()
-                        })
-                        .collect(),
+                    conflicts: conflicts.into_iter().map(convert_conflict).collect(),
                     ..Default::default()
                 })
             }
@@ -115,6 +124,24 @@ impl From<LandStackError> for RepoLandStackExn {
     }
 }
 
+impl From<LandStackError> for RepoLandStackExn {
+    fn from(e: LandStackError) -> RepoLandStackExn {
+        e.into_exn(
+            RepoLandStackExn::hook_rejections,
+            RepoLandStackExn::pushrebase_conflicts,
+        )
+    }
+}
+
+impl From<LandStackError> for RepoCreateCommitExn {
+    fn from(e: LandStackError) -> RepoCreateCommitExn {
+        e.into_exn(
+            RepoCreateCommitExn::hook_rejections,
+            RepoCreateCommitExn::pushrebase_conflicts,
+        )
+    }
+}
+
 impl LoggableError for LandStackError {
     fn status_and_description(&self) -> (Status, String) {
         match self {
@@ -191,4 +218,55 @@ impl SourceControlServiceImpl {
     {
         self.impl_repo_land_stack(ctx, repo, params).await
     }
+
+    /// Check whether a stack would conflict if it were landed, without
+    /// landing it.
+    pub(crate) async fn repo_land_stack_preview(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoLandStackPreviewParams,
+    ) -> Result<thrift::RepoLandStackPreviewResponse, errors::ServiceError> {
+        let repo = self.repo(ctx, &repo).await?;
+        borrowed!(params.head, params.base);
+        let head = repo
+            .changeset(ChangesetSpecifier::from_request(head)?)
+            .await
+            .context("failed to resolve head commit")?
+            .ok_or_else(|| errors::commit_not_found(head.to_string()))?;
+        let base = repo
+            .changeset(ChangesetSpecifier::from_request(base)?)
+            .await
+            .context("failed to resolve base commit")?
+            .ok_or_else(|| errors::commit_not_found(base.to_string()))?;
+
+        match repo
+            .preview_land_stack(&params.bookmark, head.id(), base.id())
+            .await
+        {
+            Ok(preview) => {
+                let bookmark_value = match preview.bookmark_value {
+                    Some(cs_id) => {
+                        map_commit_identities(&repo, vec![cs_id], &params.identity_schemes)
+                            .await?
+                            .remove(&cs_id)
+                    }
+                    None => None,
+                };
+                Ok(thrift::RepoLandStackPreviewResponse {
+                    conflicts: Vec::new(),
+                    bookmark_value,
+                    pushrebase_distance: preview.pushrebase_distance.0 as i64,
+                    ..Default::default()
+                })
+            }
+            Err(MononokeError::PushrebaseConflicts(conflicts)) => {
+                Ok(thrift::RepoLandStackPreviewResponse {
+                    conflicts: conflicts.into_iter().map(convert_conflict).collect(),
+                    ..Default::default()
+                })
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
 }
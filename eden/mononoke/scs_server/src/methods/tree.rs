@@ -5,7 +5,22 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Mutex;
+
 use context::CoreContext;
+use futures::stream;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
+use mononoke_api::ChangesetContext;
+use mononoke_api::MononokePath;
+use mononoke_api::TreeContext;
+use mononoke_api::TreeId;
+use mononoke_types::ChangesetId;
+use mononoke_types::MPathElement;
 use source_control as thrift;
 
 use crate::errors;
@@ -13,6 +28,231 @@ use crate::from_request::check_range_and_convert;
 use crate::into_response::IntoResponse;
 use crate::source_control_impl::SourceControlServiceImpl;
 
+/// The maximum number of entries in the sorted listings kept by
+/// `TreeListCache`, across all listings.
+const TREE_LIST_CACHE_MAX_ENTRIES: usize = 1_000_000;
+
+/// A tree entry along with the key it is sorted by.
+struct SortableEntry {
+    key: i64,
+    entry: thrift::TreeEntry,
+}
+
+impl SortableEntry {
+    fn sort_key(&self) -> (i64, &str) {
+        (self.key, self.entry.name.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+struct TreeListKey {
+    tree_id: TreeId,
+    sort_order: thrift::TreeListSortOrder,
+    entry_types: Option<BTreeSet<thrift::EntryType>>,
+    /// Mtimes depend on the history of the commit, and not just the tree.
+    changeset_id: Option<ChangesetId>,
+}
+
+#[derive(Default)]
+struct CachedListings {
+    listings: HashMap<TreeListKey, Arc<Vec<SortableEntry>>>,
+    insertion_order: VecDeque<TreeListKey>,
+    total_entries: usize,
+}
+
+/// Recently listed directories, sorted in ascending order, so that listing
+/// further pages of a directory doesn't load its fsnode or look up the
+/// history of its entries again.
+#[derive(Default)]
+pub(crate) struct TreeListCache {
+    cached: Mutex<CachedListings>,
+}
+
+impl TreeListCache {
+    fn get(&self, key: &TreeListKey) -> Option<Arc<Vec<SortableEntry>>> {
+        let cached = self.cached.lock().expect("lock poisoned");
+        cached.listings.get(key).cloned()
+    }
+
+    fn insert(&self, key: TreeListKey, listing: Arc<Vec<SortableEntry>>) {
+        if listing.len() > TREE_LIST_CACHE_MAX_ENTRIES {
+            return;
+        }
+        let mut cached = self.cached.lock().expect("lock poisoned");
+        if cached.listings.contains_key(&key) {
+            return;
+        }
+        cached.total_entries += listing.len();
+        cached.insertion_order.push_back(key.clone());
+        cached.listings.insert(key, listing);
+        while cached.total_entries > TREE_LIST_CACHE_MAX_ENTRIES {
+            let oldest = match cached.insertion_order.pop_front() {
+                Some(oldest) => oldest,
+                None => break,
+            };
+            if let Some(evicted) = cached.listings.remove(&oldest) {
+                cached.total_entries -= evicted.len();
+            }
+        }
+    }
+}
+
+/// The size of an entry: the file size for files, or the total size of the
+/// files in the directory for directories.
+fn entry_size(entry: &thrift::TreeEntry) -> i64 {
+    match &entry.info {
+        thrift::EntryInfo::file(info) => info.file_size,
+        thrift::EntryInfo::tree(info) => info.descendant_files_total_size,
+        _ => 0,
+    }
+}
+
+/// Continuation tokens identify the last entry of the previous page by its
+/// sort key and name, along with the order they were sorted in, so that they
+/// can't be used with a different order.
+fn continuation_token(params: &thrift::TreeListParams, entry: &SortableEntry) -> String {
+    format!(
+        "{}:{}:{}:{}",
+        params.sort_order, params.descending, entry.key, entry.entry.name
+    )
+}
+
+fn parse_continuation_token(
+    params: &thrift::TreeListParams,
+    token: &str,
+) -> Result<(i64, String), errors::ServiceError> {
+    let invalid = || errors::invalid_request(format!("invalid continuation token: {}", token));
+    let mut parts = token.splitn(4, ':');
+    let (sort_order, descending, key, name) =
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(sort_order), Some(descending), Some(key), Some(name)) => {
+                (sort_order, descending, key, name)
+            }
+            _ => return Err(invalid().into()),
+        };
+    if sort_order != params.sort_order.to_string()
+        || descending != params.descending.to_string()
+    {
+        return Err(errors::invalid_request(
+            "continuation token was issued for a different sort order",
+        )
+        .into());
+    }
+    let key = key.parse::<i64>().map_err(|_| invalid())?;
+    Ok((key, name.to_string()))
+}
+
+/// The author dates of the commits that last modified the named entries of
+/// the directory at `path` in `changeset`, in seconds since the epoch.
+async fn entry_mtimes(
+    changeset: &ChangesetContext,
+    path: &MononokePath,
+    names: impl Iterator<Item = &str>,
+) -> Result<HashMap<String, i64>, errors::ServiceError> {
+    let paths = names
+        .map(|name| {
+            let element = MPathElement::new(name.as_bytes().to_vec())
+                .map_err(|e| errors::internal_error(format!("invalid entry name: {}", e)))?;
+            Ok(path.append(&element))
+        })
+        .collect::<Result<Vec<_>, errors::ServiceError>>()?;
+    let last_modified: Vec<(String, Option<ChangesetContext>)> = changeset
+        .paths_with_history(paths.into_iter())
+        .await?
+        .map_ok(|context| async move {
+            let name = match context.path().as_mpath() {
+                Some(mpath) => mpath.basename().to_string(),
+                None => String::new(),
+            };
+            Ok::<_, errors::ServiceError>((name, context.last_modified().await?))
+        })
+        .map_err(errors::ServiceError::from)
+        .try_buffer_unordered(100)
+        .try_collect()
+        .await?;
+
+    // Entries are often last modified by the same commits, so look up the
+    // author date of each commit only once.
+    let mut commits = HashMap::new();
+    for changeset in last_modified.iter().filter_map(|(_, changeset)| changeset.as_ref()) {
+        commits
+            .entry(changeset.id())
+            .or_insert_with(|| changeset.clone());
+    }
+    let author_dates: HashMap<ChangesetId, i64> = stream::iter(commits.into_values())
+        .map(|changeset| async move {
+            let author_date = changeset.author_date().await?;
+            Ok::<_, errors::ServiceError>((changeset.id(), author_date.timestamp()))
+        })
+        .buffer_unordered(100)
+        .try_collect()
+        .await?;
+
+    Ok(last_modified
+        .into_iter()
+        .map(|(name, changeset)| {
+            let mtime = changeset
+                .and_then(|changeset| author_dates.get(&changeset.id()).copied())
+                .unwrap_or(0);
+            (name, mtime)
+        })
+        .collect())
+}
+
+/// List the entries of `tree` of the requested types, sorted in ascending
+/// order.
+async fn sorted_listing(
+    tree: &TreeContext,
+    params: &thrift::TreeListParams,
+    changeset_path: Option<&(ChangesetContext, MononokePath)>,
+) -> Result<Vec<SortableEntry>, errors::ServiceError> {
+    let entry_types = params.entry_types.as_ref();
+    let entries: Vec<thrift::TreeEntry> = tree
+        .list()
+        .await?
+        .map(IntoResponse::into_response)
+        .filter(|entry: &thrift::TreeEntry| {
+            entry_types.map_or(true, |types| types.contains(&entry.r#type))
+        })
+        .collect();
+
+    let mut entries: Vec<SortableEntry> = match params.sort_order {
+        thrift::TreeListSortOrder::SIZE => entries
+            .into_iter()
+            .map(|entry| SortableEntry {
+                key: entry_size(&entry),
+                entry,
+            })
+            .collect(),
+        thrift::TreeListSortOrder::MTIME => {
+            if entries.len() as i64 > source_control::TREE_LIST_MTIME_SORT_MAX_ENTRIES {
+                return Err(errors::invalid_request(format!(
+                    "sorting by mtime is not supported for trees with more than {} entries",
+                    source_control::TREE_LIST_MTIME_SORT_MAX_ENTRIES,
+                ))
+                .into());
+            }
+            let (changeset, dir_path) = changeset_path
+                .ok_or_else(|| errors::internal_error("missing commit for mtime sort"))?;
+            let names = entries.iter().map(|entry| entry.name.as_str());
+            let mtimes = entry_mtimes(changeset, dir_path, names).await?;
+            entries
+                .into_iter()
+                .map(|entry| SortableEntry {
+                    key: mtimes.get(&entry.name).copied().unwrap_or(0),
+                    entry,
+                })
+                .collect()
+        }
+        _ => entries
+            .into_iter()
+            .map(|entry| SortableEntry { key: 0, entry })
+            .collect(),
+    };
+    entries.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+    Ok(entries)
+}
+
 impl SourceControlServiceImpl {
     /// Determine whether a tree exists.
     pub(crate) async fn tree_exists(
@@ -26,41 +266,126 @@ impl SourceControlServiceImpl {
     }
 
     /// List the contents of a directory.
+    ///
+    /// Entries can be filtered by type and sorted by name, size or mtime,
+    /// and are returned a page at a time.
     pub(crate) async fn tree_list(
         &self,
         ctx: CoreContext,
         tree: thrift::TreeSpecifier,
         params: thrift::TreeListParams,
     ) -> Result<thrift::TreeListResponse, errors::ServiceError> {
-        let (_repo, tree) = self.repo_tree(ctx, &tree).await?;
         let offset: usize = check_range_and_convert("offset", params.offset, 0..)?;
         let limit: usize = check_range_and_convert(
             "limit",
             params.limit,
             0..=source_control::TREE_LIST_MAX_LIMIT,
         )?;
-        if let Some(tree) = tree {
-            let summary = tree.summary().await?;
-            let entries = tree
-                .list()
-                .await?
+        let after = params
+            .continuation_token
+            .as_deref()
+            .map(|token| parse_continuation_token(&params, token))
+            .transpose()?;
+
+        // Sorting by mtime needs the commit and path of the tree to look up
+        // the history of its entries.
+        let (tree, changeset_path) = match (&tree, params.sort_order) {
+            (
+                thrift::TreeSpecifier::by_commit_path(commit_path),
+                thrift::TreeListSortOrder::MTIME,
+            ) => {
+                let (_repo, changeset) = self.repo_changeset(ctx, &commit_path.commit).await?;
+                let path = changeset.path_with_content(&commit_path.path).await?;
+                let dir_path = path.path().clone();
+                (path.tree().await?, Some((changeset, dir_path)))
+            }
+            (_, thrift::TreeListSortOrder::MTIME) => {
+                return Err(errors::invalid_request(
+                    "sorting by mtime is only supported for trees specified by commit and path",
+                )
+                .into());
+            }
+            _ => (self.repo_tree(ctx, &tree).await?.1, None),
+        };
+
+        let tree = match tree {
+            Some(tree) => tree,
+            None => {
+                // Listing a path that is not a directory just returns an empty list.
+                return Ok(thrift::TreeListResponse {
+                    entries: Vec::new(),
+                    count: 0,
+                    ..Default::default()
+                });
+            }
+        };
+
+        let key = TreeListKey {
+            tree_id: tree.id().clone(),
+            sort_order: params.sort_order,
+            entry_types: params.entry_types.clone(),
+            changeset_id: changeset_path
+                .as_ref()
+                .map(|(changeset, _)| changeset.id()),
+        };
+        let entries = match self.tree_list_cache.get(&key) {
+            Some(entries) => entries,
+            None => {
+                let entries =
+                    Arc::new(sorted_listing(&tree, &params, changeset_path.as_ref()).await?);
+                self.tree_list_cache.insert(key, entries.clone());
+                entries
+            }
+        };
+        let count = entries.len();
+
+        let page: Vec<&SortableEntry> = if params.descending {
+            let end = match &after {
+                Some((key, name)) => {
+                    let after = (*key, name.as_str());
+                    entries.partition_point(|entry| entry.sort_key() < after)
+                }
+                None => entries.len(),
+            };
+            entries[..end]
+                .iter()
+                .rev()
                 .skip(offset)
-                .take(limit)
-                .map(IntoResponse::into_response)
-                .collect();
-            let response = thrift::TreeListResponse {
-                entries,
-                count: (summary.child_files_count + summary.child_dirs_count) as i64,
-                ..Default::default()
+                .take(limit.saturating_add(1))
+                .collect()
+        } else {
+            let start = match &after {
+                Some((key, name)) => {
+                    let after = (*key, name.as_str());
+                    entries.partition_point(|entry| entry.sort_key() <= after)
+                }
+                None => 0,
             };
-            Ok(response)
+            entries[start..]
+                .iter()
+                .skip(offset)
+                .take(limit.saturating_add(1))
+                .collect()
+        };
+
+        // We fetched one more entry than the limit to find out whether
+        // there are more.
+        let continuation_token = if page.len() > limit && limit > 0 {
+            Some(continuation_token(&params, page[limit - 1]))
         } else {
-            // Listing a path that is not a directory just returns an empty list.
-            Ok(thrift::TreeListResponse {
-                entries: Vec::new(),
-                count: 0,
-                ..Default::default()
-            })
-        }
+            None
+        };
+        let entries = page
+            .into_iter()
+            .take(limit)
+            .map(|entry| entry.entry.clone())
+            .collect();
+
+        Ok(thrift::TreeListResponse {
+            entries,
+            count: count as i64,
+            continuation_token,
+            ..Default::default()
+        })
     }
 }
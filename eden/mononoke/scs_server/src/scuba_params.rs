@@ -62,6 +62,9 @@ impl AddScubaParams for thrift::RepoCreateCommitParams {
         if let Some(service_identity) = self.service_identity.as_deref() {
             scuba.add("service_identity", service_identity);
         }
+        if let Some(land_to_bookmark) = self.land_to_bookmark.as_deref() {
+            scuba.add("bookmark_name", land_to_bookmark);
+        }
     }
 }
 
@@ -112,6 +115,16 @@ impl AddScubaParams for thrift::RepoCreateBookmarkParams {
     }
 }
 
+impl AddScubaParams for thrift::RepoCreateTagParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        scuba.add("param_tag", self.tag.as_str());
+        scuba.add("commit", self.target.to_string());
+        if let Some(service_identity) = self.service_identity.as_deref() {
+            scuba.add("service_identity", service_identity);
+        }
+    }
+}
+
 impl AddScubaParams for thrift::RepoMoveBookmarkParams {
     fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
         scuba.add("bookmark_name", self.bookmark.as_str());
@@ -162,6 +175,15 @@ impl AddScubaParams for thrift::RepoLandStackParams {
     }
 }
 
+impl AddScubaParams for thrift::RepoLandStackPreviewParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        scuba.add("bookmark_name", self.bookmark.as_str());
+        scuba.add("commit", self.head.to_string());
+        scuba.add("param_base", self.base.to_string());
+        self.identity_schemes.add_scuba_params(scuba);
+    }
+}
+
 impl AddScubaParams for thrift::RepoListBookmarksParams {
     fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
         scuba.add("param_include_scratch", self.include_scratch as i32);
@@ -174,6 +196,17 @@ impl AddScubaParams for thrift::RepoListBookmarksParams {
     }
 }
 
+impl AddScubaParams for thrift::RepoListTagsParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        scuba.add("param_tag_prefix", self.tag_prefix.as_str());
+        scuba.add("param_limit", self.limit);
+        if let Some(after) = &self.after {
+            scuba.add("param_after", after.as_str());
+        }
+        self.identity_schemes.add_scuba_params(scuba);
+    }
+}
+
 impl AddScubaParams for thrift::RepoResolveBookmarkParams {
     fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
         scuba.add("bookmark_name", self.bookmark_name.as_str());
@@ -188,6 +221,13 @@ impl AddScubaParams for thrift::RepoBookmarkInfoParams {
     }
 }
 
+impl AddScubaParams for thrift::RepoStatsParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        scuba.add("bookmark_name", self.bookmark_name.as_str());
+        self.identity_schemes.add_scuba_params(scuba);
+    }
+}
+
 impl AddScubaParams for thrift::RepoResolveCommitPrefixParams {
     fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
         scuba.add("param_prefix", self.prefix.as_str());
@@ -196,6 +236,14 @@ impl AddScubaParams for thrift::RepoResolveCommitPrefixParams {
     }
 }
 
+impl AddScubaParams for thrift::RepoResolveCommitsParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        scuba.add("param_revisions_count", self.revisions.len());
+        scuba.add("param_hash_scheme", self.hash_scheme.to_string());
+        self.identity_schemes.add_scuba_params(scuba);
+    }
+}
+
 impl AddScubaParams for thrift::RepoStackInfoParams {}
 
 impl AddScubaParams for thrift::RepoPrepareCommitsParams {}
@@ -236,6 +284,19 @@ impl AddScubaParams for thrift::CommitFileDiffsParams {
     }
 }
 
+impl AddScubaParams for thrift::CommitDiffParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        scuba.add(
+            "other_commit",
+            self.other_commit_id.as_ref().map(|oci| oci.to_string()),
+        );
+        scuba.add("param_context", self.context);
+        if let Some(after) = &self.after {
+            scuba.add("param_after", after.as_str());
+        }
+    }
+}
+
 impl AddScubaParams for thrift::CommitFindFilesParams {
     fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
         scuba.add("param_limit", self.limit);
@@ -280,6 +341,10 @@ impl AddScubaParams for thrift::CommitLookupParams {
 
 impl AddScubaParams for thrift::CommitLookupPushrebaseHistoryParams {}
 
+impl AddScubaParams for thrift::CommitPhaseParams {}
+
+impl AddScubaParams for thrift::CommitSignatureVerificationParams {}
+
 impl AddScubaParams for thrift::CommitHistoryParams {
     fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
         scuba.add("param_format", self.format.to_string());
@@ -322,6 +387,12 @@ impl AddScubaParams for thrift::CommitRunHooksParams {
     }
 }
 
+impl AddScubaParams for thrift::CommitDerivationTraceParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        scuba.add("param_derived_data_type", self.derived_data_type.as_str());
+    }
+}
+
 impl AddScubaParams for thrift::CommitLookupXRepoParams {
     fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
         scuba.add("other_repo", self.other_repo.name.as_str());
@@ -453,6 +524,11 @@ impl AddScubaParams for thrift::TreeListParams {
     fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
         scuba.add("param_offset", self.offset);
         scuba.add("param_limit", self.limit);
+        scuba.add("param_sort_order", self.sort_order.to_string());
+        scuba.add("param_descending", self.descending);
+        if let Some(continuation_token) = &self.continuation_token {
+            scuba.add("param_continuation_token", continuation_token.as_str());
+        }
     }
 }
 
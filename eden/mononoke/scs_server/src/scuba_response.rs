@@ -47,20 +47,30 @@ impl AddScubaResponse for thrift::RepoCreateStackResponse {
 
 impl AddScubaResponse for thrift::RepoCreateBookmarkResponse {}
 
+impl AddScubaResponse for thrift::RepoCreateTagResponse {}
+
 impl AddScubaResponse for thrift::RepoMoveBookmarkResponse {}
 
 impl AddScubaResponse for thrift::RepoDeleteBookmarkResponse {}
 
 impl AddScubaResponse for thrift::RepoLandStackResponse {}
 
+impl AddScubaResponse for thrift::RepoLandStackPreviewResponse {}
+
 impl AddScubaResponse for thrift::RepoListBookmarksResponse {}
 
+impl AddScubaResponse for thrift::RepoListTagsResponse {}
+
 impl AddScubaResponse for thrift::RepoResolveBookmarkResponse {}
 
 impl AddScubaResponse for thrift::RepoResolveCommitPrefixResponse {}
 
+impl AddScubaResponse for thrift::RepoResolveCommitsResponse {}
+
 impl AddScubaResponse for thrift::RepoBookmarkInfoResponse {}
 
+impl AddScubaResponse for thrift::RepoStatsResponse {}
+
 impl AddScubaResponse for thrift::RepoStackInfoResponse {}
 
 impl AddScubaResponse for thrift::RepoPrepareCommitsResponse {}
@@ -75,12 +85,18 @@ impl AddScubaResponse for thrift::CommitCompareResponse {}
 
 impl AddScubaResponse for thrift::CommitFileDiffsResponse {}
 
+impl AddScubaResponse for thrift::CommitDiffResponse {}
+
 impl AddScubaResponse for thrift::CommitFindFilesResponse {}
 
 impl AddScubaResponse for thrift::CommitInfo {}
 
 impl AddScubaResponse for thrift::CommitLookupResponse {}
 
+impl AddScubaResponse for thrift::CommitPhaseResponse {}
+
+impl AddScubaResponse for thrift::CommitSignatureVerificationResponse {}
+
 impl AddScubaResponse for thrift::CommitLookupPushrebaseHistoryResponse {}
 
 impl AddScubaResponse for thrift::CommitHistoryResponse {}
@@ -89,6 +105,8 @@ impl AddScubaResponse for thrift::CommitListDescendantBookmarksResponse {}
 
 impl AddScubaResponse for thrift::CommitRunHooksResponse {}
 
+impl AddScubaResponse for thrift::CommitDerivationTraceResponse {}
+
 impl AddScubaResponse for thrift::CommitPathBlameResponse {}
 
 impl AddScubaResponse for thrift::CommitPathHistoryResponse {}
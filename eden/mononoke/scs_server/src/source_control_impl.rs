@@ -16,6 +16,10 @@ use ephemeral_blobstore::BubbleId;
 use ephemeral_blobstore::RepoEphemeralStore;
 use fbinit::FacebookInit;
 use futures::future::BoxFuture;
+use futures::stream;
+use futures::stream::BoxStream;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
 use futures::try_join;
 use futures::FutureExt;
 use futures_ext::FbFutureExt;
@@ -47,6 +51,7 @@ use repo_authorization::AuthorizationContext;
 use scribe_ext::Scribe;
 use scuba_ext::MononokeScubaSampleBuilder;
 use scuba_ext::ScubaValue;
+use slo::SloTracker;
 use slog::Logger;
 use source_control as thrift;
 use source_control::server::SourceControlService;
@@ -61,10 +66,14 @@ use crate::errors;
 use crate::errors::ServiceErrorResultExt;
 use crate::errors::Status;
 use crate::from_request::FromRequest;
+use crate::methods::tree::TreeListCache;
 use crate::scuba_params::AddScubaParams;
 use crate::scuba_response::AddScubaResponse;
 use crate::specifiers::SpecifierExt;
 
+type CommitDiffThriftStream =
+    BoxStream<'static, Result<thrift::CommitDiffFile, service::CommitDiffStreamExn>>;
+
 const FORWARDED_IDENTITIES_HEADER: &str = "scm_forwarded_identities";
 const FORWARDED_CLIENT_IP_HEADER: &str = "scm_forwarded_client_ip";
 const FORWARDED_CLIENT_DEBUG_HEADER: &str = "scm_forwarded_client_debug";
@@ -97,7 +106,9 @@ pub(crate) struct SourceControlServiceImpl {
     pub(crate) scuba_builder: MononokeScubaSampleBuilder,
     pub(crate) identity: Identity,
     pub(crate) scribe: Scribe,
+    pub(crate) tree_list_cache: Arc<TreeListCache>,
     identity_proxy_checker: Arc<ConnectionSecurityChecker>,
+    slo_tracker: Arc<SloTracker>,
 }
 
 pub(crate) struct SourceControlServiceThriftImpl(SourceControlServiceImpl);
@@ -112,6 +123,7 @@ impl SourceControlServiceImpl {
         scribe: Scribe,
         identity_proxy_checker: ConnectionSecurityChecker,
         common_config: &CommonConfig,
+        slo_tracker: Arc<SloTracker>,
     ) -> Self {
         scuba_builder.add_common_server_data();
 
@@ -126,7 +138,9 @@ impl SourceControlServiceImpl {
                 common_config.internal_identity.id_data.as_str(),
             ),
             scribe,
+            tree_list_cache: Arc::new(TreeListCache::default()),
             identity_proxy_checker: Arc::new(identity_proxy_checker),
+            slo_tracker,
         }
     }
 
@@ -150,6 +164,24 @@ impl SourceControlServiceImpl {
         Ok(ctx)
     }
 
+    /// Record a completed request against the service level objectives of
+    /// the repo it was for.
+    fn record_slo<T>(
+        &self,
+        reponame: Option<String>,
+        method: &str,
+        stats: &FutureStats,
+        result: &Result<T, impl errors::LoggableError>,
+    ) {
+        if let Some(reponame) = reponame {
+            let internal_failure = match result {
+                Ok(_) => false,
+                Err(err) => matches!(err.status_and_description().0, Status::InternalError),
+            };
+            self.slo_tracker.record(&reponame, method, stats.completion_time, internal_failure);
+        }
+    }
+
     /// Create and configure a scuba sample builder for a request.
     fn create_scuba(
         &self,
@@ -613,6 +645,17 @@ macro_rules! create_ctx {
     };
 }
 
+// Define a macro to get the name of the repo that a request is for, if any.
+macro_rules! scuba_reponame {
+    ( $params_name:ident ) => {
+        None
+    };
+
+    ( $obj_name:ident, $params_name:ident ) => {
+        SpecifierExt::scuba_reponame(&$obj_name)
+    };
+}
+
 // Define a macro that generates a non-async wrapper that delegates to the
 // async implementation of the method.
 //
@@ -631,6 +674,7 @@ macro_rules! impl_thrift_methods {
                 Self: Sync + 'async_trait,
             {
                 let handler = async move {
+                    let reponame = scuba_reponame!($( $param_name ),*);
                     let ctx = create_ctx!(self.0, $method_name, req_ctxt, $( $param_name ),*).await?;
                     ctx.scuba().clone().log_with_msg("Request start", None);
                     STATS::total_request_start.add_value(1);
@@ -641,6 +685,7 @@ macro_rules! impl_thrift_methods {
                         .await;
                     log_result(ctx, &stats, &res);
                     let method = stringify!($method_name).to_string();
+                    self.0.record_slo(reponame, &method, &stats, &res);
                     STATS::method_completion_time_ms.add_value(stats.completion_time.as_millis_unchecked() as i64, (method,));
                     res.map_err(Into::into)
                 };
@@ -673,11 +718,31 @@ impl SourceControlService for SourceControlServiceThriftImpl {
             params: thrift::RepoResolveCommitPrefixParams,
         ) -> Result<thrift::RepoResolveCommitPrefixResponse, service::RepoResolveCommitPrefixExn>;
 
+        async fn repo_resolve_commits(
+            repo: thrift::RepoSpecifier,
+            params: thrift::RepoResolveCommitsParams,
+        ) -> Result<thrift::RepoResolveCommitsResponse, service::RepoResolveCommitsExn>;
+
         async fn repo_list_bookmarks(
             repo: thrift::RepoSpecifier,
             params: thrift::RepoListBookmarksParams,
         ) -> Result<thrift::RepoListBookmarksResponse, service::RepoListBookmarksExn>;
 
+        async fn repo_list_tags(
+            repo: thrift::RepoSpecifier,
+            params: thrift::RepoListTagsParams,
+        ) -> Result<thrift::RepoListTagsResponse, service::RepoListTagsExn>;
+
+        async fn commit_phase(
+            commit: thrift::CommitSpecifier,
+            params: thrift::CommitPhaseParams,
+        ) -> Result<thrift::CommitPhaseResponse, service::CommitPhaseExn>;
+
+        async fn commit_signature_verification(
+            commit: thrift::CommitSpecifier,
+            params: thrift::CommitSignatureVerificationParams,
+        ) -> Result<thrift::CommitSignatureVerificationResponse, service::CommitSignatureVerificationExn>;
+
         async fn commit_common_base_with(
             commit: thrift::CommitSpecifier,
             params: thrift::CommitCommonBaseWithParams,
@@ -733,6 +798,11 @@ impl SourceControlService for SourceControlServiceThriftImpl {
             params: thrift::CommitRunHooksParams,
         ) -> Result<thrift::CommitRunHooksResponse, service::CommitRunHooksExn>;
 
+        async fn commit_derivation_trace(
+            commit: thrift::CommitSpecifier,
+            params: thrift::CommitDerivationTraceParams,
+        ) -> Result<thrift::CommitDerivationTraceResponse, service::CommitDerivationTraceExn>;
+
         async fn commit_lookup_xrepo(
             commit: thrift::CommitSpecifier,
             params: thrift::CommitLookupXRepoParams,
@@ -828,6 +898,11 @@ impl SourceControlService for SourceControlServiceThriftImpl {
             params: thrift::RepoBookmarkInfoParams,
         ) -> Result<thrift::RepoBookmarkInfoResponse, service::RepoBookmarkInfoExn>;
 
+        async fn repo_stats(
+            repo: thrift::RepoSpecifier,
+            params: thrift::RepoStatsParams,
+        ) -> Result<thrift::RepoStatsResponse, service::RepoStatsExn>;
+
         async fn repo_stack_info(
             repo: thrift::RepoSpecifier,
             params: thrift::RepoStackInfoParams,
@@ -838,6 +913,11 @@ impl SourceControlService for SourceControlServiceThriftImpl {
             params: thrift::RepoCreateBookmarkParams,
         ) -> Result<thrift::RepoCreateBookmarkResponse, service::RepoCreateBookmarkExn>;
 
+        async fn repo_create_tag(
+            repo: thrift::RepoSpecifier,
+            params: thrift::RepoCreateTagParams,
+        ) -> Result<thrift::RepoCreateTagResponse, service::RepoCreateTagExn>;
+
         async fn repo_move_bookmark(
             repo: thrift::RepoSpecifier,
             params: thrift::RepoMoveBookmarkParams,
@@ -853,6 +933,11 @@ impl SourceControlService for SourceControlServiceThriftImpl {
             params: thrift::RepoLandStackParams,
         ) -> Result<thrift::RepoLandStackResponse, service::RepoLandStackExn>;
 
+        async fn repo_land_stack_preview(
+            repo: thrift::RepoSpecifier,
+            params: thrift::RepoLandStackPreviewParams,
+        ) -> Result<thrift::RepoLandStackPreviewResponse, service::RepoLandStackPreviewExn>;
+
         async fn repo_prepare_commits(
             repo: thrift::RepoSpecifier,
             params: thrift::RepoPrepareCommitsParams,
@@ -921,4 +1006,55 @@ impl SourceControlService for SourceControlServiceThriftImpl {
             params: thrift::CreateGitTreeParams,
         ) -> Result<thrift::CreateGitTreeResponse, service::CreateGitTreeExn>;
     }
+
+    // Streaming methods are implemented by hand, as the items of the stream
+    // have a different error type.  The request is logged once the stream
+    // has been set up.
+    fn commit_diff<'implementation, 'req_ctxt, 'async_trait>(
+        &'implementation self,
+        req_ctxt: &'req_ctxt RequestContext,
+        commit: thrift::CommitSpecifier,
+        params: thrift::CommitDiffParams,
+    ) -> Pin<
+        Box<
+            dyn Future<
+                    Output = Result<
+                        (thrift::CommitDiffResponse, CommitDiffThriftStream),
+                        service::CommitDiffExn,
+                    >,
+                > + Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'implementation: 'async_trait,
+        'req_ctxt: 'async_trait,
+        Self: Sync + 'async_trait,
+    {
+        let handler = async move {
+            let reponame = scuba_reponame!(commit, params);
+            let ctx = create_ctx!(self.0, commit_diff, req_ctxt, commit, params).await?;
+            ctx.scuba().clone().log_with_msg("Request start", None);
+            STATS::total_request_start.add_value(1);
+            let (stats, res) = (self.0)
+                .commit_diff(ctx.clone(), commit, params)
+                .timed()
+                .on_cancel_with_data(|stats| log_cancelled(&ctx, &stats))
+                .await;
+            let (res, files) = match res {
+                Ok((response, files)) => (Ok(response), files),
+                Err(e) => (Err(e), stream::empty().boxed()),
+            };
+            log_result(ctx, &stats, &res);
+            let method = "commit_diff".to_string();
+            self.0.record_slo(reponame, &method, &stats, &res);
+            STATS::method_completion_time_ms.add_value(
+                stats.completion_time.as_millis_unchecked() as i64,
+                (method,),
+            );
+            let files = files.map_err(service::CommitDiffStreamExn::from).boxed();
+            Ok((res?, files))
+        };
+        Box::pin(handler)
+    }
 }
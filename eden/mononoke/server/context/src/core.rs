@@ -5,7 +5,10 @@
  * GNU General Public License version 2.
  */
 
+use std::future;
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Instant;
 
 use fbinit::FacebookInit;
 use metadata::Metadata;
@@ -13,6 +16,7 @@ use scribe_ext::Scribe;
 use scuba_ext::MononokeScubaSampleBuilder;
 use slog::Logger;
 use slog_glog_fmt::logger_that_can_work_in_tests;
+use thiserror::Error;
 
 use crate::logging::LoggingContainer;
 use crate::logging::SamplingKey;
@@ -21,11 +25,30 @@ use crate::perf_counters_stack::PerfCountersStack;
 use crate::session::SessionClass;
 use crate::session::SessionContainer;
 
+/// Work was abandoned because nobody is waiting for its result any more.
+#[derive(Debug, Error)]
+pub enum RequestAbandoned {
+    #[error("Deadline exceeded: the request is no longer waited for")]
+    DeadlineExceeded,
+    #[error("Client disconnected: the request is no longer waited for")]
+    ClientDisconnected,
+}
+
+impl RequestAbandoned {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            RequestAbandoned::DeadlineExceeded => "deadline_exceeded",
+            RequestAbandoned::ClientDisconnected => "client_disconnected",
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct CoreContext {
     pub fb: FacebookInit,
     session: SessionContainer,
     logging: LoggingContainer,
+    deadline: Option<Instant>,
 }
 
 impl CoreContext {
@@ -34,6 +57,7 @@ impl CoreContext {
             fb,
             logging,
             session,
+            deadline: None,
         }
     }
 
@@ -64,14 +88,75 @@ impl CoreContext {
     /// Create a new CoreContext, with a reset LoggingContainer. This is useful to reset perf
     /// counters. The existing CoreContext is unaffected.
     pub fn clone_and_reset(&self) -> Self {
-        self.session
-            .new_context(self.logger().clone(), self.scuba().clone())
+        let mut ctx = self
+            .session
+            .new_context(self.logger().clone(), self.scuba().clone());
+        ctx.deadline = self.deadline;
+        ctx
+    }
+
+    /// Create a new CoreContext for work that must finish by `deadline`.  If
+    /// this context already has an earlier deadline, that one is kept.
+    pub fn clone_with_deadline(&self, deadline: Instant) -> Self {
+        let mut ctx = self.clone();
+        ctx.deadline = Some(match self.deadline {
+            Some(existing) => existing.min(deadline),
+            None => deadline,
+        });
+        ctx
+    }
+
+    /// The time by which work on behalf of this context should finish, if
+    /// there is one.  Long-running operations should check it and give up
+    /// once it has passed.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub fn deadline_exceeded(&self) -> bool {
+        self.deadline.map_or(false, |deadline| Instant::now() >= deadline)
+    }
+
+    /// Whether nobody is waiting for work on behalf of this context any more,
+    /// either because its deadline has passed or because its session has
+    /// been cancelled.
+    pub fn is_abandoned(&self) -> bool {
+        self.deadline_exceeded() || self.session.is_cancelled()
+    }
+
+    /// Wait until nobody is waiting for work on behalf of this context any
+    /// more.
+    pub async fn abandoned(&self) -> RequestAbandoned {
+        let deadline = async {
+            match self.deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                None => future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = self.session.cancelled() => RequestAbandoned::ClientDisconnected,
+            _ = deadline => RequestAbandoned::DeadlineExceeded,
+        }
+    }
+
+    /// Run `fut`, abandoning it if this context's deadline passes or its
+    /// session is cancelled before it finishes.
+    pub async fn run_until_abandoned<F: Future>(
+        &self,
+        fut: F,
+    ) -> Result<F::Output, RequestAbandoned> {
+        tokio::select! {
+            biased;
+            output = fut => Ok(output),
+            abandoned = self.abandoned() => Err(abandoned),
+        }
     }
 
     pub fn clone_and_sample(&self, sampling_key: SamplingKey) -> Self {
         Self {
             fb: self.fb,
             session: self.session.clone(),
+            deadline: self.deadline,
             logging: self.logging.clone_and_sample(sampling_key),
         }
     }
@@ -80,6 +165,7 @@ impl CoreContext {
         Self {
             fb: self.fb,
             session: self.session.clone(),
+            deadline: self.deadline,
             logging: self.logging.clone_with_logger(logger),
         }
     }
@@ -88,6 +174,7 @@ impl CoreContext {
         Self {
             fb: self.fb,
             session: self.session.clone(),
+            deadline: self.deadline,
             logging: self.logging.clone_with_repo_name(repo_name),
         }
     }
@@ -99,6 +186,7 @@ impl CoreContext {
         Self {
             fb: self.fb,
             session: self.session.clone(),
+            deadline: self.deadline,
             logging: self.logging.with_mutated_scuba(mutator),
         }
     }
@@ -139,3 +227,37 @@ impl CoreContext {
         self.logging.fork_perf_counters()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[fbinit::test]
+    async fn test_abandoned_on_cancel(fb: FacebookInit) {
+        let session = SessionContainer::new_with_defaults(fb);
+        let ctx = CoreContext::test_mock_session(session.clone());
+        assert_eq!(ctx.run_until_abandoned(async { 1 }).await.unwrap(), 1);
+        assert!(!ctx.is_abandoned());
+
+        let work = ctx.run_until_abandoned(tokio::time::sleep(Duration::from_secs(3600)));
+        session.cancel();
+        assert!(matches!(work.await, Err(RequestAbandoned::ClientDisconnected)));
+        assert!(ctx.is_abandoned());
+    }
+
+    #[fbinit::test]
+    async fn test_abandoned_past_deadline(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let later = ctx.clone_with_deadline(Instant::now() + Duration::from_secs(3600));
+        assert_eq!(later.run_until_abandoned(async { 1 }).await.unwrap(), 1);
+
+        // The earlier deadline is kept.
+        let ctx = later.clone_with_deadline(Instant::now() + Duration::from_millis(10));
+        let work = ctx.run_until_abandoned(tokio::time::sleep(Duration::from_secs(3600)));
+        assert!(matches!(work.await, Err(RequestAbandoned::DeadlineExceeded)));
+        assert!(ctx.is_abandoned());
+        assert!(!later.is_abandoned());
+    }
+}
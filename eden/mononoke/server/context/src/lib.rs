@@ -11,6 +11,7 @@
 pub use session_id::SessionId;
 
 pub use crate::core::CoreContext;
+pub use crate::core::RequestAbandoned;
 pub use crate::logging::LoggingContainer;
 pub use crate::logging::SamplingKey;
 pub use crate::perf_counters::PerfCounterType;
@@ -19,6 +20,8 @@ pub use crate::session::SessionClass;
 pub use crate::session::SessionContainer;
 pub use crate::session::SessionContainerBuilder;
 
+pub mod log_keys;
+
 mod core;
 mod logging;
 mod perf_counters;
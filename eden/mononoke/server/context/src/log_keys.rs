@@ -0,0 +1,26 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Keys for the key-value pairs of log records.
+//!
+//! Identifiers that log records are about should be logged as key-value
+//! pairs with these keys, rather than formatted into the message, so that
+//! the records about e.g. a repo or a commit can be found by key regardless
+//! of which binary logged them.
+
+/// The name of the repo the record is about.
+pub const REPO: &str = "repo";
+
+/// The id of the bonsai changeset the record is about.
+pub const CS_ID: &str = "cs_id";
+
+/// The wireproto command being processed.
+pub const COMMAND: &str = "command";
+
+/// The correlator the client logs its own telemetry with, which joins
+/// server-side records to the client-side ones.
+pub const CORRELATOR: &str = "correlator";
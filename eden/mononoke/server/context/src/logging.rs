@@ -15,6 +15,7 @@ use scuba_ext::MononokeScubaSampleBuilder;
 use slog::o;
 use slog::Logger;
 
+use crate::log_keys;
 use crate::perf_counters::PerfCounters;
 use crate::perf_counters_stack::PerfCountersStack;
 
@@ -87,7 +88,7 @@ impl LoggingContainer {
 
     pub fn clone_with_repo_name(&self, repo_name: &str) -> Self {
         Self {
-            logger: self.logger.new(o!("repo" => repo_name.to_string())),
+            logger: self.logger.new(o!(log_keys::REPO => repo_name.to_string())),
             scuba: self.scuba.clone(),
             perf_counters: self.perf_counters.clone(),
             sampling_key: self.sampling_key.clone(),
@@ -66,6 +66,8 @@ define_perf_counters! {
         BlobPutsMaxLatency,
         BlobPutsDeduplicated,
         BlobPutsTotalSize,
+        BoundedTraversalMaxWeight,
+        BoundedTraversalUnfolds,
         BytesSent,
         CachelibHits,
         CachelibMisses,
@@ -169,6 +171,7 @@ impl PerfCounterType {
             | BlobPutsShardAccessWait
             | BlobPutsDeduplicated
             | BlobPutsTotalSize
+            | BoundedTraversalUnfolds
             | BytesSent
             | CachelibHits
             | CachelibMisses
@@ -220,6 +223,7 @@ impl PerfCounterType {
             | BlobGetsNotFoundMaxLatency
             | BlobPresenceChecksMaxLatency
             | BlobPutsMaxLatency
+            | BoundedTraversalMaxWeight
             | GetpackMaxFileSize => PerfCounterTypeUpdateFunc::Max,
         }
     }
@@ -14,6 +14,7 @@ use governor::Quota;
 use governor::RateLimiter;
 use metadata::Metadata;
 use rate_limiting::BoxRateLimiter;
+use tokio_util::sync::CancellationToken;
 
 use super::SessionClass;
 use super::SessionContainer;
@@ -43,6 +44,7 @@ impl SessionContainerBuilder {
                 blobstore_write_limiter: None,
                 blobstore_read_limiter: None,
                 readonly: false,
+                cancellation: CancellationToken::new(),
             },
             session_class: SessionClass::UserWaiting,
         }
@@ -92,4 +94,10 @@ impl SessionContainerBuilder {
         self.inner.readonly = readonly;
         self
     }
+
+    /// Use a token that is cancelled when the session's client disconnects.
+    pub fn cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.inner.cancellation = cancellation;
+        self
+    }
 }
@@ -19,6 +19,7 @@ use rate_limiting::RateLimiter;
 use scribe_ext::Scribe;
 use scuba_ext::MononokeScubaSampleBuilder;
 use slog::Logger;
+use tokio_util::sync::CancellationToken;
 
 pub use self::builder::SessionContainerBuilder;
 use crate::core::CoreContext;
@@ -57,6 +58,9 @@ struct SessionContainerInner {
     // Whether this session is supposed to be readonly, this will cause the right
     // AuthContext to constructed.
     readonly: bool,
+    // Cancelled when nobody is waiting for the work of this session any
+    // more, e.g. because the client has disconnected.
+    cancellation: CancellationToken,
 }
 
 impl SessionContainer {
@@ -144,6 +148,21 @@ impl SessionContainer {
         self.inner.blobstore_write_limiter.as_ref()
     }
 
+    /// Cancel the work of this session.  Work that is run until abandoned
+    /// will stop, rather than finish for nobody.
+    pub fn cancel(&self) {
+        self.inner.cancellation.cancel()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancellation.is_cancelled()
+    }
+
+    /// Wait until this session is cancelled.
+    pub async fn cancelled(&self) {
+        self.inner.cancellation.cancelled().await
+    }
+
     pub fn session_class(&self) -> SessionClass {
         self.session_class
     }
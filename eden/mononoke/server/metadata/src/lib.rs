@@ -9,6 +9,7 @@
 
 use std::net::IpAddr;
 use std::time::Duration;
+use std::time::Instant;
 
 use anyhow::anyhow;
 use anyhow::Error;
@@ -21,6 +22,18 @@ use session_id::SessionId;
 use tokio::time::timeout;
 use trust_dns_resolver::TokioAsyncResolver;
 
+/// Header in which clients can say how many milliseconds they will wait for
+/// a response, so that work on their behalf can be abandoned once they have
+/// given up.
+pub const CLIENT_DEADLINE_HEADER: &str = "x-client-deadline-ms";
+
+/// Parse the value of the client deadline header into the deadline it sets,
+/// measured from now.
+pub fn parse_client_deadline(value: &str) -> Option<Instant> {
+    let millis = value.trim().parse().ok()?;
+    Instant::now().checked_add(Duration::from_millis(millis))
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct Metadata {
     session_id: SessionId,
@@ -34,6 +47,7 @@ pub struct Metadata {
     revproxy_region: Option<String>,
     raw_encoded_cats: Option<String>,
     client_info: Option<ClientInfo>,
+    client_version: Option<String>,
 }
 
 impl Metadata {
@@ -74,6 +88,7 @@ impl Metadata {
             revproxy_region: None,
             raw_encoded_cats: None,
             client_info: None,
+            client_version: None,
         }
     }
 
@@ -111,6 +126,11 @@ impl Metadata {
         self
     }
 
+    pub fn add_client_version(&mut self, client_version: String) -> &mut Self {
+        self.client_version = Some(client_version);
+        self
+    }
+
     pub fn add_original_identities(&mut self, identities: MononokeIdentitySet) -> &mut Self {
         self.original_identities = Some(identities);
         self
@@ -196,4 +216,25 @@ impl Metadata {
     pub fn clientinfo_tw_task(&self) -> Option<&str> {
         self.client_info.as_ref().and_then(|ci| ci.fb.tw_task())
     }
+
+    pub fn client_version(&self) -> Option<&str> {
+        self.client_version.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_client_deadline() {
+        let before = Instant::now();
+        let deadline = parse_client_deadline(" 1500 ").unwrap();
+        assert!(deadline >= before + Duration::from_millis(1500));
+        assert!(deadline <= Instant::now() + Duration::from_millis(1500));
+
+        assert!(parse_client_deadline("").is_none());
+        assert!(parse_client_deadline("-1").is_none());
+        assert!(parse_client_deadline("1.5s").is_none());
+    }
 }
@@ -0,0 +1,330 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Administrative mutations exposed over the HTTP control API.
+//!
+//! Every mutation is authorized against the `admin_allowlist` in the common
+//! config, and every attempt (whether permitted or not) is recorded in the
+//! repository's admin audit log.  Permitted mutations are recorded before
+//! they are performed, so that even those that are interrupted part way
+//! through are recorded, and they aren't performed if that fails.
+
+use std::str::FromStr;
+
+use admin_audit_log::format_actor;
+use admin_audit_log::AdminAuditEntry;
+use admin_audit_log::AdminAuditLogRef;
+use admin_audit_log::AdminAuditOutcome;
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use bookmarks::BookmarkKey;
+use bookmarks::BookmarkUpdateReason;
+use bookmarks::BookmarksRef;
+use context::CoreContext;
+use derived_data_utils::derived_data_utils;
+use fbinit::FacebookInit;
+use mononoke_api::Repo;
+use mononoke_types::ChangesetId;
+use mononoke_types::Timestamp;
+use repo_derived_data::RepoDerivedDataArc;
+use repo_lock::ReadOnlyInfo;
+use repo_lock::RepoLockRef;
+use repo_lock::RepoLockState;
+use serde::Deserialize;
+use serde::Serialize;
+use slog::warn;
+
+/// A request to perform an administrative mutation on a repository.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AdminMutationRequest {
+    /// Name of the repository to mutate.
+    pub repo: String,
+
+    #[serde(flatten)]
+    pub mutation: AdminMutation,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum AdminMutation {
+    /// Move (or create) a bookmark to point at a changeset.  If
+    /// `old_target` is provided, the move only succeeds if the bookmark
+    /// currently points there.
+    MoveBookmark {
+        bookmark: String,
+        target: String,
+        old_target: Option<String>,
+    },
+    /// Lock the repository for pushes.
+    LockRepo { reason: String },
+    /// Unlock the repository.
+    UnlockRepo,
+    /// Derive a derived data type for a changeset.
+    Derive {
+        derived_data_type: String,
+        changeset: String,
+    },
+}
+
+impl AdminMutation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MoveBookmark { .. } => "move_bookmark",
+            Self::LockRepo { .. } => "lock_repo",
+            Self::UnlockRepo => "unlock_repo",
+            Self::Derive { .. } => "derive",
+        }
+    }
+}
+
+/// Response returned to the caller of an admin mutation.
+#[derive(Debug, Serialize)]
+pub struct AdminMutationResponse {
+    pub operation: &'static str,
+    pub result: String,
+}
+
+async fn perform(
+    fb: FacebookInit,
+    ctx: &CoreContext,
+    repo: &Repo,
+    mutation: &AdminMutation,
+) -> Result<String> {
+    match mutation {
+        AdminMutation::MoveBookmark {
+            bookmark,
+            target,
+            old_target,
+        } => {
+            let bookmark = BookmarkKey::new(bookmark)?;
+            let target = ChangesetId::from_str(target).context("Invalid target changeset")?;
+            let old_target = match old_target {
+                Some(old_target) => Some(
+                    ChangesetId::from_str(old_target).context("Invalid old target changeset")?,
+                ),
+                None => repo
+                    .blob_repo()
+                    .bookmarks()
+                    .get(ctx.clone(), &bookmark)
+                    .await
+                    .with_context(|| format!("Failed to resolve bookmark '{}'", bookmark))?,
+            };
+            let mut transaction = repo.blob_repo().bookmarks().create_transaction(ctx.clone());
+            match old_target {
+                Some(old_target) => transaction.update(
+                    &bookmark,
+                    target,
+                    old_target,
+                    BookmarkUpdateReason::ManualMove,
+                )?,
+                None => transaction.create(&bookmark, target, BookmarkUpdateReason::ManualMove)?,
+            }
+            if !transaction.commit().await? {
+                return Err(anyhow!(
+                    "Bookmark '{}' was moved concurrently, transaction failed",
+                    bookmark
+                ));
+            }
+            Ok(format!("{} now points to {}", bookmark, target))
+        }
+        AdminMutation::LockRepo { reason } => {
+            let changed = repo
+                .repo_lock()
+                .set_repo_lock(RepoLockState::Locked(ReadOnlyInfo::new(reason.clone())))
+                .await?;
+            Ok(if changed {
+                "locked".to_string()
+            } else {
+                "already locked".to_string()
+            })
+        }
+        AdminMutation::UnlockRepo => {
+            let changed = repo
+                .repo_lock()
+                .set_repo_lock(RepoLockState::Unlocked)
+                .await?;
+            Ok(if changed {
+                "unlocked".to_string()
+            } else {
+                "already unlocked".to_string()
+            })
+        }
+        AdminMutation::Derive {
+            derived_data_type,
+            changeset,
+        } => {
+            let cs_id = ChangesetId::from_str(changeset).context("Invalid changeset")?;
+            let utils = derived_data_utils(fb, repo.blob_repo(), derived_data_type)?;
+            utils
+                .derive(ctx.clone(), repo.blob_repo().repo_derived_data_arc(), cs_id)
+                .await
+        }
+    }
+}
+
+/// Authorize, perform and audit an administrative mutation.
+///
+/// Returns `Ok(None)` if the caller is not permitted to perform admin
+/// mutations.  Denied attempts are still recorded in the audit log.
+pub async fn authorize_and_perform(
+    fb: FacebookInit,
+    ctx: &CoreContext,
+    repo: &Repo,
+    is_admin: bool,
+    request: &AdminMutationRequest,
+) -> Result<Option<AdminMutationResponse>> {
+    let operation = request.mutation.name();
+    let arguments =
+        serde_json::to_string(&request.mutation).context("Failed to serialize arguments")?;
+    let record = |outcome| {
+        repo.admin_audit_log().record(
+            ctx,
+            AdminAuditEntry {
+                timestamp: Timestamp::now(),
+                actor: format_actor(ctx.metadata().identities()),
+                operation: operation.to_string(),
+                arguments: arguments.clone(),
+                outcome,
+            },
+        )
+    };
+
+    if !is_admin {
+        record(AdminAuditOutcome::Denied)
+            .await
+            .context("Failed to record admin audit log entry")?;
+        return Ok(None);
+    }
+
+    record(AdminAuditOutcome::Attempted)
+        .await
+        .context("Failed to record admin audit log entry, so the mutation was not performed")?;
+    let result = perform(fb, ctx, repo, &request.mutation).await;
+    let outcome = match &result {
+        Ok(_) => AdminAuditOutcome::Success,
+        Err(e) => AdminAuditOutcome::Failure(format!("{:#}", e)),
+    };
+    // The attempt is already recorded, so the mutation's result is returned
+    // even if its outcome can't be.
+    if let Err(e) = record(outcome).await {
+        warn!(
+            ctx.logger(),
+            "Failed to record outcome of admin mutation {}: {:#}", operation, e
+        );
+    }
+
+    Ok(Some(AdminMutationResponse {
+        operation,
+        result: result?,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use blobrepo::BlobRepo;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use test_repo_factory::TestRepoFactory;
+
+    use super::*;
+
+    async fn test_repo(fb: FacebookInit, ctx: &CoreContext) -> Result<Repo> {
+        let blob_repo: BlobRepo = TestRepoFactory::new(fb)?.build()?;
+        Repo::new_test(ctx.clone(), blob_repo).await
+    }
+
+    fn move_main(target: ChangesetId) -> AdminMutationRequest {
+        AdminMutationRequest {
+            repo: "repo".to_string(),
+            mutation: AdminMutation::MoveBookmark {
+                bookmark: "main".to_string(),
+                target: target.to_string(),
+                old_target: None,
+            },
+        }
+    }
+
+    async fn main_target(ctx: &CoreContext, repo: &Repo) -> Result<Option<ChangesetId>> {
+        repo.blob_repo()
+            .bookmarks()
+            .get(ctx.clone(), &BookmarkKey::new("main")?)
+            .await
+    }
+
+    async fn outcomes(ctx: &CoreContext, repo: &Repo) -> Result<Vec<(String, AdminAuditOutcome)>> {
+        Ok(repo
+            .admin_audit_log()
+            .list_recent(ctx, 10)
+            .await?
+            .into_iter()
+            .map(|entry| (entry.operation, entry.outcome))
+            .collect())
+    }
+
+    #[fbinit::test]
+    async fn test_successful_mutation(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = test_repo(fb, &ctx).await?;
+
+        let response = authorize_and_perform(fb, &ctx, &repo, true, &move_main(ONES_CSID)).await?;
+        assert_eq!(
+            response.map(|response| response.result),
+            Some(format!("main now points to {}", ONES_CSID))
+        );
+        assert_eq!(main_target(&ctx, &repo).await?, Some(ONES_CSID));
+        assert_eq!(
+            outcomes(&ctx, &repo).await?,
+            vec![
+                ("move_bookmark".to_string(), AdminAuditOutcome::Success),
+                ("move_bookmark".to_string(), AdminAuditOutcome::Attempted),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_denied_mutation(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = test_repo(fb, &ctx).await?;
+
+        let response = authorize_and_perform(fb, &ctx, &repo, false, &move_main(ONES_CSID)).await?;
+        assert!(response.is_none());
+        assert_eq!(main_target(&ctx, &repo).await?, None);
+        assert_eq!(
+            outcomes(&ctx, &repo).await?,
+            vec![("move_bookmark".to_string(), AdminAuditOutcome::Denied)]
+        );
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_failed_mutation(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = test_repo(fb, &ctx).await?;
+        // Test repos are always unlocked, so locking them fails.
+        let request = AdminMutationRequest {
+            repo: "repo".to_string(),
+            mutation: AdminMutation::LockRepo {
+                reason: "test".to_string(),
+            },
+        };
+
+        let result = authorize_and_perform(fb, &ctx, &repo, true, &request).await;
+        assert!(result.is_err());
+        let outcomes = outcomes(&ctx, &repo).await?;
+        assert_eq!(outcomes.len(), 2);
+        assert!(matches!(outcomes[0].1, AdminAuditOutcome::Failure(_)));
+        assert_eq!(
+            outcomes[1],
+            ("lock_repo".to_string(), AdminAuditOutcome::Attempted)
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use metaconfig_types::ClientVersion;
+use metaconfig_types::ClientVersionConfig;
+
+/// How a client's version compares to the minimum versions of a repo.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ClientVersionStatus {
+    /// The client is new enough, or its version is unknown and unknown
+    /// versions are allowed.
+    Supported,
+    /// The client is allowed, but should be warned to upgrade.
+    Deprecated,
+    /// The client must be rejected.
+    Rejected,
+}
+
+impl ClientVersionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClientVersionStatus::Supported => "supported",
+            ClientVersionStatus::Deprecated => "deprecated",
+            ClientVersionStatus::Rejected => "rejected",
+        }
+    }
+}
+
+/// Check the version reported by a client against the config of a repo.
+/// Versions that can't be parsed are handled like missing ones.
+///
+/// Clients whose version is unknown are at most warned, never rejected:
+/// clients connecting over SSH don't report their version.
+pub fn check_client_version(
+    config: &ClientVersionConfig,
+    client_version: Option<&str>,
+) -> ClientVersionStatus {
+    let version = match client_version.and_then(|v| v.parse::<ClientVersion>().ok()) {
+        Some(version) => version,
+        None => {
+            let has_threshold = config.reject_below.is_some() || config.warn_below.is_some();
+            return if config.treat_unknown_as_outdated && has_threshold {
+                ClientVersionStatus::Deprecated
+            } else {
+                ClientVersionStatus::Supported
+            };
+        }
+    };
+    let is_older = |threshold: &Option<ClientVersion>| match threshold {
+        Some(threshold) => &version < threshold,
+        None => false,
+    };
+    if is_older(&config.reject_below) {
+        ClientVersionStatus::Rejected
+    } else if is_older(&config.warn_below) {
+        ClientVersionStatus::Deprecated
+    } else {
+        ClientVersionStatus::Supported
+    }
+}
+
+/// The message shown to a client whose version is rejected or deprecated.
+pub fn client_version_message(
+    config: &ClientVersionConfig,
+    client_version: Option<&str>,
+    status: ClientVersionStatus,
+) -> String {
+    let version = client_version.unwrap_or("unknown");
+    let mut msg = match (status, &config.reject_below, &config.warn_below) {
+        (ClientVersionStatus::Rejected, Some(min), _) => format!(
+            "Client version {} is no longer supported, the minimum version is {}.",
+            version, min
+        ),
+        (_, _, Some(min)) => format!(
+            "Client version {} is deprecated, please upgrade to {} or newer.",
+            version, min
+        ),
+        _ => format!("Client version {} is not supported.", version),
+    };
+    if let Some(message) = &config.message {
+        msg.push(' ');
+        msg.push_str(message);
+    }
+    msg
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(treat_unknown_as_outdated: bool) -> ClientVersionConfig {
+        ClientVersionConfig {
+            reject_below: Some("4.4.0".parse().unwrap()),
+            warn_below: Some("4.6.0".parse().unwrap()),
+            treat_unknown_as_outdated,
+            message: Some("Run `hg upgrade`.".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_check_client_version() {
+        let config = config(false);
+        let check = |version| check_client_version(&config, version);
+        assert_eq!(check(Some("4.6.0")), ClientVersionStatus::Supported);
+        assert_eq!(check(Some("4.10.1")), ClientVersionStatus::Supported);
+        assert_eq!(check(Some("4.5.9")), ClientVersionStatus::Deprecated);
+        assert_eq!(
+            check(Some("4.3.20230523-092610")),
+            ClientVersionStatus::Rejected
+        );
+        assert_eq!(check(None), ClientVersionStatus::Supported);
+        assert_eq!(check(Some("unknown")), ClientVersionStatus::Supported);
+
+        let default = ClientVersionConfig::default();
+        assert_eq!(
+            check_client_version(&default, Some("0.1")),
+            ClientVersionStatus::Supported
+        );
+    }
+
+    #[test]
+    fn test_check_unknown_client_version() {
+        // Clients that don't report their version, such as those connecting
+        // over SSH, are warned but not rejected.
+        let config = config(true);
+        assert_eq!(
+            check_client_version(&config, None),
+            ClientVersionStatus::Deprecated
+        );
+        assert_eq!(
+            check_client_version(&config, Some("unknown")),
+            ClientVersionStatus::Deprecated
+        );
+        assert_eq!(
+            check_client_version(&config, Some("4.3.0")),
+            ClientVersionStatus::Rejected
+        );
+
+        // Without any thresholds there is nothing to be outdated against.
+        let config = ClientVersionConfig {
+            treat_unknown_as_outdated: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            check_client_version(&config, None),
+            ClientVersionStatus::Supported
+        );
+    }
+
+    #[test]
+    fn test_client_version_message() {
+        let config = config(true);
+        assert_eq!(
+            client_version_message(&config, Some("4.3.0"), ClientVersionStatus::Rejected),
+            "Client version 4.3.0 is no longer supported, the minimum version is 4.4.0. \
+             Run `hg upgrade`."
+        );
+        assert_eq!(
+            client_version_message(&config, None, ClientVersionStatus::Deprecated),
+            "Client version unknown is deprecated, please upgrade to 4.6.0 or newer. \
+             Run `hg upgrade`."
+        );
+    }
+}
@@ -48,7 +48,6 @@ use mononoke_api::Mononoke;
 use openssl::ssl::Ssl;
 use openssl::ssl::SslAcceptor;
 use permission_checker::AclProvider;
-use permission_checker::MononokeIdentity;
 use permission_checker::MononokeIdentitySet;
 use qps::Qps;
 use quiet_stream::QuietShutdownStream;
@@ -74,6 +73,7 @@ use tokio::task::JoinHandle;
 use tokio_openssl::SslStream;
 use tokio_util::codec::FramedRead;
 use tokio_util::codec::FramedWrite;
+use tokio_util::sync::CancellationToken;
 
 use crate::errors::ErrorKind;
 use crate::http_service::MononokeHttpService;
@@ -96,6 +96,11 @@ lazy_static! {
     static ref OPEN_CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
 }
 
+/// Number of connections being served.
+pub fn open_connections() -> usize {
+    OPEN_CONNECTIONS.load(Ordering::Relaxed)
+}
+
 pub async fn wait_for_connections_closed(logger: &Logger) {
     loop {
         let conns = OPEN_CONNECTIONS.load(Ordering::Relaxed);
@@ -275,7 +280,10 @@ async fn handle_connection(conn: PendingConnection, sock: TcpStream) -> Result<(
         .context("Failed to perform tls handshake")?;
 
     let identities = match ssl_socket.ssl().peer_certificate() {
-        Some(cert) => MononokeIdentity::try_from_x509(&cert),
+        Some(cert) => conn
+            .acceptor
+            .security_checker
+            .identities_from_certificate(&cert),
         None => Err(ErrorKind::ConnectionNoClientCertificate.into()),
     }?;
 
@@ -335,6 +343,7 @@ where
         logger,
         keep_alive,
         join_handle,
+        disconnected,
     } = ChannelConn::setup(framed, conn.clone(), metadata.clone());
 
     if metadata.client_debug() {
@@ -364,6 +373,7 @@ where
         conn.pending.acceptor.scribe.clone(),
         conn.pending.acceptor.qps.clone(),
         conn.pending.acceptor.readonly,
+        disconnected,
     )
     .await
     .context("Failed to execute request_handler");
@@ -404,6 +414,9 @@ pub struct ChannelConn {
     logger: Logger,
     keep_alive: AbortHandle,
     join_handle: JoinHandle<Result<(), io::Error>>,
+    /// Cancelled when the client disconnects, as responses can no longer be
+    /// forwarded to it.
+    disconnected: CancellationToken,
 }
 
 impl ChannelConn {
@@ -426,6 +439,8 @@ impl ChannelConn {
             }
         }));
 
+        let disconnected = CancellationToken::new();
+
         let (stdout, stderr, keep_alive, join_handle) = {
             let (otx, orx) = mpsc::channel(1);
             let (etx, erx) = mpsc::unbounded();
@@ -442,6 +457,7 @@ impl ChannelConn {
             let krx = krx.map(|v| SshMsg::new(IoStream::Stderr, v));
 
             // Glue them together
+            let client_gone = disconnected.clone();
             let fwd = async move {
                 let wr = WireprotoSink::new(wr);
 
@@ -456,6 +472,9 @@ impl ChannelConn {
                     .await;
 
                 if let Err(e) = res.as_ref() {
+                    // The client is gone, so nothing more can be sent to it.
+                    client_gone.cancel();
+
                     let projected_wr = wr.as_mut().project();
                     let data = projected_wr.data;
 
@@ -524,6 +543,7 @@ impl ChannelConn {
             logger,
             keep_alive,
             join_handle,
+            disconnected,
         }
     }
 }
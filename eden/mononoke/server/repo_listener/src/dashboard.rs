@@ -0,0 +1,444 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Snapshots of the state of the server and its repos, served as JSON over
+//! the HTTP control API so that dashboards can scrape them.
+//!
+//! Each part of a repo's snapshot is taken independently, so a failure to
+//! take one part (e.g. because the bookmarks database is unreachable) is
+//! reported in the snapshot rather than failing the whole of it.
+
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use blobstore::Blobstore;
+use bookmarks::BookmarkCategory;
+use bookmarks::BookmarkKind;
+use bookmarks::BookmarkPagination;
+use bookmarks::BookmarkPrefix;
+use bookmarks::BookmarkUpdateLogRef;
+use bookmarks::BookmarksRef;
+use bookmarks::Freshness;
+use context::CoreContext;
+use derived_data_utils::derived_data_utils;
+use fbinit::FacebookInit;
+use futures::future::try_join_all;
+use futures::join;
+use futures::stream;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
+use mononoke_api::Mononoke;
+use mononoke_api::Repo;
+use mononoke_types::ChangesetId;
+use mononoke_types::Timestamp;
+use repo_blobstore::RepoBlobstoreRef;
+use repo_client::in_flight_commands;
+use repo_derived_data::RepoDerivedDataArc;
+use repo_derived_data::RepoDerivedDataRef;
+use serde::Serialize;
+use sha1::Digest;
+use sha1::Sha1;
+
+use crate::connection_acceptor::open_connections;
+
+/// The most publishing bookmarks reported for each repo.
+const MAX_BOOKMARKS: u64 = 100;
+
+/// Key read to check that a repo's storage is reachable.  Nothing is stored
+/// there, so reading it only has to reach the storage.
+const STORAGE_PROBE_KEY: &str = "mononoke_dashboard_probe";
+const STORAGE_PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many bookmarks of a repo have their snapshots taken at once.
+const CONCURRENT_BOOKMARK_QUERIES: usize = 10;
+
+/// How many repos have their snapshots taken at once.
+const CONCURRENT_REPO_SNAPSHOTS: usize = 10;
+
+/// How long taking a snapshot of the server may take.  Repos whose
+/// snapshots aren't taken in time are reported as timed out.
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Serialize)]
+pub struct ServerSnapshot {
+    pub hostname: String,
+    pub open_connections: usize,
+    /// Whether the server is shutting down.
+    pub exiting: bool,
+    /// The repos, ordered by name.
+    pub repos: Vec<RepoSnapshot>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RepoSnapshot {
+    pub name: String,
+    /// Hash of the repo's config as loaded, which changes whenever a changed
+    /// config is loaded.
+    pub config_hash: String,
+    /// Number of wireproto commands to the repo being served.
+    pub in_flight_commands: usize,
+    /// The publishing bookmarks, ordered by name.
+    pub bookmarks: Vec<BookmarkSnapshot>,
+    /// How far derivation of each enabled derived data type lags behind the
+    /// publishing bookmarks.
+    pub derivation_lag: BTreeMap<String, DerivationLag>,
+    /// Unset if the storage wasn't checked in time.
+    pub storage: Option<StorageHealth>,
+    /// Why parts of the snapshot couldn't be taken, if they couldn't.
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BookmarkSnapshot {
+    pub name: String,
+    pub tip: ChangesetId,
+    /// The tip the warm bookmarks cache serves, which lags behind the
+    /// actual tip until it is warm.
+    pub cache_tip: Option<ChangesetId>,
+    /// When the bookmark last moved, in seconds since the epoch.
+    pub moved_at: Option<i64>,
+    /// Whether the cache serves a tip other than the actual one.
+    pub stale: bool,
+    /// How long the cache has served a tip other than the actual one.
+    pub staleness_secs: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DerivationLag {
+    /// Number of bookmarks whose tips aren't derived.
+    pub underived_bookmarks: usize,
+    /// How long the tip that has waited longest has waited to be derived.
+    pub lag_secs: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StorageHealth {
+    pub healthy: bool,
+    /// How long it took to read from the storage.
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+fn config_hash(repo: &Repo) -> String {
+    let config = canonical_debug(repo.config());
+    format!("{:x}", Sha1::digest(config.as_bytes()))
+}
+
+/// Debug output with the entries of each map and set sorted, so that it
+/// doesn't depend on the order that hash maps and sets iterate in, which
+/// differs between processes.
+fn canonical_debug(value: &impl Debug) -> String {
+    let debug = format!("{:?}", value);
+    canonicalize(&mut debug.chars().peekable(), None)
+}
+
+/// Copy Debug output up to the `close` delimiter, sorting the entries of the
+/// maps and sets in it.
+fn canonicalize(chars: &mut Peekable<Chars>, close: Option<char>) -> String {
+    let mut out = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            c if Some(c) == close => return out,
+            '"' | '\'' => {
+                out.push(c);
+                while let Some(q) = chars.next() {
+                    out.push(q);
+                    if q == '\\' {
+                        out.extend(chars.next());
+                    } else if q == c {
+                        break;
+                    }
+                }
+            }
+            '(' | '[' => {
+                let close = if c == '(' { ')' } else { ']' };
+                let inner = canonicalize(chars, Some(close));
+                out.push(c);
+                out.push_str(&inner);
+                out.push(close);
+            }
+            '{' => {
+                // Structs are formatted as `Name { .. }`, while maps and sets
+                // are formatted as a bare `{..}`.
+                let is_struct = out
+                    .trim_end()
+                    .ends_with(|c: char| c.is_alphanumeric() || c == '_');
+                let inner = canonicalize(chars, Some('}'));
+                out.push('{');
+                if is_struct {
+                    out.push_str(&inner);
+                } else {
+                    let mut entries = split_entries(&inner);
+                    entries.sort_unstable();
+                    out.push_str(&entries.join(", "));
+                }
+                out.push('}');
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Split the Debug output of the entries of a map or set into the entries.
+fn split_entries(inner: &str) -> Vec<&str> {
+    let mut entries = Vec::new();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                ',' if depth == 0 => {
+                    entries.push(inner[start..i].trim());
+                    start = i + 1;
+                }
+                _ => {}
+            },
+        }
+    }
+    if !inner[start..].trim().is_empty() {
+        entries.push(inner[start..].trim());
+    }
+    entries
+}
+
+async fn bookmark_snapshots(ctx: &CoreContext, repo: &Repo) -> Result<Vec<BookmarkSnapshot>> {
+    // Bookmarks are read from replicas, as dashboards scrape often and don't
+    // need the very latest state.
+    let bookmarks = repo
+        .blob_repo()
+        .bookmarks()
+        .list(
+            ctx.clone(),
+            Freshness::MaybeStale,
+            &BookmarkPrefix::empty(),
+            BookmarkCategory::ALL,
+            BookmarkKind::ALL_PUBLISHING,
+            &BookmarkPagination::FromStart,
+            MAX_BOOKMARKS,
+        )
+        .try_collect::<Vec<_>>()
+        .await
+        .context("Failed to list bookmarks")?;
+    stream::iter(bookmarks.into_iter().map(|(bookmark, tip)| async move {
+        let cache_tip = repo
+            .warm_bookmarks_cache()
+            .get(ctx, &bookmark.key)
+            .await
+            .with_context(|| format!("Failed to get {} from the cache", bookmark.key))?;
+        let moved_at = repo
+            .blob_repo()
+            .bookmark_update_log()
+            .list_bookmark_log_entries(
+                ctx.clone(),
+                bookmark.key.clone(),
+                1,
+                None,
+                Freshness::MaybeStale,
+            )
+            .try_next()
+            .await
+            .with_context(|| format!("Failed to get when {} last moved", bookmark.key))?
+            .map(|(_id, _tip, _reason, timestamp)| timestamp);
+        let stale = cache_tip != Some(tip);
+        anyhow::Ok(BookmarkSnapshot {
+            name: bookmark.key.to_string(),
+            tip,
+            cache_tip,
+            moved_at: moved_at.map(|moved_at| moved_at.timestamp_seconds()),
+            stale,
+            staleness_secs: moved_at
+                .filter(|_| stale)
+                .map(|moved_at| moved_at.since_seconds()),
+        })
+    }))
+    .buffered(CONCURRENT_BOOKMARK_QUERIES)
+    .try_collect()
+    .await
+}
+
+async fn derivation_lag(
+    fb: FacebookInit,
+    ctx: &CoreContext,
+    repo: &Repo,
+    bookmarks: &[BookmarkSnapshot],
+) -> Result<BTreeMap<String, DerivationLag>> {
+    let tips = bookmarks
+        .iter()
+        .map(|bookmark| bookmark.tip)
+        .collect::<HashSet<_>>();
+    let now = Timestamp::now().timestamp_seconds();
+    let derived_data = repo.blob_repo().repo_derived_data();
+    let lags = try_join_all(derived_data.active_config().types.iter().map(
+        |derived_data_type| {
+            let tips = tips.iter().copied().collect();
+            async move {
+                let utils = derived_data_utils(fb, repo.blob_repo(), derived_data_type)?;
+                let underived = utils
+                    .pending(ctx.clone(), repo.blob_repo().repo_derived_data_arc(), tips)
+                    .await
+                    .with_context(|| format!("Failed to check {} derivation", derived_data_type))?
+                    .into_iter()
+                    .collect::<HashSet<_>>();
+                let underived_bookmarks = bookmarks
+                    .iter()
+                    .filter(|bookmark| underived.contains(&bookmark.tip))
+                    .collect::<Vec<_>>();
+                let lag_secs = underived_bookmarks
+                    .iter()
+                    .filter_map(|bookmark| bookmark.moved_at)
+                    .map(|moved_at| now - moved_at)
+                    .max()
+                    .unwrap_or(0);
+                let lag = DerivationLag {
+                    underived_bookmarks: underived_bookmarks.len(),
+                    lag_secs,
+                };
+                anyhow::Ok((derived_data_type.clone(), lag))
+            }
+        },
+    ))
+    .await?;
+    Ok(lags.into_iter().collect())
+}
+
+async fn storage_health(ctx: &CoreContext, repo: &Repo) -> StorageHealth {
+    let started = Instant::now();
+    let blobstore = repo.blob_repo().repo_blobstore();
+    let result = tokio::time::timeout(
+        STORAGE_PROBE_TIMEOUT,
+        blobstore.is_present(ctx, STORAGE_PROBE_KEY),
+    )
+    .await
+    .map_err(|_| anyhow!("Timed out after {:?}", STORAGE_PROBE_TIMEOUT))
+    .and_then(|result| result);
+    StorageHealth {
+        healthy: result.is_ok(),
+        latency_ms: started.elapsed().as_millis() as u64,
+        error: result.err().map(|e| format!("{:#}", e)),
+    }
+}
+
+async fn repo_snapshot(fb: FacebookInit, ctx: &CoreContext, repo: &Repo) -> RepoSnapshot {
+    let mut errors = Vec::new();
+    let (bookmarks, storage) = join!(bookmark_snapshots(ctx, repo), storage_health(ctx, repo));
+    let bookmarks = bookmarks.unwrap_or_else(|e| {
+        errors.push(format!("{:#}", e));
+        Vec::new()
+    });
+    let derivation_lag = derivation_lag(fb, ctx, repo, &bookmarks)
+        .await
+        .unwrap_or_else(|e| {
+            errors.push(format!("{:#}", e));
+            BTreeMap::new()
+        });
+    RepoSnapshot {
+        name: repo.name().to_string(),
+        config_hash: config_hash(repo),
+        in_flight_commands: in_flight_commands(repo.name()),
+        bookmarks,
+        derivation_lag,
+        storage: Some(storage),
+        errors,
+    }
+}
+
+/// Snapshot of a repo whose snapshot wasn't taken in time.
+fn timed_out_repo_snapshot(repo: &Repo) -> RepoSnapshot {
+    RepoSnapshot {
+        name: repo.name().to_string(),
+        config_hash: config_hash(repo),
+        in_flight_commands: in_flight_commands(repo.name()),
+        bookmarks: Vec::new(),
+        derivation_lag: BTreeMap::new(),
+        storage: None,
+        errors: vec![format!("Timed out after {:?}", SNAPSHOT_TIMEOUT)],
+    }
+}
+
+/// Take a snapshot of the state of the server and of each of its repos.
+pub async fn snapshot(
+    fb: FacebookInit,
+    ctx: &CoreContext,
+    mononoke: &Mononoke,
+    hostname: String,
+    exiting: bool,
+) -> ServerSnapshot {
+    let deadline = tokio::time::Instant::now() + SNAPSHOT_TIMEOUT;
+    let mut repos = stream::iter(mononoke.repos())
+        .map(|repo| async move {
+            tokio::time::timeout_at(deadline, repo_snapshot(fb, ctx, &repo))
+                .await
+                .unwrap_or_else(|_| timed_out_repo_snapshot(&repo))
+        })
+        .buffer_unordered(CONCURRENT_REPO_SNAPSHOTS)
+        .collect::<Vec<_>>()
+        .await;
+    repos.sort_by(|a, b| a.name.cmp(&b.name));
+    ServerSnapshot {
+        hostname,
+        open_connections: open_connections(),
+        exiting,
+        repos,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct Config {
+        name: String,
+        features: HashMap<String, Vec<u32>>,
+        identities: Option<HashSet<&'static str>>,
+    }
+
+    #[test]
+    fn test_canonical_debug() {
+        let config = |entries: &[(&str, Vec<u32>)]| Config {
+            name: "{a, b}".to_string(),
+            features: entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect(),
+            identities: Some(["user", "service", "group"].into_iter().collect()),
+        };
+        let first = config(&[("zeta", vec![2, 1]), ("alpha", vec![]), ("mu", vec![3])]);
+        let second = config(&[("mu", vec![3]), ("zeta", vec![2, 1]), ("alpha", vec![])]);
+
+        // Struct fields, lists and strings keep their order, while map and
+        // set entries are sorted.
+        let expected = concat!(
+            r#"Config { name: "{a, b}", "#,
+            r#"features: {"alpha": [], "mu": [3], "zeta": [2, 1]}, "#,
+            r#"identities: Some({"group", "service", "user"}) }"#,
+        );
+        assert_eq!(canonical_debug(&first), expected);
+        assert_eq!(canonical_debug(&second), expected);
+    }
+}
@@ -14,6 +14,8 @@ pub enum ErrorKind {
     ConnectionNoClientCertificate,
     #[error("Unauthorized access, permission denied")]
     AuthorizationFailed,
+    #[error("{0}")]
+    ClientVersionRejected(String),
     #[error("Large repo not found: {0}")]
     LargeRepoNotFound(RepositoryId),
 }
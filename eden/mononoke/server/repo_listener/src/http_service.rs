@@ -9,7 +9,9 @@ use std::io::Cursor;
 use std::marker::PhantomData;
 use std::str::FromStr;
 use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use std::task;
+use std::time::Duration;
 
 use anyhow::anyhow;
 use anyhow::Context;
@@ -20,6 +22,8 @@ use bookmarks::BookmarksRef;
 use clientinfo::ClientInfo;
 #[cfg(fbcode_build)]
 use clientinfo::CLIENT_INFO_HEADER;
+use context::CoreContext;
+use context::SessionContainer;
 use futures::future::BoxFuture;
 use futures::future::FutureExt;
 use gotham_ext::socket_data::TlsSocketData;
@@ -29,11 +33,16 @@ use http::Method;
 use http::Request;
 use http::Response;
 use http::Uri;
+use hyper::body::HttpBody;
 use hyper::service::Service;
 use hyper::Body;
 use metadata::Metadata;
 use percent_encoding::percent_decode;
+use permission_checker::MononokeIdentitySet;
 use qps::Qps;
+use request_profiler::ProfileStatus;
+use request_profiler::RequestClass;
+use scuba_ext::MononokeScubaSampleBuilder;
 use session_id::generate_session_id;
 use sha1::Digest;
 use sha1::Sha1;
@@ -46,14 +55,18 @@ use tokio::io::AsyncReadExt;
 use tunables::force_update_tunables;
 use tunables::tunables;
 
+use crate::admin_mutations::authorize_and_perform;
+use crate::admin_mutations::AdminMutationRequest;
 use crate::connection_acceptor;
 use crate::connection_acceptor::AcceptedConnection;
 use crate::connection_acceptor::Acceptor;
 use crate::connection_acceptor::FramedConn;
 use crate::connection_acceptor::MononokeStream;
+use crate::dashboard;
 
 const HEADER_CLIENT_COMPRESSION: &str = "x-client-compression";
 const HEADER_CLIENT_DEBUG: &str = "x-client-debug";
+const HEADER_CLIENT_VERSION: &str = "x-client-version";
 const HEADER_WEBSOCKET_KEY: &str = "sec-websocket-key";
 const HEADER_WEBSOCKET_ACCEPT: &str = "sec-websocket-accept";
 const HEADER_MONONOKE_ENCODING: &str = "x-mononoke-encoding";
@@ -63,6 +76,12 @@ const HEADER_REVPROXY_REGION: &str = "x-fb-revproxy-region";
 // See https://tools.ietf.org/html/rfc6455#section-1.3
 const WEBSOCKET_MAGIC_KEY: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
 
+const DEFAULT_PROFILE_MAX_DURATION_SECS: u64 = 60;
+
+/// The largest admin request body that is read.  Admin requests are small
+/// JSON objects, so anything larger isn't one.
+const MAX_ADMIN_REQUEST_SIZE: usize = 64 * 1024;
+
 #[derive(Error, Debug)]
 pub enum HttpError {
     #[error("Bad request")]
@@ -77,6 +96,9 @@ pub enum HttpError {
     #[error("Method not allowed")]
     MethodNotAllowed,
 
+    #[error("Payload too large")]
+    PayloadTooLarge,
+
     #[error("Internal server error")]
     InternalServerError(#[source] Error),
 }
@@ -92,6 +114,7 @@ impl HttpError {
             Self::Forbidden => http::StatusCode::FORBIDDEN,
             Self::NotFound => http::StatusCode::NOT_FOUND,
             Self::MethodNotAllowed => http::StatusCode::METHOD_NOT_ALLOWED,
+            Self::PayloadTooLarge => http::StatusCode::PAYLOAD_TOO_LARGE,
             Self::InternalServerError(..) => http::StatusCode::INTERNAL_SERVER_ERROR,
         };
 
@@ -100,6 +123,7 @@ impl HttpError {
             Self::Forbidden => Body::empty(),
             Self::NotFound => Body::empty(),
             Self::MethodNotAllowed => Body::empty(),
+            Self::PayloadTooLarge => Body::empty(),
             Self::InternalServerError(ref e) => Body::from(format!("{:#}", e)),
         };
 
@@ -107,6 +131,34 @@ impl HttpError {
     }
 }
 
+/// Read a request body, failing with `PayloadTooLarge` as soon as it is
+/// known to be larger than `limit`, without reading the rest of it.
+async fn read_limited_body(
+    headers: &HeaderMap,
+    mut body: Body,
+    limit: usize,
+) -> Result<Vec<u8>, HttpError> {
+    let content_length = headers
+        .get(http::header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    if content_length.map_or(false, |length| length > limit) {
+        return Err(HttpError::PayloadTooLarge);
+    }
+
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk
+            .context("Failed to read request body")
+            .map_err(HttpError::BadRequest)?;
+        if bytes.len() + chunk.len() > limit {
+            return Err(HttpError::PayloadTooLarge);
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
 pub struct MononokeHttpService<S> {
     pub conn: AcceptedConnection,
     sock: PhantomData<S>,
@@ -130,6 +182,33 @@ impl<S> Clone for MononokeHttpService<S> {
     }
 }
 
+/// The token of a bearer authorization header.  Other authorization schemes
+/// are left to the other ways of authenticating clients.
+fn bearer_token(headers: &HeaderMap<HeaderValue>) -> Option<&str> {
+    let value = headers.get(http::header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.trim().split_once(' ')?;
+    scheme.eq_ignore_ascii_case("bearer").then_some(token)
+}
+
+/// Identities of the client making a request: those it authenticated with
+/// when connecting, plus those from a bearer token in the request, if any.
+fn request_identities(
+    conn: &AcceptedConnection,
+    headers: &HeaderMap<HeaderValue>,
+) -> Result<MononokeIdentitySet> {
+    let mut identities = (*conn.identities).clone();
+    if let Some(token) = bearer_token(headers) {
+        let token_identities = conn
+            .pending
+            .acceptor
+            .security_checker
+            .identities_from_bearer_token(token.trim())
+            .context("Invalid bearer token")?;
+        identities.extend(token_identities);
+    }
+    Ok(identities)
+}
+
 fn bump_qps(headers: &HeaderMap, qps: Option<&Qps>) -> Result<()> {
     let qps = match qps {
         Some(qps) => qps,
@@ -190,6 +269,18 @@ where
             return crate::netspeedtest::handle(req.method, &req.headers, body).await;
         }
 
+        if req.uri.path() == "/control/admin" {
+            return self.handle_admin_request(req, body).await;
+        }
+
+        if req.uri.path() == "/control/dashboard" {
+            return self.handle_dashboard_request(req.method).await;
+        }
+
+        if let Some(class) = req.uri.path().strip_prefix("/control/profile/") {
+            return self.handle_profile_request(req.method, class, req.uri.query()).await;
+        }
+
         if let Some(path) = req.uri.path().strip_prefix("/control") {
             return self.handle_control_request(req.method, path).await;
         }
@@ -230,11 +321,19 @@ where
             .header(http::header::UPGRADE, "websocket")
             .header(HEADER_WEBSOCKET_ACCEPT, websocket_key);
 
-        let metadata = h2m::try_convert_headers_to_metadata(&self.conn, req.headers())
+        let mut metadata = h2m::try_convert_headers_to_metadata(&self.conn, req.headers())
             .await
             .context("Invalid metadata")
             .map_err(HttpError::BadRequest)?;
 
+        if let Some(version) = req.headers().get(HEADER_CLIENT_VERSION) {
+            let version = version
+                .to_str()
+                .context("Invalid client version")
+                .map_err(HttpError::BadRequest)?;
+            metadata.add_client_version(version.to_string());
+        }
+
         let zstd_level: i32 = tunables::tunables()
             .zstd_compression_level()
             .unwrap_or_default()
@@ -351,6 +450,144 @@ where
         Err(HttpError::NotFound)
     }
 
+    /// GET returns a JSON snapshot of the state of the server and its repos.
+    async fn handle_dashboard_request(&self, method: Method) -> Result<Response<Body>, HttpError> {
+        if method != Method::GET {
+            return Err(HttpError::MethodNotAllowed);
+        }
+
+        if !self.acceptor().enable_http_control_api {
+            return Err(HttpError::Forbidden);
+        }
+
+        let acceptor = self.acceptor();
+        let ctx = CoreContext::new_with_logger(acceptor.fb, self.logger().clone());
+        let snapshot = dashboard::snapshot(
+            acceptor.fb,
+            &ctx,
+            &acceptor.mononoke,
+            acceptor.server_hostname.clone(),
+            acceptor.will_exit.load(Ordering::Relaxed),
+        )
+        .await;
+
+        let body = serde_json::to_vec(&snapshot).map_err(HttpError::internal)?;
+        Response::builder()
+            .status(http::StatusCode::OK)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(body.into())
+            .map_err(HttpError::internal)
+    }
+
+    /// POST arms a CPU profile of the next request of a class, and GET
+    /// fetches it in pprof format once that request has finished.
+    async fn handle_profile_request(
+        &self,
+        method: Method,
+        class: &str,
+        query: Option<&str>,
+    ) -> Result<Response<Body>, HttpError> {
+        if !self.acceptor().enable_http_control_api {
+            return Err(HttpError::Forbidden);
+        }
+
+        let class = RequestClass::from_str(class)
+            .with_context(|| format!("Unknown request class: {}", class))
+            .map_err(HttpError::BadRequest)?;
+
+        if method == Method::POST {
+            let max_duration_secs = query
+                .and_then(|query| {
+                    query
+                        .split('&')
+                        .find_map(|param| param.strip_prefix("max_duration_secs="))
+                })
+                .map(u64::from_str)
+                .transpose()
+                .context("Invalid max_duration_secs")
+                .map_err(HttpError::BadRequest)?
+                .unwrap_or(DEFAULT_PROFILE_MAX_DURATION_SECS);
+            request_profiler::arm(class, Duration::from_secs(max_duration_secs));
+            return Response::builder()
+                .status(http::StatusCode::OK)
+                .body(Body::empty())
+                .map_err(HttpError::internal);
+        }
+
+        if method != Method::GET {
+            return Err(HttpError::MethodNotAllowed);
+        }
+
+        let profile = match request_profiler::take_profile(class) {
+            ProfileStatus::Idle => return Err(HttpError::NotFound),
+            ProfileStatus::Armed | ProfileStatus::Running => {
+                return Response::builder()
+                    .status(http::StatusCode::ACCEPTED)
+                    .body(Body::empty())
+                    .map_err(HttpError::internal);
+            }
+            ProfileStatus::Finished(profile) => profile,
+        };
+        let profile = profile
+            .map_err(|err| anyhow!("Profiling failed: {}", err))
+            .map_err(HttpError::internal)?;
+        Response::builder()
+            .status(http::StatusCode::OK)
+            .header(http::header::CONTENT_TYPE, "application/octet-stream")
+            .body(profile.into())
+            .map_err(HttpError::internal)
+    }
+
+    async fn handle_admin_request(
+        &self,
+        req: http::request::Parts,
+        body: Body,
+    ) -> Result<Response<Body>, HttpError> {
+        if req.method != Method::POST {
+            return Err(HttpError::MethodNotAllowed);
+        }
+
+        let body = read_limited_body(&req.headers, body, MAX_ADMIN_REQUEST_SIZE).await?;
+        let request: AdminMutationRequest = serde_json::from_slice(&body)
+            .context("Invalid admin request")
+            .map_err(HttpError::BadRequest)?;
+
+        let repo = self
+            .acceptor()
+            .mononoke
+            .raw_repo(&request.repo)
+            .ok_or(HttpError::NotFound)?;
+
+        let metadata = h2m::try_convert_headers_to_metadata(&self.conn, &req.headers)
+            .await
+            .context("Invalid metadata")
+            .map_err(HttpError::BadRequest)?;
+        let is_admin = self
+            .acceptor()
+            .security_checker
+            .check_if_admin(metadata.identities())
+            .await;
+        let session = SessionContainer::builder(self.acceptor().fb)
+            .metadata(Arc::new(metadata))
+            .build();
+        let ctx = session.new_context(
+            self.logger().clone(),
+            MononokeScubaSampleBuilder::with_discard(),
+        );
+
+        let response = authorize_and_perform(self.acceptor().fb, &ctx, &repo, is_admin, &request)
+            .await
+            .map_err(HttpError::internal)?
+            .ok_or(HttpError::Forbidden)?;
+
+        let body = serde_json::to_vec(&response).map_err(HttpError::internal)?;
+        Response::builder()
+            .status(http::StatusCode::OK)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(body.into())
+            .map_err(HttpError::internal)
+    }
+
     async fn handle_eden_api_request(
         &self,
         mut req: http::request::Parts,
@@ -383,7 +620,9 @@ where
         let tls_socket_data = if self.conn.is_trusted {
             TlsSocketData::trusted_proxy((*self.conn.identities).clone())
         } else {
-            TlsSocketData::authenticated_identities((*self.conn.identities).clone())
+            let identities =
+                request_identities(&self.conn, &req.headers).map_err(HttpError::BadRequest)?;
+            TlsSocketData::authenticated_identities(identities)
         };
 
         let req = Request::from_parts(req, body);
@@ -495,7 +734,7 @@ mod h2m {
 
         Ok(Metadata::new(
             Some(&generate_session_id().to_string()),
-            (*conn.identities).clone(),
+            request_identities(conn, headers)?,
             debug,
             Some(conn.pending.addr.ip()),
         )
@@ -596,7 +835,7 @@ mod h2m {
         }
 
         let mut identities = cats_identities.unwrap_or_default();
-        identities.extend(conn.identities.iter().cloned());
+        identities.extend(request_identities(conn, headers)?);
 
         // Generic fallback
         Ok(Metadata::new(
@@ -9,7 +9,10 @@
 #![feature(never_type)]
 #![recursion_limit = "256"]
 
+mod admin_mutations;
+mod client_version;
 mod connection_acceptor;
+mod dashboard;
 mod errors;
 mod http_service;
 mod netspeedtest;
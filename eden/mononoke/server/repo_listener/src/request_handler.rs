@@ -15,6 +15,7 @@ use anyhow::Result;
 use bytes::Bytes;
 use connection_security_checker::ConnectionSecurityChecker;
 use context::LoggingContainer;
+use context::RequestAbandoned;
 use context::SessionContainer;
 use context::SessionId;
 use failure_ext::SlogKVError;
@@ -29,6 +30,7 @@ use hgproto::HgProtoHandler;
 use maplit::hashmap;
 use maplit::hashset;
 use mononoke_api::Mononoke;
+use mononoke_api::MononokeErrorCode;
 use qps::Qps;
 use rate_limiting::Metric;
 use rate_limiting::RateLimitEnvironment;
@@ -37,6 +39,7 @@ use repo_permission_checker::RepoPermissionCheckerRef;
 use scribe_ext::Scribe;
 use slog::error;
 use slog::o;
+use slog::warn;
 use slog::Drain;
 use slog::Level;
 use slog::Logger;
@@ -46,18 +49,28 @@ use sshrelay::SenderBytesWrite;
 use sshrelay::Stdio;
 use stats::prelude::*;
 use time_ext::DurationExt;
+use tokio_util::sync::CancellationToken;
 
+use crate::client_version::check_client_version;
+use crate::client_version::client_version_message;
+use crate::client_version::ClientVersionStatus;
 use crate::errors::ErrorKind;
 use crate::repo_handlers::repo_handler;
 use crate::repo_handlers::RepoHandler;
 
+/// Commands that are finished even if the client disconnects, as stopping
+/// them part way could leave the repo with a partially applied push.
+const WRITE_COMMANDS: &[&str] = &["unbundle", "unbundlereplay"];
+
 define_stats! {
     prefix = "mononoke.request_handler";
     wireproto_ms:
         histogram(500, 0, 100_000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
     request_success: timeseries(Rate, Sum),
     request_failure: timeseries(Rate, Sum),
+    request_cancelled: timeseries(Rate, Sum),
     request_outcome_permille: timeseries(Average),
+    client_version: dynamic_timeseries("client_version.{}.{}", (reponame: String, status: String); Rate, Sum),
 }
 
 pub async fn request_handler(
@@ -70,6 +83,7 @@ pub async fn request_handler(
     scribe: Scribe,
     qps: Option<Arc<Qps>>,
     readonly: bool,
+    disconnected: CancellationToken,
 ) -> Result<()> {
     let Stdio {
         stdin,
@@ -106,7 +120,7 @@ pub async fn request_handler(
     let conn_log = create_conn_logger(stderr.clone(), Some(logger), Some(session_id));
 
     scuba = scuba.with_seq("seq");
-    scuba.add("repo", reponame);
+    scuba.add("repo", reponame.clone());
     scuba.add_metadata(&metadata);
     scuba.sample_for_identities(metadata.identities());
 
@@ -133,6 +147,34 @@ pub async fn request_handler(
         return Err(err);
     }
 
+    let client_versions = &repo.config().client_versions;
+    let client_version_status = check_client_version(client_versions, metadata.client_version());
+    STATS::client_version.add_value(1, (reponame, client_version_status.as_str().to_string()));
+    scuba.add("client_version_status", client_version_status.as_str());
+    match client_version_status {
+        ClientVersionStatus::Supported => {}
+        ClientVersionStatus::Deprecated => {
+            let msg = client_version_message(
+                client_versions,
+                metadata.client_version(),
+                client_version_status,
+            );
+            warn!(conn_log, "{}", msg; "remote" => "true");
+        }
+        ClientVersionStatus::Rejected => {
+            let msg = client_version_message(
+                client_versions,
+                metadata.client_version(),
+                client_version_status,
+            );
+            let err: Error = ErrorKind::ClientVersionRejected(msg).into();
+            scuba.log_with_msg("Client version rejected", format!("{}", err));
+            error!(conn_log, "{}", err; "remote" => "true");
+
+            return Err(err);
+        }
+    }
+
     // Info per wireproto command within this session
     let wireproto_calls = Arc::new(Mutex::new(Vec::new()));
 
@@ -144,6 +186,7 @@ pub async fn request_handler(
         .rate_limiter(rate_limiter);
 
     let session = session_builder.build();
+    let disconnect_session = session.clone();
 
     let mut logging = LoggingContainer::new(fb, conn_log.clone(), scuba.clone());
     logging.with_scribe(scribe);
@@ -178,8 +221,25 @@ pub async fn request_handler(
         .forward(stdout)
         .map(|_| ());
 
+    // Once the client has disconnected, stop serving it rather than finishing
+    // the command in flight for nobody, unless it writes to the repo.
+    let endres = async {
+        let endres = endres.compat();
+        futures::pin_mut!(endres);
+        tokio::select! {
+            res = &mut endres => return res,
+            _ = disconnected.cancelled() => {}
+        }
+        if is_write_in_flight(&wireproto_calls) {
+            return endres.await;
+        }
+        disconnect_session.cancel();
+        STATS::request_cancelled.add_value(1);
+        Err(RequestAbandoned::ClientDisconnected.into())
+    };
+
     // If we got an error at this point, then catch it and print a message
-    let (stats, result) = endres.compat().timed().await;
+    let (stats, result) = endres.timed().await;
 
     let wireproto_calls = {
         let mut wireproto_calls = wireproto_calls.lock().expect("lock poisoned");
@@ -197,6 +257,7 @@ pub async fn request_handler(
     // Populate stats no matter what to avoid dead detectors firing.
     STATS::request_success.add_value(0);
     STATS::request_failure.add_value(0);
+    STATS::request_cancelled.add_value(0);
 
     // Log request level perf counters
     request_perf_counters.insert_perf_counters(&mut scuba);
@@ -208,20 +269,25 @@ pub async fn request_handler(
             scuba.log_with_msg("Request finished - Success", None)
         }
         Err(err) => {
-            if err.is::<mpsc::SendError<Bytes>>() {
+            if err.is::<mpsc::SendError<Bytes>>() || err.is::<RequestAbandoned>() {
                 STATS::request_outcome_permille.add_value(0);
                 scuba.log_with_msg("Request finished - Client Disconnected", format!("{}", err));
             } else {
+                let code = MononokeErrorCode::classify(err);
+                code.report();
                 STATS::request_failure.add_value(1);
                 STATS::request_outcome_permille.add_value(0);
+                scuba.add("error_code", code.as_str());
                 scuba.log_with_msg("Request finished - Failure", format!("{:#?}", err));
             }
         }
     }
 
     if let Err(err) = result {
+        let code = MononokeErrorCode::classify(&err);
         error!(&conn_log, "Command failed";
             SlogKVError(err),
+            "error_code" => code.as_str(),
             "remote" => "true"
         );
     }
@@ -229,6 +295,15 @@ pub async fn request_handler(
     Ok(())
 }
 
+/// Whether the session has received a command that writes to the repo.
+fn is_write_in_flight(wireproto_calls: &Mutex<Vec<String>>) -> bool {
+    wireproto_calls
+        .lock()
+        .expect("lock poisoned")
+        .iter()
+        .any(|call| WRITE_COMMANDS.contains(&call.as_str()))
+}
+
 pub fn create_conn_logger(
     stderr: mpsc::UnboundedSender<Bytes>,
     server_logger: Option<Logger>,
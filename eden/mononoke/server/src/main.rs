@@ -11,6 +11,7 @@ use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Context;
 use anyhow::Result;
@@ -19,6 +20,7 @@ use cache_warmup::cache_warmup;
 use clap::Parser;
 use cloned::cloned;
 use cmdlib_logging::ScribeLoggingArgs;
+use context::log_keys;
 use environment::WarmBookmarksCacheDerivedData;
 use executor_lib::args::ShardedExecutorArgs;
 use executor_lib::RepoShardedProcess;
@@ -49,6 +51,7 @@ use slog::o;
 use slog::Logger;
 
 const SM_CLEANUP_TIMEOUT_SECS: u64 = 120;
+const SCRIBE_FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Mononoke Server
 #[derive(Parser)]
@@ -117,15 +120,9 @@ impl MononokeServerProcess {
             cache_warmup(&ctx, &blob_repo, cache_warmup_params)
                 .await
                 .with_context(|| format!("Error while warming up cache for repo {}", repo_name))?;
-            info!(
-                &logger,
-                "Completed repo {} setup in Mononoke service", repo_name
-            );
+            info!(&logger, "Completed repo setup in Mononoke service");
         } else {
-            info!(
-                &logger,
-                "Repo {} is already setup in Mononoke service", repo_name
-            );
+            info!(&logger, "Repo is already setup in Mononoke service");
         }
         Ok(())
     }
@@ -135,7 +132,7 @@ impl MononokeServerProcess {
 impl RepoShardedProcess for MononokeServerProcess {
     async fn setup(&self, repo_name: &str) -> anyhow::Result<Arc<dyn RepoShardedProcessExecutor>> {
         let logger = self.repos_mgr.repo_logger(repo_name);
-        info!(&logger, "Setting up repo {} in Mononoke service", repo_name);
+        info!(&logger, "Setting up repo in Mononoke service");
         self.add_repo(repo_name, &logger).await.with_context(|| {
             format!(
                 "Failure in setting up repo {} in Mononoke service",
@@ -176,13 +173,14 @@ impl MononokeServerProcessExecutor {
             self.repos_mgr.remove_repo(repo_name);
             info!(
                 self.repos_mgr.logger(),
-                "No longer serving repo {} in Mononoke service.", repo_name,
+                "No longer serving repo in Mononoke service.";
+                log_keys::REPO => repo_name
             );
         } else {
             info!(
                 self.repos_mgr.logger(),
-                "Continuing serving repo {} in Mononoke service because it's shallow-sharded.",
-                repo_name,
+                "Continuing serving repo in Mononoke service because it's shallow-sharded.";
+                log_keys::REPO => repo_name
             );
         }
         Ok(())
@@ -194,7 +192,8 @@ impl RepoShardedProcessExecutor for MononokeServerProcessExecutor {
     async fn execute(&self) -> anyhow::Result<()> {
         info!(
             self.repos_mgr.logger(),
-            "Serving repo {} in Mononoke service", &self.repo_name,
+            "Serving repo in Mononoke service";
+            log_keys::REPO => &self.repo_name
         );
         Ok(())
     }
@@ -261,6 +260,7 @@ fn main(fb: FacebookInit) -> Result<()> {
     info!(root_log, "Creating repo listeners");
 
     let scribe = args.scribe_logging_args.get_scribe(fb)?;
+    let scribe_to_flush = scribe.clone();
     let host_port = args.listening_host_port;
     let bound_addr_file = args.bound_address_file;
 
@@ -296,7 +296,7 @@ fn main(fb: FacebookInit) -> Result<()> {
                     let root_log = root_log.clone();
                     let cache_warmup_params = repo.config().cache_warmup.clone();
                     async move {
-                        let logger = root_log.new(o!("repo" => repo_name.clone()));
+                        let logger = root_log.new(o!(log_keys::REPO => repo_name.clone()));
                         let ctx = CoreContext::new_with_logger(fb, logger);
                         cache_warmup(&ctx, &blob_repo, cache_warmup_params)
                             .await
@@ -360,6 +360,9 @@ fn main(fb: FacebookInit) -> Result<()> {
                 error!(root_log, "could not send termination signal: {:?}", err);
             }
             repo_listener::wait_for_connections_closed(&root_log).await;
+            if let Err(err) = scribe_to_flush.flush(SCRIBE_FLUSH_TIMEOUT) {
+                error!(root_log, "could not flush scribe logging: {:?}", err);
+            }
         },
         args.shutdown_timeout_args.shutdown_timeout,
     )
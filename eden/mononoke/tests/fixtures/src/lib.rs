@@ -120,6 +120,7 @@ async fn create_bonsai_changeset_from_test_data(
         file_changes,
         is_snapshot: false,
         git_annotated_tag: None,
+        extras: None,
     }
     .freeze()
     .unwrap();
@@ -1622,6 +1623,7 @@ pub fn create_bonsai_changeset(parents: Vec<ChangesetId>) -> BonsaiChangeset {
         file_changes: Default::default(),
         is_snapshot: false,
         git_annotated_tag: None,
+        extras: None,
     }
     .freeze()
     .unwrap()
@@ -1644,6 +1646,7 @@ pub fn create_bonsai_changeset_with_author(
         file_changes: Default::default(),
         is_snapshot: false,
         git_annotated_tag: None,
+        extras: None,
     }
     .freeze()
     .unwrap()
@@ -1666,6 +1669,7 @@ pub fn create_bonsai_changeset_with_files(
         file_changes: file_changes.into(),
         is_snapshot: false,
         git_annotated_tag: None,
+        extras: None,
     }
     .freeze()
     .unwrap()
@@ -1119,6 +1119,7 @@ if [[ -n "${LFS_THRESHOLD:-}" ]]; then
 threshold=$LFS_THRESHOLD
 rollout_percentage=${LFS_ROLLOUT_PERCENTAGE:-100}
 generate_lfs_blob_in_hg_sync_job=${LFS_BLOB_HG_SYNC_JOB:-true}
+shared_storage=${LFS_SHARED_STORAGE:-false}
 CONFIG
 fi
 
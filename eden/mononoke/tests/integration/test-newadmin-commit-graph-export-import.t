@@ -0,0 +1,86 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This software may be used and distributed according to the terms of the
+# GNU General Public License found in the LICENSE file in the root
+# directory of this source tree.
+
+  $ . "${TEST_FIXTURES}/library.sh"
+
+setup configuration
+  $ setup_common_config
+  $ merge_tunables <<EOF
+  > {
+  >   "killswitches_by_repo": {
+  >     "repo": {
+  >       "enable_writing_to_new_commit_graph": true
+  >     }
+  >   }
+  > }
+  > EOF
+
+  $ testtool_drawdag -R repo << 'EOF'
+  > J G
+  > | |
+  > I F
+  > | |
+  > H E
+  > |/|
+  > C D
+  > |/
+  > B
+  > |
+  > A
+  > EOF
+  A=aa53d24251ff3f54b1b2c29ae02826701b2abeb0079f1bb13b8434b54cd87675
+  B=f8c75e41a0c4d29281df765f39de47bca1dcadfdc55ada4ccc2f6df567201658
+  C=e32a1e342cdb1e38e88466b4c1a01ae9f410024017aa21dc0a1c5da6b3963bf2
+  D=5a25c0a76794bbcc5180da0949a652750101597f0fbade488e611d5c0917e7be
+  E=f0c81a03319da010415f712831abe8469ba3c30b93b0b07af175302b8c15f0e6
+  F=48779d8d497815015031dc3f3e9888abc8cf8273184ebd9ca8a395e24d501c90
+  G=9711852ec4f4b42937dd5b760c7b3f84345bf48c74b7ef3ca7118d1d7928744d
+  H=64642fc5a09343c1699e2ecaa5fa1c31fdb19f1e125428cd745327911c0b1d83
+  I=03ffabc887d3d9a81be514037b1dfa3020466af9145bafbc33a8880fd8808c01
+  J=55e5dbaa7f26e0cfa1c2ee95479e2af088bf81caae4c2356d6eb8dfa6c114284
+
+export the commits between C, D and G as a csv edge list
+  $ mononoke_newadmin commit-graph -R repo export --heads $G --common $C,$D --format csv
+  child,parent
+  9711852ec4f4b42937dd5b760c7b3f84345bf48c74b7ef3ca7118d1d7928744d,48779d8d497815015031dc3f3e9888abc8cf8273184ebd9ca8a395e24d501c90
+  48779d8d497815015031dc3f3e9888abc8cf8273184ebd9ca8a395e24d501c90,f0c81a03319da010415f712831abe8469ba3c30b93b0b07af175302b8c15f0e6
+  f0c81a03319da010415f712831abe8469ba3c30b93b0b07af175302b8c15f0e6,e32a1e342cdb1e38e88466b4c1a01ae9f410024017aa21dc0a1c5da6b3963bf2
+  f0c81a03319da010415f712831abe8469ba3c30b93b0b07af175302b8c15f0e6,5a25c0a76794bbcc5180da0949a652750101597f0fbade488e611d5c0917e7be
+
+and as a dot graph
+  $ mononoke_newadmin commit-graph -R repo export --heads $B --format dot
+  digraph commit_graph {
+    "f8c75e41a0c4d29281df765f39de47bca1dcadfdc55ada4ccc2f6df567201658";
+    "f8c75e41a0c4d29281df765f39de47bca1dcadfdc55ada4ccc2f6df567201658" -> "aa53d24251ff3f54b1b2c29ae02826701b2abeb0079f1bb13b8434b54cd87675";
+    "aa53d24251ff3f54b1b2c29ae02826701b2abeb0079f1bb13b8434b54cd87675";
+  }
+
+export the whole graph to a file
+(header, one line per edge, and one line for the root A)
+  $ mononoke_newadmin commit-graph -R repo export --heads $J,$G --output-file "$TESTTMP/edges.csv"
+  $ wc -l < "$TESTTMP/edges.csv"
+  12
+
+import a small edge list with arbitrary node names
+  $ cat > "$TESTTMP/shape.csv" << 'EOF'
+  > child,parent
+  > root,
+  > left,root
+  > right,root
+  > merge,left
+  > merge,right
+  > EOF
+  $ mononoke_newadmin commit-graph -R repo import --input-file "$TESTTMP/shape.csv" > "$TESTTMP/imported"
+  $ cut -d' ' -f1 "$TESTTMP/imported" | sort
+  left
+  merge
+  right
+  root
+
+the imported merge has all the imported commits as ancestors
+  $ MERGE=$(grep '^merge ' "$TESTTMP/imported" | cut -d' ' -f2)
+  $ mononoke_newadmin commit-graph -R repo ancestors-difference --heads $MERGE | wc -l
+  4
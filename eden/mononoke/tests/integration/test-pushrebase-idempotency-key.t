@@ -0,0 +1,74 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This software may be used and distributed according to the terms of the
+# GNU General Public License found in the LICENSE file in the root
+# directory of this source tree.
+
+  $ . "${TEST_FIXTURES}/library.sh"
+
+  $ BLOB_TYPE="blob_sqlite" default_setup
+  hg repo
+  o  C [draft;rev=2;26805aba1e60]
+  │
+  o  B [draft;rev=1;112478962961]
+  │
+  o  A [draft;rev=0;426bada5c675]
+  $
+  blobimporting
+  starting Mononoke
+  cloning repo in hg client 'repo2'
+
+Push a commit with an idempotency key
+  $ hg up -q "min(all())"
+  $ echo 1 > 1 && hg add 1 && hg ci -m 1
+  $ ORIGINAL=$(hg log -r . -T '{node}')
+  $ hgmn push -r . --to master_bookmark --pushvars IDEMPOTENCY_KEY=key1
+  pushing rev * to destination mononoke://$LOCALIP:$LOCAL_PORT/repo bookmark master_bookmark (glob)
+  searching for changes
+  adding changesets
+  adding manifests
+  adding file changes
+  updating bookmark master_bookmark
+
+Retry the push, as a client that lost the connection before getting the
+response would: it gets the response to the original push, and the bookmark
+isn't moved again
+  $ hgmn push --hidden -r $ORIGINAL --to master_bookmark --pushvars IDEMPOTENCY_KEY=key1
+  pushing rev * to destination mononoke://$LOCALIP:$LOCAL_PORT/repo bookmark master_bookmark (glob)
+  searching for changes
+  adding changesets
+  adding manifests
+  adding file changes
+  updating bookmark master_bookmark
+  $ hgmn pull -q
+  $ log -r "master_bookmark~1::master_bookmark"
+  o  1 [public;rev=*;*] default/master_bookmark (glob)
+  │
+  ~
+
+Retrying the push without the key pushes it again, which conflicts with the
+original push
+  $ hgmn push --hidden -r $ORIGINAL --to master_bookmark 2>&1 | grep "pushrebase failed"
+  remote:     pushrebase failed Conflicts([PushrebaseConflict { left: MPath("1"), right: MPath("1") }])
+  remote:     pushrebase failed Conflicts([PushrebaseConflict { left: MPath("1"), right: MPath("1") }])
+  remote:     "pushrebase failed Conflicts([PushrebaseConflict { left: MPath(\"1\"), right: MPath(\"1\") }])"
+
+A different push can't reuse the key
+  $ hg up -q "min(all())"
+  $ echo 2 > 2 && hg add 2 && hg ci -m 2
+  $ hgmn push -r . --to master_bookmark --pushvars IDEMPOTENCY_KEY=key1
+  pushing rev * to destination mononoke://$LOCALIP:$LOCAL_PORT/repo bookmark master_bookmark (glob)
+  searching for changes
+  remote: Command failed
+  remote:   Error:
+  remote:     Idempotency key key1 was already used for a different push
+  remote: 
+  remote:   Root cause:
+  remote:     Idempotency key key1 was already used for a different push
+  remote: 
+  remote:   Debug context:
+  remote:     Error(
+  remote:         "Idempotency key key1 was already used for a different push",
+  remote:     )
+  abort: unexpected EOL, expected netstring digit
+  [255]
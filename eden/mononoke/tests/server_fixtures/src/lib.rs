@@ -0,0 +1,184 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! In-process Mononoke server fixtures for black-box integration tests.
+//!
+//! A `TestServer` serves a set of repos backed by in-memory storage, each
+//! preloaded with one of the canned repo fixtures.  Tests talk to the repos
+//! through the same entry points the servers use: `RepoContext` (the API
+//! behind the source control service) and `RepoClient` (the wireproto
+//! command handler), so feature crates can be tested end to end without
+//! shell scripts.
+
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use blobrepo::BlobRepo;
+use context::CoreContext;
+use context::LoggingContainer;
+use fbinit::FacebookInit;
+use fixtures::BranchEven;
+use fixtures::Linear;
+use fixtures::MergeEven;
+use fixtures::TestRepoFixture;
+use mononoke_api::Mononoke;
+use mononoke_api::RepoContext;
+use mononoke_types::RepositoryId;
+use repo_client::RepoClient;
+use scuba_ext::MononokeScubaSampleBuilder;
+use test_repo_factory::TestRepoFactory;
+
+/// Canned contents for a repo served by a `TestServer`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RepoFixture {
+    /// A repo with no commits.
+    Empty,
+    /// The `Linear` fixture: a single line of history.
+    Linear,
+    /// The `MergeEven` fixture: two branches of equal length merged together.
+    Merged,
+    /// The `BranchEven` fixture: two unmerged branches of equal length.
+    Branched,
+}
+
+impl RepoFixture {
+    async fn build(self, fb: FacebookInit, id: RepositoryId, name: &str) -> Result<BlobRepo> {
+        let repo = match self {
+            RepoFixture::Empty => TestRepoFactory::new(fb)?
+                .with_id(id)
+                .with_name(name)
+                .build()?,
+            RepoFixture::Linear => Linear::getrepo_with_id(fb, id).await,
+            RepoFixture::Merged => MergeEven::getrepo_with_id(fb, id).await,
+            RepoFixture::Branched => BranchEven::getrepo_with_id(fb, id).await,
+        };
+        Ok(repo)
+    }
+}
+
+pub struct TestServerBuilder {
+    fb: FacebookInit,
+    repos: Vec<(String, RepoFixture)>,
+}
+
+impl TestServerBuilder {
+    /// Serve a repo with the given name and contents.  Repos are given ids
+    /// in the order they are added, starting from 0.
+    pub fn with_repo(mut self, name: impl Into<String>, fixture: RepoFixture) -> Self {
+        self.repos.push((name.into(), fixture));
+        self
+    }
+
+    pub async fn build(self) -> Result<TestServer> {
+        let ctx = CoreContext::test_mock(self.fb);
+        let mut repos = Vec::with_capacity(self.repos.len());
+        for (index, (name, fixture)) in self.repos.into_iter().enumerate() {
+            let repo = fixture
+                .build(self.fb, RepositoryId::new(index as i32), &name)
+                .await?;
+            repos.push((name, repo));
+        }
+        let mononoke = Arc::new(Mononoke::new_test(ctx.clone(), repos).await?);
+        Ok(TestServer { ctx, mononoke })
+    }
+}
+
+/// An in-process Mononoke server backed by in-memory storage.
+pub struct TestServer {
+    ctx: CoreContext,
+    mononoke: Arc<Mononoke>,
+}
+
+impl TestServer {
+    pub fn builder(fb: FacebookInit) -> TestServerBuilder {
+        TestServerBuilder {
+            fb,
+            repos: Vec::new(),
+        }
+    }
+
+    /// The context requests to this server are made with.
+    pub fn ctx(&self) -> &CoreContext {
+        &self.ctx
+    }
+
+    pub fn mononoke(&self) -> &Arc<Mononoke> {
+        &self.mononoke
+    }
+
+    /// The context for a repo, as used by source control service methods.
+    /// Access control checks are bypassed.
+    pub async fn repo(&self, name: &str) -> Result<RepoContext> {
+        let repo = self
+            .mononoke
+            .raw_repo(name)
+            .ok_or_else(|| anyhow!("repo '{}' is not served by this server", name))?;
+        Ok(RepoContext::new_test(self.ctx.clone(), repo).await?)
+    }
+
+    /// A wireproto command handler for a repo, equivalent to the one the
+    /// server creates for each client connection.
+    pub fn wireproto_client(&self, name: &str) -> Result<RepoClient> {
+        let repo = self
+            .mononoke
+            .raw_repo(name)
+            .ok_or_else(|| anyhow!("repo '{}' is not served by this server", name))?;
+        let logging = LoggingContainer::new(
+            self.ctx.fb,
+            self.ctx.logger().clone(),
+            MononokeScubaSampleBuilder::with_discard(),
+        );
+        Ok(RepoClient::new(
+            repo,
+            self.ctx.session().clone(),
+            logging,
+            None, // No PushRedirectorArgs
+            Default::default(),
+            None, // No backup repo source
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::compat::Future01CompatExt;
+    use hgproto::HgCommands;
+    use mononoke_api::BookmarkFreshness;
+    use mononoke_api::BookmarkKey;
+
+    use super::*;
+
+    #[fbinit::test]
+    async fn test_server_fixtures(fb: FacebookInit) -> Result<()> {
+        let server = TestServer::builder(fb)
+            .with_repo("linear", RepoFixture::Linear)
+            .with_repo("merged", RepoFixture::Merged)
+            .with_repo("empty", RepoFixture::Empty)
+            .build()
+            .await?;
+
+        let repo = server.repo("linear").await?;
+        let master = repo
+            .resolve_bookmark(&BookmarkKey::new("master")?, BookmarkFreshness::MostRecent)
+            .await?
+            .expect("master should exist");
+        let master_hg = master.hg_id().await?.expect("master should have an hg id");
+
+        let heads = server.wireproto_client("linear")?.heads().compat().await?;
+        assert!(heads.contains(&master_hg));
+
+        let empty = server.repo("empty").await?;
+        let empty_master = empty
+            .resolve_bookmark(&BookmarkKey::new("master")?, BookmarkFreshness::MostRecent)
+            .await?;
+        assert!(empty_master.is_none());
+
+        assert!(server.repo("missing").await.is_err());
+        Ok(())
+    }
+}
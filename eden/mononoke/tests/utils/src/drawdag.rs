@@ -10,8 +10,12 @@ use std::collections::BTreeSet;
 
 use anyhow::anyhow;
 use anyhow::Result;
+use bookmarks::BookmarkKey;
+use bookmarks::BookmarkUpdateReason;
+use bookmarks::BookmarksRef;
 use context::CoreContext;
 use mononoke_types::ChangesetId;
+use mononoke_types::DateTime;
 
 use crate::CreateCommitContext;
 use crate::Repo;
@@ -165,6 +169,290 @@ pub async fn create_from_dag<R: Repo>(
     create_from_dag_with_changes(ctx, repo, dag, BTreeMap::new()).await
 }
 
+/// An action declared in a comment of an ASCII DAG, which customizes the
+/// commits created from it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// `# default_files: false` stops each commit from adding a file named
+    /// after it.
+    DefaultFiles(bool),
+    /// `# message: A "message"` sets the message of commit `A`.
+    Message { name: String, message: String },
+    /// `# author: A alice` sets the author of commit `A`.
+    Author { name: String, author: String },
+    /// `# author_date: A 1000` sets the author date of commit `A`, in
+    /// seconds since the epoch.
+    AuthorDate { name: String, date: DateTime },
+    /// `# modify: A path/to/file "content"` adds or modifies a file in
+    /// commit `A`.
+    Modify {
+        name: String,
+        path: String,
+        content: Vec<u8>,
+    },
+    /// `# delete: A path/to/file` deletes a file in commit `A`.
+    Delete { name: String, path: String },
+    /// `# forget: A A` removes a file change, such as the default file,
+    /// from commit `A`.
+    Forget { name: String, path: String },
+    /// `# copy: B to/file "content" A from/file` adds a file to commit `B`,
+    /// copied from a file in its parent `A`.
+    Copy {
+        name: String,
+        path: String,
+        content: Vec<u8>,
+        parent: String,
+        parent_path: String,
+    },
+    /// `# bookmark: A main` sets bookmark `main` to commit `A` once the
+    /// commits are created.
+    Bookmark { name: String, bookmark: String },
+}
+
+impl Action {
+    /// The commit the action customizes, if it customizes one.
+    fn commit_name(&self) -> Option<&str> {
+        match self {
+            Action::DefaultFiles(_) => None,
+            Action::Message { name, .. }
+            | Action::Author { name, .. }
+            | Action::AuthorDate { name, .. }
+            | Action::Modify { name, .. }
+            | Action::Delete { name, .. }
+            | Action::Forget { name, .. }
+            | Action::Copy { name, .. }
+            | Action::Bookmark { name, .. } => Some(name),
+        }
+    }
+}
+
+/// Split the arguments of an action into words, which are separated by
+/// whitespace unless they are quoted.  Quoted words may contain `\"`, `\\`
+/// and `\n` escapes.
+fn split_args(args: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut chars = args.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let mut word = String::new();
+        match chars.peek() {
+            None => return Ok(words),
+            Some('"') => {
+                chars.next();
+                loop {
+                    match chars.next() {
+                        None => return Err(anyhow!("unterminated quote in {}", args)),
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => word.push('\n'),
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            _ => return Err(anyhow!("invalid escape in {}", args)),
+                        },
+                        Some(c) => word.push(c),
+                    }
+                }
+            }
+            Some(_) => {
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    word.push(c);
+                }
+            }
+        }
+        words.push(word);
+    }
+}
+
+fn parse_action(line: &str) -> Result<Action> {
+    let (kind, args) = line
+        .split_once(':')
+        .ok_or_else(|| anyhow!("expected `action: arguments`, got {}", line))?;
+    let mut args = split_args(args)?.into_iter();
+    let mut arg = |what: &str| {
+        args.next()
+            .ok_or_else(|| anyhow!("missing {} in {}", what, line))
+    };
+    let action = match kind.trim() {
+        "default_files" => Action::DefaultFiles(arg("true or false")?.parse()?),
+        "message" => Action::Message {
+            name: arg("commit")?,
+            message: arg("message")?,
+        },
+        "author" => Action::Author {
+            name: arg("commit")?,
+            author: arg("author")?,
+        },
+        "author_date" => Action::AuthorDate {
+            name: arg("commit")?,
+            date: DateTime::from_timestamp(arg("timestamp")?.parse()?, 0)?,
+        },
+        "modify" => Action::Modify {
+            name: arg("commit")?,
+            path: arg("path")?,
+            content: arg("content")?.into_bytes(),
+        },
+        "delete" => Action::Delete {
+            name: arg("commit")?,
+            path: arg("path")?,
+        },
+        "forget" => Action::Forget {
+            name: arg("commit")?,
+            path: arg("path")?,
+        },
+        "copy" => Action::Copy {
+            name: arg("commit")?,
+            path: arg("path")?,
+            content: arg("content")?.into_bytes(),
+            parent: arg("parent")?,
+            parent_path: arg("parent path")?,
+        },
+        "bookmark" => Action::Bookmark {
+            name: arg("commit")?,
+            bookmark: arg("bookmark")?,
+        },
+        kind => return Err(anyhow!("unknown action {}", kind)),
+    };
+    if let Some(extra) = args.next() {
+        return Err(anyhow!("unexpected argument {} in {}", extra, line));
+    }
+    Ok(action)
+}
+
+/// Separate the actions declared in the comments of an ASCII DAG from the
+/// DAG itself.  Comment lines are blanked rather than removed, so that the
+/// lines of the DAG stay where they were drawn.
+pub fn parse_actions(dag: &str) -> Result<(String, Vec<Action>)> {
+    let mut lines = Vec::new();
+    let mut actions = Vec::new();
+    for line in dag.lines() {
+        match line.trim_start().strip_prefix('#') {
+            Some(comment) => {
+                actions.push(parse_action(comment.trim())?);
+                lines.push("");
+            }
+            None => lines.push(line),
+        }
+    }
+    Ok((lines.join("\n"), actions))
+}
+
+/// Create commits from an ASCII DAG, customized by the actions declared in
+/// its comments.
+///
+/// Each commit has its name as its message and, unless `default_files` is
+/// set to false, adds a file named after it, as with `create_from_dag`.
+/// Actions then customize the commits, in the order they are declared, and
+/// set bookmarks once the commits have been created.  This lets the whole
+/// scenario a test needs be declared in one place.
+///
+/// Example:
+///
+/// ```ignore
+///     create_from_dag_with_actions(
+///         ctx,
+///         repo,
+///         r##"
+///             A-B-C
+///                \
+///                 D
+///             # modify: B dir/file "content\n"
+///             # copy: C dir/copy "content\n" B dir/file
+///             # delete: D A
+///             # message: D "remove A"
+///             # bookmark: C main
+///         "##,
+///     ).await?;
+/// ```
+pub async fn create_from_dag_with_actions<R: Repo + 'static>(
+    ctx: &CoreContext,
+    repo: &R,
+    dag: &str,
+) -> Result<BTreeMap<String, ChangesetId>> {
+    let (dag, actions) = parse_actions(dag)?;
+    let parents = drawdag::parse(&dag);
+
+    let mut default_files = true;
+    let mut commit_actions: BTreeMap<String, Vec<Action>> = BTreeMap::new();
+    let mut bookmarks = Vec::new();
+    for action in actions {
+        if let Some(name) = action.commit_name() {
+            if !parents.contains_key(name) {
+                return Err(anyhow!("graph does not contain {}", name));
+            }
+        }
+        match action {
+            Action::DefaultFiles(enabled) => default_files = enabled,
+            Action::Bookmark { name, bookmark } => bookmarks.push((name, bookmark)),
+            Action::Copy { ref name, ref parent, .. } if !parents[name].contains(parent) => {
+                return Err(anyhow!("{} is not a parent of {}", parent, name));
+            }
+            action => {
+                let name = action
+                    .commit_name()
+                    .expect("Other actions customize a commit")
+                    .to_string();
+                commit_actions.entry(name).or_default().push(action);
+            }
+        }
+    }
+
+    let changes = commit_actions
+        .into_iter()
+        .map(|(name, actions)| {
+            let change: Box<ChangeFn<R>> = Box::new(
+                move |mut c: CreateCommitContext<'_, R>,
+                      committed: &BTreeMap<String, ChangesetId>| {
+                    for action in actions {
+                        c = apply_action(c, action, committed);
+                    }
+                    c
+                },
+            );
+            (name, change)
+        })
+        .collect();
+    let (commits, _dag) =
+        extend_from_dag_with_changes(ctx, repo, &dag, changes, BTreeMap::new(), default_files)
+            .await?;
+
+    if !bookmarks.is_empty() {
+        let mut txn = repo.bookmarks().create_transaction(ctx.clone());
+        for (name, bookmark) in bookmarks {
+            let bookmark = BookmarkKey::new(bookmark)?;
+            txn.force_set(&bookmark, commits[&name], BookmarkUpdateReason::TestMove)?;
+        }
+        txn.commit().await?;
+    }
+
+    Ok(commits)
+}
+
+fn apply_action<'a, R: Repo>(
+    c: CreateCommitContext<'a, R>,
+    action: Action,
+    committed: &BTreeMap<String, ChangesetId>,
+) -> CreateCommitContext<'a, R> {
+    match action {
+        Action::Message { message, .. } => c.set_message(message),
+        Action::Author { author, .. } => c.set_author(author),
+        Action::AuthorDate { date, .. } => c.set_author_date(date),
+        Action::Modify { path, content, .. } => c.add_file(path.as_str(), content),
+        Action::Delete { path, .. } => c.delete_file(path.as_str()),
+        Action::Forget { path, .. } => c.forget_file(path.as_str()),
+        Action::Copy {
+            path,
+            content,
+            parent,
+            parent_path,
+            ..
+        } => c.add_file_with_copy_info(
+            path.as_str(),
+            content,
+            (committed[&parent], parent_path.as_str()),
+        ),
+        Action::DefaultFiles(_) | Action::Bookmark { .. } => c,
+    }
+}
+
 /// Macro to allow creation of `changes` for `create_from_dag_with_changes`.
 ///
 /// Example:
@@ -207,3 +495,115 @@ macro_rules! __drawdag_changes {
 
 // Export macro within this module.
 pub use __drawdag_changes as changes;
+
+#[cfg(test)]
+mod test {
+    use blobstore::Loadable;
+    use fbinit::FacebookInit;
+    use maplit::hashmap;
+    use mononoke_types::FileChange;
+    use mononoke_types::MPath;
+    use repo_blobstore::RepoBlobstoreRef;
+
+    use super::*;
+    use crate::list_working_copy_utf8;
+    use crate::BasicTestRepo;
+
+    #[test]
+    fn test_parse_actions() -> Result<()> {
+        let (dag, actions) = parse_actions(
+            r#"
+            A-B
+            # default_files: false
+            # modify: A "dir/file name" "line one\nline \"two\""
+            # copy: B dir/copy "content" A "dir/file name"
+            # bookmark: B main
+            "#,
+        )?;
+        assert_eq!(dag.lines().map(str::trim).collect::<String>(), "A-B");
+        assert_eq!(
+            actions,
+            vec![
+                Action::DefaultFiles(false),
+                Action::Modify {
+                    name: "A".to_string(),
+                    path: "dir/file name".to_string(),
+                    content: b"line one\nline \"two\"".to_vec(),
+                },
+                Action::Copy {
+                    name: "B".to_string(),
+                    path: "dir/copy".to_string(),
+                    content: b"content".to_vec(),
+                    parent: "A".to_string(),
+                    parent_path: "dir/file name".to_string(),
+                },
+                Action::Bookmark {
+                    name: "B".to_string(),
+                    bookmark: "main".to_string(),
+                },
+            ]
+        );
+
+        assert!(parse_actions("A\n# modify: A file").is_err());
+        assert!(parse_actions("A\n# rename: A file other").is_err());
+        assert!(parse_actions("A\n# message: A \"unterminated").is_err());
+        assert!(parse_actions("A\n# author_date: A yesterday").is_err());
+        assert!(parse_actions("A\n# author_date: A 99999999999999999").is_err());
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_create_from_dag_with_actions(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: BasicTestRepo = test_repo_factory::build_empty(fb)?;
+        let commits = create_from_dag_with_actions(
+            &ctx,
+            &repo,
+            r##"
+                A-B-C
+                # modify: B dir/file "content\n"
+                # copy: C dir/copy "content\n" B dir/file
+                # delete: C A
+                # message: C "copy file"
+                # author: C alice
+                # author_date: C 1000
+                # bookmark: C main
+            "##,
+        )
+        .await?;
+
+        let bonsai = commits["C"].load(&ctx, repo.repo_blobstore()).await?;
+        assert_eq!(bonsai.parents().collect::<Vec<_>>(), vec![commits["B"]]);
+        assert_eq!(bonsai.message(), "copy file");
+        assert_eq!(bonsai.author(), "alice");
+        assert_eq!(bonsai.author_date().timestamp_secs(), 1000);
+        assert_eq!(
+            bonsai
+                .file_changes_map()
+                .get(&MPath::new("dir/copy")?)
+                .and_then(FileChange::copy_from),
+            Some(&(MPath::new("dir/file")?, commits["B"]))
+        );
+        assert_eq!(
+            list_working_copy_utf8(&ctx, &repo, commits["C"]).await?,
+            hashmap! {
+                MPath::new("B")? => "B".to_string(),
+                MPath::new("C")? => "C".to_string(),
+                MPath::new("dir/file")? => "content\n".to_string(),
+                MPath::new("dir/copy")? => "content\n".to_string(),
+            }
+        );
+        assert_eq!(
+            repo.bookmarks().get(ctx.clone(), &BookmarkKey::new("main")?).await?,
+            Some(commits["C"])
+        );
+
+        // Actions must refer to commits in the graph.
+        assert!(
+            create_from_dag_with_actions(&ctx, &repo, "D\n# message: E other")
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+}
@@ -353,6 +353,7 @@ impl<'a, R: Repo> CreateCommitContext<'a, R> {
             file_changes: Default::default(),
             is_snapshot: false,
             git_annotated_tag: None,
+            extras: None,
         };
 
         for (path, file_change) in files {
@@ -722,6 +723,7 @@ pub async fn create_commit(
         file_changes: file_changes.into(),
         is_snapshot: false,
         git_annotated_tag: None,
+        extras: None,
     }
     .freeze()
     .unwrap();
@@ -751,6 +753,7 @@ pub async fn create_commit_with_date(
         file_changes: file_changes.into(),
         is_snapshot: false,
         git_annotated_tag: None,
+        extras: None,
     }
     .freeze()
     .unwrap();
@@ -6,19 +6,25 @@
  */
 
 mononoke_app::subcommands! {
+    mod backup;
     mod blobstore;
     mod blobstore_unlink;
     mod bookmarks;
+    mod bundle;
     mod changelog;
     mod commit;
     mod commit_graph;
     mod convert;
+    mod create_repo;
     mod fetch;
     mod filestore;
     mod hg_sync;
+    mod mirror;
     mod mutable_renames;
+    mod push_log;
     mod redaction;
     mod repo;
+    mod repo_stats;
     mod repos;
     mod skiplist;
     mod ephemeral_store;
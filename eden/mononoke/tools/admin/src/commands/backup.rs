@@ -0,0 +1,88 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+mod verify;
+
+use anyhow::Context;
+use anyhow::Result;
+use bonsai_hg_mapping::BonsaiHgMapping;
+use changesets::Changesets;
+use clap::Parser;
+use clap::Subcommand;
+use metaconfig_types::RepoConfig;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::MononokeApp;
+use phases::Phases;
+use repo_blobstore::RepoBlobstore;
+use repo_derived_data::RepoDerivedData;
+use repo_identity::RepoIdentity;
+
+use self::verify::BackupVerifyArgs;
+
+/// Check backup repos against the repos they back up
+#[derive(Parser)]
+pub struct CommandArgs {
+    /// The backup repo
+    #[clap(flatten)]
+    repo_args: RepoArgs,
+
+    #[clap(subcommand)]
+    subcommand: BackupSubcommand,
+}
+
+#[facet::container]
+pub struct Repo {
+    #[facet]
+    repo_identity: RepoIdentity,
+
+    #[facet]
+    repo_config: RepoConfig,
+
+    #[facet]
+    repo_blobstore: RepoBlobstore,
+
+    #[facet]
+    changesets: dyn Changesets,
+
+    #[facet]
+    phases: dyn Phases,
+
+    #[facet]
+    bonsai_hg_mapping: dyn BonsaiHgMapping,
+
+    #[facet]
+    repo_derived_data: RepoDerivedData,
+}
+
+#[derive(Subcommand)]
+pub enum BackupSubcommand {
+    /// Verify that sampled commits can be restored from the backup
+    ///
+    /// Samples public commits of the source repo at random, checks that
+    /// their changesets and file contents are in the backup, and re-derives
+    /// their hg changesets from the backup in memory, without writing to
+    /// it.  The results are compared against the source repo, and logged
+    /// to scuba one sample per commit.
+    Verify(BackupVerifyArgs),
+}
+
+pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
+    let ctx = app.new_basic_context();
+
+    let backup_repo: Repo = app
+        .open_repo(&args.repo_args)
+        .await
+        .context("Failed to open backup repo")?;
+
+    match args.subcommand {
+        BackupSubcommand::Verify(verify_args) => {
+            verify::verify(&ctx, &app, &backup_repo, verify_args).await?
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,277 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use blobstore::Loadable;
+use bonsai_hg_mapping::BonsaiHgMappingArc;
+use bonsai_hg_mapping::BonsaiHgMappingRef;
+use bonsai_hg_mapping::MemWritesBonsaiHgMapping;
+use cacheblob::MemWritesBlobstore;
+use changesets::ChangesetsRef;
+use changesets::SortOrder;
+use clap::Args;
+use context::CoreContext;
+use derived_data_manager::Rederivation;
+use filestore::FetchKey;
+use futures::TryStreamExt;
+use mercurial_derived_data::MappedHgChangesetId;
+use metaconfig_types::RepoConfigRef;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::MononokeApp;
+use mononoke_types::ChangesetId;
+use mononoke_types::FileChange;
+use phases::PhasesRef;
+use rand::Rng;
+use repo_blobstore::RepoBlobstore;
+use repo_blobstore::RepoBlobstoreRef;
+use repo_derived_data::RepoDerivedDataRef;
+use repo_identity::RepoIdentityRef;
+use slog::info;
+use slog::warn;
+
+use super::Repo;
+
+#[derive(Args)]
+pub struct BackupVerifyArgs {
+    /// Name of the repo that is backed up.  Defaults to the source repo in
+    /// the backup repo's config.
+    #[clap(long)]
+    source_repo_name: Option<String>,
+
+    /// Number of commits to sample each time the backup is verified
+    #[clap(long, default_value_t = 100)]
+    sample_size: usize,
+
+    /// Keep verifying the backup at this interval, in seconds, instead of
+    /// verifying it once
+    #[clap(long)]
+    interval_secs: Option<u64>,
+}
+
+/// Forces the sampled commit to be derived again, rather than reading back
+/// the derived data that is already in the backup.
+struct RederiveCommit {
+    pending: Mutex<BTreeSet<ChangesetId>>,
+}
+
+impl Rederivation for RederiveCommit {
+    fn needs_rederive(&self, _derivable_name: &str, csid: ChangesetId) -> Option<bool> {
+        if self.pending.lock().unwrap().contains(&csid) {
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    fn mark_derived(&self, _derivable_name: &str, csid: ChangesetId) {
+        self.pending.lock().unwrap().remove(&csid);
+    }
+}
+
+/// Pick public commits of the source repo at random.  Draft commits that
+/// are picked are dropped, so fewer than `sample_size` commits may be
+/// returned.
+async fn sample_commits(
+    ctx: &CoreContext,
+    source_repo: &Repo,
+    sample_size: usize,
+) -> Result<BTreeSet<ChangesetId>> {
+    let changesets = source_repo.changesets();
+    let (min_id, max_id) = match changesets.enumeration_bounds(ctx, false, vec![]).await? {
+        Some(bounds) => bounds,
+        None => return Ok(BTreeSet::new()),
+    };
+    let mut cs_ids = BTreeSet::new();
+    for _ in 0..sample_size {
+        // Ids are shared between repos, so take the first commit of this
+        // repo at or after a random id.
+        let id = rand::thread_rng().gen_range(min_id..=max_id);
+        let sample = changesets
+            .list_enumeration_range(ctx, id, max_id + 1, Some((SortOrder::Ascending, 1)), false)
+            .try_next()
+            .await?;
+        if let Some((cs_id, _)) = sample {
+            cs_ids.insert(cs_id);
+        }
+    }
+    let public = source_repo
+        .phases()
+        .get_public(ctx, cs_ids.into_iter().collect(), false)
+        .await?;
+    Ok(public.into_iter().collect())
+}
+
+/// Check that a commit can be restored from the backup, and that the hg
+/// changeset derived from the restored commit matches the source repo's.
+async fn verify_commit(
+    ctx: &CoreContext,
+    source_repo: &Repo,
+    backup_repo: &Repo,
+    cs_id: ChangesetId,
+) -> Result<()> {
+    if !backup_repo.changesets().exists(ctx, cs_id).await? {
+        bail!("Commit is missing from the backup");
+    }
+    let bcs = cs_id
+        .load(ctx, backup_repo.repo_blobstore())
+        .await
+        .context("Failed to load changeset from the backup")?;
+    if bcs.get_changeset_id() != cs_id {
+        bail!(
+            "Changeset in the backup has hash {}",
+            bcs.get_changeset_id()
+        );
+    }
+    for (path, file_change) in bcs.file_changes() {
+        if let FileChange::Change(change) = file_change {
+            let key = FetchKey::Canonical(change.content_id());
+            let metadata = filestore::get_metadata(backup_repo.repo_blobstore(), ctx, &key)
+                .await?
+                .ok_or_else(|| anyhow!("Content of {} is missing from the backup", path))?;
+            if metadata.total_size != change.size() {
+                bail!(
+                    "Content of {} in the backup has size {}, expected {}",
+                    path,
+                    metadata.total_size,
+                    change.size()
+                );
+            }
+        }
+    }
+
+    // Derive into a scratch repo that reads from the backup but keeps its
+    // writes in memory.
+    let scratch_blobstore = RepoBlobstore::new_with_wrapped_inner_blobstore(
+        backup_repo.repo_blobstore().clone(),
+        |blobstore| Arc::new(MemWritesBlobstore::new(blobstore)),
+    );
+    let scratch_derived_data = backup_repo
+        .repo_derived_data()
+        .with_replaced_blobstore(scratch_blobstore)
+        .with_replaced_bonsai_hg_mapping(Arc::new(MemWritesBonsaiHgMapping::new(
+            backup_repo.bonsai_hg_mapping_arc(),
+        )));
+    let rederivation: Arc<dyn Rederivation> = Arc::new(RederiveCommit {
+        pending: Mutex::new(BTreeSet::from([cs_id])),
+    });
+    let rederived = scratch_derived_data
+        .manager()
+        .derive::<MappedHgChangesetId>(ctx, cs_id, Some(rederivation))
+        .await
+        .context("Failed to derive hg changeset from the backup")?
+        .hg_changeset_id();
+
+    match source_repo
+        .bonsai_hg_mapping()
+        .get_hg_from_bonsai(ctx, cs_id)
+        .await?
+    {
+        Some(expected) if expected != rederived => {
+            bail!(
+                "Hg changeset derived from the backup is {}, expected {}",
+                rederived,
+                expected
+            );
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+async fn verify_backup(
+    ctx: &CoreContext,
+    source_repo: &Repo,
+    backup_repo: &Repo,
+    sample_size: usize,
+) -> Result<()> {
+    let cs_ids = sample_commits(ctx, source_repo, sample_size).await?;
+    let mut failures = 0;
+    for cs_id in cs_ids.iter().copied() {
+        let result = verify_commit(ctx, source_repo, backup_repo, cs_id).await;
+
+        let mut scuba = ctx.scuba().clone();
+        scuba
+            .add("source_repo", source_repo.repo_identity().name())
+            .add("backup_repo", backup_repo.repo_identity().name())
+            .add("cs_id", cs_id.to_string())
+            .add("success", result.is_ok());
+        if let Err(err) = &result {
+            failures += 1;
+            warn!(
+                ctx.logger(),
+                "Backup verification failed for {}: {:?}", cs_id, err
+            );
+            scuba.add("error", format!("{:?}", err));
+        }
+        scuba.log_with_msg("Backup verification", None);
+    }
+
+    info!(
+        ctx.logger(),
+        "Verified {} commits of {} in backup {}: {} failed",
+        cs_ids.len(),
+        source_repo.repo_identity().name(),
+        backup_repo.repo_identity().name(),
+        failures
+    );
+    if failures > 0 {
+        bail!("Backup verification failed for {} commits", failures);
+    }
+    Ok(())
+}
+
+pub async fn verify(
+    ctx: &CoreContext,
+    app: &MononokeApp,
+    backup_repo: &Repo,
+    verify_args: BackupVerifyArgs,
+) -> Result<()> {
+    let source_repo_name = match verify_args.source_repo_name {
+        Some(name) => name,
+        None => backup_repo
+            .repo_config()
+            .backup_repo_config
+            .as_ref()
+            .map(|config| config.source_repo_name.clone())
+            .ok_or_else(|| {
+                anyhow!(
+                    "{} is not configured as a backup repo",
+                    backup_repo.repo_identity().name()
+                )
+            })?,
+    };
+    let source_repo: Repo = app
+        .open_repo(&RepoArgs::from_repo_name(source_repo_name))
+        .await
+        .context("Failed to open source repo")?;
+
+    let interval = match verify_args.interval_secs {
+        Some(interval_secs) => Duration::from_secs(interval_secs),
+        None => {
+            return verify_backup(ctx, &source_repo, backup_repo, verify_args.sample_size).await;
+        }
+    };
+
+    loop {
+        // When verifying on a schedule, failures are reported through the
+        // logs and scuba, and verification carries on.
+        if let Err(err) =
+            verify_backup(ctx, &source_repo, backup_repo, verify_args.sample_size).await
+        {
+            warn!(ctx.logger(), "{:?}", err);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
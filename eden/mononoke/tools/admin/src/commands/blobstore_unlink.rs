@@ -99,6 +99,41 @@ async fn get_blobstore(
     Ok(Arc::new(sql_blob) as Arc<dyn BlobstoreUnlinkOps>)
 }
 
+/// Get every blobstore that holds a copy of the repo's blobs, so that keys
+/// can be unlinked from all of them.  If the repo's blobstore is
+/// multiplexed, this is each of the inner blobstores.
+pub(crate) async fn get_all_blobstores(
+    fb: FacebookInit,
+    storage_config: StorageConfig,
+    readonly_storage: ReadOnlyStorage,
+    blobstore_options: &BlobstoreOptions,
+    config_store: &ConfigStore,
+) -> Result<Vec<Arc<dyn BlobstoreUnlinkOps>>, Error> {
+    let blob_config = remove_wrapper_blobconfigs(storage_config.blobstore.clone());
+    let inner_blobstore_ids: Vec<Option<u64>> = match blob_config {
+        BlobConfig::MultiplexedWal { blobstores, .. } => blobstores
+            .into_iter()
+            .map(|(blobstore_id, _, _)| Some(u64::from(blobstore_id)))
+            .collect(),
+        _ => vec![None],
+    };
+    let mut blobstores = Vec::with_capacity(inner_blobstore_ids.len());
+    for inner_blobstore_id in inner_blobstore_ids {
+        blobstores.push(
+            get_blobstore(
+                fb,
+                storage_config.clone(),
+                inner_blobstore_id,
+                readonly_storage,
+                blobstore_options,
+                config_store,
+            )
+            .await?,
+        );
+    }
+    Ok(blobstores)
+}
+
 pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
     let ctx = app.new_basic_context();
 
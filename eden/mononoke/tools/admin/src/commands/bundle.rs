@@ -0,0 +1,105 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+mod export;
+mod import;
+
+use anyhow::Context;
+use anyhow::Result;
+use bonsai_git_mapping::BonsaiGitMapping;
+use bonsai_globalrev_mapping::BonsaiGlobalrevMapping;
+use bonsai_hg_mapping::BonsaiHgMapping;
+use bonsai_svnrev_mapping::BonsaiSvnrevMapping;
+use changesets::Changesets;
+use clap::Parser;
+use clap::Subcommand;
+use commit_graph::CommitGraph;
+use filestore::FilestoreConfig;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::MononokeApp;
+use repo_blobstore::RepoBlobstore;
+use repo_identity::RepoIdentity;
+
+use self::export::BundleExportArgs;
+use self::import::BundleImportArgs;
+
+/// Transfer commits between Mononoke instances as bundle files
+#[derive(Parser)]
+pub struct CommandArgs {
+    #[clap(flatten)]
+    repo_args: RepoArgs,
+
+    #[clap(subcommand)]
+    subcommand: BundleSubcommand,
+}
+
+#[facet::container]
+pub struct Repo {
+    #[facet]
+    repo_identity: RepoIdentity,
+
+    #[facet]
+    repo_blobstore: RepoBlobstore,
+
+    #[facet]
+    filestore_config: FilestoreConfig,
+
+    #[facet]
+    changesets: dyn Changesets,
+
+    #[facet]
+    commit_graph: CommitGraph,
+
+    #[facet]
+    bonsai_hg_mapping: dyn BonsaiHgMapping,
+
+    #[facet]
+    bonsai_git_mapping: dyn BonsaiGitMapping,
+
+    #[facet]
+    bonsai_globalrev_mapping: dyn BonsaiGlobalrevMapping,
+
+    #[facet]
+    bonsai_svnrev_mapping: dyn BonsaiSvnrevMapping,
+}
+
+#[derive(Subcommand)]
+pub enum BundleSubcommand {
+    /// Export commits to a self-contained bundle file
+    ///
+    /// Exports the ancestors of the heads that are not ancestors of the
+    /// common commits, with their bonsai changesets, the contents of the
+    /// files they change and, if they have been derived, their hg
+    /// changesets, manifests and filenodes.  The bundle can be imported into
+    /// another instance that already has the common commits, e.g. for
+    /// air-gapped replication or to recover from an incident.
+    Export(BundleExportArgs),
+
+    /// Import commits from a bundle file
+    ///
+    /// Writes the blobs and file contents in the bundle to the repo, then
+    /// saves the commits and their hg mapping.  The parents of the oldest
+    /// commits in the bundle must already be in the repo.  Bookmarks are
+    /// not moved.
+    Import(BundleImportArgs),
+}
+
+pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
+    let ctx = app.new_basic_context();
+
+    let repo: Repo = app
+        .open_repo(&args.repo_args)
+        .await
+        .context("Failed to open repo")?;
+
+    match args.subcommand {
+        BundleSubcommand::Export(export_args) => export::export(&ctx, &repo, export_args).await?,
+        BundleSubcommand::Import(import_args) => import::import(&ctx, &repo, import_args).await?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use clap::Args;
+use commit_bundle::commits_to_export;
+use commit_bundle::export_bundle;
+use context::CoreContext;
+use futures::future::try_join_all;
+use slog::info;
+
+use super::Repo;
+use crate::commit_id::parse_commit_id;
+
+#[derive(Args)]
+pub struct BundleExportArgs {
+    /// Commit IDs whose ancestors are exported.
+    #[clap(long, use_value_delimiter = true, required = true)]
+    heads: Vec<String>,
+
+    /// Commit IDs whose ancestors are already present in the target repo,
+    /// and so are not exported.
+    #[clap(long, use_value_delimiter = true)]
+    common: Vec<String>,
+
+    /// File to write the bundle to.
+    #[clap(long, short = 'o')]
+    output_file: PathBuf,
+}
+
+pub async fn export(ctx: &CoreContext, repo: &Repo, args: BundleExportArgs) -> Result<()> {
+    let heads = try_join_all(args.heads.iter().map(|id| parse_commit_id(ctx, repo, id))).await?;
+    let common = try_join_all(args.common.iter().map(|id| parse_commit_id(ctx, repo, id))).await?;
+    let bonsais = commits_to_export(ctx, repo, heads, common, None).await?;
+
+    let output = File::create(&args.output_file).with_context(|| {
+        format!(
+            "Failed to create output file {}",
+            args.output_file.to_string_lossy()
+        )
+    })?;
+    let (_output, stats) =
+        export_bundle(ctx, repo, &bonsais, BufWriter::new(output), None).await?;
+
+    info!(
+        ctx.logger(),
+        "Exported {} commits, {} blobs and {} files to {}",
+        stats.commits,
+        stats.blobs,
+        stats.files,
+        args.output_file.to_string_lossy(),
+    );
+    Ok(())
+}
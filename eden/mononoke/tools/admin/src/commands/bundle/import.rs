@@ -0,0 +1,46 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use clap::Args;
+use commit_bundle::import_bundle;
+use context::CoreContext;
+use slog::info;
+
+use super::Repo;
+
+#[derive(Args)]
+pub struct BundleImportArgs {
+    /// Bundle file to import, as written by `bundle export`.
+    #[clap(long, short = 'i')]
+    input_file: PathBuf,
+
+    /// Number of commits to save at a time.
+    #[clap(long, default_value_t = 100)]
+    chunk_size: usize,
+}
+
+pub async fn import(ctx: &CoreContext, repo: &Repo, args: BundleImportArgs) -> Result<()> {
+    let input = File::open(&args.input_file).with_context(|| {
+        format!(
+            "Failed to open input file {}",
+            args.input_file.to_string_lossy()
+        )
+    })?;
+    let imported = import_bundle(ctx, repo, BufReader::new(input), args.chunk_size).await?;
+
+    info!(ctx.logger(), "Imported {} commits", imported.len());
+    for cs_id in imported {
+        println!("{}", cs_id);
+    }
+    Ok(())
+}
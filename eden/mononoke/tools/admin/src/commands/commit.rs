@@ -5,10 +5,22 @@
  * GNU General Public License version 2.
  */
 
+mod export;
+mod hide;
+mod purge;
 mod pushrebase;
 mod rebase;
+mod rebuild_hg_mapping;
 mod split;
 
+use std::future::Future;
+use std::sync::Arc;
+
+use admin_audit_log::format_actor;
+use admin_audit_log::AdminAuditEntry;
+use admin_audit_log::AdminAuditLog;
+use admin_audit_log::AdminAuditLogRef;
+use admin_audit_log::AdminAuditOutcome;
 use anyhow::bail;
 use anyhow::Context;
 use anyhow::Result;
@@ -24,21 +36,36 @@ use changesets::Changesets;
 use clap::Parser;
 use clap::Subcommand;
 use context::CoreContext;
+use context::SessionContainer;
+use filestore::FilestoreConfig;
 use futures::compat::Stream01CompatExt;
 use futures::TryStreamExt;
+use hidden_changesets::HiddenChangesets;
 use metaconfig_types::RepoConfig;
+use metadata::Metadata;
 use mononoke_app::args::RepoArgs;
 use mononoke_app::MononokeApp;
 use mononoke_types::ChangesetId;
+use mononoke_types::Timestamp;
+use permission_checker::MononokeIdentity;
+use permission_checker::MononokeIdentitySet;
+use phases::Phases;
 use pushrebase_mutation_mapping::PushrebaseMutationMapping;
 use repo_blobstore::RepoBlobstore;
 use repo_bookmark_attrs::RepoBookmarkAttrs;
 use repo_cross_repo::RepoCrossRepo;
 use repo_derived_data::RepoDerivedData;
 use repo_identity::RepoIdentity;
+use scuba_ext::MononokeScubaSampleBuilder;
 
+use self::export::CommitExportArgs;
+use self::hide::CommitHideArgs;
+use self::hide::CommitListHiddenArgs;
+use self::hide::CommitUnhideArgs;
+use self::purge::CommitPurgeArgs;
 use self::pushrebase::CommitPushrebaseArgs;
 use self::rebase::CommitRebaseArgs;
+use self::rebuild_hg_mapping::CommitRebuildHgMappingArgs;
 use self::split::CommitSplitArgs;
 
 /// Manipulate commits
@@ -74,6 +101,9 @@ pub struct Repo {
     #[facet]
     repo_blobstore: RepoBlobstore,
 
+    #[facet]
+    filestore_config: FilestoreConfig,
+
     #[facet]
     changesets: dyn Changesets,
 
@@ -94,6 +124,15 @@ pub struct Repo {
 
     #[facet]
     repo_cross_repo: RepoCrossRepo,
+
+    #[facet]
+    phases: dyn Phases,
+
+    #[facet]
+    hidden_changesets: dyn HiddenChangesets,
+
+    #[facet]
+    admin_audit_log: dyn AdminAuditLog,
 }
 
 #[derive(Subcommand)]
@@ -121,10 +160,66 @@ pub enum CommitSubcommand {
     /// Rebases a commit from its current bookmark onto a bookmark, and moves
     /// that bookmark to the newly rebased commit.
     Pushrebase(CommitPushrebaseArgs),
+
+    /// Rebuild the bonsai-hg mapping for a range of commits
+    ///
+    /// Derives the hg changesets for all commits between the bottom and top
+    /// commits, and adds or replaces any mapping entries that are missing or
+    /// don't match.  The hg changesets of the parents of the bottom commit
+    /// are taken from the existing mapping.
+    RebuildHgMapping(CommitRebuildHgMappingArgs),
+
+    /// Hide draft commits
+    ///
+    /// Hidden commits can't be resolved by their id or a prefix of it,
+    /// pulled, or listed.  Descendants of the commits are not hidden, so
+    /// they should be included explicitly.
+    Hide(CommitHideArgs),
+
+    /// Unhide commits that were previously hidden
+    Unhide(CommitUnhideArgs),
+
+    /// List hidden commits
+    ListHidden(CommitListHiddenArgs),
+
+    /// Purge draft commits, permanently deleting their blobs
+    ///
+    /// Use this when commits contain something that must not be stored, such
+    /// as a leaked secret.  The commits are hidden, and then the commits,
+    /// their hg changesets and derived data, and the contents of the files
+    /// they change that no other commit refers to, are unlinked from every
+    /// blobstore and removed from memcache.  Fails if any of the commits are
+    /// public, or have descendants that aren't being purged.
+    Purge(CommitPurgeArgs),
+
+    /// Export commits for debugging
+    ///
+    /// Prints the full bonsai changesets, including file changes, copy
+    /// information and extras, together with their hg changesets if those
+    /// have been derived.  Use `--follow-parents` to also export a window of
+    /// history leading up to the commits.
+    Export(CommitExportArgs),
+}
+
+/// Context for the commit commands, which identifies the operator running
+/// them, so that audited commands record who ran them.
+fn operator_context(app: &MononokeApp) -> CoreContext {
+    let mut identities = MononokeIdentitySet::new();
+    if let Ok(user) = std::env::var("USER") {
+        identities.insert(MononokeIdentity::new("USER", user));
+    }
+    let metadata = Metadata::default().set_identities(identities);
+    SessionContainer::builder(app.fb)
+        .metadata(Arc::new(metadata))
+        .build()
+        .new_context(
+            app.logger().clone(),
+            MononokeScubaSampleBuilder::with_discard(),
+        )
 }
 
 pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
-    let ctx = app.new_basic_context();
+    let ctx = operator_context(&app);
 
     let repo: Repo = app
         .open_repo(&args.repo_args)
@@ -137,11 +232,54 @@ pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
         CommitSubcommand::Pushrebase(pushrebase_args) => {
             pushrebase::pushrebase(&ctx, &repo, pushrebase_args).await?
         }
+        CommitSubcommand::RebuildHgMapping(rebuild_args) => {
+            rebuild_hg_mapping::rebuild_hg_mapping(&ctx, &app, &repo, rebuild_args).await?
+        }
+        CommitSubcommand::Hide(hide_args) => hide::hide(&ctx, &repo, hide_args).await?,
+        CommitSubcommand::Unhide(unhide_args) => hide::unhide(&ctx, &repo, unhide_args).await?,
+        CommitSubcommand::ListHidden(list_args) => {
+            hide::list_hidden(&ctx, &repo, list_args).await?
+        }
+        CommitSubcommand::Purge(purge_args) => {
+            purge::purge(&ctx, &app, &repo, purge_args).await?
+        }
+        CommitSubcommand::Export(export_args) => export::export(&ctx, &repo, export_args).await?,
     }
 
     Ok(())
 }
 
+/// Run an operation, recording who ran it and its outcome in the admin audit
+/// log.
+async fn run_audited(
+    ctx: &CoreContext,
+    repo: &Repo,
+    operation: &str,
+    arguments: String,
+    fut: impl Future<Output = Result<()>>,
+) -> Result<()> {
+    let result = fut.await;
+    let outcome = match &result {
+        Ok(()) => AdminAuditOutcome::Success,
+        Err(e) => AdminAuditOutcome::Failure(format!("{:#}", e)),
+    };
+    let actor = format_actor(ctx.metadata().identities());
+    repo.admin_audit_log()
+        .record(
+            ctx,
+            AdminAuditEntry {
+                timestamp: Timestamp::now(),
+                actor,
+                operation: operation.to_string(),
+                arguments,
+                outcome,
+            },
+        )
+        .await
+        .context("Failed to record admin audit log entry")?;
+    result
+}
+
 async fn resolve_stack(
     ctx: &CoreContext,
     repo: &Repo,
@@ -0,0 +1,184 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::io::Write;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use blobstore::Blobstore;
+use blobstore::Loadable;
+use bonsai_hg_mapping::BonsaiHgMappingRef;
+use clap::ArgEnum;
+use clap::Args;
+use cmdlib_displaying::DisplayChangeset;
+use context::CoreContext;
+use mercurial_types::HgChangesetId;
+use mononoke_types::BlobstoreKey;
+use mononoke_types::ChangesetId;
+use repo_blobstore::RepoBlobstoreRef;
+use serde_json::json;
+use serde_json::Value;
+
+use super::hide::resolve_commit_ids;
+use super::Repo;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ArgEnum)]
+pub enum ExportFormat {
+    /// One JSON object per commit.
+    Json,
+    /// One line per commit, with the hex-encoded compact Thrift blobs of the
+    /// bonsai and hg changesets, as stored in the blobstore.
+    Thrift,
+}
+
+#[derive(Args)]
+pub struct CommitExportArgs {
+    /// Commit IDs to export
+    #[clap(required = true)]
+    commit_ids: Vec<String>,
+
+    /// Output format
+    #[clap(long, arg_enum, default_value_t = ExportFormat::Json)]
+    format: ExportFormat,
+
+    /// Also export this many generations of ancestors of the commits
+    #[clap(long, default_value_t = 0)]
+    follow_parents: usize,
+}
+
+/// The commits to export, in breadth-first order from the requested commits
+/// to their ancestors `follow_parents` generations back.
+async fn commits_to_export(
+    ctx: &CoreContext,
+    repo: &Repo,
+    heads: Vec<ChangesetId>,
+    follow_parents: usize,
+) -> Result<Vec<ChangesetId>> {
+    let mut seen = HashSet::new();
+    let mut queue = heads
+        .into_iter()
+        .map(|cs_id| (cs_id, 0))
+        .collect::<VecDeque<_>>();
+    let mut cs_ids = Vec::new();
+    while let Some((cs_id, generation)) = queue.pop_front() {
+        if !seen.insert(cs_id) {
+            continue;
+        }
+        cs_ids.push(cs_id);
+        if generation < follow_parents {
+            let bonsai = cs_id
+                .load(ctx, repo.repo_blobstore())
+                .await
+                .with_context(|| format!("Failed to load changeset {}", cs_id))?;
+            queue.extend(bonsai.parents().map(|parent| (parent, generation + 1)));
+        }
+    }
+    Ok(cs_ids)
+}
+
+async fn hg_changeset_json(
+    ctx: &CoreContext,
+    repo: &Repo,
+    hg_cs_id: HgChangesetId,
+) -> Result<Value> {
+    let hg_cs = hg_cs_id
+        .load(ctx, repo.repo_blobstore())
+        .await
+        .with_context(|| format!("Failed to load hg changeset {}", hg_cs_id))?;
+    let lossy = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+    Ok(json!({
+        "changeset_id": hg_cs_id.to_string(),
+        "parents": hg_cs
+            .p1()
+            .into_iter()
+            .chain(hg_cs.p2())
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>(),
+        "manifest_id": hg_cs.manifestid().to_string(),
+        "user": lossy(hg_cs.user()),
+        "time": hg_cs.time(),
+        "extra": hg_cs
+            .extra()
+            .iter()
+            .map(|(k, v)| (lossy(k), Value::from(lossy(v))))
+            .collect::<serde_json::Map<_, _>>(),
+        "files": hg_cs.files().iter().map(|p| p.to_string()).collect::<Vec<_>>(),
+        "message": lossy(hg_cs.message()),
+    }))
+}
+
+async fn export_json(
+    ctx: &CoreContext,
+    repo: &Repo,
+    cs_id: ChangesetId,
+    hg_cs_id: Option<HgChangesetId>,
+) -> Result<String> {
+    let bonsai = cs_id
+        .load(ctx, repo.repo_blobstore())
+        .await
+        .with_context(|| format!("Failed to load changeset {}", cs_id))?;
+    let display_cs = DisplayChangeset::try_from(&bonsai).context("Failed to display changeset")?;
+    let hg_changeset = match hg_cs_id {
+        Some(hg_cs_id) => hg_changeset_json(ctx, repo, hg_cs_id).await?,
+        None => Value::Null,
+    };
+    let json = json!({
+        "bonsai": display_cs,
+        "hg": hg_changeset,
+    });
+    serde_json::to_string(&json).context("Failed to convert to JSON")
+}
+
+async fn raw_blob_hex(ctx: &CoreContext, repo: &Repo, key: &str) -> Result<String> {
+    let data = repo
+        .repo_blobstore()
+        .get(ctx, key)
+        .await?
+        .ok_or_else(|| anyhow!("Blob {} not found", key))?;
+    Ok(hex::encode(data.as_raw_bytes()))
+}
+
+async fn export_thrift(
+    ctx: &CoreContext,
+    repo: &Repo,
+    cs_id: ChangesetId,
+    hg_cs_id: Option<HgChangesetId>,
+) -> Result<String> {
+    let bonsai = raw_blob_hex(ctx, repo, &cs_id.blobstore_key()).await?;
+    let (hg_cs_id, hg) = match hg_cs_id {
+        Some(hg_cs_id) => (
+            hg_cs_id.to_string(),
+            raw_blob_hex(ctx, repo, &hg_cs_id.blobstore_key()).await?,
+        ),
+        None => ("-".to_string(), "-".to_string()),
+    };
+    Ok(format!("{} {} {} {}", cs_id, bonsai, hg_cs_id, hg))
+}
+
+pub async fn export(ctx: &CoreContext, repo: &Repo, export_args: CommitExportArgs) -> Result<()> {
+    let heads = resolve_commit_ids(ctx, repo, &export_args.commit_ids).await?;
+    let cs_ids = commits_to_export(ctx, repo, heads, export_args.follow_parents).await?;
+
+    let mut stdout = std::io::stdout();
+    for cs_id in cs_ids {
+        // Export the hg changeset only if it has already been derived.
+        let hg_cs_id = repo
+            .bonsai_hg_mapping()
+            .get_hg_from_bonsai(ctx, cs_id)
+            .await
+            .with_context(|| format!("Failed to get hg changeset for {}", cs_id))?;
+        let line = match export_args.format {
+            ExportFormat::Json => export_json(ctx, repo, cs_id, hg_cs_id).await?,
+            ExportFormat::Thrift => export_thrift(ctx, repo, cs_id, hg_cs_id).await?,
+        };
+        writeln!(stdout, "{}", line)?;
+    }
+    Ok(())
+}
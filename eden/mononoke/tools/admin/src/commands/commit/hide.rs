@@ -0,0 +1,128 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::bail;
+use anyhow::Result;
+use clap::Args;
+use context::CoreContext;
+use futures::future::try_join_all;
+use hidden_changesets::HiddenChangesetsRef;
+use itertools::Itertools;
+use mononoke_types::ChangesetId;
+use phases::PhasesRef;
+use serde_json::json;
+
+use super::Repo;
+use crate::commit_id::parse_commit_id;
+
+#[derive(Args)]
+pub struct CommitHideArgs {
+    /// Commit IDs to hide
+    #[clap(required = true)]
+    commit_ids: Vec<String>,
+
+    /// Why the commits are being hidden
+    #[clap(long)]
+    reason: String,
+}
+
+#[derive(Args)]
+pub struct CommitUnhideArgs {
+    /// Commit IDs to unhide
+    #[clap(required = true)]
+    commit_ids: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct CommitListHiddenArgs {}
+
+pub(super) async fn resolve_commit_ids(
+    ctx: &CoreContext,
+    repo: &Repo,
+    commit_ids: &[String],
+) -> Result<Vec<ChangesetId>> {
+    try_join_all(
+        commit_ids
+            .iter()
+            .map(|commit_id| parse_commit_id(ctx, repo, commit_id)),
+    )
+    .await
+}
+
+/// Check that none of the commits are public.  Public commits are closed
+/// under ancestry, so this also means none of them have public descendants.
+pub(super) async fn ensure_draft(
+    ctx: &CoreContext,
+    repo: &Repo,
+    cs_ids: &[ChangesetId],
+) -> Result<()> {
+    let public = repo
+        .phases()
+        .get_public(ctx, cs_ids.to_vec(), false)
+        .await?;
+    if !public.is_empty() {
+        bail!(
+            "Refusing to modify public commits: {}",
+            public.iter().sorted().join(", ")
+        );
+    }
+    Ok(())
+}
+
+pub async fn hide(ctx: &CoreContext, repo: &Repo, hide_args: CommitHideArgs) -> Result<()> {
+    let cs_ids = resolve_commit_ids(ctx, repo, &hide_args.commit_ids).await?;
+    let arguments = json!({
+        "commits": cs_ids.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        "reason": hide_args.reason,
+    });
+
+    super::run_audited(ctx, repo, "hide_commits", arguments.to_string(), async {
+        ensure_draft(ctx, repo, &cs_ids).await?;
+        repo.hidden_changesets()
+            .hide(ctx, &cs_ids, &hide_args.reason)
+            .await
+    })
+    .await?;
+
+    for cs_id in cs_ids {
+        println!("Hidden {}", cs_id);
+    }
+    Ok(())
+}
+
+pub async fn unhide(ctx: &CoreContext, repo: &Repo, unhide_args: CommitUnhideArgs) -> Result<()> {
+    let cs_ids = resolve_commit_ids(ctx, repo, &unhide_args.commit_ids).await?;
+    let arguments = json!({
+        "commits": cs_ids.iter().map(ToString::to_string).collect::<Vec<_>>(),
+    });
+
+    super::run_audited(ctx, repo, "unhide_commits", arguments.to_string(), async {
+        repo.hidden_changesets().unhide(ctx, &cs_ids).await
+    })
+    .await?;
+
+    for cs_id in cs_ids {
+        println!("Unhidden {}", cs_id);
+    }
+    Ok(())
+}
+
+pub async fn list_hidden(
+    ctx: &CoreContext,
+    repo: &Repo,
+    _list_args: CommitListHiddenArgs,
+) -> Result<()> {
+    for hidden in repo.hidden_changesets().list(ctx).await? {
+        println!(
+            "{} (hidden at {}): {}",
+            hidden.cs_id,
+            hidden.timestamp.timestamp_seconds(),
+            hidden.reason
+        );
+    }
+    Ok(())
+}
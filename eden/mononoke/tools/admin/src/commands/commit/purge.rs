@@ -0,0 +1,459 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashSet;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Error;
+use anyhow::Result;
+use blobstore::Blobstore;
+use blobstore::Loadable;
+use bonsai_hg_mapping::BonsaiHgMappingRef;
+use cacheblob::MemcacheOps;
+use changesets::ChangesetsRef;
+use changesets::SortOrder;
+use clap::Args;
+use context::CoreContext;
+use filestore::Alias;
+use fsnodes::RootFsnodeId;
+use futures::future;
+use futures::stream;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use hidden_changesets::HiddenChangesetsRef;
+use manifest::Entry;
+use manifest::ManifestOps;
+use metaconfig_types::RepoConfigRef;
+use mononoke_app::MononokeApp;
+use mononoke_types::BlobstoreKey;
+use mononoke_types::BonsaiChangeset;
+use mononoke_types::ChangesetId;
+use mononoke_types::ContentChunkId;
+use mononoke_types::ContentId;
+use mononoke_types::ContentMetadataId;
+use mononoke_types::FileContents;
+use mononoke_types::MPath;
+use repo_blobstore::RepoBlobstoreRef;
+use repo_derived_data::RepoDerivedDataRef;
+use repo_identity::RepoIdentityRef;
+use serde_json::json;
+use unodes::RootUnodeManifestId;
+
+use super::hide::ensure_draft;
+use super::hide::resolve_commit_ids;
+use super::Repo;
+use crate::commands::blobstore_unlink::get_all_blobstores;
+
+#[derive(Args)]
+pub struct CommitPurgeArgs {
+    /// Commit IDs to purge
+    #[clap(required = true)]
+    commit_ids: Vec<String>,
+
+    /// Why the commits are being purged
+    #[clap(long)]
+    reason: String,
+
+    /// Confirm that the blobs should be permanently deleted
+    #[clap(long)]
+    permanently_delete: bool,
+}
+
+pub async fn purge(
+    ctx: &CoreContext,
+    app: &MononokeApp,
+    repo: &Repo,
+    purge_args: CommitPurgeArgs,
+) -> Result<()> {
+    if !purge_args.permanently_delete {
+        bail!("You must provide --permanently-delete to this command");
+    }
+
+    let cs_ids = resolve_commit_ids(ctx, repo, &purge_args.commit_ids).await?;
+    let arguments = json!({
+        "commits": cs_ids.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        "reason": purge_args.reason,
+    });
+
+    super::run_audited(ctx, repo, "purge_commits", arguments.to_string(), async {
+        ensure_draft(ctx, repo, &cs_ids).await?;
+
+        // Hide the commits first, so that they stop being served even if
+        // unlinking fails part way through.
+        repo.hidden_changesets()
+            .hide(ctx, &cs_ids, &purge_args.reason)
+            .await?;
+
+        let keys = purged_keys(ctx, repo, &cs_ids).await?;
+        let blobstores = get_all_blobstores(
+            app.fb,
+            repo.repo_config().storage_config.clone(),
+            app.environment().readonly_storage,
+            &app.environment().blobstore_options,
+            app.config_store(),
+        )
+        .await?;
+        for blobstore in blobstores {
+            for key in keys.iter() {
+                // Multiplexed blobstores may not have every key in every
+                // inner blobstore.
+                if blobstore
+                    .is_present(ctx, key)
+                    .await?
+                    .assume_not_found_if_unsure()
+                {
+                    blobstore
+                        .unlink(ctx, key)
+                        .await
+                        .with_context(|| format!("Failed to unlink {}", key))?;
+                    println!("Unlinked {}", key);
+                }
+            }
+        }
+
+        // Memcache is shared between servers, so the blobs can be dropped
+        // from it here.  Servers also cache blobs in process, and those are
+        // only dropped when they are evicted.
+        let memcache = MemcacheOps::new(app.fb, "multiplexed", "")?;
+        for key in keys.iter() {
+            memcache
+                .invalidate(key)
+                .await
+                .with_context(|| format!("Failed to remove {} from memcache", key))?;
+        }
+        println!(
+            "Blobs may still be served from servers' in-process caches until they are evicted"
+        );
+        Ok(())
+    })
+    .await
+}
+
+/// Number of changesets listed at a time when looking for the contents that
+/// other commits still refer to.
+const LIST_CHUNK_SIZE: u64 = 10000;
+
+/// Returns the contents of the files changed by the commits, with the paths
+/// they were changed at.
+fn changed_contents(bcs: &BonsaiChangeset) -> impl Iterator<Item = (&MPath, ContentId)> {
+    bcs.file_changes().filter_map(|(path, file_change)| {
+        file_change
+            .simplify()
+            .map(|file_change| (path, file_change.content_id()))
+    })
+}
+
+/// Returns the contents of the files changed by every commit that isn't being
+/// purged.  Fails if any of those commits is a child of a purged commit, as
+/// it would be left without its parent.
+///
+/// Every file in a commit was added or changed by it or one of its ancestors,
+/// so these are all the contents that other commits still refer to.
+async fn retained_contents(
+    ctx: &CoreContext,
+    repo: &Repo,
+    purged: &HashSet<ChangesetId>,
+) -> Result<HashSet<ContentId>> {
+    let mut contents = HashSet::new();
+    let (mut min_id, max_id) = match repo
+        .changesets()
+        .enumeration_bounds(ctx, false, vec![])
+        .await?
+    {
+        Some((min_id, max_id)) => (min_id, max_id + 1),
+        None => return Ok(contents),
+    };
+    while min_id < max_id {
+        let entries = repo
+            .changesets()
+            .list_enumeration_range(
+                ctx,
+                min_id,
+                max_id,
+                Some((SortOrder::Ascending, LIST_CHUNK_SIZE)),
+                false,
+            )
+            .try_collect::<Vec<_>>()
+            .await?;
+        match entries.last() {
+            Some((_, id)) => min_id = id + 1,
+            None => break,
+        }
+        let bonsais = stream::iter(entries)
+            .filter(|(cs_id, _)| future::ready(!purged.contains(cs_id)))
+            .map(|(cs_id, _)| async move {
+                cs_id
+                    .load(ctx, repo.repo_blobstore())
+                    .await
+                    .map_err(Error::from)
+            })
+            .buffer_unordered(100)
+            .try_collect::<Vec<_>>()
+            .await?;
+        for bcs in bonsais {
+            if let Some(parent) = bcs.parents().find(|parent| purged.contains(parent)) {
+                bail!(
+                    "Refusing to purge {}, as its descendant {} is not being purged",
+                    parent,
+                    bcs.get_changeset_id()
+                );
+            }
+            contents.extend(changed_contents(&bcs).map(|(_path, content_id)| content_id));
+        }
+    }
+    Ok(contents)
+}
+
+/// Returns the paths and every directory that contains them, including the
+/// root directory.
+fn with_parent_dirs<'a>(paths: impl IntoIterator<Item = &'a MPath>) -> Vec<Option<MPath>> {
+    let mut paths = paths
+        .into_iter()
+        .flat_map(|path| path.clone().into_parent_dir_iter())
+        .map(Some)
+        .collect::<Vec<_>>();
+    paths.push(None);
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// Returns the keys of the hg changeset and derived data of the commit that
+/// describe its purged files.
+///
+/// Only commits that are being purged can refer to the manifest entries for
+/// purged contents, as other commits can't refer to those contents.  Unodes
+/// also refer to the commit that changed them, so every unode for a changed
+/// path belongs to the commit.
+async fn derived_keys(
+    ctx: &CoreContext,
+    repo: &Repo,
+    bcs: &BonsaiChangeset,
+    purged_contents: &HashSet<ContentId>,
+) -> Result<Vec<String>> {
+    let cs_id = bcs.get_changeset_id();
+    let blobstore = repo.repo_blobstore().clone();
+    let purged_paths = with_parent_dirs(
+        changed_contents(bcs)
+            .filter(|(_path, content_id)| purged_contents.contains(content_id))
+            .map(|(path, _content_id)| path),
+    );
+    let changed_paths = with_parent_dirs(bcs.file_changes().map(|(path, _)| path));
+    let mut keys = Vec::new();
+
+    if let Some(hg_cs_id) = repo
+        .bonsai_hg_mapping()
+        .get_hg_from_bonsai(ctx, cs_id)
+        .await?
+    {
+        keys.push(hg_cs_id.blobstore_key());
+        let hg_cs = hg_cs_id.load(ctx, &blobstore).await?;
+        let entries = hg_cs
+            .manifestid()
+            .find_entries(ctx.clone(), blobstore.clone(), purged_paths.clone())
+            .try_collect::<Vec<_>>()
+            .await?;
+        keys.extend(entries.into_iter().map(|(_path, entry)| match entry {
+            Entry::Tree(manifest_id) => manifest_id.blobstore_key(),
+            Entry::Leaf((_file_type, filenode_id)) => filenode_id.blobstore_key(),
+        }));
+    }
+
+    let derived_data = repo.repo_derived_data();
+    if let Some(root) = derived_data
+        .fetch_derived::<RootUnodeManifestId>(ctx, cs_id)
+        .await?
+    {
+        let entries = root
+            .manifest_unode_id()
+            .find_entries(ctx.clone(), blobstore.clone(), changed_paths)
+            .try_collect::<Vec<_>>()
+            .await?;
+        keys.extend(entries.into_iter().map(|(_path, entry)| match entry {
+            Entry::Tree(manifest_unode_id) => manifest_unode_id.blobstore_key(),
+            Entry::Leaf(file_unode_id) => file_unode_id.blobstore_key(),
+        }));
+    }
+    if let Some(root) = derived_data
+        .fetch_derived::<RootFsnodeId>(ctx, cs_id)
+        .await?
+    {
+        let entries = root
+            .fsnode_id()
+            .find_entries(ctx.clone(), blobstore.clone(), purged_paths)
+            .try_collect::<Vec<_>>()
+            .await?;
+        keys.extend(entries.into_iter().filter_map(|(_path, entry)| match entry {
+            Entry::Tree(fsnode_id) => Some(fsnode_id.blobstore_key()),
+            Entry::Leaf(_) => None,
+        }));
+    }
+    let derivation_ctx = derived_data.manager().derivation_context(None);
+    keys.push(changeset_info::format_key(&derivation_ctx, cs_id));
+
+    Ok(keys)
+}
+
+/// Returns the chunks of the contents.
+async fn content_chunks<B: Blobstore>(
+    ctx: &CoreContext,
+    blobstore: &B,
+    contents: &HashSet<ContentId>,
+) -> Result<HashSet<ContentChunkId>> {
+    let chunks = stream::iter(contents.iter().copied())
+        .map(|content_id| async move {
+            match content_id.load(ctx, blobstore).await? {
+                FileContents::Chunked(chunked) => Ok(chunked
+                    .iter_chunks()
+                    .map(|chunk| chunk.chunk_id())
+                    .collect::<Vec<_>>()),
+                FileContents::Bytes(_) => Ok::<_, Error>(Vec::new()),
+            }
+        })
+        .buffer_unordered(100)
+        .try_collect::<Vec<_>>()
+        .await?;
+    Ok(chunks.into_iter().flatten().collect())
+}
+
+/// Returns the keys of the purged contents, their metadata and aliases, and
+/// of their chunks.
+///
+/// Chunks are addressed by their content, so a retained content that shares
+/// unchanged chunks with a purged one, such as a large file with a secret
+/// appended to it, still needs them.  Those chunks are kept.
+async fn content_keys<B: Blobstore>(
+    ctx: &CoreContext,
+    blobstore: &B,
+    purged_contents: &HashSet<ContentId>,
+    retained_contents: &HashSet<ContentId>,
+) -> Result<Vec<String>> {
+    let retained_chunks = content_chunks(ctx, blobstore, retained_contents).await?;
+    let purged_chunks = content_chunks(ctx, blobstore, purged_contents).await?;
+    let mut keys = purged_chunks
+        .difference(&retained_chunks)
+        .map(|chunk_id| chunk_id.blobstore_key())
+        .collect::<Vec<_>>();
+    for content_id in purged_contents.iter().copied() {
+        if let Some(metadata) = filestore::get_metadata_readonly(blobstore, ctx, content_id).await?
+        {
+            keys.push(Alias::Sha1(metadata.sha1).blobstore_key());
+            keys.push(Alias::Sha256(metadata.sha256).blobstore_key());
+            keys.push(Alias::GitSha1(metadata.git_sha1.sha1()).blobstore_key());
+        }
+        keys.push(ContentMetadataId::from(content_id).blobstore_key());
+        keys.push(content_id.blobstore_key());
+    }
+    Ok(keys)
+}
+
+/// Returns the raw blobstore keys for the commits, their hg changesets and
+/// derived data, and the contents of the files they change that no other
+/// commit refers to.
+async fn purged_keys(
+    ctx: &CoreContext,
+    repo: &Repo,
+    cs_ids: &[ChangesetId],
+) -> Result<Vec<String>> {
+    let purged = cs_ids.iter().copied().collect::<HashSet<_>>();
+    let mut bonsais = Vec::new();
+    for cs_id in cs_ids {
+        bonsais.push(
+            cs_id
+                .load(ctx, repo.repo_blobstore())
+                .await
+                .map_err(Error::from)?,
+        );
+    }
+    let retained = retained_contents(ctx, repo, &purged).await?;
+    let purged_contents = bonsais
+        .iter()
+        .flat_map(|bcs| changed_contents(bcs).map(|(_path, content_id)| content_id))
+        .filter(|content_id| !retained.contains(content_id))
+        .collect::<HashSet<_>>();
+
+    let mut keys = content_keys(ctx, repo.repo_blobstore(), &purged_contents, &retained).await?;
+    for bcs in bonsais.iter() {
+        keys.push(bcs.get_changeset_id().blobstore_key());
+        keys.extend(derived_keys(ctx, repo, bcs, &purged_contents).await?);
+    }
+
+    let prefix = repo.repo_identity().id().prefix();
+    keys.sort();
+    keys.dedup();
+    Ok(keys
+        .into_iter()
+        .map(|key| format!("{}{}", prefix, key))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use fbinit::FacebookInit;
+    use filestore::FilestoreConfig;
+    use filestore::StoreRequest;
+    use memblob::Memblob;
+
+    use super::*;
+
+    async fn store_chunked(
+        ctx: &CoreContext,
+        blobstore: &Memblob,
+        data: &'static str,
+    ) -> Result<ContentId> {
+        let config = FilestoreConfig {
+            chunk_size: Some(4),
+            chunking_threshold: None,
+            concurrency: 1,
+        };
+        let metadata = filestore::store(
+            blobstore,
+            config,
+            ctx,
+            &StoreRequest::new(data.len() as u64),
+            stream::once(future::ready(Ok(Bytes::from(data)))),
+        )
+        .await?;
+        Ok(metadata.content_id)
+    }
+
+    #[fbinit::test]
+    async fn test_content_keys_keep_shared_chunks(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let blobstore = Memblob::default();
+
+        // The purged content is the retained one with a secret appended, so
+        // they share their first chunk.
+        let retained = store_chunked(&ctx, &blobstore, "aaaabbbb").await?;
+        let purged = store_chunked(&ctx, &blobstore, "aaaabbbbcccc").await?;
+
+        let keys = content_keys(
+            &ctx,
+            &blobstore,
+            &HashSet::from([purged]),
+            &HashSet::from([retained]),
+        )
+        .await?;
+
+        let retained_chunks = content_chunks(&ctx, &blobstore, &HashSet::from([retained])).await?;
+        let purged_chunks = content_chunks(&ctx, &blobstore, &HashSet::from([purged])).await?;
+        assert_eq!(retained_chunks.len(), 2);
+        assert_eq!(purged_chunks.len(), 3);
+        for chunk_id in purged_chunks {
+            assert_eq!(
+                keys.contains(&chunk_id.blobstore_key()),
+                !retained_chunks.contains(&chunk_id),
+            );
+        }
+        assert!(keys.contains(&purged.blobstore_key()));
+        assert!(!keys.contains(&retained.blobstore_key()));
+
+        Ok(())
+    }
+}
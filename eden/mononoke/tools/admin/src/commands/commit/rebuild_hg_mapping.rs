@@ -0,0 +1,162 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Error;
+use anyhow::Result;
+use blobstore::Loadable;
+use bonsai_hg_mapping::BonsaiHgMapping;
+use bonsai_hg_mapping::BonsaiHgMappingEntry;
+use bonsai_hg_mapping::BonsaiHgMappingRef;
+use bonsai_hg_mapping::SqlBonsaiHgMappingBuilder;
+use cacheblob::MemWritesBlobstore;
+use changeset_fetcher::ChangesetFetcherArc;
+use clap::Args;
+use context::CoreContext;
+use derived_data_manager::BonsaiDerivable;
+use futures::compat::Stream01CompatExt;
+use futures::TryStreamExt;
+use mercurial_derived_data::MappedHgChangesetId;
+use metaconfig_types::RepoConfigRef;
+use mononoke_app::MononokeApp;
+use rendezvous::RendezVousOptions;
+use repo_blobstore::RepoBlobstore;
+use repo_blobstore::RepoBlobstoreRef;
+use repo_derived_data::RepoDerivedDataRef;
+use repo_identity::RepoIdentityRef;
+
+use super::Repo;
+use crate::commit_id::parse_commit_id;
+
+#[derive(Args)]
+pub struct CommitRebuildHgMappingArgs {
+    /// Bottom Commit ID of the range to rebuild
+    #[clap(long, short = 'b')]
+    bottom: String,
+
+    /// Top Commit ID of the range to rebuild
+    #[clap(long, short = 't')]
+    top: String,
+
+    /// Only report missing or incorrect mapping entries, don't repair them
+    #[clap(long)]
+    check_only: bool,
+}
+
+pub async fn rebuild_hg_mapping(
+    ctx: &CoreContext,
+    app: &MononokeApp,
+    repo: &Repo,
+    args: CommitRebuildHgMappingArgs,
+) -> Result<()> {
+    let bottom = parse_commit_id(ctx, repo, &args.bottom).await?;
+    let top = parse_commit_id(ctx, repo, &args.top).await?;
+
+    let mut csids =
+        revset::RangeNodeStream::new(ctx.clone(), repo.changeset_fetcher_arc(), bottom, top)
+            .compat()
+            .try_collect::<Vec<_>>()
+            .await?;
+    // Reverse so that parents are derived before their children.
+    csids.reverse();
+
+    let existing = repo
+        .bonsai_hg_mapping()
+        .get(ctx, csids.clone().into())
+        .await?
+        .into_iter()
+        .map(|entry| (entry.bcs_id, entry.hg_cs_id))
+        .collect::<HashMap<_, _>>();
+
+    // Incorrect entries can only be fixed by overwriting them.
+    let mapping = if args.check_only {
+        None
+    } else {
+        Some(
+            app.repo_factory()
+                .sql_factory(&repo.repo_config().storage_config.metadata)
+                .await?
+                .open::<SqlBonsaiHgMappingBuilder>()?
+                .with_overwrite()
+                .build(
+                    repo.repo_identity().id(),
+                    RendezVousOptions {
+                        free_connections: 5,
+                    },
+                ),
+        )
+    };
+
+    // Deriving hg changesets writes their blobs, so in check-only mode they
+    // are kept in memory instead.
+    let manager = if args.check_only {
+        let scratch_blobstore = RepoBlobstore::new_with_wrapped_inner_blobstore(
+            repo.repo_blobstore().clone(),
+            |blobstore| Arc::new(MemWritesBlobstore::new(blobstore)),
+        );
+        repo.repo_derived_data()
+            .manager()
+            .with_replaced_blobstore(scratch_blobstore)
+    } else {
+        repo.repo_derived_data().manager().clone()
+    };
+    let derivation_ctx = manager.derivation_context(None);
+    let mut derived = HashMap::new();
+    let mut broken = 0;
+    for csid in csids.iter().copied() {
+        let bonsai = csid
+            .load(ctx, repo.repo_blobstore())
+            .await
+            .map_err(Error::from)?;
+        // Parents outside of the range are taken from the mapping, so the
+        // range must start at a commit whose parents are mapped correctly.
+        let parents = derivation_ctx
+            .fetch_unknown_parents(ctx, Some(&derived), &bonsai)
+            .await?;
+        let hg_cs_id =
+            MappedHgChangesetId::derive_single(ctx, &derivation_ctx, bonsai, parents).await?;
+        let expected = hg_cs_id.hg_changeset_id();
+        derived.insert(csid, hg_cs_id);
+
+        match existing.get(&csid) {
+            Some(actual) if *actual == expected => continue,
+            Some(actual) => println!("{}: mapped to {}, expected {}", csid, actual, expected),
+            None => println!("{}: missing, expected {}", csid, expected),
+        }
+        broken += 1;
+
+        if let Some(mapping) = &mapping {
+            mapping
+                .add(
+                    ctx,
+                    BonsaiHgMappingEntry {
+                        hg_cs_id: expected,
+                        bcs_id: csid,
+                    },
+                )
+                .await?;
+        }
+    }
+
+    if args.check_only {
+        println!(
+            "Found {} broken mapping entries in {} commits",
+            broken,
+            csids.len()
+        );
+    } else {
+        println!(
+            "Repaired {} broken mapping entries in {} commits",
+            broken,
+            csids.len()
+        );
+    }
+
+    Ok(())
+}
@@ -27,6 +27,7 @@ use memcache::MemcacheClient;
 use metaconfig_types::RepoConfigRef;
 use mononoke_app::MononokeApp;
 use mononoke_types::ChangesetId;
+use mutable_counters::MutableCountersRef;
 use phases::ArcPhases;
 use phases::Phases;
 use rendezvous::RendezVousOptions;
@@ -36,11 +37,15 @@ use sql_commit_graph_storage::SqlCommitGraphStorageBuilder;
 use super::checkpoints::CommitGraphBackfillerCheckpoints;
 use super::Repo;
 
+/// Mutable counter holding the id of the last changeset that was backfilled.
+const CHECKPOINT_COUNTER: &str = "commit_graph_backfiller_last_finished_id";
+
 #[derive(Args)]
 pub struct BackfillArgs {
     /// Which id to start backfilling from. Use 0 if nothing is backfilled.
-    /// If not provided loads it from commit_graph_backfiller_checkpoints
-    /// (0 if not found).
+    /// If not provided loads it from the commit_graph_backfiller_last_finished_id
+    /// mutable counter, or from the commit_graph_backfiller_checkpoints table
+    /// it replaced (0 if not found).
     #[clap(long)]
     start_id: Option<u64>,
 
@@ -116,10 +121,19 @@ async fn backfill_impl(
 
     let start_id = match args.start_id {
         Some(start_id) => start_id,
-        None => checkpoints
-            .load_checkpoint(repo.repo_identity().id())
-            .await?
-            .map_or(0, |id| id.saturating_add(1)),
+        None => {
+            let last_finished_id = match repo
+                .mutable_counters()
+                .get_counter(ctx, CHECKPOINT_COUNTER)
+                .await?
+            {
+                Some(id) => Some(id as u64),
+                None => checkpoints
+                    .load_checkpoint(repo.repo_identity().id())
+                    .await?,
+            };
+            last_finished_id.map_or(0, |id| id.saturating_add(1))
+        }
     };
 
     fetcher
@@ -159,8 +173,8 @@ async fn backfill_impl(
             result?;
 
             if let Some(last_finished_id) = last_finished_id {
-                checkpoints
-                    .update_checkpoint(repo.repo_identity().id(), last_finished_id)
+                repo.mutable_counters()
+                    .set_counter(ctx, CHECKPOINT_COUNTER, last_finished_id as i64, None)
                     .await?;
             }
 
@@ -12,6 +12,9 @@ use sql_construct::SqlConstructFromMetadataDatabaseConfig;
 use sql_ext::mononoke_queries;
 use sql_ext::SqlConnections;
 
+/// Checkpoints written by the backfiller before it stored its position in a
+/// mutable counter.  They are only read, so that a backfill that was started
+/// before then can be resumed.
 pub struct CommitGraphBackfillerCheckpoints {
     connections: SqlConnections,
 }
@@ -33,16 +36,6 @@ mononoke_queries! {
     ) -> (Option<u64>) {
         "SELECT last_finished_id FROM commit_graph_backfiller_checkpoints WHERE repo_id={repo_id}"
     }
-
-    write UpdateCheckpoints(
-        values: (
-            repo_id: RepositoryId,
-            last_finished_id: u64,
-        ),
-    ) {
-        none,
-        "REPLACE INTO commit_graph_backfiller_checkpoints (repo_id, last_finished_id) VALUES {values}"
-    }
 }
 
 impl CommitGraphBackfillerCheckpoints {
@@ -53,19 +46,6 @@ impl CommitGraphBackfillerCheckpoints {
             .first()
             .and_then(|(last_finished_id,)| *last_finished_id))
     }
-
-    pub async fn update_checkpoint(
-        &self,
-        repo_id: RepositoryId,
-        last_finished_id: u64,
-    ) -> Result<()> {
-        UpdateCheckpoints::query(
-            &self.connections.write_connection,
-            &[(&repo_id, &last_finished_id)],
-        )
-        .await?;
-        Ok(())
-    }
 }
 
 impl SqlConstructFromMetadataDatabaseConfig for CommitGraphBackfillerCheckpoints {}
@@ -76,6 +56,18 @@ mod tests {
 
     use super::*;
 
+    mononoke_queries! {
+        write UpdateCheckpoints(
+            values: (
+                repo_id: RepositoryId,
+                last_finished_id: u64,
+            ),
+        ) {
+            none,
+            "REPLACE INTO commit_graph_backfiller_checkpoints (repo_id, last_finished_id) VALUES {values}"
+        }
+    }
+
     #[fbinit::test]
     async fn test_checkpoints(_fb: FacebookInit) -> Result<()> {
         let checkpoints = CommitGraphBackfillerCheckpoints::with_sqlite_in_memory()?;
@@ -86,7 +78,11 @@ mod tests {
         assert_eq!(checkpoints.load_checkpoint(first_repo_id).await?, None);
         assert_eq!(checkpoints.load_checkpoint(second_repo_id).await?, None);
 
-        checkpoints.update_checkpoint(first_repo_id, 100).await?;
+        UpdateCheckpoints::query(
+            &checkpoints.connections.write_connection,
+            &[(&first_repo_id, &100)],
+        )
+        .await?;
 
         assert_eq!(checkpoints.load_checkpoint(first_repo_id).await?, Some(100));
         assert_eq!(checkpoints.load_checkpoint(second_repo_id).await?, None);
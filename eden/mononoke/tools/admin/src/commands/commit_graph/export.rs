@@ -0,0 +1,129 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Error;
+use anyhow::Result;
+use clap::ArgEnum;
+use clap::Args;
+use commit_graph::CommitGraphRef;
+use context::CoreContext;
+use futures::future::try_join_all;
+use futures::stream;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use mononoke_types::ChangesetId;
+
+use super::Repo;
+use crate::commit_id::parse_commit_id;
+
+#[derive(ArgEnum, Copy, Clone, Eq, PartialEq)]
+pub enum ExportFormat {
+    /// Graphviz DOT, for visualization.
+    Dot,
+    /// CSV edge list with a `child,parent` header.  Commits without parents
+    /// are written with an empty parent column.
+    Csv,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Commit IDs to export ancestors of.
+    #[clap(long, use_value_delimiter = true)]
+    heads: Vec<String>,
+
+    /// Commit IDs to exclude ancestors of.
+    #[clap(long, use_value_delimiter = true)]
+    common: Vec<String>,
+
+    /// Format to export the graph in.
+    #[clap(long, arg_enum, default_value = "csv")]
+    format: ExportFormat,
+
+    /// File to write the graph to (defaults to stdout).
+    #[clap(long, short = 'o')]
+    output_file: Option<PathBuf>,
+}
+
+fn write_dot(output: &mut dyn Write, edges: &[(ChangesetId, Vec<ChangesetId>)]) -> Result<()> {
+    writeln!(output, "digraph commit_graph {{")?;
+    for (cs_id, parents) in edges {
+        writeln!(output, "  \"{}\";", cs_id)?;
+        for parent in parents {
+            writeln!(output, "  \"{}\" -> \"{}\";", cs_id, parent)?;
+        }
+    }
+    writeln!(output, "}}")?;
+    Ok(())
+}
+
+fn write_csv(output: &mut dyn Write, edges: &[(ChangesetId, Vec<ChangesetId>)]) -> Result<()> {
+    writeln!(output, "child,parent")?;
+    for (cs_id, parents) in edges {
+        if parents.is_empty() {
+            writeln!(output, "{},", cs_id)?;
+        }
+        for parent in parents {
+            writeln!(output, "{},{}", cs_id, parent)?;
+        }
+    }
+    Ok(())
+}
+
+pub(super) async fn export(ctx: &CoreContext, repo: &Repo, args: ExportArgs) -> Result<()> {
+    let heads: Vec<_> = try_join_all(
+        args.heads
+            .iter()
+            .map(|id| parse_commit_id(ctx, repo, id))
+            .collect::<Vec<_>>(),
+    )
+    .await?;
+    let common: Vec<_> = try_join_all(
+        args.common
+            .iter()
+            .map(|id| parse_commit_id(ctx, repo, id))
+            .collect::<Vec<_>>(),
+    )
+    .await?;
+
+    let cs_ids = repo
+        .commit_graph()
+        .ancestors_difference(ctx, heads, common)
+        .await?;
+
+    let edges: Vec<(ChangesetId, Vec<ChangesetId>)> = stream::iter(cs_ids)
+        .map(|cs_id| async move {
+            let parents = repo
+                .commit_graph()
+                .changeset_parents_required(ctx, cs_id)
+                .await?;
+            Ok::<_, Error>((cs_id, parents.to_vec()))
+        })
+        .buffered(100)
+        .try_collect()
+        .await?;
+
+    let mut output: Box<dyn Write> = match &args.output_file {
+        Some(path) => Box::new(BufWriter::new(File::create(path).with_context(|| {
+            format!("Failed to create output file {}", path.to_string_lossy())
+        })?)),
+        None => Box::new(std::io::stdout()),
+    };
+
+    match args.format {
+        ExportFormat::Dot => write_dot(&mut output, &edges)?,
+        ExportFormat::Csv => write_csv(&mut output, &edges)?,
+    }
+    output.flush()?;
+
+    Ok(())
+}
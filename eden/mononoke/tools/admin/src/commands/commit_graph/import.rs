@@ -0,0 +1,104 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use changesets_creation::save_changesets;
+use clap::Args;
+use context::CoreContext;
+use mononoke_types::BonsaiChangesetMut;
+use mononoke_types::ChangesetId;
+use mononoke_types::DateTime;
+use topo_sort::sort_topological;
+
+use super::Repo;
+
+#[derive(Args)]
+pub struct ImportArgs {
+    /// CSV edge list to import, in the format written by `export --format
+    /// csv`.  Node names may be arbitrary strings that don't contain commas.
+    #[clap(long, short = 'i')]
+    input_file: PathBuf,
+
+    /// Number of commits to save at a time.
+    #[clap(long, default_value_t = 1000)]
+    chunk_size: usize,
+}
+
+/// Parse a CSV edge list into a map from each node to its parents, in the
+/// order they were listed.  Nodes that only appear as parents become roots.
+fn parse_edge_list(input: impl BufRead) -> Result<BTreeMap<String, Vec<String>>> {
+    let mut parents: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (index, line) in input.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || (index == 0 && line == "child,parent") {
+            continue;
+        }
+        let (child, parent) = line
+            .split_once(',')
+            .ok_or_else(|| anyhow!("Invalid edge on line {}: '{}'", index + 1, line))?;
+        let (child, parent) = (child.trim(), parent.trim());
+        if child.is_empty() {
+            bail!("Missing child on line {}: '{}'", index + 1, line);
+        }
+        let child_parents = parents.entry(child.to_string()).or_default();
+        if !parent.is_empty() && !child_parents.iter().any(|p| p == parent) {
+            child_parents.push(parent.to_string());
+        }
+        if !parent.is_empty() {
+            parents.entry(parent.to_string()).or_default();
+        }
+    }
+    Ok(parents)
+}
+
+pub(super) async fn import(ctx: &CoreContext, repo: &Repo, args: ImportArgs) -> Result<()> {
+    let input = BufReader::new(File::open(&args.input_file).with_context(|| {
+        format!(
+            "Failed to open input file {}",
+            args.input_file.to_string_lossy()
+        )
+    })?);
+    let parents = parse_edge_list(input)?;
+    let sorted = sort_topological(&parents).ok_or_else(|| anyhow!("Edge list has a cycle"))?;
+
+    // Changeset ids are content hashes, so each node becomes a new empty
+    // commit whose message is the node's name.
+    let mut cs_ids: HashMap<String, ChangesetId> = HashMap::new();
+    for chunk in sorted.chunks(args.chunk_size.max(1)) {
+        let mut bonsais = Vec::with_capacity(chunk.len());
+        for name in chunk {
+            let bcs = BonsaiChangesetMut {
+                parents: parents[name].iter().map(|parent| cs_ids[parent]).collect(),
+                author: "commit_graph_import".to_string(),
+                author_date: DateTime::from_timestamp(0, 0)?,
+                message: name.clone(),
+                ..Default::default()
+            }
+            .freeze()?;
+            cs_ids.insert(name.clone(), bcs.get_changeset_id());
+            bonsais.push(bcs);
+        }
+        save_changesets(ctx, repo, bonsais).await?;
+    }
+
+    for name in sorted {
+        println!("{} {}", name, cs_ids[&name]);
+    }
+
+    Ok(())
+}
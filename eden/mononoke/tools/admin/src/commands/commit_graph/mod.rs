@@ -9,6 +9,9 @@ mod ancestors_difference;
 mod backfill;
 mod backfill_one;
 mod checkpoints;
+mod export;
+mod import;
+mod update_prefix_filter;
 
 use ancestors_difference::AncestorsDifferenceArgs;
 use anyhow::Result;
@@ -23,10 +26,15 @@ use changesets::Changesets;
 use clap::Parser;
 use clap::Subcommand;
 use commit_graph::CommitGraph;
+use export::ExportArgs;
+use import::ImportArgs;
 use metaconfig_types::RepoConfig;
 use mononoke_app::args::RepoArgs;
 use mononoke_app::MononokeApp;
+use mutable_counters::MutableCounters;
+use repo_blobstore::RepoBlobstore;
 use repo_identity::RepoIdentity;
+use update_prefix_filter::UpdatePrefixFilterArgs;
 
 #[derive(Parser)]
 pub struct CommandArgs {
@@ -45,6 +53,13 @@ pub enum CommitGraphSubcommand {
     BackfillOne(BackfillOneArgs),
     /// Display ids of all commits that are ancestors of one set of commits (heads), excluding ancestors of another set of commits (common).
     AncestorsDifference(AncestorsDifferenceArgs),
+    /// Export the ancestors of a set of commits as a DOT graph or a CSV edge list.
+    Export(ExportArgs),
+    /// Create empty commits with the shape of a CSV edge list, e.g. to build test repos.
+    Import(ImportArgs),
+    /// Update the persisted changeset id prefix filter with the commits
+    /// added since it was last updated.
+    UpdatePrefixFilter(UpdatePrefixFilterArgs),
 }
 
 #[facet::container]
@@ -64,6 +79,9 @@ pub struct Repo {
     #[facet]
     id: RepoIdentity,
 
+    #[facet]
+    repo_blobstore: RepoBlobstore,
+
     #[facet]
     bonsai_hg_mapping: dyn BonsaiHgMapping,
 
@@ -75,6 +93,9 @@ pub struct Repo {
 
     #[facet]
     bonsai_svnrev_mapping: dyn BonsaiSvnrevMapping,
+
+    #[facet]
+    mutable_counters: dyn MutableCounters,
 }
 
 pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
@@ -89,5 +110,10 @@ pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
         CommitGraphSubcommand::AncestorsDifference(args) => {
             ancestors_difference::ancestors_difference(&ctx, &repo, args).await
         }
+        CommitGraphSubcommand::Export(args) => export::export(&ctx, &repo, args).await,
+        CommitGraphSubcommand::Import(args) => import::import(&ctx, &repo, args).await,
+        CommitGraphSubcommand::UpdatePrefixFilter(args) => {
+            update_prefix_filter::update_prefix_filter(&ctx, &app, &repo, args).await
+        }
     }
 }
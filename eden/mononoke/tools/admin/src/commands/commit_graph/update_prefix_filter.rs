@@ -0,0 +1,60 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use clap::Args;
+use context::CoreContext;
+use futures_stats::TimedFutureExt;
+use metaconfig_types::RepoConfigRef;
+use mononoke_app::MononokeApp;
+use prefix_filter_commit_graph_storage::PrefixFilter;
+use rendezvous::RendezVousOptions;
+use repo_blobstore::RepoBlobstoreArc;
+use repo_identity::RepoIdentityRef;
+use sql_commit_graph_storage::SqlCommitGraphStorageBuilder;
+
+use super::Repo;
+
+#[derive(Args)]
+pub struct UpdatePrefixFilterArgs {
+    /// Rebuild the filter from scratch, rather than updating the persisted
+    /// one with the changesets added since it was persisted.
+    #[clap(long)]
+    rebuild: bool,
+}
+
+pub(super) async fn update_prefix_filter(
+    ctx: &CoreContext,
+    app: &MononokeApp,
+    repo: &Repo,
+    args: UpdatePrefixFilterArgs,
+) -> Result<()> {
+    let sql_storage = app
+        .repo_factory()
+        .sql_factory(&repo.repo_config().storage_config.metadata)
+        .await?
+        .open::<SqlCommitGraphStorageBuilder>()?
+        .build(
+            RendezVousOptions {
+                free_connections: 5,
+            },
+            repo.repo_identity().id(),
+        );
+    let prefix_filter = PrefixFilter::new(Arc::new(sql_storage), repo.repo_blobstore_arc());
+
+    let (stats, result) = prefix_filter
+        .update_persisted(ctx, args.rebuild)
+        .timed()
+        .await;
+    result?;
+
+    println!("Persisted changeset id prefix filter in {:?}", stats);
+
+    Ok(())
+}
@@ -0,0 +1,128 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use bookmarks::BookmarkKey;
+use bookmarks::BookmarkUpdateReason;
+use bookmarks::Bookmarks;
+use bookmarks::BookmarksRef;
+use changesets::Changesets;
+use changesets_creation::save_changesets;
+use clap::FromArgMatches;
+use clap::Parser;
+use metaconfig_parser::builder::add_repo_from_template;
+use mononoke_app::args::ConfigArgs;
+use mononoke_app::MononokeApp;
+use mononoke_types::BonsaiChangesetMut;
+use mononoke_types::DateTime;
+use repo_blobstore::RepoBlobstore;
+use repo_identity::RepoIdentity;
+use repos::RawRepoDefinition;
+use slog::info;
+
+/// Create a new repo, ready to be served
+///
+/// Adds the repo to the config directory, with a copy of a template repo
+/// config, then creates its storage and an initial empty commit for its
+/// bookmarks to point at.  Servers start serving the repo when they next
+/// load their configs.
+#[derive(Parser)]
+pub struct CommandArgs {
+    /// Name of the new repo
+    #[clap(long)]
+    repo_name: String,
+
+    /// Id of the new repo.  Defaults to one more than the largest id of any
+    /// repo in the config directory, including disabled repos.
+    #[clap(long)]
+    repo_id: Option<i32>,
+
+    /// Name of the repo config to copy as the new repo's config, e.g.
+    /// "small" or "large".  Template configs are kept in the `repos`
+    /// directory alongside the configs of repos.
+    #[clap(long)]
+    profile: String,
+
+    /// Bookmarks to create, pointing at the initial commit
+    #[clap(long, default_value = "master", use_value_delimiter = true)]
+    bookmarks: Vec<String>,
+}
+
+#[facet::container]
+pub struct Repo {
+    #[facet]
+    repo_identity: RepoIdentity,
+
+    #[facet]
+    repo_blobstore: RepoBlobstore,
+
+    #[facet]
+    changesets: dyn Changesets,
+
+    #[facet]
+    bookmarks: dyn Bookmarks,
+}
+
+pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
+    let ctx = app.new_basic_context();
+
+    let config_path = ConfigArgs::from_arg_matches(app.matches())?.config_path();
+    let definition = RawRepoDefinition {
+        repo_id: args.repo_id,
+        enabled: Some(true),
+        ..Default::default()
+    };
+    let repo_configs =
+        add_repo_from_template(&config_path, &args.repo_name, definition, &args.profile)
+            .with_context(|| format!("Failed to add repo to configs in {}", config_path))?;
+    let repo_config = repo_configs
+        .repos
+        .get(&args.repo_name)
+        .cloned()
+        .ok_or_else(|| anyhow!("Repo {} is missing from configs", args.repo_name))?;
+    info!(
+        ctx.logger(),
+        "Added repo {} with id {} to configs", args.repo_name, repo_config.repoid
+    );
+    let repo: Repo = app
+        .create_repo_from_config(args.repo_name.clone(), repo_config)
+        .await
+        .context("Failed to create repo storage")?;
+
+    if args.bookmarks.is_empty() {
+        return Ok(());
+    }
+    let bcs = BonsaiChangesetMut {
+        author: "mononoke".to_string(),
+        author_date: DateTime::now(),
+        message: "Initial commit".to_string(),
+        ..Default::default()
+    }
+    .freeze()?;
+    let cs_id = bcs.get_changeset_id();
+    save_changesets(&ctx, &repo, vec![bcs]).await?;
+
+    let mut transaction = repo.bookmarks().create_transaction(ctx.clone());
+    for name in &args.bookmarks {
+        let key = BookmarkKey::new(name)?;
+        transaction.create(&key, cs_id, BookmarkUpdateReason::ManualMove)?;
+    }
+    if !transaction.commit().await? {
+        bail!("Failed to create bookmarks");
+    }
+    info!(
+        ctx.logger(),
+        "Created bookmarks {} at initial commit {}",
+        args.bookmarks.join(", "),
+        cs_id
+    );
+
+    Ok(())
+}
@@ -5,6 +5,8 @@
  * GNU General Public License version 2.
  */
 
+mod audit;
+mod dedupe_lfs;
 mod fetch;
 mod is_chunked;
 mod metadata;
@@ -14,6 +16,7 @@ mod verify;
 use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Result;
+use audit::FilestoreAuditArgs;
 use bonsai_git_mapping::BonsaiGitMapping;
 use bonsai_globalrev_mapping::BonsaiGlobalrevMapping;
 use bonsai_hg_mapping::BonsaiHgMapping;
@@ -22,12 +25,14 @@ use clap::ArgGroup;
 use clap::Args;
 use clap::Parser;
 use clap::Subcommand;
+use dedupe_lfs::FilestoreDedupeLfsArgs;
 use ephemeral_blobstore::RepoEphemeralStore;
 use fetch::FilestoreFetchArgs;
 use filestore::Alias;
 use filestore::FetchKey;
 use filestore::FilestoreConfig;
 use is_chunked::FilestoreIsChunkedArgs;
+use metaconfig_types::RepoConfig;
 use metadata::FilestoreMetadataArgs;
 use mononoke_app::args::RepoArgs;
 use mononoke_app::MononokeApp;
@@ -35,6 +40,8 @@ use mononoke_types::hash::Sha1;
 use mononoke_types::hash::Sha256;
 use mononoke_types::ContentId;
 use repo_blobstore::RepoBlobstore;
+use repo_derived_data::RepoDerivedData;
+use repo_identity::RepoIdentity;
 use store::FilestoreStoreArgs;
 use verify::FilestoreVerifyArgs;
 
@@ -68,8 +75,17 @@ pub struct Repo {
     #[facet]
     repo_ephemeral_store: RepoEphemeralStore,
 
+    #[facet]
+    repo_derived_data: RepoDerivedData,
+
     #[facet]
     filestore_config: FilestoreConfig,
+
+    #[facet]
+    repo_config: RepoConfig,
+
+    #[facet]
+    repo_identity: RepoIdentity,
 }
 
 #[derive(Subcommand)]
@@ -84,6 +100,11 @@ pub enum FilestoreSubcommand {
     IsChunked(FilestoreIsChunkedArgs),
     /// Verify a file is fetchable by all of its id types
     Verify(FilestoreVerifyArgs),
+    /// Move LFS objects to storage shared with other repos
+    DedupeLfs(FilestoreDedupeLfsArgs),
+    /// Report deduplication and chunking stats for the files of a commit,
+    /// and verify a sample of their contents
+    Audit(FilestoreAuditArgs),
 }
 
 #[derive(Args)]
@@ -133,6 +154,10 @@ pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
         FilestoreSubcommand::Verify(verify_args) => {
             verify::verify(&ctx, &repo, verify_args).await?
         }
+        FilestoreSubcommand::DedupeLfs(dedupe_args) => {
+            dedupe_lfs::dedupe_lfs(&ctx, &app, &repo, dedupe_args).await?
+        }
+        FilestoreSubcommand::Audit(audit_args) => audit::audit(&ctx, &repo, audit_args).await?,
     }
 
     Ok(())
@@ -0,0 +1,289 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use blobstore::Blobstore;
+use blobstore::Loadable;
+use blobstore::LoadableError;
+use clap::Args;
+use context::CoreContext;
+use filestore::FetchKey;
+use filestore::FilestoreConfigRef;
+use fsnodes::RootFsnodeId;
+use futures::stream;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use manifest::ManifestOps;
+use mononoke_types::BlobstoreKey;
+use mononoke_types::ContentChunkId;
+use mononoke_types::ContentId;
+use mononoke_types::ContentMetadataId;
+use mononoke_types::FileContents;
+use repo_blobstore::RepoBlobstoreArc;
+use repo_blobstore::RepoBlobstoreRef;
+use repo_derived_data::RepoDerivedDataRef;
+use repo_identity::RepoIdentityRef;
+
+use super::Repo;
+use crate::commit_id::parse_commit_id;
+
+#[derive(Args)]
+pub struct FilestoreAuditArgs {
+    /// Commit whose files are audited
+    #[clap(long, short = 'i')]
+    commit_id: String,
+
+    /// Verify the contents of one in this many distinct files, chosen by
+    /// content id so that runs are repeatable
+    #[clap(long, default_value_t = 100)]
+    sample_rate: u64,
+
+    /// How many contents to verify concurrently
+    #[clap(long, default_value_t = 50)]
+    concurrency: usize,
+
+    /// File to write the keys of corrupt or missing blobs to, one per line,
+    /// for the healer to repair
+    #[clap(long, value_name = "FILE", parse(from_os_str))]
+    corrupt_keys_output: Option<PathBuf>,
+}
+
+/// Outcome of verifying a single content.
+enum Verification {
+    Valid,
+    /// The content does not hash to its id.
+    CorruptContent,
+    /// The content is valid, but its stored metadata doesn't describe it.
+    CorruptMetadata,
+    Missing,
+    MissingChunks(Vec<ContentChunkId>),
+    Failed(anyhow::Error),
+}
+
+/// How a sampled content is stored.
+enum Chunking {
+    Unchunked { size: u64 },
+    Chunked { chunk_sizes: Vec<u64> },
+}
+
+async fn verify_content(
+    ctx: &CoreContext,
+    repo: &Repo,
+    content_id: ContentId,
+) -> Result<(Verification, Option<Chunking>)> {
+    let blobstore = repo.repo_blobstore();
+    let (chunking, chunk_ids) = match content_id.load(ctx, blobstore).await {
+        Ok(FileContents::Bytes(bytes)) => {
+            let size = bytes.len() as u64;
+            (Chunking::Unchunked { size }, Vec::new())
+        }
+        Ok(FileContents::Chunked(chunked)) => {
+            let chunk_sizes = chunked.iter_chunks().map(|chunk| chunk.size()).collect();
+            let chunk_ids = chunked
+                .iter_chunks()
+                .map(|chunk| chunk.chunk_id())
+                .collect::<Vec<_>>();
+            (Chunking::Chunked { chunk_sizes }, chunk_ids)
+        }
+        Err(LoadableError::Missing(_)) => return Ok((Verification::Missing, None)),
+        Err(LoadableError::Error(e)) => return Ok((Verification::Failed(e), None)),
+    };
+
+    // Missing chunks would otherwise only show up as a failure to read the
+    // content.
+    let missing_chunks = stream::iter(chunk_ids)
+        .map(|chunk_id| async move {
+            let present = blobstore
+                .is_present(ctx, &chunk_id.blobstore_key())
+                .await?
+                .fail_if_unsure()?;
+            anyhow::Ok((!present).then_some(chunk_id))
+        })
+        .buffered(10)
+        .try_filter_map(|chunk_id| async move { Ok(chunk_id) })
+        .try_collect::<Vec<_>>()
+        .await;
+    match missing_chunks {
+        Ok(missing_chunks) if missing_chunks.is_empty() => {}
+        Ok(missing_chunks) => {
+            return Ok((Verification::MissingChunks(missing_chunks), Some(chunking)));
+        }
+        Err(e) => return Ok((Verification::Failed(e), Some(chunking))),
+    }
+
+    let recomputed = match filestore::recompute_metadata(blobstore, ctx, content_id).await {
+        Ok(Some(recomputed)) => recomputed,
+        Ok(None) => return Ok((Verification::Missing, Some(chunking))),
+        Err(e) => return Ok((Verification::Failed(e), Some(chunking))),
+    };
+    if recomputed.content_id != content_id {
+        return Ok((Verification::CorruptContent, Some(chunking)));
+    }
+    let stored =
+        filestore::get_metadata_readonly(blobstore, ctx, &FetchKey::Canonical(content_id))
+            .await?
+            .flatten();
+    let verification = match stored {
+        Some(stored) if stored == recomputed => Verification::Valid,
+        _ => Verification::CorruptMetadata,
+    };
+    Ok((verification, Some(chunking)))
+}
+
+fn percent(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / whole as f64
+    }
+}
+
+pub async fn audit(ctx: &CoreContext, repo: &Repo, audit_args: FilestoreAuditArgs) -> Result<()> {
+    let cs_id = parse_commit_id(ctx, repo, &audit_args.commit_id).await?;
+    let root_fsnode_id = repo
+        .repo_derived_data()
+        .derive::<RootFsnodeId>(ctx, cs_id)
+        .await?;
+
+    // Files with the same content share a single stored copy of it.
+    let mut file_count = 0u64;
+    let mut total_bytes = 0u64;
+    let mut contents = HashMap::new();
+    let mut entries = root_fsnode_id
+        .fsnode_id()
+        .list_leaf_entries(ctx.clone(), repo.repo_blobstore_arc());
+    while let Some((_path, fsnode_file)) = entries.try_next().await? {
+        file_count += 1;
+        total_bytes += fsnode_file.size();
+        contents.insert(*fsnode_file.content_id(), fsnode_file.size());
+    }
+    let unique_bytes = contents.values().sum::<u64>();
+
+    println!("files: {}", file_count);
+    println!("distinct contents: {}", contents.len());
+    println!("total bytes: {}", total_bytes);
+    println!("distinct bytes: {}", unique_bytes);
+    println!(
+        "duplicate bytes: {:.2}%",
+        percent(total_bytes - unique_bytes, total_bytes)
+    );
+
+    let sample_rate = audit_args.sample_rate.max(1);
+    let sample = contents
+        .keys()
+        .copied()
+        .filter(|content_id| {
+            let prefix = content_id.as_ref()[..8].try_into().expect("ids are 32 bytes");
+            u64::from_le_bytes(prefix) % sample_rate == 0
+        })
+        .collect::<Vec<_>>();
+    let results = stream::iter(sample)
+        .map(|content_id| async move {
+            let result = verify_content(ctx, repo, content_id).await;
+            (content_id, result)
+        })
+        .buffer_unordered(audit_args.concurrency)
+        .collect::<Vec<_>>()
+        .await;
+
+    let chunk_size = repo.filestore_config().chunk_size;
+    let (mut valid, mut corrupt, mut missing, mut failed) = (0, 0, 0, 0);
+    let (mut chunked, mut chunk_count, mut chunked_bytes) = (0u64, 0u64, 0u64);
+    let mut oversized_unchunked = 0;
+    let mut corrupt_keys = Vec::new();
+    for (content_id, result) in results.iter() {
+        let (verification, chunking) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("{}: failed: {:#}", content_id, e);
+                failed += 1;
+                continue;
+            }
+        };
+        match chunking {
+            Some(Chunking::Chunked { chunk_sizes }) => {
+                chunked += 1;
+                chunk_count += chunk_sizes.len() as u64;
+                chunked_bytes += chunk_sizes.iter().sum::<u64>();
+            }
+            Some(Chunking::Unchunked { size }) => {
+                if chunk_size.map_or(false, |chunk_size| *size > chunk_size) {
+                    oversized_unchunked += 1;
+                }
+            }
+            None => {}
+        }
+        match verification {
+            Verification::Valid => valid += 1,
+            Verification::CorruptContent => {
+                eprintln!("{}: content is corrupt", content_id);
+                corrupt += 1;
+                corrupt_keys.push(content_id.blobstore_key());
+            }
+            Verification::CorruptMetadata => {
+                eprintln!("{}: metadata does not match content", content_id);
+                corrupt += 1;
+                corrupt_keys.push(ContentMetadataId::from(*content_id).blobstore_key());
+            }
+            Verification::Missing => {
+                eprintln!("{}: content is missing", content_id);
+                missing += 1;
+                corrupt_keys.push(content_id.blobstore_key());
+            }
+            Verification::MissingChunks(chunk_ids) => {
+                eprintln!("{}: {} chunks are missing", content_id, chunk_ids.len());
+                missing += 1;
+                corrupt_keys.extend(chunk_ids.iter().map(|chunk_id| chunk_id.blobstore_key()));
+            }
+            Verification::Failed(e) => {
+                eprintln!("{}: failed: {:#}", content_id, e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!("verified: {}", results.len());
+    println!("valid: {}", valid);
+    println!("corrupt: {}", corrupt);
+    println!("missing: {}", missing);
+    println!("failed: {}", failed);
+    println!("chunked: {}", chunked);
+    if chunked > 0 {
+        println!(
+            "average chunks per chunked content: {:.2}",
+            chunk_count as f64 / chunked as f64
+        );
+        if let Some(chunk_size) = chunk_size {
+            println!(
+                "chunk fill: {:.2}%",
+                percent(chunked_bytes, chunk_count * chunk_size)
+            );
+        }
+    }
+    println!(
+        "unchunked contents larger than the chunk size: {}",
+        oversized_unchunked
+    );
+
+    if let Some(path) = audit_args.corrupt_keys_output {
+        // The healer works on keys as they are stored, including the prefix
+        // of the repo.
+        let prefix = repo.repo_identity().id().prefix();
+        let output = corrupt_keys
+            .iter()
+            .map(|key| format!("{}{}\n", prefix, key))
+            .collect::<String>();
+        std::fs::write(&path, output)
+            .with_context(|| format!("Failed to write corrupt keys to {}", path.display()))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,139 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::Arc;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use blobstore::BlobstoreUnlinkOps;
+use clap::Args;
+use context::CoreContext;
+use filestore::Alias;
+use filestore::FetchKey;
+use filestore::FilestoreConfigRef;
+use futures::stream;
+use futures::StreamExt;
+use lfs_shared_store::MigratedContent;
+use lfs_shared_store::SharedLfsStore;
+use metaconfig_types::RepoConfigRef;
+use mononoke_app::MononokeApp;
+use mononoke_types::hash::Sha256;
+use repo_blobstore::RepoBlobstoreRef;
+use repo_identity::RepoIdentityRef;
+
+use super::Repo;
+use crate::commands::blobstore_unlink::get_all_blobstores;
+
+#[derive(Args)]
+pub struct FilestoreDedupeLfsArgs {
+    /// File with whitespace separated SHA256 hashes of the LFS objects to move
+    /// to storage shared with other repos
+    #[clap(long)]
+    oids_file: String,
+
+    /// How many objects to move concurrently
+    #[clap(long, default_value_t = 50)]
+    concurrency: usize,
+}
+
+/// Delete the repo's own copy of content that was moved to the shared store
+/// from every blobstore holding the repo's blobs.
+async fn delete_local_copy(
+    ctx: &CoreContext,
+    repo: &Repo,
+    blobstores: &[Arc<dyn BlobstoreUnlinkOps>],
+    migrated: &MigratedContent,
+) -> Result<()> {
+    let prefix = repo.repo_identity().id().prefix();
+    for blobstore in blobstores {
+        for key in migrated.local_keys.iter() {
+            let key = format!("{}{}", prefix, key);
+            // Multiplexed blobstores may not have every key in every inner
+            // blobstore, and a previous run may already have deleted some.
+            if blobstore
+                .is_present(ctx, &key)
+                .await?
+                .assume_not_found_if_unsure()
+            {
+                blobstore
+                    .unlink(ctx, &key)
+                    .await
+                    .with_context(|| format!("Failed to unlink {}", key))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn dedupe_lfs(
+    ctx: &CoreContext,
+    app: &MononokeApp,
+    repo: &Repo,
+    dedupe_args: FilestoreDedupeLfsArgs,
+) -> Result<()> {
+    // Only repos using the shared store read from it, so the repo's own
+    // copy can't be deleted unless it does.
+    if !repo.repo_config().lfs.shared_storage {
+        bail!("The repo doesn't store its LFS content in the shared store");
+    }
+    let text = std::fs::read_to_string(dedupe_args.oids_file).context("Reading oids file")?;
+    let oids = text
+        .split_whitespace()
+        .map(|oid| {
+            oid.parse::<Sha256>()
+                .with_context(|| format!("Invalid oid: {}", oid))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let blobstores = get_all_blobstores(
+        app.fb,
+        repo.repo_config().storage_config.clone(),
+        app.environment().readonly_storage,
+        &app.environment().blobstore_options,
+        app.config_store(),
+    )
+    .await?;
+    let blobstores = &blobstores;
+    let shared_store = SharedLfsStore::new(repo.repo_blobstore(), *repo.filestore_config());
+    let shared_store = &shared_store;
+    let results = stream::iter(oids)
+        .map(|oid| async move {
+            let key = FetchKey::Aliased(Alias::Sha256(oid));
+            let result = async {
+                let migrated = shared_store.migrate(ctx, &key).await?;
+                if let Some(migrated) = &migrated {
+                    delete_local_copy(ctx, repo, blobstores, migrated).await?;
+                }
+                anyhow::Ok(migrated)
+            }
+            .await;
+            (oid, result)
+        })
+        .buffer_unordered(dedupe_args.concurrency)
+        .collect::<Vec<_>>()
+        .await;
+
+    let (mut migrated, mut missing, mut failed) = (0, 0, 0);
+    for (oid, result) in results {
+        match result {
+            Ok(Some(_)) => migrated += 1,
+            Ok(None) => {
+                eprintln!("{}: not found in repo", oid);
+                missing += 1;
+            }
+            Err(e) => {
+                eprintln!("{}: failed: {:#}", oid, e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!("migrated: {}\nmissing: {}\nfailed: {}", migrated, missing, failed);
+
+    Ok(())
+}
@@ -0,0 +1,88 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+mod tail;
+
+use anyhow::Context;
+use anyhow::Result;
+use bonsai_hg_mapping::BonsaiHgMapping;
+use bookmarks::Bookmarks;
+use changesets::Changesets;
+use clap::Parser;
+use clap::Subcommand;
+use commit_graph::CommitGraph;
+use filestore::FilestoreConfig;
+use metaconfig_types::RepoConfig;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::MononokeApp;
+use repo_blobstore::RepoBlobstore;
+use repo_identity::RepoIdentity;
+
+use self::tail::MirrorTailArgs;
+
+/// Replicate mirror repos from their upstream
+#[derive(Parser)]
+pub struct CommandArgs {
+    /// The mirror repo
+    #[clap(flatten)]
+    repo_args: RepoArgs,
+
+    #[clap(subcommand)]
+    subcommand: MirrorSubcommand,
+}
+
+#[facet::container]
+pub struct Repo {
+    #[facet]
+    repo_identity: RepoIdentity,
+
+    #[facet]
+    repo_config: RepoConfig,
+
+    #[facet]
+    repo_blobstore: RepoBlobstore,
+
+    #[facet]
+    filestore_config: FilestoreConfig,
+
+    #[facet]
+    changesets: dyn Changesets,
+
+    #[facet]
+    commit_graph: CommitGraph,
+
+    #[facet]
+    bonsai_hg_mapping: dyn BonsaiHgMapping,
+
+    #[facet]
+    bookmarks: dyn Bookmarks,
+}
+
+#[derive(Subcommand)]
+pub enum MirrorSubcommand {
+    /// Replicate new commits and bookmark moves from the upstream
+    ///
+    /// Fetches the public bookmarks of the upstream repo, imports the
+    /// commits that the mirror doesn't have yet as commit bundles, then
+    /// moves the mirror's bookmarks to match the upstream.
+    Tail(MirrorTailArgs),
+}
+
+pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
+    let ctx = app.new_basic_context();
+
+    let repo: Repo = app
+        .open_repo(&args.repo_args)
+        .await
+        .context("Failed to open mirror repo")?;
+
+    match args.subcommand {
+        MirrorSubcommand::Tail(tail_args) => tail::tail(&ctx, &repo, tail_args).await?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,293 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use bookmarks::BookmarkCategory;
+use bookmarks::BookmarkKey;
+use bookmarks::BookmarkKind;
+use bookmarks::BookmarkPagination;
+use bookmarks::BookmarkPrefix;
+use bookmarks::BookmarkUpdateReason;
+use bookmarks::BookmarksRef;
+use bookmarks::Freshness;
+use bytes::Bytes;
+use changesets::ChangesetsRef;
+use clap::Args;
+use commit_bundle::import_bundle;
+use context::CoreContext;
+use futures::TryStreamExt;
+use hyper::client::HttpConnector;
+use hyper::Body;
+use hyper::Client;
+use hyper::Request;
+use hyper::Uri;
+use hyper_openssl::HttpsConnector;
+use metaconfig_types::RepoConfigRef;
+use mononoke_types::ChangesetId;
+use openssl::ssl::SslConnector;
+use openssl::ssl::SslFiletype;
+use openssl::ssl::SslMethod;
+use repo_identity::RepoIdentityRef;
+use serde::Serialize;
+use slog::info;
+use slog::warn;
+
+use super::Repo;
+
+#[derive(Args)]
+pub struct MirrorTailArgs {
+    /// Keep replicating at this interval, in seconds, instead of
+    /// replicating once
+    #[clap(long)]
+    interval_secs: Option<u64>,
+
+    /// Maximum number of commits to fetch from the upstream per bundle
+    #[clap(long, default_value_t = 1000)]
+    limit: usize,
+
+    /// Number of commits to save at a time when importing a bundle
+    #[clap(long, default_value_t = 100)]
+    chunk_size: usize,
+
+    /// TLS certificate that identifies the mirror to the upstream
+    #[clap(long)]
+    tls_certificate: String,
+
+    /// TLS private key for the certificate
+    #[clap(long)]
+    tls_private_key: String,
+
+    /// TLS CA to verify the upstream with, instead of the system default
+    #[clap(long)]
+    tls_ca: Option<String>,
+}
+
+#[derive(Serialize)]
+struct BundleRequest<'a> {
+    heads: &'a [ChangesetId],
+    common: &'a [ChangesetId],
+    limit: usize,
+}
+
+/// Client for the mirror endpoints of the upstream's EdenAPI server.
+struct Upstream {
+    client: Client<HttpsConnector<HttpConnector>>,
+    repo_url: String,
+}
+
+impl Upstream {
+    /// Connect to the upstream, identifying as the mirror with the TLS
+    /// certificate in the arguments, as the upstream only serves mirrors
+    /// that can read the whole repo.
+    fn new(upstream_url: &str, repo_name: &str, args: &MirrorTailArgs) -> Result<Self> {
+        let mut ssl = SslConnector::builder(SslMethod::tls())?;
+        ssl.set_certificate_chain_file(&args.tls_certificate)
+            .with_context(|| format!("Failed to load certificate {}", args.tls_certificate))?;
+        ssl.set_private_key_file(&args.tls_private_key, SslFiletype::PEM)
+            .with_context(|| format!("Failed to load private key {}", args.tls_private_key))?;
+        if let Some(tls_ca) = &args.tls_ca {
+            ssl.set_ca_file(tls_ca)
+                .with_context(|| format!("Failed to load CA {}", tls_ca))?;
+        }
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+        let connector = HttpsConnector::with_connector(http, ssl)?;
+        let client = Client::builder().build(connector);
+        Ok(Self {
+            client,
+            repo_url: format!("{}/{}", upstream_url.trim_end_matches('/'), repo_name),
+        })
+    }
+
+    fn uri(&self, path: &str) -> Result<Uri> {
+        let uri = format!("{}/{}", self.repo_url, path);
+        uri.parse()
+            .with_context(|| format!("Invalid upstream URL {}", uri))
+    }
+
+    async fn send(&self, request: Request<Body>) -> Result<Bytes> {
+        let uri = request.uri().clone();
+        let response = self
+            .client
+            .request(request)
+            .await
+            .with_context(|| format!("Request to {} failed", uri))?;
+        let (head, body) = response.into_parts();
+        let body = hyper::body::to_bytes(body)
+            .await
+            .with_context(|| format!("Failed to read response from {}", uri))?;
+        if !head.status.is_success() {
+            bail!(
+                "Request to {} failed with status {}: {}",
+                uri,
+                head.status,
+                String::from_utf8_lossy(&body)
+            );
+        }
+        Ok(body)
+    }
+
+    async fn bookmarks(&self) -> Result<BTreeMap<String, ChangesetId>> {
+        let request = Request::get(self.uri("mirror/bookmarks")?).body(Body::empty())?;
+        let body = self.send(request).await?;
+        serde_json::from_slice(&body).context("Failed to parse upstream bookmarks")
+    }
+
+    async fn bundle(
+        &self,
+        heads: &[ChangesetId],
+        common: &[ChangesetId],
+        limit: usize,
+    ) -> Result<Bytes> {
+        let body = serde_json::to_vec(&BundleRequest {
+            heads,
+            common,
+            limit,
+        })?;
+        let request = Request::post(self.uri("mirror/bundle")?).body(Body::from(body))?;
+        self.send(request).await
+    }
+}
+
+/// The commits in a page that are not parents of other commits in it.
+async fn page_heads(
+    ctx: &CoreContext,
+    repo: &Repo,
+    imported: &[ChangesetId],
+) -> Result<Vec<ChangesetId>> {
+    let entries = repo.changesets().get_many(ctx, imported.to_vec()).await?;
+    let parents: HashSet<ChangesetId> = entries
+        .into_iter()
+        .flat_map(|entry| entry.parents)
+        .collect();
+    Ok(imported
+        .iter()
+        .copied()
+        .filter(|cs_id| !parents.contains(cs_id))
+        .collect())
+}
+
+async fn local_bookmarks(
+    ctx: &CoreContext,
+    repo: &Repo,
+) -> Result<HashMap<BookmarkKey, ChangesetId>> {
+    repo.bookmarks()
+        .list(
+            ctx.clone(),
+            Freshness::MostRecent,
+            &BookmarkPrefix::empty(),
+            &[BookmarkCategory::Branch],
+            BookmarkKind::ALL_PUBLISHING,
+            &BookmarkPagination::FromStart,
+            std::u64::MAX,
+        )
+        .map_ok(|(bookmark, cs_id)| (bookmark.into_key(), cs_id))
+        .try_collect()
+        .await
+}
+
+/// Replicate the commits and bookmarks of the upstream once.
+async fn tail_once(
+    ctx: &CoreContext,
+    repo: &Repo,
+    upstream: &Upstream,
+    args: &MirrorTailArgs,
+) -> Result<()> {
+    let upstream_bookmarks = upstream
+        .bookmarks()
+        .await?
+        .into_iter()
+        .map(|(name, cs_id)| Ok((BookmarkKey::new(name)?, cs_id)))
+        .collect::<Result<HashMap<_, _>>>()?;
+    let local_bookmarks = local_bookmarks(ctx, repo).await?;
+
+    // Fetch bundles until the mirror has all the upstream's commits.  Each
+    // bundle contains the complete ancestry of its commits that the mirror
+    // doesn't have, so its heads can be added to the common commits.
+    let heads: Vec<ChangesetId> = upstream_bookmarks.values().copied().collect();
+    let mut common: Vec<ChangesetId> = local_bookmarks.values().copied().collect();
+    let mut total = 0;
+    loop {
+        let bundle = upstream.bundle(&heads, &common, args.limit).await?;
+        let imported = import_bundle(ctx, repo, bundle.as_ref(), args.chunk_size).await?;
+        if imported.is_empty() {
+            break;
+        }
+        total += imported.len();
+        info!(ctx.logger(), "Imported {} commits from upstream", imported.len());
+        common.extend(page_heads(ctx, repo, &imported).await?);
+    }
+
+    let mut transaction = repo.bookmarks().create_transaction(ctx.clone());
+    let mut moved = 0;
+    for (key, cs_id) in upstream_bookmarks.iter() {
+        match local_bookmarks.get(key) {
+            Some(old) if old == cs_id => continue,
+            Some(old) => transaction.update(key, *cs_id, *old, BookmarkUpdateReason::Mirror)?,
+            None => transaction.create(key, *cs_id, BookmarkUpdateReason::Mirror)?,
+        }
+        moved += 1;
+    }
+    for (key, old) in local_bookmarks.iter() {
+        if !upstream_bookmarks.contains_key(key) {
+            transaction.delete(key, *old, BookmarkUpdateReason::Mirror)?;
+            moved += 1;
+        }
+    }
+    if moved > 0 && !transaction.commit().await? {
+        bail!("Bookmarks of the mirror were moved while replicating");
+    }
+
+    info!(
+        ctx.logger(),
+        "Replicated {} commits and {} bookmark moves to {}",
+        total,
+        moved,
+        repo.repo_identity().name()
+    );
+    Ok(())
+}
+
+pub async fn tail(ctx: &CoreContext, repo: &Repo, args: MirrorTailArgs) -> Result<()> {
+    let mirror_config = repo
+        .repo_config()
+        .mirror_config
+        .as_ref()
+        .ok_or_else(|| {
+            anyhow!(
+                "{} is not configured as a mirror repo",
+                repo.repo_identity().name()
+            )
+        })?;
+    let upstream_repo_name = mirror_config
+        .upstream_repo_name
+        .as_deref()
+        .unwrap_or_else(|| repo.repo_identity().name());
+    let upstream = Upstream::new(&mirror_config.upstream_url, upstream_repo_name, &args)?;
+
+    let interval = match args.interval_secs {
+        Some(interval_secs) => Duration::from_secs(interval_secs),
+        None => return tail_once(ctx, repo, &upstream, &args).await,
+    };
+
+    loop {
+        // When replicating continuously, failures are logged and retried
+        // at the next interval.
+        if let Err(err) = tail_once(ctx, repo, &upstream, &args).await {
+            warn!(ctx.logger(), "Mirror replication failed: {:?}", err);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
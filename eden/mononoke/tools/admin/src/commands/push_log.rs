@@ -0,0 +1,114 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Context;
+use anyhow::Result;
+use clap::Parser;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::MononokeApp;
+use mononoke_types::DateTime;
+use mononoke_types::Timestamp;
+use push_log::PushLog;
+use push_log::PushLogQuery;
+use push_log::PushLogRef;
+use repo_identity::RepoIdentity;
+
+/// Show the pushes to a repo, newest first
+#[derive(Parser)]
+pub struct CommandArgs {
+    #[clap(flatten)]
+    repo: RepoArgs,
+
+    /// Only show pushes that started at or after this time, in seconds since
+    /// the epoch or as an RFC 3339 timestamp
+    #[clap(long, value_parser = parse_timestamp)]
+    since: Option<Timestamp>,
+
+    /// Only show pushes that started before this time, in seconds since the
+    /// epoch or as an RFC 3339 timestamp
+    #[clap(long, value_parser = parse_timestamp)]
+    until: Option<Timestamp>,
+
+    /// Only show pushes by this user
+    #[clap(long)]
+    user: Option<String>,
+
+    /// Only show pushes that moved this bookmark
+    #[clap(long, short = 'B')]
+    bookmark: Option<String>,
+
+    /// Maximum number of pushes to show
+    #[clap(long, default_value_t = 10)]
+    limit: u64,
+}
+
+#[facet::container]
+pub struct Repo {
+    #[facet]
+    repo_identity: RepoIdentity,
+    #[facet]
+    push_log: dyn PushLog,
+}
+
+fn parse_timestamp(value: &str) -> Result<Timestamp> {
+    match value.parse::<i64>() {
+        Ok(secs) => Ok(Timestamp::from_timestamp_secs(secs)),
+        Err(_) => Ok(DateTime::from_rfc3339(value)?.into()),
+    }
+}
+
+pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
+    let ctx = app.new_basic_context();
+    let repo: Repo = app
+        .open_repo(&args.repo)
+        .await
+        .context("Failed to open repo")?;
+
+    let query = PushLogQuery {
+        since: args
+            .since
+            .unwrap_or_else(|| Timestamp::from_timestamp_secs(0)),
+        until: args
+            .until
+            .unwrap_or_else(|| Timestamp::from_timestamp_nanos(i64::MAX)),
+        pusher: args.user,
+        bookmark: args.bookmark,
+        limit: args.limit,
+    };
+    let entries = repo.push_log().query(&ctx, &query).await?;
+    if entries.is_empty() {
+        println!("No pushes found");
+    }
+    for entry in entries {
+        println!(
+            "{} {} commits={} outcome={:?} duration={}ms session={} correlator={}",
+            entry.timestamp.timestamp_seconds(),
+            entry.pusher,
+            entry.commit_count,
+            entry.outcome,
+            entry.duration.as_millis(),
+            entry.session_id,
+            entry.client_correlator.as_deref().unwrap_or("-"),
+        );
+        for bookmark in entry.bookmarks {
+            let format_target = |target: Option<_>| match target {
+                Some(cs_id) => format!("{}", cs_id),
+                None => "-".to_string(),
+            };
+            println!(
+                "    {} {} -> {}",
+                bookmark.bookmark,
+                format_target(bookmark.from),
+                format_target(bookmark.to),
+            );
+        }
+        if let Some(hook_results) = entry.hook_results {
+            println!("    hook results: {}", hook_results);
+        }
+    }
+    Ok(())
+}
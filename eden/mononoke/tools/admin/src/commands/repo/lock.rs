@@ -12,6 +12,7 @@ use clap::Args;
 use itertools::Itertools;
 use live_commit_sync_config::CfgrCurrentCommitSyncConfig;
 use live_commit_sync_config::RepoGroup;
+use metaconfig_types::ReadOnlyInfo;
 use mononoke_app::args::MultiRepoArgs;
 use mononoke_app::MononokeApp;
 use question::Answer;
@@ -61,6 +62,12 @@ pub struct RepoLockArgs {
     /// Why is the repo being locked
     #[clap(long)]
     reason: String,
+    /// Who is locking the repo, shown to users whose pushes are rejected
+    #[clap(long)]
+    locked_by: Option<String>,
+    /// When the repo is expected to be unlocked, as a unix timestamp
+    #[clap(long)]
+    expected_unlock: Option<i64>,
     /// Lock this single repo even if it's part of a megarepo
     #[clap(long)]
     single_repo: bool,
@@ -69,8 +76,16 @@ pub struct RepoLockArgs {
 pub async fn repo_lock(app: &MononokeApp, repo: &Repo, args: RepoLockArgs) -> Result<()> {
     let RepoLockArgs {
         reason,
+        locked_by,
+        expected_unlock,
         single_repo,
     } = args;
+    let info = ReadOnlyInfo {
+        reason,
+        locked_by,
+        expected_unlock,
+        redirect_url: None,
+    };
     let config = CfgrCurrentCommitSyncConfig::new(app.config_store())?;
     let group = config.repo_group(repo.repo_identity.id()).await?;
     let repos = repos_in_group(app, repo, group, "Lock", single_repo).await?;
@@ -78,7 +93,7 @@ pub async fn repo_lock(app: &MononokeApp, repo: &Repo, args: RepoLockArgs) -> Re
     // most of our things are made for a single repo.
     for repo in repos {
         repo.repo_lock()
-            .set_repo_lock(RepoLockState::Locked(reason.clone()))
+            .set_repo_lock(RepoLockState::Locked(info.clone()))
             .await?;
         println!("{} locked", repo.repo_identity().name());
     }
@@ -133,7 +148,7 @@ pub async fn repo_show_lock(_app: &MononokeApp, repo: &Repo, args: RepoShowLockA
     let state = repo.repo_lock().check_repo_lock().await?;
     let state = match state {
         RepoLockState::Unlocked => "unlocked".to_string(),
-        RepoLockState::Locked(reason) => format!("locked with reason: {}", reason),
+        RepoLockState::Locked(info) => format!("locked with reason: {}", info),
     };
     println!("{} is {}", repo.repo_identity().name(), state);
     println!("Consider using `newadmin repos show-locks` to see locks on all repos");
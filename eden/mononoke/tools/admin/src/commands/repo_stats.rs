@@ -0,0 +1,69 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+mod collect;
+mod show;
+
+use anyhow::Context;
+use anyhow::Result;
+use bookmarks::Bookmarks;
+use clap::Parser;
+use clap::Subcommand;
+use collect::CollectArgs;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::MononokeApp;
+use repo_blobstore::RepoBlobstore;
+use repo_derived_data::RepoDerivedData;
+use repo_identity::RepoIdentity;
+use repo_stats::RepoStats;
+use show::ShowArgs;
+
+/// Collect and show repo size stats
+#[derive(Parser)]
+pub struct CommandArgs {
+    #[clap(flatten)]
+    repo: RepoArgs,
+
+    #[clap(subcommand)]
+    subcommand: RepoStatsSubcommand,
+}
+
+#[facet::container]
+pub struct Repo {
+    #[facet]
+    bookmarks: dyn Bookmarks,
+    #[facet]
+    repo_blobstore: RepoBlobstore,
+    #[facet]
+    repo_derived_data: RepoDerivedData,
+    #[facet]
+    repo_identity: RepoIdentity,
+    #[facet]
+    repo_stats: dyn RepoStats,
+}
+
+#[derive(Subcommand)]
+pub enum RepoStatsSubcommand {
+    /// Collect stats at the tips of bookmarks, once or on a schedule.
+    Collect(CollectArgs),
+    /// Show the stats collected at a bookmark, newest first.
+    Show(ShowArgs),
+}
+
+pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
+    let ctx = app.new_basic_context();
+    let repo: Repo = app
+        .open_repo(&args.repo)
+        .await
+        .context("Failed to open repo")?;
+
+    match args.subcommand {
+        RepoStatsSubcommand::Collect(args) => collect::collect(&ctx, &repo, args).await?,
+        RepoStatsSubcommand::Show(args) => show::show(&ctx, &repo, args).await?,
+    }
+    Ok(())
+}
@@ -0,0 +1,130 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use blobstore::Loadable;
+use bookmarks::BookmarkKey;
+use bookmarks::BookmarksRef;
+use clap::Args;
+use context::CoreContext;
+use fsnodes::RootFsnodeId;
+use mononoke_types::fsnode::FsnodeEntry;
+use mononoke_types::ChangesetId;
+use mononoke_types::Timestamp;
+use repo_blobstore::RepoBlobstoreRef;
+use repo_derived_data::RepoDerivedDataRef;
+use repo_stats::DirectoryStats;
+use repo_stats::RepoStatsEntry;
+use repo_stats::RepoStatsRef;
+use slog::info;
+use slog::warn;
+
+use super::Repo;
+
+#[derive(Args)]
+pub struct CollectArgs {
+    /// Bookmarks to collect stats at
+    #[clap(long = "bookmark", short = 'B', required = true)]
+    bookmarks: Vec<BookmarkKey>,
+
+    /// Keep collecting stats at this interval, in seconds, instead of
+    /// collecting them once
+    #[clap(long)]
+    interval_secs: Option<u64>,
+}
+
+/// Compute the stats of the working copy of a changeset from its fsnodes.
+async fn compute_stats(
+    ctx: &CoreContext,
+    repo: &Repo,
+    bookmark: &BookmarkKey,
+    cs_id: ChangesetId,
+) -> Result<RepoStatsEntry> {
+    let root_fsnode = repo
+        .repo_derived_data()
+        .derive::<RootFsnodeId>(ctx, cs_id)
+        .await?
+        .fsnode_id()
+        .load(ctx, repo.repo_blobstore())
+        .await?;
+
+    let mut directories: BTreeMap<String, DirectoryStats> = BTreeMap::new();
+    for (name, entry) in root_fsnode.list() {
+        match entry {
+            FsnodeEntry::File(file) => {
+                let stats = directories.entry(String::new()).or_default();
+                stats.file_count += 1;
+                stats.total_size += file.size();
+            }
+            FsnodeEntry::Directory(dir) => {
+                let summary = dir.summary();
+                directories.insert(
+                    name.to_string(),
+                    DirectoryStats {
+                        file_count: summary.descendant_files_count,
+                        total_size: summary.descendant_files_total_size,
+                    },
+                );
+            }
+        }
+    }
+
+    let summary = root_fsnode.summary();
+    Ok(RepoStatsEntry {
+        timestamp: Timestamp::now(),
+        bookmark: bookmark.to_string(),
+        cs_id,
+        file_count: summary.descendant_files_count,
+        total_size: summary.descendant_files_total_size,
+        directories,
+    })
+}
+
+async fn collect_bookmark(ctx: &CoreContext, repo: &Repo, bookmark: &BookmarkKey) -> Result<()> {
+    let cs_id = repo
+        .bookmarks()
+        .get(ctx.clone(), bookmark)
+        .await
+        .with_context(|| format!("Failed to resolve bookmark '{}'", bookmark))?
+        .ok_or_else(|| anyhow!("Bookmark '{}' does not exist", bookmark))?;
+    let entry = compute_stats(ctx, repo, bookmark, cs_id)
+        .await
+        .with_context(|| format!("Failed to compute stats for {}", cs_id))?;
+    info!(
+        ctx.logger(),
+        "{} at {}: {} files, {} bytes", bookmark, cs_id, entry.file_count, entry.total_size
+    );
+    repo.repo_stats().add_entry(ctx, entry).await
+}
+
+pub async fn collect(ctx: &CoreContext, repo: &Repo, collect_args: CollectArgs) -> Result<()> {
+    let interval = match collect_args.interval_secs {
+        Some(interval_secs) => Duration::from_secs(interval_secs),
+        None => {
+            for bookmark in collect_args.bookmarks.iter() {
+                collect_bookmark(ctx, repo, bookmark).await?;
+            }
+            return Ok(());
+        }
+    };
+
+    loop {
+        // When collecting on a schedule, a failure at one bookmark shouldn't
+        // stop the collection at the others, or at later times.
+        for bookmark in collect_args.bookmarks.iter() {
+            if let Err(err) = collect_bookmark(ctx, repo, bookmark).await {
+                warn!(ctx.logger(), "Failed to collect stats at {}: {:?}", bookmark, err);
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
@@ -0,0 +1,54 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Result;
+use clap::Args;
+use context::CoreContext;
+use repo_stats::RepoStatsRef;
+
+use super::Repo;
+
+#[derive(Args)]
+pub struct ShowArgs {
+    /// Bookmark to show the stats of
+    #[clap(long, short = 'B')]
+    bookmark: String,
+
+    /// Maximum number of entries to show
+    #[clap(long, default_value_t = 10)]
+    limit: u64,
+
+    /// Also show the stats of each top-level directory
+    #[clap(long)]
+    directories: bool,
+}
+
+pub async fn show(ctx: &CoreContext, repo: &Repo, show_args: ShowArgs) -> Result<()> {
+    let entries = repo
+        .repo_stats()
+        .list(ctx, &show_args.bookmark, show_args.limit)
+        .await?;
+    if entries.is_empty() {
+        println!("No stats collected at {}", show_args.bookmark);
+    }
+    for entry in entries {
+        println!(
+            "{} {} files={} size={}",
+            entry.timestamp.timestamp_seconds(),
+            entry.cs_id,
+            entry.file_count,
+            entry.total_size,
+        );
+        if show_args.directories {
+            for (name, stats) in entry.directories {
+                let name = if name.is_empty() { "(root)" } else { &name };
+                println!("    {} files={} size={}", name, stats.file_count, stats.total_size);
+            }
+        }
+    }
+    Ok(())
+}
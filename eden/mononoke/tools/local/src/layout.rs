@@ -0,0 +1,380 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use cached_config::ConfigStore;
+use cached_config::TestSource;
+use serde::Deserialize;
+
+/// Name of the storage config shared by all local repos.
+const STORAGE_NAME: &str = "local";
+
+/// Name of the tunables file, relative to the local configerator directory.
+const TUNABLES_NAME: &str = "mononoke_tunables.json";
+
+/// Minimal config for a local deployment.
+#[derive(Debug, Deserialize)]
+pub struct LocalConfig {
+    /// Directory that holds the generated config and all repo data.
+    pub data_dir: PathBuf,
+    /// Identities (e.g. `USER:alice`) allowed to read and write all repos.
+    #[serde(default)]
+    pub identities: Vec<String>,
+    /// Repos to serve.
+    #[serde(default)]
+    pub repos: Vec<LocalRepoConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LocalRepoConfig {
+    pub name: String,
+    /// Must be unique, and must not change once the repo has data, as the
+    /// data is keyed by it.
+    pub id: i32,
+    #[serde(default)]
+    pub readonly: bool,
+}
+
+/// The directory layout of a local deployment.
+pub struct LocalLayout {
+    data_dir: PathBuf,
+    repo_names: Vec<String>,
+}
+
+fn toml_string(value: impl Into<String>) -> String {
+    toml::Value::String(value.into()).to_string()
+}
+
+fn toml_path(path: &Path) -> String {
+    toml_string(path.to_string_lossy())
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+    }
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// The id of a repo that was written by an earlier run, if there is one.
+fn existing_repo_id(definition_path: &Path) -> Result<Option<i32>> {
+    if !definition_path.exists() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(definition_path)
+        .with_context(|| format!("Failed to read {}", definition_path.display()))?;
+    let definition: toml::Value = toml::from_str(&contents)
+        .with_context(|| format!("Failed to parse {}", definition_path.display()))?;
+    let repo_id = definition
+        .get("repo_id")
+        .and_then(toml::Value::as_integer)
+        .with_context(|| format!("No repo_id in {}", definition_path.display()))?;
+    Ok(Some(repo_id.try_into()?))
+}
+
+impl LocalConfig {
+    /// Write the Mononoke config and create the data directories for this
+    /// deployment.  Existing data is left untouched, so this can be re-run
+    /// to add repos.
+    pub fn write(&self) -> Result<LocalLayout> {
+        for repo in &self.repos {
+            if repo.name.is_empty()
+                || !repo
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                bail!("Invalid repo name '{}'", repo.name);
+            }
+        }
+        let mut repo_names_by_id = HashMap::new();
+        for repo in &self.repos {
+            if let Some(other) = repo_names_by_id.insert(repo.id, &repo.name) {
+                bail!(
+                    "Repos '{}' and '{}' have the same id {}",
+                    other,
+                    repo.name,
+                    repo.id
+                );
+            }
+        }
+
+        let layout = LocalLayout {
+            data_dir: self.data_dir.clone(),
+            repo_names: self.repos.iter().map(|repo| repo.name.clone()).collect(),
+        };
+
+        for dir in [
+            layout.sqlite_dir(),
+            layout.blobstore_dir(),
+            layout.ephemeral_blobstore_dir(),
+        ] {
+            fs::create_dir_all(&dir)
+                .with_context(|| format!("Failed to create directory {}", dir.display()))?;
+        }
+
+        let common_dir = layout.config_dir().join("common");
+        write_file(&common_dir.join("common.toml"), &layout.common_config())?;
+        write_file(&common_dir.join("storage.toml"), &layout.storage_config())?;
+        write_file(&common_dir.join("commitsyncmap.toml"), "")?;
+
+        for repo in &self.repos {
+            let definition_path = layout
+                .config_dir()
+                .join("repo_definitions")
+                .join(&repo.name)
+                .join("server.toml");
+            if let Some(existing_id) = existing_repo_id(&definition_path)? {
+                if existing_id != repo.id {
+                    bail!(
+                        "Repo '{}' already exists with id {}, not {}",
+                        repo.name,
+                        existing_id,
+                        repo.id
+                    );
+                }
+            }
+            write_file(
+                &layout
+                    .config_dir()
+                    .join("repos")
+                    .join(&repo.name)
+                    .join("server.toml"),
+                &format!("storage_config = {}\n", toml_string(STORAGE_NAME)),
+            )?;
+            write_file(
+                &definition_path,
+                &format!(
+                    "repo_id = {}\n\
+                     repo_name = {}\n\
+                     repo_config = {}\n\
+                     enabled = true\n\
+                     hipster_acl = \"default\"\n\
+                     readonly = {}\n",
+                    repo.id,
+                    toml_string(&repo.name),
+                    toml_string(&repo.name),
+                    repo.readonly,
+                ),
+            )?;
+        }
+
+        let configerator_dir = layout.configerator_dir();
+        let mononoke_dir = configerator_dir.join("scm/mononoke");
+        write_file(
+            &mononoke_dir.join("ratelimiting/ratelimits"),
+            r#"{"rate_limits": [], "load_shed_limits": [], "datacenter_prefix_capacity": {}, "commits_per_author": {"status": 0, "limit": 0, "window": 0}, "total_file_changes": {"status": 0, "limit": 0, "window": 0}}"#,
+        )?;
+        write_file(
+            &mononoke_dir.join("observability/observability_config"),
+            r#"{"slog_config": {"level": 4}, "scuba_config": {"level": 1, "verbose_sessions": [], "verbose_unixnames": [], "verbose_source_hostnames": []}}"#,
+        )?;
+        write_file(
+            &mononoke_dir.join("redaction/redaction_sets"),
+            r#"{"all_redactions": []}"#,
+        )?;
+        write_file(&configerator_dir.join(TUNABLES_NAME), "{}")?;
+
+        let acl = serde_json::json!({
+            "repos": {
+                "default": {
+                    "actions": {
+                        "read": self.identities,
+                        "write": self.identities,
+                    }
+                }
+            }
+        });
+        write_file(&layout.acl_file(), &serde_json::to_string_pretty(&acl)?)?;
+
+        Ok(layout)
+    }
+}
+
+impl LocalLayout {
+    pub fn config_dir(&self) -> PathBuf {
+        self.data_dir.join("config")
+    }
+
+    pub fn configerator_dir(&self) -> PathBuf {
+        self.data_dir.join("configerator")
+    }
+
+    pub fn sqlite_dir(&self) -> PathBuf {
+        self.data_dir.join("sqlite")
+    }
+
+    pub fn blobstore_dir(&self) -> PathBuf {
+        self.data_dir.join("blobstore")
+    }
+
+    pub fn ephemeral_blobstore_dir(&self) -> PathBuf {
+        self.data_dir.join("ephemeral_blobstore")
+    }
+
+    pub fn acl_file(&self) -> PathBuf {
+        self.data_dir.join("acls.json")
+    }
+
+    fn common_config(&self) -> String {
+        format!(
+            "[internal_identity]\n\
+             identity_type = \"SERVICE_IDENTITY\"\n\
+             identity_data = \"mononoke_local\"\n\
+             \n\
+             [redaction_config]\n\
+             blobstore = {storage}\n\
+             darkstorm_blobstore = {storage}\n\
+             redaction_sets_location = \"scm/mononoke/redaction/redaction_sets\"\n",
+            storage = toml_string(STORAGE_NAME),
+        )
+    }
+
+    fn storage_config(&self) -> String {
+        let sqlite_dir = toml_path(&self.sqlite_dir());
+        format!(
+            "[{name}.metadata.local]\n\
+             local_db_path = {sqlite_dir}\n\
+             \n\
+             [{name}.blobstore.blob_files]\n\
+             path = {blobstore_dir}\n\
+             \n\
+             [{name}.ephemeral_blobstore]\n\
+             initial_bubble_lifespan_secs = 86400\n\
+             bubble_expiration_grace_secs = 3600\n\
+             bubble_deletion_mode = 0\n\
+             blobstore = {{ blob_files = {{ path = {ephemeral_blobstore_dir} }} }}\n\
+             \n\
+             [{name}.ephemeral_blobstore.metadata.local]\n\
+             local_db_path = {sqlite_dir}\n",
+            name = STORAGE_NAME,
+            sqlite_dir = sqlite_dir,
+            blobstore_dir = toml_path(&self.blobstore_dir()),
+            ephemeral_blobstore_dir = toml_path(&self.ephemeral_blobstore_dir()),
+        )
+    }
+
+    /// Check that the generated config parses and defines every repo.
+    pub fn validate(&self) -> Result<()> {
+        let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
+        let configs = metaconfig_parser::load_repo_configs(self.config_dir(), &config_store)
+            .context("Generated config is invalid")?;
+        for name in &self.repo_names {
+            if !configs.repos.contains_key(name) {
+                bail!("Repo '{}' is missing from the generated config", name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Command line for a Mononoke server that serves this deployment.  TLS
+    /// certificates are not generated and must be supplied.
+    pub fn server_command(&self) -> String {
+        format!(
+            "mononoke \\\n  \
+             --config-path {} \\\n  \
+             --local-configerator-path {} \\\n  \
+             --tunables-config {} \\\n  \
+             --acl-file {} \\\n  \
+             --mysql-master-only \\\n  \
+             --cache-mode disabled \\\n  \
+             --listening-host-port [::1]:0 \\\n  \
+             --cert <CERT> --private-key <PRIVATE_KEY> --ca-pem <CA_PEM>",
+            self.config_dir().display(),
+            self.configerator_dir().display(),
+            TUNABLES_NAME,
+            self.acl_file().display(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_write_and_validate() -> Result<()> {
+        let data_dir = tempfile::tempdir()?;
+        let config: LocalConfig = toml::from_str(&format!(
+            r#"
+            data_dir = {}
+            identities = ["USER:alice"]
+
+            [[repos]]
+            name = "repo"
+            id = 0
+
+            [[repos]]
+            name = "other-repo"
+            id = 5
+            readonly = true
+            "#,
+            toml_path(data_dir.path()),
+        ))?;
+        let layout = config.write()?;
+        layout.validate()?;
+
+        let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
+        let configs = metaconfig_parser::load_repo_configs(layout.config_dir(), &config_store)?;
+        assert_eq!(configs.repos["repo"].repoid.id(), 0);
+        assert_eq!(configs.repos["other-repo"].repoid.id(), 5);
+        assert!(layout.sqlite_dir().is_dir());
+        assert!(layout.blobstore_dir().is_dir());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_repo_name() -> Result<()> {
+        let data_dir = tempfile::tempdir()?;
+        let config = LocalConfig {
+            data_dir: data_dir.path().to_path_buf(),
+            identities: vec![],
+            repos: vec![LocalRepoConfig {
+                name: "../repo".to_string(),
+                id: 0,
+                readonly: false,
+            }],
+        };
+        assert!(config.write().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_repo_ids() -> Result<()> {
+        let data_dir = tempfile::tempdir()?;
+        let repo = |name: &str, id| LocalRepoConfig {
+            name: name.to_string(),
+            id,
+            readonly: false,
+        };
+        let config = |repos| LocalConfig {
+            data_dir: data_dir.path().to_path_buf(),
+            identities: vec![],
+            repos,
+        };
+
+        // Ids must be unique.
+        assert!(config(vec![repo("a", 1), repo("b", 1)]).write().is_err());
+
+        // Repos can be added, but existing repos can't change id.
+        config(vec![repo("a", 1)]).write()?;
+        config(vec![repo("a", 1), repo("b", 2)])
+            .write()?
+            .validate()?;
+        assert!(config(vec![repo("a", 3)]).write().is_err());
+        Ok(())
+    }
+}
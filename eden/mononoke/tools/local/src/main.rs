@@ -0,0 +1,59 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Set up a fully local Mononoke deployment for development.
+//!
+//! All metadata (bookmarks, bonsai mappings, filenodes, mutable counters,
+//! etc.) is stored in SQLite databases and all blobs are stored as files,
+//! all under a single data directory.  The complete Mononoke configuration
+//! is generated from a minimal config that only lists the repos to serve.
+
+mod layout;
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use clap::Parser;
+use clap::Subcommand;
+
+use crate::layout::LocalConfig;
+
+/// Set up a local Mononoke deployment backed by SQLite and files.
+#[derive(Parser)]
+struct LocalArgs {
+    #[clap(subcommand)]
+    command: LocalCommand,
+}
+
+#[derive(Subcommand)]
+enum LocalCommand {
+    /// Generate the Mononoke config and data directories from a minimal
+    /// local config.
+    Init {
+        /// Path to the minimal local config (TOML).
+        #[clap(long)]
+        config: PathBuf,
+    },
+}
+
+fn main() -> Result<()> {
+    let args = LocalArgs::parse();
+    match args.command {
+        LocalCommand::Init { config } => {
+            let contents = fs::read_to_string(&config)
+                .with_context(|| format!("Failed to read {}", config.display()))?;
+            let local_config: LocalConfig = toml::from_str(&contents)
+                .with_context(|| format!("Failed to parse {}", config.display()))?;
+            let layout = local_config.write()?;
+            layout.validate()?;
+            println!("{}", layout.server_command());
+        }
+    }
+    Ok(())
+}
@@ -104,6 +104,12 @@ pub struct MononokeTunables {
     pushrebase_disable_rebased_commit_validation: TunableBool,
     filenodes_disabled: TunableBool,
     filenodes_master_fallback_ratio: TunableI64,
+    // Number of filenodes written by each multi-row insert statement.  If
+    // unset, a default is chosen for the database type.
+    filenodes_insert_chunk_size: TunableI64,
+    // Batch derivation of filenodes collects the filenodes of several commits
+    // and writes them together once there are at least this many.
+    filenodes_derive_batch_insert_size: TunableI64,
     // Skiplist config
     skiplist_max_skips_without_yield: TunableI64,
     skiplist_reload_disabled: TunableBool,
@@ -129,7 +135,23 @@ pub struct MononokeTunables {
     repo_client_clone_timeout_secs: TunableI64,
     repo_client_default_timeout_secs: TunableI64,
     repo_client_getbundle_timeout_secs: TunableI64,
+    // Cache getbundle responses for bookmarked heads in the blobstore for
+    // this long.  0 disables the cache.
+    repo_client_getbundle_cache_ttl_secs: TunableI64,
+    // Don't cache getbundle responses bigger than this
+    repo_client_getbundle_cache_max_bytes: TunableI64,
+    // Number of getbundle responses each repo can cache at once
+    repo_client_getbundle_cache_slots: TunableI64,
+    // Cache getbundle responses for pulls that aren't clones once the same
+    // pull has missed the cache this many times on a server
+    repo_client_getbundle_cache_min_misses: TunableI64,
     repo_client_getpack_timeout_secs: TunableI64,
+    // Keep the responses to pushes made with idempotency keys for this long
+    repo_client_idempotency_key_ttl_secs: TunableI64,
+    // Keep push log entries for this many days
+    repo_client_push_log_retention_days: TunableI64,
+    // Most bytes of file content that getpack holds in memory at once
+    repo_client_getpack_max_in_flight_bytes: TunableI64,
     repo_client_concurrent_blob_uploads: TunableI64,
     repo_client_max_nodes_in_known_method: TunableI64,
     // How many trees is getting prepared at once
@@ -198,6 +220,16 @@ pub struct MononokeTunables {
     // how many commits will be derived at once.
     derived_data_parallel_derivation_buffer: TunableI64,
 
+    // When a derived data type's format version has been bumped and old
+    // data is no longer compatible, limits how many changesets a single
+    // derivation will re-derive before asking for a backfill instead.
+    derived_data_max_lazy_rederivations: TunableI64,
+
+    // Derived blobs are written to memory until a batch has been derived.
+    // Once they take up more than this many bytes, they are written out
+    // early, so that large batches don't run out of memory.
+    derived_data_write_cache_max_bytes: TunableI64,
+
     // Tunables to disable derived data derivation either for the full repo
     // or for specific derived data types inside a repo
     all_derived_data_disabled: TunableBoolByRepo,
@@ -377,17 +409,28 @@ pub struct MononokeTunables {
 
     // Usage of new commit graph for speeding up server-side operations
     new_commit_graph_is_ancestor_percentage: TunableI64ByRepo,
+    // Percentage of ancestry and merge base queries answered by the skiplist
+    // index that are also answered by the new commit graph, logging any
+    // differences between the answers
+    new_commit_graph_shadow_percentage: TunableI64ByRepo,
 
     // Disable all prefetching in the commit graph
     disable_commit_graph_prefetch: TunableBool,
     // Max number of steps to make when prefetching
     commit_graph_prefetch_step_limit: TunableI64,
+    // How out of date the changeset id prefix filter may be for lookups
+    // that it rejects to be trusted, rather than passed on to the database
+    commit_graph_prefix_filter_max_staleness_ms: TunableI64,
 
     // Disable the fix to use isolation level read committed
     disable_wal_read_committed: TunableBool,
 
     // Disable sharing of large reads
     disable_large_blob_read_deduplication: TunableBool,
+
+    // Per-module log levels, as "module::path=level" entries, overriding
+    // the logging level for records logged from that module or below it
+    log_module_levels: TunableVecOfStrings,
 }
 
 fn log_tunables(tunables: &TunablesStruct) -> String {
@@ -481,23 +524,7 @@ fn wait_and_update_iteration(new_tunables: Arc<TunablesStruct>, logger: &Logger)
 }
 
 fn update_tunables(new_tunables: Arc<TunablesStruct>) -> Result<()> {
-    let tunables = tunables();
-    tunables.update_bools(&new_tunables.killswitches);
-    tunables.update_ints(&new_tunables.ints);
-    tunables.update_strings(&new_tunables.strings);
-    tunables.update_vec_of_strings(&new_tunables.vec_of_strings);
-
-    if let Some(killswitches_by_repo) = &new_tunables.killswitches_by_repo {
-        tunables.update_by_repo_bools(killswitches_by_repo);
-    }
-
-    if let Some(ints_by_repo) = &new_tunables.ints_by_repo {
-        tunables.update_by_repo_ints(ints_by_repo);
-    }
-
-    if let Some(vec_of_strings_by_repo) = &new_tunables.vec_of_strings_by_repo {
-        tunables.update_by_repo_vec_of_strings(vec_of_strings_by_repo);
-    }
+    tunables().update(&new_tunables);
     Ok(())
 }
 
@@ -806,6 +833,26 @@ mod test {
         assert_eq!(test.by_repo_repostr("repo2"), None);
     }
 
+    #[test]
+    fn test_update_from_struct() {
+        let test = TestTunables::default();
+        test.update(&TunablesStruct {
+            killswitches: hashmap! { s("boolean") => true },
+            strings_by_repo: Some(hashmap! {
+                s("repo") => hashmap! {
+                    s("repostr") => s("hello"),
+                },
+            }),
+            ..TunablesStruct::default()
+        });
+        assert_eq!(test.boolean(), Some(true));
+        assert_eq!(test.by_repo_repostr("repo"), Some(s("hello")));
+
+        test.update(&TunablesStruct::default());
+        assert_eq!(test.boolean(), None);
+        assert_eq!(test.by_repo_repostr("repo"), Some(s("hello")));
+    }
+
     #[test]
     fn update_by_repo_two_strs() {
         let test = TestTunables::default();
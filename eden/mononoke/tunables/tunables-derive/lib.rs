@@ -35,7 +35,8 @@ enum TunableType {
 // This proc macro accepts a struct and provides methods that get the atomic
 // values stored inside of it. It does this by generating methods
 // named get_<field>(). The macro also generates methods that update the
-// atomic values inside of the struct, using a provided HashMap.
+// atomic values inside of the struct, using a provided HashMap, and an
+// `update` method that updates all of them from the tunables config.
 pub fn derive_tunables(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let parsed_input = parse_macro_input!(input as DeriveInput);
 
@@ -191,6 +192,31 @@ where
         quote::format_ident!("update_by_repo_vec_of_strings"),
     ));
 
+    methods.extend(quote! {
+        pub fn update(&self, new_tunables: &crate::TunablesStruct) {
+            self.update_bools(&new_tunables.killswitches);
+            self.update_ints(&new_tunables.ints);
+            self.update_strings(&new_tunables.strings);
+            self.update_vec_of_strings(&new_tunables.vec_of_strings);
+
+            if let Some(killswitches_by_repo) = &new_tunables.killswitches_by_repo {
+                self.update_by_repo_bools(killswitches_by_repo);
+            }
+
+            if let Some(ints_by_repo) = &new_tunables.ints_by_repo {
+                self.update_by_repo_ints(ints_by_repo);
+            }
+
+            if let Some(strings_by_repo) = &new_tunables.strings_by_repo {
+                self.update_by_repo_strings(strings_by_repo);
+            }
+
+            if let Some(vec_of_strings_by_repo) = &new_tunables.vec_of_strings_by_repo {
+                self.update_by_repo_vec_of_strings(vec_of_strings_by_repo);
+            }
+        }
+    });
+
     methods
 }
 
@@ -398,6 +398,12 @@ where
             if is_chunking && chunk_members.is_empty() {
                 continue;
             }
+            maintenance_windows::wait_until_background_jobs_allowed(
+                &ctx,
+                repo_params.repo.repo_identity().name(),
+                &repo_params.maintenance_windows,
+            )
+            .await;
             chunk_num += 1;
 
             // convert from stream of (id, bounds) to ids plus overall bounds
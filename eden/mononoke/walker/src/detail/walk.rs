@@ -60,6 +60,7 @@ use mercurial_types::HgChangesetId;
 use mercurial_types::HgFileNodeId;
 use mercurial_types::HgManifestId;
 use mercurial_types::RepoPath;
+use metaconfig_types::MaintenanceWindow;
 use mononoke_types::blame::BlameMaybeRejected;
 use mononoke_types::deleted_manifest_common::DeletedManifestCommon;
 use mononoke_types::fsnode::FsnodeEntry;
@@ -1498,8 +1499,13 @@ async fn unode_manifest_step<V: VisitOne>(
         );
     }
 
+    let subentries = unode_manifest
+        .clone()
+        .into_subentries(ctx, repo.repo_blobstore())
+        .try_collect::<Vec<_>>()
+        .await?;
     let mut file_edges = vec![];
-    for (child, subentry) in unode_manifest.subentries() {
+    for (child, subentry) in &subentries {
         match subentry {
             UnodeEntry::Directory(id) => {
                 checker.add_edge_with_path(
@@ -1955,6 +1961,7 @@ pub struct RepoWalkParams {
     pub include_node_types: HashSet<NodeType>,
     pub include_edge_types: HashSet<EdgeType>,
     pub hash_validation_node_types: HashSet<NodeType>,
+    pub maintenance_windows: Vec<MaintenanceWindow>,
 }
 
 // Parameters that vary per repo but are set differently by scrub, validate etc.
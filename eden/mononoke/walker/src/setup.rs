@@ -490,6 +490,7 @@ async fn setup_repo<'a>(
             include_edge_types,
             hash_validation_node_types,
             scuba_builder,
+            maintenance_windows: resolved.config.maintenance_windows.clone(),
         },
     ))
 }
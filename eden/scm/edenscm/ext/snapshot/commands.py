@@ -6,7 +6,7 @@
 from edenscm import error, registrar
 from edenscm.i18n import _
 
-from . import createremote, isworkingcopy, labels, latest, show, update
+from . import createremote, isworkingcopy, labels, latest, listing, show, update
 
 cmdtable = {}
 command = registrar.command(cmdtable)
@@ -24,7 +24,7 @@ def snapshot(ui, repo, **opts):
 subcmd = snapshot.subcommand(
     categories=[
         ("Manage snapshots", ["create", "update", "add-labels", "remove-labels"]),
-        ("Query snapshots", ["show"]),
+        ("Query snapshots", ["show", "list"]),
     ]
 )
 
@@ -113,6 +113,32 @@ def showcmd(*args, **kwargs) -> None:
     show.show(*args, **kwargs)
 
 
+@subcmd(
+    "list",
+    [
+        (
+            "",
+            "label",
+            "",
+            _(
+                "list snapshots with this label, e.g. the name of the machine they were created on"
+            ),
+            _("LABEL"),
+        ),
+        ("", "limit", "", _("maximum number of snapshots to list"), _("LIMIT")),
+        ("", "json", None, _("output in json format instead of human-readable")),
+    ],
+)
+def listcmd(*args, **kwargs) -> None:
+    """list the most recent snapshots that have a given label
+
+    Snapshots can be labelled when they are created with --labels, for example
+    with the name of the machine they were created on. This lists them so
+    that work can be continued on another machine with 'hg snapshot update'.
+    """
+    listing.listsnapshots(*args, **kwargs)
+
+
 @subcmd(
     "isworkingcopy",
     [
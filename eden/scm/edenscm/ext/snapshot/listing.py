@@ -0,0 +1,49 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This software may be used and distributed according to the terms of the
+# GNU General Public License version 2.
+
+import json
+
+from edenscm import error
+from edenscm.i18n import _
+
+
+def listsnapshots(ui, repo, **opts):
+    label = opts.get("label")
+    if not label:
+        raise error.CommandError("snapshot list", _("missing label to list snapshots"))
+    limit = opts.get("limit")
+    try:
+        snapshots = repo.edenapi.listsnapshots(
+            {
+                "label": label,
+                "limit": int(limit) if limit else None,
+            },
+        )
+    except Exception as e:
+        ui.debug(f"error while listing snapshots: {e}\n")
+        raise error.Abort(_("snapshots couldn't be listed\n"))
+
+    if opts.get("json"):
+        ui.write(
+            json.dumps(
+                [
+                    {
+                        "id": snapshot["cs_id"].hex(),
+                        "bubble_id": snapshot["bubble_id"],
+                        "labels": snapshot["labels"],
+                    }
+                    for snapshot in snapshots
+                ]
+            ),
+            "\n",
+        )
+        return
+
+    for snapshot in snapshots:
+        ui.write(
+            _("{} labels: {}\n").format(
+                snapshot["cs_id"].hex(), ",".join(snapshot["labels"])
+            )
+        )
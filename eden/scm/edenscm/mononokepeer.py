@@ -355,6 +355,7 @@ class mononokepeer(stdiopeer.stdiopeer):
                 headers = {
                     "Host": self._host,
                     "User-Agent": useragent,
+                    "X-Client-Version": util.version(),
                     "Connection": "Upgrade",
                     "Upgrade": "websocket",
                 }
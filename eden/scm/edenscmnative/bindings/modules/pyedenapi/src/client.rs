@@ -46,6 +46,8 @@ use edenapi_types::HgChangesetContent;
 use edenapi_types::HgMutationEntryContent;
 use edenapi_types::HistoryEntry;
 use edenapi_types::LandStackResponse;
+use edenapi_types::ListSnapshotsRequest;
+use edenapi_types::ListSnapshotsResponse;
 use edenapi_types::SnapshotRawData;
 use edenapi_types::TreeAttributes;
 use edenapi_types::TreeEntry;
@@ -427,6 +429,14 @@ py_class!(pub class client |py| {
         self.inner(py).as_ref().altersnapshot_py(py, data)
     }
 
+    /// List the snapshots that have the given label, most recent first
+    def listsnapshots(
+        &self,
+        data: Serde<ListSnapshotsRequest>,
+    ) -> PyResult<Serde<Vec<ListSnapshotsResponse>>> {
+        self.inner(py).as_ref().listsnapshots_py(py, data)
+    }
+
     /// Downloads files from given upload tokens to given paths
     def downloadfiles(
         &self,
@@ -49,6 +49,8 @@ use edenapi_types::HgMutationEntryContent;
 use edenapi_types::HistoryEntry;
 use edenapi_types::IndexableId;
 use edenapi_types::LandStackResponse;
+use edenapi_types::ListSnapshotsRequest;
+use edenapi_types::ListSnapshotsResponse;
 use edenapi_types::LookupResult;
 use edenapi_types::TreeAttributes;
 use edenapi_types::TreeEntry;
@@ -787,6 +789,25 @@ pub trait EdenApiPyExt: EdenApi {
         .map(Serde)
     }
 
+    fn listsnapshots_py(
+        &self,
+        py: Python,
+        data: Serde<ListSnapshotsRequest>,
+    ) -> PyResult<Serde<Vec<ListSnapshotsResponse>>> {
+        py.allow_threads(|| {
+            block_unless_interrupted(async move {
+                self.list_snapshots(data.0)
+                    .await?
+                    .entries
+                    .try_collect::<Vec<_>>()
+                    .await
+            })
+        })
+        .map_pyerr(py)?
+        .map_pyerr(py)
+        .map(Serde)
+    }
+
     fn downloadfiletomemory_py(&self, py: Python, token: Serde<UploadToken>) -> PyResult<PyBytes> {
         py.allow_threads(|| block_unless_interrupted(self.download_file(token.0)))
             .map_pyerr(py)?
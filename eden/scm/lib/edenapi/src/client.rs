@@ -61,6 +61,8 @@ use edenapi_types::HistoryResponseChunk;
 use edenapi_types::IndexableId;
 use edenapi_types::LandStackRequest;
 use edenapi_types::LandStackResponse;
+use edenapi_types::ListSnapshotsRequest;
+use edenapi_types::ListSnapshotsResponse;
 use edenapi_types::LookupRequest;
 use edenapi_types::LookupResponse;
 use edenapi_types::LookupResult;
@@ -151,6 +153,7 @@ mod paths {
     pub const EPHEMERAL_PREPARE: &str = "ephemeral/prepare";
     pub const FETCH_SNAPSHOT: &str = "snapshot";
     pub const ALTER_SNAPSHOT: &str = "snapshot/alter";
+    pub const LIST_SNAPSHOTS: &str = "snapshot/list";
     pub const DOWNLOAD_FILE: &str = "download/file";
 }
 
@@ -623,7 +626,11 @@ impl EdenApi for Client {
 
         let url = self.build_url(paths::HISTORY)?;
         let requests = self.prepare_requests(&url, keys, self.config().max_history, |keys| {
-            let req = HistoryRequest { keys, length };
+            let req = HistoryRequest {
+                keys,
+                length,
+                commit: None,
+            };
             self.log_request(&req, "history");
             req
         })?;
@@ -1230,6 +1237,22 @@ impl EdenApi for Client {
         Ok(self.fetch::<AlterSnapshotResponse>(vec![request])?)
     }
 
+    /// List the snapshots that have the requested label
+    async fn list_snapshots(
+        &self,
+        request: ListSnapshotsRequest,
+    ) -> Result<Response<ListSnapshotsResponse>, EdenApiError> {
+        tracing::info!("Listing snapshots with label {}", request.label);
+        let url = self.build_url(paths::LIST_SNAPSHOTS)?;
+        let req = request.to_wire();
+        let request = self
+            .configure_request(self.inner.client.post(url))?
+            .cbor(&req)
+            .map_err(EdenApiError::RequestSerializationFailed)?;
+
+        Ok(self.fetch::<ListSnapshotsResponse>(vec![request])?)
+    }
+
     async fn download_file(&self, token: UploadToken) -> Result<Bytes, EdenApiError> {
         tracing::info!("Downloading file");
         let url = self.build_url(paths::DOWNLOAD_FILE)?;
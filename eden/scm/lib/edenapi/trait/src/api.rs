@@ -38,6 +38,8 @@ use edenapi_types::HgFilenodeData;
 use edenapi_types::HgMutationEntryContent;
 use edenapi_types::HistoryEntry;
 use edenapi_types::LandStackResponse;
+use edenapi_types::ListSnapshotsRequest;
+use edenapi_types::ListSnapshotsResponse;
 use edenapi_types::LookupResponse;
 use edenapi_types::TreeAttributes;
 use edenapi_types::TreeEntry;
@@ -303,6 +305,15 @@ pub trait EdenApi: Send + Sync + 'static {
         Err(EdenApiError::NotSupported)
     }
 
+    /// List the snapshots that have the requested label
+    async fn list_snapshots(
+        &self,
+        request: ListSnapshotsRequest,
+    ) -> Result<Response<ListSnapshotsResponse>, EdenApiError> {
+        let _ = request;
+        Err(EdenApiError::NotSupported)
+    }
+
     /// Download single file from upload token
     async fn download_file(&self, token: UploadToken) -> Result<Bytes, EdenApiError> {
         let _ = token;
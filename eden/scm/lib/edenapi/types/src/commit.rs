@@ -386,6 +386,34 @@ pub struct AlterSnapshotResponse {
     pub current_labels: Vec<String>,
 }
 
+#[auto_wire]
+#[derive(Clone, Default, Debug, Deserialize, Serialize, Eq, PartialEq)]
+#[cfg_attr(any(test, feature = "for-tests"), derive(Arbitrary))]
+pub struct ListSnapshotsRequest {
+    /// Only list snapshots that have this label, e.g. the name of the
+    /// machine they were created on.
+    #[id(1)]
+    pub label: String,
+    /// Maximum number of snapshots to list.  The server may list fewer
+    /// if this is more than it allows.
+    #[id(2)]
+    pub limit: Option<u32>,
+}
+
+/// A single snapshot returned by a list snapshots request. Snapshots are
+/// returned most recently created first.
+#[auto_wire]
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq)]
+#[cfg_attr(any(test, feature = "for-tests"), derive(Arbitrary))]
+pub struct ListSnapshotsResponse {
+    #[id(1)]
+    pub cs_id: BonsaiChangesetId,
+    #[id(2)]
+    pub bubble_id: Option<NonZeroU64>,
+    #[id(3)]
+    pub labels: Vec<String>,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq)]
 pub struct UploadSnapshotResponse {
     pub changeset_token: UploadToken,
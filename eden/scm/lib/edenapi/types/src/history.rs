@@ -120,6 +120,10 @@ impl From<HistoryEntry> for WireHistoryEntry {
 pub struct HistoryRequest {
     pub keys: Vec<Key>,
     pub length: Option<u32>,
+    /// A commit that contains the files, for servers that advertise the
+    /// "filenode-free" capability. When set, history is served without
+    /// filenodes, from the history of the files in this commit.
+    pub commit: Option<HgId>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
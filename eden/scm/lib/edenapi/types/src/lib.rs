@@ -91,6 +91,8 @@ pub use crate::commit::FetchSnapshotRequest;
 pub use crate::commit::FetchSnapshotResponse;
 pub use crate::commit::HgChangesetContent;
 pub use crate::commit::HgMutationEntryContent;
+pub use crate::commit::ListSnapshotsRequest;
+pub use crate::commit::ListSnapshotsResponse;
 pub use crate::commit::SnapshotRawData;
 pub use crate::commit::SnapshotRawFiles;
 pub use crate::commit::UploadBonsaiChangesetRequest;
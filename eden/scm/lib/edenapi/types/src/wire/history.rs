@@ -27,6 +27,8 @@ use crate::WireHistoryEntry;
 pub struct WireHistoryRequest {
     keys: Vec<WireKey>,
     length: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    commit: Option<WireHgId>,
 }
 
 impl ToWire for HistoryRequest {
@@ -36,6 +38,7 @@ impl ToWire for HistoryRequest {
         WireHistoryRequest {
             keys: self.keys.to_wire(),
             length: self.length.to_wire(),
+            commit: self.commit.to_wire(),
         }
     }
 }
@@ -48,6 +51,7 @@ impl ToApi for WireHistoryRequest {
         Ok(HistoryRequest {
             keys: self.keys.to_api()?,
             length: self.length.to_api()?,
+            commit: self.commit.to_api()?,
         })
     }
 }